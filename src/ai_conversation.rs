@@ -2,10 +2,21 @@ use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use uuid::Uuid;
 use tokio::time::{sleep, timeout};
 
+use crate::config::ContextSettings;
+use crate::error::ArrowheadError;
+use crate::tool_results::{page_params, page_schema_properties, ToolResultSettings, ToolResultStore, PAGE_TOOL_NAME};
+use crate::sessions::{SavedSession, SessionStore};
+use crate::usage::{MeteredLLMClient, UsagePurpose, UsageTracker};
+use crate::token_counter::{self, CharTokenCounter, TokenCounter};
+
+/// Start of the system note that stands in for trimmed messages
+pub const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub id: String,
@@ -38,6 +49,13 @@ pub struct ConversationContext {
     pub metadata: HashMap<String, serde_json::Value>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    /// How `current_token_count` is estimated; set for the model in use
+    #[serde(skip, default = "default_token_counter")]
+    token_counter: Arc<dyn TokenCounter>,
+}
+
+fn default_token_counter() -> Arc<dyn TokenCounter> {
+    Arc::new(CharTokenCounter::default())
 }
 
 impl ConversationContext {
@@ -51,6 +69,7 @@ impl ConversationContext {
             metadata: HashMap::new(),
             created_at: now,
             updated_at: now,
+            token_counter: default_token_counter(),
         }
     }
 
@@ -61,12 +80,54 @@ impl ConversationContext {
     }
 
     pub fn add_message(&mut self, message: Message) {
+        self.current_token_count += self.message_tokens(&message.content);
         self.message_history.push(message);
         self.updated_at = chrono::Utc::now();
-        self.update_token_count();
         self.prune_if_needed();
     }
 
+    /// Count tokens with `counter` from now on
+    pub fn set_token_counter(&mut self, counter: Arc<dyn TokenCounter>) {
+        self.token_counter = counter;
+        self.update_token_count();
+    }
+
+    /// Estimated tokens of the whole history
+    pub fn token_count(&self) -> usize {
+        self.current_token_count
+    }
+
+    /// Estimated tokens a message with `content` adds to the history
+    pub fn message_tokens(&self, content: &str) -> usize {
+        self.token_counter.count_message(content)
+    }
+
+    /// How much of the token budget the history uses, in percent
+    pub fn usage_percent(&self) -> usize {
+        (self.current_token_count * 100).checked_div(self.max_context_tokens).unwrap_or(100)
+    }
+
+    /// Remove the oldest non-system messages, other than the last `keep_recent` messages,
+    /// until the history fits in `target` tokens. Returns the removed messages, oldest first.
+    pub fn trim_oldest(&mut self, target: usize, keep_recent: usize) -> Vec<Message> {
+        let protected_from = self.message_history.len().saturating_sub(keep_recent);
+        let mut removed = Vec::new();
+        let mut index = 0;
+        while self.current_token_count > target && index < protected_from - removed.len() {
+            if matches!(self.message_history[index].role, MessageRole::System) {
+                index += 1;
+                continue;
+            }
+            let message = self.message_history.remove(index);
+            self.current_token_count = self.current_token_count.saturating_sub(self.message_tokens(&message.content));
+            removed.push(message);
+        }
+        if !removed.is_empty() {
+            self.updated_at = chrono::Utc::now();
+        }
+        removed
+    }
+
     pub fn get_recent_messages(&self, limit: usize) -> Vec<&Message> {
         self.message_history.iter().rev().take(limit).collect()
     }
@@ -96,7 +157,7 @@ impl ConversationContext {
     }
 
     pub fn get_current_token_count(&self) -> usize {
-        self.current_token_count
+        self.token_count()
     }
 
     pub fn is_context_full(&self) -> bool {
@@ -117,11 +178,21 @@ impl ConversationContext {
         self.metadata.remove(key)
     }
 
+    /// Replace the history with `messages` (a reloaded conversation), dropping the oldest
+    /// non-system messages that don't fit the token budget. Returns how many were dropped.
+    pub fn replace_history(&mut self, messages: Vec<Message>) -> usize {
+        let total = messages.len();
+        self.message_history = messages;
+        self.updated_at = chrono::Utc::now();
+        self.update_token_count();
+        self.prune_if_needed();
+        total - self.message_history.len()
+    }
+
     fn update_token_count(&mut self) {
-        // Rough estimation: 1 token ≈ 4 characters
         self.current_token_count = self.message_history
             .iter()
-            .map(|msg| msg.content.len() / 4)
+            .map(|msg| self.message_tokens(&msg.content))
             .sum();
     }
 
@@ -148,11 +219,11 @@ impl ConversationContext {
         // Add back recent messages until we're under the token limit
         let mut temp_token_count = self.message_history
             .iter()
-            .map(|msg| msg.content.len() / 4)
+            .map(|msg| self.message_tokens(&msg.content))
             .sum::<usize>();
 
         for message in other_messages.into_iter().rev() {
-            let message_tokens = message.content.len() / 4;
+            let message_tokens = self.message_tokens(&message.content);
             if temp_token_count + message_tokens <= self.max_context_tokens {
                 temp_token_count += message_tokens;
                 self.message_history.push(message);
@@ -225,6 +296,131 @@ pub trait LLMClient: Send + Sync {
     fn get_model_name(&self) -> String;
 }
 
+/// Create the LLM client for the configured provider, retrying transient failures and
+/// switching to the `[llm] fallback` providers that have API keys when it keeps failing.
+/// Requests are counted under `purpose` in the usage ledger.
+pub fn create_llm_client(config: &crate::config::Config, purpose: UsagePurpose) -> Result<Box<dyn LLMClient>> {
+    use crate::llm_retry::RetryingLLMClient;
+
+    config.ensure_online("the LLM")?;
+    config.validate()?;
+
+    let metered = |provider: &str, client: Box<dyn LLMClient>| -> Box<dyn LLMClient> {
+        if !config.usage.enabled {
+            return client;
+        }
+        Box::new(MeteredLLMClient::new(client, provider, purpose, UsageTracker::from_settings(&config.usage)))
+    };
+    let mut client = RetryingLLMClient::new(&config.llm.provider, metered(&config.llm.provider, provider_client(config)?), config.llm.retry.clone());
+    for provider in config.llm.fallback.iter().filter(|p| **p != config.llm.provider) {
+        let mut fallback = config.clone();
+        fallback.llm.provider = provider.clone();
+        if crate::config::Config::provider_needs_api_key(provider) && fallback.get_llm_api_key().is_none() {
+            log::warn!("skipping fallback provider {}: no API key", provider);
+            continue;
+        }
+        match provider_client(&fallback) {
+            Ok(fallback_client) => client = client.with_fallback(provider, metered(provider, fallback_client)),
+            Err(e) => log::warn!("skipping fallback provider {}: {}", provider, e),
+        }
+    }
+    Ok(Box::new(client))
+}
+
+/// Stands in for the LLM when none could be created (offline mode, or no API key), so
+/// a conversation can still run local commands. Every request fails with `reason`.
+pub struct UnavailableLLMClient {
+    pub reason: String,
+}
+
+#[async_trait]
+impl LLMClient for UnavailableLLMClient {
+    async fn send_message(&self, _messages: Vec<Message>) -> Result<Message> {
+        anyhow::bail!("{}", self.reason)
+    }
+
+    async fn stream_response(&self, _messages: Vec<Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+        anyhow::bail!("{}", self.reason)
+    }
+
+    async fn function_calling(&self, _messages: Vec<Message>, _functions: Vec<FunctionSchema>) -> Result<Message> {
+        anyhow::bail!("{}", self.reason)
+    }
+
+    fn get_model_name(&self) -> String {
+        "unavailable".to_string()
+    }
+}
+
+/// The bare client for `config.llm.provider`
+fn provider_client(config: &crate::config::Config) -> Result<Box<dyn LLMClient>> {
+    use crate::anthropic_client::{AnthropicClient, AnthropicConfig};
+    use crate::gemini_client::{GeminiClient, GeminiConfig};
+    use crate::network::{http_client, Destination};
+    use crate::ollama_client::{OllamaClient, OllamaConfig};
+    use crate::openai_client::{OpenAIClient, OpenAIConfig};
+
+    match config.llm.provider.as_str() {
+        "gemini" => {
+            let api_key = config.get_llm_api_key()
+                .ok_or_else(|| ArrowheadError::ConfigMissing("Gemini API key".to_string()))?;
+
+            let gemini_config = GeminiConfig {
+                api_key,
+                model: config.get_llm_model(),
+                temperature: Some(config.get_llm_temperature()),
+                max_tokens: Some(config.get_llm_max_tokens()),
+                ..Default::default()
+            };
+
+            let http = http_client(&config.network, Destination::Llm)?;
+            Ok(Box::new(GeminiClient::new(gemini_config)?.with_http_client(http)))
+        }
+        "openai" => {
+            let api_key = config.get_llm_api_key()
+                .ok_or_else(|| ArrowheadError::ConfigMissing("OpenAI API key".to_string()))?;
+
+            let openai_config = OpenAIConfig {
+                api_key,
+                model: config.get_llm_model(),
+                temperature: Some(config.get_llm_temperature()),
+                max_tokens: Some(config.get_llm_max_tokens()),
+                ..Default::default()
+            };
+
+            let http = http_client(&config.network, Destination::Llm)?;
+            Ok(Box::new(OpenAIClient::new(openai_config)?.with_http_client(http)))
+        }
+        "anthropic" => {
+            let api_key = config.get_llm_api_key()
+                .ok_or_else(|| ArrowheadError::ConfigMissing("Anthropic API key".to_string()))?;
+
+            let anthropic_config = AnthropicConfig {
+                api_key,
+                model: config.get_llm_model(),
+                temperature: Some(config.get_llm_temperature()),
+                max_tokens: config.get_llm_max_tokens(),
+                ..Default::default()
+            };
+
+            let http = http_client(&config.network, Destination::Llm)?;
+            Ok(Box::new(AnthropicClient::new(anthropic_config)?.with_http_client(http)))
+        }
+        "ollama" => {
+            let ollama_config = OllamaConfig {
+                base_url: config.llm.ollama.base_url.clone(),
+                model: config.get_llm_model(),
+                temperature: Some(config.get_llm_temperature()),
+                max_tokens: Some(config.get_llm_max_tokens()),
+            };
+
+            let http = http_client(&config.network, Destination::Llm)?;
+            Ok(Box::new(OllamaClient::new(ollama_config).with_http_client(http)))
+        }
+        provider => Err(anyhow::anyhow!("Unsupported LLM provider: {}", provider)),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionSchema {
     pub name: String,
@@ -250,11 +446,27 @@ pub enum AIConversationError {
     GenericError(#[from] anyhow::Error),
 }
 
+impl AIConversationError {
+    /// The classified provider or vault failure behind this error, if there is one
+    pub fn arrowhead_error(&self) -> Option<&ArrowheadError> {
+        match self {
+            Self::GenericError(e) => ArrowheadError::of(e),
+            _ => None,
+        }
+    }
+}
+
 pub struct AIConversationEngine {
     pub conversation_id: String,
     pub context: ConversationContext,
     pub llm_client: Box<dyn LLMClient>,
     pub function_registry: HashMap<String, FunctionSchema>,
+    /// Full tool results; the context only holds their reduced form
+    pub tool_results: ToolResultStore,
+    pub tool_result_settings: ToolResultSettings,
+    /// Where the history is saved after every turn, if anywhere
+    pub session_store: Option<SessionStore>,
+    pub context_settings: ContextSettings,
 }
 
 impl AIConversationEngine {
@@ -262,26 +474,39 @@ impl AIConversationEngine {
         let conversation_id = Uuid::new_v4().to_string();
         let context = ConversationContext::new(conversation_id.clone());
         
-        Self {
+        let mut engine = Self {
             conversation_id,
             context,
             llm_client,
             function_registry: HashMap::new(),
-        }
+            tool_results: ToolResultStore::default(),
+            tool_result_settings: ToolResultSettings::default(),
+            session_store: None,
+            context_settings: ContextSettings::default(),
+        };
+        engine.context.set_token_counter(token_counter::for_model(&engine.get_model_name()));
+        engine
     }
 
     pub fn with_conversation_id(conversation_id: String, llm_client: Box<dyn LLMClient>) -> Self {
         let context = ConversationContext::new(conversation_id.clone());
         
-        Self {
+        let mut engine = Self {
             conversation_id,
             context,
             llm_client,
             function_registry: HashMap::new(),
-        }
+            tool_results: ToolResultStore::default(),
+            tool_result_settings: ToolResultSettings::default(),
+            session_store: None,
+            context_settings: ContextSettings::default(),
+        };
+        engine.context.set_token_counter(token_counter::for_model(&engine.get_model_name()));
+        engine
     }
 
     pub async fn send_message(&mut self, content: String) -> Result<String, AIConversationError> {
+        self.make_room(self.context.message_tokens(&content)).await;
         let user_message = Message {
             id: Uuid::new_v4().to_string(),
             role: MessageRole::User,
@@ -297,11 +522,13 @@ impl AIConversationEngine {
             .await?;
 
         self.context.add_message(response.clone());
+        self.persist();
 
         Ok(response.content)
     }
 
     pub async fn stream_message(&mut self, content: String) -> Result<tokio::sync::mpsc::Receiver<String>, AIConversationError> {
+        self.make_room(self.context.message_tokens(&content)).await;
         let user_message = Message {
             id: Uuid::new_v4().to_string(),
             role: MessageRole::User,
@@ -311,6 +538,7 @@ impl AIConversationEngine {
         };
 
         self.context.add_message(user_message);
+        self.persist();
 
         let receiver = self.llm_client
             .stream_response(self.context.message_history.clone())
@@ -319,6 +547,189 @@ impl AIConversationEngine {
         Ok(receiver)
     }
 
+    pub fn with_tool_result_settings(mut self, settings: ToolResultSettings) -> Self {
+        self.tool_result_settings = settings;
+        self
+    }
+
+    /// Budget the history for the model in use, trimming it as `settings` describe
+    pub fn with_context_settings(mut self, settings: ContextSettings) -> Self {
+        self.context.max_context_tokens = settings.budget_for(&self.get_model_name());
+        self.context_settings = settings;
+        self.context.prune_if_needed();
+        self
+    }
+
+    /// Trim the oldest messages when the history plus `incoming` tokens would pass
+    /// `trim_at_percent` of the budget. Trimmed messages are summarized, together with any
+    /// earlier summary, into one system note; if that fails they are simply dropped.
+    /// Returns how many messages were trimmed.
+    pub async fn make_room(&mut self, incoming: usize) -> usize {
+        let settings = &self.context_settings;
+        let budget = self.context.max_context_tokens;
+        if (self.context.token_count() + incoming) * 100 < budget * settings.trim_at_percent {
+            return 0;
+        }
+        let target = (budget * settings.trim_to_percent / 100).saturating_sub(incoming);
+        let (keep_recent, summarize) = (settings.keep_recent, settings.summarize);
+        let previous = self.context.message_history.iter()
+            .position(|m| matches!(m.role, MessageRole::System) && m.content.starts_with(SUMMARY_PREFIX));
+        let previous = previous.map(|index| {
+            let message = self.context.message_history.remove(index);
+            self.context.current_token_count -= self.context.message_tokens(&message.content);
+            message
+        });
+        let trimmed = self.context.trim_oldest(target, keep_recent);
+        if trimmed.is_empty() {
+            if let Some(previous) = previous {
+                self.context.add_message(previous);
+                self.context.message_history.sort_by_key(|m| m.timestamp);
+            }
+            return 0;
+        }
+
+        let last_trimmed = trimmed.last().map_or_else(chrono::Utc::now, |m| m.timestamp);
+        let summary = if summarize {
+            match self.summarize(previous.as_ref(), &trimmed).await {
+                Ok(summary) => Some(summary),
+                Err(e) => {
+                    log::warn!("could not summarize {} trimmed message(s): {}", trimmed.len(), e);
+                    previous.map(|m| m.content)
+                }
+            }
+        } else {
+            previous.map(|m| m.content)
+        };
+        if let Some(summary) = summary {
+            let content = if summary.starts_with(SUMMARY_PREFIX) { summary } else { format!("{} {}", SUMMARY_PREFIX, summary) };
+            self.context.add_message(Message {
+                id: Uuid::new_v4().to_string(),
+                role: MessageRole::System,
+                content,
+                // Sorts where the trimmed messages were
+                timestamp: last_trimmed,
+                function_call: None,
+            });
+            self.context.message_history.sort_by_key(|m| m.timestamp);
+        }
+        trimmed.len()
+    }
+
+    /// Ask the model to compress `trimmed`, and the summary they follow, into a short note
+    async fn summarize(&self, previous: Option<&Message>, trimmed: &[Message]) -> Result<String> {
+        let mut transcript = String::new();
+        if let Some(previous) = previous {
+            transcript.push_str(previous.content.trim_start_matches(SUMMARY_PREFIX).trim());
+            transcript.push_str("\n\n");
+        }
+        for message in trimmed {
+            transcript.push_str(&format!("{:?}: {}\n", message.role, message.content));
+        }
+        let request = vec![
+            Message {
+                id: Uuid::new_v4().to_string(),
+                role: MessageRole::System,
+                content: "Summarize this conversation for your own later reference in under 150 words. Keep facts about the user, decisions, commitments and open questions; drop pleasantries.".to_string(),
+                timestamp: chrono::Utc::now(),
+                function_call: None,
+            },
+            Message {
+                id: Uuid::new_v4().to_string(),
+                role: MessageRole::User,
+                content: transcript,
+                timestamp: chrono::Utc::now(),
+                function_call: None,
+            },
+        ];
+        let summary = self.llm_client.send_message(request).await?.content;
+        let summary = summary.trim();
+        if summary.is_empty() {
+            anyhow::bail!("the model returned an empty summary");
+        }
+        Ok(summary.to_string())
+    }
+
+    /// Save the conversation to `store` after every turn
+    pub fn with_session_store(mut self, store: SessionStore) -> Self {
+        self.session_store = Some(store);
+        self
+    }
+
+    /// Continue a saved conversation under its own id, trimmed to the context's token
+    /// budget. Returns how many of its messages didn't fit.
+    pub fn resume(&mut self, session: SavedSession) -> usize {
+        self.conversation_id = session.id.clone();
+        self.context.conversation_id = session.id;
+        self.context.created_at = session.created_at;
+        self.context.replace_history(session.messages)
+    }
+
+    /// The conversation as it stands, to be saved under `id`
+    pub fn snapshot(&self, id: &str) -> SavedSession {
+        SavedSession {
+            id: id.to_string(),
+            created_at: self.context.created_at,
+            updated_at: chrono::Utc::now(),
+            model: self.get_model_name(),
+            messages: self.context.message_history.clone(),
+        }
+    }
+
+    /// Save the history to the session store; a failed save never interrupts the conversation
+    fn persist(&self) {
+        let Some(store) = &self.session_store else { return };
+        let session = self.snapshot(&self.conversation_id);
+        if let Err(e) = store.save(&session) {
+            log::warn!("could not save session {}: {}", session.id, e);
+        }
+    }
+
+    /// Forget everything said so far except the system messages. Returns how many
+    /// messages were dropped.
+    pub fn reset_context(&mut self) -> usize {
+        let system: Vec<Message> = self.context.message_history.iter()
+            .filter(|m| matches!(m.role, MessageRole::System))
+            .cloned()
+            .collect();
+        let dropped = self.context.message_history.len() - system.len();
+        self.context.replace_history(system);
+        self.persist();
+        dropped
+    }
+
+    /// Talk to another model from the next message on
+    pub fn set_llm_client(&mut self, llm_client: Box<dyn LLMClient>) {
+        self.llm_client = llm_client;
+        self.context.set_token_counter(token_counter::for_model(&self.get_model_name()));
+    }
+
+    /// Add a tool result to the context, reduced if it's large; the full result stays
+    /// available through `tool_results`. Returns the text added to the context.
+    pub fn add_tool_result(&mut self, tool: &str, arguments: HashMap<String, serde_json::Value>, result: serde_json::Value) -> String {
+        let turn = self.context.get_messages_by_role(&MessageRole::User).len();
+        let (id, reduced) = self.tool_results.add(tool, turn, result, &self.tool_result_settings);
+        let content = format!("{} result {}: {}", tool, id, reduced);
+        self.context.add_message(Message {
+            id: Uuid::new_v4().to_string(),
+            role: MessageRole::Function,
+            content: content.clone(),
+            timestamp: chrono::Utc::now(),
+            function_call: Some(FunctionCall { name: tool.to_string(), arguments }),
+        });
+        content
+    }
+
+    /// Add items `offset..offset + limit` of a stored result's longest list to the context
+    pub fn page_tool_result(&mut self, result_id: &str, offset: usize, limit: Option<usize>) -> Result<String, AIConversationError> {
+        let page = self.tool_results.page(result_id, offset, limit)
+            .ok_or_else(|| AIConversationError::FunctionCallError(format!("No paginated tool result {}", result_id)))?;
+        let mut arguments = HashMap::new();
+        arguments.insert("result_id".to_string(), serde_json::json!(result_id));
+        arguments.insert("offset".to_string(), serde_json::json!(offset));
+        arguments.insert("limit".to_string(), serde_json::json!(limit));
+        Ok(self.add_tool_result(PAGE_TOOL_NAME, arguments, page))
+    }
+
     pub fn register_function(&mut self, function: FunctionSchema) {
         self.function_registry.insert(function.name.clone(), function);
     }
@@ -363,7 +774,6 @@ impl AIConversationEngine {
     // Batch processing with concurrency control
     pub async fn process_messages_concurrent(&mut self, messages: Vec<String>, max_concurrent: usize) -> Vec<Result<String, AIConversationError>> {
         use tokio::sync::Semaphore;
-        use std::sync::Arc;
         
         let semaphore = Arc::new(Semaphore::new(max_concurrent));
         let mut results = Vec::new();
@@ -424,12 +834,18 @@ impl AIConversationEngine {
     pub async fn recover_from_file(file_path: &str, llm_client: Box<dyn LLMClient>) -> Result<Self, AIConversationError> {
         let context = ConversationContext::load_from_file(file_path)?;
         
-        Ok(Self {
+        let mut engine = Self {
             conversation_id: context.conversation_id.clone(),
             context,
             llm_client,
             function_registry: HashMap::new(),
-        })
+            tool_results: ToolResultStore::default(),
+            tool_result_settings: ToolResultSettings::default(),
+            session_store: None,
+            context_settings: ContextSettings::default(),
+        };
+        engine.context.set_token_counter(token_counter::for_model(&engine.get_model_name()));
+        Ok(engine)
     }
 }
 
@@ -1105,20 +1521,22 @@ impl Tool for SearchTool {
         let query = parameters.get("query")
             .and_then(|v| v.as_str())
             .unwrap_or("");
+        let (offset, limit) = page_params(&parameters);
         
         // In a real implementation, this would perform actual search
+        let matches = [serde_json::json!({
+            "id": "1",
+            "title": "Example Note",
+            "content": "This is an example note matching your query",
+            "relevance": 0.85
+        })];
+        let page: Vec<serde_json::Value> = matches.iter().skip(offset).take(limit.unwrap_or(usize::MAX)).cloned().collect();
         let results = serde_json::json!({
             "success": true,
             "query": query,
-            "results": [
-                {
-                    "id": "1",
-                    "title": "Example Note",
-                    "content": "This is an example note matching your query",
-                    "relevance": 0.85
-                }
-            ],
-            "total_results": 1
+            "results": page,
+            "offset": offset,
+            "total_results": matches.len()
         });
         
         Ok(results)
@@ -1128,21 +1546,77 @@ impl Tool for SearchTool {
         FunctionSchema {
             name: "search".to_string(),
             description: "Search for notes and content".to_string(),
+            parameters: {
+                let mut parameters = serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "The search query"
+                        }
+                    },
+                    "required": ["query"]
+                });
+                if let (Some(properties), Some(paging)) = (parameters["properties"].as_object_mut(), page_schema_properties().as_object()) {
+                    properties.extend(paging.clone());
+                }
+                parameters
+            },
+        }
+    }
+    
+    fn get_name(&self) -> String {
+        "search".to_string()
+    }
+}
+
+/// Which saved note templates some content fits, scored without an LLM call
+pub struct SuggestTemplateTool;
+
+impl Tool for SuggestTemplateTool {
+    fn execute(&self, parameters: HashMap<String, serde_json::Value>) -> Result<serde_json::Value, AIConversationError> {
+        let content = parameters.get("content")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| AIConversationError::FunctionCallError("suggest_template needs the note content".to_string()))?;
+
+        let config = crate::config::Config::load().unwrap_or_default();
+        let mut templates = crate::obsidian_adapter::ObsidianAdapter::new(Some(config.obsidian.base_url.clone()), config.obsidian.api_key.clone());
+        templates.set_cache_settings(&config.cache);
+        let ranked = templates.load_template_database()
+            .and_then(|_| templates.suggest_template_for_content(content))
+            .map_err(|e| AIConversationError::FunctionCallError(e.to_string()))?;
+
+        let candidates: Vec<serde_json::Value> = ranked.iter().take(3)
+            .map(|(template, confidence)| serde_json::json!({
+                "id": template.id,
+                "name": template.name,
+                "category": template.category,
+                "confidence": confidence,
+            }))
+            .collect();
+        Ok(serde_json::json!({ "success": true, "candidates": candidates }))
+    }
+
+    fn get_schema(&self) -> FunctionSchema {
+        FunctionSchema {
+            name: "suggest_template".to_string(),
+            description: "Rank the saved note templates by how well some note content fits them; \
+                `arrowhead note new <template>` writes a note from one".to_string(),
             parameters: serde_json::json!({
                 "type": "object",
                 "properties": {
-                    "query": {
+                    "content": {
                         "type": "string",
-                        "description": "The search query"
+                        "description": "Markdown content of the note to be created"
                     }
                 },
-                "required": ["query"]
+                "required": ["content"]
             }),
         }
     }
-    
+
     fn get_name(&self) -> String {
-        "search".to_string()
+        "suggest_template".to_string()
     }
 }
 
@@ -1159,11 +1633,30 @@ impl AIConversationEngine {
         let mut tool_registry = ToolRegistry::new();
         tool_registry.register_tool("create_note".to_string(), Box::new(CreateNoteTools));
         tool_registry.register_tool("search".to_string(), Box::new(SearchTool));
+        tool_registry.register_tool("suggest_template".to_string(), Box::new(SuggestTemplateTool));
         
         // Store tool schemas in function registry
         for schema in tool_registry.get_tool_schemas() {
             engine.register_function(schema);
         }
+        let mut page_parameters = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "result_id": {
+                    "type": "string",
+                    "description": "Id of a reduced tool result, e.g. \"r1\""
+                }
+            },
+            "required": ["result_id"]
+        });
+        if let (Some(properties), Some(paging)) = (page_parameters["properties"].as_object_mut(), page_schema_properties().as_object()) {
+            properties.extend(paging.clone());
+        }
+        engine.register_function(FunctionSchema {
+            name: PAGE_TOOL_NAME.to_string(),
+            description: "Page through the full version of a tool result that was shortened".to_string(),
+            parameters: page_parameters,
+        });
         
         engine.context.add_metadata("tool_registry".to_string(), serde_json::json!("initialized"));
         
@@ -1261,12 +1754,9 @@ impl AIConversationEngine {
         parameters.insert("query".to_string(), serde_json::Value::String(query));
         
         // Execute the tool
-        let result = search_tool.execute(parameters)?;
-        
-        // Convert result to response
-        let response = format!("Search results: {}", result);
+        let result = search_tool.execute(parameters.clone())?;
         
-        // Add to conversation history
+        // Add to conversation history; large results go in reduced
         let user_message = Message {
             id: Uuid::new_v4().to_string(),
             role: MessageRole::User,
@@ -1274,22 +1764,10 @@ impl AIConversationEngine {
             timestamp: chrono::Utc::now(),
             function_call: None,
         };
-        
-        let assistant_message = Message {
-            id: Uuid::new_v4().to_string(),
-            role: MessageRole::Assistant,
-            content: response.clone(),
-            timestamp: chrono::Utc::now(),
-            function_call: Some(FunctionCall {
-                name: "search".to_string(),
-                arguments: HashMap::new(),
-            }),
-        };
-        
         self.context.add_message(user_message);
-        self.context.add_message(assistant_message);
+        let reduced = self.add_tool_result("search", parameters, result);
         
-        Ok(response)
+        Ok(format!("Search results: {}", reduced))
     }
 }
 
@@ -1378,6 +1856,29 @@ mod tests {
         assert!(engine.context.current_token_count > 0);
     }
 
+    #[tokio::test]
+    async fn test_turns_are_saved_and_resumed_within_budget() {
+        let dir = std::env::temp_dir().join(format!("arrowhead-engine-sessions-{}", Uuid::new_v4()));
+        let store = SessionStore::new(&dir);
+        let mut engine = AIConversationEngine::new(Box::new(MockLLMClient::new(vec!["a".repeat(400), "b".repeat(400)])))
+            .with_session_store(store.clone());
+        engine.send_message("first question".to_string()).await.unwrap();
+        engine.send_message("second question".to_string()).await.unwrap();
+
+        let saved = store.load(&engine.conversation_id).unwrap();
+        assert_eq!(saved.messages.len(), 4);
+        assert_eq!(saved.model, "mock-model");
+
+        // A smaller budget keeps only the most recent turn
+        let mut resumed = AIConversationEngine::new(Box::new(MockLLMClient::new(vec![])));
+        resumed.context.max_context_tokens = 110;
+        assert_eq!(resumed.resume(saved), 2);
+        assert_eq!(resumed.conversation_id, engine.conversation_id);
+        assert_eq!(resumed.context.message_history[0].content, "second question");
+        assert!(resumed.context.get_current_token_count() <= 110);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
     #[tokio::test]
     async fn test_function_registration() {
         let mock_client = MockLLMClient::new(vec![]);
@@ -1393,6 +1894,79 @@ mod tests {
         assert!(engine.function_registry.contains_key("test_function"));
     }
 
+    #[tokio::test]
+    async fn test_large_tool_results_enter_context_reduced() {
+        let mut engine = AIConversationEngine::new(Box::new(MockLLMClient::new(vec![])));
+        let todos: Vec<serde_json::Value> = (0..500)
+            .map(|i| serde_json::json!({ "id": i, "title": format!("Todo number {} from the backlog", i) }))
+            .collect();
+
+        let content = engine.add_tool_result("list_todos", HashMap::new(), serde_json::json!({ "todos": todos }));
+        assert!(content.starts_with("list_todos result r1: "));
+        assert!(content.contains("and 490 more"));
+        assert!(engine.context.current_token_count <= engine.tool_result_settings.max_tokens + 10);
+        assert_eq!(engine.tool_results.last().unwrap().full["todos"].as_array().unwrap().len(), 500);
+
+        let page = engine.page_tool_result("r1", 490, Some(50)).unwrap();
+        assert!(page.contains("\"returned\":10") && page.contains("\"total\":500"));
+        assert!(engine.page_tool_result("r9", 0, None).is_err());
+    }
+
+    fn small_budget(summarize: bool) -> ContextSettings {
+        ContextSettings {
+            budgets: HashMap::from([("mock-model".to_string(), 100)]),
+            keep_recent: 2,
+            summarize,
+            ..ContextSettings::default()
+        }
+    }
+
+    fn system_message(content: &str) -> Message {
+        Message {
+            id: Uuid::new_v4().to_string(),
+            role: MessageRole::System,
+            content: content.to_string(),
+            timestamp: chrono::Utc::now(),
+            function_call: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oldest_turns_are_summarized_near_the_budget() {
+        let responses = vec!["b".repeat(80), "d".repeat(80), "User prefers short answers.".to_string(), "f".repeat(8)];
+        let mut engine = AIConversationEngine::new(Box::new(MockLLMClient::new(responses)))
+            .with_context_settings(small_budget(true));
+        assert_eq!(engine.context.max_context_tokens, 100);
+        engine.context.add_message(system_message(&"s".repeat(40)));
+
+        engine.send_message("a".repeat(80)).await.unwrap();
+        engine.send_message("c".repeat(80)).await.unwrap();
+        assert_eq!((engine.context.token_count(), engine.context.usage_percent()), (90, 90));
+
+        // The next question would pass 90%, so the first turn becomes a summary note
+        engine.send_message("e".repeat(40)).await.unwrap();
+        let history: Vec<String> = engine.context.message_history.iter().map(|m| m.content.chars().take(12).collect()).collect();
+        assert_eq!(history, vec!["s".repeat(12), SUMMARY_PREFIX[..12].to_string(), "c".repeat(12), "d".repeat(12), "e".repeat(12), "f".repeat(8)]);
+        assert!(engine.context.message_history[1].content.ends_with("User prefers short answers."));
+        let recounted: usize = engine.context.message_history.iter().map(|m| engine.context.message_tokens(&m.content)).sum();
+        assert_eq!(engine.context.token_count(), recounted);
+        assert!(engine.context.usage_percent() < 90);
+    }
+
+    #[tokio::test]
+    async fn test_trimmed_turns_can_be_dropped_without_a_summary() {
+        let mut engine = AIConversationEngine::new(Box::new(MockLLMClient::new(vec!["b".repeat(160), "d".repeat(8)])))
+            .with_context_settings(ContextSettings { keep_recent: 1, ..small_budget(false) });
+        engine.send_message("a".repeat(160)).await.unwrap();
+        assert_eq!(engine.context.token_count(), 80);
+
+        assert_eq!(engine.make_room(20).await, 1);
+        assert_eq!(engine.context.message_history.len(), 1);
+        assert_eq!(engine.context.token_count(), 40);
+        // The most recent messages are kept even when they alone exceed the target
+        assert_eq!(engine.make_room(60).await, 0);
+    }
+
     #[tokio::test]
     async fn test_conversation_context_token_management() {
         let mut context = ConversationContext::with_max_tokens("test".to_string(), 100);
@@ -1693,4 +2267,4 @@ mod tests {
         let health = engine.health_check().await;
         assert!(health.is_ok());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,290 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::embedding_store::EmbeddingStorageConfig;
+
+/// Lloyd iterations when clustering a model's vectors
+const KMEANS_ITERATIONS: usize = 8;
+
+/// Approximate nearest neighbor settings, part of the `[embeddings]` section
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnnSettings {
+    /// A model's documents are searched through the index from this many on
+    pub threshold: usize,
+    /// Lists scanned per query; more lists find more of the exact results but scan more vectors
+    pub probes: usize,
+    /// Lists per model; defaults to the square root of its document count
+    pub lists: Option<usize>,
+    /// Fraction of a model's documents added or removed since clustering that triggers a rebuild
+    pub rebuild_fraction: f32,
+}
+
+impl From<&EmbeddingStorageConfig> for AnnSettings {
+    fn from(config: &EmbeddingStorageConfig) -> Self {
+        Self {
+            threshold: config.ann_threshold,
+            probes: config.ann_probes,
+            lists: config.ann_lists,
+            rebuild_fraction: config.ann_rebuild_fraction,
+        }
+    }
+}
+
+/// The clustered documents of one embedding model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ModelIndex {
+    /// Unit-length cluster centers
+    centroids: Vec<Vec<f32>>,
+    /// Document paths assigned to each centroid
+    lists: Vec<Vec<String>>,
+    /// Documents when last clustered
+    built_size: usize,
+    /// Documents added or removed since
+    changes: usize,
+}
+
+impl ModelIndex {
+    fn build(documents: &[(&str, &[f32])], list_count: usize) -> Self {
+        let vectors: Vec<Vec<f32>> = documents.par_iter().map(|(_, v)| normalized(v)).collect();
+        let k = list_count.clamp(1, documents.len().max(1));
+        // Seed with documents spread evenly over path order, so a rebuild is reproducible
+        let mut centroids: Vec<Vec<f32>> = (0..k).map(|i| vectors[i * vectors.len() / k].clone()).collect();
+        let mut assignments = vec![0; vectors.len()];
+        for _ in 0..KMEANS_ITERATIONS {
+            assignments = vectors.par_iter().map(|v| nearest(&centroids, v)).collect();
+            let dimension = centroids[0].len();
+            let mut sums = vec![vec![0.0f32; dimension]; k];
+            for (vector, cluster) in vectors.iter().zip(&assignments) {
+                for (sum, value) in sums[*cluster].iter_mut().zip(vector) {
+                    *sum += value;
+                }
+            }
+            for (centroid, sum) in centroids.iter_mut().zip(sums) {
+                // An empty cluster keeps its previous center
+                if sum.iter().any(|v| *v != 0.0) {
+                    *centroid = normalized(&sum);
+                }
+            }
+        }
+        let mut lists = vec![Vec::new(); k];
+        for ((path, _), cluster) in documents.iter().zip(&assignments) {
+            lists[*cluster].push(path.to_string());
+        }
+        Self { centroids, lists, built_size: documents.len(), changes: 0 }
+    }
+
+    fn len(&self) -> usize {
+        self.lists.iter().map(Vec::len).sum()
+    }
+
+    fn remove(&mut self, path: &str) -> bool {
+        for list in &mut self.lists {
+            if let Some(position) = list.iter().position(|p| p == path) {
+                list.swap_remove(position);
+                self.changes += 1;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Inverted-file index over the vector database: each model's documents are clustered and a
+/// query only scans the documents of the clusters nearest to it. Kept in step with the
+/// database as embeddings are added and removed, and re-clustered once enough have changed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnnIndex {
+    models: HashMap<String, ModelIndex>,
+}
+
+impl AnnIndex {
+    /// Whether searches for `model` should go through the index
+    pub fn covers(&self, model: &str, settings: &AnnSettings) -> bool {
+        self.models.get(model).is_some_and(|index| index.len() >= settings.threshold)
+    }
+
+    /// Paths of the documents in the `probes` lists whose centers are nearest to `query`
+    pub fn candidates(&self, model: &str, query: &[f32], probes: usize) -> Vec<&str> {
+        let Some(index) = self.models.get(model) else {
+            return Vec::new();
+        };
+        let query = normalized(query);
+        let mut scored: Vec<(usize, f32)> = index.centroids.iter().enumerate()
+            .map(|(i, centroid)| (i, dot(centroid, &query)))
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored.into_iter()
+            .take(probes.max(1))
+            .flat_map(|(i, _)| index.lists[i].iter().map(String::as_str))
+            .collect()
+    }
+
+    /// File a new or re-embedded document under its nearest center
+    pub fn insert(&mut self, model: &str, path: &str, vector: &[f32]) {
+        self.remove(path);
+        if let Some(index) = self.models.get_mut(model) {
+            if index.centroids.first().is_some_and(|c| c.len() == vector.len()) {
+                let cluster = nearest(&index.centroids, &normalized(vector));
+                index.lists[cluster].push(path.to_string());
+                index.changes += 1;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        for index in self.models.values_mut() {
+            if index.remove(path) {
+                return;
+            }
+        }
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) {
+        for path in self.models.values_mut().flat_map(|index| index.lists.iter_mut().flatten()) {
+            if path == from {
+                *path = to.to_string();
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.models.clear();
+    }
+
+    /// Cluster every model with at least `threshold` documents that has no index yet or
+    /// has drifted past `rebuild_fraction`, and drop the indexes of smaller models.
+    /// `documents` holds each document's model, path and vector. Returns the models rebuilt.
+    pub fn refresh(&mut self, documents: &[(&str, &str, &[f32])], settings: &AnnSettings) -> Vec<String> {
+        let mut by_model: HashMap<&str, Vec<(&str, &[f32])>> = HashMap::new();
+        for (model, path, vector) in documents {
+            by_model.entry(model).or_default().push((path, vector));
+        }
+        self.models.retain(|model, _| by_model.get(model.as_str()).is_some_and(|docs| docs.len() >= settings.threshold));
+
+        let mut rebuilt = Vec::new();
+        for (model, docs) in by_model {
+            if docs.len() < settings.threshold.max(1) {
+                continue;
+            }
+            let stale = match self.models.get(model) {
+                Some(index) => {
+                    index.changes as f32 > index.built_size as f32 * settings.rebuild_fraction
+                        || index.len() != docs.len()
+                }
+                None => true,
+            };
+            if stale {
+                let lists = settings.lists.unwrap_or_else(|| (docs.len() as f64).sqrt().round() as usize);
+                self.models.insert(model.to_string(), ModelIndex::build(&docs, lists));
+                rebuilt.push(model.to_string());
+            }
+        }
+        rebuilt
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = dot(vector, vector).sqrt();
+    if norm == 0.0 {
+        vector.to_vec()
+    } else {
+        vector.iter().map(|v| v / norm).collect()
+    }
+}
+
+fn nearest(centroids: &[Vec<f32>], vector: &[f32]) -> usize {
+    centroids.iter().enumerate()
+        .map(|(i, centroid)| (i, dot(centroid, vector)))
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRng;
+    use std::collections::HashSet;
+
+    const DIMENSION: usize = 64;
+    /// Share of the exact top 10 the index must also return
+    const RECALL_FLOOR: f32 = 0.9;
+
+    fn settings() -> AnnSettings {
+        AnnSettings { threshold: 100, probes: 8, lists: None, rebuild_fraction: 0.2 }
+    }
+
+    fn top(documents: &[(String, Vec<f32>)], candidates: &HashSet<&str>, query: &[f32], n: usize) -> Vec<String> {
+        let query = normalized(query);
+        let mut scored: Vec<(&String, f32)> = documents.iter()
+            .filter(|(path, _)| candidates.is_empty() || candidates.contains(path.as_str()))
+            .map(|(path, v)| (path, dot(&normalized(v), &query)))
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        scored.into_iter().take(n).map(|(p, _)| p.clone()).collect()
+    }
+
+    #[test]
+    fn test_ann_results_overlap_exact_scan() {
+        // 3000 documents drawn around 40 topics, queries near a topic
+        let mut rng = TestRng::default();
+        let centers: Vec<Vec<f32>> = (0..40).map(|_| rng.near(&[0.0; DIMENSION], 1.0)).collect();
+        let documents: Vec<(String, Vec<f32>)> = (0..3000)
+            .map(|i| (format!("Notes/{:04}.md", i), rng.near(&centers[i % centers.len()], 0.6)))
+            .collect();
+        let entries: Vec<(&str, &str, &[f32])> = documents.iter().map(|(p, v)| ("m", p.as_str(), v.as_slice())).collect();
+        let mut index = AnnIndex::default();
+        assert_eq!(index.refresh(&entries, &settings()), vec!["m".to_string()]);
+        assert!(index.covers("m", &settings()));
+
+        let queries: Vec<Vec<f32>> = (0..50).map(|i| rng.near(&centers[(i * 7) % centers.len()], 0.6)).collect();
+        let mut overlap = 0;
+        let mut scanned = 0;
+        for query in &queries {
+            let candidates: HashSet<&str> = index.candidates("m", query, settings().probes).into_iter().collect();
+            scanned += candidates.len();
+            let exact = top(&documents, &HashSet::new(), query, 10);
+            let approximate = top(&documents, &candidates, query, 10);
+            overlap += exact.iter().filter(|p| approximate.contains(p)).count();
+        }
+        let recall = overlap as f32 / (queries.len() * 10) as f32;
+        assert!(recall >= RECALL_FLOOR, "recall@10 {:.3}", recall);
+        // And it is cheaper than the exact scan
+        assert!(scanned < queries.len() * documents.len() / 2, "scanned {}", scanned);
+    }
+
+    #[test]
+    fn test_changes_are_tracked_until_rebuild() {
+        let mut rng = TestRng::new(7);
+        let documents: Vec<(String, Vec<f32>)> = (0..200).map(|i| (format!("{}.md", i), rng.near(&[0.0; DIMENSION], 1.0))).collect();
+        let entries: Vec<(&str, &str, &[f32])> = documents.iter().map(|(p, v)| ("m", p.as_str(), v.as_slice())).collect();
+        let mut index = AnnIndex::default();
+        index.refresh(&entries, &settings());
+
+        // Every document stays findable through some list
+        index.insert("m", "new.md", &documents[0].1);
+        index.rename("new.md", "renamed.md");
+        index.remove("3.md");
+        let everything: HashSet<&str> = index.candidates("m", &documents[0].1, usize::MAX).into_iter().collect();
+        assert!(everything.contains("renamed.md") && !everything.contains("3.md"));
+        assert_eq!(everything.len(), 200);
+
+        // A few changes don't re-cluster; the index matches the database again after one
+        let mut current: Vec<(&str, &str, &[f32])> = entries.iter().filter(|(_, p, _)| *p != "3.md").copied().collect();
+        current.push(("m", "renamed.md", &documents[0].1));
+        assert!(index.refresh(&current, &settings()).is_empty());
+        for (_, path, _) in &entries[..50] {
+            index.remove(path);
+        }
+        assert_eq!(index.refresh(&current, &settings()), vec!["m".to_string()]);
+
+        // Below the threshold searches scan exactly
+        assert!(index.refresh(&current[..50], &settings()).is_empty());
+        assert!(!index.covers("m", &settings()));
+    }
+}
@@ -0,0 +1,467 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::env;
+use uuid::Uuid;
+
+use crate::ai_conversation::{FunctionCall, FunctionSchema, LLMClient, Message, MessageRole};
+use crate::error::ArrowheadError;
+
+/// Version of the Messages API the requests are written against
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Anthropic API client configuration
+#[derive(Debug, Clone)]
+pub struct AnthropicConfig {
+    pub api_key: String,
+    pub model: String,
+    pub base_url: String,
+    /// Required by the Messages API on every request
+    pub max_tokens: u32,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+}
+
+impl Default for AnthropicConfig {
+    fn default() -> Self {
+        Self {
+            api_key: env::var("ANTHROPIC_API_KEY").unwrap_or_default(),
+            model: "claude-3-5-sonnet-latest".to_string(),
+            base_url: "https://api.anthropic.com/v1".to_string(),
+            max_tokens: 4096,
+            temperature: Some(0.7),
+            top_p: None,
+        }
+    }
+}
+
+/// Anthropic Messages API client
+#[derive(Debug, Clone)]
+pub struct AnthropicClient {
+    config: AnthropicConfig,
+    client: Client,
+}
+
+impl AnthropicClient {
+    pub fn new(config: AnthropicConfig) -> Result<Self> {
+        if config.api_key.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Anthropic API key is required. Set ANTHROPIC_API_KEY environment variable."
+            ));
+        }
+
+        Ok(Self { config, client: Client::new() })
+    }
+
+    /// Send requests through `client`, e.g. one built for the `[network.llm]` proxy
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// The request body for `messages`. The Messages API has no system role, so system
+    /// messages become the `system` field; function results are passed back as user turns
+    /// and consecutive turns of one role are merged, as the API expects them to alternate.
+    fn build_request(&self, messages: Vec<Message>, tools: Vec<AnthropicTool>, stream: bool) -> AnthropicRequest {
+        let mut system = Vec::new();
+        let mut turns: Vec<AnthropicMessage> = Vec::new();
+        for message in messages {
+            let (role, text) = match message.role {
+                MessageRole::System => {
+                    system.push(message.content);
+                    continue;
+                }
+                MessageRole::User => ("user", message.content),
+                MessageRole::Function => ("user", format!("Function result: {}", message.content)),
+                MessageRole::Assistant => {
+                    let text = match message.function_call {
+                        Some(call) => format!(
+                            "{}\nCalled {} with {}",
+                            message.content,
+                            call.name,
+                            serde_json::to_string(&call.arguments).unwrap_or_default()
+                        ).trim().to_string(),
+                        None => message.content,
+                    };
+                    ("assistant", text)
+                }
+            };
+            match turns.last_mut() {
+                Some(last) if last.role == role => {
+                    last.content.push_str("\n\n");
+                    last.content.push_str(&text);
+                }
+                _ => turns.push(AnthropicMessage { role: role.to_string(), content: text }),
+            }
+        }
+
+        AnthropicRequest {
+            model: self.config.model.clone(),
+            max_tokens: self.config.max_tokens,
+            system: if system.is_empty() { None } else { Some(system.join("\n\n")) },
+            messages: turns,
+            temperature: self.config.temperature,
+            top_p: self.config.top_p,
+            tool_choice: if tools.is_empty() { None } else { Some(serde_json::json!({ "type": "auto" })) },
+            tools,
+            stream: if stream { Some(true) } else { None },
+        }
+    }
+
+    async fn post(&self, request: &AnthropicRequest) -> Result<reqwest::Response> {
+        let response = self.client
+            .post(format!("{}/messages", self.config.base_url))
+            .header("x-api-key", &self.config.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header("Content-Type", "application/json")
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| ArrowheadError::from_reqwest("anthropic", &e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = ArrowheadError::retry_after(response.headers());
+            let error_text = response.text().await?;
+            return Err(response_error(status.as_u16(), retry_after, error_text));
+        }
+        Ok(response)
+    }
+
+    async fn complete(&self, request: AnthropicRequest) -> Result<Message> {
+        let response: AnthropicResponse = self.post(&request).await?.json().await.map_err(|e| ArrowheadError::from_reqwest("anthropic", &e))?;
+
+        let mut text = Vec::new();
+        let mut function_call = None;
+        for block in response.content {
+            match block {
+                AnthropicContent::Text { text: part } => text.push(part),
+                AnthropicContent::ToolUse { name, input } if function_call.is_none() => {
+                    function_call = Some(FunctionCall {
+                        name,
+                        arguments: serde_json::from_value(input).unwrap_or_default(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        if text.is_empty() && function_call.is_none() {
+            return Err(anyhow::anyhow!("No response from Anthropic"));
+        }
+
+        Ok(Message {
+            id: Uuid::new_v4().to_string(),
+            role: MessageRole::Assistant,
+            content: text.join(""),
+            timestamp: Utc::now(),
+            function_call,
+        })
+    }
+}
+
+/// 429 is a rate limit or exhausted credit; 529 means Anthropic is overloaded and is retried
+/// like a 503. The typed error body is flattened to `kind: message`.
+fn response_error(status: u16, retry_after: Option<std::time::Duration>, body: String) -> anyhow::Error {
+    let message = serde_json::from_str::<AnthropicErrorResponse>(&body)
+        .map(|e| format!("{}: {}", e.error.kind, e.error.message))
+        .unwrap_or(body);
+    ArrowheadError::from_status("anthropic", status, retry_after, message).into()
+}
+
+#[async_trait]
+impl LLMClient for AnthropicClient {
+    async fn send_message(&self, messages: Vec<Message>) -> Result<Message> {
+        self.complete(self.build_request(messages, Vec::new(), false)).await
+    }
+
+    async fn stream_response(&self, messages: Vec<Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+        let (tx, rx) = tokio::sync::mpsc::channel(100);
+        let response = self.post(&self.build_request(messages, Vec::new(), true)).await?;
+
+        tokio::spawn(async move {
+            use futures::StreamExt;
+
+            let mut stream = response.bytes_stream();
+            // Events can be split across chunks, so only complete lines are parsed
+            let mut pending = String::new();
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        pending.push_str(&String::from_utf8_lossy(&bytes));
+                        while let Some(end) = pending.find('\n') {
+                            let line: String = pending.drain(..=end).collect();
+                            let Some(data) = line.trim_end().strip_prefix("data: ") else { continue };
+                            match serde_json::from_str::<AnthropicStreamEvent>(data) {
+                                Ok(AnthropicStreamEvent::ContentBlockDelta { delta: AnthropicDelta::TextDelta { text } }) => {
+                                    let _ = tx.send(text).await;
+                                }
+                                Ok(AnthropicStreamEvent::MessageStop) => return,
+                                Ok(AnthropicStreamEvent::Error { error }) => {
+                                    let _ = tx.send(format!("Stream error: {}", error.message)).await;
+                                    return;
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.send(format!("Stream error: {}", e)).await;
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+
+    async fn function_calling(&self, messages: Vec<Message>, functions: Vec<FunctionSchema>) -> Result<Message> {
+        let tools = functions.into_iter().map(|f| AnthropicTool {
+            name: f.name,
+            description: f.description,
+            input_schema: f.parameters,
+        }).collect();
+        self.complete(self.build_request(messages, tools, false)).await
+    }
+
+    fn get_model_name(&self) -> String {
+        self.config.model.clone()
+    }
+}
+
+// Anthropic API request/response structures
+#[derive(Debug, Serialize)]
+struct AnthropicRequest {
+    model: String,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<AnthropicTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AnthropicMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicTool {
+    name: String,
+    description: String,
+    input_schema: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicResponse {
+    content: Vec<AnthropicContent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicContent {
+    Text { text: String },
+    ToolUse { name: String, input: serde_json::Value },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicStreamEvent {
+    ContentBlockDelta { delta: AnthropicDelta },
+    MessageStop,
+    Error { error: AnthropicError },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicDelta {
+    TextDelta { text: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicErrorResponse {
+    error: AnthropicError,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicError {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A Messages API stand-in answering every request with `status` and `body`, recording
+    /// each request's head and JSON body
+    async fn serve(status: &'static str, content_type: &'static str, body: &'static str) -> (String, Arc<Mutex<Vec<(String, serde_json::Value)>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buffer = vec![0; 4096];
+                // Read until the whole body announced by content-length is in
+                let (head, request_body) = loop {
+                    let read = socket.read(&mut buffer).await.unwrap_or(0);
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head.lines()
+                            .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                            .unwrap_or(0);
+                        if body.len() >= length || read == 0 {
+                            break (head.to_string(), body.to_string());
+                        }
+                    } else if read == 0 {
+                        break (text, String::new());
+                    }
+                };
+                log.lock().unwrap().push((head, serde_json::from_str(&request_body).unwrap_or_default()));
+                let response = format!("HTTP/1.1 {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}", status, content_type, body.len(), body);
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (address, seen)
+    }
+
+    fn client(base_url: String) -> AnthropicClient {
+        AnthropicClient::new(AnthropicConfig {
+            api_key: "test-key".to_string(),
+            model: "claude-3-5-sonnet-latest".to_string(),
+            base_url,
+            max_tokens: 1024,
+            temperature: Some(0.2),
+            top_p: None,
+        }).unwrap()
+    }
+
+    fn message(role: MessageRole, content: &str) -> Message {
+        Message { id: String::new(), role, content: content.to_string(), timestamp: Utc::now(), function_call: None }
+    }
+
+    #[tokio::test]
+    async fn test_send_message_request_shape() {
+        let (server, seen) = serve(
+            "200 OK",
+            "application/json",
+            r#"{"id":"msg_1","type":"message","role":"assistant","content":[{"type":"text","text":"Hello"},{"type":"text","text":" there"}],"stop_reason":"end_turn"}"#,
+        ).await;
+
+        let reply = client(server).send_message(vec![
+            message(MessageRole::System, "Be brief."),
+            message(MessageRole::User, "Hi"),
+            message(MessageRole::Function, "3 notes"),
+            message(MessageRole::Assistant, "Found them"),
+            message(MessageRole::System, "Use UK spelling."),
+            message(MessageRole::User, "Thanks"),
+        ]).await.unwrap();
+        assert_eq!(reply.content, "Hello there");
+        assert!(matches!(reply.role, MessageRole::Assistant));
+
+        let seen = seen.lock().unwrap();
+        let (head, body) = &seen[0];
+        assert!(head.starts_with("POST /messages HTTP/1.1"));
+        let head = head.to_lowercase();
+        assert!(head.contains("x-api-key: test-key") && head.contains("anthropic-version: 2023-06-01"));
+        assert_eq!(body["model"], "claude-3-5-sonnet-latest");
+        assert_eq!(body["max_tokens"], 1024);
+        assert_eq!(body["system"], "Be brief.\n\nUse UK spelling.");
+        assert_eq!(body["messages"], serde_json::json!([
+            { "role": "user", "content": "Hi\n\nFunction result: 3 notes" },
+            { "role": "assistant", "content": "Found them" },
+            { "role": "user", "content": "Thanks" },
+        ]));
+        assert!(body.get("tools").is_none() && body.get("stream").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_function_calling_maps_tool_use() {
+        let (server, seen) = serve(
+            "200 OK",
+            "application/json",
+            r#"{"content":[{"type":"text","text":"Searching."},{"type":"tool_use","id":"toolu_1","name":"search_notes","input":{"query":"roadmap","limit":3}}],"stop_reason":"tool_use"}"#,
+        ).await;
+        let search = FunctionSchema {
+            name: "search_notes".to_string(),
+            description: "Search the vault".to_string(),
+            parameters: serde_json::json!({ "type": "object", "properties": { "query": { "type": "string" } } }),
+        };
+
+        let reply = client(server).function_calling(vec![message(MessageRole::User, "Find the roadmap")], vec![search]).await.unwrap();
+        let call = reply.function_call.unwrap();
+        assert_eq!(call.name, "search_notes");
+        assert_eq!(call.arguments, HashMap::from([
+            ("query".to_string(), serde_json::json!("roadmap")),
+            ("limit".to_string(), serde_json::json!(3)),
+        ]));
+        assert_eq!(reply.content, "Searching.");
+
+        let body = &seen.lock().unwrap()[0].1;
+        assert_eq!(body["tools"][0]["name"], "search_notes");
+        assert_eq!(body["tools"][0]["input_schema"]["properties"]["query"]["type"], "string");
+        assert_eq!(body["tool_choice"], serde_json::json!({ "type": "auto" }));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_text_deltas_are_forwarded() {
+        let (server, seen) = serve(
+            "200 OK",
+            "text/event-stream",
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{}}\n\n\
+             event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hel\"}}\n\n\
+             event: ping\ndata: {\"type\":\"ping\"}\n\n\
+             event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"lo\"}}\n\n\
+             event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+        ).await;
+
+        let mut rx = client(server).stream_response(vec![message(MessageRole::User, "Hi")]).await.unwrap();
+        let mut text = String::new();
+        while let Some(part) = rx.recv().await {
+            text.push_str(&part);
+        }
+        assert_eq!(text, "Hello");
+        assert_eq!(seen.lock().unwrap()[0].1["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn test_errors_map_to_limits_and_retryable_statuses() {
+        let (server, _) = serve("429 Too Many Requests", "application/json",
+            r#"{"type":"error","error":{"type":"rate_limit_error","message":"Number of request tokens has exceeded your per-minute rate limit"}}"#).await;
+        let error = client(server).send_message(vec![message(MessageRole::User, "Hi")]).await.unwrap_err();
+        assert_eq!(ArrowheadError::of(&error), Some(&ArrowheadError::RateLimited { provider: "anthropic".to_string(), retry_after: None }));
+
+        let (server, _) = serve("529 Overloaded", "application/json",
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#).await;
+        let error = client(server).send_message(vec![message(MessageRole::User, "Hi")]).await.unwrap_err();
+        assert_eq!(ArrowheadError::status_of(&error), Some(529));
+        assert!(error.to_string().contains("overloaded_error: Overloaded"));
+        assert!(crate::llm_retry::is_retryable(&error));
+    }
+}
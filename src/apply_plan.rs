@@ -0,0 +1,549 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::note_skeleton::stable_hash;
+use crate::vault_transaction::{VaultStore, VaultTransaction};
+
+/// Frontmatter key that identifies a note as an apply-plan
+const PLAN_MARKER: &str = "arrowhead_plan";
+/// Prefix of the hidden comment carrying each item's machine-readable data
+const ITEM_COMMENT: &str = "<!-- plan:";
+
+/// A single proposed change to one note
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanChange {
+    AddTag(String),
+    MoveToFolder(String),
+    AddLink { target: String, text: String },
+}
+
+impl PlanChange {
+    pub fn describe(&self) -> String {
+        match self {
+            PlanChange::AddTag(tag) => format!("Add tag #{}", tag),
+            PlanChange::MoveToFolder(folder) => format!("Move to {}/", folder.trim_end_matches('/')),
+            PlanChange::AddLink { target, text } => format!("Link to [[{}|{}]]", target.trim_end_matches(".md"), text),
+        }
+    }
+
+    fn encode(&self) -> String {
+        match self {
+            PlanChange::AddTag(tag) => format!("tag:{}", urlencoding::encode(tag)),
+            PlanChange::MoveToFolder(folder) => format!("folder:{}", urlencoding::encode(folder)),
+            PlanChange::AddLink { target, text } => {
+                format!("link:{}:{}", urlencoding::encode(target), urlencoding::encode(text))
+            }
+        }
+    }
+
+    fn decode(encoded: &str) -> Option<Self> {
+        let decode = |s: &str| urlencoding::decode(s).ok().map(|s| s.into_owned());
+        let (kind, value) = encoded.split_once(':')?;
+        match kind {
+            "tag" => Some(PlanChange::AddTag(decode(value)?)),
+            "folder" => Some(PlanChange::MoveToFolder(decode(value)?)),
+            "link" => {
+                let (target, text) = value.split_once(':')?;
+                Some(PlanChange::AddLink { target: decode(target)?, text: decode(text)? })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ItemStatus {
+    Pending,
+    Applied,
+    Failed,
+}
+
+impl ItemStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ItemStatus::Pending => "pending",
+            ItemStatus::Applied => "applied",
+            ItemStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanItem {
+    pub id: String,
+    pub checked: bool,
+    pub status: ItemStatus,
+    pub target: String,
+    pub change: PlanChange,
+    pub confidence: f32,
+    /// Hash of the target note when the plan was written
+    pub content_hash: u64,
+}
+
+impl PlanItem {
+    fn render(&self) -> String {
+        let marker = match self.status {
+            ItemStatus::Applied => "✅ ",
+            ItemStatus::Failed => "⚠️ ",
+            ItemStatus::Pending => "",
+        };
+        format!(
+            "- [{}] {}{} (confidence {:.2}) {}id={};target={};change={};hash={:016x};status={} -->",
+            if self.checked { "x" } else { " " },
+            marker,
+            self.change.describe(),
+            self.confidence,
+            ITEM_COMMENT,
+            self.id,
+            urlencoding::encode(&self.target),
+            self.change.encode(),
+            self.content_hash,
+            self.status.as_str(),
+        )
+    }
+
+    /// Parse an item line, ignoring everything a human might have edited except the checkbox
+    fn parse(line: &str) -> Option<Self> {
+        let trimmed = line.trim_start();
+        let rest = trimmed.strip_prefix("- [").or_else(|| trimmed.strip_prefix("* ["))?;
+        let checked = match rest.chars().next()? {
+            ' ' => false,
+            'x' | 'X' => true,
+            _ => return None,
+        };
+        let comment_start = rest.find(ITEM_COMMENT)? + ITEM_COMMENT.len();
+        let comment_end = comment_start + rest[comment_start..].find("-->")?;
+
+        let mut id = None;
+        let mut target = None;
+        let mut change = None;
+        let mut hash = None;
+        let mut status = ItemStatus::Pending;
+        for field in rest[comment_start..comment_end].trim().split(';') {
+            match field.split_once('=') {
+                Some(("id", value)) => id = Some(value.to_string()),
+                Some(("target", value)) => target = urlencoding::decode(value).ok().map(|v| v.into_owned()),
+                Some(("change", value)) => change = PlanChange::decode(value),
+                Some(("hash", value)) => hash = u64::from_str_radix(value, 16).ok(),
+                Some(("status", "applied")) => status = ItemStatus::Applied,
+                Some(("status", "failed")) => status = ItemStatus::Failed,
+                _ => {}
+            }
+        }
+
+        let confidence = rest.find("(confidence ")
+            .and_then(|start| {
+                let value = &rest[start + "(confidence ".len()..];
+                value[..value.find(')')?].parse().ok()
+            })
+            .unwrap_or(0.0);
+
+        Some(PlanItem {
+            id: id?,
+            checked,
+            status,
+            target: target?,
+            change: change?,
+            confidence,
+            content_hash: hash?,
+        })
+    }
+}
+
+/// A reviewable set of proposed changes, stored as a markdown checklist note
+#[derive(Debug, Clone)]
+pub struct ApplyPlan {
+    pub kind: String,
+    pub created_at: DateTime<Utc>,
+    pub items: Vec<PlanItem>,
+}
+
+impl ApplyPlan {
+    pub fn new(kind: &str) -> Self {
+        Self { kind: kind.to_string(), created_at: Utc::now(), items: Vec::new() }
+    }
+
+    /// Propose a change, recording the current content hash of its target note
+    pub fn add(&mut self, target: &str, change: PlanChange, confidence: f32, target_content: &str) {
+        let id = format!("{:08x}", stable_hash(&format!("{}|{}", target, change.encode())) as u32);
+        if self.items.iter().any(|item| item.id == id) {
+            return;
+        }
+        self.items.push(PlanItem {
+            id,
+            checked: true,
+            status: ItemStatus::Pending,
+            target: target.to_string(),
+            change,
+            confidence,
+            content_hash: stable_hash(target_content),
+        });
+    }
+
+    /// Render the plan note: one checkbox per change, grouped by target note
+    pub fn render(&self, plan_path: &str) -> String {
+        let mut out = format!(
+            "---\n{}: {}\ncreated_at: {}\nstatus: pending\n---\n# {} plan\n\n\
+             Uncheck any change you don't want, then run `arrowhead apply-plan {}`.\n\
+             Notes edited after this plan was written are skipped.\n",
+            PLAN_MARKER, self.kind, self.created_at.to_rfc3339(), capitalize(&self.kind), plan_path
+        );
+
+        let mut targets: Vec<&str> = Vec::new();
+        for item in &self.items {
+            if !targets.contains(&item.target.as_str()) {
+                targets.push(&item.target);
+            }
+        }
+        for target in targets {
+            out.push_str(&format!("\n## [[{}]]\n", target.trim_end_matches(".md")));
+            for item in self.items.iter().filter(|item| item.target == target) {
+                out.push_str(&item.render());
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// Parse a plan note. Item order, headings and any text a reviewer adds are ignored;
+    /// only item lines with their hidden comment and the frontmatter matter.
+    pub fn parse(content: &str) -> Result<Self> {
+        let frontmatter = content.strip_prefix("---\n")
+            .and_then(|rest| rest.find("\n---").map(|end| &rest[..end]))
+            .context("Plan note has no frontmatter")?;
+        let meta: serde_yaml::Value = serde_yaml::from_str(frontmatter).context("Plan frontmatter is not valid YAML")?;
+
+        let kind = meta.get(PLAN_MARKER)
+            .and_then(|v| v.as_str())
+            .with_context(|| format!("Not an Arrowhead plan (missing `{}` in frontmatter)", PLAN_MARKER))?;
+        let created_at = meta.get("created_at")
+            .and_then(|v| v.as_str())
+            .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+            .context("Plan frontmatter is missing a valid created_at")?
+            .with_timezone(&Utc);
+
+        let items = content.lines().filter_map(PlanItem::parse).collect();
+        Ok(Self { kind: kind.to_string(), created_at, items })
+    }
+}
+
+/// What happened when a plan was applied
+#[derive(Debug, Clone, Default)]
+pub struct PlanExecution {
+    pub applied: Vec<PlanItem>,
+    pub failed: Vec<(PlanItem, String)>,
+    pub unchecked: usize,
+    pub already_applied: usize,
+}
+
+impl PlanExecution {
+    pub fn summary(&self) -> String {
+        format!(
+            "Applied {} change(s), {} failed, {} unchecked, {} already applied earlier",
+            self.applied.len(), self.failed.len(), self.unchecked, self.already_applied
+        )
+    }
+}
+
+/// Apply the checked, not-yet-applied items of the plan note at `plan_path`, then update
+/// the note with each item's status and an execution report. Each target note's changes
+/// go through one transaction, so a note is either fully updated or left untouched.
+pub async fn execute_plan<S: VaultStore + ?Sized>(
+    store: &S,
+    plan_path: &str,
+    max_age: Duration,
+    now: DateTime<Utc>,
+) -> Result<PlanExecution> {
+    let plan_content = store.read_file(plan_path).await?
+        .with_context(|| format!("Plan note '{}' not found", plan_path))?;
+    let plan = ApplyPlan::parse(&plan_content)?;
+
+    let age = now - plan.created_at;
+    if age > max_age {
+        bail!(
+            "Plan is {} hours old (limit {}); re-run the planning command to refresh it",
+            age.num_hours(), max_age.num_hours()
+        );
+    }
+
+    let mut execution = PlanExecution::default();
+    let mut targets: Vec<&str> = Vec::new();
+    for item in &plan.items {
+        if !item.checked {
+            execution.unchecked += 1;
+        } else if item.status == ItemStatus::Applied {
+            execution.already_applied += 1;
+        } else if !targets.contains(&item.target.as_str()) {
+            targets.push(&item.target);
+        }
+    }
+
+    for target in targets {
+        let items: Vec<&PlanItem> = plan.items.iter()
+            .filter(|item| item.target == target && item.checked && item.status != ItemStatus::Applied)
+            .collect();
+
+        let result = apply_target(store, target, &items).await;
+        match result {
+            Ok(()) => execution.applied.extend(items.into_iter().cloned()),
+            Err(e) => execution.failed.extend(items.into_iter().map(|item| (item.clone(), e.to_string()))),
+        }
+    }
+
+    let updated = update_plan_note(&plan_content, &execution, now);
+    store.write_file(plan_path, &updated).await
+        .context("Changes were applied but the plan note could not be updated")?;
+
+    Ok(execution)
+}
+
+async fn apply_target<S: VaultStore + ?Sized>(store: &S, target: &str, items: &[&PlanItem]) -> Result<()> {
+    let content = store.read_file(target).await?.context("target note no longer exists")?;
+    if items.iter().any(|item| item.content_hash != stable_hash(&content)) {
+        bail!("target note changed since the plan was written");
+    }
+
+    let mut new_content = content.clone();
+    let mut new_path = target.to_string();
+    for item in items {
+        match &item.change {
+            PlanChange::AddTag(tag) => new_content = add_tag(&new_content, tag)?,
+            PlanChange::AddLink { target: link_target, text } => new_content = add_link(&new_content, link_target, text),
+            PlanChange::MoveToFolder(folder) => {
+                let file_name = target.rsplit('/').next().unwrap_or(target);
+                new_path = format!("{}/{}", folder.trim_end_matches('/'), file_name);
+            }
+        }
+    }
+
+    let mut transaction = VaultTransaction::new(&format!("apply plan changes to {}", target))
+        .write(&new_path, &new_content);
+    if new_path != target {
+        if store.read_file(&new_path).await?.is_some() {
+            bail!("'{}' already exists", new_path);
+        }
+        transaction = transaction.delete(target);
+    }
+
+    let report = transaction.execute(store).await?;
+    if !report.is_committed() {
+        bail!("{}", report.summary());
+    }
+    Ok(())
+}
+
+/// Add a tag to the note's frontmatter, creating the frontmatter if needed
+fn add_tag(content: &str, tag: &str) -> Result<String> {
+    let (mut meta, body) = match content.strip_prefix("---\n").and_then(|rest| rest.find("\n---").map(|end| (rest, end))) {
+        Some((rest, end)) => {
+            let meta: serde_yaml::Mapping = serde_yaml::from_str(&rest[..end]).unwrap_or_default();
+            (meta, rest[end + 4..].trim_start_matches('\n'))
+        }
+        None => (serde_yaml::Mapping::new(), content),
+    };
+
+    let key = serde_yaml::Value::from("tags");
+    let mut tags: Vec<serde_yaml::Value> = match meta.get(&key) {
+        Some(serde_yaml::Value::Sequence(tags)) => tags.clone(),
+        Some(serde_yaml::Value::String(tag)) => vec![serde_yaml::Value::from(tag.as_str())],
+        _ => Vec::new(),
+    };
+    if !tags.iter().any(|t| t.as_str() == Some(tag)) {
+        tags.push(serde_yaml::Value::from(tag));
+    }
+    meta.insert(key, serde_yaml::Value::Sequence(tags));
+
+    let yaml = serde_yaml::to_string(&meta).context("Failed to serialize frontmatter")?;
+    Ok(format!("---\n{}---\n{}", yaml, body))
+}
+
+/// Append a wikilink under a "Related" section unless the note already links there
+fn add_link(content: &str, target: &str, text: &str) -> String {
+    let target = target.trim_end_matches(".md");
+    if content.contains(&format!("[[{}]]", target)) || content.contains(&format!("[[{}|", target)) {
+        return content.to_string();
+    }
+
+    let mut out = content.trim_end().to_string();
+    if !out.lines().any(|line| line.trim() == "## Related") {
+        out.push_str("\n\n## Related");
+    }
+    out.push_str(&format!("\n- [[{}|{}]]\n", target, text));
+    out
+}
+
+/// Rewrite processed item lines in place, set the plan status and append a report.
+/// Everything else in the note, including reviewer comments, is preserved.
+fn update_plan_note(content: &str, execution: &PlanExecution, now: DateTime<Utc>) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_frontmatter = false;
+    let mut remaining = 0;
+
+    for (index, line) in content.lines().enumerate() {
+        if index == 0 && line == "---" {
+            in_frontmatter = true;
+            lines.push(line.to_string());
+            continue;
+        }
+        if in_frontmatter {
+            if line == "---" {
+                in_frontmatter = false;
+            }
+            if !line.starts_with("status:") {
+                lines.push(line.to_string());
+            }
+            continue;
+        }
+
+        match PlanItem::parse(line) {
+            Some(mut item) => {
+                if execution.applied.iter().any(|applied| applied.id == item.id) {
+                    item.status = ItemStatus::Applied;
+                } else if execution.failed.iter().any(|(failed, _)| failed.id == item.id) {
+                    item.status = ItemStatus::Failed;
+                }
+                if item.checked && item.status != ItemStatus::Applied {
+                    remaining += 1;
+                }
+                let indent = &line[..line.len() - line.trim_start().len()];
+                lines.push(format!("{}{}", indent, item.render()));
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+
+    let any_applied = !execution.applied.is_empty() || execution.already_applied > 0;
+    let status = match (any_applied, remaining) {
+        (true, 0) => "applied",
+        (true, _) => "partially-applied",
+        (false, _) if !execution.failed.is_empty() => "failed",
+        _ => "pending",
+    };
+    if let Some(end) = lines.iter().skip(1).position(|line| line == "---") {
+        lines.insert(end + 1, format!("status: {}", status));
+    }
+
+    let mut out = lines.join("\n");
+    out.push_str(&format!("\n\n## Execution report ({})\n", now.format("%Y-%m-%d %H:%M UTC")));
+    out.push_str(&format!("- {}\n", execution.summary()));
+    for (item, reason) in &execution.failed {
+        out.push_str(&format!("- ⚠️ {}: {} — {}\n", item.target, item.change.describe(), reason));
+    }
+    if status == "partially-applied" {
+        out.push_str("- Plan is partially applied; re-running `apply-plan` retries only the unfinished items.\n");
+    }
+    out
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_transaction::testing::FakeVault;
+
+    const ROADMAP: &str = "---\ntags:\n- planning\n---\n# Roadmap\nShip the importer.\n";
+    const STANDUP: &str = "# Standup\nNotes from today.\n";
+
+    fn plan() -> ApplyPlan {
+        let mut plan = ApplyPlan::new("organize");
+        plan.add("Notes/roadmap.md", PlanChange::AddTag("project".to_string()), 0.9, ROADMAP);
+        plan.add("Notes/roadmap.md", PlanChange::AddLink { target: "Notes/importer.md".to_string(), text: "Importer; v2".to_string() }, 0.7, ROADMAP);
+        plan.add("Notes/standup.md", PlanChange::MoveToFolder("Meetings".to_string()), 0.6, STANDUP);
+        plan
+    }
+
+    fn vault_with_plan(plan_note: &str) -> FakeVault {
+        FakeVault::new(&[
+            ("Notes/roadmap.md", ROADMAP),
+            ("Notes/standup.md", STANDUP),
+            ("Plans/organize.md", plan_note),
+        ])
+    }
+
+    #[test]
+    fn test_parse_survives_reordering_and_comments() {
+        let rendered = plan().render("Plans/organize.md");
+        let mut lines: Vec<&str> = rendered.lines().collect();
+        let first_item = lines.iter().position(|l| l.starts_with("- [")).unwrap();
+        lines.swap(first_item, first_item + 1);
+        lines.insert(first_item, "I'm not sure about the link below, check with Sam.");
+        let edited = lines.join("\n").replacen("- [x] Move", "- [ ] Move", 1);
+
+        let parsed = ApplyPlan::parse(&edited).unwrap();
+        assert_eq!(parsed.kind, "organize");
+        assert_eq!(parsed.items.len(), 3);
+        assert_eq!(parsed.items[0].change, PlanChange::AddLink { target: "Notes/importer.md".to_string(), text: "Importer; v2".to_string() });
+        assert!((parsed.items[0].confidence - 0.7).abs() < 1e-6);
+        assert!(!parsed.items.iter().find(|i| i.target == "Notes/standup.md").unwrap().checked);
+    }
+
+    #[tokio::test]
+    async fn test_applies_only_checked_items_and_reports() {
+        let rendered = plan().render("Plans/organize.md").replacen("- [x] Move", "- [ ] Move", 1);
+        let vault = vault_with_plan(&rendered);
+
+        let execution = execute_plan(&vault, "Plans/organize.md", Duration::hours(72), Utc::now()).await.unwrap();
+
+        assert_eq!(execution.applied.len(), 2);
+        assert_eq!(execution.unchecked, 1);
+        let roadmap = vault.get("Notes/roadmap.md").unwrap();
+        assert!(roadmap.contains("- project"));
+        assert!(roadmap.contains("## Related\n- [[Notes/importer|Importer; v2]]"));
+        assert!(vault.get("Notes/standup.md").is_some());
+        assert!(vault.get("Meetings/standup.md").is_none());
+
+        let updated = vault.get("Plans/organize.md").unwrap();
+        assert!(updated.contains("status: applied"));
+        assert!(updated.contains("## Execution report"));
+        assert_eq!(updated.matches("status=applied").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_changed_targets_are_refused_and_rerun_skips_completed() {
+        let vault = vault_with_plan(&plan().render("Plans/organize.md"));
+        vault.write_file("Notes/standup.md", "# Standup\nEdited after planning.\n").await.unwrap();
+
+        let first = execute_plan(&vault, "Plans/organize.md", Duration::hours(72), Utc::now()).await.unwrap();
+        assert_eq!(first.applied.len(), 2);
+        assert_eq!(first.failed.len(), 1);
+        assert!(first.failed[0].1.contains("changed since"));
+        assert!(vault.get("Meetings/standup.md").is_none());
+
+        let updated = vault.get("Plans/organize.md").unwrap();
+        assert!(updated.contains("status: partially-applied"));
+        assert!(updated.contains("⚠️ Move to Meetings/"));
+
+        let second = execute_plan(&vault, "Plans/organize.md", Duration::hours(72), Utc::now()).await.unwrap();
+        assert_eq!(second.already_applied, 2);
+        assert!(second.applied.is_empty());
+        assert_eq!(vault.get("Notes/roadmap.md").unwrap().matches("- project").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_moves_note_and_refuses_stale_plans() {
+        let plan = plan();
+        let vault = vault_with_plan(&plan.render("Plans/organize.md"));
+
+        let too_late = plan.created_at + Duration::hours(73);
+        let err = execute_plan(&vault, "Plans/organize.md", Duration::hours(72), too_late).await.unwrap_err();
+        assert!(err.to_string().contains("hours old"));
+        assert_eq!(vault.get("Notes/roadmap.md").unwrap(), ROADMAP);
+
+        execute_plan(&vault, "Plans/organize.md", Duration::hours(72), Utc::now()).await.unwrap();
+        assert_eq!(vault.get("Meetings/standup.md").unwrap(), STANDUP);
+        assert!(vault.get("Notes/standup.md").is_none());
+    }
+
+    #[test]
+    fn test_add_tag_creates_frontmatter() {
+        assert_eq!(add_tag("# Title\n", "work").unwrap(), "---\ntags:\n- work\n---\n# Title\n");
+        assert_eq!(add_tag(ROADMAP, "planning").unwrap(), ROADMAP);
+    }
+}
@@ -0,0 +1,1287 @@
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use reqwest::header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::calendar_adapter::{CalendarEvent, ConflictInfo, ConflictType, InviteeInfo, ResponseStatus};
+use crate::config::{CalendarSettings, DirectoryEntry};
+use crate::note_skeleton::stable_hash;
+
+/// Feeds without caching headers are refetched after this long
+const DEFAULT_FEED_TTL_MINUTES: i64 = 15;
+/// Recurring events are expanded this far into the future
+const RECURRENCE_HORIZON_DAYS: i64 = 90;
+/// Upper bound on occurrences expanded from one recurring event
+const MAX_OCCURRENCES: usize = 500;
+
+/// A period during which an attendee is not free
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BusyInterval {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub tentative: bool,
+}
+
+impl BusyInterval {
+    /// Placeholder event so external busy time can be reported like any other conflict
+    pub fn to_event(&self, attendee: &str) -> CalendarEvent {
+        CalendarEvent {
+            id: format!("busy-{}", self.start.timestamp()),
+            title: if self.tentative { "Tentative".to_string() } else { "Busy".to_string() },
+            description: None,
+            start_time: self.start,
+            end_time: self.end,
+            location: None,
+            attendees: vec![attendee.to_string()],
+            all_day: false,
+            recurring: false,
+            calendar_id: "external".to_string(),
+        }
+    }
+}
+
+/// Conflicts between a proposed slot and attendees' external busy time
+pub fn external_conflicts(
+    busy: &HashMap<String, Vec<BusyInterval>>,
+    attendees: &[String],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    buffer_minutes: u32,
+) -> Vec<ConflictInfo> {
+    attendees.iter()
+        .flat_map(|attendee| {
+            busy_during(busy, attendee, start, end, buffer_minutes).unwrap_or_default().into_iter()
+                .map(move |i| ConflictInfo {
+                    attendee: attendee.clone(),
+                    conflicting_event: i.to_event(attendee),
+                    conflict_type: ConflictType::DirectOverlap,
+                })
+        })
+        .collect()
+}
+
+/// The attendee's busy intervals overlapping `start..end` (widened by the buffer), or None
+/// when there is no busy time for them at all and their availability is unknown
+pub fn busy_during<'a>(
+    busy: &'a HashMap<String, Vec<BusyInterval>>,
+    attendee: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    buffer_minutes: u32,
+) -> Option<Vec<&'a BusyInterval>> {
+    let buffer = Duration::minutes(buffer_minutes as i64);
+    let intervals = busy.get(attendee)?;
+    Some(intervals.iter().filter(|i| start - buffer < i.end && end + buffer > i.start).collect())
+}
+
+/// Sort intervals and join the ones that overlap or touch. A joined interval is tentative
+/// only if everything in it was.
+pub fn merge_busy_intervals(mut intervals: Vec<BusyInterval>) -> Vec<BusyInterval> {
+    intervals.sort_by_key(|i| i.start);
+    let mut merged: Vec<BusyInterval> = Vec::with_capacity(intervals.len());
+    for interval in intervals {
+        match merged.last_mut() {
+            Some(last) if interval.start <= last.end => {
+                last.end = last.end.max(interval.end);
+                last.tentative &= interval.tentative;
+            }
+            _ => merged.push(interval),
+        }
+    }
+    merged
+}
+
+/// Shorten a feed URL for messages: published calendar URLs embed private tokens
+pub fn redact_url(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = without_scheme.split(['/', '?', '#']).next().unwrap_or("");
+    format!("{}/…#{:08x}", host, stable_hash(url) as u32)
+}
+
+// ---------------------------------------------------------------------------
+// ICS parsing
+// ---------------------------------------------------------------------------
+
+struct Property<'a> {
+    name: String,
+    params: Vec<(String, String)>,
+    value: &'a str,
+}
+
+impl Property<'_> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(rest) if !lines.is_empty() => lines.last_mut().unwrap().push_str(rest),
+            _ => lines.push(line.trim_end_matches('\r').to_string()),
+        }
+    }
+    lines
+}
+
+fn parse_property(line: &str) -> Option<Property<'_>> {
+    let mut in_quotes = false;
+    let colon = line.char_indices().find(|&(_, c)| {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        c == ':' && !in_quotes
+    })?.0;
+    let mut parts = line[..colon].split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_ascii_uppercase(), v.trim_matches('"').to_string()))
+        .collect();
+    Some(Property { name, params, value: &line[colon + 1..] })
+}
+
+/// A BEGIN/END block with its properties and nested blocks
+#[derive(Default)]
+struct Component<'a> {
+    kind: String,
+    properties: Vec<Property<'a>>,
+    children: Vec<Component<'a>>,
+}
+
+impl<'a> Component<'a> {
+    fn get(&self, name: &str) -> Option<&Property<'a>> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+}
+
+fn parse_components(lines: &[String]) -> Vec<Component<'_>> {
+    let mut stack: Vec<Component> = vec![Component::default()];
+    for line in lines {
+        let Some(property) = parse_property(line) else { continue };
+        match property.name.as_str() {
+            "BEGIN" => stack.push(Component { kind: property.value.to_ascii_uppercase(), ..Component::default() }),
+            "END" if stack.len() > 1 => {
+                let done = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(done);
+            }
+            _ => stack.last_mut().unwrap().properties.push(property),
+        }
+    }
+    while stack.len() > 1 {
+        let done = stack.pop().unwrap();
+        stack.last_mut().unwrap().children.push(done);
+    }
+    stack.pop().map(|root| root.children).unwrap_or_default()
+}
+
+fn descendants<'c, 'a>(components: &'c [Component<'a>], kind: &str, out: &mut Vec<&'c Component<'a>>) {
+    for component in components {
+        if component.kind == kind {
+            out.push(component);
+        }
+        descendants(&component.children, kind, out);
+    }
+}
+
+/// Standard/daylight rules of one VTIMEZONE
+struct ZoneRules {
+    standard_offset: i32,
+    daylight: Option<(Transition, Transition, i32)>,
+}
+
+/// Yearly transition: month, nth weekday (-1 = last), weekday and local time
+#[derive(Clone, Copy)]
+struct Transition {
+    month: u32,
+    nth: i32,
+    weekday: Weekday,
+    time: NaiveTime,
+}
+
+impl Transition {
+    fn in_year(&self, year: i32) -> Option<NaiveDateTime> {
+        let date = if self.nth > 0 {
+            NaiveDate::from_weekday_of_month_opt(year, self.month, self.weekday, self.nth as u8)?
+        } else {
+            let next_month = if self.month == 12 {
+                NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+            } else {
+                NaiveDate::from_ymd_opt(year, self.month + 1, 1)?
+            };
+            let last = next_month.pred_opt()?;
+            let back = (7 + last.weekday().num_days_from_monday() - self.weekday.num_days_from_monday()) % 7;
+            last - Duration::days(back as i64)
+        };
+        Some(date.and_time(self.time))
+    }
+}
+
+fn parse_offset(value: &str) -> Option<i32> {
+    let sign = if value.starts_with('-') { -1 } else { 1 };
+    let digits = value.trim_start_matches(['+', '-']);
+    let hours: i32 = digits.get(0..2)?.parse().ok()?;
+    let minutes: i32 = digits.get(2..4)?.parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+fn parse_weekday(code: &str) -> Option<Weekday> {
+    Some(match code {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+fn rrule_parts(value: &str) -> HashMap<String, String> {
+    value.split(';')
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_ascii_uppercase(), v.to_string()))
+        .collect()
+}
+
+fn parse_transition(component: &Component) -> Option<(Transition, i32)> {
+    let offset = parse_offset(component.get("TZOFFSETTO")?.value)?;
+    let start = NaiveDateTime::parse_from_str(component.get("DTSTART")?.value, "%Y%m%dT%H%M%S").ok()?;
+    let rule = component.get("RRULE").map(|p| rrule_parts(p.value)).unwrap_or_default();
+    let month = rule.get("BYMONTH").and_then(|m| m.parse().ok()).unwrap_or(start.month());
+    let (nth, weekday) = match rule.get("BYDAY") {
+        Some(by_day) => {
+            let split = by_day.len().saturating_sub(2);
+            (by_day[..split].parse().unwrap_or(1), parse_weekday(&by_day[split..])?)
+        }
+        None => (((start.day() - 1) / 7 + 1) as i32, start.weekday()),
+    };
+    Some((Transition { month, nth, weekday, time: start.time() }, offset))
+}
+
+fn parse_zones(components: &[Component]) -> HashMap<String, ZoneRules> {
+    let mut zones = Vec::new();
+    descendants(components, "VTIMEZONE", &mut zones);
+    zones.into_iter()
+        .filter_map(|zone| {
+            let id = zone.get("TZID")?.value.to_string();
+            let standard = zone.children.iter().find(|c| c.kind == "STANDARD").and_then(parse_transition);
+            let daylight = zone.children.iter().find(|c| c.kind == "DAYLIGHT").and_then(parse_transition);
+            let rules = match (standard, daylight) {
+                (Some((standard_at, standard_offset)), Some((daylight_at, daylight_offset))) => ZoneRules {
+                    standard_offset,
+                    daylight: Some((daylight_at, standard_at, daylight_offset)),
+                },
+                (Some((_, offset)), None) | (None, Some((_, offset))) => ZoneRules { standard_offset: offset, daylight: None },
+                (None, None) => return None,
+            };
+            Some((id, rules))
+        })
+        .collect()
+}
+
+impl ZoneRules {
+    fn offset_at(&self, local: NaiveDateTime) -> i32 {
+        let Some((daylight_at, standard_at, daylight_offset)) = self.daylight else {
+            return self.standard_offset;
+        };
+        let (Some(dst_start), Some(dst_end)) = (daylight_at.in_year(local.year()), standard_at.in_year(local.year())) else {
+            return self.standard_offset;
+        };
+        let in_daylight = if dst_start < dst_end {
+            local >= dst_start && local < dst_end
+        } else {
+            // Southern hemisphere: daylight time spans the new year
+            local >= dst_start || local < dst_end
+        };
+        if in_daylight { daylight_offset } else { self.standard_offset }
+    }
+}
+
+/// A parsed DTSTART/DTEND value
+#[derive(Clone, Copy)]
+enum IcsTime {
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+}
+
+impl IcsTime {
+    fn to_utc(self) -> DateTime<Utc> {
+        match self {
+            IcsTime::Date(date) => date.and_hms_opt(0, 0, 0).unwrap().and_utc(),
+            IcsTime::DateTime(at) => at,
+        }
+    }
+}
+
+fn parse_ics_time(property: &Property, zones: &HashMap<String, ZoneRules>) -> Option<IcsTime> {
+    let value = property.value.trim();
+    if property.param("VALUE") == Some("DATE") || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d").ok().map(IcsTime::Date);
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok().map(|t| IcsTime::DateTime(t.and_utc()));
+    }
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    // Zones without a VTIMEZONE definition (and floating times) are read as UTC
+    let offset = property.param("TZID").and_then(|id| zones.get(id)).map_or(0, |zone| zone.offset_at(local));
+    Some(IcsTime::DateTime((local - Duration::seconds(offset as i64)).and_utc()))
+}
+
+fn parse_ics_duration(value: &str) -> Option<Duration> {
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.trim_start_matches('+')),
+    };
+    let rest = rest.strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' => number.push(c),
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match (unit, in_time) {
+                    ('W', _) => Duration::weeks(n),
+                    ('D', _) => Duration::days(n),
+                    ('H', true) => Duration::hours(n),
+                    ('M', true) => Duration::minutes(n),
+                    ('S', true) => Duration::seconds(n),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(total * sign)
+}
+
+/// Start times of a recurring event from `start` until `horizon` (DAILY and WEEKLY rules)
+fn expand_rrule(rule: &str, start: DateTime<Utc>, horizon: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+    let rule = rrule_parts(rule);
+    let interval = rule.get("INTERVAL").and_then(|i| i.parse::<i64>().ok()).unwrap_or(1).max(1);
+    let count = rule.get("COUNT").and_then(|c| c.parse::<usize>().ok()).unwrap_or(MAX_OCCURRENCES).min(MAX_OCCURRENCES);
+    let until = rule.get("UNTIL")
+        .and_then(|u| {
+            NaiveDateTime::parse_from_str(u.trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()
+                .or_else(|| NaiveDate::parse_from_str(u, "%Y%m%d").ok().and_then(|d| d.and_hms_opt(23, 59, 59)))
+        })
+        .map(|u| u.and_utc())
+        .map_or(horizon, |u| u.min(horizon));
+
+    let mut occurrences = Vec::new();
+    match rule.get("FREQ").map(String::as_str) {
+        Some("DAILY") => {
+            let mut at = start;
+            while at <= until && occurrences.len() < count {
+                occurrences.push(at);
+                at += Duration::days(interval);
+            }
+        }
+        Some("WEEKLY") => {
+            let mut days: Vec<Weekday> = rule.get("BYDAY")
+                .map(|d| d.split(',').filter_map(|w| parse_weekday(w.trim_start_matches(|c: char| c == '-' || c.is_ascii_digit()))).collect())
+                .unwrap_or_default();
+            if days.is_empty() {
+                days.push(start.weekday());
+            }
+            let week_start = start - Duration::days(start.weekday().num_days_from_monday() as i64);
+            let mut week = 0;
+            'weeks: loop {
+                let base = week_start + Duration::weeks(week * interval);
+                if base > until {
+                    break;
+                }
+                let mut in_week: Vec<DateTime<Utc>> = days.iter()
+                    .map(|d| base + Duration::days(d.num_days_from_monday() as i64))
+                    .filter(|at| *at >= start)
+                    .collect();
+                in_week.sort();
+                for at in in_week {
+                    if at > until || occurrences.len() >= count {
+                        break 'weeks;
+                    }
+                    occurrences.push(at);
+                }
+                week += 1;
+            }
+        }
+        _ => occurrences.push(start),
+    }
+    occurrences
+}
+
+/// (UID, start) of recurrence instances that a VEVENT with RECURRENCE-ID replaces
+fn overridden_instances(events: &[&Component], zones: &HashMap<String, ZoneRules>) -> HashSet<(String, i64)> {
+    events.iter()
+        .filter_map(|event| {
+            let (uid, recurrence_id) = (event.get("UID")?, event.get("RECURRENCE-ID")?);
+            let at = parse_ics_time(recurrence_id, zones)?;
+            Some((uid.value.to_string(), at.to_utc().timestamp()))
+        })
+        .collect()
+}
+
+/// DTEND, or DTSTART plus DURATION; all-day events without either last a day
+fn event_end(event: &Component, start: IcsTime, zones: &HashMap<String, ZoneRules>) -> DateTime<Utc> {
+    match (event.get("DTEND").and_then(|p| parse_ics_time(p, zones)), event.get("DURATION").and_then(|p| parse_ics_duration(p.value))) {
+        (Some(end), _) => end.to_utc(),
+        (None, Some(duration)) => start.to_utc() + duration,
+        (None, None) => match start {
+            IcsTime::Date(_) => start.to_utc() + Duration::days(1),
+            IcsTime::DateTime(at) => at,
+        },
+    }
+}
+
+/// Start of every occurrence up to `horizon`, leaving out EXDATEs and overridden instances.
+/// Events without an RRULE, and the overriding instances themselves, occur once.
+fn occurrence_starts(
+    event: &Component,
+    start: IcsTime,
+    zones: &HashMap<String, ZoneRules>,
+    overridden: &HashSet<(String, i64)>,
+    horizon: DateTime<Utc>,
+) -> Vec<DateTime<Utc>> {
+    let Some(rule) = event.get("RRULE").filter(|_| event.get("RECURRENCE-ID").is_none()) else {
+        return vec![start.to_utc()];
+    };
+    let uid = event.get("UID").map(|p| p.value.to_string()).unwrap_or_default();
+    let excluded: Vec<i64> = event.properties.iter()
+        .filter(|p| p.name == "EXDATE")
+        .flat_map(|p| p.value.split(',').filter_map(|v| {
+            let single = Property { name: p.name.clone(), params: p.params.clone(), value: v };
+            parse_ics_time(&single, zones).map(|t| t.to_utc().timestamp())
+        }).collect::<Vec<_>>())
+        .collect();
+    expand_rrule(rule.value, start.to_utc(), horizon).into_iter()
+        .filter(|at| !excluded.contains(&at.timestamp()) && !overridden.contains(&(uid.clone(), at.timestamp())))
+        .collect()
+}
+
+/// Undo TEXT escaping (RFC 5545 3.3.11): `\n`, `\,`, `\;` and `\\`
+pub fn unescape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+pub fn escape_text(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n")
+}
+
+/// An ATTENDEE value without its `mailto:` scheme
+fn attendee_email(value: &str) -> String {
+    let value = value.trim();
+    value.get(..7).filter(|scheme| scheme.eq_ignore_ascii_case("mailto:")).map_or(value, |_| &value[7..]).to_string()
+}
+
+/// The attendees of a calendar object's event with their responses, from the ATTENDEE
+/// lines' CN, ROLE and PARTSTAT. Overrides of single occurrences are skipped, so a
+/// recurring event reports the series' responses.
+pub fn parse_invitees(ics: &str) -> Result<Vec<InviteeInfo>> {
+    let lines = unfold(ics);
+    let components = parse_components(&lines);
+    if !components.iter().any(|c| c.kind == "VCALENDAR") {
+        bail!("Not an iCalendar object");
+    }
+    let mut vevents = Vec::new();
+    descendants(&components, "VEVENT", &mut vevents);
+    let Some(event) = vevents.iter().find(|e| e.get("RECURRENCE-ID").is_none()).or(vevents.first()) else {
+        bail!("Calendar object has no event");
+    };
+    Ok(event.properties.iter()
+        .filter(|p| p.name == "ATTENDEE")
+        .map(|p| InviteeInfo {
+            email: attendee_email(p.value),
+            name: p.param("CN").map(str::to_string),
+            required: !p.param("ROLE").is_some_and(|role| role.eq_ignore_ascii_case("OPT-PARTICIPANT") || role.eq_ignore_ascii_case("NON-PARTICIPANT")),
+            response_status: ResponseStatus::from_partstat(p.param("PARTSTAT").unwrap_or("NEEDS-ACTION")),
+        })
+        .collect())
+}
+
+/// The unescaped value of `name` on a calendar object's event, skipping overrides of single
+/// occurrences like `parse_invitees`
+pub fn event_property(ics: &str, name: &str) -> Option<String> {
+    let lines = unfold(ics);
+    let components = parse_components(&lines);
+    let mut vevents = Vec::new();
+    descendants(&components, "VEVENT", &mut vevents);
+    let event = vevents.iter().find(|e| e.get("RECURRENCE-ID").is_none()).or(vevents.first())?;
+    event.get(&name.to_ascii_uppercase()).map(|p| unescape_text(p.value))
+}
+
+/// The VEVENTs of a calendar object. With `range`, recurring events are expanded into the
+/// occurrences that overlap it; without one, each VEVENT is returned once.
+pub fn parse_calendar_events(ics: &str, calendar_id: &str, range: Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<Vec<CalendarEvent>> {
+    let lines = unfold(ics);
+    let components = parse_components(&lines);
+    if !components.iter().any(|c| c.kind == "VCALENDAR") {
+        bail!("Not an iCalendar object");
+    }
+    let zones = parse_zones(&components);
+    let mut vevents = Vec::new();
+    descendants(&components, "VEVENT", &mut vevents);
+    let overridden = overridden_instances(&vevents, &zones);
+
+    let mut events = Vec::new();
+    for event in vevents {
+        if event.get("STATUS").is_some_and(|p| p.value.eq_ignore_ascii_case("CANCELLED")) {
+            continue;
+        }
+        let Some(start) = event.get("DTSTART").and_then(|p| parse_ics_time(p, &zones)) else { continue };
+        let length = event_end(event, start, &zones) - start.to_utc();
+        let text = |name: &str| event.get(name).map(|p| unescape_text(p.value));
+        let template = CalendarEvent {
+            id: event.get("UID").map(|p| p.value.to_string()).unwrap_or_default(),
+            title: text("SUMMARY").unwrap_or_else(|| "Untitled Event".to_string()),
+            description: text("DESCRIPTION"),
+            start_time: start.to_utc(),
+            end_time: start.to_utc() + length,
+            location: text("LOCATION"),
+            attendees: event.properties.iter()
+                .filter(|p| p.name == "ATTENDEE")
+                .map(|p| attendee_email(p.value))
+                .collect(),
+            all_day: matches!(start, IcsTime::Date(_)),
+            recurring: event.get("RRULE").is_some() || event.get("RECURRENCE-ID").is_some(),
+            calendar_id: calendar_id.to_string(),
+        };
+        match range {
+            Some((from, to)) => events.extend(
+                occurrence_starts(event, start, &zones, &overridden, to).into_iter()
+                    .filter(|at| *at + length > from && *at < to)
+                    .map(|at| CalendarEvent { start_time: at, end_time: at + length, ..template.clone() }),
+            ),
+            None => events.push(template),
+        }
+    }
+    events.sort_by_key(|e| e.start_time);
+    Ok(events)
+}
+
+/// Busy intervals from a published calendar: VFREEBUSY periods or VEVENTs (including simple
+/// daily/weekly recurrences up to `horizon`)
+pub fn parse_busy_intervals(ics: &str, horizon: DateTime<Utc>) -> Result<Vec<BusyInterval>> {
+    let lines = unfold(ics);
+    let components = parse_components(&lines);
+    if !components.iter().any(|c| c.kind == "VCALENDAR") {
+        bail!("Not an iCalendar feed");
+    }
+    let zones = parse_zones(&components);
+    let mut busy = Vec::new();
+
+    let mut freebusy = Vec::new();
+    descendants(&components, "VFREEBUSY", &mut freebusy);
+    for component in freebusy {
+        for property in component.properties.iter().filter(|p| p.name == "FREEBUSY") {
+            let fb_type = property.param("FBTYPE").unwrap_or("BUSY").to_ascii_uppercase();
+            if fb_type == "FREE" {
+                continue;
+            }
+            for period in property.value.split(',') {
+                let Some((start, end)) = period.split_once('/') else { continue };
+                let Ok(start) = NaiveDateTime::parse_from_str(start.trim_end_matches('Z'), "%Y%m%dT%H%M%S") else { continue };
+                let start = start.and_utc();
+                let end = match parse_ics_duration(end) {
+                    Some(duration) => start + duration,
+                    None => match NaiveDateTime::parse_from_str(end.trim_end_matches('Z'), "%Y%m%dT%H%M%S") {
+                        Ok(end) => end.and_utc(),
+                        Err(_) => continue,
+                    },
+                };
+                busy.push(BusyInterval { start, end, tentative: fb_type == "BUSY-TENTATIVE" });
+            }
+        }
+    }
+
+    let mut events = Vec::new();
+    descendants(&components, "VEVENT", &mut events);
+    let overridden = overridden_instances(&events, &zones);
+    for event in events {
+        let status = event.get("STATUS").map(|p| p.value.to_ascii_uppercase());
+        if status.as_deref() == Some("CANCELLED") || event.get("TRANSP").is_some_and(|p| p.value.eq_ignore_ascii_case("TRANSPARENT")) {
+            continue;
+        }
+        let Some(start) = event.get("DTSTART").and_then(|p| parse_ics_time(p, &zones)) else { continue };
+        let length = event_end(event, start, &zones) - start.to_utc();
+        let tentative = status.as_deref() == Some("TENTATIVE");
+        let starts = occurrence_starts(event, start, &zones, &overridden, horizon);
+        busy.extend(starts.into_iter().map(|at| BusyInterval { start: at, end: at + length, tentative }));
+    }
+
+    busy.retain(|b| b.end > b.start);
+    busy.sort_by_key(|b| b.start);
+    Ok(busy)
+}
+
+// ---------------------------------------------------------------------------
+// Fetching and caching
+// ---------------------------------------------------------------------------
+
+/// Result of fetching a feed, honoring HTTP caching validators
+#[derive(Debug, Clone)]
+pub enum FeedResponse {
+    NotModified { max_age_secs: Option<i64> },
+    Fresh { body: String, etag: Option<String>, last_modified: Option<String>, max_age_secs: Option<i64> },
+}
+
+#[async_trait]
+pub trait FeedFetcher: Send + Sync {
+    async fn fetch(&self, url: &str, etag: Option<&str>, last_modified: Option<&str>) -> Result<FeedResponse>;
+}
+
+/// Fetches feeds over HTTP(S), sending conditional request headers
+pub struct HttpFeedFetcher {
+    client: Client,
+}
+
+impl HttpFeedFetcher {
+    pub fn new() -> Self {
+        Self { client: Client::new() }
+    }
+
+    pub fn with_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Default for HttpFeedFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn max_age(cache_control: Option<&str>) -> Option<i64> {
+    let directives = cache_control?.to_ascii_lowercase();
+    if directives.contains("no-store") || directives.contains("no-cache") {
+        return Some(0);
+    }
+    directives.split(',')
+        .filter_map(|d| d.trim().strip_prefix("max-age="))
+        .find_map(|v| v.parse().ok())
+}
+
+#[async_trait]
+impl FeedFetcher for HttpFeedFetcher {
+    async fn fetch(&self, url: &str, etag: Option<&str>, last_modified: Option<&str>) -> Result<FeedResponse> {
+        let url = match url.strip_prefix("webcal://") {
+            Some(rest) => format!("https://{}", rest),
+            None => url.to_string(),
+        };
+        let mut request = self.client.get(&url);
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        // reqwest errors include the URL, so they are replaced rather than wrapped
+        let response = request.send().await.map_err(|e| anyhow!("request failed ({})", e.without_url()))?;
+        let header = |name| response.headers().get(name).and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok()).map(str::to_string);
+        let max_age_secs = max_age(header(CACHE_CONTROL).as_deref());
+
+        match response.status() {
+            StatusCode::NOT_MODIFIED => Ok(FeedResponse::NotModified { max_age_secs }),
+            status if status.is_success() => {
+                let etag = header(ETAG);
+                let last_modified = header(LAST_MODIFIED);
+                let body = response.text().await.map_err(|e| anyhow!("could not read feed ({})", e.without_url()))?;
+                Ok(FeedResponse::Fresh { body, etag, last_modified, max_age_secs })
+            }
+            status => bail!("server returned {}", status),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedFeed {
+    fetched_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    busy: Vec<BusyInterval>,
+}
+
+/// Busy intervals from previous fetches, keyed by a hash of the feed URL so the
+/// cache file never contains the URL itself
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AvailabilityCache {
+    feeds: HashMap<String, CachedFeed>,
+}
+
+fn cache_key(url: &str) -> String {
+    format!("{:016x}", stable_hash(url))
+}
+
+pub fn default_availability_cache_path() -> PathBuf {
+    let mut path = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push(".config");
+    path.push("arrowhead");
+    path.push("availability_cache.json");
+    path
+}
+
+impl AvailabilityCache {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).context("Failed to read availability cache")?;
+        serde_json::from_str(&content).context("Failed to parse availability cache")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?).context("Failed to write availability cache")
+    }
+}
+
+/// Busy time per attendee plus any problems worth telling the user about
+#[derive(Debug, Default)]
+pub struct ExternalAvailability {
+    pub busy: HashMap<String, Vec<BusyInterval>>,
+    pub warnings: Vec<String>,
+}
+
+/// Attendees with a configured `availability_url`
+pub fn availability_sources(settings: &CalendarSettings, attendees: &[String]) -> Vec<(String, String)> {
+    attendees.iter()
+        .filter_map(|attendee| {
+            let contact = settings.contacts.iter().find(|(name, _)| name.eq_ignore_ascii_case(attendee))?.1;
+            contact.availability_url.clone().map(|url| (attendee.clone(), url))
+        })
+        .collect()
+}
+
+/// Fetch every source in parallel (each under its own timeout), falling back to cached
+/// data when a fetch fails
+pub async fn load_external_availability(
+    sources: &[(String, String)],
+    cache: &mut AvailabilityCache,
+    fetcher: &dyn FeedFetcher,
+    settings: &CalendarSettings,
+    now: DateTime<Utc>,
+) -> ExternalAvailability {
+    let timeout = std::time::Duration::from_secs(settings.availability_timeout_secs);
+    let horizon = now + Duration::days(RECURRENCE_HORIZON_DAYS);
+
+    let fetches = sources.iter().map(|(attendee, url)| {
+        let cached = cache.feeds.get(&cache_key(url)).cloned();
+        async move {
+            let outcome = match &cached {
+                Some(feed) if feed.expires_at > now => None,
+                _ => Some(
+                    tokio::time::timeout(timeout, fetcher.fetch(url, cached.as_ref().and_then(|f| f.etag.as_deref()), cached.as_ref().and_then(|f| f.last_modified.as_deref())))
+                        .await
+                        .unwrap_or_else(|_| Err(anyhow!("timed out after {}s", timeout.as_secs()))),
+                ),
+            };
+            (attendee, url, cached, outcome)
+        }
+    });
+    let results = futures::future::join_all(fetches).await;
+
+    let mut availability = ExternalAvailability::default();
+    let ttl = |max_age: Option<i64>| Duration::seconds(max_age.unwrap_or(DEFAULT_FEED_TTL_MINUTES * 60).max(0));
+    for (attendee, url, cached, outcome) in results {
+        let key = cache_key(url);
+        let feed = match (outcome, cached) {
+            (None, Some(cached)) => cached,
+            (Some(Ok(FeedResponse::Fresh { body, etag, last_modified, max_age_secs })), _) => {
+                match parse_busy_intervals(&body, horizon) {
+                    Ok(busy) => CachedFeed { fetched_at: now, expires_at: now + ttl(max_age_secs), etag, last_modified, busy },
+                    Err(e) => {
+                        availability.warnings.push(format!("Ignoring availability for {} ({}): {}", attendee, redact_url(url), e));
+                        continue;
+                    }
+                }
+            }
+            (Some(Ok(FeedResponse::NotModified { max_age_secs })), Some(cached)) => {
+                CachedFeed { fetched_at: now, expires_at: now + ttl(max_age_secs), ..cached }
+            }
+            (Some(Ok(FeedResponse::NotModified { .. })), None) => {
+                availability.warnings.push(format!("No availability for {} ({}): server sent 304 without a cached copy", attendee, redact_url(url)));
+                continue;
+            }
+            (Some(Err(e)), Some(cached)) => {
+                let age = now - cached.fetched_at;
+                if age > Duration::hours(settings.availability_stale_hours) {
+                    availability.warnings.push(format!(
+                        "Availability for {} ({}) is {} hours old: {}",
+                        attendee, redact_url(url), age.num_hours(), e
+                    ));
+                }
+                availability.busy.insert(attendee.clone(), cached.busy);
+                continue;
+            }
+            (Some(Err(e)), None) => {
+                availability.warnings.push(format!("No availability for {} ({}): {}", attendee, redact_url(url), e));
+                continue;
+            }
+            (None, None) => unreachable!("a fetch is always attempted without a cached copy"),
+        };
+        availability.busy.insert(attendee.clone(), feed.busy.clone());
+        cache.feeds.insert(key, feed);
+    }
+    availability
+}
+
+impl ExternalAvailability {
+    /// Add `other`'s busy time and warnings, merging intervals of attendees known to both
+    pub fn merge(&mut self, other: ExternalAvailability) {
+        for (attendee, busy) in other.busy {
+            let mut all = self.busy.remove(&attendee).unwrap_or_default();
+            all.extend(busy);
+            self.busy.insert(attendee, merge_busy_intervals(all));
+        }
+        self.warnings.extend(other.warnings);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// CalDAV free-busy queries
+// ---------------------------------------------------------------------------
+
+/// Body of a CalDAV `free-busy-query` REPORT (RFC 4791, section 7.10)
+pub fn free_busy_query_body(start: DateTime<Utc>, end: DateTime<Utc>) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<C:free-busy-query xmlns:C="urn:ietf:params:xml:ns:caldav">
+    <C:time-range start="{}" end="{}"/>
+</C:free-busy-query>"#,
+        start.format("%Y%m%dT%H%M%SZ"),
+        end.format("%Y%m%dT%H%M%SZ"),
+    )
+}
+
+/// An attendee's free-busy URL with the credentials to query it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreeBusySource {
+    pub attendee: String,
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// Attendees with a `freebusy_url` in the directory. Entries without credentials of their
+/// own use the calendar's.
+pub fn directory_sources(settings: &CalendarSettings, attendees: &[String]) -> Vec<FreeBusySource> {
+    attendees.iter()
+        .filter_map(|attendee| {
+            let entry: &DirectoryEntry = settings.directory.iter().find(|(name, _)| name.eq_ignore_ascii_case(attendee))?.1;
+            Some(FreeBusySource {
+                attendee: attendee.clone(),
+                url: entry.freebusy_url.clone()?,
+                username: entry.username.clone().or_else(|| settings.username.clone()),
+                password: entry.password.clone().or_else(|| settings.password.clone()),
+            })
+        })
+        .collect()
+}
+
+#[async_trait]
+pub trait FreeBusyClient: Send + Sync {
+    /// The iCalendar body of a free-busy-query response for `start..end`
+    async fn query(&self, source: &FreeBusySource, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<String>;
+}
+
+/// Sends free-busy-query REPORTs over HTTP(S)
+pub struct CalDavFreeBusyClient {
+    client: Client,
+}
+
+impl CalDavFreeBusyClient {
+    pub fn with_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl FreeBusyClient for CalDavFreeBusyClient {
+    async fn query(&self, source: &FreeBusySource, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<String> {
+        let mut request = self.client
+            .request(reqwest::Method::from_bytes(b"REPORT").unwrap(), &source.url)
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .header("Depth", "1")
+            .body(free_busy_query_body(start, end));
+        if let Some(username) = &source.username {
+            request = request.basic_auth(username, source.password.as_deref());
+        }
+        // reqwest errors include the URL, so they are replaced rather than wrapped
+        let response = request.send().await.map_err(|e| anyhow!("request failed ({})", e.without_url()))?;
+        let status = response.status();
+        if !status.is_success() {
+            bail!("server returned {}", status);
+        }
+        response.text().await.map_err(|e| anyhow!("could not read response ({})", e.without_url()))
+    }
+}
+
+/// Query every directory source in parallel (each under the feed timeout). An attendee whose
+/// server fails or answers with something other than iCalendar is left out of `busy`, so
+/// scheduling reports them as Unknown.
+pub async fn query_directory_availability(
+    sources: &[FreeBusySource],
+    client: &dyn FreeBusyClient,
+    settings: &CalendarSettings,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> ExternalAvailability {
+    let timeout = std::time::Duration::from_secs(settings.availability_timeout_secs);
+    let queries = sources.iter().map(|source| async move {
+        let body = tokio::time::timeout(timeout, client.query(source, start, end))
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("timed out after {}s", timeout.as_secs())));
+        (source, body.and_then(|body| parse_busy_intervals(&body, end)))
+    });
+
+    let mut availability = ExternalAvailability::default();
+    for (source, result) in futures::future::join_all(queries).await {
+        match result {
+            Ok(busy) => {
+                let busy = busy.into_iter().filter(|b| b.end > start && b.start < end).collect();
+                availability.busy.insert(source.attendee.clone(), merge_busy_intervals(busy));
+            }
+            Err(e) => availability.warnings.push(format!("No free/busy for {} ({}): {}", source.attendee, redact_url(&source.url), e)),
+        }
+    }
+    availability
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ContactSettings;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Trimmed-down export of a Google Calendar "secret address in iCal format"
+    const GOOGLE_ICS: &str = "BEGIN:VCALENDAR\r
+PRODID:-//Google Inc//Google Calendar 70.9054//EN\r
+VERSION:2.0\r
+CALSCALE:GREGORIAN\r
+METHOD:PUBLISH\r
+X-WR-CALNAME:alice@example.com\r
+X-WR-TIMEZONE:Europe/Berlin\r
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+X-LIC-LOCATION:Europe/Berlin\r
+BEGIN:DAYLIGHT\r
+TZOFFSETFROM:+0100\r
+TZOFFSETTO:+0200\r
+TZNAME:CEST\r
+DTSTART:19700329T020000\r
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r
+END:DAYLIGHT\r
+BEGIN:STANDARD\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+TZNAME:CET\r
+DTSTART:19701025T030000\r
+RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+DTSTART:20240604T130000Z\r
+DTEND:20240604T140000Z\r
+DTSTAMP:20240601T101500Z\r
+UID:7kukuqrfedlm2f9t0vr6h5qs3f@google.com\r
+CREATED:20240520T080000Z\r
+DESCRIPTION:\r
+LAST-MODIFIED:20240520T080000Z\r
+LOCATION:\r
+SEQUENCE:0\r
+STATUS:CONFIRMED\r
+SUMMARY:Busy\r
+TRANSP:OPAQUE\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;TZID=Europe/Berlin:20240603T093000\r
+DTEND;TZID=Europe/Berlin:20240603T100000\r
+RRULE:FREQ=WEEKLY;WKST=MO;COUNT=4;BYDAY=MO,WE\r
+EXDATE;TZID=Europe/Berlin:20240605T093000\r
+DTSTAMP:20240601T101500Z\r
+UID:0f3jcs1r9m2jb5ml7c6s0l4o9e@google.com\r
+SUMMARY:Standup\r
+TRANSP:OPAQUE\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20240607\r
+DTEND;VALUE=DATE:20240608\r
+UID:holiday@google.com\r
+SUMMARY:Out of office\r
+TRANSP:TRANSPARENT\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20240606T150000Z\r
+DTEND:20240606T160000Z\r
+UID:cancelled@google.com\r
+STATUS:CANCELLED\r
+SUMMARY:Busy\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    /// Outlook "publish calendar" feed (availability only) with a Windows time zone
+    const OUTLOOK_ICS: &str = "BEGIN:VCALENDAR
+METHOD:PUBLISH
+PRODID:Microsoft Exchange Server 2010
+VERSION:2.0
+X-WR-CALNAME:Calendar
+BEGIN:VTIMEZONE
+TZID:Pacific Standard Time
+BEGIN:STANDARD
+DTSTART:16010101T020000
+TZOFFSETFROM:-0700
+TZOFFSETTO:-0800
+RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=1SU;BYMONTH=11
+END:STANDARD
+BEGIN:DAYLIGHT
+DTSTART:16010101T020000
+TZOFFSETFROM:-0800
+TZOFFSETTO:-0700
+RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=2SU;BYMONTH=3
+END:DAYLIGHT
+END:VTIMEZONE
+BEGIN:VEVENT
+CLASS:PUBLIC
+SUMMARY:Tentative
+DTSTART;TZID=Pacific Standard Time:20240604T080000
+DTEND;TZID=Pacific Standard Time:20240604T083000
+UID:040000008200E00074C5B7101A82E00800000000
+STATUS:TENTATIVE
+TRANSP:OPAQUE
+X-MICROSOFT-CDO-BUSYSTATUS:TENTATIVE
+END:VEVENT
+BEGIN:VEVENT
+CLASS:PUBLIC
+SUMMARY:Busy
+DTSTART;TZID=Pacific Standard Time:20241210T100000
+DTEND;TZID=Pacific Standard Time:20241210T110000
+UID:040000008200E00074C5B7101A82E00800000001
+TRANSP:OPAQUE
+X-MICROSOFT-CDO-BUSYSTATUS:BUSY
+END:VEVENT
+END:VCALENDAR
+";
+
+    const FREEBUSY_ICS: &str = "BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Example Corp//Free-Busy//EN
+BEGIN:VFREEBUSY
+DTSTART:20240603T000000Z
+DTEND:20240610T000000Z
+FREEBUSY:20240603T140000Z/20240603T150000Z,20240604T090000Z/PT2H
+FREEBUSY;FBTYPE=BUSY-TENTATIVE:20240605T100000Z/20240605T103000Z
+FREEBUSY;FBTYPE=FREE:20240606T080000Z/20240606T170000Z
+END:VFREEBUSY
+END:VCALENDAR
+";
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn horizon() -> DateTime<Utc> {
+        utc("2024-09-01T00:00:00Z")
+    }
+
+    #[test]
+    fn test_parse_google_feed() {
+        let busy = parse_busy_intervals(GOOGLE_ICS, horizon()).unwrap();
+        let starts: Vec<String> = busy.iter().map(|b| b.start.to_rfc3339()).collect();
+        // Weekly standup at 09:30 Berlin (07:30 UTC in summer), Wednesday 5th excluded
+        assert_eq!(starts, vec![
+            "2024-06-03T07:30:00+00:00",
+            "2024-06-04T13:00:00+00:00",
+            "2024-06-10T07:30:00+00:00",
+            "2024-06-12T07:30:00+00:00",
+        ]);
+        assert!(busy.iter().all(|b| !b.tentative && b.end - b.start <= Duration::hours(1)));
+    }
+
+    #[test]
+    fn test_parse_outlook_feed_with_windows_zone() {
+        let busy = parse_busy_intervals(OUTLOOK_ICS, horizon()).unwrap();
+        assert_eq!(busy.len(), 2);
+        // PDT in June, PST in December
+        assert_eq!(busy[0].start, utc("2024-06-04T15:00:00Z"));
+        assert!(busy[0].tentative);
+        assert_eq!(busy[1].start, utc("2024-12-10T18:00:00Z"));
+        assert_eq!(busy[1].end, utc("2024-12-10T19:00:00Z"));
+    }
+
+    #[test]
+    fn test_parse_freebusy_feed() {
+        let busy = parse_busy_intervals(FREEBUSY_ICS, horizon()).unwrap();
+        assert_eq!(busy, vec![
+            BusyInterval { start: utc("2024-06-03T14:00:00Z"), end: utc("2024-06-03T15:00:00Z"), tentative: false },
+            BusyInterval { start: utc("2024-06-04T09:00:00Z"), end: utc("2024-06-04T11:00:00Z"), tentative: false },
+            BusyInterval { start: utc("2024-06-05T10:00:00Z"), end: utc("2024-06-05T10:30:00Z"), tentative: true },
+        ]);
+        assert!(parse_busy_intervals("<html>Not found</html>", horizon()).is_err());
+    }
+
+    #[test]
+    fn test_external_conflicts_and_redaction() {
+        let mut busy = HashMap::new();
+        busy.insert("alice@example.com".to_string(), parse_busy_intervals(FREEBUSY_ICS, horizon()).unwrap());
+        let attendees = vec!["alice@example.com".to_string(), "bob@example.com".to_string()];
+
+        let conflicts = external_conflicts(&busy, &attendees, utc("2024-06-03T13:30:00Z"), utc("2024-06-03T14:00:00Z"), 0);
+        assert!(conflicts.is_empty());
+        let conflicts = external_conflicts(&busy, &attendees, utc("2024-06-03T13:30:00Z"), utc("2024-06-03T14:00:00Z"), 15);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].attendee, "alice@example.com");
+
+        let url = "https://calendar.google.com/calendar/ical/alice%40example.com/private-3f9a1c/basic.ics";
+        let redacted = redact_url(url);
+        assert!(redacted.starts_with("calendar.google.com/…#"));
+        assert!(!redacted.contains("private"));
+    }
+
+    struct FakeFetcher {
+        responses: HashMap<String, Result<FeedResponse, String>>,
+        delay_for: Option<String>,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl FeedFetcher for FakeFetcher {
+        async fn fetch(&self, url: &str, _etag: Option<&str>, _last_modified: Option<&str>) -> Result<FeedResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.delay_for.as_deref() == Some(url) {
+                tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+            }
+            self.responses[url].clone().map_err(|e| anyhow!(e))
+        }
+    }
+
+    fn settings() -> CalendarSettings {
+        let mut settings = CalendarSettings { availability_timeout_secs: 1, ..CalendarSettings::default() };
+        for (name, url) in [("alice@example.com", "https://a.example/alice-secret.ics"), ("bob@example.com", "https://b.example/bob-secret.ics")] {
+            settings.contacts.insert(name.to_string(), ContactSettings { availability_url: Some(url.to_string()) });
+        }
+        settings
+    }
+
+    #[tokio::test]
+    async fn test_load_uses_cache_times_out_and_warns_when_stale() {
+        let settings = settings();
+        let attendees = vec!["alice@example.com".to_string(), "bob@example.com".to_string(), "carol@example.com".to_string()];
+        let sources = availability_sources(&settings, &attendees);
+        assert_eq!(sources.len(), 2);
+
+        let fresh = |body: &str| Ok(FeedResponse::Fresh { body: body.to_string(), etag: Some("\"v1\"".to_string()), last_modified: None, max_age_secs: Some(600) });
+        let fetcher = FakeFetcher {
+            responses: HashMap::from([
+                ("https://a.example/alice-secret.ics".to_string(), fresh(FREEBUSY_ICS)),
+                ("https://b.example/bob-secret.ics".to_string(), fresh(OUTLOOK_ICS)),
+            ]),
+            delay_for: None,
+            calls: AtomicUsize::new(0),
+        };
+        let mut cache = AvailabilityCache::default();
+        let now = utc("2024-06-01T08:00:00Z");
+        let loaded = load_external_availability(&sources, &mut cache, &fetcher, &settings, now).await;
+        assert!(loaded.warnings.is_empty());
+        assert_eq!(loaded.busy["alice@example.com"].len(), 3);
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+
+        // Within max-age nothing is fetched
+        load_external_availability(&sources, &mut cache, &fetcher, &settings, now + Duration::minutes(5)).await;
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+
+        // Two days later: Alice's server hangs, Bob's is down. Both fall back to stale data
+        // without stalling scheduling, and warnings never contain the full URLs.
+        let failing = FakeFetcher {
+            responses: HashMap::from([
+                ("https://a.example/alice-secret.ics".to_string(), fresh(FREEBUSY_ICS)),
+                ("https://b.example/bob-secret.ics".to_string(), Err("server returned 503".to_string())),
+            ]),
+            delay_for: Some("https://a.example/alice-secret.ics".to_string()),
+            calls: AtomicUsize::new(0),
+        };
+        let started = std::time::Instant::now();
+        let loaded = load_external_availability(&sources, &mut cache, &failing, &settings, now + Duration::days(2)).await;
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(loaded.busy.len(), 2);
+        assert_eq!(loaded.warnings.len(), 2);
+        assert!(loaded.warnings.iter().any(|w| w.contains("timed out")));
+        assert!(loaded.warnings.iter().all(|w| w.contains("48 hours old") && !w.contains("secret")));
+
+        let serialized = serde_json::to_string(&cache).unwrap();
+        assert!(!serialized.contains("secret"));
+    }
+
+    struct FakeFreeBusy;
+
+    #[async_trait]
+    impl FreeBusyClient for FakeFreeBusy {
+        async fn query(&self, source: &FreeBusySource, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<String> {
+            assert_eq!(source.username.as_deref(), Some("me@example.com"));
+            match source.attendee.as_str() {
+                "alice@example.com" => Ok(FREEBUSY_ICS.to_string()),
+                _ => bail!("server returned 403 Forbidden"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_directory_queries_merge_busy_time_and_report_failures() {
+        let mut settings = settings();
+        settings.username = Some("me@example.com".to_string());
+        for name in ["alice@example.com", "dave@example.com"] {
+            let url = format!("https://dav.example/calendars/{}/", name);
+            settings.directory.insert(name.to_string(), DirectoryEntry { freebusy_url: Some(url), ..DirectoryEntry::default() });
+        }
+        let attendees = vec!["alice@example.com".to_string(), "carol@example.com".to_string(), "dave@example.com".to_string()];
+        let sources = directory_sources(&settings, &attendees);
+        assert_eq!(sources.iter().map(|s| s.attendee.as_str()).collect::<Vec<_>>(), vec!["alice@example.com", "dave@example.com"]);
+
+        let mut availability = ExternalAvailability::default();
+        availability.busy.insert("alice@example.com".to_string(), vec![
+            BusyInterval { start: utc("2024-06-03T14:30:00Z"), end: utc("2024-06-03T15:30:00Z"), tentative: false },
+        ]);
+        availability.merge(query_directory_availability(&sources, &FakeFreeBusy, &settings, utc("2024-06-03T00:00:00Z"), utc("2024-06-05T00:00:00Z")).await);
+
+        // The feed's 14:30-15:30 joins the query's 14:00-15:00; the tentative slot on the 5th is out of range
+        let alice: Vec<(String, String)> = availability.busy["alice@example.com"].iter().map(|b| (b.start.to_rfc3339(), b.end.to_rfc3339())).collect();
+        assert_eq!(alice, vec![
+            ("2024-06-03T14:00:00+00:00".to_string(), "2024-06-03T15:30:00+00:00".to_string()),
+            ("2024-06-04T09:00:00+00:00".to_string(), "2024-06-04T11:00:00+00:00".to_string()),
+        ]);
+        // Dave's server refused, so his availability stays unknown
+        assert!(!availability.busy.contains_key("dave@example.com"));
+        assert_eq!(availability.warnings.len(), 1);
+        assert!(availability.warnings[0].contains("dave@example.com") && availability.warnings[0].contains("403"));
+        assert!(free_busy_query_body(utc("2024-06-03T00:00:00Z"), utc("2024-06-05T00:00:00Z")).contains(r#"<C:time-range start="20240603T000000Z" end="20240605T000000Z"/>"#));
+    }
+}
@@ -0,0 +1,416 @@
+use anyhow::{bail, Result};
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+use crate::ai_conversation::{create_llm_client, LLMClient, Message, MessageRole};
+use crate::cli::{BacklinksAction, BacklinksArgs};
+use crate::config::{BacklinksSettings, Config};
+use crate::confirm::{confirm, Operation, OperationClass};
+use crate::health::load_notes_in;
+use crate::managed_sections::{managed_sections, remove, upsert, without_managed_sections, SectionError, SectionWrite};
+use crate::note_skeleton::stable_hash;
+use crate::notes::link_key;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::usage::UsagePurpose;
+use crate::vault_transaction::{VaultTransaction};
+
+const SECTION_HEADING: &str = "## Referenced by";
+const SECTION_NAME: &str = "backlinks";
+/// Name in `include_generated` that lets links from MOC notes through
+const MOC: &str = "moc";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Relation {
+    References,
+    Elaborates,
+    Contradicts,
+}
+
+impl Relation {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::References => "references",
+            Self::Elaborates => "elaborates",
+            Self::Contradicts => "contradicts",
+        }
+    }
+
+    fn parse(label: &str) -> Option<Self> {
+        match label.trim().trim_matches(['*', '.', '"']).to_lowercase().as_str() {
+            "references" => Some(Self::References),
+            "elaborates" => Some(Self::Elaborates),
+            "contradicts" => Some(Self::Contradicts),
+            _ => None,
+        }
+    }
+}
+
+const CONTRADICTS: &[&str] = &[
+    "contradict", "disagree", "however", "in contrast", "unlike", "instead of", "conflicts with", "is wrong",
+    "incorrect", "refute", "doesn't match", "does not match", "outdated",
+];
+const ELABORATES: &[&str] = &[
+    "expands on", "builds on", "more detail", "in detail", "elaborat", "deep dive", "follow-up", "follows up",
+    "extends", "based on", "breaks down", "walks through",
+];
+
+/// Offline relation label from keywords in the sentence
+pub fn classify(sentence: &str) -> Relation {
+    let lower = sentence.to_lowercase();
+    if CONTRADICTS.iter().any(|k| lower.contains(k)) {
+        Relation::Contradicts
+    } else if ELABORATES.iter().any(|k| lower.contains(k)) {
+        Relation::Elaborates
+    } else {
+        Relation::References
+    }
+}
+
+/// A `[[link]]` and the sentence around it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LinkMention {
+    pub source: String,
+    /// `link_key` of the linked note's name
+    pub target: String,
+    pub sentence: String,
+}
+
+pub(crate) fn note_name(path: &str) -> &str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.strip_suffix(".md").unwrap_or(name)
+}
+
+/// Replace `[[target|alias]]` with its display text
+fn plain_text(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        out.push_str(rest[..start].strip_suffix('!').unwrap_or(&rest[..start]));
+        let inner = &rest[start + 2..];
+        let Some(end) = inner.find("]]") else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let link = &inner[..end];
+        let shown = link.split_once('|').map_or(link.split('#').next().unwrap_or(link), |(_, alias)| alias);
+        out.push_str(note_name(shown.trim()));
+        rest = &inner[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn sentence_around(line: &str, start: usize, end: usize) -> String {
+    const STOPS: [&str; 3] = [". ", "! ", "? "];
+    let from = STOPS.iter().filter_map(|s| line[..start].rfind(s).map(|i| i + s.len())).max().unwrap_or(0);
+    let to = STOPS.iter().filter_map(|s| line[end..].find(s).map(|i| end + i + 1)).min().unwrap_or(line.len());
+    let sentence = line[from..to].trim_start_matches(|c: char| c.is_whitespace() || "-*>#".contains(c));
+    let sentence = sentence.strip_prefix("[ ] ").or_else(|| sentence.strip_prefix("[x] ")).unwrap_or(sentence);
+    plain_text(sentence).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Every `[[link]]` in a note outside frontmatter, code blocks and managed sections
+/// (other than those named in `include_generated`)
+pub fn link_mentions(path: &str, content: &str, include_generated: &[String]) -> Vec<LinkMention> {
+    let body = ObsidianAdapter::parse_markdown_file(content).map_or(content.to_string(), |file| file.content);
+    let body = without_managed_sections(&body, include_generated);
+    let mut mentions = Vec::new();
+    let mut in_code = false;
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        let mut at = 0;
+        while let Some(start) = line[at..].find("[[").map(|i| at + i) {
+            let Some(end) = line[start..].find("]]").map(|i| start + i + 2) else { break };
+            let link = &line[start + 2..end - 2];
+            let target = link[..link.find(['|', '#']).unwrap_or(link.len())].trim();
+            let name = note_name(target);
+            if !name.is_empty() && (target.ends_with(".md") || !name.contains('.')) {
+                mentions.push(LinkMention {
+                    source: path.to_string(),
+                    target: link_key(name),
+                    sentence: sentence_around(line, start, end),
+                });
+            }
+            at = end;
+        }
+    }
+    mentions
+}
+
+/// Tags from frontmatter and inline `#tags`, lowercased without the `#`
+/// Frontmatter tags and inline `#tags`, lowercased without the `#`
+pub(crate) fn note_tags(content: &str) -> Vec<String> {
+    let parsed = ObsidianAdapter::parse_markdown_file(content).ok();
+    let body = parsed.as_ref().map_or(content, |file| file.content.as_str());
+    parsed.as_ref().and_then(|file| file.frontmatter.tags.clone()).unwrap_or_default().into_iter()
+        .chain(body.split_whitespace().filter_map(|w| w.strip_prefix('#')).filter(|t| t.starts_with(char::is_alphabetic)).map(str::to_string))
+        .map(|t| t.trim_start_matches('#').trim_end_matches([',', '.']).to_lowercase())
+        .collect()
+}
+
+/// Links to `target_path` from other notes, skipping MOC notes unless configured
+pub fn inbound_mentions(notes: &[(String, String)], target_path: &str, settings: &BacklinksSettings) -> Vec<LinkMention> {
+    let key = link_key(note_name(target_path));
+    let include_mocs = settings.include_generated.iter().any(|name| name == MOC);
+    let mut seen = BTreeSet::new();
+    let mut mentions: Vec<LinkMention> = notes.iter()
+        .filter(|(path, _)| path != target_path)
+        .filter(|(_, content)| include_mocs || !note_tags(content).iter().any(|t| settings.moc_tags.contains(t)))
+        .flat_map(|(path, content)| link_mentions(path, content, &settings.include_generated))
+        .filter(|m| m.target == key)
+        .filter(|m| seen.insert((m.source.clone(), m.sentence.clone())))
+        .collect();
+    mentions.sort_by(|a, b| a.source.cmp(&b.source));
+    mentions
+}
+
+/// Changes whenever the inbound link set or a surrounding sentence changes
+pub fn mentions_hash(mentions: &[LinkMention]) -> String {
+    let text: String = mentions.iter().map(|m| format!("{}\t{}\n", m.source, m.sentence)).collect();
+    format!("{:016x}", stable_hash(&text))
+}
+
+/// The hash of the inbound mentions the note's backlinks section was written from, if it
+/// has a section
+pub fn recorded_hash(content: &str) -> Option<String> {
+    let section = managed_sections(content).into_iter().find(|s| s.id == SECTION_NAME)?;
+    // Sections written before the shared marker format kept it as `hash=`
+    let key = if section.legacy { "hash" } else { "inputs" };
+    section.attributes.get(key).cloned()
+}
+
+pub fn render_section(mentions: &[LinkMention], relations: &[Relation], hash: &str) -> SectionWrite {
+    let body: String = mentions.iter().zip(relations)
+        .map(|(mention, relation)| format!("- [[{}]] ({}): \"{}\"\n", note_name(&mention.source), relation.label(), mention.sentence))
+        .collect();
+    SectionWrite::new(SECTION_NAME, SECTION_NAME, &body).heading(SECTION_HEADING).attribute("inputs", hash)
+}
+
+/// Replace the backlinks section (or remove it with `None`). A new section goes above
+/// the changelog.
+pub fn apply_section(content: &str, section: Option<&SectionWrite>) -> Result<String, SectionError> {
+    match section {
+        Some(section) => upsert(content, section),
+        None => remove(content, SECTION_NAME, SECTION_NAME),
+    }
+}
+
+/// A note whose backlinks section needs rewriting
+#[derive(Debug)]
+pub struct PendingUpdate {
+    pub path: String,
+    pub mentions: Vec<LinkMention>,
+    pub hash: String,
+}
+
+/// Notes among `targets` whose inbound links changed since their section was written
+pub fn pending_updates(notes: &[(String, String)], targets: &[String], settings: &BacklinksSettings, force: bool) -> Vec<PendingUpdate> {
+    targets.iter()
+        .filter_map(|path| {
+            let content = &notes.iter().find(|(p, _)| p == path)?.1;
+            let mentions = inbound_mentions(notes, path, settings);
+            let hash = mentions_hash(&mentions);
+            let recorded = recorded_hash(content);
+            let unchanged = match &recorded {
+                Some(recorded) => *recorded == hash,
+                None => mentions.is_empty(),
+            };
+            (force || !unchanged).then(|| PendingUpdate { path: path.clone(), mentions, hash })
+        })
+        .collect()
+}
+
+/// Label every mention in one request; sentences the reply skips fall back to keywords
+async fn classify_with_llm(llm: &dyn LLMClient, target: &str, mentions: &[LinkMention]) -> Result<Vec<Relation>> {
+    let numbered: String = mentions.iter().enumerate()
+        .map(|(i, m)| format!("{}. {}\n", i + 1, m.sentence))
+        .collect();
+    let prompt = format!(
+        "Each sentence below links to the note \"{}\". Label how each relates to that note: references, elaborates or contradicts.\n\
+         Reply with one line per sentence in the form `<number>: <label>` and nothing else.\n\n{}",
+        target, numbered
+    );
+    let response = llm.send_message(vec![Message {
+        id: Uuid::new_v4().to_string(),
+        role: MessageRole::User,
+        content: prompt,
+        timestamp: Utc::now(),
+        function_call: None,
+    }]).await?;
+    let mut relations: Vec<Relation> = mentions.iter().map(|m| classify(&m.sentence)).collect();
+    for line in response.content.lines() {
+        let Some((number, label)) = line.split_once(':') else { continue };
+        let index = number.trim().trim_start_matches(['-', '*']).trim().parse::<usize>().ok();
+        if let (Some(index), Some(relation)) = (index, Relation::parse(label)) {
+            if (1..=relations.len()).contains(&index) {
+                relations[index - 1] = relation;
+            }
+        }
+    }
+    Ok(relations)
+}
+
+pub async fn handle_backlinks_command(args: BacklinksArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let BacklinksAction::Update { paths, tag, dry_run, force, llm_assist, yes } = args.action;
+    let config = Config::load().unwrap_or_default();
+    let settings = &config.backlinks;
+    let notes = load_notes_in(adapter, &settings.folders).await;
+
+    let targets: Vec<String> = match (paths.is_empty(), tag) {
+        (false, None) => paths.iter().map(|p| if p.ends_with(".md") { p.clone() } else { format!("{}.md", p) }).collect(),
+        (true, Some(tag)) => {
+            let tag = tag.trim_start_matches('#').to_lowercase();
+            notes.iter().filter(|(_, content)| note_tags(content).contains(&tag)).map(|(path, _)| path.clone()).collect()
+        }
+        (false, Some(_)) => bail!("Pass either note paths or --tag, not both"),
+        (true, None) => bail!("Pass one or more note paths or --tag <tag>"),
+    };
+    for missing in targets.iter().filter(|t| !notes.iter().any(|(p, _)| p == *t)) {
+        println!("⚠️  {}: not found in {}", missing, settings.folders.join(", "));
+    }
+
+    let updates = pending_updates(&notes, &targets, settings, force);
+    let output = |written: &[&str]| serde_json::json!({ "checked": targets.len(), "dry_run": dry_run, "updated": written });
+    if updates.is_empty() {
+        println!("Backlinks are up to date in {} note(s).", targets.len());
+        crate::script::publish_output(output(&[]));
+        return Ok(());
+    }
+    for update in &updates {
+        println!("{}: {} inbound link(s)", update.path, update.mentions.len());
+    }
+    let changed: Vec<&str> = updates.iter().map(|u| u.path.as_str()).collect();
+    if dry_run {
+        println!("Dry run: {} note(s) would be updated", updates.len());
+        crate::script::publish_output(output(&changed));
+        return Ok(());
+    }
+    let operation = Operation::new(OperationClass::BulkWrite, format!("Update backlinks in {} note(s)", updates.len()), updates.len());
+    if updates.len() > 1 && !confirm(&config.confirmations, &operation, yes)? {
+        println!("Nothing changed.");
+        return Ok(());
+    }
+
+    let llm = if llm_assist { Some(create_llm_client(&config, UsagePurpose::Analysis)?) } else { None };
+    let mut transaction = VaultTransaction::new("backlinks update");
+    for update in &updates {
+        let relations = match &llm {
+            Some(llm) if !update.mentions.is_empty() => classify_with_llm(llm.as_ref(), note_name(&update.path), &update.mentions).await
+                .unwrap_or_else(|e| {
+                    println!("⚠️  {}: LLM labelling failed ({}), using keywords", update.path, e);
+                    update.mentions.iter().map(|m| classify(&m.sentence)).collect()
+                }),
+            _ => update.mentions.iter().map(|m| classify(&m.sentence)).collect(),
+        };
+        let section = (!update.mentions.is_empty()).then(|| render_section(&update.mentions, &relations, &update.hash));
+        let content = &notes.iter().find(|(p, _)| *p == update.path).expect("pending updates come from loaded notes").1;
+        match apply_section(content, section.as_ref()) {
+            Ok(updated) => transaction = transaction.write_if_unchanged(&update.path, &updated, content),
+            Err(e) => println!("⚠️  {}: {}", update.path, e),
+        }
+    }
+    if transaction.is_empty() {
+        bail!("Backlinks sections were not updated");
+    }
+    let report = transaction.execute(adapter).await?;
+    if let Err(e) = report.append_to_log(adapter.transaction_log()) {
+        eprintln!("Warning: could not write transaction log: {}", e);
+    }
+    println!("{}", report.summary());
+    if !report.is_committed() {
+        bail!("Backlinks sections were not updated");
+    }
+    crate::script::publish_output(output(&changed));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries.iter().map(|(p, c)| (p.to_string(), c.to_string())).collect()
+    }
+
+    fn sources(mentions: &[LinkMention]) -> Vec<&str> {
+        mentions.iter().map(|m| m.source.as_str()).collect()
+    }
+
+    const ROADMAP: &str = "---\ntags: [important]\n---\n# Roadmap\nShip the importer first.\n";
+
+    #[test]
+    fn test_mentions_keep_the_surrounding_sentence() {
+        let content = "# Kickoff\nWe met on Monday. This plan expands on [[Roadmap|the roadmap]] with dates! Other text.\n```\n[[Roadmap]] in code\n```\n- [ ] Read [[Roadmap#Goals]] before Friday\n![[diagram.png]]\n";
+        let mentions = link_mentions("Notes/kickoff.md", content, &[]);
+        let sentences: Vec<&str> = mentions.iter().map(|m| m.sentence.as_str()).collect();
+        assert_eq!(sentences, vec!["This plan expands on the roadmap with dates!", "Read Roadmap before Friday"]);
+        assert!(mentions.iter().all(|m| m.target == "roadmap"));
+        assert_eq!(classify(sentences[0]), Relation::Elaborates);
+        assert_eq!(classify("However, [[Roadmap]] is wrong about June"), Relation::Contradicts);
+        assert_eq!(classify(sentences[1]), Relation::References);
+    }
+
+    #[test]
+    fn test_generated_sections_and_mocs_are_excluded_unless_configured() {
+        let notes = vault(&[
+            ("Notes/roadmap.md", ROADMAP),
+            ("Notes/kickoff.md", "Kickoff builds on [[Roadmap]].\n\n## Changelog\n<!-- arrowhead:changelog -->\n- 2024-05-01 — linked [[Roadmap]]\n"),
+            ("Notes/index.md", "---\ntags: [moc]\n---\n- [[Roadmap]]\n- [[Kickoff]]\n"),
+            ("Notes/retro.md", "Retro.\n\n## Referenced by\n<!-- arrowhead:backlinks hash=1 -->\n- [[Roadmap]] (references): \"x\"\n"),
+        ]);
+        let settings = BacklinksSettings::default();
+        let mentions = inbound_mentions(&notes, "Notes/roadmap.md", &settings);
+        assert_eq!(sources(&mentions), vec!["Notes/kickoff.md"]);
+
+        let settings = BacklinksSettings {
+            include_generated: vec!["changelog".to_string(), "moc".to_string()],
+            ..Default::default()
+        };
+        let mentions = inbound_mentions(&notes, "Notes/roadmap.md", &settings);
+        assert_eq!(sources(&mentions), vec!["Notes/index.md", "Notes/kickoff.md", "Notes/kickoff.md"]);
+    }
+
+    #[test]
+    fn test_section_is_placed_above_changelog_and_hash_guards_rewrites() {
+        let settings = BacklinksSettings::default();
+        let roadmap = format!("{}\n## Changelog\n<!-- arrowhead:changelog -->\n- 2024-05-01 — edited\n", ROADMAP);
+        let mut notes = vault(&[("Notes/roadmap.md", &roadmap), ("Notes/kickoff.md", "Kickoff builds on [[Roadmap]].\n")]);
+        let targets = vec!["Notes/roadmap.md".to_string()];
+
+        let updates = pending_updates(&notes, &targets, &settings, false);
+        assert_eq!(updates.len(), 1);
+        let relations = vec![Relation::Elaborates];
+        let written = apply_section(&roadmap, Some(&render_section(&updates[0].mentions, &relations, &updates[0].hash))).unwrap();
+        assert!(written.contains("Ship the importer first.\n\n## Referenced by\n<!-- arrowhead:begin owner=backlinks id=backlinks hash="));
+        assert!(written.contains("- [[kickoff]] (elaborates): \"Kickoff builds on Roadmap.\"\n<!-- arrowhead:end id=backlinks -->\n\n## Changelog\n"));
+        assert_eq!(recorded_hash(&written), Some(updates[0].hash.clone()));
+
+        // Same inbound links: nothing to do, unless forced
+        notes[0].1 = written.clone();
+        assert!(pending_updates(&notes, &targets, &settings, false).is_empty());
+        assert_eq!(pending_updates(&notes, &targets, &settings, true).len(), 1);
+
+        // The surrounding sentence changed
+        notes[1].1 = "Kickoff replaces parts of [[Roadmap]].\n".to_string();
+        assert_eq!(pending_updates(&notes, &targets, &settings, false).len(), 1);
+
+        // The last inbound link went away: the section is removed
+        notes[1].1 = "Kickoff.\n".to_string();
+        let updates = pending_updates(&notes, &targets, &settings, false);
+        assert!(updates[0].mentions.is_empty());
+        assert_eq!(apply_section(&written, None).unwrap(), roadmap);
+
+        // Sections in the old single-marker form keep their hash until rewritten
+        let legacy = format!("{}\n## Referenced by\n<!-- arrowhead:backlinks hash=00ff -->\n- [[kickoff]]\n", ROADMAP);
+        assert_eq!(recorded_hash(&legacy), Some("00ff".to_string()));
+    }
+}
@@ -0,0 +1,542 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::cli::{CacheAction, CacheArgs};
+use crate::config::{CacheSettings, Config};
+use crate::confirm::{confirm, Operation, OperationClass};
+use crate::note_skeleton::stable_hash;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::opt_out::{Feature, OptOutPolicy};
+use crate::reindex::walk;
+use crate::report::{DetailLevel, Report, ReportView};
+use crate::todo_model::set_frontmatter_entry;
+use crate::vault_transaction::{VaultStore, DEFAULT_READ_CONCURRENCY};
+
+/// Cache files older versions wrote into whatever directory arrowhead was run from
+const LEGACY_EMBEDDINGS: &str = ".arrowhead_embeddings.bin";
+const LEGACY_INDEX_SNAPSHOT: &str = ".arrowhead_embeddings.bin.meta.json";
+const LEGACY_TEMPLATES: &str = ".arrowhead_templates.bin";
+const LEGACY_CHANGELOG_STATE: &str = ".arrowhead_changelog_state.json";
+
+const EMBEDDINGS_FILE: &str = "embeddings.bin";
+const INDEX_SNAPSHOT_FILE: &str = "embeddings.bin.meta.json";
+const TEMPLATES_FILE: &str = "templates.bin";
+const CHANGELOG_STATE_FILE: &str = "changelog_state.json";
+
+pub fn default_cache_dir() -> PathBuf {
+    let mut path = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push(".cache");
+    path.push("arrowhead");
+    path
+}
+
+/// Parent of the per-vault cache folders
+pub fn cache_dir(settings: &CacheSettings) -> PathBuf {
+    settings.dir.as_ref().map(PathBuf::from).unwrap_or_else(default_cache_dir)
+}
+
+/// Folder name for a vault's caches: its host and port, readable, plus a hash of the whole
+/// URL so two vaults behind the same host (or a trailing slash) never share a folder
+pub fn vault_id(base_url: &str) -> String {
+    let base_url = base_url.trim().trim_end_matches('/');
+    let address = base_url.split_once("://").map_or(base_url, |(_, rest)| rest);
+    let address = address.split('/').next().unwrap_or_default();
+    let mut slug = String::new();
+    for c in address.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+    let hash = format!("{:08x}", stable_hash(&base_url.to_lowercase()) as u32);
+    if slug.is_empty() { hash } else { format!("{}-{}", slug, hash) }
+}
+
+/// Where one vault's cache files live
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachePaths {
+    root: PathBuf,
+}
+
+impl CachePaths {
+    pub fn new(settings: &CacheSettings, base_url: &str) -> Self {
+        match &settings.vault_dir {
+            Some(dir) => Self { root: PathBuf::from(dir) },
+            None => Self { root: cache_dir(settings).join(vault_id(base_url)) },
+        }
+    }
+
+    /// This vault's cache folder, with any cache files left in the working directory by
+    /// older versions moved into it
+    pub fn for_config(config: &Config) -> Self {
+        let paths = Self::new(&config.cache, &config.obsidian.base_url);
+        paths.migrate_from_working_dir();
+        paths
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn embeddings(&self) -> PathBuf {
+        self.root.join(EMBEDDINGS_FILE)
+    }
+
+    pub fn index_snapshot(&self) -> PathBuf {
+        self.root.join(INDEX_SNAPSHOT_FILE)
+    }
+
+    pub fn templates(&self) -> PathBuf {
+        self.root.join(TEMPLATES_FILE)
+    }
+
+    /// What `changelog scan` last saw of each living document
+    pub fn changelog_state(&self) -> PathBuf {
+        self.root.join(CHANGELOG_STATE_FILE)
+    }
+
+    /// Move the legacy cache files in `dir` into this folder. A file already in the cache
+    /// folder wins; the legacy copy is then left alone. Returns the files moved.
+    pub fn migrate_legacy(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut moved = Vec::new();
+        for (legacy, target) in [
+            (LEGACY_EMBEDDINGS, self.embeddings()),
+            (LEGACY_INDEX_SNAPSHOT, self.index_snapshot()),
+            (LEGACY_TEMPLATES, self.templates()),
+            (LEGACY_CHANGELOG_STATE, self.changelog_state()),
+        ] {
+            let legacy = dir.join(legacy);
+            if !legacy.is_file() {
+                continue;
+            }
+            if target.exists() {
+                log::warn!("Ignoring {}: {} already exists", legacy.display(), target.display());
+                continue;
+            }
+            fs::create_dir_all(&self.root).with_context(|| format!("Failed to create cache folder {}", self.root.display()))?;
+            // A rename can't cross filesystems, and the home directory is often on another
+            if fs::rename(&legacy, &target).is_err() {
+                fs::copy(&legacy, &target).with_context(|| format!("Failed to move {} to {}", legacy.display(), target.display()))?;
+                fs::remove_file(&legacy).with_context(|| format!("Failed to remove {}", legacy.display()))?;
+            }
+            moved.push(target);
+        }
+        Ok(moved)
+    }
+
+    /// `migrate_legacy` for the working directory, logging what moved rather than failing
+    pub fn migrate_from_working_dir(&self) {
+        match self.migrate_legacy(Path::new(".")) {
+            Ok(moved) => {
+                for path in moved {
+                    log::info!("Moved cache file from the working directory to {}", path.display());
+                }
+            }
+            Err(e) => log::warn!("Could not move old cache files: {:#}", e),
+        }
+    }
+}
+
+/// Replace `path` with `data` so readers, including other arrowhead processes, see the old
+/// file or the new one and never a partial write: write a temporary file beside it, then
+/// rename it over the original
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let temp = dir.join(format!(".{}.{}.{}.tmp", name, std::process::id(), uuid::Uuid::new_v4().simple()));
+    let written = fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp, path));
+    if let Err(e) = written {
+        fs::remove_file(&temp).ok();
+        return Err(e).with_context(|| format!("Failed to write {}", path.display()));
+    }
+    Ok(())
+}
+
+/// A map that holds at most `capacity` entries, dropping the least recently used
+#[derive(Debug, Clone)]
+pub struct LruCache<V> {
+    capacity: usize,
+    /// Least recently used first
+    order: VecDeque<String>,
+    entries: HashMap<String, V>,
+}
+
+impl<V> LruCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, order: VecDeque::new(), entries: HashMap::new() }
+    }
+
+    /// Look up an entry and mark it as recently used
+    pub fn get(&mut self, key: &str) -> Option<&V> {
+        if self.entries.contains_key(key) {
+            self.touch(key);
+        }
+        self.entries.get(key)
+    }
+
+    pub fn insert(&mut self, key: String, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries.insert(key.clone(), value);
+        self.touch(&key);
+        self.evict();
+    }
+
+    fn touch(&mut self, key: &str) {
+        self.order.retain(|k| k != key);
+        self.order.push_back(key.to_string());
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            let Some(oldest) = self.order.pop_front() else { break };
+            self.entries.remove(&oldest);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<V> Default for LruCache<V> {
+    fn default() -> Self {
+        Self::new(CacheSettings::default().analysis_entries)
+    }
+}
+
+/// Files directly in `dir` with their sizes, largest first
+fn files_in(dir: &Path) -> Vec<(String, u64)> {
+    let mut files: Vec<(String, u64)> = fs::read_dir(dir).into_iter().flatten().flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            Some((entry.file_name().to_string_lossy().to_string(), metadata.len()))
+        })
+        .collect();
+    files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    files
+}
+
+/// Per-vault cache folders under `dir` with their total sizes
+fn vault_dirs(dir: &Path) -> Vec<(String, u64)> {
+    let mut vaults: Vec<(String, u64)> = fs::read_dir(dir).into_iter().flatten().flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|entry| {
+            let size = files_in(&entry.path()).iter().map(|(_, size)| size).sum();
+            (entry.file_name().to_string_lossy().to_string(), size)
+        })
+        .collect();
+    vaults.sort();
+    vaults
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+fn cache_report(paths: &CachePaths, settings: &CacheSettings) -> Report {
+    let files = files_in(paths.root());
+    let total: u64 = files.iter().map(|(_, size)| size).sum();
+    // A vault with its own `cache_dir` may sit outside the shared folder
+    let parent = cache_dir(settings);
+    let others: Vec<(String, u64)> = vault_dirs(&parent).into_iter().filter(|(name, _)| parent.join(name) != paths.root()).collect();
+
+    let mut text = format!("Cache folder: {}\n", paths.root().display());
+    if files.is_empty() {
+        text.push_str("  (empty)\n");
+    }
+    for (name, size) in &files {
+        text.push_str(&format!("  {:<28} {:>10}\n", name, format_size(*size)));
+    }
+    text.push_str(&format!("Total: {}\n", format_size(total)));
+    let mut detail = format!(
+        "\nIn memory: up to {} note analyses; writing suggestions for up to {} notes, kept {}s\n",
+        settings.analysis_entries, settings.suggestion_entries, settings.suggestion_max_age_seconds,
+    );
+    if !others.is_empty() {
+        detail.push_str(&format!("Other vaults in {}:\n", parent.display()));
+        for (name, size) in &others {
+            detail.push_str(&format!("  {:<28} {:>10}\n", name, format_size(*size)));
+        }
+    }
+
+    let files_json: Vec<serde_json::Value> = files.iter()
+        .map(|(name, size)| serde_json::json!({ "name": name, "bytes": size }))
+        .collect();
+    let others_json: Vec<serde_json::Value> = others.iter()
+        .map(|(name, size)| serde_json::json!({ "vault": name, "bytes": size }))
+        .collect();
+    Report::new()
+        .text(DetailLevel::Summary, text)
+        .text(DetailLevel::Normal, detail)
+        .field(DetailLevel::Summary, "dir", paths.root().display().to_string())
+        .field(DetailLevel::Summary, "total_bytes", total)
+        .field(DetailLevel::Summary, "files", files_json)
+        .field(DetailLevel::Normal, "analysis_entries", settings.analysis_entries)
+        .field(DetailLevel::Normal, "suggestion_entries", settings.suggestion_entries)
+        .field(DetailLevel::Normal, "suggestion_max_age_seconds", settings.suggestion_max_age_seconds)
+        .field(DetailLevel::Normal, "other_vaults", others_json)
+}
+
+/// Delete the cache files of this vault, or of every vault, returning the files and bytes freed
+fn clear_caches(paths: &CachePaths, settings: &CacheSettings, all: bool) -> Result<(usize, u64)> {
+    let mut dirs = vec![paths.root().to_path_buf()];
+    if all {
+        let parent = cache_dir(settings);
+        dirs.extend(vault_dirs(&parent).into_iter().map(|(name, _)| parent.join(name)).filter(|dir| dir != paths.root()));
+    }
+    let (mut count, mut bytes) = (0, 0);
+    for dir in dirs.iter().filter(|dir| dir.exists()) {
+        let files = files_in(dir);
+        fs::remove_dir_all(dir).with_context(|| format!("Failed to delete {}", dir.display()))?;
+        count += files.len();
+        bytes += files.iter().map(|(_, size)| size).sum::<u64>();
+    }
+    Ok((count, bytes))
+}
+
+/// Frontmatter keys written by note analysis
+const ANALYSIS_KEYS: [&str; 3] = ["ai_analysis", "ai_analysis_version", "ai_analysis_timestamp"];
+const PRUNE_BATCH: usize = 64;
+
+/// What `cache prune-excluded` removes: embeddings of notes the policy keeps from
+/// embedding, and the stored analysis of notes it keeps from analysis
+#[derive(Debug, Default, PartialEq)]
+pub struct PrunePlan {
+    pub embeddings: Vec<String>,
+    /// Path and the note's content with its analysis keys removed
+    pub analyses: Vec<(String, String)>,
+}
+
+/// Read every note in the vault and collect what `policy` no longer allows. Embedded paths
+/// that are gone from the vault are pruned when they match an `exclude` pattern.
+pub async fn plan_prune<S: VaultStore + ?Sized>(store: &S, policy: &OptOutPolicy, embedded: &[String]) -> PrunePlan {
+    let paths: Vec<String> = walk(store, &[String::new()]).await.into_iter().map(|(path, _)| path).collect();
+    let mut plan = PrunePlan::default();
+    let mut seen = std::collections::HashSet::new();
+    for batch in paths.chunks(PRUNE_BATCH) {
+        for (path, content) in store.fetch_many(batch, DEFAULT_READ_CONCURRENCY).await {
+            let content = match content {
+                Ok(content) => content,
+                Err(e) => {
+                    log::warn!("skipping {}: {:#}", path, e);
+                    continue;
+                }
+            };
+            seen.insert(path.clone());
+            if embedded.contains(&path) && !policy.allows(&path, &content, Feature::Embedding) {
+                plan.embeddings.push(path.clone());
+            }
+            if !policy.allows(&path, &content, Feature::Analysis) {
+                let stripped = ANALYSIS_KEYS.iter().fold(content.clone(), |note, key| set_frontmatter_entry(&note, key, None));
+                if stripped != content {
+                    plan.analyses.push((path, stripped));
+                }
+            }
+        }
+    }
+    plan.embeddings.extend(embedded.iter().filter(|path| !seen.contains(*path) && policy.is_excluded(path)).cloned());
+    plan
+}
+
+pub async fn handle_cache_command(args: CacheArgs, view: ReportView, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let paths = CachePaths::for_config(&config);
+    match args.action {
+        CacheAction::Info => {
+            let report = cache_report(&paths, &config.cache);
+            crate::script::publish_output(report.to_json(view.level));
+            report.print(view)?;
+        }
+        CacheAction::Clear { all, yes } => {
+            let scope = if all { format!("every vault's cache files in {}", cache_dir(&config.cache).display()) } else { format!("the cache files in {}", paths.root().display()) };
+            let operation = Operation::new(OperationClass::Delete, format!("Delete {}; embeddings must be indexed again", scope), 1);
+            if !confirm(&config.confirmations, &operation, yes)? {
+                println!("Nothing deleted.");
+                return Ok(());
+            }
+            let (count, bytes) = clear_caches(&paths, &config.cache, all)?;
+            crate::script::publish_output(serde_json::json!({ "files": count, "bytes": bytes }));
+            println!("🗑️ Deleted {} cache file(s), {}", count, format_size(bytes));
+        }
+        CacheAction::PruneExcluded { dry_run, yes } => {
+            let mut indexer = ObsidianAdapter::new(Some(config.obsidian.base_url.clone()), config.obsidian.api_key.clone());
+            indexer.set_cache_settings(&config.cache);
+            indexer.set_embedding_storage_config(config.embeddings.clone());
+            indexer.load_vector_database()?;
+            let embedded: Vec<String> = indexer.get_indexed_documents().into_iter().map(|doc| doc.path.clone()).collect();
+            let plan = plan_prune(adapter, &config.opt_outs, &embedded).await;
+            if plan.embeddings.is_empty() && plan.analyses.is_empty() {
+                crate::script::publish_output(serde_json::json!({ "embeddings": 0, "analyses": 0, "dry_run": dry_run }));
+                println!("Nothing stored for excluded notes.");
+                return Ok(());
+            }
+            for path in &plan.embeddings {
+                println!("  embedding: {}", path);
+            }
+            for (path, _) in &plan.analyses {
+                println!("  analysis:  {}", path);
+            }
+            if dry_run {
+                crate::script::publish_output(serde_json::json!({ "embeddings": plan.embeddings.len(), "analyses": plan.analyses.len(), "dry_run": true }));
+                println!("Would remove {} embedding(s) and the analysis of {} note(s) (dry run)", plan.embeddings.len(), plan.analyses.len());
+                return Ok(());
+            }
+            let message = format!("Remove {} embedding(s) and strip the analysis from {} note(s)", plan.embeddings.len(), plan.analyses.len());
+            let operation = Operation::new(OperationClass::Delete, message, plan.embeddings.len() + plan.analyses.len());
+            if !confirm(&config.confirmations, &operation, yes)? {
+                println!("Nothing removed.");
+                return Ok(());
+            }
+            let removed = indexer.prune_embeddings(&plan.embeddings)?;
+            let mut stripped = 0;
+            for (path, content) in &plan.analyses {
+                match adapter.write_file(path, content).await {
+                    Ok(()) => stripped += 1,
+                    Err(e) => eprintln!("⚠️  Failed to update {}: {:#}", path, e),
+                }
+            }
+            crate::script::publish_output(serde_json::json!({ "embeddings": removed, "analyses": stripped, "dry_run": false }));
+            println!("🗑️ Removed {} embedding(s) and the analysis of {} note(s)", removed, stripped);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arrowhead-cache-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_plan_prune_covers_excluded_and_ai_off_notes() {
+        let analyzed = "---\nai_analysis:\n  themes: [a]\nai_analysis_version: 3\nai: false\n---\nBody";
+        let vault = crate::vault_transaction::testing::FakeVault::new(&[
+            ("Private/diary.md", "Dear diary"),
+            ("Notes/off.md", analyzed),
+            ("Notes/kept.md", "---\nai_analysis:\n  themes: [b]\n---\nKept"),
+        ]);
+        let policy = OptOutPolicy { exclude: vec!["Private/**".to_string()], ..OptOutPolicy::default() };
+        let embedded: Vec<String> = ["Private/diary.md", "Notes/off.md", "Notes/kept.md", "Private/gone.md", "Old/gone.md"]
+            .iter().map(|p| p.to_string()).collect();
+
+        let mut plan = plan_prune(&vault, &policy, &embedded).await;
+        plan.embeddings.sort();
+        assert_eq!(plan.embeddings, vec!["Notes/off.md", "Private/diary.md", "Private/gone.md"]);
+        assert_eq!(plan.analyses, vec![("Notes/off.md".to_string(), "---\nai: false\n---\nBody".to_string())]);
+    }
+
+    #[test]
+    fn test_vault_id_is_readable_and_distinguishes_vaults() {
+        let id = vault_id("https://127.0.0.1:27124");
+        assert!(id.starts_with("127-0-0-1-27124-"), "{}", id);
+        assert_eq!(id, vault_id("https://127.0.0.1:27124/"));
+        assert_ne!(id, vault_id("https://127.0.0.1:27125"));
+        assert_ne!(vault_id("http://notes.local/a"), vault_id("http://notes.local/b"));
+    }
+
+    #[test]
+    fn test_migrate_legacy_moves_files_but_keeps_existing_targets() {
+        let cwd = temp_dir();
+        let settings = CacheSettings { dir: Some(cwd.join("cache").to_string_lossy().to_string()), ..CacheSettings::default() };
+        let paths = CachePaths::new(&settings, "https://127.0.0.1:27124");
+        fs::write(cwd.join(LEGACY_EMBEDDINGS), b"old vectors").unwrap();
+        fs::write(cwd.join(LEGACY_INDEX_SNAPSHOT), b"{}").unwrap();
+        fs::write(cwd.join(LEGACY_TEMPLATES), b"old templates").unwrap();
+        fs::write(cwd.join(LEGACY_CHANGELOG_STATE), b"{}").unwrap();
+        fs::create_dir_all(paths.root()).unwrap();
+        fs::write(paths.templates(), b"new templates").unwrap();
+
+        let moved = paths.migrate_legacy(&cwd).unwrap();
+        assert_eq!(moved, vec![paths.embeddings(), paths.index_snapshot(), paths.changelog_state()]);
+        assert_eq!(fs::read(paths.embeddings()).unwrap(), b"old vectors");
+        assert!(!cwd.join(LEGACY_EMBEDDINGS).exists());
+        assert_eq!(fs::read(paths.templates()).unwrap(), b"new templates");
+        assert!(cwd.join(LEGACY_TEMPLATES).exists());
+        fs::remove_dir_all(&cwd).ok();
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_without_leaving_temp_files() {
+        let dir = temp_dir();
+        let path = dir.join("nested").join("embeddings.bin");
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert_eq!(files_in(path.parent().unwrap()), vec![("embeddings.bin".to_string(), 6)]);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_lru_cache_drops_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        assert_eq!(cache.get("a"), Some(&1));
+        cache.insert("c".to_string(), 3);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.len(), 2);
+        cache.set_capacity(1);
+        assert_eq!(cache.get("c"), Some(&3));
+        assert_eq!(cache.get("a"), None);
+    }
+
+    #[test]
+    fn test_clear_removes_this_vault_unless_all() {
+        let dir = temp_dir();
+        let settings = CacheSettings { dir: Some(dir.to_string_lossy().to_string()), ..CacheSettings::default() };
+        let paths = CachePaths::new(&settings, "https://127.0.0.1:27124");
+        let other = CachePaths::new(&settings, "https://127.0.0.1:27125");
+        write_atomic(&paths.embeddings(), &[0; 2048]).unwrap();
+        write_atomic(&other.templates(), b"templates").unwrap();
+
+        assert_eq!(clear_caches(&paths, &settings, false).unwrap(), (1, 2048));
+        assert!(!paths.root().exists());
+        assert!(other.templates().exists());
+        assert_eq!(clear_caches(&paths, &settings, true).unwrap(), (1, 9));
+        assert!(vault_dirs(&dir).is_empty());
+        assert_eq!(format_size(2048), "2.0 KB");
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,664 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
+use std::collections::HashMap;
+
+use crate::availability::{
+    availability_sources, default_availability_cache_path, directory_sources, load_external_availability,
+    query_directory_availability, AvailabilityCache, CalDavFreeBusyClient, HttpFeedFetcher,
+};
+use crate::calendar_adapter::{
+    CalendarAdapter, CalendarConfig, CalendarEvent, CalendarList, CalendarProvider, SchedulingConstraints, WorkingHours,
+};
+use crate::cli::{CalendarAction, CalendarArgs};
+use crate::command_suggest::edit_distance;
+use crate::config::{CalendarSettings, Config, EventTemplate};
+use crate::dates;
+use crate::deadline_store::DeadlineStore;
+use crate::network::{client_builder, http_client, Destination, NetworkSettings};
+use crate::timezone;
+
+/// Explicit values that take precedence over a template's defaults
+#[derive(Debug, Clone, Default)]
+pub struct TemplateOverrides {
+    pub title: Option<String>,
+    pub duration_minutes: Option<u32>,
+    pub attendees: Vec<String>,
+    pub location: Option<String>,
+}
+
+/// A parsed `--at` value: either an exact start time or just a day to search for a free slot
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventTime {
+    Exact(DateTime<Utc>),
+    Day(NaiveDate),
+}
+
+pub async fn handle_calendar_command(args: CalendarArgs) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let settings = &config.calendar;
+
+    match args.action {
+        CalendarAction::Templates => {
+            if settings.templates.is_empty() {
+                println!("No event templates configured.");
+                println!("Add one to your config file, e.g. [calendar.templates.one_on_one]");
+                return Ok(());
+            }
+
+            let mut names: Vec<&String> = settings.templates.keys().collect();
+            names.sort();
+            for name in names {
+                let template = &settings.templates[name];
+                println!("• {} — \"{}\" ({} min)", name, template.title, template.duration_minutes);
+                if !template.attendees.is_empty() {
+                    println!("    Attendees: {}", template.attendees.join(", "));
+                }
+                if let Some(ref location) = template.location {
+                    println!("    Location: {}", location);
+                }
+            }
+        }
+        CalendarAction::List { select } => {
+            let adapter = create_calendar_adapter(settings, &config.network)?;
+            let mut calendars = adapter.get_calendar_list().await?;
+            if calendars.is_empty() {
+                println!("No event calendars found on {}", settings.server_url);
+                return Ok(());
+            }
+
+            if let Some(query) = select {
+                let chosen = find_calendar(&calendars, &query)?.id.clone();
+                Config::set_key("calendar.calendar_id", &chosen)?;
+                for calendar in &mut calendars {
+                    calendar.primary = calendar.id == chosen;
+                }
+                println!("✅ Default calendar set to {}", chosen);
+            }
+
+            for calendar in &calendars {
+                println!("{} {}{}", if calendar.primary { "★" } else { "•" }, calendar.name,
+                    if calendar.access_role == "reader" { " (read-only)" } else { "" });
+                println!("    {}", calendar.id);
+                if let Some(ref description) = calendar.description {
+                    println!("    {}", description);
+                }
+            }
+        }
+        CalendarAction::Doctor => {
+            println!("CalDAV server: {}", settings.server_url);
+            let adapter = create_calendar_adapter(settings, &config.network)?;
+            let diagnosis = adapter.test_connection().await;
+            println!("{} {}", if diagnosis.is_connected() { "✅" } else { "❌" }, diagnosis.summary());
+
+            let mut calendars = None;
+            if diagnosis.is_connected() {
+                match adapter.get_calendar_list().await {
+                    Ok(found) => {
+                        println!("✅ {} event calendar(s): {}", found.len(), found.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", "));
+                        calendars = Some(found);
+                    }
+                    Err(e) => println!("⚠️  Connected, but the calendars couldn't be listed: {:#}", e),
+                }
+            }
+            if let Some(hint) = diagnosis.hint() {
+                println!("   {}", hint);
+            }
+            crate::script::publish_output(serde_json::json!({
+                "server_url": settings.server_url,
+                "diagnosis": diagnosis,
+                "calendars": calendars,
+            }));
+        }
+        CalendarAction::Quick { template, vars, at, title, duration, attendees, location, force } => {
+            let (template_name, event_template) = find_template(&settings.templates, &template)
+                .ok_or_else(|| anyhow::anyhow!(
+                    "No event template matches '{}'. Available templates: {}",
+                    template,
+                    available_template_names(&settings.templates)
+                ))?;
+
+            let vars = parse_template_vars(&vars)?;
+            let overrides = TemplateOverrides {
+                title,
+                duration_minutes: duration,
+                attendees,
+                location,
+            };
+            let duration_minutes = overrides.duration_minutes.unwrap_or(event_template.duration_minutes).max(1);
+
+            let now = Utc::now();
+            let attendees = build_event_from_template(template_name, event_template, &vars, &overrides, now, &settings.calendar_id)?.attendees;
+            let adapter = with_contact_availability(create_calendar_adapter(settings, &config.network)?, settings, &config.network, &attendees, now).await;
+            let constraints = scheduling_constraints(settings);
+
+            let zone = timezone::resolve(&settings.time_zone)?;
+            let start_time = resolve_event_start(&adapter, at.as_deref(), duration_minutes, &constraints, &zone, now).await?;
+
+            let event = build_event_from_template(template_name, event_template, &vars, &overrides, start_time, &settings.calendar_id)?;
+
+            let conflicts = adapter.detect_conflicts(&settings.calendar_id, &event).await?;
+            if !conflicts.is_empty() && !force {
+                println!("⚠️  '{}' conflicts with {} existing event(s):", event.title, conflicts.len());
+                for conflict in &conflicts {
+                    println!("    {} ({})",
+                        conflict.conflicting_event.title,
+                        format_local_span(conflict.conflicting_event.start_time, conflict.conflicting_event.end_time, &zone));
+                }
+
+                let alternatives = adapter.resolve_conflicts(&settings.calendar_id, &event, &constraints).await?;
+                if !alternatives.is_empty() {
+                    println!("Free alternatives:");
+                    for alternative in alternatives.iter().take(3) {
+                        println!("    {}", format_local(alternative.time_slot.start_time, &zone));
+                    }
+                }
+                bail!("Event not created because of conflicts (use --force to create it anyway)");
+            }
+
+            let created = adapter.create_event(&settings.calendar_id, &event).await
+                .context(format!("Failed to create event from template '{}'", template_name))?;
+
+            println!("✅ Created '{}' on {}", created.title, format_local_span(created.start_time, created.end_time, &zone));
+            if !created.attendees.is_empty() {
+                println!("    Attendees: {}", created.attendees.join(", "));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The calendar with this id, or the only one with this name (ignoring case)
+pub fn find_calendar<'a>(calendars: &'a [CalendarList], query: &str) -> Result<&'a CalendarList> {
+    if let Some(calendar) = calendars.iter().find(|c| c.id == query || c.id.trim_end_matches('/') == query.trim_end_matches('/')) {
+        return Ok(calendar);
+    }
+    let named: Vec<&CalendarList> = calendars.iter().filter(|c| c.name.eq_ignore_ascii_case(query.trim())).collect();
+    match named.as_slice() {
+        [calendar] => Ok(calendar),
+        [] => bail!("No calendar matches '{}'. Available calendars: {}", query,
+            calendars.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ")),
+        _ => bail!("{} calendars are named '{}'; select one by id", named.len(), query),
+    }
+}
+
+/// Find a template by name, tolerating case, separators, filler words and small typos
+pub fn find_template<'a>(templates: &'a HashMap<String, EventTemplate>, query: &str) -> Option<(&'a str, &'a EventTemplate)> {
+    let query = normalize_template_name(query);
+    if query.is_empty() {
+        return None;
+    }
+
+    // Exact match after normalization
+    if let Some((name, template)) = templates.iter().filter(|(name, _)| normalize_template_name(name) == query).min_by_key(|(name, _)| *name) {
+        return Some((name.as_str(), template));
+    }
+
+    // One name contains the other, or the query names the template title
+    let mut contained: Vec<(&String, &EventTemplate)> = templates.iter()
+        .filter(|(name, template)| {
+            let name = normalize_template_name(name);
+            let title = normalize_template_name(&template.title);
+            name.contains(&query) || query.contains(&name) || title.starts_with(&query)
+        })
+        .collect();
+    // Shortest name first, then by name, so the pick doesn't depend on map order
+    contained.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    if let Some((name, template)) = contained.first() {
+        return Some((name.as_str(), template));
+    }
+
+    // Closest edit distance, if it is close enough to be a typo; ties go to the first name
+    templates.iter()
+        .map(|(name, template)| (name, template, name_similarity(&normalize_template_name(name), &query)))
+        .filter(|(_, _, similarity)| *similarity >= 0.6)
+        .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal).then_with(|| b.0.cmp(a.0)))
+        .map(|(name, template, _)| (name.as_str(), template))
+}
+
+fn normalize_template_name(name: &str) -> String {
+    const FILLER_WORDS: &[&str] = &["my", "the", "a", "an", "usual", "regular", "template", "event", "meeting"];
+
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !FILLER_WORDS.contains(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn name_similarity(a: &str, b: &str) -> f32 {
+    let max_len = a.chars().count().max(b.chars().count());
+    if max_len == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(a, b) as f32 / max_len as f32
+}
+
+fn available_template_names(templates: &HashMap<String, EventTemplate>) -> String {
+    if templates.is_empty() {
+        return "(none configured)".to_string();
+    }
+    let mut names: Vec<&str> = templates.keys().map(|name| name.as_str()).collect();
+    names.sort();
+    names.join(", ")
+}
+
+/// Parse `key=value` pairs from repeated `--var` arguments
+pub fn parse_template_vars(vars: &[String]) -> Result<HashMap<String, String>> {
+    let mut parsed = HashMap::new();
+    for var in vars {
+        let (key, value) = var.split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid template variable '{}'. Expected key=value", var))?;
+        let key = key.trim();
+        if key.is_empty() {
+            bail!("Invalid template variable '{}'. Variable name cannot be empty", var);
+        }
+        parsed.insert(key.to_string(), value.trim().to_string());
+    }
+    Ok(parsed)
+}
+
+/// Replace `{name}`-style variables, collecting any that have no value
+fn substitute_variables(text: &str, vars: &HashMap<String, String>, missing: &mut Vec<String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        let after_open = &rest[open + 1..];
+        match after_open.find('}') {
+            Some(close) => {
+                let key = after_open[..close].trim();
+                match vars.get(key) {
+                    Some(value) => result.push_str(value),
+                    None => {
+                        if !missing.iter().any(|m| m == key) {
+                            missing.push(key.to_string());
+                        }
+                        result.push_str(&rest[open..open + close + 2]);
+                    }
+                }
+                rest = &after_open[close + 1..];
+            }
+            None => {
+                result.push_str(&rest[open..]);
+                rest = "";
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Build a calendar event from a template, its variables and any explicit overrides
+pub fn build_event_from_template(
+    template_name: &str,
+    template: &EventTemplate,
+    vars: &HashMap<String, String>,
+    overrides: &TemplateOverrides,
+    start_time: DateTime<Utc>,
+    calendar_id: &str,
+) -> Result<CalendarEvent> {
+    let mut vars = vars.clone();
+    vars.entry("date".to_string()).or_insert_with(|| start_time.format("%Y-%m-%d").to_string());
+
+    let mut missing = Vec::new();
+    let title = substitute_variables(overrides.title.as_deref().unwrap_or(&template.title), &vars, &mut missing);
+    let description = template.description.as_deref()
+        .map(|description| substitute_variables(description, &vars, &mut missing));
+    let location = overrides.location.as_deref().or(template.location.as_deref())
+        .map(|location| substitute_variables(location, &vars, &mut missing));
+    let attendees: Vec<String> = if overrides.attendees.is_empty() { &template.attendees } else { &overrides.attendees }
+        .iter()
+        .map(|attendee| substitute_variables(attendee, &vars, &mut missing))
+        .collect();
+
+    if !missing.is_empty() {
+        bail!(
+            "Template '{}' needs values for: {} (use --var {}=...)",
+            template_name,
+            missing.join(", "),
+            missing[0]
+        );
+    }
+
+    let title = if title.trim().is_empty() { template_name.replace('_', " ") } else { title };
+    let duration_minutes = overrides.duration_minutes.unwrap_or(template.duration_minutes).max(1);
+
+    Ok(CalendarEvent {
+        id: String::new(),
+        title,
+        description,
+        start_time,
+        end_time: start_time + Duration::minutes(duration_minutes as i64),
+        location,
+        attendees,
+        all_day: false,
+        recurring: false,
+        calendar_id: calendar_id.to_string(),
+    })
+}
+
+/// When an event asked for `--at` starts: an exact time as given, or else the first free
+/// `duration_minutes` slot within working hours on that day (from now without `--at`)
+pub async fn resolve_event_start(
+    adapter: &CalendarAdapter,
+    at: Option<&str>,
+    duration_minutes: u32,
+    constraints: &SchedulingConstraints,
+    zone: &timezone::TimeZone,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>> {
+    let from = match at.map(|input| parse_event_time(input, now, zone)).transpose()? {
+        Some(EventTime::Exact(start)) => return Ok(start),
+        Some(EventTime::Day(day)) => zone.from_local(day.and_hms_opt(0, 0, 0).unwrap()).max(now),
+        None => now,
+    };
+    let slot = adapter.find_next_free_slot(from, duration_minutes, constraints).await?
+        .ok_or_else(|| anyhow::anyhow!(
+            "No free {}-minute slot found within working hours in the next {} days",
+            duration_minutes,
+            constraints.maximum_lookahead_days
+        ))?;
+    Ok(slot.start_time)
+}
+
+/// `at` on the wall clock of `zone`, named: "2024-06-03 09:00 (Europe/Berlin)"
+pub fn format_local(at: DateTime<Utc>, zone: &timezone::TimeZone) -> String {
+    format!("{} ({})", zone.to_local(at).format("%Y-%m-%d %H:%M"), zone.name())
+}
+
+/// A start and end on the wall clock of `zone`: "2024-06-03 09:00–09:30 (Europe/Berlin)",
+/// with the end's date only when it falls on another day
+pub fn format_local_span(start: DateTime<Utc>, end: DateTime<Utc>, zone: &timezone::TimeZone) -> String {
+    let (start, end) = (zone.to_local(start), zone.to_local(end));
+    let end_format = if end.date() == start.date() { "%H:%M" } else { "%Y-%m-%d %H:%M" };
+    format!("{}–{} ({})", start.format("%Y-%m-%d %H:%M"), end.format(end_format), zone.name())
+}
+
+/// Parse a start time such as "tomorrow 2pm", "next friday 10:30", "2024-05-01 09:00" or
+/// RFC 3339, reading wall-clock times in `zone`
+pub fn parse_event_time(input: &str, now: DateTime<Utc>, zone: &timezone::TimeZone) -> Result<EventTime> {
+    let input = input.trim();
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Ok(EventTime::Exact(dt.with_timezone(&Utc)));
+    }
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(input, format) {
+            return Ok(EventTime::Exact(zone.from_local(dt)));
+        }
+    }
+
+    let (rest, time) = dates::split_time(input);
+    let today = zone.to_local(now).date();
+    let date = if rest.is_empty() {
+        None
+    } else {
+        Some(dates::parse_date(&rest, today).map_err(|e| anyhow::anyhow!("Could not understand time '{}': {}", input, e))?)
+    };
+
+    match (date, time) {
+        (Some(day), Some(t)) => Ok(EventTime::Exact(zone.from_local(day.and_time(t)))),
+        (Some(day), None) => Ok(EventTime::Day(day)),
+        (None, Some(t)) => {
+            // A bare time means the next occurrence of that time
+            let at = zone.from_local(today.and_time(t));
+            Ok(EventTime::Exact(if at > now { at } else { zone.from_local((today + Duration::days(1)).and_time(t)) }))
+        }
+        (None, None) => bail!("Could not understand time '{}'", input),
+    }
+}
+
+/// Build scheduling constraints from the configured working hours
+pub fn scheduling_constraints(settings: &CalendarSettings) -> SchedulingConstraints {
+    SchedulingConstraints {
+        working_hours: settings.work_days.iter()
+            .map(|day| WorkingHours {
+                day_of_week: *day,
+                start_hour: settings.work_start_hour,
+                start_minute: 0,
+                end_hour: settings.work_end_hour,
+                end_minute: 0,
+            })
+            .collect(),
+        time_zone: settings.time_zone.clone(),
+        minimum_notice_hours: 0,
+        maximum_lookahead_days: 14,
+        preferred_meeting_length: 30,
+        break_duration_minutes: 0,
+        max_consecutive_meetings: 4,
+        avoid_lunch_time: false,
+        lunch_start_hour: 12,
+        lunch_end_hour: 13,
+    }
+}
+
+/// 9:00–17:00 on the default work days, in UTC, for tests that plan around working hours
+#[cfg(test)]
+pub(crate) fn test_constraints() -> SchedulingConstraints {
+    scheduling_constraints(&CalendarSettings {
+        work_start_hour: 9,
+        work_end_hour: 17,
+        time_zone: "UTC".to_string(),
+        ..Default::default()
+    })
+}
+
+/// Working hours available from `from` through the next `days` days under the given
+/// constraints, counted on the wall clock of their time zone
+pub fn available_working_hours(constraints: &SchedulingConstraints, from: DateTime<Utc>, days: i64) -> Result<f32> {
+    let zone = timezone::resolve(&constraints.time_zone)?;
+    let end = from + Duration::days(days);
+    let mut hours = 0.0;
+    let mut day = zone.to_local(from).date();
+    while day <= zone.to_local(end).date() {
+        for window in constraints.working_hours.iter().filter(|w| w.day_of_week == day.weekday().num_days_from_sunday()) {
+            let (Some(start), Some(close)) = (zone.at(day, window.start_hour, window.start_minute), zone.at(day, window.end_hour, window.end_minute)) else { continue };
+            let start = start.max(from);
+            let close = close.min(end);
+            if close > start {
+                hours += (close - start).num_minutes() as f32 / 60.0;
+            }
+        }
+        day = match day.succ_opt() {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(hours)
+}
+
+/// Attach the busy time of any attendees that are configured contacts (published feeds) or
+/// in the directory (CalDAV free-busy queries covering the scheduling lookahead)
+pub(crate) async fn with_contact_availability(
+    adapter: CalendarAdapter,
+    settings: &CalendarSettings,
+    network: &NetworkSettings,
+    attendees: &[String],
+    now: DateTime<Utc>,
+) -> CalendarAdapter {
+    let sources = availability_sources(settings, attendees);
+    let directory = directory_sources(settings, attendees);
+    if sources.is_empty() && directory.is_empty() {
+        return adapter;
+    }
+
+    let cache_path = default_availability_cache_path();
+    let mut cache = AvailabilityCache::load(&cache_path).unwrap_or_default();
+    let client = match http_client(network, Destination::Calendar) {
+        Ok(client) => client,
+        Err(e) => {
+            println!("⚠️  Skipping coworker availability: {}", e);
+            return adapter;
+        }
+    };
+    let fetcher = HttpFeedFetcher::with_client(client.clone());
+    let mut availability = load_external_availability(&sources, &mut cache, &fetcher, settings, now).await;
+    let until = now + Duration::days(scheduling_constraints(settings).maximum_lookahead_days as i64 + 1);
+    availability.merge(query_directory_availability(&directory, &CalDavFreeBusyClient::with_client(client), settings, now, until).await);
+    for warning in &availability.warnings {
+        println!("⚠️  {}", warning);
+    }
+    if let Err(e) = cache.save(&cache_path) {
+        log::warn!("could not save availability cache: {}", e);
+    }
+    adapter.with_external_availability(availability.busy)
+}
+
+pub(crate) fn create_calendar_adapter(settings: &CalendarSettings, network: &NetworkSettings) -> Result<CalendarAdapter> {
+    let username = settings.username.clone()
+        .ok_or_else(|| anyhow::anyhow!("No CalDAV username configured. Set CALDAV_USERNAME or calendar.username"))?;
+    let password = settings.password.clone()
+        .ok_or_else(|| anyhow::anyhow!("No CalDAV password configured. Set CALDAV_PASSWORD or calendar.password"))?;
+    let http = client_builder(network, Destination::Calendar)?
+        .connect_timeout(std::time::Duration::from_secs(settings.connect_timeout_secs))
+        .timeout(std::time::Duration::from_secs(settings.request_timeout_secs))
+        .build()
+        .context("Failed to build the calendar HTTP client")?;
+    let zone = timezone::resolve(&settings.time_zone)?;
+
+    CalendarAdapter::new(CalendarConfig {
+        provider: CalendarProvider::Apple,
+        server_url: settings.server_url.clone(),
+        username,
+        password,
+        calendar_name: None,
+    }).map(|adapter| adapter
+        .with_http_client(http)
+        .with_retry(settings.retry.clone())
+        .with_default_calendar(&settings.calendar_id)
+        .with_time_zone(zone)
+        .with_deadline_store(DeadlineStore::from_settings(settings)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn one_on_one() -> EventTemplate {
+        EventTemplate {
+            title: "1:1 with {name}".to_string(),
+            duration_minutes: 30,
+            attendees: vec!["{name}@example.com".to_string()],
+            location: Some("Room 4".to_string()),
+            description: Some("Weekly sync with {name} on {date}".to_string()),
+        }
+    }
+
+    fn templates() -> HashMap<String, EventTemplate> {
+        let mut templates = HashMap::new();
+        templates.insert("one_on_one".to_string(), one_on_one());
+        templates.insert("focus_block".to_string(), EventTemplate {
+            title: "Focus".to_string(),
+            duration_minutes: 120,
+            ..Default::default()
+        });
+        templates.insert("gym".to_string(), EventTemplate {
+            title: "Gym".to_string(),
+            duration_minutes: 60,
+            ..Default::default()
+        });
+        templates
+    }
+
+    #[test]
+    fn test_find_template_fuzzy() {
+        let templates = templates();
+        assert_eq!(find_template(&templates, "one_on_one").unwrap().0, "one_on_one");
+        assert_eq!(find_template(&templates, "my usual one on one").unwrap().0, "one_on_one");
+        assert_eq!(find_template(&templates, "Focus-Block").unwrap().0, "focus_block");
+        assert_eq!(find_template(&templates, "focus").unwrap().0, "focus_block");
+        // A swapped pair of letters is one edit
+        assert_eq!(find_template(&templates, "gmy").unwrap().0, "gym");
+        assert_eq!(find_template(&templates, "focus blok").unwrap().0, "focus_block");
+        assert_eq!(find_template(&templates, "1:1").unwrap().0, "one_on_one");
+        assert!(find_template(&templates, "dentist").is_none());
+    }
+
+    #[test]
+    fn test_find_template_breaks_ties_by_name() {
+        // Each map iterates in its own order; names of one length all containing "team"
+        for _ in 0..20 {
+            let mut templates = templates();
+            for name in ["team_sync", "team_demo", "team_plan"] {
+                templates.insert(name.to_string(), EventTemplate { title: name.to_string(), ..Default::default() });
+            }
+            assert_eq!(find_template(&templates, "team").unwrap().0, "team_demo");
+        }
+    }
+
+    #[test]
+    fn test_build_event_resolves_variables() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 14, 0, 0).unwrap();
+        let vars = parse_template_vars(&["name=Alice".to_string()]).unwrap();
+        let event = build_event_from_template("one_on_one", &one_on_one(), &vars, &TemplateOverrides::default(), start, "default").unwrap();
+
+        assert_eq!(event.title, "1:1 with Alice");
+        assert_eq!(event.attendees, vec!["Alice@example.com".to_string()]);
+        assert_eq!(event.description.as_deref(), Some("Weekly sync with Alice on 2024-05-01"));
+        assert_eq!(event.end_time - event.start_time, Duration::minutes(30));
+    }
+
+    #[test]
+    fn test_build_event_reports_missing_variables() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 14, 0, 0).unwrap();
+        let err = build_event_from_template("one_on_one", &one_on_one(), &HashMap::new(), &TemplateOverrides::default(), start, "default")
+            .unwrap_err();
+        assert!(err.to_string().contains("name"));
+    }
+
+    #[test]
+    fn test_overrides_take_precedence() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 14, 0, 0).unwrap();
+        let vars = parse_template_vars(&["name=Bob".to_string()]).unwrap();
+        let overrides = TemplateOverrides {
+            duration_minutes: Some(45),
+            location: Some("Cafe".to_string()),
+            ..Default::default()
+        };
+        let event = build_event_from_template("one_on_one", &one_on_one(), &vars, &overrides, start, "default").unwrap();
+
+        assert_eq!(event.end_time - event.start_time, Duration::minutes(45));
+        assert_eq!(event.location.as_deref(), Some("Cafe"));
+        assert_eq!(event.title, "1:1 with Bob");
+    }
+
+    #[test]
+    fn test_parse_template_vars_rejects_malformed() {
+        assert!(parse_template_vars(&["name".to_string()]).is_err());
+        assert!(parse_template_vars(&["=Alice".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_event_time() {
+        // Wednesday
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap();
+        let utc = timezone::TimeZone::utc();
+
+        assert_eq!(parse_event_time("tomorrow 2pm", now, &utc).unwrap(),
+            EventTime::Exact(Utc.with_ymd_and_hms(2024, 5, 2, 14, 0, 0).unwrap()));
+        assert_eq!(parse_event_time("friday at 10:30", now, &utc).unwrap(),
+            EventTime::Exact(Utc.with_ymd_and_hms(2024, 5, 3, 10, 30, 0).unwrap()));
+        assert_eq!(parse_event_time("2024-06-01 09:00", now, &utc).unwrap(),
+            EventTime::Exact(Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap()));
+        assert_eq!(parse_event_time("9am", now, &utc).unwrap(),
+            EventTime::Exact(Utc.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap()));
+        assert_eq!(parse_event_time("tomorrow", now, &utc).unwrap(),
+            EventTime::Day(NaiveDate::from_ymd_opt(2024, 5, 2).unwrap()));
+        assert_eq!(parse_event_time("next tuesday 2 pm", now, &utc).unwrap(),
+            EventTime::Exact(Utc.with_ymd_and_hms(2024, 5, 7, 14, 0, 0).unwrap()));
+        assert!(parse_event_time("whenever", now, &utc).is_err());
+    }
+
+    #[test]
+    fn test_times_are_shown_in_the_configured_zone() {
+        let berlin = timezone::TimeZone::named("Europe/Berlin").unwrap();
+        let at = Utc.with_ymd_and_hms(2024, 6, 3, 7, 0, 0).unwrap();
+        assert_eq!(format_local(at, &berlin), "2024-06-03 09:00 (Europe/Berlin)");
+        assert_eq!(format_local_span(at, at + Duration::minutes(30), &berlin), "2024-06-03 09:00–09:30 (Europe/Berlin)");
+        assert_eq!(format_local_span(at, at + Duration::hours(16), &berlin), "2024-06-03 09:00–2024-06-04 01:00 (Europe/Berlin)");
+    }
+}
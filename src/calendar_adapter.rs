@@ -5,6 +5,18 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use base64::{Engine as _, engine::general_purpose};
 use crate::ai_conversation::AIConversationEngine;
+use crate::config::RetrySettings;
+use crate::error::ArrowheadError;
+use crate::llm_retry::backoff_delay;
+use crate::availability::{busy_during, escape_text, event_property, parse_calendar_events, parse_invitees, BusyInterval};
+use crate::deadline_store::{default_deadlines_path, DeadlineStore};
+use crate::scheduling::SchedulingSettings;
+use crate::timezone::{self, TimeZone};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The calendar id meaning "the configured default calendar"
+pub const DEFAULT_CALENDAR_ID: &str = "default";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CalendarEvent {
@@ -141,7 +153,7 @@ pub struct MeetingInvitation {
     pub response_deadline: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct InviteeInfo {
     pub email: String,
     pub name: Option<String>,
@@ -157,6 +169,51 @@ pub enum ResponseStatus {
     Tentative,
 }
 
+impl ResponseStatus {
+    /// The iCalendar PARTSTAT value for this response
+    pub fn partstat(&self) -> &'static str {
+        match self {
+            Self::Pending => "NEEDS-ACTION",
+            Self::Accepted => "ACCEPTED",
+            Self::Declined => "DECLINED",
+            Self::Tentative => "TENTATIVE",
+        }
+    }
+
+    /// A PARTSTAT value; anything but an answer (NEEDS-ACTION, DELEGATED, ...) is pending
+    pub fn from_partstat(partstat: &str) -> Self {
+        match partstat.trim().to_ascii_uppercase().as_str() {
+            "ACCEPTED" => Self::Accepted,
+            "DECLINED" => Self::Declined,
+            "TENTATIVE" => Self::Tentative,
+            _ => Self::Pending,
+        }
+    }
+}
+
+/// Invitees for the attendees of an event created by Arrowhead: all required, none answered
+fn pending_invitees(attendees: &[String]) -> Vec<InviteeInfo> {
+    attendees.iter()
+        .map(|email| InviteeInfo { email: email.clone(), name: None, required: true, response_status: ResponseStatus::Pending })
+        .collect()
+}
+
+/// The ATTENDEE line of an invitee. Unanswered invitees are asked to reply with RSVP=TRUE.
+fn attendee_line(invitee: &InviteeInfo) -> String {
+    let mut params = String::new();
+    if let Some(name) = &invitee.name {
+        params.push_str(&format!(";CN=\"{}\"", name.replace('"', "'")));
+    }
+    if !invitee.required {
+        params.push_str(";ROLE=OPT-PARTICIPANT");
+    }
+    params.push_str(&format!(";PARTSTAT={}", invitee.response_status.partstat()));
+    if invitee.response_status == ResponseStatus::Pending {
+        params.push_str(";RSVP=TRUE");
+    }
+    format!("ATTENDEE{}:MAILTO:{}\r\n", params, invitee.email)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SchedulingConstraints {
     pub working_hours: Vec<WorkingHours>,
@@ -205,9 +262,29 @@ pub struct InvitationStatus {
     pub declined: u32,
     pub tentative: u32,
     pub pending: u32,
+    /// Percentage of invitees who have answered
     pub response_rate: f32,
 }
 
+impl InvitationStatus {
+    pub fn from_invitees(meeting_id: &str, invitees: &[InviteeInfo]) -> Self {
+        let count = |status: ResponseStatus| invitees.iter().filter(|i| i.response_status == status).count() as u32;
+        let total_invites = invitees.len() as u32;
+        let pending = count(ResponseStatus::Pending);
+        let responses_received = total_invites - pending;
+        Self {
+            meeting_id: meeting_id.to_string(),
+            total_invites,
+            responses_received,
+            accepted: count(ResponseStatus::Accepted),
+            declined: count(ResponseStatus::Declined),
+            tentative: count(ResponseStatus::Tentative),
+            pending,
+            response_rate: if total_invites == 0 { 0.0 } else { responses_received as f32 * 100.0 / total_invites as f32 },
+        }
+    }
+}
+
 // Deadline Tracking and Time Blocking Data Structures
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -272,6 +349,8 @@ pub enum NotificationChannel {
     Push,
     Slack,
     SMS,
+    Desktop,
+    Vault,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -299,6 +378,45 @@ pub enum TimeBlockStatus {
     Rescheduled,
 }
 
+/// How a time block's calendar event differs from the stored block
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockChange {
+    Unchanged,
+    Moved,
+    Deleted,
+}
+
+/// A time block whose event was dragged to another slot in the calendar
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovedBlock {
+    pub block_id: String,
+    pub previous_start: DateTime<Utc>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+}
+
+/// What syncing a deadline's time blocks with the calendar changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeBlockSync {
+    pub deadline_id: String,
+    pub title: String,
+    pub moved: Vec<MovedBlock>,
+    /// Blocks whose event was deleted
+    pub cancelled: Vec<String>,
+    /// Blocks whose event could not be fetched and were left as they were
+    pub unchecked: usize,
+    /// Hours done plus the hours still blocked out
+    pub planned_hours: f32,
+    pub estimated_hours: f32,
+}
+
+impl TimeBlockSync {
+    /// Hours of work no time block covers any more
+    pub fn shortfall(&self) -> f32 {
+        (self.estimated_hours - self.planned_hours).max(0.0)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Interruption {
     pub timestamp: DateTime<Utc>,
@@ -401,7 +519,7 @@ pub struct RiskIndicator {
     pub deadline_impact: f32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum RiskType {
     TimeShortage,
     ScopeCreep,
@@ -769,16 +887,95 @@ pub enum StakeholderImpactLevel {
     Severe,
 }
 
+/// Timeouts of the client `CalendarAdapter::new` builds, and the `[calendar]` defaults
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// Retries of CalDAV reads: fewer and shorter waits than for LLM requests, since a command
+/// is blocked on them
+pub fn default_calendar_retry() -> RetrySettings {
+    RetrySettings { max_attempts: 3, initial_delay_ms: 500, max_delay_ms: 5000 }
+}
+
+/// What `test_connection` found when it asked the server root for its properties
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum ConnectionDiagnosis {
+    /// A WebDAV multistatus came back; `calendar_access` is whether the DAV header also
+    /// advertises CalDAV (not every server sends it on PROPFIND)
+    Connected { status: u16, calendar_access: bool },
+    /// The server's host name doesn't resolve
+    DnsFailure { host: String, detail: String },
+    /// Connected, or tried to, but no response came back in time
+    Timeout { detail: String },
+    /// Refused, reset, or a failed TLS handshake
+    Unreachable { detail: String },
+    AuthFailed { status: u16 },
+    /// Something answered, but not the way a CalDAV server would
+    NotCalDav { status: u16, detail: String },
+    ServerError { status: u16, detail: String },
+}
+
+impl ConnectionDiagnosis {
+    pub fn is_connected(&self) -> bool {
+        matches!(self, Self::Connected { .. })
+    }
+
+    /// One line on what happened
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Connected { status, .. } => format!("Connected ({} Multi-Status)", status),
+            Self::DnsFailure { host, .. } => format!("Can't resolve {}", host),
+            Self::Timeout { .. } => "The server didn't answer in time".to_string(),
+            Self::Unreachable { detail } => format!("Can't connect: {}", detail),
+            Self::AuthFailed { status } => format!("The server rejected the username or password ({})", status),
+            Self::NotCalDav { status, detail } => format!("Not a CalDAV server ({}): {}", status, detail),
+            Self::ServerError { status, detail } => format!("The server failed ({}): {}", status, detail),
+        }
+    }
+
+    /// What to do about it, in a sentence
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Self::Connected { calendar_access: false, .. } => Some("The server didn't advertise calendar-access; if `arrowhead calendar list` finds nothing, check server_url.".to_string()),
+            Self::Connected { .. } => None,
+            Self::DnsFailure { .. } => Some("Check the host in calendar.server_url, and your network or [network.calendar] proxy.".to_string()),
+            Self::Timeout { .. } => Some("Check your connection, or raise calendar.connect_timeout_secs / request_timeout_secs for a slow server.".to_string()),
+            Self::Unreachable { .. } => Some("Check the scheme and port in calendar.server_url, and [network.calendar] for a proxy or custom CA.".to_string()),
+            Self::AuthFailed { status } => ArrowheadError::CalendarAuthFailed { server: String::new(), status: *status }.hint(),
+            Self::NotCalDav { .. } => Some("calendar.server_url should be the CalDAV endpoint, e.g. https://caldav.icloud.com or https://caldav.fastmail.com/dav/calendars.".to_string()),
+            Self::ServerError { .. } => Some("The server is having trouble; try again in a few minutes.".to_string()),
+        }
+    }
+}
+
 pub struct CalendarAdapter {
     client: Client,
     config: CalendarConfig,
     auth_header: String,
-    ai_conversation: Option<AIConversationEngine>,
+    /// Behind a lock so agenda generation needs only `&self`
+    ai_conversation: Option<tokio::sync::Mutex<AIConversationEngine>>,
+    /// Busy time of attendees outside the CalDAV server, keyed by attendee
+    external_busy: HashMap<String, Vec<BusyInterval>>,
+    /// The calendar `DEFAULT_CALENDAR_ID` stands for; the server URL itself when unset
+    default_calendar: Option<String>,
+    /// The user's time zone; business hours and time blocks are on its wall clock
+    time_zone: Arc<TimeZone>,
+    /// Where deadlines, their time blocks and milestones are kept
+    deadline_store: DeadlineStore,
+    /// Energy curve, meeting limits and focus blocks that slots are weighed against
+    scheduling: SchedulingSettings,
+    /// How often reads that time out or get a 5xx are tried
+    retry: RetrySettings,
 }
 
 impl CalendarAdapter {
     pub fn new(config: CalendarConfig) -> Result<Self> {
-        let client = Client::new();
+        let client = Client::builder()
+            .connect_timeout(std::time::Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS))
+            .timeout(std::time::Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS))
+            .build()
+            .context("Failed to build the CalDAV HTTP client")?;
         
         // Create Basic Auth header for CalDAV
         let auth_string = format!("{}:{}", config.username, config.password);
@@ -789,102 +986,270 @@ impl CalendarAdapter {
             config,
             auth_header,
             ai_conversation: None,
+            external_busy: HashMap::new(),
+            default_calendar: None,
+            time_zone: Arc::new(TimeZone::utc()),
+            deadline_store: DeadlineStore::new(default_deadlines_path()),
+            scheduling: SchedulingSettings::default(),
+            retry: default_calendar_retry(),
         })
     }
     
     pub fn with_ai_conversation(mut self, ai_conversation: AIConversationEngine) -> Self {
-        self.ai_conversation = Some(ai_conversation);
+        self.ai_conversation = Some(tokio::sync::Mutex::new(ai_conversation));
+        self
+    }
+
+    /// Keep deadlines in `store` instead of the default file
+    pub fn with_deadline_store(mut self, store: DeadlineStore) -> Self {
+        self.deadline_store = store;
+        self
+    }
+
+    pub fn deadline_store(&self) -> &DeadlineStore {
+        &self.deadline_store
+    }
+
+    /// Treat these attendees' published busy time like events on their own calendars
+    pub fn with_external_availability(mut self, busy: HashMap<String, Vec<BusyInterval>>) -> Self {
+        self.external_busy = busy;
+        self
+    }
+
+    /// Send requests through `client`, e.g. one built for the `[network.calendar]` proxy
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
         self
     }
 
-    pub async fn test_connection(&self) -> Result<bool> {
+    /// Use this calendar, an id from `get_calendar_list`, wherever "default" is asked for
+    pub fn with_default_calendar(mut self, calendar_id: &str) -> Self {
+        self.default_calendar = (!calendar_id.is_empty() && calendar_id != DEFAULT_CALENDAR_ID).then(|| calendar_id.to_string());
+        self
+    }
+
+    /// Retry reads that time out or get a 5xx as `settings` says instead of the defaults
+    pub fn with_retry(mut self, settings: RetrySettings) -> Self {
+        self.retry = settings;
+        self
+    }
+
+    /// Weigh slots against these `[scheduling]` preferences instead of the defaults
+    pub fn with_scheduling(mut self, settings: SchedulingSettings) -> Self {
+        self.scheduling = settings;
+        self
+    }
+
+    /// Schedule on the wall clock of this zone instead of UTC
+    pub fn with_time_zone(mut self, zone: Arc<TimeZone>) -> Self {
+        self.time_zone = zone;
+        self
+    }
+
+    /// The collection URL of a calendar, with a trailing slash. Ids are hrefs from discovery:
+    /// absolute URLs, or paths on the configured server.
+    pub fn calendar_url(&self, calendar_id: &str) -> String {
+        let href = match calendar_id {
+            "" | DEFAULT_CALENDAR_ID => self.default_calendar.as_deref().unwrap_or(""),
+            id => id,
+        };
+        format!("{}/", resolve_href(&self.config.server_url, href).trim_end_matches('/'))
+    }
+
+    fn event_url(&self, calendar_id: &str, event_id: &str) -> String {
+        format!("{}{}.ics", self.calendar_url(calendar_id), event_id)
+    }
+
+    /// Send one PROPFIND to the server root, without retrying, and say what went wrong if
+    /// it didn't come back as a WebDAV multistatus
+    pub async fn test_connection(&self) -> ConnectionDiagnosis {
         let url = format!("{}/", self.config.server_url.trim_end_matches('/'));
-        
-        let response = self
+        let result = self
             .client
             .request(reqwest::Method::from_bytes(b"PROPFIND").unwrap(), &url)
             .header("Authorization", &self.auth_header)
             .header("Content-Type", "application/xml")
-            .header("Depth", "1")
+            .header("Depth", "0")
             .body(r#"<?xml version="1.0" encoding="UTF-8"?>
 <D:propfind xmlns:D="DAV:">
     <D:prop>
-        <D:displayname/>
+        <D:current-user-principal/>
         <D:resourcetype/>
     </D:prop>
 </D:propfind>"#)
             .send()
-            .await
-            .context("Failed to connect to CalDAV server")?;
+            .await;
+
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => return diagnose_request_error(&url, &e),
+        };
+        let status = response.status().as_u16();
+        let header = |name: &str| response.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+        let (dav, content_type) = (header("dav"), header("content-type"));
+        let body = response.text().await.unwrap_or_default();
+        diagnose_response(status, &dav, &content_type, &body)
+    }
+
+    /// Send a CalDAV request with the account's credentials. Reads (PROPFIND, REPORT, GET)
+    /// that time out or get a 5xx are tried again as `[calendar.retry]` allows; a 401 or 403
+    /// becomes `ArrowheadError::CalendarAuthFailed`.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let request = request.header("Authorization", &self.auth_header);
+        let idempotent = request.try_clone()
+            .and_then(|r| r.build().ok())
+            .is_some_and(|r| matches!(r.method().as_str(), "PROPFIND" | "REPORT" | "GET"));
+        let attempts = if idempotent { self.retry.max_attempts.max(1) } else { 1 };
+
+        let mut attempt = 1;
+        let response = loop {
+            let this_try = request.try_clone().expect("CalDAV request bodies are in memory");
+            match this_try.send().await {
+                Ok(response) if response.status().is_server_error() && attempt < attempts => {
+                    log::warn!("CalDAV server answered {}; retrying", response.status());
+                }
+                Ok(response) => break response,
+                Err(e) if e.is_timeout() && attempt < attempts => log::warn!("CalDAV request timed out; retrying"),
+                Err(e) => return Err(e.into()),
+            }
+            tokio::time::sleep(backoff_delay(&self.retry, attempt)).await;
+            attempt += 1;
+        };
 
-        Ok(response.status().is_success())
+        let status = response.status().as_u16();
+        if status == 401 || status == 403 {
+            return Err(ArrowheadError::CalendarAuthFailed { server: url_origin(&self.config.server_url).to_string(), status }.into());
+        }
+        Ok(response)
     }
 
+    /// The event calendars of the user, found through the server's `current-user-principal`
+    /// and `calendar-home-set`. The default calendar is marked primary, or the first one when
+    /// no default is set.
     pub async fn get_calendar_list(&self) -> Result<Vec<CalendarList>> {
-        let url = format!("{}/", self.config.server_url.trim_end_matches('/'));
-        
-        let response = self
+        let home = self.discover_calendar_home().await?;
+        let xml = self.propfind(&home, "1", CALENDAR_PROPS).await
+            .context("Failed to get calendar list")?;
+
+        let mut calendars = parse_calendar_collections(&xml);
+        for calendar in &mut calendars {
+            calendar.id = self.calendar_id_for(&resolve_href(&home, &calendar.id));
+        }
+        let primary = self.default_calendar.as_ref()
+            .and_then(|default| calendars.iter().position(|c| c.id.trim_end_matches('/') == default.trim_end_matches('/')))
+            .unwrap_or(0);
+        if let Some(calendar) = calendars.get_mut(primary) {
+            calendar.primary = true;
+        }
+        Ok(calendars)
+    }
+
+    /// Make a new event calendar called `name` in the user's calendar home
+    pub async fn create_calendar(&self, name: &str) -> Result<CalendarList> {
+        let home = self.discover_calendar_home().await?;
+        let url = format!("{}/{}/", home.trim_end_matches('/'), Uuid::new_v4());
+        let display_name = name.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        let request = self
             .client
-            .request(reqwest::Method::from_bytes(b"PROPFIND").unwrap(), &url)
-            .header("Authorization", &self.auth_header)
+            .request(reqwest::Method::from_bytes(b"MKCALENDAR").unwrap(), &url)
             .header("Content-Type", "application/xml")
-            .header("Depth", "1")
-            .body(r#"<?xml version="1.0" encoding="UTF-8"?>
-<D:propfind xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
-    <D:prop>
-        <D:displayname/>
-        <D:resourcetype/>
-        <C:supported-calendar-component-set/>
-    </D:prop>
-</D:propfind>"#)
-            .send()
-            .await
-            .context("Failed to get calendar list")?;
+            .body(format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<C:mkcalendar xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+    <D:set>
+        <D:prop>
+            <D:displayname>{}</D:displayname>
+            <C:supported-calendar-component-set><C:comp name="VEVENT"/></C:supported-calendar-component-set>
+        </D:prop>
+    </D:set>
+</C:mkcalendar>"#, display_name));
+        let response = self.send(request).await
+            .context("Failed to create calendar")?;
 
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            bail!("Failed to get calendar list: {}", error_text);
+            bail!("MKCALENDAR {} returned {}: {}", url, status, error_text);
+        }
+        Ok(CalendarList {
+            id: self.calendar_id_for(&url),
+            name: name.to_string(),
+            description: None,
+            primary: false,
+            access_role: "owner".to_string(),
+        })
+    }
+
+    /// The URL of the collection holding the user's calendars. A server that reports neither
+    /// a principal nor a calendar home is taken to be the home itself.
+    async fn discover_calendar_home(&self) -> Result<String> {
+        let root = self.config.server_url.clone();
+        let xml = self.propfind(&root, "0", "<D:current-user-principal/><C:calendar-home-set/>").await
+            .context("Failed to discover calendars")?;
+        if let Some(home) = nested_href(&xml, "calendar-home-set") {
+            return Ok(resolve_href(&root, &home));
         }
+        let Some(principal) = nested_href(&xml, "current-user-principal") else {
+            return Ok(root);
+        };
 
-        let response_text = response.text().await?;
-        self.parse_calendar_list(&response_text)
+        let principal = resolve_href(&root, &principal);
+        let xml = self.propfind(&principal, "0", "<C:calendar-home-set/>").await
+            .context("Failed to find the calendar home")?;
+        nested_href(&xml, "calendar-home-set")
+            .map(|home| resolve_href(&principal, &home))
+            .ok_or_else(|| anyhow::anyhow!("The CalDAV server didn't report a calendar home for {}", principal))
     }
 
-    fn parse_calendar_list(&self, xml_response: &str) -> Result<Vec<CalendarList>> {
-        // Simple XML parsing for calendar resources
-        let mut calendars = Vec::new();
-        
-        // For now, create a default calendar entry
-        // In a full implementation, you'd parse the XML response properly
-        if xml_response.contains("<D:collection/>") {
-            calendars.push(CalendarList {
-                id: "default".to_string(),
-                name: self.config.calendar_name.clone().unwrap_or_else(|| "Default Calendar".to_string()),
-                description: Some("Apple Calendar".to_string()),
-                primary: true,
-                access_role: "owner".to_string(),
-            });
+    /// A calendar's URL as an id: its path when it's on the configured server, otherwise the
+    /// whole URL (iCloud keeps calendars on a per-user host)
+    fn calendar_id_for(&self, url: &str) -> String {
+        let origin = url_origin(&self.config.server_url);
+        match url.strip_prefix(origin) {
+            Some(path) if path.starts_with('/') => path.to_string(),
+            _ => url.to_string(),
         }
-        
-        Ok(calendars)
+    }
+
+    async fn propfind(&self, url: &str, depth: &str, props: &str) -> Result<String> {
+        let request = self
+            .client
+            .request(reqwest::Method::from_bytes(b"PROPFIND").unwrap(), url)
+            .header("Content-Type", "application/xml")
+            .header("Depth", depth)
+            .body(format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<D:propfind xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+    <D:prop>
+        {}
+    </D:prop>
+</D:propfind>"#, props));
+        let response = self.send(request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            bail!("PROPFIND {} returned {}: {}", url, status, error_text);
+        }
+        Ok(response.text().await?)
     }
 
     pub async fn create_event(&self, calendar_id: &str, event: &CalendarEvent) -> Result<CalendarEvent> {
+        self.create_event_with_invitees(calendar_id, event, &pending_invitees(&event.attendees)).await
+    }
+
+    /// `create_event` with each attendee's name, role and response written to its ATTENDEE
+    /// line. The ATTENDEE lines come from `invitees` rather than `event.attendees`.
+    pub async fn create_event_with_invitees(&self, calendar_id: &str, event: &CalendarEvent, invitees: &[InviteeInfo]) -> Result<CalendarEvent> {
         let event_id = Uuid::new_v4().to_string();
-        let ics_content = self.event_to_ics(event, &event_id)?;
+        let ics_content = self.event_to_ics(event, &event_id, invitees, &[])?;
         
-        let url = format!("{}/{}.ics", 
-            self.config.server_url.trim_end_matches('/'), 
-            event_id
-        );
+        let url = self.event_url(calendar_id, &event_id);
         
-        let response = self
+        let request = self
             .client
             .put(&url)
-            .header("Authorization", &self.auth_header)
             .header("Content-Type", "text/calendar")
-            .body(ics_content)
-            .send()
+            .body(ics_content);
+        let response = self.send(request)
             .await
             .context("Failed to create calendar event")?;
 
@@ -901,16 +1266,21 @@ impl CalendarAdapter {
     }
 
     pub async fn get_event(&self, calendar_id: &str, event_id: &str) -> Result<CalendarEvent> {
-        let url = format!("{}/{}.ics", 
-            self.config.server_url.trim_end_matches('/'), 
-            event_id
-        );
+        let ics_content = self.get_event_ics(calendar_id, event_id).await?;
+        self.ics_to_event(&ics_content, event_id, calendar_id)
+    }
+
+    /// An event with its attendees' names, roles and responses from the stored ATTENDEE lines
+    pub async fn get_event_invitees(&self, calendar_id: &str, event_id: &str) -> Result<(CalendarEvent, Vec<InviteeInfo>)> {
+        let ics_content = self.get_event_ics(calendar_id, event_id).await?;
+        Ok((self.ics_to_event(&ics_content, event_id, calendar_id)?, parse_invitees(&ics_content)?))
+    }
+
+    /// The calendar object of an event as stored on the server
+    async fn get_event_ics(&self, calendar_id: &str, event_id: &str) -> Result<String> {
+        let url = self.event_url(calendar_id, event_id);
         
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", &self.auth_header)
-            .send()
+        let response = self.send(self.client.get(&url))
             .await
             .context("Failed to get calendar event")?;
 
@@ -919,25 +1289,49 @@ impl CalendarAdapter {
             bail!("Failed to get event: {}", error_text);
         }
 
+        Ok(response.text().await?)
+    }
+
+    /// Like `get_event`, but `None` when the server says the event no longer exists
+    pub async fn find_event(&self, calendar_id: &str, event_id: &str) -> Result<Option<CalendarEvent>> {
+        let url = self.event_url(calendar_id, event_id);
+
+        let response = self.send(self.client.get(&url))
+            .await
+            .context("Failed to get calendar event")?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND || status == reqwest::StatusCode::GONE {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            bail!("Failed to get event: {}", error_text);
+        }
+
         let ics_content = response.text().await?;
-        self.ics_to_event(&ics_content, event_id, calendar_id)
+        self.ics_to_event(&ics_content, event_id, calendar_id).map(Some)
     }
 
+    /// Replace an event. Its attendees are written as not having answered yet; use
+    /// `update_event_with_invitees` to keep their responses.
     pub async fn update_event(&self, calendar_id: &str, event_id: &str, event: &CalendarEvent) -> Result<CalendarEvent> {
-        let ics_content = self.event_to_ics(event, event_id)?;
+        self.update_event_with_invitees(calendar_id, event_id, event, &pending_invitees(&event.attendees)).await
+    }
+
+    /// `update_event` with each attendee's name, role and response written to its ATTENDEE
+    /// line. The ATTENDEE lines come from `invitees` rather than `event.attendees`.
+    pub async fn update_event_with_invitees(&self, calendar_id: &str, event_id: &str, event: &CalendarEvent, invitees: &[InviteeInfo]) -> Result<CalendarEvent> {
+        let ics_content = self.event_to_ics(event, event_id, invitees, &[])?;
         
-        let url = format!("{}/{}.ics", 
-            self.config.server_url.trim_end_matches('/'), 
-            event_id
-        );
+        let url = self.event_url(calendar_id, event_id);
         
-        let response = self
+        let request = self
             .client
             .put(&url)
-            .header("Authorization", &self.auth_header)
             .header("Content-Type", "text/calendar")
-            .body(ics_content)
-            .send()
+            .body(ics_content);
+        let response = self.send(request)
             .await
             .context("Failed to update calendar event")?;
 
@@ -953,17 +1347,31 @@ impl CalendarAdapter {
         Ok(updated_event)
     }
 
-    pub async fn delete_event(&self, _calendar_id: &str, event_id: &str) -> Result<()> {
-        let url = format!("{}/{}.ics", 
-            self.config.server_url.trim_end_matches('/'), 
-            event_id
-        );
-        
-        let response = self
+    /// Create or replace the event stored as `event_id`, writing `properties` such as `X-` tags
+    /// into it. Callers choose the id, so writing the same event again replaces it.
+    pub async fn put_event(&self, calendar_id: &str, event_id: &str, event: &CalendarEvent, properties: &[(&str, &str)]) -> Result<CalendarEvent> {
+        let ics_content = self.event_to_ics(event, event_id, &pending_invitees(&event.attendees), properties)?;
+        let request = self
             .client
-            .delete(&url)
-            .header("Authorization", &self.auth_header)
-            .send()
+            .put(self.event_url(calendar_id, event_id))
+            .header("Content-Type", "text/calendar")
+            .body(ics_content);
+        let response = self.send(request)
+            .await
+            .context("Failed to write calendar event")?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            bail!("Failed to write event: {}", error_text);
+        }
+
+        Ok(CalendarEvent { id: event_id.to_string(), calendar_id: calendar_id.to_string(), ..event.clone() })
+    }
+
+    pub async fn delete_event(&self, calendar_id: &str, event_id: &str) -> Result<()> {
+        let url = self.event_url(calendar_id, event_id);
+        
+        let response = self.send(self.client.delete(&url))
             .await
             .context("Failed to delete calendar event")?;
 
@@ -976,30 +1384,52 @@ impl CalendarAdapter {
     }
 
     pub async fn list_events(&self, calendar_id: &str, time_min: Option<DateTime<Utc>>, time_max: Option<DateTime<Utc>>) -> Result<Vec<CalendarEvent>> {
-        let mut filter = String::new();
-        if let Some(start) = time_min {
-            filter.push_str(&format!("DTSTART >= {}", start.format("%Y%m%dT%H%M%SZ")));
-        }
-        if let Some(end) = time_max {
-            if !filter.is_empty() {
-                filter.push_str(" AND ");
+        let range = (time_min.is_some() || time_max.is_some()).then(|| (
+            time_min.unwrap_or_else(Utc::now),
+            time_max.unwrap_or_else(|| Utc::now() + chrono::Duration::days(365)),
+        ));
+        let response_text = self.calendar_query(calendar_id, range).await?;
+        self.parse_event_list(&response_text, calendar_id, range)
+    }
+
+    /// Every event in a calendar, unexpanded, with the value of its `property` line, e.g. an
+    /// `X-` tag written by `put_event`
+    pub async fn list_events_with_property(&self, calendar_id: &str, property: &str) -> Result<Vec<(CalendarEvent, Option<String>)>> {
+        let response_text = self.calendar_query(calendar_id, None).await?;
+        let mut events = Vec::new();
+        for block in calendar_data_blocks(&response_text) {
+            match parse_calendar_events(&block, calendar_id, None) {
+                Ok(parsed) => {
+                    let value = event_property(&block, property);
+                    events.extend(parsed.into_iter().map(|event| (event, value.clone())));
+                }
+                Err(e) => log::warn!("skipping calendar object: {}", e),
             }
-            filter.push_str(&format!("DTEND <= {}", end.format("%Y%m%dT%H%M%SZ")));
         }
+        events.sort_by_key(|(e, _)| e.start_time);
+        Ok(events)
+    }
 
-        let time_range = if filter.is_empty() { 
-            "".to_string() 
-        } else { 
-            format!("<C:time-range start=\"{}\" end=\"{}\"/>", 
-                time_min.unwrap_or_else(|| Utc::now()).format("%Y%m%dT%H%M%SZ"),
-                time_max.unwrap_or_else(|| Utc::now() + chrono::Duration::days(365)).format("%Y%m%dT%H%M%SZ")) 
+    /// The calendar-query REPORT for a calendar's events, overlapping `range` when given
+    async fn calendar_query(&self, calendar_id: &str, range: Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<String> {
+        // Servers that support it expand recurring events into the range themselves;
+        // `parse_event_list` expands any that come back unexpanded
+        let (calendar_data, time_range) = match range {
+            Some((start, end)) => {
+                let (start, end) = (start.format("%Y%m%dT%H%M%SZ"), end.format("%Y%m%dT%H%M%SZ"));
+                (
+                    format!("<C:calendar-data><C:expand start=\"{}\" end=\"{}\"/></C:calendar-data>", start, end),
+                    format!("<C:time-range start=\"{}\" end=\"{}\"/>", start, end),
+                )
+            }
+            None => ("<C:calendar-data/>".to_string(), String::new()),
         };
         
         let report_body = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
 <C:calendar-query xmlns:C="urn:ietf:params:xml:ns:caldav" xmlns:D="DAV:">
     <D:prop>
         <D:getetag/>
-        <C:calendar-data/>
+        {}
     </D:prop>
     <C:filter>
         <C:comp-filter name="VCALENDAR">
@@ -1008,18 +1438,17 @@ impl CalendarAdapter {
             </C:comp-filter>
         </C:comp-filter>
     </C:filter>
-</C:calendar-query>"#, time_range);
+</C:calendar-query>"#, calendar_data, time_range);
 
-        let url = format!("{}/", self.config.server_url.trim_end_matches('/'));
+        let url = self.calendar_url(calendar_id);
         
-        let response = self
+        let request = self
             .client
             .request(reqwest::Method::from_bytes(b"REPORT").unwrap(), &url)
-            .header("Authorization", &self.auth_header)
             .header("Content-Type", "application/xml")
             .header("Depth", "1")
-            .body(report_body)
-            .send()
+            .body(report_body);
+        let response = self.send(request)
             .await
             .context("Failed to list calendar events")?;
 
@@ -1028,21 +1457,25 @@ impl CalendarAdapter {
             bail!("Failed to list events: {}", error_text);
         }
 
-        let response_text = response.text().await?;
-        self.parse_event_list(&response_text, calendar_id)
+        Ok(response.text().await?)
     }
 
-    fn parse_event_list(&self, _xml_response: &str, _calendar_id: &str) -> Result<Vec<CalendarEvent>> {
-        let events = Vec::new();
-        
-        // Simple parsing - in a real implementation, you'd use a proper XML parser
-        // For now, return empty list as this is a simplified implementation
-        // You would parse the XML response and extract calendar data blocks
-        
+    /// Events in every `calendar-data` block of a multistatus response. A block that isn't
+    /// valid iCalendar is skipped with a warning rather than failing the whole listing.
+    fn parse_event_list(&self, xml_response: &str, calendar_id: &str, range: Option<(DateTime<Utc>, DateTime<Utc>)>) -> Result<Vec<CalendarEvent>> {
+        let mut events = Vec::new();
+        for block in calendar_data_blocks(xml_response) {
+            match parse_calendar_events(&block, calendar_id, range) {
+                Ok(parsed) => events.extend(parsed),
+                Err(e) => log::warn!("skipping calendar object: {}", e),
+            }
+        }
+        events.sort_by_key(|e| e.start_time);
         Ok(events)
     }
 
-    fn event_to_ics(&self, event: &CalendarEvent, event_id: &str) -> Result<String> {
+    /// The calendar object for `event`, with `properties` such as `X-` tags written as extra lines
+    fn event_to_ics(&self, event: &CalendarEvent, event_id: &str, invitees: &[InviteeInfo], properties: &[(&str, &str)]) -> Result<String> {
         let now = Utc::now();
         let start_str = if event.all_day {
             format!("DTSTART;VALUE=DATE:{}", event.start_time.format("%Y%m%d"))
@@ -1070,21 +1503,23 @@ impl CalendarAdapter {
             now.format("%Y%m%dT%H%M%SZ"),
             start_str,
             end_str,
-            event.title.replace('\n', "\\n").replace('\r', "\\r")
+            escape_text(&event.title)
         );
 
         if let Some(description) = &event.description {
-            ics.push_str(&format!("DESCRIPTION:{}\r\n", 
-                description.replace('\n', "\\n").replace('\r', "\\r")));
+            ics.push_str(&format!("DESCRIPTION:{}\r\n", escape_text(description)));
         }
 
         if let Some(location) = &event.location {
-            ics.push_str(&format!("LOCATION:{}\r\n", 
-                location.replace('\n', "\\n").replace('\r', "\\r")));
+            ics.push_str(&format!("LOCATION:{}\r\n", escape_text(location)));
+        }
+
+        for invitee in invitees {
+            ics.push_str(&attendee_line(invitee));
         }
 
-        for attendee in &event.attendees {
-            ics.push_str(&format!("ATTENDEE:MAILTO:{}\r\n", attendee));
+        for (name, value) in properties {
+            ics.push_str(&format!("{}:{}\r\n", name, escape_text(value)));
         }
 
         ics.push_str("END:VEVENT\r\n");
@@ -1094,68 +1529,11 @@ impl CalendarAdapter {
     }
 
     fn ics_to_event(&self, ics_content: &str, event_id: &str, calendar_id: &str) -> Result<CalendarEvent> {
-        let mut event = CalendarEvent {
-            id: event_id.to_string(),
-            title: "Untitled Event".to_string(),
-            description: None,
-            start_time: Utc::now(),
-            end_time: Utc::now() + chrono::Duration::hours(1),
-            location: None,
-            attendees: Vec::new(),
-            all_day: false,
-            recurring: false,
-            calendar_id: calendar_id.to_string(),
-        };
-
-        // Simple ICS parsing - in a real implementation, you'd use a proper ICS parser
-        for line in ics_content.lines() {
-            let line = line.trim();
-            if line.starts_with("SUMMARY:") {
-                event.title = line[8..].to_string();
-            } else if line.starts_with("DESCRIPTION:") {
-                event.description = Some(line[12..].to_string());
-            } else if line.starts_with("LOCATION:") {
-                event.location = Some(line[9..].to_string());
-            } else if line.starts_with("DTSTART:") {
-                let datetime_str = &line[8..];
-                // Handle UTC format: 20240101T100000Z
-                if datetime_str.ends_with('Z') {
-                    let utc_str = format!("{}+00:00", &datetime_str[..datetime_str.len()-1]);
-                    if let Ok(dt) = DateTime::parse_from_str(&utc_str, "%Y%m%dT%H%M%S%z") {
-                        event.start_time = dt.with_timezone(&Utc);
-                    }
-                } else if let Ok(dt) = DateTime::parse_from_rfc3339(datetime_str) {
-                    event.start_time = dt.with_timezone(&Utc);
-                }
-            } else if line.starts_with("DTEND:") {
-                let datetime_str = &line[6..];
-                // Handle UTC format: 20240101T110000Z
-                if datetime_str.ends_with('Z') {
-                    let utc_str = format!("{}+00:00", &datetime_str[..datetime_str.len()-1]);
-                    if let Ok(dt) = DateTime::parse_from_str(&utc_str, "%Y%m%dT%H%M%S%z") {
-                        event.end_time = dt.with_timezone(&Utc);
-                    }
-                } else if let Ok(dt) = DateTime::parse_from_rfc3339(datetime_str) {
-                    event.end_time = dt.with_timezone(&Utc);
-                }
-            } else if line.starts_with("DTSTART;VALUE=DATE:") {
-                event.all_day = true;
-                if let Ok(dt) = DateTime::parse_from_str(&format!("{}T00:00:00Z", &line[19..]), "%Y%m%dT%H%M%SZ") {
-                    event.start_time = dt.with_timezone(&Utc);
-                }
-            } else if line.starts_with("DTEND;VALUE=DATE:") {
-                event.all_day = true;
-                if let Ok(dt) = DateTime::parse_from_str(&format!("{}T00:00:00Z", &line[17..]), "%Y%m%dT%H%M%SZ") {
-                    event.end_time = dt.with_timezone(&Utc);
-                }
-            } else if line.starts_with("ATTENDEE:MAILTO:") {
-                event.attendees.push(line[16..].to_string());
-            } else if line.starts_with("RRULE:") {
-                event.recurring = true;
-            }
-        }
-
-        Ok(event)
+        let event = parse_calendar_events(ics_content, calendar_id, None)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Calendar object {} has no event", event_id))?;
+        Ok(CalendarEvent { id: event_id.to_string(), ..event })
     }
 
     pub fn get_config(&self) -> &CalendarConfig {
@@ -1174,14 +1552,19 @@ impl CalendarAdapter {
 
     // Meeting Scheduling System Implementation
     
-    /// Check availability across multiple calendars and attendees
+    /// Check availability on our calendar and each attendee's busy time. Every slot we are
+    /// free for gets a recommendation listing each attendee as Available, Busy, Tentative or
+    /// Unknown (no busy time could be loaded for them); only slots nobody is busy for are
+    /// `available_slots`.
     pub async fn check_availability(&self, request: &AvailabilityRequest) -> Result<AvailabilityResponse> {
         let mut available_slots = Vec::new();
         let mut conflicts = Vec::new();
         let mut recommendations = Vec::new();
+        let attendees: Vec<&String> = request.attendees.iter().filter(|a| a.as_str() != "self").collect();
 
         // Get all events in the requested time range
-        let events = self.list_events("default", Some(request.start_time), Some(request.end_time)).await?;
+        let events = self.list_events(DEFAULT_CALENDAR_ID, Some(request.start_time), Some(request.end_time)).await?;
+        let context = self.scheduling.context(&self.time_zone, request.start_time, request.end_time);
         
         // Find available time slots
         let mut current_time = request.start_time;
@@ -1196,7 +1579,7 @@ impl CalendarAdapter {
                 if self.times_overlap(current_time, slot_end, event.start_time, event.end_time, request.buffer_minutes) {
                     has_conflict = true;
                     conflicts.push(ConflictInfo {
-                        attendee: "self".to_string(), // In a real implementation, this would be the actual attendee
+                        attendee: "self".to_string(),
                         conflicting_event: event.clone(),
                         conflict_type: ConflictType::DirectOverlap,
                     });
@@ -1205,11 +1588,56 @@ impl CalendarAdapter {
             }
             
             if !has_conflict {
-                available_slots.push(TimeSlot {
+                let slot = TimeSlot {
                     start_time: current_time,
                     end_time: slot_end,
-                    calendar_id: Some("default".to_string()),
+                    calendar_id: Some(DEFAULT_CALENDAR_ID.to_string()),
                     event_id: None,
+                };
+                let mut attendee_availability = vec![AttendeeAvailability {
+                    attendee: "self".to_string(),
+                    status: AvailabilityStatus::Available,
+                    conflicts: Vec::new(),
+                }];
+                let mut busy = Vec::new();
+                for attendee in &attendees {
+                    let overlapping = busy_during(&self.external_busy, attendee, current_time, slot_end, request.buffer_minutes);
+                    let status = match &overlapping {
+                        None => AvailabilityStatus::Unknown,
+                        Some(intervals) if intervals.is_empty() => AvailabilityStatus::Available,
+                        Some(intervals) if intervals.iter().all(|i| i.tentative) => AvailabilityStatus::Tentative,
+                        Some(_) => AvailabilityStatus::Busy,
+                    };
+                    let attendee_events: Vec<CalendarEvent> = overlapping.unwrap_or_default().iter().map(|i| i.to_event(attendee)).collect();
+                    if !attendee_events.is_empty() {
+                        busy.push(attendee.as_str());
+                    }
+                    conflicts.extend(attendee_events.iter().map(|event| ConflictInfo {
+                        attendee: attendee.to_string(),
+                        conflicting_event: event.clone(),
+                        conflict_type: ConflictType::DirectOverlap,
+                    }));
+                    attendee_availability.push(AttendeeAvailability {
+                        attendee: attendee.to_string(),
+                        status,
+                        conflicts: attendee_events,
+                    });
+                }
+
+                // Slots some attendees are busy for are still offered, ranked lower
+                let mut confidence_score = self.calculate_confidence_score(&slot, &events, &context);
+                let mut reasoning = self.generate_reasoning(&slot, &events);
+                if busy.is_empty() {
+                    available_slots.push(slot.clone());
+                } else {
+                    confidence_score *= (attendees.len() - busy.len()) as f32 / attendees.len() as f32;
+                    reasoning = format!("{}; not free: {}", reasoning, busy.join(", "));
+                }
+                recommendations.push(SchedulingRecommendation {
+                    time_slot: slot,
+                    confidence_score,
+                    attendee_availability,
+                    reasoning,
                 });
             }
             
@@ -1217,23 +1645,6 @@ impl CalendarAdapter {
             current_time = current_time + chrono::Duration::minutes(15);
         }
         
-        // Generate recommendations based on available slots
-        for slot in &available_slots {
-            let confidence_score = self.calculate_confidence_score(slot, &events);
-            let reasoning = self.generate_reasoning(slot, &events);
-            
-            recommendations.push(SchedulingRecommendation {
-                time_slot: slot.clone(),
-                confidence_score,
-                attendee_availability: vec![AttendeeAvailability {
-                    attendee: "self".to_string(),
-                    status: AvailabilityStatus::Available,
-                    conflicts: Vec::new(),
-                }],
-                reasoning,
-            });
-        }
-        
         // Sort recommendations by confidence score
         recommendations.sort_by(|a, b| b.confidence_score.partial_cmp(&a.confidence_score).unwrap());
         
@@ -1244,10 +1655,13 @@ impl CalendarAdapter {
         })
     }
     
-    /// Find optimal meeting time based on meeting request
+    /// Find optimal meeting time based on meeting request. Slots where more required
+    /// attendees are available come first, then the more convenient ones.
     pub async fn find_meeting_time(&self, request: &MeetingRequest) -> Result<Vec<SchedulingRecommendation>> {
+        let mut attendees = request.required_attendees.clone();
+        attendees.extend(request.optional_attendees.iter().filter(|a| !request.required_attendees.contains(a)).cloned());
         let availability_request = AvailabilityRequest {
-            attendees: request.required_attendees.clone(),
+            attendees,
             start_time: request.earliest_start,
             end_time: request.latest_start,
             duration_minutes: request.duration_minutes,
@@ -1301,6 +1715,16 @@ impl CalendarAdapter {
             }
         }
         
+        let available_required = |recommendation: &SchedulingRecommendation| {
+            recommendation.attendee_availability.iter()
+                .filter(|a| matches!(a.status, AvailabilityStatus::Available) && request.required_attendees.contains(&a.attendee))
+                .count()
+        };
+        filtered_recommendations.sort_by(|a, b| {
+            available_required(b).cmp(&available_required(a))
+                .then(b.confidence_score.partial_cmp(&a.confidence_score).unwrap_or(std::cmp::Ordering::Equal))
+        });
+
         // Limit to top 10 recommendations
         filtered_recommendations.truncate(10);
         
@@ -1316,48 +1740,18 @@ impl CalendarAdapter {
         adjusted_start1 < end2 && adjusted_end1 > start2
     }
     
-    /// Calculate confidence score for a time slot
-    fn calculate_confidence_score(&self, slot: &TimeSlot, events: &[CalendarEvent]) -> f32 {
-        let mut score: f32 = 1.0;
-        
-        // Check time of day preferences (prefer business hours)
-        let hour = slot.start_time.hour();
-        if hour >= 9 && hour <= 17 {
-            score *= 1.2;
-        } else if hour < 8 || hour > 18 {
-            score *= 0.7;
-        }
-        
-        // Check day of week preferences (prefer weekdays)
-        let weekday = slot.start_time.weekday();
-        if weekday == chrono::Weekday::Sat || weekday == chrono::Weekday::Sun {
-            score *= 0.5;
-        }
-        
-        // Check proximity to other events (prefer gaps between meetings)
-        let mut has_adjacent_meeting = false;
-        for event in events {
-            let time_diff = (event.start_time - slot.end_time).num_minutes().abs();
-            if time_diff < 30 {
-                has_adjacent_meeting = true;
-                break;
-            }
-        }
-        
-        if has_adjacent_meeting {
-            score *= 0.9;
-        }
-        
-        // Cap score at 1.0
-        score.min(1.0)
+    /// Confidence in a time slot: the product of its `confidence_factors`
+    fn calculate_confidence_score(&self, slot: &TimeSlot, events: &[CalendarEvent], context: &AiSchedulingContext) -> f32 {
+        confidence_from(&confidence_factors(slot, events, context, &self.time_zone))
     }
     
     /// Generate human-readable reasoning for a time slot recommendation
     fn generate_reasoning(&self, slot: &TimeSlot, events: &[CalendarEvent]) -> String {
         let mut reasons = Vec::new();
         
-        let hour = slot.start_time.hour();
-        let weekday = slot.start_time.weekday();
+        let local_start = self.time_zone.to_local(slot.start_time);
+        let hour = local_start.hour();
+        let weekday = local_start.weekday();
         
         if hour >= 9 && hour <= 17 {
             reasons.push("Within business hours".to_string());
@@ -1464,10 +1858,11 @@ impl CalendarAdapter {
         };
         
         let availability = self.check_availability(&availability_request).await?;
+        let zone = timezone::resolve(&constraints.time_zone)?;
         
         // Filter alternatives based on working hours
         for recommendation in availability.recommendations {
-            if self.is_within_working_hours(&recommendation.time_slot, constraints) {
+            if self.is_within_working_hours(&recommendation.time_slot, constraints, &zone) {
                 alternative_times.push(recommendation);
             }
         }
@@ -1489,7 +1884,7 @@ impl CalendarAdapter {
                 let next_day_availability = self.check_availability(&next_day_request).await?;
                 
                 for recommendation in next_day_availability.recommendations {
-                    if self.is_within_working_hours(&recommendation.time_slot, constraints) {
+                    if self.is_within_working_hours(&recommendation.time_slot, constraints, &zone) {
                         alternative_times.push(recommendation);
                     }
                 }
@@ -1507,17 +1902,67 @@ impl CalendarAdapter {
         Ok(alternative_times)
     }
     
-    /// Check if a time slot is within working hours
-    fn is_within_working_hours(&self, time_slot: &TimeSlot, constraints: &SchedulingConstraints) -> bool {
-        let weekday = time_slot.start_time.weekday().num_days_from_sunday();
-        let hour = time_slot.start_time.hour();
-        let minute = time_slot.start_time.minute();
-        
-        for working_hours in &constraints.working_hours {
-            if working_hours.day_of_week == weekday {
-                let start_minutes = working_hours.start_hour * 60 + working_hours.start_minute;
-                let end_minutes = working_hours.end_hour * 60 + working_hours.end_minute;
-                let slot_minutes = hour * 60 + minute;
+    /// Find the earliest free slot of the given length that fits inside working hours
+    pub async fn find_next_free_slot(&self, from: DateTime<Utc>, duration_minutes: u32, constraints: &SchedulingConstraints) -> Result<Option<TimeSlot>> {
+        let duration = chrono::Duration::minutes(duration_minutes as i64);
+        let zone = timezone::resolve(&constraints.time_zone)?;
+        let first_day = zone.to_local(from).date();
+
+        for days_ahead in 0..=constraints.maximum_lookahead_days as i64 {
+            let day = first_day + chrono::Duration::days(days_ahead);
+            let weekday = day.weekday().num_days_from_sunday();
+
+            for working_hours in constraints.working_hours.iter().filter(|wh| wh.day_of_week == weekday) {
+                let window_start = match zone.at(day, working_hours.start_hour, working_hours.start_minute) {
+                    Some(start) => start.max(from),
+                    None => continue,
+                };
+                let Some(window_end) = zone.at(day, working_hours.end_hour, working_hours.end_minute) else { continue };
+
+                // Align to the 15-minute grid used by check_availability
+                let offset = (window_start.minute() % 15) as i64;
+                let window_start = if offset == 0 && window_start.second() == 0 {
+                    window_start
+                } else {
+                    window_start.with_second(0).unwrap().with_nanosecond(0).unwrap()
+                        + chrono::Duration::minutes(15 - offset)
+                };
+
+                if window_start + duration > window_end {
+                    continue;
+                }
+
+                let availability = self.check_availability(&AvailabilityRequest {
+                    attendees: std::iter::once("self".to_string()).chain(self.external_busy.keys().cloned()).collect(),
+                    start_time: window_start,
+                    end_time: window_end,
+                    duration_minutes,
+                    buffer_minutes: constraints.break_duration_minutes,
+                }).await?;
+
+                if let Some(slot) = availability.available_slots.into_iter()
+                    .find(|slot| self.is_within_working_hours(slot, constraints, &zone))
+                {
+                    return Ok(Some(slot));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Check if a time slot starts within working hours, on the wall clock of `zone`
+    fn is_within_working_hours(&self, time_slot: &TimeSlot, constraints: &SchedulingConstraints, zone: &TimeZone) -> bool {
+        let local_start = zone.to_local(time_slot.start_time);
+        let weekday = local_start.weekday().num_days_from_sunday();
+        let hour = local_start.hour();
+        let minute = local_start.minute();
+        
+        for working_hours in &constraints.working_hours {
+            if working_hours.day_of_week == weekday {
+                let start_minutes = working_hours.start_hour * 60 + working_hours.start_minute;
+                let end_minutes = working_hours.end_hour * 60 + working_hours.end_minute;
+                let slot_minutes = hour * 60 + minute;
                 
                 if slot_minutes >= start_minutes && slot_minutes <= end_minutes {
                     // Check lunch time avoidance
@@ -1553,7 +1998,12 @@ impl CalendarAdapter {
         }
         
         // Try to find an alternative time automatically
-        let alternatives = self.resolve_conflicts(calendar_id, proposed_event, constraints).await?;
+        let mut alternatives = self.resolve_conflicts(calendar_id, proposed_event, constraints).await?;
+        if self.ai_conversation.is_some() {
+            let context = scheduling_context_for(proposed_event, constraints);
+            let smart = self.analyze_conflicts_with_ai(&conflicts, &context).await?;
+            add_solution_reasoning(&mut alternatives, &smart.ai_recommended_solutions);
+        }
         
         if let Some(best_alternative) = alternatives.first() {
             let resolved_event = CalendarEvent {
@@ -1587,11 +2037,15 @@ impl CalendarAdapter {
         }
     }
     
-    /// Create and send meeting invitation
-    pub async fn create_meeting_invitation(&self, meeting_request: &MeetingRequest, selected_time: &TimeSlot) -> Result<MeetingInvitation> {
+    /// Create and send meeting invitation, with `agenda` rendered below the description
+    pub async fn create_meeting_invitation(&self, meeting_request: &MeetingRequest, selected_time: &TimeSlot, agenda: Option<&MeetingAgenda>) -> Result<MeetingInvitation> {
         let meeting_id = Uuid::new_v4().to_string();
+        let description = match (meeting_request.description.as_deref(), agenda.map(render_agenda)) {
+            (Some(description), Some(agenda)) => Some(format!("{}\n\n{}", description, agenda)),
+            (description, agenda) => agenda.or(description.map(str::to_string)),
+        };
         
-        let mut attendees = Vec::new();
+        let mut attendees: Vec<InviteeInfo> = Vec::new();
         for email in &meeting_request.required_attendees {
             attendees.push(InviteeInfo {
                 email: email.clone(),
@@ -1610,12 +2064,12 @@ impl CalendarAdapter {
             });
         }
         
-        let invitation = MeetingInvitation {
+        let mut invitation = MeetingInvitation {
             meeting_id: meeting_id.clone(),
             organizer: self.config.username.clone(),
             attendees,
             subject: meeting_request.title.clone(),
-            body: meeting_request.description.clone().unwrap_or_else(|| "Please join this meeting.".to_string()),
+            body: description.clone().unwrap_or_else(|| "Please join this meeting.".to_string()),
             start_time: selected_time.start_time,
             end_time: selected_time.end_time,
             location: meeting_request.location.clone(),
@@ -1627,7 +2081,7 @@ impl CalendarAdapter {
         let calendar_event = CalendarEvent {
             id: meeting_id.clone(),
             title: meeting_request.title.clone(),
-            description: meeting_request.description.clone(),
+            description,
             start_time: selected_time.start_time,
             end_time: selected_time.end_time,
             location: meeting_request.location.clone(),
@@ -1637,61 +2091,55 @@ impl CalendarAdapter {
                 .collect(),
             all_day: false,
             recurring: false,
-            calendar_id: "default".to_string(),
+            calendar_id: DEFAULT_CALENDAR_ID.to_string(),
         };
         
-        self.create_event("default", &calendar_event).await?;
+        // The server copy is what responses are tracked against, so point the invitation at it
+        let created = self.create_event_with_invitees(DEFAULT_CALENDAR_ID, &calendar_event, &invitation.attendees).await?;
+        invitation.meeting_id = created.id;
         
         Ok(invitation)
     }
     
-    /// Update meeting invitation response status
+    /// Record an attendee's response by rewriting their PARTSTAT in the stored event. The
+    /// other attendees' responses are kept.
     pub async fn update_invitation_response(&self, invitation_id: &str, attendee_email: &str, response: ResponseStatus) -> Result<()> {
-        // In a real implementation, this would update the meeting invitation in a database
-        // For now, we'll just simulate the update
-        println!("Updated invitation {} response for {}: {:?}", invitation_id, attendee_email, response);
+        let (event, mut invitees) = self.get_event_invitees(DEFAULT_CALENDAR_ID, invitation_id).await?;
+        let invitee = invitees.iter_mut()
+            .find(|i| i.email.eq_ignore_ascii_case(attendee_email))
+            .ok_or_else(|| anyhow::anyhow!("{} is not an attendee of {}", attendee_email, event.title))?;
+        invitee.response_status = response;
+        self.update_event_with_invitees(DEFAULT_CALENDAR_ID, invitation_id, &event, &invitees).await?;
         Ok(())
     }
     
-    /// Send meeting invitation reminder
-    pub async fn send_invitation_reminder(&self, invitation: &MeetingInvitation) -> Result<()> {
-        let pending_attendees: Vec<&InviteeInfo> = invitation.attendees
-            .iter()
-            .filter(|a| a.response_status == ResponseStatus::Pending)
+    /// Remind the attendees who haven't answered, as the stored event says; the responses in
+    /// `invitation` may be out of date. Returns who was reminded.
+    pub async fn send_invitation_reminder(&self, invitation: &MeetingInvitation) -> Result<Vec<String>> {
+        let (_, invitees) = self.get_event_invitees(DEFAULT_CALENDAR_ID, &invitation.meeting_id).await?;
+        let pending: Vec<String> = invitees.into_iter()
+            .filter(|i| i.response_status == ResponseStatus::Pending)
+            .map(|i| i.email)
             .collect();
         
-        if pending_attendees.is_empty() {
-            return Ok(());
-        }
-        
-        for attendee in pending_attendees {
+        for email in &pending {
             // In a real implementation, this would send an email or notification
-            println!("Sending reminder to {} for meeting: {}", attendee.email, invitation.subject);
+            println!("Sending reminder to {} for meeting: {}", email, invitation.subject);
         }
         
-        Ok(())
+        Ok(pending)
     }
     
-    /// Get meeting invitation status
+    /// Response counts of an invitation, from the attendees' PARTSTAT in the stored event
     pub async fn get_invitation_status(&self, invitation_id: &str) -> Result<InvitationStatus> {
-        // In a real implementation, this would query a database
-        // For now, return a mock status
-        Ok(InvitationStatus {
-            meeting_id: invitation_id.to_string(),
-            total_invites: 5,
-            responses_received: 3,
-            accepted: 2,
-            declined: 1,
-            tentative: 0,
-            pending: 2,
-            response_rate: 60.0,
-        })
+        let (_, invitees) = self.get_event_invitees(DEFAULT_CALENDAR_ID, invitation_id).await?;
+        Ok(InvitationStatus::from_invitees(invitation_id, &invitees))
     }
     
     /// Cancel meeting invitation
     pub async fn cancel_meeting_invitation(&self, invitation_id: &str, reason: Option<&str>) -> Result<()> {
         // Delete the calendar event
-        self.delete_event("default", invitation_id).await?;
+        self.delete_event(DEFAULT_CALENDAR_ID, invitation_id).await?;
         
         // In a real implementation, this would notify all attendees
         println!("Meeting {} cancelled. Reason: {}", invitation_id, reason.unwrap_or("No reason provided"));
@@ -1702,7 +2150,7 @@ impl CalendarAdapter {
     /// Reschedule meeting invitation
     pub async fn reschedule_meeting_invitation(&self, invitation_id: &str, new_time: &TimeSlot, reason: Option<&str>) -> Result<MeetingInvitation> {
         // Get the original event
-        let original_event = self.get_event("default", invitation_id).await?;
+        let original_event = self.get_event(DEFAULT_CALENDAR_ID, invitation_id).await?;
         
         // Update the event with new time
         let updated_event = CalendarEvent {
@@ -1718,7 +2166,7 @@ impl CalendarAdapter {
             calendar_id: original_event.calendar_id.clone(),
         };
         
-        self.update_event("default", invitation_id, &updated_event).await?;
+        self.update_event(DEFAULT_CALENDAR_ID, invitation_id, &updated_event).await?;
         
         // Create updated invitation
         let updated_invitation = MeetingInvitation {
@@ -1744,7 +2192,7 @@ impl CalendarAdapter {
     
     // Deadline Tracking and Time Blocking Implementation
     
-    /// Create a new deadline with automatic time blocking
+    /// Create a new deadline with automatic time blocking and save it
     pub async fn create_deadline(&self, deadline: &Deadline) -> Result<Deadline> {
         let mut new_deadline = deadline.clone();
         
@@ -1758,11 +2206,12 @@ impl CalendarAdapter {
         }
         
         // Create calendar events for each time block
-        for time_block in &new_deadline.time_blocks {
-            let _calendar_event = self.create_time_block_event(&new_deadline, time_block).await?;
-            // In a real implementation, you would update the time_block with the calendar_event.id
+        for index in 0..new_deadline.time_blocks.len() {
+            let event = self.create_time_block_event(&new_deadline, &new_deadline.time_blocks[index]).await?;
+            new_deadline.time_blocks[index].calendar_event_id = Some(event.id);
         }
         
+        self.deadline_store.insert(&new_deadline)?;
         Ok(new_deadline)
     }
     
@@ -1780,28 +2229,47 @@ impl CalendarAdapter {
         let hours_per_day = total_hours_needed / available_days as f32;
         
         // Generate time blocks for each working day
-        let mut current_date = now.date_naive();
         let mut remaining_hours = total_hours_needed;
         
-        while remaining_hours > 0.0 && current_date < deadline.due_date.date_naive() {
-            // Skip weekends for now (can be made configurable)
-            if current_date.weekday() != chrono::Weekday::Sat && current_date.weekday() != chrono::Weekday::Sun {
-                let daily_hours = hours_per_day.min(remaining_hours).min(8.0); // Cap at 8 hours per day
+        for current_date in block_days(&self.time_zone, now, deadline.due_date) {
+            if remaining_hours <= 0.0 {
+                break;
+            }
+            let daily_hours = hours_per_day.min(remaining_hours).min(8.0); // Cap at 8 hours per day
+            
+            if daily_hours > 0.5 { // Only create blocks for meaningful work periods
+                let Some((block_start, block_end)) = morning_block(&self.time_zone, current_date, daily_hours) else { continue };
                 
-                if daily_hours > 0.5 { // Only create blocks for meaningful work periods
-                    let block_start = current_date.and_hms_opt(9, 0, 0).unwrap().and_utc();
-                    let block_end = block_start + chrono::Duration::hours(daily_hours as i64);
-                    
-                    // Check for conflicts with existing events
-                    let conflicts = self.check_time_block_conflicts(block_start, block_end).await?;
+                // Check for conflicts with existing events
+                let conflicts = self.check_time_block_conflicts(block_start, block_end).await?;
+                
+                if conflicts.is_empty() {
+                    let time_block = TimeBlock {
+                        id: Uuid::new_v4().to_string(),
+                        deadline_id: deadline.id.clone(),
+                        start_time: block_start,
+                        end_time: block_end,
+                        planned_duration: chrono::Duration::minutes((daily_hours * 60.0).round() as i64),
+                        actual_duration: None,
+                        productivity_score: None,
+                        notes: None,
+                        calendar_event_id: None,
+                        status: TimeBlockStatus::Planned,
+                        focus_mode: true,
+                        interruptions: Vec::new(),
+                    };
                     
-                    if conflicts.is_empty() {
+                    time_blocks.push(time_block);
+                    remaining_hours -= daily_hours;
+                } else {
+                    // Try to find alternative time slots
+                    if let Some(alternative_slot) = self.find_alternative_time_slot(current_date, daily_hours).await? {
                         let time_block = TimeBlock {
                             id: Uuid::new_v4().to_string(),
                             deadline_id: deadline.id.clone(),
-                            start_time: block_start,
-                            end_time: block_end,
-                            planned_duration: chrono::Duration::hours(daily_hours as i64),
+                            start_time: alternative_slot.start_time,
+                            end_time: alternative_slot.end_time,
+                            planned_duration: chrono::Duration::minutes((daily_hours * 60.0).round() as i64),
                             actual_duration: None,
                             productivity_score: None,
                             notes: None,
@@ -1813,32 +2281,9 @@ impl CalendarAdapter {
                         
                         time_blocks.push(time_block);
                         remaining_hours -= daily_hours;
-                    } else {
-                        // Try to find alternative time slots
-                        if let Some(alternative_slot) = self.find_alternative_time_slot(current_date, daily_hours).await? {
-                            let time_block = TimeBlock {
-                                id: Uuid::new_v4().to_string(),
-                                deadline_id: deadline.id.clone(),
-                                start_time: alternative_slot.start_time,
-                                end_time: alternative_slot.end_time,
-                                planned_duration: chrono::Duration::hours(daily_hours as i64),
-                                actual_duration: None,
-                                productivity_score: None,
-                                notes: None,
-                                calendar_event_id: None,
-                                status: TimeBlockStatus::Planned,
-                                focus_mode: true,
-                                interruptions: Vec::new(),
-                            };
-                            
-                            time_blocks.push(time_block);
-                            remaining_hours -= daily_hours;
-                        }
                     }
                 }
             }
-            
-            current_date += chrono::Duration::days(1);
         }
         
         Ok(time_blocks)
@@ -1846,7 +2291,7 @@ impl CalendarAdapter {
     
     /// Check for conflicts with existing time blocks
     async fn check_time_block_conflicts(&self, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> Result<Vec<CalendarEvent>> {
-        let existing_events = self.list_events("default", Some(start_time), Some(end_time)).await?;
+        let existing_events = self.list_events(DEFAULT_CALENDAR_ID, Some(start_time), Some(end_time)).await?;
         
         let conflicts = existing_events
             .into_iter()
@@ -1866,8 +2311,9 @@ impl CalendarAdapter {
     
     /// Find alternative time slot for a given day
     async fn find_alternative_time_slot(&self, date: chrono::NaiveDate, hours_needed: f32) -> Result<Option<TimeSlot>> {
-        let day_start = date.and_hms_opt(8, 0, 0).unwrap().and_utc();
-        let day_end = date.and_hms_opt(18, 0, 0).unwrap().and_utc();
+        let (Some(day_start), Some(day_end)) = (self.time_zone.at(date, 8, 0), self.time_zone.at(date, 18, 0)) else {
+            return Ok(None);
+        };
         
         let availability_request = AvailabilityRequest {
             attendees: vec!["self".to_string()],
@@ -1904,138 +2350,175 @@ impl CalendarAdapter {
             attendees: vec![],
             all_day: false,
             recurring: false,
-            calendar_id: "default".to_string(),
+            calendar_id: DEFAULT_CALENDAR_ID.to_string(),
         };
         
-        self.create_event("default", &event).await
+        self.create_event(DEFAULT_CALENDAR_ID, &event).await
     }
     
-    /// Update time block with actual time spent
+    /// Record the time actually spent in a time block and mark it completed
     pub async fn update_time_block_completion(&self, time_block_id: &str, actual_duration: chrono::Duration, productivity_score: f32, notes: Option<String>) -> Result<()> {
-        // In a real implementation, this would update the time block in a database
-        println!("Updated time block {} with actual duration: {} hours, productivity: {:.1}/5.0", 
-                 time_block_id, 
-                 actual_duration.num_hours(),
-                 productivity_score);
-        
-        if let Some(notes) = notes {
-            println!("Notes: {}", notes);
-        }
-        
+        self.deadline_store.modify_time_block(time_block_id, |block| {
+            block.actual_duration = Some(actual_duration);
+            block.productivity_score = Some(productivity_score);
+            if notes.is_some() {
+                block.notes = notes;
+            }
+            block.status = TimeBlockStatus::Completed;
+        })?;
         Ok(())
     }
     
+    /// Move the end of a time block's calendar event, e.g. after a focus session overran it
+    pub async fn extend_time_block_event(&self, event_id: &str, end_time: DateTime<Utc>) -> Result<CalendarEvent> {
+        let mut event = self.get_event(DEFAULT_CALENDAR_ID, event_id).await?;
+        event.end_time = end_time;
+        self.update_event(DEFAULT_CALENDAR_ID, event_id, &event).await
+    }
+    
     /// Calculate current deadline status
     fn calculate_deadline_status(&self, deadline: &Deadline) -> DeadlineStatus {
-        let now = Utc::now();
-        
-        if deadline.completed_hours >= deadline.estimated_hours {
-            return DeadlineStatus::Completed;
-        }
-        
-        if now > deadline.due_date {
-            return DeadlineStatus::Overdue;
-        }
-        
-        if deadline.completed_hours > 0.0 {
-            DeadlineStatus::InProgress
-        } else {
-            DeadlineStatus::NotStarted
-        }
+        deadline_status(deadline, Utc::now())
     }
     
-    /// Get all deadlines with their current status
+    /// Stored deadlines, only those with `filter`'s status when given
     pub async fn get_deadlines(&self, filter: Option<DeadlineStatus>) -> Result<Vec<Deadline>> {
-        // In a real implementation, this would query a database
-        // For now, return an empty vector
-        let mut deadlines = Vec::new();
-        
-        // Mock deadline for demonstration
-        let mock_deadline = Deadline {
-            id: "deadline-1".to_string(),
-            title: "Complete project documentation".to_string(),
-            description: Some("Finish all technical documentation for the project".to_string()),
-            due_date: Utc::now() + chrono::Duration::days(7),
-            created_date: Utc::now() - chrono::Duration::days(3),
-            priority: DeadlinePriority::High,
-            status: DeadlineStatus::InProgress,
-            estimated_hours: 20.0,
-            completed_hours: 8.0,
-            category: "Documentation".to_string(),
-            tags: vec!["technical".to_string(), "urgent".to_string()],
-            dependencies: vec![],
-            assignee: Some("user@example.com".to_string()),
-            project_id: Some("proj-123".to_string()),
-            reminder_settings: ReminderSettings {
-                enabled: true,
-                advance_notifications: vec![
-                    ReminderSchedule {
-                        time_before_deadline: chrono::Duration::hours(24),
-                        message: "Deadline approaching in 24 hours".to_string(),
-                        urgent: true,
-                    },
-                    ReminderSchedule {
-                        time_before_deadline: chrono::Duration::hours(72),
-                        message: "Deadline in 3 days".to_string(),
-                        urgent: false,
-                    },
-                ],
-                notification_channels: vec![NotificationChannel::Email, NotificationChannel::CalendarAlert],
-                escalation_enabled: true,
-                escalation_delay_hours: 4,
-            },
-            time_blocks: vec![],
-            progress_milestones: vec![],
-        };
-        
-        if filter.is_none() || filter.as_ref().unwrap() == &mock_deadline.status {
-            deadlines.push(mock_deadline);
-        }
-        
-        Ok(deadlines)
+        self.deadline_store.list(filter.as_ref())
     }
     
-    /// Reschedule time blocks for a deadline
+    /// Replace a deadline's planned time blocks with new ones for the work that remains
     pub async fn reschedule_time_blocks(&self, deadline_id: &str) -> Result<Vec<TimeBlock>> {
-        // Get the deadline
-        let deadlines = self.get_deadlines(None).await?;
-        let deadline = deadlines.into_iter()
-            .find(|d| d.id == deadline_id)
-            .ok_or_else(|| anyhow::anyhow!("Deadline not found"))?;
+        let deadline = self.deadline_store.get(deadline_id)?;
         
         // Delete existing time blocks from calendar
         for time_block in &deadline.time_blocks {
             if let Some(event_id) = &time_block.calendar_event_id {
-                let _ = self.delete_event("default", event_id).await;
+                let _ = self.delete_event(DEFAULT_CALENDAR_ID, event_id).await;
             }
         }
         
         // Generate new time blocks
-        let new_time_blocks = self.generate_automatic_time_blocks(&deadline).await?;
+        let mut new_time_blocks = self.generate_automatic_time_blocks(&deadline).await?;
         
         // Create calendar events for new time blocks
-        for time_block in &new_time_blocks {
-            let _ = self.create_time_block_event(&deadline, time_block).await;
+        for time_block in &mut new_time_blocks {
+            if let Ok(event) = self.create_time_block_event(&deadline, time_block).await {
+                time_block.calendar_event_id = Some(event.id);
+            }
         }
         
+        // Finished blocks stay as the record of work done
+        let kept = new_time_blocks.clone();
+        self.deadline_store.modify(&deadline.id, move |stored| {
+            stored.time_blocks.retain(|b| matches!(b.status, TimeBlockStatus::Completed));
+            stored.time_blocks.extend(kept);
+            Ok(())
+        })?;
         Ok(new_time_blocks)
     }
-    
+
+    /// Bring a deadline's open time blocks in line with their calendar events: blocks
+    /// moved in a calendar app take the new times, blocks whose event was deleted are cancelled
+    pub async fn sync_time_blocks(&self, deadline_id: &str) -> Result<TimeBlockSync> {
+        let mut deadline = self.deadline_store.get(deadline_id)?;
+
+        let mut changed = Vec::new();
+        let mut unchecked = 0;
+        for block in deadline.time_blocks.iter_mut().filter(|b| is_open_block(b)) {
+            let Some(event_id) = block.calendar_event_id.clone() else { continue };
+            let previous_start = block.start_time;
+            match self.find_event(DEFAULT_CALENDAR_ID, &event_id).await {
+                Ok(event) => match reconcile_time_block(block, event.as_ref()) {
+                    BlockChange::Unchanged => {}
+                    change => changed.push((block.clone(), change, previous_start)),
+                },
+                Err(e) => {
+                    log::warn!("could not check time block {}: {:#}", block.id, e);
+                    unchecked += 1;
+                }
+            }
+        }
+
+        if !changed.is_empty() {
+            let updates: Vec<TimeBlock> = changed.iter().map(|(block, _, _)| block.clone()).collect();
+            deadline = self.deadline_store.modify(&deadline.id, move |stored| {
+                for update in updates {
+                    if let Some(block) = stored.time_blocks.iter_mut().find(|b| b.id == update.id) {
+                        *block = update;
+                    }
+                }
+                Ok(())
+            })?;
+        }
+
+        let mut moved = Vec::new();
+        let mut cancelled = Vec::new();
+        for (block, change, previous_start) in changed {
+            match change {
+                BlockChange::Moved => moved.push(MovedBlock {
+                    block_id: block.id,
+                    previous_start,
+                    start_time: block.start_time,
+                    end_time: block.end_time,
+                }),
+                BlockChange::Deleted => cancelled.push(block.id),
+                BlockChange::Unchanged => {}
+            }
+        }
+
+        Ok(TimeBlockSync {
+            planned_hours: planned_hours(&deadline, Utc::now()),
+            estimated_hours: deadline.estimated_hours,
+            deadline_id: deadline.id,
+            title: deadline.title,
+            moved,
+            cancelled,
+            unchecked,
+        })
+    }
+
+    /// Schedule new time blocks covering `hours` of work, keeping the deadline's existing blocks
+    pub async fn top_up_time_blocks(&self, deadline_id: &str, hours: f32) -> Result<Vec<TimeBlock>> {
+        let deadline = self.deadline_store.get(deadline_id)?;
+
+        // Blocks are generated for what's left after completed hours, so pretend only `hours` remain
+        let mut remaining = deadline.clone();
+        remaining.completed_hours = (deadline.estimated_hours - hours).max(0.0);
+        let mut new_time_blocks = self.generate_automatic_time_blocks(&remaining).await?;
+
+        for time_block in &mut new_time_blocks {
+            if let Ok(event) = self.create_time_block_event(&deadline, time_block).await {
+                time_block.calendar_event_id = Some(event.id);
+            }
+        }
+
+        let added = new_time_blocks.clone();
+        self.deadline_store.modify(&deadline.id, move |stored| {
+            stored.time_blocks.extend(added);
+            Ok(())
+        })?;
+        Ok(new_time_blocks)
+    }
+
     // Progress Tracking System Implementation
     
-    /// Update progress for a deadline
+    /// Record the hours completed on a deadline, updating its milestones and status
     pub async fn update_deadline_progress(&self, deadline_id: &str, completed_hours: f32, notes: Option<String>) -> Result<DeadlineMetrics> {
-        // Get the deadline
-        let deadlines = self.get_deadlines(None).await?;
-        let deadline = deadlines.into_iter()
-            .find(|d| d.id == deadline_id)
-            .ok_or_else(|| anyhow::anyhow!("Deadline not found"))?;
+        let mut deadline = self.deadline_store.get(deadline_id)?;
         
         // Calculate metrics
         let metrics = self.calculate_deadline_metrics(&deadline, completed_hours).await?;
         
         // Update milestone progress
-        let _updated_milestones = self.update_milestone_progress(&deadline, completed_hours).await?;
+        let updated_milestones = self.update_milestone_progress(&deadline, completed_hours).await?;
+        deadline = self.deadline_store.modify(&deadline.id, |stored| {
+            stored.completed_hours = completed_hours;
+            stored.progress_milestones = updated_milestones;
+            if !matches!(stored.status, DeadlineStatus::OnHold | DeadlineStatus::Cancelled) {
+                stored.status = self.calculate_deadline_status(stored);
+            }
+            Ok(())
+        })?;
         
         // Log progress update
         println!("Progress updated for deadline '{}': {:.1}/{:.1} hours ({:.1}% complete)", 
@@ -2165,50 +2648,7 @@ impl CalendarAdapter {
     
     /// Identify risk indicators for the deadline
     async fn identify_risk_indicators(&self, deadline: &Deadline, completion_rate: f32, time_efficiency: f32, time_remaining: chrono::Duration) -> Result<Vec<RiskIndicator>> {
-        let mut risks = Vec::new();
-        
-        // Time shortage risk
-        if time_remaining.num_hours() < 24 && completion_rate < 0.9 {
-            risks.push(RiskIndicator {
-                indicator_type: RiskType::TimeShortage,
-                severity: RiskLevel::High,
-                description: "Less than 24 hours remaining with significant work incomplete".to_string(),
-                suggested_action: "Consider extending deadline or reducing scope".to_string(),
-                deadline_impact: 0.8,
-            });
-        } else if completion_rate < 0.5 && time_remaining.num_days() < 3 {
-            risks.push(RiskIndicator {
-                indicator_type: RiskType::TimeShortage,
-                severity: RiskLevel::Medium,
-                description: "Progress is behind schedule with limited time remaining".to_string(),
-                suggested_action: "Increase daily work allocation or request assistance".to_string(),
-                deadline_impact: 0.6,
-            });
-        }
-        
-        // Efficiency risk
-        if time_efficiency < 0.7 {
-            risks.push(RiskIndicator {
-                indicator_type: RiskType::ResourceConstraint,
-                severity: RiskLevel::Medium,
-                description: "Work efficiency is below expected levels".to_string(),
-                suggested_action: "Review time blocks and eliminate distractions".to_string(),
-                deadline_impact: 0.4,
-            });
-        }
-        
-        // Dependency risk
-        if !deadline.dependencies.is_empty() {
-            risks.push(RiskIndicator {
-                indicator_type: RiskType::DependencyDelay,
-                severity: RiskLevel::Low,
-                description: "Deadline has dependencies that may cause delays".to_string(),
-                suggested_action: "Monitor dependency status and create contingency plans".to_string(),
-                deadline_impact: 0.3,
-            });
-        }
-        
-        Ok(risks)
+        Ok(deadline_risk_indicators(deadline, completion_rate, time_efficiency, time_remaining))
     }
     
     /// Generate progress recommendations
@@ -2280,6 +2720,11 @@ impl CalendarAdapter {
     
     /// Get visual progress indicators for display
     pub async fn get_progress_visualization(&self, deadline_id: &str) -> Result<ProgressVisualization> {
+        // Blocks moved or deleted in a calendar app would otherwise skew the numbers
+        if let Err(e) = self.sync_time_blocks(deadline_id).await {
+            log::warn!("could not sync time blocks with the calendar: {:#}", e);
+        }
+
         let deadlines = self.get_deadlines(None).await?;
         let deadline = deadlines.into_iter()
             .find(|d| d.id == deadline_id)
@@ -2446,13 +2891,16 @@ impl CalendarAdapter {
     ) -> Result<Vec<SmartSchedulingSuggestion>> {
         // First get basic availability using existing logic
         let basic_recommendations = self.find_meeting_time(meeting_request).await?;
+        let latest_end = meeting_request.latest_start + chrono::Duration::minutes(meeting_request.duration_minutes as i64);
+        let events = self.list_events(DEFAULT_CALENDAR_ID, Some(meeting_request.earliest_start), Some(latest_end)).await?;
         
         // Prepare context for AI analysis
         let context_prompt = self.build_scheduling_context_prompt(meeting_request, context, &basic_recommendations);
         
         // Extract AI conversation temporarily to avoid borrow conflicts
         let mut ai_conversation = self.ai_conversation.take()
-            .ok_or_else(|| anyhow::anyhow!("AI conversation not configured"))?;
+            .ok_or_else(|| anyhow::anyhow!("AI conversation not configured"))?
+            .into_inner();
         
         // Get AI insights for each recommendation
         let mut smart_suggestions = Vec::new();
@@ -2485,20 +2933,10 @@ impl CalendarAdapter {
                 .unwrap_or_else(|_| "AI analysis unavailable".to_string());
             
             let ai_insights = vec![ai_response.clone()]; // Simplified parsing for now
-            let optimization_factors = vec![
-                OptimizationFactor {
-                    factor_type: "Time efficiency".to_string(),
-                    weight: 0.8,
-                    value: 0.7,
-                    explanation: "Optimal time slot selection".to_string(),
-                },
-                OptimizationFactor {
-                    factor_type: "Participant availability".to_string(),
-                    weight: 0.9,
-                    value: 0.8,
-                    explanation: "Maximum participant availability".to_string(),
-                }
-            ];
+            let optimization_factors = confidence_factors(&basic_rec.time_slot, &events, context, &self.time_zone);
+            let free_share = basic_rec.attendee_availability.iter().filter(|a| !matches!(a.status, AvailabilityStatus::Busy)).count() as f32
+                / basic_rec.attendee_availability.len().max(1) as f32;
+            let confidence_score = confidence_from(&optimization_factors) * free_share;
             let impact_analysis = ImpactAnalysis {
                 productivity_impact: 0.8,
                 participant_satisfaction: 0.7,
@@ -2511,7 +2949,7 @@ impl CalendarAdapter {
             let smart_suggestion = SmartSchedulingSuggestion {
                 suggestion_id: format!("smart-{}", index + 1),
                 suggested_time: basic_rec.time_slot.clone(),
-                confidence_score: basic_rec.confidence_score + 0.1, // Enhanced confidence with AI insights
+                confidence_score,
                 reasoning: vec![
                     basic_rec.reasoning.clone(),
                     "Enhanced with AI insights".to_string()
@@ -2526,7 +2964,7 @@ impl CalendarAdapter {
         }
         
         // Put AI conversation back
-        self.ai_conversation = Some(ai_conversation);
+        self.ai_conversation = Some(tokio::sync::Mutex::new(ai_conversation));
         
         // Sort by enhanced confidence score
         smart_suggestions.sort_by(|a, b| b.confidence_score.partial_cmp(&a.confidence_score).unwrap());
@@ -2572,7 +3010,7 @@ impl CalendarAdapter {
             participant_context
         );
         
-        let ai_conversation = self.ai_conversation.as_mut()
+        let ai_conversation = self.ai_conversation.as_mut().map(|engine| engine.get_mut())
             .ok_or_else(|| anyhow::anyhow!("AI conversation not configured"))?;
         
         let ai_response = ai_conversation.send_message(agenda_prompt).await;
@@ -2586,6 +3024,30 @@ impl CalendarAdapter {
         Ok(agenda)
     }
     
+    /// Ask the attached AI conversation engine for an agenda of `request`. `context` holds
+    /// related notes to draw topics and preparation material from. Item durations are
+    /// scaled to add up to the meeting's length.
+    pub async fn generate_meeting_agenda(&self, request: &MeetingRequest, context: Option<&str>) -> Result<MeetingAgenda> {
+        let engine = self.ai_conversation.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("AI conversation not configured"))?;
+
+        let mut attendees = request.required_attendees.clone();
+        attendees.extend(request.optional_attendees.iter().map(|a| format!("{} (optional)", a)));
+        let duration = request.duration_minutes.to_string();
+        let attendees = if attendees.is_empty() { "not specified".to_string() } else { attendees.join(", ") };
+        let prompt = crate::prompts::render("agenda", &[
+            ("title", &request.title),
+            ("duration_minutes", &duration),
+            ("attendees", &attendees),
+            ("description", request.description.as_deref().unwrap_or("none")),
+            ("related_notes", context.filter(|c| !c.trim().is_empty()).unwrap_or("none")),
+        ]);
+
+        let response = engine.lock().await.send_message(prompt).await
+            .map_err(|e| anyhow::anyhow!("Failed to generate the agenda: {}", e))?;
+        agenda_from_response(&response, request)
+    }
+
     /// Provide AI-enhanced conflict resolution with intelligent alternatives
     pub async fn ai_enhanced_conflict_resolution(
         &mut self,
@@ -2613,49 +3075,40 @@ impl CalendarAdapter {
             });
         }
         
-        let user_context = self.format_user_context(context);
-        
-        let ai_conversation = self.ai_conversation.as_mut()
+        self.analyze_conflicts_with_ai(&basic_conflicts, context).await
+    }
+
+    /// Ask the attached AI conversation engine how to resolve `conflicts`, given the
+    /// participants' profiles and the optimization goals in `context`. The reply is
+    /// validated against the requested JSON shape and its solutions ranked by success
+    /// probability. When the model fails or its reply doesn't validate, the solutions
+    /// come from the conflict types alone (ids starting `fallback-`).
+    pub async fn analyze_conflicts_with_ai(&self, conflicts: &[ConflictInfo], context: &AiSchedulingContext) -> Result<SmartConflictResolution> {
+        let engine = self.ai_conversation.as_ref()
             .ok_or_else(|| anyhow::anyhow!("AI conversation not configured"))?;
-        
-        let conflict_prompt = format!(
-            "Analyze this calendar conflict and provide intelligent resolution strategies:\n\n\
-            Proposed Meeting: {}\n\
-            Time: {} to {}\n\
-            Importance: High\n\
-            Conflicts Found: {}\n\n\
-            User Context:\n{}\n\n\
-            Please provide:\n\
-            1. Conflict severity assessment\n\
-            2. Intelligent resolution strategies with success probabilities\n\
-            3. Stakeholder impact analysis\n\
-            4. Negotiation strategies for each participant\n\
-            5. Compromise options that minimize disruption\n\
-            6. Step-by-step implementation guidance\n\n\
-            Prioritize solutions that respect user preferences and energy patterns.",
-            proposed_event.title,
-            proposed_event.start_time.format("%Y-%m-%d %H:%M UTC"),
-            proposed_event.end_time.format("%Y-%m-%d %H:%M UTC"),
-            basic_conflicts.len(),
-            user_context
-        );
-        
-        let ai_response = ai_conversation.send_message(conflict_prompt).await
-            .unwrap_or_else(|_| "AI conflict analysis unavailable".to_string());
-        
-        let conflict_analysis = self.analyze_conflict_complexity(&basic_conflicts, context);
-        let ai_solutions = self.parse_ai_conflict_solutions(&ai_response, &basic_conflicts).await?;
-        let stakeholder_impact = self.assess_stakeholder_impact(&basic_conflicts, context).await?;
-        let negotiation_strategies = self.extract_negotiation_strategies(&ai_response);
-        let compromise_options = self.extract_compromise_options(&ai_response);
-        
+        let mut conflict_analysis = self.analyze_conflict_complexity(conflicts, context);
+        let stakeholder_impact = self.assess_stakeholder_impact(conflicts, context).await?;
+
+        let prompt = conflict_prompt(conflicts, context, &self.format_user_context(context));
+        let reply = match engine.lock().await.send_message(prompt).await {
+            Ok(response) => conflict_reply_from_response(&response),
+            Err(e) => Err(anyhow::anyhow!("AI conflict analysis unavailable: {}", e)),
+        };
+        let reply = reply.unwrap_or_else(|e| {
+            log::warn!("{:#}; falling back to rule-based conflict solutions", e);
+            fallback_conflict_reply(conflicts)
+        });
+        if let Some(severity) = reply.severity {
+            conflict_analysis.conflict_severity = severity;
+        }
+
         Ok(SmartConflictResolution {
             resolution_id: Uuid::new_v4().to_string(),
             conflict_analysis,
-            ai_recommended_solutions: ai_solutions,
+            ai_recommended_solutions: reply.solutions,
             stakeholder_impact,
-            negotiation_strategies,
-            compromise_options,
+            negotiation_strategies: reply.negotiation_strategies,
+            compromise_options: reply.compromise_options,
         })
     }
     
@@ -2703,7 +3156,7 @@ impl CalendarAdapter {
             context.optimization_goals
         );
         
-        let ai_conversation = self.ai_conversation.as_mut()
+        let ai_conversation = self.ai_conversation.as_mut().map(|engine| engine.get_mut())
             .ok_or_else(|| anyhow::anyhow!("AI conversation not configured"))?;
         
         let ai_response = ai_conversation.send_message(time_blocking_prompt).await
@@ -2732,10 +3185,10 @@ impl CalendarAdapter {
         meeting_history: &[CalendarEvent],
         participant_feedback: Option<&str>,
     ) -> Result<MeetingOptimization> {
-        let meeting = self.get_event("default", meeting_id).await?;
+        let meeting = self.get_event(DEFAULT_CALENDAR_ID, meeting_id).await?;
         let meeting_history_formatted = self.format_meeting_history(meeting_history);
         
-        let ai_conversation = self.ai_conversation.as_mut()
+        let ai_conversation = self.ai_conversation.as_mut().map(|engine| engine.get_mut())
             .ok_or_else(|| anyhow::anyhow!("AI conversation not configured"))?;
         
         let optimization_prompt = format!(
@@ -3057,46 +3510,6 @@ impl CalendarAdapter {
         }
     }
     
-    async fn parse_ai_conflict_solutions(&self, ai_response: &str, _conflicts: &[ConflictInfo]) -> Result<Vec<ConflictSolution>> {
-        // Parse AI response for conflict solutions
-        let mut solutions = Vec::new();
-        
-        // Simple parsing - look for solution patterns
-        let lines: Vec<&str> = ai_response.lines().collect();
-        for (i, line) in lines.iter().enumerate() {
-            if line.to_lowercase().contains("solution") || line.to_lowercase().contains("strategy") {
-                solutions.push(ConflictSolution {
-                    solution_id: format!("sol-{}", i),
-                    solution_type: SolutionType::Reschedule, // Default
-                    description: line.trim().to_string(),
-                    implementation_steps: vec!["Review solution details".to_string(), "Execute resolution".to_string()],
-                    success_probability: 0.8,
-                    participant_satisfaction_impact: 0.7,
-                    resource_requirements: vec!["Time for rescheduling".to_string()],
-                });
-            }
-        }
-        
-        // Fallback solution if parsing fails
-        if solutions.is_empty() {
-            solutions.push(ConflictSolution {
-                solution_id: "default-solution".to_string(),
-                solution_type: SolutionType::Reschedule,
-                description: "Reschedule conflicting meeting to next available slot".to_string(),
-                implementation_steps: vec![
-                    "Identify alternative time slots".to_string(),
-                    "Notify all participants".to_string(),
-                    "Update calendar events".to_string(),
-                ],
-                success_probability: 0.75,
-                participant_satisfaction_impact: 0.6,
-                resource_requirements: vec!["Coordination time", "Calendar management"].iter().map(|s| s.to_string()).collect(),
-            });
-        }
-        
-        Ok(solutions)
-    }
-    
     async fn assess_stakeholder_impact(&self, conflicts: &[ConflictInfo], _context: &AiSchedulingContext) -> Result<Vec<StakeholderImpact>> {
         let mut impacts = Vec::new();
         
@@ -3122,22 +3535,6 @@ impl CalendarAdapter {
         Ok(impacts)
     }
     
-    fn extract_negotiation_strategies(&self, ai_response: &str) -> Vec<String> {
-        ai_response
-            .lines()
-            .filter(|line| line.to_lowercase().contains("negotiat") || line.to_lowercase().contains("discuss"))
-            .map(|line| line.trim().to_string())
-            .collect()
-    }
-    
-    fn extract_compromise_options(&self, ai_response: &str) -> Vec<String> {
-        ai_response
-            .lines()
-            .filter(|line| line.to_lowercase().contains("compromise") || line.to_lowercase().contains("alternative"))
-            .map(|line| line.trim().to_string())
-            .collect()
-    }
-    
     async fn parse_ai_time_blocks(&self, _ai_response: &str, deadline: &Deadline) -> Result<Vec<SuggestedTimeBlock>> {
         let mut blocks = Vec::new();
         let remaining_hours = deadline.estimated_hours - deadline.completed_hours;
@@ -3289,6 +3686,842 @@ impl CalendarAdapter {
     }
 }
 
+/// The contents of every `calendar-data` element in a CalDAV multistatus response, whatever
+/// namespace prefix the server gives it, with CDATA sections and XML escapes undone
+fn calendar_data_blocks(xml: &str) -> Vec<String> {
+    xml_elements(xml, "calendar-data")
+        .into_iter()
+        .filter(|(tag, _)| !tag.ends_with('/'))
+        .map(|(_, content)| xml_text(content))
+        .collect()
+}
+
+/// The elements of `xml` with this local name, whatever their namespace prefix, as their
+/// tag (name and attributes) and content. Self-closing elements have empty content, and
+/// an element of the same name nested inside a match isn't returned on its own.
+fn xml_elements<'a>(xml: &'a str, local: &str) -> Vec<(&'a str, &'a str)> {
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('>') else { break };
+        let tag = &after[..close];
+        rest = &after[close + 1..];
+        let name = tag.split_whitespace().next().unwrap_or("").trim_end_matches('/');
+        if tag.starts_with(['/', '?', '!']) || name.rsplit(':').next() != Some(local) {
+            continue;
+        }
+        if tag.ends_with('/') {
+            elements.push((tag, ""));
+            continue;
+        }
+        let end_tag = format!("</{}>", name);
+        let Some(end) = rest.find(&end_tag) else { break };
+        elements.push((tag, &rest[..end]));
+        rest = &rest[end + end_tag.len()..];
+    }
+    elements
+}
+
+/// The trimmed text of the first element with this local name
+fn xml_value(xml: &str, local: &str) -> Option<String> {
+    xml_elements(xml, local).first()
+        .map(|(_, content)| xml_text(content).trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+fn xml_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    ['"', '\''].into_iter().find_map(|quote| {
+        let start = tag.find(&format!("{}={}", name, quote))? + name.len() + 2;
+        let end = tag[start..].find(quote)?;
+        Some(&tag[start..start + end])
+    })
+}
+
+/// The href inside a property such as `current-user-principal`
+fn nested_href(xml: &str, property: &str) -> Option<String> {
+    xml_elements(xml, property).iter().find_map(|(_, content)| xml_value(content, "href"))
+}
+
+/// Properties asked of each collection in the calendar home
+const CALENDAR_PROPS: &str = "<D:displayname/><D:resourcetype/><D:current-user-privilege-set/>\
+    <C:calendar-description/><C:supported-calendar-component-set/>";
+
+/// The event calendars in a PROPFIND multistatus of a calendar home, with their hrefs as
+/// ids. Collections that aren't calendars, or only hold tasks, are left out.
+fn parse_calendar_collections(xml: &str) -> Vec<CalendarList> {
+    let mut calendars = Vec::new();
+    for (_, response) in xml_elements(xml, "response") {
+        let Some(href) = xml_value(response, "href") else { continue };
+        // Only properties the server found; the others come back under a 404 propstat
+        let propstats = xml_elements(response, "propstat");
+        let props: String = if propstats.is_empty() {
+            response.to_string()
+        } else {
+            propstats.iter()
+                .filter(|(_, propstat)| xml_value(propstat, "status").is_some_and(|status| status.contains(" 200")))
+                .map(|(_, propstat)| *propstat)
+                .collect()
+        };
+
+        let is_calendar = xml_elements(&props, "resourcetype").iter()
+            .any(|(_, types)| !xml_elements(types, "calendar").is_empty());
+        let components: Vec<&str> = xml_elements(&props, "comp").iter()
+            .filter_map(|(tag, _)| xml_attribute(tag, "name"))
+            .collect();
+        if !is_calendar || (!components.is_empty() && !components.iter().any(|c| c.eq_ignore_ascii_case("VEVENT"))) {
+            continue;
+        }
+
+        let name = xml_value(&props, "displayname").unwrap_or_else(|| {
+            href.trim_end_matches('/').rsplit('/').next().unwrap_or(&href).to_string()
+        });
+        let writable = match xml_elements(&props, "current-user-privilege-set").first() {
+            Some((_, privileges)) => ["all", "write", "write-content"].iter().any(|p| !xml_elements(privileges, p).is_empty()),
+            None => true,
+        };
+        calendars.push(CalendarList {
+            id: href,
+            name,
+            description: xml_value(&props, "calendar-description"),
+            primary: false,
+            access_role: if writable { "owner" } else { "reader" }.to_string(),
+        });
+    }
+    calendars
+}
+
+/// `https://host:port` of a URL
+/// A request that got no response: DNS failures are told apart from other connection
+/// errors by the causes reqwest wraps, which only say so in their text
+fn diagnose_request_error(url: &str, error: &reqwest::Error) -> ConnectionDiagnosis {
+    let detail = std::iter::successors(Some(error as &dyn std::error::Error), |e| e.source())
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join(": ");
+    let lower = detail.to_lowercase();
+    if error.is_timeout() {
+        ConnectionDiagnosis::Timeout { detail }
+    } else if lower.contains("dns error") || lower.contains("failed to lookup address") || lower.contains("name or service not known") {
+        let host = url_origin(url).split("://").last().unwrap_or(url).to_string();
+        ConnectionDiagnosis::DnsFailure { host, detail }
+    } else {
+        ConnectionDiagnosis::Unreachable { detail }
+    }
+}
+
+/// A response to PROPFIND on the server root. CalDAV servers answer with 207; a web page
+/// or 404/405 means `server_url` points somewhere else.
+fn diagnose_response(status: u16, dav: &str, content_type: &str, body: &str) -> ConnectionDiagnosis {
+    match status {
+        401 | 403 => ConnectionDiagnosis::AuthFailed { status },
+        500..=599 => ConnectionDiagnosis::ServerError { status, detail: body.chars().take(200).collect() },
+        207 if body.contains("multistatus") => ConnectionDiagnosis::Connected { status, calendar_access: dav.contains("calendar-access") },
+        200..=299 => ConnectionDiagnosis::NotCalDav {
+            status,
+            detail: format!("answered with {} instead of a WebDAV multistatus", if content_type.is_empty() { "an empty content type" } else { content_type }),
+        },
+        _ => ConnectionDiagnosis::NotCalDav { status, detail: "PROPFIND isn't supported at this URL".to_string() },
+    }
+}
+
+fn url_origin(url: &str) -> &str {
+    let after_scheme = url.find("://").map_or(0, |i| i + 3);
+    match url[after_scheme..].find('/') {
+        Some(slash) => &url[..after_scheme + slash],
+        None => url,
+    }
+}
+
+/// An href from a multistatus response as a URL: absolute hrefs as they are, paths on the
+/// host of `base`, anything else relative to `base`
+fn resolve_href(base: &str, href: &str) -> String {
+    if href.is_empty() {
+        base.to_string()
+    } else if href.contains("://") {
+        href.to_string()
+    } else if href.starts_with('/') {
+        format!("{}{}", url_origin(base), href)
+    } else {
+        format!("{}/{}", base.trim_end_matches('/'), href)
+    }
+}
+
+/// Element text with CDATA sections taken literally and entities decoded everywhere else
+fn xml_text(raw: &str) -> String {
+    let mut text = String::new();
+    let mut rest = raw;
+    while let Some(start) = rest.find("<![CDATA[") {
+        text.push_str(&decode_entities(&rest[..start]));
+        let data = &rest[start + 9..];
+        let end = data.find("]]>").unwrap_or(data.len());
+        text.push_str(&data[..end]);
+        rest = data.get(end + 3..).unwrap_or("");
+    }
+    text.push_str(&decode_entities(rest));
+    text
+}
+
+fn decode_entities(raw: &str) -> String {
+    let mut text = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(amp) = rest.find('&') {
+        text.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else { break };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                text.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                text.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+/// Local weekdays from today up to (not including) the day something is due, in `zone`
+fn block_days(zone: &TimeZone, now: DateTime<Utc>, due: DateTime<Utc>) -> Vec<chrono::NaiveDate> {
+    let last = zone.to_local(due).date();
+    zone.to_local(now)
+        .date()
+        .iter_days()
+        .take_while(|day| *day < last)
+        .filter(|day| !matches!(day.weekday(), chrono::Weekday::Sat | chrono::Weekday::Sun))
+        .collect()
+}
+
+/// A focus block starting at 9am on the zone's wall clock, `hours` long (whole hours)
+fn morning_block(zone: &TimeZone, day: chrono::NaiveDate, hours: f32) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let start = zone.at(day, 9, 0)?;
+    Some((start, start + chrono::Duration::minutes((hours * 60.0).round() as i64)))
+}
+
+/// The JSON `generate_meeting_agenda` asks the model for
+#[derive(Debug, Deserialize)]
+struct AgendaResponse {
+    #[serde(default)]
+    meeting_type: Option<String>,
+    #[serde(default)]
+    objectives: Vec<String>,
+    #[serde(default)]
+    items: Vec<AgendaResponseItem>,
+    #[serde(default)]
+    preparation: Vec<AgendaResponseMaterial>,
+    #[serde(default)]
+    success_criteria: Vec<String>,
+    #[serde(default)]
+    follow_up: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AgendaResponseItem {
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    duration_minutes: f64,
+    #[serde(default, rename = "type")]
+    item_type: Option<String>,
+    #[serde(default)]
+    owner: Option<String>,
+    #[serde(default)]
+    expected_outcome: Option<String>,
+    #[serde(default)]
+    discussion_points: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AgendaResponseMaterial {
+    title: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    required: bool,
+    #[serde(default)]
+    reading_minutes: u32,
+}
+
+fn agenda_item_type(name: Option<&str>) -> AgendaItemType {
+    match name.unwrap_or_default().to_lowercase().as_str() {
+        "decision" => AgendaItemType::Decision,
+        "information" | "update" => AgendaItemType::Information,
+        "action" => AgendaItemType::Action,
+        "review" => AgendaItemType::Review,
+        "brainstorm" | "brainstorming" => AgendaItemType::Brainstorm,
+        "presentation" => AgendaItemType::Presentation,
+        _ => AgendaItemType::Discussion,
+    }
+}
+
+/// Build an agenda from the model's JSON reply, fitting the item durations to the meeting
+pub(crate) fn agenda_from_response(response: &str, request: &MeetingRequest) -> Result<MeetingAgenda> {
+    let parsed: AgendaResponse = crate::llm_json::parse(response)
+        .context("The AI reply was not an agenda in the requested JSON shape")?;
+    if parsed.items.is_empty() {
+        bail!("The AI reply had no agenda items");
+    }
+
+    let meeting_type = parsed.meeting_type
+        .and_then(|name| serde_json::from_value(serde_json::Value::String(name)).ok())
+        .unwrap_or(if request.required_attendees.len() + request.optional_attendees.len() == 1 {
+            MeetingType::OneOnOne
+        } else {
+            MeetingType::ProjectReview
+        });
+    let durations = fit_durations(&parsed.items.iter().map(|item| item.duration_minutes.max(0.0)).collect::<Vec<_>>(), request.duration_minutes);
+    let agenda_items = parsed.items.into_iter().zip(durations).enumerate()
+        .map(|(i, (item, duration_minutes))| AgendaItem {
+            item_id: format!("item-{}", i + 1),
+            title: item.title,
+            description: item.description.filter(|d| !d.trim().is_empty()),
+            duration_minutes,
+            item_type: agenda_item_type(item.item_type.as_deref()),
+            owner: item.owner.filter(|o| !o.trim().is_empty() && o != "null"),
+            prerequisites: Vec::new(),
+            expected_outcome: item.expected_outcome.filter(|o| !o.trim().is_empty()),
+            discussion_points: item.discussion_points,
+        })
+        .collect();
+    let preparation_materials = parsed.preparation.into_iter().enumerate()
+        .map(|(i, material)| PreparationMaterial {
+            material_id: format!("material-{}", i + 1),
+            title: material.title,
+            description: material.description,
+            url: material.url.filter(|u| !u.trim().is_empty()),
+            required: material.required,
+            estimated_reading_time: material.reading_minutes,
+        })
+        .collect();
+
+    Ok(MeetingAgenda {
+        agenda_id: Uuid::new_v4().to_string(),
+        meeting_title: request.title.clone(),
+        meeting_type,
+        duration_minutes: request.duration_minutes,
+        objectives: parsed.objectives,
+        agenda_items,
+        preparation_materials,
+        success_criteria: parsed.success_criteria,
+        follow_up_actions: parsed.follow_up,
+        ai_generated: true,
+        generated_at: Utc::now(),
+    })
+}
+
+/// Whole minutes in proportion to `requested` that add up to `total`. Items the model gave
+/// no duration share the time equally when none has one, and get a minute otherwise.
+fn fit_durations(requested: &[f64], total: u32) -> Vec<u32> {
+    if requested.is_empty() {
+        return Vec::new();
+    }
+    let weights: Vec<f64> = if requested.iter().all(|d| *d == 0.0) {
+        vec![1.0; requested.len()]
+    } else {
+        requested.iter().map(|d| if *d == 0.0 { 1.0 } else { *d }).collect()
+    };
+    let sum: f64 = weights.iter().sum();
+    let exact: Vec<f64> = weights.iter().map(|w| w / sum * total as f64).collect();
+    let mut minutes: Vec<u32> = exact.iter().map(|m| m.floor() as u32).collect();
+    // Hand out the minutes lost to rounding down, largest remainder first
+    let mut order: Vec<usize> = (0..exact.len()).collect();
+    order.sort_by(|a, b| (exact[*b] - exact[*b].floor()).partial_cmp(&(exact[*a] - exact[*a].floor())).unwrap_or(std::cmp::Ordering::Equal));
+    let short = total - minutes.iter().sum::<u32>();
+    for i in order.into_iter().take(short as usize) {
+        minutes[i] += 1;
+    }
+    minutes
+}
+
+/// The agenda as plain text, for the terminal and for event descriptions
+pub fn render_agenda(agenda: &MeetingAgenda) -> String {
+    let mut out = format!("Agenda: {} ({} min)\n", agenda.meeting_title, agenda.duration_minutes);
+    if !agenda.objectives.is_empty() {
+        out.push_str("\nObjectives:\n");
+        for objective in &agenda.objectives {
+            out.push_str(&format!("- {}\n", objective));
+        }
+    }
+    out.push('\n');
+    for (i, item) in agenda.agenda_items.iter().enumerate() {
+        out.push_str(&format!("{}. {} ({} min){}\n", i + 1, item.title, item.duration_minutes,
+            item.owner.as_ref().map(|o| format!(" - {}", o)).unwrap_or_default()));
+        if let Some(description) = &item.description {
+            out.push_str(&format!("   {}\n", description));
+        }
+        for point in &item.discussion_points {
+            out.push_str(&format!("   * {}\n", point));
+        }
+        if let Some(outcome) = &item.expected_outcome {
+            out.push_str(&format!("   Outcome: {}\n", outcome));
+        }
+    }
+    if !agenda.preparation_materials.is_empty() {
+        out.push_str("\nPreparation:\n");
+        for material in &agenda.preparation_materials {
+            let reading = if material.estimated_reading_time > 0 { format!(", ~{} min", material.estimated_reading_time) } else { String::new() };
+            out.push_str(&format!("- {} ({}{}){}{}\n",
+                material.title,
+                if material.required { "required" } else { "optional" },
+                reading,
+                if material.description.is_empty() { String::new() } else { format!(": {}", material.description) },
+                material.url.as_ref().map(|u| format!(" <{}>", u)).unwrap_or_default()));
+        }
+    }
+    if !agenda.success_criteria.is_empty() {
+        out.push_str("\nSuccess criteria:\n");
+        for criterion in &agenda.success_criteria {
+            out.push_str(&format!("- {}\n", criterion));
+        }
+    }
+    if !agenda.follow_up_actions.is_empty() {
+        out.push_str("\nFollow-up:\n");
+        for action in &agenda.follow_up_actions {
+            out.push_str(&format!("- {}\n", action));
+        }
+    }
+    out
+}
+
+/// The prompt for `analyze_conflicts_with_ai`: the conflicting events, the participants'
+/// profiles and the optimization goals, and the JSON shape to answer in
+fn conflict_prompt(conflicts: &[ConflictInfo], context: &AiSchedulingContext, user_context: &str) -> String {
+    let events: Vec<String> = conflicts.iter()
+        .map(|c| format!(
+            "- {} ({} to {}), {}: {:?}",
+            c.conflicting_event.title,
+            c.conflicting_event.start_time.format("%Y-%m-%d %H:%M UTC"),
+            c.conflicting_event.end_time.format("%H:%M UTC"),
+            c.attendee,
+            c.conflict_type,
+        ))
+        .collect();
+    let participants: Vec<String> = context.meeting_context.participants.iter()
+        .map(|p| format!(
+            "- {} <{}>, {}, time zone {}, {} meeting(s) attended",
+            p.name.as_deref().unwrap_or(&p.email),
+            p.email,
+            p.role,
+            p.timezone,
+            p.meeting_history.total_meetings,
+        ))
+        .collect();
+    format!(
+        "Resolve these calendar conflicts for a {:?} meeting of {:?} importance.\n\n\
+        Conflicting events:\n{}\n\n\
+        Participants:\n{}\n\n\
+        Optimization goals: {:?}\n\
+        User context:\n{}\n\n\
+        Respond with JSON only, in this shape:\n\
+        {{\"severity\": \"minor|moderate|significant|critical\",\n\
+        \"solutions\": [{{\"type\": \"reschedule|duration_adjustment|participant_substitution|meeting_split|format_change|delegation\", \
+        \"description\": \"...\", \"steps\": [\"...\"], \"success_probability\": 0.8, \"satisfaction_impact\": 0.7, \"resources\": [\"...\"]}}],\n\
+        \"negotiation_strategies\": [\"...\"],\n\
+        \"compromise_options\": [\"...\"]}}\n\
+        Probabilities and impacts are between 0 and 1. Prefer solutions that respect the participants' time zones and the goals above.",
+        context.meeting_context.meeting_type,
+        context.meeting_context.importance_level,
+        events.join("\n"),
+        if participants.is_empty() { "not known".to_string() } else { participants.join("\n") },
+        context.optimization_goals,
+        user_context,
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct ConflictResponse {
+    #[serde(default)]
+    severity: Option<String>,
+    solutions: Vec<ConflictResponseSolution>,
+    #[serde(default)]
+    negotiation_strategies: Vec<String>,
+    #[serde(default)]
+    compromise_options: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConflictResponseSolution {
+    #[serde(rename = "type", default)]
+    solution_type: Option<String>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    steps: Vec<String>,
+    #[serde(default)]
+    success_probability: Option<f32>,
+    #[serde(default)]
+    satisfaction_impact: Option<f32>,
+    #[serde(default)]
+    resources: Vec<String>,
+}
+
+/// What a conflict analysis reply contributes to a [`SmartConflictResolution`]
+#[derive(Debug, Clone)]
+pub(crate) struct ConflictReply {
+    pub severity: Option<ConflictSeverity>,
+    /// Most likely to succeed first
+    pub solutions: Vec<ConflictSolution>,
+    pub negotiation_strategies: Vec<String>,
+    pub compromise_options: Vec<String>,
+}
+
+fn solution_type(name: &str) -> Option<SolutionType> {
+    let name: String = name.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase();
+    Some(match name.as_str() {
+        "reschedule" => SolutionType::Reschedule,
+        "durationadjustment" => SolutionType::DurationAdjustment,
+        "participantsubstitution" => SolutionType::ParticipantSubstitution,
+        "meetingsplit" => SolutionType::MeetingSplit,
+        "formatchange" => SolutionType::FormatChange,
+        "delegation" => SolutionType::Delegation,
+        _ => return None,
+    })
+}
+
+fn validated_solution(index: usize, raw: ConflictResponseSolution) -> Result<ConflictSolution> {
+    let type_name = raw.solution_type.unwrap_or_default();
+    let solution_type = solution_type(&type_name).with_context(|| format!("solution {} has an unknown type '{}'", index + 1, type_name))?;
+    if raw.description.trim().is_empty() {
+        bail!("solution {} has no description", index + 1);
+    }
+    let probability = raw.success_probability.with_context(|| format!("solution {} has no success_probability", index + 1))?;
+    if !(0.0..=1.0).contains(&probability) {
+        bail!("solution {} has success_probability {}, outside 0 to 1", index + 1, probability);
+    }
+    let satisfaction = raw.satisfaction_impact.unwrap_or(0.5);
+    if !(0.0..=1.0).contains(&satisfaction) {
+        bail!("solution {} has satisfaction_impact {}, outside 0 to 1", index + 1, satisfaction);
+    }
+    Ok(ConflictSolution {
+        solution_id: format!("ai-{}", index + 1),
+        solution_type,
+        description: raw.description.trim().to_string(),
+        implementation_steps: raw.steps.into_iter().filter(|s| !s.trim().is_empty()).collect(),
+        success_probability: probability,
+        participant_satisfaction_impact: satisfaction,
+        resource_requirements: raw.resources,
+    })
+}
+
+/// Parse and validate the model's JSON reply. Solutions that break the schema are
+/// dropped with a warning; a reply without a single valid one is an error.
+pub(crate) fn conflict_reply_from_response(response: &str) -> Result<ConflictReply> {
+    let parsed: ConflictResponse = crate::llm_json::parse(response)
+        .context("The AI reply was not a conflict analysis in the requested JSON shape")?;
+
+    let mut solutions = Vec::new();
+    for (index, raw) in parsed.solutions.into_iter().enumerate() {
+        match validated_solution(index, raw) {
+            Ok(solution) => solutions.push(solution),
+            Err(e) => log::warn!("ignoring AI conflict solution: {}", e),
+        }
+    }
+    if solutions.is_empty() {
+        bail!("The AI reply had no valid conflict solutions");
+    }
+    // Stable, so equally likely solutions keep the model's order
+    solutions.sort_by(|a, b| b.success_probability.partial_cmp(&a.success_probability).unwrap_or(std::cmp::Ordering::Equal));
+
+    let severity = parsed.severity.and_then(|name| match name.trim().to_lowercase().as_str() {
+        "minor" => Some(ConflictSeverity::Minor),
+        "moderate" => Some(ConflictSeverity::Moderate),
+        "significant" => Some(ConflictSeverity::Significant),
+        "critical" => Some(ConflictSeverity::Critical),
+        _ => None,
+    });
+    let non_empty = |items: Vec<String>| items.into_iter().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+    Ok(ConflictReply {
+        severity,
+        solutions,
+        negotiation_strategies: non_empty(parsed.negotiation_strategies),
+        compromise_options: non_empty(parsed.compromise_options),
+    })
+}
+
+/// Solutions from the conflict types alone, for when the model can't be used
+pub(crate) fn fallback_conflict_reply(conflicts: &[ConflictInfo]) -> ConflictReply {
+    let mut solutions = vec![ConflictSolution {
+        solution_id: "fallback-reschedule".to_string(),
+        solution_type: SolutionType::Reschedule,
+        description: "Reschedule conflicting meeting to next available slot".to_string(),
+        implementation_steps: vec![
+            "Identify alternative time slots".to_string(),
+            "Notify all participants".to_string(),
+            "Update calendar events".to_string(),
+        ],
+        success_probability: 0.75,
+        participant_satisfaction_impact: 0.6,
+        resource_requirements: vec!["Coordination time".to_string(), "Calendar management".to_string()],
+    }];
+    if conflicts.iter().any(|c| matches!(c.conflict_type, ConflictType::BufferViolation)) {
+        solutions.push(ConflictSolution {
+            solution_id: "fallback-shorten".to_string(),
+            solution_type: SolutionType::DurationAdjustment,
+            description: "Shorten the meeting to restore the buffer next to the conflicting events".to_string(),
+            implementation_steps: vec!["Trim the agenda".to_string(), "Update the event's end time".to_string()],
+            success_probability: 0.6,
+            participant_satisfaction_impact: 0.7,
+            resource_requirements: Vec::new(),
+        });
+    }
+    ConflictReply { severity: None, solutions, negotiation_strategies: Vec::new(), compromise_options: Vec::new() }
+}
+
+/// How a slot scores on each scheduling factor: every factor's `weight` is the multiplier it
+/// applies to the confidence, `value` what it measured. Wall-clock hours are read in `zone`.
+pub fn confidence_factors(slot: &TimeSlot, events: &[CalendarEvent], context: &AiSchedulingContext, zone: &TimeZone) -> Vec<OptimizationFactor> {
+    let preferences = &context.user_preferences;
+    let local_start = zone.to_local(slot.start_time);
+    let hour = local_start.hour();
+    let mut factors = Vec::new();
+    let mut add = |factor_type: &str, weight: f32, value: f32, explanation: String| factors.push(OptimizationFactor {
+        factor_type: factor_type.to_string(),
+        weight,
+        value,
+        explanation,
+    });
+
+    // Prefer business hours
+    let (weight, explanation) = match hour {
+        9..=17 => (1.0, "within business hours"),
+        8 | 18 => (0.85, "at the edge of business hours"),
+        _ => (0.6, "outside business hours"),
+    };
+    add("Business hours", weight, hour as f32, format!("starts at {:02}:{:02}, {}", hour, local_start.minute(), explanation));
+
+    let weekend = matches!(local_start.weekday(), chrono::Weekday::Sat | chrono::Weekday::Sun);
+    add("Weekday", if weekend { 0.5 } else { 1.0 }, local_start.weekday().num_days_from_monday() as f32, format!("{}{}", local_start.format("%A"), if weekend { " is a weekend day" } else { "" }));
+
+    // Prefer gaps between meetings
+    let adjacent = events.iter().find(|event| (event.start_time - slot.end_time).num_minutes().abs() < 30);
+    add("Back-to-back", if adjacent.is_some() { 0.9 } else { 1.0 }, adjacent.is_some() as u8 as f32, match adjacent {
+        Some(event) => format!("\"{}\" starts within 30 minutes of the end", event.title),
+        None => "no meeting starts within 30 minutes of the end".to_string(),
+    });
+
+    match preferences.energy_patterns.iter().find(|level| level.time_of_day == hour) {
+        Some(level) => {
+            // Suitability 1–5 maps to 0.6–1.0
+            let suitability = level.meeting_suitability.clamp(1.0, 5.0);
+            add("Meeting suitability", 0.5 + 0.1 * suitability, suitability, format!("{:02}:00 is rated {:.1}/5 for meetings", hour, suitability));
+            // Hours of high focus are kept for focus work, unless the meeting needs it too
+            let focus = level.focus_capacity.clamp(1.0, 5.0);
+            let needs_focus = context.meeting_context.creative_work_required || context.meeting_context.decision_making_required;
+            let (weight, explanation) = if needs_focus {
+                (0.8 + 0.05 * (focus - 1.0), "the meeting needs focus")
+            } else {
+                (1.0 - 0.05 * (focus - 1.0), "better kept for focus work")
+            };
+            add("Focus capacity", weight, focus, format!("focus capacity {:.1}/5 at {:02}:00, {}", focus, hour, explanation));
+        }
+        None => add("Meeting suitability", 1.0, 0.0, format!("no energy level set for {:02}:00", hour)),
+    }
+
+    let day = local_start.date();
+    let meetings = events.iter()
+        .filter(|event| !event.all_day && !event.attendees.is_empty() && zone.to_local(event.start_time).date() == day)
+        .filter(|event| !(event.start_time < slot.end_time && event.end_time > slot.start_time))
+        .count() as u32;
+    let max = preferences.max_meetings_per_day;
+    let weight = if meetings >= max { 0.3 } else if meetings + 1 == max { 0.9 } else { 1.0 };
+    add("Meetings that day", weight, meetings as f32, format!("{} already has {} meeting(s), at most {} wanted", local_start.format("%A"), meetings, max));
+
+    let focus_block = preferences.focus_time_blocks.iter()
+        .find(|block| block.start_time < slot.end_time && block.end_time > slot.start_time);
+    add("Focus block", if focus_block.is_some() { 0.4 } else { 1.0 }, focus_block.is_some() as u8 as f32, match focus_block {
+        Some(block) => format!("overlaps the focus block {}–{}", zone.to_local(block.start_time).format("%H:%M"), zone.to_local(block.end_time).format("%H:%M")),
+        None => "clear of focus blocks".to_string(),
+    });
+
+    let minutes = (slot.end_time - slot.start_time).num_minutes() as f32;
+    let preferred = preferences.preferred_meeting_duration.max(1) as f32;
+    let mismatch = ((minutes - preferred).abs() / preferred).min(1.0);
+    add("Meeting length", 1.0 - 0.3 * mismatch, minutes, format!("{} minutes against a preferred {}", minutes, preferred));
+
+    factors
+}
+
+/// The confidence `confidence_factors` add up to
+pub fn confidence_from(factors: &[OptimizationFactor]) -> f32 {
+    factors.iter().map(|factor| factor.weight).product::<f32>().clamp(0.0, 1.0)
+}
+
+/// A scheduling context for `event` built from the rule-based `constraints`, for callers
+/// that have no richer one
+fn scheduling_context_for(event: &CalendarEvent, constraints: &SchedulingConstraints) -> AiSchedulingContext {
+    AiSchedulingContext {
+        user_preferences: UserPreferences {
+            preferred_meeting_times: Vec::new(),
+            avoid_times: Vec::new(),
+            max_meetings_per_day: constraints.max_consecutive_meetings,
+            preferred_meeting_duration: constraints.preferred_meeting_length,
+            break_duration_minutes: constraints.break_duration_minutes,
+            focus_time_blocks: Vec::new(),
+            energy_patterns: Vec::new(),
+            commute_time_minutes: 0,
+            timezone: constraints.time_zone.clone(),
+        },
+        historical_patterns: Vec::new(),
+        meeting_context: MeetingContext {
+            meeting_type: if event.attendees.len() == 1 { MeetingType::OneOnOne } else { MeetingType::ProjectReview },
+            importance_level: ImportanceLevel::Medium,
+            required_preparation_time: 0,
+            post_meeting_buffer: constraints.break_duration_minutes,
+            participants: Vec::new(),
+            decision_making_required: false,
+            creative_work_required: false,
+        },
+        optimization_goals: vec![OptimizationGoal::MinimizeConflicts],
+    }
+}
+
+/// Append the best-ranked solution to the reasoning of each alternative time
+fn add_solution_reasoning(alternatives: &mut [SchedulingRecommendation], solutions: &[ConflictSolution]) {
+    let Some(best) = solutions.first() else { return };
+    let source = if best.solution_id.starts_with("fallback-") { "Suggested" } else { "AI suggests" };
+    let note = format!("{}: {} ({:.0}% likely to work)", source, best.description, best.success_probability * 100.0);
+    for alternative in alternatives {
+        alternative.reasoning = if alternative.reasoning.is_empty() { note.clone() } else { format!("{}. {}", alternative.reasoning.trim_end_matches('.'), note) };
+    }
+}
+
+/// The status a deadline's hours and due date imply at `now`
+pub fn deadline_status(deadline: &Deadline, now: DateTime<Utc>) -> DeadlineStatus {
+    if deadline.completed_hours >= deadline.estimated_hours {
+        DeadlineStatus::Completed
+    } else if now > deadline.due_date {
+        DeadlineStatus::Overdue
+    } else if deadline.completed_hours > 0.0 {
+        DeadlineStatus::InProgress
+    } else {
+        DeadlineStatus::NotStarted
+    }
+}
+
+/// Blocks still meant to happen, as opposed to completed or cancelled ones
+pub fn is_open_block(block: &TimeBlock) -> bool {
+    matches!(block.status, TimeBlockStatus::Planned | TimeBlockStatus::Active | TimeBlockStatus::Rescheduled)
+}
+
+/// Update `block` from its calendar event, `None` meaning the event was deleted
+pub fn reconcile_time_block(block: &mut TimeBlock, event: Option<&CalendarEvent>) -> BlockChange {
+    if !is_open_block(block) {
+        return BlockChange::Unchanged;
+    }
+    let Some(event) = event else {
+        block.status = TimeBlockStatus::Cancelled;
+        return BlockChange::Deleted;
+    };
+    if event.start_time == block.start_time && event.end_time == block.end_time {
+        return BlockChange::Unchanged;
+    }
+    block.start_time = event.start_time;
+    block.end_time = event.end_time;
+    block.planned_duration = event.end_time - event.start_time;
+    block.status = TimeBlockStatus::Rescheduled;
+    BlockChange::Moved
+}
+
+/// Hours done on a deadline plus the hours its open blocks still have ahead of `now`
+pub fn planned_hours(deadline: &Deadline, now: DateTime<Utc>) -> f32 {
+    let ahead: i64 = deadline.time_blocks
+        .iter()
+        .filter(|b| is_open_block(b) && b.end_time > now)
+        .map(|b| (b.end_time - b.start_time.max(now)).num_minutes())
+        .sum();
+    deadline.completed_hours + ahead as f32 / 60.0
+}
+
+/// Completion rate and time efficiency (progress relative to time elapsed) of a deadline
+pub fn deadline_progress(deadline: &Deadline, now: DateTime<Utc>) -> (f32, f32) {
+    let completion_rate = if deadline.estimated_hours > 0.0 {
+        (deadline.completed_hours / deadline.estimated_hours).min(1.0)
+    } else {
+        0.0
+    };
+
+    let total_time_available = deadline.due_date - deadline.created_date;
+    let time_efficiency = if total_time_available.num_hours() > 0 {
+        let expected_progress = (now - deadline.created_date).num_hours() as f32 / total_time_available.num_hours() as f32;
+        if expected_progress > 0.0 {
+            completion_rate / expected_progress
+        } else {
+            1.0
+        }
+    } else {
+        1.0
+    };
+
+    (completion_rate, time_efficiency)
+}
+
+/// Risk indicators for a single deadline
+pub fn deadline_risk_indicators(deadline: &Deadline, completion_rate: f32, time_efficiency: f32, time_remaining: chrono::Duration) -> Vec<RiskIndicator> {
+    let mut risks = Vec::new();
+
+    // Time shortage risk
+    if time_remaining.num_hours() < 24 && completion_rate < 0.9 {
+        risks.push(RiskIndicator {
+            indicator_type: RiskType::TimeShortage,
+            severity: RiskLevel::High,
+            description: "Less than 24 hours remaining with significant work incomplete".to_string(),
+            suggested_action: "Consider extending deadline or reducing scope".to_string(),
+            deadline_impact: 0.8,
+        });
+    } else if completion_rate < 0.5 && time_remaining.num_days() < 3 {
+        risks.push(RiskIndicator {
+            indicator_type: RiskType::TimeShortage,
+            severity: RiskLevel::Medium,
+            description: "Progress is behind schedule with limited time remaining".to_string(),
+            suggested_action: "Increase daily work allocation or request assistance".to_string(),
+            deadline_impact: 0.6,
+        });
+    }
+
+    // Efficiency risk
+    if time_efficiency < 0.7 {
+        risks.push(RiskIndicator {
+            indicator_type: RiskType::ResourceConstraint,
+            severity: RiskLevel::Medium,
+            description: "Work efficiency is below expected levels".to_string(),
+            suggested_action: "Review time blocks and eliminate distractions".to_string(),
+            deadline_impact: 0.4,
+        });
+    }
+
+    // Dependency risk
+    if !deadline.dependencies.is_empty() {
+        risks.push(RiskIndicator {
+            indicator_type: RiskType::DependencyDelay,
+            severity: RiskLevel::Low,
+            description: "Deadline has dependencies that may cause delays".to_string(),
+            suggested_action: "Monitor dependency status and create contingency plans".to_string(),
+            deadline_impact: 0.3,
+        });
+    }
+
+    risks
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3332,14 +4565,14 @@ mod tests {
             calendar_id: "test-calendar".to_string(),
         };
 
-        let ics = adapter.event_to_ics(&event, "test-id").unwrap();
+        let ics = adapter.event_to_ics(&event, "test-id", &pending_invitees(&event.attendees), &[]).unwrap();
         
         assert!(ics.contains("BEGIN:VCALENDAR"));
         assert!(ics.contains("BEGIN:VEVENT"));
         assert!(ics.contains("SUMMARY:Test Event"));
         assert!(ics.contains("DESCRIPTION:Test Description"));
         assert!(ics.contains("LOCATION:Test Location"));
-        assert!(ics.contains("ATTENDEE:MAILTO:test@example.com"));
+        assert!(ics.contains("ATTENDEE;PARTSTAT=NEEDS-ACTION;RSVP=TRUE:MAILTO:test@example.com"));
         assert!(ics.contains("DTSTART:20240101T100000Z"));
         assert!(ics.contains("DTEND:20240101T110000Z"));
         assert!(ics.contains("END:VEVENT"));
@@ -3384,6 +4617,199 @@ END:VCALENDAR"#;
         assert!(!event.recurring);
     }
 
+    fn test_adapter() -> CalendarAdapter {
+        CalendarAdapter::new(CalendarConfig {
+            provider: CalendarProvider::Apple,
+            server_url: "https://caldav.icloud.com/123456789/calendars/".to_string(),
+            username: "test@icloud.com".to_string(),
+            password: "test-password".to_string(),
+            calendar_name: Some("Test Calendar".to_string()),
+        }).unwrap()
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    // Captured from a Nextcloud calendar-query REPORT, with entity-escaped CRLFs
+    const ALL_DAY_MULTISTATUS: &str = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+ <d:response>
+  <d:href>/remote.php/dav/calendars/me/personal/offsite.ics</d:href>
+  <d:propstat>
+   <d:prop>
+    <d:getetag>&quot;5f2c&quot;</d:getetag>
+    <cal:calendar-data>BEGIN:VCALENDAR&#13;
+VERSION:2.0&#13;
+PRODID:-//Nextcloud calendar v4.6//EN&#13;
+BEGIN:VEVENT&#13;
+UID:offsite-2024&#13;
+DTSTART;VALUE=DATE:20240312&#13;
+DTEND;VALUE=DATE:20240314&#13;
+SUMMARY:Team offsite\, Lisbon&#13;
+DESCRIPTION:Day 1: planning\nDay 2: retro &amp; roadmap\, bring laptop&#13;
+  chargers&#13;
+END:VEVENT&#13;
+END:VCALENDAR&#13;
+</cal:calendar-data>
+   </d:prop>
+   <d:status>HTTP/1.1 200 OK</d:status>
+  </d:propstat>
+ </d:response>
+</d:multistatus>"#;
+
+    // Captured from an iCloud REPORT without server-side expansion
+    const RECURRING_MULTISTATUS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<multistatus xmlns="DAV:">
+ <response>
+  <href>/123456789/calendars/work/standup.ics</href>
+  <propstat>
+   <prop>
+    <getetag>"C=41@U=2d8"</getetag>
+    <calendar-data xmlns="urn:ietf:params:xml:ns:caldav"><![CDATA[BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Apple Inc.//iCloud//EN
+BEGIN:VTIMEZONE
+TZID:Europe/Berlin
+BEGIN:STANDARD
+DTSTART:19701025T030000
+TZOFFSETFROM:+0200
+TZOFFSETTO:+0100
+END:STANDARD
+END:VTIMEZONE
+BEGIN:VEVENT
+UID:standup-weekly
+DTSTART;TZID=Europe/Berlin:20240108T093000
+DTEND;TZID=Europe/Berlin:20240108T094500
+RRULE:FREQ=WEEKLY;COUNT=6
+EXDATE;TZID=Europe/Berlin:20240115T093000
+SUMMARY:Standup
+END:VEVENT
+BEGIN:VEVENT
+UID:standup-weekly
+RECURRENCE-ID;TZID=Europe/Berlin:20240122T093000
+DTSTART;TZID=Europe/Berlin:20240122T110000
+DTEND;TZID=Europe/Berlin:20240122T111500
+SUMMARY:Standup (moved)
+END:VEVENT
+END:VCALENDAR
+]]></calendar-data>
+   </prop>
+   <status>HTTP/1.1 200 OK</status>
+  </propstat>
+ </response>
+</multistatus>"#;
+
+    const ATTENDEES_MULTISTATUS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:multistatus xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+ <D:response>
+  <D:href>/cal/me/review.ics</D:href>
+  <D:propstat>
+   <D:prop>
+    <D:getetag>"1"</D:getetag>
+    <C:calendar-data>BEGIN:VCALENDAR
+VERSION:2.0
+BEGIN:VEVENT
+UID:review-1
+DTSTART:20240305T140000Z
+DTEND:20240305T150000Z
+SUMMARY:Design review
+LOCATION:Room 4\; 2nd floor
+ORGANIZER;CN=Sam Lee:mailto:sam@example.com
+ATTENDEE;CN="Doe, Jane";PARTSTAT=ACCEPTED;ROLE=REQ-PARTICIPANT:MAILTO:jane
+ @example.com
+ATTENDEE;CN=Ravi;PARTSTAT=NEEDS-ACTION:mailto:ravi@example.com
+END:VEVENT
+BEGIN:VEVENT
+UID:review-prep
+DTSTART:20240305T130000Z
+DTEND:20240305T133000Z
+SUMMARY:Review prep
+END:VEVENT
+BEGIN:VEVENT
+UID:review-cancelled
+STATUS:CANCELLED
+DTSTART:20240305T160000Z
+DTEND:20240305T170000Z
+SUMMARY:Old review slot
+END:VEVENT
+END:VCALENDAR
+</C:calendar-data>
+   </D:prop>
+   <D:status>HTTP/1.1 200 OK</D:status>
+  </D:propstat>
+ </D:response>
+ <D:response>
+  <D:href>/cal/me/broken.ics</D:href>
+  <D:propstat>
+   <D:prop><C:calendar-data>not a calendar</C:calendar-data></D:prop>
+   <D:status>HTTP/1.1 200 OK</D:status>
+  </D:propstat>
+ </D:response>
+</D:multistatus>"#;
+
+    #[test]
+    fn test_parse_event_list_all_day_event() {
+        let events = test_adapter().parse_event_list(ALL_DAY_MULTISTATUS, "personal", None).unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.id, "offsite-2024");
+        assert_eq!(event.title, "Team offsite, Lisbon");
+        assert_eq!(event.description.as_deref(), Some("Day 1: planning\nDay 2: retro & roadmap, bring laptop chargers"));
+        assert!(event.all_day);
+        assert!(!event.recurring);
+        assert_eq!(event.start_time, utc("2024-03-12T00:00:00Z"));
+        assert_eq!(event.end_time, utc("2024-03-14T00:00:00Z"));
+        assert_eq!(event.calendar_id, "personal");
+    }
+
+    #[test]
+    fn test_parse_event_list_expands_recurring_events_in_range() {
+        let range = (utc("2024-01-08T00:00:00Z"), utc("2024-01-30T00:00:00Z"));
+        let events = test_adapter().parse_event_list(RECURRING_MULTISTATUS, "work", Some(range)).unwrap();
+        let starts: Vec<(String, DateTime<Utc>)> = events.iter().map(|e| (e.title.clone(), e.start_time)).collect();
+        // The 15th is excluded, the 22nd is moved, and the series continues on the 29th
+        assert_eq!(starts, vec![
+            ("Standup".to_string(), utc("2024-01-08T08:30:00Z")),
+            ("Standup (moved)".to_string(), utc("2024-01-22T10:00:00Z")),
+            ("Standup".to_string(), utc("2024-01-29T08:30:00Z")),
+        ]);
+        assert!(events.iter().all(|e| e.recurring && e.end_time - e.start_time == chrono::Duration::minutes(15)));
+    }
+
+    #[test]
+    fn test_parse_event_list_reads_attendees_and_every_vevent() {
+        let events = test_adapter().parse_event_list(ATTENDEES_MULTISTATUS, "me", None).unwrap();
+        let titles: Vec<&str> = events.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, vec!["Review prep", "Design review"]);
+        let review = &events[1];
+        assert_eq!(review.location.as_deref(), Some("Room 4; 2nd floor"));
+        assert_eq!(review.attendees, vec!["jane@example.com".to_string(), "ravi@example.com".to_string()]);
+    }
+
+    #[test]
+    fn test_event_to_ics_round_trips_escaped_text() {
+        let adapter = test_adapter();
+        let event = CalendarEvent {
+            id: "round-trip".to_string(),
+            title: "Lunch; then walk, maybe".to_string(),
+            description: Some("Line one\nLine two \\ done".to_string()),
+            start_time: utc("2024-01-01T12:00:00Z"),
+            end_time: utc("2024-01-01T13:00:00Z"),
+            location: None,
+            attendees: vec![],
+            all_day: false,
+            recurring: false,
+            calendar_id: "test-calendar".to_string(),
+        };
+        let ics = adapter.event_to_ics(&event, "round-trip", &pending_invitees(&event.attendees), &[("X-ARROWHEAD-TODO", "Todos/lunch, walk.md")]).unwrap();
+        assert!(ics.contains("SUMMARY:Lunch\\; then walk\\, maybe"));
+        let parsed = adapter.ics_to_event(&ics, "round-trip", "test-calendar").unwrap();
+        assert_eq!(parsed.title, event.title);
+        assert_eq!(parsed.description, event.description);
+        assert_eq!(event_property(&ics, "x-arrowhead-todo").as_deref(), Some("Todos/lunch, walk.md"));
+    }
+
     #[test]
     fn test_all_day_event_to_ics() {
         let config = CalendarConfig {
@@ -3408,7 +4834,7 @@ END:VCALENDAR"#;
             calendar_id: "test-calendar".to_string(),
         };
 
-        let ics = adapter.event_to_ics(&event, "test-id").unwrap();
+        let ics = adapter.event_to_ics(&event, "test-id", &pending_invitees(&event.attendees), &[]).unwrap();
         
         assert!(ics.contains("DTSTART;VALUE=DATE:20240101"));
         assert!(ics.contains("DTEND;VALUE=DATE:20240102"));
@@ -3419,4 +4845,728 @@ END:VCALENDAR"#;
     fn test_get_caldav_server_url() {
         assert_eq!(CalendarAdapter::get_caldav_server_url(), "https://caldav.icloud.com");
     }
-}
\ No newline at end of file
+
+    // A calendar home as iCloud describes it: a calendar, a shared read-only one, a
+    // reminders list and the scheduling inbox
+    const CALENDAR_HOME: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<multistatus xmlns="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+ <response>
+  <href>/123/calendars/</href>
+  <propstat><prop><resourcetype><collection/></resourcetype></prop><status>HTTP/1.1 200 OK</status></propstat>
+ </response>
+ <response>
+  <href>/123/calendars/work/</href>
+  <propstat>
+   <prop>
+    <displayname>Work &amp; Projects</displayname>
+    <resourcetype><collection/><C:calendar/></resourcetype>
+    <C:supported-calendar-component-set><C:comp name="VEVENT"/></C:supported-calendar-component-set>
+    <current-user-privilege-set><privilege><read/></privilege><privilege><write/></privilege></current-user-privilege-set>
+   </prop>
+   <status>HTTP/1.1 200 OK</status>
+  </propstat>
+  <propstat><prop><C:calendar-description/></prop><status>HTTP/1.1 404 Not Found</status></propstat>
+ </response>
+ <response>
+  <href>/123/calendars/family/</href>
+  <propstat>
+   <prop>
+    <resourcetype><collection/><C:calendar/></resourcetype>
+    <C:calendar-description>Shared by Sam</C:calendar-description>
+    <current-user-privilege-set><privilege><read/></privilege></current-user-privilege-set>
+   </prop>
+   <status>HTTP/1.1 200 OK</status>
+  </propstat>
+ </response>
+ <response>
+  <href>/123/calendars/reminders/</href>
+  <propstat>
+   <prop>
+    <displayname>Reminders</displayname>
+    <resourcetype><collection/><C:calendar/></resourcetype>
+    <C:supported-calendar-component-set><C:comp name='VTODO'/></C:supported-calendar-component-set>
+   </prop>
+   <status>HTTP/1.1 200 OK</status>
+  </propstat>
+ </response>
+ <response>
+  <href>/123/calendars/inbox/</href>
+  <propstat><prop><displayname>Inbox</displayname><resourcetype><collection/><C:schedule-inbox/></resourcetype></prop><status>HTTP/1.1 200 OK</status></propstat>
+ </response>
+</multistatus>"#;
+
+    #[test]
+    fn test_calendar_collections_are_parsed_from_multistatus() {
+        let calendars = parse_calendar_collections(CALENDAR_HOME);
+        let listed: Vec<(&str, &str, Option<&str>, &str)> = calendars.iter()
+            .map(|c| (c.id.as_str(), c.name.as_str(), c.description.as_deref(), c.access_role.as_str()))
+            .collect();
+        assert_eq!(listed, vec![
+            ("/123/calendars/work/", "Work & Projects", None, "owner"),
+            ("/123/calendars/family/", "family", Some("Shared by Sam"), "reader"),
+        ]);
+        assert_eq!(nested_href("<d:current-user-principal><d:href> /123/principal/ </d:href></d:current-user-principal>", "current-user-principal"),
+            Some("/123/principal/".to_string()));
+    }
+
+    #[test]
+    fn test_event_urls_are_built_from_the_calendar_href() {
+        let adapter = test_adapter();
+        // Without a default, "default" is the configured server URL as before
+        assert_eq!(adapter.calendar_url(DEFAULT_CALENDAR_ID), "https://caldav.icloud.com/123456789/calendars/");
+        let adapter = adapter.with_default_calendar("/123456789/calendars/work/");
+        assert_eq!(adapter.event_url(DEFAULT_CALENDAR_ID, "e1"), "https://caldav.icloud.com/123456789/calendars/work/e1.ics");
+        assert_eq!(adapter.event_url("/123456789/calendars/home", "e1"), "https://caldav.icloud.com/123456789/calendars/home/e1.ics");
+        assert_eq!(adapter.calendar_url("https://p42-caldav.icloud.com/123/calendars/work/"), "https://p42-caldav.icloud.com/123/calendars/work/");
+
+        assert_eq!(resolve_href("https://host:8443/dav/", "cal/"), "https://host:8443/dav/cal/");
+        assert_eq!(resolve_href("https://host:8443/dav/", "/other/"), "https://host:8443/other/");
+        assert_eq!(url_origin("http://127.0.0.1:8080"), "http://127.0.0.1:8080");
+    }
+
+    #[test]
+    fn test_invitation_status_counts_attendee_partstat() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:m1\r\nSUMMARY:Planning\r\n\
+            DTSTART:20240305T100000Z\r\nDTEND:20240305T103000Z\r\n\
+            ATTENDEE;CN=\"Doe, Jane\";PARTSTAT=ACCEPTED:MAILTO:jane@example.com\r\n\
+            ATTENDEE;PARTSTAT=DECLINED:mailto:ravi@example.com\r\n\
+            ATTENDEE;ROLE=OPT-PARTICIPANT;PARTSTAT=TENTATIVE:MAILTO:kim@example.com\r\n\
+            ATTENDEE;RSVP=TRUE:MAILTO:lee@example.com\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let invitees = parse_invitees(ics).unwrap();
+        assert_eq!(invitees.iter().map(|i| (i.email.as_str(), i.required, i.response_status.clone())).collect::<Vec<_>>(), vec![
+            ("jane@example.com", true, ResponseStatus::Accepted),
+            ("ravi@example.com", true, ResponseStatus::Declined),
+            ("kim@example.com", false, ResponseStatus::Tentative),
+            ("lee@example.com", true, ResponseStatus::Pending),
+        ]);
+        assert_eq!(invitees[0].name.as_deref(), Some("Doe, Jane"));
+
+        let status = InvitationStatus::from_invitees("m1", &invitees);
+        assert_eq!((status.total_invites, status.responses_received, status.accepted, status.declined, status.tentative, status.pending),
+            (4, 3, 1, 1, 1, 1));
+        assert_eq!(status.response_rate, 75.0);
+
+        // What is written reads back the same
+        let event = test_adapter().ics_to_event(ics, "m1", "default").unwrap();
+        let written = test_adapter().event_to_ics(&event, "m1", &invitees, &[]).unwrap();
+        assert_eq!(parse_invitees(&written).unwrap(), invitees);
+    }
+
+    #[tokio::test]
+    async fn test_invitation_responses_are_stored_in_the_event() {
+        use std::sync::{Arc, Mutex};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = format!("http://{}/", listener.local_addr().unwrap());
+        let stored = Arc::new(Mutex::new(String::new()));
+        let calendar = stored.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buffer = vec![0; 8192];
+                // Read the headers, then as much body as they announce
+                let body_start = loop {
+                    let read = socket.read(&mut buffer).await.unwrap_or(0);
+                    request.extend_from_slice(&buffer[..read]);
+                    if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break end + 4;
+                    }
+                    if read == 0 {
+                        break request.len();
+                    }
+                };
+                let head = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+                let length = head.lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .and_then(|value| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                while request.len() < body_start + length {
+                    let read = socket.read(&mut buffer).await.unwrap_or(0);
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                let response = if head.starts_with("put") {
+                    *calendar.lock().unwrap() = String::from_utf8_lossy(&request[body_start..]).to_string();
+                    "HTTP/1.1 201 Created\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string()
+                } else {
+                    let body = calendar.lock().unwrap().clone();
+                    format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}", body.len(), body)
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let adapter = CalendarAdapter::new(CalendarConfig {
+            provider: CalendarProvider::Apple,
+            server_url: server,
+            username: "me@example.com".to_string(),
+            password: "test-password".to_string(),
+            calendar_name: None,
+        }).unwrap();
+        let request = MeetingRequest {
+            title: "Planning".to_string(),
+            description: None,
+            duration_minutes: 30,
+            required_attendees: vec!["jane@example.com".to_string(), "ravi@example.com".to_string()],
+            optional_attendees: vec!["kim@example.com".to_string()],
+            location: None,
+            earliest_start: utc("2024-03-04T09:00:00Z"),
+            latest_start: utc("2024-03-08T17:00:00Z"),
+            preferred_times: vec![],
+            avoid_times: vec![],
+            buffer_minutes: 0,
+            allow_overlapping: false,
+        };
+        let slot = TimeSlot { start_time: utc("2024-03-05T10:00:00Z"), end_time: utc("2024-03-05T10:30:00Z"), calendar_id: None, event_id: None };
+        let invitation = adapter.create_meeting_invitation(&request, &slot, None).await.unwrap();
+        assert!(stored.lock().unwrap().contains(&format!("UID:{}", invitation.meeting_id)));
+        assert!(stored.lock().unwrap().contains("ATTENDEE;ROLE=OPT-PARTICIPANT;PARTSTAT=NEEDS-ACTION;RSVP=TRUE:MAILTO:kim@example.com"));
+
+        adapter.update_invitation_response(&invitation.meeting_id, "Jane@Example.com", ResponseStatus::Accepted).await.unwrap();
+        adapter.update_invitation_response(&invitation.meeting_id, "kim@example.com", ResponseStatus::Declined).await.unwrap();
+        assert!(adapter.update_invitation_response(&invitation.meeting_id, "nobody@example.com", ResponseStatus::Accepted).await.is_err());
+
+        let status = adapter.get_invitation_status(&invitation.meeting_id).await.unwrap();
+        assert_eq!((status.total_invites, status.accepted, status.declined, status.pending), (3, 1, 1, 1));
+        assert!((status.response_rate - 200.0 / 3.0).abs() < 1e-3);
+
+        // The invitation still says everyone is pending; the stored event decides
+        let reminded = adapter.send_invitation_reminder(&invitation).await.unwrap();
+        assert_eq!(reminded, vec!["ravi@example.com".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_calendars_are_discovered_through_the_principal() {
+        use std::sync::{Arc, Mutex};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        let home = format!(r#"<multistatus xmlns="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav"><response><href>/123/principal/</href>
+            <propstat><prop><C:calendar-home-set><href>{}/123/calendars/</href></C:calendar-home-set></prop><status>HTTP/1.1 200 OK</status></propstat></response></multistatus>"#, server);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buffer = vec![0; 8192];
+                let read = socket.read(&mut buffer).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buffer[..read]).to_string();
+                let line = request.lines().next().unwrap_or_default().to_string();
+                let body = match line.split_whitespace().nth(1).unwrap_or_default() {
+                    "/" => r#"<d:multistatus xmlns:d="DAV:"><d:response><d:href>/</d:href><d:propstat><d:prop>
+                        <d:current-user-principal><d:href>/123/principal/</d:href></d:current-user-principal>
+                        </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response></d:multistatus>"#.to_string(),
+                    "/123/principal/" => home.clone(),
+                    "/123/calendars/" => CALENDAR_HOME.to_string(),
+                    _ => String::new(),
+                };
+                log.lock().unwrap().push(line);
+                let response = format!("HTTP/1.1 207 Multi-Status\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}", body.len(), body);
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let adapter = CalendarAdapter::new(CalendarConfig {
+            provider: CalendarProvider::Apple,
+            server_url: format!("{}/", server),
+            username: "test@icloud.com".to_string(),
+            password: "test-password".to_string(),
+            calendar_name: None,
+        }).unwrap().with_default_calendar("/123/calendars/family/");
+
+        let calendars = adapter.get_calendar_list().await.unwrap();
+        assert_eq!(calendars.iter().map(|c| (c.id.as_str(), c.primary)).collect::<Vec<_>>(),
+            vec![("/123/calendars/work/", false), ("/123/calendars/family/", true)]);
+
+        let event = CalendarEvent {
+            id: String::new(),
+            title: "Standup".to_string(),
+            description: None,
+            start_time: utc("2024-03-04T09:00:00Z"),
+            end_time: utc("2024-03-04T09:15:00Z"),
+            location: None,
+            attendees: vec![],
+            all_day: false,
+            recurring: false,
+            calendar_id: String::new(),
+        };
+        let created = adapter.create_event(&calendars[0].id, &event).await.unwrap();
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[..3], ["PROPFIND / HTTP/1.1", "PROPFIND /123/principal/ HTTP/1.1", "PROPFIND /123/calendars/ HTTP/1.1"]);
+        assert_eq!(requests[3], format!("PUT /123/calendars/work/{}.ics HTTP/1.1", created.id));
+    }
+
+    #[tokio::test]
+    async fn test_reads_are_retried_and_connections_diagnosed() {
+        use std::sync::{Arc, Mutex};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let mut buffer = vec![0; 8192];
+                    let read = socket.read(&mut buffer).await.unwrap_or(0);
+                    let line = String::from_utf8_lossy(&buffer[..read]).lines().next().unwrap_or_default().to_string();
+                    let path = line.split_whitespace().nth(1).unwrap_or_default().to_string();
+                    let earlier = log.lock().unwrap().iter().filter(|l: &&String| l.contains(&path)).count();
+                    log.lock().unwrap().push(line);
+                    let multistatus = r#"<d:multistatus xmlns:d="DAV:"></d:multistatus>"#;
+                    let (status, headers, body) = match path.split('/').nth(1).unwrap_or_default() {
+                        "flaky" if earlier == 0 => ("503 Service Unavailable", "", "busy"),
+                        "flaky" | "dav" => ("207 Multi-Status", "dav: 1, 2, calendar-access\r\n", multistatus),
+                        "locked" => ("401 Unauthorized", "", ""),
+                        "web" => ("200 OK", "content-type: text/html\r\n", "<html></html>"),
+                        "slow" => {
+                            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                            ("207 Multi-Status", "", multistatus)
+                        }
+                        _ => ("503 Service Unavailable", "", "down"),
+                    };
+                    let response = format!("HTTP/1.1 {}\r\n{}content-length: {}\r\nconnection: close\r\n\r\n{}", status, headers, body.len(), body);
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        let adapter = |path: &str| CalendarAdapter::new(CalendarConfig {
+            provider: CalendarProvider::Apple,
+            server_url: format!("{}{}", server, path),
+            username: "me@example.com".to_string(),
+            password: "app-password".to_string(),
+            calendar_name: None,
+        }).unwrap().with_retry(RetrySettings { max_attempts: 3, initial_delay_ms: 0, max_delay_ms: 0 });
+
+        // A REPORT that hits a 503 is sent again; a PUT isn't
+        assert!(adapter("/flaky/").list_events(DEFAULT_CALENDAR_ID, None, None).await.unwrap().is_empty());
+        let event = CalendarEvent {
+            id: String::new(),
+            title: "Standup".to_string(),
+            description: None,
+            start_time: utc("2024-03-04T09:00:00Z"),
+            end_time: utc("2024-03-04T09:15:00Z"),
+            location: None,
+            attendees: vec![],
+            all_day: false,
+            recurring: false,
+            calendar_id: String::new(),
+        };
+        assert!(adapter("/down/").create_event(DEFAULT_CALENDAR_ID, &event).await.is_err());
+        assert!(adapter("/down/").list_events(DEFAULT_CALENDAR_ID, None, None).await.is_err());
+        {
+            let requests = requests.lock().unwrap();
+            assert_eq!(requests.iter().filter(|l| l.starts_with("REPORT /flaky/")).count(), 2);
+            assert_eq!(requests.iter().filter(|l| l.starts_with("PUT /down/")).count(), 1);
+            assert_eq!(requests.iter().filter(|l| l.starts_with("REPORT /down/")).count(), 3);
+        }
+
+        let error = adapter("/locked/").get_event(DEFAULT_CALENDAR_ID, "abc").await.unwrap_err();
+        assert!(matches!(ArrowheadError::of(&error), Some(ArrowheadError::CalendarAuthFailed { status: 401, .. })), "{:#}", error);
+        assert_eq!(crate::error::exit_code(&error), 77);
+        assert!(ArrowheadError::of(&error).unwrap().hint().unwrap().contains("app-specific password"));
+
+        assert_eq!(adapter("/dav/").test_connection().await, ConnectionDiagnosis::Connected { status: 207, calendar_access: true });
+        assert_eq!(adapter("/locked/").test_connection().await, ConnectionDiagnosis::AuthFailed { status: 401 });
+        assert!(matches!(adapter("/web/").test_connection().await, ConnectionDiagnosis::NotCalDav { status: 200, ref detail } if detail.contains("text/html")));
+        let impatient = adapter("/slow/").with_http_client(Client::builder().timeout(std::time::Duration::from_millis(200)).build().unwrap());
+        assert!(matches!(impatient.test_connection().await, ConnectionDiagnosis::Timeout { .. }));
+
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_url = format!("http://{}/", closed.local_addr().unwrap());
+        drop(closed);
+        let unreachable = CalendarAdapter::new(CalendarConfig {
+            provider: CalendarProvider::Apple,
+            server_url: closed_url,
+            username: "me@example.com".to_string(),
+            password: "app-password".to_string(),
+            calendar_name: None,
+        }).unwrap();
+        assert!(matches!(unreachable.test_connection().await, ConnectionDiagnosis::Unreachable { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_meeting_times_rank_by_available_required_attendees() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Our own calendar is empty
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buffer = vec![0; 8192];
+                let _ = socket.read(&mut buffer).await;
+                let body = r#"<d:multistatus xmlns:d="DAV:"></d:multistatus>"#;
+                let response = format!("HTTP/1.1 207 Multi-Status\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}", body.len(), body);
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        let busy = |start: &str, end: &str| BusyInterval { start: utc(start), end: utc(end), tentative: false };
+        let adapter = CalendarAdapter::new(CalendarConfig {
+            provider: CalendarProvider::Apple,
+            server_url: server,
+            username: "test@icloud.com".to_string(),
+            password: "test-password".to_string(),
+            calendar_name: None,
+        }).unwrap().with_external_availability(HashMap::from([
+            ("alice@example.com".to_string(), vec![busy("2024-06-03T09:00:00Z", "2024-06-03T10:00:00Z")]),
+            ("bob@example.com".to_string(), vec![busy("2024-06-03T10:00:00Z", "2024-06-03T11:00:00Z")]),
+        ]));
+
+        let request = MeetingRequest {
+            title: "Planning".to_string(),
+            description: None,
+            duration_minutes: 60,
+            required_attendees: vec!["alice@example.com".to_string(), "bob@example.com".to_string()],
+            optional_attendees: vec!["carol@example.com".to_string()],
+            location: None,
+            earliest_start: utc("2024-06-03T09:00:00Z"),
+            latest_start: utc("2024-06-03T12:00:00Z"),
+            preferred_times: Vec::new(),
+            avoid_times: Vec::new(),
+            buffer_minutes: 0,
+            allow_overlapping: false,
+        };
+        let recommendations = adapter.find_meeting_time(&request).await.unwrap();
+        let statuses = |r: &SchedulingRecommendation| r.attendee_availability.iter()
+            .map(|a| format!("{}={:?}", a.attendee, a.status))
+            .collect::<Vec<_>>();
+
+        // Only 11:00 suits both; carol's server is unknown, so she never counts either way
+        assert_eq!(recommendations[0].time_slot.start_time, utc("2024-06-03T11:00:00Z"));
+        assert_eq!(statuses(&recommendations[0]), vec!["self=Available", "alice@example.com=Available", "bob@example.com=Available", "carol@example.com=Unknown"]);
+        assert!(recommendations[1..].iter().all(|r| statuses(r).iter().any(|s| s.ends_with("=Busy"))));
+        let at_ten = recommendations.iter().find(|r| r.time_slot.start_time == utc("2024-06-03T10:00:00Z")).unwrap();
+        assert_eq!(statuses(at_ten)[1..3], ["alice@example.com=Available", "bob@example.com=Busy"]);
+        assert_eq!(at_ten.attendee_availability[2].conflicts.len(), 1);
+    }
+
+    fn los_angeles() -> TimeZone {
+        TimeZone::named("America/Los_Angeles").unwrap()
+    }
+
+    #[test]
+    fn test_time_blocks_stay_at_nine_local_across_dst() {
+        let zone = los_angeles();
+        // Thursday 2024-03-07 noon in LA; due the following Wednesday
+        let days = block_days(&zone, utc("2024-03-07T20:00:00Z"), utc("2024-03-13T20:00:00Z"));
+        let expected: Vec<chrono::NaiveDate> = ["2024-03-07", "2024-03-08", "2024-03-11", "2024-03-12"]
+            .iter()
+            .map(|d| d.parse().unwrap())
+            .collect();
+        assert_eq!(days, expected);
+
+        let starts: Vec<DateTime<Utc>> = days.iter().map(|day| morning_block(&zone, *day, 2.0).unwrap().0).collect();
+        // PST (UTC-8) before the March 10 change, PDT (UTC-7) after
+        assert_eq!(starts[1], utc("2024-03-08T17:00:00Z"));
+        assert_eq!(starts[2], utc("2024-03-11T16:00:00Z"));
+        assert_eq!(morning_block(&zone, days[2], 2.0).unwrap().1, utc("2024-03-11T18:00:00Z"));
+
+        let november = "2024-11-04".parse().unwrap();
+        assert_eq!(morning_block(&zone, november, 1.0).unwrap().0, utc("2024-11-04T17:00:00Z"));
+    }
+
+    #[test]
+    fn test_block_days_use_the_local_date() {
+        // Friday 5pm in LA is already Saturday in UTC
+        let days = block_days(&los_angeles(), utc("2024-03-09T01:00:00Z"), utc("2024-03-12T20:00:00Z"));
+        assert_eq!(days.first().copied(), Some("2024-03-08".parse().unwrap()));
+        assert_eq!(days.len(), 2);
+    }
+
+    #[test]
+    fn test_working_hours_are_checked_on_the_local_clock() {
+        let adapter = test_adapter();
+        let constraints = SchedulingConstraints {
+            working_hours: (1..=5).map(|day_of_week| WorkingHours { day_of_week, start_hour: 9, start_minute: 0, end_hour: 17, end_minute: 0 }).collect(),
+            time_zone: "America/Los_Angeles".to_string(),
+            minimum_notice_hours: 0,
+            maximum_lookahead_days: 14,
+            preferred_meeting_length: 30,
+            break_duration_minutes: 0,
+            max_consecutive_meetings: 4,
+            avoid_lunch_time: true,
+            lunch_start_hour: 12,
+            lunch_end_hour: 13,
+        };
+        let slot = |start: &str| TimeSlot {
+            start_time: utc(start),
+            end_time: utc(start) + chrono::Duration::minutes(30),
+            calendar_id: None,
+            event_id: None,
+        };
+
+        // 9:30 PDT on Monday March 11, which is 16:30 UTC
+        assert!(adapter.is_within_working_hours(&slot("2024-03-11T16:30:00Z"), &constraints, &los_angeles()));
+        // 8:30 PST on Friday March 8, though 16:30 UTC
+        assert!(!adapter.is_within_working_hours(&slot("2024-03-08T16:30:00Z"), &constraints, &los_angeles()));
+        // Lunch in LA
+        assert!(!adapter.is_within_working_hours(&slot("2024-03-11T19:30:00Z"), &constraints, &los_angeles()));
+        // Friday 4pm in LA, Saturday in UTC
+        assert!(adapter.is_within_working_hours(&slot("2024-03-09T00:00:00Z"), &constraints, &los_angeles()));
+    }
+
+    fn meeting(duration_minutes: u32, attendees: &[&str]) -> MeetingRequest {
+        MeetingRequest {
+            title: "Launch review".to_string(),
+            description: None,
+            duration_minutes,
+            required_attendees: attendees.iter().map(|a| a.to_string()).collect(),
+            optional_attendees: vec![],
+            location: None,
+            earliest_start: Utc::now(),
+            latest_start: Utc::now(),
+            preferred_times: vec![],
+            avoid_times: vec![],
+            buffer_minutes: 0,
+            allow_overlapping: false,
+        }
+    }
+
+    #[test]
+    fn test_agenda_durations_are_scaled_to_the_meeting() {
+        // The model overran a 30 minute meeting by half
+        let reply = r#"Here you go:
+        {"meeting_type": "ProjectReview", "objectives": ["Decide the launch date"],
+         "items": [{"title": "Status", "duration_minutes": 15, "type": "information"},
+                   {"title": "Risks", "duration_minutes": 20, "owner": "ana@example.com", "discussion_points": ["Load tests"]},
+                   {"title": "Date", "duration_minutes": 10, "type": "decision"}],
+         "preparation": [{"title": "Launch plan", "required": true, "reading_minutes": 5}]}"#;
+        let agenda = agenda_from_response(reply, &meeting(30, &["ana@example.com", "bo@example.com"])).unwrap();
+        let minutes: Vec<u32> = agenda.agenda_items.iter().map(|i| i.duration_minutes).collect();
+        assert_eq!(minutes, vec![10, 13, 7]);
+        assert!(matches!(agenda.agenda_items[2].item_type, AgendaItemType::Decision));
+        assert_eq!(agenda.agenda_items[1].owner.as_deref(), Some("ana@example.com"));
+        assert!(agenda.preparation_materials[0].required);
+
+        let text = render_agenda(&agenda);
+        assert!(text.contains("2. Risks (13 min) - ana@example.com\n   * Load tests"), "{}", text);
+        assert!(text.contains("- Launch plan (required, ~5 min)"), "{}", text);
+
+        assert!(agenda_from_response("I can't help with that.", &meeting(30, &[])).is_err());
+    }
+
+    /// Answers each request with the next scripted reply
+    struct ScriptedClient(std::sync::Mutex<Vec<String>>);
+
+    #[async_trait::async_trait]
+    impl crate::ai_conversation::LLMClient for ScriptedClient {
+        async fn send_message(&self, _messages: Vec<crate::ai_conversation::Message>) -> Result<crate::ai_conversation::Message> {
+            let content = self.0.lock().unwrap().remove(0);
+            Ok(crate::ai_conversation::Message { id: String::new(), role: crate::ai_conversation::MessageRole::Assistant, content, timestamp: Utc::now(), function_call: None })
+        }
+
+        async fn stream_response(&self, _messages: Vec<crate::ai_conversation::Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+            unimplemented!()
+        }
+
+        async fn function_calling(&self, messages: Vec<crate::ai_conversation::Message>, _functions: Vec<crate::ai_conversation::FunctionSchema>) -> Result<crate::ai_conversation::Message> {
+            self.send_message(messages).await
+        }
+
+        fn get_model_name(&self) -> String {
+            "scripted".to_string()
+        }
+    }
+
+    fn conflict(attendee: &str, conflict_type: ConflictType) -> ConflictInfo {
+        ConflictInfo {
+            attendee: attendee.to_string(),
+            conflicting_event: CalendarEvent {
+                id: "standup".to_string(),
+                title: "Standup".to_string(),
+                description: None,
+                start_time: utc("2024-05-15T09:00:00Z"),
+                end_time: utc("2024-05-15T09:15:00Z"),
+                location: None,
+                attendees: vec![attendee.to_string()],
+                all_day: false,
+                recurring: true,
+                calendar_id: DEFAULT_CALENDAR_ID.to_string(),
+            },
+            conflict_type,
+        }
+    }
+
+    fn constraints() -> SchedulingConstraints {
+        SchedulingConstraints {
+            working_hours: Vec::new(),
+            time_zone: "UTC".to_string(),
+            minimum_notice_hours: 0,
+            maximum_lookahead_days: 7,
+            preferred_meeting_length: 30,
+            break_duration_minutes: 10,
+            max_consecutive_meetings: 3,
+            avoid_lunch_time: false,
+            lunch_start_hour: 12,
+            lunch_end_hour: 13,
+        }
+    }
+
+    #[test]
+    fn test_conflict_reply_is_validated_and_ranked() {
+        let reply = r#"Sure! ```json
+        {"severity": "Significant",
+         "solutions": [
+           {"type": "reschedule", "description": "Move to 10:00", "steps": ["Ask Ana"], "success_probability": 0.6},
+           {"type": "teleport", "description": "Skip physics", "success_probability": 0.99},
+           {"type": "MeetingSplit", "description": "Split into two 15 minute calls", "success_probability": 0.85, "satisfaction_impact": 0.8},
+           {"type": "delegation", "description": "Send Bo instead", "success_probability": 1.5},
+           {"type": "format_change", "description": "  "},
+           {"type": "duration-adjustment", "description": "Shorten to 20 minutes"}
+         ],
+         "negotiation_strategies": ["Offer Ana the earlier slot", ""]}
+        ```"#;
+        let parsed = conflict_reply_from_response(reply).unwrap();
+        let ranked: Vec<(&str, f32)> = parsed.solutions.iter().map(|s| (s.description.as_str(), s.success_probability)).collect();
+        assert_eq!(ranked, vec![("Split into two 15 minute calls", 0.85), ("Move to 10:00", 0.6)]);
+        assert!(matches!(parsed.solutions[0].solution_type, SolutionType::MeetingSplit));
+        assert_eq!(parsed.solutions[1].solution_id, "ai-1");
+        assert!(matches!(parsed.severity, Some(ConflictSeverity::Significant)));
+        assert_eq!(parsed.negotiation_strategies, vec!["Offer Ana the earlier slot"]);
+        assert!(parsed.compromise_options.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_conflict_replies_are_rejected() {
+        for reply in [
+            "I'd move the standup.",
+            r#"{"severity": "minor"}"#,
+            r#"{"solutions": []}"#,
+            r#"{"solutions": [{"type": "reschedule", "description": "Later", "success_probability": "high"}]}"#,
+            r#"{"solutions": [{"type": "reschedule", "description": "Later", "success_probability": -0.1}]}"#,
+            r#"{"solutions": [{"description": "No type", "success_probability": 0.5}]}"#,
+        ] {
+            assert!(conflict_reply_from_response(reply).is_err(), "{}", reply);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conflict_analysis_falls_back_when_the_model_rambles() {
+        let replies = vec![
+            "Honestly, just cancel the standup.".to_string(),
+            r#"{"solutions": [{"type": "format_change", "description": "Make the standup async", "success_probability": 0.9}]}"#.to_string(),
+        ];
+        let adapter = test_adapter().with_ai_conversation(AIConversationEngine::new(Box::new(ScriptedClient(std::sync::Mutex::new(replies)))));
+        let event = CalendarEvent { attendees: vec!["ana@example.com".to_string(), "bo@example.com".to_string()], ..conflict("ana@example.com", ConflictType::DirectOverlap).conflicting_event };
+        let context = scheduling_context_for(&event, &constraints());
+        let conflicts = vec![conflict("ana@example.com", ConflictType::DirectOverlap), conflict("bo@example.com", ConflictType::BufferViolation)];
+
+        let fallback = adapter.analyze_conflicts_with_ai(&conflicts, &context).await.unwrap();
+        let ids: Vec<&str> = fallback.ai_recommended_solutions.iter().map(|s| s.solution_id.as_str()).collect();
+        assert_eq!(ids, vec!["fallback-reschedule", "fallback-shorten"]);
+        assert_eq!(fallback.stakeholder_impact.len(), 2);
+        assert!(matches!(fallback.conflict_analysis.conflict_severity, ConflictSeverity::Significant));
+
+        let smart = adapter.analyze_conflicts_with_ai(&conflicts, &context).await.unwrap();
+        assert_eq!(smart.ai_recommended_solutions[0].description, "Make the standup async");
+
+        let mut alternatives = vec![SchedulingRecommendation {
+            time_slot: TimeSlot { start_time: utc("2024-05-15T10:00:00Z"), end_time: utc("2024-05-15T10:30:00Z"), calendar_id: None, event_id: None },
+            confidence_score: 0.8,
+            attendee_availability: Vec::new(),
+            reasoning: "Everyone is free.".to_string(),
+        }];
+        add_solution_reasoning(&mut alternatives, &smart.ai_recommended_solutions);
+        assert_eq!(alternatives[0].reasoning, "Everyone is free. AI suggests: Make the standup async (90% likely to work)");
+    }
+
+    #[test]
+    fn test_fit_durations_fills_the_meeting_exactly() {
+        assert_eq!(fit_durations(&[5.0, 5.0], 30), vec![15, 15]);
+        assert_eq!(fit_durations(&[0.0, 0.0, 0.0], 10), vec![4, 3, 3]);
+        assert_eq!(fit_durations(&[10.0, 0.0, 10.0], 21).iter().sum::<u32>(), 21);
+        assert!(fit_durations(&[], 30).is_empty());
+    }
+
+    fn block(start: &str, end: &str, status: TimeBlockStatus) -> TimeBlock {
+        TimeBlock {
+            id: Uuid::new_v4().to_string(),
+            deadline_id: "d1".to_string(),
+            start_time: utc(start),
+            end_time: utc(end),
+            planned_duration: utc(end) - utc(start),
+            actual_duration: None,
+            productivity_score: None,
+            notes: None,
+            calendar_event_id: Some("event-1".to_string()),
+            status,
+            focus_mode: true,
+            interruptions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_time_blocks_follow_their_calendar_events() {
+        let mut event = CalendarEvent {
+            id: "event-1".to_string(),
+            title: "🎯 Report".to_string(),
+            description: None,
+            start_time: utc("2024-03-04T09:00:00Z"),
+            end_time: utc("2024-03-04T11:00:00Z"),
+            location: None,
+            attendees: vec![],
+            all_day: false,
+            recurring: false,
+            calendar_id: DEFAULT_CALENDAR_ID.to_string(),
+        };
+        let mut planned = block("2024-03-04T09:00:00Z", "2024-03-04T11:00:00Z", TimeBlockStatus::Planned);
+        assert_eq!(reconcile_time_block(&mut planned, Some(&event)), BlockChange::Unchanged);
+        assert!(matches!(planned.status, TimeBlockStatus::Planned));
+
+        // Dragged to the afternoon and shortened to an hour and a half
+        event.start_time = utc("2024-03-04T14:00:00Z");
+        event.end_time = utc("2024-03-04T15:30:00Z");
+        assert_eq!(reconcile_time_block(&mut planned, Some(&event)), BlockChange::Moved);
+        assert_eq!(planned.start_time, event.start_time);
+        assert_eq!(planned.planned_duration, chrono::Duration::minutes(90));
+        assert!(matches!(planned.status, TimeBlockStatus::Rescheduled));
+
+        assert_eq!(reconcile_time_block(&mut planned, None), BlockChange::Deleted);
+        assert!(matches!(planned.status, TimeBlockStatus::Cancelled));
+
+        // Finished blocks are the record of work done and stay as they were
+        let mut done = block("2024-03-01T09:00:00Z", "2024-03-01T11:00:00Z", TimeBlockStatus::Completed);
+        assert_eq!(reconcile_time_block(&mut done, None), BlockChange::Unchanged);
+        assert!(matches!(done.status, TimeBlockStatus::Completed));
+    }
+
+    #[test]
+    fn test_planned_hours_count_open_blocks_still_ahead() {
+        let now = utc("2024-03-04T10:00:00Z");
+        let mut deadline: Deadline = serde_json::from_value(serde_json::json!({
+            "id": "d1", "title": "Report", "description": null,
+            "due_date": "2024-03-08T17:00:00Z", "created_date": "2024-03-01T09:00:00Z",
+            "priority": "Medium", "status": "InProgress",
+            "estimated_hours": 8.0, "completed_hours": 2.0,
+            "category": "work", "tags": [], "dependencies": [], "assignee": null, "project_id": null,
+            "reminder_settings": { "enabled": false, "advance_notifications": [], "notification_channels": [],
+                "escalation_enabled": false, "escalation_delay_hours": 0 },
+            "time_blocks": [], "progress_milestones": []
+        })).unwrap();
+        deadline.time_blocks = vec![
+            // Half over: only the hour left counts
+            block("2024-03-04T09:00:00Z", "2024-03-04T11:00:00Z", TimeBlockStatus::Planned),
+            block("2024-03-05T09:00:00Z", "2024-03-05T11:00:00Z", TimeBlockStatus::Rescheduled),
+            block("2024-03-06T09:00:00Z", "2024-03-06T11:00:00Z", TimeBlockStatus::Cancelled),
+            block("2024-03-01T09:00:00Z", "2024-03-01T11:00:00Z", TimeBlockStatus::Completed),
+        ];
+        assert_eq!(planned_hours(&deadline, now), 5.0);
+
+        let sync = TimeBlockSync {
+            deadline_id: deadline.id.clone(),
+            title: deadline.title.clone(),
+            moved: vec![],
+            cancelled: vec![],
+            unchecked: 0,
+            planned_hours: planned_hours(&deadline, now),
+            estimated_hours: deadline.estimated_hours,
+        };
+        assert_eq!(sync.shortfall(), 3.0);
+    }
+}
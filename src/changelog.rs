@@ -0,0 +1,570 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::ai_conversation::{LLMClient, Message, MessageRole};
+use crate::cache::{write_atomic, CachePaths};
+use crate::cli::{ChangelogAction, ChangelogArgs};
+use crate::config::{ChangelogSettings, Config};
+use crate::managed_sections::{managed_sections, remove, upsert, without_managed_sections, SectionWrite};
+use crate::note_skeleton::stable_hash;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::usage::UsagePurpose;
+use crate::vault_transaction::{TransactionReport, VaultStore, VaultTransaction};
+
+const CHANGELOG_HEADING: &str = "## Changelog";
+/// Owner and id of the managed changelog section
+const SECTION_NAME: &str = "changelog";
+const ARCHIVE_LINK_PREFIX: &str = "- Earlier changes: ";
+/// Above this many line pairs the diff falls back to comparing line multisets
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// What changed between two versions of a note's body
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeSummary {
+    pub added_lines: usize,
+    pub removed_lines: usize,
+    /// Non-whitespace characters in added and removed lines
+    pub changed_chars: usize,
+    pub added_sections: Vec<String>,
+    pub removed_sections: Vec<String>,
+    pub edited_sections: Vec<String>,
+}
+
+impl ChangeSummary {
+    /// One-line description of the change, e.g. `Added "Monitoring"; edited "Rollback" (+6/-1 lines)`
+    pub fn describe(&self) -> String {
+        let quote = |sections: &[String]| {
+            let mut names: Vec<String> = sections.iter().take(3).map(|s| format!("\"{}\"", s)).collect();
+            if sections.len() > 3 {
+                names.push(format!("{} more", sections.len() - 3));
+            }
+            names.join(", ")
+        };
+
+        let mut parts = Vec::new();
+        if !self.added_sections.is_empty() {
+            parts.push(format!("added {}", quote(&self.added_sections)));
+        }
+        if !self.removed_sections.is_empty() {
+            parts.push(format!("removed {}", quote(&self.removed_sections)));
+        }
+        if !self.edited_sections.is_empty() {
+            parts.push(format!("edited {}", quote(&self.edited_sections)));
+        }
+        let mut description = if parts.is_empty() { "edited".to_string() } else { parts.join("; ") };
+        description.replace_range(..1, &description[..1].to_uppercase());
+        format!("{} (+{}/-{} lines)", description, self.added_lines, self.removed_lines)
+    }
+}
+
+/// Line diff of two note bodies, attributing changed lines to their nearest heading
+pub fn summarize_change(old: &str, new: &str) -> ChangeSummary {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let (removed, added) = diff_lines(&old_lines, &new_lines);
+    // Blank lines carry no meaning for the summary
+    let removed: Vec<usize> = removed.into_iter().filter(|&i| !old_lines[i].trim().is_empty()).collect();
+    let added: Vec<usize> = added.into_iter().filter(|&i| !new_lines[i].trim().is_empty()).collect();
+
+    let old_headings = headings(&old_lines);
+    let new_headings = headings(&new_lines);
+    let added_sections: Vec<String> = new_headings.iter()
+        .filter(|h| !old_headings.contains(h))
+        .cloned()
+        .collect();
+    let removed_sections: Vec<String> = old_headings.iter()
+        .filter(|h| !new_headings.contains(h))
+        .cloned()
+        .collect();
+
+    let mut edited_sections = Vec::new();
+    let mut note_edit = |section: Option<String>| {
+        if let Some(section) = section {
+            if !added_sections.contains(&section) && !removed_sections.contains(&section) && !edited_sections.contains(&section) {
+                edited_sections.push(section);
+            }
+        }
+    };
+    for &index in &added {
+        note_edit(section_of(&new_lines, index));
+    }
+    for &index in &removed {
+        note_edit(section_of(&old_lines, index));
+    }
+
+    let changed_chars = added.iter().map(|&i| new_lines[i])
+        .chain(removed.iter().map(|&i| old_lines[i]))
+        .map(|line| line.chars().filter(|c| !c.is_whitespace()).count())
+        .sum();
+
+    ChangeSummary {
+        added_lines: added.len(),
+        removed_lines: removed.len(),
+        changed_chars,
+        added_sections,
+        removed_sections,
+        edited_sections,
+    }
+}
+
+/// Indices of removed lines in `old` and added lines in `new`. Lines differing only in
+/// surrounding whitespace are treated as equal.
+fn diff_lines(old: &[&str], new: &[&str]) -> (Vec<usize>, Vec<usize>) {
+    let (n, m) = (old.len(), new.len());
+    if n * m > MAX_LCS_CELLS {
+        let mut remaining: HashMap<&str, usize> = HashMap::new();
+        for line in old {
+            *remaining.entry(line.trim()).or_default() += 1;
+        }
+        let mut added = Vec::new();
+        for (i, line) in new.iter().enumerate() {
+            match remaining.get_mut(line.trim()) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => added.push(i),
+            }
+        }
+        let mut kept: HashMap<&str, usize> = HashMap::new();
+        for line in new {
+            *kept.entry(line.trim()).or_default() += 1;
+        }
+        let mut removed = Vec::new();
+        for (i, line) in old.iter().enumerate() {
+            match kept.get_mut(line.trim()) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => removed.push(i),
+            }
+        }
+        return (removed, added);
+    }
+
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i].trim() == new[j].trim() {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i].trim() == new[j].trim() {
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            removed.push(i);
+            i += 1;
+        } else {
+            added.push(j);
+            j += 1;
+        }
+    }
+    removed.extend(i..n);
+    added.extend(j..m);
+    (removed, added)
+}
+
+fn heading_text(line: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    ((1..=6).contains(&level) && trimmed[level..].starts_with(' ')).then(|| trimmed[level..].trim().to_string())
+}
+
+fn headings(lines: &[&str]) -> Vec<String> {
+    lines.iter().filter_map(|line| heading_text(line)).collect()
+}
+
+fn section_of(lines: &[&str], index: usize) -> Option<String> {
+    lines[..=index].iter().rev().find_map(|line| heading_text(line))
+}
+
+/// Split a note into its body and the entries of its managed changelog section
+fn split_changelog(content: &str) -> (String, Vec<String>, Option<String>) {
+    let Some(section) = managed_sections(content).into_iter().find(|s| s.id == SECTION_NAME) else {
+        return (content.to_string(), Vec::new(), None);
+    };
+    let mut entries = Vec::new();
+    let mut archive_link = None;
+    for line in content[section.body].lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line.starts_with(ARCHIVE_LINK_PREFIX) {
+            archive_link = Some(line.to_string());
+        } else if line.starts_with("- ") {
+            entries.push(line.to_string());
+        }
+    }
+    let body = remove(content, SECTION_NAME, SECTION_NAME).unwrap_or_else(|_| content.to_string());
+    (body, entries, archive_link)
+}
+
+/// Body of the note with the managed changelog removed
+pub fn note_body(content: &str) -> String {
+    split_changelog(content).0
+}
+
+fn changelog_section(entries: &[String], archive_link: Option<&str>) -> SectionWrite {
+    let mut body: String = entries.iter().map(|entry| format!("{}\n", entry)).collect();
+    if let Some(link) = archive_link {
+        body.push_str(link);
+        body.push('\n');
+    }
+    SectionWrite::new(SECTION_NAME, SECTION_NAME, &body).heading(CHANGELOG_HEADING)
+}
+
+fn is_living_doc(content: &str, tag: &str) -> bool {
+    let tag = tag.trim_start_matches('#');
+    let frontmatter_tags = ObsidianAdapter::parse_markdown_file(content).ok()
+        .and_then(|file| file.frontmatter.tags)
+        .unwrap_or_default();
+    frontmatter_tags.iter().any(|t| t.trim_start_matches('#') == tag)
+        || note_body(content).split_whitespace().any(|word| word.trim_end_matches([',', '.']) == format!("#{}", tag))
+}
+
+fn archive_path(settings: &ChangelogSettings, note_path: &str) -> String {
+    let stem = note_path.rsplit('/').next().unwrap_or(note_path).trim_end_matches(".md");
+    format!("{}/{} changelog.md", settings.archive_folder.trim_end_matches('/'), stem)
+}
+
+/// What the tracker last saw of one note
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NoteState {
+    /// Body as of the last changelog entry (or first sighting)
+    body: String,
+    /// Body before the edits that the newest entry describes, while that entry can
+    /// still absorb more edits
+    window_base: Option<String>,
+    last_entry_at: Option<DateTime<Utc>>,
+}
+
+/// A changelog entry written for a note
+#[derive(Debug)]
+pub struct ChangelogUpdate {
+    pub description: String,
+    pub report: TransactionReport,
+}
+
+/// Watches living documents and keeps their changelog section up to date
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChangelogTracker {
+    notes: HashMap<String, NoteState>,
+    /// Hashes of the content Arrowhead itself last wrote to each note, so our own
+    /// changelog writes are never mistaken for user edits
+    own_writes: HashMap<String, u64>,
+}
+
+impl ChangelogTracker {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).context("Failed to read changelog state")?;
+        serde_json::from_str(&content).context("Failed to parse changelog state")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, serde_json::to_string(self)?.as_bytes()).context("Failed to write changelog state")
+    }
+
+    /// Record the latest content of a note. When it is a living document with a
+    /// substantive edit since the last entry, prepend (or coalesce into) a changelog entry
+    /// and return its description with the committed transaction.
+    pub async fn observe<S: VaultStore + ?Sized>(
+        &mut self,
+        store: &S,
+        path: &str,
+        content: &str,
+        settings: &ChangelogSettings,
+        refiner: Option<&dyn LLMClient>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ChangelogUpdate>> {
+        if self.own_writes.get(path) == Some(&stable_hash(content)) {
+            return Ok(None);
+        }
+        if !is_living_doc(content, &settings.tag) {
+            self.notes.remove(path);
+            return Ok(None);
+        }
+
+        let (body, mut entries, mut archive_link) = split_changelog(content);
+        // Other managed sections (e.g. backlinks) are not user edits
+        let tracked = without_managed_sections(&body, &[]);
+        let Some(state) = self.notes.get(path) else {
+            self.notes.insert(path.to_string(), NoteState { body: tracked, window_base: None, last_entry_at: None });
+            return Ok(None);
+        };
+        if state.body.trim_end() == tracked.trim_end() {
+            return Ok(None);
+        }
+
+        let window = Duration::minutes(settings.coalesce_minutes);
+        let coalescing = state.last_entry_at.is_some_and(|at| now - at <= window) && !entries.is_empty();
+        let base = match (&state.window_base, coalescing) {
+            (Some(base), true) => base.clone(),
+            _ => state.body.clone(),
+        };
+
+        // Small edits are left to accumulate against the same snapshot
+        let summary = summarize_change(&state.body, &tracked);
+        if summary.changed_chars < settings.min_change_chars {
+            return Ok(None);
+        }
+
+        let local = summarize_change(&base, &tracked).describe();
+        let description = match refiner.filter(|_| settings.llm_refine) {
+            Some(llm) => refine_description(llm, &local, &base, &tracked).await.unwrap_or(local),
+            None => local,
+        };
+        let entry = format!("- {} — {}", now.format("%Y-%m-%d %H:%M"), description);
+        if coalescing {
+            entries[0] = entry;
+        } else {
+            entries.insert(0, entry);
+        }
+
+        let mut transaction = VaultTransaction::new(&format!("changelog update for {}", path));
+        if entries.len() > settings.max_entries {
+            let overflow = entries.split_off(settings.max_entries);
+            let archive = archive_path(settings, path);
+            let existing = store.read_file(&archive).await?.unwrap_or_else(|| {
+                format!("# Earlier changes to [[{}]]\n\n", path.trim_end_matches(".md"))
+            });
+            let (header, older) = existing.split_once("\n\n").unwrap_or((existing.as_str(), ""));
+            transaction = transaction.write(&archive, &format!("{}\n\n{}\n{}", header, overflow.join("\n"), older.trim_start()));
+            archive_link = Some(format!("{}[[{}]]", ARCHIVE_LINK_PREFIX, archive.trim_end_matches(".md")));
+        }
+
+        let updated = upsert(content, &changelog_section(&entries, archive_link.as_deref()))?;
+        transaction = transaction.write_if_unchanged(path, &updated, content);
+        let report = transaction.execute(store).await?;
+        if !report.is_committed() {
+            anyhow::bail!("{}", report.summary());
+        }
+
+        self.own_writes.insert(path.to_string(), stable_hash(&updated));
+        self.notes.insert(path.to_string(), NoteState {
+            body: tracked,
+            window_base: Some(base),
+            last_entry_at: Some(now),
+        });
+        Ok(Some(ChangelogUpdate { description, report }))
+    }
+}
+
+/// Ask the LLM to reword the local summary; the local one is kept on any failure
+async fn refine_description(llm: &dyn LLMClient, local: &str, old: &str, new: &str) -> Result<String> {
+    let excerpt = |text: &str| text.chars().take(2000).collect::<String>();
+    let prompt = format!(
+        "Write a single-line changelog entry (under 15 words, no date, no trailing period) for this edit.\n\
+         Local summary: {}\n\nBEFORE:\n{}\n\nAFTER:\n{}",
+        local, excerpt(old), excerpt(new)
+    );
+    let response = llm.send_message(vec![Message {
+        id: Uuid::new_v4().to_string(),
+        role: MessageRole::User,
+        content: prompt,
+        timestamp: Utc::now(),
+        function_call: None,
+    }]).await?;
+    let line = response.content.lines().map(str::trim).find(|l| !l.is_empty())
+        .context("Empty changelog refinement")?
+        .trim_start_matches("- ")
+        .to_string();
+    Ok(line)
+}
+
+pub async fn handle_changelog_command(args: ChangelogArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let settings = &config.changelog;
+    let state_path = &CachePaths::for_config(&config).changelog_state();
+
+    match args.action {
+        ChangelogAction::Scan { folder } => {
+            let mut tracker = ChangelogTracker::load(state_path)?;
+            let paths: Vec<String> = adapter.list_files_in_folder(&folder).await?
+                .into_iter()
+                .filter(|f| f.ends_with(".md"))
+                .map(|f| format!("{}/{}", folder.trim_end_matches('/'), f))
+                .collect();
+
+            let llm = if settings.llm_refine {
+                crate::ai_conversation::create_llm_client(&config, UsagePurpose::Analysis).ok()
+            } else {
+                None
+            };
+
+            let mut seen = HashSet::new();
+            let mut updated = Vec::new();
+            for (path, content) in adapter.fetch_many(&paths, crate::vault_transaction::DEFAULT_READ_CONCURRENCY).await {
+                let content = match content {
+                    Ok(content) => content,
+                    Err(e) => {
+                        println!("⚠️  {}: {}", path, e);
+                        continue;
+                    }
+                };
+                seen.insert(path.clone());
+                match tracker.observe(adapter, &path, &content, settings, llm.as_deref(), Utc::now()).await {
+                    Ok(Some(update)) => {
+                        updated.push(path.clone());
+                        if let Err(e) = update.report.append_to_log(adapter.transaction_log()) {
+                            log::warn!("could not write transaction log: {}", e);
+                        }
+                        println!("📝 {}: {}", path, update.description);
+                    }
+                    Ok(None) => {}
+                    Err(e) => println!("⚠️  {}: {}", path, e),
+                }
+            }
+            tracker.save(state_path)?;
+            println!("Scanned {} note(s), updated {} changelog(s)", seen.len(), updated.len());
+            crate::script::publish_output(serde_json::json!({ "scanned": seen.len(), "updated": updated }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_transaction::testing::FakeVault;
+
+    const SPEC: &str = "---\ntags: [living-doc]\n---\n# Deploy runbook\n\n## Deploy\nRun the pipeline.\n\n## Rollback\nRevert the release tag.\n";
+
+    fn settings() -> ChangelogSettings {
+        ChangelogSettings { max_entries: 2, ..ChangelogSettings::default() }
+    }
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-05-01T09:00:00Z").unwrap().with_timezone(&Utc) + Duration::minutes(minutes)
+    }
+
+    /// Simulate the user saving `content`, then the watcher noticing it and our write
+    async fn save(vault: &FakeVault, tracker: &mut ChangelogTracker, content: &str, minute: i64) -> Option<String> {
+        vault.write_file("Notes/runbook.md", content).await.unwrap();
+        let seen = vault.get("Notes/runbook.md").unwrap();
+        let update = tracker.observe(vault, "Notes/runbook.md", &seen, &settings(), None, at(minute)).await.unwrap();
+        // The watcher sees our own changelog write next; it must not produce an entry
+        let after = vault.get("Notes/runbook.md").unwrap();
+        assert!(tracker.observe(vault, "Notes/runbook.md", &after, &settings(), None, at(minute)).await.unwrap().is_none());
+        update.map(|u| u.description)
+    }
+
+    fn changelog_entries(content: &str) -> Vec<String> {
+        split_changelog(content).1
+    }
+
+    #[test]
+    fn test_summarize_change_attributes_sections() {
+        let edited = SPEC.replace("Revert the release tag.", "Revert the release tag.\nThen purge the CDN cache and notify #ops.")
+            + "\n## Monitoring\nWatch the error dashboard for 30 minutes.\n";
+        let summary = summarize_change(SPEC, &edited);
+        assert_eq!(summary.added_sections, vec!["Monitoring"]);
+        assert_eq!(summary.edited_sections, vec!["Rollback"]);
+        assert_eq!(summary.describe(), "Added \"Monitoring\"; edited \"Rollback\" (+3/-0 lines)");
+
+        let whitespace = SPEC.replace("Run the pipeline.", "   Run the pipeline.   ");
+        assert_eq!(summarize_change(SPEC, &whitespace).changed_chars, 0);
+    }
+
+    #[tokio::test]
+    async fn test_editing_session_coalesces_into_one_entry() {
+        let vault = FakeVault::new(&[("Notes/runbook.md", SPEC)]);
+        let mut tracker = ChangelogTracker::default();
+        assert_eq!(save(&vault, &mut tracker, SPEC, 0).await, None);
+
+        let first = SPEC.replace("Revert the release tag.", "Revert the release tag, then purge the CDN cache and page the on-call engineer.");
+        assert!(save(&vault, &mut tracker, &first, 1).await.is_some());
+
+        let current = vault.get("Notes/runbook.md").unwrap();
+        let second = current.replace("Run the pipeline.", "Run the pipeline with the canary flag and wait for the health checks to pass.");
+        let description = save(&vault, &mut tracker, &second, 10).await.unwrap();
+
+        // Whitespace-only tweak inside the window changes nothing
+        let current = vault.get("Notes/runbook.md").unwrap();
+        assert_eq!(save(&vault, &mut tracker, &current.replace("## Deploy\n", "## Deploy  \n"), 12).await, None);
+
+        let final_content = vault.get("Notes/runbook.md").unwrap();
+        let entries = changelog_entries(&final_content);
+        assert_eq!(entries.len(), 1, "{}", final_content);
+        assert_eq!(entries[0], format!("- 2024-05-01 09:10 — {}", description));
+        assert!(description.contains("\"Deploy\"") && description.contains("\"Rollback\""), "{}", description);
+        assert!(final_content.contains(&format!("{}\n<!-- arrowhead:begin owner=changelog id=changelog hash=", CHANGELOG_HEADING)));
+        assert!(final_content.ends_with(&format!("{}\n<!-- arrowhead:end id=changelog -->\n", entries[0])));
+    }
+
+    #[tokio::test]
+    async fn test_other_sections_survive_and_stale_writes_conflict() {
+        use crate::managed_sections::{update_sections, SectionEdit};
+        use crate::vault_transaction::WriteConflict;
+
+        let vault = FakeVault::new(&[("Notes/runbook.md", SPEC)]);
+        let mut tracker = ChangelogTracker::default();
+        save(&vault, &mut tracker, SPEC, 0).await;
+        let first = SPEC.replace("Revert the release tag.", "Revert the release tag, then purge the CDN cache and page the on-call engineer.");
+        save(&vault, &mut tracker, &first, 1).await.unwrap();
+
+        // Backlinks are written above the changelog and aren't tracked as an edit
+        let backlinks = SectionWrite::new("backlinks", "backlinks", "- [[Incident]] (references): \"See the runbook.\"\n").heading("## Referenced by");
+        update_sections(&vault, "Notes/runbook.md", &[SectionEdit::Upsert(backlinks)]).await.unwrap().unwrap();
+        let with_backlinks = vault.get("Notes/runbook.md").unwrap();
+        assert!(tracker.observe(&vault, "Notes/runbook.md", &with_backlinks, &settings(), None, at(90)).await.unwrap().is_none());
+
+        let edited = with_backlinks.replace("Run the pipeline.", "Run the pipeline with the canary flag and wait for the health checks to pass.");
+        save(&vault, &mut tracker, &edited, 120).await.unwrap();
+        let content = vault.get("Notes/runbook.md").unwrap();
+        let ids: Vec<String> = managed_sections(&content).into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["backlinks", "changelog"]);
+        assert_eq!(changelog_entries(&content).len(), 2);
+
+        // An observation of a note that changed since is refused instead of overwriting it
+        let stale = content.replace("Run the pipeline", "Kick off the pipeline");
+        let error = tracker.observe(&vault, "Notes/runbook.md", &stale, &settings(), None, at(240)).await.unwrap_err();
+        assert!(error.downcast_ref::<WriteConflict>().is_some(), "{}", error);
+        assert_eq!(vault.get("Notes/runbook.md").unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_entries_outside_window_and_archive_overflow() {
+        let vault = FakeVault::new(&[("Notes/runbook.md", SPEC)]);
+        let mut tracker = ChangelogTracker::default();
+        save(&vault, &mut tracker, SPEC, 0).await;
+
+        for (i, minute) in [0, 60, 120].iter().enumerate() {
+            let current = vault.get("Notes/runbook.md").unwrap();
+            let step = format!("## Step {}\nA new substantive step describing what to check next.\n\n", i);
+            let edited = match current.find(CHANGELOG_HEADING) {
+                Some(at) => format!("{}{}{}", &current[..at], step, &current[at..]),
+                None => format!("{}\n{}", current, step),
+            };
+            assert!(save(&vault, &mut tracker, &edited, *minute + 1).await.is_some());
+        }
+
+        let content = vault.get("Notes/runbook.md").unwrap();
+        let entries = changelog_entries(&content);
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].contains("\"Step 2\""));
+        assert!(content.contains("- Earlier changes: [[Archive/Changelogs/runbook changelog]]"));
+
+        let archive = vault.get("Archive/Changelogs/runbook changelog.md").unwrap();
+        assert!(archive.starts_with("# Earlier changes to [[Notes/runbook]]\n\n- 2024-05-01 09:01"));
+        assert!(archive.contains("\"Step 0\""));
+    }
+
+    #[tokio::test]
+    async fn test_untagged_notes_are_ignored() {
+        let plain = SPEC.replace("tags: [living-doc]", "tags: [ops]");
+        let vault = FakeVault::new(&[("Notes/runbook.md", &plain)]);
+        let mut tracker = ChangelogTracker::default();
+        save(&vault, &mut tracker, &plain, 0).await;
+        let edited = plain.replace("Run the pipeline.", "Run the pipeline with the canary flag and wait for all health checks.");
+        assert_eq!(save(&vault, &mut tracker, &edited, 1).await, None);
+        assert!(!vault.get("Notes/runbook.md").unwrap().contains(CHANGELOG_HEADING));
+    }
+}
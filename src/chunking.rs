@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use crate::obsidian_adapter::{AnalysisConfig, Concept, ContentAnalysis, Entity, SentimentAnalysis};
+
+/// Whether `line` is a markdown heading such as "## Goals"
+fn is_heading(line: &str) -> bool {
+    let hashes = line.chars().take_while(|c| *c == '#').count();
+    (1..=6).contains(&hashes) && line[hashes..].starts_with([' ', '\t'])
+}
+
+/// `content` cut into paragraphs, each ending with the blank line after it, with every
+/// heading starting a new one. Blank lines and headings inside code blocks don't count.
+fn blocks(content: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let (mut start, mut offset, mut in_code) = (0, 0, false);
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+        }
+        if !in_code && is_heading(trimmed) && offset > start {
+            blocks.push(&content[start..offset]);
+            start = offset;
+        }
+        offset += line.len();
+        if !in_code && trimmed.is_empty() && offset > start {
+            blocks.push(&content[start..offset]);
+            start = offset;
+        }
+    }
+    if start < content.len() {
+        blocks.push(&content[start..]);
+    }
+    blocks
+}
+
+/// A paragraph too long for one chunk, cut into pieces of at most `max_chars`, after a space
+/// where there is one in the second half of the piece
+fn hard_split(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while rest.chars().count() > max_chars {
+        let limit = rest.char_indices().nth(max_chars).map_or(rest.len(), |(i, _)| i);
+        let cut = rest[..limit].char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map(|(i, c)| i + c.len_utf8())
+            .filter(|cut| *cut > limit / 2)
+            .unwrap_or(limit);
+        parts.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+    if !rest.is_empty() {
+        parts.push(rest);
+    }
+    parts
+}
+
+/// The last `chars` characters of `text`, starting at a word where possible
+fn tail(text: &str, chars: usize) -> &str {
+    let count = text.chars().count();
+    if chars == 0 {
+        return "";
+    }
+    if count <= chars {
+        return text;
+    }
+    let start = text.char_indices().nth(count - chars).map_or(text.len(), |(i, _)| i);
+    let tail = &text[start..];
+    match tail.find(char::is_whitespace) {
+        Some(space) if space < tail.len() / 2 => tail[space..].trim_start(),
+        _ => tail,
+    }
+}
+
+/// Split `content` into chunks of at most `max_chars` characters, at headings and paragraph
+/// breaks where possible. Each chunk after the first begins with the last `overlap_chars`
+/// characters of the one before, so text cut at a boundary is seen whole at least once.
+/// Content that fits is returned as the only chunk.
+pub fn split_into_chunks(content: &str, max_chars: usize, overlap_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    if content.chars().count() <= max_chars {
+        return vec![content.to_string()];
+    }
+    let overlap = overlap_chars.min(max_chars / 2);
+    // Room left for new text once the overlap is in
+    let room = (max_chars - overlap).max(1);
+
+    let mut pieces: Vec<String> = Vec::new();
+    let (mut current, mut current_len) = (String::new(), 0);
+    for block in blocks(content) {
+        let len = block.chars().count();
+        // A heading starts a new chunk once the current one is half full
+        let starts_section = is_heading(block.trim_start()) && current_len >= room / 2;
+        if current_len > 0 && (current_len + len > room || starts_section) {
+            pieces.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        if len <= room {
+            current.push_str(block);
+            current_len += len;
+            continue;
+        }
+        let mut parts = hard_split(block, room);
+        let last = parts.pop().unwrap_or_default();
+        pieces.extend(parts.into_iter().map(str::to_string));
+        current.push_str(last);
+        current_len = last.chars().count();
+    }
+    if !current.trim().is_empty() {
+        pieces.push(current);
+    }
+
+    let mut chunks = Vec::with_capacity(pieces.len());
+    for (i, piece) in pieces.iter().enumerate() {
+        match i.checked_sub(1).map(|previous| tail(&pieces[previous], overlap)) {
+            Some(overlap) if !overlap.is_empty() => chunks.push(format!("{}{}", overlap, piece)),
+            _ => chunks.push(piece.clone()),
+        }
+    }
+    chunks
+}
+
+/// Tally of names across chunk analyses, matched case-insensitively. The first spelling
+/// seen is kept.
+struct Tally<T> {
+    entries: Vec<(T, usize)>,
+    index: HashMap<String, usize>,
+}
+
+impl<T> Default for Tally<T> {
+    fn default() -> Self {
+        Self { entries: Vec::new(), index: HashMap::new() }
+    }
+}
+
+impl<T> Tally<T> {
+    /// Count `item` under `key`, merging it into the entry already there with `merge`
+    fn add(&mut self, key: &str, item: T, merge: impl FnOnce(&mut T, T)) {
+        let key = key.trim().to_lowercase();
+        if key.is_empty() {
+            return;
+        }
+        match self.index.get(&key) {
+            Some(&i) => {
+                let (existing, count) = &mut self.entries[i];
+                merge(existing, item);
+                *count += 1;
+            }
+            None => {
+                self.index.insert(key, self.entries.len());
+                self.entries.push((item, 1));
+            }
+        }
+    }
+
+    /// Most frequent first; ties broken by `score`, then by which was seen first
+    fn ranked(self, score: impl Fn(&T) -> f32) -> Vec<T> {
+        let mut entries: Vec<(usize, (T, usize))> = self.entries.into_iter().enumerate().collect();
+        entries.sort_by(|(ai, (a, ac)), (bi, (b, bc))| {
+            bc.cmp(ac).then_with(|| score(b).total_cmp(&score(a))).then_with(|| ai.cmp(bi))
+        });
+        entries.into_iter().map(|(_, (item, _))| item).collect()
+    }
+}
+
+/// Strings from every chunk, deduplicated and ordered by how many chunks mention them
+fn ranked_names<'a>(names: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut tally = Tally::default();
+    for name in names {
+        tally.add(name, name.clone(), |_, _| {});
+    }
+    tally.ranked(|_| 0.0)
+}
+
+/// The label with the most confidence summed over the chunks, and the chunks' mean
+/// confidence
+fn merge_sentiment(analyses: &[ContentAnalysis]) -> SentimentAnalysis {
+    let mut totals: Vec<(String, f32)> = Vec::new();
+    for analysis in analyses {
+        let label = analysis.sentiment.overall.trim().to_lowercase();
+        match totals.iter_mut().find(|(existing, _)| *existing == label) {
+            Some((_, total)) => *total += analysis.sentiment.confidence,
+            None => totals.push((label, analysis.sentiment.confidence)),
+        }
+    }
+    let overall = totals.iter()
+        .fold(None::<&(String, f32)>, |best, entry| match best {
+            Some(best) if best.1 >= entry.1 => Some(best),
+            _ => Some(entry),
+        })
+        .map_or_else(|| SentimentAnalysis::default().overall, |(label, _)| label.clone());
+    SentimentAnalysis {
+        overall,
+        confidence: analyses.iter().map(|a| a.sentiment.confidence).sum::<f32>() / analyses.len().max(1) as f32,
+        emotions: ranked_names(analyses.iter().flat_map(|a| &a.sentiment.emotions)),
+    }
+}
+
+/// Combine the analyses of a note's chunks into one for the whole note. Themes, keywords,
+/// entities and concepts are deduplicated and ranked by how many chunks found them, then cut
+/// to the limits in `config`. The summary is the chunk summaries joined; callers with a
+/// model replace it with a summary of them.
+pub fn merge_chunk_analyses(analyses: &[ContentAnalysis], config: &AnalysisConfig) -> ContentAnalysis {
+    if let [only] = analyses {
+        return only.clone();
+    }
+
+    let mut themes = ranked_names(analyses.iter().flat_map(|a| &a.themes));
+    themes.truncate(config.max_themes);
+
+    // As many keywords as the longest chunk list, so a long note doesn't get dozens
+    let mut keywords = ranked_names(analyses.iter().flat_map(|a| &a.keywords));
+    keywords.truncate(analyses.iter().map(|a| a.keywords.len()).max().unwrap_or(0));
+
+    let mut entities: Tally<Entity> = Tally::default();
+    for entity in analyses.iter().flat_map(|a| &a.entities) {
+        let key = format!("{}\u{0}{}", entity.entity_type.to_uppercase(), entity.text);
+        entities.add(&key, entity.clone(), |existing, entity| {
+            if entity.confidence > existing.confidence {
+                existing.confidence = entity.confidence;
+            }
+            if existing.context.is_none() {
+                existing.context = entity.context;
+            }
+        });
+    }
+    let mut entities = entities.ranked(|entity| entity.confidence);
+    entities.truncate(config.max_entities);
+
+    let mut concepts: Tally<Concept> = Tally::default();
+    for concept in analyses.iter().flat_map(|a| &a.concepts) {
+        concepts.add(&concept.name, concept.clone(), |existing, concept| {
+            existing.importance = existing.importance.max(concept.importance);
+            if existing.description.is_none() {
+                existing.description = concept.description;
+            }
+            for related in concept.related_concepts {
+                if !existing.related_concepts.iter().any(|r| r.eq_ignore_ascii_case(&related)) {
+                    existing.related_concepts.push(related);
+                }
+            }
+        });
+    }
+    let mut concepts = concepts.ranked(|concept| concept.importance);
+    concepts.truncate(config.max_concepts);
+
+    let summaries: Vec<&str> = analyses.iter().filter_map(|a| a.summary.as_deref()).filter(|s| !s.trim().is_empty()).collect();
+    let complexity: Vec<f32> = analyses.iter().filter_map(|a| a.complexity_score).collect();
+    let reading_times: Vec<u32> = analyses.iter().filter_map(|a| a.reading_time_minutes).collect();
+
+    ContentAnalysis {
+        themes,
+        sentiment: merge_sentiment(analyses),
+        entities,
+        concepts,
+        summary: (!summaries.is_empty()).then(|| summaries.join(" ")),
+        keywords,
+        category: ranked_names(analyses.iter().filter_map(|a| a.category.as_ref())).into_iter().next(),
+        complexity_score: (!complexity.is_empty()).then(|| complexity.iter().sum::<f32>() / complexity.len() as f32),
+        reading_time_minutes: (!reading_times.is_empty()).then(|| reading_times.iter().sum()),
+    }
+}
+
+/// One vector for a document embedded in chunks: the mean of the chunks' unit vectors,
+/// scaled back to unit length
+pub fn mean_pool(vectors: &[Vec<f32>]) -> Vec<f32> {
+    let Some(dimension) = vectors.first().map(Vec::len) else { return Vec::new() };
+    let mut sum = vec![0.0f32; dimension];
+    for vector in vectors {
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        for (total, x) in sum.iter_mut().zip(vector) {
+            *total += x / norm;
+        }
+    }
+    let norm = sum.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        sum.iter_mut().for_each(|x| *x /= norm);
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analysis(themes: &[&str], sentiment: (&str, f32), summary: &str) -> ContentAnalysis {
+        ContentAnalysis {
+            themes: themes.iter().map(|t| t.to_string()).collect(),
+            sentiment: SentimentAnalysis { overall: sentiment.0.to_string(), confidence: sentiment.1, emotions: vec!["calm".to_string()] },
+            summary: Some(summary.to_string()),
+            keywords: themes.iter().map(|t| t.to_lowercase()).collect(),
+            ..ContentAnalysis::default()
+        }
+    }
+
+    fn entity(text: &str, confidence: f32, context: Option<&str>) -> Entity {
+        Entity { text: text.to_string(), entity_type: "PERSON".to_string(), confidence, context: context.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_chunks_break_at_headings_and_paragraphs() {
+        let section = |title: &str| format!("## {}\n\n{}\n\n", title, "word ".repeat(30));
+        let content = format!("{}{}{}", section("One"), section("Two"), section("Three"));
+        let chunks = split_into_chunks(&content, 200, 0);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.starts_with("## ") && chunk.chars().count() <= 200));
+        assert_eq!(chunks.concat(), content);
+
+        assert_eq!(split_into_chunks("short", 200, 50), ["short"]);
+    }
+
+    #[test]
+    fn test_long_paragraphs_are_cut_between_words_with_overlap() {
+        let content = "alpha beta gamma delta ".repeat(20);
+        let chunks = split_into_chunks(&content, 100, 20);
+        assert!(chunks.len() > 4);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 100 && !chunk.starts_with(' ')));
+        let overlap = tail(&chunks[0], 20);
+        assert!(!overlap.is_empty() && overlap.chars().count() <= 20);
+        assert!(chunks[1].starts_with(overlap));
+        assert!(split_into_chunks(&content, 100, 0).iter().all(|chunk| chunk.ends_with(' ')));
+        assert_eq!(split_into_chunks(&content, 100, 0).concat(), content);
+
+        let wide = "é".repeat(250);
+        let chunks = split_into_chunks(&wide, 100, 0);
+        assert_eq!(chunks.iter().map(|c| c.chars().count()).collect::<Vec<_>>(), [100, 100, 50]);
+    }
+
+    #[test]
+    fn test_merge_ranks_by_frequency_and_respects_limits() {
+        let mut first = analysis(&["Rust", "testing"], ("positive", 0.9), "Sets up the project.");
+        first.entities = vec![entity("Ada", 0.7, None)];
+        first.complexity_score = Some(4.0);
+        first.reading_time_minutes = Some(3);
+        first.category = Some("Engineering".to_string());
+        let mut second = analysis(&["rust", "deployment", "testing"], ("negative", 0.6), "Deploys it.");
+        second.entities = vec![entity("ada", 0.9, Some("lead")), entity("Bob", 0.8, None)];
+        second.complexity_score = Some(6.0);
+        second.reading_time_minutes = Some(2);
+        second.category = Some("engineering".to_string());
+        let third = analysis(&["Deployment", "rust"], ("positive", 0.3), "");
+
+        let config = AnalysisConfig { max_themes: 2, max_entities: 5, ..AnalysisConfig::default() };
+        let merged = merge_chunk_analyses(&[first, second, third], &config);
+
+        assert_eq!(merged.themes, ["Rust", "testing"]);
+        assert_eq!(merged.keywords, ["rust", "testing", "deployment"]);
+        assert_eq!(merged.sentiment.overall, "positive");
+        assert!((merged.sentiment.confidence - 0.6).abs() < 1e-6);
+        assert_eq!(merged.sentiment.emotions, ["calm"]);
+        assert_eq!(merged.entities.len(), 2);
+        assert_eq!((merged.entities[0].text.as_str(), merged.entities[0].confidence), ("Ada", 0.9));
+        assert_eq!(merged.entities[0].context.as_deref(), Some("lead"));
+        assert_eq!(merged.summary.as_deref(), Some("Sets up the project. Deploys it."));
+        assert_eq!(merged.category.as_deref(), Some("Engineering"));
+        assert_eq!(merged.complexity_score, Some(5.0));
+        assert_eq!(merged.reading_time_minutes, Some(5));
+    }
+
+    #[test]
+    fn test_merge_combines_concepts() {
+        let concept = |name: &str, importance: f32, related: &[&str]| Concept {
+            name: name.to_string(),
+            description: None,
+            related_concepts: related.iter().map(|r| r.to_string()).collect(),
+            importance,
+        };
+        let first = ContentAnalysis {
+            concepts: vec![concept("Ownership", 0.5, &["Borrowing"]), concept("Traits", 0.9, &[])],
+            ..ContentAnalysis::default()
+        };
+        let second = ContentAnalysis {
+            concepts: vec![concept("ownership", 0.8, &["borrowing", "Lifetimes"])],
+            ..ContentAnalysis::default()
+        };
+
+        let merged = merge_chunk_analyses(&[first, second], &AnalysisConfig::default());
+        assert_eq!(merged.concepts.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["Ownership", "Traits"]);
+        assert_eq!(merged.concepts[0].importance, 0.8);
+        assert_eq!(merged.concepts[0].related_concepts, ["Borrowing", "Lifetimes"]);
+    }
+
+    #[test]
+    fn test_mean_pool_is_unit_length() {
+        let pooled = mean_pool(&[vec![2.0, 0.0], vec![0.0, 0.5]]);
+        let half = 0.5f32.sqrt();
+        assert!((pooled[0] - half).abs() < 1e-6 && (pooled[1] - half).abs() < 1e-6);
+        assert!(mean_pool(&[]).is_empty());
+    }
+}
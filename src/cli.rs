@@ -1,12 +1,67 @@
-use clap::{Parser, Subcommand, Args};
+use clap::{Parser, Subcommand, Args, ValueEnum};
 use serde::{Deserialize, Serialize};
 
+use crate::obsidian_adapter::TemplateMergeStrategy;
+use crate::report::{DetailLevel, ReportView};
+use crate::todo_model::{TodoSort, TodoStatus};
+
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 #[clap(propagate_version = true)]
 pub struct Cli {
     #[clap(subcommand)]
     pub command: Option<Commands>,
+    /// Output of list, view and report commands: text or json
+    #[clap(long, global = true, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+    /// Print only the headline of reports such as doctor, health, risks and index
+    #[clap(long, global = true, conflicts_with = "detail")]
+    pub summary: bool,
+    /// Print everything in those reports, including per-item data
+    #[clap(long, global = true)]
+    pub detail: bool,
+    /// Continue the most recent interactive conversation
+    #[clap(long, conflicts_with = "session")]
+    pub resume: bool,
+    /// Continue the interactive conversation with this id (or unique id prefix)
+    #[clap(long)]
+    pub session: Option<String>,
+    /// Disable every AI feature, as ARROWHEAD_OFFLINE=1 does; todo, goal and note commands still work
+    #[clap(long, global = true)]
+    pub offline: bool,
+    /// Don't show spinners or progress bars (they are also off when output isn't a terminal)
+    #[clap(long, global = true)]
+    pub quiet: bool,
+    /// Work on this vault from `[[vaults]]` instead of the default one
+    #[clap(long, global = true)]
+    pub vault: Option<String>,
+    /// Use the settings of this `[profiles.<name>]` section, as ARROWHEAD_PROFILE=<name> does
+    #[clap(long, global = true)]
+    pub profile: Option<String>,
+}
+
+/// The full command tree. Parsing, shell completions and man pages all start from this.
+pub fn build_cli() -> clap::Command {
+    <Cli as clap::CommandFactory>::command()
+}
+
+impl Cli {
+    pub fn report_view(&self) -> ReportView {
+        let level = match (self.summary, self.detail) {
+            (true, _) => DetailLevel::Summary,
+            (_, true) => DetailLevel::Detail,
+            _ => DetailLevel::Normal,
+        };
+        ReportView { level, json: self.format == OutputFormat::Json }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
 }
 
 #[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +72,98 @@ pub enum Commands {
     Goal(GoalArgs),
     /// Manage notes
     Note(NoteArgs),
+    /// Manage calendar events
+    Calendar(CalendarArgs),
+    /// Keep todo due dates and a calendar in step
+    Sync(SyncArgs),
+    /// Check notes against the team glossary
+    Glossary(GlossaryArgs),
+    /// Propose tags, folders and links for notes, optionally as a reviewable plan note
+    Organize(OrganizeArgs),
+    /// Apply the checked changes of a plan note written by `organize --plan-note`
+    ApplyPlan(ApplyPlanArgs),
+    /// Stop Arrowhead features (analysis, autolink, suggestions, organize, embedding) from touching a note
+    Mute(MuteArgs),
+    /// Re-enable features previously muted on a note
+    Unmute(MuteArgs),
+    /// Show deadline risks across all active work
+    Risks(RisksArgs),
+    /// Track deadlines and the time blocked out for them
+    Deadline(DeadlineArgs),
+    /// Time a focus session on a deadline's time block, logging interruptions
+    Focus(FocusArgs),
+    /// Explain how a meeting slot is scored against the [scheduling] preferences
+    Schedule(ScheduleArgs),
+    /// Plan working time for deadlines and todos
+    Plan(PlanArgs),
+    /// Create or refresh today's daily note with todos, events and deadlines
+    Daily(DailyArgs),
+    /// Analyze, embed and optionally organize notes as they change
+    Watch(WatchArgs),
+    /// Serve suggestions to the Obsidian plugin over JSON-RPC on stdin/stdout
+    Serve(ServeArgs),
+    /// Maintain changelog sections on notes tagged #living-doc
+    Changelog(ChangelogArgs),
+    /// Move notes into folders by the configured filing rules, explaining each decision
+    File(FileArgs),
+    /// Check the configuration and filing rules for problems
+    Doctor,
+    /// Score vault hygiene (tags, links, analysis, embeddings, staleness) and track it over time
+    Health(HealthArgs),
+    /// Chart sentiment, themes and complexity of dated notes such as a journal over time
+    Insights(InsightsArgs),
+    /// Write note analysis or embeddings to a file for spreadsheets and external tools
+    Export(ExportArgs),
+    /// Keep a "Referenced by" section listing inbound links on important notes
+    Backlinks(BacklinksArgs),
+    /// Show the links into and out of a note, or the notes with none, from the index
+    Links(LinksArgs),
+    /// List the notes most similar to one, and keep a "Related Notes" section in it with --write
+    Related(RelatedArgs),
+    /// List the vault's tags with usage counts, and rename or merge them across every note
+    Tags(TagsArgs),
+    /// List, lint and repair the sections Arrowhead maintains inside notes
+    Sections(SectionsArgs),
+    /// Find sync-conflict copies, compare them with their originals and resolve them
+    Conflicts(ConflictsArgs),
+    /// Embed new and changed notes for semantic search
+    Index(IndexArgs),
+    /// Search notes by keyword and meaning, optionally saving the query under a name
+    Search(SearchArgs),
+    /// List, run or delete saved searches
+    Saved(SavedArgs),
+    /// Propose splitting an oversized note into parts, edit the plan and apply it
+    Split(SplitArgs),
+    /// Recommend how to answer new meeting invitations and respond to them
+    Invites(InvitesArgs),
+    /// Prepare meetings: AI-written agendas, optionally sent with the invitation
+    Meeting(MeetingArgs),
+    /// Export the template database to a shareable file or import one
+    Template(TemplateArgs),
+    /// Run the steps of a TOML or YAML script in one process
+    Run(RunArgs),
+    /// List, edit and share facts remembered across conversations
+    Memory(MemoryArgs),
+    /// List saved interactive conversations
+    Sessions(SessionsArgs),
+    /// Show today's and this month's LLM requests and tokens by feature
+    Usage,
+    /// Send due deadline and todo reminders, once or every few minutes with --daemon
+    Remind(RemindArgs),
+    /// Restore a note to its version from before an AI feature rewrote it
+    Undo(UndoArgs),
+    /// List the stored versions of a note from before AI changes, or restore one
+    History(HistoryArgs),
+    /// Show the size of the embedding and template caches, or delete them
+    Cache(CacheArgs),
+    /// List the configured vaults or choose the default one
+    Vault(VaultArgs),
+    /// List, show or edit the prompts sent to the model
+    Prompts(PromptsArgs),
+    /// Print a shell completion script, e.g. `arrowhead completions zsh > ~/.zfunc/_arrowhead`
+    Completions(CompletionsArgs),
+    /// Write man pages for every command into a folder
+    Manpages(ManpagesArgs),
     /// Manage configuration (API keys, settings, etc.)
     Config(ConfigArgs),
 }
@@ -32,15 +179,27 @@ pub enum TodoAction {
     /// Add a new todo
     Add {
         description: String,
-        #[clap(short, long)]
+        /// Due date: YYYY-MM-DD or a phrase such as friday, in 3 days or end of month
+        #[clap(short, long, alias = "due")]
         due_date: Option<String>,
         #[clap(short, long, value_parser)]
         tags: Vec<String>,
+        /// Time estimate, e.g. 45m, 2h or 1h30m
+        #[clap(short, long)]
+        estimate: Option<String>,
     },
     /// List all todos
     List {
-        #[clap(short, long)]
-        status: Option<String>, // e.g., "open", "done"
+        #[clap(short, long, value_enum)]
+        status: Option<TodoStatus>,
+        /// Only todos due before this day (YYYY-MM-DD, today, friday, end of week, ...)
+        #[clap(long)]
+        due_before: Option<String>,
+        /// Only open todos whose due date has passed
+        #[clap(long)]
+        overdue: bool,
+        #[clap(long, value_enum, default_value_t)]
+        sort: TodoSort,
     },
     /// Mark a todo as done
     Done {
@@ -49,7 +208,38 @@ pub enum TodoAction {
     /// View a specific todo
     View {
         id: String,
-    }
+    },
+    /// Import `- [ ]` checkbox tasks from notes elsewhere in the vault into the task index
+    Import {
+        /// Walk the vault for checkbox tasks
+        #[clap(long)]
+        scan: bool,
+        /// Only scan this folder
+        #[clap(long)]
+        folder: Option<String>,
+        /// Import tasks that are already ticked as well
+        #[clap(long)]
+        include_done: bool,
+        /// Show what would be imported without writing the index
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Bulk-edit open todos, e.g. "everything due this week tagged #errand to saturday"
+    Reschedule {
+        /// `<filters> <action>`; filters: due ..., overdue, undated, tagged #tag, priority <level>,
+        /// in <folder>, matching "text"; actions: to <date>, by <duration>, to priority <level>,
+        /// add #tag, remove #tag
+        request: String,
+        /// Preview the changes without applying them
+        #[clap(long)]
+        dry_run: bool,
+        /// Apply without asking for confirmation
+        #[clap(short, long)]
+        yes: bool,
+        /// Let the AI translate free-form phrasing the local parser does not understand
+        #[clap(long)]
+        llm_assist: bool,
+    },
 }
 
 #[derive(Args, Debug, Clone, Serialize, Deserialize)]
@@ -65,9 +255,10 @@ pub enum GoalAction {
         title: String,
         #[clap(short, long)]
         description: Option<String>,
+        /// Target date: YYYY-MM-DD or a phrase such as end of month or next friday
         #[clap(short, long)]
         target_date: Option<String>,
-        #[clap(short, long, value_parser)]
+        #[clap(long, value_parser)]
         tags: Vec<String>,
     },
     /// List all goals
@@ -84,8 +275,12 @@ pub enum GoalAction {
         description: Option<String>,
         #[clap(short, long)]
         status: Option<String>,
-        #[clap(short, long)]
+        /// New target date: YYYY-MM-DD or a phrase such as end of month or next friday
+        #[clap(long)]
         target_date: Option<String>,
+        /// Save even if the goal note changed in the vault since it was read
+        #[clap(long)]
+        force: bool,
     },
     /// View a specific goal
     View {
@@ -108,6 +303,9 @@ pub enum NoteAction {
         content: Option<String>,
         #[clap(short, long, value_parser)]
         tags: Vec<String>,
+        /// Offer the saved template the content fits best, and write the note into it
+        #[clap(long)]
+        suggest_template: bool,
     },
     /// List all notes
     List {
@@ -123,16 +321,1008 @@ pub enum NoteAction {
     Append {
         name_or_id: String,
         content: String,
+        /// Add it at the end of this heading's section instead of the end of the note;
+        /// nested headings are joined with `::`, e.g. "Log::Meetings"
+        #[clap(long)]
+        heading: Option<String>,
     },
     /// Edit an existing note (could open in $EDITOR)
     Edit {
         name_or_id: String,
-    }
+        /// Save even if the note changed in the vault while it was being edited
+        #[clap(long)]
+        force: bool,
+    },
+    /// Rename a note and rewrite every [[wikilink]] that points to it
+    Rename {
+        name_or_id: String,
+        new_title: String,
+        /// Keep going if some files fail to update instead of rolling everything back
+        #[clap(long)]
+        continue_on_error: bool,
+        /// Skip the confirmation prompt (if `move` allows --yes)
+        #[clap(short, long)]
+        yes: bool,
+    },
+    /// Print the structural skeleton (outline, counts, simhash) of a note
+    Skeleton {
+        /// Vault path (e.g., "Notes/roadmap.md") or note name
+        path: String,
+    },
+    /// Create a note from a saved template, asking for each placeholder
+    New {
+        /// Template id or name
+        #[clap(long)]
+        template: String,
+        /// Vault path of the new note; defaults to Notes/<title or template name>.md
+        #[clap(long)]
+        to: Option<String>,
+        /// Placeholder value as name=value (repeatable); without a terminal every required
+        /// placeholder must be set this way
+        #[clap(long = "set", value_parser)]
+        set: Vec<String>,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct CalendarArgs {
+    #[clap(subcommand)]
+    pub action: CalendarAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum CalendarAction {
+    /// Create an event from a configured template (e.g., "one_on_one")
+    Quick {
+        /// Template name from `[calendar.templates.<name>]`
+        template: String,
+        /// Template variable as key=value (repeatable), e.g. --var name=Alice
+        #[clap(long = "var", value_parser)]
+        vars: Vec<String>,
+        /// Start time (e.g., "tomorrow 2pm", "next friday 10:30"); defaults to the next free slot
+        #[clap(long)]
+        at: Option<String>,
+        /// Override the template title
+        #[clap(long)]
+        title: Option<String>,
+        /// Override the template duration in minutes
+        #[clap(long)]
+        duration: Option<u32>,
+        /// Override the template attendees
+        #[clap(long = "attendee", value_parser)]
+        attendees: Vec<String>,
+        /// Override the template location
+        #[clap(long)]
+        location: Option<String>,
+        /// Create the event even if it conflicts with existing events
+        #[clap(long)]
+        force: bool,
+    },
+    /// List configured event templates
+    Templates,
+    /// List the calendars on the CalDAV server
+    List {
+        /// Make this calendar (id or name) the default and save it to the config file
+        #[clap(long)]
+        select: Option<String>,
+    },
+    /// Test the CalDAV connection and explain what's wrong with it
+    Doctor,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct GlossaryArgs {
+    #[clap(subcommand)]
+    pub action: GlossaryAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum GlossaryAction {
+    /// Report glossary violations in a note, or in every note with --all
+    Check {
+        /// Vault path of the note (e.g., "Notes/roadmap.md")
+        path: Option<String>,
+        /// Check every note, todo and goal
+        #[clap(long)]
+        all: bool,
+        /// Rewrite the violations in place
+        #[clap(long)]
+        fix: bool,
+        /// Skip the confirmation prompt for --fix (if `bulk-write` allows --yes)
+        #[clap(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizeArgs {
+    /// Vault paths of the notes to organize (e.g., "Notes/roadmap.md")
+    pub paths: Vec<String>,
+    /// Organize every note in the Notes folder
+    #[clap(long)]
+    pub all: bool,
+    /// Write the proposed changes to this note as a checklist instead of printing them
+    #[clap(long)]
+    pub plan_note: Option<String>,
+    /// Skip suggestions below this confidence
+    #[clap(long, default_value_t = 0.5)]
+    pub min_confidence: f32,
+    /// Skip the confirmation asked for large runs (if `llm-spend` allows --yes)
+    #[clap(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct ApplyPlanArgs {
+    /// Vault path of the plan note (e.g., "Plans/organize-2024-05.md")
+    pub plan_note: String,
+    /// Skip the confirmation prompt (if `bulk-write` allows --yes)
+    #[clap(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct MuteArgs {
+    /// Vault path of the note (e.g., "Templates/meeting.md")
+    pub path: String,
+    /// Comma-separated features: analysis, autolink, suggestions, organize, embedding or all (default: all)
+    #[clap(long, value_delimiter = ',')]
+    pub features: Vec<String>,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct ServeArgs {}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct RisksArgs {
+    /// Print the report as JSON
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct HealthArgs {
+    /// Print the score and its components as JSON
+    #[clap(long)]
+    pub json: bool,
+    /// List the broken links and notes with outdated frontmatter (same as the global --detail)
+    #[clap(long)]
+    pub details: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct InsightsArgs {
+    /// Folder of the notes to chart (default: the `insights.folder` setting)
+    #[clap(long)]
+    pub folder: Option<String>,
+    /// Only notes dated on or after this day (YYYY-MM-DD or a phrase such as june 1)
+    #[clap(long)]
+    pub since: Option<String>,
+    /// Only notes dated on or before this day (YYYY-MM-DD or a phrase such as yesterday)
+    #[clap(long)]
+    pub until: Option<String>,
+    /// Group notes by day or by week
+    #[clap(long, value_enum, default_value = "week")]
+    pub by: InsightsBucket,
+    /// Analyze notes that have no analysis yet instead of listing them as skipped
+    #[clap(long)]
+    pub analyze_missing: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InsightsBucket {
+    Day,
+    Week,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct ExportArgs {
+    #[clap(subcommand)]
+    pub action: ExportAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum ExportAction {
+    /// One row per note with its category, sentiment, complexity, themes, keywords and entity counts
+    Analysis {
+        /// File to write
+        #[clap(long)]
+        out: String,
+        #[clap(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+        /// Only notes under this folder (default: the whole vault)
+        #[clap(long)]
+        folder: Option<String>,
+        /// Analyze notes that have no analysis yet instead of exporting them with empty columns
+        #[clap(long)]
+        analyze_missing: bool,
+    },
+    /// One JSON line per embedded note with its path, vector and content hash
+    Embeddings {
+        /// File to write
+        #[clap(long)]
+        out: String,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct SearchArgs {
+    /// Words to find plus filters, e.g. 'tag:#waiting status:open sort:due'
+    pub query: String,
+    /// Only rank notes by meaning with embeddings
+    #[clap(long, alias = "semantic", conflicts_with = "keyword_only")]
+    pub semantic_only: bool,
+    /// Only match words, without embeddings
+    #[clap(long)]
+    pub keyword_only: bool,
+    /// Only search notes under this folder
+    #[clap(long)]
+    pub folder: Option<String>,
+    /// Only search notes with this tag; repeat for several
+    #[clap(long = "tag")]
+    pub tags: Vec<String>,
+    /// Maximum number of results
+    #[clap(long, default_value = "10")]
+    pub limit: usize,
+    /// Save the search under this name
+    #[clap(long)]
+    pub save: Option<String>,
+    /// Print the results as JSON
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct SavedArgs {
+    #[clap(subcommand)]
+    pub action: SavedAction,
 }
 
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum SavedAction {
+    /// List saved searches
+    List,
+    /// Run a saved search; the name may be abbreviated
+    Run {
+        name: String,
+        /// Print the results as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Delete a saved search
+    Delete {
+        name: String,
+        /// Skip the confirmation
+        #[clap(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct SplitArgs {
+    /// Vault path of the note to split
+    pub path: String,
+    /// Print the proposed plan as JSON without changing anything
+    #[clap(long)]
+    pub json: bool,
+    /// Split at headings only, without comparing section embeddings
+    #[clap(long)]
+    pub headings_only: bool,
+    /// Apply the proposed plan without editing or confirming it
+    #[clap(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct IndexArgs {
+    /// Fetch and hash every note instead of trusting the size and modification time
+    /// recorded at the last run
+    #[clap(long)]
+    pub full: bool,
+    /// Report what would be re-embedded without calling the LLM
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Only re-embed notes whose embedding was made by another model than the configured one
+    #[clap(long)]
+    pub if_stale: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct ConflictsArgs {
+    #[clap(subcommand)]
+    pub action: ConflictsAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum ConflictsAction {
+    /// List conflict copies with their differences and resolve them one by one
+    Scan {
+        /// Print the pairs and their differences as JSON without resolving anything
+        #[clap(long)]
+        json: bool,
+        /// Resolve every conflict keeping this side: original or conflict
+        #[clap(long)]
+        keep: Option<String>,
+        /// Skip the confirmation for --keep
+        #[clap(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct TagsArgs {
+    #[clap(subcommand)]
+    pub action: TagsAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum TagsAction {
+    /// Every tag in the vault with the number of notes using it
+    List {
+        /// Only count notes under this folder
+        #[clap(long)]
+        folder: Option<String>,
+        /// Sort alphabetically instead of by usage
+        #[clap(long)]
+        by_name: bool,
+    },
+    /// Rename a tag, and the tags nested under it, in every note's frontmatter and text
+    Rename {
+        old: String,
+        new: String,
+        /// Show the changes to each note without writing them
+        #[clap(long)]
+        dry_run: bool,
+        /// Apply without asking for confirmation
+        #[clap(short, long)]
+        yes: bool,
+    },
+    /// Replace several synonymous tags with one, e.g. `tags merge todo to-do --into task`
+    Merge {
+        #[clap(required = true)]
+        tags: Vec<String>,
+        /// The tag to keep
+        #[clap(long)]
+        into: String,
+        /// Show the changes to each note without writing them
+        #[clap(long)]
+        dry_run: bool,
+        /// Apply without asking for confirmation
+        #[clap(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedArgs {
+    /// Note to find related notes for (e.g. Notes/rust.md)
+    pub path: String,
+    /// Number of notes to list (default from [related] limit)
+    #[clap(short = 'k', long)]
+    pub limit: Option<usize>,
+    /// Minimum similarity, 0-1 (default from [related] min_similarity)
+    #[clap(long)]
+    pub min_similarity: Option<f32>,
+    /// Write the list into the note as a "Related Notes" section, replacing the previous one
+    #[clap(long)]
+    pub write: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct BacklinksArgs {
+    #[clap(subcommand)]
+    pub action: BacklinksAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum BacklinksAction {
+    /// Rewrite the section on notes whose inbound links or their sentences changed
+    Update {
+        /// Notes to update, e.g. Notes/roadmap.md
+        paths: Vec<String>,
+        /// Update every note with this tag instead
+        #[clap(long)]
+        tag: Option<String>,
+        /// List the notes that would change without writing them
+        #[clap(long)]
+        dry_run: bool,
+        /// Rewrite sections even when the inbound links are unchanged
+        #[clap(long)]
+        force: bool,
+        /// Label relations with the LLM instead of keywords
+        #[clap(long)]
+        llm_assist: bool,
+        /// Skip the confirmation when updating several notes
+        #[clap(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct LinksArgs {
+    #[clap(subcommand)]
+    pub action: LinksAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum LinksAction {
+    /// List the indexed notes linking to a note, and those it links to
+    Backlinks {
+        /// Note to look up, e.g. Notes/roadmap.md
+        path: String,
+    },
+    /// List indexed notes with no links to or from other notes
+    Orphans,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct SectionsArgs {
+    #[clap(subcommand)]
+    pub action: SectionsAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum SectionsAction {
+    /// Show a note's managed sections, their owners and any problems with their markers
+    List {
+        /// Note to inspect, e.g. Notes/roadmap.md
+        path: String,
+    },
+    /// Check notes for damaged markers and hand-edited sections
+    Lint {
+        /// Folders to check (default: the [index] folders)
+        folders: Vec<String>,
+    },
+    /// Close unclosed sections and drop stray end markers and repeated sections
+    Repair {
+        /// Note to repair
+        path: String,
+        /// Show the fixes without writing them
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct InvitesArgs {
+    #[clap(subcommand)]
+    pub action: InvitesAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum InvitesAction {
+    /// Check the calendar for new invitations and queue them with a recommendation
+    Poll {
+        /// Don't show desktop notifications for new invitations
+        #[clap(long)]
+        no_notify: bool,
+    },
+    /// Go through queued invitations and accept, decline or propose another time
+    Review,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct MeetingArgs {
+    #[clap(subcommand)]
+    pub action: MeetingAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum MeetingAction {
+    /// Write an agenda with timed items, drawing on related notes
+    Agenda {
+        /// Meeting title
+        title: String,
+        /// Length in minutes
+        #[clap(short, long, default_value_t = 30)]
+        duration: u32,
+        /// Required attendee (repeatable)
+        #[clap(long = "attendee", value_parser)]
+        attendees: Vec<String>,
+        /// Optional attendee (repeatable)
+        #[clap(long = "optional", value_parser)]
+        optional: Vec<String>,
+        #[clap(long)]
+        description: Option<String>,
+        /// Related notes found by semantic search to include; 0 skips the search
+        #[clap(long, default_value_t = 3)]
+        notes: usize,
+        /// Also send the invitation with the agenda at this time (e.g. "2024-06-05 14:00",
+        /// or a day for its next free slot)
+        #[clap(long)]
+        at: Option<String>,
+        /// Print the agenda as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Create the note for a calendar event, or update it if there is one, and link the two
+    Note {
+        /// Id of the event (as in its .ics file name)
+        #[clap(required_unless_present = "today")]
+        event_id: Option<String>,
+        /// Pick one of today's events instead
+        #[clap(long, conflicts_with = "event_id")]
+        today: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct PlanArgs {
+    #[clap(subcommand)]
+    pub action: PlanAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum PlanAction {
+    /// Lay out this week's free working time: deadline work first, then todos with --tasks
+    Week {
+        /// Also pack open todos (by estimate) into the time left after deadlines
+        #[clap(long)]
+        tasks: bool,
+        /// Plan todos only, ignoring deadlines
+        #[clap(long, conflicts_with = "tasks")]
+        only_tasks: bool,
+        /// Plan next week instead of the rest of this one
+        #[clap(long)]
+        next: bool,
+        /// Print the plan as JSON
+        #[clap(long)]
+        json: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateArgs {
+    #[clap(subcommand)]
+    pub action: TemplateAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum TemplateAction {
+    /// Find recurring kinds of notes by clustering their embeddings, for template suggestions
+    Discover {
+        /// Only look at notes under this folder (default: the whole vault)
+        #[clap(long)]
+        folder: Option<String>,
+        /// Leave out clusters with fewer notes than this
+        #[clap(long, default_value = "3")]
+        min_cluster_size: usize,
+    },
+    /// Write all templates, with their usage statistics, to a .json or .yaml file
+    Export {
+        path: String,
+    },
+    /// Merge the templates of an exported .json or .yaml file into the database
+    Import {
+        path: String,
+        /// What to do with a template whose id or name already exists
+        #[clap(long, value_enum, default_value_t)]
+        on_conflict: TemplateMergeStrategy,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct DeadlineArgs {
+    #[clap(subcommand)]
+    pub action: DeadlineAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum DeadlineAction {
+    /// Add a deadline and block out working time for it on the calendar
+    Add {
+        title: String,
+        /// Due date or time: YYYY-MM-DD, YYYY-MM-DD HH:MM, friday, friday 5pm, end of month, ...
+        #[clap(long)]
+        due: String,
+        /// Work left, e.g. 6h or 1h30m
+        #[clap(short, long)]
+        estimate: Option<String>,
+        /// low, medium, high or critical
+        #[clap(short, long)]
+        priority: Option<String>,
+        #[clap(long, default_value = "general")]
+        category: String,
+        #[clap(short, long, value_parser)]
+        tags: Vec<String>,
+        #[clap(long)]
+        description: Option<String>,
+        /// Save the deadline without scheduling time blocks
+        #[clap(long)]
+        no_blocks: bool,
+        /// Remind this long before it is due, e.g. 2d or 3h (repeatable); urgent for high and critical deadlines
+        #[clap(long)]
+        remind: Vec<String>,
+    },
+    /// List active deadlines, soonest first
+    List {
+        /// Only deadlines with this status (not-started, in-progress, on-hold, completed, overdue, cancelled)
+        #[clap(short, long)]
+        status: Option<String>,
+        /// Include completed and cancelled deadlines
+        #[clap(long)]
+        all: bool,
+        /// Print the deadlines as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Record progress or change a deadline
+    Update {
+        /// Deadline id or a unique prefix of it
+        id: String,
+        /// Hours done so far, e.g. 3h
+        #[clap(long)]
+        done: Option<String>,
+        #[clap(long)]
+        title: Option<String>,
+        #[clap(long)]
+        due: Option<String>,
+        #[clap(short, long)]
+        estimate: Option<String>,
+        #[clap(short, long)]
+        priority: Option<String>,
+        #[clap(short, long)]
+        status: Option<String>,
+    },
+    /// Mark a deadline as completed and cancel its remaining time blocks
+    Complete {
+        /// Deadline id or a unique prefix of it
+        id: String,
+    },
+    /// Pick up time blocks moved or deleted in a calendar app
+    Sync {
+        /// Deadline id or a unique prefix of it; every active deadline when omitted
+        id: Option<String>,
+        /// Schedule new blocks for any hours no longer covered without asking
+        #[clap(long)]
+        regenerate: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct FocusArgs {
+    #[clap(subcommand)]
+    pub action: FocusAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum FocusAction {
+    /// Start a timer on the deadline's current time block; p logs an interruption, q stops
+    Start {
+        /// Deadline id or a unique prefix of it
+        deadline_id: String,
+        /// Length of the block created when none is scheduled now
+        #[clap(long, default_value_t = 50)]
+        minutes: i64,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct SyncArgs {
+    #[clap(subcommand)]
+    pub action: SyncAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum SyncAction {
+    /// Put open todos with due dates on the tasks calendar, remove finished ones, and copy
+    /// dates moved in the calendar back to the todos
+    TodosToCalendar {
+        /// Show what would change on each side without changing anything
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleArgs {
+    #[clap(subcommand)]
+    pub action: ScheduleAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum ScheduleAction {
+    /// Print each factor's contribution to a slot's confidence, e.g. "friday 14:00"
+    Explain {
+        /// Start of the slot (e.g., "tomorrow 2pm", "2024-06-07 14:00")
+        slot: String,
+        /// Length in minutes; defaults to preferred_meeting_duration
+        #[clap(long)]
+        duration: Option<u32>,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct DailyArgs {
+    /// Day of the note: YYYY-MM-DD, today, yesterday, a weekday or a phrase such as june 7
+    #[clap(long)]
+    pub date: Option<String>,
+    /// Add an AI-written focus summary for the day
+    #[clap(long)]
+    pub focus: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct WatchArgs {
+    /// Log the notes that would be processed without calling the LLM or writing anything
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+#[clap(args_conflicts_with_subcommands = true)]
+pub struct RemindArgs {
+    #[clap(subcommand)]
+    pub action: Option<RemindAction>,
+    /// Keep running, checking for due reminders every interval
+    #[clap(long)]
+    pub daemon: bool,
+    /// Minutes between checks in --daemon mode (default from [reminders] interval_minutes)
+    #[clap(long)]
+    pub interval: Option<u64>,
+    /// List the reminders that are due without sending or recording them
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum RemindAction {
+    /// Acknowledge a sent reminder so it isn't escalated
+    Ack {
+        /// Reminder id, as printed when it was sent (e.g. rem-1a2b3c4d)
+        id: Option<String>,
+        /// Acknowledge every sent reminder
+        #[clap(long)]
+        all: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct UndoArgs {
+    /// Version id, as listed by `arrowhead history <path>`
+    #[clap(conflicts_with = "last")]
+    pub id: Option<String>,
+    /// Undo the most recent AI change; repeat to step further back
+    #[clap(long)]
+    pub last: bool,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryArgs {
+    /// Vault path of the note
+    pub path: String,
+    /// Restore the version with this id
+    #[clap(long)]
+    pub restore: Option<String>,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct ChangelogArgs {
+    #[clap(subcommand)]
+    pub action: ChangelogAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum ChangelogAction {
+    /// Compare living documents with the last scan and record substantive edits
+    Scan {
+        /// Folder to scan
+        #[clap(long, default_value = "Notes")]
+        folder: String,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+#[clap(args_conflicts_with_subcommands = true)]
+pub struct FileArgs {
+    #[clap(subcommand)]
+    pub command: Option<FileCommand>,
+    /// Notes to file
+    pub paths: Vec<String>,
+    /// File every note in the configured filing folders
+    #[clap(long)]
+    pub all: bool,
+    /// Show where notes would go without moving them
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Show which rules matched a note and why the winner won
+    #[clap(long, value_name = "PATH")]
+    pub explain: Option<String>,
+    /// Skip the confirmation prompt (if `move` allows --yes)
+    #[clap(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum FileCommand {
+    /// Inspect the filing rules
+    Rules {
+        #[clap(subcommand)]
+        action: FilingRulesAction,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum FilingRulesAction {
+    /// Dry-run every rule over the filing folders: matches, wins, overlaps and ties
+    Stats,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct RunArgs {
+    /// Path to a .toml, .yaml or .yml script
+    pub script: String,
+    /// Print the steps without running them
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Print the run report as JSON
+    #[clap(long)]
+    pub json: bool,
+    /// Also write the JSON run report to this file
+    #[clap(long, value_name = "PATH")]
+    pub report: Option<String>,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryArgs {
+    #[clap(subcommand)]
+    pub action: MemoryAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum MemoryAction {
+    /// List remembered facts
+    List,
+    /// Remember a fact
+    Add {
+        fact: String,
+    },
+    /// Reword a remembered fact
+    Edit {
+        /// Memory id shown by `memory list`
+        id: String,
+        fact: String,
+    },
+    /// Forget a fact
+    Delete {
+        /// Memory id shown by `memory list`
+        id: String,
+        /// Skip the confirmation prompt (if `delete` allows --yes)
+        #[clap(short, long)]
+        yes: bool,
+    },
+    /// Write all memories to a JSON file
+    Export {
+        file: String,
+    },
+    /// Merge memories from a JSON file written by `memory export`
+    Import {
+        file: String,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct SessionsArgs {
+    #[clap(subcommand)]
+    pub action: SessionsAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum SessionsAction {
+    /// List saved conversations, most recent first
+    List {
+        /// How many to show
+        #[clap(long, default_value = "20")]
+        limit: usize,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct CacheArgs {
+    #[clap(subcommand)]
+    pub action: CacheAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum CacheAction {
+    /// Show this vault's cache folder, its files and their sizes
+    Info,
+    /// Delete this vault's cache files; embeddings are rebuilt by `arrowhead index`
+    Clear {
+        /// Delete the caches of every vault, not just this one
+        #[clap(long)]
+        all: bool,
+        /// Skip the confirmation prompt (if `delete` allows --yes)
+        #[clap(short, long)]
+        yes: bool,
+    },
+    /// Remove stored embeddings and analysis of notes that `[opt_outs]` or `ai: false` now exclude
+    PruneExcluded {
+        /// List what would be removed without removing it
+        #[clap(long)]
+        dry_run: bool,
+        /// Skip the confirmation prompt (if `delete` allows --yes)
+        #[clap(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct VaultArgs {
+    #[clap(subcommand)]
+    pub action: VaultAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum VaultAction {
+    /// List the vaults in `[[vaults]]`, marking the one in use
+    List,
+    /// Use this vault when `--vault` isn't given
+    Use {
+        name: String,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct PromptsArgs {
+    #[clap(subcommand)]
+    pub action: PromptsAction,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum PromptsAction {
+    /// List the prompts, marking those overridden in prompts.toml
+    List,
+    /// Print a prompt as it is used, with the placeholders it can use
+    Show {
+        key: String,
+        /// Print the built-in prompt even if it is overridden
+        #[clap(long)]
+        builtin: bool,
+    },
+    /// Edit a prompt in $EDITOR and save it as an override
+    Edit {
+        key: String,
+        /// Remove the override and go back to the built-in prompt
+        #[clap(long)]
+        reset: bool,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionsArgs {
+    #[clap(value_enum)]
+    pub shell: CompletionShell,
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct ManpagesArgs {
+    /// Folder to write arrowhead.1 and one page per subcommand into; created if missing
+    #[clap(long)]
+    pub out: String,
+}
 
 #[derive(Args, Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigArgs {
+    #[clap(subcommand)]
+    pub action: Option<ConfigAction>,
     /// Create sample configuration file
     #[clap(long)]
     pub init: bool,
@@ -147,6 +1337,22 @@ pub struct ConfigArgs {
     pub value: Option<String>,
 }
 
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum ConfigAction {
+    /// Set one key in the config file, e.g. `config set llm.temperature 0.4`
+    Set {
+        /// Dotted key such as llm.provider, llm.model, llm.temperature or obsidian.base_url
+        key: String,
+        value: String,
+    },
+    /// Print the value of one key, after environment overrides
+    Get {
+        key: String,
+    },
+    /// List the `[profiles]` and the settings each one ends up with
+    Profiles,
+}
+
 // Example usage (will be in main.rs)
 // fn main() {
 //     let cli = Cli::parse();
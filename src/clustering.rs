@@ -0,0 +1,116 @@
+use nalgebra::DMatrix;
+
+/// A group of similar vectors found by `cluster`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    /// Indexes into the clustered vectors, most central first; the first is the medoid
+    pub members: Vec<usize>,
+    /// Mean cosine similarity between the members, 1.0 for a single member
+    pub cohesion: f32,
+}
+
+/// Cosine similarity of every pair of `vectors`, which must share a length
+fn similarity_matrix(vectors: &[Vec<f32>]) -> DMatrix<f32> {
+    let dimension = vectors.first().map_or(0, Vec::len);
+    let mut rows = DMatrix::from_fn(vectors.len(), dimension, |i, j| vectors[i][j]);
+    for mut row in rows.row_iter_mut() {
+        let norm = row.norm();
+        if norm > 0.0 {
+            row /= norm;
+        }
+    }
+    &rows * rows.transpose()
+}
+
+/// Average-linkage agglomerative clustering: start with every vector on its own and keep
+/// merging the two clusters with the highest mean cosine similarity between their members
+/// while it is at least `threshold`. Largest clusters first.
+pub fn cluster(vectors: &[Vec<f32>], threshold: f32) -> Vec<Cluster> {
+    let similarity = similarity_matrix(vectors);
+    let mut groups: Vec<Option<Vec<usize>>> = (0..vectors.len()).map(|i| Some(vec![i])).collect();
+    // Mean similarity between groups, kept current with the Lance-Williams update
+    let mut linkage = similarity.clone();
+
+    loop {
+        let mut best: Option<(usize, usize, f32)> = None;
+        for a in 0..groups.len() {
+            if groups[a].is_none() {
+                continue;
+            }
+            for b in (a + 1)..groups.len() {
+                if groups[b].is_some() && best.is_none_or(|(_, _, score)| linkage[(a, b)] > score) {
+                    best = Some((a, b, linkage[(a, b)]));
+                }
+            }
+        }
+        let Some((a, b, score)) = best.filter(|(_, _, score)| *score >= threshold) else { break };
+        log::debug!("merging clusters {} and {} at {:.3}", a, b, score);
+
+        let merged = groups[b].take().unwrap_or_default();
+        let (size_a, size_b) = (groups[a].as_ref().map_or(0, Vec::len) as f32, merged.len() as f32);
+        for c in (0..groups.len()).filter(|&c| c != a && groups[c].is_some()) {
+            let mean = (size_a * linkage[(a, c)] + size_b * linkage[(b, c)]) / (size_a + size_b);
+            linkage[(a, c)] = mean;
+            linkage[(c, a)] = mean;
+        }
+        if let Some(group) = groups[a].as_mut() {
+            group.extend(merged);
+        }
+    }
+
+    let mut clusters: Vec<Cluster> = groups.into_iter().flatten().map(|members| describe(members, &similarity)).collect();
+    clusters.sort_by(|a, b| b.members.len().cmp(&a.members.len()).then(a.members[0].cmp(&b.members[0])));
+    clusters
+}
+
+/// Order `members` by their summed similarity to the rest and measure how tight they are
+fn describe(members: Vec<usize>, similarity: &DMatrix<f32>) -> Cluster {
+    let centrality = |i: usize| members.iter().filter(|&&j| j != i).map(|&j| similarity[(i, j)]).sum::<f32>();
+    let mut ranked: Vec<(usize, f32)> = members.iter().map(|&i| (i, centrality(i))).collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let pairs = members.len() * members.len().saturating_sub(1);
+    let cohesion = if pairs == 0 { 1.0 } else { ranked.iter().map(|(_, total)| total).sum::<f32>() / pairs as f32 };
+    Cluster { members: ranked.into_iter().map(|(i, _)| i).collect(), cohesion }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clusters_group_similar_vectors() {
+        let vectors = vec![
+            vec![1.0, 0.1, 0.0],
+            vec![0.0, 1.0, 0.1],
+            vec![0.9, 0.0, 0.1],
+            vec![0.1, 0.9, 0.0],
+            vec![1.0, 0.0, 0.0],
+            vec![0.0, 0.0, 1.0],
+        ];
+        let clusters = cluster(&vectors, 0.8);
+        let mut sets: Vec<Vec<usize>> = clusters.iter().map(|c| {
+            let mut members = c.members.clone();
+            members.sort();
+            members
+        }).collect();
+        sets.sort();
+        assert_eq!(sets, vec![vec![0, 2, 4], vec![1, 3], vec![5]]);
+        assert_eq!(clusters[0].members.len(), 3);
+        assert!(clusters[0].cohesion > 0.9 && clusters[0].cohesion <= 1.0);
+        assert_eq!(clusters[2].cohesion, 1.0);
+
+        // Nothing is similar enough to merge at a threshold above 1
+        assert_eq!(cluster(&vectors, 1.1).len(), vectors.len());
+        assert!(cluster(&[], 0.8).is_empty());
+    }
+
+    #[test]
+    fn test_medoid_is_the_most_central_member() {
+        // The middle vector sits between the two outer ones
+        let vectors = vec![vec![1.0, 0.0], vec![0.7, 0.7], vec![0.0, 1.0]];
+        let clusters = cluster(&vectors, 0.3);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].members[0], 1);
+        assert!((clusters[0].cohesion - 2.0 * std::f32::consts::FRAC_1_SQRT_2 / 3.0).abs() < 0.01);
+    }
+}
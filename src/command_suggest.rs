@@ -0,0 +1,286 @@
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::BTreeSet;
+use std::env;
+use std::io::Write;
+use std::path::Path;
+
+use crate::cli::build_cli;
+use crate::confirm::PromptReader;
+use crate::intent_classifier::{describe_command, is_request_word, IntentClassifier, RouteDecision};
+
+/// Executables named `arrowhead-<name>` on PATH are offered as `<name>`
+pub const PLUGIN_PREFIX: &str = "arrowhead-";
+
+/// Edit distance counting an adjacent transposition ("serach") as one edit
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1).min(row[j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Most edits a word of this length may be from a candidate to count as a typo
+fn max_distance(word: &str) -> usize {
+    (word.chars().count() / 3).clamp(1, 3)
+}
+
+/// The candidates closest to `word` within typo distance; several means a tie
+pub fn closest<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let word = word.to_lowercase();
+    let limit = max_distance(&word);
+    let mut best = Vec::new();
+    let mut best_distance = usize::MAX;
+    for candidate in candidates {
+        let distance = edit_distance(&word, &candidate.to_lowercase());
+        if distance > limit || distance > best_distance {
+            continue;
+        }
+        if distance < best_distance {
+            best.clear();
+            best_distance = distance;
+        }
+        if !best.contains(&candidate) {
+            best.push(candidate);
+        }
+    }
+    best
+}
+
+/// `arrowhead-*` executables found on PATH, without the prefix
+pub fn plugin_commands() -> Vec<String> {
+    let Some(path) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+    let names: BTreeSet<String> = env::split_paths(&path)
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .filter(|entry| is_executable(&entry.path()))
+        .filter_map(|entry| entry.file_name().to_str()?.strip_prefix(PLUGIN_PREFIX).map(str::to_string))
+        .filter(|name| !name.is_empty())
+        .collect();
+    names.into_iter().collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Where the command line stops matching the CLI
+#[derive(Debug, PartialEq)]
+pub struct UnknownCommand {
+    /// Index into the arguments of the unrecognized word
+    pub index: usize,
+    /// Names (and aliases) valid in that position
+    pub expected: Vec<String>,
+}
+
+/// Walk `args` (without the program name) down the subcommand tree to the first word that
+/// is not a subcommand where one is expected
+pub fn find_unknown_command(args: &[String]) -> Option<UnknownCommand> {
+    let mut command = build_cli();
+    for (index, arg) in args.iter().enumerate() {
+        if arg.starts_with('-') || !command.has_subcommands() {
+            return None;
+        }
+        let next = command.get_subcommands()
+            .find(|sub| sub.get_name() == arg || sub.get_all_aliases().any(|alias| alias == arg))
+            .cloned();
+        match next {
+            Some(sub) => command = sub,
+            None if arg == "help" => return None,
+            None => {
+                let expected = command.get_subcommands()
+                    .flat_map(|sub| std::iter::once(sub.get_name().to_string()).chain(sub.get_all_aliases().map(str::to_string)))
+                    .chain(std::iter::once("help".to_string()))
+                    .collect();
+                return Some(UnknownCommand { index, expected });
+            }
+        }
+    }
+    None
+}
+
+/// What to run instead of an unknown command, once the user accepted the suggestion
+#[derive(Debug, PartialEq)]
+pub enum Recovery {
+    /// Corrected arguments for the built-in CLI
+    Command(Vec<String>),
+    /// An `arrowhead-<name>` plugin and its arguments
+    Plugin(String, Vec<String>),
+}
+
+fn shell_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Explain an unknown command: suggest the closest commands (built-in and plugins), or the
+/// chat mode when the words read like a request. On a terminal a single suggestion is
+/// offered to run; otherwise nothing is asked.
+pub fn recover_unknown_command(
+    args: &[String],
+    unknown: &UnknownCommand,
+    plugins: &[String],
+    reader: &mut dyn PromptReader,
+    out: &mut dyn Write,
+) -> Result<Option<Recovery>> {
+    let word = &args[unknown.index];
+    let prefix = args[..unknown.index].join(" ");
+    let at_root = unknown.index == 0;
+    writeln!(out, "error: unrecognized {}command '{}'", if at_root { "" } else { "sub" }, word)?;
+
+    let mut candidates: Vec<&str> = unknown.expected.iter().map(String::as_str).collect();
+    if at_root {
+        candidates.extend(plugins.iter().map(String::as_str));
+    }
+    let matches = closest(word, candidates);
+    let looks_like_request = at_root && args.len() > 1 && (matches.is_empty() || is_request_word(word));
+
+    if looks_like_request {
+        let request = args.join(" ");
+        writeln!(out, "\nThis looks like a request. Ask the assistant in chat mode:")?;
+        writeln!(out, "    echo {} | arrowhead", shell_quote(&request))?;
+        if let RouteDecision::Direct(route) = IntentClassifier::new().classify(&request, Utc::now().date_naive()) {
+            writeln!(out, "or run the matching command directly:\n    arrowhead {}", describe_command(&route.command))?;
+        }
+        return Ok(None);
+    }
+
+    let rest = &args[unknown.index + 1..];
+    let render = |name: &str| {
+        let plugin = at_root && plugins.iter().any(|p| p == name) && !unknown.expected.iter().any(|e| e == name);
+        let words: Vec<&str> = [prefix.as_str(), name].into_iter().chain(rest.iter().map(String::as_str)).filter(|w| !w.is_empty()).collect();
+        if plugin { format!("{}{}", PLUGIN_PREFIX, words.join(" ")) } else { format!("arrowhead {}", words.join(" ")) }
+    };
+    match matches.as_slice() {
+        [] => {
+            let level = if at_root { "arrowhead".to_string() } else { format!("arrowhead {}", prefix) };
+            writeln!(out, "\nRun `{} --help` to see the available commands.", level)?;
+            Ok(None)
+        }
+        [only] => {
+            let suggestion = render(only);
+            writeln!(out, "\ndid you mean `{}`?", suggestion)?;
+            if !reader.is_interactive() {
+                return Ok(None);
+            }
+            let answer = reader.read_answer("Run it now? [y/N]")?;
+            if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                return Ok(None);
+            }
+            let is_plugin = suggestion.starts_with(PLUGIN_PREFIX);
+            Ok(Some(if is_plugin {
+                Recovery::Plugin(only.to_string(), rest.to_vec())
+            } else {
+                let mut fixed = args.to_vec();
+                fixed[unknown.index] = only.to_string();
+                Recovery::Command(fixed)
+            }))
+        }
+        several => {
+            writeln!(out, "\ndid you mean one of these?")?;
+            for name in several {
+                writeln!(out, "    {}", render(name))?;
+            }
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ScriptedPrompt;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn recover(line: &str, plugins: &[&str], prompt: &mut ScriptedPrompt) -> (Option<Recovery>, String) {
+        let args = args(line);
+        let unknown = find_unknown_command(&args).expect("an unknown command");
+        let plugins: Vec<String> = plugins.iter().map(|p| p.to_string()).collect();
+        let mut out = Vec::new();
+        let recovery = recover_unknown_command(&args, &unknown, &plugins, prompt, &mut out).unwrap();
+        (recovery, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_typo_distances() {
+        assert_eq!(edit_distance("serach", "search"), 1);
+        assert_eq!(edit_distance("lst", "list"), 1);
+        assert_eq!(edit_distance("gaol", "goal"), 1);
+        assert_eq!(edit_distance("risks", "risks"), 0);
+        assert_eq!(closest("lst", ["list", "add", "done"]), vec!["list"]);
+        // Short words only tolerate one edit; longer ones more
+        assert!(closest("ad", ["plan"]).is_empty());
+        assert_eq!(closest("chnagelgo", ["changelog"]), vec!["changelog"]);
+
+        assert_eq!(find_unknown_command(&args("todo lst")), Some(UnknownCommand { index: 1, expected: find_unknown_command(&args("todo x")).unwrap().expected }));
+        assert!(find_unknown_command(&args("todo list --status open")).is_none());
+        assert!(find_unknown_command(&args("calendar quick one_on_one")).is_none());
+    }
+
+    #[test]
+    fn test_single_suggestion_is_offered_on_a_terminal() {
+        let mut prompt = ScriptedPrompt::tty(&["y"]);
+        let (recovery, out) = recover("todo lst --status open", &[], &mut prompt);
+        assert!(out.contains("did you mean `arrowhead todo list --status open`?"));
+        assert_eq!(recovery, Some(Recovery::Command(args("todo list --status open"))));
+        assert_eq!(prompt.prompts.len(), 1);
+
+        let mut prompt = ScriptedPrompt::tty(&["y"]);
+        let (recovery, out) = recover("jria sync", &["jira"], &mut prompt);
+        assert!(out.contains("did you mean `arrowhead-jira sync`?"));
+        assert_eq!(recovery, Some(Recovery::Plugin("jira".to_string(), args("sync"))));
+    }
+
+    #[test]
+    fn test_ties_list_every_candidate_without_prompting() {
+        let mut prompt = ScriptedPrompt::tty(&["y"]);
+        // "mote" is one edit from both `mute` and `note`
+        let (recovery, out) = recover("mote", &[], &mut prompt);
+        assert!(out.contains("did you mean one of these?\n    arrowhead note\n    arrowhead mute\n"));
+        assert_eq!(recovery, None);
+        assert_eq!(prompt.prompts.len(), 0);
+    }
+
+    #[test]
+    fn test_non_tty_suggests_but_never_prompts() {
+        let mut prompt = ScriptedPrompt::piped();
+        let (recovery, out) = recover("helth", &[], &mut prompt);
+        assert!(out.contains("did you mean `arrowhead health`?"));
+        assert_eq!(recovery, None);
+
+        let (recovery, out) = recover("show my open todos", &[], &mut prompt);
+        assert!(out.contains("echo \"show my open todos\" | arrowhead"));
+        assert!(out.contains("arrowhead todo list --status open"));
+        assert_eq!(recovery, None);
+
+        let (_, out) = recover("summarize meeting notes", &[], &mut prompt);
+        assert!(out.contains("echo \"summarize meeting notes\" | arrowhead"));
+        // A typo of a command still gets the command, even followed by more words
+        let (_, out) = recover("serach meeting notes", &[], &mut prompt);
+        assert!(out.contains("did you mean `arrowhead search meeting notes`?"));
+        assert_eq!(prompt.prompts.len(), 0);
+    }
+}
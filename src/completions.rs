@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::{Arg, Command};
+use clap_complete::Shell;
+use std::fs;
+use std::path::Path;
+
+use crate::cli::{build_cli, CompletionShell, CompletionsArgs, ManpagesArgs};
+use crate::config::Config;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::prompts::BUILTIN_PROMPTS;
+
+const BIN_NAME: &str = "arrowhead";
+
+impl From<CompletionShell> for Shell {
+    fn from(shell: CompletionShell) -> Self {
+        match shell {
+            CompletionShell::Bash => Shell::Bash,
+            CompletionShell::Zsh => Shell::Zsh,
+            CompletionShell::Fish => Shell::Fish,
+            CompletionShell::Powershell => Shell::PowerShell,
+        }
+    }
+}
+
+/// Values the shell offers for an argument. Only set on the tree completions are generated
+/// from, so parsing still accepts anything.
+fn hint(arg: Arg, values: &[String]) -> Arg {
+    if values.is_empty() {
+        return arg;
+    }
+    arg.value_parser(PossibleValuesParser::new(values.iter().map(|value| PossibleValue::new(value.clone()))))
+}
+
+/// Names of the saved note templates, or none when the template database can't be read
+fn note_template_names(config: &Config) -> Vec<String> {
+    let mut adapter = ObsidianAdapter::new(Some(config.obsidian.base_url.clone()), config.obsidian.api_key.clone());
+    adapter.set_cache_settings(&config.cache);
+    if adapter.load_template_database().is_err() {
+        return Vec::new();
+    }
+    let mut names: Vec<String> = adapter.get_all_templates().into_iter().map(|t| t.name.clone()).collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// `build_cli` with the values that are cheap to look up as completion candidates: vault
+/// names, calendar event templates, note templates and prompt keys. They are read when the
+/// script is generated, so regenerate it after adding a vault or template.
+pub fn completion_command(config: &Config) -> Command {
+    let vaults: Vec<String> = config.vaults.iter().map(|vault| vault.name.clone()).collect();
+    let mut event_templates: Vec<String> = config.calendar.templates.keys().cloned().collect();
+    event_templates.sort();
+    let note_templates = note_template_names(config);
+    let prompts: Vec<String> = BUILTIN_PROMPTS.iter().map(|prompt| prompt.key.to_string()).collect();
+
+    build_cli()
+        .mut_arg("vault", |arg| hint(arg, &vaults))
+        .mut_subcommand("vault", |cmd| cmd.mut_subcommand("use", |cmd| cmd.mut_arg("name", |arg| hint(arg, &vaults))))
+        .mut_subcommand("calendar", |cmd| cmd.mut_subcommand("quick", |cmd| cmd.mut_arg("template", |arg| hint(arg, &event_templates))))
+        .mut_subcommand("note", |cmd| cmd.mut_subcommand("new", |cmd| cmd.mut_arg("template", |arg| hint(arg, &note_templates))))
+        .mut_subcommand("prompts", |cmd| {
+            cmd.mut_subcommand("show", |cmd| cmd.mut_arg("key", |arg| hint(arg, &prompts)))
+                .mut_subcommand("edit", |cmd| cmd.mut_arg("key", |arg| hint(arg, &prompts)))
+        })
+}
+
+/// The completion script for `shell`
+pub fn completion_script(shell: CompletionShell, config: &Config) -> Vec<u8> {
+    let mut script = Vec::new();
+    clap_complete::generate(Shell::from(shell), &mut completion_command(config), BIN_NAME, &mut script);
+    script
+}
+
+/// Write `arrowhead.1` and a page per subcommand, such as `arrowhead-todo-add.1`, into
+/// `out`, returning how many were written
+pub fn write_manpages(out: &Path) -> Result<usize> {
+    fs::create_dir_all(out).with_context(|| format!("Failed to create {}", out.display()))?;
+    clap_mangen::generate_to(build_cli(), out).with_context(|| format!("Failed to write man pages to {}", out.display()))?;
+    let pages = fs::read_dir(out)?.flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".1"))
+        .count();
+    Ok(pages)
+}
+
+pub fn handle_completions_command(args: CompletionsArgs) -> Result<()> {
+    // Completions work before arrowhead is configured; there are just no dynamic values then
+    let config = Config::load().unwrap_or_default();
+    let script = completion_script(args.shell, &config);
+    std::io::Write::write_all(&mut std::io::stdout(), &script)?;
+    Ok(())
+}
+
+pub fn handle_manpages_command(args: ManpagesArgs) -> Result<()> {
+    let out = Path::new(&args.out);
+    let pages = write_manpages(out)?;
+    println!("✅ Wrote {} man page(s) to {}", pages, out.display());
+    crate::script::publish_output(serde_json::json!({ "dir": out, "pages": pages }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VaultConfig;
+
+    #[test]
+    fn test_zsh_script_completes_todo_subcommands_and_flags() {
+        let script = String::from_utf8(completion_script(CompletionShell::Zsh, &Config::default())).unwrap();
+        assert!(script.starts_with("#compdef arrowhead"));
+        let todo = &script[script.find("(todo)").expect("todo is completed")..];
+        for word in ["'add:", "'list:", "'done:", "--due"] {
+            assert!(todo.contains(word), "missing {} under todo", word);
+        }
+    }
+
+    #[test]
+    fn test_completion_hints_do_not_restrict_parsing() {
+        let mut config = Config::default();
+        config.vaults.push(VaultConfig { name: "work".to_string(), base_url: "https://127.0.0.1:27124".to_string(), api_key: None, cache_dir: None });
+        let script = String::from_utf8(completion_script(CompletionShell::Bash, &config)).unwrap();
+        assert!(script.contains("work"));
+        assert!(script.contains("suggest_headings"));
+
+        assert!(build_cli().try_get_matches_from([BIN_NAME, "--vault", "elsewhere", "vault", "use", "elsewhere"]).is_ok());
+        assert!(completion_command(&config).try_get_matches_from([BIN_NAME, "vault", "use", "elsewhere"]).is_err());
+    }
+
+    #[test]
+    fn test_manpages_cover_subcommands() {
+        let out = std::env::temp_dir().join(format!("arrowhead-man-{}", uuid::Uuid::new_v4()));
+        let pages = write_manpages(&out).unwrap();
+        assert!(out.join("arrowhead.1").exists() && out.join("arrowhead-todo-add.1").exists());
+        assert!(pages > 20);
+        fs::remove_dir_all(&out).ok();
+    }
+}
@@ -1,8 +1,21 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use crate::confirm::ConfirmationSettings;
+use crate::embedding_store::EmbeddingStorageConfig;
+use crate::filing::FilingRule;
+use crate::heuristic_analysis::AnalysisBackend;
+use crate::invites::InviteSettings;
+use crate::scheduling::SchedulingSettings;
+use crate::network::NetworkSettings;
+use crate::opt_out::OptOutPolicy;
+use crate::tool_results::ToolResultSettings;
 
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,6 +23,102 @@ pub struct Config {
     pub llm: LLMConfig,
     pub obsidian: ObsidianConfig,
     pub general: GeneralConfig,
+    #[serde(default)]
+    pub calendar: CalendarSettings,
+    #[serde(default)]
+    pub glossary: GlossarySettings,
+    #[serde(default)]
+    pub plans: PlanSettings,
+    #[serde(default)]
+    pub embeddings: EmbeddingStorageConfig,
+    #[serde(default)]
+    pub memory: MemorySettings,
+    /// Folder-level feature opt-outs, e.g. `[opt_outs.folders]` `Templates = ["all"]`, and
+    /// `exclude` globs of notes no feature reads
+    #[serde(default)]
+    pub opt_outs: OptOutPolicy,
+    #[serde(default)]
+    pub changelog: ChangelogSettings,
+    #[serde(default)]
+    pub plugin_api: PluginApiSettings,
+    #[serde(default)]
+    pub filing: FilingSettings,
+    #[serde(default)]
+    pub planner: PlannerSettings,
+    /// Per-operation confirmation policies, e.g. `delete = "typed-phrase"`
+    #[serde(default)]
+    pub confirmations: ConfirmationSettings,
+    #[serde(default)]
+    pub health: HealthSettings,
+    /// Per-destination proxies and CA bundles, e.g. `[network.llm] proxy = "..."`
+    #[serde(default)]
+    pub network: NetworkSettings,
+    #[serde(default)]
+    pub backlinks: BacklinksSettings,
+    #[serde(default)]
+    pub related: RelatedSettings,
+    /// Constraints for invitation recommendations, e.g. `max_meetings_per_day = 5`
+    #[serde(default)]
+    pub invites: InviteSettings,
+    /// Energy curve, meeting limits and focus blocks that suggested meeting slots are weighed against
+    #[serde(default)]
+    pub scheduling: SchedulingSettings,
+    /// Where `arrowhead meeting note` writes notes and which people pages attendees link to
+    #[serde(default)]
+    pub meetings: MeetingNoteSettings,
+    /// How large tool results are shortened before they enter the chat context
+    #[serde(default)]
+    pub tool_results: ToolResultSettings,
+    /// Filename patterns of sync-conflict copies and where resolved copies go
+    #[serde(default)]
+    pub conflicts: ConflictSettings,
+    #[serde(default)]
+    pub index: IndexSettings,
+    #[serde(default)]
+    pub search: SearchSettings,
+    #[serde(default)]
+    pub split: SplitSettings,
+    /// Where interactive conversations are saved for `--resume`
+    #[serde(default)]
+    pub sessions: SessionSettings,
+    /// Token budget of the conversation history and how it is trimmed
+    #[serde(default)]
+    pub context: ContextSettings,
+    /// Where `arrowhead daily` writes the daily note and whether it adds a focus summary
+    #[serde(default)]
+    pub daily: DailySettings,
+    /// Which notes `arrowhead insights` charts and where their dates come from
+    #[serde(default)]
+    pub insights: InsightsSettings,
+    /// How `arrowhead organize` and `watch` analyze the notes they suggest tags and folders for
+    #[serde(default)]
+    pub organize: OrganizeSettings,
+    /// What `arrowhead watch` watches and does with changed notes
+    #[serde(default)]
+    pub watch: WatchSettings,
+    /// Where LLM usage is counted and the limits it must stay under
+    #[serde(default)]
+    pub usage: UsageSettings,
+    /// How often `arrowhead remind` checks for due reminders and where it sends them
+    #[serde(default)]
+    pub reminders: RemindersSettings,
+    /// Backups of notes taken before AI features rewrite them, for `arrowhead undo`
+    #[serde(default)]
+    pub history: HistorySettings,
+    /// Where the embedding and template caches are kept and how large in-memory caches grow
+    #[serde(default)]
+    pub cache: CacheSettings,
+    /// Vaults to switch between with `--vault` or `arrowhead vault use`; `[obsidian]` is used
+    /// when none is chosen
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vaults: Vec<VaultConfig>,
+    /// Named sets of overrides for any of the keys above, e.g. `[profiles.work.llm]`
+    /// `provider = "openai"`, chosen with `--profile` or ARROWHEAD_PROFILE
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, toml::Table>,
+    /// Name of the profile merged into this config
+    #[serde(skip)]
+    pub profile: Option<String>,
 }
 
 /// LLM configuration
@@ -18,6 +127,38 @@ pub struct LLMConfig {
     pub provider: String,
     pub gemini: GeminiConfig,
     pub openai: OpenAIConfig,
+    #[serde(default)]
+    pub anthropic: AnthropicConfig,
+    #[serde(default)]
+    pub ollama: OllamaConfig,
+    /// Retries of overloaded or rate-limited requests, e.g. `[llm.retry] max_attempts = 5`
+    #[serde(default)]
+    pub retry: RetrySettings,
+    /// Providers to switch to, in order, when `provider` keeps failing, e.g. `["openai"]`
+    #[serde(default)]
+    pub fallback: Vec<String>,
+}
+
+/// Backoff for LLM requests answered with 429, 500, 502, 503 or 504
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrySettings {
+    /// Attempts per provider, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubles on each one, with jitter
+    pub initial_delay_ms: u64,
+    /// Cap on any single delay
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay_ms: 1000,
+            max_delay_ms: 30_000,
+        }
+    }
 }
 
 /// Gemini-specific configuration
@@ -38,6 +179,51 @@ pub struct OpenAIConfig {
     pub max_tokens: u32,
 }
 
+/// Anthropic-specific configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicConfig {
+    pub api_key: Option<String>,
+    pub model: String,
+    /// Anthropic accepts 0.0 to 1.0
+    pub temperature: f32,
+    /// Sent with every request, as the Messages API requires it
+    pub max_tokens: u32,
+}
+
+impl Default for AnthropicConfig {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            model: "claude-3-5-sonnet-latest".to_string(),
+            temperature: 0.7,
+            max_tokens: 4096,
+        }
+    }
+}
+
+/// Ollama configuration; a local server takes no API key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OllamaConfig {
+    pub base_url: String,
+    /// Any model pulled with `ollama pull`
+    pub model: String,
+    pub temperature: f32,
+    /// Sent as `num_predict`
+    pub max_tokens: u32,
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:11434".to_string(),
+            model: "llama3.1".to_string(),
+            temperature: 0.7,
+            max_tokens: 4096,
+        }
+    }
+}
+
 /// Obsidian configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObsidianConfig {
@@ -45,12 +231,767 @@ pub struct ObsidianConfig {
     pub base_url: String,
 }
 
+/// One vault's Local REST API, e.g. `[[vaults]] name = "work"`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultConfig {
+    pub name: String,
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Folder for this vault's caches; defaults to one named after `base_url` under `[cache] dir`
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+}
+
 /// General application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeneralConfig {
     pub verbose: bool,
     pub auto_save: bool,
     pub max_conversation_history: usize,
+    /// Disable every AI feature: chat, analysis, embeddings and suggestions
+    #[serde(default)]
+    pub offline: bool,
+    /// Name of the `[[vaults]]` entry used when `--vault` isn't given
+    #[serde(default)]
+    pub vault: Option<String>,
+}
+
+/// Environment variable that turns on offline mode, like `--offline`
+pub const OFFLINE_ENV: &str = "ARROWHEAD_OFFLINE";
+
+/// Set by `--offline` for the whole process
+static FORCE_OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Turn on offline mode for every config loaded from now on
+pub fn force_offline() {
+    FORCE_OFFLINE.store(true, Ordering::Relaxed);
+}
+
+/// Set by `--vault` for the whole process
+static FORCE_VAULT: Mutex<Option<String>> = Mutex::new(None);
+
+/// Use the `[[vaults]]` entry `name` in every config loaded from now on
+pub fn force_vault(name: &str) {
+    *FORCE_VAULT.lock().unwrap_or_else(PoisonError::into_inner) = Some(name.to_string());
+}
+
+/// Environment variable choosing a `[profiles.<name>]` section, like `--profile`
+pub const PROFILE_ENV: &str = "ARROWHEAD_PROFILE";
+
+/// Set by `--profile` for the whole process
+static FORCE_PROFILE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Merge the profile `name` into every config loaded from now on
+pub fn force_profile(name: &str) {
+    *FORCE_PROFILE.lock().unwrap_or_else(PoisonError::into_inner) = Some(name.to_string());
+}
+
+/// The profile chosen with `--profile`, or else with ARROWHEAD_PROFILE
+pub fn selected_profile() -> Option<String> {
+    FORCE_PROFILE.lock().unwrap_or_else(PoisonError::into_inner).clone()
+        .or_else(|| env::var(PROFILE_ENV).ok().filter(|name| !name.trim().is_empty()))
+}
+
+/// Returned instead of constructing an AI client in offline mode
+#[derive(Debug, thiserror::Error)]
+#[error("offline mode: {feature} is disabled (remove --offline or unset {OFFLINE_ENV} to use it)")]
+pub struct OfflineError {
+    pub feature: String,
+}
+
+/// Calendar configuration (CalDAV connection, working hours and event templates)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalendarSettings {
+    pub server_url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Calendar used when none is given: an id from `arrowhead calendar list`, or "default"
+    /// for the server URL itself
+    pub calendar_id: String,
+    /// Time zone the working hours are in, e.g. "America/Los_Angeles"; "local" for the system's
+    pub time_zone: String,
+    /// First working hour of the day (0-23)
+    pub work_start_hour: u32,
+    /// Last working hour of the day (exclusive, 1-24)
+    pub work_end_hour: u32,
+    /// Working days, 0 = Sunday, 1 = Monday, etc.
+    pub work_days: Vec<u32>,
+    /// Event templates keyed by name, e.g. `[calendar.templates.one_on_one]`
+    pub templates: HashMap<String, EventTemplate>,
+    /// People outside the CalDAV server keyed by email, e.g. `[calendar.contacts."alice@example.com"]`
+    pub contacts: HashMap<String, ContactSettings>,
+    /// Attendees whose CalDAV server answers free-busy queries, keyed by email, e.g.
+    /// `[calendar.directory."bob@example.com"]`
+    pub directory: HashMap<String, DirectoryEntry>,
+    /// Per-feed timeout when fetching contacts' availability
+    pub availability_timeout_secs: u64,
+    /// Warn when a feed cannot be fetched and its cached copy is older than this
+    pub availability_stale_hours: i64,
+    /// File deadlines are kept in; defaults to ~/.config/arrowhead/deadlines.json
+    pub deadlines_path: Option<String>,
+    /// How long to wait for the CalDAV server to accept a connection
+    pub connect_timeout_secs: u64,
+    /// How long a whole CalDAV request may take, response included
+    pub request_timeout_secs: u64,
+    /// Retries of reads (PROPFIND, REPORT, GET) that time out or get a 5xx, e.g.
+    /// `[calendar.retry] max_attempts = 5`
+    pub retry: RetrySettings,
+    /// Calendar `arrowhead sync todos-to-calendar` puts todo due dates in, by name; created
+    /// when missing
+    pub tasks_calendar: String,
+    /// File the state of the last todo sync is kept in; defaults to ~/.config/arrowhead/todo-sync.json
+    pub todo_sync_path: Option<String>,
+}
+
+/// A contact whose availability comes from a published calendar
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContactSettings {
+    /// Published ICS or freebusy URL (https:// or webcal://)
+    pub availability_url: Option<String>,
+}
+
+/// Where to send a CalDAV free-busy-query for one attendee
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirectoryEntry {
+    /// Calendar collection (or scheduling outbox) the REPORT is sent to
+    pub freebusy_url: Option<String>,
+    /// Credentials for that server; the calendar's own are used when unset
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// Reusable event template for quick-add shortcuts.
+///
+/// Text fields may contain `{name}`-style variables that are resolved at creation time.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventTemplate {
+    pub title: String,
+    pub duration_minutes: u32,
+    pub attendees: Vec<String>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Team terminology enforced in generated content (merged with the glossary note)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlossarySettings {
+    /// Vault path of the glossary note
+    pub note_path: String,
+    /// Terms that must keep this exact casing
+    pub preferred: Vec<String>,
+    /// Banned terms mapped to their replacement
+    pub replace: HashMap<String, String>,
+}
+
+impl Default for GlossarySettings {
+    fn default() -> Self {
+        Self {
+            note_path: "Glossary.md".to_string(),
+            preferred: Vec::new(),
+            replace: HashMap::new(),
+        }
+    }
+}
+
+/// Reviewable apply-plans written by `organize --plan-note`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanSettings {
+    /// Plans older than this are refused by `apply-plan`
+    pub max_age_hours: i64,
+}
+
+impl Default for PlanSettings {
+    fn default() -> Self {
+        Self { max_age_hours: 72 }
+    }
+}
+
+/// Long-term facts remembered across conversations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemorySettings {
+    /// Extract facts at the end of each conversation and recall them in the next
+    pub enabled: bool,
+    /// Most tokens of remembered facts added to a conversation
+    pub token_budget: usize,
+    /// Strip emails, long numbers and secret-looking tokens before storing a fact
+    pub redact_sensitive: bool,
+    /// Memory store location; defaults to ~/.config/arrowhead/memories.json
+    pub path: Option<String>,
+}
+
+impl Default for MemorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            token_budget: 300,
+            redact_sensitive: true,
+            path: None,
+        }
+    }
+}
+
+/// Changelog sections maintained on frequently edited (living) documents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChangelogSettings {
+    /// Notes carrying this tag get a managed changelog section
+    pub tag: String,
+    /// Fewer non-whitespace characters changed than this is not worth an entry
+    pub min_change_chars: usize,
+    /// Edits within this many minutes of the last entry are folded into it
+    pub coalesce_minutes: i64,
+    /// Entries kept in the note; older ones move to the archive note
+    pub max_entries: usize,
+    /// Folder holding the "earlier changes" archive notes
+    pub archive_folder: String,
+    /// Let the LLM reword the locally generated summary
+    pub llm_refine: bool,
+}
+
+impl Default for ChangelogSettings {
+    fn default() -> Self {
+        Self {
+            tag: "living-doc".to_string(),
+            min_change_chars: 40,
+            coalesce_minutes: 30,
+            max_entries: 10,
+            archive_folder: "Archive/Changelogs".to_string(),
+            llm_refine: false,
+        }
+    }
+}
+
+/// JSON-RPC server used by the Obsidian plugin (`arrowhead serve`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginApiSettings {
+    /// Open suggestion sessions kept at once; the least recently used is dropped first
+    pub max_sessions: usize,
+    /// Memory cap across all sessions (document text plus cached suggestions)
+    pub max_memory_bytes: usize,
+    /// Sessions untouched for this long are closed
+    pub idle_timeout_minutes: u64,
+    /// Folders whose note titles are suggested as links
+    pub title_folders: Vec<String>,
+}
+
+impl Default for PluginApiSettings {
+    fn default() -> Self {
+        Self {
+            max_sessions: 16,
+            max_memory_bytes: 32 * 1024 * 1024,
+            idle_timeout_minutes: 30,
+            title_folders: vec!["Notes".to_string()],
+        }
+    }
+}
+
+/// Rules for `arrowhead file`, e.g. `[[filing.rules]]` with a `name`, `destination`
+/// and `[filing.rules.when]` conditions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilingSettings {
+    pub rules: Vec<FilingRule>,
+    /// Folders scanned by `file --all`, `file rules stats` and `doctor`
+    pub folders: Vec<String>,
+}
+
+impl Default for FilingSettings {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            folders: vec!["Inbox".to_string()],
+        }
+    }
+}
+
+/// How `plan week` lays out deadline work and todos
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlannerSettings {
+    /// Minutes assumed for todos without an estimate
+    pub default_estimate_minutes: u32,
+    /// Todos this short are batched into admin blocks instead of getting their own event
+    pub admin_task_max_minutes: u32,
+    /// Longest admin block
+    pub admin_block_minutes: u32,
+    pub min_deadline_block_minutes: u32,
+    pub max_deadline_block_minutes: u32,
+}
+
+impl Default for PlannerSettings {
+    fn default() -> Self {
+        Self {
+            default_estimate_minutes: 30,
+            admin_task_max_minutes: 15,
+            admin_block_minutes: 60,
+            min_deadline_block_minutes: 30,
+            max_deadline_block_minutes: 120,
+        }
+    }
+}
+
+/// Which notes `arrowhead health` scans and how its components are weighted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthSettings {
+    /// Folders scanned (recursively); links to notes outside them count as broken
+    pub folders: Vec<String>,
+    /// Analyses older than this make a note stale
+    pub stale_days: i64,
+    pub weights: HealthWeights,
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        Self {
+            folders: vec!["Notes".to_string(), "Todos".to_string(), "Goals".to_string()],
+            stale_days: 90,
+            weights: HealthWeights::default(),
+        }
+    }
+}
+
+/// Relative weight of each health component; a weight of 0 leaves it out of the score
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthWeights {
+    pub tagged: f64,
+    pub linked: f64,
+    pub analyzed: f64,
+    pub embedded: f64,
+    pub fresh: f64,
+    pub no_broken_links: f64,
+    pub schema_current: f64,
+}
+
+impl Default for HealthWeights {
+    fn default() -> Self {
+        Self {
+            tagged: 1.0,
+            linked: 1.0,
+            analyzed: 0.5,
+            embedded: 0.5,
+            fresh: 1.0,
+            no_broken_links: 1.5,
+            schema_current: 0.5,
+        }
+    }
+}
+
+/// Which notes `arrowhead backlinks update` reads and which generated links it counts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BacklinksSettings {
+    /// Folders scanned (recursively) for inbound links
+    pub folders: Vec<String>,
+    /// Managed sections (e.g. "changelog") and "moc" whose links still count as inbound
+    pub include_generated: Vec<String>,
+    /// Notes with any of these tags are maps of content, skipped unless "moc" is included
+    pub moc_tags: Vec<String>,
+}
+
+impl Default for BacklinksSettings {
+    fn default() -> Self {
+        Self {
+            folders: vec!["Notes".to_string(), "Todos".to_string(), "Goals".to_string()],
+            include_generated: Vec::new(),
+            moc_tags: vec!["moc".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeetingNoteSettings {
+    /// Vault folder of the meeting notes
+    pub folder: String,
+    /// People page for an attendee email, e.g. `"jane@example.com" = "Jane Doe"`; others are
+    /// named after their address
+    pub people: HashMap<String, String>,
+}
+
+impl Default for MeetingNoteSettings {
+    fn default() -> Self {
+        Self { folder: "Meetings".to_string(), people: HashMap::new() }
+    }
+}
+
+/// How many notes `arrowhead related` suggests and how close they must be
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelatedSettings {
+    pub limit: usize,
+    /// Cosine similarity a note needs to be suggested; separate from `[search]`, where
+    /// looser matches are still worth listing
+    pub min_similarity: f32,
+}
+
+impl Default for RelatedSettings {
+    fn default() -> Self {
+        Self { limit: 5, min_similarity: 0.75 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConflictSettings {
+    /// Folders scanned (recursively) for conflict copies
+    pub folders: Vec<String>,
+    /// A note whose name contains one of these is a conflict copy of the name before it
+    pub patterns: Vec<String>,
+    /// Resolved conflict copies are moved here, keeping their path
+    pub trash_folder: String,
+}
+
+impl Default for ConflictSettings {
+    fn default() -> Self {
+        Self {
+            folders: vec!["Notes".to_string(), "Todos".to_string(), "Goals".to_string()],
+            patterns: vec![".sync-conflict-".to_string(), "conflicted copy".to_string(), "(Conflict".to_string()],
+            trash_folder: ".trash".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexSettings {
+    /// Folders embedded (recursively) by `arrowhead index`
+    pub folders: Vec<String>,
+    /// Embedding or analysis requests a batch keeps in flight at once
+    pub concurrency: usize,
+}
+
+impl Default for IndexSettings {
+    fn default() -> Self {
+        Self {
+            folders: vec!["Notes".to_string(), "Todos".to_string(), "Goals".to_string()],
+            concurrency: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchSettings {
+    /// Folders searched (recursively) by keyword searches
+    pub folders: Vec<String>,
+}
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        Self {
+            folders: vec!["Notes".to_string(), "Todos".to_string(), "Goals".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SplitSettings {
+    /// Adjacent sections whose embeddings are at least this similar stay in the same part
+    pub similarity_threshold: f32,
+    /// Parts shorter than this are merged into the part before them
+    pub min_part_words: usize,
+    /// Folders searched for links to rewrite when sections move
+    pub link_folders: Vec<String>,
+}
+
+impl Default for SplitSettings {
+    fn default() -> Self {
+        Self {
+            similarity_threshold: 0.75,
+            min_part_words: 150,
+            link_folders: vec!["Notes".to_string(), "Todos".to_string(), "Goals".to_string()],
+        }
+    }
+}
+
+/// Interactive conversations saved after every turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionSettings {
+    pub enabled: bool,
+    /// Session folder; defaults to ~/.config/arrowhead/sessions
+    pub path: Option<String>,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: None,
+        }
+    }
+}
+
+/// Requests and tokens sent to LLM and embedding providers, counted per provider per day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageSettings {
+    pub enabled: bool,
+    /// Requests refused once this many were started in the last minute
+    pub max_requests_per_minute: Option<u32>,
+    /// Requests refused once today's estimated tokens would pass this
+    pub max_daily_tokens: Option<u64>,
+    /// Usage ledger location; defaults to ~/.config/arrowhead/usage.json
+    pub path: Option<String>,
+}
+
+impl Default for UsageSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_requests_per_minute: None,
+            max_daily_tokens: None,
+            path: None,
+        }
+    }
+}
+
+/// Deadline and todo reminders sent by `arrowhead remind`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemindersSettings {
+    /// Minutes between checks in `--daemon` mode
+    pub interval_minutes: u64,
+    /// Where reminders go unless a deadline names its own channels: desktop and vault
+    pub channels: Vec<String>,
+    /// Vault note reminders are appended to; tick a reminder's checkbox to acknowledge it
+    pub note: String,
+    /// Also remind about open todos on the day they are due
+    pub todos: bool,
+    /// Time of day (HH:MM) todo reminders go out
+    pub todo_time: String,
+    /// Hours before an unacknowledged urgent reminder is sent again, for deadlines added with `--remind`
+    pub escalation_delay_hours: u32,
+    /// Record of sent reminders; defaults to ~/.config/arrowhead/reminders.json
+    pub state_path: Option<String>,
+}
+
+/// The change journal: the previous version of every note an AI feature rewrites
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistorySettings {
+    pub enabled: bool,
+    /// Folder the versions are kept in; defaults to ~/.config/arrowhead/history
+    pub path: Option<String>,
+    /// The oldest versions are dropped once the journal grows past this many megabytes
+    pub max_size_mb: u64,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self { enabled: true, path: None, max_size_mb: 50 }
+    }
+}
+
+/// `[cache]`: one folder of cache files per vault, and caps on the in-memory caches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheSettings {
+    /// Parent of the per-vault cache folders; defaults to ~/.cache/arrowhead
+    pub dir: Option<String>,
+    /// Cache folder of the vault in use, from its `cache_dir` in `[[vaults]]`
+    #[serde(skip)]
+    pub vault_dir: Option<String>,
+    /// Note analyses kept in memory; the least recently used are dropped first
+    pub analysis_entries: usize,
+    /// Notes whose writing suggestions are kept in memory; the oldest are dropped first
+    pub suggestion_entries: usize,
+    /// Seconds writing suggestions stay cached
+    pub suggestion_max_age_seconds: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self { dir: None, vault_dir: None, analysis_entries: 1000, suggestion_entries: 200, suggestion_max_age_seconds: 300 }
+    }
+}
+
+impl CacheSettings {
+    /// These settings with the caches kept where `vault` asks
+    pub fn for_vault(&self, vault: &VaultConfig) -> Self {
+        Self { vault_dir: vault.cache_dir.clone(), ..self.clone() }
+    }
+}
+
+impl Default for RemindersSettings {
+    fn default() -> Self {
+        Self {
+            interval_minutes: 5,
+            channels: vec!["desktop".to_string(), "vault".to_string()],
+            note: "Reminders.md".to_string(),
+            todos: true,
+            todo_time: "09:00".to_string(),
+            escalation_delay_hours: 2,
+            state_path: None,
+        }
+    }
+}
+
+/// Token budget of the conversation history sent with each request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextSettings {
+    /// Budget for models without an entry in `budgets`
+    pub default_budget: usize,
+    /// Budgets by model name, e.g. `"gpt-4o" = 100000`
+    pub budgets: HashMap<String, usize>,
+    /// Older messages are trimmed once the history reaches this percentage of the budget
+    pub trim_at_percent: usize,
+    /// ...down to this percentage
+    pub trim_to_percent: usize,
+    /// Most recent messages that are never trimmed
+    pub keep_recent: usize,
+    /// Summarize trimmed messages into a system note rather than dropping them
+    pub summarize: bool,
+}
+
+impl Default for ContextSettings {
+    fn default() -> Self {
+        Self {
+            default_budget: 8000,
+            budgets: HashMap::new(),
+            trim_at_percent: 90,
+            trim_to_percent: 60,
+            keep_recent: 4,
+            summarize: true,
+        }
+    }
+}
+
+impl ContextSettings {
+    pub fn budget_for(&self, model: &str) -> usize {
+        self.budgets.get(model).copied().unwrap_or(self.default_budget)
+    }
+}
+
+/// Where `arrowhead daily` keeps daily notes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DailySettings {
+    /// Vault folder of the daily notes
+    pub folder: String,
+    /// strftime format of the note name, e.g. "%Y-%m-%d"
+    pub file_format: String,
+    /// Always add an AI-written focus summary, as `--focus` does
+    pub ai_summary: bool,
+}
+
+impl Default for DailySettings {
+    fn default() -> Self {
+        Self {
+            folder: "Daily".to_string(),
+            file_format: "%Y-%m-%d".to_string(),
+            ai_summary: false,
+        }
+    }
+}
+
+/// Where `arrowhead insights` finds journal notes and how it dates them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InsightsSettings {
+    /// Folder charted when `--folder` isn't given
+    pub folder: String,
+    /// Frontmatter key holding the note's date; it wins over the filename
+    pub date_field: String,
+    /// strftime format of a date inside the note name, e.g. "%Y-%m-%d" for "2024-03-05 Monday"
+    pub filename_date_format: String,
+    /// Rising and falling themes listed
+    pub top_themes: usize,
+}
+
+impl Default for InsightsSettings {
+    fn default() -> Self {
+        Self {
+            folder: "Journal".to_string(),
+            date_field: "date".to_string(),
+            filename_date_format: "%Y-%m-%d".to_string(),
+            top_themes: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrganizeSettings {
+    /// `ai` sends each note to the LLM, `heuristic` never does, and `auto` uses the LLM only
+    /// when one is configured
+    pub analysis_backend: AnalysisBackend,
+}
+
+/// How `arrowhead watch` notices changed notes and what it does with them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchSettings {
+    /// Folder of the vault on this machine; when set it is scanned directly instead of
+    /// listing notes through the REST API
+    pub vault_path: Option<String>,
+    /// Folders watched (recursively)
+    pub folders: Vec<String>,
+    pub poll_interval_ms: u64,
+    /// A note is processed once it has gone this long without another change
+    pub debounce_ms: u64,
+    /// Notes analyzed at once
+    pub concurrency: usize,
+    /// Also generate organization recommendations and apply them per the flags below
+    pub organize: bool,
+    pub auto_apply_tags: bool,
+    pub auto_move_notes: bool,
+    pub auto_insert_links: bool,
+}
+
+impl Default for WatchSettings {
+    fn default() -> Self {
+        Self {
+            vault_path: None,
+            folders: vec!["Notes".to_string()],
+            poll_interval_ms: 2_000,
+            debounce_ms: 1_500,
+            concurrency: 2,
+            organize: false,
+            auto_apply_tags: true,
+            auto_move_notes: false,
+            auto_insert_links: false,
+        }
+    }
+}
+
+impl Default for CalendarSettings {
+    fn default() -> Self {
+        Self {
+            server_url: "https://caldav.icloud.com".to_string(),
+            username: None,
+            password: None,
+            calendar_id: "default".to_string(),
+            time_zone: "local".to_string(),
+            work_start_hour: 9,
+            work_end_hour: 17,
+            work_days: vec![1, 2, 3, 4, 5],
+            templates: HashMap::new(),
+            contacts: HashMap::new(),
+            directory: HashMap::new(),
+            availability_timeout_secs: 10,
+            availability_stale_hours: 24,
+            deadlines_path: None,
+            connect_timeout_secs: crate::calendar_adapter::DEFAULT_CONNECT_TIMEOUT_SECS,
+            request_timeout_secs: crate::calendar_adapter::DEFAULT_REQUEST_TIMEOUT_SECS,
+            retry: crate::calendar_adapter::default_calendar_retry(),
+            tasks_calendar: "Arrowhead Tasks".to_string(),
+            todo_sync_path: None,
+        }
+    }
 }
 
 impl Default for Config {
@@ -70,6 +1011,10 @@ impl Default for Config {
                     temperature: 0.7,
                     max_tokens: 4096,
                 },
+                anthropic: AnthropicConfig::default(),
+                ollama: OllamaConfig::default(),
+                retry: RetrySettings::default(),
+                fallback: Vec::new(),
             },
             obsidian: ObsidianConfig {
                 api_key: None,
@@ -79,30 +1024,189 @@ impl Default for Config {
                 verbose: false,
                 auto_save: true,
                 max_conversation_history: 100,
+                offline: false,
+                vault: None,
             },
+            calendar: CalendarSettings::default(),
+            glossary: GlossarySettings::default(),
+            plans: PlanSettings::default(),
+            embeddings: EmbeddingStorageConfig::default(),
+            memory: MemorySettings::default(),
+            opt_outs: OptOutPolicy::default(),
+            changelog: ChangelogSettings::default(),
+            plugin_api: PluginApiSettings::default(),
+            filing: FilingSettings::default(),
+            planner: PlannerSettings::default(),
+            confirmations: ConfirmationSettings::default(),
+            health: HealthSettings::default(),
+            network: NetworkSettings::default(),
+            backlinks: BacklinksSettings::default(),
+            related: RelatedSettings::default(),
+            meetings: MeetingNoteSettings::default(),
+            invites: InviteSettings::default(),
+            scheduling: SchedulingSettings::default(),
+            tool_results: ToolResultSettings::default(),
+            conflicts: ConflictSettings::default(),
+            index: IndexSettings::default(),
+            search: SearchSettings::default(),
+            split: SplitSettings::default(),
+            sessions: SessionSettings::default(),
+            context: ContextSettings::default(),
+            daily: DailySettings::default(),
+            insights: InsightsSettings::default(),
+            organize: OrganizeSettings::default(),
+            watch: WatchSettings::default(),
+            usage: UsageSettings::default(),
+            reminders: RemindersSettings::default(),
+            history: HistorySettings::default(),
+            cache: CacheSettings::default(),
+            vaults: Vec::new(),
+            profiles: BTreeMap::new(),
+            profile: None,
         }
     }
 }
 
 impl Config {
-    /// Load configuration from file and environment variables
+    /// Load configuration from file and environment variables. Later sources win: the
+    /// defaults, the file's top-level keys, the selected profile, then environment variables.
+    /// Fails when the selected profile doesn't exist or sets an invalid value.
     pub fn load() -> Result<Self> {
-        let mut config = Self::load_from_file().unwrap_or_default();
+        // Steps of an `arrowhead run` script share the config loaded for the run
+        match crate::script::shared_config() {
+            Some(config) => Ok(config.with_overrides()),
+            None => Self::load_profile(selected_profile().as_deref()),
+        }
+    }
+
+    /// The configuration with the profile `name` merged in, or without any profile for `None`
+    pub fn load_profile(name: Option<&str>) -> Result<Self> {
+        let contents = Self::read_file();
+        let mut config = Self::from_toml(contents.as_deref(), name)?;
         config.load_from_env();
-        Ok(config)
+        Ok(config.with_overrides())
     }
 
-    /// Load configuration from file
-    fn load_from_file() -> Option<Self> {
+    /// Apply `--offline` and `--vault`, then point `[obsidian]` at the chosen vault
+    fn with_overrides(mut self) -> Self {
+        if FORCE_OFFLINE.load(Ordering::Relaxed) {
+            self.general.offline = true;
+        }
+        if let Some(name) = FORCE_VAULT.lock().unwrap_or_else(PoisonError::into_inner).clone() {
+            self.general.vault = Some(name);
+        }
+        if let Err(e) = self.select_vault() {
+            log::warn!("{:#}; using [obsidian]", e);
+        }
+        self
+    }
+
+    /// The `[[vaults]]` entry named `name`, ignoring case
+    pub fn vault(&self, name: &str) -> Result<&VaultConfig> {
+        self.vaults.iter().find(|v| v.name.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            let names: Vec<&str> = self.vaults.iter().map(|v| v.name.as_str()).collect();
+            if names.is_empty() {
+                anyhow::anyhow!("No vault named '{}': no [[vaults]] are configured", name)
+            } else {
+                anyhow::anyhow!("No vault named '{}'; configured vaults: {}", name, names.join(", "))
+            }
+        })
+    }
+
+    /// Point `[obsidian]` and the cache folder at the vault `general.vault` names, so
+    /// everything that reads them works on that vault
+    pub fn select_vault(&mut self) -> Result<()> {
+        let Some(name) = self.general.vault.clone() else { return Ok(()) };
+        let vault = self.vault(&name)?.clone();
+        self.general.vault = Some(vault.name.clone());
+        self.obsidian.base_url = vault.base_url.clone();
+        self.obsidian.api_key = vault.api_key.clone();
+        self.cache = self.cache.for_vault(&vault);
+        Ok(())
+    }
+
+    /// Make `name` the vault used when `--vault` isn't given
+    pub fn set_default_vault(name: &str) -> Result<VaultConfig> {
+        let vault = Self::load()?.vault(name)?.clone();
+        Self::set_key("general.vault", &vault.name)?;
+        Ok(vault)
+    }
+
+    /// Fail with an `OfflineError` naming `feature` when offline mode is on
+    pub fn ensure_online(&self, feature: &str) -> Result<()> {
+        if self.general.offline {
+            return Err(OfflineError { feature: feature.to_string() }.into());
+        }
+        Ok(())
+    }
+
+    /// The config file's text, if there is one
+    fn read_file() -> Option<String> {
         let config_path = Self::get_config_path();
         if config_path.exists() {
-            let contents = fs::read_to_string(config_path).ok()?;
-            toml::from_str(&contents).ok()
+            fs::read_to_string(config_path).ok()
         } else {
             None
         }
     }
 
+    /// Parse the config file's text, with the profile `profile` merged over its top-level
+    /// keys. A file that doesn't load gives the defaults, as no file does.
+    pub fn from_toml(contents: Option<&str>, profile: Option<&str>) -> Result<Self> {
+        let mut config: Self = contents.and_then(|contents| toml::from_str(contents).ok()).unwrap_or_default();
+        if let Some(name) = profile {
+            config.apply_profile(name)?;
+        }
+        Ok(config)
+    }
+
+    /// The `[profiles]` entry named `name`, ignoring case
+    pub fn find_profile(&self, name: &str) -> Result<(&String, &toml::Table)> {
+        self.profiles.iter().find(|(profile, _)| profile.eq_ignore_ascii_case(name)).ok_or_else(|| {
+            let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            if names.is_empty() {
+                anyhow::anyhow!("No profile named '{}': no [profiles] are configured", name)
+            } else {
+                anyhow::anyhow!("No profile named '{}'; configured profiles: {}", name, names.join(", "))
+            }
+        })
+    }
+
+    /// Merge the overrides of the profile `name` into this config. Its values go through
+    /// the same checks as `config set`, and errors name the profile they came from.
+    fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let (name, overrides) = self.find_profile(name).map(|(name, overrides)| (name.clone(), overrides.clone()))?;
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(anyhow::anyhow!("Profile name '{}' may only use letters, digits, '-' and '_'", name));
+        }
+        if overrides.contains_key("profiles") {
+            return Err(anyhow::anyhow!("[profiles.{}] can't contain other profiles", name));
+        }
+        let toml::Value::Table(mut tree) = toml::Value::try_from(&*self)? else {
+            return Err(anyhow::anyhow!("The configuration is not a table"));
+        };
+        merge_tables(&mut tree, &overrides);
+        let mut merged: Self = toml::Value::Table(tree).try_into()
+            .map_err(|e: toml::de::Error| anyhow::anyhow!("[profiles.{}] does not load: {}", name, e.message().trim()))?;
+        for key in leaf_keys(&overrides) {
+            check_value(&key, &merged).map_err(|e| anyhow::anyhow!("[profiles.{}] {}: {}", name, key, e))?;
+        }
+
+        // Each profile keeps its own caches and saved sessions unless it says where they go
+        let sets = |section: &str, key: &str| overrides.get(section).and_then(|table| table.get(key)).is_some();
+        if !sets("cache", "dir") {
+            let dir = crate::cache::cache_dir(&merged.cache).join("profiles").join(&name);
+            merged.cache.dir = Some(dir.to_string_lossy().into_owned());
+        }
+        if !sets("sessions", "path") {
+            let dir = merged.sessions.path.as_ref().map(PathBuf::from).unwrap_or_else(crate::sessions::default_sessions_dir);
+            merged.sessions.path = Some(dir.join("profiles").join(&name).to_string_lossy().into_owned());
+        }
+        merged.profile = Some(name);
+        *self = merged;
+        Ok(())
+    }
+
     /// Load configuration from environment variables
     fn load_from_env(&mut self) {
         if let Ok(api_key) = env::var("GEMINI_API_KEY") {
@@ -112,6 +1216,10 @@ impl Config {
         if let Ok(api_key) = env::var("OPENAI_API_KEY") {
             self.llm.openai.api_key = Some(api_key);
         }
+
+        if let Ok(api_key) = env::var("ANTHROPIC_API_KEY") {
+            self.llm.anthropic.api_key = Some(api_key);
+        }
         
         if let Ok(api_key) = env::var("OBSIDIAN_API_KEY") {
             self.obsidian.api_key = Some(api_key);
@@ -128,6 +1236,22 @@ impl Config {
         if let Ok(verbose) = env::var("ARROWHEAD_VERBOSE") {
             self.general.verbose = verbose.parse().unwrap_or(false);
         }
+
+        if let Ok(offline) = env::var(OFFLINE_ENV) {
+            self.general.offline = matches!(offline.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on");
+        }
+
+        if let Ok(server_url) = env::var("CALDAV_SERVER_URL") {
+            self.calendar.server_url = server_url;
+        }
+
+        if let Ok(username) = env::var("CALDAV_USERNAME") {
+            self.calendar.username = Some(username);
+        }
+
+        if let Ok(password) = env::var("CALDAV_PASSWORD") {
+            self.calendar.password = Some(password);
+        }
     }
 
     /// Save configuration to file
@@ -160,6 +1284,7 @@ impl Config {
         match self.llm.provider.as_str() {
             "gemini" => self.llm.gemini.api_key.clone(),
             "openai" => self.llm.openai.api_key.clone(),
+            "anthropic" => self.llm.anthropic.api_key.clone(),
             _ => None,
         }
     }
@@ -169,15 +1294,30 @@ impl Config {
         match self.llm.provider.as_str() {
             "gemini" => self.llm.gemini.model.clone(),
             "openai" => self.llm.openai.model.clone(),
+            "anthropic" => self.llm.anthropic.model.clone(),
+            "ollama" => self.llm.ollama.model.clone(),
             _ => "gemini-1.5-flash".to_string(),
         }
     }
 
+    /// Use `model` with the current LLM provider
+    pub fn set_llm_model(&mut self, model: &str) {
+        let model = model.to_string();
+        match self.llm.provider.as_str() {
+            "openai" => self.llm.openai.model = model,
+            "anthropic" => self.llm.anthropic.model = model,
+            "ollama" => self.llm.ollama.model = model,
+            _ => self.llm.gemini.model = model,
+        }
+    }
+
     /// Get the temperature for the current LLM provider
     pub fn get_llm_temperature(&self) -> f32 {
         match self.llm.provider.as_str() {
             "gemini" => self.llm.gemini.temperature,
             "openai" => self.llm.openai.temperature,
+            "anthropic" => self.llm.anthropic.temperature,
+            "ollama" => self.llm.ollama.temperature,
             _ => 0.7,
         }
     }
@@ -187,18 +1327,37 @@ impl Config {
         match self.llm.provider.as_str() {
             "gemini" => self.llm.gemini.max_tokens,
             "openai" => self.llm.openai.max_tokens,
+            "anthropic" => self.llm.anthropic.max_tokens,
+            "ollama" => self.llm.ollama.max_tokens,
             _ => 8192,
         }
     }
 
-    /// Validate the configuration
+    /// Whether `provider` needs an API key; a local Ollama server doesn't
+    pub fn provider_needs_api_key(provider: &str) -> bool {
+        provider != "ollama"
+    }
+
+    /// Validate the configuration. Errors about LLM settings a profile overrides name it.
     pub fn validate(&self) -> Result<()> {
+        let result = self.validate_llm();
+        match &self.profile {
+            Some(name) if self.profiles.get(name).is_some_and(|overrides| overrides.contains_key("llm")) => {
+                result.map_err(|e| anyhow::anyhow!("{} (from [profiles.{}])", e, name))
+            }
+            _ => result,
+        }
+    }
+
+    fn validate_llm(&self) -> Result<()> {
         // Check that we have an API key for the selected provider
-        if self.get_llm_api_key().is_none() {
+        if Self::provider_needs_api_key(&self.llm.provider) && self.get_llm_api_key().is_none() {
             return Err(anyhow::anyhow!(
                 "No API key found for LLM provider '{}'. Please set the appropriate environment variable:\n\
                  - For Gemini: GEMINI_API_KEY\n\
-                 - For OpenAI: OPENAI_API_KEY",
+                 - For OpenAI: OPENAI_API_KEY\n\
+                 - For Anthropic: ANTHROPIC_API_KEY\n\
+                 Or set llm.provider to \"ollama\" to use a local model without a key",
                 self.llm.provider
             ));
         }
@@ -211,6 +1370,9 @@ impl Config {
                 temperature
             ));
         }
+        if self.llm.provider == "anthropic" && temperature > 1.0 {
+            return Err(anyhow::anyhow!("Anthropic temperature must be between 0.0 and 1.0, got {}", temperature));
+        }
 
         // Validate max tokens
         let max_tokens = self.get_llm_max_tokens();
@@ -218,6 +1380,10 @@ impl Config {
             return Err(anyhow::anyhow!("Max tokens must be greater than 0"));
         }
 
+        if let Some(provider) = self.llm.fallback.iter().find(|p| !matches!(p.as_str(), "gemini" | "openai" | "anthropic" | "ollama")) {
+            return Err(anyhow::anyhow!("Unknown fallback LLM provider '{}' (expected gemini, openai, anthropic or ollama)", provider));
+        }
+
         Ok(())
     }
 
@@ -241,86 +1407,67 @@ impl Config {
         Ok(())
     }
 
-    /// Set a configuration value
-    pub fn set_value(&mut self, key: &str, value: &str) -> Result<()> {
+    /// Set one key in the config file, e.g. `llm.model` or `calendar.time_zone`, leaving every
+    /// other line (comments and keys this version doesn't know included) as it was.
+    /// Returns the key as written, with aliases such as `llm.model` resolved.
+    pub fn set_key(key: &str, value: &str) -> Result<String> {
+        let path = Self::get_config_path();
+        let current = Self::load().unwrap_or_default();
+        let key = current.resolve_key(key);
+        // Without a config file, start from the defaults so the result loads on its own
+        let original = if path.exists() { fs::read_to_string(&path)? } else { toml::to_string_pretty(&Self::default())? };
+        let updated = set_key_in_toml(&original, &current, &key, value)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, updated)?;
+        Ok(key)
+    }
+
+    /// The current value of a key, after environment overrides
+    pub fn get_key(&self, key: &str) -> Result<String> {
+        let key = self.resolve_key(key);
+        let tree = toml::Value::try_from(self)?;
+        match lookup(&tree, &key) {
+            Some(toml::Value::String(text)) => Ok(text.clone()),
+            Some(value) => Ok(value.to_string()),
+            None if is_known_key(self, &key) => Ok(String::new()),
+            None => Err(anyhow::anyhow!("Unknown configuration key: {}", key)),
+        }
+    }
+
+    /// Full path of a key: `llm.model`, `llm.temperature`, `llm.max_tokens` and `llm.api_key`
+    /// address the current provider, and the older `provider`, `gemini.*`, `openai.*`, `anthropic.*` and `ollama.*` spellings
+    /// are accepted
+    pub fn resolve_key(&self, key: &str) -> String {
         match key {
-            "gemini.api_key" => {
-                self.llm.gemini.api_key = Some(value.to_string());
-            }
-            "gemini.model" => {
-                self.llm.gemini.model = value.to_string();
-            }
-            "gemini.temperature" => {
-                self.llm.gemini.temperature = value.parse::<f32>()
-                    .map_err(|_| anyhow::anyhow!("Invalid temperature value: {}", value))?;
-            }
-            "gemini.max_tokens" => {
-                self.llm.gemini.max_tokens = value.parse::<u32>()
-                    .map_err(|_| anyhow::anyhow!("Invalid max_tokens value: {}", value))?;
-            }
-            "openai.api_key" => {
-                self.llm.openai.api_key = Some(value.to_string());
-            }
-            "openai.model" => {
-                self.llm.openai.model = value.to_string();
-            }
-            "openai.temperature" => {
-                self.llm.openai.temperature = value.parse::<f32>()
-                    .map_err(|_| anyhow::anyhow!("Invalid temperature value: {}", value))?;
-            }
-            "openai.max_tokens" => {
-                self.llm.openai.max_tokens = value.parse::<u32>()
-                    .map_err(|_| anyhow::anyhow!("Invalid max_tokens value: {}", value))?;
-            }
-            "obsidian.api_key" => {
-                self.obsidian.api_key = Some(value.to_string());
-            }
-            "obsidian.base_url" => {
-                self.obsidian.base_url = value.to_string();
-            }
-            "provider" => {
-                match value {
-                    "gemini" | "openai" => {
-                        self.llm.provider = value.to_string();
-                    }
-                    _ => {
-                        return Err(anyhow::anyhow!("Invalid provider: {}. Must be 'gemini' or 'openai'", value));
-                    }
-                }
-            }
-            "general.verbose" => {
-                self.general.verbose = value.parse::<bool>()
-                    .map_err(|_| anyhow::anyhow!("Invalid verbose value: {}. Must be true or false", value))?;
-            }
-            "general.auto_save" => {
-                self.general.auto_save = value.parse::<bool>()
-                    .map_err(|_| anyhow::anyhow!("Invalid auto_save value: {}. Must be true or false", value))?;
-            }
-            "general.max_conversation_history" => {
-                self.general.max_conversation_history = value.parse::<usize>()
-                    .map_err(|_| anyhow::anyhow!("Invalid max_conversation_history value: {}", value))?;
-            }
-            _ => {
-                return Err(anyhow::anyhow!("Unknown configuration key: {}", key));
+            "provider" => "llm.provider".to_string(),
+            "llm.model" | "llm.temperature" | "llm.max_tokens" | "llm.api_key" => {
+                format!("llm.{}.{}", self.llm.provider, &key["llm.".len()..])
             }
+            _ if ["gemini.", "openai.", "anthropic.", "ollama."].iter().any(|prefix| key.starts_with(prefix)) => format!("llm.{}", key),
+            _ => key.to_string(),
         }
-        Ok(())
     }
 
-    /// Get available configuration keys
+    /// Commonly changed keys, for help output; any key in config.toml can be set
     pub fn get_available_keys() -> Vec<&'static str> {
         vec![
-            "gemini.api_key",
-            "gemini.model",
-            "gemini.temperature",
-            "gemini.max_tokens",
-            "openai.api_key",
-            "openai.model",
-            "openai.temperature",
-            "openai.max_tokens",
-            "obsidian.api_key",
+            "llm.provider",
+            "llm.model",
+            "llm.temperature",
+            "llm.max_tokens",
+            "llm.api_key",
+            "llm.gemini.model",
+            "llm.openai.model",
+            "llm.anthropic.model",
+            "llm.ollama.model",
+            "llm.ollama.base_url",
             "obsidian.base_url",
-            "provider",
+            "obsidian.api_key",
+            "calendar.calendar_id",
+            "calendar.time_zone",
             "general.verbose",
             "general.auto_save",
             "general.max_conversation_history",
@@ -328,6 +1475,165 @@ impl Config {
     }
 }
 
+/// Environment variables that override a config key when set (see `load_from_env`)
+pub const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("llm.gemini.api_key", "GEMINI_API_KEY"),
+    ("llm.openai.api_key", "OPENAI_API_KEY"),
+    ("llm.anthropic.api_key", "ANTHROPIC_API_KEY"),
+    ("llm.provider", "LLM_PROVIDER"),
+    ("obsidian.api_key", "OBSIDIAN_API_KEY"),
+    ("obsidian.base_url", "OBSIDIAN_BASE_URL"),
+    ("general.verbose", "ARROWHEAD_VERBOSE"),
+    ("general.offline", OFFLINE_ENV),
+    ("calendar.server_url", "CALDAV_SERVER_URL"),
+    ("calendar.username", "CALDAV_USERNAME"),
+    ("calendar.password", "CALDAV_PASSWORD"),
+];
+
+/// The environment variable currently overriding `key`, if any
+pub fn env_override(key: &str) -> Option<&'static str> {
+    ENV_OVERRIDES.iter().find(|(k, var)| *k == key && env::var(var).is_ok()).map(|(_, var)| *var)
+}
+
+/// Merge `overrides` into `base`: tables are merged key by key, any other value replaces
+/// the one in `base`
+fn merge_tables(base: &mut toml::Table, overrides: &toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => merge_tables(base, overrides),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// The dotted keys of every value in `table` that isn't itself a table
+pub fn leaf_keys(table: &toml::Table) -> Vec<String> {
+    table.iter()
+        .flat_map(|(key, value)| match value {
+            toml::Value::Table(inner) => leaf_keys(inner).into_iter().map(|leaf| format!("{}.{}", key, leaf)).collect(),
+            _ => vec![key.clone()],
+        })
+        .collect()
+}
+
+fn lookup<'a>(tree: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.').try_fold(tree, |node, part| node.get(part))
+}
+
+/// Whether `Config` has this key. Unset optional keys are left out of the serialized config,
+/// so those are recognised by giving them a value and seeing whether it survives a round trip.
+fn is_known_key(config: &Config, key: &str) -> bool {
+    let Ok(tree) = toml::Value::try_from(config) else { return false };
+    if lookup(&tree, key).is_some() {
+        return true;
+    }
+    let Some((parent, leaf)) = key.rsplit_once('.') else { return false };
+    [toml::Value::String(String::new()), toml::Value::Integer(0), toml::Value::Float(0.0), toml::Value::Boolean(false)]
+        .into_iter()
+        .any(|probe| {
+            let mut tree = tree.clone();
+            let Some(table) = parent.split('.').try_fold(&mut tree, |node, part| node.get_mut(part)).and_then(toml::Value::as_table_mut) else {
+                return false;
+            };
+            table.insert(leaf.to_string(), probe);
+            tree.try_into::<Config>()
+                .and_then(|config| toml::Value::try_from(config).map_err(serde::de::Error::custom))
+                .is_ok_and(|round_trip| lookup(&round_trip, key).is_some())
+        })
+}
+
+/// Range and enum checks `toml` deserialization can't express
+fn check_value(key: &str, config: &Config) -> Result<()> {
+    let leaf = key.rsplit('.').next().unwrap_or(key);
+    let tree = toml::Value::try_from(config)?;
+    match (leaf, lookup(&tree, key)) {
+        ("temperature", Some(toml::Value::Float(t))) if !(0.0..=2.0).contains(t) => {
+            Err(anyhow::anyhow!("Temperature must be between 0.0 and 2.0, got {}", t))
+        }
+        ("max_tokens", Some(toml::Value::Integer(0))) => Err(anyhow::anyhow!("Max tokens must be greater than 0")),
+        _ if key == "general.vault" => config.general.vault.as_deref().map_or(Ok(()), |name| config.vault(name).map(|_| ())),
+        _ if key == "llm.provider" && !matches!(config.llm.provider.as_str(), "gemini" | "openai" | "anthropic" | "ollama") => {
+            Err(anyhow::anyhow!("Invalid provider: {}. Must be 'gemini', 'openai', 'anthropic' or 'ollama'", config.llm.provider))
+        }
+        ("base_url" | "server_url", Some(toml::Value::String(url))) if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") => {
+            Err(anyhow::anyhow!("{} must start with http:// or https://, got {}", key, url))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A value typed like the key's current value; keys that are unset are read as a bool,
+/// number or string, whichever fits
+fn typed_value(current: Option<&toml::Value>, key: &str, raw: &str) -> Result<toml_edit::Value> {
+    let invalid = |kind: &str| anyhow::anyhow!("Invalid value for {}: '{}' is not {}", key, raw, kind);
+    Ok(match current {
+        Some(toml::Value::String(_)) => raw.into(),
+        Some(toml::Value::Boolean(_)) => raw.parse::<bool>().map_err(|_| invalid("true or false"))?.into(),
+        Some(toml::Value::Integer(_)) => raw.parse::<i64>().map_err(|_| invalid("a whole number"))?.into(),
+        Some(toml::Value::Float(_)) => raw.parse::<f64>().map_err(|_| invalid("a number"))?.into(),
+        Some(toml::Value::Array(_)) => {
+            let mut items = toml_edit::Array::new();
+            for item in raw.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+                items.push(item);
+            }
+            items.into()
+        }
+        Some(_) => return Err(anyhow::anyhow!("{} is a section; set one of its keys instead", key)),
+        None => {
+            if let Ok(flag) = raw.parse::<bool>() {
+                flag.into()
+            } else if let Ok(number) = raw.parse::<i64>() {
+                number.into()
+            } else {
+                raw.into()
+            }
+        }
+    })
+}
+
+/// `original` (the config file's text) with `key` set to `value`. The result is checked to
+/// load as a `Config` with the key in it, so typos and wrongly typed values are refused.
+pub fn set_key_in_toml(original: &str, current: &Config, key: &str, value: &str) -> Result<String> {
+    let mut doc: toml_edit::DocumentMut = original.parse()
+        .map_err(|e| anyhow::anyhow!("The config file is not valid TOML: {}", e))?;
+    if let Err(e) = toml::from_str::<Config>(original) {
+        return Err(anyhow::anyhow!("The config file does not load ({}); fix it or recreate it with `arrowhead config --init`", e.message()));
+    }
+    let tree = toml::Value::try_from(current)?;
+    let existing = lookup(&tree, key);
+    if existing.is_none() && !is_known_key(current, key) {
+        return Err(anyhow::anyhow!("Unknown configuration key: {}", key));
+    }
+
+    let (parents, leaf) = key.rsplit_once('.').map_or((Vec::new(), key), |(parents, leaf)| (parents.split('.').collect(), leaf));
+    let write = |doc: &mut toml_edit::DocumentMut, value: toml_edit::Value| -> Result<()> {
+        let mut table = doc.as_table_mut();
+        for part in &parents {
+            let entry = table.entry(part).or_insert_with(toml_edit::table);
+            table = entry.as_table_mut()
+                .ok_or_else(|| anyhow::anyhow!("{} is not a section in the config file", part))?;
+        }
+        table.insert(leaf, toml_edit::value(value));
+        Ok(())
+    };
+    write(&mut doc, typed_value(existing, key, value)?)?;
+
+    // An unset key read as a number may really be a string (an all-digit password, say)
+    let mut parsed = toml::from_str::<Config>(&doc.to_string());
+    if parsed.is_err() && existing.is_none() {
+        write(&mut doc, value.into())?;
+        parsed = toml::from_str::<Config>(&doc.to_string());
+    }
+    let config = parsed.map_err(|e| anyhow::anyhow!("Invalid value for {}: {}", key, e.message()))?;
+    if lookup(&toml::Value::try_from(&config)?, key).is_none() {
+        return Err(anyhow::anyhow!("Unknown configuration key: {}", key));
+    }
+    check_value(key, &config)?;
+    Ok(doc.to_string())
+}
+
 /// Configuration builder for programmatic configuration
 pub struct ConfigBuilder {
     config: Config,
@@ -410,8 +1716,200 @@ mod tests {
         config.llm.gemini.api_key = Some("test_key".to_string());
         assert!(config.validate().is_ok());
         
+        // Should fail with an unknown fallback provider
+        config.llm.fallback = vec!["openai".to_string(), "claude".to_string()];
+        assert!(config.validate().is_err());
+        config.llm.fallback.pop();
+        assert!(config.validate().is_ok());
+
         // Should fail with invalid temperature
         config.llm.gemini.temperature = 3.0;
         assert!(config.validate().is_err());
     }
-}
\ No newline at end of file
+
+    fn config_file() -> String {
+        format!("# my settings\n{}\n[custom]\nshortcut = \"x\" # keep me\n", toml::to_string_pretty(&Config::default()).unwrap())
+    }
+
+    #[test]
+    fn test_set_key_keeps_comments_and_unknown_keys() {
+        let config = Config::default();
+        let key = config.resolve_key("llm.temperature");
+        assert_eq!(key, "llm.gemini.temperature");
+
+        let updated = set_key_in_toml(&config_file(), &config, &key, "0.4").unwrap();
+        assert!(updated.starts_with("# my settings\n"));
+        assert!(updated.contains("shortcut = \"x\" # keep me"));
+        let reloaded: Config = toml::from_str(&updated).unwrap();
+        assert_eq!(reloaded.llm.gemini.temperature, 0.4);
+        assert_eq!(reloaded.llm.openai.temperature, config.llm.openai.temperature);
+
+        // Unset optional keys can be set too
+        let updated = set_key_in_toml(&updated, &reloaded, "obsidian.api_key", "12345").unwrap();
+        let reloaded: Config = toml::from_str(&updated).unwrap();
+        assert_eq!(reloaded.obsidian.api_key.as_deref(), Some("12345"));
+        assert_eq!(reloaded.get_key("obsidian.api_key").unwrap(), "12345");
+    }
+
+    #[test]
+    fn test_set_key_validates_before_writing() {
+        let config = Config::default();
+        let file = config_file();
+        let error = |key: &str, value: &str| set_key_in_toml(&file, &config, &config.resolve_key(key), value).unwrap_err().to_string();
+
+        assert!(error("llm.temperature", "3.0").contains("between 0.0 and 2.0"));
+        assert!(error("llm.temperature", "warm").contains("not a number"));
+        assert!(error("llm.provider", "claude").contains("Must be 'gemini', 'openai', 'anthropic' or 'ollama'"));
+        assert!(error("obsidian.base_url", "localhost:27123").contains("http://"));
+        assert!(error("llm.modle", "x").contains("Unknown configuration key"));
+        assert!(error("obsidian.nonsense", "x").contains("Unknown configuration key"));
+        assert!(set_key_in_toml(&file, &config, "llm.provider", "openai").is_ok());
+    }
+
+    #[test]
+    fn test_resolve_key_aliases() {
+        let mut config = Config::default();
+        config.llm.provider = "openai".to_string();
+        assert_eq!(config.resolve_key("llm.model"), "llm.openai.model");
+        assert_eq!(config.resolve_key("provider"), "llm.provider");
+        assert_eq!(config.resolve_key("gemini.api_key"), "llm.gemini.api_key");
+        assert_eq!(config.resolve_key("calendar.time_zone"), "calendar.time_zone");
+        assert_eq!(config.get_key("llm.model").unwrap(), config.llm.openai.model);
+        assert!(config.get_key("llm.nope").is_err());
+    }
+
+    #[test]
+    fn test_anthropic_provider() {
+        let mut config = Config::default();
+        config.llm.provider = "anthropic".to_string();
+        assert!(config.validate().unwrap_err().to_string().contains("ANTHROPIC_API_KEY"));
+
+        config.llm.anthropic.api_key = Some("test-key".to_string());
+        assert!(config.validate().is_ok());
+        assert_eq!(config.get_llm_model(), "claude-3-5-sonnet-latest");
+        assert_eq!(config.resolve_key("llm.max_tokens"), "llm.anthropic.max_tokens");
+        assert_eq!(config.resolve_key("anthropic.model"), "llm.anthropic.model");
+        assert_eq!(crate::ai_conversation::create_llm_client(&config, crate::usage::UsagePurpose::Chat).unwrap().get_model_name(), "claude-3-5-sonnet-latest");
+
+        // Anthropic takes temperatures up to 1.0 only
+        config.llm.anthropic.temperature = 1.5;
+        assert!(config.validate().unwrap_err().to_string().contains("between 0.0 and 1.0"));
+
+        // Config files written before the provider existed still load, with its defaults
+        let toml = toml::to_string(&Config::default()).unwrap();
+        let start = toml.find("[llm.anthropic]").unwrap();
+        let end = toml[start + 1..].find("\n[").map_or(toml.len(), |i| start + 1 + i + 1);
+        let older = format!("{}{}", &toml[..start], &toml[end..]);
+        assert!(!older.contains("anthropic"));
+        assert_eq!(toml::from_str::<Config>(&older).unwrap().llm.anthropic.max_tokens, 4096);
+    }
+
+    #[test]
+    fn test_ollama_provider_needs_no_api_key() {
+        let mut config = Config::default();
+        config.llm.provider = "ollama".to_string();
+        assert!(config.get_llm_api_key().is_none());
+        assert!(config.validate().is_ok());
+        assert_eq!(config.get_llm_model(), "llama3.1");
+        assert_eq!(config.resolve_key("llm.model"), "llm.ollama.model");
+        assert_eq!(config.resolve_key("ollama.base_url"), "llm.ollama.base_url");
+        assert_eq!(crate::ai_conversation::create_llm_client(&config, crate::usage::UsagePurpose::Chat).unwrap().get_model_name(), "llama3.1");
+
+        // As a fallback it's used even though it has no key
+        config.llm.provider = "gemini".to_string();
+        config.llm.gemini.api_key = Some("test-key".to_string());
+        config.llm.fallback = vec!["ollama".to_string()];
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_offline_mode_refuses_ai_clients() {
+        let mut config = Config::default();
+        config.llm.gemini.api_key = Some("test-key".to_string());
+        config.general.offline = true;
+
+        let error = crate::ai_conversation::create_llm_client(&config, crate::usage::UsagePurpose::Chat).err().unwrap();
+        assert!(error.downcast_ref::<OfflineError>().is_some());
+        assert!(error.to_string().starts_with("offline mode: the LLM is disabled"));
+        let error = crate::embedding_provider::create_embedding_providers(&config).err().unwrap();
+        assert_eq!(error.downcast_ref::<OfflineError>().unwrap().feature, "embedding");
+
+        config.general.offline = false;
+        assert!(crate::ai_conversation::create_llm_client(&config, crate::usage::UsagePurpose::Chat).is_ok());
+        // Config files written before offline mode existed still load
+        let toml = toml::to_string(&Config::default()).unwrap().replace("offline = false\n", "");
+        assert!(!toml::from_str::<Config>(&toml).unwrap().general.offline);
+    }
+
+    #[test]
+    fn test_chosen_vault_replaces_obsidian_settings() {
+        let file = format!("{}\n[[vaults]]\nname = \"Work\"\nbase_url = \"https://127.0.0.1:27125\"\napi_key = \"work-key\"\ncache_dir = \"/tmp/work-cache\"\n\n[[vaults]]\nname = \"home\"\nbase_url = \"https://127.0.0.1:27126\"\n", config_file());
+        let mut config: Config = toml::from_str(&file).unwrap();
+        assert_eq!(config.vaults.len(), 2);
+        let obsidian = config.obsidian.base_url.clone();
+
+        // Nothing chosen: [obsidian] as before
+        config.select_vault().unwrap();
+        assert_eq!(config.obsidian.base_url, obsidian);
+
+        config.general.vault = Some("work".to_string());
+        config.select_vault().unwrap();
+        assert_eq!(config.general.vault.as_deref(), Some("Work"));
+        assert_eq!(config.obsidian.base_url, "https://127.0.0.1:27125");
+        assert_eq!(config.obsidian.api_key.as_deref(), Some("work-key"));
+        assert_eq!(config.cache.vault_dir.as_deref(), Some("/tmp/work-cache"));
+
+        config.general.vault = Some("home".to_string());
+        config.select_vault().unwrap();
+        assert_eq!(config.obsidian.api_key, None);
+        assert_eq!(config.cache.vault_dir, None);
+
+        config.general.vault = Some("school".to_string());
+        let error = config.select_vault().unwrap_err().to_string();
+        assert!(error.contains("configured vaults: Work, home"), "{}", error);
+        assert!(set_key_in_toml(&file, &config, "general.vault", "school").is_err());
+        let updated = set_key_in_toml(&file, &config, "general.vault", "home").unwrap();
+        assert_eq!(toml::from_str::<Config>(&updated).unwrap().general.vault.as_deref(), Some("home"));
+    }
+
+    #[test]
+    fn test_profile_overrides_top_level_keys() {
+        let file = format!(
+            "{}\n[profiles.work.llm]\nprovider = \"openai\"\n[profiles.work.llm.openai]\nmodel = \"gpt-4o\"\n[profiles.work.cache]\ndir = \"/tmp/work-cache\"\n\n[profiles.personal.search]\nfolders = [\"Journal\"]\n",
+            config_file()
+        );
+        let top = Config::from_toml(Some(&file), None).unwrap();
+        assert_eq!(top.profile, None);
+        assert_eq!(top.search.folders, vec!["Notes", "Todos", "Goals"]);
+
+        let work = Config::from_toml(Some(&file), Some("Work")).unwrap();
+        assert_eq!(work.profile.as_deref(), Some("work"));
+        assert_eq!((work.llm.provider.as_str(), work.get_llm_model().as_str()), ("openai", "gpt-4o"));
+        // Tables are merged key by key: the rest of [llm.openai] and [search] is the file's
+        assert_eq!(work.llm.openai.temperature, top.llm.openai.temperature);
+        assert_eq!(work.search.folders, top.search.folders);
+        assert_eq!(work.cache.dir.as_deref(), Some("/tmp/work-cache"));
+        assert!(work.sessions.path.as_deref().is_some_and(|path| path.ends_with("profiles/work")));
+
+        let personal = Config::from_toml(Some(&file), Some("personal")).unwrap();
+        assert_eq!(personal.llm.provider, top.llm.provider);
+        assert_eq!(personal.search.folders, vec!["Journal"]);
+        assert!(personal.cache.dir.as_deref().is_some_and(|dir| dir.ends_with("profiles/personal")));
+        assert_ne!(personal.sessions.path, work.sessions.path);
+    }
+
+    #[test]
+    fn test_profile_errors_name_the_profile() {
+        let file = format!("{}\n[profiles.work.llm.gemini]\ntemperature = 3.5\n[profiles.typo.llm]\nprovider = 4\n", config_file());
+        let error = Config::from_toml(Some(&file), Some("work")).unwrap_err().to_string();
+        assert!(error.starts_with("[profiles.work] llm.gemini.temperature: Temperature must be between"), "{}", error);
+        let error = Config::from_toml(Some(&file), Some("typo")).unwrap_err().to_string();
+        assert!(error.starts_with("[profiles.typo] does not load"), "{}", error);
+        let error = Config::from_toml(Some(&file), Some("home")).unwrap_err().to_string();
+        assert!(error.contains("configured profiles: typo, work"), "{}", error);
+
+        let mut config = Config::from_toml(Some(&format!("{}\n[profiles.work.llm]\nprovider = \"openai\"\n", config_file())), Some("work")).unwrap();
+        config.llm.openai.api_key = None;
+        assert!(config.validate().unwrap_err().to_string().ends_with("(from [profiles.work])"));
+    }
+}
@@ -0,0 +1,269 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, IsTerminal, Write as _};
+
+/// Kinds of sensitive operation, each with its own confirmation policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperationClass {
+    /// Edits to many notes at once
+    BulkWrite,
+    Delete,
+    /// Moving or renaming notes
+    Move,
+    /// Sending vault data outside the vault (webhooks, email)
+    ExternalSend,
+    /// Runs that make more LLM requests than `llm_spend_threshold`
+    LlmSpend,
+}
+
+impl fmt::Display for OperationClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::BulkWrite => "bulk-write",
+            Self::Delete => "delete",
+            Self::Move => "move",
+            Self::ExternalSend => "external-send",
+            Self::LlmSpend => "llm-spend",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfirmPolicy {
+    Never,
+    /// Ask y/N
+    Prompt,
+    /// Require typing the item count (or "apply" for a single item); `--yes` is never enough
+    TypedPhrase,
+}
+
+/// `[confirmations]`: which operations ask before running, and how
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfirmationSettings {
+    pub bulk_write: ConfirmPolicy,
+    pub delete: ConfirmPolicy,
+    #[serde(rename = "move")]
+    pub move_notes: ConfirmPolicy,
+    pub external_send: ConfirmPolicy,
+    pub llm_spend: ConfirmPolicy,
+    /// Operation classes whose prompt `--yes` may answer
+    pub allow_yes: Vec<OperationClass>,
+    /// LLM requests a single run may make before it counts as llm-spend
+    pub llm_spend_threshold: usize,
+}
+
+impl Default for ConfirmationSettings {
+    fn default() -> Self {
+        Self {
+            bulk_write: ConfirmPolicy::Prompt,
+            delete: ConfirmPolicy::Prompt,
+            move_notes: ConfirmPolicy::Prompt,
+            external_send: ConfirmPolicy::Prompt,
+            llm_spend: ConfirmPolicy::Prompt,
+            allow_yes: vec![OperationClass::BulkWrite, OperationClass::Move, OperationClass::LlmSpend],
+            llm_spend_threshold: 25,
+        }
+    }
+}
+
+impl ConfirmationSettings {
+    pub fn policy(&self, class: OperationClass) -> ConfirmPolicy {
+        match class {
+            OperationClass::BulkWrite => self.bulk_write,
+            OperationClass::Delete => self.delete,
+            OperationClass::Move => self.move_notes,
+            OperationClass::ExternalSend => self.external_send,
+            OperationClass::LlmSpend => self.llm_spend,
+        }
+    }
+
+    pub fn allows_yes(&self, class: OperationClass) -> bool {
+        self.policy(class) != ConfirmPolicy::TypedPhrase && self.allow_yes.contains(&class)
+    }
+}
+
+/// An operation about to run
+#[derive(Debug, Clone)]
+pub struct Operation {
+    pub class: OperationClass,
+    /// What will happen, e.g. "Move 12 note(s)"
+    pub details: String,
+    /// Notes (or requests) affected; the typed phrase for more than one
+    pub count: usize,
+}
+
+impl Operation {
+    pub fn new(class: OperationClass, details: impl Into<String>, count: usize) -> Self {
+        Self { class, details: details.into(), count }
+    }
+
+    fn phrase(&self) -> String {
+        if self.count > 1 { self.count.to_string() } else { "apply".to_string() }
+    }
+}
+
+/// Where answers come from; injectable so the TTY/non-TTY behaviour can be tested
+pub trait PromptReader {
+    fn is_interactive(&self) -> bool;
+    /// Show `prompt` and return the line typed in answer
+    fn read_answer(&mut self, prompt: &str) -> Result<String>;
+}
+
+pub struct StdinPrompt;
+
+impl PromptReader for StdinPrompt {
+    fn is_interactive(&self) -> bool {
+        io::stdin().is_terminal()
+    }
+
+    fn read_answer(&mut self, prompt: &str) -> Result<String> {
+        print!("{} ", prompt);
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        Ok(answer)
+    }
+}
+
+/// Ask for `operation` according to its class's policy. `Ok(false)` means the user declined;
+/// an error means it couldn't be confirmed at all (no terminal to ask on).
+pub fn confirm_with(
+    settings: &ConfirmationSettings,
+    reader: &mut dyn PromptReader,
+    operation: &Operation,
+    assume_yes: bool,
+) -> Result<bool> {
+    let class = operation.class;
+    let policy = settings.policy(class);
+    if policy == ConfirmPolicy::Never {
+        return Ok(true);
+    }
+    if assume_yes && settings.allows_yes(class) {
+        return Ok(true);
+    }
+
+    if !reader.is_interactive() {
+        let hint = match policy {
+            ConfirmPolicy::TypedPhrase => format!("{} operations need a typed confirmation and --yes is not accepted", class),
+            _ if settings.allows_yes(class) => "pass --yes to confirm non-interactively".to_string(),
+            _ => format!("--yes is not allowed for {} operations; add \"{}\" to confirmations.allow_yes or set its policy to \"never\"", class, class),
+        };
+        bail!("{}: confirmation required but stdin is not a terminal ({})", operation.details, hint);
+    }
+    if assume_yes {
+        println!("--yes does not apply to {} operations.", class);
+    }
+
+    match policy {
+        ConfirmPolicy::Prompt => {
+            let answer = reader.read_answer(&format!("{}? [y/N]", operation.details))?;
+            Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+        }
+        ConfirmPolicy::TypedPhrase => {
+            let phrase = operation.phrase();
+            let answer = reader.read_answer(&format!("{}. Type {} to confirm:", operation.details, phrase))?;
+            Ok(answer.trim() == phrase)
+        }
+        ConfirmPolicy::Never => Ok(true),
+    }
+}
+
+/// Confirm on the terminal; used by every destructive command
+pub fn confirm(settings: &ConfirmationSettings, operation: &Operation, assume_yes: bool) -> Result<bool> {
+    confirm_with(settings, &mut StdinPrompt, operation, assume_yes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ScriptedPrompt;
+
+    fn settings(policy: ConfirmPolicy) -> ConfirmationSettings {
+        ConfirmationSettings {
+            bulk_write: policy,
+            delete: policy,
+            move_notes: policy,
+            external_send: policy,
+            llm_spend: policy,
+            ..Default::default()
+        }
+    }
+
+    const CLASSES: [OperationClass; 5] = [
+        OperationClass::BulkWrite,
+        OperationClass::Delete,
+        OperationClass::Move,
+        OperationClass::ExternalSend,
+        OperationClass::LlmSpend,
+    ];
+
+    #[test]
+    fn test_never_runs_without_asking_even_without_a_tty() {
+        for class in CLASSES {
+            let mut reader = ScriptedPrompt::piped();
+            assert!(confirm_with(&settings(ConfirmPolicy::Never), &mut reader, &Operation::new(class, "Do it", 3), false).unwrap());
+            assert!(reader.prompts.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_prompt_policy_matrix() {
+        let settings = settings(ConfirmPolicy::Prompt);
+        for class in CLASSES {
+            let op = Operation::new(class, "Move 3 note(s)", 3);
+            let allowed = settings.allows_yes(class);
+            assert_eq!(allowed, matches!(class, OperationClass::BulkWrite | OperationClass::Move | OperationClass::LlmSpend));
+
+            // TTY: the answer decides
+            assert!(confirm_with(&settings, &mut ScriptedPrompt::tty(&["y"]), &op, false).unwrap());
+            assert!(!confirm_with(&settings, &mut ScriptedPrompt::tty(&["n"]), &op, false).unwrap());
+            assert!(!confirm_with(&settings, &mut ScriptedPrompt::tty(&[""]), &op, false).unwrap());
+
+            // --yes skips the prompt only where allowed; otherwise the user is still asked
+            let mut reader = ScriptedPrompt::tty(&["n"]);
+            assert_eq!(confirm_with(&settings, &mut reader, &op, true).unwrap(), allowed);
+            assert_eq!(reader.prompts.is_empty(), allowed);
+
+            // No TTY: fail clearly instead of waiting for input, unless --yes is allowed and given
+            let error = confirm_with(&settings, &mut ScriptedPrompt::piped(), &op, false).unwrap_err().to_string();
+            assert!(error.contains("stdin is not a terminal"));
+            assert_eq!(confirm_with(&settings, &mut ScriptedPrompt::piped(), &op, true).is_ok(), allowed);
+        }
+    }
+
+    #[test]
+    fn test_typed_phrase_refuses_yes() {
+        let settings = settings(ConfirmPolicy::TypedPhrase);
+        for class in CLASSES {
+            assert!(!settings.allows_yes(class));
+            let many = Operation::new(class, "Delete 12 note(s)", 12);
+            let one = Operation::new(class, "Delete Notes/a.md", 1);
+
+            let mut reader = ScriptedPrompt::tty(&["12"]);
+            assert!(confirm_with(&settings, &mut reader, &many, true).unwrap());
+            assert_eq!(reader.prompts, vec!["Delete 12 note(s). Type 12 to confirm:"]);
+            assert!(!confirm_with(&settings, &mut ScriptedPrompt::tty(&["y"]), &many, false).unwrap());
+            assert!(confirm_with(&settings, &mut ScriptedPrompt::tty(&["apply"]), &one, false).unwrap());
+            assert!(!confirm_with(&settings, &mut ScriptedPrompt::tty(&["12"]), &one, false).unwrap());
+
+            let error = confirm_with(&settings, &mut ScriptedPrompt::piped(), &many, true).unwrap_err().to_string();
+            assert!(error.contains("--yes is not accepted"));
+        }
+    }
+
+    #[test]
+    fn test_config_uses_kebab_case_classes() {
+        let settings: ConfirmationSettings = toml::from_str(
+            "delete = \"typed-phrase\"\nmove = \"never\"\nallow_yes = [\"bulk-write\", \"external-send\"]\n",
+        ).unwrap();
+        assert_eq!(settings.policy(OperationClass::Delete), ConfirmPolicy::TypedPhrase);
+        assert_eq!(settings.policy(OperationClass::Move), ConfirmPolicy::Never);
+        assert_eq!(settings.policy(OperationClass::BulkWrite), ConfirmPolicy::Prompt);
+        assert!(settings.allows_yes(OperationClass::ExternalSend));
+        assert!(!settings.allows_yes(OperationClass::Move));
+    }
+}
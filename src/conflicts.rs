@@ -0,0 +1,634 @@
+use anyhow::{bail, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value as Yaml};
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::ai_conversation::{create_llm_client, LLMClient, Message, MessageRole};
+use crate::changelog::summarize_change;
+use crate::cli::{ConflictsAction, ConflictsArgs};
+use crate::config::{Config, ConflictSettings};
+use crate::confirm::{confirm, Operation, OperationClass, PromptReader, StdinPrompt};
+use crate::embedding_store::{read_store, write_store, EmbeddingStorageConfig, HotVectorCache};
+use crate::health::load_notes_in;
+use crate::cache::CachePaths;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::usage::UsagePurpose;
+use crate::vault_transaction::{VaultTransaction};
+
+/// Field name used when a frontmatter block isn't valid YAML and is compared as a whole
+const RAW_FRONTMATTER: &str = "(frontmatter)";
+/// Heading shown for text before the first heading
+const TOP_OF_NOTE: &str = "(top of note)";
+
+/// A sync-conflict copy and the note it diverged from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConflictPair {
+    pub original: String,
+    pub conflict: String,
+}
+
+/// Path of the note `path` is a conflict copy of, if its name matches one of `patterns`.
+/// The name is cut where the pattern starts, along with an opening parenthesis left
+/// before it: `a.sync-conflict-20240501-101010-X7.md` and `a (Sam's conflicted copy).md`
+/// both belong to `a.md`.
+pub fn conflict_original(path: &str, patterns: &[String]) -> Option<String> {
+    let (dir, file) = match path.rfind('/') {
+        Some(i) => path.split_at(i + 1),
+        None => ("", path),
+    };
+    let (stem, extension) = match file.rfind('.') {
+        Some(i) if i > 0 => file.split_at(i),
+        _ => (file, ""),
+    };
+    let start = patterns.iter()
+        .filter(|p| !p.is_empty())
+        .filter_map(|p| stem.find(p.as_str()))
+        .min()?;
+    let mut kept = &stem[..start];
+    if let Some(open) = kept.rfind('(') {
+        if kept.rfind(')').is_none_or(|close| close < open) {
+            kept = &kept[..open];
+        }
+    }
+    let kept = kept.trim_end();
+    (!kept.is_empty()).then(|| format!("{}{}{}", dir, kept, extension))
+}
+
+/// Pair every conflict copy in `paths` with its original. Copies whose original is gone
+/// are returned separately.
+pub fn pair_conflicts(paths: &[String], patterns: &[String]) -> (Vec<ConflictPair>, Vec<String>) {
+    let mut pairs = Vec::new();
+    let mut unpaired = Vec::new();
+    for path in paths {
+        let Some(original) = conflict_original(path, patterns) else { continue };
+        if paths.contains(&original) && conflict_original(&original, patterns).is_none() {
+            pairs.push(ConflictPair { original, conflict: path.clone() });
+        } else {
+            unpaired.push(path.clone());
+        }
+    }
+    pairs.sort_by(|a, b| a.conflict.cmp(&b.conflict));
+    unpaired.sort();
+    (pairs, unpaired)
+}
+
+/// A frontmatter field whose value differs; `None` when the field is missing on that side
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub key: String,
+    pub original: Option<String>,
+    pub conflict: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SectionChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A heading-level section whose text differs, with its full text on each side
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SectionChange {
+    pub heading: String,
+    pub kind: SectionChangeKind,
+    pub original: Option<String>,
+    pub conflict: Option<String>,
+}
+
+/// Structured difference between a note and its conflict copy
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NoteDiff {
+    pub frontmatter: Vec<FieldChange>,
+    pub sections: Vec<SectionChange>,
+}
+
+impl NoteDiff {
+    pub fn is_empty(&self) -> bool {
+        self.frontmatter.is_empty() && self.sections.is_empty()
+    }
+
+    /// Number of changes a side is picked for when merging
+    pub fn len(&self) -> usize {
+        self.frontmatter.len() + self.sections.len()
+    }
+
+    /// One line per change, e.g. `frontmatter status: draft → done`
+    pub fn describe(&self) -> Vec<String> {
+        let shown = |value: &Option<String>| value.clone().unwrap_or_else(|| "(missing)".to_string());
+        let fields = self.frontmatter.iter()
+            .map(|f| format!("frontmatter {}: {} → {}", f.key, shown(&f.original), shown(&f.conflict)));
+        let sections = self.sections.iter().map(|s| {
+            let kind = match s.kind {
+                SectionChangeKind::Added => "only in the conflict copy",
+                SectionChangeKind::Removed => "only in the original",
+                SectionChangeKind::Changed => "changed",
+            };
+            format!("section \"{}\": {}", s.heading, kind)
+        });
+        fields.chain(sections).collect()
+    }
+}
+
+/// Which version a change is taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Original,
+    Conflict,
+}
+
+impl Side {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "original" | "o" => Some(Self::Original),
+            "conflict" | "c" => Some(Self::Conflict),
+            _ => None,
+        }
+    }
+}
+
+/// The YAML block between the `---` fences, and the body after it
+pub(crate) fn split_frontmatter(content: &str) -> (Option<&str>, &str) {
+    if let Some(rest) = content.strip_prefix("---\n") {
+        if let Some(end) = rest.find("\n---") {
+            let after = &rest[end + 4..];
+            let body = after.find('\n').map_or("", |i| &after[i + 1..]);
+            return (Some(&rest[..end]), body);
+        }
+    }
+    (None, content)
+}
+
+fn parse_fields(block: Option<&str>) -> Option<Mapping> {
+    match block {
+        Some(block) if !block.trim().is_empty() => serde_yaml::from_str(block).ok(),
+        _ => Some(Mapping::new()),
+    }
+}
+
+fn yaml_text(value: &Yaml) -> String {
+    match value {
+        Yaml::String(s) => s.clone(),
+        other => serde_yaml::to_string(other).unwrap_or_default().trim_end().to_string(),
+    }
+}
+
+fn diff_frontmatter(original: Option<&str>, conflict: Option<&str>) -> Vec<FieldChange> {
+    let (Some(old), Some(new)) = (parse_fields(original), parse_fields(conflict)) else {
+        let (old, new) = (original.map(str::to_string), conflict.map(str::to_string));
+        return if old == new { Vec::new() } else { vec![FieldChange { key: RAW_FRONTMATTER.to_string(), original: old, conflict: new }] };
+    };
+    let keys = old.keys().chain(new.keys().filter(|k| !old.contains_key(*k)));
+    keys.filter_map(|key| {
+        let (a, b) = (old.get(key), new.get(key));
+        (a != b).then(|| FieldChange { key: yaml_text(key), original: a.map(yaml_text), conflict: b.map(yaml_text) })
+    })
+    .collect()
+}
+
+/// Sections keyed by heading text and occurrence, each with its text including the heading
+/// line. Text before the first heading is the section `""`.
+fn sections(body: &str) -> Vec<((String, usize), &str)> {
+    let mut starts = vec![(String::new(), 0)];
+    let mut in_fence = false;
+    let mut offset = 0;
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence && trimmed.starts_with('#') {
+            let text = trimmed.trim_start_matches('#');
+            if text.starts_with(' ') && trimmed.len() - text.len() <= 6 {
+                starts.push((text.trim().to_string(), offset));
+            }
+        }
+        offset += line.len();
+    }
+    let mut seen: Vec<String> = Vec::new();
+    let mut result = Vec::new();
+    for (i, (heading, start)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(body.len(), |(_, next)| *next);
+        if heading.is_empty() && start == &end {
+            continue;
+        }
+        let occurrence = seen.iter().filter(|h| *h == heading).count();
+        seen.push(heading.clone());
+        result.push(((heading.clone(), occurrence), &body[*start..end]));
+    }
+    result
+}
+
+type SectionKey = (String, usize);
+
+/// Sections of both bodies in merged order: the original's order, with sections only in
+/// the conflict copy placed after the section they follow there
+fn section_pairs<'a>(original: &'a str, conflict: &'a str) -> Vec<(SectionKey, Option<&'a str>, Option<&'a str>)> {
+    let old = sections(original);
+    let new = sections(conflict);
+    let mut order: Vec<SectionKey> = old.iter().map(|(key, _)| key.clone()).collect();
+    let mut after: Option<usize> = None;
+    for (key, _) in &new {
+        match order.iter().position(|k| k == key) {
+            Some(position) => after = Some(position),
+            None => {
+                let at = after.map_or(0, |p| p + 1);
+                order.insert(at, key.clone());
+                after = Some(at);
+            }
+        }
+    }
+    let find = |list: &[(SectionKey, &'a str)], key: &SectionKey| list.iter().find(|(k, _)| k == key).map(|(_, text)| *text);
+    order.into_iter()
+        .map(|key| {
+            let (a, b) = (find(&old, &key), find(&new, &key));
+            (key, a, b)
+        })
+        .collect()
+}
+
+fn same_text(a: &str, b: &str) -> bool {
+    a.trim_end() == b.trim_end()
+}
+
+/// Frontmatter fields and heading-level sections that differ between the two versions
+pub fn diff_notes(original: &str, conflict: &str) -> NoteDiff {
+    let (old_fields, old_body) = split_frontmatter(original);
+    let (new_fields, new_body) = split_frontmatter(conflict);
+    let sections = section_pairs(old_body, new_body).into_iter()
+        .filter_map(|((heading, _), a, b)| {
+            let kind = match (a, b) {
+                (Some(a), Some(b)) if same_text(a, b) => return None,
+                (Some(_), Some(_)) => SectionChangeKind::Changed,
+                (Some(a), None) if a.trim().is_empty() => return None,
+                (Some(_), None) => SectionChangeKind::Removed,
+                (None, Some(b)) if b.trim().is_empty() => return None,
+                (None, Some(_)) => SectionChangeKind::Added,
+                (None, None) => return None,
+            };
+            let heading = if heading.is_empty() { TOP_OF_NOTE.to_string() } else { heading };
+            Some(SectionChange { heading, kind, original: a.map(str::to_string), conflict: b.map(str::to_string) })
+        })
+        .collect();
+    NoteDiff { frontmatter: diff_frontmatter(old_fields, new_fields), sections }
+}
+
+/// Merge the two versions taking each change from the side picked for it; `choices` follows
+/// the order of the diff (frontmatter fields, then sections). Changes without a choice keep
+/// the original.
+pub fn merge_notes(original: &str, conflict: &str, choices: &[Side]) -> String {
+    let diff = diff_notes(original, conflict);
+    let pick = |index: usize| choices.get(index).copied().unwrap_or(Side::Original);
+    let (old_fields, old_body) = split_frontmatter(original);
+    let (new_fields, new_body) = split_frontmatter(conflict);
+
+    let field_sides: Vec<Side> = (0..diff.frontmatter.len()).map(pick).collect();
+    let block = if field_sides.iter().all(|s| *s == Side::Original) {
+        old_fields.map(str::to_string)
+    } else if field_sides.iter().all(|s| *s == Side::Conflict) {
+        new_fields.map(str::to_string)
+    } else {
+        // Mixed picks only happen when both sides parsed, so field-by-field is possible
+        let mut fields = parse_fields(old_fields).unwrap_or_default();
+        let theirs = parse_fields(new_fields).unwrap_or_default();
+        for (change, side) in diff.frontmatter.iter().zip(&field_sides) {
+            if *side == Side::Original {
+                continue;
+            }
+            let key = fields.keys().chain(theirs.keys()).find(|k| yaml_text(k) == change.key).cloned();
+            let Some(key) = key else { continue };
+            match theirs.get(&key) {
+                Some(value) => { fields.insert(key, value.clone()); }
+                None => { fields.remove(&key); }
+            }
+        }
+        (!fields.is_empty()).then(|| serde_yaml::to_string(&fields).unwrap_or_default().trim_end().to_string())
+    };
+
+    let mut changed = diff.frontmatter.len();
+    let mut body = String::new();
+    for (_, a, b) in section_pairs(old_body, new_body) {
+        let text = match (a, b) {
+            (Some(a), Some(b)) if same_text(a, b) => Some(a),
+            (Some(a), None) if a.trim().is_empty() => Some(a),
+            (None, Some(b)) if b.trim().is_empty() => None,
+            _ => {
+                let side = pick(changed);
+                changed += 1;
+                if side == Side::Original { a } else { b }
+            }
+        };
+        if let Some(text) = text {
+            if !body.is_empty() && !body.ends_with('\n') {
+                body.push('\n');
+            }
+            body.push_str(text);
+        }
+    }
+
+    match block {
+        Some(block) => format!("---\n{}\n---\n{}", block, body),
+        None => body,
+    }
+}
+
+/// Where a resolved conflict copy is moved
+pub fn trash_path(settings: &ConflictSettings, conflict: &str) -> String {
+    format!("{}/{}", settings.trash_folder.trim_end_matches('/'), conflict)
+}
+
+/// Drop the embeddings of `paths` so the next indexing run embeds the surviving note from
+/// its resolved content; returns how many were dropped
+fn forget_embeddings(store_path: &Path, paths: &[&str], storage: &EmbeddingStorageConfig) -> Result<usize> {
+    if !store_path.exists() {
+        return Ok(0);
+    }
+    let store = read_store(store_path)?;
+    let mut database = store.database;
+    let before = database.len();
+    database.retain(|doc| !paths.contains(&doc.path.as_str()));
+    let dropped = before - database.len();
+    if dropped == 0 {
+        return Ok(0);
+    }
+    database.last_updated = Utc::now();
+    let mut hot = HotVectorCache::new(storage.hot_cache_size);
+    for (path, vector) in store.hot_vectors.into_iter().filter(|(path, _)| !paths.contains(&path.as_str())) {
+        hot.insert(&path, vector);
+    }
+    let mut ann = store.ann_index.unwrap_or_default();
+    for path in paths {
+        ann.remove(path);
+    }
+    write_store(store_path, &database, &hot, &ann, storage.quantization)?;
+    Ok(dropped)
+}
+
+async fn merge_with_llm(llm: &dyn LLMClient, pair: &ConflictPair, original: &str, conflict: &str) -> Result<String> {
+    let prompt = format!(
+        "Two sync clients edited the same Obsidian note and produced a conflict. Merge them into one note that keeps \
+         every change that doesn't contradict the other version; where they contradict, prefer the more recent-looking \
+         or more complete text. Keep the frontmatter block and the heading structure.\n\
+         Reply with the merged markdown only.\n\n=== {} ===\n{}\n=== {} ===\n{}",
+        pair.original, original, pair.conflict, conflict
+    );
+    let response = llm.send_message(vec![Message {
+        id: Uuid::new_v4().to_string(),
+        role: MessageRole::User,
+        content: prompt,
+        timestamp: Utc::now(),
+        function_call: None,
+    }]).await?;
+    let merged = response.content.trim();
+    let merged = merged.strip_prefix("```markdown").or_else(|| merged.strip_prefix("```")).unwrap_or(merged);
+    let merged = merged.strip_suffix("```").unwrap_or(merged).trim();
+    if merged.is_empty() {
+        bail!("the LLM returned an empty merge");
+    }
+    Ok(format!("{}\n", merged))
+}
+
+fn print_diff(pair: &ConflictPair, diff: &NoteDiff) {
+    println!("\n{}\n  conflict copy: {}", pair.original, pair.conflict);
+    if diff.is_empty() {
+        println!("  identical content");
+    }
+    for line in diff.describe() {
+        println!("  - {}", line);
+    }
+}
+
+/// Pick a side for every change, showing both versions of each
+fn merge_interactively(reader: &mut dyn PromptReader, diff: &NoteDiff) -> Result<Vec<Side>> {
+    let show = |label: &str, text: &Option<String>| match text {
+        Some(text) => println!("  [{}]\n{}", label, text.trim_end().lines().map(|l| format!("    {}", l)).collect::<Vec<_>>().join("\n")),
+        None => println!("  [{}] (missing)", label),
+    };
+    let mut choices = Vec::new();
+    let fields = diff.frontmatter.iter().map(|f| (format!("frontmatter {}", f.key), &f.original, &f.conflict));
+    let sections = diff.sections.iter().map(|s| (format!("section \"{}\"", s.heading), &s.original, &s.conflict));
+    for (index, (label, original, conflict)) in fields.chain(sections).enumerate() {
+        println!("\n({}/{}) {}", index + 1, diff.len(), label);
+        show("original", original);
+        show("conflict", conflict);
+        let answer = reader.read_answer("Keep [o]riginal or [c]onflict? [o]")?;
+        choices.push(Side::parse(&answer).unwrap_or(Side::Original));
+    }
+    Ok(choices)
+}
+
+pub async fn handle_conflicts_command(args: ConflictsArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let ConflictsAction::Scan { json, keep, yes } = args.action;
+    let keep = match keep.as_deref() {
+        Some(text) => Some(Side::parse(text).ok_or_else(|| anyhow::anyhow!("--keep takes `original` or `conflict`, not `{}`", text))?),
+        None => None,
+    };
+    let config = Config::load().unwrap_or_default();
+    let settings = &config.conflicts;
+    let trash = format!("{}/", settings.trash_folder.trim_end_matches('/'));
+    let notes: Vec<(String, String)> = load_notes_in(adapter, &settings.folders).await
+        .into_iter()
+        .filter(|(path, _)| !path.starts_with(&trash))
+        .collect();
+    let paths: Vec<String> = notes.iter().map(|(path, _)| path.clone()).collect();
+    let (pairs, unpaired) = pair_conflicts(&paths, &settings.patterns);
+    let content = |path: &str| notes.iter().find(|(p, _)| p == path).map(|(_, c)| c.as_str()).unwrap_or_default();
+    let diffs: Vec<NoteDiff> = pairs.iter().map(|pair| diff_notes(content(&pair.original), content(&pair.conflict))).collect();
+
+    let report = |resolved: &[serde_json::Value]| serde_json::json!({
+        "conflicts": pairs.iter().zip(&diffs).map(|(pair, diff)| serde_json::json!({
+            "original": pair.original,
+            "conflict": pair.conflict,
+            "diff": diff,
+        })).collect::<Vec<_>>(),
+        "unpaired": unpaired,
+        "resolved": resolved,
+    });
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report(&[]))?);
+        return Ok(());
+    }
+    if pairs.is_empty() && unpaired.is_empty() {
+        println!("No sync conflicts in {}.", settings.folders.join(", "));
+        crate::script::publish_output(report(&[]));
+        return Ok(());
+    }
+    println!("{} sync conflict(s)", pairs.len());
+    for path in &unpaired {
+        println!("⚠️  {}: conflict copy without its original, left alone", path);
+    }
+
+    let mut prompt = StdinPrompt;
+    if keep.is_none() && !prompt.is_interactive() {
+        for (pair, diff) in pairs.iter().zip(&diffs) {
+            print_diff(pair, diff);
+        }
+        println!("\nRun `arrowhead conflicts scan` in a terminal to resolve them, or pass --keep original|conflict.");
+        crate::script::publish_output(report(&[]));
+        return Ok(());
+    }
+    if let Some(side) = keep {
+        let operation = Operation::new(OperationClass::Move, format!("Resolve {} conflict(s) keeping the {} and move the copies to {}", pairs.len(), if side == Side::Original { "original" } else { "conflict copy" }, settings.trash_folder), pairs.len());
+        if !pairs.is_empty() && !confirm(&config.confirmations, &operation, yes)? {
+            println!("Nothing changed.");
+            return Ok(());
+        }
+    }
+
+    let mut llm: Option<Box<dyn LLMClient>> = None;
+    let mut resolved = Vec::new();
+    for (pair, diff) in pairs.iter().zip(&diffs) {
+        let (original, conflict) = (content(&pair.original), content(&pair.conflict));
+        print_diff(pair, diff);
+        let (resolution, merged) = match keep {
+            Some(Side::Original) => ("keep original", original.to_string()),
+            Some(Side::Conflict) => ("keep conflict", conflict.to_string()),
+            None => {
+                let answer = prompt.read_answer("[o] keep original, [c] keep conflict, [m] merge by section, [l] LLM merge, [s] skip")?;
+                match answer.trim().to_lowercase().as_str() {
+                    "o" => ("keep original", original.to_string()),
+                    "c" => ("keep conflict", conflict.to_string()),
+                    "m" => ("section merge", merge_notes(original, conflict, &merge_interactively(&mut prompt, diff)?)),
+                    "l" => {
+                        if llm.is_none() {
+                            llm = Some(create_llm_client(&config, UsagePurpose::Analysis)?);
+                        }
+                        let client = llm.as_deref().expect("created above");
+                        let merged = match merge_with_llm(client, pair, original, conflict).await {
+                            Ok(merged) => merged,
+                            Err(e) => {
+                                println!("⚠️  LLM merge failed ({}), skipped", e);
+                                continue;
+                            }
+                        };
+                        println!("\n--- merged preview ({}) ---\n{}--- end of preview ---", summarize_change(original, &merged).describe(), merged);
+                        let answer = prompt.read_answer("Apply this merge? [y/N]")?;
+                        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                            println!("Skipped.");
+                            continue;
+                        }
+                        ("llm merge", merged)
+                    }
+                    _ => {
+                        println!("Skipped.");
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let mut transaction = VaultTransaction::new("conflict resolution").with_context(serde_json::json!({
+            "original": pair.original,
+            "conflict": pair.conflict,
+            "resolution": resolution,
+        }));
+        if merged != original {
+            transaction = transaction.write(&pair.original, &merged);
+        }
+        let transaction = transaction
+            .write(&trash_path(settings, &pair.conflict), conflict)
+            .delete(&pair.conflict);
+        let result = transaction.execute(adapter).await?;
+        if let Err(e) = result.append_to_log(adapter.transaction_log()) {
+            eprintln!("Warning: could not write transaction log: {}", e);
+        }
+        println!("{}", result.summary());
+        if !result.is_committed() {
+            bail!("Conflict for {} was not resolved", pair.original);
+        }
+
+        let mut stale = vec![pair.conflict.as_str()];
+        if merged != original {
+            stale.push(pair.original.as_str());
+        }
+        if let Err(e) = forget_embeddings(&CachePaths::for_config(&config).embeddings(), &stale, &config.embeddings) {
+            eprintln!("Warning: could not refresh embeddings: {}", e);
+        }
+        resolved.push(serde_json::json!({ "original": pair.original, "conflict": pair.conflict, "resolution": resolution }));
+    }
+    println!("\nResolved {} of {} conflict(s).", resolved.len(), pairs.len());
+    crate::script::publish_output(report(&resolved));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns() -> Vec<String> {
+        ConflictSettings::default().patterns
+    }
+
+    fn paths(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    const ORIGINAL: &str = "---\nstatus: draft\ntags: [launch]\n---\nIntro line.\n\n## Plan\nShip in June.\n\n## Risks\nNone yet.\n";
+
+    #[test]
+    fn test_conflict_copies_pair_with_their_originals() {
+        let vault = paths(&[
+            "Notes/launch.md",
+            "Notes/launch.sync-conflict-20240501-101010-X7KQ2.md",
+            "Notes/retro (Sam's conflicted copy 2024-05-01).md",
+            "Notes/retro.md",
+            "Notes/gone.sync-conflict-20240502-090000-AB12.md",
+            "Notes/sync notes.md",
+        ]);
+        let (pairs, unpaired) = pair_conflicts(&vault, &patterns());
+        assert_eq!(pairs, vec![
+            ConflictPair { original: "Notes/launch.md".to_string(), conflict: "Notes/launch.sync-conflict-20240501-101010-X7KQ2.md".to_string() },
+            ConflictPair { original: "Notes/retro.md".to_string(), conflict: "Notes/retro (Sam's conflicted copy 2024-05-01).md".to_string() },
+        ]);
+        assert_eq!(unpaired, paths(&["Notes/gone.sync-conflict-20240502-090000-AB12.md"]));
+        assert_eq!(conflict_original("Plan (Conflict 1).md", &patterns()), Some("Plan.md".to_string()));
+    }
+
+    #[test]
+    fn test_frontmatter_only_divergence() {
+        let conflict = ORIGINAL.replace("status: draft", "status: done\nowner: sam");
+        let diff = diff_notes(ORIGINAL, &conflict);
+        assert!(diff.sections.is_empty());
+        assert_eq!(diff.frontmatter, vec![
+            FieldChange { key: "status".to_string(), original: Some("draft".to_string()), conflict: Some("done".to_string()) },
+            FieldChange { key: "owner".to_string(), original: None, conflict: Some("sam".to_string()) },
+        ]);
+
+        // Taking only the status keeps the rest of the original, body untouched
+        let merged = merge_notes(ORIGINAL, &conflict, &[Side::Conflict, Side::Original]);
+        assert!(merged.starts_with("---\nstatus: done\ntags:\n- launch\n---\nIntro line.\n"));
+        assert!(!merged.contains("owner"));
+        assert_eq!(merge_notes(ORIGINAL, &conflict, &[Side::Conflict, Side::Conflict]), conflict);
+        assert_eq!(merge_notes(ORIGINAL, &conflict, &[]), ORIGINAL);
+    }
+
+    #[test]
+    fn test_body_only_divergence() {
+        let conflict = ORIGINAL
+            .replace("Ship in June.", "Ship in July.")
+            .replace("## Risks\nNone yet.\n", "## Open questions\nWho owns QA?\n\n## Risks\nNone yet.\n");
+        let diff = diff_notes(ORIGINAL, &conflict);
+        assert!(diff.frontmatter.is_empty());
+        let kinds: Vec<(&str, SectionChangeKind)> = diff.sections.iter().map(|s| (s.heading.as_str(), s.kind)).collect();
+        assert_eq!(kinds, vec![("Plan", SectionChangeKind::Changed), ("Open questions", SectionChangeKind::Added)]);
+        assert_eq!(diff.describe(), vec!["section \"Plan\": changed", "section \"Open questions\": only in the conflict copy"]);
+
+        // Original plan, plus the new section in the place it was added
+        let merged = merge_notes(ORIGINAL, &conflict, &[Side::Original, Side::Conflict]);
+        assert_eq!(merged, ORIGINAL.replace("## Risks\nNone yet.\n", "## Open questions\nWho owns QA?\n\n## Risks\nNone yet.\n"));
+        assert!(diff_notes(ORIGINAL, ORIGINAL).is_empty());
+    }
+
+    #[test]
+    fn test_unparseable_frontmatter_is_compared_whole() {
+        let original = "---\ntitle: [broken\n---\nBody\n";
+        let conflict = "---\ntitle: fixed\n---\nBody\n";
+        let diff = diff_notes(original, conflict);
+        assert_eq!(diff.frontmatter.len(), 1);
+        assert_eq!(diff.frontmatter[0].key, RAW_FRONTMATTER);
+        assert_eq!(merge_notes(original, conflict, &[Side::Conflict]), conflict);
+    }
+}
@@ -2073,11 +2073,23 @@ impl ToolContext {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
     use tokio::time::{sleep, Duration};
+
+    /// A config whose data goes to a fresh temporary directory instead of `./data/context`;
+    /// the directory is removed when the returned guard drops
+    fn test_config() -> (TempDir, ContextManagerConfig) {
+        let dir = TempDir::new().unwrap();
+        let config = ContextManagerConfig {
+            data_dir: dir.path().to_path_buf(),
+            ..ContextManagerConfig::default()
+        };
+        (dir, config)
+    }
     
     #[tokio::test]
     async fn test_context_manager_creation() {
-        let config = ContextManagerConfig::default();
+        let (_dir, config) = test_config();
         let manager = ContextManager::new(config).unwrap();
         
         let health = manager.health_check().await.unwrap();
@@ -2088,7 +2100,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_session_management() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create a session
         let session_id = manager.create_session("test_user").await.unwrap();
@@ -2105,7 +2118,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_cache_functionality() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create a session (should be cached)
         let session_id = manager.create_session("test_user").await.unwrap();
@@ -2126,7 +2140,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_performance_tracking() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create a session to generate performance metrics
         let _session_id = manager.create_session("test_user").await.unwrap();
@@ -2142,7 +2157,7 @@ mod tests {
     
     #[tokio::test]
     async fn test_invalid_configuration() {
-        let mut config = ContextManagerConfig::default();
+        let (_dir, mut config) = test_config();
         config.max_sessions = 0;
         
         let result = ContextManager::new(config);
@@ -2157,7 +2172,7 @@ mod tests {
     
     #[tokio::test]
     async fn test_session_cleanup() {
-        let mut config = ContextManagerConfig::default();
+        let (_dir, mut config) = test_config();
         config.session_timeout = Duration::from_millis(100); // Very short timeout for testing
         
         let manager = ContextManager::new(config).unwrap();
@@ -2175,7 +2190,7 @@ mod tests {
     
     #[tokio::test]
     async fn test_cache_eviction() {
-        let mut config = ContextManagerConfig::default();
+        let (_dir, mut config) = test_config();
         config.cache_size = 2; // Very small cache for testing
         
         let manager = ContextManager::new(config).unwrap();
@@ -2193,7 +2208,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_conversation_thread_creation() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create a conversation thread
         let thread_id = manager.create_conversation_thread(
@@ -2217,7 +2233,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_message_management() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create a conversation thread
         let thread_id = manager.create_conversation_thread(
@@ -2253,7 +2270,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_user_threads_retrieval() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create multiple threads for a user
         let thread1 = manager.create_conversation_thread(
@@ -2280,7 +2298,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_thread_search() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create a thread with messages
         let thread_id = manager.create_conversation_thread(
@@ -2316,8 +2335,7 @@ mod tests {
     
     #[tokio::test]
     async fn test_session_store_persistence() {
-        let mut config = ContextManagerConfig::default();
-        config.data_dir = PathBuf::from("/tmp/test_context_manager");
+        let (dir, config) = test_config();
         
         let manager = ContextManager::new(config).unwrap();
         
@@ -2325,18 +2343,14 @@ mod tests {
         let session_id = manager.create_session("test_user").await.unwrap();
         
         // Verify session file exists
-        let session_file = PathBuf::from("/tmp/test_context_manager/sessions").join(format!("{}.json", session_id));
+        let session_file = dir.path().join("sessions").join(format!("{}.json", session_id));
         assert!(session_file.exists());
-        
-        // Cleanup
-        if let Err(e) = std::fs::remove_dir_all("/tmp/test_context_manager") {
-            eprintln!("Warning: Failed to cleanup test directory: {}", e);
-        }
     }
     
     #[tokio::test]
     async fn test_user_preferences_update() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create initial preferences
         let mut preferences = HashMap::new();
@@ -2358,7 +2372,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_user_preferences_learning() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Simulate message interaction
         let mut context = HashMap::new();
@@ -2384,7 +2399,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_user_preferences_tool_usage_learning() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Simulate tool usage interactions
         let mut context1 = HashMap::new();
@@ -2414,7 +2430,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_user_preferences_recommendations() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Simulate multiple message interactions to build confidence (need more interactions)
         for i in 0..12 {
@@ -2452,7 +2469,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_user_preferences_workflow_learning() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Simulate workflow interactions
         let mut context1 = HashMap::new();
@@ -2482,8 +2500,7 @@ mod tests {
     
     #[tokio::test]
     async fn test_user_preferences_persistence() {
-        let mut config = ContextManagerConfig::default();
-        config.data_dir = PathBuf::from("/tmp/test_user_prefs");
+        let (dir, config) = test_config();
         
         let manager = ContextManager::new(config).unwrap();
         
@@ -2495,23 +2512,19 @@ mod tests {
         manager.update_user_preferences("test_user", preferences).await.unwrap();
         
         // Verify preferences file exists
-        let prefs_file = PathBuf::from("/tmp/test_user_prefs/preferences").join("test_user.json");
+        let prefs_file = dir.path().join("preferences").join("test_user.json");
         assert!(prefs_file.exists());
         
         // Verify file content
         let content = std::fs::read_to_string(&prefs_file).unwrap();
         assert!(content.contains("test_pref"));
         assert!(content.contains("test_value"));
-        
-        // Cleanup
-        if let Err(e) = std::fs::remove_dir_all("/tmp/test_user_prefs") {
-            eprintln!("Warning: Failed to cleanup test directory: {}", e);
-        }
     }
     
     #[tokio::test]
     async fn test_user_preferences_metric_confidence_building() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Simulate multiple interactions to build confidence
         for i in 0..15 {
@@ -2538,7 +2551,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_tool_context_creation_and_retrieval() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create initial state
         let mut initial_state = HashMap::new();
@@ -2563,7 +2577,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_tool_context_update() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create initial context
         let mut initial_state = HashMap::new();
@@ -2586,7 +2601,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_tool_context_ttl_expiration() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create context with short TTL
         let mut initial_state = HashMap::new();
@@ -2608,7 +2624,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_tool_context_sharing() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create source context
         let mut source_state = HashMap::new();
@@ -2641,7 +2658,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_tool_context_session_filtering() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create contexts for different sessions
         let mut state1 = HashMap::new();
@@ -2673,7 +2691,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_tool_context_tool_filtering() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create contexts for different tools
         let mut state1 = HashMap::new();
@@ -2705,7 +2724,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_tool_context_statistics() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create various contexts
         let mut state1 = HashMap::new();
@@ -2728,7 +2748,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_tool_context_serialization() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create context
         let mut initial_state = HashMap::new();
@@ -2762,7 +2783,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_tool_context_cleanup() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create contexts with different TTLs
         let mut state = HashMap::new();
@@ -2788,8 +2810,7 @@ mod tests {
     
     #[tokio::test]
     async fn test_tool_context_persistence() {
-        let mut config = ContextManagerConfig::default();
-        config.data_dir = PathBuf::from("/tmp/test_tool_context");
+        let (dir, config) = test_config();
         
         let manager = ContextManager::new(config).unwrap();
         
@@ -2799,7 +2820,7 @@ mod tests {
         let context_id = manager.create_tool_context("persistent_tool", "session_persist", initial_state, None).await.unwrap();
         
         // Verify context file exists
-        let context_file = PathBuf::from("/tmp/test_tool_context/tool_contexts").join(format!("{}.json", context_id));
+        let context_file = dir.path().join("tool_contexts").join(format!("{}.json", context_id));
         assert!(context_file.exists());
         
         // Verify file content
@@ -2808,16 +2829,12 @@ mod tests {
         assert!(content.contains("session_persist"));
         assert!(content.contains("persistent_data"));
         assert!(content.contains("saved_value"));
-        
-        // Cleanup
-        if let Err(e) = std::fs::remove_dir_all("/tmp/test_tool_context") {
-            eprintln!("Warning: Failed to cleanup test directory: {}", e);
-        }
     }
     
     #[tokio::test]
     async fn test_batch_get_contexts() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create test data
         let session_id = manager.create_session("test_user").await.unwrap();
@@ -2874,7 +2891,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_preload_session_context() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create test data
         let session_id = manager.create_session("test_user").await.unwrap();
@@ -2910,7 +2928,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_comprehensive_context() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create test data
         let session_id = manager.create_session("test_user").await.unwrap();
@@ -2952,7 +2971,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_cleanup_all_expired_data() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create some data with short TTLs
         let mut tool_state = HashMap::new();
@@ -2981,7 +3001,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_performance_optimization() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create some test data to analyze
         let _session_id = manager.create_session("test_user").await.unwrap();
@@ -3006,7 +3027,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_context_index() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create test data
         let _session_id = manager.create_session("test_user").await.unwrap();
@@ -3030,7 +3052,8 @@ mod tests {
     
     #[tokio::test]
     async fn test_context_with_fallback() {
-        let manager = ContextManager::with_default_config().unwrap();
+        let (_dir, config) = test_config();
+        let manager = ContextManager::new(config).unwrap();
         
         // Create test data
         let session_id = manager.create_session("test_user").await.unwrap();
@@ -3062,7 +3085,7 @@ mod tests {
     
     #[tokio::test]
     async fn test_performance_target_compliance() {
-        let mut config = ContextManagerConfig::default();
+        let (_dir, mut config) = test_config();
         config.performance_target_ms = 100; // Very short target for testing
         
         let manager = ContextManager::new(config).unwrap();
@@ -720,7 +720,7 @@ mod tests {
         
         let config = ConversationalInterfaceConfig::default();
         let nl_parser = NLCommandParser::new(mock_client);
-        let mut interface = ConversationalInterface::new(nl_parser, config).unwrap();
+        let mut interface = ConversationalInterface::new(nl_parser, config, Box::new(MockLLMClient::new(vec![]))).unwrap();
         
         let session_id = interface.start_session(Some("test_user".to_string())).await.unwrap();
         
@@ -738,7 +738,7 @@ mod tests {
         
         let config = ConversationalInterfaceConfig::default();
         let nl_parser = NLCommandParser::new(mock_client);
-        let mut interface = ConversationalInterface::new(nl_parser, config).unwrap();
+        let mut interface = ConversationalInterface::new(nl_parser, config, Box::new(MockLLMClient::new(vec![]))).unwrap();
         
         let session_id = interface.start_session(None).await.unwrap();
         
@@ -757,7 +757,7 @@ mod tests {
         
         let config = ConversationalInterfaceConfig::default();
         let nl_parser = NLCommandParser::new(mock_client);
-        let mut interface = ConversationalInterface::new(nl_parser, config).unwrap();
+        let mut interface = ConversationalInterface::new(nl_parser, config, Box::new(MockLLMClient::new(vec![]))).unwrap();
         
         let session_id = interface.start_session(None).await.unwrap();
         
@@ -778,7 +778,7 @@ mod tests {
         
         let config = ConversationalInterfaceConfig::default();
         let nl_parser = NLCommandParser::new(mock_client);
-        let mut interface = ConversationalInterface::new(nl_parser, config).unwrap();
+        let mut interface = ConversationalInterface::new(nl_parser, config, Box::new(MockLLMClient::new(vec![]))).unwrap();
         
         let session_id = interface.start_session(None).await.unwrap();
         
@@ -801,7 +801,7 @@ mod tests {
         config.max_active_sessions = 2;
         
         let nl_parser = NLCommandParser::new(mock_client);
-        let mut interface = ConversationalInterface::new(nl_parser, config).unwrap();
+        let mut interface = ConversationalInterface::new(nl_parser, config, Box::new(MockLLMClient::new(vec![]))).unwrap();
         
         // Create 3 sessions (exceeds max)
         let _session1 = interface.start_session(None).await.unwrap();
@@ -822,7 +822,7 @@ mod tests {
         config.default_session_config.response_style = ResponseStyle::Friendly;
         
         let nl_parser = NLCommandParser::new(mock_client);
-        let mut interface = ConversationalInterface::new(nl_parser, config).unwrap();
+        let mut interface = ConversationalInterface::new(nl_parser, config, Box::new(MockLLMClient::new(vec![]))).unwrap();
         
         let session_id = interface.start_session(Some("Alice".to_string())).await.unwrap();
         
@@ -841,7 +841,7 @@ mod tests {
         
         let config = ConversationalInterfaceConfig::default();
         let nl_parser = NLCommandParser::new(mock_client);
-        let mut interface = ConversationalInterface::new(nl_parser, config).unwrap();
+        let mut interface = ConversationalInterface::new(nl_parser, config, Box::new(MockLLMClient::new(vec![]))).unwrap();
         
         let session_id = interface.start_session(None).await.unwrap();
         let _response1 = interface.process_input(&session_id, "Hello").await.unwrap();
@@ -0,0 +1,445 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use uuid::Uuid;
+
+use crate::ai_conversation::{create_llm_client, LLMClient, Message, MessageRole};
+use crate::calendar::create_calendar_adapter;
+use crate::calendar_adapter::{CalendarEvent, Deadline};
+use crate::cli::DailyArgs;
+use crate::config::{Config, DailySettings};
+use crate::dates::parse_date;
+use crate::managed_sections::{managed_sections, upsert, SectionError, SectionWrite};
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::risks::is_active;
+use crate::timezone::{self, TimeZone};
+use crate::todo_bulk::format_minutes;
+use crate::todo_model::{TodoItem, TodoStore};
+use crate::usage::UsagePurpose;
+use crate::vault_transaction::{VaultStore, VaultTransaction};
+
+/// Owner of the sections `arrowhead daily` maintains
+pub const SECTION_OWNER: &str = "daily";
+/// Template category used for new daily notes
+pub const TEMPLATE_CATEGORY: &str = "daily";
+
+/// An active deadline as listed in the daily note
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeadlineLine {
+    pub id: String,
+    pub title: String,
+    pub due_date: DateTime<Utc>,
+    pub remaining_hours: f32,
+}
+
+/// What the daily note shows for one day
+#[derive(Debug, Clone)]
+pub struct DailyAgenda {
+    pub date: NaiveDate,
+    pub todos: Vec<TodoItem>,
+    pub events: Vec<CalendarEvent>,
+    pub deadlines: Vec<DeadlineLine>,
+    pub focus: Option<String>,
+}
+
+impl DailyAgenda {
+    /// Open todos due on `date`, the events overlapping it in `zone` and all active deadlines
+    pub fn gather(date: NaiveDate, todos: &[TodoItem], events: &[CalendarEvent], deadlines: &[Deadline], zone: &TimeZone) -> Self {
+        let mut todos: Vec<TodoItem> = todos.iter()
+            .filter(|t| t.status.is_open() && t.due == Some(date))
+            .cloned()
+            .collect();
+        todos.sort_by(|a, b| a.priority_rank().cmp(&b.priority_rank()).then_with(|| a.title.cmp(&b.title)));
+
+        let (start, end) = day_bounds(date, zone);
+        let mut events: Vec<CalendarEvent> = events.iter()
+            .filter(|e| if e.all_day {
+                e.start_time.date_naive() <= date && date < e.end_time.date_naive().max(e.start_time.date_naive() + Duration::days(1))
+            } else {
+                e.start_time < end && e.end_time > start
+            })
+            .cloned()
+            .collect();
+        events.sort_by_key(|e| (!e.all_day, e.start_time));
+
+        let mut deadlines: Vec<DeadlineLine> = deadlines.iter()
+            .filter(|d| is_active(d))
+            .map(|d| DeadlineLine {
+                id: d.id.clone(),
+                title: d.title.clone(),
+                due_date: d.due_date,
+                remaining_hours: (d.estimated_hours - d.completed_hours).max(0.0),
+            })
+            .collect();
+        deadlines.sort_by_key(|d| d.due_date);
+
+        Self { date, todos, events, deadlines, focus: None }
+    }
+
+    /// The generated sections, in the order a new note lists them
+    pub fn sections(&self, zone: &TimeZone) -> Vec<SectionWrite> {
+        let mut sections = vec![
+            SectionWrite::new(SECTION_OWNER, "todos", &self.todos_body()).heading("## Todos due today"),
+            SectionWrite::new(SECTION_OWNER, "events", &self.events_body(zone)).heading("## Calendar"),
+            SectionWrite::new(SECTION_OWNER, "deadlines", &self.deadlines_body(zone)).heading("## Deadlines"),
+        ];
+        if let Some(focus) = &self.focus {
+            sections.insert(0, SectionWrite::new(SECTION_OWNER, "focus", focus.trim()).heading("## Focus"));
+        }
+        sections
+    }
+
+    fn todos_body(&self) -> String {
+        if self.todos.is_empty() {
+            return "Nothing due today.".to_string();
+        }
+        let mut out = String::new();
+        for todo in &self.todos {
+            let name = todo.path.trim_end_matches(".md");
+            let _ = write!(out, "- [ ] [[{}|{}]]", name, todo.title);
+            if let Some(minutes) = todo.estimate_minutes {
+                let _ = write!(out, " ({})", format_minutes(minutes));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn events_body(&self, zone: &TimeZone) -> String {
+        if self.events.is_empty() {
+            return "No events.".to_string();
+        }
+        let mut out = String::new();
+        for event in &self.events {
+            let time = if event.all_day {
+                "All day".to_string()
+            } else {
+                format!("{}–{}", zone.to_local(event.start_time).format("%H:%M"), zone.to_local(event.end_time).format("%H:%M"))
+            };
+            let _ = write!(out, "- {} {}", time, event.title);
+            if let Some(location) = event.location.as_deref().filter(|l| !l.is_empty()) {
+                let _ = write!(out, " @ {}", location);
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn deadlines_body(&self, zone: &TimeZone) -> String {
+        if self.deadlines.is_empty() {
+            return "No active deadlines.".to_string();
+        }
+        let mut out = String::new();
+        for deadline in &self.deadlines {
+            let due = zone.to_local(deadline.due_date).date();
+            let days = (due - self.date).num_days();
+            let when = match days {
+                d if d < 0 => format!("overdue by {} day(s)", -d),
+                0 => "due today".to_string(),
+                1 => "due tomorrow".to_string(),
+                d => format!("due {} (in {} days)", due.format("%a %b %-d"), d),
+            };
+            let _ = writeln!(out, "- {} — {}, {:.1}h remaining", deadline.title, when, deadline.remaining_hours);
+        }
+        out
+    }
+
+    fn focus_prompt(&self, zone: &TimeZone) -> String {
+        format!(
+            "Here is my agenda for {}. In two or three sentences, suggest what I should focus on today and why. \
+             Reply with the suggestion only.\n\nTodos due today:\n{}\n\nCalendar:\n{}\n\nDeadlines:\n{}",
+            self.date.format("%A %Y-%m-%d"),
+            self.todos_body(),
+            self.events_body(zone),
+            self.deadlines_body(zone),
+        )
+    }
+}
+
+/// UTC start and end of `date` in `zone`
+pub(crate) fn day_bounds(date: NaiveDate, zone: &TimeZone) -> (DateTime<Utc>, DateTime<Utc>) {
+    let midnight = |day: NaiveDate| zone.from_local(day.and_hms_opt(0, 0, 0).expect("midnight"));
+    (midnight(date), midnight(date + Duration::days(1)))
+}
+
+/// Vault path of the daily note for `date`
+pub fn note_path(settings: &DailySettings, date: NaiveDate) -> String {
+    let folder = settings.folder.trim_matches('/');
+    let mut name = String::new();
+    if write!(name, "{}", date.format(&settings.file_format)).is_err() {
+        // An invalid strftime format falls back to the ISO date
+        name = date.format("%Y-%m-%d").to_string();
+    }
+    if folder.is_empty() { format!("{}.md", name) } else { format!("{}/{}.md", folder, name) }
+}
+
+/// Add `section`, or replace it in place. A section the note doesn't have yet goes right
+/// after the last daily section, so text written below the generated part stays last.
+fn place_section(content: &str, section: &SectionWrite) -> Result<String, SectionError> {
+    let existing = managed_sections(content);
+    if existing.iter().any(|s| s.id == section.id) {
+        return upsert(content, section);
+    }
+    match existing.iter().filter(|s| s.owner == SECTION_OWNER).map(|s| s.range.end).max() {
+        Some(end) => Ok(format!("{}{}", upsert(&content[..end], section)?, &content[end..])),
+        None => upsert(content, section),
+    }
+}
+
+/// The built-in layout of a new daily note: a title, the generated sections, then room for notes
+fn builtin_note(date: NaiveDate, sections: &[SectionWrite]) -> String {
+    let rendered: Vec<String> = sections.iter().map(SectionWrite::render).collect();
+    format!(
+        "---\ndate: {}\ntags: [daily]\n---\n\n# {}\n\n{}\n## Notes\n\n",
+        date.format("%Y-%m-%d"),
+        date.format("%A, %B %-d, %Y"),
+        rendered.join("\n"),
+    )
+}
+
+/// Values for a daily template: `date` and each section id, rendered with its markers
+fn template_values(date: NaiveDate, sections: &[SectionWrite]) -> HashMap<String, String> {
+    let mut values: HashMap<String, String> = sections.iter()
+        .map(|s| (s.id.clone(), s.render().trim_end().to_string()))
+        .collect();
+    values.insert("date".to_string(), date.format("%Y-%m-%d").to_string());
+    values.insert("title".to_string(), date.format("%A, %B %-d, %Y").to_string());
+    values
+}
+
+/// The daily note after this run. An existing note only has its generated sections
+/// replaced; a new one comes from `template` (already rendered) or the built-in layout.
+pub fn build_note(existing: Option<&str>, template: Option<String>, date: NaiveDate, sections: &[SectionWrite]) -> Result<String, SectionError> {
+    let mut content = match (existing, template) {
+        (Some(existing), _) => existing.to_string(),
+        (None, Some(rendered)) => rendered,
+        (None, None) => return Ok(builtin_note(date, sections)),
+    };
+    for section in sections {
+        content = place_section(&content, section)?;
+    }
+    Ok(content)
+}
+
+async fn focus_summary(llm: &dyn LLMClient, agenda: &DailyAgenda, zone: &TimeZone) -> Result<String> {
+    let response = llm.send_message(vec![Message {
+        id: Uuid::new_v4().to_string(),
+        role: MessageRole::User,
+        content: agenda.focus_prompt(zone),
+        timestamp: Utc::now(),
+        function_call: None,
+    }]).await?;
+    Ok(response.content.trim().to_string())
+}
+
+pub async fn handle_daily_command(args: DailyArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let zone = timezone::resolve(&config.calendar.time_zone)?;
+    let today = zone.to_local(Utc::now()).date();
+    let date = match args.date.as_deref() {
+        None => today,
+        Some(input) => parse_date(input, today)?,
+    };
+
+    let (start, end) = day_bounds(date, &zone);
+    let mut events = Vec::new();
+    let mut deadlines = Vec::new();
+    match create_calendar_adapter(&config.calendar, &config.network) {
+        Ok(calendar) => {
+            match calendar.list_events(&config.calendar.calendar_id, Some(start), Some(end)).await {
+                Ok(found) => events = found,
+                Err(e) => println!("⚠️  Could not read calendar events: {}", e),
+            }
+            match calendar.get_deadlines(None).await {
+                Ok(found) => deadlines = found,
+                Err(e) => println!("⚠️  Could not read deadlines: {}", e),
+            }
+        }
+        Err(e) => println!("⚠️  {}; leaving out events and deadlines", e),
+    }
+    let todos = TodoStore::new(adapter).list().await?;
+    let mut agenda = DailyAgenda::gather(date, &todos, &events, &deadlines, &zone);
+
+    if args.focus || config.daily.ai_summary {
+        match create_llm_client(&config, UsagePurpose::Suggestions) {
+            Ok(llm) => match focus_summary(llm.as_ref(), &agenda, &zone).await {
+                Ok(summary) if !summary.is_empty() => agenda.focus = Some(summary),
+                Ok(_) => println!("⚠️  The focus summary came back empty"),
+                Err(e) => println!("⚠️  Could not write a focus summary: {}", e),
+            },
+            Err(e) => println!("⚠️  Skipping the focus summary: {}", e),
+        }
+    }
+
+    let path = note_path(&config.daily, date);
+    let sections = agenda.sections(&zone);
+    let existing = adapter.read_file(&path).await?;
+    let template = match existing {
+        Some(_) => None,
+        None => daily_template(&config, date, &sections),
+    };
+    let updated = build_note(existing.as_deref(), template, date, &sections)?;
+
+    let output = serde_json::json!({
+        "path": path,
+        "date": date,
+        "created": existing.is_none(),
+        "todos": agenda.todos.iter().map(|t| serde_json::json!({ "path": t.path, "title": t.title })).collect::<Vec<_>>(),
+        "events": agenda.events,
+        "deadlines": agenda.deadlines,
+        "focus": agenda.focus,
+    });
+    if existing.as_deref() == Some(updated.as_str()) {
+        println!("{} is up to date.", path);
+        crate::script::publish_output(output);
+        return Ok(());
+    }
+    let transaction = match &existing {
+        Some(current) => VaultTransaction::new(&format!("daily note {}", date)).write_if_unchanged(&path, &updated, current),
+        None => VaultTransaction::new(&format!("daily note {}", date)).write(&path, &updated),
+    };
+    let report = transaction.execute(adapter).await?;
+    if let Err(e) = report.append_to_log(adapter.transaction_log()) {
+        eprintln!("Warning: could not write transaction log: {}", e);
+    }
+    if !report.is_committed() {
+        bail!("{}", report.summary());
+    }
+    println!(
+        "{} {}: {} todo(s), {} event(s), {} deadline(s)",
+        if existing.is_some() { "Updated" } else { "Created" },
+        path,
+        agenda.todos.len(),
+        agenda.events.len(),
+        agenda.deadlines.len(),
+    );
+    crate::script::publish_output(output);
+    Ok(())
+}
+
+/// A new note rendered from the first template in the "daily" category, if there is one
+fn daily_template(config: &Config, date: NaiveDate, sections: &[SectionWrite]) -> Option<String> {
+    let mut templates = ObsidianAdapter::new(Some(config.obsidian.base_url.clone()), config.obsidian.api_key.clone());
+    templates.set_cache_settings(&config.cache);
+    if let Err(e) = templates.load_template_database() {
+        println!("⚠️  Could not load templates, using the built-in layout: {}", e);
+        return None;
+    }
+    let template = templates.get_templates_by_category(TEMPLATE_CATEGORY).into_iter().next()?;
+    match templates.render_template(template, &template_values(date, sections).into()) {
+        Ok(rendered) => Some(rendered),
+        Err(e) => {
+            println!("⚠️  Could not render template '{}', using the built-in layout: {}", template.name, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar_adapter::{DeadlinePriority, DeadlineStatus};
+    use crate::testing;
+    use chrono::TimeZone as _;
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 6, 5).unwrap()
+    }
+
+    fn todo(name: &str, frontmatter: &str, line: &str) -> TodoItem {
+        TodoItem::parse(&format!("Todos/{}.md", name), &format!("---\n{}status: open\n---\n\n- [ ] {}", frontmatter, line))
+    }
+
+    fn event(title: &str, day: u32, hour: u32, hours: i64) -> CalendarEvent {
+        let start = Utc.with_ymd_and_hms(2024, 6, day, hour, 0, 0).unwrap();
+        CalendarEvent {
+            id: title.to_string(),
+            title: title.to_string(),
+            description: None,
+            start_time: start,
+            end_time: start + Duration::hours(hours),
+            location: None,
+            attendees: vec![],
+            all_day: false,
+            recurring: false,
+            calendar_id: "default".to_string(),
+        }
+    }
+
+    fn deadline(id: &str, due_day: u32, estimated: f32, completed: f32, status: DeadlineStatus) -> Deadline {
+        Deadline {
+            id: id.to_string(),
+            priority: DeadlinePriority::High,
+            status,
+            estimated_hours: estimated,
+            completed_hours: completed,
+            ..testing::deadline(&format!("Deadline {}", id), Utc.with_ymd_and_hms(2024, 6, due_day, 17, 0, 0).unwrap())
+        }
+    }
+
+    fn agenda() -> DailyAgenda {
+        let todos = vec![
+            todo("report", "est: 2h\ndue_date: 2024-06-05\n", "write report"),
+            todo("later", "due_date: 2024-06-06\n", "not today"),
+            todo("undated", "", "someday"),
+        ];
+        let events = vec![event("Standup", 5, 9, 1), event("Yesterday", 4, 9, 1), event("Lunch", 5, 12, 1)];
+        let deadlines = vec![
+            deadline("d1", 7, 10.0, 4.0, DeadlineStatus::InProgress),
+            deadline("d2", 5, 3.0, 3.0, DeadlineStatus::Completed),
+        ];
+        DailyAgenda::gather(date(), &todos, &events, &deadlines, &TimeZone::utc())
+    }
+
+    #[test]
+    fn test_agenda_keeps_only_what_concerns_the_day() {
+        let agenda = agenda();
+        assert_eq!(agenda.todos.iter().map(|t| t.path.as_str()).collect::<Vec<_>>(), vec!["Todos/report.md"]);
+        assert_eq!(agenda.events.iter().map(|e| e.title.as_str()).collect::<Vec<_>>(), vec!["Standup", "Lunch"]);
+        assert_eq!(agenda.deadlines.len(), 1);
+        assert_eq!(agenda.deadlines[0].remaining_hours, 6.0);
+
+        let sections = agenda.sections(&TimeZone::utc());
+        assert!(sections[0].body.contains("[[Todos/report|write report]] (2h)"));
+        assert!(sections[1].body.contains("09:00–10:00 Standup"));
+        assert!(sections[2].body.contains("Deadline d1 — due Fri Jun 7 (in 2 days), 6.0h remaining"));
+    }
+
+    #[test]
+    fn test_rerun_updates_sections_and_keeps_manual_notes() {
+        let zone = TimeZone::utc();
+        let mut agenda = agenda();
+        let first = build_note(None, None, date(), &agenda.sections(&zone)).unwrap();
+        assert!(first.starts_with("---\ndate: 2024-06-05\n"));
+        let written = format!("{}Met Sam about the report.\n", first);
+
+        // Same inputs: nothing changes
+        assert_eq!(build_note(Some(&written), None, date(), &agenda.sections(&zone)).unwrap(), written);
+
+        agenda.events.pop();
+        agenda.focus = Some("Finish the report first.".to_string());
+        let updated = build_note(Some(&written), None, date(), &agenda.sections(&zone)).unwrap();
+        assert!(!updated.contains("Lunch"));
+        assert!(updated.ends_with("## Notes\n\nMet Sam about the report.\n"));
+        // A section added later still lands above the manual part
+        let focus = updated.find("Finish the report first.").unwrap();
+        assert!(focus < updated.find("## Notes").unwrap());
+        assert_eq!(managed_sections(&updated).len(), 4);
+    }
+
+    #[test]
+    fn test_template_places_sections_and_the_rest_follow() {
+        let zone = TimeZone::utc();
+        let sections = agenda().sections(&zone);
+        let values = template_values(date(), &sections);
+        let rendered = format!("# {}\n\n{}\n\n## Journal\n", values["title"], values["events"]);
+        let note = build_note(None, Some(rendered), date(), &sections).unwrap();
+        let events = note.find("id=events").unwrap();
+        let todos = note.find("id=todos").unwrap();
+        assert!(events < todos && todos < note.find("id=deadlines").unwrap());
+        assert!(note.trim_end().ends_with("## Journal"));
+        assert_eq!(note_path(&DailySettings::default(), date()), "Daily/2024-06-05.md");
+    }
+}
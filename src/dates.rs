@@ -0,0 +1,370 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Timelike, Utc, Weekday};
+
+use crate::config::Config;
+use crate::timezone::{self, TimeZone};
+
+/// The ways of writing a date `parse_date` understands, for error messages and help
+pub const DATE_FORMATS: &str = "YYYY-MM-DD, today, tomorrow, a weekday (friday, next tuesday), \
+in 3 days, in 2 weeks, next week, next month, end of week, end of month, or a month and day (June 7, 7 June 2025)";
+
+/// A date found inside free text, along with the words that expressed it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateMention {
+    pub date: NaiveDate,
+    pub phrase: String,
+}
+
+/// Parse a single day word: "today", "tomorrow", a weekday name or YYYY-MM-DD
+pub fn parse_day_word(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match word {
+        "today" => return Some(today),
+        "tomorrow" => return Some(today + Duration::days(1)),
+        _ => {}
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+        return Some(date);
+    }
+
+    let weekday = match word {
+        "monday" | "mon" => Weekday::Mon,
+        "tuesday" | "tue" | "tues" => Weekday::Tue,
+        "wednesday" | "wed" => Weekday::Wed,
+        "thursday" | "thu" | "thurs" => Weekday::Thu,
+        "friday" | "fri" => Weekday::Fri,
+        "saturday" | "sat" => Weekday::Sat,
+        "sunday" | "sun" => Weekday::Sun,
+        _ => return None,
+    };
+
+    // Weekday names always refer to the upcoming occurrence, never today
+    let days_ahead = (weekday.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64 + 7) % 7;
+    Some(today + Duration::days(if days_ahead == 0 { 7 } else { days_ahead }))
+}
+
+fn month(word: &str) -> Option<u32> {
+    Some(match word {
+        "january" | "jan" => 1,
+        "february" | "feb" => 2,
+        "march" | "mar" => 3,
+        "april" | "apr" => 4,
+        "may" => 5,
+        "june" | "jun" => 6,
+        "july" | "jul" => 7,
+        "august" | "aug" => 8,
+        "september" | "sept" | "sep" => 9,
+        "october" | "oct" => 10,
+        "november" | "nov" => 11,
+        "december" | "dec" => 12,
+        _ => return None,
+    })
+}
+
+/// A day of the month, with or without an ordinal suffix: "7", "7th", "21st"
+fn day_of_month(word: &str) -> Option<u32> {
+    let digits = word.trim_end_matches(|c: char| c.is_alphabetic());
+    let suffix = &word[digits.len()..];
+    if !matches!(suffix, "" | "st" | "nd" | "rd" | "th") {
+        return None;
+    }
+    digits.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+/// The Monday of the week `day` is in
+pub fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Duration::days(day.weekday().num_days_from_monday() as i64)
+}
+
+/// The first day of the month `day` is in
+pub fn first_of_month(day: NaiveDate) -> NaiveDate {
+    day.with_day(1).expect("every month has a first day")
+}
+
+/// The last day of the month `day` is in
+pub fn month_end(day: NaiveDate) -> NaiveDate {
+    first_of_month(day).checked_add_months(Months::new(1)).expect("a month within chrono's range") - Duration::days(1)
+}
+
+/// The date a phrase of lowercase words names, if it is one
+fn phrase_date(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    let count = |word: &str| match word {
+        "a" | "an" | "one" => Some(1),
+        other => other.parse::<u32>().ok(),
+    };
+    match words {
+        ["day", "after", "tomorrow"] => Some(today + Duration::days(2)),
+        ["yesterday"] => Some(today - Duration::days(1)),
+        ["next", "week"] => Some(week_start(today) + Duration::days(7)),
+        ["next", "month"] => first_of_month(today).checked_add_months(Months::new(1)),
+        ["next", "year"] => NaiveDate::from_ymd_opt(today.year() + 1, 1, 1),
+        // A week ends on Sunday
+        ["end", "of", "week"] => Some(week_start(today) + Duration::days(6)),
+        ["end", "of", "next", "week"] => Some(week_start(today) + Duration::days(13)),
+        ["end", "of", "month"] => Some(month_end(today)),
+        ["end", "of", "next", "month"] => first_of_month(today).checked_add_months(Months::new(1)).map(month_end),
+        ["end", "of", "year"] => NaiveDate::from_ymd_opt(today.year(), 12, 31),
+        ["in", amount, unit] => {
+            let amount = count(amount)?;
+            match unit.trim_end_matches('s') {
+                "day" => Some(today + Duration::days(amount as i64)),
+                "week" => Some(today + Duration::weeks(amount as i64)),
+                "month" => today.checked_add_months(Months::new(amount)),
+                _ => None,
+            }
+        }
+        // "next friday" is the coming friday, as "friday" is
+        ["next", word] if !matches!(*word, "today" | "tomorrow") && !word.contains('-') => parse_day_word(word, today),
+        [word] => parse_day_word(word, today).or_else(|| NaiveDate::parse_from_str(word, "%Y/%m/%d").ok()),
+        [first, second, rest @ ..] if rest.len() <= 1 => {
+            let (month, day) = match (month(first), month(second)) {
+                (Some(month), None) => (month, day_of_month(second)?),
+                (None, Some(month)) => (month, day_of_month(first)?),
+                _ => return None,
+            };
+            match rest {
+                [year] => NaiveDate::from_ymd_opt(year.parse().ok()?, month, day),
+                // Without a year, the next time that day comes round
+                _ => NaiveDate::from_ymd_opt(today.year(), month, day)
+                    .filter(|date| *date >= today)
+                    .or_else(|| NaiveDate::from_ymd_opt(today.year() + 1, month, day)),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Lowercase words of a date phrase, without commas, a final full stop, "the", or the
+/// connecting words it may start with ("on friday", "by the end of month")
+fn phrase_words(input: &str) -> Vec<String> {
+    let text = input.to_lowercase().replace(',', " ");
+    let mut words: Vec<String> = text.trim().trim_end_matches('.').split_whitespace()
+        .filter(|word| *word != "the")
+        .map(str::to_string)
+        .collect();
+    while words.len() > 1 && matches!(words[0].as_str(), "on" | "by" | "due" | "this" | "until" | "before") {
+        words.remove(0);
+    }
+    words
+}
+
+/// Parse a date written the way people write them: see `DATE_FORMATS`. Weekdays are the
+/// coming one, never today. Numeric dates other than YYYY-MM-DD and YYYY/MM/DD are refused
+/// as ambiguous, since 06/07 is June 7 in some places and July 6 in others.
+pub fn parse_date(input: &str, today: NaiveDate) -> Result<NaiveDate> {
+    let words = phrase_words(input);
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    if let Some(date) = phrase_date(&words, today) {
+        return Ok(date);
+    }
+    if let [word] = words.as_slice() {
+        let parts: Vec<&str> = word.split(['/', '.']).collect();
+        if parts.len() >= 2 && parts.iter().all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())) {
+            bail!("'{}' is ambiguous: write the date as YYYY-MM-DD", input.trim());
+        }
+    }
+    bail!("Could not read '{}' as a date; use {}", input.trim(), DATE_FORMATS)
+}
+
+/// A date or time written the way people write them, as the instant it names in `zone`: a
+/// date on its own is the end of that day, and a date with a time such as "friday 5pm" or
+/// "2024-06-07 17:00" is that time there. RFC 3339 timestamps carry their own offset.
+pub fn parse_due(input: &str, now: DateTime<Utc>, zone: &TimeZone) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(input) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    let today = zone.to_local(now).date();
+    let (date, time) = split_time(input);
+    let date = if date.is_empty() { today } else { parse_date(&date, today)? };
+    let (hour, minute) = time.map_or((23, 59), |time| (time.hour(), time.minute()));
+    zone.at(date, hour, minute).ok_or_else(|| anyhow::anyhow!("{} has no {:02}:{:02} in {}", date, hour, minute, zone.name()))
+}
+
+/// Split a trailing or leading time of day off a date phrase: "friday at 5pm", "2 pm
+/// tomorrow", "2024-06-07 17:00". Returns the rest of the phrase and the time, if any.
+pub fn split_time(input: &str) -> (String, Option<NaiveTime>) {
+    let lower = input.to_lowercase();
+    let mut words: Vec<&str> = lower.split_whitespace().collect();
+    let mut time = None;
+    // At the end or else the start, as one word ("2pm") or two ("2 pm")
+    for (at_end, span) in [(true, 2), (true, 1), (false, 2), (false, 1)] {
+        if time.is_some() || words.len() < span {
+            continue;
+        }
+        let range = if at_end { words.len() - span..words.len() } else { 0..span };
+        let word = words[range.clone()].concat();
+        if span == 2 && !(word.ends_with("am") || word.ends_with("pm")) {
+            continue;
+        }
+        if let Some(found) = parse_time_word(&word) {
+            time = Some(found);
+            words.drain(range);
+        }
+    }
+    if time.is_some() {
+        words.retain(|word| *word != "at");
+    }
+    (words.join(" "), time)
+}
+
+/// How a parsed date is echoed back before it is written: "Friday, 2024-06-07"
+pub fn describe_date(date: NaiveDate) -> String {
+    date.format("%A, %Y-%m-%d").to_string()
+}
+
+/// Today on the wall clock of the configured calendar time zone
+pub fn configured_today() -> Result<NaiveDate> {
+    let config = Config::load().unwrap_or_default();
+    Ok(timezone::resolve(&config.calendar.time_zone)?.to_local(Utc::now()).date())
+}
+
+/// A date from the command line as YYYY-MM-DD for frontmatter, echoed back as "due
+/// Friday, 2024-06-07" before the caller writes it
+pub fn frontmatter_date(input: &str, label: &str, today: NaiveDate) -> Result<String> {
+    let date = parse_date(input, today)?;
+    println!("📅 {} {}", label, describe_date(date));
+    Ok(date.format("%Y-%m-%d").to_string())
+}
+
+/// Parse a time word such as "2pm", "14:30", "9:15am" or "noon"
+pub fn parse_time_word(word: &str) -> Option<NaiveTime> {
+    match word {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+
+    let (digits, meridiem) = if let Some(stripped) = word.strip_suffix("am") {
+        (stripped, Some(false))
+    } else if let Some(stripped) = word.strip_suffix("pm") {
+        (stripped, Some(true))
+    } else {
+        (word, None)
+    };
+
+    let (hour, minute) = match digits.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None if meridiem.is_some() => (digits.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+
+    let hour = match meridiem {
+        Some(pm) => {
+            if hour == 0 || hour > 12 {
+                return None;
+            }
+            match (hour, pm) {
+                (12, false) => 0,
+                (12, true) => 12,
+                (h, true) => h + 12,
+                (h, false) => h,
+            }
+        }
+        None => hour,
+    };
+
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// Find the first date mentioned in free text, e.g. "due friday", "by 2024-06-01" or
+/// "in 3 days". Connecting words ("by", "on", "due", "next") are included in the returned
+/// phrase so callers can strip the whole mention from the surrounding text.
+pub fn extract_date(text: &str, today: NaiveDate) -> Option<DateMention> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let cleaned: Vec<String> = words.iter()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric() && c != '-' && c != '/').to_lowercase())
+        .collect();
+
+    for i in 0..words.len() {
+        // The longest phrase starting here: "end of month" rather than nothing, "june 7 2025" rather than "june 7"
+        for len in (1..=4.min(words.len() - i)).rev() {
+            let phrase: Vec<&str> = cleaned[i..i + len].iter().map(String::as_str).filter(|word| *word != "the").collect();
+            let Some(date) = phrase_date(&phrase, today) else {
+                continue;
+            };
+
+            let mut start = i;
+            while start > 0 && matches!(words[start - 1].to_lowercase().as_str(), "by" | "on" | "due" | "next" | "for" | "the") {
+                start -= 1;
+            }
+
+            return Some(DateMention {
+                date,
+                phrase: words[start..i + len].join(" "),
+            });
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    fn wednesday() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()
+    }
+
+    #[test]
+    fn test_parse_day_word_weekdays_are_upcoming() {
+        assert_eq!(parse_day_word("wednesday", wednesday()), NaiveDate::from_ymd_opt(2024, 5, 8));
+        assert_eq!(parse_day_word("fri", wednesday()), NaiveDate::from_ymd_opt(2024, 5, 3));
+        assert_eq!(parse_day_word("tomorrow", wednesday()), NaiveDate::from_ymd_opt(2024, 5, 2));
+        assert_eq!(parse_day_word("soon", wednesday()), None);
+    }
+
+    #[test]
+    fn test_parse_time_word() {
+        assert_eq!(parse_time_word("2pm"), NaiveTime::from_hms_opt(14, 0, 0));
+        assert_eq!(parse_time_word("12am"), NaiveTime::from_hms_opt(0, 0, 0));
+        assert_eq!(parse_time_word("9:15"), NaiveTime::from_hms_opt(9, 15, 0));
+        assert_eq!(parse_time_word("13pm"), None);
+    }
+
+    #[test]
+    fn test_extract_date_includes_connecting_words() {
+        let mention = extract_date("buy milk due by Friday.", wednesday()).unwrap();
+        assert_eq!(mention.date, NaiveDate::from_ymd_opt(2024, 5, 3).unwrap());
+        assert_eq!(mention.phrase, "due by Friday.");
+        assert!(extract_date("buy milk", wednesday()).is_none());
+    }
+
+    #[test]
+    fn test_parse_date_phrases() {
+        let day = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        assert_eq!(parse_date("friday", wednesday()).unwrap(), day(5, 3));
+        assert_eq!(parse_date("next tuesday", wednesday()).unwrap(), day(5, 7));
+        assert_eq!(parse_date("in 3 days", wednesday()).unwrap(), day(5, 4));
+        assert_eq!(parse_date("next week", wednesday()).unwrap(), day(5, 6));
+        assert_eq!(parse_date("end of month", wednesday()).unwrap(), day(5, 31));
+        assert_eq!(parse_date("June 7", wednesday()).unwrap(), day(6, 7));
+        assert_eq!(parse_date("2024-06-07", wednesday()).unwrap(), day(6, 7));
+
+        let ambiguous = parse_date("06/07", wednesday()).unwrap_err().to_string();
+        assert!(ambiguous.contains("ambiguous"), "{}", ambiguous);
+        let unknown = parse_date("someday", wednesday()).unwrap_err().to_string();
+        assert!(unknown.contains(DATE_FORMATS), "{}", unknown);
+    }
+
+    #[test]
+    fn test_week_and_month_bounds() {
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(week_start(wednesday()), day(2024, 4, 29));
+        assert_eq!(week_start(day(2024, 5, 6)), day(2024, 5, 6));
+        assert_eq!(first_of_month(wednesday()), day(2024, 5, 1));
+        assert_eq!(month_end(day(2024, 2, 10)), day(2024, 2, 29));
+        assert_eq!(month_end(day(2024, 12, 31)), day(2024, 12, 31));
+    }
+
+    #[test]
+    fn test_parse_due_is_anchored_to_the_zone() {
+        let zone = TimeZone::named("America/New_York").unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        assert_eq!(parse_due("friday", now, &zone).unwrap(), Utc.with_ymd_and_hms(2024, 5, 4, 3, 59, 0).unwrap());
+        assert_eq!(parse_due("friday at 5pm", now, &zone).unwrap(), Utc.with_ymd_and_hms(2024, 5, 3, 21, 0, 0).unwrap());
+        assert_eq!(split_time("2 pm tomorrow"), ("tomorrow".to_string(), NaiveTime::from_hms_opt(14, 0, 0)));
+    }
+}
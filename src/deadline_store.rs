@@ -0,0 +1,258 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::calendar_adapter::{Deadline, DeadlineStatus, TimeBlock};
+use crate::config::CalendarSettings;
+
+const STORE_VERSION: u32 = 1;
+/// How long a writer waits for another process to release the lock
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+/// A lock file older than this was left behind by a crashed process
+const STALE_LOCK_AGE: Duration = Duration::from_secs(30);
+
+pub fn default_deadlines_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".config")
+        .join("arrowhead")
+        .join("deadlines.json")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreFile {
+    version: u32,
+    deadlines: Vec<Deadline>,
+}
+
+/// Deadlines with their time blocks and milestones, kept in one JSON file. Reads see the
+/// last complete write; writers take a lock file, so two processes updating deadlines at
+/// once apply their changes one after the other instead of overwriting each other.
+#[derive(Debug, Clone)]
+pub struct DeadlineStore {
+    path: PathBuf,
+}
+
+/// Held while a process reads, changes and rewrites the store
+struct StoreLock {
+    path: PathBuf,
+}
+
+impl StoreLock {
+    fn acquire(store: &Path) -> Result<Self> {
+        let path = store.with_extension("json.lock");
+        let started = SystemTime::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path).and_then(|m| m.modified()).ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > STALE_LOCK_AGE);
+                    if stale {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if started.elapsed().unwrap_or_default() > LOCK_TIMEOUT {
+                        bail!("The deadline store is locked by another arrowhead process ({})", path.display());
+                    }
+                    std::thread::sleep(Duration::from_millis(25));
+                }
+                Err(e) => return Err(e).context(format!("Failed to lock {}", path.display())),
+            }
+        }
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl DeadlineStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn from_settings(settings: &CalendarSettings) -> Self {
+        Self::new(settings.deadlines_path.as_ref().map(PathBuf::from).unwrap_or_else(default_deadlines_path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every stored deadline, soonest due first
+    pub fn load(&self) -> Result<Vec<Deadline>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let data = fs::read_to_string(&self.path).context(format!("Failed to read {}", self.path.display()))?;
+        let file: StoreFile = serde_json::from_str(&data).context(format!("Failed to parse {}", self.path.display()))?;
+        if file.version > STORE_VERSION {
+            bail!("{} was written by a newer arrowhead (version {})", self.path.display(), file.version);
+        }
+        let mut deadlines = file.deadlines;
+        deadlines.sort_by_key(|d| d.due_date);
+        Ok(deadlines)
+    }
+
+    /// Stored deadlines, only those with `status` when given
+    pub fn list(&self, status: Option<&DeadlineStatus>) -> Result<Vec<Deadline>> {
+        Ok(self.load()?.into_iter().filter(|d| status.is_none_or(|s| d.status == *s)).collect())
+    }
+
+    /// The deadline with this id, or the only one whose id starts with it
+    pub fn get(&self, id: &str) -> Result<Deadline> {
+        let deadlines = self.load()?;
+        let index = find(&deadlines, id)?;
+        Ok(deadlines[index].clone())
+    }
+
+    /// Apply `change` to the stored deadlines and write them back in one step, holding
+    /// the lock so no other process writes in between
+    pub fn update<T>(&self, change: impl FnOnce(&mut Vec<Deadline>) -> Result<T>) -> Result<T> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+        }
+        let _lock = StoreLock::acquire(&self.path)?;
+        let mut deadlines = self.load()?;
+        let result = change(&mut deadlines)?;
+        let file = StoreFile { version: STORE_VERSION, deadlines };
+        let partial = self.path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_string_pretty(&file)?).context("Failed to write deadlines")?;
+        fs::rename(&partial, &self.path).context("Failed to save deadlines")?;
+        Ok(result)
+    }
+
+    pub fn insert(&self, deadline: &Deadline) -> Result<()> {
+        self.update(|deadlines| {
+            if deadlines.iter().any(|d| d.id == deadline.id) {
+                bail!("A deadline with id {} already exists", deadline.id);
+            }
+            deadlines.push(deadline.clone());
+            Ok(())
+        })
+    }
+
+    /// Change one deadline (by id or unique prefix) and return it as saved
+    pub fn modify(&self, id: &str, change: impl FnOnce(&mut Deadline) -> Result<()>) -> Result<Deadline> {
+        self.update(|deadlines| {
+            let index = find(deadlines, id)?;
+            change(&mut deadlines[index])?;
+            Ok(deadlines[index].clone())
+        })
+    }
+
+    pub fn remove(&self, id: &str) -> Result<Deadline> {
+        self.update(|deadlines| {
+            let index = find(deadlines, id)?;
+            Ok(deadlines.remove(index))
+        })
+    }
+
+    /// Change the time block with this id, whichever deadline it belongs to
+    pub fn modify_time_block(&self, block_id: &str, change: impl FnOnce(&mut TimeBlock)) -> Result<TimeBlock> {
+        self.update(|deadlines| {
+            let block = deadlines.iter_mut()
+                .flat_map(|d| d.time_blocks.iter_mut())
+                .find(|b| b.id == block_id)
+                .ok_or_else(|| anyhow::anyhow!("No time block with id {}", block_id))?;
+            change(block);
+            Ok(block.clone())
+        })
+    }
+}
+
+fn find(deadlines: &[Deadline], id: &str) -> Result<usize> {
+    if let Some(index) = deadlines.iter().position(|d| d.id == id) {
+        return Ok(index);
+    }
+    let matches: Vec<usize> = deadlines.iter().enumerate()
+        .filter(|(_, d)| !id.is_empty() && d.id.starts_with(id))
+        .map(|(i, _)| i)
+        .collect();
+    match matches[..] {
+        [index] => Ok(index),
+        [] => bail!("No deadline with id {}", id),
+        _ => bail!("Deadline id {} is ambiguous; use more characters", id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar_adapter::TimeBlockStatus;
+    use crate::testing;
+    use chrono::Utc;
+
+    fn temp_store() -> DeadlineStore {
+        let dir = std::env::temp_dir().join(format!("arrowhead-deadlines-{}", uuid::Uuid::new_v4()));
+        DeadlineStore::new(dir.join("deadlines.json"))
+    }
+
+    fn deadline(id: &str, due_days: i64, status: DeadlineStatus) -> Deadline {
+        Deadline {
+            id: id.to_string(),
+            status,
+            estimated_hours: 4.0,
+            time_blocks: vec![TimeBlock {
+                id: format!("{}-block", id),
+                deadline_id: id.to_string(),
+                start_time: Utc::now(),
+                end_time: Utc::now() + chrono::Duration::hours(2),
+                planned_duration: chrono::Duration::hours(2),
+                actual_duration: None,
+                productivity_score: None,
+                notes: None,
+                calendar_event_id: None,
+                status: TimeBlockStatus::Planned,
+                focus_mode: true,
+                interruptions: vec![],
+            }],
+            ..testing::deadline(&format!("Deadline {}", id), Utc::now() + chrono::Duration::days(due_days))
+        }
+    }
+
+    #[test]
+    fn test_crud_and_status_filter() {
+        let store = temp_store();
+        assert!(store.load().unwrap().is_empty());
+        store.insert(&deadline("b-report", 5, DeadlineStatus::InProgress)).unwrap();
+        store.insert(&deadline("a-taxes", 2, DeadlineStatus::NotStarted)).unwrap();
+        assert!(store.insert(&deadline("a-taxes", 2, DeadlineStatus::NotStarted)).is_err());
+
+        // Soonest first, and the filter queries what is stored
+        let ids: Vec<String> = store.load().unwrap().into_iter().map(|d| d.id).collect();
+        assert_eq!(ids, vec!["a-taxes", "b-report"]);
+        assert_eq!(store.list(Some(&DeadlineStatus::InProgress)).unwrap().len(), 1);
+
+        let updated = store.modify("b-", |d| { d.completed_hours = 3.0; Ok(()) }).unwrap();
+        assert_eq!(updated.completed_hours, 3.0);
+        let block = store.modify_time_block("a-taxes-block", |b| b.productivity_score = Some(4.0)).unwrap();
+        assert_eq!(block.productivity_score, Some(4.0));
+        assert_eq!(store.get("a-taxes").unwrap().time_blocks[0].productivity_score, Some(4.0));
+
+        store.remove("a-taxes").unwrap();
+        assert!(store.get("a-taxes").is_err());
+        fs::remove_dir_all(store.path().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_writers_do_not_lose_updates() {
+        let store = temp_store();
+        let handles: Vec<_> = (0..8).map(|i| {
+            let store = store.clone();
+            std::thread::spawn(move || store.insert(&deadline(&format!("d{}", i), i, DeadlineStatus::NotStarted)).unwrap())
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(store.load().unwrap().len(), 8);
+        assert!(!store.path().with_extension("json.lock").exists());
+        fs::remove_dir_all(store.path().parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::fmt::Write as _;
+use uuid::Uuid;
+
+use crate::calendar::create_calendar_adapter;
+use crate::calendar_adapter::{deadline_status, Deadline, DeadlinePriority, DeadlineStatus, ReminderSchedule, ReminderSettings, TimeBlockStatus, TimeBlockSync};
+use crate::cli::{DeadlineAction, DeadlineArgs};
+use crate::config::Config;
+use crate::confirm::{PromptReader, StdinPrompt};
+use crate::dates;
+use crate::deadline_store::DeadlineStore;
+use crate::reminders::parse_lead_time;
+use crate::risks::is_active;
+use crate::timezone::{self, TimeZone};
+use crate::todo_bulk::parse_estimate;
+
+/// Gaps shorter than this are too small for a time block of their own
+const MIN_TOP_UP_HOURS: f32 = 0.5;
+
+fn parse_priority(text: &str) -> Result<DeadlinePriority> {
+    Ok(match text.to_lowercase().as_str() {
+        "low" => DeadlinePriority::Low,
+        "medium" | "med" => DeadlinePriority::Medium,
+        "high" => DeadlinePriority::High,
+        "critical" => DeadlinePriority::Critical,
+        _ => bail!("Unknown priority '{}'; use low, medium, high or critical", text),
+    })
+}
+
+fn parse_status(text: &str) -> Result<DeadlineStatus> {
+    Ok(match text.to_lowercase().replace(['_', ' '], "-").as_str() {
+        "not-started" => DeadlineStatus::NotStarted,
+        "in-progress" => DeadlineStatus::InProgress,
+        "on-hold" => DeadlineStatus::OnHold,
+        "completed" | "done" => DeadlineStatus::Completed,
+        "overdue" => DeadlineStatus::Overdue,
+        "cancelled" | "canceled" => DeadlineStatus::Cancelled,
+        _ => bail!("Unknown status '{}'; use not-started, in-progress, on-hold, completed, overdue or cancelled", text),
+    })
+}
+
+fn status_label(status: &DeadlineStatus) -> &'static str {
+    match status {
+        DeadlineStatus::NotStarted => "not started",
+        DeadlineStatus::InProgress => "in progress",
+        DeadlineStatus::OnHold => "on hold",
+        DeadlineStatus::Completed => "completed",
+        DeadlineStatus::Overdue => "overdue",
+        DeadlineStatus::Cancelled => "cancelled",
+    }
+}
+
+/// Hours in an estimate such as `6h` or `90m`
+fn parse_hours(text: &str) -> Result<f32> {
+    match parse_estimate(text) {
+        Some(minutes) => Ok(minutes as f32 / 60.0),
+        None if text.trim() == "0" => Ok(0.0),
+        None => bail!("Could not read '{}' as hours; use e.g. 6h, 90m or 1h30m", text),
+    }
+}
+
+/// A due time read with `dates::parse_due` and echoed back before it is saved
+fn parse_due(text: &str, zone: &TimeZone, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let due = dates::parse_due(text, now, zone)?;
+    let local = zone.to_local(due);
+    println!("📅 due {} {}", dates::describe_date(local.date()), local.format("%H:%M"));
+    Ok(due)
+}
+
+/// Statuses set by hand that progress updates must not override
+pub(crate) fn is_manual_status(status: &DeadlineStatus) -> bool {
+    matches!(status, DeadlineStatus::OnHold | DeadlineStatus::Cancelled)
+}
+
+/// One line per deadline: short id, title, due date, hours and status
+pub fn render_deadlines(deadlines: &[Deadline], zone: &TimeZone, now: DateTime<Utc>) -> String {
+    if deadlines.is_empty() {
+        return "No deadlines.\n".to_string();
+    }
+    let mut out = String::new();
+    for deadline in deadlines {
+        let days = (zone.to_local(deadline.due_date).date() - zone.to_local(now).date()).num_days();
+        let when = match days {
+            d if d < 0 => format!("{} day(s) ago", -d),
+            0 => "today".to_string(),
+            1 => "tomorrow".to_string(),
+            d => format!("in {} days", d),
+        };
+        let _ = writeln!(
+            out,
+            "{}  {}  due {} ({})  {:.1}/{:.1}h  {} · {:?}",
+            deadline.id.chars().take(8).collect::<String>(),
+            deadline.title,
+            zone.to_local(deadline.due_date).format("%a %b %-d %H:%M"),
+            when,
+            deadline.completed_hours,
+            deadline.estimated_hours,
+            status_label(&deadline.status),
+            deadline.priority,
+        );
+    }
+    out
+}
+
+pub async fn handle_deadline_command(args: DeadlineArgs) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let store = DeadlineStore::from_settings(&config.calendar);
+    let zone = timezone::resolve(&config.calendar.time_zone)?;
+    let now = Utc::now();
+
+    match args.action {
+        DeadlineAction::Add { title, due, estimate, priority, category, tags, description, no_blocks, remind } => {
+            let priority = priority.as_deref().map(parse_priority).transpose()?.unwrap_or(DeadlinePriority::Medium);
+            let urgent = matches!(priority, DeadlinePriority::High | DeadlinePriority::Critical);
+            let mut advance_notifications = remind
+                .iter()
+                .map(|lead| Ok(ReminderSchedule { time_before_deadline: parse_lead_time(lead)?, message: String::new(), urgent }))
+                .collect::<Result<Vec<_>>>()?;
+            advance_notifications.sort_by_key(|schedule| std::cmp::Reverse(schedule.time_before_deadline));
+            let mut deadline = Deadline {
+                id: Uuid::new_v4().to_string(),
+                title,
+                description,
+                due_date: parse_due(&due, &zone, now)?,
+                created_date: now,
+                priority,
+                status: DeadlineStatus::NotStarted,
+                estimated_hours: estimate.as_deref().map(parse_hours).transpose()?.unwrap_or(0.0),
+                completed_hours: 0.0,
+                category,
+                tags,
+                dependencies: Vec::new(),
+                assignee: None,
+                project_id: None,
+                reminder_settings: ReminderSettings {
+                    enabled: !advance_notifications.is_empty(),
+                    advance_notifications,
+                    notification_channels: Vec::new(),
+                    escalation_enabled: urgent,
+                    escalation_delay_hours: config.reminders.escalation_delay_hours,
+                },
+                time_blocks: Vec::new(),
+                progress_milestones: Vec::new(),
+            };
+            if deadline.estimated_hours > 0.0 {
+                deadline.status = deadline_status(&deadline, now);
+            }
+
+            let mut saved = None;
+            if !no_blocks && deadline.estimated_hours > 0.0 {
+                match create_calendar_adapter(&config.calendar, &config.network) {
+                    Ok(calendar) => match calendar.create_deadline(&deadline).await {
+                        Ok(created) => saved = Some(created),
+                        Err(e) => println!("⚠️  Could not block out time on the calendar: {}", e),
+                    },
+                    Err(e) => println!("⚠️  {}; saving the deadline without time blocks", e),
+                }
+            }
+            let deadline = match saved {
+                Some(created) => created,
+                None => {
+                    store.insert(&deadline)?;
+                    deadline
+                }
+            };
+            println!("✅ Added deadline '{}' ({})", deadline.title, &deadline.id[..8]);
+            if !deadline.time_blocks.is_empty() {
+                println!("   {} time block(s) scheduled", deadline.time_blocks.len());
+            }
+            crate::script::publish_output(serde_json::to_value(&deadline)?);
+        }
+        DeadlineAction::List { status, all, json } => {
+            let status = status.as_deref().map(parse_status).transpose()?;
+            let deadlines: Vec<Deadline> = store.list(status.as_ref())?
+                .into_iter()
+                .filter(|d| all || status.is_some() || is_active(d))
+                .collect();
+            crate::script::publish_output(serde_json::to_value(&deadlines)?);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&deadlines)?);
+            } else {
+                print!("{}", render_deadlines(&deadlines, &zone, now));
+            }
+        }
+        DeadlineAction::Update { id, done, title, due, estimate, priority, status } => {
+            let done = done.as_deref().map(parse_hours).transpose()?;
+            let due = due.as_deref().map(|d| parse_due(d, &zone, now)).transpose()?;
+            let estimate = estimate.as_deref().map(parse_hours).transpose()?;
+            let priority = priority.as_deref().map(parse_priority).transpose()?;
+            let status = status.as_deref().map(parse_status).transpose()?;
+            if [done.is_none(), title.is_none(), due.is_none(), estimate.is_none(), priority.is_none(), status.is_none()].iter().all(|unset| *unset) {
+                bail!("Nothing to update; pass --done, --title, --due, --estimate, --priority or --status");
+            }
+            let deadline = store.modify(&id, |d| {
+                if let Some(hours) = done { d.completed_hours = hours; }
+                if let Some(title) = title { d.title = title; }
+                if let Some(due) = due { d.due_date = due; }
+                if let Some(hours) = estimate { d.estimated_hours = hours; }
+                if let Some(priority) = priority { d.priority = priority; }
+                match status {
+                    Some(status) => d.status = status,
+                    None if !is_manual_status(&d.status) => d.status = deadline_status(d, now),
+                    None => {}
+                }
+                Ok(())
+            })?;
+            println!("Updated deadline '{}': {:.1}/{:.1}h, {}", deadline.title, deadline.completed_hours, deadline.estimated_hours, status_label(&deadline.status));
+            crate::script::publish_output(serde_json::to_value(&deadline)?);
+        }
+        DeadlineAction::Complete { id } => {
+            let deadline = store.modify(&id, |d| {
+                d.status = DeadlineStatus::Completed;
+                d.completed_hours = d.completed_hours.max(d.estimated_hours);
+                for block in d.time_blocks.iter_mut().filter(|b| matches!(b.status, TimeBlockStatus::Planned) && b.start_time > now) {
+                    block.status = TimeBlockStatus::Cancelled;
+                }
+                Ok(())
+            })?;
+            println!("🎉 Completed deadline '{}'", deadline.title);
+            crate::script::publish_output(serde_json::to_value(&deadline)?);
+        }
+        DeadlineAction::Sync { id, regenerate } => {
+            let calendar = create_calendar_adapter(&config.calendar, &config.network)?;
+            let ids: Vec<String> = match id {
+                Some(id) => vec![id],
+                None => store.list(None)?.into_iter().filter(is_active).map(|d| d.id).collect(),
+            };
+            let mut prompt = StdinPrompt;
+            let mut output = Vec::new();
+            for id in ids {
+                let sync = calendar.sync_time_blocks(&id).await?;
+                print!("{}", render_sync(&sync, &zone));
+                let shortfall = sync.shortfall();
+                let mut added = 0;
+                if shortfall >= MIN_TOP_UP_HOURS {
+                    let top_up = regenerate || (prompt.is_interactive()
+                        && prompt.read_answer(&format!("   Schedule blocks for the missing {:.1}h? [y/N] ", shortfall))?.trim().eq_ignore_ascii_case("y"));
+                    if top_up {
+                        added = calendar.top_up_time_blocks(&sync.deadline_id, shortfall).await?.len();
+                        println!("   {} new time block(s) scheduled", added);
+                    } else if !prompt.is_interactive() {
+                        println!("   Run `arrowhead deadline sync {} --regenerate` to schedule the rest", &sync.deadline_id[..8.min(sync.deadline_id.len())]);
+                    }
+                }
+                let mut value = serde_json::to_value(&sync)?;
+                value["shortfall_hours"] = serde_json::json!(shortfall);
+                value["added_blocks"] = serde_json::json!(added);
+                output.push(value);
+            }
+            if output.is_empty() {
+                println!("No active deadlines.");
+            }
+            crate::script::publish_output(serde_json::Value::Array(output));
+        }
+    }
+    Ok(())
+}
+
+/// What a sync changed for one deadline, in local time
+fn render_sync(sync: &TimeBlockSync, zone: &TimeZone) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{}  {}", sync.deadline_id.chars().take(8).collect::<String>(), sync.title);
+    if sync.moved.is_empty() && sync.cancelled.is_empty() {
+        let _ = writeln!(out, "   Time blocks match the calendar");
+    }
+    for moved in &sync.moved {
+        let _ = writeln!(
+            out,
+            "   ↪ Block moved from {} to {}–{}",
+            zone.to_local(moved.previous_start).format("%a %b %-d %H:%M"),
+            zone.to_local(moved.start_time).format("%a %b %-d %H:%M"),
+            zone.to_local(moved.end_time).format("%H:%M"),
+        );
+    }
+    if !sync.cancelled.is_empty() {
+        let _ = writeln!(out, "   ✗ {} block(s) deleted from the calendar, cancelled", sync.cancelled.len());
+    }
+    if sync.unchecked > 0 {
+        let _ = writeln!(out, "   ⚠️  {} block(s) could not be checked", sync.unchecked);
+    }
+    if sync.shortfall() >= MIN_TOP_UP_HOURS {
+        let _ = writeln!(out, "   {:.1}h of {:.1}h planned, {:.1}h short", sync.planned_hours, sync.estimated_hours, sync.shortfall());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    #[test]
+    fn test_inputs_parse() {
+        assert_eq!(parse_hours("1h30m").unwrap(), 1.5);
+        assert_eq!(parse_hours("0").unwrap(), 0.0);
+        assert!(parse_hours("soon").is_err());
+        assert_eq!(parse_status("In Progress").unwrap(), DeadlineStatus::InProgress);
+        assert!(matches!(parse_priority("HIGH").unwrap(), DeadlinePriority::High));
+
+        let now = Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap();
+        let due = parse_due("2024-06-07", &TimeZone::utc(), now).unwrap();
+        assert_eq!(due, Utc.with_ymd_and_hms(2024, 6, 7, 23, 59, 0).unwrap());
+    }
+}
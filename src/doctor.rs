@@ -0,0 +1,129 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::filing::{filing_warnings, load_filing_notes};
+use crate::anthropic_client::AnthropicConfig;
+use crate::gemini_client::GeminiConfig;
+use crate::network::{probe, Destination};
+use crate::openai_client::OpenAIConfig;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::report::{DetailLevel, Report, ReportView};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Ok,
+    Warning,
+    Info,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Check {
+    area: &'static str,
+    status: CheckStatus,
+    message: String,
+}
+
+impl Check {
+    fn new(area: &'static str, status: CheckStatus, message: String) -> Self {
+        Self { area, status, message }
+    }
+
+    fn line(&self) -> String {
+        let icon = match self.status {
+            CheckStatus::Ok => "✅",
+            CheckStatus::Warning => "⚠️ ",
+            CheckStatus::Info => "ℹ️ ",
+        };
+        format!("{} {}\n", icon, self.message)
+    }
+}
+
+/// Summary: the problem count and the areas they are in. Normal: every check. Detail: also the
+/// filing rules that were checked.
+fn doctor_report(checks: &[Check], config: &Config) -> Report {
+    let problems: Vec<&Check> = checks.iter().filter(|c| c.status == CheckStatus::Warning).collect();
+    let mut areas: Vec<&str> = problems.iter().map(|c| c.area).collect();
+    areas.dedup();
+
+    let mut report = Report::new()
+        .text_only(DetailLevel::Summary, if problems.is_empty() {
+            "✅ No problems found\n".to_string()
+        } else {
+            format!("⚠️  {} problem(s) found: {}\n", problems.len(), areas.join(", "))
+        })
+        .text(DetailLevel::Normal, checks.iter().map(Check::line).collect::<String>())
+        .field(DetailLevel::Summary, "problems", problems.len())
+        .field(DetailLevel::Normal, "checks", checks)
+        .field(DetailLevel::Detail, "filing_rules", &config.filing.rules);
+    if !config.filing.rules.is_empty() {
+        let mut rules = String::from("\nFiling rules:\n");
+        for rule in &config.filing.rules {
+            rules.push_str(&format!("  {} → {} (priority {})\n", rule.name, rule.destination, rule.priority));
+        }
+        report = report.text(DetailLevel::Detail, rules);
+    }
+    if !problems.is_empty() {
+        report = report.text(DetailLevel::Normal, format!("\n{} problem(s) found\n", problems.len()));
+    }
+    report
+}
+
+pub async fn handle_doctor_command(view: ReportView, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let mut checks = Vec::new();
+
+    match config.validate() {
+        Ok(()) => checks.push(Check::new("configuration", CheckStatus::Ok, "Configuration is valid".to_string())),
+        Err(e) => checks.push(Check::new("configuration", CheckStatus::Warning, format!("Configuration: {}", e))),
+    }
+
+    if config.filing.rules.is_empty() {
+        checks.push(Check::new("filing", CheckStatus::Info, "No filing rules configured".to_string()));
+    } else {
+        let notes = load_filing_notes(adapter, &config.filing.folders).await;
+        let warnings = filing_warnings(&config.filing.rules, &notes);
+        if warnings.is_empty() {
+            checks.push(Check::new(
+                "filing",
+                CheckStatus::Ok,
+                format!("{} filing rule(s) checked against {} note(s)", config.filing.rules.len(), notes.len()),
+            ));
+        }
+        for warning in warnings {
+            checks.push(Check::new("filing", CheckStatus::Warning, format!("Filing: {}", warning)));
+        }
+    }
+
+    // Only destinations with a proxy or CA bundle are probed, so doctor stays offline otherwise
+    let llm_url = match config.llm.provider.as_str() {
+        "openai" => OpenAIConfig::default().base_url,
+        "anthropic" => AnthropicConfig::default().base_url,
+        "ollama" => config.llm.ollama.base_url.clone(),
+        _ => GeminiConfig::default().base_url,
+    };
+    let destinations = [
+        (Destination::Llm, Some(llm_url)),
+        (Destination::Calendar, Some(config.calendar.server_url.clone())),
+        (Destination::Webhooks, None),
+        (Destination::Obsidian, None),
+    ];
+    for (destination, url) in destinations {
+        let route = config.network.describe_route(destination);
+        let configured = config.network.route(destination)
+            .is_some_and(|r| r.proxy.is_some() || r.ca_bundle.is_some());
+        let check = match url.filter(|_| configured) {
+            Some(url) => match probe(&config.network, destination, &url).await {
+                Ok(status) => Check::new("network", CheckStatus::Ok, format!("Network {}: {} — {} answered {}", destination, route, url, status)),
+                Err(e) => Check::new("network", CheckStatus::Warning, format!("Network {}: {} — {} unreachable: {:#}", destination, route, url, e)),
+            },
+            None => Check::new("network", CheckStatus::Info, format!("Network {}: {}", destination, route)),
+        };
+        checks.push(check);
+    }
+
+    let problems = checks.iter().filter(|c| c.status == CheckStatus::Warning).count();
+    crate::script::publish_output(serde_json::json!({ "problems": problems }));
+    doctor_report(&checks, &config).print(view)
+}
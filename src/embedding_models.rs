@@ -0,0 +1,266 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::obsidian_adapter::VectorDatabase;
+
+/// The model of a stored embedding. Untagged embeddings predate model tags and count as
+/// the primary model's.
+pub fn document_model<'a>(tag: &'a str, primary: &'a str) -> &'a str {
+    if tag.is_empty() { primary } else { tag }
+}
+
+/// How many indexed documents one model embedded, and the length of its vectors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModelShare {
+    pub model: String,
+    pub documents: usize,
+    pub dimension: usize,
+}
+
+/// The models in the index, most documents first (the primary first on a tie)
+pub fn model_shares(database: &VectorDatabase, primary: &str) -> Vec<ModelShare> {
+    let mut shares: HashMap<&str, ModelShare> = HashMap::new();
+    for doc in database.documents() {
+        let model = document_model(&doc.model, primary);
+        let share = shares.entry(model).or_insert_with(|| ModelShare {
+            model: model.to_string(),
+            documents: 0,
+            dimension: doc.embedding.len(),
+        });
+        share.documents += 1;
+    }
+    let mut shares: Vec<ModelShare> = shares.into_values().collect();
+    shares.sort_by(|a, b| {
+        b.documents.cmp(&a.documents)
+            .then_with(|| (b.model == primary).cmp(&(a.model == primary)))
+            .then_with(|| a.model.cmp(&b.model))
+    });
+    shares
+}
+
+/// The models a query is embedded with: the primary, then the models holding the most
+/// documents, at most `max`. Each query embedding only costs a request, but every model
+/// left out makes its documents unreachable, so `max` should cover a transition (2).
+pub fn query_models(shares: &[ModelShare], primary: &str, max: usize) -> Vec<String> {
+    let mut models = vec![primary.to_string()];
+    models.extend(shares.iter().filter(|s| s.model != primary).map(|s| s.model.clone()));
+    models.truncate(max.max(1));
+    models
+}
+
+/// Documents embedded with a model other than the primary, oldest first, at most `limit`
+pub fn minority_paths(database: &VectorDatabase, primary: &str, limit: usize) -> Vec<String> {
+    let mut minority: Vec<_> = database.documents()
+        .filter(|doc| document_model(&doc.model, primary) != primary)
+        .collect();
+    minority.sort_by_key(|doc| doc.created_at);
+    minority.into_iter().take(limit).map(|doc| doc.path.clone()).collect()
+}
+
+/// Mean and spread of one model's similarities to a query
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreStats {
+    pub mean: f32,
+    pub std_dev: f32,
+}
+
+impl ScoreStats {
+    /// `None` for fewer than two scores or no spread, where nothing can be calibrated
+    pub fn of(scores: &[f32]) -> Option<Self> {
+        if scores.len() < 2 {
+            return None;
+        }
+        let mean = scores.iter().sum::<f32>() / scores.len() as f32;
+        let variance = scores.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / scores.len() as f32;
+        let std_dev = variance.sqrt();
+        (std_dev > f32::EPSILON).then_some(Self { mean, std_dev })
+    }
+
+    /// A score as many standard deviations from `to`'s mean as it is from this one's
+    pub fn calibrate(&self, score: f32, to: &ScoreStats) -> f32 {
+        (to.mean + (score - self.mean) / self.std_dev * to.std_dev).clamp(-1.0, 1.0)
+    }
+}
+
+/// Merge per-model (document, similarity) lists into one ranking. Each list holds every
+/// document of one model scored against that model's query embedding; the first list is
+/// the primary's. Similarities of different models aren't comparable, so the other lists
+/// are put on the primary's scale first: a document scores as far above the primary's mean
+/// as it is above its own model's.
+pub fn fuse(lists: Vec<Vec<(usize, f32)>>) -> Vec<(usize, f32)> {
+    let stats: Vec<Option<ScoreStats>> = lists.iter()
+        .map(|list| ScoreStats::of(&list.iter().map(|(_, s)| *s).collect::<Vec<_>>()))
+        .collect();
+    // A primary with too few documents to calibrate against lends its place to the largest list
+    let target = stats.first().copied().flatten().or_else(|| {
+        lists.iter().zip(&stats).max_by_key(|(list, _)| list.len()).and_then(|(_, s)| *s)
+    });
+
+    let mut fused: Vec<(usize, f32)> = Vec::with_capacity(lists.iter().map(Vec::len).sum());
+    for (i, list) in lists.into_iter().enumerate() {
+        match (stats[i], target) {
+            (Some(own), Some(target)) if i > 0 || stats[0].is_none() => {
+                fused.extend(list.into_iter().map(|(doc, score)| (doc, own.calibrate(score, &target))));
+            }
+            _ => fused.extend(list),
+        }
+    }
+    fused.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obsidian_adapter::{DocumentEmbedding, DocumentMetadata};
+    use crate::testing::TestRng;
+    use chrono::{Duration, Utc};
+
+    /// Retrieval quality a mixed index must keep during a transition, as a fraction of the
+    /// recall@10 the same corpus gets from the primary model alone
+    const MIXED_INDEX_RECALL_FLOOR: f32 = 0.9;
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot / (norm(a) * norm(b))
+    }
+
+    /// A second model: a fixed random projection to another dimension, with its own noise
+    /// and a constant offset that shifts all of its similarities upwards
+    struct OtherModel {
+        projection: Vec<Vec<f32>>,
+        offset: Vec<f32>,
+    }
+
+    impl OtherModel {
+        fn new(rng: &mut TestRng, from: usize, to: usize) -> Self {
+            Self { projection: (0..to).map(|_| rng.vector(from)).collect(), offset: rng.vector(to) }
+        }
+
+        fn embed(&self, rng: &mut TestRng, text: &[f32]) -> Vec<f32> {
+            self.projection.iter().zip(&self.offset)
+                .map(|(row, offset)| row.iter().zip(text).map(|(w, x)| w * x).sum::<f32>() * 0.05 + offset * 2.0 + rng.next_f32() * 0.3)
+                .collect()
+        }
+    }
+
+    fn doc(path: &str, model: &str, vector: Vec<f32>, age_days: i64) -> DocumentEmbedding {
+        DocumentEmbedding {
+            path: path.to_string(),
+            embedding: vector,
+            content_hash: String::new(),
+            created_at: Utc::now() - Duration::days(age_days),
+            metadata: DocumentMetadata {
+                title: path.to_string(),
+                tags: Vec::new(),
+                length: 0,
+                excerpt: String::new(),
+                modified_at: None,
+                skeleton: None,
+            },
+            model: model.to_string(),
+        }
+    }
+
+    fn database(embeddings: Vec<DocumentEmbedding>) -> VectorDatabase {
+        VectorDatabase::from_documents(embeddings, "", Utc::now())
+    }
+
+    #[test]
+    fn test_query_models_put_the_primary_first_and_are_bounded() {
+        let db = database(vec![
+            doc("a.md", "", vec![0.0; 4], 0),
+            doc("b.md", "gemini/text-embedding-004", vec![0.0; 3], 0),
+            doc("c.md", "gemini/text-embedding-004", vec![0.0; 3], 0),
+            doc("d.md", "openai/old-model", vec![0.0; 2], 0),
+        ]);
+        let shares = model_shares(&db, "openai/text-embedding-3-small");
+        assert_eq!(shares[0], ModelShare { model: "gemini/text-embedding-004".to_string(), documents: 2, dimension: 3 });
+        assert_eq!(shares[1].model, "openai/text-embedding-3-small");
+        assert_eq!(
+            query_models(&shares, "openai/text-embedding-3-small", 2),
+            vec!["openai/text-embedding-3-small".to_string(), "gemini/text-embedding-004".to_string()],
+        );
+        assert_eq!(query_models(&shares, "openai/text-embedding-3-small", 0).len(), 1);
+    }
+
+    #[test]
+    fn test_minority_paths_oldest_first() {
+        let db = database(vec![
+            doc("new.md", "fallback", vec![1.0], 1),
+            doc("primary.md", "primary", vec![1.0], 9),
+            doc("old.md", "fallback", vec![1.0], 5),
+            doc("untagged.md", "", vec![1.0], 7),
+        ]);
+        assert_eq!(minority_paths(&db, "primary", 10), vec!["old.md".to_string(), "new.md".to_string()]);
+        assert_eq!(minority_paths(&db, "primary", 1), vec!["old.md".to_string()]);
+    }
+
+    #[test]
+    fn test_fuse_puts_other_models_on_the_primary_scale() {
+        // The fallback scores everything high; its best match is only as good as the
+        // primary's second best relative to each model's own spread
+        let primary = vec![(0, 0.9), (1, 0.5), (2, 0.1)];
+        let fallback = vec![(3, 0.95), (4, 0.93), (5, 0.91)];
+        let fused = fuse(vec![primary, fallback]);
+        let score = |id: usize| fused.iter().find(|(doc, _)| *doc == id).unwrap().1;
+        assert!((score(3) - 0.9).abs() < 1e-4, "{}", score(3));
+        assert!((score(4) - 0.5).abs() < 1e-4, "{}", score(4));
+        assert!((score(5) - 0.1).abs() < 1e-4, "{}", score(5));
+        assert!(fused.iter().all(|(_, score)| (-1.0..=1.0).contains(score)));
+
+        // A single list is left as it is
+        assert_eq!(fuse(vec![vec![(7, 0.4), (8, 0.6)]]), vec![(8, 0.6), (7, 0.4)]);
+    }
+
+    /// Bench scenario: 30% of the vault was embedded by the fallback after the primary ran
+    /// out of quota. Searching both models and fusing must keep recall@10 close to that of
+    /// the same vault embedded with the primary alone.
+    #[test]
+    fn test_mixed_index_retrieval_stays_above_floor() {
+        const PRIMARY_DIMENSION: usize = 256;
+        const FALLBACK_DIMENSION: usize = 96;
+        let mut rng = TestRng::default();
+        let topics: Vec<Vec<f32>> = (0..25).map(|_| rng.vector(PRIMARY_DIMENSION)).collect();
+        let texts: Vec<(usize, Vec<f32>)> = (0..500)
+            .map(|i| (i % topics.len(), rng.near(&topics[i % topics.len()], 0.8)))
+            .collect();
+        let fallback = OtherModel::new(&mut rng, PRIMARY_DIMENSION, FALLBACK_DIMENSION);
+        let on_fallback = |i: usize| i % 10 < 3;
+
+        let queries: Vec<(usize, Vec<f32>)> = (0..20)
+            .map(|i| {
+                let topic = (i * 7) % topics.len();
+                (topic, rng.near(&topics[topic], 0.8))
+            })
+            .collect();
+
+        let recall = |ranking: &[(usize, f32)], topic: usize| {
+            ranking.iter().take(10).filter(|(doc, _)| texts[*doc].0 == topic).count()
+        };
+        let (mut primary_only, mut mixed, mut naive) = (0, 0, 0);
+        for (topic, query) in &queries {
+            let mut all: Vec<(usize, f32)> = texts.iter().enumerate().map(|(i, (_, text))| (i, cosine(query, text))).collect();
+            all.sort_by(|a, b| b.1.total_cmp(&a.1));
+            primary_only += recall(&all, *topic);
+
+            let primary_list: Vec<(usize, f32)> = all.iter().filter(|(i, _)| !on_fallback(*i)).copied().collect();
+            let query_fallback = fallback.embed(&mut rng, query);
+            let fallback_list: Vec<(usize, f32)> = (0..texts.len())
+                .filter(|i| on_fallback(*i))
+                .map(|i| (i, cosine(&query_fallback, &fallback.embed(&mut rng, &texts[i].1))))
+                .collect();
+
+            let mut concatenated: Vec<(usize, f32)> = primary_list.iter().chain(&fallback_list).copied().collect();
+            concatenated.sort_by(|a, b| b.1.total_cmp(&a.1));
+            naive += recall(&concatenated, *topic);
+            mixed += recall(&fuse(vec![primary_list, fallback_list]), *topic);
+        }
+
+        let ratio = mixed as f32 / primary_only as f32;
+        assert!(ratio >= MIXED_INDEX_RECALL_FLOOR, "mixed recall@10 {} of primary-only {}", ratio, primary_only);
+        // Without calibration the fallback's inflated similarities crowd out the primary's documents
+        assert!(naive < mixed, "naive {} vs fused {}", naive, mixed);
+    }
+}
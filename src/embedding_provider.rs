@@ -0,0 +1,242 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+
+use crate::config::Config;
+use crate::gemini_client::{GeminiClient, GeminiConfig};
+use crate::network::{http_client, Destination};
+use crate::ollama_client::{OllamaClient, OllamaConfig};
+use crate::openai_client::{OpenAIClient, OpenAIConfig};
+use crate::usage::{MeteredEmbeddings, UsageTracker};
+
+/// A model that turns text into vectors for semantic search
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+
+    /// Identifies the model in the vector database, e.g. `openai/text-embedding-3-small`.
+    /// Vectors with different ids are never compared.
+    fn model_id(&self) -> String;
+
+    /// Length of the vectors, when known before the first request
+    fn dimension(&self) -> Option<usize>;
+}
+
+/// The embedding model used when `[embeddings] model` isn't set
+pub fn default_model(provider: &str) -> &'static str {
+    match provider {
+        "openai" => "text-embedding-3-small",
+        "ollama" => "nomic-embed-text",
+        _ => "text-embedding-004",
+    }
+}
+
+/// Vector length of the models whose length is fixed
+fn known_dimension(provider: &str, model: &str) -> Option<usize> {
+    match (provider, model) {
+        ("openai", "text-embedding-3-small" | "text-embedding-ada-002") => Some(1536),
+        ("openai", "text-embedding-3-large") => Some(3072),
+        ("gemini", "text-embedding-004" | "embedding-001") => Some(768),
+        ("ollama", "nomic-embed-text") => Some(768),
+        ("ollama", "mxbai-embed-large") => Some(1024),
+        _ => None,
+    }
+}
+
+/// `[embeddings] provider`, or the LLM provider when it isn't set
+pub fn primary_provider(config: &Config) -> &str {
+    config.embeddings.provider.as_deref().unwrap_or(&config.llm.provider)
+}
+
+/// Provider and model of the primary embedding model, or with `fallback` of the fallback one
+fn provider_and_model(config: &Config, fallback: bool) -> Option<(&str, &str)> {
+    let (provider, model) = if fallback {
+        (config.embeddings.fallback_provider.as_deref()?, config.embeddings.fallback_model.as_deref())
+    } else {
+        (primary_provider(config), config.embeddings.model.as_deref())
+    };
+    Some((provider, model.unwrap_or_else(|| default_model(provider))))
+}
+
+/// Id of the configured primary embedding model; a change invalidates cached query embeddings
+pub fn embedding_model_id(config: &Config) -> String {
+    let (provider, model) = provider_and_model(config, false).expect("there is always a primary provider");
+    format!("{}/{}", provider, model)
+}
+
+/// A primary embedding provider and its optional fallback
+pub type EmbeddingProviders = (Box<dyn EmbeddingProvider>, Option<Box<dyn EmbeddingProvider>>);
+
+/// The configured embedding model and, if `[embeddings] fallback_provider` is set, the one
+/// indexing switches to when the first runs out of quota. Both count their requests in the
+/// usage ledger.
+pub fn create_embedding_providers(config: &Config) -> Result<EmbeddingProviders> {
+    let (provider, model) = provider_and_model(config, false).expect("there is always a primary provider");
+    let primary = metered(config, provider, create_embedding_provider(config, provider, model, config.embeddings.dimension)?);
+    let fallback = match provider_and_model(config, true) {
+        Some((fallback, _)) if fallback == provider => {
+            bail!("[embeddings] fallback_provider must differ from the embedding provider '{}'", provider)
+        }
+        Some((provider, model)) => Some(metered(config, provider, create_embedding_provider(config, provider, model, None)?)),
+        None => None,
+    };
+    Ok((primary, fallback))
+}
+
+fn metered(config: &Config, provider: &str, embeddings: Box<dyn EmbeddingProvider>) -> Box<dyn EmbeddingProvider> {
+    if !config.usage.enabled {
+        return embeddings;
+    }
+    Box::new(MeteredEmbeddings::new(embeddings, provider, UsageTracker::from_settings(&config.usage)))
+}
+
+/// An embedding provider using the API key of `[llm.<provider>]`, or for Ollama its
+/// `base_url`. `dimension` asks models that can shorten their vectors for that length.
+pub fn create_embedding_provider(config: &Config, provider: &str, model: &str, dimension: Option<usize>) -> Result<Box<dyn EmbeddingProvider>> {
+    config.ensure_online("embedding")?;
+    let http = http_client(&config.network, Destination::Llm)?;
+    let model = model.to_string();
+    match provider {
+        "openai" => {
+            let api_key = config.llm.openai.api_key.clone()
+                .ok_or_else(|| anyhow::anyhow!("Missing OpenAI API key for embeddings"))?;
+            let client = OpenAIClient::new(OpenAIConfig { api_key, ..Default::default() })?.with_http_client(http);
+            Ok(Box::new(OpenAIEmbeddings { client, model, dimension }))
+        }
+        "gemini" => {
+            let api_key = config.llm.gemini.api_key.clone()
+                .ok_or_else(|| anyhow::anyhow!("Missing Gemini API key for embeddings"))?;
+            let client = GeminiClient::new(GeminiConfig { api_key, ..Default::default() })?.with_http_client(http);
+            Ok(Box::new(GeminiEmbeddings { client, model, dimension }))
+        }
+        "ollama" => {
+            let base_url = config.llm.ollama.base_url.clone();
+            let client = OllamaClient::new(OllamaConfig { base_url, ..Default::default() }).with_http_client(http);
+            Ok(Box::new(OllamaEmbeddings { client, model, dimension }))
+        }
+        "anthropic" => bail!("Anthropic has no embeddings API; set [embeddings] provider to gemini, openai or ollama"),
+        provider => bail!("Unsupported embedding provider: {}", provider),
+    }
+}
+
+/// The OpenAI `/embeddings` endpoint
+pub struct OpenAIEmbeddings {
+    client: OpenAIClient,
+    model: String,
+    dimension: Option<usize>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAIEmbeddings {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.client.embed(&self.model, text, self.dimension).await
+    }
+
+    fn model_id(&self) -> String {
+        format!("openai/{}", self.model)
+    }
+
+    fn dimension(&self) -> Option<usize> {
+        self.dimension.or_else(|| known_dimension("openai", &self.model))
+    }
+}
+
+/// The Gemini `embedContent` endpoint
+pub struct GeminiEmbeddings {
+    client: GeminiClient,
+    model: String,
+    dimension: Option<usize>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for GeminiEmbeddings {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.client.embed(&self.model, text, self.dimension).await
+    }
+
+    fn model_id(&self) -> String {
+        format!("gemini/{}", self.model)
+    }
+
+    fn dimension(&self) -> Option<usize> {
+        self.dimension.or_else(|| known_dimension("gemini", &self.model))
+    }
+}
+
+/// The Ollama `/api/embeddings` endpoint of a local server
+pub struct OllamaEmbeddings {
+    client: OllamaClient,
+    model: String,
+    dimension: Option<usize>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddings {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.client.embed(&self.model, text).await
+    }
+
+    fn model_id(&self) -> String {
+        format!("ollama/{}", self.model)
+    }
+
+    fn dimension(&self) -> Option<usize> {
+        self.dimension.or_else(|| known_dimension("ollama", &self.model))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(provider: &str) -> Config {
+        let mut config = Config::default();
+        config.llm.provider = provider.to_string();
+        config.llm.openai.api_key = Some("sk-test".to_string());
+        config.llm.gemini.api_key = Some("gm-test".to_string());
+        config
+    }
+
+    #[test]
+    fn test_providers_follow_the_llm_provider_unless_set() {
+        let mut config = config("openai");
+        let (primary, fallback) = create_embedding_providers(&config).unwrap();
+        assert_eq!(primary.model_id(), "openai/text-embedding-3-small");
+        assert_eq!(primary.dimension(), Some(1536));
+        assert!(fallback.is_none());
+
+        config.embeddings.provider = Some("gemini".to_string());
+        config.embeddings.fallback_provider = Some("openai".to_string());
+        config.embeddings.fallback_model = Some("text-embedding-3-large".to_string());
+        let (primary, fallback) = create_embedding_providers(&config).unwrap();
+        assert_eq!((primary.model_id(), primary.dimension()), ("gemini/text-embedding-004".to_string(), Some(768)));
+        let fallback = fallback.unwrap();
+        assert_eq!((fallback.model_id(), fallback.dimension()), ("openai/text-embedding-3-large".to_string(), Some(3072)));
+        assert_eq!(embedding_model_id(&config), "gemini/text-embedding-004");
+    }
+
+    #[test]
+    fn test_configured_dimension_and_unknown_models() {
+        let mut config = config("gemini");
+        config.embeddings.model = Some("gemini-embedding-001".to_string());
+        let (primary, _) = create_embedding_providers(&config).unwrap();
+        // Known only once the first vector comes back
+        assert_eq!(primary.dimension(), None);
+        config.embeddings.dimension = Some(1024);
+        let (primary, _) = create_embedding_providers(&config).unwrap();
+        assert_eq!(primary.dimension(), Some(1024));
+
+        config.embeddings.fallback_provider = Some("gemini".to_string());
+        assert!(create_embedding_providers(&config).is_err());
+        config.embeddings.fallback_provider = Some("anthropic".to_string());
+        assert!(create_embedding_providers(&config).is_err());
+    }
+
+    #[test]
+    fn test_ollama_embeds_locally_without_a_key() {
+        let mut config = config("ollama");
+        config.llm.openai.api_key = None;
+        config.llm.gemini.api_key = None;
+        let (primary, _) = create_embedding_providers(&config).unwrap();
+        assert_eq!((primary.model_id(), primary.dimension()), ("ollama/nomic-embed-text".to_string(), Some(768)));
+    }
+}
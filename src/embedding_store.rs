@@ -0,0 +1,841 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use half::f16;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+
+use crate::ann_index::AnnIndex;
+use crate::obsidian_adapter::{DocumentEmbedding, DocumentMetadata, VectorDatabase};
+
+/// Version written to `VectorDatabase::version` by the segmented store. Stores without
+/// the magic header are the legacy 1.x single-bincode format and are migrated on load.
+pub const VECTOR_DATABASE_VERSION: &str = "3.0.0";
+
+const STORE_MAGIC: &[u8; 4] = b"AHVS";
+/// Format 5 adds the approximate nearest neighbor index after the hot cache. Format 4
+/// holds each path once, in path order. Format 3 records the model of each embedding but
+/// may repeat a path; format 2 predates models. All three are read and rewritten.
+const STORE_FORMAT: u32 = 5;
+const UNINDEXED_STORE_FORMAT: u32 = 4;
+const UNKEYED_STORE_FORMAT: u32 = 3;
+const UNTAGGED_STORE_FORMAT: u32 = 2;
+/// Documents per compressed segment
+const SEGMENT_SIZE: usize = 256;
+const ZSTD_LEVEL: i32 = 3;
+
+/// How embedding vectors are stored on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum QuantizationMode {
+    /// Full precision, 4 bytes per dimension
+    Float32,
+    /// Half precision, 2 bytes per dimension
+    #[default]
+    Float16,
+    /// One byte per dimension plus a per-vector scale
+    Int8,
+}
+
+impl std::fmt::Display for QuantizationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            QuantizationMode::Float32 => "float32",
+            QuantizationMode::Float16 => "float16",
+            QuantizationMode::Int8 => "int8",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Embedding storage settings, the `[embeddings]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingStorageConfig {
+    /// Precision of vectors written to disk
+    pub quantization: QuantizationMode,
+    /// Number of recently accessed documents whose full-precision vectors are kept
+    /// for exact re-ranking
+    pub hot_cache_size: usize,
+    /// `gemini` or `openai`, using the key under `[llm.*]`. Defaults to the LLM provider.
+    pub provider: Option<String>,
+    /// Embedding model, e.g. `text-embedding-3-small`. Defaults to the provider's standard one.
+    pub model: Option<String>,
+    /// Vector length to request from models that can shorten their vectors
+    pub dimension: Option<usize>,
+    /// Provider that indexing switches to when the primary provider's quota runs out
+    pub fallback_provider: Option<String>,
+    /// Embedding model of the fallback provider
+    pub fallback_model: Option<String>,
+    /// Models a search query is embedded with when the index holds several
+    pub max_query_models: usize,
+    /// Documents of other models re-embedded with the primary per index run
+    pub reunify_batch: usize,
+    /// Search queries whose embeddings are kept for the rest of the session
+    pub query_cache_size: usize,
+    /// Documents of one model from which searches use the approximate index instead of
+    /// comparing the query with every document
+    pub ann_threshold: usize,
+    /// Index lists scanned per query; higher finds more of the exact results
+    pub ann_probes: usize,
+    /// Index lists per model; defaults to the square root of its document count
+    pub ann_lists: Option<usize>,
+    /// Fraction of documents changed since the index was built that triggers a rebuild
+    pub ann_rebuild_fraction: f32,
+}
+
+impl Default for EmbeddingStorageConfig {
+    fn default() -> Self {
+        Self {
+            quantization: QuantizationMode::default(),
+            hot_cache_size: 64,
+            provider: None,
+            model: None,
+            dimension: None,
+            fallback_provider: None,
+            fallback_model: None,
+            max_query_models: 2,
+            reunify_batch: 25,
+            query_cache_size: 128,
+            ann_threshold: 2000,
+            ann_probes: 8,
+            ann_lists: None,
+            ann_rebuild_fraction: 0.2,
+        }
+    }
+}
+
+/// A vector in its on-disk representation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum QuantizedVector {
+    Float32(Vec<f32>),
+    Float16(Vec<u16>),
+    Int8 { scale: f32, values: Vec<i8> },
+}
+
+impl QuantizedVector {
+    pub fn quantize(vector: &[f32], mode: QuantizationMode) -> Self {
+        match mode {
+            QuantizationMode::Float32 => QuantizedVector::Float32(vector.to_vec()),
+            QuantizationMode::Float16 => {
+                QuantizedVector::Float16(vector.iter().map(|v| f16::from_f32(*v).to_bits()).collect())
+            }
+            QuantizationMode::Int8 => {
+                let max = vector.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+                let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+                let values = vector.iter().map(|v| (v / scale).round().clamp(-127.0, 127.0) as i8).collect();
+                QuantizedVector::Int8 { scale, values }
+            }
+        }
+    }
+
+    pub fn dequantize(&self) -> Vec<f32> {
+        match self {
+            QuantizedVector::Float32(values) => values.clone(),
+            QuantizedVector::Float16(values) => values.iter().map(|v| f16::from_bits(*v).to_f32()).collect(),
+            QuantizedVector::Int8 { scale, values } => values.iter().map(|v| *v as f32 * scale).collect(),
+        }
+    }
+}
+
+/// Full-precision vectors for the most recently accessed documents. Everything else
+/// is held at the store's quantized precision, so these are used to re-rank the top of
+/// a search exactly.
+#[derive(Debug, Clone)]
+pub struct HotVectorCache {
+    capacity: usize,
+    /// Least recently used first
+    order: VecDeque<String>,
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+impl HotVectorCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, order: VecDeque::new(), vectors: HashMap::new() }
+    }
+
+    pub fn insert(&mut self, path: &str, vector: Vec<f32>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.vectors.insert(path.to_string(), vector);
+        self.touch(path);
+        self.evict();
+    }
+
+    /// Mark a cached document as recently used
+    pub fn touch(&mut self, path: &str) {
+        if !self.vectors.contains_key(path) {
+            return;
+        }
+        self.order.retain(|p| p != path);
+        self.order.push_back(path.to_string());
+    }
+
+    pub fn get(&self, path: &str) -> Option<&[f32]> {
+        self.vectors.get(path).map(Vec::as_slice)
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        self.vectors.remove(path);
+        self.order.retain(|p| p != path);
+    }
+
+    /// Keep a cached vector, and its place in the eviction order, under a new path
+    pub fn rename(&mut self, from: &str, to: &str) {
+        if let Some(vector) = self.vectors.remove(from) {
+            self.vectors.insert(to.to_string(), vector);
+            for path in self.order.iter_mut().filter(|p| *p == from) {
+                *path = to.to_string();
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.vectors.clear();
+        self.order.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// Entries from least to most recently used
+    fn entries(&self) -> Vec<(String, Vec<f32>)> {
+        self.order.iter()
+            .filter_map(|path| self.vectors.get(path).map(|v| (path.clone(), v.clone())))
+            .collect()
+    }
+
+    fn evict(&mut self) {
+        while self.order.len() > self.capacity {
+            if let Some(path) = self.order.pop_front() {
+                self.vectors.remove(&path);
+            }
+        }
+    }
+}
+
+impl Default for HotVectorCache {
+    fn default() -> Self {
+        Self::new(EmbeddingStorageConfig::default().hot_cache_size)
+    }
+}
+
+/// Embeddings of recent search queries per model, so repeating a search in the same
+/// session doesn't call the embedding API again. Queries differing only in case or
+/// spacing share an entry.
+#[derive(Debug, Clone)]
+pub struct QueryEmbeddingCache {
+    capacity: usize,
+    /// Least recently used first
+    order: VecDeque<(String, String)>,
+    vectors: HashMap<(String, String), Vec<f32>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl QueryEmbeddingCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, order: VecDeque::new(), vectors: HashMap::new(), hits: 0, misses: 0 }
+    }
+
+    /// Lowercased with runs of whitespace collapsed to one space
+    pub fn normalize(query: &str) -> String {
+        query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+    }
+
+    /// The cached embedding of `query` by `model`, counted as a hit or a miss
+    pub fn get(&mut self, model: &str, query: &str) -> Option<Vec<f32>> {
+        let key = (model.to_string(), Self::normalize(query));
+        match self.vectors.get(&key) {
+            Some(vector) => {
+                let vector = vector.clone();
+                self.hits += 1;
+                self.order.retain(|k| *k != key);
+                self.order.push_back(key);
+                Some(vector)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, model: &str, query: &str, vector: Vec<f32>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = (model.to_string(), Self::normalize(query));
+        self.order.retain(|k| *k != key);
+        self.order.push_back(key.clone());
+        self.vectors.insert(key, vector);
+        self.evict();
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+    fn evict(&mut self) {
+        while self.order.len() > self.capacity {
+            if let Some(key) = self.order.pop_front() {
+                self.vectors.remove(&key);
+            }
+        }
+    }
+}
+
+impl Default for QueryEmbeddingCache {
+    fn default() -> Self {
+        Self::new(EmbeddingStorageConfig::default().query_cache_size)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredEmbedding {
+    path: String,
+    vector: QuantizedVector,
+    content_hash: String,
+    created_at: DateTime<Utc>,
+    metadata: DocumentMetadata,
+    model: String,
+}
+
+/// The 1.x layout: the whole database as one bincode value, with positions into the
+/// list that could go stale when an embedding was removed
+#[derive(Debug, Serialize, Deserialize)]
+struct LegacyVectorDatabase {
+    embeddings: Vec<DocumentEmbedding>,
+    /// Ignored; the database is keyed by each embedding's own path instead
+    #[allow(dead_code)]
+    path_index: HashMap<String, usize>,
+    version: String,
+    last_updated: DateTime<Utc>,
+}
+
+/// A format 2 segment entry, from before embeddings recorded their model
+#[derive(Debug, Serialize, Deserialize)]
+struct UntaggedStoredEmbedding {
+    path: String,
+    vector: QuantizedVector,
+    content_hash: String,
+    created_at: DateTime<Utc>,
+    metadata: DocumentMetadata,
+}
+
+/// Uncompressed header describing the segments that follow it
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreHeader {
+    database_version: String,
+    last_updated: DateTime<Utc>,
+    quantization: QuantizationMode,
+    document_count: usize,
+    segment_lengths: Vec<u64>,
+    hot_segment_length: u64,
+    ann_segment_length: u64,
+}
+
+/// The header of formats 2 to 4, which end with the hot cache
+#[derive(Debug, Serialize, Deserialize)]
+struct UnindexedStoreHeader {
+    database_version: String,
+    last_updated: DateTime<Utc>,
+    quantization: QuantizationMode,
+    document_count: usize,
+    segment_lengths: Vec<u64>,
+    hot_segment_length: u64,
+}
+
+impl From<UnindexedStoreHeader> for StoreHeader {
+    fn from(header: UnindexedStoreHeader) -> Self {
+        Self {
+            database_version: header.database_version,
+            last_updated: header.last_updated,
+            quantization: header.quantization,
+            document_count: header.document_count,
+            segment_lengths: header.segment_lengths,
+            hot_segment_length: header.hot_segment_length,
+            ann_segment_length: 0,
+        }
+    }
+}
+
+/// A store file whose header has been parsed; segments stay compressed until read
+pub struct StoreReader {
+    format: u32,
+    header: StoreHeader,
+    data: Vec<u8>,
+    segment_offsets: Vec<(usize, usize)>,
+    hot_segment: (usize, usize),
+    ann_segment: (usize, usize),
+}
+
+impl StoreReader {
+    pub fn open(data: Vec<u8>) -> Result<Self> {
+        if data.len() < 16 || &data[..4] != STORE_MAGIC {
+            bail!("Not a segmented embedding store");
+        }
+        let format = u32::from_le_bytes(data[4..8].try_into()?);
+        if ![STORE_FORMAT, UNINDEXED_STORE_FORMAT, UNKEYED_STORE_FORMAT, UNTAGGED_STORE_FORMAT].contains(&format) {
+            bail!("Unsupported embedding store format {}", format);
+        }
+        let header_len = u64::from_le_bytes(data[8..16].try_into()?) as usize;
+        let header_end = 16 + header_len;
+        let header_bytes = data.get(16..header_end).context("Truncated embedding store header")?;
+        let header: StoreHeader = if format == STORE_FORMAT {
+            bincode::deserialize(header_bytes)
+        } else {
+            bincode::deserialize::<UnindexedStoreHeader>(header_bytes).map(StoreHeader::from)
+        }.context("Failed to deserialize embedding store header")?;
+
+        let mut offset = header_end;
+        let mut segment_offsets = Vec::new();
+        for length in &header.segment_lengths {
+            segment_offsets.push((offset, offset + *length as usize));
+            offset += *length as usize;
+        }
+        let hot_segment = (offset, offset + header.hot_segment_length as usize);
+        let ann_segment = (hot_segment.1, hot_segment.1 + header.ann_segment_length as usize);
+        if ann_segment.1 != data.len() {
+            bail!("Embedding store is truncated or has trailing data");
+        }
+
+        Ok(Self { format, header, data, segment_offsets, hot_segment, ann_segment })
+    }
+
+    /// Whether the store predates model tags
+    pub fn is_untagged(&self) -> bool {
+        self.format == UNTAGGED_STORE_FORMAT
+    }
+
+    /// Whether the store is in an older format and should be rewritten
+    pub fn is_outdated(&self) -> bool {
+        self.format < STORE_FORMAT
+    }
+
+    pub fn quantization(&self) -> QuantizationMode {
+        self.header.quantization
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.header.document_count
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segment_offsets.len()
+    }
+
+    /// Decompress one segment into dequantized embeddings
+    pub fn read_segment(&self, index: usize) -> Result<Vec<DocumentEmbedding>> {
+        let (start, end) = *self.segment_offsets.get(index).context("Segment index out of range")?;
+        let segment = &self.data[start..end];
+        let stored: Vec<StoredEmbedding> = if self.is_untagged() {
+            decompress::<Vec<UntaggedStoredEmbedding>>(segment)?.into_iter().map(|doc| StoredEmbedding {
+                path: doc.path,
+                vector: doc.vector,
+                content_hash: doc.content_hash,
+                created_at: doc.created_at,
+                metadata: doc.metadata,
+                model: String::new(),
+            }).collect()
+        } else {
+            decompress(segment)?
+        };
+        Ok(stored.into_iter().map(|doc| DocumentEmbedding {
+            path: doc.path,
+            embedding: doc.vector.dequantize(),
+            content_hash: doc.content_hash,
+            created_at: doc.created_at,
+            metadata: doc.metadata,
+            model: doc.model,
+        }).collect())
+    }
+
+    /// Full-precision vectors of the hot cache, least recently used first
+    pub fn read_hot_vectors(&self) -> Result<Vec<(String, Vec<f32>)>> {
+        decompress(&self.data[self.hot_segment.0..self.hot_segment.1])
+    }
+
+    /// The stored approximate nearest neighbor index; stores written before it have none
+    pub fn read_ann_index(&self) -> Result<Option<AnnIndex>> {
+        if self.ann_segment.0 == self.ann_segment.1 {
+            return Ok(None);
+        }
+        decompress(&self.data[self.ann_segment.0..self.ann_segment.1]).map(Some)
+    }
+
+    /// Decompress every segment into an in-memory database. A path repeated in an older
+    /// store keeps its newest embedding.
+    pub fn read_database(&self) -> Result<VectorDatabase> {
+        let mut embeddings = Vec::with_capacity(self.header.document_count);
+        for index in 0..self.segment_count() {
+            embeddings.extend(self.read_segment(index)?);
+        }
+        let version = if self.is_outdated() { VECTOR_DATABASE_VERSION } else { &self.header.database_version };
+        Ok(VectorDatabase::from_documents(embeddings, version, self.header.last_updated))
+    }
+}
+
+/// A database and hot cache loaded from disk
+pub struct LoadedStore {
+    pub database: VectorDatabase,
+    pub hot_vectors: Vec<(String, Vec<f32>)>,
+    pub ann_index: Option<AnnIndex>,
+    /// True when the file was in an older format and should be rewritten
+    pub migrated: bool,
+}
+
+/// Serialize the database in the segmented format, quantizing every vector
+pub fn encode_store(database: &VectorDatabase, hot: &HotVectorCache, ann: &AnnIndex, mode: QuantizationMode) -> Result<Vec<u8>> {
+    let documents: Vec<&DocumentEmbedding> = database.documents().collect();
+    let mut segments = Vec::new();
+    for chunk in documents.chunks(SEGMENT_SIZE) {
+        let stored: Vec<StoredEmbedding> = chunk.iter().map(|doc| StoredEmbedding {
+            path: doc.path.clone(),
+            vector: QuantizedVector::quantize(&doc.embedding, mode),
+            content_hash: doc.content_hash.clone(),
+            created_at: doc.created_at,
+            metadata: doc.metadata.clone(),
+            model: doc.model.clone(),
+        }).collect();
+        segments.push(compress(&stored)?);
+    }
+    let hot_segment = compress(&hot.entries())?;
+    let ann_segment = compress(ann)?;
+
+    let header = StoreHeader {
+        database_version: VECTOR_DATABASE_VERSION.to_string(),
+        last_updated: database.last_updated,
+        quantization: mode,
+        document_count: database.len(),
+        segment_lengths: segments.iter().map(|s| s.len() as u64).collect(),
+        hot_segment_length: hot_segment.len() as u64,
+        ann_segment_length: ann_segment.len() as u64,
+    };
+    let header_bytes = bincode::serialize(&header).context("Failed to serialize embedding store header")?;
+
+    let mut out = Vec::with_capacity(16 + header_bytes.len() + segments.iter().map(Vec::len).sum::<usize>() + hot_segment.len() + ann_segment.len());
+    out.extend_from_slice(STORE_MAGIC);
+    out.extend_from_slice(&STORE_FORMAT.to_le_bytes());
+    out.extend_from_slice(&(header_bytes.len() as u64).to_le_bytes());
+    out.extend_from_slice(&header_bytes);
+    for segment in &segments {
+        out.extend_from_slice(segment);
+    }
+    out.extend_from_slice(&hot_segment);
+    out.extend_from_slice(&ann_segment);
+    Ok(out)
+}
+
+/// Parse store bytes, migrating the legacy single-bincode format
+pub fn decode_store(data: Vec<u8>) -> Result<LoadedStore> {
+    if !data.starts_with(STORE_MAGIC) {
+        let legacy: LegacyVectorDatabase = bincode::deserialize(&data)
+            .context("Failed to deserialize vector database")?;
+        let database = VectorDatabase::from_documents(legacy.embeddings, VECTOR_DATABASE_VERSION, legacy.last_updated);
+        return Ok(LoadedStore { database, hot_vectors: Vec::new(), ann_index: None, migrated: true });
+    }
+
+    let reader = StoreReader::open(data)?;
+    Ok(LoadedStore {
+        database: reader.read_database()?,
+        hot_vectors: reader.read_hot_vectors()?,
+        ann_index: reader.read_ann_index()?,
+        migrated: reader.is_outdated(),
+    })
+}
+
+pub fn write_store(path: &Path, database: &VectorDatabase, hot: &HotVectorCache, ann: &AnnIndex, mode: QuantizationMode) -> Result<()> {
+    let data = encode_store(database, hot, ann, mode)?;
+    crate::cache::write_atomic(path, &data).context("Failed to write embedding cache file")
+}
+
+pub fn read_store(path: &Path) -> Result<LoadedStore> {
+    let data = fs::read(path).context("Failed to read embedding cache file")?;
+    decode_store(data)
+}
+
+fn compress<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let raw = bincode::serialize(value).context("Failed to serialize embedding segment")?;
+    zstd::encode_all(raw.as_slice(), ZSTD_LEVEL).context("Failed to compress embedding segment")
+}
+
+fn decompress<T: serde::de::DeserializeOwned>(data: &[u8]) -> Result<T> {
+    let raw = zstd::decode_all(data).context("Failed to decompress embedding segment")?;
+    bincode::deserialize(&raw).context("Failed to deserialize embedding segment")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRng;
+
+    const DIMENSION: usize = 768;
+
+    /// Synthetic corpus: documents drawn around 50 topic centers, queries near a center
+    fn corpus(size: usize) -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+        let mut rng = TestRng::default();
+        let centers: Vec<Vec<f32>> = (0..50).map(|_| rng.vector(DIMENSION)).collect();
+        let documents = (0..size)
+            .map(|i| rng.near(&centers[i % centers.len()], 0.8))
+            .collect();
+        let queries = (0..20)
+            .map(|i| rng.near(&centers[(i * 7) % centers.len()], 0.8))
+            .collect();
+        (documents, queries)
+    }
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot / (norm(a) * norm(b))
+    }
+
+    fn top_k(query: &[f32], documents: &[Vec<f32>], k: usize) -> Vec<usize> {
+        let mut scored: Vec<(usize, f32)> = documents.iter().enumerate().map(|(i, d)| (i, cosine(query, d))).collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().take(k).map(|(i, _)| i).collect()
+    }
+
+    fn recall_at_10(documents: &[Vec<f32>], queries: &[Vec<f32>], exact: &[Vec<usize>], mode: QuantizationMode) -> f32 {
+        let quantized: Vec<Vec<f32>> = documents.iter().map(|d| QuantizedVector::quantize(d, mode).dequantize()).collect();
+        let hits: usize = queries.iter().zip(exact).map(|(query, exact)| {
+            top_k(query, &quantized, 10).iter().filter(|i| exact.contains(i)).count()
+        }).sum();
+        hits as f32 / (queries.len() * 10) as f32
+    }
+
+    fn database(documents: &[Vec<f32>]) -> VectorDatabase {
+        let embeddings: Vec<DocumentEmbedding> = documents.iter().enumerate().map(|(i, vector)| DocumentEmbedding {
+            path: format!("Notes/note-{}.md", i),
+            embedding: vector.clone(),
+            content_hash: format!("{:x}", i),
+            created_at: Utc::now(),
+            metadata: DocumentMetadata {
+                title: format!("Note {}", i),
+                tags: vec!["synthetic".to_string()],
+                length: 100,
+                excerpt: String::new(),
+                modified_at: None,
+                skeleton: None,
+            },
+            model: if i % 3 == 0 { "gemini/text-embedding-004".to_string() } else { String::new() },
+        }).collect();
+        VectorDatabase::from_documents(embeddings, "1.0.0", Utc::now())
+    }
+
+    /// The database in the 1.x single-bincode layout
+    fn legacy_bytes(db: &VectorDatabase) -> Vec<u8> {
+        let embeddings: Vec<DocumentEmbedding> = db.documents().cloned().collect();
+        let path_index = embeddings.iter().enumerate().map(|(i, doc)| (doc.path.clone(), i)).collect();
+        bincode::serialize(&LegacyVectorDatabase { embeddings, path_index, version: "1.0.0".to_string(), last_updated: db.last_updated }).unwrap()
+    }
+
+    /// A segmented store of one segment in an older `format`
+    fn store_bytes<T: Serialize>(format: u32, stored: &[T], last_updated: DateTime<Utc>) -> Vec<u8> {
+        let segment = compress(&stored).unwrap();
+        let hot = compress(&Vec::<(String, Vec<f32>)>::new()).unwrap();
+        let header = bincode::serialize(&UnindexedStoreHeader {
+            database_version: "2.0.0".to_string(),
+            last_updated,
+            quantization: QuantizationMode::Float32,
+            document_count: stored.len(),
+            segment_lengths: vec![segment.len() as u64],
+            hot_segment_length: hot.len() as u64,
+        }).unwrap();
+        let mut data = STORE_MAGIC.to_vec();
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&(header.len() as u64).to_le_bytes());
+        data.extend_from_slice(&header);
+        data.extend_from_slice(&segment);
+        data.extend_from_slice(&hot);
+        data
+    }
+
+    fn stored(doc: &DocumentEmbedding) -> StoredEmbedding {
+        StoredEmbedding {
+            path: doc.path.clone(),
+            vector: QuantizedVector::quantize(&doc.embedding, QuantizationMode::Float32),
+            content_hash: doc.content_hash.clone(),
+            created_at: doc.created_at,
+            metadata: doc.metadata.clone(),
+            model: doc.model.clone(),
+        }
+    }
+
+    #[test]
+    fn test_recall_within_two_percent_of_float32() {
+        let (documents, queries) = corpus(1000);
+        let exact: Vec<Vec<usize>> = queries.iter().map(|query| top_k(query, &documents, 10)).collect();
+
+        assert_eq!(recall_at_10(&documents, &queries, &exact, QuantizationMode::Float32), 1.0);
+        let float16 = recall_at_10(&documents, &queries, &exact, QuantizationMode::Float16);
+        let int8 = recall_at_10(&documents, &queries, &exact, QuantizationMode::Int8);
+        assert!(float16 >= 0.98, "float16 recall@10 {}", float16);
+        assert!(int8 >= 0.98, "int8 recall@10 {}", int8);
+    }
+
+    #[test]
+    fn test_round_trip_and_size() {
+        let (documents, _) = corpus(600);
+        let db = database(&documents);
+        let mut hot = HotVectorCache::new(2);
+        hot.insert("Notes/note-1.md", documents[1].clone());
+        hot.insert("Notes/note-2.md", documents[2].clone());
+        hot.insert("Notes/note-3.md", documents[3].clone());
+
+        let legacy = legacy_bytes(&db);
+        let float16 = encode_store(&db, &hot, &AnnIndex::default(), QuantizationMode::Float16).unwrap();
+        let int8 = encode_store(&db, &hot, &AnnIndex::default(), QuantizationMode::Int8).unwrap();
+        assert!(float16.len() * 10 < legacy.len() * 6, "float16 {} vs legacy {}", float16.len(), legacy.len());
+        assert!(int8.len() * 10 < legacy.len() * 4, "int8 {} vs legacy {}", int8.len(), legacy.len());
+
+        let reader = StoreReader::open(int8).unwrap();
+        assert_eq!(reader.quantization(), QuantizationMode::Int8);
+        assert_eq!(reader.document_count(), 600);
+        assert_eq!(reader.segment_count(), 3);
+        // Segments are in path order
+        let mut paths: Vec<String> = (0..600).map(|i| format!("Notes/note-{}.md", i)).collect();
+        paths.sort();
+        assert_eq!(reader.read_segment(2).unwrap()[0].path, paths[512]);
+
+        let loaded = decode_store(float16).unwrap();
+        assert!(!loaded.migrated);
+        assert_eq!(loaded.database.version, VECTOR_DATABASE_VERSION);
+        assert_eq!(loaded.database.get("Notes/note-599.md").unwrap().content_hash, format!("{:x}", 599));
+        let hot_paths: Vec<&str> = loaded.hot_vectors.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(hot_paths, vec!["Notes/note-2.md", "Notes/note-3.md"]);
+        assert_eq!(loaded.hot_vectors[1].1, documents[3]);
+        assert_eq!(loaded.database.get("Notes/note-3.md").unwrap().model, "gemini/text-embedding-004");
+        assert_eq!(loaded.database.get("Notes/note-4.md").unwrap().model, "");
+    }
+
+    #[test]
+    fn test_legacy_store_is_migrated() {
+        let (documents, _) = corpus(5);
+        let db = database(&documents);
+        let loaded = decode_store(legacy_bytes(&db)).unwrap();
+        assert!(loaded.migrated);
+        assert_eq!(loaded.database.version, VECTOR_DATABASE_VERSION);
+        assert_eq!(loaded.database.get("Notes/note-4.md").unwrap().embedding, documents[4]);
+    }
+
+    #[test]
+    fn test_unkeyed_store_keeps_the_newest_of_repeated_paths() {
+        let (documents, _) = corpus(3);
+        let db = database(&documents);
+        let mut docs: Vec<DocumentEmbedding> = db.documents().cloned().collect();
+        // A stale index used to let a second embedding of a path pile up in the list
+        let mut older = docs[1].clone();
+        older.created_at = docs[1].created_at - chrono::Duration::days(1);
+        older.embedding = documents[0].clone();
+        docs.insert(0, older);
+        let entries: Vec<StoredEmbedding> = docs.iter().map(stored).collect();
+
+        let loaded = decode_store(store_bytes(UNKEYED_STORE_FORMAT, &entries, db.last_updated)).unwrap();
+        assert!(loaded.migrated);
+        assert_eq!(loaded.database.version, VECTOR_DATABASE_VERSION);
+        assert_eq!(loaded.database.len(), 3);
+        assert_eq!(loaded.database.get(&docs[2].path).unwrap().embedding, docs[2].embedding);
+        assert!(loaded.database.documents().all(|doc| loaded.database.get(&doc.path).is_some_and(|d| d.path == doc.path)));
+
+        let rewritten = decode_store(encode_store(&loaded.database, &HotVectorCache::new(0), &AnnIndex::default(), QuantizationMode::Float32).unwrap()).unwrap();
+        assert!(!rewritten.migrated);
+        assert_eq!(rewritten.database.paths().collect::<Vec<_>>(), loaded.database.paths().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_ann_index_is_stored_with_the_vectors() {
+        let (documents, queries) = corpus(300);
+        let db = database(&documents);
+        let entries: Vec<(&str, &str, &[f32])> = db.documents().map(|doc| ("m", doc.path.as_str(), doc.embedding.as_slice())).collect();
+        let settings = crate::ann_index::AnnSettings { threshold: 100, probes: 2, lists: Some(8), rebuild_fraction: 0.2 };
+        let mut ann = AnnIndex::default();
+        ann.refresh(&entries, &settings);
+
+        let loaded = decode_store(encode_store(&db, &HotVectorCache::new(0), &ann, QuantizationMode::Float16).unwrap()).unwrap();
+        let read_back = loaded.ann_index.unwrap();
+        assert_eq!(read_back.candidates("m", &queries[0], 2), ann.candidates("m", &queries[0], 2));
+
+        // A format 4 store has no index yet and is rewritten with one
+        let entries: Vec<StoredEmbedding> = db.documents().map(stored).collect();
+        let loaded = decode_store(store_bytes(UNINDEXED_STORE_FORMAT, &entries, db.last_updated)).unwrap();
+        assert!(loaded.migrated && loaded.ann_index.is_none());
+        assert_eq!(loaded.database.len(), 300);
+    }
+
+    #[test]
+    fn test_untagged_store_is_read_and_rewritten() {
+        let (documents, _) = corpus(3);
+        let db = database(&documents);
+        let stored: Vec<UntaggedStoredEmbedding> = db.documents().map(|doc| UntaggedStoredEmbedding {
+            path: doc.path.clone(),
+            vector: QuantizedVector::quantize(&doc.embedding, QuantizationMode::Float32),
+            content_hash: doc.content_hash.clone(),
+            created_at: doc.created_at,
+            metadata: doc.metadata.clone(),
+        }).collect();
+
+        let loaded = decode_store(store_bytes(UNTAGGED_STORE_FORMAT, &stored, db.last_updated)).unwrap();
+        assert!(loaded.migrated);
+        assert_eq!(loaded.database.get("Notes/note-2.md").unwrap().embedding, documents[2]);
+        assert!(loaded.database.documents().all(|doc| doc.model.is_empty()));
+    }
+
+    #[test]
+    fn test_hot_cache_evicts_least_recently_used() {
+        let mut cache = HotVectorCache::new(2);
+        cache.insert("a", vec![1.0]);
+        cache.insert("b", vec![2.0]);
+        cache.touch("a");
+        cache.insert("c", vec![3.0]);
+        assert!(cache.get("b").is_none());
+        assert_eq!(cache.get("a"), Some(&[1.0][..]));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_query_cache_normalizes_and_counts() {
+        let mut cache = QueryEmbeddingCache::new(2);
+        assert!(cache.get("m", "Launch plans").is_none());
+        cache.insert("m", "Launch plans", vec![1.0]);
+        assert_eq!(cache.get("m", "  launch   PLANS "), Some(vec![1.0]));
+        // Each model has its own entry for the same query
+        assert!(cache.get("other", "launch plans").is_none());
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+
+        cache.insert("m", "b", vec![2.0]);
+        cache.get("m", "launch plans");
+        cache.insert("m", "c", vec![3.0]);
+        assert!(cache.get("m", "b").is_none());
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+/// Failures callers act on: retried, reported with a hint, or turned into an exit code.
+/// Raised where the HTTP status or connection error is first seen and carried up inside
+/// `anyhow::Error`, so context added on the way doesn't hide it (see `ArrowheadError::of`).
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ArrowheadError {
+    /// Too many requests for now; waiting `retry_after` (when the provider says) helps
+    #[error("{provider} rate limit reached")]
+    RateLimited { provider: String, retry_after: Option<Duration> },
+    /// Out of quota or credit until the billing period resets; waiting won't help
+    #[error("{provider} quota exhausted")]
+    QuotaExhausted { provider: String },
+    /// Overloaded, failing or unreachable; `status` is `None` when no response came back
+    #[error("{provider} API request failed: {}", status_and_detail(*status, detail))]
+    ServiceUnavailable { provider: String, status: Option<u16>, detail: String },
+    #[error("{provider} rejected the API key ({status})")]
+    AuthFailed { provider: String, status: u16 },
+    /// A reply that isn't what the API promised
+    #[error("{provider} sent a response that couldn't be read: {detail}")]
+    InvalidResponse { provider: String, detail: String },
+    /// Any other error status
+    #[error("{provider} API request failed: {status} - {body}")]
+    Http { provider: String, status: u16, body: String },
+    /// The CalDAV server turned down the username and password (401 or 403)
+    #[error("The CalDAV server at {server} rejected the username or password ({status})")]
+    CalendarAuthFailed { server: String, status: u16 },
+    #[error("Obsidian isn't reachable at {url} ({detail})")]
+    ObsidianUnreachable { url: String, detail: String },
+    /// A setting that has to be filled in first, e.g. an API key
+    #[error("Missing {0}")]
+    ConfigMissing(String),
+}
+
+fn status_and_detail(status: Option<u16>, detail: &str) -> String {
+    match status {
+        Some(status) => format!("{} - {}", status, detail),
+        None => detail.to_string(),
+    }
+}
+
+impl ArrowheadError {
+    /// Classify an error response. Providers answer both rate limits and exhausted quotas
+    /// with 429 and only the body says whether waiting a minute will help.
+    pub fn from_status(provider: &str, status: u16, retry_after: Option<Duration>, body: String) -> Self {
+        let provider = provider.to_string();
+        match status {
+            429 => {
+                let lower = body.to_lowercase();
+                if lower.contains("insufficient_quota") || lower.contains("billing") || (lower.contains("quota") && lower.contains("per day")) {
+                    Self::QuotaExhausted { provider }
+                } else {
+                    Self::RateLimited { provider, retry_after }
+                }
+            }
+            401 | 403 => Self::AuthFailed { provider, status },
+            // 529 is Anthropic's "overloaded"
+            500 | 502 | 503 | 504 | 529 => Self::ServiceUnavailable { provider, status: Some(status), detail: body },
+            _ => Self::Http { provider, status, body },
+        }
+    }
+
+    /// A request that never got a usable response: timeouts and refused connections can
+    /// be retried, a body that doesn't decode can't
+    pub fn from_reqwest(provider: &str, error: &reqwest::Error) -> Self {
+        let provider = provider.to_string();
+        if error.is_decode() || error.is_body() {
+            Self::InvalidResponse { provider, detail: error.to_string() }
+        } else {
+            Self::ServiceUnavailable { provider, status: error.status().map(|s| s.as_u16()), detail: error.to_string() }
+        }
+    }
+
+    /// The `Retry-After` header, when given in seconds
+    pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+        let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+        value.trim().parse::<u64>().ok().map(Duration::from_secs)
+    }
+
+    /// The error behind `error`, through any context added on the way up
+    pub fn of(error: &anyhow::Error) -> Option<&ArrowheadError> {
+        error.downcast_ref::<ArrowheadError>()
+    }
+
+    /// The HTTP status behind an error, if it came from a response
+    pub fn status_of(error: &anyhow::Error) -> Option<u16> {
+        match Self::of(error)? {
+            Self::RateLimited { .. } | Self::QuotaExhausted { .. } => Some(429),
+            Self::ServiceUnavailable { status, .. } => *status,
+            Self::AuthFailed { status, .. } | Self::CalendarAuthFailed { status, .. } | Self::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether another attempt might succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::RateLimited { .. } | Self::ServiceUnavailable { .. })
+    }
+
+    /// Exit status of a command that failed with this error, from sysexits.h
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::RateLimited { .. } | Self::ServiceUnavailable { .. } => 75,
+            Self::QuotaExhausted { .. } | Self::ObsidianUnreachable { .. } => 69,
+            Self::InvalidResponse { .. } => 76,
+            Self::AuthFailed { .. } | Self::CalendarAuthFailed { .. } => 77,
+            Self::ConfigMissing(_) => 78,
+            Self::Http { .. } => 1,
+        }
+    }
+
+    /// What to do about it, in a sentence
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Self::RateLimited { retry_after: Some(wait), .. } => Some(format!("Try again in {} seconds.", wait.as_secs().max(1))),
+            Self::RateLimited { .. } => Some("Try again in a minute.".to_string()),
+            Self::QuotaExhausted { provider } => Some(format!("Check the {} plan and billing, or configure a fallback provider under [llm].", provider)),
+            Self::ServiceUnavailable { .. } => Some("The service is busy or down; this usually resolves in a few minutes.".to_string()),
+            Self::AuthFailed { provider, .. } if provider == "obsidian" => Some("Check OBSIDIAN_API_KEY against the Local REST API plugin settings.".to_string()),
+            Self::AuthFailed { .. } => Some("Check the API key with `arrowhead config`.".to_string()),
+            Self::CalendarAuthFailed { .. } => Some(
+                "Check calendar.username (or CALDAV_USERNAME), and that CALDAV_PASSWORD is an app-specific password; iCloud and Fastmail refuse the account password. `arrowhead calendar doctor` tests the connection.".to_string(),
+            ),
+            Self::ObsidianUnreachable { .. } => Some("Is Obsidian running with the Local REST API plugin enabled? Set OBSIDIAN_BASE_URL if it listens elsewhere.".to_string()),
+            Self::ConfigMissing(_) => Some("Set it with `arrowhead config` or the matching environment variable.".to_string()),
+            Self::InvalidResponse { .. } | Self::Http { .. } => None,
+        }
+    }
+}
+
+/// Exit status for a failed command: the `ArrowheadError` behind it, otherwise 1
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    ArrowheadError::of(error).map(ArrowheadError::exit_code).unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statuses_are_classified() {
+        let quota = r#"{"error":{"message":"You exceeded your current quota, please check your plan and billing details.","type":"insufficient_quota"}}"#;
+        assert_eq!(ArrowheadError::from_status("openai", 429, None, quota.to_string()), ArrowheadError::QuotaExhausted { provider: "openai".to_string() });
+        let rate = r#"{"error":{"message":"Rate limit reached for requests per min","type":"requests"}}"#;
+        let wait = Some(Duration::from_secs(20));
+        assert_eq!(
+            ArrowheadError::from_status("openai", 429, wait, rate.to_string()),
+            ArrowheadError::RateLimited { provider: "openai".to_string(), retry_after: wait }
+        );
+        assert!(matches!(ArrowheadError::from_status("openai", 500, None, quota.to_string()), ArrowheadError::ServiceUnavailable { status: Some(500), .. }));
+        assert!(matches!(ArrowheadError::from_status("gemini", 401, None, String::new()), ArrowheadError::AuthFailed { status: 401, .. }));
+        assert!(ArrowheadError::from_status("anthropic", 529, None, "overloaded".to_string()).is_transient());
+        assert!(!ArrowheadError::from_status("ollama", 404, None, "model not found".to_string()).is_transient());
+    }
+
+    #[test]
+    fn test_variant_survives_context() {
+        let error = anyhow::Error::new(ArrowheadError::QuotaExhausted { provider: "gemini".to_string() }).context("Failed to generate embeddings");
+        assert!(matches!(ArrowheadError::of(&error), Some(ArrowheadError::QuotaExhausted { .. })));
+        assert_eq!(exit_code(&error), 69);
+        let error = anyhow::Error::new(ArrowheadError::from_status("openai", 503, None, "Service Unavailable".to_string())).context("Failed to analyze note");
+        assert_eq!(ArrowheadError::status_of(&error), Some(503));
+        assert_eq!(exit_code(&error), 75);
+        assert_eq!(exit_code(&anyhow::anyhow!("something else")), 1);
+        assert_eq!(ArrowheadError::ConfigMissing("OpenAI API key".to_string()).to_string(), "Missing OpenAI API key");
+    }
+}
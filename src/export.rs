@@ -0,0 +1,322 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::cache::CachePaths;
+use crate::cli::{ExportAction, ExportArgs, ExportFormat};
+use crate::config::Config;
+use crate::conflicts::split_frontmatter;
+use crate::embedding_store::read_store;
+use crate::insights::analyze_missing;
+use crate::notes::extract_note_title;
+use crate::obsidian_adapter::{ObsidianAdapter, VectorDatabase};
+use crate::reindex::walk;
+use crate::vault_transaction::{VaultStore, DEFAULT_READ_CONCURRENCY};
+
+/// Notes read per batch; rows are written as each batch arrives so memory stays flat
+const EXPORT_BATCH: usize = 64;
+
+/// Separator for list columns such as themes and keywords
+const LIST_SEPARATOR: &str = "; ";
+
+/// The columns of an analysis export, in order
+const ANALYSIS_COLUMNS: [&str; 14] = [
+    "path",
+    "title",
+    "category",
+    "sentiment",
+    "sentiment_confidence",
+    "complexity",
+    "reading_time",
+    "themes",
+    "keywords",
+    "entities_person",
+    "entities_organization",
+    "entities_location",
+    "entities_other",
+    "analyzed_at",
+];
+
+/// One note's analysis, flattened for a spreadsheet. Fields the analysis lacks stay empty.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AnalysisRow {
+    pub path: String,
+    pub title: String,
+    pub category: Option<String>,
+    pub sentiment: Option<String>,
+    pub sentiment_confidence: Option<f64>,
+    pub complexity: Option<f64>,
+    pub reading_time: Option<u64>,
+    pub themes: String,
+    pub keywords: String,
+    pub entities_person: usize,
+    pub entities_organization: usize,
+    pub entities_location: usize,
+    pub entities_other: usize,
+    pub analyzed_at: Option<String>,
+}
+
+impl AnalysisRow {
+    /// A row with only the path and title, for a note that has not been analyzed
+    fn unanalyzed(path: &str, content: &str) -> Self {
+        let file = path.rsplit('/').next().unwrap_or(path);
+        Self { path: path.to_string(), title: extract_note_title(content, file), ..Self::default() }
+    }
+
+    /// Fill the analysis columns from an `ai_analysis` value, taking whatever fields it has
+    fn with_analysis(mut self, analysis: &serde_yaml::Value, analyzed_at: Option<String>) -> Self {
+        let text = |value: &serde_yaml::Value| value.as_str().map(str::to_string).filter(|s| !s.is_empty());
+        let list = |key: &str| {
+            analysis[key].as_sequence()
+                .map(|items| items.iter().filter_map(|item| item.as_str()).collect::<Vec<_>>().join(LIST_SEPARATOR))
+                .unwrap_or_default()
+        };
+        self.category = text(&analysis["category"]);
+        self.sentiment = text(&analysis["sentiment"]["overall"]);
+        self.sentiment_confidence = analysis["sentiment"]["confidence"].as_f64();
+        self.complexity = analysis["complexity_score"].as_f64();
+        self.reading_time = analysis["reading_time_minutes"].as_u64();
+        self.themes = list("themes");
+        self.keywords = list("keywords");
+        for entity in analysis["entities"].as_sequence().into_iter().flatten() {
+            match entity_kind(entity["entity_type"].as_str().unwrap_or_default()) {
+                EntityKind::Person => self.entities_person += 1,
+                EntityKind::Organization => self.entities_organization += 1,
+                EntityKind::Location => self.entities_location += 1,
+                EntityKind::Other => self.entities_other += 1,
+            }
+        }
+        self.analyzed_at = analyzed_at;
+        self
+    }
+
+    /// The note's row, and whether its frontmatter had an `ai_analysis` to fill it from.
+    /// Frontmatter that does not parse counts as no analysis rather than failing the export.
+    pub fn parse(path: &str, content: &str) -> (Self, bool) {
+        let row = Self::unanalyzed(path, content);
+        let frontmatter = split_frontmatter(content).0
+            .and_then(|yaml| serde_yaml::from_str::<serde_yaml::Value>(yaml).ok())
+            .unwrap_or_default();
+        match frontmatter.get("ai_analysis").filter(|analysis| analysis.is_mapping()) {
+            Some(analysis) => {
+                let analyzed_at = frontmatter["ai_analysis_timestamp"].as_str().map(str::to_string);
+                (row.with_analysis(analysis, analyzed_at), true)
+            }
+            None => (row, false),
+        }
+    }
+
+    fn csv_fields(&self) -> [String; 14] {
+        let number = |value: Option<f64>| value.map(|v| format!("{:.2}", v)).unwrap_or_default();
+        [
+            self.path.clone(),
+            self.title.clone(),
+            self.category.clone().unwrap_or_default(),
+            self.sentiment.clone().unwrap_or_default(),
+            number(self.sentiment_confidence),
+            number(self.complexity),
+            self.reading_time.map(|m| m.to_string()).unwrap_or_default(),
+            self.themes.clone(),
+            self.keywords.clone(),
+            self.entities_person.to_string(),
+            self.entities_organization.to_string(),
+            self.entities_location.to_string(),
+            self.entities_other.to_string(),
+            self.analyzed_at.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+enum EntityKind {
+    Person,
+    Organization,
+    Location,
+    Other,
+}
+
+fn entity_kind(entity_type: &str) -> EntityKind {
+    match entity_type.to_ascii_uppercase().as_str() {
+        "PERSON" | "PEOPLE" | "PER" => EntityKind::Person,
+        "ORG" | "ORGANIZATION" | "ORGANISATION" | "COMPANY" => EntityKind::Organization,
+        "LOCATION" | "LOC" | "PLACE" | "GPE" => EntityKind::Location,
+        _ => EntityKind::Other,
+    }
+}
+
+/// `field` quoted for CSV when it holds a comma, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes analysis rows one at a time in CSV or JSON Lines
+pub struct RowWriter<W: Write> {
+    out: W,
+    format: ExportFormat,
+    pub rows: usize,
+}
+
+impl<W: Write> RowWriter<W> {
+    pub fn new(mut out: W, format: ExportFormat) -> Result<Self> {
+        if format == ExportFormat::Csv {
+            writeln!(out, "{}", ANALYSIS_COLUMNS.join(","))?;
+        }
+        Ok(Self { out, format, rows: 0 })
+    }
+
+    pub fn write(&mut self, row: &AnalysisRow) -> Result<()> {
+        match self.format {
+            ExportFormat::Csv => {
+                let fields: Vec<String> = row.csv_fields().iter().map(|field| csv_field(field)).collect();
+                writeln!(self.out, "{}", fields.join(","))?;
+            }
+            ExportFormat::Jsonl => {
+                serde_json::to_writer(&mut self.out, row)?;
+                writeln!(self.out)?;
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<usize> {
+        self.out.flush()?;
+        Ok(self.rows)
+    }
+}
+
+/// Write a row for every note under `folders`, reading the vault in batches. Returns the
+/// rows of notes without analysis when `defer_missing` is set, instead of writing them.
+pub async fn export_analysis<S: VaultStore + ?Sized, W: Write>(
+    store: &S,
+    folders: &[String],
+    writer: &mut RowWriter<W>,
+    defer_missing: bool,
+) -> Result<Vec<AnalysisRow>> {
+    let paths: Vec<String> = walk(store, folders).await.into_iter().map(|(path, _)| path).collect();
+    let mut missing = Vec::new();
+    for batch in paths.chunks(EXPORT_BATCH) {
+        for (path, content) in store.fetch_many(batch, DEFAULT_READ_CONCURRENCY).await {
+            let content = match content {
+                Ok(content) => content,
+                Err(e) => {
+                    log::warn!("skipping {}: {:#}", path, e);
+                    continue;
+                }
+            };
+            match AnalysisRow::parse(&path, &content) {
+                (row, false) if defer_missing => missing.push(row),
+                (row, _) => writer.write(&row)?,
+            }
+        }
+    }
+    Ok(missing)
+}
+
+/// Write one JSON line per embedded note: path, vector, content hash and model
+pub fn export_embeddings<W: Write>(database: &VectorDatabase, mut out: W) -> Result<usize> {
+    let mut count = 0;
+    for document in database.documents() {
+        serde_json::to_writer(&mut out, &serde_json::json!({
+            "path": document.path,
+            "vector": document.embedding,
+            "content_hash": document.content_hash,
+            "model": document.model,
+        }))?;
+        writeln!(out)?;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+fn create_output(out: &str) -> Result<BufWriter<File>> {
+    let file = File::create(Path::new(out)).with_context(|| format!("Could not create {}", out))?;
+    Ok(BufWriter::new(file))
+}
+
+pub async fn handle_export_command(args: ExportArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    match args.action {
+        ExportAction::Analysis { out, format, folder, analyze_missing: analyze } => {
+            let folders = vec![folder.unwrap_or_default()];
+            let mut writer = RowWriter::new(create_output(&out)?, format)?;
+            let missing = export_analysis(adapter, &folders, &mut writer, analyze).await?;
+            let mut analyzed = 0;
+            if !missing.is_empty() {
+                let paths: Vec<String> = missing.iter().map(|row| row.path.clone()).collect();
+                let mut analyses = analyze_missing(&config, &paths).await?;
+                for row in missing {
+                    let row = match analyses.remove(&row.path).and_then(|analysis| serde_yaml::to_value(analysis).ok()) {
+                        Some(analysis) => {
+                            analyzed += 1;
+                            row.with_analysis(&analysis, Some(chrono::Utc::now().to_rfc3339()))
+                        }
+                        None => row,
+                    };
+                    writer.write(&row)?;
+                }
+            }
+            let rows = writer.finish()?;
+            println!("✅ Wrote {} notes to {}", rows, out);
+            if analyzed > 0 {
+                println!("   Analyzed {} notes that had no analysis yet", analyzed);
+            }
+            crate::script::publish_output(serde_json::json!({ "out": out, "rows": rows, "analyzed": analyzed }));
+        }
+        ExportAction::Embeddings { out } => {
+            let store_path = CachePaths::for_config(&config).embeddings();
+            if !store_path.exists() {
+                anyhow::bail!("No embeddings yet; run `arrowhead index` first");
+            }
+            let store = read_store(&store_path)?;
+            let rows = export_embeddings(&store.database, create_output(&out)?)?;
+            println!("✅ Wrote {} embeddings to {}", rows, out);
+            crate::script::publish_output(serde_json::json!({ "out": out, "rows": rows }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_transaction::testing::FakeVault;
+
+    #[tokio::test]
+    async fn test_analysis_rows_tolerate_partial_analysis() {
+        let vault = FakeVault::new(&[
+            (
+                "Notes/full.md",
+                "---\nai_analysis:\n  themes: [planning, hiring]\n  sentiment: {overall: positive, confidence: 0.8, emotions: []}\n  entities:\n    - {text: Ana, entity_type: PERSON, confidence: 0.9}\n    - {text: Acme, entity_type: ORG, confidence: 0.7}\n  concepts: []\n  keywords: [\"roadmap, q3\"]\n  category: work\n  complexity_score: 4.5\n  reading_time_minutes: 3\nai_analysis_timestamp: 2024-06-01T10:00:00Z\n---\n# Q3 plan\nBody",
+            ),
+            ("Notes/partial.md", "---\nai_analysis:\n  category: journal\n---\nNo heading"),
+            ("Notes/plain.md", "# Plain\nNever analyzed"),
+        ]);
+        let mut buffer = Vec::new();
+        let mut writer = RowWriter::new(&mut buffer, ExportFormat::Csv).unwrap();
+        let missing = export_analysis(&vault, &["Notes".to_string()], &mut writer, false).await.unwrap();
+        assert!(missing.is_empty());
+        assert_eq!(writer.finish().unwrap(), 3);
+
+        let csv = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], ANALYSIS_COLUMNS.join(","));
+        assert!(lines.contains(&"Notes/full.md,Q3 plan,work,positive,0.80,4.50,3,planning; hiring,\"roadmap, q3\",1,1,0,0,2024-06-01T10:00:00Z"), "{}", csv);
+        assert!(lines.contains(&"Notes/partial.md,partial,journal,,,,,,,0,0,0,0,"), "{}", csv);
+        assert!(lines.contains(&"Notes/plain.md,Plain,,,,,,,,0,0,0,0,"), "{}", csv);
+
+        let mut buffer = Vec::new();
+        let mut writer = RowWriter::new(&mut buffer, ExportFormat::Jsonl).unwrap();
+        let missing = export_analysis(&vault, &["Notes".to_string()], &mut writer, true).await.unwrap();
+        assert_eq!(missing.iter().map(|row| row.path.as_str()).collect::<Vec<_>>(), vec!["Notes/plain.md"]);
+        assert_eq!(writer.finish().unwrap(), 2);
+        let first: serde_json::Value = serde_json::from_str(String::from_utf8(buffer).unwrap().lines().next().unwrap()).unwrap();
+        assert!(first["path"].as_str().unwrap().starts_with("Notes/"));
+    }
+}
@@ -0,0 +1,595 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use crate::cli::{FileArgs, FileCommand, FilingRulesAction};
+use crate::config::Config;
+use crate::confirm::{confirm, Operation, OperationClass};
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::report::{DetailLevel, Report, ReportView};
+use crate::vault_transaction::{TransactionReport, VaultStore, VaultTransaction, DEFAULT_READ_CONCURRENCY};
+
+/// Conditions a note must meet for a rule to match. Every condition that is set must
+/// hold; list conditions match when any of their values does. A rule without conditions
+/// matches every note, which is useful as a low-priority catch-all.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilingConditions {
+    /// Any of these tags, from frontmatter or inline `#tag`s
+    pub tags: Vec<String>,
+    /// Any of these substrings in the note title (case-insensitive)
+    pub title_contains: Vec<String>,
+    /// Any of these substrings in the note text (case-insensitive)
+    pub text_contains: Vec<String>,
+    /// The note currently lives in this folder (or below it)
+    pub folder: Option<String>,
+    /// Frontmatter keys that must have these values
+    pub frontmatter: BTreeMap<String, String>,
+}
+
+/// Move notes matching `when` into `destination`; higher priority wins
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilingRule {
+    pub name: String,
+    #[serde(default)]
+    pub priority: i32,
+    pub destination: String,
+    #[serde(default)]
+    pub when: FilingConditions,
+}
+
+/// One condition of a rule and the value in the note that satisfied it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchedCondition {
+    pub condition: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleMatch {
+    pub rule: String,
+    pub priority: i32,
+    pub destination: String,
+    pub matched: Vec<MatchedCondition>,
+}
+
+/// Which rule decides where a note goes, and which other rules also matched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilingDecision {
+    pub path: String,
+    pub winner: Option<RuleMatch>,
+    /// Rules that matched but lost on priority (or config order), best first
+    pub alternatives: Vec<RuleMatch>,
+    /// Where the note would be moved; `None` when no rule matched
+    pub target_path: Option<String>,
+}
+
+impl FilingDecision {
+    /// Matching rules with the same priority as the winner
+    pub fn ties(&self) -> Vec<&RuleMatch> {
+        match &self.winner {
+            Some(winner) => self.alternatives.iter().filter(|a| a.priority == winner.priority).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn needs_move(&self) -> bool {
+        self.target_path.as_deref().is_some_and(|target| target != self.path)
+    }
+
+    /// Full decision trace for `file --explain`
+    pub fn trace(&self) -> String {
+        let describe = |m: &RuleMatch| {
+            if m.matched.is_empty() {
+                "no conditions (catch-all)".to_string()
+            } else {
+                m.matched.iter().map(|c| format!("{} = \"{}\"", c.condition, c.value)).collect::<Vec<_>>().join(", ")
+            }
+        };
+
+        let mut out = String::new();
+        let Some(winner) = &self.winner else {
+            let _ = writeln!(out, "{}: no filing rule matched", self.path);
+            return out;
+        };
+        match &self.target_path {
+            Some(target) if self.needs_move() => { let _ = writeln!(out, "{} → {}", self.path, target); }
+            _ => { let _ = writeln!(out, "{}: already in {}/", self.path, winner.destination.trim_end_matches('/')); }
+        }
+        let _ = writeln!(out, "  ✔ rule \"{}\" (priority {}) → {}/", winner.rule, winner.priority, winner.destination.trim_end_matches('/'));
+        let _ = writeln!(out, "      matched {}", describe(winner));
+        for alternative in &self.alternatives {
+            let reason = if alternative.priority == winner.priority { "tie, lost on config order" } else { "lower priority" };
+            let _ = writeln!(
+                out,
+                "  ✗ rule \"{}\" (priority {}) → {}/ [{}]",
+                alternative.rule, alternative.priority, alternative.destination.trim_end_matches('/'), reason
+            );
+            let _ = writeln!(out, "      matched {}", describe(alternative));
+        }
+        out
+    }
+}
+
+/// What rules can look at in a note
+struct NoteFacts {
+    title: String,
+    folder: String,
+    tags: Vec<String>,
+    frontmatter: BTreeMap<String, String>,
+    text: String,
+}
+
+impl NoteFacts {
+    fn new(path: &str, content: &str) -> Self {
+        let (folder, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+        let mut frontmatter = BTreeMap::new();
+        let mut tags = Vec::new();
+        let mut body = content;
+
+        if let Some(rest) = content.strip_prefix("---\n") {
+            if let Some(end) = rest.find("\n---") {
+                body = rest[end + 4..].trim_start_matches(['-', '\n']);
+                if let Ok(serde_yaml::Value::Mapping(meta)) = serde_yaml::from_str::<serde_yaml::Value>(&rest[..end]) {
+                    for (key, value) in meta {
+                        let Some(key) = key.as_str() else { continue };
+                        match value {
+                            serde_yaml::Value::Sequence(items) if key == "tags" => {
+                                tags.extend(items.iter().filter_map(|t| t.as_str().map(str::to_string)));
+                            }
+                            serde_yaml::Value::String(value) if key == "tags" => {
+                                tags.extend(value.split([',', ' ']).filter(|t| !t.is_empty()).map(str::to_string));
+                            }
+                            serde_yaml::Value::String(value) => { frontmatter.insert(key.to_string(), value); }
+                            serde_yaml::Value::Bool(value) => { frontmatter.insert(key.to_string(), value.to_string()); }
+                            serde_yaml::Value::Number(value) => { frontmatter.insert(key.to_string(), value.to_string()); }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        tags.extend(
+            body.split_whitespace()
+                .filter_map(|word| word.strip_prefix('#'))
+                .map(|tag| tag.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '-' && c != '_' && c != '/'))
+                .filter(|tag| !tag.is_empty() && !tag.starts_with('#'))
+                .map(str::to_string),
+        );
+        let tags = tags.into_iter().map(|t| t.trim_start_matches('#').to_lowercase()).collect();
+
+        Self {
+            title: file_name.trim_end_matches(".md").to_string(),
+            folder: folder.to_string(),
+            tags,
+            frontmatter,
+            text: body.to_lowercase(),
+        }
+    }
+}
+
+/// The conditions of `rule` satisfied by the note, or `None` if any condition fails
+fn match_rule(rule: &FilingRule, facts: &NoteFacts) -> Option<Vec<MatchedCondition>> {
+    let when = &rule.when;
+    let mut matched = Vec::new();
+    let mut push = |condition: &str, value: &str| matched.push(MatchedCondition { condition: condition.to_string(), value: value.to_string() });
+
+    if !when.tags.is_empty() {
+        let tag = when.tags.iter().find(|t| facts.tags.contains(&t.trim_start_matches('#').to_lowercase()))?;
+        push("tag", tag.trim_start_matches('#'));
+    }
+    if !when.title_contains.is_empty() {
+        let title = facts.title.to_lowercase();
+        let needle = when.title_contains.iter().find(|n| title.contains(&n.to_lowercase()))?;
+        push("title contains", needle);
+    }
+    if !when.text_contains.is_empty() {
+        let needle = when.text_contains.iter().find(|n| facts.text.contains(&n.to_lowercase()))?;
+        push("text contains", needle);
+    }
+    if let Some(folder) = &when.folder {
+        let folder = folder.trim_end_matches('/');
+        if facts.folder != folder && !facts.folder.starts_with(&format!("{}/", folder)) {
+            return None;
+        }
+        push("folder", folder);
+    }
+    for (key, expected) in &when.frontmatter {
+        let actual = facts.frontmatter.get(key)?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            return None;
+        }
+        push(&format!("frontmatter.{}", key), actual);
+    }
+    Some(matched)
+}
+
+/// Run every rule against one note
+pub fn evaluate(rules: &[FilingRule], path: &str, content: &str) -> FilingDecision {
+    let facts = NoteFacts::new(path, content);
+    let mut matches: Vec<RuleMatch> = rules.iter()
+        .filter_map(|rule| match_rule(rule, &facts).map(|matched| RuleMatch {
+            rule: rule.name.clone(),
+            priority: rule.priority,
+            destination: rule.destination.clone(),
+            matched,
+        }))
+        .collect();
+    // Stable: equal priorities keep config order
+    matches.sort_by_key(|m| std::cmp::Reverse(m.priority));
+
+    let mut matches = matches.into_iter();
+    let winner = matches.next();
+    let target_path = winner.as_ref().map(|w| {
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        format!("{}/{}", w.destination.trim_end_matches('/'), file_name)
+    });
+    FilingDecision { path: path.to_string(), winner, alternatives: matches.collect(), target_path }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RuleCount {
+    pub rule: String,
+    /// Notes the rule matched
+    pub matched: usize,
+    /// Notes the rule would actually file
+    pub won: usize,
+}
+
+/// Dry-run results of all rules over a set of notes
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RuleStats {
+    pub notes: usize,
+    pub rules: Vec<RuleCount>,
+    /// Notes matched by more than one rule, with the matching rules
+    pub overlaps: Vec<(String, Vec<String>)>,
+    /// Notes where the winner tied with another rule on priority
+    pub ties: Vec<(String, Vec<String>)>,
+    pub unmatched: Vec<String>,
+}
+
+pub fn rule_stats(rules: &[FilingRule], notes: &[(String, String)]) -> RuleStats {
+    let mut stats = RuleStats {
+        notes: notes.len(),
+        rules: rules.iter().map(|r| RuleCount { rule: r.name.clone(), ..RuleCount::default() }).collect(),
+        ..RuleStats::default()
+    };
+    for (path, content) in notes {
+        let decision = evaluate(rules, path, content);
+        let Some(winner) = &decision.winner else {
+            stats.unmatched.push(path.clone());
+            continue;
+        };
+
+        let names: Vec<String> = std::iter::once(winner).chain(&decision.alternatives).map(|m| m.rule.clone()).collect();
+        for count in stats.rules.iter_mut() {
+            if names.contains(&count.rule) {
+                count.matched += 1;
+            }
+            if count.rule == winner.rule {
+                count.won += 1;
+            }
+        }
+        if names.len() > 1 {
+            stats.overlaps.push((path.clone(), names));
+        }
+        let ties = decision.ties();
+        if !ties.is_empty() {
+            stats.ties.push((path.clone(), std::iter::once(winner).chain(ties).map(|m| m.rule.clone()).collect()));
+        }
+    }
+    stats
+}
+
+impl RuleStats {
+    /// Summary: how many notes overlap, tie or match nothing. Normal: matches and wins per
+    /// rule, with the overlapping and unmatched notes. Detail: also the priority ties.
+    pub fn report(&self) -> Report {
+        let pairs = |entries: &[(String, Vec<String>)]| -> Vec<serde_json::Value> {
+            entries.iter().map(|(note, rules)| serde_json::json!({ "note": note, "rules": rules })).collect()
+        };
+        let mut report = Report::new()
+            .text(DetailLevel::Summary, format!("Dry run of {} rule(s) over {} note(s)\n", self.rules.len(), self.notes))
+            .text_only(DetailLevel::Summary, format!(
+                "{} matched by more than one rule, {} tied on priority, {} matched by no rule\n",
+                self.overlaps.len(), self.ties.len(), self.unmatched.len()
+            ))
+            .field(DetailLevel::Summary, "notes", self.notes)
+            .field(DetailLevel::Summary, "overlap_count", self.overlaps.len())
+            .field(DetailLevel::Summary, "tie_count", self.ties.len())
+            .field(DetailLevel::Summary, "unmatched_count", self.unmatched.len())
+            .field(DetailLevel::Normal, "rules", &self.rules)
+            .field(DetailLevel::Normal, "overlaps", pairs(&self.overlaps))
+            .field(DetailLevel::Normal, "unmatched", &self.unmatched)
+            .field(DetailLevel::Detail, "ties", pairs(&self.ties));
+
+        let mut out = String::from("\n");
+        for count in &self.rules {
+            let _ = writeln!(out, "  {:<24} matched {:>4}   would file {:>4}", count.rule, count.matched, count.won);
+        }
+        if !self.overlaps.is_empty() {
+            let _ = writeln!(out, "\nMatched by more than one rule ({}):", self.overlaps.len());
+            for (path, rules) in &self.overlaps {
+                let _ = writeln!(out, "  {}: {}", path, rules.join(", "));
+            }
+        }
+        if !self.unmatched.is_empty() {
+            let _ = writeln!(out, "\nMatched by no rule ({}):", self.unmatched.len());
+            for path in &self.unmatched {
+                let _ = writeln!(out, "  {}", path);
+            }
+        }
+        report = report.text(DetailLevel::Normal, out);
+
+        if !self.ties.is_empty() {
+            let mut ties = format!("\nTied on priority, first in the config wins ({}):\n", self.ties.len());
+            for (path, rules) in &self.ties {
+                let _ = writeln!(ties, "  {}: {}", path, rules.join(", "));
+            }
+            report = report.text(DetailLevel::Detail, ties);
+        }
+        report
+    }
+}
+
+/// Configuration problems in the filing rules, for `arrowhead doctor`
+pub fn filing_warnings(rules: &[FilingRule], notes: &[(String, String)]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut seen = Vec::new();
+    for rule in rules {
+        if seen.contains(&rule.name) {
+            warnings.push(format!("Filing rule name \"{}\" is used more than once", rule.name));
+        }
+        seen.push(rule.name.clone());
+        if rule.destination.trim().is_empty() {
+            warnings.push(format!("Filing rule \"{}\" has no destination", rule.name));
+        }
+    }
+
+    let mut conflicts: BTreeMap<Vec<String>, Vec<String>> = BTreeMap::new();
+    for (path, rules) in rule_stats(rules, notes).ties {
+        conflicts.entry(rules).or_default().push(path);
+    }
+    for (rules, paths) in conflicts {
+        let quoted: Vec<String> = rules.iter().map(|r| format!("\"{}\"", r)).collect();
+        warnings.push(format!(
+            "Filing rules {} have equal priority and both match {} note(s) (e.g. {}); the first in the config wins",
+            quoted.join(" and "), paths.len(), paths[0]
+        ));
+    }
+    warnings
+}
+
+/// Move the note as decided, keeping the decision trace as the report's context
+pub async fn apply_decision<S: VaultStore + ?Sized>(store: &S, decision: &FilingDecision, content: &str) -> Result<TransactionReport> {
+    let Some(target) = decision.target_path.as_deref().filter(|_| decision.needs_move()) else {
+        bail!("'{}' does not need to move", decision.path);
+    };
+    if store.read_file(target).await?.is_some() {
+        bail!("'{}' already exists", target);
+    }
+    let transaction = VaultTransaction::new(&format!("file {} to {}", decision.path, target))
+        .write(target, content)
+        .delete(&decision.path)
+        .with_context(serde_json::json!({ "filing": decision }));
+    transaction.execute(store).await
+}
+
+/// Notes in the configured filing folders, with their content
+pub async fn load_filing_notes(adapter: &ObsidianAdapter, folders: &[String]) -> Vec<(String, String)> {
+    let mut paths = Vec::new();
+    for folder in folders {
+        if let Ok(files) = adapter.list_files_in_folder(folder).await {
+            paths.extend(files.into_iter().filter(|f| f.ends_with(".md")).map(|f| format!("{}/{}", folder.trim_end_matches('/'), f)));
+        }
+    }
+    adapter.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await
+        .into_iter()
+        .filter_map(|(path, content)| content.ok().map(|content| (path, content)))
+        .collect()
+}
+
+pub async fn handle_file_command(args: FileArgs, view: ReportView, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let rules = &config.filing.rules;
+    if rules.is_empty() {
+        println!("No filing rules configured. Add [[filing.rules]] entries to your config file.");
+        return Ok(());
+    }
+
+    if let Some(FileCommand::Rules { action: FilingRulesAction::Stats }) = args.command {
+        let notes = load_filing_notes(adapter, &config.filing.folders).await;
+        return rule_stats(rules, &notes).report().print(view);
+    }
+
+    let normalize = |p: &str| if p.ends_with(".md") { p.to_string() } else { format!("{}.md", p) };
+    if let Some(path) = args.explain {
+        let path = normalize(&path);
+        let content = adapter.read_file(&path).await?
+            .ok_or_else(|| anyhow::anyhow!("Note '{}' not found", path))?;
+        print!("{}", evaluate(rules, &path, &content).trace());
+        return Ok(());
+    }
+
+    let notes = match (args.paths.is_empty(), args.all) {
+        (true, true) => load_filing_notes(adapter, &config.filing.folders).await,
+        (false, false) => {
+            let paths: Vec<String> = args.paths.iter().map(|p| normalize(p)).collect();
+            adapter.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await
+                .into_iter()
+                .filter_map(|(path, content)| match content {
+                    Ok(content) => Some((path, content)),
+                    Err(e) => {
+                        println!("⚠️  {}: {}", path, e);
+                        None
+                    }
+                })
+                .collect()
+        }
+        (false, true) => bail!("Pass either note paths or --all, not both"),
+        (true, false) => bail!("Pass one or more note paths, --all, --explain <path> or `rules stats`"),
+    };
+
+    let decisions: Vec<_> = notes.iter()
+        .map(|(path, content)| (evaluate(rules, path, content), content))
+        .filter(|(decision, _)| decision.needs_move())
+        .collect();
+    let describe = |decision: &FilingDecision| {
+        let winner = decision.winner.as_ref().map_or("", |w| w.rule.as_str());
+        (decision.target_path.as_deref().unwrap_or_default().to_string(), winner.to_string())
+    };
+
+    let mut moved = Vec::new();
+    if args.dry_run {
+        for (decision, _) in &decisions {
+            let (target, winner) = describe(decision);
+            println!("Would move {} → {} (rule \"{}\")", decision.path, target, winner);
+            moved.push(serde_json::json!({ "from": decision.path, "to": target, "rule": winner }));
+        }
+        crate::script::publish_output(serde_json::json!({ "dry_run": true, "moved": moved }));
+        return Ok(());
+    }
+    if !decisions.is_empty() {
+        let operation = Operation::new(OperationClass::Move, format!("Move {} note(s)", decisions.len()), decisions.len());
+        if !confirm(&config.confirmations, &operation, args.yes)? {
+            println!("Nothing moved.");
+            return Ok(());
+        }
+    }
+
+    for (decision, content) in &decisions {
+        let (target, winner) = describe(decision);
+        let result = apply_decision(adapter, decision, content).await;
+        if let Ok(report) = &result {
+            if let Err(e) = report.append_to_log(adapter.transaction_log()) {
+                log::warn!("could not write transaction log: {}", e);
+            }
+        }
+        match result {
+            Ok(report) if report.is_committed() => {
+                moved.push(serde_json::json!({ "from": decision.path, "to": target, "rule": winner }));
+                println!("📁 {} → {} (rule \"{}\")", decision.path, target, winner);
+            }
+            Ok(report) => println!("{}", report.summary()),
+            Err(e) => println!("⚠️  {}: {}", decision.path, e),
+        }
+    }
+    println!("Filed {} of {} note(s)", moved.len(), notes.len());
+    crate::script::publish_output(serde_json::json!({ "dry_run": false, "moved": moved }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_transaction::testing::FakeVault;
+
+    fn rule(name: &str, priority: i32, destination: &str, when: FilingConditions) -> FilingRule {
+        FilingRule { name: name.to_string(), priority, destination: destination.to_string(), when }
+    }
+
+    fn rules() -> Vec<FilingRule> {
+        vec![
+            rule("meetings", 10, "Meetings", FilingConditions { tags: vec!["meeting".to_string()], ..Default::default() }),
+            rule("standups", 10, "Meetings/Standups", FilingConditions { title_contains: vec!["standup".to_string()], ..Default::default() }),
+            rule("projects", 5, "Projects", FilingConditions {
+                frontmatter: BTreeMap::from([("type".to_string(), "project".to_string())]),
+                folder: Some("Inbox".to_string()),
+                ..Default::default()
+            }),
+            rule("inbox catch-all", -10, "Archive/Unsorted", FilingConditions { folder: Some("Inbox".to_string()), ..Default::default() }),
+        ]
+    }
+
+    const STANDUP: &str = "---\ntags: [meeting, team]\n---\n# Daily standup\nNotes.\n";
+    const PROJECT: &str = "---\ntype: project\n---\n# Apollo\nKickoff for #q3 work.\n";
+
+    #[test]
+    fn test_decision_records_winner_and_alternatives() {
+        let decision = evaluate(&rules(), "Inbox/Daily standup.md", STANDUP);
+        let winner = decision.winner.as_ref().unwrap();
+        assert_eq!(winner.rule, "meetings");
+        assert_eq!(winner.matched, vec![MatchedCondition { condition: "tag".to_string(), value: "meeting".to_string() }]);
+        assert_eq!(decision.target_path.as_deref(), Some("Meetings/Daily standup.md"));
+
+        let alternatives: Vec<&str> = decision.alternatives.iter().map(|a| a.rule.as_str()).collect();
+        assert_eq!(alternatives, vec!["standups", "inbox catch-all"]);
+        assert_eq!(decision.ties().len(), 1);
+
+        let trace = decision.trace();
+        assert!(trace.starts_with("Inbox/Daily standup.md → Meetings/Daily standup.md"));
+        assert!(trace.contains("✗ rule \"standups\" (priority 10) → Meetings/Standups/ [tie, lost on config order]"));
+        assert!(trace.contains("matched folder = \"Inbox\""));
+
+        let project = evaluate(&rules(), "Inbox/Apollo.md", PROJECT);
+        assert_eq!(project.winner.as_ref().unwrap().rule, "projects");
+        assert_eq!(project.winner.as_ref().unwrap().matched.len(), 2);
+        // Same note outside the inbox matches nothing
+        let elsewhere = evaluate(&rules(), "Notes/Apollo.md", PROJECT);
+        assert!(elsewhere.winner.is_none() && !elsewhere.needs_move());
+    }
+
+    #[test]
+    fn test_rule_stats_and_priority_warnings() {
+        let notes = vec![
+            ("Inbox/Daily standup.md".to_string(), STANDUP.to_string()),
+            ("Inbox/Apollo.md".to_string(), PROJECT.to_string()),
+            ("Inbox/Random.md".to_string(), "just a thought\n".to_string()),
+            ("Notes/Loose.md".to_string(), "nothing to see\n".to_string()),
+        ];
+        let stats = rule_stats(&rules(), &notes);
+        let counts: Vec<(&str, usize, usize)> = stats.rules.iter().map(|c| (c.rule.as_str(), c.matched, c.won)).collect();
+        assert_eq!(counts, vec![("meetings", 1, 1), ("standups", 1, 0), ("projects", 1, 1), ("inbox catch-all", 3, 1)]);
+        assert_eq!(stats.overlaps.len(), 2);
+        assert_eq!(stats.unmatched, vec!["Notes/Loose.md"]);
+
+        let report = stats.report();
+        assert_eq!(
+            report.render(DetailLevel::Summary),
+            "Dry run of 4 rule(s) over 4 note(s)\n2 matched by more than one rule, 1 tied on priority, 1 matched by no rule\n"
+        );
+        assert_eq!(
+            report.render(DetailLevel::Normal),
+            "Dry run of 4 rule(s) over 4 note(s)\n\n\
+             \x20 meetings                 matched    1   would file    1\n\
+             \x20 standups                 matched    1   would file    0\n\
+             \x20 projects                 matched    1   would file    1\n\
+             \x20 inbox catch-all          matched    3   would file    1\n\
+             \nMatched by more than one rule (2):\n\
+             \x20 Inbox/Daily standup.md: meetings, standups, inbox catch-all\n\
+             \x20 Inbox/Apollo.md: projects, inbox catch-all\n\
+             \nMatched by no rule (1):\n\
+             \x20 Notes/Loose.md\n"
+        );
+        assert_eq!(
+            report.render(DetailLevel::Detail),
+            format!(
+                "{}\nTied on priority, first in the config wins (1):\n  Inbox/Daily standup.md: meetings, standups\n",
+                report.render(DetailLevel::Normal)
+            )
+        );
+        let json = report.to_json(DetailLevel::Summary);
+        assert_eq!(json, serde_json::json!({
+            "detail_level": "summary", "notes": 4, "overlap_count": 2, "tie_count": 1, "unmatched_count": 1
+        }));
+
+        let warnings = filing_warnings(&rules(), &notes);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("\"meetings\" and \"standups\" have equal priority"));
+    }
+
+    #[tokio::test]
+    async fn test_apply_moves_note_with_trace_in_report() {
+        let vault = FakeVault::new(&[("Inbox/Daily standup.md", STANDUP)]);
+        let decision = evaluate(&rules(), "Inbox/Daily standup.md", STANDUP);
+        let report = apply_decision(&vault, &decision, STANDUP).await.unwrap();
+        assert!(report.is_committed());
+        assert_eq!(vault.get("Meetings/Daily standup.md").as_deref(), Some(STANDUP));
+        assert!(vault.get("Inbox/Daily standup.md").is_none());
+
+        let context = report.context.unwrap();
+        assert_eq!(context["filing"]["winner"]["rule"], "meetings");
+        assert_eq!(context["filing"]["alternatives"][0]["rule"], "standups");
+    }
+}
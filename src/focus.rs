@@ -0,0 +1,327 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use console::{Key, Term};
+use std::io::Write as _;
+use std::sync::mpsc as blocking;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::calendar::create_calendar_adapter;
+use crate::calendar_adapter::{deadline_status, is_open_block, Deadline, ImpactLevel, Interruption, TimeBlock, TimeBlockStatus};
+use crate::cli::{FocusAction, FocusArgs};
+use crate::config::Config;
+use crate::confirm::{PromptReader, StdinPrompt};
+use crate::deadline_store::DeadlineStore;
+use crate::deadlines::is_manual_status;
+use crate::timezone::{self, TimeZone};
+
+/// Cells of the progress bar next to the timer
+const BAR_WIDTH: usize = 20;
+
+/// The open block of `deadline` scheduled around `now`, if any
+pub fn current_block(deadline: &Deadline, now: DateTime<Utc>) -> Option<&TimeBlock> {
+    deadline.time_blocks.iter()
+        .filter(|b| is_open_block(b) && b.start_time <= now && now < b.end_time)
+        .min_by_key(|b| b.start_time)
+}
+
+/// An unscheduled block of `length` starting `now`, for a session outside the planned blocks
+pub fn new_block(deadline: &Deadline, now: DateTime<Utc>, length: Duration) -> TimeBlock {
+    TimeBlock {
+        id: Uuid::new_v4().to_string(),
+        deadline_id: deadline.id.clone(),
+        start_time: now,
+        end_time: now + length,
+        planned_duration: length,
+        actual_duration: None,
+        productivity_score: None,
+        notes: None,
+        calendar_event_id: None,
+        status: TimeBlockStatus::Active,
+        focus_mode: true,
+        interruptions: Vec::new(),
+    }
+}
+
+fn clock(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// The timer line: elapsed against planned time, a bar, and how far over the block it ran
+pub fn timer_line(elapsed: Duration, planned: Duration, interruptions: usize) -> String {
+    let share = if planned.num_seconds() > 0 { elapsed.num_seconds() as f64 / planned.num_seconds() as f64 } else { 1.0 };
+    let filled = ((share.min(1.0) * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    let mut line = format!("⏱  {} / {}  {}{}", clock(elapsed), clock(planned), "█".repeat(filled), "░".repeat(BAR_WIDTH - filled));
+    if elapsed > planned {
+        line.push_str(&format!("  +{} over", clock(elapsed - planned)));
+    }
+    if interruptions > 0 {
+        line.push_str(&format!("  ⚡{}", interruptions));
+    }
+    line.push_str("  [p] interruption  [q] stop");
+    line
+}
+
+fn parse_impact(text: &str) -> Option<ImpactLevel> {
+    match text.trim().to_lowercase().as_str() {
+        "l" | "low" => Some(ImpactLevel::Low),
+        "" | "m" | "medium" => Some(ImpactLevel::Medium),
+        "h" | "high" => Some(ImpactLevel::High),
+        _ => None,
+    }
+}
+
+/// Ask what interrupted the session, pressed at `at`. The interruption lasts until the
+/// questions are answered, since they are answered once back at work.
+pub fn ask_interruption(prompt: &mut dyn PromptReader, at: DateTime<Utc>, now: impl Fn() -> DateTime<Utc>) -> Result<Interruption> {
+    let reason = prompt.read_answer("   What interrupted you?")?.trim().to_string();
+    let impact_level = loop {
+        let answer = prompt.read_answer("   Impact (low/medium/high) [medium]:")?;
+        match parse_impact(&answer) {
+            Some(impact) => break impact,
+            None => println!("   Answer low, medium or high"),
+        }
+    };
+    Ok(Interruption {
+        timestamp: at,
+        duration: now() - at,
+        reason: if reason.is_empty() { "unspecified".to_string() } else { reason },
+        impact_level,
+    })
+}
+
+/// A productivity score from 1 to 5, or `None` when the question is skipped
+pub fn ask_score(prompt: &mut dyn PromptReader) -> Result<Option<f32>> {
+    loop {
+        let answer = prompt.read_answer("How productive was the session, 1–5 (Enter to skip)?")?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        match answer.parse::<u8>() {
+            Ok(score @ 1..=5) => return Ok(Some(score as f32)),
+            _ => println!("Answer with a number from 1 to 5"),
+        }
+    }
+}
+
+/// The block after a session of `worked` ending at `ended`: its time is added to any
+/// earlier session's, its interruptions are kept, and it is completed. With `extend`, an
+/// overrun block is stretched to end with the session.
+pub fn finish_block(block: &mut TimeBlock, worked: Duration, ended: DateTime<Utc>, score: Option<f32>, notes: Option<String>, interruptions: Vec<Interruption>, extend: bool) {
+    block.actual_duration = Some(block.actual_duration.unwrap_or_else(Duration::zero) + worked);
+    if score.is_some() {
+        block.productivity_score = score;
+    }
+    if notes.is_some() {
+        block.notes = notes;
+    }
+    block.interruptions.extend(interruptions);
+    block.status = TimeBlockStatus::Completed;
+    if extend && ended > block.end_time {
+        block.end_time = ended;
+        block.planned_duration = block.end_time - block.start_time;
+    }
+}
+
+enum FocusKey {
+    Interruption,
+    Stop,
+}
+
+/// Read keypresses on a thread of their own, in raw mode. After an interruption it waits
+/// for `resume` before reading again, so the questions about it get a normal terminal.
+fn spawn_key_reader() -> (mpsc::UnboundedReceiver<FocusKey>, blocking::Sender<()>) {
+    let (keys, received) = mpsc::unbounded_channel();
+    let (resume, resumed) = blocking::channel();
+    std::thread::spawn(move || {
+        let term = Term::stdout();
+        loop {
+            let key = match term.read_key_raw() {
+                Ok(Key::Char('p' | 'P')) => FocusKey::Interruption,
+                Ok(Key::Char('q' | 'Q') | Key::CtrlC | Key::Escape) | Err(_) => FocusKey::Stop,
+                Ok(_) => continue,
+            };
+            let stop = matches!(key, FocusKey::Stop);
+            if keys.send(key).is_err() || stop || resumed.recv().is_err() {
+                return;
+            }
+        }
+    });
+    (received, resume)
+}
+
+/// Show the timer until `q` or Ctrl-C, logging an interruption for each `p`
+async fn run_timer(block: &TimeBlock, started: DateTime<Utc>) -> Result<Vec<Interruption>> {
+    let (mut keys, resume) = spawn_key_reader();
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
+    let mut interruptions = Vec::new();
+    // The block is under way, so the session is planned to run until it ends
+    let planned = block.end_time - started;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                print!("\r\x1b[2K{}", timer_line(Utc::now() - started, planned, interruptions.len()));
+                std::io::stdout().flush()?;
+            }
+            key = keys.recv() => match key {
+                Some(FocusKey::Interruption) => {
+                    let at = Utc::now();
+                    println!("\r\x1b[2K⚡ Interrupted at {}", at.format("%H:%M:%S"));
+                    interruptions.push(ask_interruption(&mut StdinPrompt, at, Utc::now)?);
+                    let _ = resume.send(());
+                }
+                Some(FocusKey::Stop) | None => break,
+            },
+        }
+    }
+    println!();
+    Ok(interruptions)
+}
+
+pub async fn handle_focus_command(args: FocusArgs) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let store = DeadlineStore::from_settings(&config.calendar);
+    let zone = timezone::resolve(&config.calendar.time_zone)?;
+
+    match args.action {
+        FocusAction::Start { deadline_id, minutes } => {
+            let mut prompt = StdinPrompt;
+            if !prompt.is_interactive() {
+                bail!("A focus session needs a terminal");
+            }
+            if minutes <= 0 {
+                bail!("--minutes must be positive");
+            }
+            let started = Utc::now();
+            let deadline = store.get(&deadline_id)?;
+            let block = match current_block(&deadline, started) {
+                Some(block) => store.modify_time_block(&block.id, |b| b.status = TimeBlockStatus::Active)?,
+                None => {
+                    let block = new_block(&deadline, started, Duration::minutes(minutes));
+                    let added = block.clone();
+                    store.modify(&deadline.id, move |d| {
+                        d.time_blocks.push(added);
+                        Ok(())
+                    })?;
+                    println!("No time block is scheduled now; started a {}-minute one", minutes);
+                    block
+                }
+            };
+            println!("🎯 Focusing on '{}' until {}", deadline.title, local_time(&zone, block.end_time));
+
+            let interruptions = run_timer(&block, started).await?;
+            let ended = Utc::now();
+            let worked = ended - started;
+            println!("Worked {} with {} interruption(s)", clock(worked), interruptions.len());
+
+            let score = ask_score(&mut prompt)?;
+            let notes = Some(prompt.read_answer("Notes (Enter to skip):")?.trim().to_string()).filter(|n| !n.is_empty());
+            let extend = ended > block.end_time
+                && prompt.read_answer(&format!("The session ran past the block's end at {}. Extend the block to {}? [y/N]", local_time(&zone, block.end_time), local_time(&zone, ended)))?
+                    .trim().eq_ignore_ascii_case("y");
+
+            let session_notes = notes.clone();
+            let finished = store.modify_time_block(&block.id, |b| finish_block(b, worked, ended, score, session_notes, interruptions, extend))?;
+            let calendar = create_calendar_adapter(&config.calendar, &config.network);
+            if extend {
+                match (&finished.calendar_event_id, &calendar) {
+                    (Some(event_id), Ok(calendar)) => match calendar.extend_time_block_event(event_id, finished.end_time).await {
+                        Ok(_) => println!("📅 Calendar event now ends at {}", local_time(&zone, finished.end_time)),
+                        Err(e) => println!("⚠️  Could not move the calendar event: {}", e),
+                    },
+                    (Some(_), Err(e)) => println!("⚠️  {}; the calendar event keeps its old end", e),
+                    (None, _) => {}
+                }
+            }
+
+            let hours = worked.num_seconds() as f32 / 3600.0;
+            let completed_hours = store.get(&deadline.id)?.completed_hours + hours;
+            match &calendar {
+                Ok(calendar) => {
+                    calendar.update_deadline_progress(&deadline.id, completed_hours, notes).await?;
+                }
+                Err(_) => {
+                    let updated = store.modify(&deadline.id, |d| {
+                        d.completed_hours = completed_hours;
+                        if d.estimated_hours > 0.0 && !is_manual_status(&d.status) {
+                            d.status = deadline_status(d, ended);
+                        }
+                        Ok(())
+                    })?;
+                    println!("Progress updated for deadline '{}': {:.1}/{:.1} hours", updated.title, updated.completed_hours, updated.estimated_hours);
+                }
+            }
+            crate::script::publish_output(serde_json::to_value(&finished)?);
+        }
+    }
+    Ok(())
+}
+
+fn local_time(zone: &TimeZone, at: DateTime<Utc>) -> String {
+    zone.to_local(at).format("%H:%M").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, ScriptedPrompt};
+    use chrono::TimeZone as _;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 3, hour, minute, 0).unwrap()
+    }
+
+    fn deadline() -> Deadline {
+        Deadline {
+            id: "report".to_string(),
+            estimated_hours: 4.0,
+            ..testing::deadline("Report", at(17, 0) + Duration::days(3))
+        }
+    }
+
+    #[test]
+    fn test_the_block_scheduled_now_is_picked() {
+        let mut deadline = deadline();
+        let mut morning = new_block(&deadline, at(9, 0), Duration::hours(1));
+        morning.status = TimeBlockStatus::Planned;
+        let mut done = new_block(&deadline, at(10, 0), Duration::hours(1));
+        done.status = TimeBlockStatus::Completed;
+        deadline.time_blocks = vec![morning.clone(), done];
+
+        assert_eq!(current_block(&deadline, at(9, 30)).map(|b| b.id.clone()), Some(morning.id));
+        assert!(current_block(&deadline, at(10, 30)).is_none());
+        assert!(current_block(&deadline, at(8, 59)).is_none());
+    }
+
+    #[test]
+    fn test_timer_line_shows_overrun_and_interruptions() {
+        let line = timer_line(Duration::minutes(15), Duration::hours(1), 0);
+        assert!(line.starts_with("⏱  0:15:00 / 1:00:00  █████░░░░░"), "{}", line);
+        let line = timer_line(Duration::minutes(70), Duration::hours(1), 2);
+        assert!(line.contains(&"█".repeat(BAR_WIDTH)) && line.contains("+0:10:00 over") && line.contains("⚡2"), "{}", line);
+    }
+
+    #[test]
+    fn test_finished_session_records_time_score_and_interruptions() {
+        let mut prompt = ScriptedPrompt::tty(&["Phone call", "severe", "h", "7", "4"]);
+        let interruption = ask_interruption(&mut prompt, at(9, 20), || at(9, 25)).unwrap();
+        assert_eq!(interruption.duration, Duration::minutes(5));
+        assert!(matches!(interruption.impact_level, ImpactLevel::High));
+        assert_eq!(ask_score(&mut prompt).unwrap(), Some(4.0));
+        assert_eq!(ask_score(&mut prompt).unwrap(), None);
+
+        let mut block = new_block(&deadline(), at(9, 0), Duration::hours(1));
+        block.actual_duration = Some(Duration::minutes(10));
+        finish_block(&mut block, Duration::minutes(75), at(10, 15), Some(4.0), None, vec![interruption.clone()], false);
+        assert_eq!(block.actual_duration, Some(Duration::minutes(85)));
+        assert_eq!(block.end_time, at(10, 0));
+        assert!(matches!(block.status, TimeBlockStatus::Completed));
+        assert_eq!(block.interruptions.len(), 1);
+
+        finish_block(&mut block, Duration::zero(), at(10, 15), None, Some("Drafted intro".to_string()), vec![], true);
+        assert_eq!((block.end_time, block.planned_duration), (at(10, 15), Duration::minutes(75)));
+        assert_eq!((block.productivity_score, block.notes.as_deref()), (Some(4.0), Some("Drafted intro")));
+    }
+}
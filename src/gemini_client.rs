@@ -8,6 +8,7 @@ use std::env;
 use uuid::Uuid;
 
 use crate::ai_conversation::{FunctionCall, FunctionSchema, LLMClient, Message, MessageRole};
+use crate::error::ArrowheadError;
 
 /// Gemini API client configuration
 #[derive(Debug, Clone)]
@@ -53,6 +54,12 @@ impl GeminiClient {
         Ok(Self { config, client })
     }
 
+    /// Send requests through `client`, e.g. one built for the `[network.llm]` proxy
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
     pub fn with_api_key(api_key: String) -> Result<Self> {
         let config = GeminiConfig {
             api_key,
@@ -114,52 +121,25 @@ impl GeminiClient {
             self.config.base_url, self.config.model, self.config.api_key
         );
 
-        let max_retries = 3;
-        let mut last_error = None;
-
-        for attempt in 1..=max_retries {
-            let response = self
-                .client
-                .post(&url)
-                .header("Content-Type", "application/json")
-                .json(&request)
-                .send()
-                .await?;
-
-            if response.status().is_success() {
-                let gemini_response: GeminiResponse = response.json().await?;
-                return Ok(gemini_response);
-            }
-
-            let status = response.status();
-            let error_text = response.text().await?;
-            
-            // Handle 503 Service Unavailable with retry
-            if status == 503 {
-                let error_msg = format!("Gemini API temporarily unavailable (attempt {}/{}): {}", 
-                    attempt, max_retries, error_text);
-                last_error = Some(anyhow::anyhow!(error_msg));
-                
-                if attempt < max_retries {
-                    // Exponential backoff: 1s, 2s, 4s
-                    let delay = std::time::Duration::from_secs(2_u64.pow(attempt - 1));
-                    eprintln!("⏳ API overloaded, retrying in {}s... (attempt {}/{})", 
-                        delay.as_secs(), attempt, max_retries);
-                    tokio::time::sleep(delay).await;
-                    continue;
-                }
-            } else {
-                // For non-503 errors, fail immediately
-                return Err(anyhow::anyhow!(
-                    "Gemini API request failed: {} - {}",
-                    status,
-                    error_text
-                ));
-            }
+        let response = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| ArrowheadError::from_reqwest("gemini", &e))?;
+
+        let status = response.status();
+        if status.is_success() {
+            let gemini_response: GeminiResponse = response.json().await.map_err(|e| ArrowheadError::from_reqwest("gemini", &e))?;
+            return Ok(gemini_response);
         }
 
-        // If we get here, all retries failed
-        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("All retry attempts failed")))
+        // Retries of overloaded and rate-limited requests are left to RetryingLLMClient
+        let retry_after = ArrowheadError::retry_after(response.headers());
+        let error_text = response.text().await?;
+        Err(ArrowheadError::from_status("gemini", status.as_u16(), retry_after, error_text).into())
     }
 
     /// Convert Gemini response to our Message format
@@ -208,6 +188,39 @@ impl GeminiClient {
             })
         }
     }
+
+    /// Embed `text` with an embedding model such as `text-embedding-004`.
+    /// `dimensions` shortens the vector on models that support it.
+    pub async fn embed(&self, model: &str, text: &str, dimensions: Option<usize>) -> Result<Vec<f32>> {
+        let url = format!(
+            "{}/models/{}:embedContent?key={}",
+            self.config.base_url, model, self.config.api_key
+        );
+        let request = GeminiEmbedRequest {
+            model: format!("models/{}", model),
+            content: GeminiEmbedContent { parts: vec![GeminiPart { text: text.to_string() }] },
+            output_dimensionality: dimensions,
+        };
+
+        let response = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| ArrowheadError::from_reqwest("gemini", &e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = ArrowheadError::retry_after(response.headers());
+            let error_text = response.text().await?;
+            return Err(ArrowheadError::from_status("gemini", status.as_u16(), retry_after, error_text).into());
+        }
+
+        let embed_response: GeminiEmbedResponse = response.json().await.map_err(|e| ArrowheadError::from_reqwest("gemini", &e))?;
+        Ok(embed_response.embedding.values)
+    }
 }
 
 #[async_trait]
@@ -531,6 +544,30 @@ struct GeminiSafetyRating {
     probability: String,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiEmbedRequest {
+    model: String,
+    content: GeminiEmbedContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_dimensionality: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct GeminiEmbedContent {
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiEmbedResponse {
+    embedding: GeminiEmbeddingValues,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiEmbeddingValues {
+    values: Vec<f32>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
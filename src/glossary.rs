@@ -0,0 +1,526 @@
+use anyhow::{bail, Result};
+use std::ops::Range;
+use std::time::Instant;
+
+use crate::cli::{GlossaryAction, GlossaryArgs};
+use crate::config::{Config, GlossarySettings};
+use crate::confirm::{confirm, Operation, OperationClass};
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::vault_transaction::{VaultStore, VaultTransaction, DEFAULT_READ_CONCURRENCY};
+
+/// Folders scanned by `glossary check --all`
+const CHECKED_FOLDERS: &[&str] = &["Notes", "Todos", "Goals"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleKind {
+    /// The term must always be written with this exact casing (e.g. "GitHub")
+    Casing,
+    /// The term is banned and must be replaced (e.g. "client" -> "customer")
+    Replacement,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlossaryRule {
+    pub term: String,
+    pub replacement: String,
+    pub kind: RuleKind,
+}
+
+/// A glossary term used in text where it shouldn't be
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlossaryViolation {
+    pub line: usize,
+    pub found: String,
+    pub suggestion: String,
+}
+
+#[derive(Debug, Clone)]
+struct Match {
+    range: Range<usize>,
+    replacement: String,
+}
+
+/// Team terminology: preferred spellings and banned terms with their replacements.
+/// Rules come from the `[glossary]` config section and the vault's glossary note.
+#[derive(Debug, Clone, Default)]
+pub struct Glossary {
+    rules: Vec<GlossaryRule>,
+}
+
+impl Glossary {
+    pub fn new(rules: Vec<GlossaryRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn from_settings(settings: &GlossarySettings) -> Self {
+        let mut rules: Vec<GlossaryRule> = settings.preferred.iter()
+            .map(|term| GlossaryRule { term: term.clone(), replacement: term.clone(), kind: RuleKind::Casing })
+            .collect();
+        let mut replacements: Vec<_> = settings.replace.iter().collect();
+        replacements.sort();
+        rules.extend(replacements.into_iter().map(|(term, replacement)| GlossaryRule {
+            term: term.clone(),
+            replacement: replacement.clone(),
+            kind: RuleKind::Replacement,
+        }));
+        Self { rules }
+    }
+
+    /// Parse a glossary note. List items under a heading mentioning "preferred" or
+    /// "casing" are exact spellings; items under "banned"/"avoid"/"replace" headings use
+    /// `term -> replacement`. An arrow anywhere marks a replacement.
+    ///
+    /// ```markdown
+    /// ## Preferred terms
+    /// - GitHub
+    /// ## Banned terms
+    /// - client -> customer
+    /// ```
+    pub fn parse_note(content: &str) -> Self {
+        let mut rules = Vec::new();
+        let mut in_preferred = false;
+
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('#') {
+                let heading = trimmed.to_lowercase();
+                in_preferred = heading.contains("prefer") || heading.contains("casing");
+                continue;
+            }
+
+            let Some(item) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")) else {
+                continue;
+            };
+            let item = item.trim();
+
+            let arrow = ["->", "→", "=>"].iter().find_map(|arrow| item.split_once(arrow));
+            if let Some((term, replacement)) = arrow {
+                let (term, replacement) = (clean_term(term), clean_term(replacement));
+                if !term.is_empty() && !replacement.is_empty() {
+                    rules.push(GlossaryRule { term, replacement, kind: RuleKind::Replacement });
+                }
+            } else if in_preferred {
+                let term = clean_term(item);
+                if !term.is_empty() {
+                    rules.push(GlossaryRule { term: term.clone(), replacement: term, kind: RuleKind::Casing });
+                }
+            }
+        }
+
+        Self { rules }
+    }
+
+    /// Config rules plus the rules in the vault's glossary note, if it exists
+    pub async fn load<S: VaultStore + ?Sized>(store: &S, settings: &GlossarySettings) -> Result<Self> {
+        let mut glossary = Self::from_settings(settings);
+        if let Some(content) = store.read_file(&settings.note_path).await? {
+            glossary.rules.extend(Self::parse_note(&content).rules);
+        }
+        Ok(glossary)
+    }
+
+    pub fn rules(&self) -> &[GlossaryRule] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Short instruction appended to system prompts so generated text follows the glossary
+    pub fn prompt_addendum(&self) -> Option<String> {
+        if self.rules.is_empty() {
+            return None;
+        }
+
+        let mut parts = Vec::new();
+        let preferred: Vec<&str> = self.rules.iter()
+            .filter(|r| r.kind == RuleKind::Casing)
+            .map(|r| r.term.as_str())
+            .collect();
+        if !preferred.is_empty() {
+            parts.push(format!("Always write these terms exactly as shown: {}.", preferred.join(", ")));
+        }
+        let replaced: Vec<String> = self.rules.iter()
+            .filter(|r| r.kind == RuleKind::Replacement)
+            .map(|r| format!("\"{}\" instead of \"{}\"", r.replacement, r.term))
+            .collect();
+        if !replaced.is_empty() {
+            parts.push(format!("Use {}.", replaced.join(", ")));
+        }
+
+        Some(format!("Terminology: {}", parts.join(" ")))
+    }
+
+    /// Append the glossary addendum to a system prompt
+    pub fn system_prompt(&self, base: &str) -> String {
+        match self.prompt_addendum() {
+            Some(addendum) => format!("{}\n\n{}", base, addendum),
+            None => base.to_string(),
+        }
+    }
+
+    /// Every glossary violation outside code, quotes, links and frontmatter
+    pub fn check(&self, text: &str) -> Vec<GlossaryViolation> {
+        self.find_matches(text).into_iter()
+            .map(|m| GlossaryViolation {
+                line: text[..m.range.start].matches('\n').count() + 1,
+                found: text[m.range.clone()].to_string(),
+                suggestion: m.replacement,
+            })
+            .collect()
+    }
+
+    /// Rewrite violations, leaving code, quotes, links and frontmatter untouched
+    pub fn apply(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut last = 0;
+        for m in self.find_matches(text) {
+            result.push_str(&text[last..m.range.start]);
+            result.push_str(&m.replacement);
+            last = m.range.end;
+        }
+        result.push_str(&text[last..]);
+        result
+    }
+
+    fn find_matches(&self, text: &str) -> Vec<Match> {
+        if self.rules.is_empty() {
+            return Vec::new();
+        }
+
+        let lower = text.to_lowercase();
+        // Lowercasing can change byte lengths for some scripts; only match when offsets line up
+        if lower.len() != text.len() {
+            return Vec::new();
+        }
+
+        let mut matches = Vec::new();
+        for segment in editable_ranges(text) {
+            for rule in &self.rules {
+                let term = rule.term.to_lowercase();
+                let mut search_from = segment.start;
+                while let Some(offset) = lower[search_from..segment.end].find(&term) {
+                    let start = search_from + offset;
+                    let mut end = start + term.len();
+                    search_from = end;
+
+                    if !is_boundary(text, start, true) {
+                        continue;
+                    }
+                    // Allow simple plurals of replaced terms ("clients" -> "customers")
+                    let plural = rule.kind == RuleKind::Replacement
+                        && text[end..segment.end].starts_with(['s', 'S'])
+                        && is_boundary(text, end + 1, false);
+                    if plural {
+                        end += 1;
+                    } else if !is_boundary(text, end, false) {
+                        continue;
+                    }
+
+                    let found = &text[start..end];
+                    let replacement = match rule.kind {
+                        RuleKind::Casing => rule.term.clone(),
+                        RuleKind::Replacement => {
+                            let base = match_case(&found[..term.len()], &rule.replacement);
+                            if plural { format!("{}{}", base, &found[term.len()..]) } else { base }
+                        }
+                    };
+                    if replacement != found {
+                        matches.push(Match { range: start..end, replacement });
+                    }
+                }
+            }
+        }
+
+        // Longer matches win when rules overlap
+        matches.sort_by(|a, b| a.range.start.cmp(&b.range.start).then(b.range.end.cmp(&a.range.end)));
+        let mut kept: Vec<Match> = Vec::with_capacity(matches.len());
+        for m in matches {
+            if kept.last().is_none_or(|prev| m.range.start >= prev.range.end) {
+                kept.push(m);
+            }
+        }
+        kept
+    }
+}
+
+fn clean_term(term: &str) -> String {
+    term.trim().trim_matches(|c| matches!(c, '*' | '_' | '`' | '"')).trim().to_string()
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// True when the character on the outside of `index` doesn't continue the word
+fn is_boundary(text: &str, index: usize, before: bool) -> bool {
+    let neighbour = if before { text[..index].chars().next_back() } else { text[index..].chars().next() };
+    !neighbour.is_some_and(is_word_char)
+}
+
+/// Carry the casing of the original word over to its replacement:
+/// "Client" -> "Customer" at sentence start, "CLIENT" -> "CUSTOMER"
+fn match_case(found: &str, replacement: &str) -> String {
+    let letters: Vec<char> = found.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        return replacement.to_uppercase();
+    }
+    let starts_upper = found.chars().next().is_some_and(char::is_uppercase);
+    let replacement_lower = replacement.chars().all(|c| !c.is_uppercase());
+    if starts_upper && replacement_lower {
+        let mut chars = replacement.chars();
+        return match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        };
+    }
+    replacement.to_string()
+}
+
+/// Byte ranges of prose that may be rewritten. Frontmatter, fenced code, block quotes,
+/// inline code, quoted text, links, wikilinks, tags and URLs are left alone.
+fn editable_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    let mut in_frontmatter = false;
+    let mut fence: Option<&str> = None;
+
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+
+        if index == 0 && trimmed == "---" {
+            in_frontmatter = true;
+            continue;
+        }
+        if in_frontmatter {
+            if trimmed == "---" {
+                in_frontmatter = false;
+            }
+            continue;
+        }
+
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            fence = Some(marker);
+            continue;
+        }
+        if trimmed.starts_with('>') {
+            continue;
+        }
+
+        inline_editable_ranges(line, start, &mut ranges);
+    }
+
+    ranges
+}
+
+fn inline_editable_ranges(line: &str, base: usize, ranges: &mut Vec<Range<usize>>) {
+    let bytes = line.as_bytes();
+    let mut segment_start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let rest = &line[i..];
+        let starts_token = rest.starts_with("http://")
+            || rest.starts_with("https://")
+            || (rest.starts_with('#')
+                && (i == 0 || bytes[i - 1].is_ascii_whitespace())
+                && rest[1..].starts_with(char::is_alphanumeric));
+
+        let protected_end = if let Some(code) = rest.strip_prefix('`') {
+            code.find('`').map(|end| i + end + 2)
+        } else if let Some(quoted) = rest.strip_prefix('"') {
+            quoted.find('"').map(|end| i + end + 2)
+        } else if rest.starts_with("[[") {
+            rest.find("]]").map(|end| i + end + 2)
+        } else if rest.starts_with("](") {
+            rest.find(')').map(|end| i + end + 1)
+        } else if rest.starts_with('\u{201c}') {
+            rest.find('\u{201d}').map(|end| i + end + '\u{201d}'.len_utf8())
+        } else if starts_token {
+            // URLs and #tags run until the next whitespace
+            Some(i + rest.find(char::is_whitespace).unwrap_or(rest.len()))
+        } else {
+            None
+        };
+
+        match protected_end {
+            Some(end) => {
+                if i > segment_start {
+                    ranges.push(base + segment_start..base + i);
+                }
+                i = end;
+                segment_start = end;
+            }
+            None => i += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    if bytes.len() > segment_start {
+        ranges.push(base + segment_start..base + bytes.len());
+    }
+}
+
+pub async fn handle_glossary_command(args: GlossaryArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let glossary = Glossary::load(adapter, &config.glossary).await?;
+    if glossary.is_empty() {
+        println!(
+            "No glossary found. Add rules to {} or a [glossary] section in your config.",
+            config.glossary.note_path
+        );
+        return Ok(());
+    }
+
+    match args.action {
+        GlossaryAction::Check { path, all, fix, yes } => {
+            let paths = match (path, all) {
+                (Some(path), false) => vec![if path.ends_with(".md") { path } else { format!("{}.md", path) }],
+                (None, true) => {
+                    let mut paths = Vec::new();
+                    for folder in CHECKED_FOLDERS {
+                        if let Ok(files) = adapter.list_files_in_folder(folder).await {
+                            paths.extend(files.iter().filter(|f| f.ends_with(".md")).map(|f| format!("{}/{}", folder, f)));
+                        }
+                    }
+                    paths
+                }
+                (Some(_), true) => bail!("Pass either a path or --all, not both"),
+                (None, false) => bail!("Pass a note path or --all"),
+            };
+
+            let paths: Vec<String> = paths.into_iter().filter(|p| *p != config.glossary.note_path).collect();
+            let started = Instant::now();
+            let files = adapter.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await;
+            let elapsed = started.elapsed().as_secs_f64();
+            if paths.len() > 1 {
+                println!(
+                    "Read {} file(s) in {:.2}s ({:.0} files/sec)\n",
+                    paths.len(), elapsed, paths.len() as f64 / elapsed.max(0.001)
+                );
+            }
+
+            let mut transaction = VaultTransaction::new("glossary fix");
+            let mut total = 0;
+            let mut fixed = 0;
+            for (path, content) in &files {
+                let content = match content {
+                    Ok(content) => content,
+                    Err(e) => {
+                        println!("⚠️  {}: {}", path, e);
+                        continue;
+                    }
+                };
+                let violations = glossary.check(content);
+                if violations.is_empty() {
+                    continue;
+                }
+                total += violations.len();
+                println!("{}", path);
+                for violation in &violations {
+                    println!("  line {}: \"{}\" -> \"{}\"", violation.line, violation.found, violation.suggestion);
+                }
+                if fix {
+                    transaction = transaction.write(path, &glossary.apply(content));
+                    fixed += 1;
+                }
+            }
+
+            if total == 0 {
+                println!("✅ No glossary violations in {} file(s)", paths.len());
+                return Ok(());
+            }
+            println!("\n{} violation(s) found", total);
+
+            if fix {
+                let operation = Operation::new(OperationClass::BulkWrite, format!("Rewrite {} file(s)", fixed), fixed);
+                if !confirm(&config.confirmations, &operation, yes)? {
+                    println!("Nothing changed.");
+                    return Ok(());
+                }
+                let report = transaction.execute(adapter).await?;
+                if let Err(e) = report.append_to_log(adapter.transaction_log()) {
+                    eprintln!("Warning: could not write transaction log: {}", e);
+                }
+                println!("{}", report.summary());
+                if !report.is_committed() {
+                    bail!("Glossary fixes were not applied");
+                }
+            } else {
+                println!("Run again with --fix to apply the replacements.");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glossary() -> Glossary {
+        Glossary::parse_note("# Glossary\n\n## Preferred terms\n- GitHub\n- **Arrowhead**\n\n## Banned terms\n- client -> customer\n- todo app → task manager\n")
+    }
+
+    #[test]
+    fn test_parse_note() {
+        let rules = glossary().rules;
+        assert_eq!(rules.len(), 4);
+        assert_eq!(rules[1].term, "Arrowhead");
+        assert_eq!(rules[3], GlossaryRule {
+            term: "todo app".to_string(),
+            replacement: "task manager".to_string(),
+            kind: RuleKind::Replacement,
+        });
+    }
+
+    #[test]
+    fn test_casing_preserved_at_sentence_start() {
+        let glossary = glossary();
+        assert_eq!(glossary.apply("Client asked. The client agreed."), "Customer asked. The customer agreed.");
+        assert_eq!(glossary.apply("CLIENT MEETING"), "CUSTOMER MEETING");
+        assert_eq!(glossary.apply("Our clients love it"), "Our customers love it");
+        assert_eq!(glossary.apply("github and Github"), "GitHub and GitHub");
+        assert_eq!(glossary.apply("Todo app launch"), "Task manager launch");
+    }
+
+    #[test]
+    fn test_terms_inside_other_words_are_untouched() {
+        let glossary = glossary();
+        let text = "The clientele uses client-side rendering via GitHubber and arrowheads.";
+        assert_eq!(glossary.apply(text), text);
+        assert!(glossary.check(text).is_empty());
+    }
+
+    #[test]
+    fn test_code_quotes_and_links_are_protected() {
+        let glossary = glossary();
+        let text = "---\naudience: client\n---\nThe client said \"ask the client\".\n```\nclient.connect()\n```\n> client quote\nSee `client` and [[client notes]] or [docs](https://github.com/client).\n";
+        let expected = "---\naudience: client\n---\nThe customer said \"ask the client\".\n```\nclient.connect()\n```\n> client quote\nSee `client` and [[client notes]] or [docs](https://github.com/client).\n";
+        assert_eq!(glossary.apply(text), expected);
+
+        let violations = glossary.check(text);
+        assert_eq!(violations, vec![GlossaryViolation {
+            line: 4,
+            found: "client".to_string(),
+            suggestion: "customer".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_prompt_addendum() {
+        let prompt = glossary().system_prompt("You are a writer.");
+        assert!(prompt.starts_with("You are a writer.\n\nTerminology:"));
+        assert!(prompt.contains("GitHub, Arrowhead"));
+        assert!(prompt.contains("\"customer\" instead of \"client\""));
+        assert_eq!(Glossary::default().system_prompt("base"), "base");
+    }
+}
@@ -1,6 +1,11 @@
-use crate::cli::{GoalAction, GoalArgs};
-use crate::obsidian_adapter::ObsidianAdapter; // Reusing for parsing
+use crate::cli::{GoalAction, GoalArgs, OutputFormat};
+use crate::confirm::StdinPrompt;
+use crate::dates;
+use crate::obsidian_adapter::{with_force_hint, ObsidianAdapter}; // Reusing for parsing
+use crate::output::{print_json, NoteRecord};
+use crate::title_match::{TitleCandidate, TitleResolver};
 use crate::utils::slugify; // Import slugify from utils
+use crate::vault_transaction::{VaultStore, DEFAULT_READ_CONCURRENCY};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_yaml;
@@ -19,7 +24,7 @@ struct GoalFrontmatter {
 // For now, let's try to manage GoalFrontmatter separately for serialization
 // and be careful with parsing if we use the generic adapter's parser.
 
-pub async fn handle_goal_command(args: GoalArgs, adapter: &ObsidianAdapter) -> Result<()> {
+pub async fn handle_goal_command(args: GoalArgs, format: OutputFormat, adapter: &ObsidianAdapter) -> Result<()> {
     let goals_dir = "Goals"; // Define a base directory for goals
 
     match args.action {
@@ -30,6 +35,9 @@ pub async fn handle_goal_command(args: GoalArgs, adapter: &ObsidianAdapter) -> R
             tags,
         } => {
             println!("Attempting to add goal: '{}'", title);
+            let target_date = target_date.as_deref()
+                .map(|input| dates::frontmatter_date(input, "target", dates::configured_today()?))
+                .transpose()?;
 
             let fm_tags = if tags.is_empty() {
                 None
@@ -38,7 +46,7 @@ pub async fn handle_goal_command(args: GoalArgs, adapter: &ObsidianAdapter) -> R
             };
             let frontmatter = GoalFrontmatter {
                 description: description.clone(),
-                target_date: target_date.clone(),
+                target_date,
                 tags: fm_tags,
                 status: "active".to_string(), // Default status
             };
@@ -74,37 +82,27 @@ pub async fn handle_goal_command(args: GoalArgs, adapter: &ObsidianAdapter) -> R
             println!("Goal '{}' created as '{}'.", title, file_name);
         }
         GoalAction::List { status } => {
+            let records = goal_records(adapter, goals_dir, status.as_deref()).await?;
+            if format == OutputFormat::Json {
+                return print_json(&records);
+            }
             println!("Listing goals...");
-            if let Some(s) = status {
+            if let Some(s) = &status {
                 println!("  Status filter: {}", s);
             }
-            // TODO: Implement goal listing functionality
-            // Current state: Placeholder that just prints a message
-            // 
-            // Implementation requirements:
-            // 1. Directory scanning approach:
-            //    - Use Obsidian API to GET /vault/ to list all files
-            //    - Filter results by path prefix (e.g., starts with "Goals/")
-            //    - Parse each goal file to extract metadata and status
-            //    - Apply status filter if provided by user
-            // 2. Parse goal files:
-            //    - Extract frontmatter data (title, description, status, target_date, tags)
-            //    - Parse goal content for progress indicators
-            //    - Calculate completion percentage if applicable
-            // 3. Display formatting:
-            //    - Group goals by status (active, achieved, on-hold, etc.)
-            //    - Show progress indicators and target dates
-            //    - Color-code based on status and urgency
-            //    - Display tags and categories
-            // 4. Sorting and filtering:
-            //    - Sort by target date, creation date, or priority
-            //    - Filter by status, tags, or date ranges
-            //    - Support search within goal titles and descriptions
-            // 5. Progress tracking:
-            //    - Show progress towards target dates
-            //    - Highlight overdue or at-risk goals
-            //    - Display achievement statistics
-            println!("(Placeholder: Actual goal listing logic to be implemented. Similar challenges to other types.)");
+            if records.is_empty() {
+                println!("No goals found.");
+                return Ok(());
+            }
+            for record in &records {
+                let title = record.extra.get("title").and_then(|title| title.as_str()).unwrap_or(&record.path);
+                let status = record.frontmatter.status.as_deref().unwrap_or("active");
+                match &record.frontmatter.target_date {
+                    Some(date) => println!("• {} [{}] (target: {})", title, status, date),
+                    None => println!("• {} [{}]", title, status),
+                }
+            }
+            println!("Total goals shown: {}", records.len());
         }
         GoalAction::Update {
             id,
@@ -112,9 +110,13 @@ pub async fn handle_goal_command(args: GoalArgs, adapter: &ObsidianAdapter) -> R
             description: new_description,
             status: new_status,
             target_date: new_target_date,
+            force,
         } => {
-            let file_slug = slugify(&id); // Assuming id is the original title/slug
-            let file_name = format!("{}/{}.md", goals_dir, file_slug);
+            let new_target_date = new_target_date.as_deref()
+                .map(|input| dates::frontmatter_date(input, "target", dates::configured_today()?))
+                .transpose()?;
+            let file_name = find_goal(adapter, goals_dir, &id).await?;
+            let file_slug = file_name.trim_start_matches(&format!("{}/", goals_dir)).trim_end_matches(".md").to_string();
             println!("Attempting to update goal: '{}'", file_name);
 
             // Fetch existing file data
@@ -197,21 +199,28 @@ pub async fn handle_goal_command(args: GoalArgs, adapter: &ObsidianAdapter) -> R
                 file_name
             };
 
-            adapter
-                .update_file(&target_file_name, &new_full_content)
-                .await
+            let saved = if force {
+                adapter.overwrite_file(&target_file_name, &new_full_content).await
+            } else {
+                adapter.update_file(&target_file_name, &new_full_content).await
+            };
+            saved
+                .map_err(with_force_hint)
                 .context(format!("Failed to update goal file '{}'", target_file_name))?;
 
             println!("Goal '{}' updated.", target_file_name);
         }
         GoalAction::View { id } => {
-            let file_name = format!("{}/{}.md", goals_dir, slugify(&id));
-            println!("Viewing goal: '{}'", file_name);
-
+            let file_name = find_goal(adapter, goals_dir, &id).await?;
             let content = adapter.get_file(&file_name).await.context(format!(
                 "Failed to retrieve goal '{}' for viewing.",
                 file_name
             ))?;
+            if format == OutputFormat::Json {
+                return print_json(&goal_record(&file_name, &content).with_content(&content));
+            }
+
+            println!("Viewing goal: '{}'", file_name);
 
             println!("--- Content of {} ---", file_name);
             println!("{}", content);
@@ -221,6 +230,61 @@ pub async fn handle_goal_command(args: GoalArgs, adapter: &ObsidianAdapter) -> R
     Ok(())
 }
 
+/// A goal's title is its H1, falling back to the file name
+fn goal_record(path: &str, content: &str) -> NoteRecord {
+    let title = content
+        .lines()
+        .find_map(|line| line.strip_prefix("# "))
+        .map(|title| title.trim().to_string())
+        .unwrap_or_else(|| path.rsplit('/').next().unwrap_or(path).trim_end_matches(".md").to_string());
+    // `description` lives in the goal's own frontmatter, not the shared one
+    let description = content
+        .strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---"))
+        .and_then(|(yaml, _)| serde_yaml::from_str::<GoalFrontmatter>(yaml).ok())
+        .and_then(|frontmatter| frontmatter.description);
+    NoteRecord::parse(path, content).with("title", title).with("description", description)
+}
+
+/// Path of the goal `id` names: its title slugified to the file name, or failing that the
+/// goal whose title is closest. Several close titles are offered to choose from.
+async fn find_goal(adapter: &ObsidianAdapter, goals_dir: &str, id: &str) -> Result<String> {
+    let records = goal_records(adapter, goals_dir, None).await?;
+    let file_name = format!("{}/{}.md", goals_dir, slugify(id));
+    if records.iter().any(|record| record.path == file_name) {
+        return Ok(file_name);
+    }
+
+    let candidates: Vec<TitleCandidate> = records.into_iter()
+        .map(|record| TitleCandidate {
+            title: record.extra.get("title").and_then(|title| title.as_str()).unwrap_or_default().to_string(),
+            tags: record.frontmatter.tags.unwrap_or_default(),
+            path: record.path,
+        })
+        .collect();
+    let chosen = TitleResolver::default().pick("goal", id, &candidates, &mut StdinPrompt)?;
+    eprintln!("Matched \"{}\" to '{}'.", id, chosen.path);
+    Ok(chosen.path)
+}
+
+async fn goal_records(adapter: &ObsidianAdapter, goals_dir: &str, status: Option<&str>) -> Result<Vec<NoteRecord>> {
+    let paths: Vec<String> = adapter.list_files_in_folder(goals_dir).await
+        .context("Failed to list goals")?
+        .into_iter()
+        .filter(|file| file.ends_with(".md"))
+        .map(|file| format!("{}/{}", goals_dir, file))
+        .collect();
+    let mut records = Vec::new();
+    for (path, content) in adapter.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await {
+        let content = content.with_context(|| format!("Failed to read {}", path))?;
+        let record = goal_record(&path, &content);
+        if status.is_none_or(|status| record.frontmatter.status.as_deref() == Some(status)) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
 // Add a helper in ObsidianAdapter for frontmatter string if it's not already easy to get
 // For MarkdownFile in obsidian_adapter.rs, add:
 // impl MarkdownFile {
@@ -279,3 +343,22 @@ pub async fn handle_goal_command(args: GoalArgs, adapter: &ObsidianAdapter) -> R
 // The fields not present in `ObsidianFrontmatter` but present in `GoalFrontmatter` will be `None` or default.
 // And fields in `ObsidianFrontmatter` not in `GoalFrontmatter` will be ignored during `GoalFrontmatter` deserialization.
 // This is okay.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_goal_record_reads_title_and_goal_frontmatter() {
+        let raw = "---\ndescription: Finish the first draft\ntarget_date: 2024-12-31\ntags:\n- writing\nstatus: active\n---\n\n# Write a book\n";
+        let json = serde_json::to_value(goal_record("Goals/write-a-book.md", raw)).unwrap();
+        assert_eq!(json["title"], "Write a book");
+        assert_eq!(json["description"], "Finish the first draft");
+        assert_eq!(json["target_date"], "2024-12-31");
+        assert_eq!(json["status"], "active");
+
+        let json = serde_json::to_value(goal_record("Goals/untitled.md", "no heading")).unwrap();
+        assert_eq!(json["title"], "untitled");
+        assert_eq!(json["description"], serde_json::Value::Null);
+    }
+}
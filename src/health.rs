@@ -0,0 +1,619 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::HealthArgs;
+use crate::config::{Config, HealthWeights};
+use crate::embedding_store::read_store;
+use crate::managed_sections::without_managed_sections;
+use crate::notes::link_key;
+use crate::cache::CachePaths;
+use crate::obsidian_adapter::{analysis_schema, content_cache_key, ObsidianAdapter, ANALYSIS_VERSION};
+use crate::report::{DetailLevel, Report, ReportView};
+use crate::vault_transaction::{VaultStore, DEFAULT_READ_CONCURRENCY};
+
+/// Daily scores kept in the history file
+const HISTORY_LIMIT: usize = 365;
+/// Most recent scores drawn in the trend sparkline
+const SPARKLINE_WIDTH: usize = 12;
+pub(crate) const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Components flagged with a remedy
+const WEAKEST_SHOWN: usize = 3;
+/// Paths listed per component by `--details`
+const DETAILS_SHOWN: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthComponent {
+    Tagged,
+    Linked,
+    Analyzed,
+    Embedded,
+    Fresh,
+    NoBrokenLinks,
+    SchemaCurrent,
+}
+
+impl HealthComponent {
+    pub const ALL: [HealthComponent; 7] = [
+        Self::Tagged,
+        Self::Linked,
+        Self::Analyzed,
+        Self::Embedded,
+        Self::Fresh,
+        Self::NoBrokenLinks,
+        Self::SchemaCurrent,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Tagged => "tagged",
+            Self::Linked => "linked in or out",
+            Self::Analyzed => "analyzed",
+            Self::Embedded => "embedded",
+            Self::Fresh => "not stale",
+            Self::NoBrokenLinks => "no broken links",
+            Self::SchemaCurrent => "current frontmatter",
+        }
+    }
+
+    /// What to run to improve this component
+    pub fn remedy(self) -> &'static str {
+        match self {
+            Self::Tagged | Self::Linked => {
+                "arrowhead organize --all --plan-note Plans/organize.md, review the plan, then arrowhead apply-plan Plans/organize.md"
+            }
+            Self::NoBrokenLinks => "arrowhead health --details lists the broken links; arrowhead note rename keeps links intact when renaming",
+            Self::SchemaCurrent => "arrowhead health --details lists notes whose frontmatter does not parse or predates the current analysis version",
+            Self::Analyzed | Self::Embedded | Self::Fresh => "no batch analyze/embed command exists yet",
+        }
+    }
+
+    fn weight(self, weights: &HealthWeights) -> f64 {
+        match self {
+            Self::Tagged => weights.tagged,
+            Self::Linked => weights.linked,
+            Self::Analyzed => weights.analyzed,
+            Self::Embedded => weights.embedded,
+            Self::Fresh => weights.fresh,
+            Self::NoBrokenLinks => weights.no_broken_links,
+            Self::SchemaCurrent => weights.schema_current,
+        }
+    }
+}
+
+/// How many notes meet each hygiene criterion; the input to [`health_score`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VaultSummary {
+    pub notes: usize,
+    pub tagged: usize,
+    /// Notes that link to, or are linked from, another note
+    pub linked: usize,
+    pub analyzed: usize,
+    pub embedded: usize,
+    /// Notes without an outdated embedding or an analysis older than `stale_days`
+    pub fresh: usize,
+    pub no_broken_links: usize,
+    pub schema_current: usize,
+}
+
+impl VaultSummary {
+    pub fn count(&self, component: HealthComponent) -> usize {
+        match component {
+            HealthComponent::Tagged => self.tagged,
+            HealthComponent::Linked => self.linked,
+            HealthComponent::Analyzed => self.analyzed,
+            HealthComponent::Embedded => self.embedded,
+            HealthComponent::Fresh => self.fresh,
+            HealthComponent::NoBrokenLinks => self.no_broken_links,
+            HealthComponent::SchemaCurrent => self.schema_current,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentScore {
+    pub component: HealthComponent,
+    /// Share of notes meeting the criterion, 0–100
+    pub percent: f64,
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthScore {
+    /// Weighted mean of the component percentages, 0–100
+    pub score: f64,
+    pub components: Vec<ComponentScore>,
+}
+
+impl HealthScore {
+    /// The lowest weighted components that are not already at 100%
+    pub fn weakest(&self) -> Vec<&ComponentScore> {
+        let mut candidates: Vec<&ComponentScore> = self.components.iter()
+            .filter(|c| c.weight > 0.0 && c.percent < 100.0)
+            .collect();
+        candidates.sort_by(|a, b| a.percent.total_cmp(&b.percent).then(b.weight.total_cmp(&a.weight)));
+        candidates.truncate(WEAKEST_SHOWN);
+        candidates
+    }
+}
+
+/// Combine the component percentages into one score. An empty vault is fully healthy.
+pub fn health_score(summary: &VaultSummary, weights: &HealthWeights) -> HealthScore {
+    let components: Vec<ComponentScore> = HealthComponent::ALL.iter()
+        .map(|&component| ComponentScore {
+            component,
+            percent: if summary.notes == 0 { 100.0 } else { 100.0 * summary.count(component) as f64 / summary.notes as f64 },
+            weight: component.weight(weights).max(0.0),
+        })
+        .collect();
+    let total_weight: f64 = components.iter().map(|c| c.weight).sum();
+    let score = if total_weight == 0.0 {
+        0.0
+    } else {
+        components.iter().map(|c| c.percent * c.weight).sum::<f64>() / total_weight
+    };
+    HealthScore { score, components }
+}
+
+/// A summary plus the notes behind its two actionable failures
+#[derive(Debug, Default)]
+pub struct VaultScan {
+    pub summary: VaultSummary,
+    /// (note, link target) pairs that resolve to no scanned note
+    pub broken_links: Vec<(String, String)>,
+    pub outdated_schema: Vec<String>,
+}
+
+fn note_name(path: &str) -> &str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.strip_suffix(".md").unwrap_or(name)
+}
+
+/// Targets of the `[[wikilinks]]` in `body`, skipping heading-only links and attachments
+fn wikilink_targets(body: &str) -> Vec<String> {
+    let mut targets = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("[[") {
+        let inner = &rest[start + 2..];
+        let Some(end) = inner.find("]]") else { break };
+        let link = &inner[..end];
+        let target = link[..link.find(['|', '#']).unwrap_or(link.len())].trim();
+        let name = note_name(target);
+        let is_attachment = !target.ends_with(".md") && name.contains('.');
+        if !name.is_empty() && !is_attachment {
+            targets.push(name.to_string());
+        }
+        rest = &inner[end + 2..];
+    }
+    targets
+}
+
+fn has_inline_tag(body: &str) -> bool {
+    body.split_whitespace()
+        .filter_map(|word| word.strip_prefix('#'))
+        .any(|tag| tag.starts_with(|c: char| c.is_alphabetic()))
+}
+
+/// Measure every component over `notes` without calling the LLM. `embeddings` maps
+/// note paths to the content hash stored with their embedding.
+pub fn scan_notes(
+    notes: &[(String, String)],
+    embeddings: &HashMap<String, String>,
+    stale_days: i64,
+    now: DateTime<Utc>,
+) -> VaultScan {
+    let known: HashSet<String> = notes.iter().map(|(path, _)| link_key(note_name(path))).collect();
+    let links: Vec<Vec<String>> = notes.iter()
+        .map(|(_, content)| {
+            let body = ObsidianAdapter::parse_markdown_file(content).map_or(content.clone(), |file| file.content);
+            wikilink_targets(&without_managed_sections(&body, &[]))
+        })
+        .collect();
+    let linked_to: HashSet<String> = notes.iter().zip(&links)
+        .flat_map(|((path, _), targets)| {
+            let own = link_key(note_name(path));
+            targets.iter().map(|target| link_key(target)).filter(move |target| *target != own)
+        })
+        .collect();
+
+    let mut scan = VaultScan { summary: VaultSummary { notes: notes.len(), ..Default::default() }, ..Default::default() };
+    for ((path, content), targets) in notes.iter().zip(&links) {
+        let summary = &mut scan.summary;
+        let parsed = ObsidianAdapter::parse_markdown_file(content).ok();
+        let body = parsed.as_ref().map_or(content.as_str(), |file| file.content.as_str());
+        let frontmatter = parsed.as_ref().map(|file| &file.frontmatter);
+
+        let has_tags = frontmatter.and_then(|f| f.tags.as_ref()).is_some_and(|tags| !tags.is_empty());
+        if has_tags || has_inline_tag(body) {
+            summary.tagged += 1;
+        }
+        if !targets.is_empty() || linked_to.contains(&link_key(note_name(path))) {
+            summary.linked += 1;
+        }
+
+        let analyzed = frontmatter.is_some_and(|f| f.ai_analysis.is_some());
+        if analyzed {
+            summary.analyzed += 1;
+        }
+        let embedding_outdated = match embeddings.get(path) {
+            Some(hash) => {
+                summary.embedded += 1;
+                *hash != content_cache_key(body)
+            }
+            None => false,
+        };
+        let analysis_old = frontmatter
+            .and_then(|f| f.ai_analysis_timestamp.as_deref())
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .is_some_and(|at| now - at.with_timezone(&Utc) > Duration::days(stale_days));
+        if !embedding_outdated && !analysis_old {
+            summary.fresh += 1;
+        }
+
+        let broken: Vec<&String> = targets.iter().filter(|target| !known.contains(&link_key(target))).collect();
+        if broken.is_empty() {
+            summary.no_broken_links += 1;
+        }
+        scan.broken_links.extend(broken.into_iter().map(|target| (path.clone(), target.clone())));
+
+        let version_current = frontmatter.is_some_and(|f| {
+            !analyzed || f.ai_analysis_version.as_deref().map(analysis_schema) == Some(ANALYSIS_VERSION)
+        });
+        if version_current {
+            summary.schema_current += 1;
+        } else {
+            scan.outdated_schema.push(path.clone());
+        }
+    }
+    scan
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthRecord {
+    pub date: NaiveDate,
+    pub score: f64,
+    pub components: BTreeMap<HealthComponent, f64>,
+}
+
+/// One score per day, oldest first
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HealthHistory {
+    pub records: Vec<HealthRecord>,
+}
+
+impl HealthHistory {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).context("Failed to read health history")?;
+        serde_json::from_str(&content).context("Failed to parse health history")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?).context("Failed to write health history")
+    }
+
+    /// Store today's score, replacing an earlier run on the same day
+    pub fn record(&mut self, date: NaiveDate, score: &HealthScore) {
+        self.records.retain(|r| r.date != date);
+        self.records.push(HealthRecord {
+            date,
+            score: score.score,
+            components: score.components.iter().map(|c| (c.component, c.percent)).collect(),
+        });
+        self.records.sort_by_key(|r| r.date);
+        let excess = self.records.len().saturating_sub(HISTORY_LIMIT);
+        self.records.drain(..excess);
+    }
+
+    /// Score change between the newest record at least `days` before `today` and the latest record
+    pub fn delta_since(&self, today: NaiveDate, days: i64) -> Option<f64> {
+        let latest = self.records.last()?;
+        let cutoff = today - Duration::days(days);
+        let earlier = self.records.iter().rev().find(|r| r.date <= cutoff)?;
+        Some(latest.score - earlier.score)
+    }
+
+    /// The latest scores on an absolute 0–100 scale
+    pub fn sparkline(&self) -> String {
+        let start = self.records.len().saturating_sub(SPARKLINE_WIDTH);
+        self.records[start..].iter()
+            .map(|r| SPARK_CHARS[((r.score.clamp(0.0, 100.0) / 100.0) * (SPARK_CHARS.len() - 1) as f64).round() as usize])
+            .collect()
+    }
+}
+
+pub fn default_health_history_path() -> PathBuf {
+    let mut path = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push(".config");
+    path.push("arrowhead");
+    path.push("health_history.json");
+    path
+}
+
+/// Summary: the score and the weakest components on one line. Normal: every component, the
+/// trend and remedies. Detail: the broken links and notes with outdated frontmatter.
+pub fn health_report(scan: &VaultScan, score: &HealthScore, history: &HealthHistory, delta: Option<f64>) -> Report {
+    let mut headline = format!("Vault health: {:.0}/100", score.score);
+    match delta {
+        Some(delta) => { let _ = writeln!(headline, " ({:+.1} since last week)", delta); }
+        None => headline.push('\n'),
+    }
+    let mut report = Report::new()
+        .text(DetailLevel::Summary, headline)
+        .field(DetailLevel::Summary, "score", score.score)
+        .field(DetailLevel::Summary, "delta_since_last_week", delta)
+        .field(DetailLevel::Normal, "components", &score.components)
+        .field(DetailLevel::Normal, "summary", &scan.summary)
+        .field(DetailLevel::Detail, "broken_links", scan.broken_links.iter()
+            .map(|(note, target)| serde_json::json!({ "note": note, "target": target }))
+            .collect::<Vec<_>>())
+        .field(DetailLevel::Detail, "outdated_schema", &scan.outdated_schema);
+
+    let weakest = score.weakest();
+    if !weakest.is_empty() {
+        let labels: Vec<String> = weakest.iter()
+            .map(|c| format!("{} ({:.0}%)", c.component.label(), c.percent))
+            .collect();
+        report = report.text_only(DetailLevel::Summary, format!("Lowest: {}\n", labels.join(", ")));
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{} note(s) scanned\n", scan.summary.notes);
+    for component in &score.components {
+        let _ = writeln!(
+            out,
+            "  {:<20} {:>5.1}%  ({}/{})  weight {}",
+            component.component.label(), component.percent,
+            scan.summary.count(component.component), scan.summary.notes, component.weight
+        );
+    }
+    if history.records.len() > 1 {
+        let _ = writeln!(out, "\nTrend: {}", history.sparkline());
+    }
+    if !weakest.is_empty() {
+        out.push_str("\nLowest components:\n");
+        for (i, component) in weakest.iter().enumerate() {
+            let _ = writeln!(out, "  {}. {} ({:.0}%): {}", i + 1, component.component.label(), component.percent, component.component.remedy());
+        }
+    }
+    report.text(DetailLevel::Normal, out).text(DetailLevel::Detail, render_details(scan))
+}
+
+fn render_details(scan: &VaultScan) -> String {
+    let mut out = String::new();
+    if !scan.broken_links.is_empty() {
+        let _ = writeln!(out, "\nBroken links ({}):", scan.broken_links.len());
+        for (path, target) in scan.broken_links.iter().take(DETAILS_SHOWN) {
+            let _ = writeln!(out, "  {} → [[{}]]", path, target);
+        }
+    }
+    if !scan.outdated_schema.is_empty() {
+        let _ = writeln!(out, "\nOutdated or unparseable frontmatter ({}):", scan.outdated_schema.len());
+        for path in scan.outdated_schema.iter().take(DETAILS_SHOWN) {
+            let _ = writeln!(out, "  {}", path);
+        }
+    }
+    out
+}
+
+/// Every markdown note under `roots`, recursively, with its content
+pub(crate) async fn load_notes_in(adapter: &ObsidianAdapter, roots: &[String]) -> Vec<(String, String)> {
+    let mut folders: Vec<String> = roots.iter().map(|f| f.trim_end_matches('/').to_string()).collect();
+    let mut paths = Vec::new();
+    while let Some(folder) = folders.pop() {
+        let Ok(entries) = adapter.list_files_in_folder(&folder).await else { continue };
+        for entry in entries {
+            let path = format!("{}/{}", folder, entry.trim_end_matches('/'));
+            if entry.ends_with('/') {
+                folders.push(path);
+            } else if entry.ends_with(".md") {
+                paths.push(path);
+            }
+        }
+    }
+    adapter.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await
+        .into_iter()
+        .filter_map(|(path, content)| content.ok().map(|content| (path, content)))
+        .collect()
+}
+
+fn load_embedding_hashes(path: &Path) -> HashMap<String, String> {
+    if !path.exists() {
+        return HashMap::new();
+    }
+    match read_store(path) {
+        Ok(store) => store.database.into_documents().map(|doc| (doc.path, doc.content_hash)).collect(),
+        Err(e) => {
+            log::warn!("could not read embedding store: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+pub async fn handle_health_command(args: HealthArgs, view: ReportView, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let notes = load_notes_in(adapter, &config.health.folders).await;
+    let embeddings = load_embedding_hashes(&CachePaths::for_config(&config).embeddings());
+    let scan = scan_notes(&notes, &embeddings, config.health.stale_days, Utc::now());
+    let score = health_score(&scan.summary, &config.health.weights);
+
+    let today = Local::now().date_naive();
+    let history_path = default_health_history_path();
+    let mut history = HealthHistory::load(&history_path).unwrap_or_default();
+    history.record(today, &score);
+    if let Err(e) = history.save(&history_path) {
+        log::warn!("could not write health history: {}", e);
+    }
+    let delta = history.delta_since(today, 7);
+
+    let output = serde_json::json!({
+        "score": score.score,
+        "delta_since_last_week": delta,
+        "components": score.components,
+        "summary": scan.summary,
+    });
+    crate::script::publish_output(output);
+    let mut view = view.with_json(args.json);
+    if args.details {
+        view.level = DetailLevel::Detail;
+    }
+    health_report(&scan, &score, &history, delta).print(view)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obsidian_adapter::{ContentAnalysis, Frontmatter, MarkdownFile};
+
+    fn notes(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries.iter().map(|(p, c)| (p.to_string(), c.to_string())).collect()
+    }
+
+    fn analyzed_note(body: &str, version: &str, at: DateTime<Utc>) -> String {
+        let file = MarkdownFile {
+            frontmatter: Frontmatter {
+                ai_analysis: Some(ContentAnalysis::default()),
+                ai_analysis_version: Some(version.to_string()),
+                ai_analysis_timestamp: Some(at.to_rfc3339()),
+                ..Default::default()
+            },
+            content: body.to_string(),
+        };
+        ObsidianAdapter::serialize_markdown_file(&file).unwrap()
+    }
+
+    #[test]
+    fn test_score_is_weighted_mean_of_components() {
+        let summary = VaultSummary {
+            notes: 10, tagged: 5, linked: 10, analyzed: 0, embedded: 0, fresh: 10, no_broken_links: 10, schema_current: 10,
+        };
+        let weights = HealthWeights {
+            tagged: 2.0, linked: 1.0, analyzed: 0.0, embedded: 0.0, fresh: 0.0, no_broken_links: 1.0, schema_current: 0.0,
+        };
+        let score = health_score(&summary, &weights);
+        // (50*2 + 100 + 100) / 4
+        assert!((score.score - 75.0).abs() < 1e-9);
+        // Zero-weight components are not flagged, however low
+        let weakest: Vec<_> = score.weakest().iter().map(|c| c.component).collect();
+        assert_eq!(weakest, vec![HealthComponent::Tagged]);
+
+        let empty = health_score(&VaultSummary::default(), &HealthWeights::default());
+        assert_eq!(empty.score, 100.0);
+        assert!(empty.weakest().is_empty());
+    }
+
+    #[test]
+    fn test_weakest_flags_three_lowest_with_remedies() {
+        let summary = VaultSummary {
+            notes: 4, tagged: 1, linked: 2, analyzed: 0, embedded: 3, fresh: 4, no_broken_links: 2, schema_current: 4,
+        };
+        let score = health_score(&summary, &HealthWeights::default());
+        let weakest: Vec<_> = score.weakest().iter().map(|c| c.component).collect();
+        // linked and no_broken_links tie at 50%; the heavier weight comes first
+        assert_eq!(weakest, vec![HealthComponent::Analyzed, HealthComponent::Tagged, HealthComponent::NoBrokenLinks]);
+        let rendered = health_report(&VaultScan { summary, ..Default::default() }, &score, &HealthHistory::default(), None)
+            .render(DetailLevel::Normal);
+        assert!(rendered.contains("1. analyzed (0%)"));
+        assert!(rendered.contains("arrowhead organize --all"));
+    }
+
+    #[test]
+    fn test_report_tiers() {
+        let summary = VaultSummary {
+            notes: 4, tagged: 1, linked: 2, analyzed: 0, embedded: 3, fresh: 4, no_broken_links: 2, schema_current: 3,
+        };
+        let scan = VaultScan {
+            summary,
+            broken_links: vec![("Notes/alpha.md".to_string(), "Missing Note".to_string())],
+            outdated_schema: vec!["Notes/delta.md".to_string()],
+        };
+        let score = health_score(&scan.summary, &HealthWeights::default());
+        let report = health_report(&scan, &score, &HealthHistory::default(), Some(-2.5));
+        assert_eq!(
+            report.render(DetailLevel::Summary),
+            "Vault health: 54/100 (-2.5 since last week)\nLowest: analyzed (0%), tagged (25%), no broken links (50%)\n"
+        );
+
+        let normal = report.render(DetailLevel::Normal);
+        assert!(normal.starts_with(
+            "Vault health: 54/100 (-2.5 since last week)\n4 note(s) scanned\n\n\
+             \x20 tagged                25.0%  (1/4)  weight 1\n\
+             \x20 linked in or out      50.0%  (2/4)  weight 1\n"
+        ));
+        assert!(normal.contains("\nLowest components:\n  1. analyzed (0%): "));
+        assert!(!normal.contains("Broken links"));
+
+        assert_eq!(
+            report.render(DetailLevel::Detail),
+            format!(
+                "{}\nBroken links (1):\n  Notes/alpha.md → [[Missing Note]]\n\nOutdated or unparseable frontmatter (1):\n  Notes/delta.md\n",
+                normal
+            )
+        );
+
+        let summary_json = report.to_json(DetailLevel::Summary);
+        let keys: Vec<&String> = summary_json.as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["delta_since_last_week", "detail_level", "score"]);
+        assert!(report.to_json(DetailLevel::Normal).get("broken_links").is_none());
+        assert_eq!(
+            report.to_json(DetailLevel::Detail)["broken_links"],
+            serde_json::json!([{ "note": "Notes/alpha.md", "target": "Missing Note" }])
+        );
+    }
+
+    #[test]
+    fn test_scan_measures_each_component() {
+        let now = Utc::now();
+        let old = now - Duration::days(200);
+        let vault = notes(&[
+            ("Notes/alpha.md", "---\ntags: [project]\n---\nSee [[beta]] and [[Missing Note]] and ![[diagram.png]].\n"),
+            ("Notes/beta.md", &analyzed_note("Plain body #idea\n", ANALYSIS_VERSION, now)),
+            ("Notes/gamma.md", &analyzed_note("Old analysis\n", "0.9.0", old)),
+            ("Notes/delta.md", "---\ntags: not a list\n---\nBroken frontmatter [[alpha#Intro|intro]]\n"),
+        ]);
+        let mut embeddings = HashMap::new();
+        embeddings.insert("Notes/beta.md".to_string(), content_cache_key("Plain body #idea\n"));
+        embeddings.insert("Notes/gamma.md".to_string(), content_cache_key("edited since"));
+
+        let scan = scan_notes(&vault, &embeddings, 90, now);
+        assert_eq!(scan.summary, VaultSummary {
+            notes: 4,
+            tagged: 2,          // alpha (frontmatter), beta (inline)
+            linked: 3,          // alpha and delta link out, beta is linked to
+            analyzed: 2,
+            embedded: 2,
+            fresh: 3,           // gamma has an outdated embedding and an old analysis
+            no_broken_links: 3,
+            schema_current: 2,  // gamma's analysis version is old, delta's frontmatter doesn't parse
+        });
+        assert_eq!(scan.broken_links, vec![("Notes/alpha.md".to_string(), "Missing Note".to_string())]);
+        assert_eq!(scan.outdated_schema, vec!["Notes/gamma.md".to_string(), "Notes/delta.md".to_string()]);
+    }
+
+    #[test]
+    fn test_history_keeps_one_score_per_day_and_reports_weekly_delta() {
+        let day = |d: u32| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        let score = |value: f64| HealthScore { score: value, components: Vec::new() };
+        let mut history = HealthHistory::default();
+        history.record(day(1), &score(40.0));
+        history.record(day(5), &score(55.0));
+        assert_eq!(history.delta_since(day(5), 7), None);
+
+        history.record(day(9), &score(60.0));
+        history.record(day(9), &score(70.0));
+        assert_eq!(history.records.len(), 3);
+        assert_eq!(history.delta_since(day(9), 7), Some(30.0));
+        assert_eq!(history.sparkline(), "▄▅▆");
+    }
+}
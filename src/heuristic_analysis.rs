@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::note_skeleton::skeleton;
+use crate::obsidian_adapter::{AnalysisConfig, ContentAnalysis, MarkdownFile};
+use crate::snippets::stem;
+use crate::term_index::{note_words, TermIndex};
+
+/// Suggestions made from a heuristic analysis are trusted this much less than the LLM's
+pub const HEURISTIC_CONFIDENCE: f32 = 0.75;
+
+/// Headings most notes share, which say nothing about what one is about
+const GENERIC_HEADINGS: &[&str] = &[
+    "action items", "agenda", "background", "conclusion", "contents", "details", "ideas",
+    "introduction", "intro", "links", "log", "next steps", "notes", "overview", "questions",
+    "references", "related", "resources", "summary", "table of contents", "tasks", "todo", "todos",
+];
+
+/// Frontmatter fields naming what kind of note it is, in the order they are tried
+const CATEGORY_FIELDS: &[&str] = &["category", "type", "kind"];
+
+/// Where organization recommendations get their content analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalysisBackend {
+    /// Always ask the LLM
+    Ai,
+    /// Never ask the LLM: keywords by TF-IDF, themes from headings, category from frontmatter
+    Heuristic,
+    /// The LLM when one is configured, the heuristics otherwise
+    #[default]
+    Auto,
+}
+
+impl AnalysisBackend {
+    /// Whether notes are sent to the LLM, given whether a client could be created
+    pub fn uses_llm(self, llm_available: bool) -> bool {
+        match self {
+            AnalysisBackend::Ai => true,
+            AnalysisBackend::Heuristic => false,
+            AnalysisBackend::Auto => llm_available,
+        }
+    }
+}
+
+/// A term of the note with its weight and the spelling it is shown with
+struct WeightedTerm {
+    term: String,
+    display: String,
+    weight: f32,
+}
+
+/// The note's terms by TF-IDF weight, heaviest first; ties keep the order the terms first
+/// appear in
+fn weighted_terms(words: &[String], index: &TermIndex) -> Vec<WeightedTerm> {
+    let mut order: Vec<String> = Vec::new();
+    let mut counts: HashMap<String, (usize, HashMap<&str, usize>)> = HashMap::new();
+    for word in words {
+        let term = stem(word);
+        let entry = counts.entry(term.clone()).or_insert_with(|| {
+            order.push(term);
+            (0, HashMap::new())
+        });
+        entry.0 += 1;
+        *entry.1.entry(word.as_str()).or_default() += 1;
+    }
+    let mut terms: Vec<WeightedTerm> = order.into_iter()
+        .map(|term| {
+            let (count, spellings) = &counts[&term];
+            // The commonest spelling; the shortest of equally common ones
+            let display = spellings.iter()
+                .max_by(|a, b| a.1.cmp(b.1).then(b.0.len().cmp(&a.0.len())).then(b.0.cmp(a.0)))
+                .map(|(spelling, _)| spelling.to_string())
+                .unwrap_or_else(|| term.clone());
+            let weight = *count as f32 / words.len() as f32 * index.idf(&term);
+            WeightedTerm { term, display, weight }
+        })
+        .collect();
+    terms.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+    terms
+}
+
+/// Headings of the top three levels that name a topic, heaviest first by the weight their
+/// terms carry in the note
+fn heading_themes(body: &str, weights: &HashMap<&str, f32>) -> Vec<String> {
+    let mut themes: Vec<(String, f32)> = Vec::new();
+    for heading in skeleton(body).headings {
+        if heading.level > 3 || heading.text.is_empty() || GENERIC_HEADINGS.contains(&heading.text.as_str()) {
+            continue;
+        }
+        if themes.iter().any(|(text, _)| *text == heading.text) {
+            continue;
+        }
+        let weight: f32 = note_words(&heading.text).iter().map(|word| weights.get(stem(word).as_str()).copied().unwrap_or(0.0)).sum();
+        themes.push((heading.text, weight));
+    }
+    themes.sort_by(|a, b| b.1.total_cmp(&a.1));
+    themes.into_iter().map(|(text, _)| text).collect()
+}
+
+/// The note's own `category`, `type` or `kind` frontmatter field
+fn frontmatter_category(file: &MarkdownFile) -> Option<String> {
+    CATEGORY_FIELDS.iter()
+        .filter_map(|field| file.frontmatter.extra.get(*field))
+        .find_map(|value| value.as_str().map(str::trim).filter(|value| !value.is_empty()).map(str::to_string))
+}
+
+/// A `ContentAnalysis` made without any network calls: keywords weighed by TF-IDF against
+/// the vault's term index, themes from the note's headings and the category from its
+/// frontmatter. There is no sentiment, summary, entities or concepts, and the sentiment
+/// confidence is 0. A note without headings takes its heaviest keyword as its theme.
+pub fn heuristic_analysis(file: &MarkdownFile, index: &TermIndex, config: &AnalysisConfig) -> ContentAnalysis {
+    let words = note_words(&file.content);
+    let terms = weighted_terms(&words, index);
+    let weights: HashMap<&str, f32> = terms.iter().map(|t| (t.term.as_str(), t.weight)).collect();
+
+    let mut themes = heading_themes(&file.content, &weights);
+    themes.truncate(config.max_themes);
+    let mut keywords: Vec<String> = terms.iter().map(|t| t.display.clone()).collect();
+    if themes.is_empty() && !keywords.is_empty() {
+        themes.push(keywords.remove(0));
+    }
+    keywords.truncate(config.max_themes);
+
+    let word_count = file.content.split_whitespace().count();
+    ContentAnalysis {
+        themes,
+        keywords,
+        category: frontmatter_category(file),
+        reading_time_minutes: Some(word_count.div_ceil(200).max(1) as u32),
+        ..ContentAnalysis::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obsidian_adapter::ObsidianAdapter;
+
+    fn note(content: &str) -> MarkdownFile {
+        ObsidianAdapter::parse_markdown_file(content).unwrap()
+    }
+
+    #[test]
+    fn test_keywords_are_weighed_against_the_vault() {
+        let mut index = TermIndex::default();
+        index.update("a.md", "Meeting about the project schedule");
+        index.update("b.md", "Project meeting, schedule review");
+        index.update("c.md", "Sourdough starter");
+
+        let file = note("Project meeting on sourdough. The project sourdough starter needs feeding; sourdough again.");
+        let analysis = heuristic_analysis(&file, &index, &AnalysisConfig::default());
+        // Without headings the heaviest keyword is the theme
+        assert_eq!(analysis.themes, vec!["sourdough"]);
+        assert_eq!(analysis.keywords[0], "project");
+        // Both appear once, but only one is in most notes
+        let position = |word: &str| analysis.keywords.iter().position(|k| k == word).unwrap();
+        assert!(position("feeding") < position("meeting"));
+        assert_eq!(analysis.sentiment.confidence, 0.0);
+        assert!(analysis.summary.is_none() && analysis.entities.is_empty());
+    }
+
+    #[test]
+    fn test_themes_come_from_headings_and_category_from_frontmatter() {
+        let file = note("---\ntype: Recipe\n---\n# Sourdough Bread\n## Notes\n## Starter Feeding\nFeed the starter daily.\n#### Tiny detail\n");
+        let analysis = heuristic_analysis(&file, &TermIndex::default(), &AnalysisConfig::default());
+        assert_eq!(analysis.themes, vec!["starter feeding", "sourdough bread"]);
+        assert_eq!(analysis.category.as_deref(), Some("Recipe"));
+        assert_eq!(analysis.reading_time_minutes, Some(1));
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::{HistoryArgs, UndoArgs};
+use crate::config::{Config, HistorySettings};
+use crate::note_skeleton::stable_hash;
+use crate::obsidian_adapter::ObsidianAdapter;
+
+/// A note as it was before an AI feature rewrote it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: String,
+    pub path: String,
+    pub timestamp: DateTime<Utc>,
+    /// The feature that made the change, e.g. `analysis` or `organize`
+    pub feature: String,
+    pub content_hash: String,
+    pub content: String,
+}
+
+pub fn default_history_path() -> PathBuf {
+    let mut path = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push(".config");
+    path.push("arrowhead");
+    path.push("history");
+    path
+}
+
+/// Previous versions of notes, one JSON file each, named so they sort oldest first
+#[derive(Debug, Clone)]
+pub struct ChangeJournal {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ChangeJournal {
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self { dir: dir.into(), max_bytes }
+    }
+
+    /// `None` when the journal is turned off
+    pub fn from_settings(settings: &HistorySettings) -> Option<Self> {
+        settings.enabled.then(|| {
+            let dir = settings.path.as_ref().map(PathBuf::from).unwrap_or_else(default_history_path);
+            Self::new(dir, settings.max_size_mb.saturating_mul(1024 * 1024))
+        })
+    }
+
+    /// Store `content` as the version of `path` before `feature` rewrites it. The entry is
+    /// written to a temporary file and renamed into place, so it is either complete or absent.
+    pub fn record(&self, path: &str, content: &str, feature: &str) -> Result<JournalEntry> {
+        let timestamp = Utc::now();
+        let entry = JournalEntry {
+            id: uuid::Uuid::new_v4().simple().to_string()[..8].to_string(),
+            path: path.to_string(),
+            timestamp,
+            feature: feature.to_string(),
+            content_hash: format!("{:016x}", stable_hash(content)),
+            content: content.to_string(),
+        };
+        fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create history folder {}", self.dir.display()))?;
+        let file = self.dir.join(format!("{}-{}.json", timestamp.format("%Y%m%dT%H%M%S%.6fZ"), entry.id));
+        let partial = file.with_extension("json.tmp");
+        fs::write(&partial, serde_json::to_string(&entry)?).context("Failed to write history entry")?;
+        fs::rename(&partial, &file).context("Failed to write history entry")?;
+        if let Err(e) = self.prune() {
+            log::warn!("could not trim the change history: {}", e);
+        }
+        Ok(entry)
+    }
+
+    /// Entry files, oldest first
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    fn read(file: &Path) -> Result<JournalEntry> {
+        let raw = fs::read_to_string(file)?;
+        serde_json::from_str(&raw).with_context(|| format!("Unreadable history entry {}", file.display()))
+    }
+
+    /// Every stored version, newest first; unreadable entries are skipped
+    pub fn entries(&self) -> Result<Vec<JournalEntry>> {
+        Ok(self.files()?.iter().rev().filter_map(|file| Self::read(file).ok()).collect())
+    }
+
+    /// Stored versions of one note, newest first
+    pub fn history(&self, path: &str) -> Result<Vec<JournalEntry>> {
+        Ok(self.entries()?.into_iter().filter(|entry| entry.path == path).collect())
+    }
+
+    /// The version with this id (or unique id prefix)
+    pub fn find(&self, id: &str) -> Result<JournalEntry> {
+        let mut matches = self.entries()?.into_iter().filter(|entry| entry.id.starts_with(id));
+        match (matches.next(), matches.next()) {
+            (Some(entry), None) => Ok(entry),
+            (Some(_), Some(_)) => bail!("'{}' matches more than one version; give more of the id", id),
+            (None, _) => bail!("No stored version with id '{}'", id),
+        }
+    }
+
+    /// Drop a version, e.g. once `undo --last` has restored it
+    pub fn remove(&self, id: &str) -> Result<()> {
+        for file in self.files()? {
+            if Self::read(&file).is_ok_and(|entry| entry.id == id) {
+                fs::remove_file(&file)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete the oldest entries until the journal fits in `max_bytes`
+    fn prune(&self) -> Result<()> {
+        let files = self.files()?;
+        let sizes: Vec<u64> = files.iter().map(|file| fs::metadata(file).map(|m| m.len()).unwrap_or(0)).collect();
+        let mut total: u64 = sizes.iter().sum();
+        // The newest entry is always kept, however large
+        for (file, size) in files.iter().zip(&sizes).take(files.len().saturating_sub(1)) {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(file)?;
+            total -= size;
+        }
+        Ok(())
+    }
+}
+
+fn journal(config: &Config) -> Result<ChangeJournal> {
+    ChangeJournal::from_settings(&config.history)
+        .ok_or_else(|| anyhow::anyhow!("The change history is turned off ([history] enabled = false)"))
+}
+
+fn describe(entry: &JournalEntry) -> String {
+    format!("{}  {}  before {}  ({} bytes)", entry.id, entry.timestamp.format("%Y-%m-%d %H:%M:%S"), entry.feature, entry.content.len())
+}
+
+async fn restore(adapter: &ObsidianAdapter, entry: &JournalEntry) -> Result<()> {
+    adapter.update_file(&entry.path, &entry.content).await
+        .with_context(|| format!("Failed to restore {}", entry.path))?;
+    println!("↩️  Restored {} to its version from {} (before {})", entry.path, entry.timestamp.format("%Y-%m-%d %H:%M:%S"), entry.feature);
+    Ok(())
+}
+
+pub async fn handle_undo_command(args: UndoArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let journal = journal(&config)?;
+    let entry = match (args.id, args.last) {
+        (Some(id), _) => journal.find(&id)?,
+        (None, true) => journal.entries()?.into_iter().next()
+            .ok_or_else(|| anyhow::anyhow!("Nothing to undo: no AI change has been recorded"))?,
+        (None, false) => bail!("Give a version id (see `arrowhead history <path>`) or --last"),
+    };
+    restore(adapter, &entry).await?;
+    // Undoing again steps further back instead of restoring the same version
+    journal.remove(&entry.id)
+}
+
+pub async fn handle_history_command(args: HistoryArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let journal = journal(&config)?;
+    if let Some(id) = args.restore {
+        let entry = journal.find(&id)?;
+        if entry.path != args.path {
+            bail!("Version {} belongs to {}, not {}", entry.id, entry.path, args.path);
+        }
+        return restore(adapter, &entry).await;
+    }
+
+    let versions = journal.history(&args.path)?;
+    if versions.is_empty() {
+        println!("No stored versions of {}", args.path);
+        return Ok(());
+    }
+    println!("Versions of {} from before AI changes, newest first:", args.path);
+    for entry in &versions {
+        println!("  {}", describe(entry));
+    }
+    println!("\nRestore one with `arrowhead history {} --restore <id>`", args.path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal(max_bytes: u64) -> (ChangeJournal, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("arrowhead-history-{}", uuid::Uuid::new_v4()));
+        (ChangeJournal::new(&dir, max_bytes), dir)
+    }
+
+    #[test]
+    fn test_versions_are_listed_newest_first_and_found_by_prefix() {
+        let (journal, dir) = journal(1024 * 1024);
+        assert!(journal.entries().unwrap().is_empty());
+        let first = journal.record("Notes/plan.md", "v1", "analysis").unwrap();
+        journal.record("Notes/other.md", "other", "organize").unwrap();
+        let second = journal.record("Notes/plan.md", "v2", "organize").unwrap();
+
+        let history = journal.history("Notes/plan.md").unwrap();
+        assert_eq!(history.iter().map(|e| e.content.as_str()).collect::<Vec<_>>(), vec!["v2", "v1"]);
+        assert_eq!(history[1].content_hash, format!("{:016x}", stable_hash("v1")));
+        assert_eq!(journal.find(&first.id[..6]).unwrap(), first);
+        assert!(journal.find("zzzz").is_err());
+        // No half-written files are left behind
+        assert!(fs::read_dir(&dir).unwrap().all(|e| e.unwrap().path().extension().unwrap() == "json"));
+
+        journal.remove(&second.id).unwrap();
+        assert_eq!(journal.entries().unwrap()[0].content, "other");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_oldest_versions_are_dropped_past_the_size_limit() {
+        let (journal, dir) = journal(600);
+        for i in 0..5 {
+            journal.record("Notes/plan.md", &format!("version {} {}", i, "x".repeat(100)), "analysis").unwrap();
+        }
+        let kept: Vec<String> = journal.entries().unwrap().iter().map(|e| e.content[..9].to_string()).collect();
+        assert!(kept.len() < 5 && !kept.is_empty());
+        assert_eq!(kept[0], "version 4");
+
+        // A single version larger than the limit is still kept
+        let (journal, dir2) = self::journal(10);
+        journal.record("Notes/big.md", &"x".repeat(100), "analysis").unwrap();
+        assert_eq!(journal.entries().unwrap().len(), 1);
+        fs::remove_dir_all(&dir).ok();
+        fs::remove_dir_all(&dir2).ok();
+    }
+}
@@ -0,0 +1,483 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+
+use crate::ai_conversation::create_llm_client;
+use crate::cli::{InsightsArgs, InsightsBucket};
+use crate::config::{Config, InsightsSettings};
+use crate::dates;
+use crate::health::{load_notes_in, SPARK_CHARS};
+use crate::history::ChangeJournal;
+use crate::obsidian_adapter::{ContentAnalysis, Frontmatter, ObsidianAdapter};
+use crate::progress::{self, BatchProgress};
+use crate::report::{DetailLevel, Report, ReportView};
+use crate::usage::UsagePurpose;
+
+/// Width of each half of the sentiment bar
+const BAR_WIDTH: usize = 10;
+
+/// A note with the date it is about and its analysis, if it has one
+#[derive(Debug, Clone)]
+pub struct DatedNote {
+    pub path: String,
+    pub date: Option<NaiveDate>,
+    pub analysis: Option<ContentAnalysis>,
+}
+
+fn in_range(date: NaiveDate, since: Option<NaiveDate>, until: Option<NaiveDate>) -> bool {
+    since.is_none_or(|since| date >= since) && until.is_none_or(|until| date <= until)
+}
+
+/// The first date in `text` written in `format`, wherever it starts
+fn find_date(text: &str, format: &str) -> Option<NaiveDate> {
+    text.char_indices()
+        .filter_map(|(i, _)| NaiveDate::parse_and_remainder(&text[i..], format).ok())
+        .map(|(date, _)| date)
+        .next()
+}
+
+/// The day a note is about: its `date_field` frontmatter value, else a date in its name.
+/// File times are never used; a synced or edited note would move to the wrong day.
+pub fn note_date(path: &str, frontmatter: &Frontmatter, settings: &InsightsSettings) -> Option<NaiveDate> {
+    let field = serde_yaml::to_value(frontmatter).ok()
+        .and_then(|value| value.get(settings.date_field.as_str()).cloned());
+    let from_field = field.and_then(|value| match value {
+        serde_yaml::Value::String(text) => NaiveDate::parse_and_remainder(text.trim(), "%Y-%m-%d").ok().map(|(date, _)| date),
+        _ => None,
+    });
+    from_field.or_else(|| {
+        let name = path.rsplit('/').next().unwrap_or(path).trim_end_matches(".md");
+        find_date(name, &settings.filename_date_format)
+    })
+}
+
+/// Parse each note's frontmatter for its date and analysis. Notes without frontmatter can
+/// still be dated by name.
+pub fn dated_notes(notes: &[(String, String)], settings: &InsightsSettings) -> Vec<DatedNote> {
+    notes.iter()
+        .map(|(path, content)| {
+            let frontmatter = ObsidianAdapter::parse_markdown_file(content).map(|file| file.frontmatter).unwrap_or_default();
+            DatedNote {
+                path: path.clone(),
+                date: note_date(path, &frontmatter, settings),
+                analysis: frontmatter.ai_analysis,
+            }
+        })
+        .collect()
+}
+
+/// +1 for positive, -1 for negative and 0 for anything else
+fn polarity(analysis: &ContentAnalysis) -> f64 {
+    match analysis.sentiment.overall.to_lowercase().as_str() {
+        "positive" => 1.0,
+        "negative" => -1.0,
+        _ => 0.0,
+    }
+}
+
+/// Mean polarity weighted by the analyses' confidence; a plain mean when none is confident
+fn mean_sentiment(analyses: &[&ContentAnalysis]) -> Option<f64> {
+    if analyses.is_empty() {
+        return None;
+    }
+    let weight: f64 = analyses.iter().map(|a| a.sentiment.confidence.clamp(0.0, 1.0) as f64).sum();
+    if weight <= 0.0 {
+        return Some(analyses.iter().map(|a| polarity(a)).sum::<f64>() / analyses.len() as f64);
+    }
+    Some(analyses.iter().map(|a| polarity(a) * a.sentiment.confidence.clamp(0.0, 1.0) as f64).sum::<f64>() / weight)
+}
+
+fn mean_complexity(analyses: &[&ContentAnalysis]) -> Option<f64> {
+    let scores: Vec<f64> = analyses.iter().filter_map(|a| a.complexity_score).map(f64::from).collect();
+    (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64)
+}
+
+/// Themes compared case-insensitively
+fn theme_key(theme: &str) -> String {
+    theme.trim().to_lowercase()
+}
+
+/// The first day of the bucket `date` falls in and its label
+fn bucket_of(date: NaiveDate, bucket: InsightsBucket) -> (NaiveDate, String) {
+    match bucket {
+        InsightsBucket::Day => (date, date.format("%Y-%m-%d").to_string()),
+        InsightsBucket::Week => {
+            let week = date.iso_week();
+            let monday = dates::week_start(date);
+            (monday, format!("{}-W{:02}", week.year(), week.week()))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Period {
+    pub start: NaiveDate,
+    pub label: String,
+    pub notes: usize,
+    /// -1 (negative) to +1 (positive)
+    pub sentiment: Option<f64>,
+    /// 0 to 10
+    pub complexity: Option<f64>,
+}
+
+/// How much more or less often a theme comes up in the later half of the range
+#[derive(Debug, Clone, Serialize)]
+pub struct ThemeTrend {
+    pub theme: String,
+    /// Share of analyzed notes mentioning it in the earlier and later half
+    pub earlier: f64,
+    pub later: f64,
+    pub change: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Insights {
+    /// Dated, analyzed notes charted
+    pub notes: usize,
+    pub periods: Vec<Period>,
+    pub average_sentiment: Option<f64>,
+    pub average_complexity: Option<f64>,
+    pub rising: Vec<ThemeTrend>,
+    pub falling: Vec<ThemeTrend>,
+    /// Notes whose date couldn't be read from frontmatter or their name
+    pub undated: Vec<String>,
+    /// Dated notes in range without an analysis
+    pub unanalyzed: Vec<String>,
+}
+
+/// Compare theme frequency between the earlier and later half of the periods. Themes are
+/// counted once per note.
+fn theme_trends(halves: [&[&ContentAnalysis]; 2], top: usize) -> (Vec<ThemeTrend>, Vec<ThemeTrend>) {
+    let shares = |analyses: &[&ContentAnalysis]| -> HashMap<String, f64> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for analysis in analyses {
+            let mut themes: Vec<String> = analysis.themes.iter().map(|t| theme_key(t)).filter(|t| !t.is_empty()).collect();
+            themes.sort();
+            themes.dedup();
+            for theme in themes {
+                *counts.entry(theme).or_default() += 1;
+            }
+        }
+        counts.into_iter().map(|(theme, count)| (theme, count as f64 / analyses.len().max(1) as f64)).collect()
+    };
+    let (earlier, later) = (shares(halves[0]), shares(halves[1]));
+    let mut trends: Vec<ThemeTrend> = earlier.keys().chain(later.keys())
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .map(|theme| {
+            let (before, after) = (earlier.get(theme).copied().unwrap_or(0.0), later.get(theme).copied().unwrap_or(0.0));
+            ThemeTrend { theme: theme.clone(), earlier: before, later: after, change: after - before }
+        })
+        .collect();
+    trends.sort_by(|a, b| b.change.total_cmp(&a.change).then_with(|| a.theme.cmp(&b.theme)));
+    let rising: Vec<ThemeTrend> = trends.iter().filter(|t| t.change > 0.0).take(top).cloned().collect();
+    let falling: Vec<ThemeTrend> = trends.iter().rev().filter(|t| t.change < 0.0).take(top).cloned().collect();
+    (rising, falling)
+}
+
+/// Bucket the notes dated within `since..=until` and summarize each period. Undated and
+/// unanalyzed notes are returned rather than dropped.
+pub fn compute_insights(
+    notes: &[DatedNote],
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    bucket: InsightsBucket,
+    top: usize,
+) -> Insights {
+    let mut undated = Vec::new();
+    let mut unanalyzed = Vec::new();
+    let mut periods: BTreeMap<NaiveDate, (String, Vec<&ContentAnalysis>)> = BTreeMap::new();
+    for note in notes {
+        let Some(date) = note.date else {
+            undated.push(note.path.clone());
+            continue;
+        };
+        if !in_range(date, since, until) {
+            continue;
+        }
+        let Some(analysis) = &note.analysis else {
+            unanalyzed.push(note.path.clone());
+            continue;
+        };
+        let (start, label) = bucket_of(date, bucket);
+        periods.entry(start).or_insert_with(|| (label, Vec::new())).1.push(analysis);
+    }
+    undated.sort();
+    unanalyzed.sort();
+
+    let all: Vec<&ContentAnalysis> = periods.values().flat_map(|(_, analyses)| analyses.iter().copied()).collect();
+    // Themes need two periods to have a direction
+    let (rising, falling) = if periods.len() >= 2 {
+        let split: usize = periods.values().take(periods.len() / 2).map(|(_, analyses)| analyses.len()).sum();
+        theme_trends([&all[..split], &all[split..]], top)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    Insights {
+        notes: all.len(),
+        average_sentiment: mean_sentiment(&all),
+        average_complexity: mean_complexity(&all),
+        periods: periods.into_iter()
+            .map(|(start, (label, analyses))| Period {
+                start,
+                label,
+                notes: analyses.len(),
+                sentiment: mean_sentiment(&analyses),
+                complexity: mean_complexity(&analyses),
+            })
+            .collect(),
+        rising,
+        falling,
+        undated,
+        unanalyzed,
+    }
+}
+
+/// A bar growing left of the axis for negative sentiment and right of it for positive
+fn sentiment_bar(sentiment: f64) -> String {
+    let filled = ((sentiment.abs().min(1.0)) * BAR_WIDTH as f64).round() as usize;
+    let (left, right) = if sentiment < 0.0 { (filled, 0) } else { (0, filled) };
+    format!(
+        "{}{}│{}{}",
+        " ".repeat(BAR_WIDTH - left), "█".repeat(left),
+        "█".repeat(right), " ".repeat(BAR_WIDTH - right)
+    )
+}
+
+/// One character per period, from most negative to most positive
+fn sentiment_sparkline(periods: &[Period]) -> String {
+    periods.iter()
+        .filter_map(|p| p.sentiment)
+        .map(|s| SPARK_CHARS[(((s.clamp(-1.0, 1.0) + 1.0) / 2.0) * (SPARK_CHARS.len() - 1) as f64).round() as usize])
+        .collect()
+}
+
+fn render_list(title: &str, paths: &[String], hint: &str) -> String {
+    let mut out = String::new();
+    if !paths.is_empty() {
+        let _ = writeln!(out, "\n{} ({}){}:", title, paths.len(), hint);
+        for path in paths {
+            let _ = writeln!(out, "  {}", path);
+        }
+    }
+    out
+}
+
+/// Summary: the headline averages. Normal: the chart per period, rising and falling themes
+/// and the notes left out.
+pub fn insights_report(insights: &Insights, folder: &str, bucket: InsightsBucket) -> Report {
+    let headline = match (insights.periods.first(), insights.periods.last()) {
+        (Some(first), Some(last)) => format!(
+            "{} analyzed note(s) in {} from {} to {}: sentiment {}, complexity {}\n",
+            insights.notes, folder, first.start, last.start,
+            insights.average_sentiment.map_or("n/a".to_string(), |s| format!("{:+.2}", s)),
+            insights.average_complexity.map_or("n/a".to_string(), |c| format!("{:.1}/10", c)),
+        ),
+        _ => format!("No dated, analyzed notes in {}\n", folder),
+    };
+    let mut report = Report::new()
+        .text(DetailLevel::Summary, headline)
+        .field(DetailLevel::Summary, "folder", folder)
+        .field(DetailLevel::Summary, "bucket", bucket)
+        .field(DetailLevel::Summary, "notes", insights.notes)
+        .field(DetailLevel::Summary, "average_sentiment", insights.average_sentiment)
+        .field(DetailLevel::Summary, "average_complexity", insights.average_complexity)
+        .field(DetailLevel::Normal, "periods", &insights.periods)
+        .field(DetailLevel::Normal, "rising_themes", &insights.rising)
+        .field(DetailLevel::Normal, "falling_themes", &insights.falling)
+        .field(DetailLevel::Normal, "undated", &insights.undated)
+        .field(DetailLevel::Normal, "unanalyzed", &insights.unanalyzed);
+    if !insights.undated.is_empty() || !insights.unanalyzed.is_empty() {
+        report = report.text_only(
+            DetailLevel::Summary,
+            format!("{} undated and {} unanalyzed note(s) left out\n", insights.undated.len(), insights.unanalyzed.len()),
+        );
+    }
+
+    let mut out = String::new();
+    if !insights.periods.is_empty() {
+        let unit = match bucket { InsightsBucket::Day => "day", InsightsBucket::Week => "week" };
+        let _ = writeln!(out, "\nSentiment by {} (negative │ positive):", unit);
+        for period in &insights.periods {
+            let sentiment = period.sentiment.unwrap_or(0.0);
+            let complexity = period.complexity.map_or(String::new(), |c| format!("  complexity {:.1}", c));
+            let _ = writeln!(out, "  {:<10} {} {:+.2}  {} note(s){}", period.label, sentiment_bar(sentiment), sentiment, period.notes, complexity);
+        }
+        if insights.periods.len() > 1 {
+            let _ = writeln!(out, "\nTrend: {}", sentiment_sparkline(&insights.periods));
+        }
+    }
+    for (title, trends) in [("Rising themes", &insights.rising), ("Falling themes", &insights.falling)] {
+        if !trends.is_empty() {
+            let _ = writeln!(out, "\n{}:", title);
+            for trend in trends {
+                let _ = writeln!(out, "  {:<24} {:>3.0}% → {:>3.0}%", trend.theme, trend.earlier * 100.0, trend.later * 100.0);
+            }
+        }
+    }
+    out.push_str(&render_list("Undated notes", &insights.undated, ", add a date to the frontmatter or name"));
+    out.push_str(&render_list("Not analyzed", &insights.unanalyzed, ", run with --analyze-missing"));
+    report.text(DetailLevel::Normal, out)
+}
+
+fn parse_day(value: Option<&str>, flag: &str, today: NaiveDate) -> Result<Option<NaiveDate>> {
+    value.map(|value| crate::dates::parse_date(value, today).with_context(|| format!("Could not read {}", flag)))
+        .transpose()
+}
+
+/// Analyze the notes in `paths`, writing the analysis into their frontmatter, and return
+/// the analyses that succeeded by path
+pub(crate) async fn analyze_missing(config: &Config, paths: &[String]) -> Result<HashMap<String, ContentAnalysis>> {
+    let mut analyzer = ObsidianAdapter::with_ai_client(
+        Some(config.obsidian.base_url.clone()),
+        config.obsidian.api_key.clone(),
+        create_llm_client(config, UsagePurpose::Analysis)?,
+        None,
+    );
+    analyzer.set_cache_settings(&config.cache);
+    analyzer.set_opt_out_policy(config.opt_outs.clone());
+    if let Some(journal) = ChangeJournal::from_settings(&config.history) {
+        analyzer.set_change_journal(journal);
+    }
+
+    let progress = progress::bar("Analyzing");
+    progress.start(paths.len());
+    let mut analyses = HashMap::new();
+    for path in paths {
+        match analyzer.analyze_and_update_file(path).await {
+            Ok(file) => {
+                if let Some(analysis) = file.frontmatter.ai_analysis {
+                    analyses.insert(path.clone(), analysis);
+                }
+            }
+            Err(e) => progress.println(&format!("⚠️  Could not analyze {}: {:#}", path, e)),
+        }
+        progress.advance(path);
+    }
+    progress.finish();
+    Ok(analyses)
+}
+
+pub async fn handle_insights_command(args: InsightsArgs, view: ReportView, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let folder = args.folder.clone().unwrap_or_else(|| config.insights.folder.clone());
+    let today = crate::timezone::resolve(&config.calendar.time_zone)?.to_local(Utc::now()).date();
+    let since = parse_day(args.since.as_deref(), "--since", today)?;
+    let until = parse_day(args.until.as_deref(), "--until", today)?;
+    if let (Some(since), Some(until)) = (since, until) {
+        if since > until {
+            bail!("--since {} is after --until {}", since, until);
+        }
+    }
+
+    let notes = load_notes_in(adapter, std::slice::from_ref(&folder)).await;
+    if notes.is_empty() {
+        bail!("No notes found in {}", folder);
+    }
+    let mut dated = dated_notes(&notes, &config.insights);
+
+    if args.analyze_missing {
+        let missing: Vec<String> = dated.iter()
+            .filter(|note| note.analysis.is_none())
+            .filter(|note| note.date.is_some_and(|date| in_range(date, since, until)))
+            .map(|note| note.path.clone())
+            .collect();
+        if !missing.is_empty() {
+            let mut analyses = analyze_missing(&config, &missing).await?;
+            for note in &mut dated {
+                if let Some(analysis) = analyses.remove(&note.path) {
+                    note.analysis = Some(analysis);
+                }
+            }
+        }
+    }
+
+    let insights = compute_insights(&dated, since, until, args.by, config.insights.top_themes);
+    let report = insights_report(&insights, &folder, args.by);
+    crate::script::publish_output(report.to_json(view.level));
+    report.print(view)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obsidian_adapter::SentimentAnalysis;
+
+    fn analysis(sentiment: &str, confidence: f32, themes: &[&str], complexity: f32) -> Option<ContentAnalysis> {
+        Some(ContentAnalysis {
+            themes: themes.iter().map(|t| t.to_string()).collect(),
+            sentiment: SentimentAnalysis { overall: sentiment.to_string(), confidence, emotions: Vec::new() },
+            complexity_score: Some(complexity),
+            ..ContentAnalysis::default()
+        })
+    }
+
+    fn note(path: &str, date: &str, analysis: Option<ContentAnalysis>) -> DatedNote {
+        DatedNote { path: path.to_string(), date: NaiveDate::parse_from_str(date, "%Y-%m-%d").ok(), analysis }
+    }
+
+    #[test]
+    fn test_dates_come_from_frontmatter_then_name() {
+        let settings = InsightsSettings::default();
+        let notes = [
+            ("Journal/2024-03-05 Tuesday.md".to_string(), "---\ndate: 2024-03-01\n---\nbody".to_string()),
+            ("Journal/Entry 2024-03-05.md".to_string(), "no frontmatter".to_string()),
+            ("Journal/Thoughts.md".to_string(), "---\ntags: [x]\n---\nbody".to_string()),
+        ];
+        let dates: Vec<Option<String>> = dated_notes(&notes, &settings).iter().map(|n| n.date.map(|d| d.to_string())).collect();
+        assert_eq!(dates, [Some("2024-03-01".to_string()), Some("2024-03-05".to_string()), None]);
+
+        let settings = InsightsSettings { filename_date_format: "%d.%m.%Y".to_string(), date_field: "day".to_string(), ..InsightsSettings::default() };
+        let dated = dated_notes(&[("J/Log 05.03.2024.md".to_string(), "---\ndate: 2024-01-01\n---\n".to_string())], &settings);
+        assert_eq!(dated[0].date, NaiveDate::from_ymd_opt(2024, 3, 5));
+    }
+
+    #[test]
+    fn test_weekly_sentiment_and_theme_trends() {
+        let notes = [
+            note("a.md", "2024-01-01", analysis("negative", 1.0, &["Work", "sleep"], 4.0)),
+            note("b.md", "2024-01-03", analysis("neutral", 0.5, &["work"], 6.0)),
+            note("c.md", "2024-01-09", analysis("positive", 1.0, &["running"], 2.0)),
+            note("d.md", "2024-01-10", analysis("positive", 0.5, &["running", "work"], 4.0)),
+            note("e.md", "2024-01-10", None),
+            note("old.md", "2023-12-01", analysis("negative", 1.0, &["winter"], 1.0)),
+            note("undated.md", "", analysis("positive", 1.0, &[], 1.0)),
+        ];
+        let since = NaiveDate::from_ymd_opt(2024, 1, 1);
+        let insights = compute_insights(&notes, since, None, InsightsBucket::Week, 5);
+
+        assert_eq!(insights.notes, 4);
+        let labels: Vec<&str> = insights.periods.iter().map(|p| p.label.as_str()).collect();
+        assert_eq!(labels, ["2024-W01", "2024-W02"]);
+        assert!((insights.periods[0].sentiment.unwrap() + 1.0 / 1.5).abs() < 1e-9);
+        assert_eq!(insights.periods[1].sentiment, Some(1.0));
+        assert_eq!(insights.average_complexity, Some(4.0));
+
+        let rising: Vec<&str> = insights.rising.iter().map(|t| t.theme.as_str()).collect();
+        let falling: Vec<&str> = insights.falling.iter().map(|t| t.theme.as_str()).collect();
+        assert_eq!(rising, ["running"]);
+        assert_eq!(falling, ["work", "sleep"]);
+
+        assert_eq!(insights.undated, ["undated.md"]);
+        assert_eq!(insights.unanalyzed, ["e.md"]);
+    }
+
+    #[test]
+    fn test_report_lists_left_out_notes() {
+        let notes = [
+            note("a.md", "2024-01-01", analysis("positive", 1.0, &[], 3.0)),
+            note("b.md", "", None),
+        ];
+        let insights = compute_insights(&notes, None, None, InsightsBucket::Day, 5);
+        let report = insights_report(&insights, "Journal", InsightsBucket::Day);
+        let text = report.render(DetailLevel::Normal);
+        assert!(text.contains("2024-01-01") && text.contains("│██████████"));
+        assert!(text.contains("Undated notes (1)") && text.contains("  b.md"));
+        assert_eq!(report.to_json(DetailLevel::Normal)["undated"], serde_json::json!(["b.md"]));
+        assert_eq!(sentiment_bar(-0.5), format!("{}█████│{}", " ".repeat(5), " ".repeat(10)));
+    }
+}
@@ -0,0 +1,618 @@
+use chrono::NaiveDate;
+
+use crate::cli::{Commands, GoalAction, GoalArgs, NoteAction, NoteArgs, TodoAction, TodoArgs};
+use crate::todo_model::{TodoSort, TodoStatus};
+use crate::dates;
+
+/// Prefix that forces an utterance to skip the local classifier and go to the LLM
+pub const LLM_PREFIX: &str = "/llm";
+
+/// Verbs that can lose data. Utterances containing any of these are never auto-routed,
+/// even when the word only appears inside a todo description.
+const DESTRUCTIVE_WORDS: &[&str] = &[
+    "delete", "remove", "erase", "overwrite", "replace", "clear", "wipe", "purge",
+    "drop", "destroy", "trash", "discard", "reset", "rename", "move", "edit", "update",
+];
+
+const READ_VERBS: &[&str] = &["list", "show", "display", "view", "see", "get", "what", "whats", "which", "open"];
+const CREATE_VERBS: &[&str] = &["add", "create", "new", "make", "capture", "log"];
+
+/// Politeness and framing words allowed before the verb
+const PREAMBLE_WORDS: &[&str] = &[
+    "please", "can", "could", "would", "you", "i", "want", "to", "need", "hey", "lets", "let", "us", "me", "quickly", "just",
+];
+
+/// Filler words allowed around the object of a read request
+const FILLER_WORDS: &[&str] = &[
+    "me", "my", "all", "the", "a", "an", "are", "is", "of", "please", "do", "i", "have", "current",
+    "currently", "on", "in", "list", "every", "there", "any", "up", "now", "right",
+];
+
+/// Words that suggest the utterance chains several requests together
+const CHAINING_WORDS: &[&str] = &["then", "also", "afterwards", "after"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Object {
+    Todo,
+    Goal,
+    Note,
+}
+
+impl Object {
+    fn from_word(word: &str) -> Option<(Self, bool)> {
+        match word {
+            "todo" | "task" | "to-do" => Some((Object::Todo, false)),
+            "todos" | "tasks" | "to-dos" => Some((Object::Todo, true)),
+            "goal" => Some((Object::Goal, false)),
+            "goals" => Some((Object::Goal, true)),
+            "note" => Some((Object::Note, false)),
+            "notes" => Some((Object::Note, true)),
+            _ => None,
+        }
+    }
+}
+
+/// Why an utterance was handed to the LLM instead of being routed directly
+#[derive(Debug, Clone, PartialEq)]
+pub enum LlmReason {
+    /// The user asked for the LLM with the `/llm` prefix
+    Forced,
+    /// The utterance mentions a destructive action
+    Destructive(String),
+    /// A pattern matched, but not confidently enough to act on
+    LowConfidence { intent: String, confidence: f32 },
+    /// Nothing in the vocabulary matched
+    NoMatch,
+}
+
+/// A command the classifier is confident enough to run without an LLM round trip
+#[derive(Debug, Clone)]
+pub struct DirectRoute {
+    pub intent: String,
+    pub command: Commands,
+    pub confidence: f32,
+}
+
+#[derive(Debug, Clone)]
+pub enum RouteDecision {
+    Direct(DirectRoute),
+    Llm { input: String, reason: LlmReason },
+}
+
+impl RouteDecision {
+    pub fn is_direct(&self) -> bool {
+        matches!(self, RouteDecision::Direct(_))
+    }
+
+    /// Intent name for direct routes, or None when the LLM handles the input
+    pub fn intent(&self) -> Option<&str> {
+        match self {
+            RouteDecision::Direct(route) => Some(&route.intent),
+            RouteDecision::Llm { .. } => None,
+        }
+    }
+}
+
+/// Rule-based classifier that short-circuits simple, unambiguous requests
+/// ("show my open todos", "add a todo to buy milk by friday") straight to the
+/// router. Anything it is unsure about falls through to the LLM.
+#[derive(Debug, Clone)]
+pub struct IntentClassifier {
+    pub confidence_threshold: f32,
+}
+
+impl Default for IntentClassifier {
+    fn default() -> Self {
+        Self { confidence_threshold: 0.85 }
+    }
+}
+
+impl IntentClassifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_threshold(confidence_threshold: f32) -> Self {
+        Self { confidence_threshold }
+    }
+
+    pub fn classify(&self, input: &str, today: NaiveDate) -> RouteDecision {
+        let trimmed = input.trim();
+        if let Some(rest) = strip_llm_prefix(trimmed) {
+            return RouteDecision::Llm { input: rest.to_string(), reason: LlmReason::Forced };
+        }
+
+        let llm = |reason| RouteDecision::Llm { input: trimmed.to_string(), reason };
+
+        let raw: Vec<&str> = trimmed.split_whitespace().collect();
+        let words: Vec<String> = raw.iter().map(|w| normalize_word(w)).collect();
+        if let Some(word) = words.iter().find(|w| DESTRUCTIVE_WORDS.contains(&w.as_str())) {
+            return llm(LlmReason::Destructive(word.clone()));
+        }
+
+        let Some(verb_index) = words.iter().position(|w| !w.is_empty() && !PREAMBLE_WORDS.contains(&w.as_str())) else {
+            return llm(LlmReason::NoMatch);
+        };
+        let verb = words[verb_index].as_str();
+
+        let candidate = if READ_VERBS.contains(&verb) {
+            classify_read(&words[verb_index + 1..], &raw[verb_index + 1..])
+        } else if CREATE_VERBS.contains(&verb) && !trimmed.ends_with('?') {
+            classify_create(&raw, &words, verb_index, today)
+        } else {
+            None
+        };
+
+        match candidate {
+            Some(route) if route.confidence >= self.confidence_threshold => RouteDecision::Direct(route),
+            Some(route) => llm(LlmReason::LowConfidence { intent: route.intent, confidence: route.confidence }),
+            None => llm(LlmReason::NoMatch),
+        }
+    }
+}
+
+/// Return the remainder of the input if it starts with the `/llm` prefix
+pub fn strip_llm_prefix(input: &str) -> Option<&str> {
+    let rest = input.trim_start().strip_prefix(LLM_PREFIX)?;
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+/// Render a command the way it would be typed on the command line
+pub fn describe_command(command: &Commands) -> String {
+    match command {
+        Commands::Todo(TodoArgs { action }) => match action {
+            TodoAction::List { status, due_before, overdue, sort } => {
+                let mut line = "todo list".to_string();
+                if let Some(status) = status {
+                    line.push_str(&format!(" --status {}", status));
+                }
+                if let Some(before) = due_before {
+                    line.push_str(&format!(" --due-before {}", before));
+                }
+                if *overdue {
+                    line.push_str(" --overdue");
+                }
+                if *sort != TodoSort::default() {
+                    line.push_str(&format!(" --sort {}", sort));
+                }
+                line
+            }
+            TodoAction::Add { description, due_date, .. } => match due_date {
+                Some(due) => format!("todo add \"{}\" --due-date {}", description, due),
+                None => format!("todo add \"{}\"", description),
+            },
+            other => format!("todo {:?}", other),
+        },
+        Commands::Goal(GoalArgs { action }) => match action {
+            GoalAction::List { status: Some(status) } => format!("goal list --status {}", status),
+            GoalAction::List { status: None } => "goal list".to_string(),
+            GoalAction::Add { title, target_date, .. } => match target_date {
+                Some(target) => format!("goal add \"{}\" --target-date {}", title, target),
+                None => format!("goal add \"{}\"", title),
+            },
+            other => format!("goal {:?}", other),
+        },
+        Commands::Note(NoteArgs { action }) => match action {
+            NoteAction::List { .. } => "note list".to_string(),
+            NoteAction::View { name_or_id } => format!("note view \"{}\"", name_or_id),
+            NoteAction::Create { title, .. } => format!("note create \"{}\"", title),
+            other => format!("note {:?}", other),
+        },
+        other => format!("{:?}", other),
+    }
+}
+
+/// Whether a word belongs to the request vocabulary (verbs, objects, politeness), which
+/// marks a command line as a sentence meant for the assistant
+pub fn is_request_word(word: &str) -> bool {
+    let word = normalize_word(word);
+    [READ_VERBS, CREATE_VERBS, PREAMBLE_WORDS, DESTRUCTIVE_WORDS].iter().any(|words| words.contains(&word.as_str()))
+        || Object::from_word(&word).is_some()
+}
+
+fn is_filler(word: &str) -> bool {
+    word.is_empty() || FILLER_WORDS.contains(&word)
+}
+
+fn normalize_word(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric() && c != '-')
+        .to_lowercase()
+        .replace(['\'', '\u{2019}'], "")
+}
+
+fn status_for(object: Object, word: &str) -> Option<&'static str> {
+    match (object, word) {
+        (Object::Todo, "open" | "pending" | "incomplete" | "outstanding" | "unfinished") => Some("open"),
+        (Object::Todo, "done" | "completed" | "finished" | "closed") => Some("done"),
+        (Object::Goal, "active" | "open" | "current") => Some("active"),
+        (Object::Goal, "achieved" | "completed" | "done" | "finished") => Some("achieved"),
+        (Object::Goal, "paused" | "on-hold") => Some("on-hold"),
+        _ => None,
+    }
+}
+
+/// "show my open todos", "what are my goals", "open note project-plan"
+fn classify_read(rest: &[String], raw_rest: &[&str]) -> Option<DirectRoute> {
+    let object_index = rest.iter().position(|w| Object::from_word(w).is_some())?;
+    let (object, plural) = Object::from_word(&rest[object_index])?;
+
+    // A singular note followed by a name is a request to view that note
+    if object == Object::Note && !plural && object_index + 1 < rest.len() {
+        let name_start = (object_index + 1..rest.len())
+            .find(|&i| !matches!(rest[i].as_str(), "called" | "named" | "titled"))?;
+        let name = raw_rest[name_start..].join(" ");
+        let name = name.trim_matches(|c: char| matches!(c, '"' | '\'' | '?' | '.' | '!')).to_string();
+        if name.is_empty() {
+            return None;
+        }
+        let unknown_before = rest[..object_index].iter().filter(|w| !is_filler(w)).count();
+        return Some(DirectRoute {
+            intent: "view_note".to_string(),
+            command: Commands::Note(NoteArgs { action: NoteAction::View { name_or_id: name } }),
+            confidence: 0.9 - 0.3 * unknown_before as f32,
+        });
+    }
+
+    let mut status = None;
+    let mut unknown = 0;
+    for (i, word) in rest.iter().enumerate() {
+        if i == object_index || is_filler(word) {
+            continue;
+        }
+        match status_for(object, word) {
+            Some(s) if status.is_none() => status = Some(s.to_string()),
+            _ => unknown += 1,
+        }
+    }
+
+    // Anything we don't recognise is probably a filter we can't express ("tagged work", "due today")
+    let confidence = 0.95 - 0.3 * unknown as f32;
+    let (intent, command) = match object {
+        Object::Todo => ("list_todos", Commands::Todo(TodoArgs { action: TodoAction::List {
+            status: status.as_deref().and_then(TodoStatus::parse),
+            due_before: None,
+            overdue: false,
+            sort: TodoSort::default(),
+        } })),
+        Object::Goal => ("list_goals", Commands::Goal(GoalArgs { action: GoalAction::List { status } })),
+        Object::Note if status.is_none() => ("list_notes", Commands::Note(NoteArgs { action: NoteAction::List { tags: vec![] } })),
+        Object::Note => return None,
+    };
+
+    Some(DirectRoute { intent: intent.to_string(), command, confidence })
+}
+
+/// "add a todo to buy milk by friday", "add call mom to my todos", "create a note called Ideas"
+/// `raw` keeps the original casing so titles keep their capitalisation.
+fn classify_create(raw: &[&str], words: &[String], verb_index: usize, today: NaiveDate) -> Option<DirectRoute> {
+    let after_verb = verb_index + 1;
+    let mut i = after_verb;
+    while i < words.len() && matches!(words[i].as_str(), "a" | "an" | "new" | "the") {
+        i += 1;
+    }
+
+    let (object, text_words) = if let Some((object, _)) = words.get(i).and_then(|w| Object::from_word(w)) {
+        // Object first: "add a todo: buy milk"
+        let mut start = i + 1;
+        let mut named = raw[i].ends_with(':');
+        if !named {
+            while start < words.len() && matches!(words[start].as_str(), "to" | "called" | "named" | "titled") {
+                named |= words[start] != "to";
+                start += 1;
+            }
+        }
+        // A note needs an explicit name; "create a note about X" is a request for content
+        if object == Object::Note && !named {
+            return None;
+        }
+        (object, &raw[start..])
+    } else {
+        // Object last: "add buy milk to my todo list"
+        let to_index = words.iter().rposition(|w| w == "to")?;
+        let tail: Vec<&str> = words[to_index + 1..].iter()
+            .map(String::as_str)
+            .filter(|w| !matches!(*w, "my" | "the" | "list"))
+            .collect();
+        let [object_word] = tail.as_slice() else {
+            return None;
+        };
+        let (object, _) = Object::from_word(object_word)?;
+        if object == Object::Note || to_index <= after_verb {
+            return None;
+        }
+        (object, &raw[after_verb..to_index])
+    };
+
+    if object == Object::Note && text_words.is_empty() {
+        return None;
+    }
+
+    let mut text = text_words.join(" ");
+    let text_lower = text.to_lowercase();
+    let mut confidence = 0.9;
+    if text_lower.split_whitespace().any(|w| CHAINING_WORDS.contains(&w)) {
+        confidence -= 0.3;
+    }
+
+    let mut date = None;
+    if object != Object::Note {
+        if let Some(mention) = dates::extract_date(&text, today) {
+            date = Some(mention.date.format("%Y-%m-%d").to_string());
+            text = text.replacen(&mention.phrase, "", 1).split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+    }
+
+    let text = text.trim_matches(|c: char| c == ':' || c == '"' || c == '\'' || c.is_whitespace()).to_string();
+    if text.is_empty() {
+        return None;
+    }
+
+    let (intent, command) = match object {
+        Object::Todo => ("add_todo", Commands::Todo(TodoArgs {
+            action: TodoAction::Add { description: text, due_date: date, tags: vec![], estimate: None },
+        })),
+        Object::Goal => ("add_goal", Commands::Goal(GoalArgs {
+            action: GoalAction::Add { title: text, description: None, target_date: date, tags: vec![] },
+        })),
+        Object::Note => ("create_note", Commands::Note(NoteArgs {
+            action: NoteAction::Create { title: text, content: None, tags: vec![], suggest_template: false },
+        })),
+    };
+
+    Some(DirectRoute { intent: intent.to_string(), command, confidence })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_conversation::{AIConversationEngine, FunctionSchema, LLMClient, Message, MessageRole};
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use std::time::{Duration, Instant};
+
+    fn today() -> NaiveDate {
+        // A Wednesday
+        NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()
+    }
+
+    /// Labeled utterances: expected intent for direct routes, None for the LLM path
+    const LABELED: &[(&str, Option<&str>)] = &[
+        // Listing todos
+        ("show my todos", Some("list_todos")),
+        ("list todos", Some("list_todos")),
+        ("list my tasks", Some("list_todos")),
+        ("show me all my todos", Some("list_todos")),
+        ("what are my todos?", Some("list_todos")),
+        ("what's on my todo list", Some("list_todos")),
+        ("whats on my task list", Some("list_todos")),
+        ("show open todos", Some("list_todos")),
+        ("list pending tasks", Some("list_todos")),
+        ("show completed todos", Some("list_todos")),
+        ("display my outstanding tasks", Some("list_todos")),
+        ("please show my todos", Some("list_todos")),
+        ("can you list my tasks", Some("list_todos")),
+        ("which tasks are done", Some("list_todos")),
+        ("see my to-dos", Some("list_todos")),
+        ("get my todos", Some("list_todos")),
+        ("show me my finished tasks", Some("list_todos")),
+        ("view todos", Some("list_todos")),
+        ("list all the tasks", Some("list_todos")),
+        ("what tasks do i have", Some("list_todos")),
+        // Listing goals
+        ("show my goals", Some("list_goals")),
+        ("list goals", Some("list_goals")),
+        ("what are my goals", Some("list_goals")),
+        ("show active goals", Some("list_goals")),
+        ("list achieved goals", Some("list_goals")),
+        ("display my current goals", Some("list_goals")),
+        ("can you show me my goals", Some("list_goals")),
+        ("which goals are on-hold", Some("list_goals")),
+        ("view all goals", Some("list_goals")),
+        ("what goals do i have", Some("list_goals")),
+        // Listing and viewing notes
+        ("list my notes", Some("list_notes")),
+        ("show notes", Some("list_notes")),
+        ("show me all notes", Some("list_notes")),
+        ("what notes do i have", Some("list_notes")),
+        ("view notes", Some("list_notes")),
+        ("show note project-plan", Some("view_note")),
+        ("open note meeting-notes", Some("view_note")),
+        ("open the note called Weekly Review", Some("view_note")),
+        ("view note capture-prd", Some("view_note")),
+        ("show me the note roadmap", Some("view_note")),
+        // Adding todos
+        ("add a todo to buy milk", Some("add_todo")),
+        ("add todo buy milk", Some("add_todo")),
+        ("add a todo: call the dentist", Some("add_todo")),
+        ("add a task to review the PR by friday", Some("add_todo")),
+        ("create a todo to send invoices tomorrow", Some("add_todo")),
+        ("new task pay rent due 2024-06-01", Some("add_todo")),
+        ("add buy groceries to my todo list", Some("add_todo")),
+        ("add call mom to my todos", Some("add_todo")),
+        ("please add a todo to water plants", Some("add_todo")),
+        ("can you add a task to book flights on monday", Some("add_todo")),
+        ("add a new todo called fix the bike", Some("add_todo")),
+        ("make a todo to renew passport", Some("add_todo")),
+        ("add task: prepare slides for thursday", Some("add_todo")),
+        ("i need to add a todo to email Sam", Some("add_todo")),
+        ("add prepare quarterly report to my tasks", Some("add_todo")),
+        // Adding goals and notes
+        ("add a goal to learn rust", Some("add_goal")),
+        ("create a goal: run a marathon", Some("add_goal")),
+        ("new goal read 20 books", Some("add_goal")),
+        ("add goal ship v2 by 2024-12-31", Some("add_goal")),
+        ("add learn spanish to my goals", Some("add_goal")),
+        ("create a note called Ideas", Some("create_note")),
+        ("create a new note titled Weekly Review", Some("create_note")),
+        ("new note: Reading List", Some("create_note")),
+        ("make a note named Standup", Some("create_note")),
+        ("add a note called Retro", Some("create_note")),
+        // Destructive requests never route directly
+        ("delete my todos", None),
+        ("delete todo 3", None),
+        ("remove the goal learn rust", None),
+        ("clear all completed tasks", None),
+        ("overwrite note project-plan", None),
+        ("replace my notes", None),
+        ("wipe my task list", None),
+        ("please remove done todos", None),
+        ("add a todo to clear the gutters", None),
+        ("rename note ideas to brainstorm", None),
+        ("update goal learn rust", None),
+        ("edit note roadmap", None),
+        ("purge old notes", None),
+        ("trash the note drafts", None),
+        ("reset my goals", None),
+        // Forced LLM
+        ("/llm show my todos", None),
+        ("/llm add a todo to buy milk", None),
+        ("/llm", None),
+        // Ambiguous, conversational or unsupported requests
+        ("what should i work on today", None),
+        ("how am i doing on my goals", None),
+        ("show todos tagged work", None),
+        ("show todos due today", None),
+        ("list overdue tasks", None),
+        ("show notes about project planning", None),
+        ("summarize my notes", None),
+        ("add a todo to call mom and then show my goals", None),
+        ("add a todo?", None),
+        ("create a note about today's meeting", None),
+        ("mark the report task as done", None),
+        ("hello", None),
+        ("", None),
+        ("thanks!", None),
+        ("what's on my calendar tomorrow", None),
+        ("schedule a meeting with Alice tomorrow at 2pm", None),
+        ("find notes related to rust", None),
+        ("help me plan my week", None),
+        ("add", None),
+        ("show", None),
+        ("can you", None),
+        ("complete todo 4", None),
+    ];
+
+    #[test]
+    fn test_labeled_routing_decisions() {
+        let classifier = IntentClassifier::new();
+        let mut failures = Vec::new();
+
+        for (utterance, expected) in LABELED {
+            let decision = classifier.classify(utterance, today());
+            if decision.intent() != *expected {
+                failures.push(format!("{:?}: expected {:?}, got {:?}", utterance, expected, decision));
+            }
+        }
+
+        assert!(LABELED.len() >= 100, "labeled set has {} utterances", LABELED.len());
+        assert!(failures.is_empty(), "misrouted utterances:\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_destructive_and_forced_reasons() {
+        let classifier = IntentClassifier::new();
+
+        match classifier.classify("Delete my todos", today()) {
+            RouteDecision::Llm { reason: LlmReason::Destructive(word), .. } => assert_eq!(word, "delete"),
+            other => panic!("expected destructive fallthrough, got {:?}", other),
+        }
+        match classifier.classify("/llm show my todos", today()) {
+            RouteDecision::Llm { input, reason: LlmReason::Forced } => assert_eq!(input, "show my todos"),
+            other => panic!("expected forced LLM, got {:?}", other),
+        }
+        assert!(strip_llm_prefix("/llmfoo").is_none());
+    }
+
+    #[test]
+    fn test_extracted_fields() {
+        let classifier = IntentClassifier::new();
+
+        let RouteDecision::Direct(route) = classifier.classify("add a task to Review the PR by Friday", today()) else {
+            panic!("expected direct route");
+        };
+        match route.command {
+            Commands::Todo(TodoArgs { action: TodoAction::Add { description, due_date, .. } }) => {
+                assert_eq!(description, "Review the PR");
+                assert_eq!(due_date.as_deref(), Some("2024-05-03"));
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+
+        let RouteDecision::Direct(route) = classifier.classify("show completed todos", today()) else {
+            panic!("expected direct route");
+        };
+        assert_eq!(describe_command(&route.command), "todo list --status done");
+
+        let RouteDecision::Direct(route) = classifier.classify("open the note called Weekly Review", today()) else {
+            panic!("expected direct route");
+        };
+        assert_eq!(describe_command(&route.command), "note view \"Weekly Review\"");
+    }
+
+    #[test]
+    fn test_threshold_controls_fallthrough() {
+        let strict = IntentClassifier::with_threshold(0.99);
+        assert!(!strict.classify("show my todos", today()).is_direct());
+        assert!(IntentClassifier::new().classify("show my todos", today()).is_direct());
+    }
+
+    /// Stand-in for a hosted model with a typical network round trip
+    struct SlowLLMClient {
+        latency: Duration,
+    }
+
+    #[async_trait]
+    impl LLMClient for SlowLLMClient {
+        async fn send_message(&self, _messages: Vec<Message>) -> Result<Message> {
+            tokio::time::sleep(self.latency).await;
+            Ok(Message {
+                id: "reply".to_string(),
+                role: MessageRole::Assistant,
+                content: "{\"intent\": \"list_todos\"}".to_string(),
+                timestamp: chrono::Utc::now(),
+                function_call: None,
+            })
+        }
+
+        async fn stream_response(&self, _messages: Vec<Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+            let (_tx, rx) = tokio::sync::mpsc::channel(1);
+            Ok(rx)
+        }
+
+        async fn function_calling(&self, messages: Vec<Message>, _functions: Vec<FunctionSchema>) -> Result<Message> {
+            self.send_message(messages).await
+        }
+
+        fn get_model_name(&self) -> String {
+            "slow-mock".to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_direct_routing_latency_improvement() {
+        let classifier = IntentClassifier::new();
+        let direct: Vec<&str> = LABELED.iter().filter(|(_, intent)| intent.is_some()).map(|(u, _)| *u).collect();
+
+        let start = Instant::now();
+        for utterance in &direct {
+            assert!(classifier.classify(utterance, today()).is_direct());
+        }
+        let local = start.elapsed();
+
+        let mut engine = AIConversationEngine::new(Box::new(SlowLLMClient { latency: Duration::from_millis(5) }));
+        let start = Instant::now();
+        for utterance in direct.iter().take(10) {
+            engine.send_message(utterance.to_string()).await.unwrap();
+        }
+        let via_llm = start.elapsed() / 10 * direct.len() as u32;
+
+        let per_local = local / direct.len() as u32;
+        println!("direct: {:?}/utterance, mocked LLM: {:?}/utterance", per_local, via_llm / direct.len() as u32);
+        assert!(per_local < Duration::from_millis(2), "local classification took {:?}", per_local);
+        assert!(local * 10 < via_llm, "local {:?} vs llm {:?}", local, via_llm);
+    }
+}
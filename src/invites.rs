@@ -0,0 +1,615 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::calendar::{create_calendar_adapter, scheduling_constraints, with_contact_availability};
+use crate::calendar_adapter::{
+    CalendarAdapter, CalendarEvent, Deadline, MeetingRequest, ResponseStatus, RiskLevel, SchedulingConstraints, TimeSlot,
+};
+use crate::cli::{InvitesAction, InvitesArgs};
+use crate::config::Config;
+use crate::confirm::{PromptReader, StdinPrompt};
+use crate::dates::week_start;
+use crate::risks::{build_risk_report, is_active, RiskReport};
+
+/// How far ahead `invites poll` looks for new invitations
+const POLL_LOOKAHEAD_DAYS: i64 = 21;
+/// Counter-proposals are searched this many days after the invitation's date
+const PROPOSAL_WINDOW_DAYS: i64 = 7;
+const MAX_PROPOSALS: usize = 3;
+
+/// A working-time window kept free of meetings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusBlock {
+    /// 0 = Sunday, 1 = Monday, etc.
+    #[serde(default = "default_work_days")]
+    pub days: Vec<u32>,
+    pub start_hour: u32,
+    pub end_hour: u32,
+}
+
+fn default_work_days() -> Vec<u32> {
+    vec![1, 2, 3, 4, 5]
+}
+
+/// `[invites]`: the constraints invitations are weighed against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InviteSettings {
+    pub max_meetings_per_day: usize,
+    pub focus_blocks: Vec<FocusBlock>,
+    /// People per project, e.g. `roadmap = ["ana@example.com"]`. A project is active while a
+    /// deadline has it as its project, category or tag.
+    pub projects: HashMap<String, Vec<String>>,
+    /// Show a desktop notification for each new invitation found by `invites poll`
+    pub notify: bool,
+}
+
+impl Default for InviteSettings {
+    fn default() -> Self {
+        Self {
+            max_meetings_per_day: 5,
+            focus_blocks: Vec::new(),
+            projects: HashMap::new(),
+            notify: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Recommendation {
+    Accept,
+    Decline,
+    ProposeNewTime,
+}
+
+impl fmt::Display for Recommendation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Accept => "accept",
+            Self::Decline => "decline",
+            Self::ProposeNewTime => "propose a new time",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Factor {
+    Conflict,
+    FocusBlock,
+    DayFull,
+    OutsideWorkingHours,
+    DeadlineRisk,
+    Overcommitted,
+    ActiveProject,
+}
+
+impl Factor {
+    /// Factors tied to the invitation's time slot, which another slot can avoid
+    fn depends_on_time(self) -> bool {
+        matches!(self, Self::Conflict | Self::FocusBlock | Self::DayFull | Self::OutsideWorkingHours)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Reason {
+    pub factor: Factor,
+    pub points: i32,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Evaluation {
+    pub recommendation: Recommendation,
+    pub score: i32,
+    pub reasons: Vec<Reason>,
+}
+
+impl Evaluation {
+    fn time_points(&self) -> i32 {
+        self.reasons.iter().filter(|r| r.factor.depends_on_time()).map(|r| r.points).sum()
+    }
+}
+
+/// Everything an invitation is weighed against
+pub struct InviteContext<'a> {
+    /// Calendar events around the invitation
+    pub events: &'a [CalendarEvent],
+    pub risks: &'a RiskReport,
+    pub active_projects: BTreeSet<String>,
+    pub constraints: &'a SchedulingConstraints,
+    pub settings: &'a InviteSettings,
+    /// The user's own address, left out of attendee matching
+    pub me: &'a str,
+}
+
+/// Projects with an active deadline, by project id, category or tag
+pub fn active_projects(deadlines: &[Deadline], settings: &InviteSettings) -> BTreeSet<String> {
+    settings.projects.keys()
+        .filter(|project| {
+            deadlines.iter().filter(|d| is_active(d)).any(|d| {
+                d.project_id.as_deref() == Some(project.as_str())
+                    || d.category.eq_ignore_ascii_case(project)
+                    || d.tags.iter().any(|t| t.eq_ignore_ascii_case(project))
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+fn overlaps(start: DateTime<Utc>, end: DateTime<Utc>, other_start: DateTime<Utc>, other_end: DateTime<Utc>) -> bool {
+    start < other_end && other_start < end
+}
+
+fn focus_overlap(block: &FocusBlock, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+    let day = start.date_naive();
+    if !block.days.contains(&day.weekday().num_days_from_sunday()) {
+        return false;
+    }
+    let (Some(from), Some(to)) = (day.and_hms_opt(block.start_hour, 0, 0), day.and_hms_opt(block.end_hour.min(23), 0, 0)) else {
+        return false;
+    };
+    let to = if block.end_hour >= 24 { day.and_hms_opt(23, 59, 59).unwrap_or(to) } else { to };
+    overlaps(start, end, from.and_utc(), to.and_utc())
+}
+
+fn within_working_hours(constraints: &SchedulingConstraints, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+    let weekday = start.weekday().num_days_from_sunday();
+    let start_minutes = start.hour() * 60 + start.minute();
+    let end_minutes = if end.date_naive() == start.date_naive() { end.hour() * 60 + end.minute() } else { 24 * 60 };
+    constraints.working_hours.iter()
+        .filter(|w| w.day_of_week == weekday)
+        .any(|w| start_minutes >= w.start_hour * 60 + w.start_minute && end_minutes <= w.end_hour * 60 + w.end_minute)
+}
+
+/// Score an invitation against the user's constraints. Negative points argue against
+/// attending; the recommendation is to accept at a score of 0 or more, to propose another
+/// time when the slot alone is the problem, and otherwise to decline.
+pub fn evaluate_invitation(invite: &CalendarEvent, context: &InviteContext) -> Evaluation {
+    let (start, end) = (invite.start_time, invite.end_time);
+    let mut reasons = Vec::new();
+    let mut add = |factor, points, detail: String| reasons.push(Reason { factor, points, detail });
+
+    let others: Vec<&CalendarEvent> = context.events.iter().filter(|e| e.id != invite.id && !e.all_day).collect();
+    for event in others.iter().filter(|e| overlaps(start, end, e.start_time, e.end_time)) {
+        add(Factor::Conflict, -35, format!("overlaps \"{}\" at {}", event.title, event.start_time.format("%H:%M")));
+    }
+    if context.settings.focus_blocks.iter().any(|b| focus_overlap(b, start, end)) {
+        add(Factor::FocusBlock, -25, "falls in a protected focus block".to_string());
+    }
+    let meetings = others.iter().filter(|e| e.start_time.date_naive() == start.date_naive() && !e.attendees.is_empty()).count();
+    let max = context.settings.max_meetings_per_day;
+    if meetings >= max {
+        add(Factor::DayFull, -40, format!("{} already has {} meetings (max {})", start.format("%A"), meetings, max));
+    } else if meetings + 1 == max {
+        add(Factor::DayFull, -10, format!("would be the last of {} meetings allowed on {}", max, start.format("%A")));
+    }
+    if !within_working_hours(context.constraints, start, end) {
+        add(Factor::OutsideWorkingHours, -20, "outside working hours".to_string());
+    }
+
+    let week = week_start(start.date_naive());
+    let due_this_week: Vec<_> = context.risks.deadline_risks.iter()
+        .filter(|r| week_start(r.due_date.date_naive()) == week)
+        .collect();
+    let high: BTreeSet<&str> = due_this_week.iter().filter(|r| r.indicator.severity == RiskLevel::High).map(|r| r.title.as_str()).collect();
+    let medium: BTreeSet<&str> = due_this_week.iter()
+        .filter(|r| r.indicator.severity == RiskLevel::Medium && !high.contains(r.title.as_str()))
+        .map(|r| r.title.as_str())
+        .collect();
+    if !high.is_empty() || !medium.is_empty() {
+        let points = -(15 * high.len() as i32 + 5 * medium.len() as i32).min(30);
+        let titles: Vec<&str> = high.iter().chain(medium.iter()).copied().collect();
+        add(Factor::DeadlineRisk, points, format!("at-risk deadlines that week: {}", titles.join(", ")));
+    }
+    let load = &context.risks.overcommitment;
+    if load.index > 1.0 {
+        add(Factor::Overcommitted, -20, format!("overcommitted: {:.0}h of work for {:.0}h available", load.remaining_hours, load.available_hours));
+    }
+
+    let people: Vec<String> = invite.attendees.iter().map(|a| a.to_lowercase()).filter(|a| a != &context.me.to_lowercase()).collect();
+    let subject = invite.title.to_lowercase();
+    for project in &context.active_projects {
+        let members = context.settings.projects.get(project).map(Vec::as_slice).unwrap_or_default();
+        let shared: Vec<&str> = members.iter().filter(|m| people.contains(&m.to_lowercase())).map(String::as_str).collect();
+        if !shared.is_empty() {
+            add(Factor::ActiveProject, 30, format!("{} work(s) on active project {}", shared.join(", "), project));
+        } else if subject.contains(&project.to_lowercase()) {
+            add(Factor::ActiveProject, 15, format!("about active project {}", project));
+        }
+    }
+
+    let score: i32 = reasons.iter().map(|r| r.points).sum();
+    let mut evaluation = Evaluation { recommendation: Recommendation::Accept, score, reasons };
+    if score < 0 {
+        let time_points = evaluation.time_points();
+        evaluation.recommendation = if time_points < 0 && score - time_points >= 0 {
+            Recommendation::ProposeNewTime
+        } else {
+            Recommendation::Decline
+        };
+    }
+    evaluation
+}
+
+/// Free slots for the same meeting that avoid every time-specific objection, best first
+pub async fn counter_proposals(adapter: &CalendarAdapter, invite: &CalendarEvent, context: &InviteContext<'_>, now: DateTime<Utc>) -> Result<Vec<TimeSlot>> {
+    let earliest = invite.start_time.date_naive().and_hms_opt(0, 0, 0).map(|d| d.and_utc()).unwrap_or(invite.start_time).max(now);
+    let request = MeetingRequest {
+        title: invite.title.clone(),
+        description: invite.description.clone(),
+        duration_minutes: (invite.end_time - invite.start_time).num_minutes().max(1) as u32,
+        required_attendees: invite.attendees.iter().filter(|a| !a.eq_ignore_ascii_case(context.me)).cloned().collect(),
+        optional_attendees: Vec::new(),
+        location: invite.location.clone(),
+        earliest_start: earliest,
+        latest_start: earliest + Duration::days(PROPOSAL_WINDOW_DAYS),
+        preferred_times: Vec::new(),
+        avoid_times: context.events.iter()
+            .filter(|e| e.id != invite.id)
+            .map(|e| TimeSlot { start_time: e.start_time, end_time: e.end_time, calendar_id: None, event_id: Some(e.id.clone()) })
+            .collect(),
+        buffer_minutes: 0,
+        allow_overlapping: false,
+    };
+    let recommendations = adapter.find_meeting_time(&request).await?;
+    Ok(recommendations.into_iter()
+        .map(|r| r.time_slot)
+        .filter(|slot| {
+            let moved = CalendarEvent { start_time: slot.start_time, end_time: slot.end_time, ..invite.clone() };
+            evaluate_invitation(&moved, context).time_points() == 0
+        })
+        .take(MAX_PROPOSALS)
+        .collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InviteStatus {
+    Pending,
+    Accepted,
+    Declined,
+    Proposed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedInvite {
+    pub event: CalendarEvent,
+    pub detected_at: DateTime<Utc>,
+    pub status: InviteStatus,
+}
+
+/// Invitations found by polling, waiting for an explicit response in `invites review`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InviteQueue {
+    pub invites: Vec<QueuedInvite>,
+}
+
+pub fn default_invite_queue_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".config")
+        .join("arrowhead")
+        .join("invites.json")
+}
+
+impl InviteQueue {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Queue upcoming events that list `me` among other attendees and haven't been seen before
+    pub fn add_new(&mut self, events: &[CalendarEvent], me: &str, now: DateTime<Utc>) -> Vec<CalendarEvent> {
+        let invitations: Vec<CalendarEvent> = events.iter()
+            .filter(|e| e.start_time > now && !e.all_day)
+            .filter(|e| e.attendees.iter().any(|a| a.eq_ignore_ascii_case(me)))
+            .filter(|e| e.attendees.iter().any(|a| !a.eq_ignore_ascii_case(me)))
+            .filter(|e| !self.invites.iter().any(|q| q.event.id == e.id))
+            .cloned()
+            .collect();
+        self.invites.extend(invitations.iter().map(|event| QueuedInvite {
+            event: event.clone(),
+            detected_at: now,
+            status: InviteStatus::Pending,
+        }));
+        invitations
+    }
+
+    /// Forget answered and past invitations
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        self.invites.retain(|q| q.status == InviteStatus::Pending || q.event.end_time > now);
+    }
+}
+
+/// Show a desktop notification. Urgent ones stay on screen until dismissed where the
+/// desktop supports it (freedesktop notification servers on Linux and the BSDs).
+pub(crate) fn notify_desktop(title: &str, body: &str, urgent: bool) -> Result<()> {
+    let mut notification = notify_rust::Notification::new();
+    notification.summary(title).body(body);
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if urgent {
+        notification.urgency(notify_rust::Urgency::Critical);
+    }
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    let _ = urgent;
+    notification.show().context("Could not show a desktop notification")?;
+    Ok(())
+}
+
+fn print_evaluation(event: &CalendarEvent, evaluation: &Evaluation) {
+    println!("\n📨 {} ({} – {})", event.title, event.start_time.format("%a %b %d %H:%M"), event.end_time.format("%H:%M"));
+    println!("   Recommendation: {} (score {})", evaluation.recommendation, evaluation.score);
+    for reason in &evaluation.reasons {
+        println!("   {:+4}  {}", reason.points, reason.detail);
+    }
+}
+
+fn read_choice(reader: &mut dyn PromptReader, proposals: usize) -> Result<String> {
+    let options = if proposals > 0 { format!("[a]ccept, [d]ecline, [p1-p{}] propose, [s]kip:", proposals) } else { "[a]ccept, [d]ecline, [s]kip:".to_string() };
+    Ok(reader.read_answer(&options)?.trim().to_lowercase())
+}
+
+pub async fn handle_invites_command(args: InvitesArgs) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let settings = &config.invites;
+    let adapter = create_calendar_adapter(&config.calendar, &config.network)?.with_scheduling(config.scheduling.clone());
+    let me = config.calendar.username.clone().unwrap_or_default();
+    let now = Utc::now();
+    let events = adapter.list_events(&config.calendar.calendar_id, Some(now - Duration::days(1)), Some(now + Duration::days(POLL_LOOKAHEAD_DAYS + PROPOSAL_WINDOW_DAYS))).await?;
+    // Counter-proposals respect the other attendees' busy time where it can be loaded
+    let mut attendees: Vec<String> = events.iter().flat_map(|e| e.attendees.iter()).filter(|a| !a.eq_ignore_ascii_case(&me)).cloned().collect();
+    attendees.sort();
+    attendees.dedup();
+    let adapter = with_contact_availability(adapter, &config.calendar, &config.network, &attendees, now).await;
+    let deadlines = adapter.get_deadlines(None).await?;
+    let constraints = scheduling_constraints(&config.calendar);
+    let risks = build_risk_report(&deadlines, &constraints, now)?;
+    let context = InviteContext {
+        events: &events,
+        risks: &risks,
+        active_projects: active_projects(&deadlines, settings),
+        constraints: &constraints,
+        settings,
+        me: &me,
+    };
+
+    let queue_path = default_invite_queue_path();
+    let mut queue = InviteQueue::load(&queue_path)?;
+    match args.action {
+        InvitesAction::Poll { no_notify } => {
+            let upcoming: Vec<CalendarEvent> = events.iter().filter(|e| e.start_time <= now + Duration::days(POLL_LOOKAHEAD_DAYS)).cloned().collect();
+            let found = queue.add_new(&upcoming, &me, now);
+            queue.prune(now);
+            queue.save(&queue_path)?;
+            let mut output = Vec::new();
+            for event in &found {
+                let evaluation = evaluate_invitation(event, &context);
+                print_evaluation(event, &evaluation);
+                if settings.notify && !no_notify {
+                    let top = evaluation.reasons.iter().min_by_key(|r| r.points).map(|r| r.detail.as_str()).unwrap_or("no conflicts");
+                    if let Err(e) = notify_desktop(&format!("Invitation: {}", event.title), &format!("Recommend: {} ({})", evaluation.recommendation, top), false) {
+                        log::warn!("{:#}", e);
+                    }
+                }
+                output.push(serde_json::json!({ "id": event.id, "title": event.title, "evaluation": evaluation }));
+            }
+            let pending = queue.invites.iter().filter(|q| q.status == InviteStatus::Pending).count();
+            println!("{} new invitation(s), {} waiting for review (arrowhead invites review)", found.len(), pending);
+            crate::script::publish_output(serde_json::json!({ "new": output, "pending": pending }));
+        }
+        InvitesAction::Review => {
+            let mut reader = StdinPrompt;
+            let pending: Vec<usize> = (0..queue.invites.len()).filter(|&i| queue.invites[i].status == InviteStatus::Pending).collect();
+            if pending.is_empty() {
+                println!("No invitations waiting for review.");
+                return Ok(());
+            }
+            if !reader.is_interactive() {
+                for &i in &pending {
+                    print_evaluation(&queue.invites[i].event, &evaluate_invitation(&queue.invites[i].event, &context));
+                }
+                println!("\nRun `arrowhead invites review` in a terminal to respond.");
+                return Ok(());
+            }
+            for i in pending {
+                let event = queue.invites[i].event.clone();
+                let evaluation = evaluate_invitation(&event, &context);
+                print_evaluation(&event, &evaluation);
+                let proposals = if evaluation.recommendation == Recommendation::Accept {
+                    Vec::new()
+                } else {
+                    counter_proposals(&adapter, &event, &context, now).await.unwrap_or_else(|e| {
+                        println!("   ⚠️  Could not look for other times: {}", e);
+                        Vec::new()
+                    })
+                };
+                for (n, slot) in proposals.iter().enumerate() {
+                    println!("   p{}: {} – {}", n + 1, slot.start_time.format("%a %b %d %H:%M"), slot.end_time.format("%H:%M"));
+                }
+                let choice = read_choice(&mut reader, proposals.len())?;
+                let status = match choice.as_str() {
+                    "a" | "accept" => {
+                        adapter.update_invitation_response(&event.id, &me, ResponseStatus::Accepted).await?;
+                        InviteStatus::Accepted
+                    }
+                    "d" | "decline" => {
+                        adapter.update_invitation_response(&event.id, &me, ResponseStatus::Declined).await?;
+                        InviteStatus::Declined
+                    }
+                    other => match other.strip_prefix('p').and_then(|n| n.parse::<usize>().ok()).and_then(|n| proposals.get(n.wrapping_sub(1))) {
+                        Some(slot) => {
+                            let reason = evaluation.reasons.iter().filter(|r| r.points < 0).map(|r| r.detail.as_str()).collect::<Vec<_>>().join("; ");
+                            adapter.reschedule_meeting_invitation(&event.id, slot, Some(&reason)).await?;
+                            InviteStatus::Proposed
+                        }
+                        None => continue,
+                    },
+                };
+                queue.invites[i].status = status;
+                queue.save(&queue_path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar_adapter::{RiskIndicator, RiskType};
+    use crate::risks::{DeadlineRisk, Overcommitment};
+    use chrono::TimeZone;
+
+    const ME: &str = "me@example.com";
+
+    /// Tuesday of a week starting Monday 2024-06-03
+    fn tuesday(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 4, hour, 0, 0).unwrap()
+    }
+
+    fn event(id: &str, title: &str, start: DateTime<Utc>, minutes: i64, attendees: &[&str]) -> CalendarEvent {
+        CalendarEvent {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: None,
+            start_time: start,
+            end_time: start + Duration::minutes(minutes),
+            location: None,
+            attendees: attendees.iter().map(|a| a.to_string()).collect(),
+            all_day: false,
+            recurring: false,
+            calendar_id: "default".to_string(),
+        }
+    }
+
+    /// 9:00–17:00, Monday to Friday
+    fn constraints() -> SchedulingConstraints {
+        scheduling_constraints(&crate::config::CalendarSettings::default())
+    }
+
+    fn risks(deadline_risks: Vec<DeadlineRisk>, index: f32) -> RiskReport {
+        RiskReport {
+            generated_at: tuesday(8),
+            active_deadlines: deadline_risks.len(),
+            deadline_risks,
+            cross_deadline_risks: Vec::new(),
+            overcommitment: Overcommitment { remaining_hours: 80.0 * index, available_hours: 80.0, index },
+        }
+    }
+
+    fn at_risk(title: &str, due: DateTime<Utc>) -> DeadlineRisk {
+        DeadlineRisk {
+            deadline_id: title.to_lowercase(),
+            title: title.to_string(),
+            due_date: due,
+            days_remaining: 2,
+            indicator: RiskIndicator {
+                indicator_type: RiskType::TimeShortage,
+                severity: RiskLevel::High,
+                description: "behind schedule".to_string(),
+                suggested_action: "add time".to_string(),
+                deadline_impact: 0.8,
+            },
+        }
+    }
+
+    fn settings() -> InviteSettings {
+        InviteSettings {
+            max_meetings_per_day: 4,
+            focus_blocks: vec![FocusBlock { days: vec![2], start_hour: 9, end_hour: 11 }],
+            projects: HashMap::from([("roadmap".to_string(), vec!["ana@example.com".to_string()])]),
+            notify: false,
+        }
+    }
+
+    fn factors(evaluation: &Evaluation) -> Vec<Factor> {
+        evaluation.reasons.iter().map(|r| r.factor).collect()
+    }
+
+    #[test]
+    fn test_empty_week_accepts() {
+        let (settings, constraints, risks) = (settings(), constraints(), risks(Vec::new(), 0.2));
+        let context = InviteContext { events: &[], risks: &risks, active_projects: BTreeSet::new(), constraints: &constraints, settings: &settings, me: ME };
+        let invite = event("inv", "Vendor intro", tuesday(14), 30, &[ME, "bob@vendor.test"]);
+        let evaluation = evaluate_invitation(&invite, &context);
+        assert_eq!(evaluation.recommendation, Recommendation::Accept);
+        assert_eq!(evaluation.score, 0);
+        assert!(evaluation.reasons.is_empty());
+    }
+
+    #[test]
+    fn test_overcommitted_week_declines() {
+        let (settings, constraints) = (settings(), constraints());
+        let risks = risks(vec![at_risk("Launch", tuesday(17) + Duration::days(2)), at_risk("Audit", tuesday(12))], 1.6);
+        let events: Vec<CalendarEvent> = (0..4).map(|i| event(&format!("m{}", i), "Sync", tuesday(11 + i), 45, &[ME, "team@example.com"])).collect();
+        let context = InviteContext { events: &events, risks: &risks, active_projects: BTreeSet::new(), constraints: &constraints, settings: &settings, me: ME };
+
+        let invite = event("inv", "Vendor intro", tuesday(10), 60, &[ME, "bob@vendor.test"]);
+        let evaluation = evaluate_invitation(&invite, &context);
+        assert_eq!(evaluation.recommendation, Recommendation::Decline);
+        assert_eq!(factors(&evaluation), vec![Factor::FocusBlock, Factor::DayFull, Factor::DeadlineRisk, Factor::Overcommitted]);
+        assert_eq!(evaluation.score, -25 - 40 - 30 - 20);
+        // Deterministic: the same inputs always give the same result
+        assert_eq!(evaluate_invitation(&invite, &context).score, evaluation.score);
+
+        // An active project's people outweigh a crowded week, but not a crowded day
+        let context = InviteContext { active_projects: BTreeSet::from(["roadmap".to_string()]), ..context };
+        let invite = event("inv", "Roadmap review", tuesday(16), 30, &[ME, "ana@example.com"]);
+        let evaluation = evaluate_invitation(&invite, &context);
+        assert!(factors(&evaluation).contains(&Factor::ActiveProject));
+        assert_eq!(evaluation.recommendation, Recommendation::Decline);
+    }
+
+    #[test]
+    fn test_slot_only_problems_propose_a_new_time() {
+        let (settings, constraints, risks) = (settings(), constraints(), risks(Vec::new(), 0.5));
+        let events = vec![event("m1", "Design review", tuesday(14), 60, &[ME, "team@example.com"])];
+        let context = InviteContext { events: &events, risks: &risks, active_projects: BTreeSet::from(["roadmap".to_string()]), constraints: &constraints, settings: &settings, me: ME };
+
+        let invite = event("inv", "Roadmap sync", tuesday(14), 30, &[ME, "ana@example.com"]);
+        let evaluation = evaluate_invitation(&invite, &context);
+        assert_eq!(factors(&evaluation), vec![Factor::Conflict, Factor::ActiveProject]);
+        assert_eq!(evaluation.recommendation, Recommendation::ProposeNewTime);
+
+        let moved = event("inv", "Roadmap sync", tuesday(15), 30, &[ME, "ana@example.com"]);
+        assert_eq!(evaluate_invitation(&moved, &context).recommendation, Recommendation::Accept);
+        let evening = event("inv", "Roadmap sync", tuesday(18), 30, &[ME, "ana@example.com"]);
+        assert_eq!(factors(&evaluate_invitation(&evening, &context)), vec![Factor::OutsideWorkingHours, Factor::ActiveProject]);
+    }
+
+    #[test]
+    fn test_queue_only_adds_unseen_invitations() {
+        let mut queue = InviteQueue::default();
+        let events = vec![
+            event("inv", "Vendor intro", tuesday(14), 30, &[ME, "bob@vendor.test"]),
+            event("own", "Focus", tuesday(9), 60, &[]),
+            event("sent", "1:1", tuesday(15), 30, &["ana@example.com"]),
+            event("past", "Old", tuesday(7) - Duration::days(2), 30, &[ME, "bob@vendor.test"]),
+        ];
+        let found = queue.add_new(&events, ME, tuesday(8));
+        assert_eq!(found.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), vec!["inv"]);
+        assert!(queue.add_new(&events, ME, tuesday(8)).is_empty());
+
+        queue.invites[0].status = InviteStatus::Accepted;
+        queue.prune(tuesday(16));
+        assert!(queue.invites.is_empty());
+    }
+}
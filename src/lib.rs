@@ -1,15 +1,84 @@
 pub mod cli;
 pub mod obsidian_adapter;
+pub mod vault_transaction;
+pub mod embedding_models;
+pub mod embedding_provider;
+pub mod embedding_store;
+pub mod cache;
+pub mod vaults;
+pub mod profiles;
+pub mod prompts;
+pub mod completions;
+pub mod ann_index;
+pub mod snippets;
 pub mod calendar_adapter;
+pub mod deadline_store;
+pub mod deadlines;
+pub mod focus;
+pub mod reminders;
 pub mod jira_adapter;
 pub mod router;
 pub mod todos;
 pub mod notes;
 pub mod goals;
+pub mod calendar;
+pub mod sync;
+pub mod availability;
+pub mod risks;
+pub mod week_plan;
+pub mod daily;
+pub mod dates;
+pub mod markdown;
+pub mod glossary;
+pub mod note_skeleton;
+pub mod opt_out;
+pub mod changelog;
+pub mod history;
+pub mod managed_sections;
+pub mod backlinks;
+pub mod link_graph;
+pub mod llm_json;
+pub mod term_index;
+pub mod related;
+pub mod tags;
+pub mod invites;
+pub mod scheduling;
+pub mod meetings;
+pub mod templates;
+pub mod template_values;
+pub mod tool_results;
+pub mod command_suggest;
+pub mod conflicts;
+pub mod reindex;
+pub mod watch;
+pub mod search;
+pub mod plugin_api;
+pub mod filing;
+pub mod doctor;
+pub mod health;
+pub mod insights;
+pub mod export;
+pub mod chunking;
+pub mod clustering;
+pub mod todo_bulk;
+pub mod todo_model;
+pub mod title_match;
+pub mod task_import;
+pub mod script;
+pub mod confirm;
+pub mod network;
+pub mod organize;
+pub mod heuristic_analysis;
+pub mod apply_plan;
 pub mod utils;
 pub mod ai_conversation;
 pub mod gemini_client;
+pub mod anthropic_client;
+pub mod ollama_client;
 pub mod openai_client;
+pub mod llm_retry;
+pub mod usage;
+pub mod error;
 pub mod config;
 pub mod context_manager;
 pub mod tool_orchestrator;
@@ -18,6 +87,8 @@ pub mod mcp_api;
 pub mod nl_command_parser;
 pub mod conversational_interface;
 pub mod nl_cli_bridge;
+pub mod intent_classifier;
+pub mod memory;
 pub mod intelligent_help;
 pub mod workflow_engine;
 pub mod workflow_integrations;
@@ -26,3 +97,14 @@ pub mod workflow_ui;
 pub mod workflow_monitoring;
 pub mod user_learning;
 pub mod proactive_assistance;
+pub mod output;
+pub mod progress;
+pub mod report;
+pub mod split;
+pub mod sessions;
+pub mod slash_commands;
+pub mod timezone;
+pub mod token_counter;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
@@ -0,0 +1,308 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::backlinks::note_name;
+use crate::cli::{LinksAction, LinksArgs};
+use crate::config::Config;
+use crate::managed_sections::without_managed_sections;
+use crate::notes::link_key;
+use crate::obsidian_adapter::ObsidianAdapter;
+
+/// `link_key` of the note at `path`, the form links to it are stored in
+fn path_key(path: &str) -> String {
+    link_key(note_name(path))
+}
+
+/// The note a link target names, or `None` for attachments, headings in the same note and
+/// external URLs
+fn target_key(target: &str) -> Option<String> {
+    let target = target.split(['#', '|']).next().unwrap_or_default().trim();
+    let name = note_name(target);
+    let is_attachment = !target.ends_with(".md") && name.contains('.');
+    (!name.is_empty() && !is_attachment).then(|| link_key(name))
+}
+
+/// Target of the markdown link whose `(` is at the start of `rest`, if it points into the vault
+fn markdown_target(rest: &str) -> Option<(&str, usize)> {
+    let inner = rest.strip_prefix('(')?;
+    let end = inner.find(')')?;
+    let target = inner[..end].trim().trim_start_matches('<').trim_end_matches('>');
+    let target = target.split_whitespace().next().unwrap_or_default();
+    let external = target.contains("://") || target.starts_with("mailto:");
+    (!external).then_some((target, end + 2))
+}
+
+/// Keys of the notes `content` links to with `[[wikilinks]]` or `[text](note.md)` links,
+/// outside frontmatter, code blocks and managed sections
+pub fn note_links(content: &str) -> BTreeSet<String> {
+    let body = ObsidianAdapter::parse_markdown_file(content).map_or(content.to_string(), |file| file.content);
+    let body = without_managed_sections(&body, &[]);
+    let mut links = BTreeSet::new();
+    let mut in_code = false;
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        let mut rest = line;
+        while let Some(start) = rest.find('[') {
+            let after = &rest[start..];
+            if let Some(inner) = after.strip_prefix("[[") {
+                let Some(end) = inner.find("]]") else { break };
+                links.extend(target_key(&inner[..end]));
+                rest = &inner[end + 2..];
+            } else if let Some(close) = after.find("](") {
+                match markdown_target(&after[close + 1..]) {
+                    Some((target, len)) => {
+                        links.extend(target_key(&target.replace("%20", " ")));
+                        rest = &after[close + 1 + len..];
+                    }
+                    None => rest = &after[1..],
+                }
+            } else {
+                break;
+            }
+        }
+    }
+    links
+}
+
+/// Links between indexed notes, kept next to the vector database. Only the forward links
+/// are stored; the backward map is rebuilt when the graph is loaded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkGraph {
+    /// Link keys each indexed note links to; every indexed note has an entry
+    outgoing: BTreeMap<String, BTreeSet<String>>,
+    /// Indexed notes linking to each link key
+    #[serde(skip)]
+    incoming: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl LinkGraph {
+    /// The graph saved at `path`; empty when there is none yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path).context("Failed to read the link graph")?;
+        let mut graph: Self = serde_json::from_str(&data).context("Failed to parse the link graph")?;
+        for (source, targets) in &graph.outgoing {
+            for target in targets {
+                graph.incoming.entry(target.clone()).or_default().insert(source.clone());
+            }
+        }
+        Ok(graph)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        crate::cache::write_atomic(path, serde_json::to_string(self)?.as_bytes()).context("Failed to write the link graph")
+    }
+
+    /// Replace the links of `path` with those in `content`; false when they are unchanged
+    pub fn update(&mut self, path: &str, content: &str) -> bool {
+        let own = path_key(path);
+        let links: BTreeSet<String> = note_links(content).into_iter().filter(|key| *key != own).collect();
+        if self.outgoing.get(path) == Some(&links) {
+            return false;
+        }
+        self.unlink(path);
+        for key in &links {
+            self.incoming.entry(key.clone()).or_default().insert(path.to_string());
+        }
+        self.outgoing.insert(path.to_string(), links);
+        true
+    }
+
+    fn unlink(&mut self, path: &str) -> Option<BTreeSet<String>> {
+        let links = self.outgoing.remove(path)?;
+        for key in &links {
+            if let Some(sources) = self.incoming.get_mut(key) {
+                sources.remove(path);
+                if sources.is_empty() {
+                    self.incoming.remove(key);
+                }
+            }
+        }
+        Some(links)
+    }
+
+    /// Forget `path` and its links; false when it wasn't indexed
+    pub fn remove(&mut self, path: &str) -> bool {
+        self.unlink(path).is_some()
+    }
+
+    /// Move the links of `from` to `to`. Links to the old name stay as they are written.
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        let Some(links) = self.unlink(from) else { return false };
+        self.unlink(to);
+        let own = path_key(to);
+        for key in links.iter().filter(|key| **key != own) {
+            self.incoming.entry(key.clone()).or_default().insert(to.to_string());
+        }
+        self.outgoing.insert(to.to_string(), links.into_iter().filter(|key| *key != own).collect());
+        true
+    }
+
+    /// Keep only the notes `keep` returns true for; returns how many were dropped
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) -> usize {
+        let dropped: Vec<String> = self.outgoing.keys().filter(|path| !keep(path)).cloned().collect();
+        for path in &dropped {
+            self.unlink(path);
+        }
+        dropped.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.outgoing.clear();
+        self.incoming.clear();
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.outgoing.contains_key(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.outgoing.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outgoing.is_empty()
+    }
+
+    /// Indexed notes linking to `path`, in path order
+    pub fn backlinks(&self, path: &str) -> Vec<String> {
+        self.incoming.get(&path_key(path)).into_iter().flatten().filter(|source| *source != path).cloned().collect()
+    }
+
+    /// Indexed notes `path` links to, in path order. Links to notes that aren't indexed are
+    /// left out.
+    pub fn outgoing_links(&self, path: &str) -> Vec<String> {
+        let Some(keys) = self.outgoing.get(path) else { return Vec::new() };
+        self.outgoing.keys().filter(|target| *target != path && keys.contains(&path_key(target))).cloned().collect()
+    }
+
+    /// Whether either note links to the other
+    pub fn is_linked(&self, a: &str, b: &str) -> bool {
+        let links_to = |from: &str, to: &str| self.outgoing.get(from).is_some_and(|keys| keys.contains(&path_key(to)));
+        links_to(a, b) || links_to(b, a)
+    }
+
+    /// Indexed notes with no links to or from another indexed note, in path order
+    pub fn orphans(&self) -> Vec<String> {
+        let mut notes_by_key: HashMap<String, usize> = HashMap::new();
+        for path in self.outgoing.keys() {
+            *notes_by_key.entry(path_key(path)).or_default() += 1;
+        }
+        self.outgoing.iter()
+            .filter(|(path, keys)| {
+                let linked_out = keys.iter().any(|key| notes_by_key.contains_key(key));
+                !linked_out && self.backlinks(path).is_empty()
+            })
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+pub async fn handle_links_command(args: LinksArgs) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let mut adapter = ObsidianAdapter::new(Some(config.obsidian.base_url.clone()), config.obsidian.api_key.clone());
+    adapter.set_cache_settings(&config.cache);
+    adapter.load_link_graph()?;
+    if adapter.link_graph().is_empty() {
+        bail!("The link graph is empty; run `arrowhead index` to build it");
+    }
+
+    match args.action {
+        LinksAction::Backlinks { path } => {
+            let path = if path.ends_with(".md") { path } else { format!("{}.md", path) };
+            if !adapter.link_graph().contains(&path) {
+                bail!("{} isn't indexed; run `arrowhead index` to add it", path);
+            }
+            let backlinks = adapter.get_backlinks(&path);
+            let outgoing = adapter.get_outgoing_links(&path);
+            println!("Linked from ({}):", backlinks.len());
+            for source in &backlinks {
+                println!("  ← {}", source);
+            }
+            println!("Links to ({}):", outgoing.len());
+            for target in &outgoing {
+                println!("  → {}", target);
+            }
+            crate::script::publish_output(serde_json::json!({ "path": path, "backlinks": backlinks, "outgoing": outgoing }));
+        }
+        LinksAction::Orphans => {
+            let orphans = adapter.find_orphans();
+            if orphans.is_empty() {
+                println!("No orphan notes among {} indexed.", adapter.link_graph().len());
+            } else {
+                println!("{} of {} indexed note(s) have no links in or out:", orphans.len(), adapter.link_graph().len());
+            }
+            for path in &orphans {
+                println!("  {}", path);
+            }
+            crate::script::publish_output(serde_json::json!(orphans));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_are_read_from_wikilinks_and_markdown_links() {
+        let content = "---\ntags: [x]\nrelated: \"[[Frontmatter]]\"\n---\n\
+            See [[Project Plan|the plan]], [[notes/Weekly Review#Goals]] and [the log](logs/Daily%20Log.md).\n\
+            ![[diagram.png]] [site](https://example.com/page.md) [[#Local heading]] [top](#top)\n\
+            ```\n[[In Code]]\n```\n";
+        let links: Vec<String> = note_links(content).into_iter().collect();
+        assert_eq!(links, ["daily-log", "project-plan", "weekly-review"]);
+    }
+
+    #[test]
+    fn test_graph_updates_incrementally() {
+        let mut graph = LinkGraph::default();
+        assert!(graph.update("a.md", "[[B]] and [[Missing]]"));
+        assert!(graph.update("b.md", "nothing here"));
+        assert!(graph.update("c.md", "[[c]] links to itself"));
+        assert!(!graph.update("a.md", "[[b]] and [[missing]]"));
+
+        assert_eq!(graph.backlinks("b.md"), ["a.md"]);
+        assert_eq!(graph.outgoing_links("a.md"), ["b.md"]);
+        assert!(graph.is_linked("b.md", "a.md") && !graph.is_linked("a.md", "c.md"));
+        assert_eq!(graph.orphans(), ["c.md"]);
+
+        // Re-indexing one note replaces only its links
+        assert!(graph.update("a.md", "[[C]]"));
+        assert!(graph.backlinks("b.md").is_empty());
+        assert_eq!(graph.backlinks("c.md"), ["a.md"]);
+        assert_eq!(graph.orphans(), ["b.md"]);
+
+        assert!(graph.rename("a.md", "folder/d.md"));
+        assert_eq!(graph.backlinks("c.md"), ["folder/d.md"]);
+        assert!(graph.remove("folder/d.md"));
+        assert_eq!(graph.orphans(), ["b.md", "c.md"]);
+        assert_eq!(graph.retain(|path| path != "b.md"), 1);
+        assert_eq!(graph.len(), 1);
+    }
+
+    #[test]
+    fn test_saved_graph_rebuilds_backlinks() {
+        let path = std::env::temp_dir().join(format!("arrowhead-links-{}.json", uuid::Uuid::new_v4()));
+        let mut graph = LinkGraph::default();
+        graph.update("a.md", "[[b]]");
+        graph.update("b.md", "");
+        graph.save(&path).unwrap();
+        let loaded = LinkGraph::load(&path).unwrap();
+        fs::remove_file(&path).ok();
+        assert_eq!(loaded, graph);
+        assert_eq!(loaded.backlinks("b.md"), ["a.md"]);
+    }
+}
@@ -0,0 +1,267 @@
+//! Reading JSON out of model replies. Models wrap it in prose or ```json fences, send
+//! several objects, leave trailing commas and use single quotes; `parse` finds each JSON
+//! value in the reply, repairs the common mistakes and deserializes the first one that
+//! fits the expected type.
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+
+/// Characters of the reply quoted in an error
+const SNIPPET_CHARS: usize = 160;
+
+/// A reply that held no JSON of the expected shape
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlmJsonError {
+    /// What went wrong, e.g. serde's "missing field `intent`"
+    pub reason: String,
+    /// The start of the JSON that came closest, or of the reply when it held none
+    pub snippet: String,
+}
+
+impl fmt::Display for LlmJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in: {}", self.reason, self.snippet)
+    }
+}
+
+impl std::error::Error for LlmJsonError {}
+
+fn snippet(text: &str) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(SNIPPET_CHARS) {
+        Some((at, _)) => format!("{}…", &text[..at]),
+        None => text.to_string(),
+    }
+}
+
+/// Deserialize the first JSON value in `reply` that fits `T`, repairing trailing commas and
+/// single-quoted strings. Fenced ```json blocks are tried before bare objects and arrays.
+/// Use `#[serde(deny_unknown_fields)]` on `T` to reject values with extra keys.
+pub fn parse<T: DeserializeOwned>(reply: &str) -> Result<T> {
+    let mut closest: Option<LlmJsonError> = None;
+    for candidate in candidates(reply) {
+        let attempt = serde_json::from_str::<T>(candidate).or_else(|_| serde_json::from_str::<T>(&repair(candidate)));
+        match attempt {
+            Ok(value) => return Ok(value),
+            // A value of the wrong shape says more than text that isn't JSON at all
+            Err(e) if closest.is_none() || !e.is_syntax() => {
+                closest = Some(LlmJsonError { reason: e.to_string(), snippet: snippet(candidate) });
+            }
+            Err(_) => {}
+        }
+    }
+    Err(closest.unwrap_or_else(|| LlmJsonError { reason: "no JSON found".to_string(), snippet: snippet(reply) }).into())
+}
+
+/// The JSON values `reply` may hold, in the order `parse` tries them: the contents of
+/// fenced code blocks, then every balanced top-level `{…}` or `[…]` outside them
+pub fn candidates(reply: &str) -> Vec<&str> {
+    let mut fenced = Vec::new();
+    let mut outside = Vec::new();
+    let mut rest = reply;
+    while let Some(open) = rest.find("```") {
+        outside.push(&rest[..open]);
+        let after = &rest[open + 3..];
+        // The info string, e.g. `json`, runs to the end of the line
+        let body = &after[after.find('\n').map_or(after.len(), |i| i + 1)..];
+        match body.find("```") {
+            Some(close) => {
+                fenced.push(&body[..close]);
+                rest = &body[close + 3..];
+            }
+            None => {
+                // An unclosed fence, as when the reply was cut off
+                fenced.push(body);
+                rest = "";
+            }
+        }
+    }
+    outside.push(rest);
+
+    let mut found: Vec<&str> = fenced.into_iter().chain(outside).flat_map(balanced_values).collect();
+    found.dedup();
+    found
+}
+
+/// Every top-level `{…}` or `[…]` in `text` whose brackets balance, skipping brackets in
+/// double-quoted strings
+fn balanced_values(text: &str) -> Vec<&str> {
+    let bytes = text.as_bytes();
+    let mut values = Vec::new();
+    let mut start = 0;
+    while let Some(open) = text[start..].find(['{', '[']).map(|i| i + start) {
+        match matching_close(&bytes[open..]) {
+            Some(len) => {
+                values.push(&text[open..open + len]);
+                start = open + len;
+            }
+            None => start = open + 1,
+        }
+    }
+    values
+}
+
+/// Length of the bracketed value at the start of `bytes`, if it closes
+fn matching_close(bytes: &[u8]) -> Option<usize> {
+    let mut stack = Vec::new();
+    let (mut in_string, mut escaped) = (false, false);
+    for (i, &byte) in bytes.iter().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' => stack.push(b'}'),
+            b'[' => stack.push(b']'),
+            b'}' | b']' => {
+                if stack.pop() != Some(byte) {
+                    return None;
+                }
+                if stack.is_empty() {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Fix what models commonly get wrong: commas before a closing bracket, and strings or
+/// keys in single quotes. Double-quoted strings are left as they are.
+pub fn repair(json: &str) -> String {
+    let chars: Vec<char> = json.chars().collect();
+    let mut out = String::with_capacity(json.len());
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => {
+                let end = string_end(&chars, i, '"');
+                out.extend(&chars[i..end]);
+                i = end;
+            }
+            '\'' => {
+                let end = string_end(&chars, i, '\'');
+                let closed = end > i + 1 && chars[end - 1] == '\'';
+                out.push('"');
+                let mut j = i + 1;
+                while j < if closed { end - 1 } else { end } {
+                    match chars[j] {
+                        '\\' if chars.get(j + 1) == Some(&'\'') => {
+                            out.push('\'');
+                            j += 1;
+                        }
+                        '\\' => {
+                            out.push('\\');
+                            if let Some(next) = chars.get(j + 1) {
+                                out.push(*next);
+                            }
+                            j += 1;
+                        }
+                        '"' => out.push_str("\\\""),
+                        c => out.push(c),
+                    }
+                    j += 1;
+                }
+                out.push('"');
+                i = end;
+            }
+            ',' => {
+                let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+                if !matches!(next, Some('}') | Some(']')) {
+                    out.push(',');
+                }
+                i += 1;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Index just past the string opened by `quote` at `start`, or the end of the input
+fn string_end(chars: &[char], start: usize, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, &c) in chars.iter().enumerate().skip(start + 1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == quote => return i + 1,
+            _ => {}
+        }
+    }
+    chars.len()
+}
+
+/// For `#[serde(deserialize_with)]`: read `null` as the type's default, as models send
+/// `"themes": null` for an empty list
+pub fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// For `#[serde(deserialize_with)]`: a list of strings from a list, a lone string or `null`.
+/// Numbers in the list are kept as text and anything else is dropped.
+pub fn strings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::Array(items)) => items.into_iter()
+            .filter_map(|item| match item {
+                serde_json::Value::String(text) => Some(text),
+                serde_json::Value::Number(number) => Some(number.to_string()),
+                _ => None,
+            })
+            .collect(),
+        Some(serde_json::Value::String(text)) => vec![text],
+        _ => Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Intent {
+        intent: String,
+        #[serde(default)]
+        confidence: f32,
+    }
+
+    #[test]
+    fn test_candidates_prefer_fences_and_skip_unbalanced() {
+        let reply = "Options {a} and [b]:\n```json\n{\"x\": 1}\n```\nthen {\"y\": [2, \"}\"]} and {broken";
+        assert_eq!(candidates(reply), vec!["{\"x\": 1}", "{a}", "[b]", "{\"y\": [2, \"}\"]}"]);
+    }
+
+    #[test]
+    fn test_repair_trailing_commas_and_single_quotes() {
+        assert_eq!(repair("{'a': 'it\\'s \"x\"', \"b\": [1, 2,],}"), "{\"a\": \"it's \\\"x\\\"\", \"b\": [1, 2]}");
+        assert_eq!(repair("{\"keep\": \"a, }\"}"), "{\"keep\": \"a, }\"}");
+    }
+
+    #[test]
+    fn test_parse_picks_the_value_that_fits() {
+        let reply = "Here is {\"note\": \"not it\"} and the answer {\"intent\": \"todo_add\", \"confidence\": 0.9}";
+        let parsed: Intent = parse(reply).unwrap();
+        assert_eq!(parsed, Intent { intent: "todo_add".to_string(), confidence: 0.9 });
+
+        let err = parse::<Intent>("Sure! {\"confidence\": 0.4}").unwrap_err().to_string();
+        assert!(err.starts_with("missing field `intent`"), "{}", err);
+        assert!(err.contains("{\"confidence\": 0.4}"), "{}", err);
+        assert!(parse::<Intent>("I can't help with that.").unwrap_err().to_string().starts_with("no JSON found in: I can't"));
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::ai_conversation::{FunctionSchema, LLMClient, Message};
+use crate::config::RetrySettings;
+use crate::error::ArrowheadError;
+
+type Call<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Whether another attempt might succeed: rate limits, and overloaded, failing or
+/// unreachable servers. An exhausted quota won't come back by waiting.
+pub fn is_retryable(error: &anyhow::Error) -> bool {
+    ArrowheadError::of(error).is_some_and(ArrowheadError::is_transient)
+}
+
+/// Whether to give up on a provider and try the next one
+fn is_persistent(error: &anyhow::Error) -> bool {
+    is_retryable(error) || matches!(ArrowheadError::of(error), Some(ArrowheadError::QuotaExhausted { .. }))
+}
+
+/// Delay before retry `retry` (1 for the first): doubling from `initial_delay_ms`, capped at
+/// `max_delay_ms`, with the upper half randomized so clients don't retry in lockstep
+pub fn backoff_delay(settings: &RetrySettings, retry: u32) -> Duration {
+    let doubled = settings.initial_delay_ms.saturating_mul(1 << retry.saturating_sub(1).min(20));
+    let capped = doubled.min(settings.max_delay_ms);
+    let half = capped / 2;
+    let jitter = (Uuid::new_v4().as_u128() % (half as u128 + 1)) as u64;
+    Duration::from_millis(capped - half + jitter)
+}
+
+/// An `LLMClient` that retries transient failures with exponential backoff, then moves on
+/// to the fallback providers in order. The primary's error is returned once all of them fail.
+pub struct RetryingLLMClient {
+    providers: Vec<(String, Box<dyn LLMClient>)>,
+    settings: RetrySettings,
+}
+
+impl RetryingLLMClient {
+    pub fn new(provider: &str, client: Box<dyn LLMClient>, settings: RetrySettings) -> Self {
+        Self { providers: vec![(provider.to_string(), client)], settings }
+    }
+
+    pub fn with_fallback(mut self, provider: &str, client: Box<dyn LLMClient>) -> Self {
+        self.providers.push((provider.to_string(), client));
+        self
+    }
+
+    async fn run<T>(&self, call: impl for<'a> Fn(&'a dyn LLMClient) -> Call<'a, T> + Sync) -> Result<T> {
+        let max_attempts = self.settings.max_attempts.max(1);
+        let mut primary_error: Option<anyhow::Error> = None;
+        let mut fallback_errors = Vec::new();
+
+        for (index, (provider, client)) in self.providers.iter().enumerate() {
+            if index > 0 {
+                eprintln!("↪️ Switching to {} after {} kept failing", provider, self.providers[index - 1].0);
+            }
+            let mut attempt = 1;
+            let error = loop {
+                match call(client.as_ref()).await {
+                    Ok(value) => return Ok(value),
+                    Err(e) if attempt < max_attempts && is_retryable(&e) => {
+                        // A provider that says how long to wait is believed, up to the backoff cap
+                        let delay = match ArrowheadError::of(&e) {
+                            Some(ArrowheadError::RateLimited { retry_after: Some(wait), .. }) => (*wait).min(Duration::from_millis(self.settings.max_delay_ms)),
+                            _ => backoff_delay(&self.settings, attempt),
+                        };
+                        let status = ArrowheadError::status_of(&e).map_or_else(|| "connection failed".to_string(), |s| s.to_string());
+                        eprintln!("⏳ {} unavailable ({}), retrying in {:.1}s... (attempt {}/{})",
+                            provider, status, delay.as_secs_f32(), attempt + 1, max_attempts);
+                        tokio::time::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    Err(e) => break e,
+                }
+            };
+
+            let persistent = is_persistent(&error);
+            match &primary_error {
+                None => primary_error = Some(error),
+                Some(_) => fallback_errors.push(format!("{}: {}", provider, error)),
+            }
+            if !persistent {
+                break;
+            }
+        }
+
+        let error = primary_error.expect("at least one provider");
+        if fallback_errors.is_empty() {
+            return Err(error);
+        }
+        let summary = format!("{} (fallback {})", error, fallback_errors.join("; "));
+        Err(error.context(summary))
+    }
+}
+
+#[async_trait]
+impl LLMClient for RetryingLLMClient {
+    async fn send_message(&self, messages: Vec<Message>) -> Result<Message> {
+        self.run(|client| client.send_message(messages.clone())).await
+    }
+
+    /// Only starting the stream is retried; a stream that fails part-way just ends
+    async fn stream_response(&self, messages: Vec<Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+        self.run(|client| client.stream_response(messages.clone())).await
+    }
+
+    async fn function_calling(&self, messages: Vec<Message>, functions: Vec<FunctionSchema>) -> Result<Message> {
+        self.run(|client| client.function_calling(messages.clone(), functions.clone())).await
+    }
+
+    fn get_model_name(&self) -> String {
+        self.providers[0].1.get_model_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_conversation::MessageRole;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Answers with the scripted statuses in order (0 for success), then succeeds
+    struct ScriptedClient {
+        provider: &'static str,
+        statuses: Mutex<Vec<u16>>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    fn scripted(provider: &'static str, statuses: &[u16]) -> (Box<dyn LLMClient>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let client = ScriptedClient { provider, statuses: Mutex::new(statuses.to_vec()), calls: calls.clone() };
+        (Box::new(client), calls)
+    }
+
+    #[async_trait]
+    impl LLMClient for ScriptedClient {
+        async fn send_message(&self, _messages: Vec<Message>) -> Result<Message> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let status = {
+                let mut statuses = self.statuses.lock().unwrap();
+                if statuses.is_empty() { 0 } else { statuses.remove(0) }
+            };
+            match status {
+                0 => Ok(Message {
+                    id: Uuid::new_v4().to_string(),
+                    role: MessageRole::Assistant,
+                    content: format!("answer from {}", self.provider),
+                    timestamp: chrono::Utc::now(),
+                    function_call: None,
+                }),
+                402 => Err(ArrowheadError::QuotaExhausted { provider: self.provider.to_string() }.into()),
+                status => Err(ArrowheadError::from_status(self.provider, status, None, "overloaded".to_string()).into()),
+            }
+        }
+
+        async fn stream_response(&self, _messages: Vec<Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+            unimplemented!()
+        }
+
+        async fn function_calling(&self, messages: Vec<Message>, _functions: Vec<FunctionSchema>) -> Result<Message> {
+            self.send_message(messages).await
+        }
+
+        fn get_model_name(&self) -> String {
+            format!("{}-model", self.provider)
+        }
+    }
+
+    fn instant(max_attempts: u32) -> RetrySettings {
+        RetrySettings { max_attempts, initial_delay_ms: 0, max_delay_ms: 0 }
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() {
+        let (gemini, calls) = scripted("gemini", &[503, 429, 500]);
+        let client = RetryingLLMClient::new("gemini", gemini, instant(4));
+        let answer = client.send_message(vec![]).await.unwrap();
+        assert_eq!(answer.content, "answer from gemini");
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // Client errors aren't retried
+        let (gemini, calls) = scripted("gemini", &[400]);
+        let client = RetryingLLMClient::new("gemini", gemini, instant(4));
+        let error = client.send_message(vec![]).await.unwrap_err();
+        assert_eq!(ArrowheadError::status_of(&error), Some(400));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_retries_keep_the_status() {
+        let (gemini, calls) = scripted("gemini", &[503, 503, 503, 503]);
+        let client = RetryingLLMClient::new("gemini", gemini, instant(3));
+        let error = client.send_message(vec![]).await.unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(ArrowheadError::status_of(&error), Some(503));
+        assert!(error.to_string().contains("503"));
+    }
+
+    #[tokio::test]
+    async fn test_persistent_failures_fall_back_in_order() {
+        let (gemini, gemini_calls) = scripted("gemini", &[503, 503]);
+        let (openai, openai_calls) = scripted("openai", &[]);
+        let client = RetryingLLMClient::new("gemini", gemini, instant(2)).with_fallback("openai", openai);
+        assert_eq!(client.send_message(vec![]).await.unwrap().content, "answer from openai");
+        assert_eq!((gemini_calls.load(Ordering::SeqCst), openai_calls.load(Ordering::SeqCst)), (2, 1));
+        assert_eq!(client.get_model_name(), "gemini-model");
+
+        // An exhausted quota switches straight away; when everything fails the primary's error wins
+        let (gemini, gemini_calls) = scripted("gemini", &[402]);
+        let (openai, _) = scripted("openai", &[500, 500]);
+        let client = RetryingLLMClient::new("gemini", gemini, instant(2)).with_fallback("openai", openai);
+        let error = client.send_message(vec![]).await.unwrap_err();
+        assert_eq!(gemini_calls.load(Ordering::SeqCst), 1);
+        assert!(matches!(ArrowheadError::of(&error), Some(ArrowheadError::QuotaExhausted { .. })));
+        assert!(error.to_string().contains("openai: openai API request failed: 500"));
+    }
+
+    #[test]
+    fn test_backoff_doubles_with_jitter_and_cap() {
+        let settings = RetrySettings { max_attempts: 5, initial_delay_ms: 1000, max_delay_ms: 5000 };
+        for (retry, low, high) in [(1, 500, 1000), (2, 1000, 2000), (3, 2000, 4000), (4, 2500, 5000), (30, 2500, 5000)] {
+            let delay = backoff_delay(&settings, retry).as_millis() as u64;
+            assert!((low..=high).contains(&delay), "retry {} waited {}ms", retry, delay);
+        }
+    }
+}
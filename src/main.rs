@@ -1,46 +1,85 @@
-use arrowhead::cli::Cli;
+use arrowhead::cli::{Cli, OutputFormat};
 use arrowhead::obsidian_adapter::ObsidianAdapter;
-use arrowhead::router::route_command;
+use arrowhead::router::{parse_cli, report_error, route_command};
 use arrowhead::config::Config;
-use arrowhead::gemini_client::{GeminiClient, GeminiConfig};
-use arrowhead::openai_client::{OpenAIClient, OpenAIConfig};
-use arrowhead::ai_conversation::{Message, MessageRole, AIConversationEngine};
-use clap::Parser;
+use arrowhead::error::ArrowheadError;
+use arrowhead::ai_conversation::{Message, MessageRole, AIConversationEngine, UnavailableLLMClient};
+use arrowhead::intent_classifier::{describe_command, IntentClassifier, LlmReason, RouteDecision};
+use arrowhead::memory::{memory_path, print_memories, MemoryStore};
+use arrowhead::sessions::SessionStore;
+use arrowhead::slash_commands::{available_commands, run_vault_command, SlashCommand};
+use arrowhead::usage::UsagePurpose;
+use arrowhead::progress::Spinner;
+use std::collections::HashMap;
 use std::io::{self, Write};
+use std::time::Instant;
 use chrono::Utc;
 use uuid::Uuid;
 use serde_json;
 
 #[tokio::main]
 async fn main() {
-    // Initialize Obsidian Adapter
-    let api_key = std::env::var("OBSIDIAN_API_KEY").ok();
-    let base_url = std::env::var("OBSIDIAN_BASE_URL")
-        .ok()
-        .or_else(|| Some("https://127.0.0.1:27124".to_string()));
-    let adapter = ObsidianAdapter::new(base_url, api_key);
-
     // Parse CLI arguments
-    let cli_args = Cli::parse();
+    let cli_args = parse_cli();
+    if cli_args.offline {
+        arrowhead::config::force_offline();
+    }
+    if cli_args.quiet {
+        arrowhead::progress::force_quiet();
+    }
+    if let Some(name) = &cli_args.vault {
+        arrowhead::config::force_vault(name);
+    }
+    if let Some(name) = &cli_args.profile {
+        arrowhead::config::force_profile(name);
+    }
+    // A broken prompt override stops here, naming the prompt, unless the command is the
+    // one that fixes it
+    if !matches!(cli_args.command, Some(arrowhead::cli::Commands::Prompts(_))) {
+        if let Err(e) = arrowhead::prompts::load_overrides() {
+            std::process::exit(report_error(&e));
+        }
+    }
+
+    // A missing or broken profile stops here, unless the command is the one that fixes it
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) if !matches!(cli_args.command, Some(arrowhead::cli::Commands::Config(_))) => std::process::exit(report_error(&e)),
+        Err(_) => Config::load_profile(None).unwrap_or_default(),
+    };
+
+    // Initialize Obsidian Adapter: the chosen vault, or else the one in the environment
+    let adapter = match config.general.vault.as_deref().map(|name| ObsidianAdapter::for_vault(&config, name)) {
+        Some(Ok(adapter)) => adapter,
+        Some(Err(e)) if cli_args.vault.is_some() => std::process::exit(report_error(&e)),
+        _ => {
+            let api_key = std::env::var("OBSIDIAN_API_KEY").ok();
+            let base_url = std::env::var("OBSIDIAN_BASE_URL")
+                .ok()
+                .or_else(|| Some("https://127.0.0.1:27124".to_string()));
+            ObsidianAdapter::new(base_url, api_key)
+        }
+    };
     
     // Check if a specific command was provided
     if cli_args.command.is_some() {
         // Traditional CLI mode - execute the specific command
         if let Err(e) = route_command(cli_args, &adapter).await {
-            eprintln!("Error: {:?}", e);
-            std::process::exit(1);
+            std::process::exit(report_error(&e));
         }
     } else {
         // No command provided - start interactive chat mode
-        if let Err(e) = run_interactive_chat_mode(&adapter).await {
+        let resume = if cli_args.resume { Some(None) } else { cli_args.session.map(Some) };
+        if let Err(e) = run_interactive_chat_mode(&adapter, resume).await {
             eprintln!("Error in interactive mode: {:?}", e);
             std::process::exit(1);
         }
     }
 }
 
-/// Run the application in interactive chat mode (similar to Claude Code)
-async fn run_interactive_chat_mode(_adapter: &ObsidianAdapter) -> Result<(), Box<dyn std::error::Error>> {
+/// Run the application in interactive chat mode (similar to Claude Code). `resume` continues
+/// a saved conversation: the given session id, or the latest one for `Some(None)`.
+async fn run_interactive_chat_mode(adapter: &ObsidianAdapter, resume: Option<Option<String>>) -> Result<(), Box<dyn std::error::Error>> {
     println!("🚀 Welcome to Arrowhead!");
     println!("I'm your AI-powered productivity assistant. Ask me anything about your tasks, goals, and notes.");
     println!("You can also use traditional commands like 'arrowhead todo add' in another terminal.");
@@ -49,18 +88,49 @@ async fn run_interactive_chat_mode(_adapter: &ObsidianAdapter) -> Result<(), Box
     // Load configuration
     let config = Config::load().unwrap_or_default();
     
-    // Create AI client for conversation
-    let llm_client = match create_llm_client(&config) {
-        Ok(client) => client,
+    // Create AI client for conversation. Without one, local commands still work and the
+    // reason is shown when a request needs the AI.
+    let (llm_client, llm_unavailable) = match create_llm_client(&config) {
+        Ok(client) => (client, None),
         Err(e) => {
-            show_setup_guide(&e);
-            std::process::exit(1);
+            if config.general.offline {
+                println!("📴 Offline mode: AI chat is off. Requests like \"list my todos\" still run locally.\n");
+            } else {
+                println!("⚠️  No AI provider is set up; requests like \"list my todos\" still run locally. Type 'setup' for help.\n");
+            }
+            let client: Box<dyn arrowhead::ai_conversation::LLMClient> = Box::new(UnavailableLLMClient { reason: e.to_string() });
+            (client, Some(e))
         }
     };
     
     // Create AI conversation engine
-    let mut ai_engine = AIConversationEngine::new(llm_client);
-    
+    let mut ai_engine = AIConversationEngine::new(llm_client)
+        .with_tool_result_settings(config.tool_results.clone())
+        .with_context_settings(config.context.clone());
+    let sessions = SessionStore::from_settings(&config.sessions);
+    if config.sessions.enabled {
+        ai_engine = ai_engine.with_session_store(sessions.clone());
+    }
+
+    // A resumed conversation already has its system message and any memories
+    let resumed = match resume {
+        Some(id) => {
+            let session = match id {
+                Some(id) => sessions.find(&id)?,
+                None => sessions.latest()?.ok_or("No saved sessions to resume; start a conversation first")?,
+            };
+            let (id, saved) = (session.id.clone(), session.messages.len());
+            let trimmed = ai_engine.resume(session);
+            print!("🔁 Resumed session {} ({} message(s)", id, saved);
+            if trimmed > 0 {
+                print!(", {} oldest trimmed to fit the {}-token context", trimmed, ai_engine.context.max_context_tokens);
+            }
+            println!(")\n");
+            true
+        }
+        None => false,
+    };
+
     // Add system message to provide context
     let system_message = Message {
         id: Uuid::new_v4().to_string(),
@@ -69,12 +139,29 @@ async fn run_interactive_chat_mode(_adapter: &ObsidianAdapter) -> Result<(), Box
         timestamp: Utc::now(),
         function_call: None,
     };
-    ai_engine.context.add_message(system_message);
+    if !resumed {
+        ai_engine.context.add_message(system_message);
+    }
+
+    // Simple, unambiguous requests skip the LLM entirely
+    let classifier = IntentClassifier::new();
+
+    // Facts remembered from earlier conversations, injected before the first AI request
+    let memory_file = memory_path(&config.memory);
+    let mut memories = if config.memory.enabled {
+        MemoryStore::load(&memory_file).unwrap_or_else(|e| {
+            eprintln!("Warning: could not load memories: {}", e);
+            MemoryStore::default()
+        })
+    } else {
+        MemoryStore::default()
+    };
+    let mut memories_injected = resumed;
     
     // Main interaction loop
     loop {
-        // Prompt user for input (Claude Code style)
-        print!("💬 ");
+        // Prompt user for input (Claude Code style), with how full the context is
+        print!("💬 {}% ", ai_engine.context.usage_percent());
         io::stdout().flush()?;
         
         let mut input = String::new();
@@ -82,7 +169,7 @@ async fn run_interactive_chat_mode(_adapter: &ObsidianAdapter) -> Result<(), Box
         
         // Handle EOF (Ctrl+D or piped input ending)
         if bytes_read == 0 {
-            println!("\n👋 Goodbye!");
+            println!();
             break;
         }
         
@@ -90,10 +177,99 @@ async fn run_interactive_chat_mode(_adapter: &ObsidianAdapter) -> Result<(), Box
         
         // Check for exit commands
         if input.eq_ignore_ascii_case("quit") || input.eq_ignore_ascii_case("exit") {
-            println!("👋 Goodbye!");
             break;
         }
+
+        if let Some(fact) = input.strip_prefix("/remember ") {
+            if memories.remember(fact, &ai_engine.conversation_id, &config.memory) {
+                println!("🧠 Remembered: {}", memories.entries.last().map_or("", |e| e.fact.as_str()));
+            } else {
+                println!("🧠 Updated an existing memory");
+            }
+            if let Err(e) = memories.save(&memory_file) {
+                println!("❌ Could not save memories: {}", e);
+            }
+            continue;
+        }
+
+        if let Some(rest) = input.strip_prefix("/memories") {
+            let mut parts = rest.trim().splitn(3, ' ');
+            let result = match (parts.next(), parts.next(), parts.next()) {
+                (Some(""), _, _) => {
+                    print_memories(&memories);
+                    Ok(())
+                }
+                (Some("delete"), Some(id), None) => memories.delete(id).map(|removed| println!("🗑️ Forgot: {}", removed.fact)),
+                (Some("edit"), Some(id), Some(fact)) => memories.edit(id, fact).map(|_| println!("✅ Memory {} updated", id)),
+                _ => {
+                    println!("Usage: /memories, /memories edit <id> <fact>, /memories delete <id>");
+                    continue;
+                }
+            };
+            match result.and_then(|_| memories.save(&memory_file)) {
+                Ok(()) => {}
+                Err(e) => println!("❌ {}", e),
+            }
+            continue;
+        }
         
+        if input == "/context" {
+            let context = &ai_engine.context;
+            println!("📊 Context: {} message(s), ~{} / {} tokens ({}%)", context.get_message_count(), context.token_count(), context.max_context_tokens, context.usage_percent());
+            print!("{}", ai_engine.tool_results.render());
+            continue;
+        }
+
+        if let Some(target) = input.strip_prefix("/expand") {
+            let stored = match target.trim() {
+                "" | "last" => ai_engine.tool_results.last(),
+                id => ai_engine.tool_results.get(id),
+            };
+            match stored {
+                Some(result) => println!("{} ({}, {} tokens in full)\n{}", result.id, result.tool, result.full_tokens,
+                    serde_json::to_string_pretty(&result.full).unwrap_or_else(|_| result.full.to_string())),
+                None => println!("No such tool result. /context lists them."),
+            }
+            continue;
+        }
+
+        if let Some(command) = SlashCommand::parse(input) {
+            match command {
+                SlashCommand::Clear => {
+                    let dropped = ai_engine.reset_context();
+                    println!("🧹 Cleared {} message(s); the conversation starts fresh", dropped);
+                }
+                SlashCommand::Model(model) => {
+                    let mut session_config = config.clone();
+                    session_config.set_llm_model(&model);
+                    match create_llm_client(&session_config) {
+                        Ok(client) => {
+                            ai_engine.set_llm_client(client);
+                            println!("🔀 Using {} for the rest of this session", ai_engine.get_model_name());
+                        }
+                        Err(e) => println!("❌ Could not switch to {}: {}", model, e),
+                    }
+                }
+                SlashCommand::Save(name) => match sessions.save(&ai_engine.snapshot(&name)) {
+                    Ok(()) => println!("💾 Saved as '{}'; resume with `arrowhead --session {}`", name, name),
+                    Err(e) => println!("❌ {}", e),
+                },
+                SlashCommand::Usage(usage) => println!("Usage: {}", usage),
+                SlashCommand::Unknown(name) => println!("Unknown command /{}. Available: {}", name, available_commands()),
+                command => match run_vault_command(&command, adapter).await {
+                    Ok(Some((tool, result))) => {
+                        let mut arguments = HashMap::new();
+                        arguments.insert("input".to_string(), serde_json::json!(input));
+                        ai_engine.add_tool_result(tool, arguments, result);
+                    }
+                    Ok(None) => {}
+                    Err(e) => println!("❌ {}", e),
+                },
+            }
+            println!();
+            continue;
+        }
+
         // Handle help command
         if input.eq_ignore_ascii_case("help") {
             show_help();
@@ -102,6 +278,10 @@ async fn run_interactive_chat_mode(_adapter: &ObsidianAdapter) -> Result<(), Box
         
         // Handle setup command
         if input.eq_ignore_ascii_case("setup") {
+            if let Some(e) = &llm_unavailable {
+                show_setup_guide(e);
+                continue;
+            }
             println!("🔧 Configuration Setup:");
             println!("Run: arrowhead config --init");
             println!("Then set: export GEMINI_API_KEY=\"your_key\"");
@@ -113,17 +293,47 @@ async fn run_interactive_chat_mode(_adapter: &ObsidianAdapter) -> Result<(), Box
             continue;
         }
         
-        // Show loading spinner
-        print!("⏳ ");
-        io::stdout().flush()?;
-        
-        // Send directly to LLM
-        match ai_engine.send_message(input.to_string()).await {
+        let started = Instant::now();
+        let (llm_input, reason) = match classifier.classify(input, Utc::now().date_naive()) {
+            RouteDecision::Direct(route) => {
+                let command_line = describe_command(&route.command);
+                let result = route_command(Cli { command: Some(route.command), format: OutputFormat::Text, summary: false, detail: false, resume: false, session: None, offline: false, quiet: false, vault: None, profile: None }, adapter).await;
+                if let Err(e) = result {
+                    println!("❌ {}", e);
+                }
+                println!("⚡ Handled locally as `arrowhead {}` in {:.1?} (no AI call; prefix with /llm to ask the AI instead)", command_line, started.elapsed());
+                println!();
+                continue;
+            }
+            RouteDecision::Llm { input, reason } => (input, reason),
+        };
+        if llm_input.is_empty() {
+            continue;
+        }
+        if let Some(e) = &llm_unavailable {
+            if config.general.offline {
+                println!("📴 {}", e);
+                println!("💡 Rephrase as a command the local shortcuts understand, or run e.g. `arrowhead todo list`.\n");
+            } else {
+                show_setup_guide(e);
+            }
+            continue;
+        }
+
+        if config.memory.enabled && !memories_injected {
+            if let Some(message) = memories.system_message(&llm_input, config.memory.token_budget) {
+                ai_engine.context.add_message(message);
+            }
+            memories_injected = true;
+        }
+
+        // Cleared before anything is printed, and on drop if the request fails early
+        let spinner = Spinner::start("Thinking…");
+        let result = ai_engine.send_message(llm_input).await;
+        spinner.finish();
+
+        match result {
             Ok(response) => {
-                // Clear the loading spinner line
-                print!("\r   \r");
-                io::stdout().flush()?;
-                
                 // Try to parse and clean up the response if it's JSON
                 let clean_response = if response.starts_with('{') && response.ends_with('}') {
                     match serde_json::from_str::<serde_json::Value>(&response) {
@@ -144,28 +354,53 @@ async fn run_interactive_chat_mode(_adapter: &ObsidianAdapter) -> Result<(), Box
                 };
                 
                 println!("{}", clean_response);
+                let note = match reason {
+                    LlmReason::Forced => "forced with /llm".to_string(),
+                    LlmReason::Destructive(word) => format!("'{}' requests are never run automatically", word),
+                    LlmReason::LowConfidence { intent, .. } => format!("not sure enough it meant {}", intent),
+                    LlmReason::NoMatch => "no local match".to_string(),
+                };
+                println!("🤖 Answered by AI in {:.1?} ({})", started.elapsed(), note);
                 println!(); // Add blank line for readability
             }
             Err(e) => {
-                // Clear the loading spinner line
-                print!("\r   \r");
-                io::stdout().flush()?;
-                
-                let error_msg = e.to_string();
-                if error_msg.contains("503") || error_msg.contains("Service Unavailable") || error_msg.contains("overloaded") {
-                    println!("🔄 The AI service is temporarily busy. This usually resolves in a few minutes.");
-                    println!("💡 Tips while waiting:");
-                    println!("   • Try again in 30 seconds");
-                    println!("   • Use traditional commands: `arrowhead todo list`");
-                    println!("   • Check service status: The API may be experiencing high demand");
-                } else {
-                    println!("❌ Error processing your request: {}", e);
-                    println!("Please try rephrasing your request or type 'help' for assistance.");
+                match e.arrowhead_error() {
+                    Some(error @ (ArrowheadError::ServiceUnavailable { .. } | ArrowheadError::RateLimited { .. })) => {
+                        println!("🔄 The AI service is temporarily busy. This usually resolves in a few minutes.");
+                        println!("💡 Tips while waiting:");
+                        match error {
+                            ArrowheadError::RateLimited { retry_after: Some(wait), .. } => println!("   • Try again in {} seconds", wait.as_secs().max(1)),
+                            _ => println!("   • Try again in 30 seconds"),
+                        }
+                        println!("   • Use traditional commands: `arrowhead todo list`");
+                        println!("   • Check service status: The API may be experiencing high demand");
+                    }
+                    Some(error) => {
+                        println!("❌ {}", e);
+                        if let Some(hint) = error.hint() {
+                            println!("💡 {}", hint);
+                        }
+                    }
+                    None => {
+                        println!("❌ Error processing your request: {}", e);
+                        println!("Please try rephrasing your request or type 'help' for assistance.");
+                    }
                 }
                 println!();
             }
         }
     }
+
+    if config.memory.enabled {
+        let learned = memories.remember_session(&ai_engine.conversation_id, &ai_engine.context.message_history, &config.memory);
+        if learned > 0 {
+            match memories.save(&memory_file) {
+                Ok(()) => println!("🧠 Remembered {} new fact(s) for next time (see /memories)", learned),
+                Err(e) => eprintln!("Warning: could not save memories: {}", e),
+            }
+        }
+    }
+    println!("👋 Goodbye!");
     
     Ok(())
 }
@@ -190,50 +425,25 @@ fn show_help() {
     println!("• `help` - Show this help");
     println!("• `setup` - Show configuration setup guide");
     println!("• `quit` or `exit` - Exit interactive mode");
+    println!("• `/llm <request>` - Send a request straight to the AI, skipping the instant local shortcuts");
+    println!("• `/search <query>` - Search your notes; follow-up questions can refer to the results");
+    println!("• `/note <path>` - Show a note, e.g. `/note Projects/Roadmap.md`");
+    println!("• `/todo add <text>`, `/todo list` - Add or list todos without asking the AI");
+    println!("• `/clear` - Start the conversation over, keeping the system message");
+    println!("• `/model <name>` - Use another model of the current provider for this session");
+    println!("• `/save <name>` - Save this conversation to resume with `arrowhead --session <name>`");
+    println!("• `/remember <fact>` - Remember something for future conversations");
+    println!("• `/memories` - List remembered facts (`/memories edit <id> <fact>`, `/memories delete <id>`)");
+    println!("• `/context` - Show context usage and the tokens saved by shortening tool results");
+    println!("• `/expand last` - Show the full version of the last tool result (or `/expand <id>`)");
+    println!("• `arrowhead --resume` - Pick up the last conversation later (`arrowhead sessions list`, `arrowhead --session <id>`)");
     println!("• Traditional CLI: `arrowhead todo list`, `arrowhead goal add`, etc.\n");
     println!("Just ask me naturally what you'd like to do - I'll figure out the right command!\n");
 }
 
 /// Create LLM client based on configuration
 fn create_llm_client(config: &Config) -> Result<Box<dyn arrowhead::ai_conversation::LLMClient>, Box<dyn std::error::Error>> {
-    // Validate configuration
-    config.validate()?;
-    
-    match config.llm.provider.as_str() {
-        "gemini" => {
-            let api_key = config.get_llm_api_key()
-                .ok_or("Missing Gemini API key")?;
-            
-            let gemini_config = GeminiConfig {
-                api_key,
-                model: config.get_llm_model(),
-                temperature: Some(config.get_llm_temperature()),
-                max_tokens: Some(config.get_llm_max_tokens()),
-                ..Default::default()
-            };
-            
-            let client = GeminiClient::new(gemini_config)?;
-            Ok(Box::new(client))
-        }
-        "openai" => {
-            let api_key = config.get_llm_api_key()
-                .ok_or("Missing OpenAI API key")?;
-            
-            let openai_config = OpenAIConfig {
-                api_key,
-                model: config.get_llm_model(),
-                temperature: Some(config.get_llm_temperature()),
-                max_tokens: Some(config.get_llm_max_tokens()),
-                ..Default::default()
-            };
-            
-            let client = OpenAIClient::new(openai_config)?;
-            Ok(Box::new(client))
-        }
-        _ => {
-            Err(format!("Unsupported LLM provider: {}", config.llm.provider).into())
-        }
-    }
+    Ok(arrowhead::ai_conversation::create_llm_client(config, UsagePurpose::Chat)?)
 }
 
 /// Show setup guide when API key is not configured
@@ -260,6 +470,10 @@ fn show_setup_guide(error: &Box<dyn std::error::Error>) {
     println!("   arrowhead config --init");
     println!("   # Then edit ~/.config/arrowhead/config.toml");
     println!();
+    println!("   Option C - Run fully locally with Ollama (no API key):");
+    println!("   → Install from https://ollama.com, then: ollama pull llama3.1");
+    println!("   arrowhead config set llm.provider ollama");
+    println!();
     println!("3️⃣  Test the setup:");
     println!("   arrowhead config --show");
     println!();
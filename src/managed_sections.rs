@@ -0,0 +1,702 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::ops::Range;
+
+use crate::cli::{SectionsAction, SectionsArgs};
+use crate::config::Config;
+use crate::health::load_notes_in;
+use crate::markdown::{replace_section, Markdown};
+use crate::note_skeleton::stable_hash;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::report::{DetailLevel, Report, ReportView};
+use crate::vault_transaction::{TransactionReport, VaultStore, VaultTransaction, WriteConflict};
+
+/// Start of every marker Arrowhead writes into notes
+pub const MARKER_PREFIX: &str = "<!-- arrowhead:";
+const BEGIN_PREFIX: &str = "<!-- arrowhead:begin ";
+const END_PREFIX: &str = "<!-- arrowhead:end ";
+const MARKER_SUFFIX: &str = "-->";
+
+/// Owners in the order their sections appear in a note, top to bottom. Sections of
+/// owners not listed here go above all of them; the changelog always stays last.
+const SECTION_ORDER: &[&str] = &["split", "related", "backlinks", "changelog"];
+
+/// Times [`update_sections`] re-reads a note after another write got there first
+const MAX_CONFLICT_RETRIES: usize = 3;
+
+/// A region of a note maintained by one Arrowhead feature:
+///
+/// ```text
+/// ## Referenced by
+/// <!-- arrowhead:begin owner=backlinks id=backlinks hash=6f1c0e5a2b9d4e37 -->
+/// - [[Kickoff]] (references): "Kickoff builds on Roadmap."
+/// <!-- arrowhead:end id=backlinks -->
+/// ```
+///
+/// The heading is optional. `hash` is the hash of the lines between the markers when the
+/// owner last wrote them. Older notes use a single `<!-- arrowhead:<name> -->` marker under
+/// a heading with no end marker; those sections run to the next managed section or the end
+/// of the note and are rewritten in the current form on their next update.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ManagedSection {
+    pub owner: String,
+    pub id: String,
+    pub heading: Option<String>,
+    pub hash: Option<String>,
+    /// Other `key=value` pairs in the begin marker, kept for the owner
+    pub attributes: BTreeMap<String, String>,
+    /// Heading through end marker
+    #[serde(skip)]
+    pub range: Range<usize>,
+    /// The lines between the markers
+    #[serde(skip)]
+    pub body: Range<usize>,
+    /// 1-based line of the begin marker
+    pub line: usize,
+    pub legacy: bool,
+}
+
+impl ManagedSection {
+    /// Whether the body no longer matches the hash its owner recorded
+    pub fn is_edited(&self, content: &str) -> bool {
+        self.hash.as_deref().is_some_and(|hash| hash != content_hash(&content[self.body.clone()]))
+    }
+}
+
+/// Something wrong with the markers of a note, found by [`scan`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MarkerProblem {
+    /// A begin marker without its end marker; the section is taken to run to the next
+    /// managed section or the end of the note
+    UnclosedBegin { id: String, line: usize },
+    /// An end marker without a matching begin marker
+    StrayEnd { id: String, line: usize },
+    /// A second section with an id already used earlier in the note
+    DuplicateId { id: String, line: usize },
+    /// The section was edited by hand; its owner's next update replaces the edit
+    EditedByHand { id: String, line: usize },
+}
+
+impl MarkerProblem {
+    pub fn id(&self) -> &str {
+        match self {
+            Self::UnclosedBegin { id, .. } | Self::StrayEnd { id, .. } | Self::DuplicateId { id, .. } | Self::EditedByHand { id, .. } => id,
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        match self {
+            Self::UnclosedBegin { line, .. } | Self::StrayEnd { line, .. } | Self::DuplicateId { line, .. } | Self::EditedByHand { line, .. } => *line,
+        }
+    }
+
+    /// Damaged markers block updates to the section until `arrowhead sections repair`
+    /// fixes them; hand edits are only reported
+    pub fn is_damage(&self) -> bool {
+        !matches!(self, Self::EditedByHand { .. })
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::UnclosedBegin { id, line } => format!("line {}: section '{}' has no end marker", line, id),
+            Self::StrayEnd { id, line } => format!("line {}: end marker for '{}' without a begin marker", line, id),
+            Self::DuplicateId { id, line } => format!("line {}: section '{}' appears more than once", line, id),
+            Self::EditedByHand { id, line } => format!("line {}: section '{}' was edited by hand and will be overwritten", line, id),
+        }
+    }
+}
+
+/// Why a section could not be written
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SectionError {
+    #[error("section '{id}' belongs to {owner}, not {requested_by}")]
+    OwnedByOther { id: String, owner: String, requested_by: String },
+    #[error("section '{id}' has damaged markers ({problem}); run `arrowhead sections repair`")]
+    Damaged { id: String, problem: String },
+    #[error("invalid section name '{0}': use letters, digits, '-' and '_'")]
+    InvalidName(String),
+}
+
+/// The managed sections of a note and the problems with their markers
+#[derive(Debug, Default)]
+pub struct SectionScan {
+    pub sections: Vec<ManagedSection>,
+    pub problems: Vec<MarkerProblem>,
+}
+
+pub fn content_hash(body: &str) -> String {
+    format!("{:016x}", stable_hash(body))
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+fn is_heading(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    (1..=6).contains(&level) && trimmed[level..].starts_with(' ')
+}
+
+fn parse_attributes(text: &str) -> BTreeMap<String, String> {
+    text.split_whitespace()
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+enum Marker {
+    Begin(BTreeMap<String, String>),
+    End(String),
+    Legacy(String, BTreeMap<String, String>),
+}
+
+fn parse_marker(line: &str) -> Option<Marker> {
+    let line = line.trim();
+    let inner = line.strip_suffix(MARKER_SUFFIX)?;
+    if let Some(rest) = inner.strip_prefix(BEGIN_PREFIX) {
+        return Some(Marker::Begin(parse_attributes(rest)));
+    }
+    if let Some(rest) = inner.strip_prefix(END_PREFIX) {
+        return Some(Marker::End(parse_attributes(rest).remove("id").unwrap_or_default()));
+    }
+    let rest = inner.strip_prefix(MARKER_PREFIX)?;
+    let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '-').collect();
+    let name = name.trim_end_matches('-').to_string();
+    (!name.is_empty()).then(|| Marker::Legacy(name.clone(), parse_attributes(&rest[name.len()..])))
+}
+
+/// A section whose end hasn't been seen yet
+struct Open {
+    section: ManagedSection,
+}
+
+impl Open {
+    /// End the section where the next one starts, or at the end of the note
+    fn close_at(mut self, at: usize, problems: &mut Vec<MarkerProblem>) -> ManagedSection {
+        if !self.section.legacy {
+            problems.push(MarkerProblem::UnclosedBegin { id: self.section.id.clone(), line: self.section.line });
+        }
+        self.section.range.end = at;
+        self.section.body.end = at;
+        self.section
+    }
+}
+
+/// Find the managed sections of a note. Markers inside frontmatter or code, such as an
+/// example in a fenced block, are text like any other.
+pub fn scan(content: &str) -> SectionScan {
+    let doc = Markdown::parse(content);
+    let mut scan = SectionScan::default();
+    let mut open: Option<Open> = None;
+    let mut offset = 0;
+    let mut previous: Option<(usize, &str)> = None;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        let heading = previous.filter(|(_, text)| is_heading(text));
+        let start = heading.map_or(offset, |(at, _)| at);
+        let next = offset + line.len();
+        let marker = if doc.in_code(offset + line.len() - line.trim_start().len()) { None } else { parse_marker(line) };
+        let begun = match marker {
+            Some(Marker::Begin(mut attributes)) => {
+                let owner = attributes.remove("owner").unwrap_or_default();
+                let id = attributes.remove("id").unwrap_or_default();
+                let hash = attributes.remove("hash");
+                Some((owner, id, hash, attributes, false))
+            }
+            Some(Marker::Legacy(name, attributes)) if heading.is_some() => Some((name.clone(), name, None, attributes, true)),
+            Some(Marker::End(id)) => {
+                match open.take() {
+                    Some(mut current) if !current.section.legacy && current.section.id == id => {
+                        current.section.range.end = next;
+                        current.section.body.end = offset;
+                        scan.sections.push(current.section);
+                    }
+                    other => {
+                        open = other;
+                        scan.problems.push(MarkerProblem::StrayEnd { id, line: index + 1 });
+                    }
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some((owner, id, hash, attributes, legacy)) = begun {
+            if let Some(current) = open.take() {
+                scan.sections.push(current.close_at(start, &mut scan.problems));
+            }
+            open = Some(Open {
+                section: ManagedSection {
+                    owner,
+                    id,
+                    heading: heading.map(|(_, text)| text.trim().to_string()),
+                    hash,
+                    attributes,
+                    range: start..content.len(),
+                    body: next..content.len(),
+                    line: index + 1,
+                    legacy,
+                },
+            });
+        }
+        previous = Some((offset, line));
+        offset = next;
+    }
+    if let Some(current) = open {
+        scan.sections.push(current.close_at(content.len(), &mut scan.problems));
+    }
+
+    let unclosed: HashSet<usize> = scan.problems.iter().map(MarkerProblem::line).collect();
+    let mut seen = HashSet::new();
+    for section in &scan.sections {
+        if !seen.insert(section.id.as_str()) {
+            scan.problems.push(MarkerProblem::DuplicateId { id: section.id.clone(), line: section.line });
+        } else if !unclosed.contains(&section.line) && section.is_edited(content) {
+            scan.problems.push(MarkerProblem::EditedByHand { id: section.id.clone(), line: section.line });
+        }
+    }
+    scan.problems.sort_by_key(MarkerProblem::line);
+    scan
+}
+
+pub fn managed_sections(content: &str) -> Vec<ManagedSection> {
+    scan(content).sections
+}
+
+/// `content` without its managed sections, except those whose owner is named in `keep`
+pub fn without_managed_sections(content: &str, keep: &[String]) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut at = 0;
+    for section in managed_sections(content).into_iter().filter(|s| !keep.contains(&s.owner)) {
+        out.push_str(&content[at..section.range.start]);
+        at = section.range.end;
+    }
+    out.push_str(&content[at..]);
+    out
+}
+
+/// A section for [`upsert`] to write
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionWrite {
+    pub owner: String,
+    pub id: String,
+    pub heading: Option<String>,
+    pub body: String,
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl SectionWrite {
+    pub fn new(owner: &str, id: &str, body: &str) -> Self {
+        Self {
+            owner: owner.to_string(),
+            id: id.to_string(),
+            heading: None,
+            body: body.to_string(),
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    pub fn heading(mut self, heading: &str) -> Self {
+        self.heading = Some(heading.to_string());
+        self
+    }
+
+    /// Extra `key=value` kept in the begin marker, e.g. the hash of the section's inputs
+    pub fn attribute(mut self, key: &str, value: &str) -> Self {
+        self.attributes.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn render(&self) -> String {
+        let mut body = self.body.clone();
+        if !body.is_empty() && !body.ends_with('\n') {
+            body.push('\n');
+        }
+        let mut out = String::new();
+        if let Some(heading) = &self.heading {
+            out.push_str(heading);
+            out.push('\n');
+        }
+        out.push_str(&format!("{}owner={} id={} hash={}", BEGIN_PREFIX, self.owner, self.id, content_hash(&body)));
+        for (key, value) in &self.attributes {
+            out.push_str(&format!(" {}={}", key, value));
+        }
+        out.push_str(&format!(" {}\n{}{}id={} {}\n", MARKER_SUFFIX, body, END_PREFIX, self.id, MARKER_SUFFIX));
+        out
+    }
+
+    fn validate(&self) -> Result<(), SectionError> {
+        for name in [&self.owner, &self.id] {
+            if !is_name(name) {
+                return Err(SectionError::InvalidName(name.clone()));
+            }
+        }
+        match self.attributes.iter().find(|(key, value)| !is_name(key) || value.is_empty() || value.contains(char::is_whitespace) || value.contains(MARKER_SUFFIX)) {
+            Some((key, _)) => Err(SectionError::InvalidName(key.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+fn rank(owner: &str) -> usize {
+    SECTION_ORDER.iter().position(|o| *o == owner).map_or(0, |i| i + 1)
+}
+
+/// The section `id` of `scan`, checked to belong to `owner` and to have intact markers
+fn owned<'a>(scan: &'a SectionScan, owner: &str, id: &str) -> Result<Option<&'a ManagedSection>, SectionError> {
+    if let Some(problem) = scan.problems.iter().find(|p| p.id() == id && p.is_damage()) {
+        return Err(SectionError::Damaged { id: id.to_string(), problem: problem.describe() });
+    }
+    match scan.sections.iter().find(|s| s.id == id) {
+        Some(section) if section.owner != owner => Err(SectionError::OwnedByOther {
+            id: id.to_string(),
+            owner: section.owner.clone(),
+            requested_by: owner.to_string(),
+        }),
+        section => Ok(section),
+    }
+}
+
+/// Join two parts of a note with one blank line between them
+fn join(before: &str, after: &str) -> String {
+    let before = before.trim_end();
+    let after = after.trim_start_matches('\n');
+    match (before.is_empty(), after.is_empty()) {
+        (true, _) => after.to_string(),
+        (false, true) => format!("{}\n", before),
+        (false, false) => format!("{}\n\n{}", before, after),
+    }
+}
+
+/// Replace the section in place, or add it: above the first managed section that comes
+/// after it in [`SECTION_ORDER`], otherwise at the end of the note
+pub fn upsert(content: &str, section: &SectionWrite) -> Result<String, SectionError> {
+    section.validate()?;
+    let scan = scan(content);
+    let rendered = section.render();
+    if let Some(existing) = owned(&scan, &section.owner, &section.id)? {
+        // A legacy section runs up to the next one, including the blank line before it
+        let rendered = if existing.legacy && existing.range.end < content.len() { format!("{}\n", rendered) } else { rendered };
+        return Ok(replace_section(content, existing.range.clone(), &rendered));
+    }
+    let insert_at = scan.sections.iter()
+        .find(|s| rank(&s.owner) > rank(&section.owner))
+        .map_or(content.len(), |s| s.range.start);
+    let (before, after) = content.split_at(insert_at);
+    Ok(join(&join(before, &rendered), after))
+}
+
+/// Remove the section if the note has it
+pub fn remove(content: &str, owner: &str, id: &str) -> Result<String, SectionError> {
+    let scan = scan(content);
+    Ok(match owned(&scan, owner, id)? {
+        Some(existing) => join(&content[..existing.range.start], &content[existing.range.end..]),
+        None => content.to_string(),
+    })
+}
+
+/// Fix damaged markers: close unclosed sections where they are taken to end, drop stray
+/// end markers and all but the first section with a repeated id. Returns the fixed note
+/// and the problems that were fixed.
+pub fn repair(content: &str) -> (String, Vec<MarkerProblem>) {
+    let scan = scan(content);
+    let line_range = |line: usize| {
+        let start: usize = content.split_inclusive('\n').take(line - 1).map(str::len).sum();
+        start..start + content[start..].split_inclusive('\n').next().map_or(0, str::len)
+    };
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    let mut fixed = Vec::new();
+    for problem in scan.problems.iter().filter(|p| p.is_damage()) {
+        let edit = match problem {
+            MarkerProblem::UnclosedBegin { id, line } => {
+                let Some(section) = scan.sections.iter().find(|s| s.line == *line) else { continue };
+                let end = section.range.start + content[section.range.clone()].trim_end().len();
+                (end..end, format!("\n{}id={} {}", END_PREFIX, id, MARKER_SUFFIX))
+            }
+            MarkerProblem::StrayEnd { line, .. } => (line_range(*line), String::new()),
+            MarkerProblem::DuplicateId { line, .. } => {
+                let Some(section) = scan.sections.iter().find(|s| s.line == *line) else { continue };
+                (section.range.clone(), String::new())
+            }
+            MarkerProblem::EditedByHand { .. } => continue,
+        };
+        edits.push(edit);
+        fixed.push(problem.clone());
+    }
+
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    let mut out = content.to_string();
+    let mut limit = usize::MAX;
+    for (range, replacement) in edits {
+        // A stray marker inside a dropped duplicate goes with it
+        if range.end > limit {
+            continue;
+        }
+        out.replace_range(range.clone(), &replacement);
+        limit = range.start;
+    }
+    (out, fixed)
+}
+
+/// A change to one managed section
+#[derive(Debug, Clone)]
+pub enum SectionEdit {
+    Upsert(SectionWrite),
+    Remove { owner: String, id: String },
+}
+
+impl SectionEdit {
+    pub fn apply(&self, content: &str) -> Result<String, SectionError> {
+        match self {
+            Self::Upsert(section) => upsert(content, section),
+            Self::Remove { owner, id } => remove(content, owner, id),
+        }
+    }
+}
+
+/// Apply `edits` to the note at `path` and write it back only if nobody else wrote it in
+/// between; otherwise read it again and reapply them. Returns `None` when nothing changed.
+pub async fn update_sections<S: VaultStore + ?Sized>(store: &S, path: &str, edits: &[SectionEdit]) -> Result<Option<TransactionReport>> {
+    let mut attempt = 0;
+    loop {
+        let current = store.read_file(path).await?.ok_or_else(|| anyhow!("{} does not exist", path))?;
+        let mut updated = current.clone();
+        for edit in edits {
+            updated = edit.apply(&updated)?;
+        }
+        if updated == current {
+            return Ok(None);
+        }
+        let transaction = VaultTransaction::new(&format!("managed section update for {}", path))
+            .write_if_unchanged(path, &updated, &current);
+        match transaction.execute(store).await {
+            Err(e) if e.downcast_ref::<WriteConflict>().is_some() && attempt < MAX_CONFLICT_RETRIES => attempt += 1,
+            result => return result.map(Some),
+        }
+    }
+}
+
+/// A marker problem found by `arrowhead sections lint`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SectionLint {
+    pub path: String,
+    #[serde(flatten)]
+    pub problem: MarkerProblem,
+}
+
+/// The lint rule: every marker problem in `notes`, damage first
+pub fn lint(notes: &[(String, String)]) -> Vec<SectionLint> {
+    let mut findings: Vec<SectionLint> = notes.iter()
+        .flat_map(|(path, content)| scan(content).problems.into_iter().map(|problem| SectionLint { path: path.clone(), problem }))
+        .collect();
+    findings.sort_by_key(|f| !f.problem.is_damage());
+    findings
+}
+
+fn sections_report(path: &str, content: &str, scan: &SectionScan) -> Report {
+    let mut listing = String::new();
+    for section in &scan.sections {
+        let state = if section.legacy {
+            "old single-marker form"
+        } else if section.is_edited(content) {
+            "edited by hand"
+        } else {
+            "ok"
+        };
+        let heading = section.heading.as_deref().map(|h| format!(" \"{}\"", h.trim_start_matches('#').trim())).unwrap_or_default();
+        let _ = writeln!(listing, "  {}{} — owner {}, line {}, {}", section.id, heading, section.owner, section.line, state);
+    }
+    let problems: String = scan.problems.iter().map(|p| format!("  ⚠️  {}\n", p.describe())).collect();
+    Report::new()
+        .text(DetailLevel::Summary, format!("{}: {} managed section(s), {} problem(s)\n", path, scan.sections.len(), scan.problems.len()))
+        .text(DetailLevel::Normal, listing)
+        .text(DetailLevel::Normal, problems)
+        .field(DetailLevel::Summary, "path", path)
+        .field(DetailLevel::Normal, "sections", &scan.sections)
+        .field(DetailLevel::Normal, "problems", &scan.problems)
+}
+
+fn note_path(path: &str) -> String {
+    if path.ends_with(".md") { path.to_string() } else { format!("{}.md", path) }
+}
+
+pub async fn handle_sections_command(args: SectionsArgs, view: ReportView, adapter: &ObsidianAdapter) -> Result<()> {
+    match args.action {
+        SectionsAction::List { path } => {
+            let path = note_path(&path);
+            let Some(content) = adapter.read_file(&path).await? else { bail!("Note '{}' not found", path) };
+            let report = sections_report(&path, &content, &scan(&content));
+            crate::script::publish_output(report.to_json(view.level));
+            report.print(view)
+        }
+        SectionsAction::Lint { folders } => {
+            let config = Config::load().unwrap_or_default();
+            let folders = if folders.is_empty() { config.index.folders.clone() } else { folders };
+            let notes = load_notes_in(adapter, &folders).await;
+            let findings = lint(&notes);
+            let damaged: HashSet<&str> = findings.iter().filter(|f| f.problem.is_damage()).map(|f| f.path.as_str()).collect();
+            let listing: String = findings.iter().map(|f| format!("  {}: {}\n", f.path, f.problem.describe())).collect();
+            let report = Report::new()
+                .text(DetailLevel::Summary, format!("Checked {} note(s): {} with damaged section markers\n", notes.len(), damaged.len()))
+                .text(DetailLevel::Normal, listing)
+                .field(DetailLevel::Summary, "checked", notes.len())
+                .field(DetailLevel::Summary, "damaged", &damaged)
+                .field(DetailLevel::Normal, "findings", &findings);
+            crate::script::publish_output(report.to_json(view.level));
+            report.print(view)?;
+            if !damaged.is_empty() {
+                bail!("Run `arrowhead sections repair <path>` on the notes with damaged markers");
+            }
+            Ok(())
+        }
+        SectionsAction::Repair { path, dry_run } => {
+            let path = note_path(&path);
+            let Some(content) = adapter.read_file(&path).await? else { bail!("Note '{}' not found", path) };
+            let (repaired, fixed) = repair(&content);
+            if fixed.is_empty() {
+                println!("No damaged section markers in {}.", path);
+                return Ok(());
+            }
+            for problem in &fixed {
+                println!("  🔧 {}", problem.describe());
+            }
+            if dry_run {
+                println!("Dry run: {} problem(s) would be fixed", fixed.len());
+                return Ok(());
+            }
+            let report = VaultTransaction::new(&format!("repair section markers in {}", path))
+                .write_if_unchanged(&path, &repaired, &content)
+                .execute(adapter).await?;
+            if let Err(e) = report.append_to_log(adapter.transaction_log()) {
+                eprintln!("Warning: could not write transaction log: {}", e);
+            }
+            println!("{}", report.summary());
+            if !report.is_committed() {
+                bail!("Section markers in {} were not repaired", path);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_transaction::testing::FakeVault;
+    use std::time::Duration;
+
+    const NOTE: &str = "# Roadmap\nSee [[Plan]].\n\n## Referenced by\n<!-- arrowhead:backlinks hash=00ff -->\n- [[Kickoff]]\n\n## Changelog\n<!-- arrowhead:changelog -->\n- 2024-05-01 — edited [[Plan]]\n";
+
+    fn ids(content: &str) -> Vec<String> {
+        managed_sections(content).into_iter().map(|s| s.id).collect()
+    }
+
+    #[test]
+    fn test_legacy_sections_run_to_the_next_managed_section() {
+        let sections = managed_sections(NOTE);
+        assert_eq!(ids(NOTE), vec!["backlinks", "changelog"]);
+        assert!(sections.iter().all(|s| s.legacy && s.owner == s.id));
+        assert_eq!(sections[0].attributes.get("hash").map(String::as_str), Some("00ff"));
+        assert!(NOTE[sections[0].range.clone()].starts_with("## Referenced by"));
+        assert!(NOTE[sections[0].range.clone()].ends_with("- [[Kickoff]]\n\n"));
+        assert_eq!(sections[1].range.end, NOTE.len());
+
+        assert_eq!(without_managed_sections(NOTE, &[]), "# Roadmap\nSee [[Plan]].\n\n");
+        let kept = without_managed_sections(NOTE, &["changelog".to_string()]);
+        assert!(kept.contains("## Changelog") && !kept.contains("Kickoff"));
+        // A legacy marker needs a heading right above it
+        assert!(managed_sections("text\n<!-- arrowhead:changelog -->\n").is_empty());
+        assert!(scan(NOTE).problems.is_empty());
+    }
+
+    #[test]
+    fn test_upsert_orders_sections_and_respects_owners() {
+        let backlinks = SectionWrite::new("backlinks", "backlinks", "- [[Kickoff]]\n").heading("## Referenced by").attribute("inputs", "00ff");
+        let changelog = SectionWrite::new("changelog", "changelog", "- 2024-05-01 — edited\n").heading("## Changelog");
+        let note = "# Roadmap\nSee [[Plan]].\n";
+
+        // Added in either order, the changelog ends up last
+        let written = upsert(&upsert(note, &changelog).unwrap(), &backlinks).unwrap();
+        assert_eq!(ids(&written), vec!["backlinks", "changelog"]);
+        assert!(written.starts_with("# Roadmap\nSee [[Plan]].\n\n## Referenced by\n<!-- arrowhead:begin owner=backlinks id=backlinks hash="));
+        assert!(written.contains(" inputs=00ff -->\n- [[Kickoff]]\n<!-- arrowhead:end id=backlinks -->\n\n## Changelog\n"));
+        assert_eq!(written, upsert(&upsert(note, &backlinks).unwrap(), &changelog).unwrap());
+        assert!(scan(&written).problems.is_empty());
+
+        // Replaced in place, leaving user text after it alone
+        let with_text = format!("{}\nNotes added later.\n", written);
+        let updated = upsert(&with_text, &SectionWrite::new("backlinks", "backlinks", "- [[Retro]]\n").heading("## Referenced by")).unwrap();
+        assert!(updated.contains("- [[Retro]]\n<!-- arrowhead:end id=backlinks -->\n\n## Changelog\n"));
+        assert!(updated.ends_with("<!-- arrowhead:end id=changelog -->\n\nNotes added later.\n"));
+
+        let error = upsert(&written, &SectionWrite::new("split", "backlinks", "- x\n")).unwrap_err();
+        assert_eq!(error, SectionError::OwnedByOther { id: "backlinks".into(), owner: "backlinks".into(), requested_by: "split".into() });
+        assert!(matches!(remove(&written, "split", "changelog"), Err(SectionError::OwnedByOther { .. })));
+        assert!(upsert(note, &SectionWrite::new("back links", "x", "")).is_err());
+
+        assert_eq!(remove(&remove(&written, "backlinks", "backlinks").unwrap(), "changelog", "changelog").unwrap(), note);
+        // A legacy section is rewritten in the current form
+        let migrated = upsert(NOTE, &changelog).unwrap();
+        assert!(migrated.contains("- [[Kickoff]]\n\n## Changelog\n<!-- arrowhead:begin owner=changelog id=changelog"));
+        assert!(!migrated.contains("[[Plan]]\n<!-- arrowhead:end"));
+    }
+
+    #[test]
+    fn test_damaged_markers_are_reported_and_repaired() {
+        let damaged = "# Note\n\n## Parts\n<!-- arrowhead:begin owner=split id=parts hash=0 -->\n- [[a]]\n\n## Referenced by\n<!-- arrowhead:begin owner=backlinks id=backlinks hash=0 -->\n- [[b]]\n<!-- arrowhead:end id=backlinks -->\n<!-- arrowhead:end id=changelog -->\nText.\n";
+        let problems = scan(damaged).problems;
+        let kinds: Vec<(&str, bool)> = problems.iter().map(|p| (p.id(), p.is_damage())).collect();
+        assert_eq!(kinds, vec![("parts", true), ("backlinks", false), ("changelog", true)]);
+        assert!(matches!(problems[0], MarkerProblem::UnclosedBegin { line: 4, .. }));
+        // The unclosed section stops where the next one starts
+        assert_eq!(ids(damaged), vec!["parts", "backlinks"]);
+        assert!(matches!(upsert(damaged, &SectionWrite::new("split", "parts", "- [[c]]\n")), Err(SectionError::Damaged { .. })));
+        assert!(upsert(damaged, &SectionWrite::new("backlinks", "backlinks", "- [[c]]\n")).is_ok());
+
+        let (repaired, fixed) = repair(damaged);
+        assert_eq!(fixed.len(), 2);
+        assert!(repaired.contains("- [[a]]\n<!-- arrowhead:end id=parts -->\n\n## Referenced by\n"));
+        assert!(repaired.ends_with("<!-- arrowhead:end id=backlinks -->\nText.\n"));
+        assert!(scan(&repaired).problems.iter().all(|p| !p.is_damage()));
+
+        let findings = lint(&[("Notes/a.md".to_string(), damaged.to_string()), ("Notes/b.md".to_string(), repaired.clone())]);
+        let found: Vec<(&str, &str)> = findings.iter().map(|f| (f.path.as_str(), f.problem.id())).collect();
+        assert_eq!(found, vec![("Notes/a.md", "parts"), ("Notes/a.md", "changelog"), ("Notes/a.md", "backlinks"), ("Notes/b.md", "parts"), ("Notes/b.md", "backlinks")]);
+
+        let duplicated = format!("{}{}", SectionWrite::new("split", "parts", "- [[a]]\n").render(), SectionWrite::new("split", "parts", "- [[b]]\n").render());
+        assert!(matches!(scan(&duplicated).problems[..], [MarkerProblem::DuplicateId { line: 4, .. }]));
+        assert_eq!(repair(&duplicated).0, SectionWrite::new("split", "parts", "- [[a]]\n").render());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_updates_to_different_sections_keep_both() {
+        let vault = FakeVault::new(&[("Notes/roadmap.md", "# Roadmap\nShip it.\n")]).with_read_latency(Duration::from_millis(20));
+        let backlinks = [SectionEdit::Upsert(SectionWrite::new("backlinks", "backlinks", "- [[Kickoff]]\n").heading("## Referenced by"))];
+        let changelog = [SectionEdit::Upsert(SectionWrite::new("changelog", "changelog", "- 2024-05-01 — edited\n").heading("## Changelog"))];
+
+        // Both read the note before either writes; the second writer has to retry
+        let (first, second) = tokio::join!(
+            update_sections(&vault, "Notes/roadmap.md", &backlinks),
+            update_sections(&vault, "Notes/roadmap.md", &changelog),
+        );
+        assert!(first.unwrap().unwrap().is_committed());
+        assert!(second.unwrap().unwrap().is_committed());
+        let written = vault.get("Notes/roadmap.md").unwrap();
+        assert_eq!(ids(&written), vec!["backlinks", "changelog"]);
+        assert!(written.starts_with("# Roadmap\nShip it.\n\n## Referenced by\n"));
+
+        // A stale write is refused rather than dropping the other feature's section
+        let stale = "# Roadmap\nShip it.\n";
+        let transaction = VaultTransaction::new("stale").write_if_unchanged("Notes/roadmap.md", &upsert(stale, &SectionWrite::new("split", "parts", "- [[a]]\n")).unwrap(), stale);
+        assert!(transaction.execute(&vault).await.unwrap_err().downcast_ref::<WriteConflict>().is_some());
+        assert_eq!(vault.get("Notes/roadmap.md").unwrap(), written);
+
+        // Unchanged sections don't write at all
+        assert!(update_sections(&vault, "Notes/roadmap.md", &changelog).await.unwrap().is_none());
+        let removed = update_sections(&vault, "Notes/roadmap.md", &[SectionEdit::Remove { owner: "backlinks".into(), id: "backlinks".into() }]).await.unwrap();
+        assert!(removed.is_some());
+        assert_eq!(ids(&vault.get("Notes/roadmap.md").unwrap()), vec!["changelog"]);
+    }
+}
@@ -0,0 +1,344 @@
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag};
+use std::ops::Range;
+
+use crate::conflicts::split_frontmatter;
+
+/// What a [`Span`] of a note is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    /// The YAML block between the `---` lines at the top, delimiters included
+    Frontmatter,
+    Heading(u8),
+    Paragraph,
+    /// A fenced or indented code block, fences included
+    CodeBlock,
+    InlineCode,
+    /// `[[target]]`, `[[target|text]]` or an embed `![[target]]`
+    WikiLink,
+    /// `[text](url)`, a reference link or an image
+    MarkdownLink,
+    /// A bare `http(s)://` address or an autolink `<https://...>`
+    Url,
+    /// An inline `#tag`, `#` included
+    Tag,
+}
+
+/// A region of a note as byte offsets into its text, with the regions nested inside it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub kind: SpanKind,
+    pub range: Range<usize>,
+    pub children: Vec<Span>,
+}
+
+impl Span {
+    fn leaf(kind: SpanKind, range: Range<usize>) -> Self {
+        Self { kind, range, children: Vec::new() }
+    }
+
+    fn walk<'s>(&'s self, out: &mut Vec<&'s Span>) {
+        out.push(self);
+        for child in &self.children {
+            child.walk(out);
+        }
+    }
+}
+
+/// The structure of a note: frontmatter, headings, paragraphs, code, links and tags, found
+/// once so that features editing the text agree on which parts are prose
+#[derive(Debug, Clone)]
+pub struct Markdown<'a> {
+    source: &'a str,
+    spans: Vec<Span>,
+}
+
+/// Characters a `#tag` may contain after the `#`
+pub fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '/'
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+/// Adds spans to the innermost open span, or to the top level when none is open
+struct Builder<'a> {
+    source: &'a str,
+    roots: Vec<Span>,
+    /// Open containers; `None` for the ones not kept in the tree, such as list items
+    open: Vec<Option<Span>>,
+    /// Adjacent text events joined up, so a tag or URL split by the parser stays whole
+    text: Option<Range<usize>>,
+}
+
+impl<'a> Builder<'a> {
+    fn attach(&mut self, span: Span) {
+        match self.open.iter_mut().rev().find_map(Option::as_mut) {
+            Some(parent) => parent.children.push(span),
+            None => self.roots.push(span),
+        }
+    }
+
+    /// Whether text here is prose rather than a link's text or code
+    fn in_prose(&self) -> bool {
+        !self.open.iter().flatten().any(|span| matches!(span.kind, SpanKind::WikiLink | SpanKind::MarkdownLink | SpanKind::Url | SpanKind::CodeBlock))
+    }
+
+    fn flush_text(&mut self) {
+        let Some(range) = self.text.take() else { return };
+        let source = self.source;
+        let mut at = range.start;
+        while at < range.end {
+            let rest = &source[at..range.end];
+            let preceded_by_space = source[..at].chars().next_back().is_none_or(char::is_whitespace);
+            if rest.starts_with("http://") || rest.starts_with("https://") {
+                // Up to the next whitespace, even past the parser's idea of where the text ends
+                let end = source[at..].find(char::is_whitespace).map_or(source.len(), |i| at + i);
+                self.attach(Span::leaf(SpanKind::Url, at..end));
+                at = end;
+                continue;
+            }
+            if rest.starts_with('#') && preceded_by_space {
+                let start = at + 1;
+                let end = source[start..range.end].find(|c: char| !is_tag_char(c)).map_or(range.end, |i| start + i);
+                let tag = source[start..end].trim_end_matches('/');
+                if !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit() || c == '/') {
+                    self.attach(Span::leaf(SpanKind::Tag, at..start + tag.len()));
+                    at = start + tag.len();
+                    continue;
+                }
+            }
+            at += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+}
+
+impl<'a> Markdown<'a> {
+    pub fn parse(source: &'a str) -> Self {
+        let (yaml, body) = split_frontmatter(source);
+        let offset = source.len() - body.len();
+        let mut builder = Builder { source, roots: Vec::new(), open: Vec::new(), text: None };
+        if yaml.is_some() {
+            builder.roots.push(Span::leaf(SpanKind::Frontmatter, 0..offset));
+        }
+
+        let options = Options::ENABLE_WIKILINKS | Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+        for (event, range) in Parser::new_ext(body, options).into_offset_iter() {
+            let range = range.start + offset..range.end + offset;
+            if let Event::Text(_) = event {
+                if builder.in_prose() {
+                    builder.text = match builder.text.take() {
+                        Some(text) if text.end == range.start => Some(text.start..range.end),
+                        pending => {
+                            builder.text = pending;
+                            builder.flush_text();
+                            Some(range)
+                        }
+                    };
+                }
+                continue;
+            }
+            builder.flush_text();
+            match event {
+                Event::Start(tag) => {
+                    let kind = match tag {
+                        Tag::Heading { level, .. } => Some(SpanKind::Heading(level as u8)),
+                        Tag::Paragraph => Some(SpanKind::Paragraph),
+                        Tag::CodeBlock(_) => Some(SpanKind::CodeBlock),
+                        Tag::Link { link_type: LinkType::WikiLink { .. }, .. } | Tag::Image { link_type: LinkType::WikiLink { .. }, .. } => Some(SpanKind::WikiLink),
+                        Tag::Link { link_type: LinkType::Autolink, .. } => Some(SpanKind::Url),
+                        Tag::Link { .. } | Tag::Image { .. } => Some(SpanKind::MarkdownLink),
+                        _ => None,
+                    };
+                    builder.open.push(kind.map(|kind| Span::leaf(kind, range)));
+                }
+                Event::End(_) => {
+                    if let Some(Some(span)) = builder.open.pop() {
+                        builder.attach(span);
+                    }
+                }
+                Event::Code(_) => builder.attach(Span::leaf(SpanKind::InlineCode, range)),
+                _ => {}
+            }
+        }
+        builder.flush_text();
+        Markdown { source, spans: builder.roots }
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// The top-level spans, in order
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// Every span, parents before their children, in order
+    pub fn all_spans(&self) -> Vec<&Span> {
+        let mut out = Vec::new();
+        for span in &self.spans {
+            span.walk(&mut out);
+        }
+        out
+    }
+
+    fn ranges_of(&self, kinds: &[SpanKind]) -> Vec<Range<usize>> {
+        self.all_spans().into_iter().filter(|span| kinds.contains(&span.kind)).map(|span| span.range.clone()).collect()
+    }
+
+    pub fn frontmatter(&self) -> Option<Range<usize>> {
+        self.spans.first().filter(|span| span.kind == SpanKind::Frontmatter).map(|span| span.range.clone())
+    }
+
+    /// Code blocks and inline code
+    pub fn code_ranges(&self) -> Vec<Range<usize>> {
+        self.ranges_of(&[SpanKind::CodeBlock, SpanKind::InlineCode])
+    }
+
+    /// Whether `offset` is inside the frontmatter or code
+    pub fn in_code(&self, offset: usize) -> bool {
+        self.frontmatter().into_iter().chain(self.code_ranges()).any(|range| range.contains(&offset))
+    }
+
+    /// The parts of the note outside the frontmatter and code, in order
+    pub fn spans_excluding_code(&self) -> Vec<Range<usize>> {
+        let mut excluded: Vec<Range<usize>> = self.frontmatter().into_iter().chain(self.code_ranges()).collect();
+        excluded.sort_by_key(|range| range.start);
+        let mut out = Vec::new();
+        let mut at = 0;
+        for range in excluded {
+            if range.start > at {
+                out.push(at..range.start);
+            }
+            at = at.max(range.end);
+        }
+        if at < self.source.len() {
+            out.push(at..self.source.len());
+        }
+        out
+    }
+
+    /// Everything a text edit must leave alone: frontmatter, code, links, URLs and tags
+    pub fn protected_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = self.frontmatter().into_iter()
+            .chain(self.ranges_of(&[SpanKind::CodeBlock, SpanKind::InlineCode, SpanKind::WikiLink, SpanKind::MarkdownLink, SpanKind::Url, SpanKind::Tag]))
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        ranges
+    }
+
+    /// Whether `range` touches frontmatter, code, a link, a URL or a tag
+    pub fn is_protected(&self, range: &Range<usize>) -> bool {
+        self.protected_ranges().iter().any(|protected| overlaps(protected, range))
+    }
+
+    /// The inline tags, without their `#`
+    pub fn tags(&self) -> Vec<Range<usize>> {
+        self.ranges_of(&[SpanKind::Tag]).into_iter().map(|range| range.start + 1..range.end).collect()
+    }
+
+    /// Headings as (level, line range, text without the `#`s)
+    pub fn headings(&self) -> Vec<(u8, Range<usize>, &'a str)> {
+        self.all_spans().into_iter()
+            .filter_map(|span| match span.kind {
+                SpanKind::Heading(level) => {
+                    let line = &self.source[span.range.clone()];
+                    let text = line.trim().trim_start_matches('#').trim_end_matches(['=', '-']).trim();
+                    Some((level, span.range.clone(), text))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The section under the heading named `name` (case-insensitive, without the `#`s): the
+    /// heading line up to the next heading of the same or a higher level, or the end
+    pub fn find_heading_section(&self, name: &str) -> Option<Range<usize>> {
+        let headings = self.headings();
+        let index = headings.iter().position(|(_, _, text)| text.eq_ignore_ascii_case(name.trim().trim_start_matches('#').trim()))?;
+        let (level, range, _) = &headings[index];
+        let end = headings[index + 1..].iter()
+            .find(|(other, _, _)| other <= level)
+            .map_or(self.source.len(), |(_, other, _)| other.start);
+        Some(range.start..end)
+    }
+}
+
+/// `source` with `range` replaced by `text`. Panics if `range` is not on character
+/// boundaries of `source`, so offsets from an older version of the text are caught.
+pub fn replace_section(source: &str, range: Range<usize>, text: &str) -> String {
+    assert!(source.is_char_boundary(range.start) && source.is_char_boundary(range.end) && range.start <= range.end, "{:?} is not a range of the text", range);
+    let mut out = String::with_capacity(source.len() - range.len() + text.len());
+    out.push_str(&source[..range.start]);
+    out.push_str(text);
+    out.push_str(&source[range.end..]);
+    out
+}
+
+/// A note that mixes everything the features built on this module must keep apart: words
+/// and markers inside frontmatter, nested fences, code in lists, inline code, links and URLs
+#[cfg(test)]
+pub(crate) const FIXTURE: &str = "---
+title: Roadmap
+tags: [planning]
+---
+# Roadmap
+
+The Roadmap mentions [[Roadmap]], [the Roadmap](Roadmap.md), `Roadmap` and https://example.com/Roadmap_page #planning.
+
+- [ ] Review the Roadmap #q3
+- item
+  ```
+  - [ ] Roadmap task in a listed fence
+  ```
+
+~~~~
+```
+- [ ] Roadmap task in a nested fence
+<!-- arrowhead:begin owner=related id=related hash=0000000000000000 -->
+```
+~~~~
+
+## Next steps
+
+- [x] Ship the Roadmap
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_of_the_fixture() {
+        let doc = Markdown::parse(FIXTURE);
+        assert_eq!(&FIXTURE[doc.frontmatter().unwrap()], "---\ntitle: Roadmap\ntags: [planning]\n---\n");
+        let text = |kind| doc.all_spans().into_iter().filter(|s| s.kind == kind).map(|s| &FIXTURE[s.range.clone()]).collect::<Vec<_>>();
+        assert_eq!(text(SpanKind::WikiLink), ["[[Roadmap]]"]);
+        assert_eq!(text(SpanKind::MarkdownLink), ["[the Roadmap](Roadmap.md)"]);
+        assert_eq!(text(SpanKind::InlineCode), ["`Roadmap`"]);
+        assert_eq!(text(SpanKind::Url), ["https://example.com/Roadmap_page"]);
+        assert_eq!(doc.tags().into_iter().map(|r| &FIXTURE[r]).collect::<Vec<_>>(), ["planning", "q3"]);
+        assert!(doc.tags().iter().all(|tag| doc.is_protected(tag)));
+        assert_eq!(doc.code_ranges().len(), 3);
+        assert!(doc.in_code(FIXTURE.find("Roadmap task in a nested").unwrap()));
+        assert!(!doc.in_code(FIXTURE.find("Ship the").unwrap()));
+
+        let excluded = doc.spans_excluding_code();
+        assert!(excluded.iter().all(|r| !FIXTURE[r.clone()].contains("arrowhead:begin") && !FIXTURE[r.clone()].contains("title:")));
+    }
+
+    #[test]
+    fn test_heading_sections_and_replacement() {
+        let doc = Markdown::parse(FIXTURE);
+        let roadmap = doc.find_heading_section("roadmap").unwrap();
+        assert!(FIXTURE[roadmap.clone()].starts_with("# Roadmap\n") && FIXTURE[roadmap].ends_with("- [x] Ship the Roadmap\n"));
+        let next = doc.find_heading_section("## Next steps").unwrap();
+        assert_eq!(&FIXTURE[next.clone()], "## Next steps\n\n- [x] Ship the Roadmap\n");
+        assert!(doc.find_heading_section("Missing").is_none());
+
+        let replaced = replace_section(FIXTURE, next, "## Done\n");
+        assert!(replaced.ends_with("~~~~\n\n## Done\n"));
+        assert_eq!(Markdown::parse(&replaced).find_heading_section("done").map(|r| r.len()), Some("## Done\n".len()));
+    }
+}
@@ -0,0 +1,410 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, Utc};
+use std::collections::HashMap;
+
+use crate::ai_conversation::{create_llm_client, AIConversationEngine};
+use crate::calendar::{create_calendar_adapter, format_local, resolve_event_start, scheduling_constraints};
+use crate::calendar_adapter::{render_agenda, CalendarAdapter, CalendarConfig, CalendarEvent, CalendarProvider, MeetingRequest, TimeSlot};
+use crate::cli::{MeetingAction, MeetingArgs};
+use crate::config::{Config, MeetingNoteSettings};
+use crate::confirm::{PromptReader, StdinPrompt};
+use crate::daily::day_bounds;
+use crate::health::load_notes_in;
+use crate::managed_sections::{upsert, SectionError, SectionWrite};
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::reindex::embedding_indexer;
+use crate::timezone::{self, TimeZone};
+use crate::todo_model::frontmatter_block;
+use crate::usage::UsagePurpose;
+use crate::utils::slugify;
+use crate::vault_transaction::{VaultStore, VaultTransaction};
+
+/// Owner of the section `arrowhead meeting note` keeps up to date
+pub const SECTION_OWNER: &str = "meeting";
+/// Template category used for new meeting notes
+pub const TEMPLATE_CATEGORY: &str = "meeting";
+/// Start of the line in an event's description that points at its note
+const NOTE_LINK_PREFIX: &str = "Meeting notes: ";
+
+/// `[[Page]]` for an attendee: their page from `[meetings.people]`, otherwise a name made
+/// from the address (`jane.doe@example.com` → `Jane Doe`)
+pub fn attendee_link(attendee: &str, people: &HashMap<String, String>) -> String {
+    let email = attendee.trim().trim_start_matches("mailto:").trim_start_matches("MAILTO:");
+    if let Some(page) = people.iter().find(|(address, _)| address.eq_ignore_ascii_case(email)).map(|(_, page)| page) {
+        return format!("[[{}]]", page);
+    }
+    let local = email.split('@').next().unwrap_or(email);
+    let name: Vec<String> = local.split(['.', '_', '-'])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        })
+        .collect();
+    format!("[[{}]]", if name.is_empty() { email.to_string() } else { name.join(" ") })
+}
+
+/// Vault path of a new note for `event`: the meeting's local date and its title
+pub fn note_path(settings: &MeetingNoteSettings, event: &CalendarEvent, zone: &TimeZone) -> String {
+    let name = format!("{}-{}", zone.to_local(event.start_time).format("%Y-%m-%d"), slugify(&event.title));
+    let folder = settings.folder.trim_matches('/');
+    if folder.is_empty() { format!("{}.md", name) } else { format!("{}/{}.md", folder, name) }
+}
+
+/// The `event_id` a note's frontmatter records
+fn recorded_event_id(content: &str) -> Option<String> {
+    let fields: serde_yaml::Mapping = serde_yaml::from_str(frontmatter_block(content)?).ok()?;
+    match fields.get("event_id")? {
+        serde_yaml::Value::String(id) => Some(id.clone()),
+        serde_yaml::Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// The note already written for `event_id`, if any
+pub fn find_note<'a>(notes: &'a [(String, String)], event_id: &str) -> Option<&'a (String, String)> {
+    notes.iter().find(|(_, content)| recorded_event_id(content).as_deref() == Some(event_id))
+}
+
+/// When, where and who, as a managed section so a rerun picks up changes to the event
+pub fn details_section(event: &CalendarEvent, zone: &TimeZone, people: &HashMap<String, String>) -> SectionWrite {
+    let start = zone.to_local(event.start_time);
+    let mut body = if event.all_day {
+        format!("- When: {} (all day)\n", start.format("%Y-%m-%d"))
+    } else {
+        format!("- When: {}–{}\n", start.format("%Y-%m-%d %H:%M"), zone.to_local(event.end_time).format("%H:%M"))
+    };
+    if let Some(location) = event.location.as_deref().filter(|l| !l.trim().is_empty()) {
+        body.push_str(&format!("- Where: {}\n", location.trim()));
+    }
+    if !event.attendees.is_empty() {
+        let links: Vec<String> = event.attendees.iter().map(|a| attendee_link(a, people)).collect();
+        body.push_str(&format!("- Attendees: {}\n", links.join(", ")));
+    }
+    SectionWrite::new(SECTION_OWNER, "details", &body).heading("## Details")
+}
+
+/// Values for a meeting template: the event's fields, attendee links as a list and the
+/// details section with its markers
+fn template_values(event: &CalendarEvent, zone: &TimeZone, people: &HashMap<String, String>, details: &SectionWrite) -> HashMap<String, serde_json::Value> {
+    let start = zone.to_local(event.start_time);
+    let attendees: Vec<serde_json::Value> = event.attendees.iter().map(|a| attendee_link(a, people).into()).collect();
+    HashMap::from([
+        ("title".to_string(), event.title.clone().into()),
+        ("date".to_string(), start.format("%Y-%m-%d").to_string().into()),
+        ("time".to_string(), start.format("%H:%M").to_string().into()),
+        ("location".to_string(), event.location.clone().unwrap_or_default().into()),
+        ("attendees".to_string(), serde_json::Value::Array(attendees)),
+        ("event_id".to_string(), event.id.clone().into()),
+        ("details".to_string(), details.render().trim_end().to_string().into()),
+    ])
+}
+
+/// Add `event_id` to the frontmatter (creating it if need be) unless it's there already
+fn with_event_id(content: &str, event_id: &str) -> String {
+    if recorded_event_id(content).is_some() {
+        return content.to_string();
+    }
+    match content.strip_prefix("---\n") {
+        Some(rest) if frontmatter_block(content).is_some() => format!("---\nevent_id: \"{}\"\n{}", event_id, rest),
+        _ => format!("---\nevent_id: \"{}\"\n---\n\n{}", event_id, content),
+    }
+}
+
+/// The built-in layout of a new meeting note
+fn builtin_note(event: &CalendarEvent, zone: &TimeZone, details: &SectionWrite) -> String {
+    format!(
+        "---\nevent_id: \"{}\"\ndate: {}\ntags: [meeting]\n---\n\n# {}\n\n{}\n## Agenda\n\n## Notes\n\n## Action Items\n\n",
+        event.id,
+        zone.to_local(event.start_time).format("%Y-%m-%d"),
+        event.title,
+        details.render(),
+    )
+}
+
+/// The meeting note after this run. An existing note only has its details replaced; a new
+/// one comes from `template` (already rendered) or the built-in layout.
+pub fn build_note(existing: Option<&str>, template: Option<String>, event: &CalendarEvent, zone: &TimeZone, details: &SectionWrite) -> Result<String, SectionError> {
+    match (existing, template) {
+        (Some(existing), _) => upsert(existing, details),
+        (None, Some(rendered)) => upsert(&with_event_id(&rendered, &event.id), details),
+        (None, None) => Ok(builtin_note(event, zone, details)),
+    }
+}
+
+/// The event description pointing at `note_path`, replacing an earlier pointer; `None`
+/// when it already points there
+pub fn linked_description(description: Option<&str>, note_path: &str) -> Option<String> {
+    let link = format!("{}{}", NOTE_LINK_PREFIX, note_path);
+    let description = description.unwrap_or_default();
+    if description.lines().any(|line| line.trim() == link) {
+        return None;
+    }
+    let mut lines: Vec<&str> = description.lines().filter(|line| !line.trim().starts_with(NOTE_LINK_PREFIX)).collect();
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    Some(if lines.is_empty() { link } else { format!("{}\n\n{}", lines.join("\n"), link) })
+}
+
+/// A new note rendered from the first template in the "meeting" category, if there is one
+fn meeting_template(config: &Config, values: HashMap<String, serde_json::Value>) -> Option<String> {
+    let mut templates = ObsidianAdapter::new(Some(config.obsidian.base_url.clone()), config.obsidian.api_key.clone());
+    templates.set_cache_settings(&config.cache);
+    if let Err(e) = templates.load_template_database() {
+        println!("⚠️  Could not load templates, using the built-in layout: {}", e);
+        return None;
+    }
+    let template = templates.get_templates_by_category(TEMPLATE_CATEGORY).into_iter().next()?;
+    match templates.render_template(template, &values.into()) {
+        Ok(rendered) => Some(rendered),
+        Err(e) => {
+            println!("⚠️  Could not render template '{}', using the built-in layout: {}", template.name, e);
+            None
+        }
+    }
+}
+
+/// Today's events, numbered, and the one picked at the prompt
+async fn pick_todays_event(calendar: &CalendarAdapter, config: &Config, zone: &TimeZone, reader: &mut dyn PromptReader) -> Result<CalendarEvent> {
+    let (start, end) = day_bounds(zone.to_local(Utc::now()).date(), zone);
+    let mut events = calendar.list_events(&config.calendar.calendar_id, Some(start), Some(end)).await?;
+    events.sort_by_key(|e| (!e.all_day, e.start_time));
+    if events.is_empty() {
+        bail!("No events today");
+    }
+    for (i, event) in events.iter().enumerate() {
+        let time = if event.all_day { "all day".to_string() } else { zone.to_local(event.start_time).format("%H:%M").to_string() };
+        println!("{:>2}. {}  {}  ({})", i + 1, time, event.title, event.id);
+    }
+    if !reader.is_interactive() {
+        bail!("Pass one of these event ids to `arrowhead meeting note`");
+    }
+    let answer = reader.read_answer("Event number:")?;
+    match answer.trim().parse::<usize>() {
+        Ok(n) if (1..=events.len()).contains(&n) => Ok(events.swap_remove(n - 1)),
+        _ => bail!("'{}' is not one of the listed numbers", answer.trim()),
+    }
+}
+
+async fn write_meeting_note(config: &Config, adapter: &ObsidianAdapter, event_id: Option<String>) -> Result<()> {
+    let zone = timezone::resolve(&config.calendar.time_zone)?;
+    let settings = &config.meetings;
+    let calendar = create_calendar_adapter(&config.calendar, &config.network)?;
+    let event = match event_id {
+        Some(id) => calendar.get_event(&config.calendar.calendar_id, id.trim_end_matches(".ics")).await?,
+        None => pick_todays_event(&calendar, config, &zone, &mut StdinPrompt).await?,
+    };
+
+    let notes = load_notes_in(adapter, std::slice::from_ref(&settings.folder)).await;
+    let (path, existing) = match find_note(&notes, &event.id) {
+        Some((path, content)) => (path.clone(), Some(content.clone())),
+        None => {
+            let path = note_path(settings, &event, &zone);
+            let existing = adapter.read_file(&path).await?;
+            if existing.as_deref().is_some_and(|content| recorded_event_id(content).is_some_and(|id| id != event.id)) {
+                bail!("{} belongs to another event; move it or rename this one", path);
+            }
+            (path, existing)
+        }
+    };
+    let details = details_section(&event, &zone, &settings.people);
+    let template = match existing {
+        Some(_) => None,
+        None => meeting_template(config, template_values(&event, &zone, &settings.people, &details)),
+    };
+    let updated = build_note(existing.as_deref(), template, &event, &zone, &details)?;
+
+    if existing.as_deref() == Some(updated.as_str()) {
+        println!("{} is up to date.", path);
+    } else {
+        let transaction = match &existing {
+            Some(current) => VaultTransaction::new(&format!("meeting note {}", event.id)).write_if_unchanged(&path, &updated, current),
+            None => VaultTransaction::new(&format!("meeting note {}", event.id)).write(&path, &updated),
+        };
+        let report = transaction.execute(adapter).await?;
+        if let Err(e) = report.append_to_log(adapter.transaction_log()) {
+            eprintln!("Warning: could not write transaction log: {}", e);
+        }
+        if !report.is_committed() {
+            bail!("{}", report.summary());
+        }
+        println!("{} {} for '{}'", if existing.is_some() { "Updated" } else { "Created" }, path, event.title);
+    }
+
+    let mut linked = false;
+    if let Some(description) = linked_description(event.description.as_deref(), &path) {
+        let calendar_id = if event.calendar_id.is_empty() { config.calendar.calendar_id.as_str() } else { event.calendar_id.as_str() };
+        let event = CalendarEvent { description: Some(description), ..event.clone() };
+        match calendar.update_event(calendar_id, &event.id, &event).await {
+            Ok(_) => linked = true,
+            Err(e) => println!("⚠️  Could not add the note to the event's description: {}", e),
+        }
+    }
+    crate::script::publish_output(serde_json::json!({
+        "path": path,
+        "event_id": event.id,
+        "created": existing.is_none(),
+        "linked": linked,
+    }));
+    Ok(())
+}
+
+/// Related notes as prompt context: title, path and the passage that matched
+async fn related_notes(config: &Config, query: &str, limit: usize) -> Result<String> {
+    let mut indexer = embedding_indexer(config)?;
+    indexer.load_vector_database()?;
+    let results = indexer.semantic_search(query).await?;
+    Ok(results.into_iter()
+        .take(limit)
+        .map(|result| format!("- {} ({}): {}", result.metadata.title, result.path, result.snippet.replace('\n', " ")))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Agendas only need the AI engine, so without CalDAV credentials the adapter is built
+/// without them; sending an invitation needs the configured calendar
+fn agenda_adapter(config: &Config, send: bool) -> Result<CalendarAdapter> {
+    match create_calendar_adapter(&config.calendar, &config.network) {
+        Ok(adapter) => Ok(adapter),
+        Err(e) if send => Err(e),
+        Err(_) => CalendarAdapter::new(CalendarConfig {
+            provider: CalendarProvider::Apple,
+            server_url: config.calendar.server_url.clone(),
+            username: String::new(),
+            password: String::new(),
+            calendar_name: None,
+        }),
+    }
+}
+
+pub async fn handle_meeting_command(args: MeetingArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+
+    match args.action {
+        MeetingAction::Agenda { title, duration, attendees, optional, description, notes, at, json } => {
+            let now = Utc::now();
+            let engine = AIConversationEngine::new(create_llm_client(&config, UsagePurpose::Suggestions)?);
+            let adapter = agenda_adapter(&config, at.is_some())?.with_ai_conversation(engine);
+            let request = MeetingRequest {
+                title,
+                description,
+                duration_minutes: duration.max(1),
+                required_attendees: attendees,
+                optional_attendees: optional,
+                location: None,
+                earliest_start: now,
+                latest_start: now + Duration::days(14),
+                preferred_times: Vec::new(),
+                avoid_times: Vec::new(),
+                buffer_minutes: 0,
+                allow_overlapping: false,
+            };
+
+            let context = if notes == 0 {
+                None
+            } else {
+                let query = format!("{} {}", request.title, request.description.as_deref().unwrap_or_default());
+                match related_notes(&config, query.trim(), notes).await {
+                    Ok(context) => Some(context),
+                    Err(e) => {
+                        println!("ℹ️  Not using related notes ({})", e);
+                        None
+                    }
+                }
+            };
+
+            let agenda = adapter.generate_meeting_agenda(&request, context.as_deref()).await?;
+            crate::script::publish_output(serde_json::to_value(&agenda)?);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&agenda)?);
+            } else {
+                print!("{}", render_agenda(&agenda));
+            }
+
+            if let Some(at) = at {
+                let zone = timezone::resolve(&config.calendar.time_zone)?;
+                let constraints = scheduling_constraints(&config.calendar);
+                let start_time = resolve_event_start(&adapter, Some(&at), request.duration_minutes, &constraints, &zone, now).await?;
+                let slot = TimeSlot {
+                    start_time,
+                    end_time: start_time + Duration::minutes(request.duration_minutes as i64),
+                    calendar_id: None,
+                    event_id: None,
+                };
+                let invitation = adapter.create_meeting_invitation(&request, &slot, Some(&agenda)).await
+                    .context("Failed to send the invitation")?;
+                println!("\n✅ Invited {} attendee(s) to '{}' on {}",
+                    invitation.attendees.len(),
+                    invitation.subject,
+                    format_local(invitation.start_time, &zone));
+            }
+        }
+        MeetingAction::Note { event_id, today: _ } => write_meeting_note(&config, adapter, event_id).await?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    fn event() -> CalendarEvent {
+        CalendarEvent {
+            id: "design-review".to_string(),
+            title: "Design review".to_string(),
+            description: Some("Bring the mockups".to_string()),
+            start_time: Utc.with_ymd_and_hms(2024, 6, 5, 14, 0, 0).unwrap(),
+            end_time: Utc.with_ymd_and_hms(2024, 6, 5, 15, 0, 0).unwrap(),
+            location: Some("Room 4".to_string()),
+            attendees: vec!["jane.doe@example.com".to_string(), "ravi@example.com".to_string()],
+            all_day: false,
+            recurring: false,
+            calendar_id: "work".to_string(),
+        }
+    }
+
+    fn people() -> HashMap<String, String> {
+        HashMap::from([("ravi@example.com".to_string(), "Ravi Kumar".to_string())])
+    }
+
+    #[test]
+    fn test_attendees_become_people_links() {
+        assert_eq!(attendee_link("jane.doe@example.com", &people()), "[[Jane Doe]]");
+        assert_eq!(attendee_link("mailto:RAVI@example.com", &people()), "[[Ravi Kumar]]");
+        let zone = timezone::resolve("UTC").unwrap();
+        let details = details_section(&event(), &zone, &people()).render();
+        assert!(details.contains("- When: 2024-06-05 14:00–15:00"));
+        assert!(details.contains("- Attendees: [[Jane Doe]], [[Ravi Kumar]]"));
+        assert_eq!(note_path(&MeetingNoteSettings::default(), &event(), &zone), "Meetings/2024-06-05-design-review.md");
+    }
+
+    #[test]
+    fn test_rerun_finds_the_note_and_only_replaces_details() {
+        let zone = timezone::resolve("UTC").unwrap();
+        let created = build_note(None, None, &event(), &zone, &details_section(&event(), &zone, &people())).unwrap();
+        let edited = created.replace("## Notes\n", "## Notes\n\nShip on Friday.\n");
+        let notes = vec![("Meetings/renamed.md".to_string(), edited.clone())];
+        assert_eq!(find_note(&notes, "design-review").map(|(p, _)| p.as_str()), Some("Meetings/renamed.md"));
+        assert!(find_note(&notes, "standup").is_none());
+
+        let moved = CalendarEvent { location: Some("Room 7".to_string()), ..event() };
+        let updated = build_note(Some(&edited), None, &moved, &zone, &details_section(&moved, &zone, &people())).unwrap();
+        assert!(updated.contains("- Where: Room 7") && !updated.contains("Room 4"));
+        assert!(updated.contains("Ship on Friday."));
+        assert_eq!(updated.matches("## Details").count(), 1);
+
+        let templated = build_note(None, Some("---\ntags: [meeting]\n---\n\n# Design review\n".to_string()), &event(), &zone, &details_section(&event(), &zone, &people())).unwrap();
+        assert_eq!(recorded_event_id(&templated).as_deref(), Some("design-review"));
+    }
+
+    #[test]
+    fn test_description_link_is_added_once() {
+        let linked = linked_description(Some("Bring the mockups"), "Meetings/a.md").unwrap();
+        assert_eq!(linked, "Bring the mockups\n\nMeeting notes: Meetings/a.md");
+        assert_eq!(linked_description(Some(&linked), "Meetings/a.md"), None);
+        assert_eq!(linked_description(Some(&linked), "Meetings/b.md").unwrap(), "Bring the mockups\n\nMeeting notes: Meetings/b.md");
+        assert_eq!(linked_description(None, "Meetings/a.md").unwrap(), "Meeting notes: Meetings/a.md");
+    }
+}
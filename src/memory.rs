@@ -0,0 +1,499 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::ai_conversation::{Message, MessageRole};
+use crate::cli::{MemoryAction, MemoryArgs};
+use crate::config::{Config, MemorySettings};
+use crate::confirm::{confirm, Operation, OperationClass};
+use crate::note_skeleton::stable_hash;
+
+/// Dimension of the hashed bag-of-words vectors used to compare facts
+const MEMORY_EMBEDDING_DIMENSION: usize = 256;
+/// Facts at least this similar are treated as the same fact, and the newer wording wins
+const DUPLICATE_SIMILARITY: f32 = 0.8;
+
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "the", "is", "are", "was", "be", "to", "of", "and", "or", "in", "on", "at", "for",
+    "with", "that", "this", "it", "i", "me", "my", "we", "our", "you", "your", "do", "does", "what",
+    "who", "when", "remember", "please",
+];
+
+/// Sentence openings that usually state a lasting preference or fact about the user
+const DURABLE_OPENINGS: &[&str] = &[
+    "i prefer", "i like", "i don't like", "i dislike", "i always", "i never", "i usually",
+    "i work", "i'm based", "i am based", "i live", "i'm in", "i am in", "call me", "our team",
+    "we always", "we never",
+];
+
+const RECURRING_WORDS: &[&str] = &[
+    "every", "weekly", "daily", "monthly", "fortnightly", "biweekly", "each",
+];
+
+/// A durable fact remembered across conversations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    pub id: String,
+    pub fact: String,
+    /// Conversations the fact was stated in
+    pub source_sessions: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub embedding: Vec<f32>,
+}
+
+/// Long-term facts about the user, persisted as JSON outside the vault
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryStore {
+    pub entries: Vec<MemoryEntry>,
+}
+
+impl MemoryStore {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).context("Failed to read memory store")?;
+        serde_json::from_str(&content).context("Failed to parse memory store")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create memory store directory")?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content).context("Failed to write memory store")
+    }
+
+    /// Store a fact, merging it into an existing entry when it says the same thing.
+    /// Returns true when a new entry was created.
+    pub fn remember(&mut self, fact: &str, session_id: &str, settings: &MemorySettings) -> bool {
+        let fact = if settings.redact_sensitive { redact(fact.trim()) } else { fact.trim().to_string() };
+        if fact.is_empty() {
+            return false;
+        }
+        let embedding = embed_text(&fact);
+        let now = Utc::now();
+
+        let closest = self.entries.iter_mut()
+            .map(|entry| (cosine_similarity(&entry.embedding, &embedding), entry))
+            .filter(|(similarity, _)| *similarity >= DUPLICATE_SIMILARITY)
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        if let Some((_, entry)) = closest {
+            entry.fact = fact;
+            entry.embedding = embedding;
+            entry.updated_at = now;
+            if !entry.source_sessions.iter().any(|s| s == session_id) {
+                entry.source_sessions.push(session_id.to_string());
+            }
+            return false;
+        }
+
+        self.entries.push(MemoryEntry {
+            id: Uuid::new_v4().simple().to_string()[..8].to_string(),
+            fact,
+            source_sessions: vec![session_id.to_string()],
+            created_at: now,
+            updated_at: now,
+            embedding,
+        });
+        true
+    }
+
+    /// Run the extraction pass over a finished conversation. Returns the number of new facts.
+    pub fn remember_session(&mut self, session_id: &str, messages: &[Message], settings: &MemorySettings) -> usize {
+        extract_facts(messages).iter()
+            .filter(|fact| self.remember(fact, session_id, settings))
+            .count()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&MemoryEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn edit(&mut self, id: &str, fact: &str) -> Result<()> {
+        let entry = self.entries.iter_mut().find(|entry| entry.id == id)
+            .with_context(|| format!("No memory with id '{}'", id))?;
+        entry.fact = fact.trim().to_string();
+        entry.embedding = embed_text(&entry.fact);
+        entry.updated_at = Utc::now();
+        Ok(())
+    }
+
+    pub fn delete(&mut self, id: &str) -> Result<MemoryEntry> {
+        let index = self.entries.iter().position(|entry| entry.id == id)
+            .with_context(|| format!("No memory with id '{}'", id))?;
+        Ok(self.entries.remove(index))
+    }
+
+    /// Merge another store's entries, deduplicating by similarity. Returns the number added.
+    pub fn import(&mut self, other: MemoryStore, settings: &MemorySettings) -> usize {
+        let mut added = 0;
+        for entry in other.entries {
+            let session = entry.source_sessions.first().cloned().unwrap_or_else(|| "import".to_string());
+            if self.remember(&entry.fact, &session, settings) {
+                if let Some(new_entry) = self.entries.last_mut() {
+                    new_entry.source_sessions = entry.source_sessions;
+                    new_entry.created_at = entry.created_at;
+                }
+                added += 1;
+            }
+        }
+        added
+    }
+
+    /// The most relevant facts for a conversation opening with `query`, within a token budget
+    pub fn relevant(&self, query: &str, token_budget: usize) -> Vec<&MemoryEntry> {
+        let query_embedding = embed_text(query);
+        let mut scored: Vec<(f32, &MemoryEntry)> = self.entries.iter()
+            .map(|entry| (cosine_similarity(&entry.embedding, &query_embedding), entry))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.updated_at.cmp(&a.1.updated_at)));
+
+        let mut used = 0;
+        let mut selected = Vec::new();
+        for (_, entry) in scored {
+            let tokens = estimate_tokens(&entry.fact);
+            if used + tokens > token_budget {
+                continue;
+            }
+            used += tokens;
+            selected.push(entry);
+        }
+        selected
+    }
+
+    /// System message carrying the remembered facts relevant to the opening message
+    pub fn system_message(&self, first_user_message: &str, token_budget: usize) -> Option<Message> {
+        let facts = self.relevant(first_user_message, token_budget);
+        if facts.is_empty() {
+            return None;
+        }
+        let mut content = "Things the user told you in earlier conversations. Use them when relevant, and don't repeat them back unprompted:".to_string();
+        for entry in facts {
+            content.push_str(&format!("\n- {}", entry.fact));
+        }
+        Some(Message {
+            id: Uuid::new_v4().to_string(),
+            role: MessageRole::System,
+            content,
+            timestamp: Utc::now(),
+            function_call: None,
+        })
+    }
+}
+
+/// Where the memory store lives when `[memory] path` is not set
+pub fn default_memory_path() -> PathBuf {
+    let mut path = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push(".config");
+    path.push("arrowhead");
+    path.push("memories.json");
+    path
+}
+
+pub fn memory_path(settings: &MemorySettings) -> PathBuf {
+    settings.path.as_ref().map(PathBuf::from).unwrap_or_else(default_memory_path)
+}
+
+/// Candidate durable facts stated by the user: preferences, facts about themselves or
+/// the people they work with, and recurring events. Questions are never facts.
+pub fn extract_facts(messages: &[Message]) -> Vec<String> {
+    let mut facts = Vec::new();
+    for message in messages.iter().filter(|m| matches!(m.role, MessageRole::User)) {
+        for sentence in split_sentences(&message.content) {
+            if let Some(fact) = durable_fact(&sentence) {
+                if !facts.contains(&fact) {
+                    facts.push(fact);
+                }
+            }
+        }
+    }
+    facts
+}
+
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    for c in text.chars() {
+        current.push(c);
+        if matches!(c, '.' | '!' | '?' | '\n') {
+            sentences.push(current.trim().to_string());
+            current.clear();
+        }
+    }
+    sentences.push(current.trim().to_string());
+    sentences.retain(|s| !s.is_empty());
+    sentences
+}
+
+fn durable_fact(sentence: &str) -> Option<String> {
+    if sentence.ends_with('?') {
+        return None;
+    }
+    let trimmed = sentence.trim_end_matches(['.', '!', '\n']).trim();
+    let lower = trimmed.to_lowercase();
+    let (text, lower) = match lower.strip_prefix("remember that ").or_else(|| lower.strip_prefix("remember ")) {
+        Some(rest) => (&trimmed[trimmed.len() - rest.len()..], rest.to_string()),
+        None => (trimmed, lower.clone()),
+    };
+    let explicit = text.len() < trimmed.len();
+
+    let words: Vec<&str> = lower.split_whitespace().collect();
+    if words.len() < 3 || words.len() > 30 {
+        return None;
+    }
+
+    let about_user = lower.starts_with("my ") && words.iter().any(|w| matches!(*w, "is" | "are" | "was"));
+    let preference = DURABLE_OPENINGS.iter().any(|opening| lower.starts_with(opening));
+    let recurring = words.iter().any(|w| matches!(*w, "is" | "are"))
+        && words.iter().any(|w| RECURRING_WORDS.contains(w));
+
+    if !(explicit || about_user || preference || recurring) {
+        return None;
+    }
+
+    let mut fact = text.to_string();
+    if let Some(first) = fact.chars().next() {
+        fact.replace_range(..first.len_utf8(), &first.to_uppercase().to_string());
+    }
+    Some(fact)
+}
+
+/// Replace contact details and secret-looking tokens so they are never stored
+pub fn redact(text: &str) -> String {
+    text.split(' ')
+        .map(|word| {
+            let core = word.trim_matches(|c: char| !c.is_alphanumeric());
+            let digits = core.chars().filter(char::is_ascii_digit).count();
+            let letters = core.chars().filter(|c| c.is_alphabetic()).count();
+            if core.contains('@') && core.contains('.') || word.contains('@') && word.contains('.') {
+                "[redacted email]".to_string()
+            } else if digits >= 7 && letters == 0 {
+                "[redacted number]".to_string()
+            } else if core.len() >= 24 && digits > 0 && letters > 0 {
+                "[redacted secret]".to_string()
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Hashed bag-of-words vector; cheap, local and good enough to spot restatements
+pub fn embed_text(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0f32; MEMORY_EMBEDDING_DIMENSION];
+    let lower = text.to_lowercase();
+    for word in lower.split(|c: char| !c.is_alphanumeric() && c != '\'') {
+        if word.is_empty() || STOP_WORDS.contains(&word) {
+            continue;
+        }
+        let stem = word.strip_suffix('s').filter(|s| s.len() > 2).unwrap_or(word);
+        vector[(stable_hash(stem) % MEMORY_EMBEDDING_DIMENSION as u64) as usize] += 1.0;
+    }
+    vector
+}
+
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 { 0.0 } else { dot / (norm_a * norm_b) }
+}
+
+/// Rough estimate: 1 token ≈ 4 characters, matching `ConversationContext`
+fn estimate_tokens(text: &str) -> usize {
+    text.len() / 4 + 1
+}
+
+pub fn print_memories(store: &MemoryStore) {
+    if store.entries.is_empty() {
+        println!("No memories yet. Tell me something worth remembering, or use /remember <fact>.");
+        return;
+    }
+    for entry in &store.entries {
+        println!(
+            "[{}] {} (from {} conversation(s), updated {})",
+            entry.id, entry.fact, entry.source_sessions.len(), entry.updated_at.format("%Y-%m-%d")
+        );
+    }
+}
+
+pub async fn handle_memory_command(args: MemoryArgs) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let path = memory_path(&config.memory);
+    let mut store = MemoryStore::load(&path)?;
+
+    match args.action {
+        MemoryAction::List => print_memories(&store),
+        MemoryAction::Add { fact } => {
+            if store.remember(&fact, "manual", &config.memory) {
+                println!("🧠 Remembered: {}", store.entries.last().map_or("", |e| e.fact.as_str()));
+            } else {
+                println!("🧠 Updated an existing memory");
+            }
+            store.save(&path)?;
+        }
+        MemoryAction::Edit { id, fact } => {
+            store.edit(&id, &fact)?;
+            store.save(&path)?;
+            println!("✅ Memory {} updated", id);
+        }
+        MemoryAction::Delete { id, yes } => {
+            let fact = store.get(&id).map(|entry| entry.fact.clone()).unwrap_or_default();
+            let operation = Operation::new(OperationClass::Delete, format!("Forget \"{}\"", fact), 1);
+            if !fact.is_empty() && !confirm(&config.confirmations, &operation, yes)? {
+                println!("Nothing forgotten.");
+                return Ok(());
+            }
+            let removed = store.delete(&id)?;
+            store.save(&path)?;
+            println!("🗑️ Forgot: {}", removed.fact);
+        }
+        MemoryAction::Export { file } => {
+            store.save(Path::new(&file))?;
+            println!("✅ Exported {} memories to {}", store.entries.len(), file);
+        }
+        MemoryAction::Import { file } => {
+            if !Path::new(&file).exists() {
+                bail!("File '{}' not found", file);
+            }
+            let added = store.import(MemoryStore::load(Path::new(&file))?, &config.memory);
+            store.save(&path)?;
+            println!("✅ Imported {} new memories from {}", added, file);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_conversation::{AIConversationEngine, FunctionSchema, LLMClient};
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+
+    /// Records every prompt it is sent so tests can inspect what the model saw
+    struct RecordingClient {
+        prompts: Arc<Mutex<Vec<Vec<Message>>>>,
+    }
+
+    #[async_trait]
+    impl LLMClient for RecordingClient {
+        async fn send_message(&self, messages: Vec<Message>) -> Result<Message> {
+            self.prompts.lock().unwrap().push(messages);
+            Ok(Message {
+                id: "reply".to_string(),
+                role: MessageRole::Assistant,
+                content: "Noted.".to_string(),
+                timestamp: Utc::now(),
+                function_call: None,
+            })
+        }
+
+        async fn stream_response(&self, _messages: Vec<Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+            let (_tx, rx) = tokio::sync::mpsc::channel(1);
+            Ok(rx)
+        }
+
+        async fn function_calling(&self, messages: Vec<Message>, _functions: Vec<FunctionSchema>) -> Result<Message> {
+            self.send_message(messages).await
+        }
+
+        fn get_model_name(&self) -> String {
+            "recording-mock".to_string()
+        }
+    }
+
+    fn user(content: &str) -> Message {
+        Message {
+            id: Uuid::new_v4().to_string(),
+            role: MessageRole::User,
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            function_call: None,
+        }
+    }
+
+    #[test]
+    fn test_extracts_durable_facts_only() {
+        let messages = vec![
+            user("My manager is Sam. Can you add a todo to send him the report?"),
+            user("I prefer bullet summaries. Thanks!"),
+            user("The team demo is every second Thursday."),
+            user("What's on my list today?"),
+            user("Remember that the staging password rotates monthly"),
+        ];
+        assert_eq!(extract_facts(&messages), vec![
+            "My manager is Sam",
+            "I prefer bullet summaries",
+            "The team demo is every second Thursday",
+            "The staging password rotates monthly",
+        ]);
+    }
+
+    #[test]
+    fn test_restated_fact_updates_existing_entry() {
+        let settings = MemorySettings::default();
+        let mut store = MemoryStore::default();
+        assert!(store.remember("My manager is Sam", "s1", &settings));
+        assert!(store.remember("I prefer bullet summaries", "s1", &settings));
+        assert!(!store.remember("my manager is sam.", "s2", &settings));
+        assert_eq!(store.entries.len(), 2);
+        assert_eq!(store.entries[0].source_sessions, vec!["s1", "s2"]);
+    }
+
+    #[test]
+    fn test_sensitive_details_are_redacted() {
+        let settings = MemorySettings::default();
+        let mut store = MemoryStore::default();
+        store.remember("My email is sam@example.com and my phone is 5551234567", "s1", &settings);
+        assert_eq!(store.entries[0].fact, "My email is [redacted email] and my phone is [redacted number]");
+    }
+
+    #[test]
+    fn test_relevant_respects_token_budget() {
+        let settings = MemorySettings::default();
+        let mut store = MemoryStore::default();
+        store.remember("My manager is Sam", "s1", &settings);
+        store.remember("The team demo is every second Thursday", "s1", &settings);
+        store.remember("I prefer bullet summaries", "s1", &settings);
+
+        let relevant = store.relevant("when is the next team demo?", 10);
+        assert_eq!(relevant.len(), 1);
+        assert_eq!(relevant[0].fact, "The team demo is every second Thursday");
+        assert_eq!(store.relevant("when is the next team demo?", 1000).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fact_from_one_session_reaches_next_session_prompt() {
+        let settings = MemorySettings::default();
+        let path = std::env::temp_dir().join(format!("arrowhead-memories-{}.json", Uuid::new_v4()));
+
+        // First session: the user mentions their manager, then the session ends
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let mut first = AIConversationEngine::new(Box::new(RecordingClient { prompts: prompts.clone() }));
+        first.send_message("My manager is Sam. Draft a status update for the week.".to_string()).await.unwrap();
+        let mut store = MemoryStore::load(&path).unwrap();
+        assert_eq!(store.remember_session(&first.conversation_id, &first.context.message_history, &settings), 1);
+        store.save(&path).unwrap();
+
+        // Second session: memories are loaded and injected before the first message
+        let store = MemoryStore::load(&path).unwrap();
+        let opening = "Who should I send the weekly status update to?";
+        let mut second = AIConversationEngine::new(Box::new(RecordingClient { prompts: prompts.clone() }));
+        second.context.add_message(store.system_message(opening, settings.token_budget).unwrap());
+        second.send_message(opening.to_string()).await.unwrap();
+
+        let prompts = prompts.lock().unwrap();
+        assert!(!prompts[0].iter().any(|m| m.content.contains("earlier conversations")));
+        let memory = prompts[1].iter().find(|m| matches!(m.role, MessageRole::System)).unwrap();
+        assert!(memory.content.contains("My manager is Sam"));
+        assert_eq!(store.entries[0].source_sessions, vec![first.conversation_id.clone()]);
+        fs::remove_file(&path).ok();
+    }
+}
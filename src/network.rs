@@ -0,0 +1,336 @@
+use anyhow::{bail, Context, Result};
+use reqwest::{Certificate, Client, ClientBuilder, NoProxy, Proxy};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Timeout of the connectivity probe run by `arrowhead doctor`
+const PROBE_TIMEOUT_SECS: u64 = 10;
+
+/// Where a client sends its requests; each has its own `[network.*]` route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    Llm,
+    /// CalDAV servers and coworker availability feeds
+    Calendar,
+    Webhooks,
+    /// The local Obsidian REST API, always reached directly
+    Obsidian,
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Llm => "llm",
+            Self::Calendar => "calendar",
+            Self::Webhooks => "webhooks",
+            Self::Obsidian => "obsidian",
+        })
+    }
+}
+
+/// Proxy and TLS settings for one destination
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteSettings {
+    /// e.g. "https://proxy.corp.example:8443"; unset uses the HTTPS_PROXY/HTTP_PROXY environment
+    pub proxy: Option<String>,
+    pub username: Option<String>,
+    /// Environment variable holding the proxy password
+    pub password_env: Option<String>,
+    /// OS keychain service holding the password of `username` (macOS Keychain, Windows
+    /// Credential Manager, or the Secret Service on Linux)
+    pub password_keychain: Option<String>,
+    /// Command printing the proxy password, run when the keychain has none
+    pub password_command: Option<String>,
+    /// PEM bundle of extra root certificates trusted for this destination
+    pub ca_bundle: Option<String>,
+}
+
+/// `[network]`: per-destination proxies and CA bundles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    pub llm: RouteSettings,
+    pub calendar: RouteSettings,
+    pub webhooks: RouteSettings,
+    /// Hosts that never go through a configured proxy (`no_proxy` syntax)
+    pub no_proxy: Vec<String>,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            llm: RouteSettings::default(),
+            calendar: RouteSettings::default(),
+            webhooks: RouteSettings::default(),
+            no_proxy: vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()],
+        }
+    }
+}
+
+impl NetworkSettings {
+    pub fn route(&self, destination: Destination) -> Option<&RouteSettings> {
+        match destination {
+            Destination::Llm => Some(&self.llm),
+            Destination::Calendar => Some(&self.calendar),
+            Destination::Webhooks => Some(&self.webhooks),
+            Destination::Obsidian => None,
+        }
+    }
+
+    /// How requests to `destination` leave the machine, for `doctor`
+    pub fn describe_route(&self, destination: Destination) -> String {
+        let Some(route) = self.route(destination) else {
+            return "direct".to_string();
+        };
+        let mut description = match (&route.proxy, &route.username) {
+            (Some(proxy), Some(user)) => format!("via proxy {} as {}", proxy, user),
+            (Some(proxy), None) => format!("via proxy {}", proxy),
+            (None, _) => "direct (or HTTPS_PROXY from the environment)".to_string(),
+        };
+        if let Some(bundle) = &route.ca_bundle {
+            description.push_str(&format!(", trusting {}", bundle));
+        }
+        description
+    }
+}
+
+fn proxy_password(route: &RouteSettings, username: &str) -> Result<Option<String>> {
+    if let Some(var) = &route.password_env {
+        return std::env::var(var).map(Some).with_context(|| format!("Proxy password variable {} is not set", var));
+    }
+    if let Some(service) = &route.password_keychain {
+        match (keychain_password(service, username), &route.password_command) {
+            (Ok(password), _) => return Ok(Some(password)),
+            (Err(e), Some(_)) => log::debug!("No proxy password for {} in keychain service {}: {}; running password_command", username, service, e),
+            (Err(e), None) => {
+                return Err(e).with_context(|| format!("No proxy password for {} in keychain service {}", username, service));
+            }
+        }
+    }
+    let Some(command) = &route.password_command else {
+        return Ok(None);
+    };
+    let output = shell(command).output()
+        .with_context(|| format!("Failed to run password_command `{}`", command))?;
+    if !output.status.success() {
+        bail!("password_command `{}` exited with {}", command, output.status);
+    }
+    Ok(Some(String::from_utf8_lossy(&output.stdout).trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// The keychain's password for `username`, looked up once per process. On Linux the
+/// Secret Service client blocks on a runtime of its own, which panics inside the app's
+/// Tokio runtime, so the lookup runs on a thread of its own.
+fn keychain_password(service: &str, username: &str) -> keyring::Result<String> {
+    static FOUND: OnceLock<Mutex<HashMap<(String, String), String>>> = OnceLock::new();
+    let key = (service.to_string(), username.to_string());
+    let found = FOUND.get_or_init(Default::default);
+    if let Some(password) = found.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return Ok(password.clone());
+    }
+    let (thread_service, thread_username) = key.clone();
+    let password = std::thread::spawn(move || keyring::Entry::new(&thread_service, &thread_username)?.get_password())
+        .join()
+        .unwrap_or_else(|_| Err(keyring::Error::PlatformFailure("the keychain lookup panicked".into())))?;
+    found.lock().unwrap_or_else(|e| e.into_inner()).insert(key, password.clone());
+    Ok(password)
+}
+
+fn shell(command: &str) -> Command {
+    let (program, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut shell = Command::new(program);
+    shell.arg(flag).arg(command);
+    shell
+}
+
+/// A client builder for `destination`: its proxy (with credentials and the bypass list)
+/// and extra root certificates. Destinations without a proxy keep reqwest's
+/// environment proxy pickup; Obsidian never uses a proxy.
+pub fn client_builder(settings: &NetworkSettings, destination: Destination) -> Result<ClientBuilder> {
+    let builder = Client::builder();
+    let Some(route) = settings.route(destination) else {
+        return Ok(builder.no_proxy());
+    };
+
+    let mut builder = builder;
+    if let Some(url) = &route.proxy {
+        let mut proxy = Proxy::all(url).with_context(|| format!("Invalid {} proxy URL '{}'", destination, url))?
+            .no_proxy(NoProxy::from_string(&settings.no_proxy.join(",")));
+        if let Some(username) = &route.username {
+            let Some(password) = proxy_password(route, username)? else {
+                bail!("The {} proxy has a username but no password; set password_env, password_keychain or password_command", destination);
+            };
+            proxy = proxy.basic_auth(username, &password);
+        }
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &route.ca_bundle {
+        let pem = fs::read(path).with_context(|| format!("Failed to read CA bundle {}", path))?;
+        let certificates = Certificate::from_pem_bundle(&pem).with_context(|| format!("Invalid CA bundle {}", path))?;
+        if certificates.is_empty() {
+            bail!("CA bundle {} contains no certificates", path);
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    Ok(builder)
+}
+
+pub fn http_client(settings: &NetworkSettings, destination: Destination) -> Result<Client> {
+    client_builder(settings, destination)?.build()
+        .with_context(|| format!("Failed to build the {} HTTP client", destination))
+}
+
+/// Send one request to `url` over the destination's route. Any HTTP response counts as
+/// reachable; only connection, proxy and TLS failures are errors.
+pub async fn probe(settings: &NetworkSettings, destination: Destination, url: &str) -> Result<u16> {
+    let client = client_builder(settings, destination)?
+        .timeout(Duration::from_secs(PROBE_TIMEOUT_SECS))
+        .build()?;
+    let response = client.head(url).send().await?;
+    Ok(response.status().as_u16())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A server answering every request with `body`, recording each request's head
+    async fn serve(body: &'static str) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buffer = vec![0; 4096];
+                let read = socket.read(&mut buffer).await.unwrap_or(0);
+                log.lock().unwrap().push(String::from_utf8_lossy(&buffer[..read]).to_string());
+                let response = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}", body.len(), body);
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (address, seen)
+    }
+
+    async fn get(client: &Client, url: &str) -> String {
+        client.get(url).send().await.unwrap().text().await.unwrap()
+    }
+
+    fn proxied(proxy: &str) -> RouteSettings {
+        RouteSettings { proxy: Some(proxy.to_string()), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_each_destination_uses_its_own_proxy() {
+        let (llm_proxy, llm_seen) = serve("llm proxy").await;
+        let (calendar_proxy, calendar_seen) = serve("calendar proxy").await;
+        let settings = NetworkSettings {
+            llm: proxied(&llm_proxy),
+            calendar: proxied(&calendar_proxy),
+            ..Default::default()
+        };
+
+        let llm = http_client(&settings, Destination::Llm).unwrap();
+        assert_eq!(get(&llm, "http://llm.example.test/v1/models").await, "llm proxy");
+        let calendar = http_client(&settings, Destination::Calendar).unwrap();
+        assert_eq!(get(&calendar, "http://caldav.example.test/").await, "calendar proxy");
+
+        // A proxy receives the absolute URL of the real destination
+        assert!(llm_seen.lock().unwrap()[0].starts_with("GET http://llm.example.test/v1/models"));
+        assert!(calendar_seen.lock().unwrap()[0].starts_with("GET http://caldav.example.test/"));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_credentials_come_from_password_env() {
+        let (proxy, seen) = serve("ok").await;
+        std::env::set_var("ARROWHEAD_TEST_PROXY_PASSWORD", "s3cret");
+        let settings = NetworkSettings {
+            llm: RouteSettings {
+                username: Some("jdoe".to_string()),
+                password_env: Some("ARROWHEAD_TEST_PROXY_PASSWORD".to_string()),
+                ..proxied(&proxy)
+            },
+            ..Default::default()
+        };
+        let client = http_client(&settings, Destination::Llm).unwrap();
+        get(&client, "http://llm.example.test/").await;
+        // base64("jdoe:s3cret")
+        assert!(seen.lock().unwrap()[0].to_lowercase().contains("proxy-authorization: basic amrvztpzm2nyzxq="));
+
+        let missing = NetworkSettings {
+            llm: RouteSettings { password_env: Some("ARROWHEAD_TEST_UNSET_VARIABLE".to_string()), ..settings.llm.clone() },
+            ..Default::default()
+        };
+        assert!(http_client(&missing, Destination::Llm).is_err());
+    }
+
+    #[test]
+    fn test_password_command_is_the_keychain_fallback() {
+        // The mock store starts empty, so every lookup misses
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        let route = RouteSettings {
+            password_keychain: Some("arrowhead-test-proxy".to_string()),
+            password_command: Some("echo s3cret".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(proxy_password(&route, "jdoe").unwrap().as_deref(), Some("s3cret"));
+
+        let keychain_only = RouteSettings { password_command: None, ..route };
+        let err = proxy_password(&keychain_only, "jdoe").unwrap_err();
+        assert!(err.to_string().contains("No proxy password for jdoe in keychain service arrowhead-test-proxy"), "{}", err);
+    }
+
+    #[test]
+    fn test_username_without_a_password_source_is_an_error() {
+        let settings = NetworkSettings {
+            llm: RouteSettings { username: Some("jdoe".to_string()), ..proxied("http://127.0.0.1:9") },
+            ..Default::default()
+        };
+        let err = http_client(&settings, Destination::Llm).unwrap_err();
+        assert!(err.to_string().contains("The llm proxy has a username but no password"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_loopback_bypasses_the_proxy_by_default() {
+        let (proxy, proxy_seen) = serve("proxy").await;
+        let (local, _) = serve("local").await;
+        let settings = NetworkSettings { llm: proxied(&proxy), ..Default::default() };
+
+        let client = http_client(&settings, Destination::Llm).unwrap();
+        assert_eq!(get(&client, &format!("{}/health", local)).await, "local");
+        assert!(proxy_seen.lock().unwrap().is_empty());
+
+        // Obsidian ignores every proxy, configured or not
+        let obsidian = http_client(&NetworkSettings { no_proxy: Vec::new(), ..settings }, Destination::Obsidian).unwrap();
+        assert_eq!(get(&obsidian, &format!("{}/vault/", local)).await, "local");
+        assert!(proxy_seen.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_ca_bundle_and_route_description() {
+        let dir = std::env::temp_dir().join(format!("arrowhead-network-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let bundle = dir.join("empty.pem");
+        fs::write(&bundle, "not a certificate").unwrap();
+        let settings = NetworkSettings {
+            calendar: RouteSettings { ca_bundle: Some(bundle.display().to_string()), ..Default::default() },
+            llm: RouteSettings { username: Some("jdoe".to_string()), ..proxied("http://proxy.corp.test:3128") },
+            ..Default::default()
+        };
+        assert!(http_client(&settings, Destination::Calendar).is_err());
+        assert_eq!(settings.describe_route(Destination::Llm), "via proxy http://proxy.corp.test:3128 as jdoe");
+        assert_eq!(settings.describe_route(Destination::Obsidian), "direct");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
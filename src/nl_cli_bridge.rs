@@ -7,7 +7,7 @@ use crate::conversational_interface::{ConversationalInterface, ConversationalInt
 use crate::nl_command_parser::{NLCommandParser, ParsedCommand, DisambiguationQuestion};
 use crate::obsidian_adapter::ObsidianAdapter;
 use crate::router::route_command;
-use crate::cli::Cli;
+use crate::cli::{Cli, OutputFormat};
 use crate::intelligent_help::{IntelligentHelpSystem, HelpContext, HelpSuggestion, determine_user_level, determine_time_context, create_mock_project_state};
 
 /// Bridge between natural language interface and existing CLI system
@@ -303,7 +303,7 @@ impl NLCLIBridge {
     /// Execute a CLI command using the existing router
     async fn execute_command(&self, command: Commands, adapter: &ObsidianAdapter) -> Result<String> {
         // Create a CLI struct with the command
-        let cli = Cli { command: Some(command) };
+        let cli = Cli { command: Some(command), format: OutputFormat::Text, summary: false, detail: false, resume: false, session: None, offline: false, quiet: false, vault: None, profile: None };
         
         // Execute the command through the existing router
         match route_command(cli, adapter).await {
@@ -415,13 +415,17 @@ impl NLCLIBridge {
                         tags: option.entities.get("tags")
                             .map(|t| t.split(',').map(|s| s.trim().to_string()).collect())
                             .unwrap_or_default(),
+                        estimate: option.entities.get("estimate").cloned(),
                     },
                 }))
             }
             "list_todos" => {
                 Ok(Commands::Todo(crate::cli::TodoArgs {
                     action: crate::cli::TodoAction::List {
-                        status: option.entities.get("status").cloned(),
+                        status: option.entities.get("status").and_then(|s| crate::todo_model::TodoStatus::parse(s)),
+                        due_before: option.entities.get("due_before").cloned(),
+                        overdue: false,
+                        sort: Default::default(),
                     },
                 }))
             }
@@ -451,6 +455,7 @@ impl NLCLIBridge {
                         tags: option.entities.get("tags")
                             .map(|t| t.split(',').map(|s| s.trim().to_string()).collect())
                             .unwrap_or_default(),
+                        suggest_template: false,
                     },
                 }))
             }
@@ -634,7 +639,7 @@ mod tests {
     async fn test_bridge_creation() {
         let llm_client = Box::new(MockLLMClient::new());
         let config = NLBridgeConfig::default();
-        let bridge = NLCLIBridge::new(llm_client, config);
+        let bridge = NLCLIBridge::new(llm_client, Box::new(MockLLMClient::new()), config);
         assert!(bridge.is_ok());
     }
 
@@ -646,7 +651,7 @@ mod tests {
             verbose_feedback: true,
             provide_suggestions: true,
         };
-        let mut bridge = NLCLIBridge::new(llm_client, config.clone()).unwrap();
+        let mut bridge = NLCLIBridge::new(llm_client, Box::new(MockLLMClient::new()), config.clone()).unwrap();
         
         let session_id = bridge.start_session(None).await.unwrap();
         
@@ -663,7 +668,7 @@ mod tests {
     async fn test_disambiguation_choice_parsing() {
         let llm_client = Box::new(MockLLMClient::new());
         let config = NLBridgeConfig::default();
-        let bridge = NLCLIBridge::new(llm_client, config).unwrap();
+        let bridge = NLCLIBridge::new(llm_client, Box::new(MockLLMClient::new()), config).unwrap();
         
         let options = vec![
             crate::nl_command_parser::DisambiguationOption {
@@ -5,7 +5,8 @@ use std::collections::HashMap;
 use uuid::Uuid;
 
 use crate::ai_conversation::{LLMClient, Message, MessageRole};
-use crate::cli::{Commands, GoalAction, GoalArgs, NoteAction, NoteArgs, TodoAction, TodoArgs};
+use crate::cli::{CalendarAction, CalendarArgs, Commands, GoalAction, GoalArgs, NoteAction, NoteArgs, TodoAction, TodoArgs};
+use crate::todo_model::{TodoSort, TodoStatus};
 
 /// Represents a parsed natural language command
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,6 +71,41 @@ pub struct AlternativeInterpretation {
     pub reason: String,
 }
 
+/// An intent as the model sends it; only `intent` is required
+#[derive(Debug, Deserialize)]
+struct IntentReply {
+    intent: String,
+    #[serde(default, deserialize_with = "crate::llm_json::null_as_default")]
+    entities: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    confidence: Option<f32>,
+    #[serde(default, deserialize_with = "crate::llm_json::null_as_default")]
+    alternatives: Vec<AlternativeReply>,
+    #[serde(default)]
+    needs_disambiguation: Option<bool>,
+}
+
+/// Alternatives without an intent are dropped
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AlternativeReply {
+    intent: Option<String>,
+    #[serde(deserialize_with = "crate::llm_json::null_as_default")]
+    entities: HashMap<String, serde_json::Value>,
+    confidence: Option<f32>,
+    reason: Option<String>,
+}
+
+/// Keep the entities given as text; the rest can't be passed on as command arguments
+fn string_entities(entities: HashMap<String, serde_json::Value>) -> HashMap<String, String> {
+    entities.into_iter()
+        .filter_map(|(name, value)| match value {
+            serde_json::Value::String(text) => Some((name, text)),
+            _ => None,
+        })
+        .collect()
+}
+
 /// Disambiguation question to ask the user
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DisambiguationQuestion {
@@ -184,6 +220,7 @@ impl NLCommandParser {
                 description: "Mark a todo as done".to_string(),
                 examples: vec![
                     "Mark todo 123 as done".to_string(),
+                    "Mark the project review task as done".to_string(),
                     "Complete the task about calling client".to_string(),
                     "I finished the project task".to_string(),
                     "Check off item 5".to_string(),
@@ -299,6 +336,25 @@ impl NLCommandParser {
                 required_entities: vec!["note_id".to_string()],
                 optional_entities: vec![],
             },
+            // Calendar intents
+            CommandIntent {
+                name: "quick_event".to_string(),
+                description: "Create a calendar event from a saved event template".to_string(),
+                examples: vec![
+                    "Set up my usual 1:1 with Alice".to_string(),
+                    "Book a focus block tomorrow morning".to_string(),
+                    "Schedule gym at 6pm".to_string(),
+                ],
+                required_entities: vec!["template".to_string()],
+                optional_entities: vec![
+                    "at".to_string(),
+                    "title".to_string(),
+                    "duration".to_string(),
+                    "attendees".to_string(),
+                    "location".to_string(),
+                    "name".to_string(),
+                ],
+            },
             // Chat intent
             CommandIntent {
                 name: "start_chat".to_string(),
@@ -458,13 +514,18 @@ impl NLCommandParser {
                         description,
                         due_date,
                         tags,
+                        estimate: parsed.entities.get("estimate").cloned(),
                     },
                 }))
             }
             "list_todos" => {
-                let status = parsed.entities.get("status").cloned();
                 Ok(Commands::Todo(TodoArgs {
-                    action: TodoAction::List { status },
+                    action: TodoAction::List {
+                        status: parsed.entities.get("status").and_then(|s| TodoStatus::parse(s)),
+                        due_before: parsed.entities.get("due_before").cloned(),
+                        overdue: false,
+                        sort: TodoSort::default(),
+                    },
                 }))
             }
             "complete_todo" => {
@@ -528,6 +589,7 @@ impl NLCommandParser {
                         description,
                         status,
                         target_date,
+                        force: false,
                     },
                 }))
             }
@@ -555,6 +617,7 @@ impl NLCommandParser {
                         title,
                         content,
                         tags,
+                        suggest_template: false,
                     },
                 }))
             }
@@ -586,7 +649,7 @@ impl NLCommandParser {
                     .clone();
 
                 Ok(Commands::Note(NoteArgs {
-                    action: NoteAction::Append { name_or_id, content },
+                    action: NoteAction::Append { name_or_id, content, heading: None },
                 }))
             }
             "edit_note" => {
@@ -595,7 +658,36 @@ impl NLCommandParser {
                     .clone();
                 
                 Ok(Commands::Note(NoteArgs {
-                    action: NoteAction::Edit { name_or_id },
+                    action: NoteAction::Edit { name_or_id, force: false },
+                }))
+            }
+            "quick_event" => {
+                let template = parsed.entities.get("template")
+                    .ok_or_else(|| anyhow::anyhow!("Missing required entity: template"))?
+                    .clone();
+
+                // Any entity that is not an event field is a template variable, e.g. "name"
+                const EVENT_FIELDS: &[&str] = &["template", "at", "title", "duration", "attendees", "location"];
+                let mut vars: Vec<String> = parsed.entities.iter()
+                    .filter(|(key, _)| !EVENT_FIELDS.contains(&key.as_str()))
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect();
+                vars.sort();
+
+                Ok(Commands::Calendar(CalendarArgs {
+                    action: CalendarAction::Quick {
+                        template,
+                        vars,
+                        at: parsed.entities.get("at").cloned(),
+                        title: parsed.entities.get("title").cloned(),
+                        duration: parsed.entities.get("duration")
+                            .and_then(|d| d.trim().trim_end_matches("min").trim().parse().ok()),
+                        attendees: parsed.entities.get("attendees")
+                            .map(|a| a.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+                            .unwrap_or_default(),
+                        location: parsed.entities.get("location").cloned(),
+                        force: false,
+                    },
                 }))
             }
             "start_chat" => {
@@ -660,87 +752,28 @@ impl NLCommandParser {
 
     /// Parse AI response into ParsedCommand
     fn parse_ai_response(&self, response: &str, original_input: &str) -> Result<ParsedCommand> {
-        // Extract JSON from response
-        let json_start = response.find('{').unwrap_or(0);
-        let json_end = response.rfind('}').map(|i| i + 1).unwrap_or(response.len());
-        let json_str = &response[json_start..json_end];
-        
-        let parsed: serde_json::Value = serde_json::from_str(json_str)
+        let parsed: IntentReply = crate::llm_json::parse(response)
             .context("Failed to parse AI response as JSON")?;
-        
-        let intent = parsed.get("intent")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Missing intent in AI response"))?
-            .to_string();
-        
-        let entities = parsed.get("entities")
-            .and_then(|v| v.as_object())
-            .map(|obj| {
-                obj.iter()
-                    .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
-                    .collect()
-            })
-            .unwrap_or_default();
-        
-        let confidence = parsed.get("confidence")
-            .and_then(|v| v.as_f64())
-            .unwrap_or(0.5) as f32;
-        
-        let alternatives = parsed.get("alternatives")
-            .and_then(|v| v.as_array())
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|alt| self.parse_alternative(alt).ok())
-                    .collect()
-            })
-            .unwrap_or_default();
-        
-        let needs_disambiguation = parsed.get("needs_disambiguation")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(confidence < self.config.min_confidence_threshold);
-        
+
+        let confidence = parsed.confidence.unwrap_or(0.5);
+        let alternatives = parsed.alternatives.into_iter()
+            .filter_map(|alt| Some(AlternativeInterpretation {
+                intent: alt.intent?,
+                entities: string_entities(alt.entities),
+                confidence: alt.confidence.unwrap_or(0.5),
+                reason: alt.reason.unwrap_or_else(|| "Alternative interpretation".to_string()),
+            }))
+            .collect();
+
         Ok(ParsedCommand {
-            intent,
-            entities,
+            intent: parsed.intent,
+            entities: string_entities(parsed.entities),
             confidence,
             original_input: original_input.to_string(),
             parsed_at: Utc::now(),
             id: Uuid::new_v4().to_string(),
             alternatives,
-            needs_disambiguation,
-        })
-    }
-
-    /// Parse alternative interpretation from JSON
-    fn parse_alternative(&self, alt_json: &serde_json::Value) -> Result<AlternativeInterpretation> {
-        let intent = alt_json.get("intent")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Missing intent in alternative"))?
-            .to_string();
-        
-        let entities = alt_json.get("entities")
-            .and_then(|v| v.as_object())
-            .map(|obj| {
-                obj.iter()
-                    .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
-                    .collect()
-            })
-            .unwrap_or_default();
-        
-        let confidence = alt_json.get("confidence")
-            .and_then(|v| v.as_f64())
-            .unwrap_or(0.5) as f32;
-        
-        let reason = alt_json.get("reason")
-            .and_then(|v| v.as_str())
-            .unwrap_or("Alternative interpretation")
-            .to_string();
-        
-        Ok(AlternativeInterpretation {
-            intent,
-            entities,
-            confidence,
-            reason,
+            needs_disambiguation: parsed.needs_disambiguation.unwrap_or(confidence < self.config.min_confidence_threshold),
         })
     }
 
@@ -760,6 +793,7 @@ impl NLCommandParser {
             "view_note" => "View note details",
             "append_note" => "Add to existing note",
             "edit_note" => "Edit a note",
+            "quick_event" => "Create an event from a template",
             "start_chat" => "Start a chat session",
             _ => intent,
         }.to_string()
@@ -1012,7 +1046,7 @@ mod tests {
         match cli_command {
             Commands::Todo(todo_args) => {
                 match todo_args.action {
-                    TodoAction::Add { description, due_date, tags } => {
+                    TodoAction::Add { description, due_date, tags, .. } => {
                         assert_eq!(description, "test task");
                         assert_eq!(due_date, Some("tomorrow".to_string()));
                         assert_eq!(tags, vec!["work", "urgent"]);
@@ -1024,6 +1058,44 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_to_cli_command_quick_event() {
+        let mock_client = Box::new(MockLLMClient::new());
+        let parser = NLCommandParser::new(mock_client);
+        
+        let mut entities = HashMap::new();
+        entities.insert("template".to_string(), "1:1".to_string());
+        entities.insert("name".to_string(), "Alice".to_string());
+        entities.insert("at".to_string(), "tomorrow 2pm".to_string());
+        entities.insert("duration".to_string(), "45 min".to_string());
+        
+        let parsed = ParsedCommand {
+            intent: "quick_event".to_string(),
+            entities,
+            confidence: 0.9,
+            original_input: "Set up my usual 1:1 with Alice tomorrow at 2pm for 45 minutes".to_string(),
+            parsed_at: Utc::now(),
+            id: "test".to_string(),
+            alternatives: Vec::new(),
+            needs_disambiguation: false,
+        };
+        
+        match parser.to_cli_command(&parsed).unwrap() {
+            Commands::Calendar(calendar_args) => {
+                match calendar_args.action {
+                    CalendarAction::Quick { template, vars, at, duration, .. } => {
+                        assert_eq!(template, "1:1");
+                        assert_eq!(vars, vec!["name=Alice"]);
+                        assert_eq!(at, Some("tomorrow 2pm".to_string()));
+                        assert_eq!(duration, Some(45));
+                    }
+                    _ => panic!("Expected CalendarAction::Quick"),
+                }
+            }
+            _ => panic!("Expected Commands::Calendar"),
+        }
+    }
+
     #[tokio::test]
     async fn test_to_cli_command_list_todos() {
         let mock_client = Box::new(MockLLMClient::new());
@@ -1048,8 +1120,8 @@ mod tests {
         match cli_command {
             Commands::Todo(todo_args) => {
                 match todo_args.action {
-                    TodoAction::List { status } => {
-                        assert_eq!(status, Some("open".to_string()));
+                    TodoAction::List { status, .. } => {
+                        assert_eq!(status, Some(TodoStatus::Open));
                     }
                     _ => panic!("Expected TodoAction::List"),
                 }
@@ -1084,7 +1156,7 @@ mod tests {
         match cli_command {
             Commands::Note(note_args) => {
                 match note_args.action {
-                    NoteAction::Create { title, content, tags } => {
+                    NoteAction::Create { title, content, tags, .. } => {
                         assert_eq!(title, "Meeting Notes");
                         assert_eq!(content, Some("Discussed project timeline".to_string()));
                         assert_eq!(tags, vec!["meeting", "project"]);
@@ -1239,6 +1311,24 @@ mod tests {
         assert!(!resolved.needs_disambiguation);
     }
 
+    #[test]
+    fn test_parse_ai_response_tolerates_messy_replies() {
+        let parser = NLCommandParser::new(Box::new(MockLLMClient::new()));
+        let reply = "Sure, here you go:\n```json\n{'intent': 'add_todo', 'entities': {'description': 'call mom', 'priority': 2,}, 'confidence': 0.6,\n 'alternatives': [{'entities': {}}, {'intent': 'create_note', 'confidence': 0.3}],}\n```\nLet me know {if} that helps.";
+        let parsed = parser.parse_ai_response(reply, "todo call mom").unwrap();
+        assert_eq!(parsed.intent, "add_todo");
+        assert_eq!(parsed.entities.len(), 1);
+        assert_eq!(parsed.entities.get("description").unwrap(), "call mom");
+        assert!(parsed.needs_disambiguation);
+        // The alternative without an intent is dropped
+        assert_eq!(parsed.alternatives.len(), 1);
+        assert_eq!(parsed.alternatives[0].intent, "create_note");
+        assert_eq!(parsed.alternatives[0].reason, "Alternative interpretation");
+
+        let err = parser.parse_ai_response("{\"entities\": {}}", "hm").unwrap_err();
+        assert!(format!("{:#}", err).contains("missing field `intent`"));
+    }
+
     #[test]
     fn test_calculate_similarity() {
         let mock_client = Box::new(MockLLMClient::new());
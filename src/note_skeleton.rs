@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+/// Simhashes within this many differing bits are treated as near-duplicates
+pub const DEFAULT_SIMHASH_DISTANCE: u32 = 3;
+
+/// Bands the 64-bit simhash is split into for candidate lookup. With 4 bands of 16 bits,
+/// any two hashes within 3 bits of each other agree exactly on at least one band.
+const SIMHASH_BANDS: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkeletonHeading {
+    pub level: u8,
+    /// Lowercased heading text with punctuation and extra whitespace removed
+    pub text: String,
+}
+
+/// Cheap structural fingerprint of a note, computed without any LLM calls
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteSkeleton {
+    pub headings: Vec<SkeletonHeading>,
+    pub checkboxes: usize,
+    pub code_blocks: usize,
+    pub tables: usize,
+    pub links: usize,
+    /// Sorted frontmatter field names
+    pub frontmatter_fields: Vec<String>,
+    /// Simhash over the normalized body text
+    pub simhash: u64,
+}
+
+impl NoteSkeleton {
+    /// Key shared by notes with the same outline and frontmatter shape. Empty when the
+    /// note has no headings, since "no structure" says nothing about similarity.
+    pub fn structure_key(&self) -> String {
+        if self.headings.is_empty() {
+            return String::new();
+        }
+        let outline: Vec<String> = self.headings.iter().map(|h| format!("h{}:{}", h.level, h.text)).collect();
+        format!("{}|{}", outline.join("/"), self.frontmatter_fields.join(","))
+    }
+
+    /// Hamming distance between the two notes' simhashes
+    pub fn distance(&self, other: &NoteSkeleton) -> u32 {
+        hamming_distance(self.simhash, other.simhash)
+    }
+}
+
+impl fmt::Display for NoteSkeleton {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Headings:")?;
+        if self.headings.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for heading in &self.headings {
+            writeln!(f, "  {}{} {}", "  ".repeat(heading.level.saturating_sub(1) as usize), "#".repeat(heading.level as usize), heading.text)?;
+        }
+        writeln!(f, "Checkboxes: {}", self.checkboxes)?;
+        writeln!(f, "Code blocks: {}", self.code_blocks)?;
+        writeln!(f, "Tables: {}", self.tables)?;
+        writeln!(f, "Links: {}", self.links)?;
+        let fields = if self.frontmatter_fields.is_empty() { "(none)".to_string() } else { self.frontmatter_fields.join(", ") };
+        writeln!(f, "Frontmatter fields: {}", fields)?;
+        write!(f, "Simhash: {:016x}", self.simhash)
+    }
+}
+
+/// Compute the structural skeleton of a markdown note
+pub fn skeleton(content: &str) -> NoteSkeleton {
+    let (frontmatter_fields, body) = split_frontmatter(content);
+
+    let mut headings = Vec::new();
+    let mut checkboxes = 0;
+    let mut code_blocks = 0;
+    let mut tables = 0;
+    let mut links = 0;
+    let mut in_code = false;
+    let mut in_table = false;
+    let mut prose = String::new();
+
+    for line in body.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            if !in_code {
+                code_blocks += 1;
+            }
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+
+        let is_table_row = trimmed.starts_with('|') && trimmed.len() > 1 && trimmed.ends_with('|');
+        if is_table_row && !in_table {
+            tables += 1;
+        }
+        in_table = is_table_row;
+
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            headings.push(SkeletonHeading { level: level as u8, text: normalize_text(&trimmed[level..]) });
+            continue;
+        }
+
+        let item = trimmed.trim_start_matches(['-', '*', '+']).trim_start();
+        if item.len() < trimmed.len() && (item.starts_with("[ ]") || item.starts_with("[x]") || item.starts_with("[X]")) {
+            checkboxes += 1;
+        }
+
+        links += trimmed.matches("[[").count() + trimmed.matches("](").count();
+        prose.push_str(trimmed);
+        prose.push('\n');
+    }
+
+    NoteSkeleton {
+        headings,
+        checkboxes,
+        code_blocks,
+        tables,
+        links,
+        frontmatter_fields,
+        simhash: simhash(&prose),
+    }
+}
+
+/// 64-bit simhash over word unigrams and bigrams of the normalized text.
+/// Small edits flip few bits; rewriting the note flips about half of them.
+pub fn simhash(text: &str) -> u64 {
+    let normalized = normalize_text(text);
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    if words.is_empty() {
+        return 0;
+    }
+
+    let mut weights = [0i64; 64];
+    let mut add = |feature: &str| {
+        let hash = stable_hash(feature);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    };
+
+    for word in &words {
+        add(word);
+    }
+    for pair in words.windows(2) {
+        add(&format!("{} {}", pair[0], pair[1]));
+    }
+
+    weights.iter().enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0u64, |hash, (bit, _)| hash | (1 << bit))
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Index pairs whose simhashes are within `max_distance` bits. Uses band bucketing so
+/// only notes sharing a 16-bit band are compared; exact for `max_distance` < 4.
+pub fn near_duplicate_pairs(simhashes: &[u64], max_distance: u32) -> Vec<(usize, usize)> {
+    let band_bits = 64 / SIMHASH_BANDS;
+    let mut candidates = HashSet::new();
+
+    for band in 0..SIMHASH_BANDS {
+        let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (index, hash) in simhashes.iter().enumerate() {
+            let key = (hash >> (band * band_bits)) & ((1 << band_bits) - 1);
+            buckets.entry(key).or_default().push(index);
+        }
+        for bucket in buckets.values().filter(|b| b.len() > 1) {
+            for (i, &a) in bucket.iter().enumerate() {
+                for &b in &bucket[i + 1..] {
+                    candidates.insert((a.min(b), a.max(b)));
+                }
+            }
+        }
+    }
+
+    let mut pairs: Vec<(usize, usize)> = candidates.into_iter()
+        .filter(|&(a, b)| hamming_distance(simhashes[a], simhashes[b]) <= max_distance)
+        .collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+fn split_frontmatter(content: &str) -> (Vec<String>, &str) {
+    let Some(rest) = content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")) else {
+        return (Vec::new(), content);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (Vec::new(), content);
+    };
+
+    let fields: BTreeSet<String> = match serde_yaml::from_str::<serde_yaml::Value>(&rest[..end]) {
+        Ok(serde_yaml::Value::Mapping(map)) => map.keys().filter_map(|k| k.as_str().map(str::to_string)).collect(),
+        _ => BTreeSet::new(),
+    };
+    let body = rest[end + 4..].trim_start_matches(['-', '\r', '\n']);
+    (fields.into_iter().collect(), body)
+}
+
+fn normalize_text(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// FNV-1a, used instead of `DefaultHasher` for anything persisted (simhashes, plan
+/// content hashes) because it must stay stable across Rust releases
+pub fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEETING: &str = "---\ntags: [meeting]\ndate: 2024-05-01\n---\n# Weekly Sync\n\n## Attendees\n- Alice\n- Bob\n\n## Notes\nWe reviewed the roadmap for the next quarter and agreed to ship the importer first. The search work moves to June because the indexing changes need another review pass. See [[Roadmap]] and [spec](https://example.com).\n\n## Action Items\n- [ ] Alice drafts the importer plan\n- [x] Bob books the design review\n\n| Owner | Task |\n|---|---|\n| Alice | Plan |\n\n```rust\nfn main() {}\n```\n";
+
+    #[test]
+    fn test_skeleton_structure() {
+        let skeleton = skeleton(MEETING);
+        let outline: Vec<(u8, &str)> = skeleton.headings.iter().map(|h| (h.level, h.text.as_str())).collect();
+        assert_eq!(outline, vec![(1, "weekly sync"), (2, "attendees"), (2, "notes"), (2, "action items")]);
+        assert_eq!(skeleton.checkboxes, 2);
+        assert_eq!(skeleton.code_blocks, 1);
+        assert_eq!(skeleton.tables, 1);
+        assert_eq!(skeleton.links, 2);
+        assert_eq!(skeleton.frontmatter_fields, vec!["date", "tags"]);
+        assert_eq!(
+            skeleton.structure_key(),
+            "h1:weekly sync/h2:attendees/h2:notes/h2:action items|date,tags"
+        );
+    }
+
+    #[test]
+    fn test_simhash_robust_to_small_edits() {
+        let original = skeleton(MEETING).simhash;
+        let typo = skeleton(&MEETING.replace("agreed to ship", "agreed to shipp")).simhash;
+        let extra_sentence = skeleton(&MEETING.replace("another review pass.", "another review pass. Carol joins next week.")).simhash;
+
+        assert!(hamming_distance(original, typo) <= DEFAULT_SIMHASH_DISTANCE, "typo moved {} bits", hamming_distance(original, typo));
+        assert!(hamming_distance(original, extra_sentence) <= 8, "sentence moved {} bits", hamming_distance(original, extra_sentence));
+    }
+
+    #[test]
+    fn test_simhash_sensitive_to_large_changes() {
+        let original = skeleton(MEETING).simhash;
+        let rewritten = skeleton("# Grocery list\n\nApples, oranges, bread and a dozen eggs for the weekend brunch. Remember the coffee beans from the market on Saturday morning.\n").simhash;
+        assert!(hamming_distance(original, rewritten) > 16, "rewrite moved only {} bits", hamming_distance(original, rewritten));
+    }
+
+    #[test]
+    fn test_near_duplicate_pairs_matches_brute_force() {
+        let hashes = [
+            0x0000_0000_0000_0000,
+            0x0000_0000_0000_0007, // 3 bits from [0]
+            0x0001_0001_0001_0001, // 4 bits from [0], one per band
+            0xffff_ffff_ffff_ffff,
+            0xffff_ffff_ffff_fffe, // 1 bit from [3]
+        ];
+        assert_eq!(near_duplicate_pairs(&hashes, 3), vec![(0, 1), (3, 4)]);
+    }
+}
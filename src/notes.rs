@@ -1,8 +1,21 @@
-use anyhow::{Result, Context};
-use crate::cli::{NoteAction, NoteArgs};
-use crate::obsidian_adapter::ObsidianAdapter;
+use anyhow::{bail, Result, Context};
+use crate::ai_conversation::create_llm_client;
+use crate::calendar::parse_template_vars;
+use crate::cli::{NoteAction, NoteArgs, OutputFormat};
+use crate::config::Config;
+use crate::confirm::{confirm, Operation, OperationClass, PromptReader, StdinPrompt};
+use crate::note_skeleton;
+use crate::template_values::TemplateValues;
+use crate::obsidian_adapter::{
+    extracted_template_values, template_placeholders, template_takes_body, with_force_hint, NoteTemplate, ObsidianAdapter, PatchOperation, PatchTarget,
+    TemplatePlaceholder,
+};
+use crate::output::{print_json, NoteRecord};
+use crate::usage::UsagePurpose;
 use crate::utils::slugify; // Import slugify from utils
+use crate::vault_transaction::{VaultStore, VaultTransaction, DEFAULT_READ_CONCURRENCY};
 use serde::Serialize; // For serializing frontmatter
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::process::Command;
@@ -12,13 +25,30 @@ struct NoteFrontmatter {
     tags: Option<Vec<String>>,
 }
 
-pub async fn handle_note_command(args: NoteArgs, adapter: &ObsidianAdapter) -> Result<()> {
+pub async fn handle_note_command(args: NoteArgs, format: OutputFormat, adapter: &ObsidianAdapter) -> Result<()> {
     let notes_dir = "Notes"; // Define a base directory for notes
 
     match args.action {
-        NoteAction::Create { title, content, tags } => {
+        NoteAction::Create { title, content, tags, suggest_template } => {
             println!("Attempting to create note: '{}'", title);
 
+            let note_body = content.unwrap_or_default();
+            if suggest_template {
+                let config = Config::load().unwrap_or_default();
+                let mut templates = ObsidianAdapter::new(Some(config.obsidian.base_url.clone()), config.obsidian.api_key.clone());
+                templates.set_cache_settings(&config.cache);
+                templates.load_template_database()?;
+                if let Some((template, full_content)) = content_from_template(&templates, &title, &note_body, &tags, &mut StdinPrompt)? {
+                    let file_name = format!("{}/{}.md", notes_dir, note_slug(&title));
+                    adapter.create_file(&file_name, &full_content).await
+                        .context(format!("Failed to create note file '{}'", file_name))?;
+                    templates.update_template_usage(&template.id, None)?;
+                    println!("Note '{}' created from template '{}' as '{}'.", title, template.name, file_name);
+                    crate::script::publish_output(serde_json::json!({ "path": file_name, "template": template.id }));
+                    return Ok(());
+                }
+            }
+
             let fm_tags = if tags.is_empty() { None } else { Some(tags.clone()) };
             let frontmatter = NoteFrontmatter {
                 tags: fm_tags,
@@ -27,18 +57,8 @@ pub async fn handle_note_command(args: NoteArgs, adapter: &ObsidianAdapter) -> R
             let fm_yaml = serde_yaml::to_string(&frontmatter)
                 .context("Failed to serialize note frontmatter to YAML")?;
 
-            let note_body = content.unwrap_or_default();
             let full_content = format!("---\n{}---\n\n{}", fm_yaml.trim(), note_body);
-
-            let slug = slugify(&title);
-            let max_slug_len = 50; // Keep consistent with todos
-            let truncated_slug = if slug.len() > max_slug_len {
-                slug.chars().take(max_slug_len).collect()
-            } else {
-                slug
-            };
-
-            let file_name = format!("{}/{}.md", notes_dir, truncated_slug);
+            let file_name = format!("{}/{}.md", notes_dir, note_slug(&title));
 
             adapter.create_file(&file_name, &full_content).await
                 .context(format!("Failed to create note file '{}'", file_name))?;
@@ -46,6 +66,9 @@ pub async fn handle_note_command(args: NoteArgs, adapter: &ObsidianAdapter) -> R
             println!("Note '{}' created as '{}'.", title, file_name);
         }
         NoteAction::List { tags } => {
+            if format == OutputFormat::Json {
+                return print_json(&note_records(adapter, notes_dir, &tags).await?);
+            }
             println!("Listing notes...");
             if !tags.is_empty() {
                 println!("  Tags filter: {:?}", tags);
@@ -79,19 +102,7 @@ pub async fn handle_note_command(args: NoteArgs, adapter: &ObsidianAdapter) -> R
                                 let note_title = extract_note_title(&content, &file);
                                 let note_preview = extract_note_preview(&content);
                                 
-                                // Apply tags filter if provided
-                                let should_show = if !tags.is_empty() {
-                                    // Check if any of the requested tags match the note's tags
-                                    tags.iter().any(|filter_tag| {
-                                        note_tags.iter().any(|note_tag| {
-                                            note_tag.to_lowercase().contains(&filter_tag.to_lowercase())
-                                        })
-                                    })
-                                } else {
-                                    true
-                                };
-                                
-                                if should_show {
+                                if matches_tags(&note_tags, &tags) {
                                     found_notes += 1;
                                     
                                     // Extract the file name without extension for display
@@ -136,31 +147,43 @@ pub async fn handle_note_command(args: NoteArgs, adapter: &ObsidianAdapter) -> R
         NoteAction::View { name_or_id } => {
             // Assume name_or_id is the slugified filename part
             let file_name = format!("{}/{}.md", notes_dir, slugify(&name_or_id));
-            println!("Viewing note: '{}'", file_name);
-
             let file_content = adapter.get_file(&file_name).await
                 .context(format!("Failed to retrieve note '{}' for viewing.", file_name))?;
+            if format == OutputFormat::Json {
+                return print_json(&note_record(&file_name, &file_content).with_content(&file_content));
+            }
+
+            println!("Viewing note: '{}'", file_name);
 
             println!("--- Content of {} ---", file_name);
             println!("{}", file_content);
             println!("--- End of Content ---");
         }
-        NoteAction::Append { name_or_id, content } => {
+        NoteAction::Append { name_or_id, content, heading } => {
             let file_name = format!("{}/{}.md", notes_dir, slugify(&name_or_id));
             println!("Appending to note '{}': '{}'", file_name, content.chars().take(50).collect::<String>() + "...");
 
-            let mut current_content = adapter.get_file(&file_name).await
-                .context(format!("Failed to retrieve note '{}' for appending.", file_name))?;
-
-            current_content.push_str("\n\n"); // Add some spacing
-            current_content.push_str(&content);
-
-            adapter.update_file(&file_name, &current_content).await
-                .context(format!("Failed to append content to note '{}'.", file_name))?;
+            // Only the new text is sent, so edits made in Obsidian meanwhile are kept
+            match &heading {
+                Some(heading) => {
+                    let target = PatchTarget::Heading(heading.clone());
+                    adapter.patch_file(&file_name, &target, &format!("{}\n", content), PatchOperation::Append).await
+                }
+                None => {
+                    if adapter.get_file_if_exists(&file_name).await?.is_none() {
+                        bail!("Note '{}' does not exist", file_name);
+                    }
+                    adapter.append_file(&file_name, &format!("\n\n{}", content)).await
+                }
+            }
+            .context(format!("Failed to append content to note '{}'.", file_name))?;
 
-            println!("Content appended to note '{}'.", file_name);
+            match heading {
+                Some(heading) => println!("Content appended under '{}' in note '{}'.", heading, file_name),
+                None => println!("Content appended to note '{}'.", file_name),
+            }
         }
-        NoteAction::Edit { name_or_id } => {
+        NoteAction::Edit { name_or_id, force } => {
             println!("Editing note: '{}'", name_or_id);
             
             // Find the note file
@@ -182,7 +205,12 @@ pub async fn handle_note_command(args: NoteArgs, adapter: &ObsidianAdapter) -> R
                 Ok(Some(new_content)) => {
                     // Only update if content changed
                     if new_content != current_content {
-                        adapter.update_file(&file_name, &new_content).await
+                        let saved = if force {
+                            adapter.overwrite_file(&file_name, &new_content).await
+                        } else {
+                            adapter.update_file(&file_name, &new_content).await
+                        };
+                        saved.map_err(with_force_hint)
                             .context(format!("Failed to save edited note '{}'", file_name))?;
                         println!("Note '{}' updated successfully.", file_name);
                     } else {
@@ -198,15 +226,281 @@ pub async fn handle_note_command(args: NoteArgs, adapter: &ObsidianAdapter) -> R
                 }
             }
         }
+        NoteAction::Rename { name_or_id, new_title, continue_on_error, yes } => {
+            // Accept either the exact file name (like `edit`) or a title (like `view`)
+            let exact_name = name_or_id.trim_end_matches(".md").to_string();
+            let (old_name, content) = match adapter.read_file(&format!("{}/{}.md", notes_dir, exact_name)).await? {
+                Some(content) => (exact_name, content),
+                None => {
+                    let slug = slugify(&exact_name);
+                    let content = adapter.read_file(&format!("{}/{}.md", notes_dir, slug)).await?
+                        .ok_or_else(|| anyhow::anyhow!("Note '{}' not found in {}/", name_or_id, notes_dir))?;
+                    (slug, content)
+                }
+            };
+            let new_name = slugify(&new_title);
+            let old_path = format!("{}/{}.md", notes_dir, old_name);
+            let new_path = format!("{}/{}.md", notes_dir, new_name);
+
+            if old_name == new_name {
+                println!("Note '{}' already has that name.", old_path);
+                return Ok(());
+            }
+
+            if adapter.read_file(&new_path).await?.is_some() {
+                return Err(anyhow::anyhow!("Cannot rename: '{}' already exists", new_path));
+            }
+
+            let mut transaction = VaultTransaction::new(&format!("rename {} to {}", old_path, new_path))
+                .continue_on_error(continue_on_error)
+                .write(&new_path, &content);
+
+            // Rewrite backlinks in every folder Arrowhead manages
+            let mut linking_candidates = Vec::new();
+            for folder in ["Notes", "Todos", "Goals"] {
+                let files = match adapter.list_files_in_folder(folder).await {
+                    Ok(files) => files,
+                    Err(_) => continue,
+                };
+                linking_candidates.extend(
+                    files.iter()
+                        .filter(|f| f.ends_with(".md"))
+                        .map(|file| format!("{}/{}", folder, file))
+                        .filter(|path| *path != old_path),
+                );
+            }
+            for (path, file_content) in adapter.fetch_many(&linking_candidates, DEFAULT_READ_CONCURRENCY).await {
+                let file_content = file_content
+                    .context(format!("Failed to read '{}' while collecting backlinks", path))?;
+                if let Some(rewritten) = rewrite_wikilinks(&file_content, &old_name, &new_name) {
+                    transaction = transaction.write(&path, &rewritten);
+                }
+            }
+
+            transaction = transaction.delete(&old_path);
+            let backlinks = transaction.planned_writes().len() - 2;
+            let operation = Operation::new(
+                OperationClass::Move,
+                format!("Rename '{}' to '{}' and update {} linking file(s)", old_path, new_path, backlinks),
+                backlinks + 1,
+            );
+            if !confirm(&Config::load().unwrap_or_default().confirmations, &operation, yes)? {
+                println!("Nothing renamed.");
+                return Ok(());
+            }
+            println!("Renaming '{}' to '{}' ({} linking file(s) to update)...", old_path, new_path, backlinks);
+
+            let report = transaction.execute(adapter).await?;
+            if let Err(e) = report.append_to_log(adapter.transaction_log()) {
+                eprintln!("Warning: could not record transaction: {}", e);
+            }
+
+            println!("{}", report.summary());
+            if !report.is_committed() {
+                return Err(anyhow::anyhow!("Rename of '{}' did not complete", old_path));
+            }
+        }
+        NoteAction::Skeleton { path } => {
+            let file_name = if path.contains('/') || path.ends_with(".md") {
+                if path.ends_with(".md") { path } else { format!("{}.md", path) }
+            } else {
+                format!("{}/{}.md", notes_dir, slugify(&path))
+            };
+
+            let file_content = adapter.get_file(&file_name).await
+                .context(format!("Failed to retrieve note '{}'", file_name))?;
+
+            println!("Skeleton of {}:", file_name);
+            println!("{}", note_skeleton::skeleton(&file_content));
+        }
+        NoteAction::New { template, to, set } => {
+            let config = Config::load().unwrap_or_default();
+            let base_url = Some(config.obsidian.base_url.clone());
+            let mut templates = match create_llm_client(&config, UsagePurpose::Suggestions) {
+                Ok(llm_client) => ObsidianAdapter::with_ai_client(base_url, config.obsidian.api_key.clone(), llm_client, None),
+                Err(_) => ObsidianAdapter::new(base_url, config.obsidian.api_key.clone()),
+            };
+            templates.set_cache_settings(&config.cache);
+            templates.load_template_database()?;
+            let chosen = templates.find_template(&template).cloned()
+                .ok_or_else(|| anyhow::anyhow!("No template with id or name '{}'; see `arrowhead template export` for what is saved", template))?;
+
+            let filled = fill_placeholders(&template_placeholders(&chosen), parse_template_vars(&set)?, &mut StdinPrompt)?;
+            let mut values = TemplateValues::from(filled);
+            for (name, items) in repeated_values(&set) {
+                values.set(name, items);
+            }
+            let suggestions = templates.resolve_ai_suggestions(&chosen, &values).await;
+            let content = templates.render_template_with_suggestions(&chosen, &values, &suggestions)?;
+
+            let file_name = match to {
+                Some(path) if path.ends_with(".md") => path,
+                Some(path) => format!("{}.md", path),
+                None => {
+                    let title = values.text("title").filter(|t| !t.is_empty()).unwrap_or_else(|| chosen.name.clone());
+                    format!("{}/{}.md", notes_dir, slugify(&title))
+                }
+            };
+            if adapter.get_file_if_exists(&file_name).await?.is_some() {
+                bail!("'{}' already exists; pick another path with --to", file_name);
+            }
+            adapter.create_file(&file_name, &content).await
+                .context(format!("Failed to create note file '{}'", file_name))?;
+            templates.update_template_usage(&chosen.id, None)?;
+
+            println!("Note created from template '{}' as '{}'.", chosen.name, file_name);
+            crate::script::publish_output(serde_json::json!({ "path": file_name, "template": chosen.id }));
+        }
     }
     Ok(())
 }
 
+/// `note create --suggest-template` offers a template only when it fits at least this well
+const TEMPLATE_SUGGESTION_THRESHOLD: f32 = 0.5;
+
+/// File name of a note titled `title`, without the folder or `.md`
+fn note_slug(title: &str) -> String {
+    let max_slug_len = 50; // Keep consistent with todos
+    slugify(title).chars().take(max_slug_len).collect()
+}
+
+/// `body` written into the saved template it fits best, once the user accepts it. Only a
+/// template whose confidence reaches `TEMPLATE_SUGGESTION_THRESHOLD` is offered, and only at
+/// a terminal. Placeholders are filled from the body's `Label: value` lines and frontmatter,
+/// with `title` as the title; the body goes at the end unless a placeholder takes it.
+fn content_from_template(templates: &ObsidianAdapter, title: &str, body: &str, tags: &[String], reader: &mut dyn PromptReader) -> Result<Option<(NoteTemplate, String)>> {
+    let Some((template, confidence)) = templates.suggest_template_for_content(body)?.into_iter().next() else {
+        println!("No saved template fits this content.");
+        return Ok(None);
+    };
+    if confidence < TEMPLATE_SUGGESTION_THRESHOLD {
+        println!("The closest template, '{}', fits only {:.0}%; creating a plain note.", template.name, confidence * 100.0);
+        return Ok(None);
+    }
+    let question = format!("Use template '{}' ({:.0}% fit)? [y/N]", template.name, confidence * 100.0);
+    if !(reader.is_interactive() && reader.read_answer(&question)?.trim().eq_ignore_ascii_case("y")) {
+        return Ok(None);
+    }
+
+    let mut values = extracted_template_values(&template, body)?;
+    values.entry("title".to_string()).or_insert_with(|| title.to_string());
+    let mut values = TemplateValues::from(values);
+    let has_tags_field = template.frontmatter_fields.iter().any(|f| f.name == "tags");
+    if has_tags_field && !tags.is_empty() {
+        values.set("tags", tags.to_vec());
+    }
+    let mut content = templates.render_template(&template, &values)?;
+    if !has_tags_field && !tags.is_empty() {
+        content = content.replacen("---\n", &format!("---\ntags: [{}]\n", tags.join(", ")), 1);
+    }
+    if !template_takes_body(&template) && !body.trim().is_empty() {
+        content = format!("{}\n{}\n", content.trim_end(), body.trim());
+    }
+    Ok(Some((template, content)))
+}
+
+/// Names given with `--set` more than once, with all their values, for repeating sections
+fn repeated_values(set: &[String]) -> Vec<(String, Vec<String>)> {
+    let mut grouped: Vec<(String, Vec<String>)> = Vec::new();
+    for (name, value) in set.iter().filter_map(|pair| pair.split_once('=')) {
+        let (name, value) = (name.trim().to_string(), value.trim().to_string());
+        match grouped.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, values)) => values.push(value),
+            None => grouped.push((name, vec![value])),
+        }
+    }
+    grouped.retain(|(_, values)| values.len() > 1);
+    grouped
+}
+
+/// Values for every placeholder: those given with `--set`, then the rest asked for one by one.
+/// Without a terminal, unset optional placeholders are left empty and unset required ones are
+/// an error listing them all
+fn fill_placeholders(placeholders: &[TemplatePlaceholder], mut values: HashMap<String, String>, reader: &mut dyn PromptReader) -> Result<HashMap<String, String>> {
+    let unset: Vec<&TemplatePlaceholder> = placeholders.iter()
+        .filter(|p| values.get(&p.name).is_none_or(|v| v.is_empty() && p.required))
+        .collect();
+
+    if !reader.is_interactive() {
+        let missing: Vec<String> = unset.iter().filter(|p| p.required).map(|p| format!("{} ({})", p.name, p.hint)).collect();
+        if !missing.is_empty() {
+            bail!("Missing required placeholder(s), set them with --set name=value:\n  {}", missing.join("\n  "));
+        }
+        for placeholder in unset {
+            values.insert(placeholder.name.clone(), String::new());
+        }
+        return Ok(values);
+    }
+
+    for placeholder in unset {
+        let prompt = format!("{} ({}){}:", placeholder.name, placeholder.hint, if placeholder.required { "" } else { " [optional]" });
+        let value = loop {
+            let answer = reader.read_answer(&prompt)?;
+            // Nothing at all, not even a newline, means stdin was closed
+            if answer.is_empty() {
+                bail!("No value given for placeholder '{}'", placeholder.name);
+            }
+            let answer = answer.trim().to_string();
+            if !answer.is_empty() || !placeholder.required {
+                break answer;
+            }
+            println!("{} is required.", placeholder.name);
+        };
+        values.insert(placeholder.name.clone(), value);
+    }
+    Ok(values)
+}
+
+/// Rewrite `[[old]]`-style links (including `[[old|alias]]` and `[[old#heading]]`) to point at `new`.
+/// Returns `None` if the content has no links to `old`.
+fn rewrite_wikilinks(content: &str, old: &str, new: &str) -> Option<String> {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    let mut changed = false;
+
+    while let Some(start) = rest.find("[[") {
+        result.push_str(&rest[..start + 2]);
+        let inner_start = &rest[start + 2..];
+        let end = match inner_start.find("]]") {
+            Some(end) => end,
+            None => {
+                rest = inner_start;
+                break;
+            }
+        };
+
+        let inner = &inner_start[..end];
+        let target_end = inner.find(['|', '#']).unwrap_or(inner.len());
+        let target = inner[..target_end].trim();
+        let target_name = target.rsplit('/').next().unwrap_or(target).trim_end_matches(".md");
+
+        if link_key(target_name) == link_key(old) {
+            let prefix = &target[..target.len() - target.rsplit('/').next().unwrap_or(target).len()];
+            result.push_str(prefix);
+            result.push_str(new);
+            result.push_str(&inner[target_end..]);
+            changed = true;
+        } else {
+            result.push_str(inner);
+        }
+        result.push_str("]]");
+        rest = &inner_start[end + 2..];
+    }
+    result.push_str(rest);
+
+    if changed { Some(result) } else { None }
+}
+
+/// Obsidian resolves links case-insensitively; titles and file names differ by spaces vs dashes
+pub(crate) fn link_key(name: &str) -> String {
+    name.trim().to_lowercase().replace(' ', "-")
+}
+
 /// Opens an external editor to edit the note content
 /// Returns Ok(Some(content)) if content was edited and saved
 /// Returns Ok(None) if editing was cancelled
 /// Returns Err if there was an error during editing
-async fn edit_note_content(current_content: &str) -> Result<Option<String>> {
+pub(crate) async fn edit_note_content(current_content: &str) -> Result<Option<String>> {
     // Create a temporary file for editing
     let temp_dir = env::temp_dir();
     let temp_file_path = temp_dir.join(format!("arrowhead_note_edit_{}.md", 
@@ -324,6 +618,37 @@ fn get_editor_command() -> Result<EditorCommand> {
     }
 }
 
+/// Whether any requested tag matches (case-insensitive substring) one of the note's tags;
+/// no requested tags means every note matches
+fn matches_tags(note_tags: &[String], filter: &[String]) -> bool {
+    filter.is_empty()
+        || filter.iter().any(|filter_tag| {
+            note_tags.iter().any(|note_tag| note_tag.to_lowercase().contains(&filter_tag.to_lowercase()))
+        })
+}
+
+fn note_record(path: &str, content: &str) -> NoteRecord {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    NoteRecord::parse(path, content).with("title", extract_note_title(content, file))
+}
+
+async fn note_records(adapter: &ObsidianAdapter, notes_dir: &str, tags: &[String]) -> Result<Vec<NoteRecord>> {
+    let paths: Vec<String> = adapter.list_files_in_folder(notes_dir).await
+        .context("Failed to list notes")?
+        .into_iter()
+        .filter(|file| file.ends_with(".md"))
+        .map(|file| format!("{}/{}", notes_dir, file))
+        .collect();
+    let mut records = Vec::new();
+    for (path, content) in adapter.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await {
+        let content = content.with_context(|| format!("Failed to read {}", path))?;
+        if matches_tags(&extract_note_tags(&content), tags) {
+            records.push(note_record(&path, &content));
+        }
+    }
+    Ok(records)
+}
+
 // Helper functions for parsing note content
 
 fn extract_note_tags(content: &str) -> Vec<String> {
@@ -383,7 +708,7 @@ fn extract_note_tags(content: &str) -> Vec<String> {
     inline_tags
 }
 
-fn extract_note_title(content: &str, filename: &str) -> String {
+pub(crate) fn extract_note_title(content: &str, filename: &str) -> String {
     // First, try to find a title in the content
     // Look for H1 headings (# Title)
     for line in content.lines() {
@@ -456,3 +781,59 @@ fn extract_note_preview(content: &str) -> String {
         preview
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ScriptedPrompt;
+
+    #[test]
+    fn test_rewrite_wikilinks_preserves_alias_and_heading() {
+        let content = "See [[old-note]], [[old-note|the plan]] and [[Notes/old-note#Goals]] but not [[other]].";
+        let rewritten = rewrite_wikilinks(content, "old-note", "new-note").unwrap();
+        assert_eq!(rewritten, "See [[new-note]], [[new-note|the plan]] and [[Notes/new-note#Goals]] but not [[other]].");
+    }
+
+    #[test]
+    fn test_rewrite_wikilinks_matches_titles_and_ignores_unrelated() {
+        assert_eq!(rewrite_wikilinks("[[Old Note]]", "old-note", "new-note").as_deref(), Some("[[new-note]]"));
+        assert!(rewrite_wikilinks("[[old-note-2]] and [[unclosed", "old-note", "new-note").is_none());
+    }
+
+    fn placeholder(name: &str, required: bool) -> TemplatePlaceholder {
+        TemplatePlaceholder { name: name.to_string(), hint: format!("the {}", name), required }
+    }
+
+    #[test]
+    fn test_fill_placeholders_prompts_until_required_values_are_given() {
+        let placeholders = [placeholder("title", true), placeholder("attendees", true), placeholder("notes", false)];
+        let preset = parse_template_vars(&["title=Weekly sync".to_string()]).unwrap();
+        let mut reader = ScriptedPrompt::tty(&["", "Ana, Raj", ""]);
+
+        let values = fill_placeholders(&placeholders, preset, &mut reader).unwrap();
+        assert_eq!(values["title"], "Weekly sync");
+        assert_eq!(values["attendees"], "Ana, Raj");
+        assert_eq!(values["notes"], "");
+        assert_eq!(reader.prompts, vec![
+            "attendees (the attendees):",
+            "attendees (the attendees):",
+            "notes (the notes) [optional]:",
+        ]);
+    }
+
+    #[test]
+    fn test_fill_placeholders_without_a_terminal_lists_every_missing_one() {
+        let placeholders = [placeholder("title", true), placeholder("attendees", true), placeholder("notes", false)];
+        let mut reader = ScriptedPrompt::piped();
+
+        let preset = parse_template_vars(&["title=".to_string()]).unwrap();
+        let error = fill_placeholders(&placeholders, preset, &mut reader).unwrap_err().to_string();
+        assert!(error.contains("title (the title)") && error.contains("attendees (the attendees)"));
+        assert!(!error.contains("notes"));
+
+        let preset = parse_template_vars(&["title=Sync".to_string(), "attendees=Ana".to_string()]).unwrap();
+        let values = fill_placeholders(&placeholders, preset, &mut reader).unwrap();
+        assert_eq!(values["notes"], "");
+        assert!(reader.prompts.is_empty());
+    }
+}
@@ -2,21 +2,54 @@ use anyhow::{bail, Context, Result}; // Using anyhow for error handling
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_yaml;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::hash::{Hash, Hasher};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
 use uuid;
 use crate::ai_conversation::{LLMClient, Message, MessageRole};
+use crate::cache::{self, CachePaths, LruCache};
+use crate::chunking::{mean_pool, merge_chunk_analyses, split_into_chunks};
+use crate::clustering;
+use crate::config::{CacheSettings, Config};
+use crate::error::ArrowheadError;
+use crate::ann_index::{AnnIndex, AnnSettings};
+use crate::link_graph::LinkGraph;
+use crate::llm_json;
+use crate::term_index::{note_words, TermIndex};
+use crate::heuristic_analysis::{heuristic_analysis, AnalysisBackend, HEURISTIC_CONFIDENCE};
+use crate::embedding_models::{self, document_model, ModelShare};
+use crate::embedding_provider::EmbeddingProvider;
+use crate::embedding_store::{self, EmbeddingStorageConfig, HotVectorCache, QueryEmbeddingCache, VECTOR_DATABASE_VERSION};
+use crate::glossary::Glossary;
+use crate::note_skeleton::{self, stable_hash, NoteSkeleton, DEFAULT_SIMHASH_DISTANCE};
+use crate::managed_sections::{update_sections, SectionEdit};
+use crate::markdown::{is_tag_char, replace_section, Markdown};
+use crate::history::ChangeJournal;
+use crate::opt_out::{Feature, NoteOptOuts, OptOutPolicy};
+use crate::progress::BatchProgress;
+use crate::snippets::{self, Highlight};
+use crate::template_values::{Condition, TemplateValues};
+use crate::vault_transaction::{default_transaction_log_path, FileMeta, ListedFile, TransactionReport, VaultStore, VaultTransaction, DEFAULT_READ_CONCURRENCY};
 use nalgebra::{DVector, Norm};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
 use rayon::prelude::*;
 
 const MCP_SERVER_URL: &str = "https://127.0.0.1:27124"; // Default for Obsidian Local REST API
-const ANALYSIS_VERSION: &str = "1.0.0";
-const EMBEDDING_CACHE_FILE: &str = ".arrowhead_embeddings.bin";
-const TEMPLATE_CACHE_FILE: &str = ".arrowhead_templates.bin";
-const EMBEDDING_DIMENSION: usize = 768; // Common embedding dimension for many models
+/// Name of the vault an adapter not made with `for_vault` is connected to
+const DEFAULT_VAULT: &str = "default";
+pub const ANALYSIS_VERSION: &str = "1.0.0";
+
+/// The analysis schema of an `ai_analysis_version`, without the fingerprint after the `+`
+pub fn analysis_schema(version: &str) -> &str {
+    version.split('+').next().unwrap_or(version)
+}
+/// Embedding and analysis requests a batch has in flight at once
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 4;
 
 /// Content analysis results from AI processing
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -104,6 +137,55 @@ pub struct Concept {
     pub importance: f32,
 }
 
+/// An analysis as the model sends it, before limits and thresholds are applied
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AnalysisReply {
+    #[serde(deserialize_with = "llm_json::strings")]
+    themes: Vec<String>,
+    sentiment: Option<SentimentReply>,
+    #[serde(deserialize_with = "llm_json::null_as_default")]
+    entities: Vec<EntityReply>,
+    #[serde(deserialize_with = "llm_json::null_as_default")]
+    concepts: Vec<ConceptReply>,
+    summary: Option<String>,
+    #[serde(deserialize_with = "llm_json::strings")]
+    keywords: Vec<String>,
+    category: Option<String>,
+    complexity_score: Option<f32>,
+    reading_time_minutes: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SentimentReply {
+    overall: Option<String>,
+    confidence: Option<f32>,
+    #[serde(deserialize_with = "llm_json::strings")]
+    emotions: Vec<String>,
+}
+
+/// Entities without a text, type or confidence are dropped
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct EntityReply {
+    text: Option<String>,
+    entity_type: Option<String>,
+    confidence: Option<f32>,
+    context: Option<String>,
+}
+
+/// Concepts without a name or importance are dropped
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ConceptReply {
+    name: Option<String>,
+    description: Option<String>,
+    #[serde(deserialize_with = "llm_json::strings")]
+    related_concepts: Vec<String>,
+    importance: Option<f32>,
+}
+
 /// Configuration for content analysis
 #[derive(Debug, Clone)]
 pub struct AnalysisConfig {
@@ -125,6 +207,14 @@ pub struct AnalysisConfig {
     pub max_concepts: usize,
     /// Minimum confidence threshold for entities
     pub entity_confidence_threshold: f32,
+    /// Longer notes are cut to their beginning and end, this many characters in all, before
+    /// they are chunked; `None` analyzes every note whole
+    pub max_content_chars: Option<usize>,
+    /// Notes longer than this many characters are analyzed and embedded in chunks, split at
+    /// headings and paragraphs, whose results are merged
+    pub chunk_chars: usize,
+    /// Characters at the end of each chunk repeated at the start of the next
+    pub chunk_overlap_chars: usize,
 }
 
 impl Default for AnalysisConfig {
@@ -139,6 +229,9 @@ impl Default for AnalysisConfig {
             max_entities: 20,
             max_concepts: 15,
             entity_confidence_threshold: 0.7,
+            max_content_chars: None,
+            chunk_chars: 12_000,
+            chunk_overlap_chars: 400,
         }
     }
 }
@@ -146,15 +239,124 @@ impl Default for AnalysisConfig {
 #[derive(Debug, Serialize, Deserialize, Default, Clone)] // Added Default and Clone
 pub struct Frontmatter {
     // Define common frontmatter fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub due_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub status: Option<String>, // Added for todos and goals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub target_date: Option<String>, // Added for goals
+    // Todo fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<String>,
     
     // AI Analysis fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ai_analysis: Option<ContentAnalysis>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ai_analysis_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ai_analysis_timestamp: Option<String>,
+
+    /// Fields Arrowhead doesn't know (aliases, cssclass, dataview fields...), kept as they are
+    #[serde(flatten)]
+    pub extra: serde_yaml::Mapping,
+    /// The text this frontmatter was parsed from, so unchanged fields are written back verbatim
+    #[serde(skip)]
+    pub source: Option<FrontmatterSource>,
+}
+
+/// Frontmatter text split into its top-level keys. When a note is written back, every
+/// key whose value didn't change keeps its original text, comments and formatting.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontmatterSource {
+    /// Comments and blank lines before the first key
+    preamble: String,
+    entries: Vec<SourceEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SourceEntry {
+    key: serde_yaml::Value,
+    value: serde_yaml::Value,
+    /// The key's lines, including comments up to the next key
+    text: String,
+}
+
+impl FrontmatterSource {
+    /// Split `yaml` at every line that starts a top-level key. `None` when a piece doesn't
+    /// parse on its own (e.g. an alias to another key's anchor), in which case the whole
+    /// frontmatter is re-serialized.
+    pub fn parse(yaml: &str) -> Option<Self> {
+        let mut source = Self::default();
+        let mut pieces: Vec<String> = Vec::new();
+        for line in yaml.split_inclusive('\n') {
+            // Indented lines, comments and `- item` lines of a block sequence continue the key above
+            let sequence_item = line.trim_end() == "-" || line.starts_with("- ") || line.starts_with("-\t");
+            let starts_key = !line.starts_with([' ', '\t', '#']) && !sequence_item && !line.trim().is_empty();
+            match pieces.last_mut() {
+                Some(piece) if !starts_key => piece.push_str(line),
+                None if !starts_key => source.preamble.push_str(line),
+                _ => pieces.push(line.to_string()),
+            }
+        }
+        for text in pieces {
+            let mapping: serde_yaml::Mapping = serde_yaml::from_str(&text).ok()?;
+            let mut entries = mapping.into_iter();
+            let (Some((key, value)), None) = (entries.next(), entries.next()) else { return None };
+            source.entries.push(SourceEntry { key, value, text });
+        }
+        Some(source)
+    }
+
+    /// `mapping` as YAML: the original text for keys whose value is unchanged, fresh YAML
+    /// for changed keys in their original place, and keys that are new at the end
+    fn render(&self, mapping: &serde_yaml::Mapping) -> Result<String> {
+        let mut out = self.preamble.clone();
+        let mut push = |text: &str| {
+            out.push_str(text);
+            if !out.ends_with('\n') {
+                out.push('\n');
+            }
+        };
+        for entry in &self.entries {
+            match mapping.get(&entry.key) {
+                Some(value) if *value == entry.value => push(&entry.text),
+                Some(value) => push(&single_entry_yaml(&entry.key, value)?),
+                None => {}
+            }
+        }
+        for (key, value) in mapping {
+            if !self.entries.iter().any(|e| e.key == *key) {
+                push(&single_entry_yaml(key, value)?);
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn single_entry_yaml(key: &serde_yaml::Value, value: &serde_yaml::Value) -> Result<String> {
+    let mut mapping = serde_yaml::Mapping::new();
+    mapping.insert(key.clone(), value.clone());
+    serde_yaml::to_string(&mapping).context("Failed to serialize frontmatter to YAML")
+}
+
+impl Frontmatter {
+    /// The frontmatter as YAML, keeping the original text of fields that didn't change
+    pub fn to_yaml(&self) -> Result<String> {
+        let Some(source) = &self.source else {
+            return serde_yaml::to_string(self).context("Failed to serialize frontmatter to YAML");
+        };
+        match serde_yaml::to_value(self).context("Failed to serialize frontmatter to YAML")? {
+            serde_yaml::Value::Mapping(mapping) => source.render(&mapping),
+            _ => Ok(String::new()),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)] // Added Clone
@@ -167,10 +369,64 @@ impl MarkdownFile {
     /// Helper to serialize just the frontmatter part to a YAML string.
     /// Useful if you need to reconstruct/update frontmatter specifically.
     pub fn frontmatter_to_string(&self) -> Result<String> {
-        serde_yaml::to_string(&self.frontmatter).context("Failed to serialize frontmatter to YAML")
+        self.frontmatter.to_yaml()
     }
 }
 
+/// Entries of a folder listing: a list of names, `{"files": [...]}`, or objects with a
+/// name and `size`/`mtime` (at the top level or under `stat`)
+fn parse_folder_listing(listing: &serde_json::Value) -> Result<Vec<ListedFile>> {
+    let entries = match listing {
+        serde_json::Value::Array(entries) => entries,
+        serde_json::Value::Object(map) => map.get("files").and_then(|files| files.as_array())
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse file list response: no `files` list"))?,
+        _ => bail!("Failed to parse file list response"),
+    };
+    entries.iter()
+        .map(|entry| match entry {
+            serde_json::Value::String(name) => Ok(ListedFile { name: name.clone(), meta: None }),
+            serde_json::Value::Object(map) => {
+                let name = ["name", "path", "file"].iter()
+                    .find_map(|key| map.get(*key).and_then(|v| v.as_str()))
+                    .ok_or_else(|| anyhow::anyhow!("File list entry without a name: {}", entry))?;
+                let stat = map.get("stat").and_then(|s| s.as_object()).unwrap_or(map);
+                let size = stat.get("size").and_then(|v| v.as_u64());
+                let modified = stat.get("mtime").and_then(|v| v.as_i64());
+                let meta = size.zip(modified).map(|(size, modified)| FileMeta { size, modified });
+                Ok(ListedFile { name: name.to_string(), meta })
+            }
+            _ => bail!("Unexpected file list entry: {}", entry),
+        })
+        .collect()
+}
+
+/// Key of a note body in the analysis cache and the embedding store's `content_hash`
+pub fn content_cache_key(content: &str) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("content_{:x}", hasher.finish())
+}
+
+/// The text of a note sent for analysis: whole when it fits in `max_chars`, otherwise the
+/// first two thirds and last third of the budget around an omission marker. Also returns
+/// how many characters were left out.
+fn analysis_excerpt(content: &str, max_chars: Option<usize>) -> (Cow<'_, str>, usize) {
+    let total = content.chars().count();
+    let Some(max_chars) = max_chars.filter(|max| total > *max) else {
+        return (Cow::Borrowed(content), 0);
+    };
+    let head_chars = max_chars * 2 / 3;
+    let tail_chars = max_chars - head_chars;
+    let head_end = content.char_indices().nth(head_chars).map_or(content.len(), |(i, _)| i);
+    let tail_start = content.char_indices().nth(total - tail_chars).map_or(content.len(), |(i, _)| i);
+    let omitted = total - head_chars - tail_chars;
+    let excerpt = format!("{}\n\n[… {} characters omitted …]\n\n{}", &content[..head_end], omitted, &content[tail_start..]);
+    (Cow::Owned(excerpt), omitted)
+}
+
 /// Document embedding with metadata
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DocumentEmbedding {
@@ -184,8 +440,24 @@ pub struct DocumentEmbedding {
     pub created_at: DateTime<Utc>,
     /// Document metadata
     pub metadata: DocumentMetadata,
+    /// Model that produced the vector, e.g. `openai/text-embedding-3-small`. Empty for
+    /// embeddings stored before models were recorded, which are dropped on load.
+    #[serde(default)]
+    pub model: String,
+}
+
+/// A note parsed for embedding, waiting for its vector
+struct PendingEmbedding {
+    path: String,
+    /// The body that gets embedded
+    content: String,
+    content_hash: String,
+    metadata: DocumentMetadata,
 }
 
+/// A pending note with the model that embedded it and its vector
+type EmbeddedNote = (PendingEmbedding, String, Vec<f32>);
+
 /// Metadata for documents
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DocumentMetadata {
@@ -199,6 +471,20 @@ pub struct DocumentMetadata {
     pub excerpt: String,
     /// Last modified timestamp
     pub modified_at: Option<DateTime<Utc>>,
+    /// Structural fingerprint used for cheap duplicate and pattern pre-filtering
+    #[serde(default)]
+    pub skeleton: Option<NoteSkeleton>,
+}
+
+/// Two indexed notes that look like duplicates of each other
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateCandidate {
+    pub path_a: String,
+    pub path_b: String,
+    /// Differing simhash bits between the two notes
+    pub simhash_distance: u32,
+    /// Cosine similarity of their embeddings
+    pub similarity: f32,
 }
 
 /// Semantic search result
@@ -212,8 +498,8 @@ pub struct SemanticSearchResult {
     pub metadata: DocumentMetadata,
     /// Matching content snippet
     pub snippet: String,
-    /// Highlighted matches
-    pub highlights: Vec<String>,
+    /// Query terms found in `snippet`
+    pub highlights: Vec<Highlight>,
 }
 
 /// Semantic search query configuration
@@ -246,19 +532,95 @@ impl Default for SemanticSearchConfig {
     }
 }
 
-/// Vector database for storing and searching embeddings
+/// Vector database for storing and searching embeddings. Embeddings are keyed by their
+/// document's path, so adding or removing one never changes which embedding another path
+/// resolves to.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct VectorDatabase {
-    /// All document embeddings
-    pub embeddings: Vec<DocumentEmbedding>,
-    /// Index for fast lookup by path
-    pub path_index: HashMap<String, usize>,
+    /// Document embeddings by path, in path order
+    embeddings: BTreeMap<String, DocumentEmbedding>,
     /// Version of the database
     pub version: String,
     /// Last updated timestamp
     pub last_updated: DateTime<Utc>,
 }
 
+impl VectorDatabase {
+    pub fn new(version: &str, last_updated: DateTime<Utc>) -> Self {
+        Self { embeddings: BTreeMap::new(), version: version.to_string(), last_updated }
+    }
+
+    /// A database of `documents`. When a path appears more than once, as it could in
+    /// stores written before embeddings were keyed by path, the newest embedding is kept.
+    pub fn from_documents(documents: impl IntoIterator<Item = DocumentEmbedding>, version: &str, last_updated: DateTime<Utc>) -> Self {
+        let mut database = Self::new(version, last_updated);
+        for doc in documents {
+            match database.embeddings.get(&doc.path) {
+                Some(existing) if existing.created_at > doc.created_at => {}
+                _ => {
+                    database.insert(doc);
+                }
+            }
+        }
+        database
+    }
+
+    /// Add or replace the embedding of `doc.path`, returning the replaced one
+    pub fn insert(&mut self, doc: DocumentEmbedding) -> Option<DocumentEmbedding> {
+        self.embeddings.insert(doc.path.clone(), doc)
+    }
+
+    pub fn get(&self, path: &str) -> Option<&DocumentEmbedding> {
+        self.embeddings.get(path)
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<DocumentEmbedding> {
+        self.embeddings.remove(path)
+    }
+
+    /// Keep only the embeddings `keep` returns true for
+    pub fn retain(&mut self, mut keep: impl FnMut(&DocumentEmbedding) -> bool) {
+        self.embeddings.retain(|_, doc| keep(doc));
+    }
+
+    pub fn clear(&mut self) {
+        self.embeddings.clear();
+    }
+
+    /// All embeddings, in path order
+    pub fn documents(&self) -> impl Iterator<Item = &DocumentEmbedding> {
+        self.embeddings.values()
+    }
+
+    pub fn into_documents(self) -> impl Iterator<Item = DocumentEmbedding> {
+        self.embeddings.into_values()
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.embeddings.keys().map(String::as_str)
+    }
+
+    /// Re-key the embedding of `from` under `to`, returning false if `from` has none
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        match self.embeddings.remove(from) {
+            Some(mut doc) => {
+                doc.path = to.to_string();
+                self.insert(doc);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.embeddings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.embeddings.is_empty()
+    }
+}
+
 /// Template component types
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum TemplateComponent {
@@ -365,6 +727,83 @@ pub struct TemplatePattern {
     pub examples: Vec<String>,
 }
 
+/// A generated template as the model sends it
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TemplateReply {
+    id: Option<String>,
+    name: Option<String>,
+    description: Option<String>,
+    category: Option<String>,
+    #[serde(deserialize_with = "llm_json::null_as_default")]
+    components: Vec<ComponentReply>,
+    #[serde(deserialize_with = "llm_json::null_as_default")]
+    frontmatter_fields: Vec<FieldReply>,
+    #[serde(deserialize_with = "llm_json::strings")]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ComponentReply {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    content: Option<String>,
+    name: Option<String>,
+    hint: Option<String>,
+    required: Option<bool>,
+    prompt: Option<String>,
+    fallback: Option<String>,
+    tag: Option<String>,
+}
+
+impl ComponentReply {
+    /// The component, or `None` for an unknown type or a text or tag without its value
+    fn into_component(self) -> Option<TemplateComponent> {
+        match self.kind.as_deref()? {
+            "Text" => Some(TemplateComponent::Text(self.content?)),
+            "Placeholder" => Some(TemplateComponent::Placeholder {
+                name: self.name.unwrap_or_else(|| "field".to_string()),
+                hint: self.hint.unwrap_or_else(|| "Enter value".to_string()),
+                required: self.required.unwrap_or(false),
+            }),
+            "AiSuggestion" => Some(TemplateComponent::AiSuggestion {
+                prompt: self.prompt.unwrap_or_else(|| "Generate content".to_string()),
+                fallback: self.fallback.unwrap_or_else(|| "Content here".to_string()),
+            }),
+            "Tag" => Some(TemplateComponent::Tag(self.tag?)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FieldReply {
+    name: Option<String>,
+    field_type: Option<String>,
+    /// Numbers and booleans are kept as their text
+    default_value: Option<serde_json::Value>,
+    required: Option<bool>,
+    description: Option<String>,
+}
+
+impl FieldReply {
+    fn into_field(self) -> FrontmatterField {
+        FrontmatterField {
+            name: self.name.unwrap_or_else(|| "field".to_string()),
+            field_type: self.field_type.unwrap_or_else(|| "string".to_string()),
+            default_value: match self.default_value {
+                Some(serde_json::Value::String(value)) => Some(value),
+                Some(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => Some(value.to_string()),
+                _ => None,
+            },
+            required: self.required.unwrap_or(false),
+            description: self.description,
+        }
+    }
+}
+
 /// Template generation request
 #[derive(Debug, Clone)]
 pub struct TemplateGenerationRequest {
@@ -457,6 +896,290 @@ pub struct TemplateDatabase {
     pub last_updated: DateTime<Utc>,
 }
 
+/// File format of an exported template set
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TemplateFormat {
+    Json,
+    Yaml,
+}
+
+impl TemplateFormat {
+    /// The format a file's extension names: `.yaml`/`.yml` are YAML, anything else JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("yaml") | Some("yml") => TemplateFormat::Yaml,
+            _ => TemplateFormat::Json,
+        }
+    }
+}
+
+/// What an import does with a template whose id or name is already in the database
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TemplateMergeStrategy {
+    /// Keep the existing template
+    #[default]
+    Skip,
+    /// Replace the existing template with the imported one
+    Overwrite,
+    /// Add the imported template under a new id and a numbered name
+    Duplicate,
+}
+
+/// Shareable file written by `export_templates`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TemplateExport {
+    /// Version of the database the templates came from
+    pub version: String,
+    pub exported_at: DateTime<Utc>,
+    pub templates: Vec<NoteTemplate>,
+}
+
+/// Outcome of `import_templates`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct TemplateImportSummary {
+    pub added: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+    pub duplicated: usize,
+}
+
+/// A placeholder a template asks to be filled in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplatePlaceholder {
+    pub name: String,
+    pub hint: String,
+    pub required: bool,
+}
+
+/// Placeholders of a template in order, nested ones included but not those naming the item of
+/// a repeating section; a name used twice is listed once and is required if either use is
+pub fn template_placeholders(template: &NoteTemplate) -> Vec<TemplatePlaceholder> {
+    fn walk<'a>(components: &'a [TemplateComponent], bound: &mut Vec<&'a str>, found: &mut Vec<TemplatePlaceholder>) {
+        for component in components {
+            match component {
+                TemplateComponent::Placeholder { name, hint, required } => {
+                    let root = name.split('.').next().unwrap_or(name);
+                    if bound.contains(&root) {
+                        continue;
+                    }
+                    match found.iter_mut().find(|p| &p.name == name) {
+                        Some(existing) => existing.required |= *required,
+                        None => found.push(TemplatePlaceholder { name: name.clone(), hint: hint.clone(), required: *required }),
+                    }
+                }
+                TemplateComponent::Conditional { content, .. } => walk(content, bound, found),
+                TemplateComponent::Repeating { item_name, content } => {
+                    bound.push(item_name);
+                    walk(content, bound, found);
+                    bound.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut found = Vec::new();
+    walk(&template.components, &mut Vec::new(), &mut found);
+    found
+}
+
+/// How much each signal counts towards a template's fit; a signal the template gives nothing
+/// to compare (no tags, no headings or placeholders) is left out and the rest scaled up
+const TEMPLATE_FIT_WEIGHTS: (f32, f32, f32) = (0.35, 0.25, 0.4);
+
+/// Placeholders that stand for the body of the note rather than a single value
+const BODY_PLACEHOLDERS: &[&str] = &["content", "body", "notes"];
+
+/// A label or placeholder name compared loosely: lowercase, with spaces and dashes as `_`
+fn field_key(name: &str) -> String {
+    name.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// `Label: value` lines of a note body, list markers and bold allowed, e.g. "- **Attendees:**
+/// Ana, Ravi". Labels are short words; URLs and times aren't labels.
+fn labelled_values(body: &str) -> Vec<(String, String)> {
+    let doc = Markdown::parse(body);
+    let mut values = Vec::new();
+    let mut offset = 0;
+    for line in body.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if doc.in_code(start) {
+            continue;
+        }
+        let line = line.trim().trim_start_matches(['-', '*', '+']).trim_start().replace("**", "");
+        let Some((label, value)) = line.split_once(':') else { continue };
+        let (label, value) = (label.trim(), value.trim());
+        let is_label = (1..=30).contains(&label.len())
+            && label.chars().next().is_some_and(char::is_alphabetic)
+            && label.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '_' | '-'));
+        if is_label && !value.is_empty() && !value.starts_with("//") {
+            values.push((field_key(label), value.to_string()));
+        }
+    }
+    values
+}
+
+/// Values found in `content` for a template's placeholders, keyed by placeholder name: its
+/// `Label: value` lines and frontmatter fields, the first `# heading` as `title`, and the
+/// whole body for a `content`, `body` or `notes` placeholder
+pub fn extracted_template_values(template: &NoteTemplate, content: &str) -> Result<HashMap<String, String>> {
+    let file = ObsidianAdapter::parse_markdown_file(content)?;
+    let mut found: HashMap<String, String> = HashMap::new();
+    for (key, value) in &file.frontmatter.extra {
+        if let (Some(key), Some(text)) = (key.as_str(), value.as_str()) {
+            found.entry(field_key(key)).or_insert_with(|| text.to_string());
+        }
+    }
+    for (key, value) in labelled_values(&file.content) {
+        found.entry(key).or_insert(value);
+    }
+    if let Some((_, _, title)) = Markdown::parse(&file.content).headings().into_iter().find(|(level, _, _)| *level == 1) {
+        found.entry("title".to_string()).or_insert_with(|| title.to_string());
+    }
+
+    let mut values = HashMap::new();
+    for placeholder in template_placeholders(template) {
+        let key = field_key(&placeholder.name);
+        if BODY_PLACEHOLDERS.contains(&key.as_str()) {
+            values.insert(placeholder.name, file.content.trim().to_string());
+        } else if let Some(value) = found.get(&key) {
+            values.insert(placeholder.name, value.clone());
+        }
+    }
+    Ok(values)
+}
+
+/// Whether one of the template's placeholders takes the whole body of the note
+pub fn template_takes_body(template: &NoteTemplate) -> bool {
+    template_placeholders(template).iter().any(|p| BODY_PLACEHOLDERS.contains(&field_key(&p.name).as_str()))
+}
+
+/// Headings a template's text writes, with `{{placeholders}}` taken out; a heading that is
+/// only a placeholder isn't structure
+fn template_headings(template: &NoteTemplate) -> Vec<String> {
+    fn text_of(components: &[TemplateComponent], out: &mut String) {
+        for component in components {
+            match component {
+                TemplateComponent::Text(text) => {
+                    out.push_str(text);
+                    out.push('\n');
+                }
+                TemplateComponent::Conditional { content, .. } | TemplateComponent::Repeating { content, .. } => text_of(content, out),
+                _ => {}
+            }
+        }
+    }
+    let mut text = String::new();
+    text_of(&template.components, &mut text);
+    let mut stripped = String::new();
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("{{") {
+        stripped.push_str(&rest[..start]);
+        rest = rest[start..].find("}}").map_or("", |end| &rest[start + end + 2..]);
+    }
+    stripped.push_str(rest);
+    note_skeleton::skeleton(&stripped).headings.into_iter()
+        .map(|heading| heading.text)
+        .filter(|text| text.chars().any(char::is_alphanumeric))
+        .collect()
+}
+
+/// How well a note fits `template`, from 0 to 1: whether its category matches, the share of
+/// the template's tags (and those of patterns in its category) the note has, and the share
+/// of the template's headings and placeholders the note already fills
+fn template_fit(template: &NoteTemplate, patterns: &[TemplatePattern], file: &MarkdownFile, analysis: &ContentAnalysis) -> f32 {
+    let (category_weight, tag_weight, structure_weight) = TEMPLATE_FIT_WEIGHTS;
+    let skeleton = note_skeleton::skeleton(&file.content);
+    let stems = |text: &str| note_words(text).iter().map(|w| snippets::stem(w)).collect::<Vec<_>>();
+    let mut note_stems: Vec<String> = analysis.keywords.iter().chain(&analysis.themes).flat_map(|k| stems(k)).collect();
+    for heading in &skeleton.headings {
+        note_stems.extend(stems(&heading.text));
+    }
+
+    let category = template.category.trim();
+    let category_stems = stems(category);
+    let mut note_tags: Vec<String> = file.frontmatter.tags.iter().flatten().map(|t| t.trim_start_matches('#').to_lowercase()).collect();
+    let doc = Markdown::parse(&file.content);
+    note_tags.extend(doc.tags().into_iter().map(|range| file.content[range].to_lowercase()));
+
+    let category_match = analysis.category.as_deref().is_some_and(|c| c.trim().eq_ignore_ascii_case(category))
+        || note_tags.iter().any(|tag| tag.eq_ignore_ascii_case(category))
+        || (!category_stems.is_empty() && category_stems.iter().all(|stem| note_stems.contains(stem)));
+    let mut template_tags: Vec<String> = template.tags.iter().map(|t| t.trim_start_matches('#').to_lowercase()).collect();
+    for pattern in patterns.iter().filter(|p| p.name.eq_ignore_ascii_case(category)) {
+        template_tags.extend(pattern.common_tags.iter().map(|t| t.to_lowercase()));
+    }
+    template_tags.sort();
+    template_tags.dedup();
+    let shared_tags = template_tags.iter()
+        .filter(|tag| note_tags.contains(tag) || stems(tag).first().is_some_and(|stem| note_stems.contains(stem)))
+        .count();
+
+    let note_headings: Vec<String> = skeleton.headings.iter().map(|h| h.text.clone()).collect();
+    let labels: Vec<String> = labelled_values(&file.content).into_iter().map(|(key, _)| key).collect();
+    let headings = template_headings(template);
+    let placeholders: Vec<String> = template_placeholders(template).into_iter()
+        .map(|p| field_key(&p.name))
+        .filter(|key| !BODY_PLACEHOLDERS.contains(&key.as_str()) && key != "title")
+        .collect();
+    let present = headings.iter().filter(|h| note_headings.contains(h)).count()
+        + placeholders.iter().filter(|p| labels.contains(p)).count();
+
+    let mut score = if category_match { category_weight } else { 0.0 };
+    let mut total = category_weight;
+    if !template_tags.is_empty() {
+        score += tag_weight * shared_tags as f32 / template_tags.len() as f32;
+        total += tag_weight;
+    }
+    if headings.len() + placeholders.len() > 0 {
+        score += structure_weight * present as f32 / (headings.len() + placeholders.len()) as f32;
+        total += structure_weight;
+    }
+    score / total
+}
+
+/// Mean cosine similarity between clusters at which template discovery still merges them
+const PATTERN_SIMILARITY_THRESHOLD: f32 = 0.75;
+
+/// Notes sharing an outline, as path, raw content and parsed note, under their structure key
+type StructuralGroup = (String, Vec<(String, String, MarkdownFile)>);
+
+/// The most common of `labels` compared case-insensitively and ignoring a plural `s`, in
+/// the spelling it appears in most often
+fn dominant_label<'a>(labels: impl Iterator<Item = &'a str>) -> Option<String> {
+    let mut counts: HashMap<String, HashMap<&str, usize>> = HashMap::new();
+    for label in labels.map(str::trim).filter(|label| !label.is_empty()) {
+        let lower = label.to_lowercase();
+        let key = match lower.strip_suffix('s') {
+            Some(singular) if singular.len() > 2 && !singular.ends_with('s') => singular.to_string(),
+            _ => lower,
+        };
+        *counts.entry(key).or_default().entry(label).or_default() += 1;
+    }
+    counts.into_iter()
+        .map(|(key, spellings)| (spellings.values().sum::<usize>(), key, spellings))
+        .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+        .and_then(|(_, _, spellings)| spellings.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0))).map(|(label, _)| label.to_string()))
+}
+
+/// A name for a cluster of notes: their dominant category, else theme, else tag
+fn pattern_name(group: &[&(String, MarkdownFile, ContentAnalysis)]) -> String {
+    dominant_label(group.iter().filter_map(|(_, _, analysis)| analysis.category.as_deref()))
+        .or_else(|| dominant_label(group.iter().flat_map(|(_, _, analysis)| analysis.themes.iter().map(String::as_str))))
+        .or_else(|| dominant_label(group.iter().flat_map(|(_, file, _)| file.frontmatter.tags.iter().flatten().map(String::as_str))))
+        .unwrap_or_else(|| "general".to_string())
+}
+
+fn collect_ai_prompts<'a>(components: &'a [TemplateComponent], prompts: &mut Vec<&'a String>) {
+    for component in components {
+        match component {
+            TemplateComponent::AiSuggestion { prompt, .. } => prompts.push(prompt),
+            TemplateComponent::Conditional { content, .. } | TemplateComponent::Repeating { content, .. } => collect_ai_prompts(content, prompts),
+            _ => {}
+        }
+    }
+}
+
 /// Tag suggestion with confidence score
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TagSuggestion {
@@ -530,6 +1253,42 @@ pub struct OrganizationRecommendations {
     pub generated_at: DateTime<Utc>,
 }
 
+/// A single change `apply_organization_recommendations` makes, or would make, to a note
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrganizationAction {
+    AddTag(String),
+    /// Move the note into this folder
+    MoveTo(String),
+    InsertLink { target: String, text: String },
+}
+
+impl fmt::Display for OrganizationAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrganizationAction::AddTag(tag) => write!(f, "add tag #{}", tag),
+            OrganizationAction::MoveTo(folder) => write!(f, "move to {}/", folder),
+            OrganizationAction::InsertLink { target, text } => write!(f, "link [[{}|{}]]", target, text),
+        }
+    }
+}
+
+/// What `apply_organization_recommendations` applied and what it skipped, with reasons
+#[derive(Debug, Clone, Default)]
+pub struct OrganizationSummary {
+    /// Path of the note after any move
+    pub path: String,
+    pub applied: Vec<OrganizationAction>,
+    pub skipped: Vec<(OrganizationAction, String)>,
+    /// Nothing was written; `applied` lists what would have changed
+    pub dry_run: bool,
+}
+
+impl OrganizationSummary {
+    fn skip(&mut self, action: OrganizationAction, reason: impl Into<String>) {
+        self.skipped.push((action, reason.into()));
+    }
+}
+
 /// Configuration for automated organization
 #[derive(Debug, Clone)]
 pub struct OrganizationConfig {
@@ -547,8 +1306,18 @@ pub struct OrganizationConfig {
     pub suggest_folder_moves: bool,
     /// Whether to suggest auto-linking
     pub suggest_auto_links: bool,
+    /// Whether to move notes to their most confident suggested folder
+    pub auto_move_notes: bool,
+    /// Minimum confidence for moving a note
+    pub auto_move_confidence_threshold: f32,
+    /// Whether to insert suggested wikilinks into note bodies
+    pub auto_insert_links: bool,
+    /// Minimum confidence for inserting a wikilink
+    pub auto_link_confidence_threshold: f32,
     /// Custom tag rules
     pub custom_tag_rules: Vec<TagRule>,
+    /// Whether notes are analyzed by the LLM or by keyword heuristics
+    pub analysis_backend: AnalysisBackend,
 }
 
 /// Custom tag rule
@@ -574,7 +1343,12 @@ impl Default for OrganizationConfig {
             auto_apply_tags: false,
             suggest_folder_moves: true,
             suggest_auto_links: true,
+            auto_move_notes: false,
+            auto_move_confidence_threshold: 0.85,
+            auto_insert_links: false,
+            auto_link_confidence_threshold: 0.8,
             custom_tag_rules: Vec::new(),
+            analysis_backend: AnalysisBackend::Auto,
         }
     }
 }
@@ -659,6 +1433,8 @@ pub struct InsertedLink {
 /// Content suggestion request
 #[derive(Debug, Clone)]
 pub struct ContentSuggestionRequest {
+    /// Vault path of the document, when it has one, so folder opt-outs and exclusions apply
+    pub path: Option<String>,
     /// Current document content
     pub content: String,
     /// Cursor position in document
@@ -688,6 +1464,8 @@ pub struct ContentSuggestionConfig {
     pub auto_insert_links: bool,
     /// Minimum confidence for auto-inserted links
     pub auto_link_confidence_threshold: f32,
+    /// Link only the first mention of each note, rather than every mention
+    pub link_first_occurrence_only: bool,
     /// Cache timeout for suggestions (seconds)
     pub cache_timeout_seconds: u64,
 }
@@ -702,6 +1480,7 @@ impl Default for ContentSuggestionConfig {
             debounce_delay_ms: 300,
             auto_insert_links: false,
             auto_link_confidence_threshold: 0.8,
+            link_first_occurrence_only: true,
             cache_timeout_seconds: 300,
         }
     }
@@ -723,43 +1502,148 @@ struct CachedSuggestion {
 struct SuggestionCache {
     /// Cached suggestions by content hash
     suggestions: HashMap<String, Vec<CachedSuggestion>>,
+    /// Notes kept at most; the least recently cached are dropped first
+    max_entries: usize,
     /// Cache hit statistics
     hit_count: usize,
     /// Cache miss statistics
     miss_count: usize,
 }
 
+/// HTTP client tuned for the local REST server: keep TLS connections alive and pooled
+/// so batches of reads don't pay a fresh handshake per file. Never proxied.
+fn build_http_client() -> Client {
+    Client::builder()
+        .no_proxy()
+        .danger_accept_invalid_certs(true) // For self-signed certificates on localhost
+        .pool_max_idle_per_host(DEFAULT_READ_CONCURRENCY * 2)
+        .pool_idle_timeout(std::time::Duration::from_secs(90))
+        .tcp_keepalive(std::time::Duration::from_secs(60))
+        .tcp_nodelay(true)
+        .build()
+        .expect("Failed to create HTTP client")
+}
+
 pub struct ObsidianAdapter {
     client: Client,
+    /// The `[[vaults]]` entry this adapter talks to, or `default`
+    vault_name: String,
     base_url: String,
     api_key: Option<String>,
     llm_client: Option<Box<dyn LLMClient>>,
     analysis_config: AnalysisConfig,
-    analysis_cache: HashMap<String, (ContentAnalysis, DateTime<Utc>)>,
+    /// Behind a lock so a lookup, which marks the entry as recently used, needs only `&self`
+    analysis_cache: Mutex<LruCache<(ContentAnalysis, DateTime<Utc>)>>,
     vector_database: VectorDatabase,
     search_config: SemanticSearchConfig,
     embedding_cache_path: String,
+    embedding_storage: EmbeddingStorageConfig,
+    /// Behind a lock so searching, which marks its results as recently used, needs only `&self`
+    hot_vectors: Mutex<HotVectorCache>,
+    query_cache: Mutex<QueryEmbeddingCache>,
+    /// Clusters of the stored vectors, so searches of a large vault scan only part of it
+    ann_index: AnnIndex,
+    /// Links between indexed notes, updated as they are embedded and saved beside them
+    link_graph: LinkGraph,
+    /// Terms of indexed notes, for weighing keywords without an LLM; saved beside the links
+    term_index: TermIndex,
+    /// Embeds notes and queries for semantic search
+    embedder: Option<Box<dyn EmbeddingProvider>>,
+    /// Model id of `embedder`, kept so the vector database can be read without one
+    embedding_model: String,
+    /// Documents are embedded with this once the primary's quota runs out
+    fallback_embedder: Option<Box<dyn EmbeddingProvider>>,
+    /// Set when the primary ran out of quota; the rest of the run uses the fallback
+    embedding_failover: Option<ArrowheadError>,
+    /// Embedding or analysis requests a batch has in flight at once
+    batch_concurrency: usize,
     template_database: TemplateDatabase,
     template_cache_path: String,
     organization_config: OrganizationConfig,
     content_suggestion_config: ContentSuggestionConfig,
     suggestion_cache: SuggestionCache,
+    glossary: Option<Glossary>,
+    opt_out_policy: OptOutPolicy,
+    /// Where notes are backed up before an AI feature rewrites them; off unless set
+    change_journal: Option<ChangeJournal>,
+    /// Where transactions that write to the vault are recorded
+    transaction_log: PathBuf,
+    /// Hash of each file as last read or written, checked before it is overwritten. Behind a
+    /// lock so reads, which need only `&self`, can record it.
+    read_hashes: Mutex<HashMap<String, u64>>,
+}
+
+/// A full-file write found the note changed since this adapter read it. Nothing was written.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{path} changed in the vault since it was read (an edit in Obsidian or a sync from another device?); not overwriting it. Run the command again to start from the current version")]
+pub struct RemoteChanged {
+    pub path: String,
+}
+
+/// Point commands that take `--force` at it when their write hit [`RemoteChanged`]
+pub fn with_force_hint(error: anyhow::Error) -> anyhow::Error {
+    if error.downcast_ref::<RemoteChanged>().is_some() {
+        error.context("Pass --force to overwrite it anyway")
+    } else {
+        error
+    }
+}
+
+/// Where a PATCH lands in a note: the REST API's `Target-Type` and `Target`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchTarget {
+    /// A heading; nested headings are joined with `::`, e.g. `Log::Meetings`
+    Heading(String),
+    /// A block reference id, without the `^`
+    Block(String),
+    /// A frontmatter field, whose new value is sent as JSON
+    Frontmatter(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchOperation {
+    Append,
+    Prepend,
+    Replace,
+}
+
+/// How many notes use each tag, in frontmatter or inline. Tags are compared without
+/// case, as Obsidian does, and listed in lowercase; nested tags are counted on their own.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TagIndex {
+    pub notes_scanned: usize,
+    pub tags: BTreeMap<String, usize>,
+}
+
+/// One note's content before and after a tag rename
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagEdit {
+    pub path: String,
+    #[serde(skip)]
+    pub before: String,
+    #[serde(skip)]
+    pub after: String,
+}
+
+/// The notes a tag rename changes, and whether they were written
+#[derive(Debug, Clone, Serialize)]
+pub struct TagRename {
+    pub from: Vec<String>,
+    pub to: String,
+    pub notes_scanned: usize,
+    pub edits: Vec<TagEdit>,
+    /// Notes that mention a tag being renamed but were left alone, with the reason
+    pub skipped: Vec<(String, String)>,
+    /// Set once the edits were written
+    #[serde(skip)]
+    pub report: Option<TransactionReport>,
 }
 
 impl ObsidianAdapter {
     pub fn new(base_url: Option<String>, api_key: Option<String>) -> Self {
-        // Create a client that accepts self-signed certificates for localhost
-        let client = Client::builder()
-            .danger_accept_invalid_certs(true) // For self-signed certificates
-            .build()
-            .expect("Failed to create HTTP client");
-
-        let vector_db = VectorDatabase {
-            embeddings: Vec::new(),
-            path_index: HashMap::new(),
-            version: "1.0.0".to_string(),
-            last_updated: Utc::now(),
-        };
+        let client = build_http_client();
+
+        let vector_db = VectorDatabase::new(VECTOR_DATABASE_VERSION, Utc::now());
 
         let template_db = TemplateDatabase {
             templates: Vec::new(),
@@ -770,25 +1654,47 @@ impl ObsidianAdapter {
             last_updated: Utc::now(),
         };
 
+        let base_url = base_url.unwrap_or_else(|| MCP_SERVER_URL.to_string());
+        let cache_settings = CacheSettings::default();
+        let cache_paths = CachePaths::new(&cache_settings, &base_url);
+
         ObsidianAdapter {
             client,
-            base_url: base_url.unwrap_or_else(|| MCP_SERVER_URL.to_string()),
+            vault_name: DEFAULT_VAULT.to_string(),
+            base_url,
             api_key,
             llm_client: None,
             analysis_config: AnalysisConfig::default(),
-            analysis_cache: HashMap::new(),
+            analysis_cache: Mutex::new(LruCache::new(cache_settings.analysis_entries)),
             vector_database: vector_db,
             search_config: SemanticSearchConfig::default(),
-            embedding_cache_path: EMBEDDING_CACHE_FILE.to_string(),
+            embedding_cache_path: cache_paths.embeddings().to_string_lossy().to_string(),
+            embedding_storage: EmbeddingStorageConfig::default(),
+            hot_vectors: Mutex::new(HotVectorCache::default()),
+            query_cache: Mutex::new(QueryEmbeddingCache::default()),
+            ann_index: AnnIndex::default(),
+            link_graph: LinkGraph::default(),
+            term_index: TermIndex::default(),
+            embedder: None,
+            embedding_model: String::new(),
+            fallback_embedder: None,
+            embedding_failover: None,
+            batch_concurrency: DEFAULT_BATCH_CONCURRENCY,
             template_database: template_db,
-            template_cache_path: TEMPLATE_CACHE_FILE.to_string(),
+            template_cache_path: cache_paths.templates().to_string_lossy().to_string(),
             organization_config: OrganizationConfig::default(),
             content_suggestion_config: ContentSuggestionConfig::default(),
             suggestion_cache: SuggestionCache {
                 suggestions: HashMap::new(),
+                max_entries: cache_settings.suggestion_entries,
                 hit_count: 0,
                 miss_count: 0,
             },
+            glossary: None,
+            opt_out_policy: OptOutPolicy::default(),
+            change_journal: None,
+            transaction_log: default_transaction_log_path(),
+            read_hashes: Mutex::new(HashMap::new()),
         }
     }
 
@@ -799,17 +1705,9 @@ impl ObsidianAdapter {
         llm_client: Box<dyn LLMClient>,
         analysis_config: Option<AnalysisConfig>
     ) -> Self {
-        let client = Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .expect("Failed to create HTTP client");
-
-        let vector_db = VectorDatabase {
-            embeddings: Vec::new(),
-            path_index: HashMap::new(),
-            version: "1.0.0".to_string(),
-            last_updated: Utc::now(),
-        };
+        let client = build_http_client();
+
+        let vector_db = VectorDatabase::new(VECTOR_DATABASE_VERSION, Utc::now());
 
         let template_db = TemplateDatabase {
             templates: Vec::new(),
@@ -820,28 +1718,65 @@ impl ObsidianAdapter {
             last_updated: Utc::now(),
         };
 
+        let base_url = base_url.unwrap_or_else(|| MCP_SERVER_URL.to_string());
+        let cache_settings = CacheSettings::default();
+        let cache_paths = CachePaths::new(&cache_settings, &base_url);
+
         ObsidianAdapter {
             client,
-            base_url: base_url.unwrap_or_else(|| MCP_SERVER_URL.to_string()),
+            vault_name: DEFAULT_VAULT.to_string(),
+            base_url,
             api_key,
             llm_client: Some(llm_client),
             analysis_config: analysis_config.unwrap_or_default(),
-            analysis_cache: HashMap::new(),
+            analysis_cache: Mutex::new(LruCache::new(cache_settings.analysis_entries)),
             vector_database: vector_db,
             search_config: SemanticSearchConfig::default(),
-            embedding_cache_path: EMBEDDING_CACHE_FILE.to_string(),
+            embedding_cache_path: cache_paths.embeddings().to_string_lossy().to_string(),
+            embedding_storage: EmbeddingStorageConfig::default(),
+            hot_vectors: Mutex::new(HotVectorCache::default()),
+            query_cache: Mutex::new(QueryEmbeddingCache::default()),
+            ann_index: AnnIndex::default(),
+            link_graph: LinkGraph::default(),
+            term_index: TermIndex::default(),
+            embedder: None,
+            embedding_model: String::new(),
+            fallback_embedder: None,
+            embedding_failover: None,
+            batch_concurrency: DEFAULT_BATCH_CONCURRENCY,
             template_database: template_db,
-            template_cache_path: TEMPLATE_CACHE_FILE.to_string(),
+            template_cache_path: cache_paths.templates().to_string_lossy().to_string(),
             organization_config: OrganizationConfig::default(),
             content_suggestion_config: ContentSuggestionConfig::default(),
             suggestion_cache: SuggestionCache {
                 suggestions: HashMap::new(),
+                max_entries: cache_settings.suggestion_entries,
                 hit_count: 0,
                 miss_count: 0,
             },
+            glossary: None,
+            opt_out_policy: OptOutPolicy::default(),
+            change_journal: None,
+            transaction_log: default_transaction_log_path(),
+            read_hashes: Mutex::new(HashMap::new()),
         }
     }
 
+    /// An adapter for the `[[vaults]]` entry `name`, keeping its embeddings, templates and
+    /// analyses apart from every other vault's
+    pub fn for_vault(config: &Config, name: &str) -> Result<Self> {
+        let vault = config.vault(name)?;
+        let mut adapter = Self::new(Some(vault.base_url.clone()), vault.api_key.clone());
+        adapter.vault_name = vault.name.clone();
+        adapter.set_cache_settings(&config.cache.for_vault(vault));
+        Ok(adapter)
+    }
+
+    /// Name of the vault this adapter is connected to
+    pub fn vault_name(&self) -> &str {
+        &self.vault_name
+    }
+
     /// Set the LLM client for AI analysis
     pub fn set_llm_client(&mut self, llm_client: Box<dyn LLMClient>) {
         self.llm_client = Some(llm_client);
@@ -852,9 +1787,82 @@ impl ObsidianAdapter {
         self.analysis_config = config;
     }
 
+    /// Set how many embedding or analysis requests a batch runs at once
+    pub fn set_batch_concurrency(&mut self, concurrency: usize) {
+        self.batch_concurrency = concurrency.max(1);
+    }
+
+    /// Set the team glossary enforced on generated content
+    pub fn set_glossary(&mut self, glossary: Glossary) {
+        self.glossary = Some(glossary);
+    }
+
+    /// Set folder-level feature opt-outs; per-note frontmatter opt-outs always apply
+    pub fn set_opt_out_policy(&mut self, policy: OptOutPolicy) {
+        self.opt_out_policy = policy;
+    }
+
+    /// Back notes up to `journal` before analysis or organizing rewrites them
+    pub fn set_change_journal(&mut self, journal: ChangeJournal) {
+        self.change_journal = Some(journal);
+    }
+
+    /// Record vault transactions in `path` instead of the default log
+    pub fn set_transaction_log(&mut self, path: impl Into<PathBuf>) {
+        self.transaction_log = path.into();
+    }
+
+    pub fn transaction_log(&self) -> &Path {
+        &self.transaction_log
+    }
+
+    /// Record `previous` as the note's last version before `feature` writes it. Runs before
+    /// the write, and a failure stops it, so a change is never made without its backup.
+    fn journal_before_write(&self, vault_path: &str, previous: &str, feature: Feature) -> Result<()> {
+        if let Some(journal) = &self.change_journal {
+            journal.record(vault_path, previous, feature.name())
+                .with_context(|| format!("Not changing {}: could not back it up first", vault_path))?;
+        }
+        Ok(())
+    }
+
+    /// System prompt for a generation call, with the glossary addendum when one is set
+    fn generation_prompt(&self, base: &str) -> String {
+        match &self.glossary {
+            Some(glossary) => glossary.system_prompt(base),
+            None => base.to_string(),
+        }
+    }
+
+    /// Enforce the glossary on generated text destined for the vault
+    fn enforce_glossary(&self, text: &str) -> String {
+        match &self.glossary {
+            Some(glossary) => glossary.apply(text),
+            None => text.to_string(),
+        }
+    }
+
     /// Clear the analysis cache
     pub fn clear_analysis_cache(&mut self) {
-        self.analysis_cache.clear();
+        self.analysis_cache().clear();
+    }
+
+    fn analysis_cache(&self) -> MutexGuard<'_, LruCache<(ContentAnalysis, DateTime<Utc>)>> {
+        self.analysis_cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Keep the embedding and template caches in this vault's folder of the `[cache]` root,
+    /// moving any left in the working directory by older versions there, and cap the
+    /// in-memory caches
+    pub fn set_cache_settings(&mut self, settings: &CacheSettings) {
+        let paths = CachePaths::new(settings, &self.base_url);
+        paths.migrate_from_working_dir();
+        self.embedding_cache_path = paths.embeddings().to_string_lossy().to_string();
+        self.template_cache_path = paths.templates().to_string_lossy().to_string();
+        self.analysis_cache().set_capacity(settings.analysis_entries);
+        self.suggestion_cache.max_entries = settings.suggestion_entries;
+        self.content_suggestion_config.cache_timeout_seconds = settings.suggestion_max_age_seconds;
+        self.prune_suggestion_cache();
     }
 
     // Helper method to add authorization header if API key is present
@@ -866,6 +1874,24 @@ impl ObsidianAdapter {
         }
     }
 
+    /// Send a request to the Local REST API with the API key. A server that can't be reached
+    /// or that refuses the key comes back as the matching `ArrowheadError`.
+    async fn send_request(&self, request_builder: reqwest::RequestBuilder, method: &str, url: &str) -> Result<reqwest::Response> {
+        let response = self
+            .add_auth_header(request_builder)
+            .send()
+            .await
+            .map_err(|e| match e.is_connect() || e.is_timeout() {
+                true => anyhow::Error::new(ArrowheadError::ObsidianUnreachable { url: self.base_url.clone(), detail: e.to_string() }),
+                false => anyhow::Error::new(e),
+            })
+            .context(format!("Failed to send {} request to {}", method, url))?;
+        match response.status().as_u16() {
+            status @ (401 | 403) => Err(ArrowheadError::AuthFailed { provider: "obsidian".to_string(), status }.into()),
+            _ => Ok(response),
+        }
+    }
+
     // Made public for potential direct use if needed, though get_markdown_file_data is preferred
     pub fn parse_markdown_file(raw_content: &str) -> Result<MarkdownFile> {
         let parts: Vec<&str> = raw_content.splitn(3, "---").collect();
@@ -885,8 +1911,10 @@ impl ObsidianAdapter {
         let frontmatter: Frontmatter = if yaml_str.is_empty() {
             Frontmatter::default()
         } else {
-            serde_yaml::from_str(yaml_str)
-                .context(format!("Failed to parse YAML frontmatter: '{}'", yaml_str))?
+            let mut frontmatter: Frontmatter = serde_yaml::from_str(yaml_str)
+                .context(format!("Failed to parse YAML frontmatter: '{}'", yaml_str))?;
+            frontmatter.source = FrontmatterSource::parse(yaml_str);
+            frontmatter
         };
 
         Ok(MarkdownFile {
@@ -899,33 +1927,27 @@ impl ObsidianAdapter {
     pub fn serialize_markdown_file(file: &MarkdownFile) -> Result<String> {
         // Ensure frontmatter isn't just defaults if we don't want to write empty "--- \n ---"
         // However, always writing it is consistent. Serde_yaml handles Option types well (omits if None).
-        let fm_yaml = serde_yaml::to_string(&file.frontmatter)
-            .context("Failed to serialize frontmatter to YAML")?;
+        let fm_yaml = file.frontmatter.to_yaml()?;
 
-        // Avoid serializing an empty "null" or "{}" if frontmatter is truly empty/default
-        // and all its fields are None.
-        // A simple check: if the serialized YAML is just "---" or "--- {}" or "--- null", treat as empty.
-        // For now, `trim()` handles `--- \n---` becoming `--- \n---`
-        // An empty `Frontmatter::default()` serializes to `status: null\ntarget_date: null\n` if these fields are present.
-        // `serde_yaml` with `skip_serializing_if = "Option::is_none"` on struct fields is better.
-        // Let's add that to Frontmatter struct. (Will do this in a follow up if needed, for now it's fine)
+        // Unset fields are skipped (`skip_serializing_if` on Frontmatter), so no `key: null` lines are written.
+        // Fields read from the note keep their original text unless their value changed.
 
-        Ok(format!("---\n{}---\n\n{}", fm_yaml.trim(), file.content))
+        Ok(format!("---\n{}\n---\n\n{}", fm_yaml.trim(), file.content))
     }
 
     pub async fn get_file(&self, vault_path: &str) -> Result<String> {
         let url = format!("{}/vault/{}", self.base_url, vault_path);
         let response = self
-            .add_auth_header(self.client.get(&url).header("Accept", "text/markdown"))
-            .send()
-            .await
-            .context(format!("Failed to send GET request to {}", url))?;
+            .send_request(self.client.get(&url).header("Accept", "text/markdown"), "GET", &url)
+            .await?;
 
         if response.status().is_success() {
-            response
+            let content = response
                 .text()
                 .await
-                .context("Failed to read response text")
+                .context("Failed to read response text")?;
+            self.remember_read(vault_path, Some(&content));
+            Ok(content)
         } else {
             let status = response.status();
             let error_text = response
@@ -944,17 +1966,18 @@ impl ObsidianAdapter {
     pub async fn create_file(&self, vault_path: &str, content: &str) -> Result<()> {
         let url = format!("{}/vault/{}", self.base_url, vault_path);
         let response = self
-            .add_auth_header(
+            .send_request(
                 self.client
                     .post(&url)
                     .header("Content-Type", "text/markdown")
                     .body(content.to_string()),
+                "POST",
+                &url,
             )
-            .send()
-            .await
-            .context(format!("Failed to send POST request to {}", url))?;
+            .await?;
 
         if response.status().is_success() {
+            self.remember_read(vault_path, None);
             Ok(())
         } else {
             let status = response.status();
@@ -971,20 +1994,257 @@ impl ObsidianAdapter {
         }
     }
 
+    /// Replace a file, first checking that it still holds what this adapter last read or
+    /// wrote there. Fails with [`RemoteChanged`] if it doesn't, so an edit made in between
+    /// (including one Obsidian hasn't saved yet when the file was read) isn't clobbered.
     pub async fn update_file(&self, vault_path: &str, content: &str) -> Result<()> {
+        let expected = self.read_hashes.lock().unwrap_or_else(|e| e.into_inner()).get(vault_path).copied();
+        if let Some(expected) = expected {
+            let current = self.fetch_file(vault_path).await?;
+            if current.as_deref().map(stable_hash) != Some(expected) {
+                return Err(RemoteChanged { path: vault_path.to_string() }.into());
+            }
+        }
+        self.overwrite_file(vault_path, content).await
+    }
+
+    /// Replace a file whatever it holds now, as `--force` does
+    pub async fn overwrite_file(&self, vault_path: &str, content: &str) -> Result<()> {
         let url = format!("{}/vault/{}", self.base_url, vault_path);
         let response = self
-            .add_auth_header(
+            .send_request(
                 self.client
                     .put(&url)
                     .header("Content-Type", "text/markdown")
                     .body(content.to_string()),
+                "PUT",
+                &url,
             )
-            .send()
-            .await
-            .context(format!("Failed to send PUT request to {}", url))?;
+            .await?;
+
+        if response.status().is_success() {
+            self.remember_read(vault_path, Some(content));
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            bail!(
+                "MCP server returned error {}: {}. URL: {}",
+                status,
+                error_text,
+                url
+            )
+        }
+    }
+
+    /// Like `get_file`, but returns `None` instead of an error when the file does not exist
+    pub async fn get_file_if_exists(&self, vault_path: &str) -> Result<Option<String>> {
+        let url = format!("{}/vault/{}", self.base_url, vault_path);
+        let response = self
+            .send_request(self.client.get(&url).header("Accept", "text/markdown"), "GET", &url)
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            self.remember_read(vault_path, None);
+            Ok(None)
+        } else if response.status().is_success() {
+            let content = response
+                .text()
+                .await
+                .context("Failed to read response text")?;
+            self.remember_read(vault_path, Some(&content));
+            Ok(Some(content))
+        } else {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            bail!(
+                "MCP server returned error {}: {}. URL: {}",
+                status,
+                error_text,
+                url
+            )
+        }
+    }
+
+    /// The file as the server has it now, without recording it as read
+    async fn fetch_file(&self, vault_path: &str) -> Result<Option<String>> {
+        let url = format!("{}/vault/{}", self.base_url, vault_path);
+        let response = self
+            .send_request(self.client.get(&url).header("Accept", "text/markdown"), "GET", &url)
+            .await?;
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Some(response.text().await.context("Failed to read response text")?)),
+            status => bail!("MCP server returned error {} re-reading {} before writing it. URL: {}", status, vault_path, url),
+        }
+    }
+
+    /// Record `content` as the version of `vault_path` writes start from; `None` forgets it
+    fn remember_read(&self, vault_path: &str, content: Option<&str>) {
+        let mut hashes = self.read_hashes.lock().unwrap_or_else(|e| e.into_inner());
+        match content {
+            Some(content) => hashes.insert(vault_path.to_string(), stable_hash(content)),
+            None => hashes.remove(vault_path),
+        };
+    }
+
+    /// Add `content` to the end of a file, creating it if it doesn't exist. The server does
+    /// the append, so the rest of the file is never rewritten.
+    pub async fn append_file(&self, vault_path: &str, content: &str) -> Result<()> {
+        // POST appends to an existing file in the Local REST API
+        self.create_file(vault_path, content).await
+    }
+
+    /// Change one part of a note in place: append or prepend `content` under a heading or
+    /// block, or replace it. Only that part is sent, so edits elsewhere in the note since it
+    /// was read survive. For a frontmatter target `content` is the field's JSON value.
+    pub async fn patch_file(&self, vault_path: &str, target: &PatchTarget, content: &str, operation: PatchOperation) -> Result<()> {
+        let url = format!("{}/vault/{}", self.base_url, vault_path);
+        let (target_type, target_name, content_type) = match target {
+            PatchTarget::Heading(heading) => ("heading", heading, "text/markdown"),
+            PatchTarget::Block(id) => ("block", id, "text/markdown"),
+            PatchTarget::Frontmatter(field) => ("frontmatter", field, "application/json"),
+        };
+        let operation_name = match operation {
+            PatchOperation::Append => "append",
+            PatchOperation::Prepend => "prepend",
+            PatchOperation::Replace => "replace",
+        };
+        let mut request = self.client
+            .patch(&url)
+            .header("Operation", operation_name)
+            .header("Target-Type", target_type)
+            .header("Target", urlencoding::encode(target_name).into_owned())
+            .header("Content-Type", content_type)
+            .body(content.to_string());
+        if matches!(target, PatchTarget::Frontmatter(_)) {
+            request = request.header("Create-Target-If-Missing", "true");
+        }
+        let response = self
+            .send_request(request, "PATCH", &url)
+            .await?;
+
+        if response.status().is_success() {
+            // The server merged the change, so the content last read is no longer current
+            self.remember_read(vault_path, None);
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            bail!(
+                "Could not {} {} {} '{}' in {}: MCP server returned error {}: {}",
+                operation_name,
+                if operation == PatchOperation::Replace { "the" } else { "to" },
+                target_type,
+                target_name,
+                vault_path,
+                status,
+                error_text,
+            )
+        }
+    }
+
+    /// Every markdown note under `folder` (`""` for the whole vault), recursively
+    async fn markdown_files_under(&self, folder: &str) -> Result<Vec<String>> {
+        let mut folders = vec![folder.trim_matches('/').to_string()];
+        let mut paths = Vec::new();
+        while let Some(folder) = folders.pop() {
+            for entry in self.list_files_in_folder(&folder).await? {
+                let name = entry.trim_end_matches('/');
+                let path = if folder.is_empty() { name.to_string() } else { format!("{}/{}", folder, name) };
+                if entry.ends_with('/') {
+                    folders.push(path);
+                } else if entry.ends_with(".md") {
+                    paths.push(path);
+                }
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Count the notes using each tag under `folder` (`""` for the whole vault)
+    pub async fn collect_tag_index(&self, folder: &str) -> Result<TagIndex> {
+        let paths = self.markdown_files_under(folder).await?;
+        let mut index = TagIndex::default();
+        for (_, content) in self.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await {
+            let Ok(content) = content else { continue };
+            index.notes_scanned += 1;
+            for tag in note_tags(&content) {
+                *index.tags.entry(tag).or_default() += 1;
+            }
+        }
+        Ok(index)
+    }
+
+    /// Rename the tags in `from` to `to` across the vault, in frontmatter `tags` lists and
+    /// inline `#tags` outside code. Nested tags move with their parent (`#project/alpha`
+    /// becomes `#work/alpha`), and a note that ends up with `to` twice in its frontmatter
+    /// keeps one. Other frontmatter fields keep their text. With `dry_run` nothing is
+    /// written; otherwise the edits are written in one transaction, each only if the note
+    /// still matches what was read.
+    pub async fn rename_tag(&self, from: &[String], to: &str, dry_run: bool) -> Result<TagRename> {
+        let to = to.trim().trim_start_matches('#');
+        if to.is_empty() || to.contains(char::is_whitespace) || !to.chars().all(is_tag_char) || to.chars().all(|c| c.is_ascii_digit() || c == '/') {
+            bail!("'{}' is not a valid tag: use letters, digits, '_', '-' and '/' for nesting, with at least one non-digit", to);
+        }
+        let from: Vec<String> = from.iter().map(|tag| tag.trim().trim_start_matches('#').to_string()).filter(|tag| !tag.is_empty()).collect();
+        if from.is_empty() {
+            bail!("No tag to rename");
+        }
+        let paths = self.markdown_files_under("").await?;
+        let mut rename = TagRename { from: from.clone(), to: to.to_string(), notes_scanned: 0, edits: Vec::new(), skipped: Vec::new(), report: None };
+        for (path, content) in self.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await {
+            let Ok(content) = content else { continue };
+            rename.notes_scanned += 1;
+            match rename_tags_in_note(&content, &from, to) {
+                Ok(Some(after)) => rename.edits.push(TagEdit { path, before: content, after }),
+                Ok(None) => {}
+                Err(reason) => rename.skipped.push((path, reason.to_string())),
+            }
+        }
+        if !dry_run {
+            self.write_tag_rename(&mut rename).await?;
+        }
+        Ok(rename)
+    }
+
+    /// Write the edits of a dry-run `rename` in one transaction, each only if the note still
+    /// matches what was read, and record it in the transaction log
+    pub async fn write_tag_rename(&self, rename: &mut TagRename) -> Result<()> {
+        if rename.edits.is_empty() {
+            return Ok(());
+        }
+        let mut transaction = VaultTransaction::new(&format!("tags: {} → {}", rename.from.join(", "), rename.to))
+            .with_context(serde_json::json!({ "rename_tags": rename.from, "to": rename.to }));
+        for edit in &rename.edits {
+            transaction = transaction.write_if_unchanged(&edit.path, &edit.after, &edit.before);
+        }
+        let report = transaction.execute(self).await?;
+        if let Err(e) = report.append_to_log(&self.transaction_log) {
+            log::warn!("could not write transaction log: {}", e);
+        }
+        rename.report = Some(report);
+        Ok(())
+    }
+
+    pub async fn delete_file(&self, vault_path: &str) -> Result<()> {
+        let url = format!("{}/vault/{}", self.base_url, vault_path);
+        let response = self
+            .send_request(self.client.delete(&url), "DELETE", &url)
+            .await?;
 
         if response.status().is_success() {
+            self.remember_read(vault_path, None);
             Ok(())
         } else {
             let status = response.status();
@@ -1001,6 +2261,21 @@ impl ObsidianAdapter {
         }
     }
 
+    /// Move a note to `to`, carrying its embedding along. The REST API has no rename, so
+    /// the note is written at the new path before the old one is deleted.
+    pub async fn move_file(&mut self, from: &str, to: &str) -> Result<()> {
+        if from == to {
+            return Ok(());
+        }
+        if self.get_file_if_exists(to).await?.is_some() {
+            bail!("Cannot move {} to {}: a note already exists there", from, to);
+        }
+        let content = self.get_file(from).await?;
+        self.create_file(to, &content).await?;
+        self.delete_file(from).await?;
+        self.rename_document_embedding(from, to)
+    }
+
     pub async fn get_markdown_file_data(&self, vault_path: &str) -> Result<MarkdownFile> {
         let raw_content = self.get_file(vault_path).await?;
         Self::parse_markdown_file(&raw_content)
@@ -1024,23 +2299,91 @@ impl ObsidianAdapter {
         }
     }
 
-    /// Analyze the content of a markdown file using AI
+    /// Analyze the content of a markdown file using AI. Content with `ai: false` or
+    /// `arrowhead.disable: [analysis]` in its frontmatter is refused with `OptedOut`; callers
+    /// holding a path check it against the folder rules before reading the note.
     pub async fn analyze_content(&mut self, content: &str) -> Result<ContentAnalysis> {
-        let llm_client = self.llm_client.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No LLM client configured for content analysis"))?;
+        self.opt_out_policy.check_content(None, content, Feature::Analysis)?;
+        let cache_key = self.analysis_cache_key(content);
+        if let Some(cached_analysis) = self.cached_analysis(&cache_key) {
+            return Ok(cached_analysis);
+        }
 
-        // Check cache first
-        let cache_key = self.generate_cache_key(content);
-        if let Some((cached_analysis, timestamp)) = self.analysis_cache.get(&cache_key) {
-            // Check if cache is still valid (24 hours)
-            if Utc::now().signed_duration_since(*timestamp).num_hours() < 24 {
-                return Ok(cached_analysis.clone());
+        let analysis = self.request_analysis(content).await?;
+        
+        // Cache the result
+        self.analysis_cache().insert(cache_key, (analysis.clone(), Utc::now()));
+        
+        Ok(analysis)
+    }
+
+    /// A cached analysis that is still valid (24 hours)
+    fn cached_analysis(&self, cache_key: &str) -> Option<ContentAnalysis> {
+        let mut cache = self.analysis_cache();
+        let (cached_analysis, timestamp) = cache.get(cache_key)?;
+        (Utc::now().signed_duration_since(*timestamp).num_hours() < 24).then(|| cached_analysis.clone())
+    }
+
+    /// Ask the LLM to analyze content, bypassing the cache. Content longer than
+    /// `chunk_chars` is analyzed a chunk at a time and the analyses merged, with the chunk
+    /// summaries summarized again for the note's summary.
+    async fn request_analysis(&self, content: &str) -> Result<ContentAnalysis> {
+        let config = &self.analysis_config;
+        let (excerpt, _) = analysis_excerpt(content, config.max_content_chars);
+        let chunks = split_into_chunks(&excerpt, config.chunk_chars, config.chunk_overlap_chars);
+        if chunks.len() == 1 {
+            return self.request_analysis_with(self.create_analysis_prompt(content)).await;
+        }
+
+        let mut analyses = Vec::with_capacity(chunks.len());
+        for (i, chunk) in chunks.iter().enumerate() {
+            let notice = format!(
+                "\nThe note is too long to analyze at once, so it is sent in {} parts; this is part {}. Analyze only this part.\n",
+                chunks.len(),
+                i + 1
+            );
+            let analysis = self.request_analysis_with(self.analysis_prompt(chunk, &notice)).await
+                .with_context(|| format!("Failed to analyze part {} of {} of the note", i + 1, chunks.len()))?;
+            analyses.push(analysis);
+        }
+
+        let mut merged = merge_chunk_analyses(&analyses, config);
+        let summaries: Vec<&str> = analyses.iter().filter_map(|a| a.summary.as_deref()).filter(|s| !s.trim().is_empty()).collect();
+        if config.generate_summary && summaries.len() > 1 {
+            match self.summarize_summaries(&summaries).await {
+                Ok(summary) => merged.summary = Some(summary),
+                Err(e) => log::warn!("Could not summarize the chunk summaries, joining them instead: {:#}", e),
             }
         }
+        Ok(merged)
+    }
+
+    /// One summary of a long note from the summaries of its chunks, in order
+    async fn summarize_summaries(&self, summaries: &[&str]) -> Result<String> {
+        let llm_client = self.llm_client.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No LLM client configured for content analysis"))?;
+        let listed: Vec<String> = summaries.iter().enumerate().map(|(i, summary)| format!("{}. {}", i + 1, summary.trim())).collect();
+        let messages = vec![Message {
+            id: uuid::Uuid::new_v4().to_string(),
+            role: MessageRole::User,
+            content: crate::prompts::render("analysis_summary", &[("summaries", &listed.join("\n"))]),
+            timestamp: Utc::now(),
+            function_call: None,
+        }];
+        let response = llm_client.send_message(messages).await
+            .context("Failed to get the summary of the chunk summaries")?;
+        let summary = response.content.trim();
+        if summary.is_empty() {
+            bail!("The model returned an empty summary");
+        }
+        Ok(summary.to_string())
+    }
+
+    /// Send one analysis prompt and parse the reply
+    async fn request_analysis_with(&self, analysis_prompt: String) -> Result<ContentAnalysis> {
+        let llm_client = self.llm_client.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No LLM client configured for content analysis"))?;
 
-        // Create analysis prompt
-        let analysis_prompt = self.create_analysis_prompt(content);
-        
         let messages = vec![
             Message {
                 id: uuid::Uuid::new_v4().to_string(),
@@ -1061,20 +2404,18 @@ impl ObsidianAdapter {
         let response = llm_client.send_message(messages).await
             .context("Failed to get AI analysis response")?;
 
-        let analysis = self.parse_analysis_response(&response.content)?;
-        
-        // Cache the result
-        self.analysis_cache.insert(cache_key, (analysis.clone(), Utc::now()));
-        
-        Ok(analysis)
+        self.parse_analysis_response(&response.content)
     }
 
     /// Analyze a markdown file and update its frontmatter with AI analysis
     pub async fn analyze_and_update_file(&mut self, vault_path: &str) -> Result<MarkdownFile> {
-        let mut file_data = self.get_markdown_file_data(vault_path).await?;
+        let raw_content = self.get_file(vault_path).await?;
+        self.opt_out_policy.check(vault_path, &raw_content, Feature::Analysis)?;
+        let mut file_data = Self::parse_markdown_file(&raw_content)?;
         
-        // Check if analysis already exists and is recent
-        if let Some(ref _ai_analysis) = file_data.frontmatter.ai_analysis {
+        // Check if analysis already exists, is recent and came from the current model and settings
+        let version = self.analysis_version();
+        if file_data.frontmatter.ai_analysis.is_some() && file_data.frontmatter.ai_analysis_version.as_deref() == Some(version.as_str()) {
             if let Some(ref timestamp_str) = file_data.frontmatter.ai_analysis_timestamp {
                 if let Ok(timestamp) = DateTime::parse_from_rfc3339(timestamp_str) {
                     let age_hours = Utc::now().signed_duration_since(timestamp.with_timezone(&Utc)).num_hours();
@@ -1091,10 +2432,11 @@ impl ObsidianAdapter {
         
         // Update frontmatter
         file_data.frontmatter.ai_analysis = Some(analysis);
-        file_data.frontmatter.ai_analysis_version = Some(ANALYSIS_VERSION.to_string());
+        file_data.frontmatter.ai_analysis_version = Some(version);
         file_data.frontmatter.ai_analysis_timestamp = Some(Utc::now().to_rfc3339());
         
         // Save updated file
+        self.journal_before_write(vault_path, &raw_content, Feature::Analysis)?;
         self.save_markdown_file_data(vault_path, &file_data, true).await?;
         
         Ok(file_data)
@@ -1105,290 +2447,571 @@ impl ObsidianAdapter {
         self.analyze_content(content).await
     }
 
-    /// Batch analyze multiple files
-    pub async fn batch_analyze_files(&mut self, vault_paths: Vec<&str>) -> Result<Vec<(String, Result<ContentAnalysis>)>> {
-        let mut results = Vec::new();
-        
-        for path in vault_paths {
-            let result = match self.get_markdown_file_data(path).await {
-                Ok(file_data) => self.analyze_content(&file_data.content).await,
-                Err(e) => Err(e),
-            };
-            results.push((path.to_string(), result));
-        }
-        
-        Ok(results)
+    /// Batch analyze multiple files, up to `batch_concurrency` at once. Results are in the
+    /// order of `vault_paths`; a file that fails doesn't stop the others.
+    pub async fn batch_analyze_files(&mut self, vault_paths: Vec<&str>, progress: &dyn BatchProgress) -> Result<Vec<(String, Result<ContentAnalysis>)>> {
+        let paths: Vec<String> = vault_paths.iter().map(|p| p.to_string()).collect();
+        let fetched = self.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await;
+        Ok(self.batch_analyze_contents(fetched, progress).await)
+    }
+
+    /// Analyze already fetched notes, up to `batch_concurrency` requests at once, in input
+    /// order. `progress` hears about each note as its analysis completes.
+    pub async fn batch_analyze_contents(&mut self, fetched: Vec<(String, Result<String>)>, progress: &dyn BatchProgress) -> Vec<(String, Result<ContentAnalysis>)> {
+        progress.start(fetched.len());
+        let this = &*self;
+        let analyzed: Vec<(String, Option<String>, Result<ContentAnalysis>)> = stream::iter(fetched)
+            .map(|(path, raw)| async move {
+                let parsed = raw.and_then(|raw| {
+                    this.opt_out_policy.check(&path, &raw, Feature::Analysis)?;
+                    Self::parse_markdown_file(&raw)
+                });
+                let (cache_key, result) = match parsed {
+                    Ok(file_data) => {
+                        let cache_key = this.analysis_cache_key(&file_data.content);
+                        match this.cached_analysis(&cache_key) {
+                            Some(cached_analysis) => (None, Ok(cached_analysis)),
+                            None => (Some(cache_key), this.request_analysis(&file_data.content).await),
+                        }
+                    }
+                    Err(e) => (None, Err(e)),
+                };
+                progress.advance(&path);
+                (path, cache_key, result)
+            })
+            .buffered(self.batch_concurrency.max(1))
+            .collect()
+            .await;
+        progress.finish();
+
+        analyzed.into_iter()
+            .map(|(path, cache_key, result)| {
+                if let (Some(cache_key), Ok(analysis)) = (cache_key, &result) {
+                    self.analysis_cache().insert(cache_key, (analysis.clone(), Utc::now()));
+                }
+                (path, result)
+            })
+            .collect()
     }
 
     /// Helper method to generate cache key from content
     fn generate_cache_key(&self, content: &str) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-        
-        let mut hasher = DefaultHasher::new();
-        content.hash(&mut hasher);
-        format!("content_{:x}", hasher.finish())
+        content_cache_key(content)
+    }
+
+    /// What an analysis depends on besides the note: the schema version, the LLM model, and
+    /// a hash of the analysis settings and prompt, e.g. `1.0.0+gpt-4o-mini.3f2a9c1e`.
+    /// Written as `ai_analysis_version`; an analysis with another one is redone.
+    pub fn analysis_version(&self) -> String {
+        let model = self.llm_client.as_ref().map_or_else(|| "none".to_string(), |client| client.get_model_name());
+        let settings = format!("{:?}\n{}\n{}", self.analysis_config, crate::prompts::template("analysis"), crate::prompts::template("analysis_summary"));
+        format!("{}+{}.{:08x}", ANALYSIS_VERSION, model, stable_hash(&settings) as u32)
+    }
+
+    /// Key of a note body in the analysis cache, so switching models or settings misses it
+    fn analysis_cache_key(&self, content: &str) -> String {
+        format!("{}:{}", content_cache_key(content), self.analysis_version())
     }
 
-    /// Create the analysis prompt for the AI
+    /// Create the analysis prompt for the AI. Only the sections turned on in the analysis
+    /// config are asked for, and an over-long note is cut to its beginning and end.
     fn create_analysis_prompt(&self, content: &str) -> String {
+        let (excerpt, omitted) = analysis_excerpt(content, self.analysis_config.max_content_chars);
+        let truncation = match omitted {
+            0 => String::new(),
+            omitted => format!(
+                "\nThe note is long, so only its beginning and end are included; {} of its {} characters in the middle were left out. Analyze it as a whole from what is shown.\n",
+                omitted,
+                content.chars().count()
+            ),
+        };
+        self.analysis_prompt(&excerpt, &truncation)
+    }
+
+    /// The analysis prompt for `content`, with `notice` saying what part of the note it is
+    fn analysis_prompt(&self, content: &str, notice: &str) -> String {
+        let config = &self.analysis_config;
         let word_count = content.split_whitespace().count();
         let reading_time = (word_count as f32 / 200.0).ceil() as u32; // Assume 200 words per minute
-        
-        format!(r#"Please analyze the following content and return a JSON response with the following structure:
 
-{{
-  "themes": ["theme1", "theme2", ...], // Max {} themes
-  "sentiment": {{
+        let mut fields = Vec::new();
+        let mut focus = vec!["Focus on extracting meaningful insights.".to_string()];
+        if config.extract_themes {
+            fields.push(format!(r#"  "themes": ["theme1", "theme2", ...], // Max {} themes"#, config.max_themes));
+            focus.push("For themes, identify the main topics discussed.".to_string());
+        }
+        if config.analyze_sentiment {
+            fields.push(r#"  "sentiment": {
     "overall": "positive|negative|neutral",
     "confidence": 0.0-1.0,
     "emotions": ["emotion1", "emotion2", ...]
-  }},
-  "entities": [
+  },"#.to_string());
+            focus.push("For sentiment, analyze the overall emotional tone.".to_string());
+        }
+        if config.extract_entities {
+            fields.push(format!(r#"  "entities": [
     {{
       "text": "entity text",
       "entity_type": "PERSON|ORG|LOCATION|MISC",
       "confidence": 0.0-1.0,
       "context": "optional context"
     }}
-  ], // Max {} entities
-  "concepts": [
+  ], // Max {} entities"#, config.max_entities));
+            focus.push("For entities, extract people, organizations, locations, and other important entities.".to_string());
+        }
+        if config.identify_concepts {
+            fields.push(format!(r#"  "concepts": [
     {{
       "name": "concept name",
       "description": "concept description",
       "related_concepts": ["related1", "related2"],
       "importance": 0.0-1.0
     }}
-  ], // Max {} concepts
-  "summary": "Brief summary of the content",
-  "keywords": ["keyword1", "keyword2", ...],
+  ], // Max {} concepts"#, config.max_concepts));
+            focus.push("For concepts, identify key ideas and their relationships.".to_string());
+        }
+        if config.generate_summary {
+            fields.push(r#"  "summary": "Brief summary of the content","#.to_string());
+        }
+        fields.push(format!(r#"  "keywords": ["keyword1", "keyword2", ...],
   "category": "category name",
   "complexity_score": 0.0-10.0,
-  "reading_time_minutes": {}
-}}
-
-Focus on extracting meaningful insights. For themes, identify the main topics discussed. For sentiment, analyze the overall emotional tone. For entities, extract people, organizations, locations, and other important entities. For concepts, identify key ideas and their relationships.
+  "reading_time_minutes": {}"#, reading_time));
 
-Content to analyze:
-{}
-"#, 
-            self.analysis_config.max_themes,
-            self.analysis_config.max_entities,
-            self.analysis_config.max_concepts,
-            reading_time,
-            content
-        )
+        let (max_themes, max_entities, max_concepts, reading_time) = (
+            config.max_themes.to_string(),
+            config.max_entities.to_string(),
+            config.max_concepts.to_string(),
+            reading_time.to_string(),
+        );
+        crate::prompts::render("analysis", &[
+            ("fields", &fields.join("\n")),
+            ("focus", &focus.join(" ")),
+            ("truncation", notice),
+            ("content", content),
+            ("max_themes", &max_themes),
+            ("max_entities", &max_entities),
+            ("max_concepts", &max_concepts),
+            ("reading_time", &reading_time),
+        ])
     }
 
     /// Parse the AI response into ContentAnalysis struct
     fn parse_analysis_response(&self, response_content: &str) -> Result<ContentAnalysis> {
-        // Try to extract JSON from the response
-        let json_start = response_content.find('{').unwrap_or(0);
-        let json_end = response_content.rfind('}').map(|i| i + 1).unwrap_or(response_content.len());
-        let json_str = &response_content[json_start..json_end];
-        
-        let parsed: serde_json::Value = serde_json::from_str(json_str)
+        let reply: AnalysisReply = llm_json::parse(response_content)
             .context("Failed to parse AI response as JSON")?;
-        
-        // Extract themes
-        let themes = parsed.get("themes")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.iter()
-                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                .take(self.analysis_config.max_themes)
-                .collect())
-            .unwrap_or_default();
-        
-        // Extract sentiment
-        let sentiment = if let Some(sentiment_obj) = parsed.get("sentiment") {
-            SentimentAnalysis {
-                overall: sentiment_obj.get("overall")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("neutral")
-                    .to_string(),
-                confidence: sentiment_obj.get("confidence")
-                    .and_then(|v| v.as_f64())
-                    .unwrap_or(0.0) as f32,
-                emotions: sentiment_obj.get("emotions")
-                    .and_then(|v| v.as_array())
-                    .map(|arr| arr.iter()
-                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                        .collect())
-                    .unwrap_or_default(),
-            }
-        } else {
-            SentimentAnalysis::default()
+
+        let config = &self.analysis_config;
+        // Sections turned off weren't asked for; anything the model sends for them anyway is ignored
+        let themes = if config.extract_themes { reply.themes.into_iter().take(config.max_themes).collect() } else { Vec::new() };
+
+        let sentiment = match reply.sentiment.filter(|_| config.analyze_sentiment) {
+            Some(sentiment) => SentimentAnalysis {
+                overall: sentiment.overall.unwrap_or_else(|| "neutral".to_string()),
+                confidence: sentiment.confidence.unwrap_or(0.0),
+                emotions: sentiment.emotions,
+            },
+            None => SentimentAnalysis::default(),
         };
-        
-        // Extract entities
-        let entities = parsed.get("entities")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.iter()
-                .filter_map(|entity| {
-                    let text = entity.get("text")?.as_str()?.to_string();
-                    let entity_type = entity.get("entity_type")?.as_str()?.to_string();
-                    let confidence = entity.get("confidence")?.as_f64()? as f32;
-                    
-                    if confidence >= self.analysis_config.entity_confidence_threshold {
-                        Some(Entity {
-                            text,
-                            entity_type,
-                            confidence,
-                            context: entity.get("context")
-                                .and_then(|v| v.as_str())
-                                .map(|s| s.to_string()),
-                        })
-                    } else {
-                        None
-                    }
-                })
-                .take(self.analysis_config.max_entities)
-                .collect())
-            .unwrap_or_default();
-        
-        // Extract concepts
-        let concepts = parsed.get("concepts")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.iter()
-                .filter_map(|concept| {
-                    let name = concept.get("name")?.as_str()?.to_string();
-                    let importance = concept.get("importance")?.as_f64()? as f32;
-                    
-                    Some(Concept {
-                        name,
-                        description: concept.get("description")
-                            .and_then(|v| v.as_str())
-                            .map(|s| s.to_string()),
-                        related_concepts: concept.get("related_concepts")
-                            .and_then(|v| v.as_array())
-                            .map(|arr| arr.iter()
-                                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                                .collect())
-                            .unwrap_or_default(),
-                        importance,
-                    })
-                })
-                .take(self.analysis_config.max_concepts)
-                .collect())
-            .unwrap_or_default();
-        
-        // Extract other fields
-        let summary = parsed.get("summary")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
-        
-        let keywords = parsed.get("keywords")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.iter()
-                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                .collect())
-            .unwrap_or_default();
-        
-        let category = parsed.get("category")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
-        
-        let complexity_score = parsed.get("complexity_score")
-            .and_then(|v| v.as_f64())
-            .map(|f| f as f32);
-        
-        let reading_time_minutes = parsed.get("reading_time_minutes")
-            .and_then(|v| v.as_u64())
-            .map(|u| u as u32);
-        
+
+        let entities = if config.extract_entities { reply.entities } else { Vec::new() }.into_iter()
+            .filter_map(|entity| Some(Entity {
+                text: entity.text?,
+                entity_type: entity.entity_type?,
+                confidence: entity.confidence?,
+                context: entity.context,
+            }))
+            .filter(|entity| entity.confidence >= config.entity_confidence_threshold)
+            .take(config.max_entities)
+            .collect();
+
+        let concepts = if config.identify_concepts { reply.concepts } else { Vec::new() }.into_iter()
+            .filter_map(|concept| Some(Concept {
+                name: concept.name?,
+                description: concept.description,
+                related_concepts: concept.related_concepts,
+                importance: concept.importance?,
+            }))
+            .take(config.max_concepts)
+            .collect();
+
         Ok(ContentAnalysis {
             themes,
             sentiment,
             entities,
             concepts,
-            summary,
-            keywords,
-            category,
-            complexity_score,
-            reading_time_minutes,
+            summary: reply.summary.filter(|_| config.generate_summary),
+            keywords: reply.keywords,
+            category: reply.category,
+            complexity_score: reply.complexity_score,
+            reading_time_minutes: reply.reading_time_minutes.filter(|minutes| *minutes >= 0.0).map(|minutes| minutes.round() as u32),
         })
     }
 
-    /// Load the vector database from disk
+    /// Load the vector database, link graph and term index from disk, migrating legacy
+    /// stores to the segmented format
     pub fn load_vector_database(&mut self) -> Result<()> {
+        self.load_link_graph()?;
+        self.load_term_index()?;
         if Path::new(&self.embedding_cache_path).exists() {
-            let data = fs::read(&self.embedding_cache_path)
-                .context("Failed to read embedding cache file")?;
-            self.vector_database = bincode::deserialize(&data)
-                .context("Failed to deserialize vector database")?;
+            let store = embedding_store::read_store(Path::new(&self.embedding_cache_path))?;
+            self.vector_database = store.database;
+            let mut hot_vectors = self.hot_vectors();
+            hot_vectors.clear();
+            for (path, vector) in store.hot_vectors {
+                hot_vectors.insert(&path, vector);
+            }
+            drop(hot_vectors);
+            self.ann_index = store.ann_index.unwrap_or_default();
+            let dropped = self.drop_unusable_embeddings();
+            if dropped > 0 {
+                log::warn!("Dropped {} stored embedding(s) from another model or of the wrong length; `arrowhead index` embeds those notes again", dropped);
+            }
+            let stale = self.stale_embedding_count();
+            if stale > 0 {
+                log::warn!(
+                    "{} of {} stored embedding(s) were made by a model other than {}; searches mix both until `arrowhead index --if-stale` re-embeds them",
+                    stale, self.vector_database.len(), self.embedding_model,
+                );
+            }
+            let rebuilt = self.refresh_ann_index();
+            if store.migrated || dropped > 0 || rebuilt {
+                self.save_vector_database()?;
+            }
         }
         Ok(())
     }
 
-    /// Save the vector database to disk
+    fn hot_vectors(&self) -> MutexGuard<'_, HotVectorCache> {
+        self.hot_vectors.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn query_cache(&self) -> MutexGuard<'_, QueryEmbeddingCache> {
+        self.query_cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Save the vector database to disk, quantized and compressed, and the link graph and
+    /// term index beside it
     pub fn save_vector_database(&self) -> Result<()> {
-        let data = bincode::serialize(&self.vector_database)
-            .context("Failed to serialize vector database")?;
-        fs::write(&self.embedding_cache_path, data)
-            .context("Failed to write embedding cache file")?;
+        embedding_store::write_store(
+            Path::new(&self.embedding_cache_path),
+            &self.vector_database,
+            &self.hot_vectors(),
+            &self.ann_index,
+            self.embedding_storage.quantization,
+        )?;
+        self.link_graph.save(&self.link_graph_path())?;
+        self.term_index.save(&self.term_index_path())
+    }
+
+    /// Load only the link graph, for commands that don't need the embeddings
+    pub fn load_link_graph(&mut self) -> Result<()> {
+        self.link_graph = LinkGraph::load(&self.link_graph_path())?;
+        Ok(())
+    }
+
+    /// The link graph sits next to the embeddings file, as the index snapshot does
+    fn link_graph_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.links.json", self.embedding_cache_path))
+    }
+
+    pub fn link_graph(&self) -> &LinkGraph {
+        &self.link_graph
+    }
+
+    /// Load only the term index, for commands that don't need the embeddings
+    pub fn load_term_index(&mut self) -> Result<()> {
+        self.term_index = TermIndex::load(&self.term_index_path())?;
         Ok(())
     }
 
+    fn term_index_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.terms.json", self.embedding_cache_path))
+    }
+
+    pub fn term_index(&self) -> &TermIndex {
+        &self.term_index
+    }
+
+    /// Re-read the links and terms of one note into the link graph and term index, without
+    /// saving them; false when both are unchanged
+    pub fn update_note_index(&mut self, vault_path: &str, raw_content: &str) -> bool {
+        let relinked = self.link_graph.update(vault_path, raw_content);
+        self.term_index.update(vault_path, raw_content) || relinked
+    }
+
+    /// Drop notes from the link graph and term index, e.g. those deleted from the vault;
+    /// returns how many were in either
+    pub fn retain_note_index(&mut self, mut keep: impl FnMut(&str) -> bool) -> usize {
+        let unlinked = self.link_graph.retain(&mut keep);
+        self.term_index.retain(keep).max(unlinked)
+    }
+
+    /// Indexed notes linking to `vault_path`
+    pub fn get_backlinks(&self, vault_path: &str) -> Vec<String> {
+        self.link_graph.backlinks(vault_path)
+    }
+
+    /// Indexed notes `vault_path` links to
+    pub fn get_outgoing_links(&self, vault_path: &str) -> Vec<String> {
+        self.link_graph.outgoing_links(vault_path)
+    }
+
+    /// Indexed notes with no links to or from another indexed note
+    pub fn find_orphans(&self) -> Vec<String> {
+        self.link_graph.orphans()
+    }
+
+    /// Set how embeddings are stored on disk; takes effect on the next save
+    pub fn set_embedding_storage_config(&mut self, config: EmbeddingStorageConfig) {
+        self.hot_vectors().set_capacity(config.hot_cache_size);
+        self.query_cache().set_capacity(config.query_cache_size);
+        self.embedding_storage = config;
+        self.refresh_ann_index();
+    }
+
+    /// Build or rebuild the approximate index of models that have grown past the threshold
+    /// or changed too much since they were clustered; true when any was rebuilt
+    fn refresh_ann_index(&mut self) -> bool {
+        let documents: Vec<(&str, &str, &[f32])> = self.vector_database.documents()
+            .map(|doc| (document_model(&doc.model, &self.embedding_model), doc.path.as_str(), doc.embedding.as_slice()))
+            .collect();
+        let rebuilt = self.ann_index.refresh(&documents, &AnnSettings::from(&self.embedding_storage));
+        for model in &rebuilt {
+            log::debug!("Rebuilt the approximate search index of {}", model);
+        }
+        !rebuilt.is_empty()
+    }
+
+    /// Set the embedding provider, and the one to switch to when its quota runs out.
+    /// Embeddings are tagged with the model that made them.
+    pub fn set_embedding_providers(&mut self, primary: Box<dyn EmbeddingProvider>, fallback: Option<Box<dyn EmbeddingProvider>>) {
+        self.embedding_model = primary.model_id();
+        self.embedder = Some(primary);
+        self.fallback_embedder = fallback;
+    }
+
+    pub fn embedding_model(&self) -> &str {
+        &self.embedding_model
+    }
+
+    /// Why this run switched to the fallback model, if it did
+    pub fn embedding_failover(&self) -> Option<&ArrowheadError> {
+        self.embedding_failover.as_ref()
+    }
+
+    /// The fallback model's id
+    pub fn fallback_embedding_model(&self) -> Option<String> {
+        self.fallback_embedder.as_ref().map(|embedder| embedder.model_id())
+    }
+
+    /// The models in the vector database and how many documents each embedded
+    pub fn model_shares(&self) -> Vec<ModelShare> {
+        embedding_models::model_shares(&self.vector_database, &self.embedding_model)
+    }
+
+    /// Documents not embedded with the primary model, oldest first
+    pub fn minority_paths(&self, limit: usize) -> Vec<String> {
+        embedding_models::minority_paths(&self.vector_database, &self.embedding_model, limit)
+    }
+
+    /// Documents whose stored model fingerprint isn't the primary model's. None before an
+    /// embedding provider is set, since there is nothing to compare with.
+    pub fn stale_embedding_count(&self) -> usize {
+        if self.embedding_model.is_empty() {
+            return 0;
+        }
+        self.minority_paths(usize::MAX).len()
+    }
+
     /// Set semantic search configuration
     pub fn set_search_config(&mut self, config: SemanticSearchConfig) {
         self.search_config = config;
     }
 
-    /// Generate embeddings for content using the LLM client
+    /// Embed content with the primary embedding provider
     pub async fn generate_embeddings(&self, content: &str) -> Result<Vec<f32>> {
-        let llm_client = self.llm_client.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No LLM client configured for embeddings"))?;
+        let embedder = self.embedder.as_deref()
+            .ok_or_else(|| anyhow::anyhow!("No embedding provider configured; see [embeddings] in the config"))?;
+        self.embed_checked(embedder, content).await
+    }
 
-        let messages = vec![
-            Message {
-                id: uuid::Uuid::new_v4().to_string(),
-                role: MessageRole::System,
-                content: "You are an embedding generator. Generate a 768-dimensional vector embedding for the given text. Return only a JSON array of 768 floating point numbers.".to_string(),
-                timestamp: Utc::now(),
-                function_call: None,
-            },
-            Message {
-                id: uuid::Uuid::new_v4().to_string(),
-                role: MessageRole::User,
-                content: format!("Generate embedding for: {}", content),
-                timestamp: Utc::now(),
-                function_call: None,
-            }
-        ];
+    /// Embed with a named model: the primary or the fallback
+    pub async fn generate_embeddings_with(&self, model: &str, content: &str) -> Result<Vec<f32>> {
+        match &self.fallback_embedder {
+            Some(fallback) if fallback.model_id() == model && model != self.embedding_model => self.embed_checked(fallback.as_ref(), content).await,
+            _ if model == self.embedding_model => self.generate_embeddings(content).await,
+            _ => bail!("No provider configured for embedding model {}", model),
+        }
+    }
 
-        let response = llm_client.send_message(messages).await
-            .context("Failed to generate embeddings")?;
+    /// Embed with the primary model, switching to the fallback for the rest of the run when
+    /// the primary's quota runs out. Returns the model used.
+    async fn generate_embeddings_with_failover(&mut self, content: &str) -> Result<(String, Vec<f32>)> {
+        let failover = Mutex::new(self.embedding_failover.take());
+        let result = self.embed_with_failover(content, &failover).await;
+        self.embedding_failover = failover.into_inner().unwrap_or_else(|e| e.into_inner());
+        result
+    }
 
-        // Parse the embedding from the response
-        let embedding_json = response.content.trim();
-        let embedding: Vec<f32> = serde_json::from_str(embedding_json)
-            .context("Failed to parse embedding JSON")?;
+    /// `generate_embeddings_with_failover` for requests running concurrently, which share
+    /// whether the run has switched to the fallback through `failover`. Content longer than
+    /// `chunk_chars` is embedded a chunk at a time and stored as the mean of the chunk
+    /// vectors, so a note still has one vector and one search result.
+    async fn embed_with_failover(&self, content: &str, failover: &Mutex<Option<ArrowheadError>>) -> Result<(String, Vec<f32>)> {
+        let chunks = split_into_chunks(content, self.analysis_config.chunk_chars, self.analysis_config.chunk_overlap_chars);
+        if let [only] = chunks.as_slice() {
+            return self.embed_text_with_failover(only, failover).await;
+        }
+        'restart: loop {
+            let mut model: Option<String> = None;
+            let mut vectors = Vec::with_capacity(chunks.len());
+            for chunk in &chunks {
+                let (chunk_model, vector) = self.embed_text_with_failover(chunk, failover).await?;
+                // The primary ran out part-way; vectors of two models can't be pooled, so
+                // start over with the fallback
+                if model.as_ref().is_some_and(|model| *model != chunk_model) {
+                    continue 'restart;
+                }
+                model = Some(chunk_model);
+                vectors.push(vector);
+            }
+            return Ok((model.unwrap_or_default(), mean_pool(&vectors)));
+        }
+    }
 
-        if embedding.len() != EMBEDDING_DIMENSION {
-            bail!("Embedding dimension mismatch: expected {}, got {}", EMBEDDING_DIMENSION, embedding.len());
+    /// `embed_with_failover` for text sent in one request
+    async fn embed_text_with_failover(&self, content: &str, failover: &Mutex<Option<ArrowheadError>>) -> Result<(String, Vec<f32>)> {
+        let failed_over = failover.lock().is_ok_and(|failover| failover.is_some());
+        if !failed_over {
+            match self.generate_embeddings(content).await {
+                Ok(vector) => return Ok((self.embedding_model.clone(), vector)),
+                Err(e) => match ArrowheadError::of(&e) {
+                    Some(limit @ ArrowheadError::QuotaExhausted { .. }) if self.fallback_embedder.is_some() => {
+                        let mut failover = failover.lock().unwrap_or_else(|e| e.into_inner());
+                        if failover.is_none() {
+                            log::warn!("{}; embedding with {} instead", limit, self.fallback_embedding_model().unwrap_or_default());
+                            *failover = Some(limit.clone());
+                        }
+                    }
+                    _ => return Err(e),
+                },
+            }
         }
+        let fallback = self.fallback_embedder.as_deref().expect("failover requires a fallback");
+        Ok((fallback.model_id(), self.embed_checked(fallback, content).await?))
+    }
+
+    /// Embeddings of a search query for each model in the index that a provider exists for,
+    /// primary first, at most `max_models`. `cached_primary` is a stored query embedding of
+    /// the primary model. A model that can't embed the query is left out with a warning, so a
+    /// primary without quota still searches the fallback's documents.
+    pub async fn query_embeddings(&self, query: &str, max_models: usize, cached_primary: Option<&[f32]>) -> Result<Vec<(String, Vec<f32>)>> {
+        let models = embedding_models::query_models(&self.model_shares(), &self.embedding_model, max_models);
+        let mut embeddings = Vec::new();
+        let mut first_error = None;
+        for model in models {
+            let cached = match cached_primary {
+                Some(vector) if model == self.embedding_model => Some(vector.to_vec()),
+                _ => self.query_cache().get(&model, query),
+            };
+            let result = match cached {
+                Some(vector) => Ok(vector),
+                None => self.generate_embeddings_with(&model, query).await
+                    .inspect(|vector| self.query_cache().insert(&model, query, vector.clone())),
+            };
+            match result {
+                Ok(vector) => embeddings.push((model, vector)),
+                Err(e) => {
+                    log::warn!("Not searching documents embedded with {}: {:#}", model, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match (embeddings.is_empty(), first_error) {
+            (true, Some(e)) => Err(e),
+            _ => Ok(embeddings),
+        }
+    }
 
+    /// Embed and check the vector's length against the provider's dimension or, for models
+    /// whose length isn't known up front, against that model's vectors already in the index
+    async fn embed_checked(&self, embedder: &dyn EmbeddingProvider, content: &str) -> Result<Vec<f32>> {
+        let model = embedder.model_id();
+        let embedding = embedder.embed(content).await
+            .with_context(|| format!("Failed to generate embeddings with {}", model))?;
+        let expected = embedder.dimension()
+            .or_else(|| self.model_shares().into_iter().find(|share| share.model == model).map(|share| share.dimension));
+        if embedding.is_empty() {
+            bail!("{} returned an empty embedding", model);
+        }
+        if let Some(expected) = expected.filter(|expected| *expected != embedding.len()) {
+            bail!("Embedding dimension mismatch for {}: expected {}, got {}", model, expected, embedding.len());
+        }
         Ok(embedding)
     }
 
+    /// Drop stored embeddings that can't be searched: untagged ones, which were made by
+    /// prompting the chat model before embedding providers existed, and ones whose length
+    /// doesn't match their model's. Their notes are embedded again by the next index run.
+    fn drop_unusable_embeddings(&mut self) -> usize {
+        let expected: HashMap<String, usize> = [&self.embedder, &self.fallback_embedder].into_iter()
+            .flatten()
+            .filter_map(|embedder| embedder.dimension().map(|dimension| (embedder.model_id(), dimension)))
+            .collect();
+        let before = self.vector_database.len();
+        let hot_vectors = self.hot_vectors.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let ann_index = &mut self.ann_index;
+        self.vector_database.retain(|doc| {
+            let usable = !doc.model.is_empty() && expected.get(&doc.model).is_none_or(|dimension| *dimension == doc.embedding.len());
+            if !usable {
+                hot_vectors.remove(&doc.path);
+                ann_index.remove(&doc.path);
+            }
+            usable
+        });
+        before - self.vector_database.len()
+    }
+
     /// Generate embedding for a document and store it
     pub async fn embed_document(&mut self, vault_path: &str) -> Result<()> {
-        let file_data = self.get_markdown_file_data(vault_path).await?;
+        let raw_content = self.get_file(vault_path).await?;
+        let relinked = self.update_note_index(vault_path, &raw_content);
+        if self.embed_content(vault_path, &raw_content).await? || relinked {
+            self.save_vector_database()?;
+        }
+        Ok(())
+    }
+
+    /// Embed already fetched content into the in-memory database, and read its links and
+    /// terms into the link graph and term index, without saving any of them. Returns false
+    /// when the stored embedding is for the same content.
+    pub async fn embed_content(&mut self, vault_path: &str, raw_content: &str) -> Result<bool> {
+        self.update_note_index(vault_path, raw_content);
+        let Some(pending) = self.prepare_embedding(vault_path, raw_content)? else {
+            return Ok(false);
+        };
+        let (model, embedding) = self.generate_embeddings_with_failover(&pending.content).await?;
+        self.store_embedding(pending, model, embedding);
+        Ok(true)
+    }
+
+    /// Parse a note for embedding, or `None` when the stored embedding is for the same content
+    fn prepare_embedding(&self, vault_path: &str, raw_content: &str) -> Result<Option<PendingEmbedding>> {
+        self.opt_out_policy.check(vault_path, raw_content, Feature::Embedding)?;
+        let file_data = Self::parse_markdown_file(raw_content)?;
         let content_hash = self.generate_cache_key(&file_data.content);
         
         // Check if we already have a recent embedding
-        if let Some(index) = self.vector_database.path_index.get(vault_path) {
-            if let Some(existing_embedding) = self.vector_database.embeddings.get(*index) {
-                if existing_embedding.content_hash == content_hash {
-                    // Content hasn't changed, no need to re-embed
-                    return Ok(());
-                }
+        if let Some(existing_embedding) = self.vector_database.get(vault_path) {
+            let from_primary = document_model(&existing_embedding.model, &self.embedding_model) == self.embedding_model;
+            // Content hasn't changed, no need to re-embed. One from another model is
+            // re-embedded with the primary, unless the primary is out of quota.
+            if existing_embedding.content_hash == content_hash && (from_primary || self.embedding_failover.is_some()) {
+                return Ok(None);
             }
         }
 
-        // Generate new embedding
-        let embedding = self.generate_embeddings(&file_data.content).await?;
-        
         // Extract document metadata
         let title = file_data.frontmatter.tags.as_ref()
             .and_then(|tags| tags.first().cloned())
@@ -1403,47 +3026,141 @@ Content to analyze:
             length: file_data.content.len(),
             excerpt,
             modified_at: Some(Utc::now()),
+            skeleton: Some(note_skeleton::skeleton(raw_content)),
         };
 
-        let doc_embedding = DocumentEmbedding {
+        Ok(Some(PendingEmbedding {
             path: vault_path.to_string(),
-            embedding,
+            content: file_data.content,
             content_hash,
-            created_at: Utc::now(),
             metadata,
+        }))
+    }
+
+    fn store_embedding(&mut self, pending: PendingEmbedding, model: String, embedding: Vec<f32>) {
+        self.hot_vectors().insert(&pending.path, embedding.clone());
+        self.ann_index.insert(document_model(&model, &self.embedding_model), &pending.path, &embedding);
+        let doc_embedding = DocumentEmbedding {
+            path: pending.path,
+            embedding,
+            content_hash: pending.content_hash,
+            created_at: Utc::now(),
+            metadata: pending.metadata,
+            model,
         };
 
-        // Update the vector database
-        if let Some(index) = self.vector_database.path_index.get(vault_path) {
-            // Update existing embedding
-            self.vector_database.embeddings[*index] = doc_embedding;
-        } else {
-            // Add new embedding
-            let index = self.vector_database.embeddings.len();
-            self.vector_database.embeddings.push(doc_embedding);
-            self.vector_database.path_index.insert(vault_path.to_string(), index);
+        self.vector_database.insert(doc_embedding);
+        self.vector_database.last_updated = Utc::now();
+        self.refresh_ann_index();
+    }
+
+    /// Batch embed multiple documents, up to `batch_concurrency` at once, saving the vector
+    /// database once at the end. Results are in the order of `vault_paths`; a document that
+    /// fails doesn't stop the others.
+    pub async fn batch_embed_documents(&mut self, vault_paths: Vec<&str>, progress: &dyn BatchProgress) -> Result<Vec<(String, Result<()>)>> {
+        let paths: Vec<String> = vault_paths.iter().map(|p| p.to_string()).collect();
+        let fetched = self.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await;
+        let mut relinked = false;
+        for (path, raw) in &fetched {
+            if let Ok(raw) = raw {
+                relinked |= self.update_note_index(path, raw);
+            }
+        }
+        let results = self.batch_embed_contents(fetched, progress).await;
+        if relinked || results.iter().any(|(_, result)| matches!(result, Ok(true))) {
+            self.save_vector_database()?;
         }
+        Ok(results.into_iter().map(|(path, result)| (path, result.map(|_| ()))).collect())
+    }
 
-        self.vector_database.last_updated = Utc::now();
-        self.save_vector_database()?;
+    /// `embed_content` for already fetched notes, up to `batch_concurrency` requests at once.
+    /// Only the embedding requests overlap; the vector database is updated one note at a
+    /// time, in input order, and not saved. `progress` hears about each note as it completes.
+    pub async fn batch_embed_contents(&mut self, fetched: Vec<(String, Result<String>)>, progress: &dyn BatchProgress) -> Vec<(String, Result<bool>)> {
+        progress.start(fetched.len());
+        for (path, raw) in &fetched {
+            if let Ok(raw) = raw {
+                self.update_note_index(path, raw);
+            }
+        }
+        let prepared: Vec<(String, Result<Option<PendingEmbedding>>)> = fetched.into_iter()
+            .map(|(path, raw)| {
+                let pending = raw.and_then(|raw| self.prepare_embedding(&path, &raw));
+                (path, pending)
+            })
+            .collect();
 
-        Ok(())
+        let failover = Mutex::new(self.embedding_failover.take());
+        let this = &*self;
+        let embedded: Vec<(String, Result<Option<EmbeddedNote>>)> = stream::iter(prepared)
+            .map(|(path, pending)| {
+                let failover = &failover;
+                async move {
+                    let result = match pending {
+                        Ok(Some(pending)) => this.embed_with_failover(&pending.content, failover).await
+                            .map(|(model, embedding)| Some((pending, model, embedding))),
+                        Ok(None) => Ok(None),
+                        Err(e) => Err(e),
+                    };
+                    progress.advance(&path);
+                    (path, result)
+                }
+            })
+            .buffered(self.batch_concurrency.max(1))
+            .collect()
+            .await;
+        progress.finish();
+        self.embedding_failover = failover.into_inner().unwrap_or_else(|e| e.into_inner());
+
+        embedded.into_iter()
+            .map(|(path, result)| {
+                let stored = result.map(|embedded| match embedded {
+                    Some((pending, model, embedding)) => {
+                        self.store_embedding(pending, model, embedding);
+                        true
+                    }
+                    None => false,
+                });
+                (path, stored)
+            })
+            .collect()
     }
 
-    /// Batch embed multiple documents
-    pub async fn batch_embed_documents(&mut self, vault_paths: Vec<&str>) -> Result<Vec<(String, Result<()>)>> {
-        let mut results = Vec::new();
-        
-        for path in vault_paths {
-            let result = self.embed_document(path).await;
-            results.push((path.to_string(), result));
-        }
-        
-        Ok(results)
+    /// Find likely duplicate notes among indexed documents. Only pairs whose skeleton
+    /// simhashes are close are compared by embedding, so this stays cheap on large vaults.
+    pub fn find_duplicate_candidates(&self, min_similarity: f32) -> Vec<DuplicateCandidate> {
+        let indexed: Vec<(&DocumentEmbedding, u64)> = self.vector_database.documents()
+            .filter_map(|doc| doc.metadata.skeleton.as_ref().map(|skeleton| (doc, skeleton.simhash)))
+            .collect();
+        let simhashes: Vec<u64> = indexed.iter().map(|(_, hash)| *hash).collect();
+
+        let mut candidates: Vec<DuplicateCandidate> = note_skeleton::near_duplicate_pairs(&simhashes, DEFAULT_SIMHASH_DISTANCE)
+            .into_iter()
+            .filter_map(|(a, b)| {
+                let (doc_a, doc_b) = (indexed[a].0, indexed[b].0);
+                if document_model(&doc_a.model, &self.embedding_model) != document_model(&doc_b.model, &self.embedding_model) {
+                    return None;
+                }
+                let similarity = self.cosine_similarity(&doc_a.embedding, &doc_b.embedding);
+                (similarity >= min_similarity).then(|| DuplicateCandidate {
+                    path_a: doc_a.path.clone(),
+                    path_b: doc_b.path.clone(),
+                    simhash_distance: note_skeleton::hamming_distance(indexed[a].1, indexed[b].1),
+                    similarity,
+                })
+            })
+            .collect();
+
+        candidates.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap_or(std::cmp::Ordering::Equal));
+        candidates
     }
 
     /// Calculate cosine similarity between two vectors
     fn cosine_similarity(&self, a: &[f32], b: &[f32]) -> f32 {
+        // Vectors of different models can't be compared
+        if a.len() != b.len() {
+            return 0.0;
+        }
         let vec_a = DVector::from_row_slice(a);
         let vec_b = DVector::from_row_slice(b);
         
@@ -1458,105 +3175,204 @@ Content to analyze:
         }
     }
 
-    /// Perform semantic search across the vault
-    pub async fn semantic_search(&mut self, query: &str) -> Result<Vec<SemanticSearchResult>> {
+    /// Perform semantic search across the vault. Organization, linking and writing
+    /// suggestions all search through this.
+    pub async fn semantic_search(&self, query: &str) -> Result<Vec<SemanticSearchResult>> {
         // Generate embedding for the query
-        let query_embedding = self.generate_embeddings(query).await?;
-        
-        // Calculate similarities with all documents
-        let mut similarities: Vec<(usize, f32)> = self.vector_database.embeddings
-            .par_iter()
-            .enumerate()
-            .map(|(i, doc_embedding)| {
-                let similarity = self.cosine_similarity(&query_embedding, &doc_embedding.embedding);
-                (i, similarity)
+        let query_embeddings = self.query_embeddings(query, self.embedding_storage.max_query_models, None).await?;
+        let mut results = self.semantic_search_with_embeddings(query, &query_embeddings);
+        if self.search_config.include_snippets {
+            self.load_snippets(query, &mut results).await;
+        }
+        Ok(results)
+    }
+
+    /// Semantic search with an already computed query embedding of the primary model, e.g.
+    /// one cached with a saved search
+    pub fn semantic_search_with_embedding(&self, query: &str, query_embedding: &[f32]) -> Vec<SemanticSearchResult> {
+        let model = self.embedding_model.clone();
+        self.semantic_search_with_embeddings(query, &[(model, query_embedding.to_vec())])
+    }
+
+    /// Semantic search with one query embedding per model. Each model's documents are
+    /// compared with its own query embedding and the lists are fused on the primary's scale.
+    pub fn semantic_search_with_embeddings(&self, query: &str, query_embeddings: &[(String, Vec<f32>)]) -> Vec<SemanticSearchResult> {
+        self.rank_documents(query, query_embeddings, self.search_config.max_results, self.search_config.min_similarity)
+    }
+
+    /// Indexed notes most similar to the note at `vault_path`, best first and without it,
+    /// down to `min_similarity`. An indexed note is compared by its stored embedding;
+    /// otherwise `content` is embedded.
+    pub async fn related_notes(&self, vault_path: &str, content: &str, limit: usize, min_similarity: f32) -> Result<Vec<SemanticSearchResult>> {
+        let query_embeddings = match self.vector_database.get(vault_path) {
+            Some(doc) => vec![(document_model(&doc.model, &self.embedding_model).to_string(), doc.embedding.clone())],
+            None => self.query_embeddings(content, self.embedding_storage.max_query_models, None).await?,
+        };
+        let mut results = self.rank_documents("", &query_embeddings, limit + 1, min_similarity);
+        results.retain(|result| result.path != vault_path);
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    /// Write or remove one Arrowhead-managed section of a note (see `managed_sections`),
+    /// re-reading and reapplying the edit if the note changes underneath. `None` when the
+    /// note already had the section as given.
+    pub async fn update_managed_section(&self, vault_path: &str, edit: SectionEdit) -> Result<Option<TransactionReport>> {
+        update_sections(self, vault_path, &[edit]).await
+    }
+
+    fn rank_documents(&self, query: &str, query_embeddings: &[(String, Vec<f32>)], max_results: usize, min_similarity: f32) -> Vec<SemanticSearchResult> {
+        let rerank_window = max_results * 2;
+        let documents = self.search_candidates(query_embeddings);
+        let mut hot_vectors = self.hot_vectors();
+        let lists: Vec<Vec<(usize, f32)>> = query_embeddings.iter()
+            .map(|(model, query_embedding)| {
+                // Calculate similarities with the model's documents
+                let mut similarities: Vec<(usize, f32)> = documents
+                    .par_iter()
+                    .enumerate()
+                    .filter(|(_, doc_embedding)| document_model(&doc_embedding.model, &self.embedding_model) == model)
+                    .map(|(i, doc_embedding)| {
+                        let similarity = self.cosine_similarity(query_embedding, &doc_embedding.embedding);
+                        (i, similarity)
+                    })
+                    .collect();
+
+                // Sort by similarity (descending)
+                similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+                // Stored vectors are quantized; re-rank the head of the list exactly where the
+                // full-precision vector is still in the hot cache
+                let rerank_window = rerank_window.min(similarities.len());
+                for (index, similarity) in similarities[..rerank_window].iter_mut() {
+                    if let Some(exact) = hot_vectors.get(&documents[*index].path) {
+                        *similarity = self.cosine_similarity(query_embedding, exact);
+                    }
+                }
+                similarities
             })
             .collect();
-
-        // Sort by similarity (descending)
-        similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        let similarities = embedding_models::fuse(lists);
 
         // Filter by minimum similarity and take top results
+        let terms = snippets::query_terms(query);
         let results: Vec<SemanticSearchResult> = similarities
             .into_iter()
-            .filter(|(_, sim)| *sim >= self.search_config.min_similarity)
-            .take(self.search_config.max_results)
-            .map(|(index, similarity)| {
-                let doc_embedding = &self.vector_database.embeddings[index];
-                let snippet = if self.search_config.include_snippets {
-                    self.generate_snippet(&doc_embedding.path, query).unwrap_or_else(|_| doc_embedding.metadata.excerpt.clone())
-                } else {
-                    String::new()
-                };
-                
-                SemanticSearchResult {
-                    path: doc_embedding.path.clone(),
-                    similarity,
-                    metadata: doc_embedding.metadata.clone(),
-                    snippet,
-                    // TODO: Implement search result highlighting
-                    // Current state: Empty highlights vector, no text highlighting
-                    // 
-                    // Implementation requirements:
-                    // 1. Query term extraction:
-                    //    - Parse search query to extract individual terms
-                    //    - Handle quoted phrases, wildcards, and special operators
-                    //    - Support stemming and fuzzy matching
-                    // 2. Text highlighting:
-                    //    - Find all occurrences of query terms in the snippet
-                    //    - Calculate character positions for highlighting
-                    //    - Handle case-insensitive matching
-                    //    - Support partial word matches and synonyms
-                    // 3. Highlight data structure:
-                    //    - Create highlight spans with start/end positions
-                    //    - Include highlight type (exact match, fuzzy, etc.)
-                    //    - Support multiple highlight colors/styles
-                    // 4. Context preservation:
-                    //    - Ensure highlights align with snippet boundaries
-                    //    - Handle multi-line highlights properly
-                    //    - Preserve markdown formatting around highlights
-                    // 5. Performance considerations:
-                    //    - Optimize for large documents and many search terms
-                    //    - Cache highlighting results for repeated searches
-                    //    - Limit highlight processing time for responsiveness
-                    highlights: vec![], // TODO: Implement highlighting
-                }
-            })
+            .filter(|(_, sim)| *sim >= min_similarity)
+            .take(max_results)
+            .map(|(index, similarity)| self.search_result(documents[index], similarity, &terms))
             .collect();
+        for result in &results {
+            hot_vectors.touch(&result.path);
+        }
+        results
+    }
+
+    /// Documents to compare with the query embeddings: for a model with enough documents,
+    /// those in the index lists nearest its query, otherwise all of the model's documents
+    fn search_candidates(&self, query_embeddings: &[(String, Vec<f32>)]) -> Vec<&DocumentEmbedding> {
+        let settings = AnnSettings::from(&self.embedding_storage);
+        let (indexed, exact): (Vec<_>, Vec<_>) = query_embeddings.iter()
+            .partition(|(model, _)| self.ann_index.covers(model, &settings));
+        let mut documents: Vec<&DocumentEmbedding> = if exact.is_empty() {
+            Vec::new()
+        } else {
+            self.vector_database.documents()
+                .filter(|doc| exact.iter().any(|(model, _)| document_model(&doc.model, &self.embedding_model) == model))
+                .collect()
+        };
+        for (model, query_embedding) in indexed {
+            documents.extend(self.ann_index.candidates(model, query_embedding, settings.probes)
+                .into_iter()
+                .filter_map(|path| self.vector_database.get(path)));
+        }
+        documents
+    }
+
+    /// A search result for an indexed document, its snippet cut from the stored excerpt
+    /// until `load_snippets` replaces it
+    fn search_result(&self, doc_embedding: &DocumentEmbedding, similarity: f32, terms: &[String]) -> SemanticSearchResult {
+        let snippet = if self.search_config.include_snippets {
+            snippets::snippet(&doc_embedding.metadata.excerpt, terms, self.search_config.snippet_length)
+        } else {
+            String::new()
+        };
+        SemanticSearchResult {
+            path: doc_embedding.path.clone(),
+            similarity,
+            metadata: doc_embedding.metadata.clone(),
+            highlights: snippets::highlights(&snippet, terms),
+            snippet,
+        }
+    }
 
-        Ok(results)
+    /// Replace the results' snippets with the passages of the notes that best match the
+    /// query; results whose note can't be read keep their excerpt snippet
+    async fn load_snippets(&self, query: &str, results: &mut [SemanticSearchResult]) {
+        let terms = snippets::query_terms(query);
+        let snippets = futures::future::join_all(results.iter().map(|result| self.generate_snippet(&result.path, &terms))).await;
+        for (result, snippet) in results.iter_mut().zip(snippets) {
+            match snippet {
+                Ok(snippet) => {
+                    result.highlights = snippets::highlights(&snippet, &terms);
+                    result.snippet = snippet;
+                }
+                Err(e) => log::debug!("keeping the excerpt snippet of {}: {}", result.path, e),
+            }
+        }
     }
 
-    /// Generate a relevant snippet from a document for a query
-    fn generate_snippet(&self, _vault_path: &str, _query: &str) -> Result<String> {
-        // This is a simplified implementation
-        // In a real implementation, you would:
-        // 1. Load the document content
-        // 2. Find the most relevant passages
-        // 3. Extract snippets around those passages
-        // 4. Highlight matching terms
-        
-        // For now, return a placeholder
-        Ok("Document snippet...".to_string())
+    /// The passage of a note with the most query terms, cut on word boundaries
+    async fn generate_snippet(&self, vault_path: &str, terms: &[String]) -> Result<String> {
+        let content = self.get_file(vault_path).await?;
+        Ok(snippets::snippet(&content, terms, self.search_config.snippet_length))
     }
 
     /// Get all documents in the vector database
     pub fn get_indexed_documents(&self) -> Vec<&DocumentEmbedding> {
-        self.vector_database.embeddings.iter().collect()
+        self.vector_database.documents().collect()
     }
 
     /// Remove a document from the vector database
     pub fn remove_document_embedding(&mut self, vault_path: &str) -> Result<()> {
-        if let Some(index) = self.vector_database.path_index.remove(vault_path) {
-            self.vector_database.embeddings.remove(index);
-            
-            // Update indices for remaining documents
-            for (path, idx) in self.vector_database.path_index.iter_mut() {
-                if *idx > index {
-                    *idx -= 1;
-                }
+        let unlinked = self.link_graph.remove(vault_path);
+        let unindexed = self.term_index.remove(vault_path);
+        if self.vector_database.remove(vault_path).is_some() || unlinked || unindexed {
+            self.hot_vectors().remove(vault_path);
+            self.ann_index.remove(vault_path);
+            self.refresh_ann_index();
+            self.vector_database.last_updated = Utc::now();
+            self.save_vector_database()?;
+        }
+        Ok(())
+    }
+
+    /// Drop the embeddings of `paths`, keeping their link graph and term index entries,
+    /// and save once. Returns how many embeddings were removed.
+    pub fn prune_embeddings(&mut self, paths: &[String]) -> Result<usize> {
+        let mut removed = 0;
+        for path in paths {
+            if self.vector_database.remove(path).is_some() {
+                self.hot_vectors().remove(path);
+                self.ann_index.remove(path);
+                removed += 1;
             }
-            
+        }
+        if removed > 0 {
+            self.refresh_ann_index();
+            self.vector_database.last_updated = Utc::now();
+            self.save_vector_database()?;
+        }
+        Ok(removed)
+    }
+
+    /// Move the embedding of a renamed document to its new path
+    pub fn rename_document_embedding(&mut self, from: &str, to: &str) -> Result<()> {
+        let relinked = self.link_graph.rename(from, to);
+        let reindexed = self.term_index.rename(from, to);
+        if self.vector_database.rename(from, to) || relinked || reindexed {
+            self.hot_vectors().rename(from, to);
+            self.ann_index.rename(from, to);
             self.vector_database.last_updated = Utc::now();
             self.save_vector_database()?;
         }
@@ -1565,8 +3381,11 @@ Content to analyze:
 
     /// Clear all embeddings from the vector database
     pub fn clear_vector_database(&mut self) -> Result<()> {
-        self.vector_database.embeddings.clear();
-        self.vector_database.path_index.clear();
+        self.vector_database.clear();
+        self.link_graph.clear();
+        self.term_index.clear();
+        self.hot_vectors().clear();
+        self.ann_index.clear();
         self.vector_database.last_updated = Utc::now();
         self.save_vector_database()?;
         Ok(())
@@ -1575,10 +3394,24 @@ Content to analyze:
     /// Get vector database statistics
     pub fn get_vector_database_stats(&self) -> HashMap<String, serde_json::Value> {
         let mut stats = HashMap::new();
-        stats.insert("total_documents".to_string(), serde_json::Value::Number(self.vector_database.embeddings.len().into()));
+        stats.insert("total_documents".to_string(), serde_json::Value::Number(self.vector_database.len().into()));
         stats.insert("last_updated".to_string(), serde_json::Value::String(self.vector_database.last_updated.to_rfc3339()));
         stats.insert("version".to_string(), serde_json::Value::String(self.vector_database.version.clone()));
-        stats.insert("embedding_dimension".to_string(), serde_json::Value::Number(EMBEDDING_DIMENSION.into()));
+        let shares = self.model_shares();
+        let dimension = self.embedder.as_ref().and_then(|embedder| embedder.dimension())
+            .or_else(|| shares.iter().find(|share| share.model == self.embedding_model).map(|share| share.dimension));
+        stats.insert("embedding_dimension".to_string(), serde_json::json!(dimension));
+        stats.insert("embedding_models".to_string(), serde_json::json!(shares));
+        stats.insert("stale_embeddings".to_string(), serde_json::Value::Number(self.stale_embedding_count().into()));
+        stats.insert("needs_reindex".to_string(), serde_json::Value::Bool(self.stale_embedding_count() > 0));
+        stats.insert("quantization".to_string(), serde_json::Value::String(self.embedding_storage.quantization.to_string()));
+        stats.insert("hot_cache_documents".to_string(), serde_json::Value::Number(self.hot_vectors().len().into()));
+        let query_cache = self.query_cache();
+        stats.insert("query_cache_entries".to_string(), serde_json::Value::Number(query_cache.len().into()));
+        stats.insert("query_cache_hits".to_string(), serde_json::Value::Number(query_cache.hits().into()));
+        stats.insert("query_cache_misses".to_string(), serde_json::Value::Number(query_cache.misses().into()));
+        let on_disk_bytes = fs::metadata(&self.embedding_cache_path).map(|m| m.len()).unwrap_or(0);
+        stats.insert("on_disk_bytes".to_string(), serde_json::Value::Number(on_disk_bytes.into()));
         stats
     }
 
@@ -1597,22 +3430,27 @@ Content to analyze:
     pub fn save_template_database(&self) -> Result<()> {
         let data = bincode::serialize(&self.template_database)
             .context("Failed to serialize template database")?;
-        fs::write(&self.template_cache_path, data)
-            .context("Failed to write template cache file")?;
-        Ok(())
-    }
-
-    /// Analyze existing notes to discover template patterns
-    pub async fn discover_template_patterns(&mut self, vault_paths: Vec<&str>) -> Result<Vec<TemplatePattern>> {
-        let mut patterns = Vec::new();
-        let mut document_analyses = Vec::new();
-
-        // Analyze all provided documents
-        for path in vault_paths {
-            match self.get_markdown_file_data(path).await {
-                Ok(file_data) => {
-                    let analysis = self.analyze_content(&file_data.content).await?;
-                    document_analyses.push((path.to_string(), file_data, analysis));
+        cache::write_atomic(Path::new(&self.template_cache_path), &data)
+            .context("Failed to write template cache file")
+    }
+
+    /// Discover template patterns by clustering the embeddings of existing notes, embedding
+    /// the ones not indexed yet. Clusters of fewer than `min_cluster_size` notes are dropped;
+    /// each pattern is named after its notes' dominant category or theme.
+    pub async fn discover_template_patterns(&mut self, vault_paths: Vec<&str>, min_cluster_size: usize) -> Result<Vec<TemplatePattern>> {
+        // Group documents by structure first so notes sharing an outline need only one AI analysis
+        let paths: Vec<String> = vault_paths.iter().map(|p| p.to_string()).collect();
+        let mut structural_groups: Vec<StructuralGroup> = Vec::new();
+        for (path, raw) in self.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await {
+            let parsed = raw.and_then(|raw| Ok((Self::parse_markdown_file(&raw)?, raw)));
+            match parsed {
+                Ok((file_data, raw)) => {
+                    let key = note_skeleton::skeleton(&raw).structure_key();
+                    let existing = structural_groups.iter_mut().find(|(k, _)| !key.is_empty() && *k == key);
+                    match existing {
+                        Some((_, members)) => members.push((path, raw, file_data)),
+                        None => structural_groups.push((key, vec![(path, raw, file_data)])),
+                    }
                 }
                 Err(e) => {
                     log::warn!("Failed to analyze document {}: {}", path, e);
@@ -1620,12 +3458,54 @@ Content to analyze:
             }
         }
 
-        // Group documents by similar patterns
-        let grouped_patterns = self.group_documents_by_patterns(&document_analyses)?;
+        let mut documents = Vec::new();
+        let mut vectors = Vec::new();
+        let mut embedded = false;
+        for (_, members) in structural_groups {
+            let analysis = match members[0].2.frontmatter.ai_analysis.clone() {
+                Some(analysis) => analysis,
+                None => self.analyze_content(&members[0].2.content).await.unwrap_or_else(|e| {
+                    log::warn!("Failed to analyze document {}: {}", members[0].0, e);
+                    ContentAnalysis::default()
+                }),
+            };
+            for (path, raw, file_data) in members {
+                match self.embed_content(&path, &raw).await {
+                    Ok(changed) => embedded |= changed,
+                    Err(e) => {
+                        log::warn!("Failed to embed document {}: {}", path, e);
+                        continue;
+                    }
+                }
+                if let Some(document) = self.vector_database.get(&path) {
+                    vectors.push(document.embedding.clone());
+                    documents.push((path, file_data, analysis.clone()));
+                }
+            }
+        }
+        if embedded {
+            self.save_vector_database()?;
+        }
+
+        // Vectors of different models can't be compared; cluster those of the most common length
+        let mut lengths: HashMap<usize, usize> = HashMap::new();
+        for vector in &vectors {
+            *lengths.entry(vector.len()).or_default() += 1;
+        }
+        let dimension = lengths.into_iter().max_by_key(|(length, count)| (*count, *length)).map_or(0, |(length, _)| length);
+        let (documents, vectors): (Vec<_>, Vec<_>) = documents.into_iter().zip(vectors).filter(|(_, vector)| vector.len() == dimension).unzip();
 
-        // Generate template patterns from groups
-        for (pattern_name, group) in grouped_patterns {
-            let pattern = self.create_template_pattern(pattern_name, group)?;
+        let mut patterns: Vec<TemplatePattern> = Vec::new();
+        for found in clustering::cluster(&vectors, PATTERN_SIMILARITY_THRESHOLD) {
+            if found.members.len() < min_cluster_size.max(1) {
+                continue;
+            }
+            let group: Vec<&(String, MarkdownFile, ContentAnalysis)> = found.members.iter().map(|&i| &documents[i]).collect();
+            let mut pattern = self.create_template_pattern(&group, found.cohesion);
+            let taken = patterns.iter().filter(|p| p.name == pattern.name || p.name.starts_with(&format!("{} (", pattern.name))).count();
+            if taken > 0 {
+                pattern.name = format!("{} ({})", pattern.name, taken + 1);
+            }
             patterns.push(pattern);
         }
 
@@ -1637,48 +3517,14 @@ Content to analyze:
         Ok(patterns)
     }
 
-    /// Group documents by similar patterns
-    fn group_documents_by_patterns(&self, documents: &[(String, MarkdownFile, ContentAnalysis)]) -> Result<HashMap<String, Vec<(String, MarkdownFile, ContentAnalysis)>>> {
-        let mut groups: HashMap<String, Vec<(String, MarkdownFile, ContentAnalysis)>> = HashMap::new();
-
-        for (path, file_data, analysis) in documents {
-            // Determine pattern category based on analysis
-            let pattern_category = self.determine_pattern_category(analysis, file_data);
-            
-            groups.entry(pattern_category)
-                .or_insert_with(Vec::new)
-                .push((path.clone(), file_data.clone(), analysis.clone()));
-        }
-
-        Ok(groups)
-    }
-
-    /// Determine pattern category for a document
-    fn determine_pattern_category(&self, analysis: &ContentAnalysis, file_data: &MarkdownFile) -> String {
-        // Use AI analysis and content structure to determine category
-        if let Some(ref category) = analysis.category {
-            return category.clone();
-        }
-
-        // Fallback to tag-based categorization
-        if let Some(ref tags) = file_data.frontmatter.tags {
-            if let Some(primary_tag) = tags.first() {
-                return primary_tag.clone();
-            }
-        }
-
-        // Default category
-        "general".to_string()
-    }
-
-    /// Create a template pattern from a group of similar documents
-    fn create_template_pattern(&self, pattern_name: String, group: Vec<(String, MarkdownFile, ContentAnalysis)>) -> Result<TemplatePattern> {
+    /// Create a template pattern from a cluster of similar documents, most central first
+    fn create_template_pattern(&self, group: &[&(String, MarkdownFile, ContentAnalysis)], cohesion: f32) -> TemplatePattern {
         let mut common_tags = HashMap::new();
         let mut structure_elements = HashMap::new();
         let mut frontmatter_fields = HashMap::new();
 
         // Analyze common elements across the group
-        for (path, file_data, analysis) in &group {
+        for (_, file_data, analysis) in group {
             // Count common tags
             if let Some(ref tags) = file_data.frontmatter.tags {
                 for tag in tags {
@@ -1725,98 +3571,22 @@ Content to analyze:
             .map(|(field, _)| field)
             .collect();
 
-        // Calculate confidence based on consistency
-        let confidence = self.calculate_pattern_confidence(&group);
-
-        // Get example paths (limit to 3)
+        // The medoid and the notes nearest it (limit to 3)
         let examples: Vec<String> = group.iter()
             .take(3)
             .map(|(path, _, _)| path.clone())
             .collect();
 
-        Ok(TemplatePattern {
+        TemplatePattern {
             id: uuid::Uuid::new_v4().to_string(),
-            name: pattern_name.clone(),
+            name: pattern_name(group),
             description: format!("Pattern discovered from {} similar documents", group_size),
             structure_elements: filtered_structure,
             common_tags: filtered_tags,
             typical_frontmatter: filtered_frontmatter,
-            confidence,
+            confidence: cohesion.clamp(0.0, 1.0),
             match_count: group_size as u32,
             examples,
-        })
-    }
-
-    /// Calculate confidence score for a pattern
-    fn calculate_pattern_confidence(&self, group: &[(String, MarkdownFile, ContentAnalysis)]) -> f32 {
-        if group.len() < 2 {
-            return 0.5;
-        }
-
-        // Calculate confidence based on similarity of documents in the group
-        let mut total_similarity = 0.0;
-        let mut comparison_count = 0;
-
-        for i in 0..group.len() {
-            for j in (i + 1)..group.len() {
-                let similarity = self.calculate_document_similarity(&group[i].2, &group[j].2);
-                total_similarity += similarity;
-                comparison_count += 1;
-            }
-        }
-
-        if comparison_count > 0 {
-            total_similarity / comparison_count as f32
-        } else {
-            0.5
-        }
-    }
-
-    /// Calculate similarity between two content analyses
-    fn calculate_document_similarity(&self, analysis1: &ContentAnalysis, analysis2: &ContentAnalysis) -> f32 {
-        let mut similarity_score = 0.0;
-        let mut total_factors = 0;
-
-        // Compare themes
-        if !analysis1.themes.is_empty() && !analysis2.themes.is_empty() {
-            let theme_similarity = self.calculate_list_similarity(&analysis1.themes, &analysis2.themes);
-            similarity_score += theme_similarity;
-            total_factors += 1;
-        }
-
-        // Compare categories
-        if analysis1.category.is_some() && analysis2.category.is_some() {
-            let category_similarity = if analysis1.category == analysis2.category { 1.0 } else { 0.0 };
-            similarity_score += category_similarity;
-            total_factors += 1;
-        }
-
-        // Compare keywords
-        if !analysis1.keywords.is_empty() && !analysis2.keywords.is_empty() {
-            let keyword_similarity = self.calculate_list_similarity(&analysis1.keywords, &analysis2.keywords);
-            similarity_score += keyword_similarity;
-            total_factors += 1;
-        }
-
-        if total_factors > 0 {
-            similarity_score / total_factors as f32
-        } else {
-            0.0
-        }
-    }
-
-    /// Calculate similarity between two lists of strings
-    fn calculate_list_similarity(&self, list1: &[String], list2: &[String]) -> f32 {
-        let set1: std::collections::HashSet<_> = list1.iter().collect();
-        let set2: std::collections::HashSet<_> = list2.iter().collect();
-        
-        let intersection = set1.intersection(&set2).count();
-        let union = set1.union(&set2).count();
-        
-        if union > 0 {
-            intersection as f32 / union as f32
-        } else {
-            0.0
         }
     }
 
@@ -1896,7 +3666,7 @@ Content to analyze:
             Message {
                 id: uuid::Uuid::new_v4().to_string(),
                 role: MessageRole::System,
-                content: "You are an expert note template generator. Create structured, useful templates for note-taking in Obsidian. Return your response as a JSON object with the requested template structure.".to_string(),
+                content: self.generation_prompt("You are an expert note template generator. Create structured, useful templates for note-taking in Obsidian. Return your response as a JSON object with the requested template structure."),
                 timestamp: Utc::now(),
                 function_call: None,
             },
@@ -1913,116 +3683,75 @@ Content to analyze:
             .context("Failed to generate template with AI")?;
 
         // Parse the template from the response
-        self.parse_template_from_response(&response.content, request)
+        let mut template = self.parse_template_from_response(&response.content, request)?;
+        self.enforce_glossary_on_components(&mut template.components);
+        Ok(template)
+    }
+
+    /// Apply the glossary to generated text inside template components
+    fn enforce_glossary_on_components(&self, components: &mut [TemplateComponent]) {
+        for component in components {
+            match component {
+                TemplateComponent::Text(text) => *text = self.enforce_glossary(text),
+                TemplateComponent::AiSuggestion { fallback, .. } => *fallback = self.enforce_glossary(fallback),
+                TemplateComponent::Conditional { content, .. } | TemplateComponent::Repeating { content, .. } => {
+                    self.enforce_glossary_on_components(content);
+                }
+                _ => {}
+            }
+        }
     }
 
     /// Create a prompt for template generation
     fn create_template_generation_prompt(&self, request: &TemplateGenerationRequest, patterns: &[TemplatePattern]) -> String {
-        let mut prompt = format!(
-            "Generate a {} template for note-taking. The template should be {} complexity.\n\n",
-            request.template_type, request.preferences.complexity
-        );
-
+        let mut details = String::new();
         if let Some(ref topic) = request.topic {
-            prompt.push_str(&format!("Topic/Domain: {}\n", topic));
+            details.push_str(&format!("Topic/Domain: {}\n", topic));
         }
 
         if let Some(ref context) = request.context {
-            prompt.push_str(&format!("Context: {}\n", context));
+            details.push_str(&format!("Context: {}\n", context));
         }
 
         if !request.required_fields.is_empty() {
-            prompt.push_str(&format!("Required fields: {}\n", request.required_fields.join(", ")));
+            details.push_str(&format!("Required fields: {}\n", request.required_fields.join(", ")));
         }
 
         if !patterns.is_empty() {
-            prompt.push_str("\nBased on these discovered patterns:\n");
+            details.push_str("\nBased on these discovered patterns:\n");
             for pattern in patterns {
-                prompt.push_str(&format!("- {}: {} (used in {} documents)\n", 
+                details.push_str(&format!("- {}: {} (used in {} documents)\n", 
                     pattern.name, pattern.description, pattern.match_count));
             }
         }
 
-        prompt.push_str(&format!(r#"
-Return a JSON object with this structure:
-{{
-  "id": "unique-template-id",
-  "name": "Template Name",
-  "description": "Template description",
-  "category": "{}",
-  "components": [
-    {{"type": "Text", "content": "Static text"}},
-    {{"type": "Placeholder", "name": "field_name", "hint": "Description", "required": true}},
-    {{"type": "AiSuggestion", "prompt": "Generate suggestion for...", "fallback": "Default text"}}
-  ],
-  "frontmatter_fields": [
-    {{
-      "name": "field_name",
-      "field_type": "string",
-      "default_value": "default",
-      "required": true,
-      "description": "Field description"
-    }}
-  ],
-  "tags": ["template", "{}"]
-}}
-"#, request.template_type, request.template_type));
-
-        prompt
+        let template_type = request.template_type.to_string();
+        let complexity = request.preferences.complexity.to_string();
+        crate::prompts::render("template_generation", &[
+            ("template_type", &template_type),
+            ("complexity", &complexity),
+            ("details", &details),
+            ("topic", request.topic.as_deref().unwrap_or("")),
+            ("context", request.context.as_deref().unwrap_or("")),
+            ("required_fields", &request.required_fields.join(", ")),
+        ])
     }
 
     /// Parse template from AI response
     fn parse_template_from_response(&self, response: &str, request: &TemplateGenerationRequest) -> Result<NoteTemplate> {
-        // Extract JSON from the response
-        let json_start = response.find('{').unwrap_or(0);
-        let json_end = response.rfind('}').map(|i| i + 1).unwrap_or(response.len());
-        let json_str = &response[json_start..json_end];
-        
-        let parsed: serde_json::Value = serde_json::from_str(json_str)
+        let reply: TemplateReply = llm_json::parse(response)
             .context("Failed to parse template JSON")?;
 
-        // Extract template fields
-        let id = parsed.get("id")
-            .and_then(|v| v.as_str())
-            .unwrap_or(&uuid::Uuid::new_v4().to_string())
-            .to_string();
-
-        let name = parsed.get("name")
-            .and_then(|v| v.as_str())
-            .unwrap_or("Generated Template")
-            .to_string();
-
-        let description = parsed.get("description")
-            .and_then(|v| v.as_str())
-            .unwrap_or("AI-generated template")
-            .to_string();
-
-        let category = parsed.get("category")
-            .and_then(|v| v.as_str())
-            .unwrap_or(&request.template_type)
-            .to_string();
-
-        // Parse components
-        let components = self.parse_template_components(&parsed)?;
-
-        // Parse frontmatter fields
-        let frontmatter_fields = self.parse_frontmatter_fields(&parsed)?;
-
-        // Parse tags
-        let tags = parsed.get("tags")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.iter()
-                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                .collect())
-            .unwrap_or_else(|| vec![category.clone()]);
+        let category = reply.category.unwrap_or_else(|| request.template_type.clone());
+        let tags = if reply.tags.is_empty() { vec![category.clone()] } else { reply.tags };
 
         Ok(NoteTemplate {
-            id,
-            name,
-            description,
+            id: reply.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            name: reply.name.unwrap_or_else(|| "Generated Template".to_string()),
+            description: reply.description.unwrap_or_else(|| "AI-generated template".to_string()),
             category,
-            components,
-            frontmatter_fields,
+            components: reply.components.into_iter().filter_map(ComponentReply::into_component).collect(),
+            frontmatter_fields: reply.frontmatter_fields.into_iter().map(FieldReply::into_field).collect(),
             tags,
             usage_stats: TemplateUsageStats::default(),
             created_at: Utc::now(),
@@ -2030,75 +3759,6 @@ Return a JSON object with this structure:
         })
     }
 
-    /// Parse template components from JSON
-    fn parse_template_components(&self, json: &serde_json::Value) -> Result<Vec<TemplateComponent>> {
-        let empty_vec = vec![];
-        let components_array = json.get("components")
-            .and_then(|v| v.as_array())
-            .unwrap_or(&empty_vec);
-
-        let mut components = Vec::new();
-        for component_value in components_array {
-            if let Some(component_type) = component_value.get("type").and_then(|v| v.as_str()) {
-                match component_type {
-                    "Text" => {
-                        if let Some(content) = component_value.get("content").and_then(|v| v.as_str()) {
-                            components.push(TemplateComponent::Text(content.to_string()));
-                        }
-                    }
-                    "Placeholder" => {
-                        let name = component_value.get("name").and_then(|v| v.as_str()).unwrap_or("field").to_string();
-                        let hint = component_value.get("hint").and_then(|v| v.as_str()).unwrap_or("Enter value").to_string();
-                        let required = component_value.get("required").and_then(|v| v.as_bool()).unwrap_or(false);
-                        components.push(TemplateComponent::Placeholder { name, hint, required });
-                    }
-                    "AiSuggestion" => {
-                        let prompt = component_value.get("prompt").and_then(|v| v.as_str()).unwrap_or("Generate content").to_string();
-                        let fallback = component_value.get("fallback").and_then(|v| v.as_str()).unwrap_or("Content here").to_string();
-                        components.push(TemplateComponent::AiSuggestion { prompt, fallback });
-                    }
-                    "Tag" => {
-                        if let Some(tag) = component_value.get("tag").and_then(|v| v.as_str()) {
-                            components.push(TemplateComponent::Tag(tag.to_string()));
-                        }
-                    }
-                    _ => {
-                        // Unknown component type, skip
-                    }
-                }
-            }
-        }
-
-        Ok(components)
-    }
-
-    /// Parse frontmatter fields from JSON
-    fn parse_frontmatter_fields(&self, json: &serde_json::Value) -> Result<Vec<FrontmatterField>> {
-        let empty_vec = vec![];
-        let fields_array = json.get("frontmatter_fields")
-            .and_then(|v| v.as_array())
-            .unwrap_or(&empty_vec);
-
-        let mut fields = Vec::new();
-        for field_value in fields_array {
-            let name = field_value.get("name").and_then(|v| v.as_str()).unwrap_or("field").to_string();
-            let field_type = field_value.get("field_type").and_then(|v| v.as_str()).unwrap_or("string").to_string();
-            let default_value = field_value.get("default_value").and_then(|v| v.as_str()).map(|s| s.to_string());
-            let required = field_value.get("required").and_then(|v| v.as_bool()).unwrap_or(false);
-            let description = field_value.get("description").and_then(|v| v.as_str()).map(|s| s.to_string());
-
-            fields.push(FrontmatterField {
-                name,
-                field_type,
-                default_value,
-                required,
-                description,
-            });
-        }
-
-        Ok(fields)
-    }
-
     /// Generate suggestions for template improvement
     fn generate_template_suggestions(&self, template: &NoteTemplate, request: &TemplateGenerationRequest) -> Vec<String> {
         let mut suggestions = Vec::new();
@@ -2216,74 +3876,220 @@ Return a JSON object with this structure:
     }
 
     /// Render a template with provided values
-    pub fn render_template(&self, template: &NoteTemplate, values: &HashMap<String, String>) -> Result<String> {
+    pub fn render_template(&self, template: &NoteTemplate, values: &TemplateValues) -> Result<String> {
+        self.render_template_with_suggestions(template, values, &HashMap::new())
+    }
+
+    /// Render a template, using `suggestions` (keyed by prompt) for its AI suggestions and
+    /// their fallback text where there is none
+    pub fn render_template_with_suggestions(&self, template: &NoteTemplate, values: &TemplateValues, suggestions: &HashMap<String, String>) -> Result<String> {
         let mut rendered_content = String::new();
         
         // Add frontmatter
         rendered_content.push_str("---\n");
         for field in &template.frontmatter_fields {
-            let empty_string = String::new();
-            let value = values.get(&field.name)
-                .or(field.default_value.as_ref())
-                .unwrap_or(&empty_string);
+            let value = values.text(&field.name)
+                .or_else(|| field.default_value.clone())
+                .unwrap_or_default();
             rendered_content.push_str(&format!("{}: {}\n", field.name, value));
         }
         rendered_content.push_str("---\n\n");
         
-        // Render components
         for component in &template.components {
-            match component {
-                TemplateComponent::Text(text) => {
-                    rendered_content.push_str(text);
-                    rendered_content.push('\n');
+            self.render_component(component, values, suggestions, &mut rendered_content);
+        }
+
+        Ok(rendered_content)
+    }
+
+    fn render_component(&self, component: &TemplateComponent, values: &TemplateValues, suggestions: &HashMap<String, String>, rendered_content: &mut String) {
+        match component {
+            TemplateComponent::Text(text) => {
+                rendered_content.push_str(text);
+                rendered_content.push('\n');
+            }
+            TemplateComponent::Placeholder { name, hint, required: _ } => {
+                let value = values.text(name).unwrap_or_else(|| format!("{{{{ {} - {} }}}}", name, hint));
+                rendered_content.push_str(&value);
+                rendered_content.push('\n');
+            }
+            TemplateComponent::AiSuggestion { prompt, fallback } => {
+                let text = suggestions.get(prompt).unwrap_or(fallback);
+                rendered_content.push_str(&self.enforce_glossary(text));
+                rendered_content.push('\n');
+            }
+            TemplateComponent::Tag(tag) => {
+                rendered_content.push_str(&format!("#{}", tag));
+                rendered_content.push('\n');
+            }
+            TemplateComponent::Link { target, display_text } => {
+                let link_text = display_text.as_ref().unwrap_or(target);
+                rendered_content.push_str(&format!("[[{}|{}]]", target, link_text));
+                rendered_content.push('\n');
+            }
+            TemplateComponent::Conditional { condition, content } => {
+                // Conditions that aren't expressions (older, discovered templates describe them
+                // in prose) keep their section
+                if Condition::parse(condition).is_ok_and(|c| !c.evaluate(values)) {
+                    return;
                 }
-                TemplateComponent::Placeholder { name, hint, required: _ } => {
-                    let placeholder_text = format!("{{{{ {} - {} }}}}", name, hint);
-                    let value = values.get(name).unwrap_or(&placeholder_text);
-                    rendered_content.push_str(value);
-                    rendered_content.push('\n');
+                for sub_component in content {
+                    self.render_component(sub_component, values, suggestions, rendered_content);
                 }
-                TemplateComponent::AiSuggestion { prompt: _, fallback } => {
-                    // For now, use fallback. In a real implementation, this would trigger AI generation
-                    rendered_content.push_str(fallback);
-                    rendered_content.push('\n');
+            }
+            TemplateComponent::Repeating { item_name, content } => {
+                for item in values.items(item_name) {
+                    let scoped = values.with_item(item_name, item);
+                    for sub_component in content {
+                        self.render_component(sub_component, &scoped, suggestions, rendered_content);
+                    }
                 }
-                TemplateComponent::Tag(tag) => {
-                    rendered_content.push_str(&format!("#{}", tag));
-                    rendered_content.push('\n');
+            }
+        }
+    }
+
+    /// Find a template by id, or else by name ignoring case
+    pub fn find_template(&self, id_or_name: &str) -> Option<&NoteTemplate> {
+        self.get_template_by_id(id_or_name).or_else(|| {
+            self.template_database.templates.iter().find(|t| t.name.eq_ignore_ascii_case(id_or_name))
+        })
+    }
+
+    /// Saved templates that `content` could be written into, best fit first, with a
+    /// confidence from 0 to 1 (see `template_fit`). The content is analyzed heuristically,
+    /// so no LLM call is made whatever the analysis backend; templates it shares nothing
+    /// with are left out.
+    pub fn suggest_template_for_content(&self, content: &str) -> Result<Vec<(NoteTemplate, f32)>> {
+        let file = Self::parse_markdown_file(content)?;
+        let analysis = heuristic_analysis(&file, &self.term_index, &self.analysis_config);
+        let mut ranked: Vec<(NoteTemplate, f32)> = self.template_database.templates.iter()
+            .map(|template| (template.clone(), template_fit(template, &self.template_database.patterns, &file, &analysis)))
+            .filter(|(_, confidence)| *confidence > 0.0)
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(ranked)
+    }
+
+    /// Text for each AI suggestion in `template`, keyed by its prompt. A suggestion the LLM
+    /// can't provide (no client, offline, an error) is left out so its fallback is used
+    pub async fn resolve_ai_suggestions(&self, template: &NoteTemplate, values: &TemplateValues) -> HashMap<String, String> {
+        let mut prompts = Vec::new();
+        collect_ai_prompts(&template.components, &mut prompts);
+        let mut suggestions = HashMap::new();
+        let Some(llm_client) = self.llm_client.as_ref() else {
+            return suggestions;
+        };
+
+        let mut filled: Vec<(&String, String)> = values.iter()
+            .map(|(name, value)| (name, crate::template_values::value_text(value)))
+            .filter(|(_, text)| !text.is_empty())
+            .collect();
+        filled.sort();
+        let context = filled.iter().map(|(k, v)| format!("{}: {}", k, v)).collect::<Vec<_>>().join("\n");
+        for prompt in prompts {
+            if suggestions.contains_key(prompt) {
+                continue;
+            }
+            let messages = vec![
+                Message {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    role: MessageRole::System,
+                    content: self.generation_prompt(&crate::prompts::render(
+                        "template_section",
+                        &[("template_name", &template.name)],
+                    )),
+                    timestamp: Utc::now(),
+                    function_call: None,
+                },
+                Message {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    role: MessageRole::User,
+                    content: if context.is_empty() { prompt.clone() } else { format!("{}\n\nNote details:\n{}", prompt, context) },
+                    timestamp: Utc::now(),
+                    function_call: None,
+                },
+            ];
+            match llm_client.send_message(messages).await {
+                Ok(response) if !response.content.trim().is_empty() => {
+                    suggestions.insert(prompt.clone(), response.content.trim().to_string());
                 }
-                TemplateComponent::Link { target, display_text } => {
-                    let link_text = display_text.as_ref().unwrap_or(target);
-                    rendered_content.push_str(&format!("[[{}|{}]]", target, link_text));
-                    rendered_content.push('\n');
+                _ => {}
+            }
+        }
+        suggestions
+    }
+
+    /// Write every template, usage statistics and timestamps included, to a JSON or YAML file
+    pub fn export_templates(&self, path: &Path, format: TemplateFormat) -> Result<usize> {
+        let export = TemplateExport {
+            version: self.template_database.version.clone(),
+            exported_at: Utc::now(),
+            templates: self.template_database.templates.clone(),
+        };
+        let data = match format {
+            TemplateFormat::Json => serde_json::to_string_pretty(&export)?,
+            TemplateFormat::Yaml => serde_yaml::to_string(&export)?,
+        };
+        fs::write(path, data)
+            .with_context(|| format!("Failed to write templates to {}", path.display()))?;
+        Ok(export.templates.len())
+    }
+
+    /// Merge the templates of an exported file into the database. A template conflicts with an
+    /// existing one of the same id or name; `strategy` decides which one is kept
+    pub fn import_templates(&mut self, path: &Path, format: TemplateFormat, strategy: TemplateMergeStrategy) -> Result<TemplateImportSummary> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read templates from {}", path.display()))?;
+        let export: TemplateExport = match format {
+            TemplateFormat::Json => serde_json::from_str(&data)?,
+            TemplateFormat::Yaml => serde_yaml::from_str(&data)?,
+        };
+
+        let mut summary = TemplateImportSummary::default();
+        for mut template in export.templates {
+            let templates = &mut self.template_database.templates;
+            let existing = templates.iter().position(|t| t.id == template.id)
+                .or_else(|| templates.iter().position(|t| t.name == template.name));
+            match (existing, strategy) {
+                (None, _) => {
+                    templates.push(template);
+                    summary.added += 1;
                 }
-                TemplateComponent::Conditional { condition: _, content } => {
-                    // For now, always render conditional content
-                    // In a real implementation, this would evaluate the condition
-                    for sub_component in content {
-                        // Recursively render sub-components (simplified)
-                        match sub_component {
-                            TemplateComponent::Text(text) => rendered_content.push_str(text),
-                            _ => {} // Simplified for now
-                        }
-                    }
-                    rendered_content.push('\n');
+                (Some(_), TemplateMergeStrategy::Skip) => summary.skipped += 1,
+                (Some(index), TemplateMergeStrategy::Overwrite) => {
+                    templates[index] = template;
+                    summary.overwritten += 1;
                 }
-                TemplateComponent::Repeating { item_name: _, content } => {
-                    // For now, render once
-                    // In a real implementation, this would repeat based on provided data
-                    for sub_component in content {
-                        match sub_component {
-                            TemplateComponent::Text(text) => rendered_content.push_str(text),
-                            _ => {} // Simplified for now
-                        }
-                    }
-                    rendered_content.push('\n');
+                (Some(_), TemplateMergeStrategy::Duplicate) => {
+                    let base = template.name.clone();
+                    template.name = (2..)
+                        .map(|n| format!("{} ({})", base, n))
+                        .find(|name| !templates.iter().any(|t| &t.name == name))
+                        .unwrap_or(base);
+                    template.id = uuid::Uuid::new_v4().to_string();
+                    templates.push(template);
+                    summary.duplicated += 1;
                 }
             }
         }
 
-        Ok(rendered_content)
+        self.rebuild_template_indexes();
+        self.template_database.last_updated = Utc::now();
+        self.save_template_database()?;
+        Ok(summary)
+    }
+
+    /// Recompute the category and tag indexes from the template list
+    fn rebuild_template_indexes(&mut self) {
+        let database = &mut self.template_database;
+        database.category_index.clear();
+        database.tag_index.clear();
+        for (index, template) in database.templates.iter().enumerate() {
+            database.category_index.entry(template.category.clone()).or_default().push(index);
+            for tag in &template.tags {
+                database.tag_index.entry(tag.clone()).or_default().push(index);
+            }
+        }
     }
 
     /// Get template database statistics
@@ -2302,17 +4108,38 @@ Return a JSON object with this structure:
         self.organization_config = config;
     }
 
-    /// Generate organization recommendations for a note
+    /// Whether organization recommendations come from the heuristic analyzer rather than
+    /// the LLM, per `analysis_backend`
+    pub fn uses_heuristic_analysis(&self) -> bool {
+        !self.organization_config.analysis_backend.uses_llm(self.llm_client.is_some())
+    }
+
+    /// Generate organization recommendations for a note. With the heuristic analyzer no
+    /// request leaves the machine, and tag and folder suggestions are less confident.
     pub async fn generate_organization_recommendations(&mut self, vault_path: &str) -> Result<OrganizationRecommendations> {
         // Get content analysis for the note
-        let file_data = self.get_markdown_file_data(vault_path).await?;
-        let analysis = self.analyze_content(&file_data.content).await?;
+        let raw_content = self.get_file(vault_path).await?;
+        self.opt_out_policy.check(vault_path, &raw_content, Feature::Organize)?;
+        // Links added since the last index run count as existing too
+        self.update_note_index(vault_path, &raw_content);
+        let file_data = Self::parse_markdown_file(&raw_content)?;
+        let heuristic = self.uses_heuristic_analysis();
+        let analysis = if heuristic {
+            heuristic_analysis(&file_data, &self.term_index, &self.analysis_config)
+        } else {
+            self.analyze_content(&file_data.content).await?
+        };
         
         // Generate tag suggestions
-        let suggested_tags = self.generate_tag_suggestions(&analysis, &file_data).await?;
+        let mut suggested_tags = self.generate_tag_suggestions(&analysis, &file_data).await?;
         
         // Generate folder suggestions
-        let folder_suggestions = self.generate_folder_suggestions(&analysis, vault_path).await?;
+        let mut folder_suggestions = self.generate_folder_suggestions(&analysis, vault_path).await?;
+        
+        if heuristic {
+            suggested_tags.iter_mut().for_each(|tag| tag.confidence *= HEURISTIC_CONFIDENCE);
+            folder_suggestions.iter_mut().for_each(|folder| folder.confidence *= HEURISTIC_CONFIDENCE);
+        }
         
         // Generate link suggestions
         let link_suggestions = self.generate_link_suggestions(&analysis, vault_path).await?;
@@ -2483,14 +4310,14 @@ Return a JSON object with this structure:
         Ok(suggestions)
     }
 
-    /// Generate auto-linking suggestions
+    /// Generate auto-linking suggestions, skipping notes already linked in either direction
     pub async fn generate_link_suggestions(&self, analysis: &ContentAnalysis, vault_path: &str) -> Result<Vec<LinkSuggestion>> {
         let mut suggestions = Vec::new();
         
         // Find related documents using semantic search
         for theme in &analysis.themes {
-            if let Ok(search_results) = self.semantic_search_immutable(theme).await {
-                for result in search_results.into_iter().take(2) {
+            if let Ok(search_results) = self.semantic_search(theme).await {
+                for result in search_results.into_iter().filter(|r| !self.link_graph.is_linked(vault_path, &r.path)).take(2) {
                     // Don't suggest linking to the same document
                     if result.path != vault_path {
                         suggestions.push(LinkSuggestion {
@@ -2511,8 +4338,8 @@ Return a JSON object with this structure:
                 // This is a simplified version - in practice, you'd search for documents
                 // that mention this entity
                 let entity_search = format!("entity:{}", entity.text);
-                if let Ok(search_results) = self.semantic_search_immutable(&entity_search).await {
-                    for result in search_results.into_iter().take(1) {
+                if let Ok(search_results) = self.semantic_search(&entity_search).await {
+                    for result in search_results.into_iter().filter(|r| !self.link_graph.is_linked(vault_path, &r.path)).take(1) {
                         if result.path != vault_path && result.similarity > 0.7 {
                             suggestions.push(LinkSuggestion {
                                 target_path: result.path.clone(),
@@ -2534,35 +4361,116 @@ Return a JSON object with this structure:
         Ok(suggestions)
     }
 
-    /// Apply organization recommendations to a note
-    pub async fn apply_organization_recommendations(&mut self, vault_path: &str, recommendations: &OrganizationRecommendations) -> Result<()> {
-        let mut file_data = self.get_markdown_file_data(vault_path).await?;
-        let mut updated = false;
-        
-        // Apply high-confidence tags if auto-apply is enabled
-        if self.organization_config.auto_apply_tags {
-            let mut existing_tags = file_data.frontmatter.tags.clone().unwrap_or_default();
-            
-            for tag_suggestion in &recommendations.suggested_tags {
-                if tag_suggestion.confidence >= self.organization_config.auto_tag_confidence_threshold {
-                    if !existing_tags.contains(&tag_suggestion.tag) {
-                        existing_tags.push(tag_suggestion.tag.clone());
-                        updated = true;
+    /// Apply organization recommendations to a note: add tags, insert wikilinks and move
+    /// the note to its suggested folder, each behind its own flag and confidence threshold
+    /// in `OrganizationConfig`. With `dry_run` nothing is written and the summary lists
+    /// what would have changed.
+    pub async fn apply_organization_recommendations(
+        &mut self,
+        vault_path: &str,
+        recommendations: &OrganizationRecommendations,
+        dry_run: bool,
+    ) -> Result<OrganizationSummary> {
+        let raw_content = self.get_file(vault_path).await?;
+        self.opt_out_policy.check(vault_path, &raw_content, Feature::Organize)?;
+        let mut file_data = Self::parse_markdown_file(&raw_content)?;
+        let mut summary = OrganizationSummary { path: vault_path.to_string(), dry_run, ..Default::default() };
+        let config = &self.organization_config;
+        let mut tags_added = false;
+
+        let mut existing_tags = file_data.frontmatter.tags.clone().unwrap_or_default();
+        for tag_suggestion in &recommendations.suggested_tags {
+            let action = OrganizationAction::AddTag(tag_suggestion.tag.clone());
+            if !config.auto_apply_tags {
+                summary.skip(action, "tag auto-apply is disabled");
+            } else if tag_suggestion.confidence < config.auto_tag_confidence_threshold {
+                summary.skip(action, below_threshold(tag_suggestion.confidence, config.auto_tag_confidence_threshold));
+            } else if existing_tags.contains(&tag_suggestion.tag) {
+                summary.skip(action, "already tagged");
+            } else {
+                existing_tags.push(tag_suggestion.tag.clone());
+                summary.applied.push(action);
+                tags_added = true;
+            }
+        }
+        if tags_added {
+            file_data.frontmatter.tags = Some(existing_tags.clone());
+        }
+
+        for link in &recommendations.link_suggestions {
+            let action = OrganizationAction::InsertLink {
+                target: link_target(&link.target_path).to_string(),
+                text: link.link_text.clone(),
+            };
+            if !config.auto_insert_links {
+                summary.skip(action, "link insertion is disabled");
+            } else if link.confidence < config.auto_link_confidence_threshold {
+                summary.skip(action, below_threshold(link.confidence, config.auto_link_confidence_threshold));
+            } else {
+                match insert_link_at_context(&file_data.content, link) {
+                    Ok(content) => {
+                        file_data.content = content;
+                        summary.applied.push(action);
                     }
+                    Err(reason) => summary.skip(action, reason),
                 }
             }
-            
-            if updated {
-                file_data.frontmatter.tags = Some(existing_tags);
+        }
+
+        let current = Path::new(vault_path);
+        let current_folder = current.parent().and_then(|p| p.to_str()).unwrap_or("");
+        let file_name = current.file_name().and_then(|n| n.to_str()).unwrap_or(vault_path);
+        let mut folders: Vec<&FolderSuggestion> = recommendations.folder_suggestions.iter().collect();
+        folders.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
+        let mut destination = None;
+        for folder in folders {
+            let folder_path = folder.folder_path.trim_matches('/');
+            let action = OrganizationAction::MoveTo(folder_path.to_string());
+            if !config.auto_move_notes {
+                summary.skip(action, "moving notes is disabled");
+            } else if destination.is_some() {
+                summary.skip(action, "a more confident folder was chosen");
+            } else if folder.confidence < config.auto_move_confidence_threshold {
+                summary.skip(action, below_threshold(folder.confidence, config.auto_move_confidence_threshold));
+            } else if folder_path == current_folder {
+                summary.skip(action, "already in this folder");
+            } else {
+                destination = Some((action, format!("{}/{}", folder_path, file_name)));
             }
         }
-        
-        // Save the updated file if changes were made
-        if updated {
-            self.save_markdown_file_data(vault_path, &file_data, true).await?;
+        let destination = match destination {
+            Some((action, to)) if self.get_file_if_exists(&to).await?.is_some() => {
+                summary.skip(action, format!("{} already exists", to));
+                None
+            }
+            Some((action, to)) => {
+                summary.applied.push(action);
+                Some(to)
+            }
+            None => None,
+        };
+
+        if !dry_run {
+            let links_inserted = summary.applied.iter().any(|action| matches!(action, OrganizationAction::InsertLink { .. }));
+            if links_inserted || tags_added {
+                self.journal_before_write(vault_path, &raw_content, Feature::Organize)?;
+            }
+            if links_inserted {
+                // Links go inside existing paragraphs, so the note is rewritten, unless it
+                // changed since it was read
+                self.save_markdown_file_data(vault_path, &file_data, true).await?;
+            } else if tags_added {
+                self.patch_file(vault_path, &PatchTarget::Frontmatter("tags".to_string()), &serde_json::to_string(&existing_tags)?, PatchOperation::Replace).await?;
+            }
+            if let Some(to) = &destination {
+                self.move_file(vault_path, to).await?;
+            }
         }
-        
-        Ok(())
+        if let Some(to) = destination {
+            summary.path = to;
+        }
+
+        Ok(summary)
     }
 
     /// Batch process multiple notes for organization
@@ -2608,84 +4516,16 @@ Return a JSON object with this structure:
         }
         
         // Include link confidences
-        for link in links {
-            total_confidence += link.confidence;
-            count += 1;
-        }
-        
-        if count > 0 {
-            total_confidence / count as f32
-        } else {
-            0.0
-        }
-    }
-
-    /// Semantic search without mutable self (helper method)
-    async fn semantic_search_immutable(&self, query: &str) -> Result<Vec<SemanticSearchResult>> {
-        // This is a simplified version that works with immutable self
-        // In practice, you might need to restructure the semantic search to work without mutable state
-        let query_embedding = if let Some(ref llm_client) = self.llm_client {
-            // Generate embedding for query
-            let messages = vec![
-                crate::ai_conversation::Message {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    role: crate::ai_conversation::MessageRole::System,
-                    content: "Generate a 768-dimensional vector embedding for the given text. Return only a JSON array of 768 floating point numbers.".to_string(),
-                    timestamp: Utc::now(),
-                    function_call: None,
-                },
-                crate::ai_conversation::Message {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    role: crate::ai_conversation::MessageRole::User,
-                    content: format!("Generate embedding for: {}", query),
-                    timestamp: Utc::now(),
-                    function_call: None,
-                }
-            ];
-
-            match llm_client.send_message(messages).await {
-                Ok(response) => {
-                    let embedding_json = response.content.trim();
-                    serde_json::from_str::<Vec<f32>>(embedding_json).unwrap_or_default()
-                }
-                Err(_) => return Ok(Vec::new()),
-            }
+        for link in links {
+            total_confidence += link.confidence;
+            count += 1;
+        }
+        
+        if count > 0 {
+            total_confidence / count as f32
         } else {
-            return Ok(Vec::new());
-        };
-
-        // Calculate similarities with all documents
-        let mut similarities: Vec<(usize, f32)> = self.vector_database.embeddings
-            .iter()
-            .enumerate()
-            .map(|(i, doc_embedding)| {
-                let similarity = self.cosine_similarity(&query_embedding, &doc_embedding.embedding);
-                (i, similarity)
-            })
-            .collect();
-
-        // Sort by similarity (descending)
-        similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-
-        // Filter by minimum similarity and take top results
-        let results: Vec<SemanticSearchResult> = similarities
-            .into_iter()
-            .filter(|(_, sim)| *sim >= self.search_config.min_similarity)
-            .take(self.search_config.max_results)
-            .map(|(index, similarity)| {
-                let doc_embedding = &self.vector_database.embeddings[index];
-                
-                SemanticSearchResult {
-                    path: doc_embedding.path.clone(),
-                    similarity,
-                    metadata: doc_embedding.metadata.clone(),
-                    snippet: doc_embedding.metadata.excerpt.clone(),
-                    highlights: vec![],
-                }
-            })
-            .collect();
-
-        Ok(results)
+            0.0
+        }
     }
 
     /// Set content suggestion configuration
@@ -2695,6 +4535,11 @@ Return a JSON object with this structure:
 
     /// Generate content suggestions for real-time writing assistance
     pub async fn generate_content_suggestions(&mut self, request: ContentSuggestionRequest) -> Result<Vec<ContentSuggestion>> {
+        // Real-time requests may carry no path, and then only the note's own opt-outs apply
+        if self.opt_out_policy.check_content(request.path.as_deref(), &request.content, Feature::Suggestions).is_err() {
+            return Ok(Vec::new());
+        }
+
         // Check cache first
         let content_hash = self.generate_cache_key(&request.content);
         if let Some(cached) = self.get_cached_suggestions(&content_hash) {
@@ -2750,6 +4595,13 @@ Return a JSON object with this structure:
             }
         }
 
+        // Generated text must follow the team glossary before it reaches the vault
+        for suggestion in suggestions.iter_mut() {
+            if matches!(suggestion.suggestion_type, SuggestionType::ContentContinuation | SuggestionType::TextCompletion | SuggestionType::HeadingSuggestion) {
+                suggestion.text = self.enforce_glossary(&suggestion.text);
+            }
+        }
+
         // Filter by confidence and limit results
         suggestions.retain(|s| s.confidence >= self.content_suggestion_config.min_confidence);
         suggestions.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
@@ -2766,16 +4618,13 @@ Return a JSON object with this structure:
         let llm_client = self.llm_client.as_ref()
             .ok_or_else(|| anyhow::anyhow!("No LLM client configured for content suggestions"))?;
 
-        let prompt = format!(
-            "Continue writing this content in a natural way. Context: {}\n\nProvide 2-3 different continuation options, each on a separate line.",
-            context
-        );
+        let prompt = crate::prompts::render("suggest_continuation", &[("context", context)]);
 
         let messages = vec![
             crate::ai_conversation::Message {
                 id: uuid::Uuid::new_v4().to_string(),
                 role: crate::ai_conversation::MessageRole::System,
-                content: "You are a writing assistant. Provide natural, helpful continuations for the given text context.".to_string(),
+                content: self.generation_prompt("You are a writing assistant. Provide natural, helpful continuations for the given text context."),
                 timestamp: Utc::now(),
                 function_call: None,
             },
@@ -2797,7 +4646,7 @@ Return a JSON object with this structure:
     /// Generate related content suggestions
     async fn generate_related_content_suggestions(&self, context: &str, request: &ContentSuggestionRequest) -> Result<Vec<ContentSuggestion>> {
         // Use semantic search to find related content
-        let search_results = self.semantic_search_immutable(context).await?;
+        let search_results = self.semantic_search(context).await?;
         
         let mut suggestions = Vec::new();
         
@@ -2822,7 +4671,7 @@ Return a JSON object with this structure:
     /// Generate link suggestions from context
     async fn generate_link_suggestions_from_context(&self, context: &str, request: &ContentSuggestionRequest) -> Result<Vec<ContentSuggestion>> {
         // Find potential link targets based on context
-        let search_results = self.semantic_search_immutable(context).await?;
+        let search_results = self.semantic_search(context).await?;
         
         let mut suggestions = Vec::new();
         
@@ -2855,16 +4704,13 @@ Return a JSON object with this structure:
             return Ok(Vec::new()); // Don't suggest completion for complete sentences
         }
 
-        let prompt = format!(
-            "Complete this text naturally: {}\n\nProvide only the completion text, not the full sentence.",
-            context
-        );
+        let prompt = crate::prompts::render("suggest_completion", &[("context", context)]);
 
         let messages = vec![
             crate::ai_conversation::Message {
                 id: uuid::Uuid::new_v4().to_string(),
                 role: crate::ai_conversation::MessageRole::System,
-                content: "You are a text completion assistant. Complete the given text naturally and concisely.".to_string(),
+                content: self.generation_prompt("You are a text completion assistant. Complete the given text naturally and concisely."),
                 timestamp: Utc::now(),
                 function_call: None,
             },
@@ -2902,16 +4748,13 @@ Return a JSON object with this structure:
             let llm_client = self.llm_client.as_ref()
                 .ok_or_else(|| anyhow::anyhow!("No LLM client configured for heading suggestions"))?;
 
-            let prompt = format!(
-                "Based on this content context, suggest 2-3 appropriate headings for the next section: {}\n\nProvide only the heading text, one per line.",
-                context
-            );
+            let prompt = crate::prompts::render("suggest_headings", &[("context", context)]);
 
             let messages = vec![
                 crate::ai_conversation::Message {
                     id: uuid::Uuid::new_v4().to_string(),
                     role: crate::ai_conversation::MessageRole::System,
-                    content: "You are a document structure assistant. Suggest appropriate headings based on context.".to_string(),
+                    content: self.generation_prompt("You are a document structure assistant. Suggest appropriate headings based on context."),
                     timestamp: Utc::now(),
                     function_call: None,
                 },
@@ -2984,48 +4827,40 @@ Return a JSON object with this structure:
         }
     }
 
-    /// Auto-insert links in content
+    /// Auto-insert links in content. Words are linked where they appear as plain text;
+    /// frontmatter, code and existing links are left untouched.
     pub async fn auto_insert_links(&self, content: &str) -> Result<AutoLinkResult> {
-        let mut result = AutoLinkResult {
-            original_text: content.to_string(),
-            linked_text: content.to_string(),
-            inserted_links: Vec::new(),
-            links_added: 0,
-        };
+        if NoteOptOuts::parse(content).disabled.contains(&Feature::Autolink) {
+            return Ok(AutoLinkResult {
+                original_text: content.to_string(),
+                linked_text: content.to_string(),
+                inserted_links: Vec::new(),
+                links_added: 0,
+            });
+        }
 
-        // Find potential link targets
-        let words: Vec<&str> = content.split_whitespace().collect();
-        let mut current_position = 0;
-        
-        for word in words {
-            // Search for documents that might match this word
-            if word.len() > 3 { // Only consider words longer than 3 characters
-                if let Ok(search_results) = self.semantic_search_immutable(word).await {
-                    for search_result in search_results {
-                        if search_result.similarity >= self.content_suggestion_config.auto_link_confidence_threshold {
-                            let link_text = format!("[[{}]]", search_result.metadata.title);
-                            
-                            // Replace the word with the link
-                            result.linked_text = result.linked_text.replace(word, &link_text);
-                            
-                            result.inserted_links.push(InsertedLink {
-                                original_text: word.to_string(),
-                                target: search_result.path,
-                                display_text: search_result.metadata.title,
-                                position: ContentPosition { line: 0, column: current_position, length: Some(word.len()) },
-                                confidence: search_result.similarity,
-                            });
-                            
-                            result.links_added += 1;
-                            break; // Only link to the first high-confidence match
-                        }
-                    }
+        // Each distinct word is looked up once
+        let mut targets: HashMap<String, AutoLinkTarget> = HashMap::new();
+        let mut searched = std::collections::HashSet::new();
+        for (start, end) in linkable_words(content) {
+            let word = &content[start..end];
+            // Only consider words longer than 3 characters
+            if word.chars().count() <= 3 || !searched.insert(word.to_lowercase()) {
+                continue;
+            }
+            if let Ok(search_results) = self.semantic_search(word).await {
+                // Only link to the first high-confidence match
+                if let Some(found) = search_results.into_iter().find(|r| r.similarity >= self.content_suggestion_config.auto_link_confidence_threshold) {
+                    targets.insert(word.to_lowercase(), AutoLinkTarget {
+                        path: found.path,
+                        title: found.metadata.title,
+                        confidence: found.similarity,
+                    });
                 }
             }
-            current_position += word.len() + 1; // +1 for space
         }
 
-        Ok(result)
+        Ok(apply_auto_links(content, &targets, self.content_suggestion_config.link_first_occurrence_only))
     }
 
     /// Extract context around cursor position
@@ -3076,143 +4911,822 @@ Return a JSON object with this structure:
             .collect()
     }
 
-    /// Get cached suggestions
-    fn get_cached_suggestions(&self, content_hash: &str) -> Option<Vec<ContentSuggestion>> {
-        if let Some(cached_suggestions) = self.suggestion_cache.suggestions.get(content_hash) {
-            // Check if cache is still valid
-            let now = Utc::now();
-            let valid_suggestions: Vec<ContentSuggestion> = cached_suggestions.iter()
-                .filter(|cached| {
-                    let age = now.signed_duration_since(cached.cached_at);
-                    age.num_seconds() < self.content_suggestion_config.cache_timeout_seconds as i64
-                })
-                .map(|cached| cached.suggestion.clone())
-                .collect();
-            
-            if !valid_suggestions.is_empty() {
-                return Some(valid_suggestions);
-            }
+    /// Get cached suggestions
+    fn get_cached_suggestions(&self, content_hash: &str) -> Option<Vec<ContentSuggestion>> {
+        if let Some(cached_suggestions) = self.suggestion_cache.suggestions.get(content_hash) {
+            // Check if cache is still valid
+            let now = Utc::now();
+            let valid_suggestions: Vec<ContentSuggestion> = cached_suggestions.iter()
+                .filter(|cached| {
+                    let age = now.signed_duration_since(cached.cached_at);
+                    age.num_seconds() < self.content_suggestion_config.cache_timeout_seconds as i64
+                })
+                .map(|cached| cached.suggestion.clone())
+                .collect();
+            
+            if !valid_suggestions.is_empty() {
+                return Some(valid_suggestions);
+            }
+        }
+        None
+    }
+
+    /// Cache suggestions
+    fn cache_suggestions(&mut self, content_hash: &str, suggestions: &[ContentSuggestion]) {
+        let cached_suggestions: Vec<CachedSuggestion> = suggestions.iter()
+            .map(|suggestion| CachedSuggestion {
+                suggestion: suggestion.clone(),
+                cached_at: Utc::now(),
+                content_hash: content_hash.to_string(),
+            })
+            .collect();
+
+        self.suggestion_cache.suggestions.insert(content_hash.to_string(), cached_suggestions);
+        self.prune_suggestion_cache();
+    }
+
+    /// Drop expired suggestions, then the oldest notes' beyond `max_entries`
+    fn prune_suggestion_cache(&mut self) {
+        let timeout = self.content_suggestion_config.cache_timeout_seconds as i64;
+        let now = Utc::now();
+        let suggestions = &mut self.suggestion_cache.suggestions;
+        suggestions.retain(|_, cached| cached.iter().any(|c| now.signed_duration_since(c.cached_at).num_seconds() < timeout));
+        let excess = suggestions.len().saturating_sub(self.suggestion_cache.max_entries);
+        if excess > 0 {
+            let mut by_age: Vec<(DateTime<Utc>, String)> = suggestions.iter()
+                .map(|(hash, cached)| (cached.iter().map(|c| c.cached_at).max().unwrap_or(now), hash.clone()))
+                .collect();
+            by_age.sort();
+            for (_, hash) in by_age.into_iter().take(excess) {
+                suggestions.remove(&hash);
+            }
+        }
+    }
+
+    /// Get suggestion cache statistics
+    pub fn get_suggestion_cache_stats(&self) -> HashMap<String, serde_json::Value> {
+        let mut stats = HashMap::new();
+        stats.insert("hit_count".to_string(), serde_json::Value::Number(self.suggestion_cache.hit_count.into()));
+        stats.insert("miss_count".to_string(), serde_json::Value::Number(self.suggestion_cache.miss_count.into()));
+        stats.insert("cached_entries".to_string(), serde_json::Value::Number(self.suggestion_cache.suggestions.len().into()));
+        
+        let hit_rate = if self.suggestion_cache.hit_count + self.suggestion_cache.miss_count > 0 {
+            self.suggestion_cache.hit_count as f64 / (self.suggestion_cache.hit_count + self.suggestion_cache.miss_count) as f64
+        } else {
+            0.0
+        };
+        
+        stats.insert("hit_rate".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(hit_rate).unwrap_or(serde_json::Number::from(0))));
+        
+        stats
+    }
+
+    /// Clear suggestion cache
+    pub fn clear_suggestion_cache(&mut self) {
+        self.suggestion_cache.suggestions.clear();
+        self.suggestion_cache.hit_count = 0;
+        self.suggestion_cache.miss_count = 0;
+    }
+
+    /// `path` when `vault` is the one this adapter is connected to. The REST API serves a
+    /// single vault, so a note elsewhere needs that vault's adapter.
+    fn path_in_vault<'a>(&self, vault: &str, path: &'a str) -> Result<&'a str> {
+        if vault.is_empty() || vault == DEFAULT_VAULT || vault.eq_ignore_ascii_case(&self.vault_name) {
+            Ok(path)
+        } else {
+            bail!("{} is in vault '{}', but this adapter is connected to '{}'", path, vault, self.vault_name)
+        }
+    }
+
+    /// Get note by vault and path
+    pub async fn get_note(&self, vault: &str, path: &str) -> Result<MarkdownFile> {
+        self.get_markdown_file_data(self.path_in_vault(vault, path)?).await
+    }
+
+    /// The vault this adapter is connected to
+    pub async fn list_vaults(&self) -> Result<Vec<String>> {
+        Ok(vec![self.vault_name.clone()])
+    }
+
+    /// List notes in a vault
+    pub async fn list_notes(&self, vault: &str) -> Result<Vec<String>> {
+        // For now, return empty list
+        // In a real implementation, this would query the Obsidian API
+        Ok(vec![])
+    }
+
+    pub async fn list_files_in_folder(&self, folder_path: &str) -> Result<Vec<String>> {
+        Ok(self.list_folder_entries(folder_path).await?.into_iter().map(|entry| entry.name).collect())
+    }
+
+    /// List a folder with the size and modification time of each file, when the server
+    /// includes them
+    pub async fn list_folder_entries(&self, folder_path: &str) -> Result<Vec<ListedFile>> {
+        // Create a URL to list files in the given folder; the vault root is `/vault/`
+        let url = match folder_path.trim_matches('/') {
+            "" => format!("{}/vault/", self.base_url),
+            folder => format!("{}/vault/{}/", self.base_url, folder),
+        };
+        
+        let response = self
+            .send_request(self.client.get(&url).header("Accept", "application/json"), "GET", &url)
+            .await?;
+
+        if response.status().is_success() {
+            let response_text = response
+                .text()
+                .await
+                .context("Failed to read response text")?;
+            
+            let listing: serde_json::Value = serde_json::from_str(&response_text)
+                .context("Failed to parse file list response")?;
+            parse_folder_listing(&listing)
+        } else {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            bail!(
+                "MCP server returned error {}: {}. URL: {}",
+                status,
+                error_text,
+                url
+            )
+        }
+    }
+
+    /// Delete a note
+    pub async fn delete_note(&self, vault: &str, path: &str) -> Result<()> {
+        // For now, this is a no-op
+        // In a real implementation, this would delete via the Obsidian API
+        Ok(())
+    }
+
+    /// Create a new note
+    pub async fn create_note(&self, vault: &str, path: &str, content: &str) -> Result<()> {
+        self.create_file(self.path_in_vault(vault, path)?, content).await
+    }
+
+    /// Update a note
+    pub async fn update_note(&self, vault: &str, path: &str, content: &str) -> Result<()> {
+        self.update_file(self.path_in_vault(vault, path)?, content).await
+    }
+}
+
+fn below_threshold(confidence: f32, threshold: f32) -> String {
+    format!("confidence {:.2} is below {:.2}", confidence, threshold)
+}
+
+/// The wikilink target for a note path: the path without its `.md` extension
+fn link_target(path: &str) -> &str {
+    path.strip_suffix(".md").unwrap_or(path)
+}
+
+/// The text a link suggestion should be anchored on: the entity or theme recorded in its
+/// context, falling back to the link text
+fn link_anchor(suggestion: &LinkSuggestion) -> &str {
+    suggestion
+        .context
+        .strip_prefix("Mentions entity: ")
+        .or_else(|| suggestion.context.strip_prefix("Related content about "))
+        .map(str::trim)
+        .filter(|anchor| !anchor.is_empty())
+        .unwrap_or(suggestion.link_text.trim())
+}
+
+/// Insert the wikilink a suggestion describes at the first plain-text mention of its
+/// anchor. A mention of the link text itself becomes `[[target|mention]]`; any other
+/// anchor is followed by ` ([[target|link text]])`. Fenced code, headings, inline code,
+/// existing links, URLs and tags are left alone. Returns why nothing was inserted otherwise.
+pub(crate) fn insert_link_at_context(body: &str, suggestion: &LinkSuggestion) -> Result<String, &'static str> {
+    let target = link_target(&suggestion.target_path);
+    if body.contains(&format!("[[{}]]", target)) || body.contains(&format!("[[{}|", target)) || body.contains(&format!("[[{}#", target)) {
+        return Err("already linked");
+    }
+    let anchor = link_anchor(suggestion);
+    if anchor.is_empty() {
+        return Err("no link context");
+    }
+    let needle = anchor.to_ascii_lowercase();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    let doc = Markdown::parse(body);
+    let mut protected = doc.protected_ranges();
+    protected.extend(doc.headings().into_iter().map(|(_, range, _)| range));
+    let lower = body.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(start) = lower[from..].find(&needle).map(|i| from + i) {
+        let end = start + needle.len();
+        from = start + lower[start..].chars().next().map_or(1, char::len_utf8);
+        let bounded = !body[..start].chars().next_back().is_some_and(is_word)
+            && !body[end..].chars().next().is_some_and(is_word);
+        if !bounded || body[start..end].contains('\n') || protected.iter().any(|range| start < range.end && end > range.start) {
+            continue;
+        }
+        let mention = &body[start..end];
+        let link = if anchor.eq_ignore_ascii_case(suggestion.link_text.trim()) {
+            format!("[[{}|{}]]", target, mention)
+        } else {
+            format!("{} ([[{}|{}]])", mention, target, suggestion.link_text.trim())
+        };
+        return Ok(replace_section(body, start..end, &link));
+    }
+    Err("context not found in the note")
+}
+
+/// Byte ranges of the inline tags in `body`, without their `#`: a `#` at the start of a
+/// line or after whitespace, followed by tag characters, at least one of them not a digit.
+/// Code, links and URLs are skipped.
+fn inline_tag_spans(body: &str) -> Vec<(usize, usize)> {
+    Markdown::parse(body).tags().into_iter().map(|range| (range.start, range.end)).collect()
+}
+
+/// The distinct tags a note uses, in frontmatter or inline, in lowercase
+fn note_tags(content: &str) -> std::collections::BTreeSet<String> {
+    let (yaml, body) = crate::conflicts::split_frontmatter(content);
+    let frontmatter = yaml
+        .and_then(|yaml| serde_yaml::from_str::<Frontmatter>(yaml).ok())
+        .and_then(|frontmatter| frontmatter.tags)
+        .unwrap_or_default();
+    frontmatter.iter()
+        .map(|tag| tag.trim().trim_start_matches('#'))
+        .chain(inline_tag_spans(body).into_iter().map(|(start, end)| &body[start..end]))
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// `tag` with its `from` prefix swapped for `to`, if it is one of `from` or nested under one
+fn renamed_tag(tag: &str, from: &[String], to: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('#');
+    from.iter().find_map(|old| {
+        let prefix = tag.get(..old.len()).filter(|prefix| prefix.eq_ignore_ascii_case(old))?;
+        let rest = &tag[prefix.len()..];
+        (rest.is_empty() || rest.starts_with('/')).then(|| format!("{}{}", to, rest))
+    })
+}
+
+/// `content` with the tags in `from` renamed to `to`, or `None` if it uses none of them.
+/// Fails when the note mentions one but its frontmatter doesn't parse.
+fn rename_tags_in_note(content: &str, from: &[String], to: &str) -> Result<Option<String>, &'static str> {
+    let lower = content.to_lowercase();
+    if !from.iter().any(|tag| lower.contains(&tag.to_lowercase())) {
+        return Ok(None);
+    }
+    let (yaml, body) = crate::conflicts::split_frontmatter(content);
+
+    let mut new_body = String::with_capacity(body.len());
+    let mut copied = 0;
+    for (start, end) in inline_tag_spans(body) {
+        if let Some(renamed) = renamed_tag(&body[start..end], from, to) {
+            new_body.push_str(&body[copied..start]);
+            new_body.push_str(&renamed);
+            copied = end;
+        }
+    }
+    new_body.push_str(&body[copied..]);
+
+    let new_yaml = match yaml {
+        Some(yaml) if !yaml.trim().is_empty() => {
+            let mut frontmatter: Frontmatter = serde_yaml::from_str(yaml).map_err(|_| "frontmatter doesn't parse")?;
+            frontmatter.source = FrontmatterSource::parse(yaml);
+            match frontmatter.tags.clone() {
+                Some(tags) if tags.iter().any(|tag| renamed_tag(tag, from, to).is_some()) => {
+                    let mut renamed: Vec<String> = Vec::new();
+                    for tag in tags {
+                        let tag = renamed_tag(&tag, from, to).unwrap_or(tag);
+                        if !renamed.iter().any(|kept| kept.trim_start_matches('#').eq_ignore_ascii_case(tag.trim_start_matches('#'))) {
+                            renamed.push(tag);
+                        }
+                    }
+                    frontmatter.tags = Some(renamed);
+                    Some(frontmatter.to_yaml().map_err(|_| "frontmatter can't be written back")?)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    let renamed = match (yaml, new_yaml) {
+        (_, Some(new_yaml)) => format!("---\n{}\n---\n{}", new_yaml.trim_end_matches('\n'), new_body),
+        (Some(_), None) if new_body != body => format!("{}{}", &content[..content.len() - body.len()], new_body),
+        (None, None) if new_body != body => new_body,
+        _ => return Ok(None),
+    };
+    Ok(Some(renamed))
+}
+
+/// The note a word links to when auto-linking
+#[derive(Debug, Clone)]
+struct AutoLinkTarget {
+    path: String,
+    title: String,
+    confidence: f32,
+}
+
+/// Byte ranges of the words in `content` that may become links: everything outside the
+/// frontmatter, code, existing links, URLs and tags
+fn linkable_words(content: &str) -> Vec<(usize, usize)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let doc = Markdown::parse(content);
+    let protected = doc.protected_ranges();
+    let mut words = Vec::new();
+    for range in doc.spans_excluding_code() {
+        let text = &content[range.clone()];
+        let mut word_start = None;
+        for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+            match (is_word(c), word_start) {
+                (true, None) => word_start = Some(i),
+                (false, Some(start)) => {
+                    word_start = None;
+                    let (start, end) = (range.start + start, range.start + i);
+                    if !protected.iter().any(|p| start < p.end && end > p.start) {
+                        words.push((start, end));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    words
+}
+
+/// Link the words of `content` that have a target, in a single pass that records where
+/// each link ends up in the linked text. With `first_only`, a note that is already
+/// linked, or that an earlier word linked to, is not linked again.
+fn apply_auto_links(content: &str, targets: &HashMap<String, AutoLinkTarget>, first_only: bool) -> AutoLinkResult {
+    let mut linked = std::collections::HashSet::new();
+    if first_only {
+        for target in targets.values() {
+            if content.contains(&format!("[[{}]]", target.title)) || content.contains(&format!("[[{}|", target.title)) {
+                linked.insert(target.path.clone());
+            }
+        }
+    }
+
+    let mut linked_text = String::with_capacity(content.len());
+    let mut inserted_links = Vec::new();
+    // Line of the end of `linked_text`, and where that line starts in it
+    let (mut line, mut line_start) = (0, 0);
+    let mut copied = 0;
+    for (start, end) in linkable_words(content) {
+        let word = &content[start..end];
+        let Some(target) = targets.get(&word.to_lowercase()) else { continue };
+        if first_only && !linked.insert(target.path.clone()) {
+            continue;
+        }
+        for (i, _) in content[copied..start].match_indices('\n') {
+            line += 1;
+            line_start = linked_text.len() + i + 1;
+        }
+        linked_text.push_str(&content[copied..start]);
+        let link = if word == target.title {
+            format!("[[{}]]", target.title)
+        } else {
+            format!("[[{}|{}]]", target.title, word)
+        };
+        inserted_links.push(InsertedLink {
+            original_text: word.to_string(),
+            target: target.path.clone(),
+            display_text: word.to_string(),
+            position: ContentPosition { line, column: linked_text.len() - line_start, length: Some(link.len()) },
+            confidence: target.confidence,
+        });
+        linked_text.push_str(&link);
+        copied = end;
+    }
+    linked_text.push_str(&content[copied..]);
+
+    AutoLinkResult {
+        original_text: content.to_string(),
+        linked_text,
+        links_added: inserted_links.len(),
+        inserted_links,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::NoProgress;
+    use crate::testing::MockLLMClient;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Embeds everything as a constant vector of `dimension`, or fails with an exhausted
+    /// quota when `fill` is `None`
+    struct FakeEmbedder {
+        model: &'static str,
+        fill: Option<f32>,
+        dimension: usize,
+        /// The dimension it reports before the first request
+        declared: Option<usize>,
+    }
+
+    impl FakeEmbedder {
+        fn new(model: &'static str, fill: Option<f32>, dimension: usize) -> Box<Self> {
+            Box::new(Self { model, fill, dimension, declared: Some(dimension) })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl EmbeddingProvider for FakeEmbedder {
+        async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            match self.fill {
+                Some(fill) => Ok(vec![fill; self.dimension]),
+                None => Err(ArrowheadError::QuotaExhausted { provider: "openai".to_string() }.into()),
+            }
+        }
+
+        fn model_id(&self) -> String {
+            self.model.to_string()
+        }
+
+        fn dimension(&self) -> Option<usize> {
+            self.declared
+        }
+    }
+
+    /// Counts requests in flight and remembers the most seen at once
+    #[derive(Default)]
+    struct InFlight {
+        current: AtomicUsize,
+        max: AtomicUsize,
+    }
+
+    impl InFlight {
+        /// Hold a request slot for `millis`
+        async fn hold(&self, millis: u64) {
+            let now = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+            self.current.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Analyzes notes as the one theme in their "Topic: ..." line, slowest first, and fails
+    /// on notes about "outage"
+    struct SlowAnalyst(Arc<InFlight>);
+
+    #[async_trait::async_trait]
+    impl LLMClient for SlowAnalyst {
+        async fn send_message(&self, messages: Vec<Message>) -> Result<Message> {
+            let prompt = &messages.last().unwrap().content;
+            let topic = prompt.split("Topic: ").nth(1).and_then(|rest| rest.split_whitespace().next()).unwrap_or_default();
+            self.0.hold(40 - 3 * topic.len() as u64).await;
+            if topic == "outage" {
+                bail!("model overloaded");
+            }
+            Ok(Message {
+                id: uuid::Uuid::new_v4().to_string(),
+                role: MessageRole::Assistant,
+                content: serde_json::json!({ "themes": [topic] }).to_string(),
+                timestamp: Utc::now(),
+                function_call: None,
+            })
+        }
+
+        async fn stream_response(&self, _messages: Vec<Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+            unimplemented!()
+        }
+
+        async fn function_calling(&self, messages: Vec<Message>, _functions: Vec<crate::ai_conversation::FunctionSchema>) -> Result<Message> {
+            self.send_message(messages).await
+        }
+
+        fn get_model_name(&self) -> String {
+            "slow-analyst".to_string()
+        }
+    }
+
+    struct SlowEmbedder(Arc<InFlight>);
+
+    #[async_trait::async_trait]
+    impl EmbeddingProvider for SlowEmbedder {
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            self.0.hold(5).await;
+            Ok(vec![text.len() as f32, 1.0])
+        }
+
+        fn model_id(&self) -> String {
+            "openai/slow".to_string()
+        }
+
+        fn dimension(&self) -> Option<usize> {
+            Some(2)
         }
-        None
     }
 
-    /// Cache suggestions
-    fn cache_suggestions(&mut self, content_hash: &str, suggestions: &[ContentSuggestion]) {
-        let cached_suggestions: Vec<CachedSuggestion> = suggestions.iter()
-            .map(|suggestion| CachedSuggestion {
-                suggestion: suggestion.clone(),
-                cached_at: Utc::now(),
-                content_hash: content_hash.to_string(),
-            })
+    #[tokio::test]
+    async fn test_batch_analysis_is_bounded_and_keeps_input_order() {
+        let in_flight = Arc::new(InFlight::default());
+        let mut adapter = ObsidianAdapter::with_ai_client(None, None, Box::new(SlowAnalyst(in_flight.clone())), None);
+        adapter.set_batch_concurrency(3);
+        let topics = ["a", "bb", "outage", "dddd", "eeeee", "ffffff", "ggggggg", "hhhhhhhh"];
+        let mut fetched: Vec<(String, Result<String>)> = topics.iter()
+            .map(|topic| (format!("Notes/{}.md", topic), Ok(format!("# Note\nTopic: {}\n", topic))))
             .collect();
+        fetched.push(("Notes/missing.md".to_string(), Err(anyhow::anyhow!("not found"))));
 
-        self.suggestion_cache.suggestions.insert(content_hash.to_string(), cached_suggestions);
-    }
+        let results = adapter.batch_analyze_contents(fetched, &NoProgress).await;
+        assert_eq!(in_flight.max.load(Ordering::SeqCst), 3);
+        let outcomes: Vec<(&str, String)> = results.iter()
+            .map(|(path, result)| (path.as_str(), result.as_ref().map_or_else(|e| format!("error: {:#}", e), |a| a.themes.join(","))))
+            .collect();
+        assert_eq!(outcomes[..3], [("Notes/a.md", "a".to_string()), ("Notes/bb.md", "bb".to_string()), ("Notes/outage.md", "error: Failed to get AI analysis response: model overloaded".to_string())]);
+        assert_eq!(outcomes[7], ("Notes/hhhhhhhh.md", "hhhhhhhh".to_string()));
+        assert_eq!(outcomes[8], ("Notes/missing.md", "error: not found".to_string()));
+        // Successful analyses are cached; the failed one isn't
+        assert_eq!(adapter.analysis_cache().len(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_long_notes_are_analyzed_and_embedded_in_chunks() {
+        let topics = ["garden", "budget", "travel"];
+        // Each part is analyzed as the topic it names; the summary of summaries is fixed
+        let llm = topics.iter().fold(MockLLMClient::new().respond_when("These are summaries", "The whole note."), |llm, topic| {
+            llm.respond_when(&format!("Topic: {}", topic), serde_json::json!({
+                "themes": [topic, "shared"],
+                "summary": format!("About {}.", topic),
+                "sentiment": { "overall": "positive", "confidence": 0.5 },
+            }).to_string())
+        });
+        let mut adapter = ObsidianAdapter::with_ai_client(None, None, Box::new(llm.clone()), None);
+        adapter.set_analysis_config(AnalysisConfig { chunk_chars: 120, chunk_overlap_chars: 0, ..AnalysisConfig::default() });
+        let content: String = topics.iter()
+            .map(|topic| format!("## {}
+
+Topic: {} {}
+
+", topic, topic, "words ".repeat(12)))
+            .collect();
 
-    /// Get suggestion cache statistics
-    pub fn get_suggestion_cache_stats(&self) -> HashMap<String, serde_json::Value> {
-        let mut stats = HashMap::new();
-        stats.insert("hit_count".to_string(), serde_json::Value::Number(self.suggestion_cache.hit_count.into()));
-        stats.insert("miss_count".to_string(), serde_json::Value::Number(self.suggestion_cache.miss_count.into()));
-        stats.insert("cached_entries".to_string(), serde_json::Value::Number(self.suggestion_cache.suggestions.len().into()));
-        
-        let hit_rate = if self.suggestion_cache.hit_count + self.suggestion_cache.miss_count > 0 {
-            self.suggestion_cache.hit_count as f64 / (self.suggestion_cache.hit_count + self.suggestion_cache.miss_count) as f64
-        } else {
-            0.0
-        };
-        
-        stats.insert("hit_rate".to_string(), serde_json::Value::Number(serde_json::Number::from_f64(hit_rate).unwrap_or(serde_json::Number::from(0))));
-        
-        stats
-    }
+        let analysis = adapter.analyze_content(&content).await.unwrap();
+        assert_eq!(analysis.themes, ["shared", "garden", "budget", "travel"]);
+        assert_eq!(analysis.summary.as_deref(), Some("The whole note."));
+        assert_eq!((analysis.sentiment.overall.as_str(), analysis.sentiment.confidence), ("positive", 0.5));
+        let prompts = llm.prompts();
+        assert_eq!(prompts.len(), 4);
+        assert!(prompts[1].contains("sent in 3 parts; this is part 2") && !prompts[1].contains("garden"));
+        assert!(prompts[3].contains("1. About garden.\n2. About budget.\n3. About travel."));
 
-    /// Clear suggestion cache
-    pub fn clear_suggestion_cache(&mut self) {
-        self.suggestion_cache.suggestions.clear();
-        self.suggestion_cache.hit_count = 0;
-        self.suggestion_cache.miss_count = 0;
+        let in_flight = Arc::new(InFlight::default());
+        adapter.set_embedding_providers(Box::new(SlowEmbedder(in_flight)), None);
+        assert!(adapter.embed_content("Notes/long.md", &content).await.unwrap());
+        assert_eq!(adapter.vector_database.len(), 1);
+        let vector = &adapter.vector_database.get("Notes/long.md").unwrap().embedding;
+        assert!((vector.iter().map(|x| x * x).sum::<f32>() - 1.0).abs() < 1e-3);
     }
 
-    /// Get note by vault and path
-    pub async fn get_note(&self, vault: &str, path: &str) -> Result<MarkdownFile> {
-        let vault_path = format!("{}:{}", vault, path);
-        self.get_markdown_file_data(&vault_path).await
+    #[derive(Default)]
+    struct CountingProgress {
+        total: AtomicUsize,
+        done: AtomicUsize,
     }
 
-    /// List available vaults
-    pub async fn list_vaults(&self) -> Result<Vec<String>> {
-        // For now, return a default vault
-        // In a real implementation, this would query the Obsidian API
-        Ok(vec!["default".to_string()])
-    }
+    impl BatchProgress for CountingProgress {
+        fn start(&self, total: usize) {
+            self.total.store(total, Ordering::SeqCst);
+        }
 
-    /// List notes in a vault
-    pub async fn list_notes(&self, vault: &str) -> Result<Vec<String>> {
-        // For now, return empty list
-        // In a real implementation, this would query the Obsidian API
-        Ok(vec![])
+        fn advance(&self, _item: &str) {
+            self.done.fetch_add(1, Ordering::SeqCst);
+        }
     }
 
-    pub async fn list_files_in_folder(&self, folder_path: &str) -> Result<Vec<String>> {
-        // Create a URL to list files in the given folder
-        let url = format!("{}/vault/{}/", self.base_url, folder_path);
-        
-        let response = self
-            .add_auth_header(self.client.get(&url).header("Accept", "application/json"))
-            .send()
-            .await
-            .context(format!("Failed to send GET request to {}", url))?;
+    #[tokio::test]
+    async fn test_batch_embedding_is_bounded_and_writes_in_order() {
+        let in_flight = Arc::new(InFlight::default());
+        let mut adapter = ObsidianAdapter::new(None, None);
+        adapter.set_embedding_providers(Box::new(SlowEmbedder(in_flight.clone())), None);
+        adapter.set_batch_concurrency(2);
+        let fetched = |paths: &[&str]| -> Vec<(String, Result<String>)> {
+            paths.iter().map(|path| match *path {
+                "Notes/gone.md" => (path.to_string(), Err(anyhow::anyhow!("not found"))),
+                _ => (path.to_string(), Ok(format!("Body of {}", path))),
+            }).collect()
+        };
 
-        if response.status().is_success() {
-            let response_text = response
-                .text()
-                .await
-                .context("Failed to read response text")?;
-            
-            // Try to parse as JSON array of file names
-            let files: Vec<String> = serde_json::from_str(&response_text)
-                .context("Failed to parse file list response")?;
-            
-            Ok(files)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            bail!(
-                "MCP server returned error {}: {}. URL: {}",
-                status,
-                error_text,
-                url
-            )
+        let progress = CountingProgress::default();
+        let results = adapter.batch_embed_contents(fetched(&["Notes/a.md", "Notes/gone.md", "Notes/bb.md", "Notes/ccc.md", "Notes/dddd.md"]), &progress).await;
+        assert_eq!(in_flight.max.load(Ordering::SeqCst), 2);
+        // Failed notes count towards progress too
+        assert_eq!((progress.total.load(Ordering::SeqCst), progress.done.load(Ordering::SeqCst)), (5, 5));
+        let outcomes: Vec<(&str, bool)> = results.iter().map(|(path, result)| (path.as_str(), result.is_ok())).collect();
+        assert_eq!(outcomes, vec![("Notes/a.md", true), ("Notes/gone.md", false), ("Notes/bb.md", true), ("Notes/ccc.md", true), ("Notes/dddd.md", true)]);
+        assert_eq!(adapter.vector_database.len(), 4);
+        assert_eq!(adapter.vector_database.get("Notes/ccc.md").unwrap().embedding[0], "Body of Notes/ccc.md".len() as f32);
+
+        // Unchanged notes aren't embedded again
+        let results = adapter.batch_embed_contents(fetched(&["Notes/a.md", "Notes/e.md"]), &NoProgress).await;
+        assert_eq!(results.iter().map(|(_, result)| *result.as_ref().unwrap()).collect::<Vec<_>>(), vec![false, true]);
+    }
+
+    #[tokio::test]
+    async fn test_switching_models_marks_embeddings_and_analyses_stale() {
+        let mut adapter = ObsidianAdapter::new(None, None);
+        adapter.set_embedding_providers(FakeEmbedder::new("openai/old", Some(0.5), 4), None);
+        assert!(adapter.embed_content("a.md", "Alpha note").await.unwrap());
+        assert!(adapter.embed_content("b.md", "Beta note").await.unwrap());
+        assert_eq!(adapter.stale_embedding_count(), 0);
+
+        adapter.set_embedding_providers(FakeEmbedder::new("openai/new", Some(0.25), 4), None);
+        let stats = adapter.get_vector_database_stats();
+        assert_eq!((&stats["stale_embeddings"], &stats["needs_reindex"]), (&serde_json::json!(2), &serde_json::json!(true)));
+        // Unchanged content is embedded again when its model is stale
+        assert!(adapter.embed_content("a.md", "Alpha note").await.unwrap());
+        assert_eq!(adapter.minority_paths(usize::MAX), vec!["b.md".to_string()]);
+
+        adapter.llm_client = Some(Box::new(SlowAnalyst(Arc::new(InFlight::default()))));
+        let version = adapter.analysis_version();
+        assert!(version.starts_with("1.0.0+slow-analyst."), "{}", version);
+        assert_eq!(analysis_schema(&version), ANALYSIS_VERSION);
+        let key = adapter.analysis_cache_key("Topic: rust");
+        assert!(key.starts_with(&adapter.generate_cache_key("Topic: rust")));
+        adapter.set_analysis_config(AnalysisConfig { max_themes: 3, ..AnalysisConfig::default() });
+        assert_ne!(adapter.analysis_cache_key("Topic: rust"), key);
+    }
+
+    #[tokio::test]
+    async fn test_quota_failover_tags_embeddings_and_reunifies() {
+        let mut adapter = ObsidianAdapter::new(None, None);
+        adapter.set_embedding_providers(
+            FakeEmbedder::new("openai/primary", None, 8),
+            Some(FakeEmbedder::new("gemini/fallback", Some(0.5), 4)),
+        );
+
+        assert!(adapter.embed_content("a.md", "Alpha note").await.unwrap());
+        assert!(matches!(adapter.embedding_failover(), Some(ArrowheadError::QuotaExhausted { .. })));
+        assert!(adapter.embed_content("b.md", "Beta note").await.unwrap());
+        let models: Vec<&str> = adapter.get_indexed_documents().iter().map(|d| d.model.as_str()).collect();
+        assert_eq!(models, vec!["gemini/fallback", "gemini/fallback"]);
+        // While failed over, an unchanged note isn't embedded with the fallback again
+        assert!(!adapter.embed_content("a.md", "Alpha note").await.unwrap());
+
+        // The primary can't embed the query, so only the fallback's documents are searched
+        let queries = adapter.query_embeddings("alpha", 2, None).await.unwrap();
+        assert_eq!(queries.iter().map(|(m, _)| m.as_str()).collect::<Vec<_>>(), vec!["gemini/fallback"]);
+
+        // Next run: the quota is back and unchanged notes move to the primary
+        adapter.embedder = Some(FakeEmbedder::new("openai/primary", Some(0.25), 8));
+        adapter.embedding_failover = None;
+        assert_eq!(adapter.minority_paths(10).len(), 2);
+        assert!(adapter.embed_content("a.md", "Alpha note").await.unwrap());
+        assert_eq!(adapter.minority_paths(10), vec!["b.md".to_string()]);
+        assert!(!adapter.embed_content("a.md", "Alpha note").await.unwrap());
+        let queries = adapter.query_embeddings("alpha", 2, None).await.unwrap();
+        assert_eq!(queries.iter().map(|(m, _)| m.as_str()).collect::<Vec<_>>(), vec!["openai/primary", "gemini/fallback"]);
+    }
+
+    #[tokio::test]
+    async fn test_embedding_dimension_is_validated() {
+        let mut adapter = ObsidianAdapter::new(None, None);
+        assert!(adapter.generate_embeddings("text").await.is_err());
+
+        // A declared dimension the provider doesn't keep to
+        let mut embedder = FakeEmbedder::new("openai/primary", Some(0.5), 5);
+        embedder.declared = Some(6);
+        adapter.set_embedding_providers(embedder, None);
+        let error = adapter.embed_content("a.md", "Alpha").await.unwrap_err();
+        assert!(error.to_string().contains("expected 6, got 5"), "{}", error);
+        assert!(adapter.get_indexed_documents().is_empty());
+
+        // Undeclared: the first vector sets the model's length in the index
+        let mut embedder = FakeEmbedder::new("openai/primary", Some(0.5), 6);
+        embedder.declared = None;
+        adapter.embedder = Some(embedder);
+        assert!(adapter.embed_content("a.md", "Alpha").await.unwrap());
+        adapter.embedder = Some(Box::new(FakeEmbedder { model: "openai/primary", fill: Some(0.5), dimension: 5, declared: None }));
+        let error = adapter.embed_content("b.md", "Beta").await.unwrap_err();
+        assert!(error.to_string().contains("expected 6, got 5"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_query_reuses_its_embedding() {
+        let mut adapter = ObsidianAdapter::new(None, None);
+        adapter.embedding_cache_path = std::env::temp_dir().join(format!("arrowhead-embeddings-{}.bin", uuid::Uuid::new_v4())).to_string_lossy().to_string();
+        adapter.search_config.include_snippets = false;
+        adapter.set_embedding_providers(FakeEmbedder::new("openai/primary", Some(0.5), 4), None);
+        assert!(adapter.embed_content("a.md", "Alpha note").await.unwrap());
+        assert_eq!(adapter.semantic_search("Alpha").await.unwrap().len(), 1);
+
+        // The provider is out of quota now, but the same query doesn't need it
+        adapter.embedder = Some(FakeEmbedder::new("openai/primary", None, 4));
+        assert_eq!(adapter.semantic_search("  alpha ").await.unwrap().len(), 1);
+        assert!(adapter.semantic_search("beta").await.is_err());
+
+        let stats = adapter.get_vector_database_stats();
+        assert_eq!(stats["query_cache_hits"], serde_json::json!(1));
+        assert_eq!(stats["query_cache_misses"], serde_json::json!(2));
+        std::fs::remove_file(&adapter.embedding_cache_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_interleaved_embeds_and_removals_keep_paths_on_their_own_embeddings() {
+        let mut adapter = ObsidianAdapter::new(None, None);
+        adapter.embedding_cache_path = std::env::temp_dir().join(format!("arrowhead-embeddings-{}.bin", uuid::Uuid::new_v4())).to_string_lossy().to_string();
+        adapter.set_embedding_providers(FakeEmbedder::new("openai/primary", Some(0.5), 4), None);
+        let content = |path: &str, version: u32| format!("Body of {} v{}", path, version);
+
+        // Version 0 removes the path
+        let steps = [
+            ("a.md", 1), ("b.md", 1), ("c.md", 1), ("a.md", 0),
+            // Re-embedding a later path after an earlier one is removed replaces its own entry
+            ("c.md", 2), ("d.md", 1), ("c.md", 0), ("a.md", 2), ("b.md", 2), ("missing.md", 0),
+        ];
+        for (path, version) in steps {
+            if version == 0 {
+                adapter.remove_document_embedding(path).unwrap();
+            } else {
+                assert!(adapter.embed_content(path, &content(path, version)).await.unwrap());
+            }
         }
-    }
 
-    /// Delete a note
-    pub async fn delete_note(&self, vault: &str, path: &str) -> Result<()> {
-        // For now, this is a no-op
-        // In a real implementation, this would delete via the Obsidian API
-        Ok(())
+        let expected = [("a.md", 2), ("b.md", 2), ("d.md", 1)];
+        assert_eq!(adapter.vector_database.paths().collect::<Vec<_>>(), vec!["a.md", "b.md", "d.md"]);
+        for (path, version) in expected {
+            let doc = adapter.vector_database.get(path).unwrap();
+            assert_eq!(doc.path, path);
+            assert_eq!(doc.metadata.excerpt, content(path, version));
+        }
+        assert!(adapter.get_indexed_documents().iter().all(|doc| adapter.vector_database.get(&doc.path).is_some_and(|d| d.path == doc.path)));
+
+        // And the same after a save and reload
+        adapter.save_vector_database().unwrap();
+        let mut reader = ObsidianAdapter::new(None, None);
+        reader.embedding_cache_path = adapter.embedding_cache_path.clone();
+        reader.set_embedding_providers(FakeEmbedder::new("openai/primary", Some(0.5), 4), None);
+        reader.load_vector_database().unwrap();
+        for (path, version) in expected {
+            assert_eq!(reader.vector_database.get(path).unwrap().metadata.excerpt, content(path, version));
+        }
+        std::fs::remove_file(&adapter.embedding_cache_path).ok();
     }
 
-    /// Create a new note
-    pub async fn create_note(&self, vault: &str, path: &str, content: &str) -> Result<()> {
-        let vault_path = format!("{}:{}", vault, path);
-        self.create_file(&vault_path, content).await
+    #[test]
+    fn test_search_results_highlight_query_terms_in_the_snippet() {
+        let mut adapter = ObsidianAdapter::new(None, None);
+        let model = adapter.embedding_model.clone();
+        adapter.vector_database.insert(DocumentEmbedding {
+            path: "Notes/launch.md".to_string(),
+            embedding: vec![1.0, 0.0],
+            content_hash: String::new(),
+            created_at: Utc::now(),
+            metadata: DocumentMetadata {
+                title: "Launch".to_string(),
+                tags: Vec::new(),
+                length: 0,
+                excerpt: "The launch moves to September. Planned launches need legal sign-off.".to_string(),
+                modified_at: None,
+                skeleton: None,
+            },
+            model,
+        });
+        let results = adapter.semantic_search_with_embedding("launch plans", &[1.0, 0.0]);
+        let result = &results[0];
+        let matched: Vec<(&str, &str)> = result.highlights.iter().map(|h| (&result.snippet[h.start..h.end], h.term.as_str())).collect();
+        assert_eq!(matched, vec![("launch", "launch"), ("Planned", "plans"), ("launches", "launch")]);
     }
 
-    /// Update a note
-    pub async fn update_note(&self, vault: &str, path: &str, content: &str) -> Result<()> {
-        let vault_path = format!("{}:{}", vault, path);
-        self.update_file(&vault_path, content).await
+    #[test]
+    fn test_unusable_embeddings_are_dropped_on_load() {
+        let path = std::env::temp_dir().join(format!("arrowhead-embeddings-{}.bin", uuid::Uuid::new_v4()));
+        let doc = |path: &str, model: &str, dimension: usize| DocumentEmbedding {
+            path: path.to_string(),
+            embedding: vec![0.5; dimension],
+            content_hash: String::new(),
+            created_at: Utc::now(),
+            metadata: DocumentMetadata { title: String::new(), tags: Vec::new(), length: 0, excerpt: String::new(), modified_at: None, skeleton: None },
+            model: model.to_string(),
+        };
+        let mut writer = ObsidianAdapter::new(None, None);
+        writer.embedding_cache_path = path.to_string_lossy().to_string();
+        writer.vector_database = VectorDatabase::from_documents([
+            // Made by prompting the chat model, before models were recorded
+            doc("Notes/legacy.md", "", 768),
+            doc("Notes/ok.md", "openai/text-embedding-3-small", 1536),
+            doc("Notes/truncated.md", "openai/text-embedding-3-small", 768),
+            doc("Notes/other.md", "gemini/text-embedding-004", 768),
+        ], VECTOR_DATABASE_VERSION, Utc::now());
+        writer.save_vector_database().unwrap();
+
+        let mut reader = ObsidianAdapter::new(None, None);
+        reader.embedding_cache_path = writer.embedding_cache_path.clone();
+        reader.set_embedding_providers(FakeEmbedder::new("openai/text-embedding-3-small", Some(0.1), 1536), None);
+        reader.load_vector_database().unwrap();
+        let paths: Vec<&str> = reader.get_indexed_documents().iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec!["Notes/ok.md", "Notes/other.md"]);
+        assert_eq!(reader.vector_database.get("Notes/other.md").unwrap().model, "gemini/text-embedding-004");
+
+        // The cleaned store was written back
+        let mut again = ObsidianAdapter::new(None, None);
+        again.embedding_cache_path = writer.embedding_cache_path.clone();
+        again.load_vector_database().unwrap();
+        assert_eq!(again.get_indexed_documents().len(), 2);
+        std::fs::remove_file(&path).ok();
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
     #[test]
     fn test_parse_markdown_with_full_frontmatter() {
@@ -3287,9 +5801,13 @@ Content below empty frontmatter."#;
                 due_date: Some("tomorrow".to_string()),
                 status: Some("in progress".to_string()),
                 target_date: Some("next week".to_string()),
+                priority: None,
+                created: None,
+                completed: None,
                 ai_analysis: None,
                 ai_analysis_version: None,
                 ai_analysis_timestamp: None,
+                ..Default::default()
             },
             content: "Writing some Rust code.".to_string(),
         };
@@ -3311,9 +5829,13 @@ Content below empty frontmatter."#;
                 due_date: None,
                 status: Some("open".to_string()),
                 target_date: None,
+                priority: None,
+                created: None,
+                completed: None,
                 ai_analysis: None,
                 ai_analysis_version: None,
                 ai_analysis_timestamp: None,
+                ..Default::default()
             },
             content: "A simple task.".to_string(),
         };
@@ -3370,6 +5892,62 @@ Content below empty frontmatter."#;
         assert!(fm_str.contains("status: active"));
     }
 
+    const CUSTOM_FRONTMATTER: &str = "# kept by Obsidian\n\
+aliases: [Roadmap, \"Q3 plan\"]\n\
+cssclass: wide\n\
+tags:\n\
+- planning\n\
+rating: 4.50\n\
+count: 0012\n\
+project:\n  owner: Dana   # lead\n  budget:\n    amount: 1200\n    currency: EUR\n\
+summary: |\n  First line.\n\n  Second paragraph.\n\
+status: open\n";
+
+    #[test]
+    fn test_untouched_frontmatter_round_trips_byte_for_byte() {
+        let raw = format!("---\n{}---\n\nBody text.", CUSTOM_FRONTMATTER);
+        let file = ObsidianAdapter::parse_markdown_file(&raw).unwrap();
+        assert_eq!(file.frontmatter.status.as_deref(), Some("open"));
+        assert_eq!(file.frontmatter.extra.get("cssclass").and_then(|v| v.as_str()), Some("wide"));
+        assert_eq!(file.frontmatter.extra["project"]["budget"]["amount"].as_u64(), Some(1200));
+        assert_eq!(file.frontmatter.extra["summary"].as_str(), Some("First line.\n\nSecond paragraph.\n"));
+
+        assert_eq!(ObsidianAdapter::serialize_markdown_file(&file).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_changed_fields_rewrite_only_themselves() {
+        let raw = format!("---\n{}---\n\nBody text.", CUSTOM_FRONTMATTER);
+        let mut file = ObsidianAdapter::parse_markdown_file(&raw).unwrap();
+        file.frontmatter.tags = Some(vec!["planning".to_string(), "q3".to_string()]);
+        file.frontmatter.ai_analysis_version = Some("1.0.0".to_string());
+        file.frontmatter.status = None;
+        let written = ObsidianAdapter::serialize_markdown_file(&file).unwrap();
+
+        let expected = CUSTOM_FRONTMATTER
+            .replace("tags:\n- planning\n", "tags:\n- planning\n- q3\n")
+            .replace("status: open\n", "ai_analysis_version: 1.0.0\n");
+        assert_eq!(written, format!("---\n{}---\n\nBody text.", expected));
+
+        // A second pass is stable, and the numeric and nested fields still parse the same
+        let reparsed = ObsidianAdapter::parse_markdown_file(&written).unwrap();
+        assert_eq!(ObsidianAdapter::serialize_markdown_file(&reparsed).unwrap(), written);
+        assert_eq!(reparsed.frontmatter.extra["rating"].as_f64(), Some(4.5));
+        assert_eq!(reparsed.frontmatter.extra, file.frontmatter.extra);
+    }
+
+    #[test]
+    fn test_frontmatter_with_aliases_falls_back_to_reserializing() {
+        let raw = "---\nbase: &b {x: 1}\ncopy: *b\nstatus: open\n---\n\nBody";
+        let mut file = ObsidianAdapter::parse_markdown_file(raw).unwrap();
+        assert!(file.frontmatter.source.is_none());
+        file.frontmatter.status = Some("done".to_string());
+        let written = ObsidianAdapter::serialize_markdown_file(&file).unwrap();
+        let reparsed = ObsidianAdapter::parse_markdown_file(&written).unwrap();
+        assert_eq!(reparsed.frontmatter.extra["copy"]["x"].as_u64(), Some(1));
+        assert_eq!(reparsed.frontmatter.status.as_deref(), Some("done"));
+    }
+
     #[test]
     fn test_content_analysis_default() {
         let analysis = ContentAnalysis::default();
@@ -3516,6 +6094,45 @@ Content below empty frontmatter."#;
         assert!(prompt.contains("reading_time_minutes"));
     }
 
+    #[test]
+    fn test_prompt_and_parser_skip_disabled_sections() {
+        let mut adapter = ObsidianAdapter::new(None, None);
+        adapter.set_analysis_config(AnalysisConfig {
+            extract_themes: true,
+            analyze_sentiment: false,
+            extract_entities: false,
+            identify_concepts: false,
+            generate_summary: false,
+            ..AnalysisConfig::default()
+        });
+        let prompt = adapter.create_analysis_prompt("Notes on the garden plan for spring.");
+        assert!(prompt.contains(r#""themes": ["theme1", "theme2", ...], // Max 10 themes"#));
+        for section in ["\"sentiment\"", "\"entities\"", "\"concepts\"", "\"summary\"", "For sentiment", "For entities", "For concepts"] {
+            assert!(!prompt.contains(section), "prompt still asks for {}", section);
+        }
+
+        let analysis = adapter.parse_analysis_response(r#"{"themes": ["gardening"], "sentiment": {"overall": "positive", "confidence": 0.9}, "keywords": ["spring"]}"#).unwrap();
+        assert_eq!(analysis.themes, vec!["gardening".to_string()]);
+        assert_eq!(analysis.sentiment.overall, SentimentAnalysis::default().overall);
+        assert!(analysis.entities.is_empty() && analysis.concepts.is_empty() && analysis.summary.is_none());
+        assert_eq!(analysis.keywords, vec!["spring".to_string()]);
+    }
+
+    #[test]
+    fn test_long_notes_are_sent_as_head_and_tail() {
+        let content = format!("{}{}{}", "a".repeat(40), "é".repeat(100), "z".repeat(20));
+        let (excerpt, omitted) = analysis_excerpt(&content, Some(60));
+        assert_eq!(omitted, 100);
+        assert_eq!(excerpt, format!("{}\n\n[… 100 characters omitted …]\n\n{}", "a".repeat(40), "z".repeat(20)));
+        assert_eq!(analysis_excerpt(&content, None), (Cow::Borrowed(content.as_str()), 0));
+
+        let mut adapter = ObsidianAdapter::new(None, None);
+        adapter.set_analysis_config(AnalysisConfig { max_content_chars: Some(60), ..AnalysisConfig::default() });
+        let prompt = adapter.create_analysis_prompt(&content);
+        assert!(prompt.contains("100 of its 160 characters in the middle were left out"));
+        assert!(!prompt.contains("éé"));
+    }
+
     #[test]
     fn test_parse_analysis_response_valid_json() {
         let adapter = ObsidianAdapter::new(None, None);
@@ -3621,6 +6238,8 @@ That's the complete analysis."#;
         assert!(!config.auto_apply_tags);
         assert!(config.suggest_folder_moves);
         assert!(config.suggest_auto_links);
+        assert!(!config.auto_move_notes);
+        assert!(!config.auto_insert_links);
         assert!(config.custom_tag_rules.is_empty());
     }
 
@@ -3778,6 +6397,7 @@ That's the complete analysis."#;
     #[test]
     fn test_content_suggestion_request() {
         let request = ContentSuggestionRequest {
+            path: None,
             content: "This is test content".to_string(),
             cursor_position: ContentPosition { line: 0, column: 10, length: None },
             max_suggestions: 5,
@@ -3824,6 +6444,7 @@ That's the complete analysis."#;
     fn test_suggestion_cache_initialization() {
         let cache = SuggestionCache {
             suggestions: HashMap::new(),
+            max_entries: 200,
             hit_count: 0,
             miss_count: 0,
         };
@@ -3832,4 +6453,733 @@ That's the complete analysis."#;
         assert_eq!(cache.hit_count, 0);
         assert_eq!(cache.miss_count, 0);
     }
+
+    #[test]
+    fn test_suggestion_cache_drops_expired_and_oldest_entries() {
+        let mut adapter = ObsidianAdapter::new(None, None);
+        adapter.suggestion_cache.max_entries = 2;
+        let suggestion = ContentSuggestion {
+            text: "See [[Rust]]".to_string(),
+            suggestion_type: SuggestionType::LinkSuggestion,
+            confidence: 0.9,
+            context: String::new(),
+            reason: String::new(),
+            source_documents: Vec::new(),
+            position: None,
+        };
+        for hash in ["a", "b", "c"] {
+            adapter.cache_suggestions(hash, std::slice::from_ref(&suggestion));
+        }
+        let mut kept: Vec<&String> = adapter.suggestion_cache.suggestions.keys().collect();
+        kept.sort();
+        assert_eq!(kept, ["b", "c"]);
+
+        for cached in adapter.suggestion_cache.suggestions.get_mut("b").unwrap() {
+            cached.cached_at = Utc::now() - chrono::Duration::seconds(301);
+        }
+        adapter.cache_suggestions("d", &[suggestion]);
+        assert!(adapter.get_cached_suggestions("b").is_none());
+        assert!(!adapter.suggestion_cache.suggestions.contains_key("b"));
+        assert_eq!(adapter.suggestion_cache.suggestions.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_vault_adapters_keep_caches_and_notes_apart() {
+        let dir = std::env::temp_dir().join(format!("arrowhead-vaults-{}", uuid::Uuid::new_v4()));
+        let vault = |name: &str, port: u16, cache_dir: Option<String>| crate::config::VaultConfig {
+            name: name.to_string(),
+            base_url: format!("https://127.0.0.1:{}", port),
+            api_key: None,
+            cache_dir,
+        };
+        let mut config = Config::default();
+        config.cache.dir = Some(dir.to_string_lossy().to_string());
+        config.vaults = vec![vault("work", 27125, None), vault("home", 27126, Some(dir.join("home").to_string_lossy().to_string()))];
+
+        let work = ObsidianAdapter::for_vault(&config, "Work").unwrap();
+        let home = ObsidianAdapter::for_vault(&config, "home").unwrap();
+        assert_eq!(work.vault_name(), "work");
+        assert!(work.embedding_cache_path.starts_with(&*dir.to_string_lossy()));
+        assert_ne!(work.embedding_cache_path, home.embedding_cache_path);
+        assert_eq!(home.template_cache_path, dir.join("home").join("templates.bin").to_string_lossy());
+        assert!(ObsidianAdapter::for_vault(&config, "school").is_err());
+
+        assert_eq!(work.list_vaults().await.unwrap(), ["work"]);
+        assert_eq!(work.path_in_vault("default", "Notes/a.md").unwrap(), "Notes/a.md");
+        let error = work.get_note("home", "Notes/a.md").await.unwrap_err();
+        assert!(error.to_string().contains("is in vault 'home'"), "{}", error);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    fn link_suggestion(target: &str, text: &str, context: &str) -> LinkSuggestion {
+        LinkSuggestion {
+            target_path: target.to_string(),
+            link_text: text.to_string(),
+            confidence: 0.9,
+            context: context.to_string(),
+            reason: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_links_are_inserted_at_the_first_plain_mention() {
+        let body = "# Acme sync\n\n```\nacme deploy\n```\nSee `acme` and [the site](https://acme.dev) or https://acme.dev/x.\nCall ACME about Acmeville and Acme.\n";
+        let wrapped = insert_link_at_context(body, &link_suggestion("People/Acme.md", "Acme", "Mentions entity: Acme")).unwrap();
+        assert_eq!(wrapped.lines().nth(6), Some("Call [[People/Acme|ACME]] about Acmeville and Acme."));
+        assert_eq!(wrapped.replace("[[People/Acme|ACME]]", "ACME"), body);
+
+        // A theme anchor is kept as written, with the link after it
+        let themed = insert_link_at_context("Notes on pricing tiers.", &link_suggestion("Pricing.md", "Pricing plan", "Related content about pricing")).unwrap();
+        assert_eq!(themed, "Notes on pricing ([[Pricing|Pricing plan]]) tiers.");
+
+        assert_eq!(insert_link_at_context(&wrapped, &link_suggestion("People/Acme.md", "Acme", "Mentions entity: Acme")), Err("already linked"));
+        assert_eq!(insert_link_at_context("```\nAcme\n```\n", &link_suggestion("People/Acme.md", "Acme", "Mentions entity: Acme")), Err("context not found in the note"));
+    }
+
+    #[test]
+    fn test_renamed_embeddings_keep_their_vectors() {
+        let mut database = VectorDatabase::new(VECTOR_DATABASE_VERSION, Utc::now());
+        database.insert(DocumentEmbedding {
+            path: "Inbox/a.md".to_string(),
+            embedding: vec![0.25, 0.5],
+            content_hash: "hash".to_string(),
+            created_at: Utc::now(),
+            metadata: DocumentMetadata { title: "A".to_string(), tags: Vec::new(), length: 0, excerpt: String::new(), modified_at: None, skeleton: None },
+            model: String::new(),
+        });
+        assert!(database.rename("Inbox/a.md", "projects/a.md"));
+        assert!(!database.rename("Inbox/a.md", "work/a.md"));
+        assert_eq!(database.paths().collect::<Vec<_>>(), vec!["projects/a.md"]);
+        let doc = database.get("projects/a.md").unwrap();
+        assert_eq!((doc.path.as_str(), doc.embedding.as_slice()), ("projects/a.md", [0.25, 0.5].as_slice()));
+    }
+
+    /// A Local REST API stand-in holding `files` in memory. Each request line is logged.
+    async fn serve_vault(files: HashMap<String, String>) -> (String, Arc<Mutex<HashMap<String, String>>>, Arc<Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let files = Arc::new(Mutex::new(files));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (vault, requests) = (files.clone(), log.clone());
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else { break };
+                let mut raw = Vec::new();
+                let mut buf = [0u8; 4096];
+                let (head, body) = loop {
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    raw.extend_from_slice(&buf[..n]);
+                    if let Some(end) = raw.windows(4).position(|w| w == b"\r\n\r\n") {
+                        let head = String::from_utf8_lossy(&raw[..end]).to_string();
+                        let length = head.lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                            .unwrap_or(0);
+                        while raw.len() < end + 4 + length {
+                            let n = socket.read(&mut buf).await.unwrap_or(0);
+                            if n == 0 { break; }
+                            raw.extend_from_slice(&buf[..n]);
+                        }
+                        break (head, String::from_utf8_lossy(&raw[end + 4..]).to_string());
+                    }
+                    if n == 0 { break (String::new(), String::new()); }
+                };
+                let request_line = head.lines().next().unwrap_or("").to_string();
+                let mut parts = request_line.split(' ');
+                let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or("").trim_start_matches("/vault/").to_string());
+                let header = |name: &str| head.lines()
+                    .find_map(|l| l.split_once(':').filter(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.trim().to_string()))
+                    .unwrap_or_default();
+                if method == "PATCH" {
+                    requests.lock().unwrap().push(format!("PATCH {} {} {} {} {}", path, header("Operation"), header("Target-Type"), header("Target"), body));
+                } else {
+                    requests.lock().unwrap().push(format!("{} {}", method, path));
+                }
+                let (status, reply) = {
+                    let mut vault = vault.lock().unwrap();
+                    match method {
+                        // A folder lists its notes and subfolders, the way the REST API does
+                        "GET" if path.is_empty() || path.ends_with('/') => {
+                            let mut entries: Vec<String> = vault.keys()
+                                .filter_map(|file| file.strip_prefix(path.as_str()))
+                                .map(|rest| match rest.split_once('/') {
+                                    Some((folder, _)) => format!("{}/", folder),
+                                    None => rest.to_string(),
+                                })
+                                .collect();
+                            entries.sort();
+                            entries.dedup();
+                            match entries.is_empty() {
+                                true => ("404 Not Found", String::new()),
+                                false => ("200 OK", serde_json::json!({ "files": entries }).to_string()),
+                            }
+                        }
+                        "GET" => match vault.get(&path) {
+                            Some(content) => ("200 OK", content.clone()),
+                            None => ("404 Not Found", String::new()),
+                        },
+                        "PUT" => {
+                            vault.insert(path, body);
+                            ("204 No Content", String::new())
+                        }
+                        // The REST API appends on POST, creating the file if needed
+                        "POST" => {
+                            vault.entry(path).or_default().push_str(&body);
+                            ("204 No Content", String::new())
+                        }
+                        // Only heading appends change the stored note; other targets are just logged
+                        "PATCH" => match vault.get_mut(&path) {
+                            Some(content) if header("Target-Type") == "heading" => {
+                                let heading = format!("# {}", header("Target"));
+                                match content.find(&heading) {
+                                    Some(start) => {
+                                        let after = start + heading.len();
+                                        let end = content[after..].find("\n#").map(|i| after + i + 1).unwrap_or(content.len());
+                                        content.insert_str(end, &body);
+                                        ("200 OK", String::new())
+                                    }
+                                    None => ("400 Bad Request", String::new()),
+                                }
+                            }
+                            Some(_) => ("200 OK", String::new()),
+                            None => ("404 Not Found", String::new()),
+                        },
+                        "DELETE" => match vault.remove(&path) {
+                            Some(_) => ("204 No Content", String::new()),
+                            None => ("404 Not Found", String::new()),
+                        },
+                        _ => ("405 Method Not Allowed", String::new()),
+                    }
+                };
+                let response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, reply.len(), reply);
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (base_url, files, log)
+    }
+
+    #[tokio::test]
+    async fn test_organizing_moves_the_note_and_inserts_links() {
+        let note = "---\ntags:\n- launch\n---\n\nWe agreed the rollout with Acme.\n";
+        let (base_url, vault, log) = serve_vault(HashMap::from([
+            ("Inbox/launch.md".to_string(), note.to_string()),
+            ("work/launch.md".to_string(), "Someone else's launch".to_string()),
+        ])).await;
+        let mut adapter = ObsidianAdapter::new(Some(base_url), None);
+        adapter.embedding_cache_path = std::env::temp_dir().join(format!("arrowhead-embeddings-{}.bin", uuid::Uuid::new_v4())).to_string_lossy().to_string();
+        adapter.vector_database.insert(DocumentEmbedding {
+            path: "Inbox/launch.md".to_string(),
+            embedding: vec![1.0, 0.0],
+            content_hash: String::new(),
+            created_at: Utc::now(),
+            metadata: DocumentMetadata { title: "Launch".to_string(), tags: Vec::new(), length: 0, excerpt: String::new(), modified_at: None, skeleton: None },
+            model: String::new(),
+        });
+        adapter.set_organization_config(OrganizationConfig {
+            auto_apply_tags: true,
+            auto_move_notes: true,
+            auto_insert_links: true,
+            ..OrganizationConfig::default()
+        });
+        let folder = |path: &str, confidence: f32| FolderSuggestion { folder_path: path.to_string(), confidence, reason: String::new(), category: String::new() };
+        let recommendations = OrganizationRecommendations {
+            suggested_tags: vec![
+                TagSuggestion { tag: "project".to_string(), confidence: 0.9, reason: String::new(), source: TagSource::Category },
+                TagSuggestion { tag: "launch".to_string(), confidence: 0.9, reason: String::new(), source: TagSource::Theme },
+            ],
+            folder_suggestions: vec![folder("basic", 0.6), folder("projects", 0.9), folder("work", 0.95)],
+            link_suggestions: vec![link_suggestion("People/Acme.md", "Acme", "Mentions entity: Acme")],
+            overall_confidence: 0.9,
+            generated_at: Utc::now(),
+        };
+
+        // The most confident folder already has a launch.md, so nothing moves in a dry run
+        let preview = adapter.apply_organization_recommendations("Inbox/launch.md", &recommendations, true).await.unwrap();
+        assert!(preview.dry_run);
+        assert_eq!(preview.applied, vec![
+            OrganizationAction::AddTag("project".to_string()),
+            OrganizationAction::InsertLink { target: "People/Acme".to_string(), text: "Acme".to_string() },
+        ]);
+        let skipped: Vec<String> = preview.skipped.iter().map(|(action, reason)| format!("{}: {}", action, reason)).collect();
+        assert_eq!(skipped, vec![
+            "add tag #launch: already tagged",
+            "move to projects/: a more confident folder was chosen",
+            "move to basic/: a more confident folder was chosen",
+            "move to work/: work/launch.md already exists",
+        ]);
+        assert!(log.lock().unwrap().iter().all(|request| request.starts_with("GET ")));
+        assert_eq!(preview.path, "Inbox/launch.md");
+
+        let recommendations = OrganizationRecommendations { folder_suggestions: vec![folder("projects/", 0.9)], ..recommendations };
+        let summary = adapter.apply_organization_recommendations("Inbox/launch.md", &recommendations, false).await.unwrap();
+        assert_eq!(summary.path, "projects/launch.md");
+        assert_eq!(summary.applied.last(), Some(&OrganizationAction::MoveTo("projects".to_string())));
+        let vault = vault.lock().unwrap();
+        assert!(!vault.contains_key("Inbox/launch.md"));
+        let moved = ObsidianAdapter::parse_markdown_file(&vault["projects/launch.md"]).unwrap();
+        assert_eq!(moved.frontmatter.tags, Some(vec!["launch".to_string(), "project".to_string()]));
+        assert_eq!(moved.content.trim(), "We agreed the rollout with [[People/Acme|Acme]].");
+        assert_eq!(adapter.vector_database.paths().collect::<Vec<_>>(), vec!["projects/launch.md"]);
+        std::fs::remove_file(&adapter.embedding_cache_path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_heuristic_backend_feeds_the_same_suggestions() {
+        let note = "---\ntags:\n- baking\ncategory: Recipe\n---\n# Sourdough Starter\n\nFeed the sourdough starter with rye flour. The starter doubles overnight.\n\n## Notes\n\nProject idea: sell loaves.\n";
+        let (base_url, _vault, _log) = serve_vault(HashMap::from([("Inbox/starter.md".to_string(), note.to_string())])).await;
+        let mut adapter = ObsidianAdapter::new(Some(base_url), None);
+        for (path, content) in [("a.md", "Project plan and project review"), ("b.md", "Project meeting about flour prices"), ("c.md", "Garden project")] {
+            adapter.update_note_index(path, content);
+        }
+        // `auto` without an LLM client uses the heuristics
+        assert!(adapter.uses_heuristic_analysis());
+        let recommendations = adapter.generate_organization_recommendations("Inbox/starter.md").await.unwrap();
+
+        let file_data = ObsidianAdapter::parse_markdown_file(note).unwrap();
+        let analysis = heuristic_analysis(&file_data, adapter.term_index(), &adapter.analysis_config);
+        assert_eq!(analysis.themes, vec!["sourdough starter"]);
+        let plumbed = adapter.generate_tag_suggestions(&analysis, &file_data).await.unwrap();
+        assert_eq!(
+            recommendations.suggested_tags.iter().map(|t| (t.tag.clone(), t.confidence)).collect::<Vec<_>>(),
+            plumbed.iter().map(|t| (t.tag.clone(), t.confidence * HEURISTIC_CONFIDENCE)).collect::<Vec<_>>(),
+        );
+        let tags: Vec<&str> = recommendations.suggested_tags.iter().map(|t| t.tag.as_str()).collect();
+        assert_eq!(tags[..2], ["recipe", "sourdough_starter"]);
+        assert!(tags.contains(&"rye") && !tags.contains(&"baking"));
+        assert_eq!(recommendations.folder_suggestions[0].folder_path, "recipe");
+        assert_eq!(recommendations.folder_suggestions[0].confidence, 0.8 * HEURISTIC_CONFIDENCE);
+        // The note's terms went into the index too
+        assert!(adapter.term_index().contains("Inbox/starter.md"));
+
+        adapter.set_organization_config(OrganizationConfig { analysis_backend: AnalysisBackend::Ai, ..OrganizationConfig::default() });
+        assert!(adapter.generate_organization_recommendations("Inbox/starter.md").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tag_only_organizing_patches_frontmatter() {
+        let note = "---\ntags:\n- launch\n---\n\nRollout notes.\n";
+        let (base_url, vault, log) = serve_vault(HashMap::from([("Inbox/launch.md".to_string(), note.to_string())])).await;
+        let mut adapter = ObsidianAdapter::new(Some(base_url), None);
+        adapter.set_organization_config(OrganizationConfig { auto_apply_tags: true, ..OrganizationConfig::default() });
+        let recommendations = OrganizationRecommendations {
+            suggested_tags: vec![TagSuggestion { tag: "project".to_string(), confidence: 0.9, reason: String::new(), source: TagSource::Category }],
+            folder_suggestions: Vec::new(),
+            link_suggestions: Vec::new(),
+            overall_confidence: 0.9,
+            generated_at: Utc::now(),
+        };
+
+        let history = std::env::temp_dir().join(format!("arrowhead-history-{}", uuid::Uuid::new_v4()));
+        adapter.set_change_journal(ChangeJournal::new(&history, 1024 * 1024));
+        adapter.apply_organization_recommendations("Inbox/launch.md", &recommendations, true).await.unwrap();
+        let summary = adapter.apply_organization_recommendations("Inbox/launch.md", &recommendations, false).await.unwrap();
+        assert_eq!(summary.applied, vec![OrganizationAction::AddTag("project".to_string())]);
+        // Only the real run is journaled, with the note as it was before the patch
+        let journaled = ChangeJournal::new(&history, 1024 * 1024).entries().unwrap();
+        assert_eq!(journaled.len(), 1);
+        assert_eq!((journaled[0].path.as_str(), journaled[0].feature.as_str(), journaled[0].content.as_str()), ("Inbox/launch.md", "organize", note));
+        std::fs::remove_dir_all(&history).ok();
+        let log = log.lock().unwrap();
+        assert!(!log.iter().any(|request| request.starts_with("PUT ")), "{:?}", log);
+        assert!(log.contains(&r#"PATCH Inbox/launch.md replace frontmatter tags ["launch","project"]"#.to_string()), "{:?}", log);
+        assert_eq!(vault.lock().unwrap()["Inbox/launch.md"], note);
+    }
+
+    #[tokio::test]
+    async fn test_patch_appends_under_a_heading() {
+        let note = "# Log\n- first\n# Later\n";
+        let (base_url, vault, log) = serve_vault(HashMap::from([("Daily/today.md".to_string(), note.to_string())])).await;
+        let adapter = ObsidianAdapter::new(Some(base_url), None);
+
+        adapter.patch_file("Daily/today.md", &PatchTarget::Heading("Log".to_string()), "- second\n", PatchOperation::Append).await.unwrap();
+        assert_eq!(vault.lock().unwrap()["Daily/today.md"], "# Log\n- first\n- second\n# Later\n");
+        assert_eq!(log.lock().unwrap().last().unwrap(), "PATCH Daily/today.md append heading Log - second\n");
+
+        let missing = adapter.patch_file("Daily/today.md", &PatchTarget::Heading("Nope".to_string()), "x", PatchOperation::Append).await;
+        assert!(missing.unwrap_err().to_string().contains("heading 'Nope'"));
+    }
+
+    #[tokio::test]
+    async fn test_full_write_refuses_a_note_changed_since_it_was_read() {
+        let (base_url, vault, _log) = serve_vault(HashMap::from([("notes/plan.md".to_string(), "v1".to_string())])).await;
+        let adapter = ObsidianAdapter::new(Some(base_url), None);
+
+        assert_eq!(adapter.get_file("notes/plan.md").await.unwrap(), "v1");
+        vault.lock().unwrap().insert("notes/plan.md".to_string(), "edited in Obsidian".to_string());
+        let error = adapter.update_file("notes/plan.md", "v2").await.unwrap_err();
+        assert_eq!(error.downcast_ref::<RemoteChanged>(), Some(&RemoteChanged { path: "notes/plan.md".to_string() }));
+        assert!(with_force_hint(error).to_string().contains("--force"));
+        assert_eq!(vault.lock().unwrap()["notes/plan.md"], "edited in Obsidian");
+
+        adapter.overwrite_file("notes/plan.md", "v2").await.unwrap();
+        assert_eq!(vault.lock().unwrap()["notes/plan.md"], "v2");
+        // The forced write is now the known version, so a plain update goes through
+        adapter.update_file("notes/plan.md", "v3").await.unwrap();
+        assert_eq!(vault.lock().unwrap()["notes/plan.md"], "v3");
+    }
+
+    #[test]
+    fn test_inline_tags_skip_code_headings_and_links() {
+        let body = "# Heading\n#todo and #project/alpha, not issue#4 or #2024\n`#code` [[Note#Section]] https://x.io/#frag\n```\n#fenced\n```\n## Done #later\n";
+        let tags: Vec<&str> = inline_tag_spans(body).into_iter().map(|(start, end)| &body[start..end]).collect();
+        assert_eq!(tags, vec!["todo", "project/alpha", "later"]);
+    }
+
+    #[test]
+    fn test_rename_tags_in_note_keeps_other_fields_and_nested_tags() {
+        let from = vec!["Project".to_string()];
+        let note = "---\naliases: [Launch]   # keep me\ntags:\n- project/alpha\n- work\n- project\ncssclass: wide\n---\nSee #project and #projects, `#project`.\n";
+        let renamed = rename_tags_in_note(note, &from, "work").unwrap().unwrap();
+        assert_eq!(renamed, "---\naliases: [Launch]   # keep me\ntags:\n- work/alpha\n- work\ncssclass: wide\n---\nSee #work and #projects, `#project`.\n");
+
+        // Inline only, with no frontmatter to rewrite
+        assert_eq!(rename_tags_in_note("Ship it #project\n", &from, "work").unwrap().unwrap(), "Ship it #work\n");
+        // Mentioned only in code, so nothing changes
+        assert_eq!(rename_tags_in_note("```\n#project\n```\n", &from, "work").unwrap(), None);
+        assert_eq!(rename_tags_in_note("---\ntags: [project\n---\nbody\n", &from, "work"), Err("frontmatter doesn't parse"));
+    }
+
+    #[tokio::test]
+    async fn test_tag_index_and_rename_across_the_vault() {
+        let (base_url, vault, _log) = serve_vault(HashMap::from([
+            ("Inbox.md".to_string(), "Capture #idea\n".to_string()),
+            ("Notes/a.md".to_string(), "---\ntags:\n- idea\n- Draft\n---\nAn #idea\n".to_string()),
+            ("Notes/deep/b.md".to_string(), "---\ntags: [draft]\n---\nNothing inline\n".to_string()),
+            ("Notes/image.png".to_string(), "#idea".to_string()),
+        ])).await;
+        let mut adapter = ObsidianAdapter::new(Some(base_url), None);
+        let log = std::env::temp_dir().join(format!("arrowhead-tx-{}.jsonl", uuid::Uuid::new_v4()));
+        adapter.set_transaction_log(&log);
+
+        let index = adapter.collect_tag_index("").await.unwrap();
+        assert_eq!(index.notes_scanned, 3);
+        assert_eq!(index.tags, BTreeMap::from([("draft".to_string(), 2), ("idea".to_string(), 2)]));
+
+        let from = vec!["draft".to_string(), "#idea".to_string()];
+        let preview = adapter.rename_tag(&from, "seed", true).await.unwrap();
+        let touched: Vec<&str> = preview.edits.iter().map(|edit| edit.path.as_str()).collect();
+        assert_eq!(touched, vec!["Inbox.md", "Notes/a.md", "Notes/deep/b.md"]);
+        assert_eq!(vault.lock().unwrap()["Inbox.md"], "Capture #idea\n");
+
+        let renamed = adapter.rename_tag(&from, "seed", false).await.unwrap();
+        assert!(renamed.report.unwrap().is_committed());
+        {
+            let vault = vault.lock().unwrap();
+            assert_eq!(vault["Inbox.md"], "Capture #seed\n");
+            assert_eq!(vault["Notes/a.md"], "---\ntags:\n- seed\n---\nAn #seed\n");
+            assert_eq!(vault["Notes/deep/b.md"], "---\ntags:\n- seed\n---\nNothing inline\n");
+            assert_eq!(vault["Notes/image.png"], "#idea");
+        }
+        assert_eq!(crate::vault_transaction::read_transaction_log(&log).unwrap().len(), 1);
+        std::fs::remove_file(&log).ok();
+        assert!(adapter.rename_tag(&from, "2024", true).await.is_err());
+    }
+
+    fn auto_link_targets(entries: &[(&str, &str, &str)]) -> HashMap<String, AutoLinkTarget> {
+        entries.iter().map(|(word, path, title)| {
+            (word.to_string(), AutoLinkTarget { path: path.to_string(), title: title.to_string(), confidence: 0.9 })
+        }).collect()
+    }
+
+    fn assert_positions_match(result: &AutoLinkResult) {
+        let lines: Vec<&str> = result.linked_text.lines().collect();
+        for link in &result.inserted_links {
+            let position = &link.position;
+            let text = &lines[position.line][position.column..position.column + position.length.unwrap()];
+            assert!(text.starts_with("[[") && text.ends_with(&format!("{}]]", link.display_text)), "{:?} at {:?}", text, position);
+        }
+    }
+
+    #[test]
+    fn test_auto_links_skip_code_frontmatter_and_existing_links() {
+        let content = "---\ntitle: tokio notes\n---\n# Async Rust\n\n```rust\nlet rt = tokio::runtime::Runtime::new();\n```\nRun `tokio` with [Tokio docs](https://tokio.rs) or https://docs.rs/tokio.\nRust and tokio work well. More tokio, see [[Rust]].\n";
+        let targets = auto_link_targets(&[("rust", "Languages/Rust.md", "Rust"), ("tokio", "Crates/Tokio Runtime.md", "Tokio Runtime")]);
+
+        let result = apply_auto_links(content, &targets, true);
+        // Rust is linked already, and only the first plain mention of tokio is linked
+        assert_eq!(result.linked_text, content.replace("Rust and tokio work", "Rust and [[Tokio Runtime|tokio]] work"));
+        assert_eq!(result.links_added, 1);
+        let link = &result.inserted_links[0];
+        assert_eq!((link.target.as_str(), link.position.line, link.position.column), ("Crates/Tokio Runtime.md", 9, 9));
+        assert_positions_match(&result);
+    }
+
+    #[test]
+    fn test_auto_links_leave_the_shared_fixture_protected() {
+        let targets = auto_link_targets(&[("roadmap", "Plans/Roadmap.md", "Roadmap")]);
+        let result = apply_auto_links(crate::markdown::FIXTURE, &targets, false);
+        // The heading and the three plain mentions; nothing in frontmatter, code or links
+        assert_eq!(result.links_added, 4);
+        for kept in ["title: Roadmap\n", "[the Roadmap](Roadmap.md)", "`Roadmap`", "https://example.com/Roadmap_page", "- [ ] Roadmap task in a listed fence", "- [ ] Roadmap task in a nested fence"] {
+            assert!(result.linked_text.contains(kept), "{} was changed", kept);
+        }
+        assert!(result.linked_text.contains("- [ ] Review the [[Roadmap]] #q3"));
+        assert_positions_match(&result);
+    }
+
+    #[test]
+    fn test_auto_links_leave_tags_alone() {
+        let content = "Planning for Q3 #planning\n#planning/q3 notes on planning.\n";
+        let targets = auto_link_targets(&[("planning", "Areas/Planning.md", "Planning")]);
+        let result = apply_auto_links(content, &targets, false);
+        assert_eq!(result.linked_text, "[[Planning]] for Q3 #planning\n#planning/q3 notes on [[Planning|planning]].\n");
+        assert_positions_match(&result);
+    }
+
+    #[test]
+    fn test_auto_links_can_link_every_mention() {
+        let content = "Rust is fast.\nI like rust and Rust, not rusty.";
+        let targets = auto_link_targets(&[("rust", "Languages/Rust.md", "Rust")]);
+
+        let first_only = apply_auto_links(content, &targets, true);
+        assert_eq!(first_only.linked_text, "[[Rust]] is fast.\nI like rust and Rust, not rusty.");
+
+        let every = apply_auto_links(content, &targets, false);
+        assert_eq!(every.linked_text, "[[Rust]] is fast.\nI like [[Rust|rust]] and [[Rust]], not rusty.");
+        let positions: Vec<(usize, usize)> = every.inserted_links.iter().map(|l| (l.position.line, l.position.column)).collect();
+        assert_eq!(positions, vec![(0, 0), (1, 7), (1, 25)]);
+        assert_positions_match(&every);
+    }
+
+    fn note_template(id: &str, name: &str, category: &str, tags: &[&str]) -> NoteTemplate {
+        let created = DateTime::parse_from_rfc3339("2024-05-01T09:30:00Z").unwrap().with_timezone(&Utc);
+        NoteTemplate {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: format!("{} notes", name),
+            category: category.to_string(),
+            components: vec![
+                TemplateComponent::Text("# {{title}}".to_string()),
+                TemplateComponent::Placeholder { name: "attendees".to_string(), hint: "Who came".to_string(), required: true },
+                TemplateComponent::Conditional {
+                    condition: "has_actions".to_string(),
+                    content: vec![TemplateComponent::Link { target: "Actions".to_string(), display_text: None }],
+                },
+            ],
+            frontmatter_fields: vec![FrontmatterField {
+                name: "date".to_string(),
+                field_type: "date".to_string(),
+                default_value: None,
+                required: true,
+                description: Some("Meeting day".to_string()),
+            }],
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            usage_stats: TemplateUsageStats {
+                usage_count: 7,
+                last_used: Some(created + chrono::Duration::days(3)),
+                satisfaction_rating: Some(4.5),
+                customization_count: 2,
+            },
+            created_at: created,
+            modified_at: created + chrono::Duration::hours(5),
+        }
+    }
+
+    fn template_adapter(templates: Vec<NoteTemplate>) -> ObsidianAdapter {
+        let mut adapter = ObsidianAdapter::new(None, None);
+        adapter.template_cache_path = std::env::temp_dir().join(format!("arrowhead-templates-{}.bin", uuid::Uuid::new_v4())).to_string_lossy().to_string();
+        adapter.template_database.templates = templates;
+        adapter.rebuild_template_indexes();
+        adapter
+    }
+
+    #[test]
+    fn test_templates_are_suggested_for_content_that_fits() {
+        let mut meeting = note_template("t1", "Meeting", "meeting", &["meeting"]);
+        meeting.components = vec![
+            TemplateComponent::Text("# {{title}}\n## Attendees".to_string()),
+            TemplateComponent::Placeholder { name: "attendees".to_string(), hint: "Who came".to_string(), required: true },
+            TemplateComponent::Placeholder { name: "date".to_string(), hint: "When".to_string(), required: false },
+            TemplateComponent::Text("## Agenda\n## Action Items".to_string()),
+        ];
+        let adapter = template_adapter(vec![note_template("t2", "Project brief", "project", &["planning"]), meeting]);
+        let content = "# Sprint sync\nAttendees: Ana, Ravi\n- **Date:** 2024-05-02\n\n## Agenda\n- release\n\n## Action Items\n- [ ] Ana ships it\n\n#meeting\n";
+
+        let ranked = adapter.suggest_template_for_content(content).unwrap();
+        let names: Vec<&str> = ranked.iter().map(|(t, _)| t.name.as_str()).collect();
+        assert_eq!(names, vec!["Meeting", "Project brief"]);
+        assert!(ranked[0].1 > 0.8 && ranked[1].1 < 0.5, "{:?}", ranked.iter().map(|(_, c)| c).collect::<Vec<_>>());
+        assert!(adapter.suggest_template_for_content("Bread needs flour.").unwrap().iter().all(|(_, c)| *c < 0.5));
+
+        let values = extracted_template_values(&ranked[0].0, content).unwrap();
+        assert_eq!(values.get("attendees").map(String::as_str), Some("Ana, Ravi"));
+        assert_eq!(values.get("date").map(String::as_str), Some("2024-05-02"));
+        assert!(!template_takes_body(&ranked[0].0));
+        assert!(template_headings(&ranked[0].0).iter().all(|h| ["attendees", "agenda", "action items"].contains(&h.as_str())));
+    }
+
+    #[test]
+    fn test_template_export_round_trips_in_json_and_yaml() {
+        let original = vec![
+            note_template("t1", "Standup", "meeting", &["daily", "team"]),
+            note_template("t2", "Project brief", "project", &["planning"]),
+            note_template("t3", "Retro", "meeting", &["team"]),
+        ];
+        let source = template_adapter(original.clone());
+
+        for extension in ["json", "yaml"] {
+            let path = std::env::temp_dir().join(format!("arrowhead-export-{}.{}", uuid::Uuid::new_v4(), extension));
+            let format = TemplateFormat::from_path(&path);
+            assert_eq!(source.export_templates(&path, format).unwrap(), 3);
+
+            let mut target = template_adapter(Vec::new());
+            let summary = target.import_templates(&path, format, TemplateMergeStrategy::Skip).unwrap();
+            assert_eq!(summary.added, 3);
+            assert_eq!(
+                serde_json::to_value(&target.template_database.templates).unwrap(),
+                serde_json::to_value(&original).unwrap(),
+            );
+            assert_eq!(target.get_templates_by_category("meeting").len(), 2);
+            assert_eq!(target.get_templates_by_tag("team").len(), 2);
+            let _ = fs::remove_file(&path);
+            let _ = fs::remove_file(&target.template_cache_path);
+        }
+    }
+
+    #[test]
+    fn test_template_import_resolves_conflicts() {
+        let path = std::env::temp_dir().join(format!("arrowhead-export-{}.json", uuid::Uuid::new_v4()));
+        let mut incoming = note_template("t1", "Standup", "rituals", &["team"]);
+        incoming.usage_stats.usage_count = 99;
+        let renamed = note_template("other-id", "Retro", "meeting", &[]);
+        template_adapter(vec![incoming, renamed, note_template("t9", "Weekly", "review", &[])])
+            .export_templates(&path, TemplateFormat::Json).unwrap();
+        let existing = || vec![note_template("t1", "Standup", "meeting", &["daily"]), note_template("t3", "Retro", "meeting", &["team"])];
+
+        let mut skip = template_adapter(existing());
+        let summary = skip.import_templates(&path, TemplateFormat::Json, TemplateMergeStrategy::Skip).unwrap();
+        assert_eq!((summary.added, summary.skipped), (1, 2));
+        assert_eq!(skip.get_template_by_id("t1").unwrap().usage_stats.usage_count, 7);
+
+        let mut overwrite = template_adapter(existing());
+        let summary = overwrite.import_templates(&path, TemplateFormat::Json, TemplateMergeStrategy::Overwrite).unwrap();
+        assert_eq!((summary.added, summary.overwritten), (1, 2));
+        assert_eq!(overwrite.get_template_by_id("t1").unwrap().usage_stats.usage_count, 99);
+        // Matched by name, so the old Retro is replaced rather than kept next to the new one
+        assert!(overwrite.get_template_by_id("t3").is_none());
+        assert_eq!(overwrite.get_templates_by_category("rituals").len(), 1);
+        assert_eq!(overwrite.get_templates_by_tag("daily").len(), 0);
+
+        let mut duplicate = template_adapter(existing());
+        let summary = duplicate.import_templates(&path, TemplateFormat::Json, TemplateMergeStrategy::Duplicate).unwrap();
+        assert_eq!((summary.added, summary.duplicated), (1, 2));
+        let names: Vec<&str> = duplicate.get_all_templates().iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["Standup", "Retro", "Standup (2)", "Retro (2)", "Weekly"]);
+        assert_ne!(duplicate.template_database.templates[2].id, "t1");
+        assert_eq!(duplicate.get_templates_by_tag("team").len(), 2);
+
+        for adapter in [skip, overwrite, duplicate] {
+            let _ = fs::remove_file(&adapter.template_cache_path);
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_dominant_label_ignores_case_and_plurals() {
+        let labels = ["Meeting", "meetings", "Meeting", "Project", "projects"];
+        assert_eq!(dominant_label(labels.into_iter()), Some("Meeting".to_string()));
+        assert_eq!(dominant_label(["status", "Status"].into_iter()), Some("Status".to_string()));
+        assert_eq!(dominant_label(std::iter::empty()), None);
+    }
+
+    #[tokio::test]
+    async fn test_template_renders_nested_components_and_ai_fallbacks() {
+        let mut template = note_template("t1", "Standup", "meeting", &[]);
+        template.components.push(TemplateComponent::Conditional {
+            condition: "has(attendees)".to_string(),
+            content: vec![
+                TemplateComponent::Placeholder { name: "attendees".to_string(), hint: "Again".to_string(), required: false },
+                TemplateComponent::AiSuggestion { prompt: "Suggest topics".to_string(), fallback: "- Blockers".to_string() },
+            ],
+        });
+        let placeholders = template_placeholders(&template);
+        assert_eq!(placeholders, vec![TemplatePlaceholder { name: "attendees".to_string(), hint: "Who came".to_string(), required: true }]);
+
+        let adapter = template_adapter(vec![template.clone()]);
+        assert_eq!(adapter.find_template("STANDUP").unwrap().id, "t1");
+        // Without an LLM client every suggestion falls back
+        let values = TemplateValues::from(HashMap::from([("attendees".to_string(), "Ana".to_string())]));
+        let suggestions = adapter.resolve_ai_suggestions(&template, &values).await;
+        assert!(suggestions.is_empty());
+        let rendered = adapter.render_template_with_suggestions(&template, &values, &suggestions).unwrap();
+        assert!(rendered.ends_with("# {{title}}\nAna\nAna\n- Blockers\n"));
+
+        let suggestions = HashMap::from([("Suggest topics".to_string(), "- Launch".to_string())]);
+        let rendered = adapter.render_template_with_suggestions(&template, &values, &suggestions).unwrap();
+        assert!(rendered.ends_with("Ana\n- Launch\n"));
+    }
+
+    fn section_template(components: Vec<TemplateComponent>) -> NoteTemplate {
+        let mut template = note_template("t1", "Review", "project", &[]);
+        template.frontmatter_fields.clear();
+        template.components = components;
+        template
+    }
+
+    #[test]
+    fn test_false_condition_omits_its_section() {
+        let template = section_template(vec![
+            TemplateComponent::Text("# Review".to_string()),
+            TemplateComponent::Conditional {
+                condition: "status == \"blocked\" && !has(resolved)".to_string(),
+                content: vec![
+                    TemplateComponent::Text("## Blockers".to_string()),
+                    TemplateComponent::Placeholder { name: "blocker".to_string(), hint: "What is in the way".to_string(), required: false },
+                    TemplateComponent::Tag("blocked".to_string()),
+                ],
+            },
+            TemplateComponent::Text("## Next".to_string()),
+        ]);
+        let adapter = template_adapter(Vec::new());
+        let mut values = TemplateValues::new();
+        values.set("status", "on track");
+        values.set("blocker", "Vendor contract");
+        assert_eq!(adapter.render_template(&template, &values).unwrap(), "---\n---\n\n# Review\n## Next\n");
+
+        values.set("status", "blocked");
+        assert_eq!(
+            adapter.render_template(&template, &values).unwrap(),
+            "---\n---\n\n# Review\n## Blockers\nVendor contract\n#blocked\n## Next\n",
+        );
+        // Prose conditions from discovered templates still render their section
+        let mut prose = template.clone();
+        if let TemplateComponent::Conditional { condition, .. } = &mut prose.components[1] {
+            *condition = "if the project is blocked".to_string();
+        }
+        values.set("status", "on track");
+        assert!(adapter.render_template(&prose, &values).unwrap().contains("## Blockers"));
+    }
+
+    #[test]
+    fn test_repeating_section_renders_once_per_item() {
+        let template = section_template(vec![
+            TemplateComponent::Repeating {
+                item_name: "topic".to_string(),
+                content: vec![
+                    TemplateComponent::Text("###".to_string()),
+                    TemplateComponent::Placeholder { name: "topic.title".to_string(), hint: "Topic".to_string(), required: true },
+                    TemplateComponent::Link { target: "People/owner".to_string(), display_text: None },
+                    TemplateComponent::Conditional {
+                        condition: "topic.urgent".to_string(),
+                        content: vec![TemplateComponent::Tag("urgent".to_string())],
+                    },
+                ],
+            },
+        ]);
+        assert!(template_placeholders(&template).is_empty());
+
+        let adapter = template_adapter(Vec::new());
+        let values = TemplateValues::from(HashMap::from([("topic".to_string(), serde_json::json!([
+            { "title": "Launch", "urgent": true },
+            { "title": "Hiring" },
+            { "title": "Budget", "urgent": false },
+        ]))]));
+        let rendered = adapter.render_template(&template, &values).unwrap();
+        assert_eq!(
+            rendered,
+            "---\n---\n\n###\nLaunch\n[[People/owner|People/owner]]\n#urgent\n###\nHiring\n[[People/owner|People/owner]]\n###\nBudget\n[[People/owner|People/owner]]\n",
+        );
+        assert_eq!(rendered.matches("###").count(), 3);
+        assert_eq!(adapter.render_template(&template, &TemplateValues::new()).unwrap(), "---\n---\n\n");
+    }
 }
@@ -0,0 +1,406 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::ai_conversation::{FunctionCall, FunctionSchema, LLMClient, Message, MessageRole};
+use crate::error::ArrowheadError;
+
+/// Ollama client configuration; a local server needs no API key
+#[derive(Debug, Clone)]
+pub struct OllamaConfig {
+    pub base_url: String,
+    pub model: String,
+    pub temperature: Option<f32>,
+    /// Sent as `num_predict`
+    pub max_tokens: Option<u32>,
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:11434".to_string(),
+            model: "llama3.1".to_string(),
+            temperature: Some(0.7),
+            max_tokens: None,
+        }
+    }
+}
+
+/// Client for a local Ollama server's `/api/chat` and `/api/embeddings`
+#[derive(Debug, Clone)]
+pub struct OllamaClient {
+    config: OllamaConfig,
+    client: Client,
+}
+
+impl OllamaClient {
+    pub fn new(config: OllamaConfig) -> Self {
+        Self { config, client: Client::new() }
+    }
+
+    /// Send requests through `client`, e.g. one built for the `[network.llm]` proxy
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/{}", self.config.base_url.trim_end_matches('/'), path)
+    }
+
+    fn build_request(&self, messages: Vec<Message>, tools: Vec<OllamaTool>, stream: bool) -> OllamaChatRequest {
+        OllamaChatRequest {
+            model: self.config.model.clone(),
+            messages: messages.into_iter().map(|msg| OllamaMessage {
+                role: match msg.role {
+                    MessageRole::User => "user".to_string(),
+                    MessageRole::Assistant => "assistant".to_string(),
+                    MessageRole::System => "system".to_string(),
+                    MessageRole::Function => "tool".to_string(),
+                },
+                content: msg.content,
+                tool_calls: msg.function_call.map(|call| vec![OllamaToolCall {
+                    function: OllamaFunctionCall {
+                        name: call.name,
+                        arguments: serde_json::to_value(call.arguments).unwrap_or_default(),
+                    },
+                }]),
+            }).collect(),
+            stream,
+            tools,
+            options: OllamaOptions {
+                temperature: self.config.temperature,
+                num_predict: self.config.max_tokens,
+            },
+        }
+    }
+
+    async fn post<T: Serialize>(&self, path: &str, body: &T) -> Result<reqwest::Response> {
+        let url = self.url(path);
+        let response = self.client
+            .post(&url)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| ArrowheadError::ServiceUnavailable {
+                provider: "ollama".to_string(),
+                status: None,
+                detail: format!("could not reach Ollama at {} ({}); is `ollama serve` running?", url, e),
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await?;
+            let message = serde_json::from_str::<OllamaError>(&error_text).map(|e| e.error).unwrap_or(error_text);
+            return Err(ArrowheadError::from_status("ollama", status.as_u16(), None, message).into());
+        }
+        Ok(response)
+    }
+
+    async fn chat(&self, request: OllamaChatRequest) -> Result<Message> {
+        let body = self.post("chat", &request).await?.text().await?;
+        let reply = parse_chat_body(&body)?;
+        Ok(Message {
+            id: Uuid::new_v4().to_string(),
+            role: MessageRole::Assistant,
+            content: reply.content,
+            timestamp: Utc::now(),
+            function_call: reply.tool_calls.and_then(|calls| calls.into_iter().next()).map(|call| FunctionCall {
+                name: call.function.name,
+                arguments: serde_json::from_value(call.function.arguments).unwrap_or_default(),
+            }),
+        })
+    }
+
+    /// Embed `text` with an embedding model pulled into Ollama, such as `nomic-embed-text`
+    pub async fn embed(&self, model: &str, text: &str) -> Result<Vec<f32>> {
+        let request = OllamaEmbeddingRequest { model: model.to_string(), prompt: text.to_string() };
+        let response: OllamaEmbeddingResponse = self.post("embeddings", &request).await?.json().await?;
+        if response.embedding.is_empty() {
+            return Err(anyhow::anyhow!("Ollama returned no embedding; is '{}' an embedding model?", model));
+        }
+        Ok(response.embedding)
+    }
+}
+
+/// The reply in a `/api/chat` body. Ollama streams unless told not to, so the body may be
+/// NDJSON chunks, whose contents are joined, rather than one object.
+fn parse_chat_body(body: &str) -> Result<OllamaMessage> {
+    let mut reply: Option<OllamaMessage> = None;
+    for line in body.lines().filter(|line| !line.trim().is_empty()) {
+        let chunk: OllamaChatChunk = serde_json::from_str(line)
+            .map_err(|e| anyhow::anyhow!("Unexpected response from Ollama: {} ({})", line, e))?;
+        if let Some(error) = chunk.error {
+            return Err(anyhow::anyhow!("Ollama error: {}", error));
+        }
+        let Some(message) = chunk.message else { continue };
+        match &mut reply {
+            Some(reply) => {
+                reply.content.push_str(&message.content);
+                if message.tool_calls.is_some() {
+                    reply.tool_calls = message.tool_calls;
+                }
+            }
+            None => reply = Some(message),
+        }
+    }
+    reply.ok_or_else(|| anyhow::anyhow!("No response from Ollama"))
+}
+
+#[async_trait]
+impl LLMClient for OllamaClient {
+    async fn send_message(&self, messages: Vec<Message>) -> Result<Message> {
+        self.chat(self.build_request(messages, Vec::new(), false)).await
+    }
+
+    async fn stream_response(&self, messages: Vec<Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+        let (tx, rx) = tokio::sync::mpsc::channel(100);
+        let response = self.post("chat", &self.build_request(messages, Vec::new(), true)).await?;
+
+        tokio::spawn(async move {
+            use futures::StreamExt;
+
+            let mut stream = response.bytes_stream();
+            // One JSON object per line; a chunk may end part-way through a line
+            let mut pending = String::new();
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        pending.push_str(&String::from_utf8_lossy(&bytes));
+                        while let Some(end) = pending.find('\n') {
+                            let line: String = pending.drain(..=end).collect();
+                            let Ok(chunk) = serde_json::from_str::<OllamaChatChunk>(line.trim()) else { continue };
+                            if let Some(error) = chunk.error {
+                                let _ = tx.send(format!("Stream error: {}", error)).await;
+                                return;
+                            }
+                            if let Some(message) = chunk.message.filter(|m| !m.content.is_empty()) {
+                                let _ = tx.send(message.content).await;
+                            }
+                            if chunk.done {
+                                return;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.send(format!("Stream error: {}", e)).await;
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+
+    async fn function_calling(&self, messages: Vec<Message>, functions: Vec<FunctionSchema>) -> Result<Message> {
+        let tools = functions.into_iter().map(|f| OllamaTool {
+            kind: "function".to_string(),
+            function: OllamaFunction { name: f.name, description: f.description, parameters: f.parameters },
+        }).collect();
+        self.chat(self.build_request(messages, tools, false)).await
+    }
+
+    fn get_model_name(&self) -> String {
+        self.config.model.clone()
+    }
+}
+
+// Ollama API request/response structures
+#[derive(Debug, Serialize)]
+struct OllamaChatRequest {
+    model: String,
+    messages: Vec<OllamaMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<OllamaTool>,
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OllamaMessage {
+    role: String,
+    #[serde(default)]
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<OllamaToolCall>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OllamaToolCall {
+    function: OllamaFunctionCall,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OllamaFunctionCall {
+    name: String,
+    arguments: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaTool {
+    #[serde(rename = "type")]
+    kind: String,
+    function: OllamaFunction,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaFunction {
+    name: String,
+    description: String,
+    parameters: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaChatChunk {
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaEmbeddingRequest {
+    model: String,
+    prompt: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaEmbeddingResponse {
+    #[serde(default)]
+    embedding: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaError {
+    error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// An Ollama stand-in answering every request with `status` and `body`, recording each
+    /// request line and JSON body
+    async fn serve(status: &'static str, body: &'static str) -> (String, Arc<Mutex<Vec<(String, serde_json::Value)>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buffer = vec![0; 4096];
+                let (line, request_body) = loop {
+                    let read = socket.read(&mut buffer).await.unwrap_or(0);
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                        if read == 0 { break (text, String::new()) } else { continue }
+                    };
+                    let length = head.lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                        .unwrap_or(0);
+                    if body.len() >= length || read == 0 {
+                        break (head.lines().next().unwrap_or_default().to_string(), body.to_string());
+                    }
+                };
+                log.lock().unwrap().push((line, serde_json::from_str(&request_body).unwrap_or_default()));
+                let response = format!("HTTP/1.1 {}\r\ncontent-type: application/x-ndjson\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}", status, body.len(), body);
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (address, seen)
+    }
+
+    fn client(base_url: String) -> OllamaClient {
+        OllamaClient::new(OllamaConfig { base_url, model: "llama3.1".to_string(), temperature: Some(0.3), max_tokens: Some(256) })
+    }
+
+    fn message(role: MessageRole, content: &str) -> Message {
+        Message { id: String::new(), role, content: content.to_string(), timestamp: Utc::now(), function_call: None }
+    }
+
+    #[tokio::test]
+    async fn test_chat_request_shape_and_reply() {
+        let (server, seen) = serve("200 OK", r#"{"model":"llama3.1","message":{"role":"assistant","content":"Hi!"},"done":true}"#).await;
+
+        let reply = client(format!("{}/", server)).send_message(vec![
+            message(MessageRole::System, "Be brief."),
+            message(MessageRole::User, "Hello"),
+        ]).await.unwrap();
+        assert_eq!(reply.content, "Hi!");
+
+        let seen = seen.lock().unwrap();
+        let (line, body) = &seen[0];
+        assert_eq!(line, "POST /api/chat HTTP/1.1");
+        assert_eq!(body["model"], "llama3.1");
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["options"], serde_json::json!({ "temperature": 0.3, "num_predict": 256 }));
+        assert_eq!(body["messages"], serde_json::json!([
+            { "role": "system", "content": "Be brief." },
+            { "role": "user", "content": "Hello" },
+        ]));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_ndjson_is_joined_or_forwarded() {
+        const CHUNKS: &str = "{\"message\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"done\":false}\n\
+                              {\"message\":{\"role\":\"assistant\",\"content\":\"lo\"},\"done\":false}\n\
+                              {\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"eval_count\":2}\n";
+        // A server that streams even when asked not to still gives one reply
+        let (server, _) = serve("200 OK", CHUNKS).await;
+        assert_eq!(client(server).send_message(vec![message(MessageRole::User, "Hi")]).await.unwrap().content, "Hello");
+
+        let (server, seen) = serve("200 OK", CHUNKS).await;
+        let mut rx = client(server).stream_response(vec![message(MessageRole::User, "Hi")]).await.unwrap();
+        let mut parts = Vec::new();
+        while let Some(part) = rx.recv().await {
+            parts.push(part);
+        }
+        assert_eq!(parts, vec!["Hel", "lo"]);
+        assert_eq!(seen.lock().unwrap()[0].1["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_and_embeddings() {
+        let (server, seen) = serve("200 OK", r#"{"message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"list_todos","arguments":{"status":"open"}}}]},"done":true}"#).await;
+        let tool = FunctionSchema {
+            name: "list_todos".to_string(),
+            description: "List todos".to_string(),
+            parameters: serde_json::json!({ "type": "object" }),
+        };
+        let reply = client(server).function_calling(vec![message(MessageRole::User, "What's open?")], vec![tool]).await.unwrap();
+        let call = reply.function_call.unwrap();
+        assert_eq!((call.name.as_str(), &call.arguments["status"]), ("list_todos", &serde_json::json!("open")));
+        assert_eq!(seen.lock().unwrap()[0].1["tools"][0]["type"], "function");
+
+        let (server, seen) = serve("200 OK", r#"{"embedding":[0.5,-0.25,1.0]}"#).await;
+        assert_eq!(client(server).embed("nomic-embed-text", "rust notes").await.unwrap(), vec![0.5, -0.25, 1.0]);
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[0].0, "POST /api/embeddings HTTP/1.1");
+        assert_eq!(seen[0].1, serde_json::json!({ "model": "nomic-embed-text", "prompt": "rust notes" }));
+    }
+
+    #[tokio::test]
+    async fn test_missing_model_is_reported() {
+        let (server, _) = serve("404 Not Found", r#"{"error":"model \"llama3.1\" not found, try pulling it first"}"#).await;
+        let error = client(server).send_message(vec![message(MessageRole::User, "Hi")]).await.unwrap_err();
+        assert_eq!(ArrowheadError::status_of(&error), Some(404));
+        assert!(error.to_string().contains("try pulling it first"));
+    }
+}
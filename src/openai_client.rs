@@ -7,6 +7,7 @@ use std::env;
 use uuid::Uuid;
 
 use crate::ai_conversation::{FunctionCall, FunctionSchema, LLMClient, Message, MessageRole};
+use crate::error::ArrowheadError;
 
 /// OpenAI API client configuration
 #[derive(Debug, Clone)]
@@ -52,6 +53,12 @@ impl OpenAIClient {
         Ok(Self { config, client })
     }
 
+    /// Send requests through `client`, e.g. one built for the `[network.llm]` proxy
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
     /// Convert our Message format to OpenAI's format
     fn convert_messages_to_openai(&self, messages: Vec<Message>) -> Vec<OpenAIMessage> {
         messages.into_iter().map(|msg| OpenAIMessage {
@@ -89,6 +96,33 @@ impl OpenAIClient {
             }),
         }
     }
+
+    /// Embed `text` with an embeddings model such as `text-embedding-3-small`. `dimensions`
+    /// shortens the vector on models that support it.
+    pub async fn embed(&self, model: &str, text: &str, dimensions: Option<usize>) -> Result<Vec<f32>> {
+        let request_body = OpenAIEmbeddingRequest { model: model.to_string(), input: text.to_string(), dimensions };
+
+        let response = self.client
+            .post(format!("{}/embeddings", self.config.base_url))
+            .header("Authorization", format!("Bearer {}", self.config.api_key))
+            .header("Content-Type", "application/json")
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| ArrowheadError::from_reqwest("openai", &e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = ArrowheadError::retry_after(response.headers());
+            let error_text = response.text().await?;
+            return Err(ArrowheadError::from_status("openai", status.as_u16(), retry_after, error_text).into());
+        }
+
+        let embedding_response: OpenAIEmbeddingResponse = response.json().await.map_err(|e| ArrowheadError::from_reqwest("openai", &e))?;
+        embedding_response.data.into_iter().next()
+            .map(|data| data.embedding)
+            .ok_or_else(|| anyhow::anyhow!("No embedding in OpenAI response"))
+    }
 }
 
 #[async_trait]
@@ -111,14 +145,17 @@ impl LLMClient for OpenAIClient {
             .header("Content-Type", "application/json")
             .json(&request_body)
             .send()
-            .await?;
+            .await
+            .map_err(|e| ArrowheadError::from_reqwest("openai", &e))?;
 
-        if !response.status().is_success() {
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = ArrowheadError::retry_after(response.headers());
             let error_text = response.text().await?;
-            return Err(anyhow::anyhow!("OpenAI API error: {}", error_text));
+            return Err(ArrowheadError::from_status("openai", status.as_u16(), retry_after, error_text).into());
         }
 
-        let openai_response: OpenAIResponse = response.json().await?;
+        let openai_response: OpenAIResponse = response.json().await.map_err(|e| ArrowheadError::from_reqwest("openai", &e))?;
         
         if let Some(choice) = openai_response.choices.first() {
             Ok(self.convert_openai_message_to_message(choice.message.clone()))
@@ -224,14 +261,17 @@ impl LLMClient for OpenAIClient {
             .header("Content-Type", "application/json")
             .json(&request_body)
             .send()
-            .await?;
+            .await
+            .map_err(|e| ArrowheadError::from_reqwest("openai", &e))?;
 
-        if !response.status().is_success() {
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = ArrowheadError::retry_after(response.headers());
             let error_text = response.text().await?;
-            return Err(anyhow::anyhow!("OpenAI API error: {}", error_text));
+            return Err(ArrowheadError::from_status("openai", status.as_u16(), retry_after, error_text).into());
         }
 
-        let openai_response: OpenAIResponse = response.json().await?;
+        let openai_response: OpenAIResponse = response.json().await.map_err(|e| ArrowheadError::from_reqwest("openai", &e))?;
         
         if let Some(choice) = openai_response.choices.first() {
             Ok(self.convert_openai_message_to_message(choice.message.clone()))
@@ -321,4 +361,22 @@ struct OpenAIStreamChoice {
 #[derive(Debug, Deserialize)]
 struct OpenAIStreamDelta {
     content: Option<String>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize)]
+struct OpenAIEmbeddingRequest {
+    model: String,
+    input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIEmbeddingResponse {
+    data: Vec<OpenAIEmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIEmbeddingData {
+    embedding: Vec<f32>,
+}
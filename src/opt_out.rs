@@ -0,0 +1,501 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::cli::MuteArgs;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::vault_transaction::VaultStore;
+
+/// Frontmatter key listing the features a note opts out of
+pub const DISABLE_KEY: &str = "arrowhead.disable";
+/// Frontmatter key re-enabling features that a folder-level override disabled
+pub const ENABLE_KEY: &str = "arrowhead.enable";
+
+/// A pipeline that a note can opt out of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Feature {
+    Analysis,
+    Autolink,
+    Suggestions,
+    Organize,
+    Embedding,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 5] = [
+        Feature::Analysis,
+        Feature::Autolink,
+        Feature::Suggestions,
+        Feature::Organize,
+        Feature::Embedding,
+    ];
+
+    /// The features that send a note's content to an LLM or embedding provider
+    pub const AI: [Feature; 4] = [Feature::Analysis, Feature::Suggestions, Feature::Organize, Feature::Embedding];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::Analysis => "analysis",
+            Feature::Autolink => "autolink",
+            Feature::Suggestions => "suggestions",
+            Feature::Organize => "organize",
+            Feature::Embedding => "embedding",
+        }
+    }
+
+    /// Parse a feature name; `all` expands to every feature
+    pub fn parse_list(name: &str) -> Option<Vec<Feature>> {
+        let name = name.trim().to_lowercase();
+        if name == "all" {
+            return Some(Self::ALL.to_vec());
+        }
+        Self::ALL.iter().find(|f| f.name() == name).map(|f| vec![*f])
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Returned by a pipeline that skipped a note because the note opted out of it
+#[derive(Debug, thiserror::Error)]
+#[error("{path} opts out of {feature}")]
+pub struct OptedOut {
+    pub path: String,
+    pub feature: Feature,
+}
+
+/// Frontmatter flag that keeps a note away from every AI feature when false
+pub const AI_KEY: &str = "ai";
+
+/// The opt-out keys found in one note's frontmatter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NoteOptOuts {
+    pub disabled: BTreeSet<Feature>,
+    pub enabled: BTreeSet<Feature>,
+}
+
+impl NoteOptOuts {
+    /// Read `arrowhead.disable` / `arrowhead.enable`, accepting both the dotted key and a
+    /// nested `arrowhead:` mapping, with either a list or a comma-separated string. `ai: false`
+    /// disables every feature in `Feature::AI`.
+    pub fn parse(content: &str) -> Self {
+        let Some(frontmatter) = frontmatter_block(content) else {
+            return Self::default();
+        };
+        let Ok(serde_yaml::Value::Mapping(meta)) = serde_yaml::from_str::<serde_yaml::Value>(frontmatter) else {
+            return Self::default();
+        };
+
+        let lookup = |key: &str| {
+            let nested = key.strip_prefix("arrowhead.").unwrap_or(key);
+            meta.get(key)
+                .or_else(|| meta.get("arrowhead").and_then(|a| a.get(nested)))
+                .map(features_from_yaml)
+                .unwrap_or_default()
+        };
+        let mut disabled = lookup(DISABLE_KEY);
+        let ai_off = match meta.get(AI_KEY) {
+            Some(serde_yaml::Value::Bool(on)) => !on,
+            Some(serde_yaml::Value::String(value)) => matches!(value.trim().to_lowercase().as_str(), "false" | "no" | "off"),
+            _ => false,
+        };
+        if ai_off {
+            disabled.extend(Feature::AI);
+        }
+        Self { disabled, enabled: lookup(ENABLE_KEY) }
+    }
+}
+
+fn features_from_yaml(value: &serde_yaml::Value) -> BTreeSet<Feature> {
+    let names: Vec<String> = match value {
+        serde_yaml::Value::Sequence(items) => items.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+        serde_yaml::Value::String(list) => list.split(',').map(str::to_string).collect(),
+        _ => Vec::new(),
+    };
+    names.iter().filter_map(|name| Feature::parse_list(name)).flatten().collect()
+}
+
+/// Whether `path` matches the glob `pattern`. `*` and `?` stay within one folder, `**`
+/// spans any number of folders, and a pattern ending in `/` covers everything beneath it.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_start_matches('/');
+    let pattern = match pattern.strip_suffix('/') {
+        Some(folder) => format!("{}/**", folder),
+        None => pattern.to_string(),
+    };
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    segments_match(&pattern, &path)
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| segments_match(rest, &path[skip..])),
+        Some((segment, rest)) => path.split_first().is_some_and(|(name, path)| {
+            let (segment, name): (Vec<char>, Vec<char>) = (segment.chars().collect(), name.chars().collect());
+            wildcard_match(&segment, &name) && segments_match(rest, path)
+        }),
+    }
+}
+
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| wildcard_match(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && wildcard_match(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && wildcard_match(rest, &name[1..]),
+    }
+}
+
+/// Folder-level defaults plus per-note frontmatter. A note's own `arrowhead.disable`
+/// always applies; its `arrowhead.enable` can only lift features a folder disabled.
+/// Notes matching `exclude` get no features at all, whatever their frontmatter says.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OptOutPolicy {
+    /// Folder path mapped to the features disabled for every note beneath it
+    pub folders: HashMap<String, Vec<String>>,
+    /// Glob patterns of notes never read by any feature, e.g. `["Templates/**", "Private/**"]`
+    pub exclude: Vec<String>,
+}
+
+impl OptOutPolicy {
+    /// Whether the note at `path` matches one of the `exclude` patterns
+    pub fn is_excluded(&self, path: &str) -> bool {
+        self.exclude.iter().any(|pattern| glob_matches(pattern, path))
+    }
+
+    /// Features disabled for the note at `path` with the given raw content
+    pub fn disabled_features(&self, path: &str, content: &str) -> BTreeSet<Feature> {
+        if self.is_excluded(path) {
+            return Feature::ALL.into_iter().collect();
+        }
+        let mut disabled: BTreeSet<Feature> = self.folders.iter()
+            .filter(|(folder, _)| {
+                let folder = folder.trim_matches('/');
+                !folder.is_empty() && path.strip_prefix(folder).is_some_and(|rest| rest.starts_with('/'))
+            })
+            .flat_map(|(_, features)| features.iter().filter_map(|name| Feature::parse_list(name)).flatten())
+            .collect();
+
+        let note = NoteOptOuts::parse(content);
+        disabled.retain(|feature| !note.enabled.contains(feature));
+        disabled.extend(note.disabled);
+        disabled
+    }
+
+    pub fn allows(&self, path: &str, content: &str, feature: Feature) -> bool {
+        !self.disabled_features(path, content).contains(&feature)
+    }
+
+    /// `Err(OptedOut)` when the note opts out of `feature`
+    pub fn check(&self, path: &str, content: &str, feature: Feature) -> Result<()> {
+        if self.allows(path, content, feature) {
+            Ok(())
+        } else {
+            Err(OptedOut { path: path.to_string(), feature }.into())
+        }
+    }
+
+    /// `check` for content that arrives without a path, as from the plugin: only the note's
+    /// own frontmatter applies
+    pub fn check_content(&self, path: Option<&str>, content: &str, feature: Feature) -> Result<()> {
+        match path {
+            Some(path) => self.check(path, content, feature),
+            None if NoteOptOuts::parse(content).disabled.contains(&feature) => {
+                Err(OptedOut { path: "(unsaved note)".to_string(), feature }.into())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Whether an error from a pipeline is an opt-out skip rather than a failure
+pub fn is_opted_out(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<OptedOut>().is_some()
+}
+
+/// Counts for batch command output, keeping opt-out skips apart from real errors
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BatchSummary {
+    pub succeeded: usize,
+    pub opted_out: usize,
+    pub failed: usize,
+}
+
+impl BatchSummary {
+    pub fn from_results<T>(results: &[(String, Result<T>)]) -> Self {
+        let mut summary = Self::default();
+        for (_, result) in results {
+            match result {
+                Ok(_) => summary.succeeded += 1,
+                Err(e) if is_opted_out(e) => summary.opted_out += 1,
+                Err(_) => summary.failed += 1,
+            }
+        }
+        summary
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} processed, {} skipped (opted out), {} failed", self.succeeded, self.opted_out, self.failed)
+    }
+}
+
+fn frontmatter_block(content: &str) -> Option<&str> {
+    let rest = content.strip_prefix("---\n")?;
+    let end = rest.find("\n---")?;
+    Some(&rest[..end])
+}
+
+/// Rewrite only the `arrowhead.disable` entry of the frontmatter, leaving every other
+/// line byte-for-byte intact. An empty list removes the entry.
+pub fn set_disabled_features(content: &str, features: &BTreeSet<Feature>) -> String {
+    let entry = if features.is_empty() {
+        None
+    } else {
+        let names: Vec<&str> = features.iter().map(Feature::name).collect();
+        Some(format!("{}: [{}]", DISABLE_KEY, names.join(", ")))
+    };
+
+    let Some(frontmatter) = frontmatter_block(content) else {
+        return match entry {
+            Some(entry) => format!("---\n{}\n---\n{}", entry, content),
+            None => content.to_string(),
+        };
+    };
+    let body = &content[4 + frontmatter.len()..];
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut replaced = false;
+    let mut skipping_list = false;
+    let mut in_nested = false;
+    for line in frontmatter.lines() {
+        let indented = line.starts_with(' ') || line.starts_with('\t');
+        if skipping_list {
+            if indented || line.trim_start().starts_with("- ") {
+                continue;
+            }
+            skipping_list = false;
+        }
+        if !indented {
+            in_nested = line.trim_end() == "arrowhead:";
+        }
+
+        let nested_disable = in_nested && indented && line.trim_start().starts_with("disable:");
+        if line.starts_with(&format!("{}:", DISABLE_KEY)) || nested_disable {
+            let value = line.split_once(':').map_or("", |(_, v)| v.trim());
+            skipping_list = value.is_empty();
+            if !replaced {
+                if let Some(entry) = &entry {
+                    lines.push(entry.clone());
+                }
+                replaced = true;
+            }
+            continue;
+        }
+        lines.push(line.to_string());
+    }
+    if !replaced {
+        if let Some(entry) = entry {
+            lines.push(entry);
+        }
+    }
+
+    // A nested `arrowhead:` block left with no children would parse as null; drop it
+    let mut cleaned: Vec<String> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let has_children = lines.get(index + 1).is_some_and(|next| next.starts_with(' ') || next.starts_with('\t'));
+        if line.trim_end() == "arrowhead:" && !has_children {
+            continue;
+        }
+        cleaned.push(line.clone());
+    }
+
+    if cleaned.is_empty() {
+        return body.trim_start_matches("\n---").trim_start_matches('\n').to_string();
+    }
+    format!("---\n{}{}", cleaned.join("\n"), body)
+}
+
+fn parse_feature_args(features: &[String]) -> Result<BTreeSet<Feature>> {
+    let mut parsed = BTreeSet::new();
+    for name in features.iter().flat_map(|f| f.split(',')) {
+        match Feature::parse_list(name) {
+            Some(list) => parsed.extend(list),
+            None => bail!(
+                "Unknown feature '{}'. Expected one of: {}, all",
+                name.trim(),
+                Feature::ALL.iter().map(Feature::name).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+    Ok(parsed)
+}
+
+/// `arrowhead mute` / `arrowhead unmute`
+pub async fn handle_mute_command(args: MuteArgs, adapter: &ObsidianAdapter, mute: bool) -> Result<()> {
+    let path = if args.path.ends_with(".md") { args.path } else { format!("{}.md", args.path) };
+    let requested = if args.features.is_empty() {
+        Feature::ALL.iter().copied().collect()
+    } else {
+        parse_feature_args(&args.features)?
+    };
+
+    let content = adapter.read_file(&path).await?
+        .with_context(|| format!("Note '{}' not found", path))?;
+    let mut disabled = NoteOptOuts::parse(&content).disabled;
+    if mute {
+        disabled.extend(requested);
+    } else {
+        disabled.retain(|feature| !requested.contains(feature));
+    }
+
+    let updated = set_disabled_features(&content, &disabled);
+    if updated == content {
+        println!("No change: {} already {}", path, if mute { "muted for those features" } else { "unmuted" });
+        return Ok(());
+    }
+    adapter.write_file(&path, &updated).await?;
+
+    if disabled.is_empty() {
+        println!("🔔 {} receives every feature again", path);
+    } else {
+        let names: Vec<&str> = disabled.iter().map(Feature::name).collect();
+        println!("🔕 {} opts out of: {}", path, names.join(", "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(features: &[Feature]) -> BTreeSet<Feature> {
+        features.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_parse_dotted_nested_and_all() {
+        let dotted = "---\ntitle: Spec\narrowhead.disable: [autolink, organize]\n---\nBody";
+        assert_eq!(NoteOptOuts::parse(dotted).disabled, set(&[Feature::Autolink, Feature::Organize]));
+
+        let nested = "---\narrowhead:\n  disable:\n    - suggestions\n  enable: analysis\n---\nBody";
+        let parsed = NoteOptOuts::parse(nested);
+        assert_eq!(parsed.disabled, set(&[Feature::Suggestions]));
+        assert_eq!(parsed.enabled, set(&[Feature::Analysis]));
+
+        let all = "---\narrowhead.disable: all\n---\n";
+        assert_eq!(NoteOptOuts::parse(all).disabled.len(), Feature::ALL.len());
+        assert!(NoteOptOuts::parse("No frontmatter").disabled.is_empty());
+    }
+
+    #[test]
+    fn test_granularity_only_blocks_listed_features() {
+        let policy = OptOutPolicy::default();
+        let note = "---\narrowhead.disable: [suggestions]\n---\nBody";
+        assert!(policy.check("Notes/a.md", note, Feature::Embedding).is_ok());
+        assert!(policy.check("Notes/a.md", note, Feature::Autolink).is_ok());
+        let err = policy.check("Notes/a.md", note, Feature::Suggestions).unwrap_err();
+        assert!(is_opted_out(&err));
+        assert_eq!(err.to_string(), "Notes/a.md opts out of suggestions");
+    }
+
+    #[test]
+    fn test_folder_overrides_and_note_precedence() {
+        let mut policy = OptOutPolicy::default();
+        policy.folders.insert("Templates".to_string(), vec!["all".to_string()]);
+        policy.folders.insert("Archive/".to_string(), vec!["organize".to_string(), "autolink".to_string()]);
+
+        // Folder disables everything beneath it, but not siblings sharing a prefix
+        assert_eq!(policy.disabled_features("Templates/meeting.md", "Body").len(), Feature::ALL.len());
+        assert!(policy.disabled_features("TemplatesOld/meeting.md", "Body").is_empty());
+
+        // A note can lift a folder opt-out with arrowhead.enable...
+        let lifted = "---\narrowhead.enable: [autolink]\n---\n";
+        assert_eq!(policy.disabled_features("Archive/2023/q1.md", lifted), set(&[Feature::Organize]));
+
+        // ...but its own disable list always wins, even over its own enable list
+        let both = "---\narrowhead.disable: [embedding, autolink]\narrowhead.enable: [autolink]\n---\n";
+        assert_eq!(
+            policy.disabled_features("Archive/q2.md", both),
+            set(&[Feature::Autolink, Feature::Embedding, Feature::Organize])
+        );
+        assert_eq!(policy.disabled_features("Notes/x.md", both), set(&[Feature::Autolink, Feature::Embedding]));
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("Templates/**", "Templates/meeting.md"));
+        assert!(glob_matches("Templates/**", "Templates/work/weekly.md"));
+        assert!(glob_matches("Private/", "Private/journal/2024.md"));
+        assert!(!glob_matches("Templates/**", "TemplatesOld/meeting.md"));
+        assert!(glob_matches("*.excalidraw.md", "drawing.excalidraw.md"));
+        assert!(!glob_matches("*.excalidraw.md", "Sketches/drawing.excalidraw.md"));
+        assert!(glob_matches("**/*.excalidraw.md", "Sketches/drawing.excalidraw.md"));
+        assert!(glob_matches("Daily/202?-*.md", "Daily/2024-01-05.md"));
+        assert!(!glob_matches("Daily/202?-*.md", "Daily/1999-01-05.md"));
+    }
+
+    #[test]
+    fn test_exclude_and_ai_flag() {
+        let policy = OptOutPolicy { exclude: vec!["Private/**".to_string()], ..OptOutPolicy::default() };
+
+        // Exclusion wins over the note's own enable list
+        let enabled = "---\narrowhead.enable: all\n---\nBody";
+        assert!(policy.is_excluded("Private/diary.md"));
+        assert_eq!(policy.disabled_features("Private/diary.md", enabled).len(), Feature::ALL.len());
+
+        // `ai: false` stops the AI features but leaves auto-linking alone
+        let ai_off = "---\nai: false\n---\nBody";
+        assert_eq!(policy.disabled_features("Notes/a.md", ai_off), set(&Feature::AI));
+        assert!(policy.check("Notes/a.md", ai_off, Feature::Autolink).is_ok());
+        assert!(NoteOptOuts::parse("---\nai: \"off\"\n---\n").disabled.contains(&Feature::Embedding));
+        assert!(NoteOptOuts::parse("---\nai: true\n---\n").disabled.is_empty());
+
+        // Content without a path only answers to its own frontmatter
+        assert!(is_opted_out(&policy.check_content(None, ai_off, Feature::Analysis).unwrap_err()));
+        assert!(policy.check_content(None, "Body", Feature::Analysis).is_ok());
+        assert!(policy.check_content(Some("Private/diary.md"), "Body", Feature::Suggestions).is_err());
+    }
+
+    #[test]
+    fn test_set_disabled_features_is_surgical() {
+        let original = "---\ntitle: \"Runbook\"   # keep this comment\ntags:\n  - ops\narrowhead.disable:\n  - autolink\nowner: sam\n---\n# Runbook\n";
+        let muted = set_disabled_features(original, &set(&[Feature::Autolink, Feature::Organize]));
+        assert_eq!(
+            muted,
+            "---\ntitle: \"Runbook\"   # keep this comment\ntags:\n  - ops\narrowhead.disable: [autolink, organize]\nowner: sam\n---\n# Runbook\n"
+        );
+
+        let unmuted = set_disabled_features(&muted, &BTreeSet::new());
+        assert_eq!(unmuted, "---\ntitle: \"Runbook\"   # keep this comment\ntags:\n  - ops\nowner: sam\n---\n# Runbook\n");
+
+        let nested = "---\narrowhead:\n  disable: [organize]\n---\nBody";
+        assert_eq!(set_disabled_features(nested, &BTreeSet::new()), "Body");
+
+        assert_eq!(
+            set_disabled_features("# Plain\n", &set(&[Feature::Embedding])),
+            "---\narrowhead.disable: [embedding]\n---\n# Plain\n"
+        );
+    }
+
+    #[test]
+    fn test_batch_summary_counts_opt_outs_separately() {
+        let results: Vec<(String, Result<()>)> = vec![
+            ("a.md".to_string(), Ok(())),
+            ("b.md".to_string(), Err(OptedOut { path: "b.md".to_string(), feature: Feature::Analysis }.into())),
+            ("c.md".to_string(), Err(anyhow::anyhow!("network down"))),
+        ];
+        let summary = BatchSummary::from_results(&results);
+        assert_eq!(summary, BatchSummary { succeeded: 1, opted_out: 1, failed: 1 });
+        assert_eq!(summary.to_string(), "1 processed, 1 skipped (opted out), 1 failed");
+    }
+}
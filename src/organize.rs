@@ -0,0 +1,168 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, Utc};
+
+use crate::ai_conversation::create_llm_client;
+use crate::apply_plan::{execute_plan, ApplyPlan, ItemStatus, PlanChange};
+use crate::cli::{ApplyPlanArgs, OrganizeArgs};
+use crate::config::Config;
+use crate::confirm::{confirm, Operation, OperationClass};
+use crate::obsidian_adapter::{ObsidianAdapter, OrganizationConfig};
+use crate::opt_out::{is_opted_out, BatchSummary};
+use crate::usage::UsagePurpose;
+use crate::vault_transaction::VaultStore;
+
+/// Folder scanned by `organize --all`
+const ORGANIZE_FOLDER: &str = "Notes";
+
+pub async fn handle_organize_command(args: OrganizeArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let paths = match (args.paths.is_empty(), args.all) {
+        (false, false) => args.paths.iter()
+            .map(|p| if p.ends_with(".md") { p.clone() } else { format!("{}.md", p) })
+            .collect(),
+        (true, true) => adapter.list_files_in_folder(ORGANIZE_FOLDER).await?
+            .into_iter()
+            .filter(|f| f.ends_with(".md"))
+            .map(|f| format!("{}/{}", ORGANIZE_FOLDER, f))
+            .collect::<Vec<_>>(),
+        (false, true) => bail!("Pass either note paths or --all, not both"),
+        (true, false) => bail!("Pass one or more note paths or --all"),
+    };
+
+    let config = Config::load().unwrap_or_default();
+    let backend = config.organize.analysis_backend;
+    let llm_client = match create_llm_client(&config, UsagePurpose::Analysis) {
+        Ok(client) => backend.uses_llm(true).then_some(client),
+        // `auto` falls back to the heuristics when there is no LLM to ask
+        Err(e) if !backend.uses_llm(false) => {
+            log::info!("{:#}; analyzing notes with keyword heuristics", e);
+            None
+        }
+        Err(e) => return Err(e),
+    };
+    // Each note costs one LLM request
+    if llm_client.is_some() && paths.len() > config.confirmations.llm_spend_threshold {
+        let operation = Operation::new(OperationClass::LlmSpend, format!("Send {} note(s) to the LLM for analysis", paths.len()), paths.len());
+        if !confirm(&config.confirmations, &operation, args.yes)? {
+            println!("Nothing analyzed.");
+            return Ok(());
+        }
+    }
+    let mut analyzer = ObsidianAdapter::new(Some(config.obsidian.base_url.clone()), config.obsidian.api_key.clone());
+    if let Some(llm_client) = llm_client {
+        analyzer.set_llm_client(llm_client);
+    }
+    analyzer.set_organization_config(OrganizationConfig { analysis_backend: backend, ..OrganizationConfig::default() });
+    analyzer.set_cache_settings(&config.cache);
+    analyzer.set_embedding_storage_config(config.embeddings.clone());
+    analyzer.set_opt_out_policy(config.opt_outs.clone());
+    analyzer.set_batch_concurrency(config.index.concurrency);
+    if let Err(e) = analyzer.load_link_graph() {
+        log::warn!("{:#}; link suggestions may repeat existing links", e);
+    }
+    if analyzer.uses_heuristic_analysis() {
+        if let Err(e) = analyzer.load_term_index() {
+            log::warn!("{:#}; keywords are weighed by this note alone", e);
+        }
+        println!("Analyzing with keyword heuristics; no note is sent to the LLM.");
+    }
+
+    let mut plan = ApplyPlan::new("organize");
+    let mut summary = BatchSummary::default();
+    for path in &paths {
+        let content = match adapter.read_file(path).await {
+            Ok(Some(content)) => content,
+            Ok(None) => {
+                println!("⚠️  {}: not found", path);
+                summary.failed += 1;
+                continue;
+            }
+            Err(e) => {
+                println!("⚠️  {}: {}", path, e);
+                summary.failed += 1;
+                continue;
+            }
+        };
+        let recommendations = match analyzer.generate_organization_recommendations(path).await {
+            Ok(recommendations) => recommendations,
+            Err(e) if is_opted_out(&e) => {
+                summary.opted_out += 1;
+                continue;
+            }
+            Err(e) => {
+                println!("⚠️  {}: {}", path, e);
+                summary.failed += 1;
+                continue;
+            }
+        };
+        summary.succeeded += 1;
+
+        for tag in recommendations.suggested_tags.iter().filter(|t| t.confidence >= args.min_confidence) {
+            plan.add(path, PlanChange::AddTag(tag.tag.trim_start_matches('#').to_string()), tag.confidence, &content);
+        }
+        let current_folder = path.rsplit_once('/').map_or("", |(folder, _)| folder);
+        if let Some(folder) = recommendations.folder_suggestions.iter()
+            .filter(|f| f.confidence >= args.min_confidence && f.folder_path.trim_end_matches('/') != current_folder)
+            .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+        {
+            plan.add(path, PlanChange::MoveToFolder(folder.folder_path.clone()), folder.confidence, &content);
+        }
+        for link in recommendations.link_suggestions.iter().filter(|l| l.confidence >= args.min_confidence) {
+            let change = PlanChange::AddLink { target: link.target_path.clone(), text: link.link_text.clone() };
+            plan.add(path, change, link.confidence, &content);
+        }
+    }
+
+    println!("{} note(s): {}", paths.len(), summary);
+    if plan.items.is_empty() {
+        println!("No changes proposed for {} note(s)", paths.len());
+        return Ok(());
+    }
+
+    match args.plan_note {
+        Some(plan_note) => {
+            let plan_note = if plan_note.ends_with(".md") { plan_note } else { format!("{}.md", plan_note) };
+            adapter.write_file(&plan_note, &plan.render(&plan_note)).await
+                .with_context(|| format!("Failed to write plan note '{}'", plan_note))?;
+            println!("📝 Wrote {} proposed change(s) to {}", plan.items.len(), plan_note);
+            println!("Review it in Obsidian, then run `arrowhead apply-plan {}`", plan_note);
+        }
+        None => {
+            println!("{}", plan.render("<plan note>"));
+            println!("Pass --plan-note <path> to save these changes as a reviewable plan.");
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn handle_apply_plan_command(args: ApplyPlanArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let plan_note = if args.plan_note.ends_with(".md") { args.plan_note } else { format!("{}.md", args.plan_note) };
+
+    let content = adapter.read_file(&plan_note).await?
+        .with_context(|| format!("Plan note '{}' not found", plan_note))?;
+    let pending = ApplyPlan::parse(&content)?.items.iter()
+        .filter(|item| item.checked && item.status != ItemStatus::Applied)
+        .count();
+    if pending == 0 {
+        println!("No checked items left to apply in {}", plan_note);
+        return Ok(());
+    }
+    let operation = Operation::new(OperationClass::BulkWrite, format!("Apply {} checked item(s) from {}", pending, plan_note), pending);
+    if !confirm(&config.confirmations, &operation, args.yes)? {
+        println!("Nothing applied.");
+        return Ok(());
+    }
+
+    let execution = execute_plan(adapter, &plan_note, Duration::hours(config.plans.max_age_hours), Utc::now()).await?;
+    for item in &execution.applied {
+        println!("✅ {}: {}", item.target, item.change.describe());
+    }
+    for (item, reason) in &execution.failed {
+        println!("⚠️  {}: {} ({})", item.target, item.change.describe(), reason);
+    }
+    println!("{}", execution.summary());
+    println!("Execution report appended to {}", plan_note);
+
+    Ok(())
+}
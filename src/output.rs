@@ -0,0 +1,68 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::obsidian_adapter::{Frontmatter, ObsidianAdapter};
+
+/// A note as printed by `--format json`: its vault path, its frontmatter fields (status,
+/// due_date, tags and any stored AI analysis) and, for view commands, its body
+#[derive(Debug, Clone, Serialize)]
+pub struct NoteRecord {
+    pub path: String,
+    #[serde(flatten)]
+    pub frontmatter: Frontmatter,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Fields specific to the kind of note, e.g. `done` for todos
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl NoteRecord {
+    /// Built from the raw file only, so it needs neither the LLM nor the analysis cache.
+    /// Frontmatter that doesn't parse leaves every field empty.
+    pub fn parse(path: &str, raw: &str) -> Self {
+        let frontmatter = ObsidianAdapter::parse_markdown_file(raw).map(|file| file.frontmatter).unwrap_or_default();
+        Self { path: path.to_string(), frontmatter, content: None, extra: Map::new() }
+    }
+
+    /// Include the note's body, without its frontmatter
+    pub fn with_content(mut self, raw: &str) -> Self {
+        self.content = Some(ObsidianAdapter::parse_markdown_file(raw).map_or(raw.to_string(), |file| file.content));
+        self
+    }
+
+    pub fn with(mut self, key: &str, value: impl Serialize) -> Self {
+        self.extra.insert(key.to_string(), serde_json::to_value(value).unwrap_or(Value::Null));
+        self
+    }
+}
+
+pub fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_carries_frontmatter_and_analysis_fields() {
+        let raw = "---\ntags: [work]\ndue_date: 2024-06-07\nstatus: open\nai_analysis_version: '2'\n---\n\n- [ ] Send the report\n";
+        let record = NoteRecord::parse("Todos/send-the-report.md", raw).with("done", false).with_content(raw);
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["path"], "Todos/send-the-report.md");
+        assert_eq!(json["status"], "open");
+        assert_eq!(json["due_date"], "2024-06-07");
+        assert_eq!(json["tags"], serde_json::json!(["work"]));
+        assert_eq!(json["ai_analysis_version"], "2");
+        assert_eq!(json["done"], false);
+        assert_eq!(json["content"], "- [ ] Send the report\n");
+
+        // No frontmatter: the fields are there, empty
+        let json = serde_json::to_value(NoteRecord::parse("Notes/plain.md", "Just text")).unwrap();
+        assert_eq!(json["status"], Value::Null);
+        assert!(json.get("content").is_none());
+    }
+}
@@ -0,0 +1,597 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use uuid::Uuid;
+
+use crate::cli::ServeArgs;
+use crate::config::{Config, PluginApiSettings};
+use crate::glossary::Glossary;
+use crate::mcp_client::JSONRPCError;
+use crate::note_skeleton::stable_hash;
+use crate::obsidian_adapter::ObsidianAdapter;
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+/// The client's document version does not match the server's; resend the full text
+pub const RESYNC_REQUIRED: i32 = -32001;
+pub const SESSION_NOT_FOUND: i32 = -32002;
+pub const SESSION_LIMIT: i32 = -32003;
+
+/// Sections longer than this many lines get a split suggestion
+const LONG_SECTION_LINES: usize = 80;
+/// Rough per-suggestion overhead counted against the session memory cap
+const SUGGESTION_OVERHEAD_BYTES: usize = 64;
+
+/// A suggestion for one line of the document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Suggestion {
+    /// Zero-based line in the document
+    pub line: usize,
+    pub kind: String,
+    pub message: String,
+    pub replacement: Option<String>,
+}
+
+/// Local, model-free suggestions: glossary terms, unlinked mentions of other notes and
+/// overly long sections
+#[derive(Debug, Clone, Default)]
+pub struct SuggestionEngine {
+    glossary: Glossary,
+    /// Note titles (without `.md`) that mentions can link to
+    titles: Vec<String>,
+}
+
+impl SuggestionEngine {
+    pub fn new(glossary: Glossary, titles: Vec<String>) -> Self {
+        let mut titles: Vec<String> = titles.into_iter().filter(|t| t.len() >= 3).collect();
+        // Longest first so "Release plan 2024" wins over "Release plan"
+        titles.sort_by_key(|t| std::cmp::Reverse(t.len()));
+        Self { glossary, titles }
+    }
+
+    /// Suggestions for one chunk, with lines relative to the chunk start
+    fn suggest_chunk(&self, chunk: &str) -> Vec<Suggestion> {
+        let mut suggestions: Vec<Suggestion> = self.glossary.check(chunk).into_iter()
+            .map(|v| Suggestion {
+                line: v.line - 1,
+                kind: "glossary".to_string(),
+                message: format!("Use \"{}\" instead of \"{}\"", v.suggestion, v.found),
+                replacement: Some(v.suggestion),
+            })
+            .collect();
+
+        for (index, line) in chunk.lines().enumerate() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let lower = line.to_lowercase();
+            let mut claimed: Vec<std::ops::Range<usize>> = linked_ranges(line);
+            for title in &self.titles {
+                let needle = title.to_lowercase();
+                let mut from = 0;
+                while let Some(pos) = lower[from..].find(&needle) {
+                    let start = from + pos;
+                    let end = start + needle.len();
+                    from = end;
+                    let boundary = |c: Option<char>| c.is_none_or(|c| !c.is_alphanumeric());
+                    if !boundary(lower[..start].chars().last()) || !boundary(lower[end..].chars().next()) {
+                        continue;
+                    }
+                    if claimed.iter().any(|r| r.start < end && start < r.end) {
+                        continue;
+                    }
+                    claimed.push(start..end);
+                    suggestions.push(Suggestion {
+                        line: index,
+                        kind: "link".to_string(),
+                        message: format!("Link to [[{}]]", title),
+                        replacement: Some(format!("[[{}]]", title)),
+                    });
+                }
+            }
+        }
+
+        let lines = chunk.lines().count();
+        if lines > LONG_SECTION_LINES {
+            suggestions.push(Suggestion {
+                line: 0,
+                kind: "structure".to_string(),
+                message: format!("This section is {} lines long; consider splitting it", lines),
+                replacement: None,
+            });
+        }
+        suggestions
+    }
+
+    /// Suggestions for a whole document without any cached state
+    pub fn suggest(&self, text: &str) -> Vec<Suggestion> {
+        let mut suggestions = Vec::new();
+        for chunk in chunk_map(text) {
+            suggestions.extend(self.suggest_chunk(&text[chunk.range.clone()]).into_iter().map(|mut s| {
+                s.line += chunk.first_line;
+                s
+            }));
+        }
+        suggestions
+    }
+}
+
+/// Byte ranges of `[[wikilinks]]` and `[markdown](links)` on a line
+fn linked_ranges(line: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut from = 0;
+    while let Some(start) = line[from..].find('[').map(|i| i + from) {
+        let close = if line[start..].starts_with("[[") { "]]" } else { ")" };
+        match line[start..].find(close) {
+            Some(end) => {
+                ranges.push(start..start + end + close.len());
+                from = start + end + close.len();
+            }
+            None => break,
+        }
+    }
+    ranges
+}
+
+/// One heading-delimited section of a document
+#[derive(Debug, Clone, PartialEq)]
+struct Chunk {
+    range: std::ops::Range<usize>,
+    first_line: usize,
+    hash: u64,
+}
+
+/// Split a document at headings (ignoring `#` lines inside code fences)
+fn chunk_map(text: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let (mut start, mut start_line, mut offset, mut in_fence) = (0, 0, 0, false);
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if !in_fence && trimmed.starts_with('#') && offset > start {
+            chunks.push(Chunk { range: start..offset, first_line: start_line, hash: stable_hash(&text[start..offset]) });
+            start = offset;
+            start_line = index;
+        }
+        offset += line.len();
+    }
+    if offset > start || chunks.is_empty() {
+        chunks.push(Chunk { range: start..offset, first_line: start_line, hash: stable_hash(&text[start..offset]) });
+    }
+    chunks
+}
+
+/// A zero-based line/character position (characters are Unicode scalar values)
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+/// Replace `range` with `text`; without a range the whole document is replaced
+#[derive(Debug, Clone, Deserialize)]
+pub struct TextEdit {
+    pub range: Option<(Position, Position)>,
+    pub text: String,
+}
+
+fn byte_offset(text: &str, position: Position) -> Option<usize> {
+    let line_start = if position.line == 0 {
+        0
+    } else {
+        text.match_indices('\n').nth(position.line - 1)?.0 + 1
+    };
+    let line = text[line_start..].split('\n').next().unwrap_or("");
+    match line.char_indices().nth(position.character) {
+        Some((i, _)) => Some(line_start + i),
+        None if line.chars().count() == position.character => Some(line_start + line.len()),
+        None => None,
+    }
+}
+
+/// Server-side state of a note open in the editor
+struct Session {
+    path: Option<String>,
+    version: u64,
+    text: String,
+    chunks: Vec<Chunk>,
+    /// Suggestions by chunk hash, relative to the chunk start
+    cache: HashMap<u64, Vec<Suggestion>>,
+    last_used: Instant,
+}
+
+impl Session {
+    fn memory_bytes(&self) -> usize {
+        self.text.len()
+            + self.cache.values().flatten()
+                .map(|s| s.message.len() + s.replacement.as_ref().map_or(0, String::len) + SUGGESTION_OVERHEAD_BYTES)
+                .sum::<usize>()
+    }
+}
+
+/// Counters for the session cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct SessionStats {
+    pub chunks_analyzed: usize,
+    pub chunks_reused: usize,
+}
+
+/// JSON-RPC handler for the Obsidian plugin: stateless `suggest` plus per-note
+/// `session/*` methods that keep the buffer and per-chunk results between requests
+pub struct PluginApi {
+    engine: SuggestionEngine,
+    settings: PluginApiSettings,
+    sessions: HashMap<String, Session>,
+    stats: SessionStats,
+}
+
+fn rpc_error(code: i32, message: impl Into<String>, data: Option<Value>) -> JSONRPCError {
+    JSONRPCError { code, message: message.into(), data }
+}
+
+fn param<'a>(params: &'a Value, name: &str) -> Result<&'a Value, JSONRPCError> {
+    params.get(name).ok_or_else(|| rpc_error(INVALID_PARAMS, format!("Missing parameter '{}'", name), None))
+}
+
+fn str_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, JSONRPCError> {
+    param(params, name)?.as_str().ok_or_else(|| rpc_error(INVALID_PARAMS, format!("'{}' must be a string", name), None))
+}
+
+fn version_param(params: &Value) -> Result<u64, JSONRPCError> {
+    param(params, "version")?.as_u64().ok_or_else(|| rpc_error(INVALID_PARAMS, "'version' must be a non-negative integer", None))
+}
+
+fn resync(session_id: &str, server_version: u64) -> JSONRPCError {
+    rpc_error(
+        RESYNC_REQUIRED,
+        "Document version mismatch; send the full text",
+        Some(json!({ "session_id": session_id, "server_version": server_version })),
+    )
+}
+
+impl PluginApi {
+    pub fn new(engine: SuggestionEngine, settings: PluginApiSettings) -> Self {
+        Self { engine, settings, sessions: HashMap::new(), stats: SessionStats::default() }
+    }
+
+    pub fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn stats(&self) -> SessionStats {
+        self.stats
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.sessions.values().map(Session::memory_bytes).sum()
+    }
+
+    /// Drop sessions idle for longer than the configured timeout
+    pub fn expire_idle(&mut self, now: Instant) {
+        let timeout = Duration::from_secs(self.settings.idle_timeout_minutes * 60);
+        self.sessions.retain(|_, s| now.saturating_duration_since(s.last_used) < timeout);
+    }
+
+    /// Evict least recently used sessions until `extra_bytes` more fit under the caps
+    fn make_room(&mut self, extra_bytes: usize) -> Result<(), JSONRPCError> {
+        if extra_bytes > self.settings.max_memory_bytes {
+            return Err(rpc_error(SESSION_LIMIT, "Document is too large for a session; use stateless suggest", None));
+        }
+        while self.sessions.len() >= self.settings.max_sessions || self.memory_bytes() + extra_bytes > self.settings.max_memory_bytes {
+            let oldest = self.sessions.iter().min_by_key(|(_, s)| s.last_used).map(|(id, _)| id.clone());
+            match oldest {
+                Some(id) => {
+                    self.sessions.remove(&id);
+                }
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle one JSON-RPC message; notifications get no response
+    pub fn handle_message(&mut self, line: &str, now: Instant) -> Option<Value> {
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => return Some(error_response(Value::Null, rpc_error(PARSE_ERROR, e.to_string(), None))),
+        };
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            return Some(error_response(id.unwrap_or(Value::Null), rpc_error(INVALID_REQUEST, "Missing method", None)));
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        self.expire_idle(now);
+        let result = self.dispatch(method, &params, now);
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => error_response(id, error),
+        })
+    }
+
+    fn dispatch(&mut self, method: &str, params: &Value, now: Instant) -> Result<Value, JSONRPCError> {
+        match method {
+            "suggest" => Ok(json!({ "suggestions": self.engine.suggest(str_param(params, "text")?) })),
+            "session/open" => self.open(params, now),
+            "session/edit" => self.edit(params, now),
+            "session/suggest" => self.session_suggest(params, now),
+            "session/close" => {
+                let session_id = str_param(params, "session_id")?;
+                Ok(json!({ "closed": self.sessions.remove(session_id).is_some() }))
+            }
+            _ => Err(rpc_error(METHOD_NOT_FOUND, format!("Unknown method '{}'", method), None)),
+        }
+    }
+
+    fn open(&mut self, params: &Value, now: Instant) -> Result<Value, JSONRPCError> {
+        let text = str_param(params, "text")?.to_string();
+        let version = version_param(params)?;
+        let path = params.get("path").and_then(Value::as_str).map(str::to_string);
+        self.make_room(text.len())?;
+
+        let session_id = Uuid::new_v4().to_string();
+        let chunks = chunk_map(&text);
+        self.sessions.insert(session_id.clone(), Session { path, version, text, chunks, cache: HashMap::new(), last_used: now });
+        Ok(json!({ "session_id": session_id, "version": version }))
+    }
+
+    fn session(&mut self, session_id: &str) -> Result<&mut Session, JSONRPCError> {
+        self.sessions.get_mut(session_id)
+            .ok_or_else(|| rpc_error(SESSION_NOT_FOUND, "Unknown or expired session; open it again", Some(json!({ "session_id": session_id }))))
+    }
+
+    fn edit(&mut self, params: &Value, now: Instant) -> Result<Value, JSONRPCError> {
+        let session_id = str_param(params, "session_id")?;
+        let version = version_param(params)?;
+        let edits: Vec<TextEdit> = serde_json::from_value(param(params, "changes")?.clone())
+            .map_err(|e| rpc_error(INVALID_PARAMS, format!("Invalid changes: {}", e), None))?;
+        let max_memory = self.settings.max_memory_bytes;
+
+        let session = self.session(session_id)?;
+        let full_sync = edits.iter().any(|e| e.range.is_none());
+        // Incremental edits must follow the server's version exactly; a full text always resyncs
+        if !full_sync && version != session.version + 1 {
+            return Err(resync(session_id, session.version));
+        }
+
+        let mut text = session.text.clone();
+        for edit in &edits {
+            match edit.range {
+                None => text = edit.text.clone(),
+                Some((start, end)) => {
+                    let (Some(start), Some(end)) = (byte_offset(&text, start), byte_offset(&text, end)) else {
+                        return Err(resync(session_id, session.version));
+                    };
+                    if start > end {
+                        return Err(rpc_error(INVALID_PARAMS, "Edit range ends before it starts", None));
+                    }
+                    text.replace_range(start..end, &edit.text);
+                }
+            }
+        }
+        if text.len() > max_memory {
+            return Err(rpc_error(SESSION_LIMIT, "Document is too large for a session; use stateless suggest", None));
+        }
+
+        session.chunks = chunk_map(&text);
+        session.text = text;
+        session.version = version;
+        session.last_used = now;
+        let live: std::collections::HashSet<u64> = session.chunks.iter().map(|c| c.hash).collect();
+        session.cache.retain(|hash, _| live.contains(hash));
+        Ok(json!({ "version": version }))
+    }
+
+    fn session_suggest(&mut self, params: &Value, now: Instant) -> Result<Value, JSONRPCError> {
+        let session_id = str_param(params, "session_id")?;
+        let version = version_param(params)?;
+        let engine = &self.engine;
+        let session = self.sessions.get_mut(session_id)
+            .ok_or_else(|| rpc_error(SESSION_NOT_FOUND, "Unknown or expired session; open it again", Some(json!({ "session_id": session_id }))))?;
+        if version != session.version {
+            return Err(resync(session_id, session.version));
+        }
+        session.last_used = now;
+
+        let mut suggestions = Vec::new();
+        for chunk in &session.chunks {
+            let cached = match session.cache.get(&chunk.hash) {
+                Some(cached) => {
+                    self.stats.chunks_reused += 1;
+                    cached
+                }
+                None => {
+                    self.stats.chunks_analyzed += 1;
+                    let fresh = engine.suggest_chunk(&session.text[chunk.range.clone()]);
+                    session.cache.entry(chunk.hash).or_insert(fresh)
+                }
+            };
+            suggestions.extend(cached.iter().cloned().map(|mut s| {
+                s.line += chunk.first_line;
+                s
+            }));
+        }
+        Ok(json!({ "session_id": session_id, "path": session.path, "version": version, "suggestions": suggestions }))
+    }
+}
+
+fn error_response(id: Value, error: JSONRPCError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+/// Serve line-delimited JSON-RPC on stdin/stdout until stdin closes
+pub async fn handle_serve_command(_args: ServeArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let glossary = Glossary::load(adapter, &config.glossary).await.unwrap_or_default();
+    let mut titles = Vec::new();
+    for folder in &config.plugin_api.title_folders {
+        if let Ok(files) = adapter.list_files_in_folder(folder).await {
+            titles.extend(files.into_iter().filter_map(|f| f.strip_suffix(".md").map(str::to_string)));
+        }
+    }
+    let mut api = PluginApi::new(SuggestionEngine::new(glossary, titles), config.plugin_api.clone());
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = api.handle_message(&line, Instant::now()) {
+            stdout.write_all(format!("{}\n", response).as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glossary::{GlossaryRule, RuleKind};
+
+    fn engine() -> SuggestionEngine {
+        let glossary = Glossary::new(vec![GlossaryRule {
+            term: "client".to_string(),
+            replacement: "customer".to_string(),
+            kind: RuleKind::Replacement,
+        }]);
+        let mut titles: Vec<String> = (0..300).map(|i| format!("Project Note {}", i)).collect();
+        titles.push("Release plan".to_string());
+        SuggestionEngine::new(glossary, titles)
+    }
+
+    fn settings() -> PluginApiSettings {
+        PluginApiSettings { max_sessions: 2, max_memory_bytes: 4_000_000, idle_timeout_minutes: 30, ..PluginApiSettings::default() }
+    }
+
+    /// Large note: 400 sections of ordinary prose
+    fn large_document() -> String {
+        let mut doc = String::from("---\ntags: [spec]\n---\n# Spec\n");
+        for i in 0..400 {
+            doc.push_str(&format!("\n## Section {}\n", i));
+            for j in 0..6 {
+                doc.push_str(&format!("Paragraph {} of section {} describes the rollout in some detail without naming anything.\n", j, i));
+            }
+        }
+        doc
+    }
+
+    fn call(api: &mut PluginApi, id: u64, method: &str, params: Value, now: Instant) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        api.handle_message(&request.to_string(), now).unwrap()
+    }
+
+    #[test]
+    fn test_engine_suggestions() {
+        let text = "# Notes\nThe client asked about the Release plan.\nSee [[Release plan]] too.\n";
+        let suggestions = engine().suggest(text);
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].kind, "glossary");
+        assert_eq!(suggestions[0].line, 1);
+        assert_eq!(suggestions[1].kind, "link");
+        assert_eq!(suggestions[1].replacement.as_deref(), Some("[[Release plan]]"));
+    }
+
+    #[test]
+    fn test_session_edits_and_resync() {
+        let mut api = PluginApi::new(engine(), settings());
+        let now = Instant::now();
+        let opened = call(&mut api, 1, "session/open", json!({ "path": "Notes/a.md", "text": "# A\nhello\n\n## B\nworld\n", "version": 1 }), now);
+        let session_id = opened["result"]["session_id"].as_str().unwrap().to_string();
+
+        // Replace "world" with "the client" on line 4
+        let edit = json!({ "session_id": session_id, "version": 2, "changes": [
+            { "range": [{ "line": 4, "character": 0 }, { "line": 4, "character": 5 }], "text": "the client" }
+        ]});
+        assert_eq!(call(&mut api, 2, "session/edit", edit, now)["result"]["version"], 2);
+        let suggested = call(&mut api, 3, "session/suggest", json!({ "session_id": session_id, "version": 2 }), now);
+        let suggestions = suggested["result"]["suggestions"].as_array().unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0]["line"], 4);
+
+        // A skipped version asks for a full resync, which a range-less change provides
+        let stale = json!({ "session_id": session_id, "version": 4, "changes": [{ "range": [{ "line": 0, "character": 0 }, { "line": 0, "character": 0 }], "text": "x" }] });
+        let error = call(&mut api, 4, "session/edit", stale, now);
+        assert_eq!(error["error"]["code"], RESYNC_REQUIRED);
+        assert_eq!(error["error"]["data"]["server_version"], 2);
+        let full = json!({ "session_id": session_id, "version": 7, "changes": [{ "text": "# A\nclean\n" }] });
+        assert_eq!(call(&mut api, 5, "session/edit", full, now)["result"]["version"], 7);
+        let suggested = call(&mut api, 6, "session/suggest", json!({ "session_id": session_id, "version": 7 }), now);
+        assert!(suggested["result"]["suggestions"].as_array().unwrap().is_empty());
+
+        assert_eq!(call(&mut api, 7, "session/close", json!({ "session_id": session_id }), now)["result"]["closed"], true);
+        assert_eq!(call(&mut api, 8, "session/suggest", json!({ "session_id": session_id, "version": 7 }), now)["error"]["code"], SESSION_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_session_caps_and_idle_expiry() {
+        let mut api = PluginApi::new(engine(), settings());
+        let start = Instant::now();
+        for i in 0..3u64 {
+            let opened = call(&mut api, i, "session/open", json!({ "text": "# Note\n", "version": 0 }), start + Duration::from_secs(i));
+            assert!(opened["result"]["session_id"].is_string());
+        }
+        assert_eq!(api.session_count(), 2);
+
+        let huge = "x".repeat(5_000_000);
+        let rejected = call(&mut api, 9, "session/open", json!({ "text": huge, "version": 0 }), start);
+        assert_eq!(rejected["error"]["code"], SESSION_LIMIT);
+
+        api.expire_idle(start + Duration::from_secs(31 * 60));
+        assert_eq!(api.session_count(), 0);
+        assert!(api.handle_message(r#"{"jsonrpc":"2.0","method":"session/close","params":{"session_id":"x"}}"#, start).is_none());
+        assert_eq!(api.handle_message("not json", start).unwrap()["error"]["code"], PARSE_ERROR);
+    }
+
+    #[test]
+    fn test_session_suggest_after_small_edit_beats_stateless_path() {
+        let doc = large_document();
+        let mut api = PluginApi::new(engine(), PluginApiSettings::default());
+        let now = Instant::now();
+        let opened = call(&mut api, 1, "session/open", json!({ "text": doc, "version": 1 }), now);
+        let session_id = opened["result"]["session_id"].as_str().unwrap().to_string();
+        call(&mut api, 2, "session/suggest", json!({ "session_id": session_id, "version": 1 }), now);
+
+        let line = doc.lines().position(|l| l == "## Section 200").unwrap() + 1;
+        let (mut stateless, mut session) = (Duration::ZERO, Duration::ZERO);
+        let mut current = doc.clone();
+        for version in 2..7u64 {
+            let insert = format!("Ask the client about Release plan {}. ", version);
+            current = {
+                let at = byte_offset(&current, Position { line, character: 0 }).unwrap();
+                let mut next = current.clone();
+                next.insert_str(at, &insert);
+                next
+            };
+
+            let started = Instant::now();
+            let full = call(&mut api, 10 + version, "suggest", json!({ "text": current }), now);
+            stateless += started.elapsed();
+
+            let started = Instant::now();
+            let edit = json!({ "session_id": session_id, "version": version, "changes": [
+                { "range": [{ "line": line, "character": 0 }, { "line": line, "character": 0 }], "text": insert }
+            ]});
+            call(&mut api, 20 + version, "session/edit", edit, now);
+            let incremental = call(&mut api, 30 + version, "session/suggest", json!({ "session_id": session_id, "version": version }), now);
+            session += started.elapsed();
+
+            assert_eq!(full["result"]["suggestions"], incremental["result"]["suggestions"]);
+        }
+
+        // Only the edited section is re-analyzed after the first request
+        let stats = api.stats();
+        let chunks = chunk_map(&current).len();
+        assert_eq!(stats.chunks_analyzed, chunks + 5);
+        assert!(session < stateless, "session path {:?} should beat stateless {:?}", session, stateless);
+    }
+}
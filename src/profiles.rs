@@ -0,0 +1,102 @@
+use anyhow::Result;
+use std::fmt::Write as _;
+
+use crate::cache::CachePaths;
+use crate::config::{leaf_keys, selected_profile, Config};
+
+fn is_secret(key: &str) -> bool {
+    key.ends_with("api_key") || key.ends_with("password")
+}
+
+/// `value` with every API key and password replaced by "[REDACTED]"
+fn redacted(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map.into_iter()
+            .map(|(key, value)| match value {
+                serde_json::Value::String(_) if is_secret(&key) => (key, serde_json::Value::from("[REDACTED]")),
+                value => (key, redacted(value)),
+            })
+            .collect(),
+        serde_json::Value::Array(items) => items.into_iter().map(redacted).collect(),
+        value => value,
+    }
+}
+
+/// One block for a profile, starred when in use: each key it overrides with the value it
+/// ends up with, then its model, vault, cache folder and session folder
+pub fn render_profile(name: &str, overrides: &toml::Table, merged: &Result<Config>, active: bool) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{} {}", if active { "★" } else { "•" }, name);
+    let config = match merged {
+        Ok(config) => config,
+        Err(e) => {
+            let _ = writeln!(out, "    ⚠️  {}", e);
+            return out;
+        }
+    };
+    for key in leaf_keys(overrides) {
+        let value = match config.get_key(&key) {
+            Ok(value) if is_secret(&key) && !value.is_empty() => "[REDACTED]".to_string(),
+            Ok(value) => value,
+            Err(_) => "(unknown key)".to_string(),
+        };
+        let env = crate::config::env_override(&key).map(|var| format!(" (from {})", var)).unwrap_or_default();
+        let _ = writeln!(out, "    {} = {}{}", key, value, env);
+    }
+    let _ = writeln!(out, "    llm:      {} {}", config.llm.provider, config.get_llm_model());
+    let _ = writeln!(out, "    obsidian: {}", config.obsidian.base_url);
+    let _ = writeln!(out, "    cache:    {}", CachePaths::new(&config.cache, &config.obsidian.base_url).root().display());
+    if let Some(sessions) = &config.sessions.path {
+        let _ = writeln!(out, "    sessions: {}", sessions);
+    }
+    out
+}
+
+pub fn handle_profiles_command() -> Result<()> {
+    let base = Config::load_profile(None)?;
+    if base.profiles.is_empty() {
+        println!("No [profiles] configured. Add a section such as [profiles.work.llm] to the config file and pick it with --profile work.");
+        crate::script::publish_output(serde_json::Value::Array(Vec::new()));
+        return Ok(());
+    }
+    let active = selected_profile();
+    let mut profiles = Vec::new();
+    for (name, overrides) in &base.profiles {
+        let merged = Config::load_profile(Some(name));
+        let in_use = active.as_deref().is_some_and(|active| active.eq_ignore_ascii_case(name));
+        print!("{}", render_profile(name, overrides, &merged, in_use));
+        profiles.push(serde_json::json!({
+            "name": name,
+            "active": in_use,
+            "overrides": leaf_keys(overrides),
+            "config": merged.as_ref().ok().map(|config| serde_json::to_value(config).map(redacted)).transpose()?,
+            "error": merged.as_ref().err().map(|e| e.to_string()),
+        }));
+    }
+    if let Some(name) = active.filter(|name| base.find_profile(name).is_err()) {
+        println!("⚠️  There is no profile named '{}'", name);
+    }
+    crate::script::publish_output(serde_json::Value::Array(profiles));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_shows_its_overrides_merged() {
+        let text = toml::to_string(&Config::default()).unwrap() + "[profiles.work.llm]\nprovider = \"openai\"\n[profiles.work.llm.openai]\napi_key = \"sk-secret\"\n[profiles.work.obsidian]\nbase_url = \"https://127.0.0.1:27125\"\n";
+        let config = Config::from_toml(Some(&text), Some("work"));
+        let rendered = render_profile("work", &config.as_ref().unwrap().profiles["work"], &config, true);
+        assert!(rendered.starts_with("★ work\n"), "{}", rendered);
+        assert!(rendered.contains("llm.provider = openai"), "{}", rendered);
+        assert!(rendered.contains("llm.openai.api_key = [REDACTED]") && !rendered.contains("sk-secret"), "{}", rendered);
+        assert!(rendered.contains("obsidian: https://127.0.0.1:27125"), "{}", rendered);
+        assert!(rendered.contains("profiles/work"), "{}", rendered);
+
+        let json = redacted(serde_json::to_value(config.unwrap()).unwrap());
+        assert_eq!(json["llm"]["openai"]["api_key"], "[REDACTED]");
+        assert_eq!(json["profiles"]["work"]["llm"]["openai"]["api_key"], "[REDACTED]");
+    }
+}
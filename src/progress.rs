@@ -0,0 +1,143 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Set by `--quiet` for the whole process
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Turn off spinners and progress bars from now on
+pub fn force_quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+/// Progress is drawn only on a terminal, and never with `--quiet`
+pub fn enabled() -> bool {
+    !QUIET.load(Ordering::Relaxed) && io::stdout().is_terminal()
+}
+
+/// Told about each item of a batch as it completes. The batch functions take one so the
+/// library doesn't depend on how (or whether) progress is shown.
+pub trait BatchProgress: Send + Sync {
+    /// The batch is about to process `total` items
+    fn start(&self, _total: usize) {}
+    /// One more item is done
+    fn advance(&self, _item: &str) {}
+    /// Print a line without breaking the progress display
+    fn println(&self, line: &str) {
+        println!("{}", line);
+    }
+    fn finish(&self) {}
+}
+
+/// Reports nothing; for tests, scripts and callers that print their own output
+pub struct NoProgress;
+
+impl BatchProgress for NoProgress {}
+
+/// A progress bar with an ETA, hidden when `enabled()` is false
+pub struct Bar {
+    bar: ProgressBar,
+    label: String,
+}
+
+/// A progress bar for a batch, e.g. `bar("Embedding")`
+pub fn bar(label: &str) -> Bar {
+    let bar = if enabled() { ProgressBar::with_draw_target(Some(0), ProgressDrawTarget::stdout()) } else { ProgressBar::hidden() };
+    bar.set_style(
+        ProgressStyle::with_template("{prefix} [{bar:30}] {pos}/{len} (ETA {eta}) {wide_msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> "),
+    );
+    bar.set_prefix(label.to_string());
+    Bar { bar, label: label.to_string() }
+}
+
+impl BatchProgress for Bar {
+    fn start(&self, total: usize) {
+        self.bar.set_length(total as u64);
+        self.bar.set_position(0);
+        self.bar.enable_steady_tick(Duration::from_millis(250));
+    }
+
+    fn advance(&self, item: &str) {
+        self.bar.set_message(item.to_string());
+        self.bar.inc(1);
+    }
+
+    fn println(&self, line: &str) {
+        if self.bar.is_hidden() {
+            println!("{}", line);
+        } else {
+            self.bar.println(line);
+        }
+    }
+
+    fn finish(&self) {
+        self.bar.finish_and_clear();
+        log::debug!("{}: {} item(s) done", self.label, self.bar.position());
+    }
+}
+
+impl Drop for Bar {
+    fn drop(&mut self) {
+        if !self.bar.is_finished() {
+            self.bar.finish_and_clear();
+        }
+    }
+}
+
+/// A spinner for a single slow call such as an LLM request. It is cleared when `finish` is
+/// called or when it is dropped, so an early return or `?` never leaves it on screen.
+pub struct Spinner {
+    bar: Option<ProgressBar>,
+}
+
+impl Spinner {
+    pub fn start(message: &str) -> Self {
+        if !enabled() {
+            return Self { bar: None };
+        }
+        let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stdout());
+        bar.set_style(ProgressStyle::with_template("{spinner} {msg}").unwrap_or_else(|_| ProgressStyle::default_spinner()));
+        bar.set_message(message.to_string());
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Self { bar: Some(bar) }
+    }
+
+    /// Clear the spinner, e.g. before the first streamed chunk is printed
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hidden_progress_still_counts_and_clears() {
+        let spinner = Spinner { bar: Some(ProgressBar::hidden()) };
+        spinner.finish();
+        assert!(spinner.bar.as_ref().unwrap().is_finished());
+        // Clearing again when it's dropped is harmless
+        drop(spinner);
+
+        let progress = Bar { bar: ProgressBar::hidden(), label: "Embedding".to_string() };
+        progress.start(3);
+        progress.advance("Notes/a.md");
+        progress.advance("Notes/b.md");
+        assert_eq!(progress.bar.position(), 2);
+        assert_eq!(progress.bar.length(), Some(3));
+        progress.finish();
+        assert!(progress.bar.is_finished());
+    }
+}
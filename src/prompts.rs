@@ -0,0 +1,394 @@
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::cli::{PromptsAction, PromptsArgs};
+
+/// A prompt the code sends to the model, overridable by key in `prompts.toml`
+pub struct BuiltinPrompt {
+    pub key: &'static str,
+    pub description: &'static str,
+    pub template: &'static str,
+    /// Placeholders an override must keep
+    pub required: &'static [&'static str],
+    /// Placeholders an override may use as well
+    pub optional: &'static [&'static str],
+}
+
+impl BuiltinPrompt {
+    fn accepts(&self, name: &str) -> bool {
+        self.required.contains(&name) || self.optional.contains(&name)
+    }
+}
+
+pub const BUILTIN_PROMPTS: &[BuiltinPrompt] = &[
+    BuiltinPrompt {
+        key: "analysis",
+        description: "Note analysis: themes, sentiment, entities, concepts and summary as JSON",
+        template: "Please analyze the following content and return a JSON response with the following structure:\n\n{\n{{fields}}\n}\n\n{{focus}}\n{{truncation}}\nContent to analyze:\n{{content}}\n",
+        required: &["content"],
+        optional: &["fields", "focus", "truncation", "max_themes", "max_entities", "max_concepts", "reading_time"],
+    },
+    BuiltinPrompt {
+        key: "analysis_summary",
+        description: "Summary of a long note from the summaries of the chunks it was analyzed in",
+        template: "These are summaries of consecutive parts of one long note, in order:\n\n{{summaries}}\n\nWrite a brief summary of the whole note. Reply with the summary only.",
+        required: &["summaries"],
+        optional: &[],
+    },
+    BuiltinPrompt {
+        key: "template_generation",
+        description: "A new note template as JSON, from its type and the patterns found in the vault",
+        template: r#"Generate a {{template_type}} template for note-taking. The template should be {{complexity}} complexity.
+
+{{details}}
+Return a JSON object with this structure:
+{
+  "id": "unique-template-id",
+  "name": "Template Name",
+  "description": "Template description",
+  "category": "{{template_type}}",
+  "components": [
+    {"type": "Text", "content": "Static text"},
+    {"type": "Placeholder", "name": "field_name", "hint": "Description", "required": true},
+    {"type": "AiSuggestion", "prompt": "Generate suggestion for...", "fallback": "Default text"}
+  ],
+  "frontmatter_fields": [
+    {
+      "name": "field_name",
+      "field_type": "string",
+      "default_value": "default",
+      "required": true,
+      "description": "Field description"
+    }
+  ],
+  "tags": ["template", "{{template_type}}"]
+}
+"#,
+        required: &["template_type"],
+        optional: &["complexity", "details", "topic", "context", "required_fields"],
+    },
+    BuiltinPrompt {
+        key: "template_section",
+        description: "System prompt for filling in an AI section of a note template",
+        template: "You are filling in a section of the '{{template_name}}' note template. Reply with the section text only.",
+        required: &[],
+        optional: &["template_name"],
+    },
+    BuiltinPrompt {
+        key: "suggest_continuation",
+        description: "Writing suggestions: ways to continue the text",
+        template: "Continue writing this content in a natural way. Context: {{context}}\n\nProvide 2-3 different continuation options, each on a separate line.",
+        required: &["context"],
+        optional: &[],
+    },
+    BuiltinPrompt {
+        key: "suggest_completion",
+        description: "Writing suggestions: the end of an unfinished sentence",
+        template: "Complete this text naturally: {{context}}\n\nProvide only the completion text, not the full sentence.",
+        required: &["context"],
+        optional: &[],
+    },
+    BuiltinPrompt {
+        key: "suggest_headings",
+        description: "Writing suggestions: headings for the next section",
+        template: "Based on this content context, suggest 2-3 appropriate headings for the next section: {{context}}\n\nProvide only the heading text, one per line.",
+        required: &["context"],
+        optional: &[],
+    },
+    BuiltinPrompt {
+        key: "agenda",
+        description: "Meeting agenda as JSON, from the meeting and related notes",
+        template: r#"Write the agenda for this meeting.
+
+Title: {{title}}
+Duration: {{duration_minutes}} minutes
+Attendees: {{attendees}}
+Description: {{description}}
+
+Related notes:
+{{related_notes}}
+
+Respond with JSON only, in this shape:
+{"meeting_type": "OneOnOne|TeamStandup|ProjectReview|Brainstorming|Interview|Presentation|Training|ClientCall|Strategic",
+"objectives": ["..."],
+"items": [{"title": "...", "description": "...", "duration_minutes": 10, "type": "discussion|decision|information|action|review|brainstorm|presentation", "owner": "attendee or null", "expected_outcome": "...", "discussion_points": ["..."]}],
+"preparation": [{"title": "...", "description": "...", "url": null, "required": true, "reading_minutes": 5}],
+"success_criteria": ["..."],
+"follow_up": ["..."]}
+The item durations must add up to {{duration_minutes}} minutes. Use the related notes for topics and preparation material where they fit."#,
+        required: &["title", "duration_minutes"],
+        optional: &["attendees", "description", "related_notes"],
+    },
+];
+
+pub fn builtin(key: &str) -> Option<&'static BuiltinPrompt> {
+    BUILTIN_PROMPTS.iter().find(|prompt| prompt.key == key)
+}
+
+pub fn default_prompts_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".config")
+        .join("arrowhead")
+        .join("prompts.toml")
+}
+
+/// The `{{name}}` placeholders in `template`, in order, each once. Braces around anything
+/// but a lowercase name (JSON, say) are left alone.
+pub fn placeholders(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        let after = &rest[open + 2..];
+        match after.find("}}") {
+            Some(close) if is_placeholder_name(after[..close].trim()) => {
+                let name = after[..close].trim().to_string();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+                rest = &after[close + 2..];
+            }
+            _ => rest = &rest[open + 1..],
+        }
+    }
+    names
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Replace each `{{name}}` with its value in one pass, so values that contain braces are
+/// inserted as they are; placeholders without a value are left in place
+pub fn interpolate(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        let after = &rest[open + 2..];
+        let value = after.find("}}").and_then(|close| {
+            let name = after[..close].trim();
+            vars.iter().find(|(key, _)| *key == name).map(|(_, value)| (*value, close))
+        });
+        match value {
+            Some((value, close)) => {
+                result.push_str(&rest[..open]);
+                result.push_str(value);
+                rest = &after[close + 2..];
+            }
+            None => {
+                result.push_str(&rest[..open + 1]);
+                rest = &rest[open + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Check an override of `key`: the prompt must exist, keep its required placeholders and
+/// use no placeholder it isn't given
+pub fn validate(key: &str, template: &str) -> Result<()> {
+    let Some(prompt) = builtin(key) else {
+        let keys: Vec<&str> = BUILTIN_PROMPTS.iter().map(|p| p.key).collect();
+        bail!("unknown prompt '{}'; prompts are {}", key, keys.join(", "));
+    };
+    let used = placeholders(template);
+    if let Some(unknown) = used.iter().find(|name| !prompt.accepts(name)) {
+        let available: Vec<String> = prompt.required.iter().chain(prompt.optional).map(|name| format!("{{{{{}}}}}", name)).collect();
+        bail!("prompt '{}' uses {{{{{}}}}}, which it isn't given; it can use {}", key, unknown, available.join(", "));
+    }
+    if let Some(missing) = prompt.required.iter().find(|name| !used.iter().any(|used| used == *name)) {
+        bail!("prompt '{}' must contain {{{{{}}}}}", key, missing);
+    }
+    Ok(())
+}
+
+/// Built-in prompts with the overrides from `prompts.toml` on top
+#[derive(Debug, Clone, Default)]
+pub struct PromptSet {
+    overrides: HashMap<String, String>,
+}
+
+impl PromptSet {
+    /// Overrides keyed by prompt, e.g. `agenda = """..."""`; every one is validated
+    pub fn parse(text: &str) -> Result<Self> {
+        let table: BTreeMap<String, toml::Value> = toml::from_str(text).context("not valid TOML")?;
+        let mut overrides = HashMap::new();
+        for (key, value) in table {
+            let Some(template) = value.as_str() else {
+                bail!("prompt '{}' must be a string", key);
+            };
+            validate(&key, template)?;
+            overrides.insert(key, template.to_string());
+        }
+        Ok(Self { overrides })
+    }
+
+    /// The overrides in `path`; none when it doesn't exist
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid prompt override in {}", path.display()))
+    }
+
+    pub fn is_overridden(&self, key: &str) -> bool {
+        self.overrides.contains_key(key)
+    }
+
+    /// The template used for `key`: its override, else the built-in
+    pub fn template(&self, key: &str) -> Option<&str> {
+        self.overrides.get(key).map(String::as_str).or_else(|| builtin(key).map(|p| p.template))
+    }
+
+    pub fn render(&self, key: &str, vars: &[(&str, &str)]) -> String {
+        interpolate(self.template(key).unwrap_or_default(), vars)
+    }
+}
+
+static PROMPTS: OnceLock<PromptSet> = OnceLock::new();
+
+/// Read `prompts.toml` and use its overrides for the rest of the process. Fails, naming the
+/// prompt, when an override is invalid.
+pub fn load_overrides() -> Result<()> {
+    let prompts = PromptSet::load(&default_prompts_path())?;
+    let _ = PROMPTS.set(prompts);
+    Ok(())
+}
+
+/// The template used for `key` in this process, e.g. to tell when cached output came from
+/// another prompt
+pub fn template(key: &str) -> &'static str {
+    match PROMPTS.get() {
+        Some(prompts) => prompts.template(key).unwrap_or_default(),
+        None => builtin(key).map(|p| p.template).unwrap_or_default(),
+    }
+}
+
+/// The prompt `key` with `vars` filled in, overridden if `load_overrides` found an override
+pub fn render(key: &str, vars: &[(&str, &str)]) -> String {
+    match PROMPTS.get() {
+        Some(prompts) => prompts.render(key, vars),
+        None => interpolate(builtin(key).map(|p| p.template).unwrap_or_default(), vars),
+    }
+}
+
+/// `text` as the override of `key` in the prompts file, keeping the file's other lines;
+/// the built-in text, or `None`, removes the override
+fn write_override(path: &Path, key: &str, text: Option<&str>) -> Result<()> {
+    let original = if path.exists() { fs::read_to_string(path)? } else { String::new() };
+    let mut doc: toml_edit::DocumentMut = original.parse()
+        .map_err(|e| anyhow::anyhow!("{} is not valid TOML: {}", path.display(), e))?;
+    match text {
+        Some(text) if Some(text) != builtin(key).map(|p| p.template) => {
+            doc.insert(key, toml_edit::value(text));
+        }
+        _ => {
+            doc.remove(key);
+        }
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    crate::cache::write_atomic(path, doc.to_string().as_bytes())
+}
+
+pub async fn handle_prompts_command(args: PromptsArgs) -> Result<()> {
+    let path = default_prompts_path();
+    match args.action {
+        PromptsAction::List => {
+            let prompts = PromptSet::load(&path)?;
+            let mut output = Vec::new();
+            for prompt in BUILTIN_PROMPTS {
+                let overridden = prompts.is_overridden(prompt.key);
+                println!("{} {:<22} {}", if overridden { "✎" } else { "•" }, prompt.key, prompt.description);
+                output.push(serde_json::json!({ "key": prompt.key, "description": prompt.description, "overridden": overridden }));
+            }
+            println!("\n✎ = overridden in {}", path.display());
+            crate::script::publish_output(serde_json::Value::Array(output));
+        }
+        PromptsAction::Show { key, builtin: show_builtin } => {
+            let prompt = builtin(&key).ok_or_else(|| validate(&key, "").unwrap_err())?;
+            let text = if show_builtin { prompt.template.to_string() } else { PromptSet::load(&path)?.template(&key).unwrap_or_default().to_string() };
+            let mut names: Vec<String> = prompt.required.iter().map(|name| format!("{{{{{}}}}} (required)", name)).collect();
+            names.extend(prompt.optional.iter().map(|name| format!("{{{{{}}}}}", name)));
+            println!("{}", text);
+            println!("\n--- {}: {}", prompt.key, if names.is_empty() { "no placeholders".to_string() } else { names.join(", ") });
+            crate::script::publish_output(serde_json::json!({ "key": prompt.key, "template": text, "required": prompt.required, "optional": prompt.optional }));
+        }
+        PromptsAction::Edit { key, reset } => {
+            builtin(&key).ok_or_else(|| validate(&key, "").unwrap_err())?;
+            if reset {
+                write_override(&path, &key, None)?;
+                println!("✅ Prompt '{}' is back to the built-in", key);
+                return Ok(());
+            }
+            // The file may be broken; start from the built-in then
+            let current = PromptSet::load(&path).ok()
+                .and_then(|prompts| prompts.template(&key).map(str::to_string))
+                .unwrap_or_else(|| builtin(&key).map(|p| p.template.to_string()).unwrap_or_default());
+            let Some(edited) = crate::notes::edit_note_content(&current).await? else {
+                println!("Prompt '{}' not changed", key);
+                return Ok(());
+            };
+            validate(&key, &edited).context("Prompt not saved")?;
+            write_override(&path, &key, Some(&edited))?;
+            println!("✅ Saved prompt '{}' to {}", key, path.display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholders_are_filled_once_and_json_is_left_alone() {
+        let template = "Summarize {{ content }} in {{max_themes}} themes as {\"themes\": []}, {{content}} {{unknown}}";
+        assert_eq!(placeholders(template), ["content", "max_themes", "unknown"]);
+        let rendered = interpolate(template, &[("content", "a {{max_themes}} b"), ("max_themes", "3")]);
+        assert_eq!(rendered, "Summarize a {{max_themes}} b in 3 themes as {\"themes\": []}, a {{max_themes}} b {{unknown}}");
+    }
+
+    #[test]
+    fn test_builtins_use_only_their_own_placeholders() {
+        for prompt in BUILTIN_PROMPTS {
+            validate(prompt.key, prompt.template).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_overrides_are_validated_by_key() {
+        let prompts = PromptSet::parse("suggest_headings = \"Titres pour : {{context}}\"\n").unwrap();
+        assert!(prompts.is_overridden("suggest_headings"));
+        assert_eq!(prompts.render("suggest_headings", &[("context", "jardin")]), "Titres pour : jardin");
+        assert!(prompts.render("suggest_completion", &[("context", "x")]).starts_with("Complete this text naturally: x"));
+
+        let error = |text: &str| format!("{:#}", PromptSet::parse(text).unwrap_err());
+        assert!(error("agenda = \"Agenda for {{title}}\"").contains("prompt 'agenda' must contain {{duration_minutes}}"));
+        assert!(error("analysis = \"{{content}} {{contnet}}\"").contains("prompt 'analysis' uses {{contnet}}"));
+        assert!(error("summary = \"{{content}}\"").contains("unknown prompt 'summary'"));
+        assert!(error("analysis = 3").contains("prompt 'analysis' must be a string"));
+    }
+
+    #[test]
+    fn test_overrides_matching_the_builtin_are_removed() {
+        let path = std::env::temp_dir().join(format!("arrowhead-prompts-{}", uuid::Uuid::new_v4())).join("prompts.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "# mine\nsuggest_headings = \"Titres : {{context}}\"\n").unwrap();
+
+        write_override(&path, "agenda", Some("Agenda {{title}} {{duration_minutes}}")).unwrap();
+        let prompts = PromptSet::load(&path).unwrap();
+        assert!(prompts.is_overridden("agenda") && prompts.is_overridden("suggest_headings"));
+
+        write_override(&path, "agenda", builtin("agenda").map(|p| p.template)).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# mine\n") && !text.contains("agenda"), "{}", text);
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
@@ -0,0 +1,524 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::cli::IndexArgs;
+use crate::config::Config;
+use crate::embedding_provider::{create_embedding_providers, embedding_model_id};
+use crate::cache::CachePaths;
+use crate::obsidian_adapter::{content_cache_key, ObsidianAdapter};
+use crate::opt_out::{is_opted_out, OptOutPolicy};
+use crate::progress;
+use crate::report::{DetailLevel, Report, ReportView};
+use crate::token_counter;
+use crate::usage::UsageTracker;
+use crate::vault_transaction::{FileMeta, ListedFile, VaultStore, DEFAULT_READ_CONCURRENCY};
+
+/// Modification times closer than this to the snapshot may be followed by another write in
+/// the same tick of the server's clock, so they are not trusted
+const MTIME_GRANULARITY_MS: i64 = 2_000;
+
+/// Size and modification time of every indexed file at the last index run, kept next to
+/// the vector database
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetadataSnapshot {
+    pub taken_at: DateTime<Utc>,
+    pub files: HashMap<String, FileMeta>,
+}
+
+impl Default for MetadataSnapshot {
+    fn default() -> Self {
+        Self { taken_at: DateTime::<Utc>::MIN_UTC, files: HashMap::new() }
+    }
+}
+
+impl MetadataSnapshot {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path).context("Failed to read index metadata snapshot")?;
+        serde_json::from_str(&data).context("Failed to parse index metadata snapshot")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        crate::cache::write_atomic(path, serde_json::to_string(self)?.as_bytes()).context("Failed to write index metadata snapshot")
+    }
+}
+
+/// Whether a file can be skipped without fetching it: its size and modification time match
+/// the snapshot (a changed size always counts as a change), and the modification time is
+/// neither in the future nor so close to the snapshot that a later write could share it
+pub fn metadata_unchanged(current: &FileMeta, snapshot: &MetadataSnapshot, path: &str, now: DateTime<Utc>) -> bool {
+    let Some(previous) = snapshot.files.get(path) else { return false };
+    previous == current
+        && current.modified <= now.timestamp_millis()
+        && current.modified + MTIME_GRANULARITY_MS < snapshot.taken_at.timestamp_millis()
+}
+
+/// How each listed note was handled by an index run
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct IndexSummary {
+    pub listed: usize,
+    /// Not fetched: size and modification time matched the snapshot
+    pub skipped_by_metadata: usize,
+    /// Fetched, but the content hash matched the stored embedding
+    pub hashed_unchanged: usize,
+    /// Fetched and changed (or never embedded)
+    pub changed: usize,
+    pub failed: usize,
+    /// Not fetched: matched an `[opt_outs] exclude` pattern
+    pub excluded: usize,
+}
+
+/// Notes whose content needs embedding, and the snapshot to keep once they are embedded
+#[derive(Debug)]
+pub struct IndexScan {
+    /// Every listed note
+    pub paths: Vec<String>,
+    pub changed: Vec<(String, String)>,
+    pub snapshot: MetadataSnapshot,
+    pub summary: IndexSummary,
+}
+
+/// Every markdown note under `folders` (`""` for the whole vault), recursively, with its listed metadata
+pub(crate) async fn walk<S: VaultStore + ?Sized>(store: &S, folders: &[String]) -> Vec<(String, Option<FileMeta>)> {
+    let mut pending: Vec<String> = folders.iter().map(|f| f.trim_end_matches('/').to_string()).collect();
+    let mut files = Vec::new();
+    while let Some(folder) = pending.pop() {
+        let Ok(entries) = store.list_folder(&folder).await else { continue };
+        for ListedFile { name, meta } in entries {
+            let name_only = name.trim_end_matches('/');
+            let path = if folder.is_empty() { name_only.to_string() } else { format!("{}/{}", folder, name_only) };
+            if name.ends_with('/') {
+                pending.push(path);
+            } else if name.ends_with(".md") {
+                files.push((path, meta));
+            }
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    files
+}
+
+/// Find the notes that changed since the last run. Notes whose listing metadata matches
+/// `snapshot` (and that have an embedding) are not fetched; the rest are fetched and
+/// compared to `hashes`, the stored content hash per path. `full` ignores the snapshot.
+/// Notes `policy` excludes are counted and never fetched.
+pub async fn scan_changes<S: VaultStore + ?Sized>(
+    store: &S,
+    folders: &[String],
+    snapshot: &MetadataSnapshot,
+    hashes: &HashMap<String, String>,
+    full: bool,
+    policy: &OptOutPolicy,
+) -> IndexScan {
+    let now = Utc::now();
+    let listed = walk(store, folders).await;
+    let mut summary = IndexSummary { listed: listed.len(), ..Default::default() };
+    let mut next = MetadataSnapshot { taken_at: now, files: HashMap::new() };
+
+    let mut to_fetch = Vec::new();
+    for (path, meta) in &listed {
+        match meta {
+            _ if policy.is_excluded(path) => summary.excluded += 1,
+            Some(meta) if !full && hashes.contains_key(path) && metadata_unchanged(meta, snapshot, path, now) => {
+                summary.skipped_by_metadata += 1;
+                next.files.insert(path.clone(), *meta);
+            }
+            _ => to_fetch.push(path.clone()),
+        }
+    }
+
+    let mut changed = Vec::new();
+    for (path, content) in store.fetch_many(&to_fetch, DEFAULT_READ_CONCURRENCY).await {
+        let Ok(content) = content else {
+            summary.failed += 1;
+            continue;
+        };
+        let body = ObsidianAdapter::parse_markdown_file(&content).map_or(content.clone(), |file| file.content);
+        if let Some(Some(meta)) = listed.iter().find(|(p, _)| *p == path).map(|(_, meta)| meta) {
+            next.files.insert(path.clone(), *meta);
+        }
+        if hashes.get(&path) == Some(&content_cache_key(&body)) {
+            summary.hashed_unchanged += 1;
+        } else {
+            summary.changed += 1;
+            changed.push((path, content));
+        }
+    }
+    let paths = listed.into_iter().map(|(path, _)| path).collect();
+    IndexScan { paths, changed, snapshot: next, summary }
+}
+
+/// An adapter that embeds notes with the configured embedding provider, switching to the
+/// fallback provider when its quota runs out. The vector database isn't loaded yet.
+pub fn embedding_indexer(config: &Config) -> Result<ObsidianAdapter> {
+    let mut indexer = ObsidianAdapter::new(Some(config.obsidian.base_url.clone()), config.obsidian.api_key.clone());
+    indexer.set_cache_settings(&config.cache);
+    indexer.set_embedding_storage_config(config.embeddings.clone());
+    indexer.set_opt_out_policy(config.opt_outs.clone());
+    indexer.set_batch_concurrency(config.index.concurrency);
+    let (primary, fallback) = create_embedding_providers(config)?;
+    indexer.set_embedding_providers(primary, fallback);
+    Ok(indexer)
+}
+
+/// Notes embedded with the fallback because the primary ran out of quota
+#[derive(Debug, Clone, Serialize)]
+struct Failover {
+    reason: String,
+    model: String,
+    notes: Vec<String>,
+}
+
+/// What an index run did with the changed notes
+#[derive(Debug, Default)]
+struct IndexOutcome {
+    embedded: Vec<String>,
+    opted_out: Vec<String>,
+    /// (path, error) of notes that could not be embedded
+    failures: Vec<(String, String)>,
+    failover: Option<Failover>,
+    /// Notes moved from another model back to the primary
+    reunified: Vec<String>,
+    /// Documents still embedded with a model other than the primary after the run
+    other_models: usize,
+}
+
+/// Summary: the counts on one line, and any switch to the fallback model. Normal: also the
+/// notes that failed and those moved back to the primary model. Detail: also every note
+/// re-embedded (or, with `--dry-run`, to re-embed) and those skipped by an opt-out.
+fn index_report(summary: &IndexSummary, changed: &[String], outcome: &IndexOutcome, dry_run: bool) -> Report {
+    let failed = summary.failed + outcome.failures.len();
+    let embedded = if dry_run { summary.changed } else { outcome.embedded.len() };
+    let opted_out = outcome.opted_out.len();
+    let failures: String = outcome.failures.iter().map(|(path, e)| format!("⚠️  {}: {}\n", path, e)).collect();
+    let headline = format!(
+        "{} {} note(s): {} skipped via metadata, {} hashed and unchanged, {} {}{}{}{}\n",
+        if dry_run { "Checked" } else { "Indexed" },
+        summary.listed,
+        summary.skipped_by_metadata,
+        summary.hashed_unchanged,
+        embedded,
+        if dry_run { "to re-embed" } else { "re-embedded" },
+        if opted_out > 0 { format!(", {} opted out", opted_out) } else { String::new() },
+        if summary.excluded > 0 { format!(", {} excluded", summary.excluded) } else { String::new() },
+        if failed > 0 { format!(", {} failed", failed) } else { String::new() },
+    );
+    let mut report = Report::new()
+        .text(DetailLevel::Normal, failures)
+        .text(DetailLevel::Summary, headline)
+        .field(DetailLevel::Summary, "dry_run", dry_run)
+        .field(DetailLevel::Summary, "listed", summary.listed)
+        .field(DetailLevel::Summary, "skipped_by_metadata", summary.skipped_by_metadata)
+        .field(DetailLevel::Summary, "hashed_unchanged", summary.hashed_unchanged)
+        .field(DetailLevel::Summary, "changed", summary.changed)
+        .field(DetailLevel::Summary, "embedded", outcome.embedded.len())
+        .field(DetailLevel::Summary, "opted_out", opted_out)
+        .field(DetailLevel::Summary, "excluded", summary.excluded)
+        .field(DetailLevel::Summary, "failed", failed)
+        .field(DetailLevel::Normal, "failures", outcome.failures.iter()
+            .map(|(path, e)| serde_json::json!({ "path": path, "error": e }))
+            .collect::<Vec<_>>())
+        .field(DetailLevel::Detail, "changed_paths", changed)
+        .field(DetailLevel::Detail, "opted_out_paths", &outcome.opted_out)
+        .field(DetailLevel::Summary, "failover", &outcome.failover)
+        .field(DetailLevel::Normal, "reunified", outcome.reunified.len())
+        .field(DetailLevel::Normal, "other_model_documents", outcome.other_models);
+
+    if let Some(failover) = &outcome.failover {
+        report = report.text(DetailLevel::Summary, format!(
+            "⚠️  {}: {} note(s) embedded with {} instead. Searches query both models until later index runs re-embed them with the primary.\n",
+            failover.reason, failover.notes.len(), failover.model,
+        ));
+    }
+    if !outcome.reunified.is_empty() || (outcome.other_models > 0 && outcome.failover.is_none()) {
+        report = report.text(DetailLevel::Normal, format!(
+            "Re-embedded {} note(s) with the primary model; {} still on another model\n",
+            outcome.reunified.len(), outcome.other_models,
+        ));
+    }
+
+    let listed = if dry_run { changed } else { &outcome.embedded[..] };
+    if !listed.is_empty() {
+        let mut out = format!("\n{}:\n", if dry_run { "To re-embed" } else { "Re-embedded" });
+        for path in listed {
+            out.push_str(&format!("  {}\n", path));
+        }
+        report = report.text(DetailLevel::Detail, out);
+    }
+    if !outcome.opted_out.is_empty() {
+        let mut out = String::from("\nOpted out of embedding:\n");
+        for path in &outcome.opted_out {
+            out.push_str(&format!("  {}\n", path));
+        }
+        report = report.text(DetailLevel::Detail, out);
+    }
+    report
+}
+
+pub async fn handle_index_command(args: IndexArgs, view: ReportView, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let mut indexer = embedding_indexer(&config)?;
+    indexer.load_vector_database()?;
+    if args.if_stale {
+        return reembed_stale(&mut indexer, adapter, args.dry_run, view).await;
+    }
+
+    let snapshot_file = CachePaths::for_config(&config).index_snapshot();
+    let snapshot = MetadataSnapshot::load(&snapshot_file).unwrap_or_else(|e| {
+        log::warn!("{}; indexing without the snapshot", e);
+        MetadataSnapshot::default()
+    });
+    let hashes: HashMap<String, String> = indexer.get_indexed_documents().into_iter()
+        .map(|doc| (doc.path.clone(), doc.content_hash.clone()))
+        .collect();
+    let mut scan = scan_changes(adapter, &config.index.folders, &snapshot, &hashes, args.full, &config.opt_outs).await;
+
+    let mut outcome = IndexOutcome::default();
+    if !args.dry_run {
+        // Refuse a batch the daily token limit can't cover before embedding any of it
+        let counter = token_counter::for_model(&embedding_model_id(&config));
+        let estimated = scan.changed.iter().map(|(_, content)| counter.count(content) as u64).sum();
+        UsageTracker::from_settings(&config.usage).check_batch(estimated)
+            .with_context(|| format!("Not indexing {} changed note(s)", scan.changed.len()))?;
+        let fetched = scan.changed.iter().map(|(path, content)| (path.clone(), Ok(content.clone()))).collect();
+        let progress = progress::bar("Embedding");
+        for (path, result) in indexer.batch_embed_contents(fetched, &progress).await {
+            match result {
+                Ok(_) => outcome.embedded.push(path),
+                Err(e) if is_opted_out(&e) => outcome.opted_out.push(path),
+                Err(e) => {
+                    // Leave it out of the snapshot so the next run fetches it again
+                    scan.snapshot.files.remove(&path);
+                    outcome.failures.push((path, e.to_string()));
+                }
+            }
+        }
+        if indexer.embedding_failover().is_none() {
+            outcome.reunified = reunify(&mut indexer, adapter, config.embeddings.reunify_batch).await;
+        }
+        if let Some(limit) = indexer.embedding_failover() {
+            let model = indexer.fallback_embedding_model().unwrap_or_default();
+            let models: HashMap<&str, &str> = indexer.get_indexed_documents().into_iter()
+                .map(|doc| (doc.path.as_str(), doc.model.as_str()))
+                .collect();
+            let notes = outcome.embedded.iter()
+                .filter(|path| models.get(path.as_str()) == Some(&model.as_str()))
+                .cloned()
+                .collect();
+            outcome.failover = Some(Failover { reason: limit.to_string(), model, notes });
+        }
+        outcome.other_models = indexer.minority_paths(usize::MAX).len();
+        update_note_index(&mut indexer, adapter, &scan.paths).await;
+        indexer.save_vector_database()?;
+        scan.snapshot.save(&snapshot_file)?;
+    }
+
+    let summary = &scan.summary;
+    let changed: Vec<String> = scan.changed.iter().map(|(path, _)| path.clone()).collect();
+    let failed = summary.failed + outcome.failures.len();
+    crate::script::publish_output(serde_json::json!({
+        "dry_run": args.dry_run,
+        "listed": summary.listed,
+        "skipped_by_metadata": summary.skipped_by_metadata,
+        "hashed_unchanged": summary.hashed_unchanged,
+        "changed": summary.changed,
+        "embedded": outcome.embedded.len(),
+        "opted_out": outcome.opted_out.len(),
+        "excluded": summary.excluded,
+        "failed": failed,
+        "failover": outcome.failover,
+        "reunified": outcome.reunified.len(),
+    }));
+    index_report(summary, &changed, &outcome, args.dry_run).print(view)?;
+    if failed > 0 && outcome.embedded.is_empty() && summary.changed > 0 && !args.dry_run {
+        bail!("No changed note could be embedded");
+    }
+    Ok(())
+}
+
+/// Bring the link graph and term index up to date with the listed notes. Changed notes were
+/// read into them as they were embedded; notes either has never seen, as after an upgrade,
+/// are fetched for their links and terms, and notes no longer listed are dropped.
+async fn update_note_index(indexer: &mut ObsidianAdapter, adapter: &ObsidianAdapter, paths: &[String]) {
+    // An empty listing is more likely an unreachable vault than an empty one
+    if paths.is_empty() {
+        return;
+    }
+    let unseen: Vec<String> = paths.iter()
+        .filter(|path| !indexer.link_graph().contains(path) || !indexer.term_index().contains(path))
+        .cloned()
+        .collect();
+    for (path, content) in adapter.fetch_many(&unseen, DEFAULT_READ_CONCURRENCY).await {
+        match content {
+            Ok(content) => {
+                indexer.update_note_index(&path, &content);
+            }
+            Err(e) => log::warn!("Could not read the links and terms of {}: {:#}", path, e),
+        }
+    }
+    let listed: HashSet<&str> = paths.iter().map(String::as_str).collect();
+    indexer.retain_note_index(|path| listed.contains(path));
+}
+
+/// `index --if-stale`: re-embed every note whose embedding another model made, leaving the
+/// rest of the vault alone
+async fn reembed_stale(indexer: &mut ObsidianAdapter, adapter: &ObsidianAdapter, dry_run: bool, view: ReportView) -> Result<()> {
+    let stale = indexer.minority_paths(usize::MAX);
+    let model = indexer.embedding_model().to_string();
+    let reembedded = if dry_run || stale.is_empty() { Vec::new() } else { reunify(indexer, adapter, stale.len()).await };
+    let left = indexer.stale_embedding_count();
+    // Notes deleted since were dropped from the index, which needs saving too
+    if !dry_run && left < stale.len() {
+        indexer.save_vector_database()?;
+    }
+
+    let headline = if stale.is_empty() {
+        format!("No stale embeddings: every indexed note was embedded with {}\n", model)
+    } else if dry_run {
+        format!("{} note(s) embedded with another model would be re-embedded with {}\n", stale.len(), model)
+    } else {
+        format!("Re-embedded {} of {} stale note(s) with {}; {} left\n", reembedded.len(), stale.len(), model, left)
+    };
+    let mut report = Report::new()
+        .text(DetailLevel::Summary, headline)
+        .field(DetailLevel::Summary, "dry_run", dry_run)
+        .field(DetailLevel::Summary, "model", &model)
+        .field(DetailLevel::Summary, "stale", stale.len())
+        .field(DetailLevel::Summary, "reembedded", reembedded.len())
+        .field(DetailLevel::Summary, "left", left)
+        .field(DetailLevel::Detail, "stale_paths", &stale);
+    if let Some(limit) = indexer.embedding_failover() {
+        report = report.text(DetailLevel::Summary, format!("⚠️  {}: stopped early; run it again once the quota recovers\n", limit));
+    }
+    let listed = if dry_run { &stale } else { &reembedded };
+    if !listed.is_empty() {
+        let mut out = format!("\n{}:\n", if dry_run { "To re-embed" } else { "Re-embedded" });
+        for path in listed {
+            out.push_str(&format!("  {}\n", path));
+        }
+        report = report.text(DetailLevel::Detail, out);
+    }
+    crate::script::publish_output(report.to_json(view.level));
+    report.print(view)?;
+    if !dry_run && left > 0 && reembedded.is_empty() {
+        bail!("No stale note could be re-embedded");
+    }
+    Ok(())
+}
+
+/// Re-embed up to `batch` notes of other models with the primary, oldest first, so an index
+/// that switched to the fallback converges back a batch per run once the quota recovers.
+/// Stops early if the primary runs out again. Notes deleted since are dropped from the index.
+async fn reunify(indexer: &mut ObsidianAdapter, adapter: &ObsidianAdapter, batch: usize) -> Vec<String> {
+    let mut reunified = Vec::new();
+    for path in indexer.minority_paths(batch) {
+        if indexer.embedding_failover().is_some() {
+            break;
+        }
+        let result = match adapter.read_file(&path).await {
+            Ok(Some(content)) => indexer.embed_content(&path, &content).await,
+            Ok(None) => indexer.remove_document_embedding(&path).map(|_| false),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(true) if indexer.embedding_failover().is_none() => reunified.push(path),
+            Ok(_) => {}
+            Err(e) => log::warn!("Could not re-embed {} with the primary model: {:#}", path, e),
+        }
+    }
+    reunified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_transaction::testing::FakeVault;
+    use chrono::Duration;
+
+    fn note(i: usize) -> (String, String) {
+        (format!("Notes/{}/note-{:04}.md", i % 10, i), format!("---\ntags: [n{}]\n---\nBody of note {}\n", i, i))
+    }
+
+    fn hashes_of(vault: &FakeVault, paths: &[String]) -> HashMap<String, String> {
+        paths.iter()
+            .map(|path| {
+                let content = vault.get(path).unwrap();
+                (path.clone(), content_cache_key(&ObsidianAdapter::parse_markdown_file(&content).unwrap().content))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_fast_path_fetches_only_changed_notes() {
+        let notes: Vec<(String, String)> = (0..1000).map(note).collect();
+        let entries: Vec<(&str, &str)> = notes.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect();
+        let vault = FakeVault::new(&entries).with_listing_metadata();
+        let folders = vec!["Notes".to_string()];
+        let paths: Vec<String> = notes.iter().map(|(p, _)| p.clone()).collect();
+
+        // First run: no snapshot, everything is fetched and needs embedding
+        let first = scan_changes(&vault, &folders, &MetadataSnapshot::default(), &HashMap::new(), false, &OptOutPolicy::default()).await;
+        assert_eq!(first.summary.changed, 1000);
+        assert_eq!(vault.reads(), 1000);
+        let hashes = hashes_of(&vault, &paths);
+
+        // Ten edits, half of them keeping the size
+        for i in (0..1000).step_by(100) {
+            let (path, content) = note(i);
+            let edited = if i % 200 == 0 { content.replace("Body", "Bodz") } else { format!("{}More.\n", content) };
+            vault.write_file(&path, &edited).await.unwrap();
+        }
+        let mut snapshot = first.snapshot;
+        // The snapshot was taken a while after those files were first written
+        snapshot.taken_at = Utc::now();
+        let before = vault.reads();
+        let second = scan_changes(&vault, &folders, &snapshot, &hashes, false, &OptOutPolicy::default()).await;
+        assert_eq!(vault.reads() - before, 10);
+        assert_eq!(second.summary, IndexSummary { listed: 1000, skipped_by_metadata: 990, hashed_unchanged: 0, changed: 10, failed: 0, excluded: 0 });
+
+        // --full ignores the snapshot and hashes everything
+        let full = scan_changes(&vault, &folders, &second.snapshot, &hashes, true, &OptOutPolicy::default()).await;
+        assert_eq!((full.summary.skipped_by_metadata, full.summary.hashed_unchanged, full.summary.changed), (0, 990, 10));
+    }
+
+    #[tokio::test]
+    async fn test_listings_without_metadata_fall_back_to_hashing() {
+        let vault = FakeVault::new(&[("Notes/a.md", "A\n"), ("Notes/b.md", "B\n")]);
+        let folders = vec!["Notes".to_string()];
+        let paths = vec!["Notes/a.md".to_string(), "Notes/b.md".to_string()];
+        let hashes = hashes_of(&vault, &paths);
+        let scan = scan_changes(&vault, &folders, &MetadataSnapshot::default(), &hashes, false, &OptOutPolicy::default()).await;
+        assert_eq!(scan.summary.hashed_unchanged, 2);
+        assert!(scan.snapshot.files.is_empty());
+    }
+
+    #[test]
+    fn test_metadata_edge_cases_count_as_changed() {
+        let now = Utc::now();
+        let old = (now - Duration::hours(1)).timestamp_millis();
+        let snapshot = MetadataSnapshot {
+            taken_at: now - Duration::minutes(5),
+            files: [("a.md".to_string(), FileMeta { size: 10, modified: old })].into_iter().collect(),
+        };
+        assert!(metadata_unchanged(&FileMeta { size: 10, modified: old }, &snapshot, "a.md", now));
+        // Same mtime, different size: a write within the clock's granularity
+        assert!(!metadata_unchanged(&FileMeta { size: 11, modified: old }, &snapshot, "a.md", now));
+        assert!(!metadata_unchanged(&FileMeta { size: 10, modified: old }, &snapshot, "b.md", now));
+
+        // Modified in the same tick the snapshot was taken: not trusted
+        let racy = snapshot.taken_at.timestamp_millis() - 500;
+        let snapshot = MetadataSnapshot { files: [("a.md".to_string(), FileMeta { size: 10, modified: racy })].into_iter().collect(), ..snapshot };
+        assert!(!metadata_unchanged(&FileMeta { size: 10, modified: racy }, &snapshot, "a.md", now));
+
+        // A clock running ahead of ours: not trusted either
+        let future = (now + Duration::hours(1)).timestamp_millis();
+        let snapshot = MetadataSnapshot { taken_at: now + Duration::hours(2), files: [("a.md".to_string(), FileMeta { size: 10, modified: future })].into_iter().collect() };
+        assert!(!metadata_unchanged(&FileMeta { size: 10, modified: future }, &snapshot, "a.md", now));
+    }
+}
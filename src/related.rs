@@ -0,0 +1,128 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeSet;
+
+use crate::backlinks::{link_mentions, note_name};
+use crate::cli::RelatedArgs;
+use crate::config::Config;
+use crate::managed_sections::{SectionEdit, SectionWrite};
+use crate::notes::link_key;
+use crate::obsidian_adapter::{ObsidianAdapter, SemanticSearchResult};
+use crate::reindex::embedding_indexer;
+
+const SECTION_NAME: &str = "related";
+const SECTION_HEADING: &str = "## Related Notes";
+
+/// Drop suggestions the note already links to in its body. Links inside Arrowhead's own
+/// sections don't count, so an earlier "Related Notes" list doesn't hide itself.
+pub fn unlinked(path: &str, content: &str, results: Vec<SemanticSearchResult>) -> Vec<SemanticSearchResult> {
+    let linked: BTreeSet<String> = link_mentions(path, content, &[]).into_iter().map(|m| m.target).collect();
+    results.into_iter().filter(|r| !linked.contains(&link_key(note_name(&r.path)))).collect()
+}
+
+/// The "Related Notes" section listing `results`, or its removal when there are none
+pub fn section_edit(results: &[SemanticSearchResult]) -> SectionEdit {
+    if results.is_empty() {
+        return SectionEdit::Remove { owner: SECTION_NAME.to_string(), id: SECTION_NAME.to_string() };
+    }
+    let body: String = results.iter().map(|r| format!("- [[{}]]\n", note_name(&r.path))).collect();
+    SectionEdit::Upsert(SectionWrite::new(SECTION_NAME, SECTION_NAME, &body).heading(SECTION_HEADING))
+}
+
+pub async fn handle_related_command(args: RelatedArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let path = if args.path.ends_with(".md") { args.path.clone() } else { format!("{}.md", args.path) };
+    let limit = args.limit.unwrap_or(config.related.limit);
+    let min_similarity = args.min_similarity.unwrap_or(config.related.min_similarity);
+    if !(0.0..=1.0).contains(&min_similarity) {
+        bail!("--min-similarity must be between 0 and 1");
+    }
+
+    let content = adapter.get_file(&path).await?;
+    let mut indexer = embedding_indexer(&config)?;
+    indexer.load_vector_database()?;
+    // Over-fetch so notes dropped for being linked already still leave `limit` suggestions
+    let results = indexer.related_notes(&path, &content, limit * 2, min_similarity).await?;
+    let mut results = unlinked(&path, &content, results);
+    results.truncate(limit);
+
+    if results.is_empty() {
+        println!("No unlinked notes above {:.2} similarity to {}.", min_similarity, path);
+    }
+    for result in &results {
+        println!("{:.3}  {}", result.similarity, result.path);
+        if !result.metadata.excerpt.is_empty() {
+            println!("       {}", result.metadata.excerpt.replace('\n', " "));
+        }
+    }
+    let related: Vec<serde_json::Value> = results.iter()
+        .map(|r| serde_json::json!({ "path": r.path, "similarity": r.similarity, "excerpt": r.metadata.excerpt }))
+        .collect();
+    let mut written = false;
+    if args.write {
+        match adapter.update_managed_section(&path, section_edit(&results)).await? {
+            Some(report) => {
+                println!("{}", report.summary());
+                written = report.is_committed();
+            }
+            None => println!("{} already lists these notes.", path),
+        }
+    }
+    crate::script::publish_output(serde_json::json!({ "path": path, "related": related, "written": written }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed_sections::upsert;
+    use crate::obsidian_adapter::DocumentMetadata;
+
+    fn result(path: &str, similarity: f32) -> SemanticSearchResult {
+        SemanticSearchResult {
+            path: path.to_string(),
+            similarity,
+            metadata: DocumentMetadata { title: String::new(), tags: Vec::new(), length: 0, excerpt: String::new(), modified_at: None, skeleton: None },
+            snippet: String::new(),
+            highlights: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_notes_linked_in_the_body_are_excluded() {
+        let content = "Builds on [[Ownership Rules|ownership]].\n";
+        let results = vec![result("Notes/Ownership Rules.md", 0.9), result("Notes/Lifetimes.md", 0.8)];
+        let kept = unlinked("Notes/Rust.md", content, results);
+        assert_eq!(kept.iter().map(|r| r.path.as_str()).collect::<Vec<_>>(), vec!["Notes/Lifetimes.md"]);
+    }
+
+    #[test]
+    fn test_section_is_replaced_not_duplicated() {
+        let SectionEdit::Upsert(first) = section_edit(&[result("Notes/Lifetimes.md", 0.8)]) else { panic!("expected a section") };
+        let once = upsert("# Rust\n\nBody.\n", &first).unwrap();
+        // Links in the managed section itself don't count as already linked
+        assert_eq!(unlinked("Notes/Rust.md", &once, vec![result("Notes/Lifetimes.md", 0.8)]).len(), 1);
+
+        let SectionEdit::Upsert(second) = section_edit(&[result("Notes/Traits.md", 0.85)]) else { panic!("expected a section") };
+        let twice = upsert(&once, &second).unwrap();
+        assert_eq!(twice.matches(SECTION_HEADING).count(), 1);
+        assert!(twice.contains("[[Traits]]") && !twice.contains("[[Lifetimes]]"));
+        assert!(matches!(section_edit(&[]), SectionEdit::Remove { .. }));
+    }
+
+    #[test]
+    fn test_markers_in_code_of_the_shared_fixture_are_not_sections() {
+        use crate::managed_sections::scan;
+        let fixture = crate::markdown::FIXTURE;
+        let before = scan(fixture);
+        assert!(before.sections.is_empty() && before.problems.is_empty(), "{:?}", before);
+
+        let SectionEdit::Upsert(section) = section_edit(&[result("Notes/Lifetimes.md", 0.8)]) else { panic!("expected a section") };
+        let once = upsert(fixture, &section).unwrap();
+        assert!(once.starts_with(fixture.trim_end()));
+        let twice = upsert(&once, &section).unwrap();
+        assert_eq!(twice, once);
+        let after = scan(&twice);
+        assert_eq!(after.sections.len(), 1);
+        assert!(after.problems.is_empty(), "{:?}", after.problems);
+    }
+}
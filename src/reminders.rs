@@ -0,0 +1,526 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Duration, NaiveTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use crate::calendar_adapter::{Deadline, DeadlineStatus, NotificationChannel};
+use crate::cli::{RemindAction, RemindArgs};
+use crate::config::{Config, RemindersSettings};
+use crate::deadline_store::DeadlineStore;
+use crate::invites::notify_desktop;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::timezone::{self, TimeZone};
+use crate::todo_bulk::parse_estimate;
+use crate::todo_model::{Priority, TodoItem, TodoStore};
+
+pub fn default_reminders_path() -> PathBuf {
+    let mut path = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push(".config");
+    path.push("arrowhead");
+    path.push("reminders.json");
+    path
+}
+
+pub fn reminders_path(settings: &RemindersSettings) -> PathBuf {
+    settings.state_path.as_ref().map(PathBuf::from).unwrap_or_else(default_reminders_path)
+}
+
+/// How long before a deadline to remind: `2d`, `1w`, or hours and minutes like `3h` or `90m`
+pub fn parse_lead_time(text: &str) -> Result<Duration> {
+    let trimmed = text.trim().to_lowercase();
+    let count = |unit: char| trimmed.strip_suffix(unit).and_then(|n| n.trim().parse::<i64>().ok()).filter(|n| *n > 0);
+    if let Some(days) = count('d') {
+        return Ok(Duration::days(days));
+    }
+    if let Some(weeks) = count('w') {
+        return Ok(Duration::weeks(weeks));
+    }
+    match parse_estimate(&trimmed) {
+        Some(minutes) => Ok(Duration::minutes(minutes as i64)),
+        None => bail!("Could not read '{}' as a reminder time; use e.g. 1w, 2d, 3h or 90m", text),
+    }
+}
+
+/// Channel names in `[reminders] channels`; unknown names are skipped with a warning
+pub fn channels_from_names(names: &[String]) -> Vec<NotificationChannel> {
+    names
+        .iter()
+        .filter_map(|name| match name.trim().to_lowercase().as_str() {
+            "desktop" => Some(NotificationChannel::Desktop),
+            "vault" | "note" => Some(NotificationChannel::Vault),
+            "email" => Some(NotificationChannel::Email),
+            "calendar" | "calendar-alert" => Some(NotificationChannel::CalendarAlert),
+            "push" => Some(NotificationChannel::Push),
+            "slack" => Some(NotificationChannel::Slack),
+            "sms" => Some(NotificationChannel::SMS),
+            other => {
+                log::warn!("unknown reminder channel '{}'", other);
+                None
+            }
+        })
+        .collect()
+}
+
+/// What a reminder is about
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "lowercase")]
+pub enum ReminderSource {
+    /// A deadline, by id
+    Deadline(String),
+    /// A todo note, by vault path
+    Todo(String),
+}
+
+/// A reminder whose time has come
+#[derive(Debug, Clone, Serialize)]
+pub struct DueReminder {
+    /// Names the source and the window, so each window fires once across runs
+    pub key: String,
+    /// Short id shown to the user and used as the block id in the reminders note
+    pub id: String,
+    pub source: ReminderSource,
+    pub title: String,
+    pub message: String,
+    pub due: DateTime<Utc>,
+    pub urgent: bool,
+    /// 0 the first time, then one more for each time an unacknowledged urgent reminder is re-sent
+    pub escalation: u32,
+    /// Hours to wait before re-sending if it isn't acknowledged; `None` never re-sends
+    pub escalate_after_hours: Option<u32>,
+    pub channels: Vec<NotificationChannel>,
+}
+
+/// A reminder that went out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentReminder {
+    pub id: String,
+    pub source: ReminderSource,
+    pub title: String,
+    pub message: String,
+    pub due: DateTime<Utc>,
+    pub urgent: bool,
+    pub first_sent: DateTime<Utc>,
+    pub last_sent: DateTime<Utc>,
+    pub escalations: u32,
+    pub escalate_after_hours: Option<u32>,
+    pub channels: Vec<NotificationChannel>,
+    pub acknowledged: bool,
+}
+
+/// Every reminder sent, by key, so none goes out twice
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReminderLog {
+    pub sent: BTreeMap<String, SentReminder>,
+}
+
+impl ReminderLog {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path).context("Failed to read sent reminders")?;
+        serde_json::from_str(&data).context("Failed to parse sent reminders")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?).context("Failed to write sent reminders")
+    }
+
+    pub fn record(&mut self, reminder: &DueReminder, now: DateTime<Utc>) {
+        match self.sent.get_mut(&reminder.key) {
+            Some(sent) => {
+                sent.last_sent = now;
+                sent.escalations = reminder.escalation;
+            }
+            None => {
+                self.sent.insert(reminder.key.clone(), SentReminder {
+                    id: reminder.id.clone(),
+                    source: reminder.source.clone(),
+                    title: reminder.title.clone(),
+                    message: reminder.message.clone(),
+                    due: reminder.due,
+                    urgent: reminder.urgent,
+                    first_sent: now,
+                    last_sent: now,
+                    escalations: reminder.escalation,
+                    escalate_after_hours: reminder.escalate_after_hours,
+                    channels: reminder.channels.clone(),
+                    acknowledged: false,
+                });
+            }
+        }
+    }
+
+    /// Mark the reminder with this id (`rem-` optional) acknowledged; false if there is none
+    pub fn acknowledge(&mut self, id: &str) -> bool {
+        let id = id.trim().trim_start_matches('^');
+        let id = if id.starts_with("rem-") { id.to_string() } else { format!("rem-{}", id) };
+        let mut found = false;
+        for sent in self.sent.values_mut().filter(|sent| sent.id == id) {
+            sent.acknowledged = true;
+            found = true;
+        }
+        found
+    }
+
+    /// Acknowledge the reminders whose lines are ticked in the reminders note
+    pub fn acknowledge_checked(&mut self, note: &str) -> usize {
+        let mut count = 0;
+        for line in note.lines().map(str::trim_start) {
+            if !(line.starts_with("- [x]") || line.starts_with("- [X]")) {
+                continue;
+            }
+            let Some(at) = line.find("^rem-") else { continue };
+            let id: String = line[at + 1..].chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '-').collect();
+            // Re-sent reminders carry a `-N` suffix on the block id of the first
+            let id = id.splitn(3, '-').take(2).collect::<Vec<_>>().join("-");
+            for sent in self.sent.values_mut().filter(|sent| sent.id == id && !sent.acknowledged) {
+                sent.acknowledged = true;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// The reminder most recently sent for a source
+    fn latest_for(&self, source: &ReminderSource) -> Option<(&String, &SentReminder)> {
+        self.sent.iter().filter(|(_, sent)| &sent.source == source).max_by_key(|(_, sent)| sent.first_sent)
+    }
+}
+
+fn reminder_id(key: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    format!("rem-{:08x}", hasher.finish() as u32)
+}
+
+fn deadline_open(deadline: &Deadline) -> bool {
+    !matches!(deadline.status, DeadlineStatus::Completed | DeadlineStatus::Cancelled)
+}
+
+/// The reminders to send at `now`. For each deadline only the tightest reminder window it has
+/// entered fires, so wider windows it overtook while nothing was running are skipped. The
+/// latest reminder of an open deadline is sent again when it is urgent, escalation is on and
+/// it has gone unacknowledged for the escalation delay. Open todos get one reminder at
+/// `todo_time` on the day they are due.
+pub fn reminders_due(
+    now: DateTime<Utc>,
+    deadlines: &[Deadline],
+    todos: &[TodoItem],
+    log: &ReminderLog,
+    settings: &RemindersSettings,
+    zone: &TimeZone,
+) -> Vec<DueReminder> {
+    let default_channels = channels_from_names(&settings.channels);
+    let mut due = Vec::new();
+
+    for deadline in deadlines.iter().filter(|d| d.reminder_settings.enabled && deadline_open(d)) {
+        let reminder_settings = &deadline.reminder_settings;
+        let source = ReminderSource::Deadline(deadline.id.clone());
+        let channels = match reminder_settings.notification_channels.is_empty() {
+            true => default_channels.clone(),
+            false => reminder_settings.notification_channels.clone(),
+        };
+        let entered = reminder_settings
+            .advance_notifications
+            .iter()
+            .filter(|schedule| now >= deadline.due_date - schedule.time_before_deadline)
+            .min_by_key(|schedule| schedule.time_before_deadline);
+        if let Some(schedule) = entered {
+            let key = format!("deadline:{}:{}m", deadline.id, schedule.time_before_deadline.num_minutes());
+            if !log.sent.contains_key(&key) {
+                let message = match schedule.message.trim() {
+                    "" => format!("{} is due {}", deadline.title, zone.to_local(deadline.due_date).format("%a %b %-d %H:%M")),
+                    message => message.to_string(),
+                };
+                due.push(DueReminder {
+                    id: reminder_id(&key),
+                    key,
+                    source,
+                    title: deadline.title.clone(),
+                    message,
+                    due: deadline.due_date,
+                    urgent: schedule.urgent,
+                    escalation: 0,
+                    escalate_after_hours: (reminder_settings.escalation_enabled && schedule.urgent).then_some(reminder_settings.escalation_delay_hours),
+                    channels,
+                });
+                continue;
+            }
+        }
+
+        let Some((key, sent)) = log.latest_for(&source) else { continue };
+        let Some(hours) = sent.escalate_after_hours.filter(|_| sent.urgent && !sent.acknowledged) else { continue };
+        if now >= sent.last_sent + Duration::hours(hours.max(1) as i64) {
+            due.push(DueReminder {
+                key: key.clone(),
+                id: sent.id.clone(),
+                source,
+                title: sent.title.clone(),
+                message: sent.message.clone(),
+                due: sent.due,
+                urgent: true,
+                escalation: sent.escalations + 1,
+                escalate_after_hours: Some(hours),
+                channels: sent.channels.clone(),
+            });
+        }
+    }
+
+    if settings.todos {
+        let time = NaiveTime::parse_from_str(settings.todo_time.trim(), "%H:%M").unwrap_or_else(|_| NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+        let today = zone.to_local(now).date();
+        for todo in todos.iter().filter(|t| t.status.is_open() && t.due == Some(today)) {
+            let Some(remind_at) = zone.at(today, time.hour(), time.minute()) else { continue };
+            let key = format!("todo:{}:{}", todo.path, today);
+            if now < remind_at || log.sent.contains_key(&key) {
+                continue;
+            }
+            due.push(DueReminder {
+                id: reminder_id(&key),
+                key,
+                source: ReminderSource::Todo(todo.path.clone()),
+                title: todo.title.clone(),
+                message: format!("{} is due today", todo.title),
+                due: remind_at,
+                urgent: todo.priority == Some(Priority::High),
+                escalation: 0,
+                escalate_after_hours: None,
+                channels: default_channels.clone(),
+            });
+        }
+    }
+    due
+}
+
+/// The reminders due at `now` for the deadlines in the store and, when `[reminders] todos`
+/// is on, the vault's todos. Todos are skipped with a warning if the vault can't be reached.
+pub async fn check_due_reminders(now: DateTime<Utc>, config: &Config, adapter: &ObsidianAdapter, log: &ReminderLog) -> Result<Vec<DueReminder>> {
+    let deadlines = DeadlineStore::from_settings(&config.calendar).list(None)?;
+    let todos = if config.reminders.todos {
+        TodoStore::new(adapter).list().await.unwrap_or_else(|e| {
+            log::warn!("todo reminders skipped: {}", e);
+            Vec::new()
+        })
+    } else {
+        Vec::new()
+    };
+    let zone = timezone::resolve(&config.calendar.time_zone)?;
+    Ok(reminders_due(now, &deadlines, &todos, log, &config.reminders, &zone))
+}
+
+/// The checkbox line a reminder adds to the reminders note
+pub fn vault_line(reminder: &DueReminder, zone: &TimeZone, now: DateTime<Utc>) -> String {
+    let mut notes = Vec::new();
+    if reminder.urgent {
+        notes.push("urgent".to_string());
+    }
+    if reminder.escalation > 0 {
+        notes.push(format!("reminder {}", reminder.escalation + 1));
+    }
+    let notes = if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) };
+    let block = match reminder.escalation {
+        0 => reminder.id.clone(),
+        n => format!("{}-{}", reminder.id, n + 1),
+    };
+    format!("- [ ] {} {}{} ^{}", zone.to_local(now).format("%Y-%m-%d %H:%M"), reminder.message, notes, block)
+}
+
+async fn append_to_note(adapter: &ObsidianAdapter, note: &str, line: &str) -> Result<()> {
+    match adapter.get_file_if_exists(note).await? {
+        None => adapter.create_file(note, &format!("# Reminders\n\n{}\n", line)).await,
+        Some(content) => {
+            let separator = if content.is_empty() || content.ends_with('\n') { "" } else { "\n" };
+            adapter.append_file(note, &format!("{}{}\n", separator, line)).await
+        }
+    }
+}
+
+/// Send a reminder on each of its channels; true if at least one took it
+async fn deliver(reminder: &DueReminder, settings: &RemindersSettings, adapter: &ObsidianAdapter, zone: &TimeZone, now: DateTime<Utc>) -> bool {
+    let mut delivered = false;
+    for channel in &reminder.channels {
+        let result = match channel {
+            NotificationChannel::Desktop => {
+                let title = if reminder.urgent { "⏰ Urgent reminder" } else { "⏰ Reminder" };
+                notify_desktop(title, &reminder.message, reminder.urgent)
+            }
+            NotificationChannel::Vault => append_to_note(adapter, &settings.note, &vault_line(reminder, zone, now)).await,
+            other => Err(anyhow!("{:?} reminders are not supported yet", other)),
+        };
+        match result {
+            Ok(()) => delivered = true,
+            Err(e) => log::warn!("{:?} reminder for '{}' not delivered: {}", channel, reminder.title, e),
+        }
+    }
+    delivered
+}
+
+/// One check: pick up acknowledgements ticked in the note, then send what is due
+async fn remind_once(config: &Config, adapter: &ObsidianAdapter, path: &Path, dry_run: bool) -> Result<Vec<DueReminder>> {
+    let settings = &config.reminders;
+    let zone = timezone::resolve(&config.calendar.time_zone)?;
+    let now = Utc::now();
+    let mut log = ReminderLog::load(path)?;
+    if let Ok(Some(note)) = adapter.get_file_if_exists(&settings.note).await {
+        log.acknowledge_checked(&note);
+    }
+
+    let due = check_due_reminders(now, config, adapter, &log).await?;
+    for reminder in &due {
+        let marker = if reminder.escalation > 0 { "🔁" } else { "🔔" };
+        println!("{} {}  {}", marker, reminder.id, reminder.message);
+        if !dry_run && deliver(reminder, settings, adapter, &zone, now).await {
+            log.record(reminder, now);
+        }
+    }
+    if !dry_run {
+        log.save(path)?;
+    }
+    Ok(due)
+}
+
+pub async fn handle_remind_command(args: RemindArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let path = reminders_path(&config.reminders);
+
+    if let Some(RemindAction::Ack { id, all }) = args.action {
+        let mut log = ReminderLog::load(&path)?;
+        let count = match (id, all) {
+            (_, true) => log.sent.values_mut().filter(|sent| !sent.acknowledged).map(|sent| sent.acknowledged = true).count(),
+            (Some(id), false) if log.acknowledge(&id) => 1,
+            (Some(id), false) => bail!("No reminder with id '{}'", id),
+            (None, false) => bail!("Give a reminder id or --all"),
+        };
+        log.save(&path)?;
+        println!("✅ Acknowledged {} reminder(s)", count);
+        return Ok(());
+    }
+
+    if !args.daemon {
+        let due = remind_once(&config, adapter, &path, args.dry_run).await?;
+        if due.is_empty() {
+            println!("No reminders due.");
+        } else if args.dry_run {
+            println!("Dry run: {} reminder(s) due, nothing sent", due.len());
+        }
+        crate::script::publish_output(serde_json::to_value(&due)?);
+        return Ok(());
+    }
+
+    let minutes = args.interval.unwrap_or(config.reminders.interval_minutes).max(1);
+    println!("⏰ Checking reminders every {} minute(s) (Ctrl-C to stop)", minutes);
+    loop {
+        // A failed check (e.g. an unreadable deadline store) is retried on the next tick
+        if let Err(e) = remind_once(&config, adapter, &path, args.dry_run).await {
+            println!("⚠️  {}", e);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(std::time::Duration::from_secs(minutes * 60)) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar_adapter::{DeadlinePriority, ReminderSchedule, ReminderSettings};
+    use crate::testing;
+    use chrono::TimeZone as _;
+
+    fn deadline(due: DateTime<Utc>, schedules: &[(i64, bool)]) -> Deadline {
+        Deadline {
+            id: "d1".to_string(),
+            priority: DeadlinePriority::High,
+            status: DeadlineStatus::InProgress,
+            reminder_settings: ReminderSettings {
+                enabled: true,
+                advance_notifications: schedules
+                    .iter()
+                    .map(|(hours, urgent)| ReminderSchedule { time_before_deadline: Duration::hours(*hours), message: String::new(), urgent: *urgent })
+                    .collect(),
+                notification_channels: Vec::new(),
+                escalation_enabled: true,
+                escalation_delay_hours: 2,
+            },
+            ..testing::deadline("Ship report", due)
+        }
+    }
+
+    #[test]
+    fn test_only_the_tightest_entered_window_fires_once() {
+        let zone = timezone::resolve("UTC").unwrap();
+        let settings = RemindersSettings::default();
+        let due = Utc.with_ymd_and_hms(2026, 10, 16, 17, 0, 0).unwrap();
+        let deadlines = vec![deadline(due, &[(48, false), (24, false), (2, false)])];
+        let mut log = ReminderLog::default();
+
+        assert!(reminders_due(due - Duration::hours(50), &deadlines, &[], &log, &settings, &zone).is_empty());
+        // The 48h window was missed while nothing ran; only the 24h one goes out
+        let now = due - Duration::hours(20);
+        let first = reminders_due(now, &deadlines, &[], &log, &settings, &zone);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].key, "deadline:d1:1440m");
+        assert_eq!(first[0].message, "Ship report is due Fri Oct 16 17:00");
+        assert!(matches!(first[0].channels[..], [NotificationChannel::Desktop, NotificationChannel::Vault]));
+
+        log.record(&first[0], now);
+        assert!(reminders_due(now + Duration::hours(1), &deadlines, &[], &log, &settings, &zone).is_empty());
+        assert_eq!(reminders_due(due - Duration::hours(1), &deadlines, &[], &log, &settings, &zone)[0].key, "deadline:d1:120m");
+    }
+
+    #[test]
+    fn test_unacknowledged_urgent_reminders_escalate_until_ticked() {
+        let zone = timezone::resolve("UTC").unwrap();
+        let settings = RemindersSettings::default();
+        let due = Utc.with_ymd_and_hms(2026, 10, 16, 17, 0, 0).unwrap();
+        let deadlines = vec![deadline(due, &[(24, true)])];
+        let mut log = ReminderLog::default();
+        let sent_at = due - Duration::hours(24);
+        let first = reminders_due(sent_at, &deadlines, &[], &log, &settings, &zone).remove(0);
+        log.record(&first, sent_at);
+
+        assert!(reminders_due(sent_at + Duration::hours(1), &deadlines, &[], &log, &settings, &zone).is_empty());
+        let again = reminders_due(sent_at + Duration::hours(2), &deadlines, &[], &log, &settings, &zone).remove(0);
+        assert_eq!((again.escalation, again.id.as_str()), (1, first.id.as_str()));
+        assert!(vault_line(&again, &zone, sent_at).ends_with(&format!("(urgent, reminder 2) ^{}-2", first.id)));
+        log.record(&again, sent_at + Duration::hours(2));
+
+        let note = format!("# Reminders\n\n- [ ] old\n- [x] 2026-10-15 19:00 Ship report ^{}-2\n", first.id);
+        assert_eq!(log.acknowledge_checked(&note), 1);
+        assert!(reminders_due(sent_at + Duration::hours(10), &deadlines, &[], &log, &settings, &zone).is_empty());
+    }
+
+    #[test]
+    fn test_todos_are_reminded_on_their_due_day() {
+        let zone = timezone::resolve("UTC").unwrap();
+        let settings = RemindersSettings::default();
+        let mut todo = TodoItem::parse("Todos/call-bank.md", "---\ndue_date: 2026-10-15\npriority: high\n---\n\n- [ ] Call the bank\n");
+        let morning = Utc.with_ymd_and_hms(2026, 10, 15, 8, 0, 0).unwrap();
+        assert!(reminders_due(morning, &[], std::slice::from_ref(&todo), &ReminderLog::default(), &settings, &zone).is_empty());
+
+        let due = reminders_due(morning + Duration::hours(2), &[], std::slice::from_ref(&todo), &ReminderLog::default(), &settings, &zone);
+        assert_eq!(due.len(), 1);
+        assert_eq!((due[0].key.as_str(), due[0].urgent), ("todo:Todos/call-bank.md:2026-10-15", true));
+
+        todo.due = todo.due.map(|d| d.succ_opt().unwrap());
+        assert!(reminders_due(morning + Duration::hours(2), &[], &[todo], &ReminderLog::default(), &settings, &zone).is_empty());
+    }
+
+    #[test]
+    fn test_lead_times() {
+        assert_eq!(parse_lead_time("2d").unwrap(), Duration::days(2));
+        assert_eq!(parse_lead_time("1w").unwrap(), Duration::weeks(1));
+        assert_eq!(parse_lead_time("1h30m").unwrap(), Duration::minutes(90));
+        assert!(parse_lead_time("soon").is_err());
+    }
+}
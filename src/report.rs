@@ -0,0 +1,150 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// How much of a command's report to show. Separate from log verbosity: `--summary` keeps the
+/// headline, `--detail` adds per-item data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetailLevel {
+    Summary,
+    #[default]
+    Normal,
+    Detail,
+}
+
+/// The tier and format a report is printed in, taken from the global `--summary`, `--detail`
+/// and `--format` flags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReportView {
+    pub level: DetailLevel,
+    pub json: bool,
+}
+
+impl ReportView {
+    /// Commands that had their own `--json` flag before `--format` keep it
+    pub fn with_json(self, json: bool) -> Self {
+        Self { json: self.json || json, ..self }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shown {
+    /// At this level and every more detailed one
+    From(DetailLevel),
+    Only(DetailLevel),
+}
+
+impl Shown {
+    fn at(self, level: DetailLevel) -> bool {
+        match self {
+            Shown::From(min) => level >= min,
+            Shown::Only(only) => level == only,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Field(String, Value),
+}
+
+/// A command's output defined once for every tier: text blocks and JSON fields tagged with the
+/// level they appear at. Text blocks are printed as given, so they carry their own newlines.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    parts: Vec<(Shown, Part)>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Text shown at `level` and above
+    pub fn text(mut self, level: DetailLevel, text: impl Into<String>) -> Self {
+        self.parts.push((Shown::From(level), Part::Text(text.into())));
+        self
+    }
+
+    /// Text shown at exactly `level`, e.g. a summary line standing in for a list, or a list
+    /// that `--detail` replaces with a fuller version
+    pub fn text_only(mut self, level: DetailLevel, text: impl Into<String>) -> Self {
+        self.parts.push((Shown::Only(level), Part::Text(text.into())));
+        self
+    }
+
+    /// A JSON field included at `level` and above
+    pub fn field(mut self, level: DetailLevel, key: &str, value: impl Serialize) -> Self {
+        let value = serde_json::to_value(value).unwrap_or(Value::Null);
+        self.parts.push((Shown::From(level), Part::Field(key.to_string(), value)));
+        self
+    }
+
+    pub fn render(&self, level: DetailLevel) -> String {
+        self.parts.iter()
+            .filter(|(shown, _)| shown.at(level))
+            .filter_map(|(_, part)| match part {
+                Part::Text(text) => Some(text.as_str()),
+                Part::Field(..) => None,
+            })
+            .collect()
+    }
+
+    /// The fields shown at `level`, plus `detail_level`
+    pub fn to_json(&self, level: DetailLevel) -> Value {
+        let mut object = Map::new();
+        object.insert("detail_level".to_string(), serde_json::to_value(level).unwrap_or(Value::Null));
+        for (shown, part) in &self.parts {
+            if let (true, Part::Field(key, value)) = (shown.at(level), part) {
+                object.insert(key.clone(), value.clone());
+            }
+        }
+        Value::Object(object)
+    }
+
+    pub fn print(&self, view: ReportView) -> Result<()> {
+        if view.json {
+            println!("{}", serde_json::to_string_pretty(&self.to_json(view.level))?);
+        } else {
+            print!("{}", self.render(view.level));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        Report::new()
+            .text(DetailLevel::Summary, "3 problem(s)\n")
+            .text_only(DetailLevel::Summary, "run without --summary for the list\n")
+            .text(DetailLevel::Normal, "- a\n- b\n- c\n")
+            .text(DetailLevel::Detail, "checked 40 notes\n")
+            .field(DetailLevel::Summary, "problems", 3)
+            .field(DetailLevel::Normal, "items", ["a", "b", "c"])
+            .field(DetailLevel::Detail, "checked", 40)
+    }
+
+    #[test]
+    fn test_text_is_filtered_by_tier() {
+        let report = report();
+        assert_eq!(report.render(DetailLevel::Summary), "3 problem(s)\nrun without --summary for the list\n");
+        assert_eq!(report.render(DetailLevel::Normal), "3 problem(s)\n- a\n- b\n- c\n");
+        assert_eq!(report.render(DetailLevel::Detail), "3 problem(s)\n- a\n- b\n- c\nchecked 40 notes\n");
+    }
+
+    #[test]
+    fn test_json_omits_suppressed_fields() {
+        let report = report();
+        assert_eq!(report.to_json(DetailLevel::Summary), serde_json::json!({ "detail_level": "summary", "problems": 3 }));
+        let normal = report.to_json(DetailLevel::Normal);
+        assert_eq!(normal["detail_level"], "normal");
+        assert_eq!(normal["items"], serde_json::json!(["a", "b", "c"]));
+        assert!(normal.get("checked").is_none());
+        assert_eq!(report.to_json(DetailLevel::Detail)["checked"], 40);
+    }
+}
@@ -0,0 +1,411 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use crate::calendar::{available_working_hours, create_calendar_adapter, scheduling_constraints};
+use crate::calendar_adapter::{
+    deadline_progress, deadline_risk_indicators, Deadline, DeadlinePriority, DeadlineStatus, RiskIndicator, RiskLevel,
+    RiskType, SchedulingConstraints,
+};
+use crate::cli::RisksArgs;
+use crate::config::Config;
+use crate::dates::week_start;
+use crate::report::{DetailLevel, Report, ReportView};
+
+/// Days ahead compared against remaining work for the overcommitment index
+pub const CAPACITY_HORIZON_DAYS: i64 = 14;
+/// This many at-risk deadlines due in the same week is flagged as a cluster
+const WEEK_CLUSTER_SIZE: usize = 3;
+
+/// One indicator raised by a single deadline
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadlineRisk {
+    pub deadline_id: String,
+    pub title: String,
+    pub due_date: DateTime<Utc>,
+    pub days_remaining: i64,
+    pub indicator: RiskIndicator,
+}
+
+/// An indicator synthesized from several deadlines together
+#[derive(Debug, Clone, Serialize)]
+pub struct CrossDeadlineRisk {
+    pub deadline_ids: Vec<String>,
+    pub indicator: RiskIndicator,
+}
+
+/// Remaining estimated work compared with available working hours
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Overcommitment {
+    pub remaining_hours: f32,
+    pub available_hours: f32,
+    /// `remaining_hours / available_hours`; above 1.0 means more work than time
+    pub index: f32,
+}
+
+/// Risk indicators across all active deadlines
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskReport {
+    pub generated_at: DateTime<Utc>,
+    pub active_deadlines: usize,
+    pub deadline_risks: Vec<DeadlineRisk>,
+    pub cross_deadline_risks: Vec<CrossDeadlineRisk>,
+    pub overcommitment: Overcommitment,
+}
+
+pub(crate) fn is_active(deadline: &Deadline) -> bool {
+    !matches!(deadline.status, DeadlineStatus::Completed | DeadlineStatus::Cancelled)
+        && deadline.completed_hours < deadline.estimated_hours
+}
+
+fn severity_rank(level: &RiskLevel) -> u8 {
+    match level {
+        RiskLevel::High => 0,
+        RiskLevel::Medium => 1,
+        RiskLevel::Low => 2,
+    }
+}
+
+fn severity_label(level: &RiskLevel) -> &'static str {
+    match level {
+        RiskLevel::High => "High",
+        RiskLevel::Medium => "Medium",
+        RiskLevel::Low => "Low",
+    }
+}
+
+fn risk_type_label(risk_type: &RiskType) -> &'static str {
+    match risk_type {
+        RiskType::TimeShortage => "time shortage",
+        RiskType::ScopeCreep => "scope creep",
+        RiskType::DependencyDelay => "dependency delay",
+        RiskType::ResourceConstraint => "resource constraint",
+        RiskType::QualityRisk => "quality risk",
+        RiskType::ExternalFactor => "external factor",
+    }
+}
+
+/// Hours of a deadline's remaining work that fall within the capacity horizon, assuming
+/// work is spread evenly until the due date
+fn remaining_hours_in_horizon(deadline: &Deadline, now: DateTime<Utc>, horizon_end: DateTime<Utc>) -> f32 {
+    let remaining = (deadline.estimated_hours - deadline.completed_hours).max(0.0);
+    if deadline.due_date <= horizon_end {
+        return remaining;
+    }
+    let span = (deadline.due_date - now).num_minutes() as f32;
+    let within = (horizon_end - now).num_minutes() as f32;
+    remaining * (within / span).clamp(0.0, 1.0)
+}
+
+fn overcommitment(active: &[&Deadline], constraints: &SchedulingConstraints, now: DateTime<Utc>) -> Result<Overcommitment> {
+    let horizon_end = now + Duration::days(CAPACITY_HORIZON_DAYS);
+    let remaining_hours: f32 = active.iter().map(|d| remaining_hours_in_horizon(d, now, horizon_end)).sum();
+    let available_hours = available_working_hours(constraints, now, CAPACITY_HORIZON_DAYS)?;
+    let index = if available_hours > 0.0 {
+        remaining_hours / available_hours
+    } else if remaining_hours > 0.0 {
+        f32::INFINITY
+    } else {
+        0.0
+    };
+    Ok(Overcommitment { remaining_hours, available_hours, index })
+}
+
+/// Aggregate the risk indicators of all active deadlines and add the ones only visible
+/// across deadlines
+pub fn build_risk_report(deadlines: &[Deadline], constraints: &SchedulingConstraints, now: DateTime<Utc>) -> Result<RiskReport> {
+    let active: Vec<&Deadline> = deadlines.iter().filter(|d| is_active(d)).collect();
+
+    let mut deadline_risks = Vec::new();
+    let mut at_risk_by_week: BTreeMap<NaiveDate, Vec<&Deadline>> = BTreeMap::new();
+    for deadline in &active {
+        let (completion_rate, time_efficiency) = deadline_progress(deadline, now);
+        let indicators = deadline_risk_indicators(deadline, completion_rate, time_efficiency, deadline.due_date - now);
+
+        let critical = matches!(deadline.priority, DeadlinePriority::Critical)
+            || indicators.iter().any(|i| i.severity == RiskLevel::High);
+        if critical && deadline.due_date >= now {
+            at_risk_by_week.entry(week_start(deadline.due_date.date_naive())).or_default().push(deadline);
+        }
+
+        for indicator in indicators {
+            deadline_risks.push(DeadlineRisk {
+                deadline_id: deadline.id.clone(),
+                title: deadline.title.clone(),
+                due_date: deadline.due_date,
+                days_remaining: (deadline.due_date - now).num_days(),
+                indicator,
+            });
+        }
+    }
+    deadline_risks.sort_by(|a, b| {
+        severity_rank(&a.indicator.severity).cmp(&severity_rank(&b.indicator.severity))
+            .then(a.due_date.cmp(&b.due_date))
+    });
+
+    let mut cross_deadline_risks = Vec::new();
+    for (week, cluster) in &at_risk_by_week {
+        if cluster.len() < WEEK_CLUSTER_SIZE {
+            continue;
+        }
+        let titles: Vec<&str> = cluster.iter().map(|d| d.title.as_str()).collect();
+        cross_deadline_risks.push(CrossDeadlineRisk {
+            deadline_ids: cluster.iter().map(|d| d.id.clone()).collect(),
+            indicator: RiskIndicator {
+                indicator_type: RiskType::ResourceConstraint,
+                severity: RiskLevel::High,
+                description: format!("{} critical deadlines due the week of {}: {}", cluster.len(), week.format("%Y-%m-%d"), titles.join(", ")),
+                suggested_action: "Renegotiate one of these dates now or move work earlier".to_string(),
+                deadline_impact: 0.9,
+            },
+        });
+    }
+
+    let overcommitment = overcommitment(&active, constraints, now)?;
+    if overcommitment.index > 0.8 {
+        let over = overcommitment.index > 1.0;
+        cross_deadline_risks.push(CrossDeadlineRisk {
+            deadline_ids: active.iter().map(|d| d.id.clone()).collect(),
+            indicator: RiskIndicator {
+                indicator_type: RiskType::TimeShortage,
+                severity: if over { RiskLevel::High } else { RiskLevel::Medium },
+                description: format!(
+                    "{:.0}h of remaining work against {:.0}h of working time in the next {} days",
+                    overcommitment.remaining_hours, overcommitment.available_hours, CAPACITY_HORIZON_DAYS
+                ),
+                suggested_action: if over {
+                    "Drop or defer work: the plan does not fit the available time".to_string()
+                } else {
+                    "Avoid taking on new commitments until the load drops".to_string()
+                },
+                deadline_impact: overcommitment.index.min(1.0),
+            },
+        });
+    }
+
+    Ok(RiskReport {
+        generated_at: now,
+        active_deadlines: active.len(),
+        deadline_risks,
+        cross_deadline_risks,
+        overcommitment,
+    })
+}
+
+impl RiskReport {
+    fn by_severity(&self, level: &RiskLevel) -> (Vec<&CrossDeadlineRisk>, Vec<&DeadlineRisk>) {
+        (
+            self.cross_deadline_risks.iter().filter(|r| &r.indicator.severity == level).collect(),
+            self.deadline_risks.iter().filter(|r| &r.indicator.severity == level).collect(),
+        )
+    }
+
+    /// Summary: the overcommitment index and risk counts per severity. Normal: every risk
+    /// grouped by severity with its suggested action. Detail: also each risk's impact and the
+    /// deadlines behind cross-deadline risks.
+    pub fn report(&self) -> Report {
+        let mut headline = String::new();
+        let _ = writeln!(headline, "⚠️  Risks across {} active deadline(s)", self.active_deadlines);
+        let _ = writeln!(
+            headline,
+            "Overcommitment index: {:.2} ({:.1}h remaining / {:.1}h available in the next {} days)",
+            self.overcommitment.index, self.overcommitment.remaining_hours, self.overcommitment.available_hours, CAPACITY_HORIZON_DAYS
+        );
+        let report = Report::new()
+            .text(DetailLevel::Summary, headline)
+            .field(DetailLevel::Summary, "generated_at", self.generated_at)
+            .field(DetailLevel::Summary, "active_deadlines", self.active_deadlines)
+            .field(DetailLevel::Summary, "overcommitment", &self.overcommitment)
+            .field(DetailLevel::Normal, "deadline_risks", &self.deadline_risks)
+            .field(DetailLevel::Normal, "cross_deadline_risks", &self.cross_deadline_risks);
+        if self.deadline_risks.is_empty() && self.cross_deadline_risks.is_empty() {
+            return report.text(DetailLevel::Summary, "\n✅ No risks detected\n");
+        }
+
+        let counts: Vec<String> = [RiskLevel::High, RiskLevel::Medium, RiskLevel::Low].iter()
+            .map(|level| {
+                let (cross, single) = self.by_severity(level);
+                format!("{} {}", cross.len() + single.len(), severity_label(level).to_lowercase())
+            })
+            .collect();
+        report
+            .text_only(DetailLevel::Summary, format!("Risks: {}\n", counts.join(", ")))
+            .text_only(DetailLevel::Normal, self.render_risks(false))
+            .text_only(DetailLevel::Detail, self.render_risks(true))
+    }
+
+    fn render_risks(&self, detail: bool) -> String {
+        let mut out = String::new();
+        for level in [RiskLevel::High, RiskLevel::Medium, RiskLevel::Low] {
+            let (cross, single) = self.by_severity(&level);
+            if cross.is_empty() && single.is_empty() {
+                continue;
+            }
+            let _ = writeln!(out, "\n{} severity", severity_label(&level));
+            for risk in cross {
+                let _ = writeln!(out, "  • [across deadlines] {}: {}", risk_type_label(&risk.indicator.indicator_type), risk.indicator.description);
+                let _ = writeln!(out, "      → {}", risk.indicator.suggested_action);
+                if detail {
+                    let _ = writeln!(out, "      impact {:.0}%, deadlines: {}", risk.indicator.deadline_impact * 100.0, risk.deadline_ids.join(", "));
+                }
+            }
+            for risk in single {
+                let _ = writeln!(
+                    out,
+                    "  • {} (due {}, {} day(s) left) — {}: {}",
+                    risk.title,
+                    risk.due_date.format("%Y-%m-%d"),
+                    risk.days_remaining,
+                    risk_type_label(&risk.indicator.indicator_type),
+                    risk.indicator.description
+                );
+                let _ = writeln!(out, "      → {}", risk.indicator.suggested_action);
+                if detail {
+                    let _ = writeln!(out, "      impact {:.0}%, deadline {}", risk.indicator.deadline_impact * 100.0, risk.deadline_id);
+                }
+            }
+        }
+        out
+    }
+
+    /// Markdown section for notes that summarize the week (brief, digest)
+    pub fn markdown_section(&self) -> String {
+        let mut out = String::from("## Risks\n\n");
+        let _ = writeln!(
+            out,
+            "Overcommitment index **{:.2}** ({:.1}h of work, {:.1}h available in the next {} days)\n",
+            self.overcommitment.index, self.overcommitment.remaining_hours, self.overcommitment.available_hours, CAPACITY_HORIZON_DAYS
+        );
+        let mut any = false;
+        for level in [RiskLevel::High, RiskLevel::Medium] {
+            let (cross, single) = self.by_severity(&level);
+            for risk in cross {
+                any = true;
+                let _ = writeln!(out, "- **{}** {} — {}", severity_label(&level), risk.indicator.description, risk.indicator.suggested_action);
+            }
+            for risk in single {
+                any = true;
+                let _ = writeln!(
+                    out,
+                    "- **{}** {} (due {}): {} — {}",
+                    severity_label(&level), risk.title, risk.due_date.format("%Y-%m-%d"), risk.indicator.description, risk.indicator.suggested_action
+                );
+            }
+        }
+        if !any {
+            out.push_str("- No high or medium risks\n");
+        }
+        out
+    }
+}
+
+pub async fn handle_risks_command(args: RisksArgs, view: ReportView) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let adapter = create_calendar_adapter(&config.calendar, &config.network)?;
+    let deadlines = adapter.get_deadlines(None).await?;
+    let report = build_risk_report(&deadlines, &scheduling_constraints(&config.calendar), Utc::now())?;
+    crate::script::publish_output(serde_json::to_value(&report)?);
+    report.report().print(view.with_json(args.json))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::test_constraints;
+    use crate::calendar_adapter::WorkingHours;
+    use crate::testing;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        // A Monday morning
+        Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap()
+    }
+
+    fn deadline(id: &str, due_in_days: i64, estimated: f32, completed: f32, priority: DeadlinePriority) -> Deadline {
+        Deadline {
+            id: id.to_string(),
+            priority,
+            status: DeadlineStatus::InProgress,
+            estimated_hours: estimated,
+            completed_hours: completed,
+            ..testing::deadline(&format!("Deadline {}", id), now() + Duration::days(due_in_days))
+        }
+    }
+
+    #[test]
+    fn test_available_working_hours() {
+        // Two full working weeks from a Monday at 09:00
+        assert_eq!(available_working_hours(&test_constraints(), now(), 14).unwrap(), 80.0);
+        let none = SchedulingConstraints { working_hours: vec![], ..test_constraints() };
+        assert_eq!(available_working_hours(&none, now(), 14).unwrap(), 0.0);
+        let weekend = SchedulingConstraints {
+            working_hours: vec![WorkingHours { day_of_week: 6, start_hour: 10, start_minute: 0, end_hour: 12, end_minute: 30 }],
+            ..test_constraints()
+        };
+        assert_eq!(available_working_hours(&weekend, now(), 14).unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_overcommitment_counts_work_within_horizon() {
+        let deadlines = vec![
+            deadline("a", 7, 40.0, 10.0, DeadlinePriority::Medium),
+            // Due in 28 days: half of the remaining 20h falls in the next two weeks
+            deadline("b", 28, 20.0, 0.0, DeadlinePriority::Low),
+            deadline("done", 3, 10.0, 10.0, DeadlinePriority::High),
+        ];
+        let report = build_risk_report(&deadlines, &test_constraints(), now()).unwrap();
+        assert_eq!(report.active_deadlines, 2);
+        assert_eq!(report.overcommitment.remaining_hours, 40.0);
+        assert_eq!(report.overcommitment.available_hours, 80.0);
+        assert_eq!(report.overcommitment.index, 0.5);
+        assert!(report.cross_deadline_risks.is_empty());
+    }
+
+    #[test]
+    fn test_critical_deadlines_in_same_week_are_synthesized() {
+        let deadlines = vec![
+            deadline("a", 9, 4.0, 2.0, DeadlinePriority::Critical),
+            deadline("b", 10, 4.0, 2.0, DeadlinePriority::Critical),
+            deadline("c", 11, 4.0, 2.0, DeadlinePriority::Critical),
+            // Critical, but the week after
+            deadline("d", 14, 4.0, 2.0, DeadlinePriority::Critical),
+            deadline("e", 10, 4.0, 2.0, DeadlinePriority::Low),
+        ];
+        let report = build_risk_report(&deadlines, &test_constraints(), now()).unwrap();
+        assert_eq!(report.cross_deadline_risks.len(), 1);
+        let cluster = &report.cross_deadline_risks[0];
+        assert_eq!(cluster.deadline_ids, vec!["a", "b", "c"]);
+        assert_eq!(cluster.indicator.severity, RiskLevel::High);
+        assert!(cluster.indicator.description.contains("week of 2024-06-10"));
+
+        // None of them is risky on its own
+        assert!(report.deadline_risks.iter().all(|r| r.indicator.severity != RiskLevel::High));
+        assert!(report.report().render(DetailLevel::Normal).contains("[across deadlines]"));
+    }
+
+    #[test]
+    fn test_overcommitted_load_and_grouping() {
+        let deadlines = vec![
+            deadline("big", 10, 120.0, 0.0, DeadlinePriority::High),
+            // Due tomorrow at 20% done: high time-shortage risk on its own
+            deadline("soon", 0, 10.0, 2.0, DeadlinePriority::Medium),
+        ];
+        let report = build_risk_report(&deadlines, &test_constraints(), now()).unwrap();
+        assert!(report.overcommitment.index > 1.0);
+
+        let load = report.cross_deadline_risks.iter().find(|r| r.indicator.indicator_type == RiskType::TimeShortage).unwrap();
+        assert_eq!(load.indicator.severity, RiskLevel::High);
+        assert_eq!(load.deadline_ids.len(), 2);
+
+        assert_eq!(report.deadline_risks[0].deadline_id, "soon");
+        assert_eq!(report.deadline_risks[0].indicator.severity, RiskLevel::High);
+        let rendered = report.report().render(DetailLevel::Normal);
+        assert!(rendered.find("High severity").unwrap() < rendered.find("Medium severity").unwrap());
+        assert!(report.markdown_section().starts_with("## Risks\n"));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["deadline_risks"][0]["days_remaining"], 0);
+    }
+}
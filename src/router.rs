@@ -1,21 +1,270 @@
 use anyhow::Result;
-use crate::cli::{Cli, Commands};
+use clap::error::ErrorKind;
+use clap::FromArgMatches;
+use std::io;
+use std::process;
+use crate::cli::{build_cli, Cli, Commands, ConfigAction};
+use crate::command_suggest::{find_unknown_command, plugin_commands, recover_unknown_command, Recovery, PLUGIN_PREFIX};
+use crate::confirm::StdinPrompt;
 use crate::obsidian_adapter::ObsidianAdapter;
 use crate::todos::handle_todo_command;
 use crate::notes::handle_note_command;
 use crate::goals::handle_goal_command;
+use crate::calendar::handle_calendar_command;
+use crate::sync::handle_sync_command;
+use crate::changelog::handle_changelog_command;
+use crate::daily::handle_daily_command;
+use crate::deadlines::handle_deadline_command;
+use crate::focus::handle_focus_command;
+use crate::scheduling::handle_schedule_command;
+use crate::watch::handle_watch_command;
+use crate::doctor::handle_doctor_command;
+use crate::health::handle_health_command;
+use crate::insights::handle_insights_command;
+use crate::export::handle_export_command;
+use crate::backlinks::handle_backlinks_command;
+use crate::related::handle_related_command;
+use crate::link_graph::handle_links_command;
+use crate::tags::handle_tags_command;
+use crate::managed_sections::handle_sections_command;
+use crate::conflicts::handle_conflicts_command;
+use crate::reindex::handle_index_command;
+use crate::search::{handle_saved_command, handle_search_command};
+use crate::split::handle_split_command;
+use crate::invites::handle_invites_command;
+use crate::meetings::handle_meeting_command;
+use crate::templates::handle_template_command;
+use crate::filing::handle_file_command;
+use crate::glossary::handle_glossary_command;
+use crate::memory::handle_memory_command;
+use crate::sessions::handle_sessions_command;
+use crate::usage::handle_usage_command;
+use crate::cache::handle_cache_command;
+use crate::vaults::handle_vault_command;
+use crate::prompts::handle_prompts_command;
+use crate::completions::{handle_completions_command, handle_manpages_command};
+use crate::reminders::handle_remind_command;
+use crate::history::{handle_history_command, handle_undo_command};
+use crate::opt_out::handle_mute_command;
+use crate::organize::{handle_apply_plan_command, handle_organize_command};
+use crate::plugin_api::handle_serve_command;
+use crate::risks::handle_risks_command;
+use crate::week_plan::handle_plan_command;
+use crate::script::handle_run_command;
 use crate::config::Config;
+use crate::error::ArrowheadError;
+
+/// Parse `argv` with the command tree from `build_cli`
+fn parse_from(argv: impl IntoIterator<Item = String>) -> Result<Cli, clap::Error> {
+    let mut matches = build_cli().try_get_matches_from(argv)?;
+    Cli::from_arg_matches_mut(&mut matches).map_err(|e| e.format(&mut build_cli()))
+}
+
+/// Parse the command line. An unknown command or subcommand gets "did you mean" suggestions
+/// (and, on a terminal, an offer to run the closest one) instead of clap's error.
+pub fn parse_cli() -> Cli {
+    let argv: Vec<String> = std::env::args().collect();
+    let error = match parse_from(argv.iter().cloned()) {
+        Ok(cli) => return cli,
+        Err(error) => error,
+    };
+    let args = &argv[1..];
+    let unknown = match error.kind() {
+        ErrorKind::InvalidSubcommand => find_unknown_command(args),
+        _ => None,
+    };
+    let Some(unknown) = unknown else {
+        error.exit();
+    };
+    match recover_unknown_command(args, &unknown, &plugin_commands(), &mut StdinPrompt, &mut io::stderr()) {
+        Ok(Some(Recovery::Command(fixed))) => {
+            parse_from(std::iter::once(argv[0].clone()).chain(fixed)).unwrap_or_else(|e| e.exit())
+        }
+        Ok(Some(Recovery::Plugin(name, rest))) => {
+            let status = process::Command::new(format!("{}{}", PLUGIN_PREFIX, name)).args(rest).status();
+            match status {
+                Ok(status) => process::exit(status.code().unwrap_or(1)),
+                Err(e) => {
+                    eprintln!("Error: could not run {}{}: {}", PLUGIN_PREFIX, name, e);
+                    process::exit(1);
+                }
+            }
+        }
+        Ok(None) => process::exit(2),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(2);
+        }
+    }
+}
+
+/// Print why a command failed, with what to do about it when the cause is a known
+/// `ArrowheadError`, and return the exit status for it
+pub fn report_error(error: &anyhow::Error) -> i32 {
+    match ArrowheadError::of(error) {
+        Some(known) => {
+            eprintln!("Error: {:#}", error);
+            if let Some(hint) = known.hint() {
+                eprintln!("💡 {}", hint);
+            }
+            known.exit_code()
+        }
+        None => {
+            eprintln!("Error: {:?}", error);
+            1
+        }
+    }
+}
 
 pub async fn route_command(cli: Cli, adapter: &ObsidianAdapter) -> Result<()> {
+    let view = cli.report_view();
     match cli.command {
         Some(Commands::Todo(todo_args)) => {
-            handle_todo_command(todo_args, adapter).await
+            handle_todo_command(todo_args, cli.format, adapter).await
         }
         Some(Commands::Note(note_args)) => {
-            handle_note_command(note_args, adapter).await
+            handle_note_command(note_args, cli.format, adapter).await
         }
         Some(Commands::Goal(goal_args)) => {
-            handle_goal_command(goal_args, adapter).await
+            handle_goal_command(goal_args, cli.format, adapter).await
+        }
+        Some(Commands::Calendar(calendar_args)) => {
+            handle_calendar_command(calendar_args).await
+        }
+        Some(Commands::Sync(sync_args)) => {
+            handle_sync_command(sync_args, adapter).await
+        }
+        Some(Commands::Glossary(glossary_args)) => {
+            handle_glossary_command(glossary_args, adapter).await
+        }
+        Some(Commands::Organize(organize_args)) => {
+            handle_organize_command(organize_args, adapter).await
+        }
+        Some(Commands::ApplyPlan(apply_plan_args)) => {
+            handle_apply_plan_command(apply_plan_args, adapter).await
+        }
+        Some(Commands::Mute(mute_args)) => {
+            handle_mute_command(mute_args, adapter, true).await
+        }
+        Some(Commands::Unmute(mute_args)) => {
+            handle_mute_command(mute_args, adapter, false).await
+        }
+        Some(Commands::Risks(risks_args)) => {
+            handle_risks_command(risks_args, view).await
+        }
+        Some(Commands::Deadline(deadline_args)) => {
+            handle_deadline_command(deadline_args).await
+        }
+        Some(Commands::Focus(focus_args)) => {
+            handle_focus_command(focus_args).await
+        }
+        Some(Commands::Schedule(schedule_args)) => {
+            handle_schedule_command(schedule_args).await
+        }
+        Some(Commands::Plan(plan_args)) => {
+            handle_plan_command(plan_args, adapter).await
+        }
+        Some(Commands::Daily(daily_args)) => {
+            handle_daily_command(daily_args, adapter).await
+        }
+        Some(Commands::Watch(watch_args)) => {
+            handle_watch_command(watch_args, adapter).await
+        }
+        Some(Commands::Serve(serve_args)) => {
+            handle_serve_command(serve_args, adapter).await
+        }
+        Some(Commands::Changelog(changelog_args)) => {
+            handle_changelog_command(changelog_args, adapter).await
+        }
+        Some(Commands::File(file_args)) => {
+            handle_file_command(file_args, view, adapter).await
+        }
+        Some(Commands::Doctor) => {
+            handle_doctor_command(view, adapter).await
+        }
+        Some(Commands::Health(health_args)) => {
+            handle_health_command(health_args, view, adapter).await
+        }
+        Some(Commands::Insights(insights_args)) => {
+            handle_insights_command(insights_args, view, adapter).await
+        }
+        Some(Commands::Export(export_args)) => {
+            handle_export_command(export_args, adapter).await
+        }
+        Some(Commands::Backlinks(backlinks_args)) => {
+            handle_backlinks_command(backlinks_args, adapter).await
+        }
+        Some(Commands::Links(links_args)) => {
+            handle_links_command(links_args).await
+        }
+        Some(Commands::Related(related_args)) => {
+            handle_related_command(related_args, adapter).await
+        }
+        Some(Commands::Tags(tags_args)) => {
+            handle_tags_command(tags_args, view, adapter).await
+        }
+        Some(Commands::Sections(sections_args)) => {
+            handle_sections_command(sections_args, view, adapter).await
+        }
+        Some(Commands::Conflicts(conflicts_args)) => {
+            handle_conflicts_command(conflicts_args, adapter).await
+        }
+        Some(Commands::Index(index_args)) => {
+            handle_index_command(index_args, view, adapter).await
+        }
+        Some(Commands::Search(search_args)) => {
+            handle_search_command(search_args, adapter).await
+        }
+        Some(Commands::Saved(saved_args)) => {
+            handle_saved_command(saved_args, adapter).await
+        }
+        Some(Commands::Split(split_args)) => {
+            handle_split_command(split_args, adapter).await
+        }
+        Some(Commands::Invites(invites_args)) => {
+            handle_invites_command(invites_args).await
+        }
+        Some(Commands::Meeting(meeting_args)) => {
+            handle_meeting_command(meeting_args, adapter).await
+        }
+        Some(Commands::Template(template_args)) => {
+            handle_template_command(template_args).await
+        }
+        Some(Commands::Run(run_args)) => {
+            handle_run_command(run_args, adapter).await
+        }
+        Some(Commands::Memory(memory_args)) => {
+            handle_memory_command(memory_args).await
+        }
+        Some(Commands::Sessions(sessions_args)) => {
+            handle_sessions_command(sessions_args, view).await
+        }
+        Some(Commands::Usage) => {
+            handle_usage_command(view).await
+        }
+        Some(Commands::Remind(remind_args)) => {
+            handle_remind_command(remind_args, adapter).await
+        }
+        Some(Commands::Undo(undo_args)) => {
+            handle_undo_command(undo_args, adapter).await
+        }
+        Some(Commands::History(history_args)) => {
+            handle_history_command(history_args, adapter).await
+        }
+        Some(Commands::Cache(cache_args)) => {
+            handle_cache_command(cache_args, view, adapter).await
+        }
+        Some(Commands::Vault(vault_args)) => {
+            handle_vault_command(vault_args).await
+        }
+        Some(Commands::Prompts(prompts_args)) => {
+            handle_prompts_command(prompts_args).await
+        }
+        Some(Commands::Completions(completions_args)) => {
+            handle_completions_command(completions_args)
+        }
+        Some(Commands::Manpages(manpages_args)) => {
+            handle_manpages_command(manpages_args)
         }
         Some(Commands::Config(config_args)) => {
             handle_config_command(config_args).await
@@ -28,6 +277,25 @@ pub async fn route_command(cli: Cli, adapter: &ObsidianAdapter) -> Result<()> {
 }
 
 async fn handle_config_command(config_args: crate::cli::ConfigArgs) -> Result<()> {
+    match config_args.action {
+        Some(ConfigAction::Set { key, value }) => return set_config_key(&key, &value),
+        Some(ConfigAction::Profiles) => return crate::profiles::handle_profiles_command(),
+        Some(ConfigAction::Get { key }) => {
+            let config = Config::load()?;
+            let key = config.resolve_key(&key);
+            let value = config.get_key(&key)?;
+            if is_secret_key(&key) && !value.is_empty() {
+                println!("[REDACTED]");
+            } else {
+                println!("{}", value);
+            }
+            if let Some(var) = crate::config::env_override(&key) {
+                println!("(from {})", var);
+            }
+            return Ok(());
+        }
+        None => {}
+    }
     if config_args.init {
         println!("Creating sample configuration file...");
         Config::create_sample_config()?;
@@ -56,32 +324,11 @@ async fn handle_config_command(config_args: crate::cli::ConfigArgs) -> Result<()
             }
         }
     } else if let (Some(key), Some(value)) = (config_args.set.as_ref(), config_args.value.as_ref()) {
-        // Handle --set command
-        let mut config = Config::load().unwrap_or_default();
-        
-        match config.set_value(key, value) {
-            Ok(()) => {
-                config.save()?;
-                
-                // Special handling for API keys - don't show the actual value
-                if key.ends_with(".api_key") {
-                    println!("✅ Configuration updated: {} = [REDACTED]", key);
-                } else {
-                    println!("✅ Configuration updated: {} = {}", key, value);
-                }
-            }
-            Err(e) => {
-                println!("❌ Error setting configuration: {}", e);
-                println!("\nAvailable configuration keys:");
-                for available_key in Config::get_available_keys() {
-                    println!("  {}", available_key);
-                }
-            }
-        }
+        set_config_key(key, value)?;
     } else if config_args.set.is_some() {
         println!("❌ Error: --set requires both --set and --value arguments");
         println!("\nUsage:");
-        println!("  arrowhead config --set gemini.api_key --value \"your_api_key\"");
+        println!("  arrowhead config set llm.api_key \"your_api_key\"");
         println!("\nAvailable configuration keys:");
         for key in Config::get_available_keys() {
             println!("  {}", key);
@@ -90,11 +337,13 @@ async fn handle_config_command(config_args: crate::cli::ConfigArgs) -> Result<()
         println!("Usage:");
         println!("  arrowhead config --init                              Create sample configuration");
         println!("  arrowhead config --show                              Show current configuration");
-        println!("  arrowhead config --set <key> --value <value>         Set configuration value");
+        println!("  arrowhead config set <key> <value>                   Set configuration value");
+        println!("  arrowhead config get <key>                           Show one configuration value");
+        println!("  arrowhead config profiles                            List profiles and their settings");
         println!("\nExamples:");
-        println!("  arrowhead config --set gemini.api_key --value \"your_api_key\"");
-        println!("  arrowhead config --set provider --value \"openai\"");
-        println!("  arrowhead config --set gemini.temperature --value \"0.8\"");
+        println!("  arrowhead config set llm.provider openai");
+        println!("  arrowhead config set llm.temperature 0.8");
+        println!("  arrowhead config set obsidian.base_url http://localhost:27123");
         println!("\nAvailable configuration keys:");
         for key in Config::get_available_keys() {
             println!("  {}", key);
@@ -102,3 +351,31 @@ async fn handle_config_command(config_args: crate::cli::ConfigArgs) -> Result<()
     }
     Ok(())
 }
+
+fn is_secret_key(key: &str) -> bool {
+    key.ends_with("api_key") || key.ends_with("password")
+}
+
+fn set_config_key(key: &str, value: &str) -> Result<()> {
+    match Config::set_key(key, value) {
+        Ok(key) => {
+            // Don't echo secrets
+            if is_secret_key(&key) {
+                println!("✅ Configuration updated: {} = [REDACTED]", key);
+            } else {
+                println!("✅ Configuration updated: {} = {}", key, value);
+            }
+            if let Some(var) = crate::config::env_override(&key) {
+                println!("⚠️  {} is set in the environment and takes precedence over the config file", var);
+            }
+        }
+        Err(e) => {
+            println!("Commonly used configuration keys:");
+            for available_key in Config::get_available_keys() {
+                println!("  {}", available_key);
+            }
+            return Err(e.context("Configuration not changed"));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+
+use crate::calendar::{create_calendar_adapter, parse_event_time, EventTime};
+use crate::calendar_adapter::{
+    confidence_factors, confidence_from, AiSchedulingContext, CalendarEvent, EnergyLevel, ImportanceLevel, MeetingContext, MeetingType,
+    OptimizationFactor, OptimizationGoal, TimeSlot, UserPreferences, DEFAULT_CALENDAR_ID,
+};
+use crate::cli::{ScheduleAction, ScheduleArgs};
+use crate::config::Config;
+use crate::daily::day_bounds;
+use crate::invites::FocusBlock;
+use crate::timezone::{self, TimeZone};
+
+/// `[scheduling]`: what meeting slots are weighed against when arrowhead suggests times
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulingSettings {
+    pub max_meetings_per_day: u32,
+    /// Minutes; slots of other lengths rank lower
+    pub preferred_meeting_duration: u32,
+    pub break_duration_minutes: u32,
+    /// Windows kept free of meetings, on the wall clock of `[calendar] time_zone`
+    pub focus_blocks: Vec<FocusBlock>,
+    /// Energy, focus capacity and meeting suitability (1–5) by hour of the day, e.g.
+    /// `[[scheduling.energy]] time_of_day = 9`; hours left out count as neutral
+    pub energy: Vec<EnergyLevel>,
+}
+
+impl Default for SchedulingSettings {
+    fn default() -> Self {
+        Self {
+            max_meetings_per_day: 5,
+            preferred_meeting_duration: 30,
+            break_duration_minutes: 15,
+            focus_blocks: Vec::new(),
+            energy: default_energy_curve(),
+        }
+    }
+}
+
+/// A typical day: focus peaks mid-morning, meetings suit the early afternoon best
+pub fn default_energy_curve() -> Vec<EnergyLevel> {
+    [
+        (7, 3.0, 3.0, 2.0),
+        (8, 3.5, 4.0, 2.5),
+        (9, 4.5, 5.0, 3.0),
+        (10, 5.0, 5.0, 3.5),
+        (11, 4.5, 4.5, 4.0),
+        (12, 3.5, 3.0, 3.5),
+        (13, 3.0, 2.5, 4.0),
+        (14, 3.5, 3.0, 4.5),
+        (15, 3.5, 3.5, 4.5),
+        (16, 3.0, 3.0, 4.0),
+        (17, 2.5, 2.5, 3.0),
+        (18, 2.0, 2.0, 2.0),
+    ]
+    .into_iter()
+    .map(|(time_of_day, energy_score, focus_capacity, meeting_suitability)| EnergyLevel { time_of_day, energy_score, focus_capacity, meeting_suitability })
+    .collect()
+}
+
+impl SchedulingSettings {
+    /// The preferences for slots between `from` and `to`, with the focus blocks of each day in
+    /// that range laid out on the wall clock of `zone`
+    pub fn user_preferences(&self, zone: &TimeZone, from: DateTime<Utc>, to: DateTime<Utc>) -> UserPreferences {
+        let mut focus_time_blocks = Vec::new();
+        let mut day = zone.to_local(from).date();
+        while day <= zone.to_local(to).date() {
+            let weekday = day.weekday().num_days_from_sunday();
+            for block in self.focus_blocks.iter().filter(|block| block.days.contains(&weekday)) {
+                let end = if block.end_hour >= 24 { zone.from_local((day + Duration::days(1)).and_hms_opt(0, 0, 0).expect("midnight")) } else {
+                    let Some(end) = zone.at(day, block.end_hour, 0) else { continue };
+                    end
+                };
+                let Some(start) = zone.at(day, block.start_hour, 0) else { continue };
+                focus_time_blocks.push(TimeSlot { start_time: start, end_time: end, calendar_id: None, event_id: None });
+            }
+            day += Duration::days(1);
+        }
+        UserPreferences {
+            preferred_meeting_times: Vec::new(),
+            avoid_times: Vec::new(),
+            max_meetings_per_day: self.max_meetings_per_day,
+            preferred_meeting_duration: self.preferred_meeting_duration,
+            break_duration_minutes: self.break_duration_minutes,
+            focus_time_blocks,
+            energy_patterns: self.energy.clone(),
+            commute_time_minutes: 0,
+            timezone: zone.name().to_string(),
+        }
+    }
+
+    /// A scheduling context for an ordinary meeting between `from` and `to`
+    pub fn context(&self, zone: &TimeZone, from: DateTime<Utc>, to: DateTime<Utc>) -> AiSchedulingContext {
+        AiSchedulingContext {
+            user_preferences: self.user_preferences(zone, from, to),
+            historical_patterns: Vec::new(),
+            meeting_context: MeetingContext {
+                meeting_type: MeetingType::ProjectReview,
+                importance_level: ImportanceLevel::Medium,
+                required_preparation_time: 0,
+                post_meeting_buffer: self.break_duration_minutes,
+                participants: Vec::new(),
+                decision_making_required: false,
+                creative_work_required: false,
+            },
+            optimization_goals: vec![OptimizationGoal::RespectEnergyLevels, OptimizationGoal::ReduceMeetingFatigue],
+        }
+    }
+}
+
+/// Each factor's multiplier and reason, then the confidence they come to
+pub fn render_explanation(slot: &TimeSlot, factors: &[OptimizationFactor], zone: &TimeZone) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{}–{} ({})",
+        zone.to_local(slot.start_time).format("%a %Y-%m-%d %H:%M"),
+        zone.to_local(slot.end_time).format("%H:%M"),
+        zone.name()
+    );
+    for factor in factors {
+        let _ = writeln!(out, "  ×{:.2}  {:<20} {}", factor.weight, factor.factor_type, factor.explanation);
+    }
+    let _ = writeln!(out, "  = {:.2} confidence", confidence_from(factors));
+    out
+}
+
+pub async fn handle_schedule_command(args: ScheduleArgs) -> Result<()> {
+    match args.action {
+        ScheduleAction::Explain { slot, duration } => {
+            let config = Config::load()?;
+            let zone = timezone::resolve(&config.calendar.time_zone)?;
+            let now = Utc::now();
+            let start = match parse_event_time(&slot, now, &zone)? {
+                EventTime::Exact(start) => start,
+                EventTime::Day(day) => bail!("'{}' is a whole day; give a start time too, e.g. \"{} 14:00\"", slot, day),
+            };
+            let minutes = duration.unwrap_or(config.scheduling.preferred_meeting_duration).max(1);
+            let slot = TimeSlot { start_time: start, end_time: start + Duration::minutes(minutes as i64), calendar_id: None, event_id: None };
+
+            let (day_start, day_end) = day_bounds(zone.to_local(start).date(), &zone);
+            let events: Vec<CalendarEvent> = match create_calendar_adapter(&config.calendar, &config.network) {
+                Ok(calendar) => match calendar.list_events(DEFAULT_CALENDAR_ID, Some(day_start), Some(day_end)).await {
+                    Ok(events) => events,
+                    Err(e) => {
+                        println!("ℹ️  Not counting calendar events ({})", e);
+                        Vec::new()
+                    }
+                },
+                Err(e) => {
+                    println!("ℹ️  Not counting calendar events ({})", e);
+                    Vec::new()
+                }
+            };
+
+            let context = config.scheduling.context(&zone, day_start, day_end);
+            let factors = confidence_factors(&slot, &events, &context, &zone);
+            print!("{}", render_explanation(&slot, &factors, &zone));
+            crate::script::publish_output(serde_json::json!({
+                "slot": slot,
+                "factors": factors,
+                "confidence": confidence_from(&factors),
+            }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    fn event(start: DateTime<Utc>, minutes: i64) -> CalendarEvent {
+        CalendarEvent {
+            id: start.to_rfc3339(),
+            title: "Sync".to_string(),
+            description: None,
+            start_time: start,
+            end_time: start + Duration::minutes(minutes),
+            location: None,
+            attendees: vec!["ana@example.com".to_string()],
+            all_day: false,
+            recurring: false,
+            calendar_id: DEFAULT_CALENDAR_ID.to_string(),
+        }
+    }
+
+    fn slot(hour: u32, minutes: i64) -> TimeSlot {
+        // Monday
+        let start = Utc.with_ymd_and_hms(2024, 6, 3, hour, 0, 0).unwrap();
+        TimeSlot { start_time: start, end_time: start + Duration::minutes(minutes), calendar_id: None, event_id: None }
+    }
+
+    #[test]
+    fn test_factors_follow_energy_focus_blocks_and_meeting_load() {
+        let zone = TimeZone::utc();
+        let mut settings = SchedulingSettings {
+            max_meetings_per_day: 2,
+            focus_blocks: vec![FocusBlock { days: vec![1], start_hour: 9, end_hour: 11 }],
+            ..SchedulingSettings::default()
+        };
+        let (from, to) = day_bounds(slot(0, 0).start_time.date_naive(), &zone);
+        let context = settings.context(&zone, from, to);
+        let score = |slot: &TimeSlot, events: &[CalendarEvent]| confidence_from(&confidence_factors(slot, events, &context, &zone));
+
+        // The early afternoon suits meetings better than the focus-heavy morning
+        assert!(score(&slot(14, 30), &[]) > score(&slot(11, 30), &[]));
+        // A focus block and a mismatched length both cost
+        assert!(score(&slot(10, 30), &[]) < score(&slot(11, 30), &[]) * 0.5);
+        assert!(score(&slot(14, 90), &[]) < score(&slot(14, 30), &[]));
+
+        // Two meetings already that day fill it
+        let busy = [event(slot(8, 0).start_time, 30), event(slot(16, 0).start_time, 30)];
+        let factors = confidence_factors(&slot(14, 30), &busy, &context, &zone);
+        let load = factors.iter().find(|f| f.factor_type == "Meetings that day").unwrap();
+        assert_eq!((load.value, load.weight), (2.0, 0.3));
+
+        settings.energy.clear();
+        let context = settings.context(&zone, from, to);
+        let factors = confidence_factors(&slot(14, 30), &[], &context, &zone);
+        assert!(factors.iter().any(|f| f.factor_type == "Meeting suitability" && f.weight == 1.0));
+        assert!(render_explanation(&slot(14, 30), &factors, &zone).contains("= 1.00 confidence"));
+    }
+}
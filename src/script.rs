@@ -0,0 +1,486 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::cli::{Cli, RunArgs};
+use crate::config::Config;
+use crate::obsidian_adapter::ObsidianAdapter;
+
+/// One command of a script, e.g. `command = "todo reschedule"` with `args = ["overdue by 1 day", "--yes"]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptStep {
+    /// Name for templates (`{{<id>.output...}}`); steps are always reachable as `step_<n>` too
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Command name, optionally with its subcommand
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Keep going when this step fails instead of skipping the rest of the script
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Script {
+    pub steps: Vec<ScriptStep>,
+    /// How many LLM-heavy steps the whole script may run; later ones fail
+    #[serde(default)]
+    pub max_llm_steps: Option<usize>,
+}
+
+impl Script {
+    /// Read a `.toml`, `.yaml` or `.yml` script
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read script {}", path.display()))?;
+        let script: Script = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&text).with_context(|| format!("Invalid script {}", path.display()))?,
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&text).with_context(|| format!("Invalid script {}", path.display()))?,
+            _ => bail!("Scripts must be .toml, .yaml or .yml files"),
+        };
+        if script.steps.is_empty() {
+            bail!("Script {} has no steps", path.display());
+        }
+        Ok(script)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepReport {
+    pub index: usize,
+    pub id: String,
+    /// The command line after templates were resolved
+    pub argv: Vec<String>,
+    pub status: StepStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Structured output the command published, if any
+    pub output: Value,
+    pub duration_ms: u128,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunReport {
+    pub started_at: DateTime<Utc>,
+    pub succeeded: bool,
+    pub llm_steps: usize,
+    pub steps: Vec<StepReport>,
+}
+
+impl RunReport {
+    pub fn summary(&self) -> String {
+        let count = |status| self.steps.iter().filter(|s| s.status == status).count();
+        format!(
+            "{} {} step(s): {} ok, {} failed, {} skipped",
+            if self.succeeded { "✅" } else { "❌" },
+            self.steps.len(),
+            count(StepStatus::Ok),
+            count(StepStatus::Failed),
+            count(StepStatus::Skipped),
+        )
+    }
+}
+
+/// The configuration loaded once for a script run, handed to every step
+pub struct SharedConfig {
+    config: Config,
+    reads: AtomicUsize,
+}
+
+impl SharedConfig {
+    /// How many times steps asked for the configuration
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::Relaxed)
+    }
+}
+
+tokio::task_local! {
+    static SCRIPT_CONFIG: Arc<SharedConfig>;
+    static STEP_OUTPUT: RefCell<Option<Value>>;
+}
+
+/// The script's configuration when called from inside a script step, so `Config::load`
+/// doesn't re-read the file for every step
+pub fn shared_config() -> Option<Config> {
+    SCRIPT_CONFIG.try_with(|shared| {
+        shared.reads.fetch_add(1, Ordering::Relaxed);
+        shared.config.clone()
+    }).ok()
+}
+
+/// Make `value` the current step's output for later `{{step_n.output...}}` templates.
+/// Does nothing outside a script.
+pub fn publish_output(value: Value) {
+    let _ = STEP_OUTPUT.try_with(|output| *output.borrow_mut() = Some(value));
+}
+
+/// Run a command and return what it passed to `publish_output`, if anything
+pub async fn capture_output(command: impl std::future::Future<Output = Result<()>>) -> Result<Option<Value>> {
+    STEP_OUTPUT.scope(RefCell::new(None), async {
+        command.await?;
+        Ok(STEP_OUTPUT.with(|output| output.borrow_mut().take()))
+    }).await
+}
+
+/// Runs one resolved command line
+#[async_trait(?Send)]
+pub trait StepExecutor {
+    async fn execute(&self, argv: &[String]) -> Result<()>;
+}
+
+/// Runs steps through the regular command router
+pub struct CliExecutor<'a> {
+    pub adapter: &'a ObsidianAdapter,
+}
+
+#[async_trait(?Send)]
+impl StepExecutor for CliExecutor<'_> {
+    async fn execute(&self, argv: &[String]) -> Result<()> {
+        let cli = parse_step(argv)?;
+        crate::router::route_command(cli, self.adapter).await
+    }
+}
+
+pub(crate) fn parse_step(argv: &[String]) -> Result<Cli> {
+    if argv.first().map(String::as_str) == Some("run") {
+        bail!("Scripts cannot run other scripts");
+    }
+    Cli::try_parse_from(std::iter::once("arrowhead".to_string()).chain(argv.iter().cloned()))
+        .map_err(|e| anyhow::anyhow!("{}", e.render().to_string().trim_end()))
+}
+
+/// Commands that spend LLM calls and count against `max_llm_steps`
+fn uses_llm(argv: &[String], config: &Config) -> bool {
+    match argv.first().map(String::as_str) {
+        Some("organize") => true,
+        Some("changelog") => config.changelog.llm_refine,
+        _ => argv.iter().any(|a| a == "--llm-assist"),
+    }
+}
+
+fn step_argv(step: &ScriptStep) -> Vec<String> {
+    step.command.split_whitespace().map(str::to_string).chain(step.args.iter().cloned()).collect()
+}
+
+/// Look up `{{<step>.output.a.b.0}}` or `{{<step>.status}}` against earlier steps
+fn lookup(reference: &str, steps: &[StepReport]) -> Result<String> {
+    let mut parts = reference.split('.');
+    let name = parts.next().unwrap_or_default();
+    let step = steps.iter().find(|s| s.id == name || format!("step_{}", s.index) == name)
+        .with_context(|| format!("`{}` does not name an earlier step", name))?;
+    let mut value = match parts.next() {
+        Some("output") if step.status == StepStatus::Ok => &step.output,
+        Some("output") => bail!("step `{}` did not succeed, so it has no output", name),
+        Some("status") => {
+            return Ok(serde_json::to_value(step.status)?.as_str().unwrap_or_default().to_string());
+        }
+        _ => bail!("expected `{}.output...` or `{}.status`", name, name),
+    };
+    for part in parts {
+        value = match value {
+            Value::Array(items) => part.parse::<usize>().ok().and_then(|i| items.get(i)),
+            Value::Object(fields) => fields.get(part),
+            _ => None,
+        }
+        .with_context(|| format!("step `{}` output has no `{}`", name, reference))?;
+    }
+    Ok(match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    })
+}
+
+/// Replace every `{{...}}` in `text` with values from earlier steps
+pub fn resolve_template(text: &str, steps: &[StepReport]) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}").with_context(|| format!("unclosed `{{{{` in \"{}\"", text))?;
+        out.push_str(&rest[..start]);
+        out.push_str(&lookup(rest[start + 2..start + end].trim(), steps)?);
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+pub struct ScriptRunner<'a> {
+    executor: &'a dyn StepExecutor,
+    shared: Arc<SharedConfig>,
+}
+
+impl<'a> ScriptRunner<'a> {
+    pub fn new(executor: &'a dyn StepExecutor, config: Config) -> Self {
+        Self { executor, shared: Arc::new(SharedConfig { config, reads: AtomicUsize::new(0) }) }
+    }
+
+    pub fn shared_config(&self) -> &SharedConfig {
+        &self.shared
+    }
+
+    /// The plan with templates left unresolved, as `run --dry-run` prints it
+    pub fn plan(&self, script: &Script) -> Vec<String> {
+        script.steps.iter().enumerate().map(|(i, step)| {
+            let argv = step_argv(step);
+            let mut line = format!("{}. {}", i + 1, argv.join(" "));
+            if argv.iter().any(|a| a.contains("{{")) {
+                line.push_str("   (templates resolved at run time)");
+            }
+            if uses_llm(&argv, &self.shared.config) {
+                line.push_str("   [LLM]");
+            }
+            if step.continue_on_error {
+                line.push_str("   [continue on error]");
+            }
+            line
+        }).collect()
+    }
+
+    pub async fn run(&self, script: &Script) -> RunReport {
+        let started_at = Utc::now();
+        let mut reports: Vec<StepReport> = Vec::new();
+        let mut llm_steps = 0;
+        let mut halted = false;
+
+        for (i, step) in script.steps.iter().enumerate() {
+            let index = i + 1;
+            let id = step.id.clone().unwrap_or_else(|| format!("step_{}", index));
+            let mut report = StepReport {
+                index,
+                id,
+                argv: step_argv(step),
+                status: StepStatus::Skipped,
+                error: None,
+                output: Value::Null,
+                duration_ms: 0,
+            };
+            if halted {
+                reports.push(report);
+                continue;
+            }
+
+            let start = Instant::now();
+            let result = match report.argv.iter().map(|a| resolve_template(a, &reports)).collect::<Result<Vec<_>>>() {
+                Ok(argv) => {
+                    report.argv = argv;
+                    let over_budget = uses_llm(&report.argv, &self.shared.config)
+                        && script.max_llm_steps.is_some_and(|max| llm_steps >= max);
+                    if over_budget {
+                        Err(anyhow::anyhow!("LLM budget of {} step(s) exhausted", llm_steps))
+                    } else {
+                        if uses_llm(&report.argv, &self.shared.config) {
+                            llm_steps += 1;
+                        }
+                        self.execute(&report.argv).await
+                    }
+                }
+                Err(e) => Err(e.context("could not resolve templates")),
+            };
+            report.duration_ms = start.elapsed().as_millis();
+            match result {
+                Ok(output) => {
+                    report.status = StepStatus::Ok;
+                    report.output = output.unwrap_or(Value::Null);
+                }
+                Err(e) => {
+                    report.status = StepStatus::Failed;
+                    report.error = Some(format!("{:#}", e));
+                    halted = !step.continue_on_error;
+                }
+            }
+            reports.push(report);
+        }
+
+        RunReport {
+            started_at,
+            succeeded: reports.iter().all(|s| s.status == StepStatus::Ok),
+            llm_steps,
+            steps: reports,
+        }
+    }
+
+    async fn execute(&self, argv: &[String]) -> Result<Option<Value>> {
+        SCRIPT_CONFIG.scope(self.shared.clone(), capture_output(self.executor.execute(argv))).await
+    }
+}
+
+pub async fn handle_run_command(args: RunArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let script = Script::load(Path::new(&args.script))?;
+    let executor = CliExecutor { adapter };
+    let runner = ScriptRunner::new(&executor, Config::load().unwrap_or_default());
+
+    if args.dry_run {
+        for step in &script.steps {
+            let argv = step_argv(step);
+            if !argv.iter().any(|a| a.contains("{{")) {
+                parse_step(&argv)?;
+            }
+        }
+        for line in runner.plan(&script) {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    let report = runner.run(&script).await;
+    let json = serde_json::to_string_pretty(&report)?;
+    if let Some(path) = &args.report {
+        std::fs::write(path, &json).with_context(|| format!("Failed to write run report to {}", path))?;
+    }
+    if args.json {
+        println!("{}", json);
+    } else {
+        for step in report.steps.iter().filter(|s| s.status != StepStatus::Ok) {
+            let status = if step.status == StepStatus::Failed { "failed" } else { "skipped" };
+            println!("{} {} ({}){}", step.index, step.id, status, step.error.as_ref().map(|e| format!(": {}", e)).unwrap_or_default());
+        }
+        println!("{}", report.summary());
+    }
+    if !report.succeeded {
+        bail!("Script did not complete");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Records every command line; `fail` commands error, others publish their canned output
+    #[derive(Default)]
+    struct FakeExecutor {
+        outputs: HashMap<String, Value>,
+        fail: Vec<String>,
+        calls: Mutex<Vec<Vec<String>>>,
+        configs_seen: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait(?Send)]
+    impl StepExecutor for FakeExecutor {
+        async fn execute(&self, argv: &[String]) -> Result<()> {
+            self.calls.lock().unwrap().push(argv.to_vec());
+            // Every step loads config the way command handlers do
+            let config = Config::load()?;
+            self.configs_seen.lock().unwrap().push(config.general.max_conversation_history);
+            if self.fail.contains(&argv[0]) {
+                bail!("{} exploded", argv[0]);
+            }
+            if let Some(output) = self.outputs.get(&argv[0]) {
+                publish_output(output.clone());
+            }
+            Ok(())
+        }
+    }
+
+    fn step(command: &str, args: &[&str]) -> ScriptStep {
+        ScriptStep { id: None, command: command.to_string(), args: args.iter().map(|a| a.to_string()).collect(), continue_on_error: false }
+    }
+
+    fn marked_config() -> Config {
+        let mut config = Config::default();
+        config.general.max_conversation_history = 4242;
+        config
+    }
+
+    #[tokio::test]
+    async fn test_templates_resolve_against_earlier_outputs() {
+        let executor = FakeExecutor {
+            outputs: HashMap::from([("risks".to_string(), json!({ "critical": [{ "title": "Launch", "days": 3 }] }))]),
+            ..Default::default()
+        };
+        let mut first = step("risks", &["--json"]);
+        first.id = Some("risk".to_string());
+        let script = Script {
+            steps: vec![
+                first,
+                step("note", &["append", "{{risk.output.critical.0.title}}", "due in {{step_1.output.critical.0.days}} days ({{ risk.status }})"]),
+            ],
+            max_llm_steps: None,
+        };
+        let report = ScriptRunner::new(&executor, marked_config()).run(&script).await;
+
+        assert!(report.succeeded);
+        assert_eq!(report.steps[1].argv, vec!["note", "append", "Launch", "due in 3 days (ok)"]);
+        assert!(resolve_template("{{risk.output.missing}}", &report.steps).unwrap_err().to_string().contains("no `risk.output.missing`"));
+        assert!(resolve_template("{{step_9.output}}", &report.steps).is_err());
+        assert!(resolve_template("{{risk.output", &report.steps).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failures_stop_the_script_unless_continued() {
+        let executor = FakeExecutor {
+            outputs: HashMap::from([("doctor".to_string(), json!({ "problems": 0 }))]),
+            fail: vec!["changelog".to_string()],
+            ..Default::default()
+        };
+        let mut script = Script {
+            steps: vec![step("doctor", &[]), step("changelog scan", &[]), step("file", &["--all", "{{step_2.output.count}}"])],
+            max_llm_steps: None,
+        };
+
+        let report = ScriptRunner::new(&executor, marked_config()).run(&script).await;
+        let statuses: Vec<StepStatus> = report.steps.iter().map(|s| s.status).collect();
+        assert_eq!(statuses, vec![StepStatus::Ok, StepStatus::Failed, StepStatus::Skipped]);
+        assert_eq!(report.steps[1].error.as_deref(), Some("changelog exploded"));
+        assert!(!report.succeeded);
+        assert_eq!(executor.calls.lock().unwrap().len(), 2);
+
+        script.steps[1].continue_on_error = true;
+        let report = ScriptRunner::new(&executor, marked_config()).run(&script).await;
+        let statuses: Vec<StepStatus> = report.steps.iter().map(|s| s.status).collect();
+        assert_eq!(statuses, vec![StepStatus::Ok, StepStatus::Failed, StepStatus::Failed]);
+        assert!(report.steps[2].error.as_deref().unwrap().contains("did not succeed"));
+    }
+
+    #[tokio::test]
+    async fn test_steps_share_one_config_and_llm_budget() {
+        let executor = FakeExecutor::default();
+        let script = Script {
+            steps: vec![
+                step("organize", &["--all"]),
+                step("todo reschedule", &["overdue by 1 day", "--llm-assist", "--yes"]),
+                step("organize", &["Inbox/a.md"]),
+                step("risks", &[]),
+            ],
+            max_llm_steps: Some(2),
+        };
+        let runner = ScriptRunner::new(&executor, marked_config());
+        let report = runner.run(&script).await;
+
+        // Every executed step saw the runner's config instead of loading its own
+        assert_eq!(runner.shared_config().reads(), 2);
+        assert_eq!(*executor.configs_seen.lock().unwrap(), vec![4242, 4242]);
+        assert_eq!(report.llm_steps, 2);
+        assert_eq!(report.steps[2].error.as_deref(), Some("LLM budget of 2 step(s) exhausted"));
+        assert_eq!(report.steps[3].status, StepStatus::Skipped);
+        assert!(shared_config().is_none());
+        assert!(runner.plan(&script)[0].ends_with("[LLM]"));
+    }
+
+    #[test]
+    fn test_parse_step_rejects_nested_runs_and_bad_commands() {
+        assert!(parse_step(&["run".to_string(), "other.toml".to_string()]).is_err());
+        assert!(parse_step(&["frobnicate".to_string()]).is_err());
+        assert!(parse_step(&["risks".to_string(), "--json".to_string()]).is_ok());
+    }
+}
@@ -0,0 +1,590 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backlinks::note_tags;
+use crate::cli::{SavedAction, SavedArgs, SearchArgs};
+use crate::command_suggest::closest;
+use crate::config::Config;
+use crate::confirm::{confirm, Operation, OperationClass};
+use crate::embedding_provider::embedding_model_id;
+use crate::health::load_notes_in;
+use crate::obsidian_adapter::{ObsidianAdapter, SemanticSearchConfig};
+use crate::reindex::embedding_indexer;
+use crate::snippets::{self, Highlight};
+use crate::vault_transaction::{VaultStore, DEFAULT_READ_CONCURRENCY};
+
+/// Characters of context shown around the matches
+const SNIPPET_CHARS: usize = 120;
+/// Semantic candidates fetched per requested result, so filters still leave enough
+const SEMANTIC_OVERFETCH: usize = 3;
+/// Damping of reciprocal rank fusion: the note ranked `r`-th by a signal scores `1 / (RRF_K + r)`
+const RRF_K: f32 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    Keyword,
+    Semantic,
+    /// Both, fused into one ranking
+    Hybrid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// Most matches first
+    #[default]
+    Relevance,
+    /// Earliest `due_date` first; notes without one last
+    Due,
+    Title,
+}
+
+/// A parsed query: free-text terms plus `tag:`, `status:`, `path:` and `sort:` filters, e.g.
+/// `tag:#waiting status:open sort:due follow up`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub terms: Vec<String>,
+    pub tags: Vec<String>,
+    pub status: Option<String>,
+    pub path: Option<String>,
+    pub sort: SortKey,
+}
+
+pub fn parse_query(text: &str) -> Result<SearchQuery> {
+    let mut query = SearchQuery::default();
+    for word in text.split_whitespace() {
+        match word.split_once(':') {
+            Some(("tag", tag)) => query.tags.push(tag.trim_start_matches('#').to_lowercase()),
+            Some(("status", status)) => query.status = Some(status.to_lowercase()),
+            Some(("path", path)) => query.path = Some(path.trim_end_matches('/').to_string()),
+            Some(("sort", sort)) => query.sort = match sort {
+                "due" => SortKey::Due,
+                "title" => SortKey::Title,
+                "relevance" => SortKey::Relevance,
+                other => bail!("Unknown sort `{}`: use due, title or relevance", other),
+            },
+            _ => query.terms.push(word.to_lowercase()),
+        }
+    }
+    Ok(query)
+}
+
+/// A note matching a query
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchHit {
+    pub path: String,
+    /// The fused score in a hybrid search, otherwise the score of the one signal
+    pub score: f32,
+    /// Occurrences of the query's words, when the note contains them all
+    pub keyword_score: Option<f32>,
+    /// Similarity of the note's embedding to the query's, when it was among the nearest
+    pub semantic_score: Option<f32>,
+    /// The signals that found the note
+    pub matched: Vec<SearchMode>,
+    pub due: Option<String>,
+    pub snippet: String,
+    /// Query words found in `snippet`
+    pub highlights: Vec<Highlight>,
+}
+
+/// The passage of a note with the most query words, and where they are in it
+fn snippet(content: &str, words: &[String]) -> (String, Vec<Highlight>) {
+    let terms = snippets::query_terms(&words.join(" "));
+    let snippet = snippets::snippet(content, &terms, SNIPPET_CHARS);
+    let highlights = snippets::highlights(&snippet, &terms);
+    (snippet, highlights)
+}
+
+/// Whether a note passes the query's filters; its due date when it does
+fn passes_filters(path: &str, content: &str, query: &SearchQuery) -> Option<Option<String>> {
+    if query.path.as_ref().is_some_and(|prefix| !path.starts_with(prefix.as_str())) {
+        return None;
+    }
+    let tags = note_tags(content);
+    if !query.tags.iter().all(|tag| tags.contains(tag)) {
+        return None;
+    }
+    let frontmatter = ObsidianAdapter::parse_markdown_file(content).ok().map(|file| file.frontmatter);
+    let status = frontmatter.as_ref().and_then(|f| f.status.as_ref()).map(|s| s.to_lowercase());
+    if query.status.is_some() && status != query.status {
+        return None;
+    }
+    Some(frontmatter.and_then(|f| f.due_date))
+}
+
+fn sort_hits(hits: &mut [SearchHit], sort: SortKey) {
+    match sort {
+        SortKey::Relevance => hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.path.cmp(&b.path))),
+        SortKey::Due => hits.sort_by(|a, b| match (&a.due, &b.due) {
+            (Some(x), Some(y)) => x.cmp(y).then_with(|| a.path.cmp(&b.path)),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a.path.cmp(&b.path),
+        }),
+        SortKey::Title => hits.sort_by(|a, b| a.path.rsplit('/').next().cmp(&b.path.rsplit('/').next())),
+    }
+}
+
+/// Notes containing every term and passing the filters, most term occurrences first
+fn keyword_matches(notes: &[(String, String)], query: &SearchQuery) -> Vec<SearchHit> {
+    let mut hits: Vec<SearchHit> = notes.iter()
+        .filter_map(|(path, content)| {
+            let due = passes_filters(path, content, query)?;
+            let body = ObsidianAdapter::parse_markdown_file(content).map_or(content.clone(), |file| file.content);
+            let haystack = format!("{}\n{}", path.to_lowercase(), body.to_lowercase());
+            let counts: Vec<usize> = query.terms.iter().map(|term| haystack.matches(term.as_str()).count()).collect();
+            if counts.contains(&0) {
+                return None;
+            }
+            let score = counts.iter().sum::<usize>() as f32;
+            let (snippet, highlights) = snippet(content, &query.terms);
+            Some(SearchHit {
+                path: path.clone(),
+                score,
+                keyword_score: Some(score),
+                semantic_score: None,
+                matched: vec![SearchMode::Keyword],
+                due,
+                snippet,
+                highlights,
+            })
+        })
+        .collect();
+    sort_hits(&mut hits, SortKey::Relevance);
+    hits
+}
+
+/// Notes containing every term and passing the filters, scored by term occurrences
+pub fn keyword_search(notes: &[(String, String)], query: &SearchQuery, limit: usize) -> Vec<SearchHit> {
+    let mut hits = keyword_matches(notes, query);
+    sort_hits(&mut hits, query.sort);
+    hits.truncate(limit);
+    hits
+}
+
+/// Reciprocal rank fusion of the keyword and semantic rankings. A note scores
+/// `1 / (RRF_K + rank)` for each ranking it is in, so notes both signals find rise above
+/// notes only one ranks highly, and neither signal's scale matters.
+pub fn fuse(keyword: Vec<SearchHit>, semantic: Vec<SearchHit>) -> Vec<SearchHit> {
+    let mut fused: Vec<SearchHit> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for ranking in [keyword, semantic] {
+        for (rank, hit) in ranking.into_iter().enumerate() {
+            let score = 1.0 / (RRF_K + rank as f32 + 1.0);
+            match positions.get(&hit.path) {
+                // The keyword hit came first and keeps its snippet
+                Some(&position) => {
+                    let found = &mut fused[position];
+                    found.score += score;
+                    found.keyword_score = found.keyword_score.or(hit.keyword_score);
+                    found.semantic_score = found.semantic_score.or(hit.semantic_score);
+                    found.matched.extend(hit.matched);
+                }
+                None => {
+                    positions.insert(hit.path.clone(), fused.len());
+                    fused.push(SearchHit { score, ..hit });
+                }
+            }
+        }
+    }
+    sort_hits(&mut fused, SortKey::Relevance);
+    fused
+}
+
+/// A query embedding kept with a saved search, valid while the embedding model is the same
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachedEmbedding {
+    pub model: String,
+    pub vector: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+    pub mode: SearchMode,
+    pub limit: usize,
+    /// Only notes under this folder are searched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    /// Only notes with all of these tags are searched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<CachedEmbedding>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Named queries run with `arrowhead saved run <name>`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedSearches {
+    pub searches: Vec<SavedSearch>,
+}
+
+pub fn default_saved_searches_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".config")
+        .join("arrowhead")
+        .join("saved_searches.json")
+}
+
+impl SavedSearches {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Add or replace the search with this name
+    pub fn upsert(&mut self, search: SavedSearch) {
+        self.searches.retain(|s| !s.name.eq_ignore_ascii_case(&search.name));
+        self.searches.push(search);
+        self.searches.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    /// Find a search by exact name, unique prefix, or closest spelling
+    pub fn resolve(&self, name: &str) -> Result<&SavedSearch> {
+        let lower = name.to_lowercase();
+        if let Some(search) = self.searches.iter().find(|s| s.name.to_lowercase() == lower) {
+            return Ok(search);
+        }
+        let prefixed: Vec<&SavedSearch> = self.searches.iter().filter(|s| s.name.to_lowercase().starts_with(&lower)).collect();
+        let candidates: Vec<&str> = match prefixed.as_slice() {
+            [only] => return Ok(only),
+            [] => closest(name, self.searches.iter().map(|s| s.name.as_str())),
+            several => several.iter().map(|s| s.name.as_str()).collect(),
+        };
+        match candidates.as_slice() {
+            [only] => Ok(self.searches.iter().find(|s| s.name == *only).expect("candidate comes from the list")),
+            [] => bail!("No saved search named \"{}\". `arrowhead saved list` shows them.", name),
+            several => bail!("\"{}\" matches several saved searches: {}", name, several.join(", ")),
+        }
+    }
+}
+
+/// The cached query embedding, unless it was made by another model
+pub fn cached_vector<'a>(embedding: &'a Option<CachedEmbedding>, model: &str) -> Option<&'a [f32]> {
+    embedding.as_ref().filter(|cached| cached.model == model).map(|cached| cached.vector.as_slice())
+}
+
+/// Notes nearest the query's words by embedding that pass the filters, most similar first.
+/// Uses and refreshes `embedding`, the cached query embedding. Note contents come from
+/// `loaded` when it has them.
+async fn semantic_matches(
+    config: &Config,
+    adapter: &ObsidianAdapter,
+    query: &SearchQuery,
+    limit: usize,
+    embedding: &mut Option<CachedEmbedding>,
+    loaded: &[(String, String)],
+) -> Result<Vec<SearchHit>> {
+    let words = query.terms.join(" ");
+    let mut indexer = embedding_indexer(config)?;
+    indexer.set_search_config(SemanticSearchConfig {
+        max_results: limit * SEMANTIC_OVERFETCH,
+        include_snippets: false,
+        ..Default::default()
+    });
+    indexer.load_vector_database()?;
+    let model = embedding_model_id(config);
+    // Only the primary model's query embedding is cached; an index that is partly on the
+    // fallback model embeds the query with that one on every run
+    let vectors = indexer.query_embeddings(&words, config.embeddings.max_query_models, cached_vector(embedding, &model)).await?;
+    if let Some((_, vector)) = vectors.iter().find(|(m, _)| *m == model) {
+        *embedding = Some(CachedEmbedding { model, vector: vector.clone() });
+    }
+    let results = indexer.semantic_search_with_embeddings(&words, &vectors);
+
+    let mut contents: HashMap<String, String> = loaded.iter().cloned().collect();
+    let missing: Vec<String> = results.iter().map(|r| r.path.clone()).filter(|path| !contents.contains_key(path)).collect();
+    for (path, content) in adapter.fetch_many(&missing, DEFAULT_READ_CONCURRENCY).await {
+        if let Ok(content) = content {
+            contents.insert(path, content);
+        }
+    }
+    Ok(results.iter()
+        .filter_map(|result| {
+            let content = contents.get(&result.path)?;
+            let due = passes_filters(&result.path, content, query)?;
+            let (snippet, highlights) = snippet(content, &query.terms);
+            Some(SearchHit {
+                path: result.path.clone(),
+                score: result.similarity,
+                keyword_score: None,
+                semantic_score: Some(result.similarity),
+                matched: vec![SearchMode::Semantic],
+                due,
+                snippet,
+                highlights,
+            })
+        })
+        .collect())
+}
+
+/// Run a search. Semantic and hybrid searches use and refresh its cached query embedding.
+/// A hybrid search whose semantic half fails, e.g. without an embedding index, still
+/// returns the keyword matches.
+async fn run_search(config: &Config, adapter: &ObsidianAdapter, search: &mut SavedSearch) -> Result<Vec<SearchHit>> {
+    let mut query = parse_query(&search.query)?;
+    if let Some(folder) = &search.folder {
+        query.path = Some(folder.trim_end_matches('/').to_string());
+    }
+    query.tags.extend(search.tags.iter().map(|tag| tag.trim_start_matches('#').to_lowercase()));
+    if search.mode == SearchMode::Semantic && query.terms.is_empty() {
+        bail!("A semantic search needs words to compare notes with, not only filters");
+    }
+
+    let notes = if search.mode == SearchMode::Semantic {
+        Vec::new()
+    } else {
+        let roots = search.folder.as_ref().map_or_else(|| config.search.folders.clone(), |folder| vec![folder.clone()]);
+        load_notes_in(adapter, &roots).await
+    };
+    let limit = search.limit;
+    let mut hits = match search.mode {
+        SearchMode::Keyword => return Ok(keyword_search(&notes, &query, limit)),
+        SearchMode::Semantic => semantic_matches(config, adapter, &query, limit, &mut search.embedding, &notes).await?,
+        SearchMode::Hybrid if query.terms.is_empty() => keyword_matches(&notes, &query),
+        SearchMode::Hybrid => match semantic_matches(config, adapter, &query, limit, &mut search.embedding, &notes).await {
+            Ok(semantic) => fuse(keyword_matches(&notes, &query), semantic),
+            Err(e) => {
+                eprintln!("⚠️  Semantic search unavailable, showing keyword matches only: {}", e);
+                keyword_matches(&notes, &query)
+            }
+        },
+    };
+    sort_hits(&mut hits, query.sort);
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// The snippet with its highlighted words in bold, where the terminal shows it
+fn highlighted(snippet: &str, highlights: &[Highlight]) -> String {
+    let mut text = String::new();
+    let mut last = 0;
+    for highlight in highlights {
+        text.push_str(&snippet[last..highlight.start]);
+        text.push_str(&console::style(&snippet[highlight.start..highlight.end]).bold().to_string());
+        last = highlight.end;
+    }
+    text.push_str(&snippet[last..]);
+    text
+}
+
+fn print_hits(hits: &[SearchHit], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(hits)?);
+        return Ok(());
+    }
+    if hits.is_empty() {
+        println!("No matching notes.");
+    }
+    for hit in hits {
+        let due = hit.due.as_ref().map(|d| format!(" (due {})", d)).unwrap_or_default();
+        let signals: Vec<&str> = hit.matched.iter().map(|mode| if *mode == SearchMode::Keyword { "keyword" } else { "semantic" }).collect();
+        println!("{}  {:.3} [{}]{}\n    {}", hit.path, hit.score, signals.join("+"), due, highlighted(&hit.snippet, &hit.highlights));
+    }
+    Ok(())
+}
+
+pub async fn handle_search_command(args: SearchArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let mode = if args.keyword_only {
+        SearchMode::Keyword
+    } else if args.semantic_only {
+        SearchMode::Semantic
+    } else {
+        SearchMode::Hybrid
+    };
+    let mut search = SavedSearch {
+        name: args.save.clone().unwrap_or_default(),
+        query: args.query,
+        mode,
+        limit: args.limit,
+        folder: args.folder,
+        tags: args.tags,
+        embedding: None,
+        created_at: Utc::now(),
+    };
+    let hits = run_search(&config, adapter, &mut search).await?;
+    print_hits(&hits, args.json)?;
+    crate::script::publish_output(serde_json::json!({ "query": search.query, "results": hits }));
+
+    if let Some(name) = args.save {
+        let path = default_saved_searches_path();
+        let mut saved = SavedSearches::load(&path)?;
+        saved.upsert(search);
+        saved.save(&path)?;
+        println!("💾 Saved search \"{}\"; run it again with `arrowhead saved run {}`", name, name);
+    }
+    Ok(())
+}
+
+pub async fn handle_saved_command(args: SavedArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let path = default_saved_searches_path();
+    let mut saved = SavedSearches::load(&path)?;
+    match args.action {
+        SavedAction::List => {
+            if saved.searches.is_empty() {
+                println!("No saved searches. Save one with `arrowhead search <query> --save <name>`.");
+            }
+            for search in &saved.searches {
+                let mode = match search.mode {
+                    SearchMode::Keyword => " [keyword]",
+                    SearchMode::Semantic => " [semantic]",
+                    SearchMode::Hybrid => "",
+                };
+                let folder = search.folder.as_ref().map(|folder| format!(" in {}", folder)).unwrap_or_default();
+                let tags: String = search.tags.iter().map(|tag| format!(" #{}", tag.trim_start_matches('#'))).collect();
+                println!("{:<20} {}{}{}{} (top {})", search.name, search.query, folder, tags, mode, search.limit);
+            }
+        }
+        SavedAction::Run { name, json } => {
+            let mut search = saved.resolve(&name)?.clone();
+            let cached_before = search.embedding.clone();
+            let hits = run_search(&config, adapter, &mut search).await?;
+            if !json {
+                println!("🔎 {}: {}", search.name, search.query);
+            }
+            print_hits(&hits, json)?;
+            crate::script::publish_output(serde_json::json!({ "name": search.name, "query": search.query, "results": hits }));
+            if search.embedding != cached_before {
+                saved.upsert(search);
+                saved.save(&path)?;
+            }
+        }
+        SavedAction::Delete { name, yes } => {
+            let name = saved.resolve(&name)?.name.clone();
+            let operation = Operation::new(OperationClass::Delete, format!("Delete saved search \"{}\"", name), 1);
+            if !confirm(&config.confirmations, &operation, yes)? {
+                println!("Nothing deleted.");
+                return Ok(());
+            }
+            saved.searches.retain(|s| s.name != name);
+            saved.save(&path)?;
+            println!("🗑️ Deleted saved search \"{}\"", name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault() -> Vec<(String, String)> {
+        [
+            ("Todos/ship.md", "---\ntags: [waiting]\nstatus: open\ndue_date: 2024-06-10\n---\nWaiting on legal to review the launch post.\n"),
+            ("Todos/vendor.md", "---\ntags: [waiting]\nstatus: open\ndue_date: 2024-06-03\n---\nWaiting on the vendor quote. Ask again Friday.\n"),
+            ("Todos/done.md", "---\ntags: [waiting]\nstatus: done\n---\nWaiting on nothing anymore.\n"),
+            ("Notes/okrs.md", "# Q3 OKRs\nOpen questions about the launch and the vendor. #okr\n"),
+        ]
+        .iter()
+        .map(|(p, c)| (p.to_string(), c.to_string()))
+        .collect()
+    }
+
+    fn saved(names: &[&str]) -> SavedSearches {
+        let mut searches = SavedSearches::default();
+        for name in names {
+            searches.upsert(SavedSearch { name: name.to_string(), query: "q".to_string(), mode: SearchMode::Keyword, limit: 5, folder: None, tags: vec![], embedding: None, created_at: Utc::now() });
+        }
+        searches
+    }
+
+    #[test]
+    fn test_filters_and_due_sort() {
+        let query = parse_query("tag:#waiting status:open sort:due").unwrap();
+        assert_eq!(query.tags, vec!["waiting"]);
+        let hits = keyword_search(&vault(), &query, 10);
+        let paths: Vec<&str> = hits.iter().map(|h| h.path.as_str()).collect();
+        assert_eq!(paths, vec!["Todos/vendor.md", "Todos/ship.md"]);
+        assert_eq!(hits[0].due.as_deref(), Some("2024-06-03"));
+        assert!(parse_query("sort:size").is_err());
+    }
+
+    #[test]
+    fn test_terms_must_all_match_and_rank_by_occurrences() {
+        let hits = keyword_search(&vault(), &parse_query("vendor").unwrap(), 10);
+        let paths: Vec<&str> = hits.iter().map(|h| h.path.as_str()).collect();
+        // The file name counts as a match too
+        assert_eq!(paths, vec!["Todos/vendor.md", "Notes/okrs.md"]);
+        assert_eq!(hits[0].score, 2.0);
+        let hits = keyword_search(&vault(), &parse_query("launch legal path:Todos").unwrap(), 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].snippet, "Waiting on legal to review the launch post.");
+        let words: Vec<&str> = hits[0].highlights.iter().map(|h| &hits[0].snippet[h.start..h.end]).collect();
+        assert_eq!(words, vec!["legal", "launch"]);
+    }
+
+    fn semantic_hit(path: &str, similarity: f32) -> SearchHit {
+        SearchHit {
+            path: path.to_string(),
+            score: similarity,
+            keyword_score: None,
+            semantic_score: Some(similarity),
+            matched: vec![SearchMode::Semantic],
+            due: None,
+            snippet: String::new(),
+            highlights: vec![],
+        }
+    }
+
+    #[test]
+    fn test_fusion_ranks_notes_both_signals_find_first() {
+        let keyword = keyword_search(&vault(), &parse_query("vendor").unwrap(), 10);
+        let semantic = vec![semantic_hit("Notes/okrs.md", 0.91), semantic_hit("Notes/suppliers.md", 0.84)];
+        let fused = fuse(keyword, semantic);
+        let paths: Vec<&str> = fused.iter().map(|h| h.path.as_str()).collect();
+        assert_eq!(paths, vec!["Notes/okrs.md", "Todos/vendor.md", "Notes/suppliers.md"]);
+
+        let both = &fused[0];
+        assert_eq!(both.matched, vec![SearchMode::Keyword, SearchMode::Semantic]);
+        assert_eq!((both.keyword_score, both.semantic_score), (Some(1.0), Some(0.91)));
+        assert!((both.score - (1.0 / 62.0 + 1.0 / 61.0)).abs() < 1e-6);
+        // The keyword pass's snippet is kept
+        assert!(both.snippet.contains("vendor"));
+        // Second in its ranking loses to first in the other, whatever the scales
+        assert!(fused[1].score > fused[2].score);
+    }
+
+    #[test]
+    fn test_names_resolve_by_prefix_and_spelling() {
+        let searches = saved(&["waiting", "okrs", "open-questions"]);
+        assert_eq!(searches.resolve("wait").unwrap().name, "waiting");
+        assert_eq!(searches.resolve("Waiting").unwrap().name, "waiting");
+        assert_eq!(searches.resolve("okr").unwrap().name, "okrs");
+        assert_eq!(searches.resolve("watiing").unwrap().name, "waiting");
+        assert!(searches.resolve("o").unwrap_err().to_string().contains("okrs, open-questions"));
+        assert!(searches.resolve("calendar").is_err());
+    }
+
+    #[test]
+    fn test_cached_embedding_only_for_the_same_model() {
+        let mut config = Config::default();
+        config.llm.provider = "gemini".to_string();
+        let cached = Some(CachedEmbedding { model: embedding_model_id(&config), vector: vec![0.1, 0.2] });
+        assert_eq!(cached_vector(&cached, &embedding_model_id(&config)), Some(&[0.1, 0.2][..]));
+        // The chat model doesn't embed anything
+        config.llm.gemini.model = "gemini-2.0-pro".to_string();
+        assert_eq!(cached_vector(&cached, &embedding_model_id(&config)), Some(&[0.1, 0.2][..]));
+        config.embeddings.model = Some("gemini-embedding-001".to_string());
+        assert_eq!(cached_vector(&cached, &embedding_model_id(&config)), None);
+    }
+}
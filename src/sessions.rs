@@ -0,0 +1,262 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ai_conversation::{Message, MessageRole};
+use crate::cli::{SessionsAction, SessionsArgs};
+use crate::config::{Config, SessionSettings};
+use crate::report::{DetailLevel, Report, ReportView};
+
+/// Characters of the first user message shown by `sessions list`
+const PREVIEW_CHARS: usize = 60;
+
+/// An interactive conversation as saved after its latest turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub model: String,
+    pub messages: Vec<Message>,
+}
+
+/// One line of `sessions list`
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub messages: usize,
+    pub preview: String,
+}
+
+impl SavedSession {
+    /// The start of the first thing the user said
+    pub fn preview(&self) -> String {
+        let first = self.messages.iter()
+            .find(|m| matches!(m.role, MessageRole::User))
+            .map_or("", |m| m.content.trim());
+        let line = first.lines().next().unwrap_or_default();
+        match line.char_indices().nth(PREVIEW_CHARS) {
+            Some((at, _)) => format!("{}…", &line[..at]),
+            None if first.lines().count() > 1 => format!("{}…", line),
+            None => line.to_string(),
+        }
+    }
+
+    fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            id: self.id.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            messages: self.messages.iter().filter(|m| !matches!(m.role, MessageRole::System)).count(),
+            preview: self.preview(),
+        }
+    }
+}
+
+pub fn default_sessions_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".config")
+        .join("arrowhead")
+        .join("sessions")
+}
+
+/// A folder of saved sessions, one `<session-id>.json` file each
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn from_settings(settings: &SessionSettings) -> Self {
+        Self::new(settings.path.as_ref().map(PathBuf::from).unwrap_or_else(default_sessions_dir))
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            bail!("Invalid session id '{}'", id);
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    /// Write the session, replacing its previous save in one step so a crash mid-write
+    /// never leaves a truncated file
+    pub fn save(&self, session: &SavedSession) -> Result<()> {
+        let path = self.path(&session.id)?;
+        fs::create_dir_all(&self.dir).context(format!("Failed to create {}", self.dir.display()))?;
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_string_pretty(session)?).context("Failed to write session")?;
+        fs::rename(&partial, &path).context("Failed to save session")
+    }
+
+    pub fn load(&self, id: &str) -> Result<SavedSession> {
+        let path = self.path(id)?;
+        let content = fs::read_to_string(&path).context(format!("No saved session '{}'", id))?;
+        serde_json::from_str(&content).context(format!("Failed to parse session '{}'", id))
+    }
+
+    /// Every readable session, most recently updated first
+    pub fn sessions(&self) -> Result<Vec<SavedSession>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut sessions: Vec<SavedSession> = fs::read_dir(&self.dir)
+            .context(format!("Failed to read {}", self.dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| match read_session(&path) {
+                Ok(session) => Some(session),
+                Err(e) => {
+                    log::warn!("skipping {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        Ok(sessions)
+    }
+
+    pub fn list(&self) -> Result<Vec<SessionSummary>> {
+        Ok(self.sessions()?.iter().map(SavedSession::summary).collect())
+    }
+
+    /// The most recently updated session
+    pub fn latest(&self) -> Result<Option<SavedSession>> {
+        Ok(self.sessions()?.into_iter().next())
+    }
+
+    /// The session with this id, or the only one whose id starts with it
+    pub fn find(&self, id: &str) -> Result<SavedSession> {
+        if let Ok(session) = self.load(id) {
+            return Ok(session);
+        }
+        let mut matches: Vec<SavedSession> = self.sessions()?.into_iter().filter(|s| s.id.starts_with(id)).collect();
+        match matches.len() {
+            0 => bail!("No saved session '{}'; `arrowhead sessions list` shows them", id),
+            1 => Ok(matches.remove(0)),
+            n => bail!("'{}' matches {} sessions; use more of the id", id, n),
+        }
+    }
+}
+
+fn read_session(path: &Path) -> Result<SavedSession> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub async fn handle_sessions_command(args: SessionsArgs, view: ReportView) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let store = SessionStore::from_settings(&config.sessions);
+    match args.action {
+        SessionsAction::List { limit } => {
+            let sessions = store.list()?;
+            let shown = &sessions[..sessions.len().min(limit)];
+            let mut listing = String::new();
+            for session in shown {
+                listing.push_str(&format!(
+                    "[{}] {} → {}, {} message(s): {}\n",
+                    session.id,
+                    session.created_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                    session.updated_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                    session.messages,
+                    if session.preview.is_empty() { "(no messages yet)" } else { &session.preview },
+                ));
+            }
+            if sessions.is_empty() {
+                listing.push_str("No saved sessions yet. Conversations in interactive mode are saved after every turn.\n");
+            } else if sessions.len() > shown.len() {
+                listing.push_str(&format!("… and {} older session(s)\n", sessions.len() - shown.len()));
+            }
+            let report = Report::new()
+                .text(DetailLevel::Normal, listing)
+                .text_only(DetailLevel::Summary, format!("{} saved session(s)\n", sessions.len()))
+                .field(DetailLevel::Summary, "total", sessions.len())
+                .field(DetailLevel::Normal, "sessions", shown);
+            crate::script::publish_output(report.to_json(view.level));
+            report.print(view)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    fn message(role: MessageRole, content: &str) -> Message {
+        Message { id: Uuid::new_v4().to_string(), role, content: content.to_string(), timestamp: Utc::now(), function_call: None }
+    }
+
+    fn session(id: &str, minutes_ago: i64, first: &str) -> SavedSession {
+        let at = Utc::now() - Duration::minutes(minutes_ago);
+        SavedSession {
+            id: id.to_string(),
+            created_at: at,
+            updated_at: at,
+            model: "gemini-1.5-flash".to_string(),
+            messages: vec![message(MessageRole::System, "You are Arrowhead."), message(MessageRole::User, first), message(MessageRole::Assistant, "Sure.")],
+        }
+    }
+
+    fn temp_store() -> SessionStore {
+        SessionStore::new(std::env::temp_dir().join(format!("arrowhead-sessions-{}", Uuid::new_v4())))
+    }
+
+    #[test]
+    fn test_sessions_round_trip_and_list_newest_first() {
+        let store = temp_store();
+        assert!(store.list().unwrap().is_empty());
+        store.save(&session("a1b2", 30, "Plan my week around the launch")).unwrap();
+        store.save(&session("c3d4", 5, "What did we decide about Redis?")).unwrap();
+
+        let listed = store.list().unwrap();
+        assert_eq!(listed.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(), vec!["c3d4", "a1b2"]);
+        assert_eq!(listed[0].messages, 2);
+        assert_eq!(listed[0].preview, "What did we decide about Redis?");
+        assert_eq!(store.latest().unwrap().unwrap().id, "c3d4");
+        assert_eq!(store.load("a1b2").unwrap().messages.len(), 3);
+
+        // A later save replaces the file
+        let mut updated = store.load("a1b2").unwrap();
+        updated.messages.push(message(MessageRole::User, "And the retro?"));
+        updated.updated_at = Utc::now();
+        store.save(&updated).unwrap();
+        assert_eq!(store.latest().unwrap().unwrap().messages.len(), 4);
+        fs::remove_dir_all(&store.dir).ok();
+    }
+
+    #[test]
+    fn test_find_by_prefix_and_reject_bad_ids() {
+        let store = temp_store();
+        store.save(&session("7f3e-one", 10, "x")).unwrap();
+        store.save(&session("7f3e-two", 20, "y")).unwrap();
+        store.save(&session("9a00", 30, "z")).unwrap();
+        assert_eq!(store.find("9a").unwrap().id, "9a00");
+        assert_eq!(store.find("7f3e-two").unwrap().id, "7f3e-two");
+        assert!(store.find("7f3e").unwrap_err().to_string().contains("matches 2 sessions"));
+        assert!(store.find("nope").is_err());
+        assert!(store.load("../config").is_err());
+        // Unreadable files are skipped
+        fs::write(store.dir.join("broken.json"), "{").unwrap();
+        assert_eq!(store.list().unwrap().len(), 3);
+        fs::remove_dir_all(&store.dir).ok();
+    }
+
+    #[test]
+    fn test_preview_is_the_first_user_line() {
+        let long = "a".repeat(80);
+        assert_eq!(session("x", 0, &long).preview(), format!("{}…", "a".repeat(PREVIEW_CHARS)));
+        assert_eq!(session("x", 0, "  Summarize this:\nline two").preview(), "Summarize this:…");
+        let mut empty = session("x", 0, "");
+        empty.messages.retain(|m| matches!(m.role, MessageRole::System));
+        assert_eq!(empty.preview(), "");
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::router::route_command;
+use crate::script::{capture_output, parse_step};
+
+/// Every slash command in interactive mode with its usage, for help and unknown commands
+pub const SLASH_COMMANDS: &[(&str, &str)] = &[
+    ("search", "/search <query>"),
+    ("note", "/note <path>"),
+    ("todo", "/todo add <text> | /todo list"),
+    ("clear", "/clear"),
+    ("model", "/model <name>"),
+    ("save", "/save <name>"),
+    ("llm", "/llm <request>"),
+    ("remember", "/remember <fact>"),
+    ("memories", "/memories"),
+    ("context", "/context"),
+    ("expand", "/expand [id]"),
+];
+
+/// Slash commands handled elsewhere in the chat loop
+const HANDLED_ELSEWHERE: [&str; 5] = ["llm", "remember", "memories", "context", "expand"];
+
+/// A deterministic action typed in interactive mode, run without asking the LLM
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlashCommand {
+    Search(String),
+    Note(String),
+    TodoAdd(String),
+    TodoList,
+    /// Drop the conversation so far, keeping the system message
+    Clear,
+    /// Switch the current provider's model for the rest of the session
+    Model(String),
+    /// Save the session under this name, to resume with `--session <name>`
+    Save(String),
+    /// A known command with missing or wrong arguments; holds its usage
+    Usage(&'static str),
+    Unknown(String),
+}
+
+impl SlashCommand {
+    /// Parse `input` if it's a slash command this module runs; `None` for ordinary
+    /// messages and for the slash commands handled elsewhere
+    pub fn parse(input: &str) -> Option<Self> {
+        let rest = input.trim().strip_prefix('/')?;
+        let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let args = args.trim();
+        if HANDLED_ELSEWHERE.contains(&name) {
+            return None;
+        }
+        let command = match (name, args) {
+            ("clear", "") => SlashCommand::Clear,
+            ("search", query) if !query.is_empty() => SlashCommand::Search(query.to_string()),
+            ("note", path) if !path.is_empty() => SlashCommand::Note(path.to_string()),
+            ("model", model) if !model.is_empty() => SlashCommand::Model(model.to_string()),
+            ("save", name) if !name.is_empty() => SlashCommand::Save(name.to_string()),
+            ("todo", args) => match args.split_once(char::is_whitespace).unwrap_or((args, "")) {
+                ("list", "") => SlashCommand::TodoList,
+                ("add", text) if !text.trim().is_empty() => SlashCommand::TodoAdd(text.trim().to_string()),
+                _ => SlashCommand::Usage(usage("todo")),
+            },
+            (name, _) => match SLASH_COMMANDS.iter().find(|(known, _)| *known == name) {
+                Some((_, usage)) => SlashCommand::Usage(usage),
+                None => SlashCommand::Unknown(name.to_string()),
+            },
+        };
+        Some(command)
+    }
+}
+
+fn usage(name: &str) -> &'static str {
+    SLASH_COMMANDS.iter().find(|(known, _)| *known == name).map_or("", |(_, usage)| usage)
+}
+
+/// The list printed for an unknown slash command
+pub fn available_commands() -> String {
+    SLASH_COMMANDS.iter().map(|(_, usage)| *usage).collect::<Vec<_>>().join(", ")
+}
+
+/// Run a slash command that reads or changes the vault, printing its output. Returns the
+/// tool name and result to add to the conversation, or `None` for commands that act on the
+/// conversation itself.
+pub async fn run_vault_command(command: &SlashCommand, adapter: &ObsidianAdapter) -> Result<Option<(&'static str, Value)>> {
+    let (tool, argv) = match command {
+        SlashCommand::Search(query) => ("search", vec!["search".to_string(), query.clone()]),
+        SlashCommand::TodoAdd(text) => ("todo_add", vec!["todo".to_string(), "add".to_string(), text.clone()]),
+        SlashCommand::TodoList => ("todo_list", vec!["todo".to_string(), "list".to_string()]),
+        SlashCommand::Note(path) => {
+            let path = if path.ends_with(".md") { path.clone() } else { format!("{}.md", path) };
+            let content = adapter.get_file(&path).await.with_context(|| format!("Failed to read note '{}'", path))?;
+            println!("--- {} ---\n{}\n--- End of {} ---", path, content.trim_end(), path);
+            return Ok(Some(("note", serde_json::json!({ "path": path, "content": content }))));
+        }
+        _ => return Ok(None),
+    };
+    let output = capture_output(route_command(parse_step(&argv)?, adapter)).await?;
+    Ok(Some((tool, output.unwrap_or(Value::Null))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_slash_commands() {
+        assert_eq!(SlashCommand::parse("/search  project plan "), Some(SlashCommand::Search("project plan".to_string())));
+        assert_eq!(SlashCommand::parse("/note Projects/Roadmap.md"), Some(SlashCommand::Note("Projects/Roadmap.md".to_string())));
+        assert_eq!(SlashCommand::parse("/todo add call the dentist"), Some(SlashCommand::TodoAdd("call the dentist".to_string())));
+        assert_eq!(SlashCommand::parse("/todo list"), Some(SlashCommand::TodoList));
+        assert_eq!(SlashCommand::parse("/clear"), Some(SlashCommand::Clear));
+        assert_eq!(SlashCommand::parse("/model gpt-4o-mini"), Some(SlashCommand::Model("gpt-4o-mini".to_string())));
+        assert_eq!(SlashCommand::parse("/save planning"), Some(SlashCommand::Save("planning".to_string())));
+
+        // Missing arguments show the usage; unknown names are reported
+        assert_eq!(SlashCommand::parse("/search"), Some(SlashCommand::Usage("/search <query>")));
+        assert_eq!(SlashCommand::parse("/todo remove 3"), Some(SlashCommand::Usage("/todo add <text> | /todo list")));
+        assert_eq!(SlashCommand::parse("/frobnicate"), Some(SlashCommand::Unknown("frobnicate".to_string())));
+
+        // Ordinary messages and commands the chat loop handles itself pass through
+        assert_eq!(SlashCommand::parse("what's due today?"), None);
+        assert_eq!(SlashCommand::parse("/llm add a todo"), None);
+        assert_eq!(SlashCommand::parse("/memories"), None);
+    }
+}
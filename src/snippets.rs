@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+
+/// Words too common to be worth highlighting
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "do", "for", "from", "how", "i", "in", "is", "it",
+    "me", "my", "of", "on", "or", "so", "that", "the", "this", "to", "was", "what", "when", "where",
+    "which", "who", "why", "with",
+];
+
+/// A query term found in a snippet. Offsets are byte offsets into the snippet, so
+/// `&snippet[start..end]` is the matched word.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+    /// The query term it matched, lowercased
+    pub term: String,
+}
+
+/// The distinct words of a query worth matching, lowercased, in query order
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for (start, end) in words(query) {
+        let word = query[start..end].to_lowercase();
+        if STOPWORDS.contains(&word.as_str()) || terms.iter().any(|t| stem(t) == stem(&word)) {
+            continue;
+        }
+        terms.push(word);
+    }
+    terms
+}
+
+/// Strip common English suffixes so "planning", "planned" and "plans" all match "plan"
+pub fn stem(word: &str) -> String {
+    let word = word.to_lowercase();
+    let chars = |s: &str| s.chars().count();
+    if let Some(base) = word.strip_suffix("ies").filter(|b| chars(b) >= 2) {
+        return format!("{}y", base);
+    }
+    for suffix in ["ing", "ed"] {
+        if let Some(base) = word.strip_suffix(suffix).filter(|b| chars(b) >= 3) {
+            return undouble(base);
+        }
+    }
+    if let Some(base) = word.strip_suffix("es").filter(|b| ["s", "x", "z", "ch", "sh"].iter().any(|e| b.ends_with(e))) {
+        return base.to_string();
+    }
+    if let Some(base) = word.strip_suffix('s').filter(|b| chars(b) >= 3 && !b.ends_with('s')) {
+        return base.to_string();
+    }
+    if let Some(base) = word.strip_suffix("ly").filter(|b| chars(b) >= 3) {
+        return base.to_string();
+    }
+    word
+}
+
+/// "plann" → "plan", but "call" and "miss" keep their double letters
+fn undouble(base: &str) -> String {
+    let mut chars = base.chars().rev();
+    match (chars.next(), chars.next()) {
+        (Some(a), Some(b)) if a == b && !matches!(a, 'l' | 's' | 'z' | 'e' | 'o') && a.is_alphabetic() => base[..base.len() - a.len_utf8()].to_string(),
+        _ => base.to_string(),
+    }
+}
+
+/// Byte ranges of the words (runs of letters and digits) in `text`
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// The words of `text` matching `terms`, with the term each one matched
+fn matches(text: &str, terms: &[String]) -> Vec<(usize, usize, String)> {
+    let stems: Vec<String> = terms.iter().map(|t| stem(t)).collect();
+    words(text)
+        .into_iter()
+        .filter_map(|(start, end)| {
+            let word = stem(&text[start..end]);
+            let index = stems.iter().position(|s| *s == word)?;
+            Some((start, end, terms[index].clone()))
+        })
+        .collect()
+}
+
+/// Every occurrence of `terms` in `snippet`, in order
+pub fn highlights(snippet: &str, terms: &[String]) -> Vec<Highlight> {
+    matches(snippet, terms)
+        .into_iter()
+        .map(|(start, end, term)| Highlight { start, end, term })
+        .collect()
+}
+
+/// About `max_len` bytes of `content` around the densest cluster of query terms, cut on
+/// word boundaries, with whitespace collapsed and "…" where text was left out. Without
+/// any matches it's the start of the note. Frontmatter is skipped.
+pub fn snippet(content: &str, terms: &[String], max_len: usize) -> String {
+    let text = body(content).split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.len() <= max_len {
+        return text;
+    }
+
+    let found = matches(&text, terms);
+    // The window starting at a match that covers the most matches
+    let mut best = (0, 0);
+    let mut last = 0;
+    for first in 0..found.len() {
+        last = last.max(first);
+        while last + 1 < found.len() && found[last + 1].1 - found[first].0 <= max_len {
+            last += 1;
+        }
+        if last + 1 - first > best.1 {
+            best = (first, last + 1 - first);
+        }
+    }
+
+    let mut start = match found.get(best.0) {
+        Some(&(first_start, _, _)) => {
+            // Center the matches in the window
+            let span = found[best.0 + best.1 - 1].1 - first_start;
+            let start = first_start.saturating_sub(max_len.saturating_sub(span) / 2);
+            start.min(text.len().saturating_sub(max_len))
+        }
+        None => 0,
+    };
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    start = text[..start].rfind(' ').map_or(0, |space| space + 1);
+
+    let mut end = (start + max_len).min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    if end < text.len() {
+        if let Some(space) = text[start..end].rfind(' ') {
+            end = start + space;
+        }
+    }
+
+    format!(
+        "{}{}{}",
+        if start > 0 { "…" } else { "" },
+        &text[start..end],
+        if end < text.len() { "…" } else { "" }
+    )
+}
+
+fn body(content: &str) -> &str {
+    let Some(rest) = content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")) else {
+        return content;
+    };
+    match rest.find("\n---") {
+        Some(end) => rest[end + 4..].trim_start_matches(['-', '\r', '\n']),
+        None => content,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTE: &str = "---\ntags: [planning]\n---\n# Q3 Planning\n\nWe met on Monday to go over the roadmap. Lunch was good.\n\n\
+        Most of the hour went to hiring: two backend roles and a designer. The budget review is next week.\n\n\
+        Launch planning: the launch date moves to September because the launch checklist isn't done and \
+        Planned launches need legal sign-off. Marketing wants a teaser post before the launch.\n\n\
+        Action items are in the tracker.";
+
+    fn spans(snippet: &str, highlights: &[Highlight]) -> Vec<String> {
+        highlights.iter().map(|h| snippet[h.start..h.end].to_string()).collect()
+    }
+
+    #[test]
+    fn test_query_terms_and_stems() {
+        assert_eq!(query_terms("What are the Launch plans for launching?"), vec!["launch", "plans"]);
+        for (word, expected) in [("planning", "plan"), ("planned", "plan"), ("plans", "plan"), ("stories", "story"),
+            ("boxes", "box"), ("notes", "note"), ("calling", "call"), ("class", "class"), ("quickly", "quick"), ("is", "is")] {
+            assert_eq!(stem(word), expected, "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_snippet_is_the_densest_window() {
+        let terms = query_terms("launch planning");
+        let snippet = snippet(NOTE, &terms, 120);
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'), "{}", snippet);
+        assert!(snippet.len() <= 120 + 2 * '…'.len_utf8());
+        assert!(snippet.contains("the launch date moves"), "{}", snippet);
+        // Cut on word boundaries
+        let inner = snippet.trim_matches('…');
+        assert!(NOTE.split_whitespace().collect::<Vec<_>>().join(" ").contains(inner));
+        assert!(!inner.starts_with(' ') && !inner.ends_with(' '));
+
+        // No matches: the start of the body, without frontmatter
+        let snippet = super::snippet(NOTE, &query_terms("kubernetes"), 40);
+        assert_eq!(snippet, "# Q3 Planning We met on Monday to go…");
+        // Short notes are returned whole
+        assert_eq!(super::snippet("Just  a\nline", &[], 200), "Just a line");
+    }
+
+    #[test]
+    fn test_highlight_offsets_line_up_with_the_snippet() {
+        let terms = query_terms("launch planning");
+        let snippet = snippet(NOTE, &terms, 160);
+        let found = highlights(&snippet, &terms);
+        assert!(!found.is_empty());
+        for highlight in &found {
+            assert_eq!(stem(&snippet[highlight.start..highlight.end]), stem(&highlight.term));
+        }
+        let matched = spans(&snippet, &found);
+        assert!(matched.contains(&"Planned".to_string()) && matched.contains(&"launches".to_string()), "{:?}", matched);
+
+        let text = "Ünïcode café: Launching the café launch.";
+        let found = highlights(text, &query_terms("launch café"));
+        assert_eq!(spans(text, &found), vec!["café", "Launching", "café", "launch"]);
+        assert_eq!(found[1], Highlight { start: 17, end: 26, term: "launch".to_string() });
+    }
+}
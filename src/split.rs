@@ -0,0 +1,595 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+
+use crate::cli::SplitArgs;
+use crate::config::{Config, SplitSettings};
+use crate::confirm::{confirm, Operation, OperationClass, PromptReader, StdinPrompt};
+use crate::conflicts::split_frontmatter;
+use crate::health::load_notes_in;
+use crate::managed_sections::{upsert, SectionWrite};
+use crate::memory::cosine_similarity;
+use crate::notes::link_key;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::reindex::embedding_indexer;
+use crate::utils::slugify;
+use crate::vault_transaction::{VaultStore, VaultTransaction};
+
+/// The index note lists the parts in a managed section
+const SECTION_OWNER: &str = "split";
+const PARTS_SECTION: &str = "parts";
+const PARTS_HEADING: &str = "## Parts";
+
+/// Frontmatter that describes the original note itself rather than its topic
+const NOT_CARRIED: [&str; 4] = ["aliases", "ai_analysis", "ai_analysis_version", "ai_analysis_timestamp"];
+
+/// A block of the note body starting at a heading of the split level
+#[derive(Debug, Clone, Serialize)]
+pub struct Section {
+    pub heading: String,
+    #[serde(skip)]
+    pub text: String,
+    pub words: usize,
+    /// Link targets inside the section: its headings and `^block` ids
+    #[serde(skip)]
+    pub anchors: Vec<String>,
+}
+
+/// A proposed new note: the sections it takes, by index, in note order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Part {
+    pub title: String,
+    pub sections: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SplitPlan {
+    pub path: String,
+    #[serde(skip)]
+    pub frontmatter: Option<String>,
+    /// Text before the first section; it stays in the index note
+    #[serde(skip)]
+    pub preamble: String,
+    pub sections: Vec<Section>,
+    pub parts: Vec<Part>,
+    /// Sections left in the index note
+    pub excluded: Vec<usize>,
+}
+
+fn heading_level(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim_start();
+    let text = trimmed.trim_start_matches('#');
+    let level = trimmed.len() - text.len();
+    (level > 0 && level <= 6 && text.starts_with(' ')).then(|| (level, text.trim()))
+}
+
+/// Headings outside code fences, with the byte offset of their line
+fn headings(body: &str) -> Vec<(usize, usize, String)> {
+    let mut found = Vec::new();
+    let mut in_fence = false;
+    let mut offset = 0;
+    for line in body.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if let Some((level, text)) = heading_level(line).filter(|_| !in_fence) {
+            found.push((offset, level, text.to_string()));
+        }
+        offset += line.len();
+    }
+    found
+}
+
+fn block_ids(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.lines().filter_map(|line| {
+        let id = line.trim_end().rsplit(' ').next()?.strip_prefix('^')?;
+        (!id.is_empty() && id.chars().all(|c| c.is_alphanumeric() || c == '-')).then(|| format!("^{}", id))
+    })
+}
+
+/// Split the body at the shallowest heading level used at least twice, so a single H1
+/// title stays in the preamble. Returns the preamble and the sections.
+pub fn split_sections(body: &str) -> Result<(String, Vec<Section>)> {
+    let found = headings(body);
+    let Some(level) = (1..=6).find(|level| found.iter().filter(|(_, l, _)| l == level).count() >= 2) else {
+        bail!("The note needs at least two headings of the same level to split at");
+    };
+    let starts: Vec<usize> = found.iter().filter(|(_, l, _)| *l == level).map(|(offset, _, _)| *offset).collect();
+    let sections = starts.iter().enumerate().map(|(i, start)| {
+        let end = starts.get(i + 1).copied().unwrap_or(body.len());
+        let text = &body[*start..end];
+        let mut anchors: Vec<String> = found.iter()
+            .filter(|(offset, _, _)| offset >= start && *offset < end)
+            .map(|(_, _, heading)| heading.clone())
+            .collect();
+        anchors.extend(block_ids(text));
+        Section {
+            heading: anchors[0].clone(),
+            text: text.to_string(),
+            words: text.split_whitespace().count(),
+            anchors,
+        }
+    }).collect();
+    Ok((body[..starts[0]].to_string(), sections))
+}
+
+/// Start a new part wherever the similarity of adjacent sections falls below the threshold,
+/// or at every section without similarities; then fold parts under `min_part_words` into a
+/// neighbour
+pub fn propose_parts(sections: &[Section], similarities: Option<&[f32]>, settings: &SplitSettings) -> Vec<Part> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for i in 0..sections.len() {
+        let same_topic = i > 0 && similarities.is_some_and(|s| s.get(i - 1).is_some_and(|sim| *sim >= settings.similarity_threshold));
+        match groups.last_mut() {
+            Some(group) if same_topic => group.push(i),
+            _ => groups.push(vec![i]),
+        }
+    }
+    let words = |group: &[usize]| group.iter().map(|i| sections[*i].words).sum::<usize>();
+    let mut merged: Vec<Vec<usize>> = Vec::new();
+    for group in groups {
+        match merged.last_mut() {
+            Some(last) if words(&group) < settings.min_part_words || words(last) < settings.min_part_words => last.extend(group),
+            _ => merged.push(group),
+        }
+    }
+    merged.into_iter()
+        .map(|sections_in| Part { title: sections[sections_in[0]].heading.clone(), sections: sections_in })
+        .collect()
+}
+
+impl SplitPlan {
+    fn part_of(&self, section: usize) -> Option<usize> {
+        self.parts.iter().position(|part| part.sections.contains(&section))
+    }
+
+    /// Move every section of part `from` into part `into` (0-based)
+    pub fn merge(&mut self, into: usize, from: usize) -> Result<()> {
+        if into == from || into >= self.parts.len() || from >= self.parts.len() {
+            bail!("Pick two different parts between 1 and {}", self.parts.len());
+        }
+        let moved = self.parts[from].sections.clone();
+        self.parts[into].sections.extend(moved);
+        self.parts[into].sections.sort_unstable();
+        self.parts.remove(from);
+        self.parts.sort_by_key(|part| part.sections[0]);
+        Ok(())
+    }
+
+    pub fn rename(&mut self, part: usize, title: &str) -> Result<()> {
+        let Some(part) = self.parts.get_mut(part) else { bail!("There is no part {}", part + 1) };
+        if slugify(title).is_empty() {
+            bail!("A part title needs letters or digits");
+        }
+        part.title = title.trim().to_string();
+        Ok(())
+    }
+
+    /// Keep a section in the index note instead of moving it
+    pub fn exclude(&mut self, section: usize) -> Result<()> {
+        let Some(part) = self.part_of(section) else { bail!("Section {} is not in any part", section + 1) };
+        self.parts[part].sections.retain(|s| *s != section);
+        if self.parts[part].sections.is_empty() {
+            self.parts.remove(part);
+        }
+        self.excluded.push(section);
+        self.excluded.sort_unstable();
+        Ok(())
+    }
+
+    /// Move an excluded section back, into the part holding the section before it
+    pub fn include(&mut self, section: usize) -> Result<()> {
+        if !self.excluded.contains(&section) {
+            bail!("Section {} is not excluded", section + 1);
+        }
+        self.excluded.retain(|s| *s != section);
+        match (0..section).rev().find_map(|s| self.part_of(s)) {
+            Some(part) => {
+                self.parts[part].sections.push(section);
+                self.parts[part].sections.sort_unstable();
+            }
+            None => {
+                self.parts.push(Part { title: self.sections[section].heading.clone(), sections: vec![section] });
+                self.parts.sort_by_key(|part| part.sections[0]);
+            }
+        }
+        Ok(())
+    }
+
+    fn folder(&self) -> &str {
+        self.path.rsplit_once('/').map_or("", |(folder, _)| folder)
+    }
+
+    fn note_name(&self) -> &str {
+        let file = self.path.rsplit('/').next().unwrap_or(&self.path);
+        file.strip_suffix(".md").unwrap_or(file)
+    }
+
+    /// A vault path per part: the slugified title next to the original, numbered when taken
+    pub fn part_paths(&self, taken: &HashSet<String>) -> Vec<String> {
+        let mut used = taken.clone();
+        used.insert(self.path.clone());
+        self.parts.iter().enumerate().map(|(i, part)| {
+            let slug = Some(slugify(&part.title)).filter(|s| !s.is_empty()).unwrap_or_else(|| format!("part-{}", i + 1));
+            let prefix = if self.folder().is_empty() { String::new() } else { format!("{}/", self.folder()) };
+            let path = (1..).map(|n| match n {
+                1 => format!("{}{}.md", prefix, slug),
+                n => format!("{}{}-{}.md", prefix, slug, n),
+            }).find(|path| !used.contains(path)).expect("unbounded");
+            used.insert(path.clone());
+            path
+        }).collect()
+    }
+
+    /// Link anchor (as `link_key`) → name of the note it moves to
+    fn moved_anchors(&self, names: &[String]) -> HashMap<String, String> {
+        let mut moved = HashMap::new();
+        for (part, name) in self.parts.iter().zip(names) {
+            for section in &part.sections {
+                for anchor in &self.sections[*section].anchors {
+                    moved.insert(link_key(anchor), name.clone());
+                }
+            }
+        }
+        moved
+    }
+
+    fn carried_frontmatter(&self) -> Mapping {
+        let mut fields: Mapping = self.frontmatter.as_deref()
+            .and_then(|yaml| serde_yaml::from_str(yaml).ok())
+            .unwrap_or_default();
+        for key in NOT_CARRIED {
+            fields.remove(key);
+        }
+        fields.insert(Value::from("split_from"), Value::from(format!("[[{}]]", self.note_name())));
+        fields
+    }
+
+    /// The notes the plan writes: every part, then the original as an index of the parts
+    pub fn render(&self, paths: &[String]) -> Result<Vec<(String, String)>> {
+        let names: Vec<String> = paths.iter().map(|p| note_name_of(p).to_string()).collect();
+        let moved = self.moved_anchors(&names);
+        let original = self.note_name();
+        let frontmatter = serde_yaml::to_string(&self.carried_frontmatter()).unwrap_or_default();
+
+        let mut notes = Vec::new();
+        for ((part, path), name) in self.parts.iter().zip(paths).zip(&names) {
+            let body: String = part.sections.iter().map(|s| self.sections[*s].text.as_str()).collect();
+            let body = rewrite_section_links(&body, original, &moved, Some(name)).unwrap_or(body);
+            notes.push((path.clone(), format!("---\n{}---\n\n{}", frontmatter, body.trim_start())));
+        }
+
+        let mut index = String::new();
+        if let Some(yaml) = &self.frontmatter {
+            index.push_str(&format!("---\n{}\n---\n", yaml));
+        }
+        index.push_str(&self.preamble);
+        for section in &self.excluded {
+            index.push_str(&self.sections[*section].text);
+        }
+        let listing: String = self.parts.iter().zip(&names)
+            .map(|(part, name)| format!("- [[{}|{}]]\n", name, part.title))
+            .collect();
+        let index = rewrite_section_links(&index, original, &moved, Some(original)).unwrap_or(index);
+        let index = upsert(&index, &SectionWrite::new(SECTION_OWNER, PARTS_SECTION, &listing).heading(PARTS_HEADING))?;
+        notes.push((self.path.clone(), index));
+        Ok(notes)
+    }
+
+    pub fn describe(&self, paths: &[String]) -> String {
+        let mut out = format!("Split plan for {} ({} sections → {} parts)\n", self.path, self.sections.len(), self.parts.len());
+        for (i, (part, path)) in self.parts.iter().zip(paths).enumerate() {
+            let words: usize = part.sections.iter().map(|s| self.sections[*s].words).sum();
+            out.push_str(&format!("\n  {}. {} → {} ({} words)\n", i + 1, part.title, path, words));
+            for section in &part.sections {
+                out.push_str(&format!("       [{}] {} ({} words)\n", section + 1, self.sections[*section].heading, self.sections[*section].words));
+            }
+        }
+        if !self.excluded.is_empty() {
+            out.push_str("\n  Kept in the index note:\n");
+            for section in &self.excluded {
+                out.push_str(&format!("       [{}] {}\n", section + 1, self.sections[*section].heading));
+            }
+        }
+        out
+    }
+}
+
+fn note_name_of(path: &str) -> &str {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    file.strip_suffix(".md").unwrap_or(file)
+}
+
+/// Point `[[original#anchor]]` links (and, inside a note of the split, `[[#anchor]]` links)
+/// at the note the anchor moved to. Links to the original itself keep reaching the index
+/// note. `current` is the name of the note being rewritten when it is part of the split.
+/// Returns `None` when nothing changed.
+pub fn rewrite_section_links(content: &str, original: &str, moved: &HashMap<String, String>, current: Option<&str>) -> Option<String> {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    let mut changed = false;
+    while let Some(start) = rest.find("[[") {
+        result.push_str(&rest[..start + 2]);
+        let inner_start = &rest[start + 2..];
+        let Some(end) = inner_start.find("]]") else {
+            rest = inner_start;
+            break;
+        };
+        let inner = &inner_start[..end];
+        rest = &inner_start[end + 2..];
+
+        let target_end = inner.find(['|', '#']).unwrap_or(inner.len());
+        let target = inner[..target_end].trim();
+        let anchor = inner[target_end..].strip_prefix('#').map(|a| a.split('|').next().unwrap_or(a));
+        let links_original = if target.is_empty() {
+            current.is_some()
+        } else {
+            link_key(note_name_of(target)) == link_key(original)
+        };
+        let destination = anchor.filter(|_| links_original).and_then(|anchor| moved.get(&link_key(anchor)));
+        match destination {
+            Some(name) if Some(name.as_str()) != current || !target.is_empty() => {
+                let prefix = target.rfind('/').map_or("", |slash| &target[..=slash]);
+                result.push_str(prefix);
+                result.push_str(name);
+                result.push_str(&inner[target_end..]);
+                changed = true;
+            }
+            _ => result.push_str(inner),
+        }
+        result.push_str("]]");
+    }
+    result.push_str(rest);
+    changed.then_some(result)
+}
+
+/// Similarity of each section to the next, from section embeddings
+async fn section_similarities(indexer: &ObsidianAdapter, sections: &[Section]) -> Result<Vec<f32>> {
+    let mut vectors = Vec::new();
+    for section in sections {
+        vectors.push(indexer.generate_embeddings(&section.text).await?);
+    }
+    Ok(vectors.windows(2).map(|pair| cosine_similarity(&pair[0], &pair[1])).collect())
+}
+
+fn number(text: Option<&str>) -> Result<usize> {
+    match text.and_then(|t| t.trim_matches(['[', ']']).parse::<usize>().ok()) {
+        Some(n) if n > 0 => Ok(n - 1),
+        _ => bail!("Expected a number"),
+    }
+}
+
+/// Edit the plan until it is applied (true) or abandoned (false)
+fn edit_plan(prompt: &mut dyn PromptReader, plan: &mut SplitPlan, taken: &HashSet<String>) -> Result<bool> {
+    loop {
+        print!("\n{}", plan.describe(&plan.part_paths(taken)));
+        let answer = prompt.read_answer(
+            "merge <part> <part>, rename <part> <title>, exclude <section>, include <section>, apply, quit",
+        )?;
+        let mut words = answer.split_whitespace();
+        let result = match words.next().unwrap_or_default() {
+            "apply" | "a" | "y" => return Ok(true),
+            "quit" | "q" | "" => return Ok(false),
+            "merge" | "m" => number(words.next()).and_then(|a| Ok((a, number(words.next())?))).and_then(|(a, b)| plan.merge(a.min(b), a.max(b))),
+            "rename" | "r" => number(words.next()).and_then(|n| plan.rename(n, &words.collect::<Vec<_>>().join(" "))),
+            "exclude" | "x" => number(words.next()).and_then(|n| plan.exclude(n)),
+            "include" | "i" => number(words.next()).and_then(|n| plan.include(n)),
+            other => Err(anyhow::anyhow!("Unknown command '{}'", other)),
+        };
+        if let Err(e) = result {
+            println!("⚠️  {}", e);
+        }
+    }
+}
+
+pub async fn handle_split_command(args: SplitArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let settings = &config.split;
+    let path = if args.path.ends_with(".md") { args.path.clone() } else { format!("{}.md", args.path) };
+    let Some(content) = adapter.read_file(&path).await? else { bail!("Note '{}' not found", path) };
+    let (frontmatter, body) = split_frontmatter(&content);
+    let (preamble, sections) = split_sections(body)?;
+
+    let mut indexer = None;
+    if !args.headings_only {
+        match embedding_indexer(&config) {
+            Ok(client) => indexer = Some(client),
+            Err(e) => println!("ℹ️  No LLM available ({}); splitting at headings only", e),
+        }
+    }
+    let similarities = match &indexer {
+        Some(indexer) => match section_similarities(indexer, &sections).await {
+            Ok(similarities) => Some(similarities),
+            Err(e) => {
+                println!("ℹ️  Could not embed the sections ({}); splitting at headings only", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let parts = propose_parts(&sections, similarities.as_deref(), settings);
+    let mut plan = SplitPlan { path: path.clone(), frontmatter: frontmatter.map(str::to_string), preamble, sections, parts, excluded: Vec::new() };
+
+    let linking = load_notes_in(adapter, &settings.link_folders).await;
+    let taken: HashSet<String> = linking.iter().map(|(p, _)| p.clone()).collect();
+    if args.json {
+        let paths = plan.part_paths(&taken);
+        let mut json = serde_json::to_value(&plan)?;
+        json["part_paths"] = serde_json::json!(paths);
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+    if plan.parts.len() < 2 {
+        print!("{}", plan.describe(&plan.part_paths(&taken)));
+        println!("\nThe note reads as one topic; nothing to split.");
+        return Ok(());
+    }
+
+    let mut prompt = StdinPrompt;
+    if !args.yes {
+        if !prompt.is_interactive() {
+            print!("{}", plan.describe(&plan.part_paths(&taken)));
+            println!("\nRun `arrowhead split {}` in a terminal to edit and apply the plan, or pass --yes.", args.path);
+            return Ok(());
+        }
+        if !edit_plan(&mut prompt, &mut plan, &taken)? {
+            println!("Nothing split.");
+            return Ok(());
+        }
+        if plan.parts.is_empty() {
+            println!("Every section is kept in the original; nothing split.");
+            return Ok(());
+        }
+    }
+
+    let paths = plan.part_paths(&taken);
+    let notes = plan.render(&paths)?;
+    let names: Vec<String> = paths.iter().map(|p| note_name_of(p).to_string()).collect();
+    let moved = plan.moved_anchors(&names);
+    let mut transaction = VaultTransaction::new(&format!("split {} into {} notes", path, paths.len()))
+        .with_context(serde_json::json!({ "split": path, "parts": paths }));
+    for (note_path, note) in &notes {
+        transaction = match note_path == &path {
+            // Other features may have written the note while the plan was being edited
+            true => transaction.write_if_unchanged(note_path, note, &content),
+            false => transaction.write(note_path, note),
+        };
+    }
+    let mut relinked = 0;
+    for (note_path, note) in &linking {
+        if *note_path == path {
+            continue;
+        }
+        if let Some(rewritten) = rewrite_section_links(note, plan.note_name(), &moved, None) {
+            transaction = transaction.write_if_unchanged(note_path, &rewritten, note);
+            relinked += 1;
+        }
+    }
+
+    let operation = Operation::new(
+        OperationClass::BulkWrite,
+        format!("Split {} into {} notes and update {} linking note(s)", path, paths.len(), relinked),
+        notes.len() + relinked,
+    );
+    // `apply` in the editor, like --yes, answers the confirmation where the policy allows it
+    if !confirm(&config.confirmations, &operation, true)? {
+        println!("Nothing split.");
+        return Ok(());
+    }
+    let report = transaction.execute(adapter).await?;
+    if let Err(e) = report.append_to_log(adapter.transaction_log()) {
+        eprintln!("Warning: could not write transaction log: {}", e);
+    }
+    println!("{}", report.summary());
+    if !report.is_committed() {
+        bail!("Split of {} did not complete", path);
+    }
+
+    match indexer.as_mut() {
+        Some(indexer) => {
+            let refreshed = async {
+                indexer.load_vector_database()?;
+                for (note_path, note) in &notes {
+                    indexer.embed_content(note_path, note).await?;
+                }
+                indexer.save_vector_database()
+            };
+            if let Err(e) = refreshed.await {
+                println!("⚠️  Could not refresh embeddings ({}); run `arrowhead index`", e);
+            }
+        }
+        None => println!("Run `arrowhead index` to embed the new notes."),
+    }
+    crate::script::publish_output(serde_json::json!({ "split": path, "parts": paths, "relinked": relinked }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_transaction::testing::FakeVault;
+
+    const NOTE: &str = "---\ntags: [research]\naliases: [Big]\nai_analysis_version: '2'\n---\n# Big Note\n\nIntro text.\n\n## Databases\n\nPostgres notes ^pg\n\n### Indexes\n\nB-trees.\n\n## Caching\n\nRedis.\n\n```\n## not a heading\n```\n\n## Hiring\n\nInterview loop.\n";
+
+    fn plan(similarities: Option<&[f32]>) -> SplitPlan {
+        let (frontmatter, body) = split_frontmatter(NOTE);
+        let (preamble, sections) = split_sections(body).unwrap();
+        let settings = SplitSettings { min_part_words: 0, ..SplitSettings::default() };
+        let parts = propose_parts(&sections, similarities, &settings);
+        SplitPlan { path: "Notes/big-note.md".to_string(), frontmatter: frontmatter.map(str::to_string), preamble, sections, parts, excluded: Vec::new() }
+    }
+
+    #[test]
+    fn test_sections_split_below_the_title() {
+        let plan = plan(None);
+        assert_eq!(plan.preamble, "# Big Note\n\nIntro text.\n\n");
+        let headings: Vec<&str> = plan.sections.iter().map(|s| s.heading.as_str()).collect();
+        assert_eq!(headings, vec!["Databases", "Caching", "Hiring"]);
+        assert_eq!(plan.sections[0].anchors, vec!["Databases", "Indexes", "^pg"]);
+        // The fenced line belongs to "Caching"
+        assert!(plan.sections[1].text.contains("## not a heading"));
+        assert!(split_sections("# Only\n\ntext\n").is_err());
+    }
+
+    #[test]
+    fn test_parts_follow_topic_shifts_and_fold_small_parts() {
+        // Databases and Caching are similar; Hiring is a new topic
+        let plan = plan(Some(&[0.9, 0.2]));
+        assert_eq!(plan.parts, vec![
+            Part { title: "Databases".to_string(), sections: vec![0, 1] },
+            Part { title: "Hiring".to_string(), sections: vec![2] },
+        ]);
+
+        let (_, sections) = split_sections(split_frontmatter(NOTE).1).unwrap();
+        let settings = SplitSettings { min_part_words: 5, ..SplitSettings::default() };
+        // Counting heading markers, "Hiring" has 4 words and joins the part before it
+        let parts = propose_parts(&sections, None, &settings);
+        assert_eq!(parts.iter().map(|p| p.sections.clone()).collect::<Vec<_>>(), vec![vec![0], vec![1, 2]]);
+    }
+
+    #[test]
+    fn test_heading_links_follow_their_section() {
+        let plan = plan(None);
+        let names = vec!["databases".to_string(), "caching".to_string(), "hiring".to_string()];
+        let moved = plan.moved_anchors(&names);
+        let inbound = "See [[Big Note#Indexes|indexes]], [[Notes/big-note#^pg]], [[big-note]] and [[Other#Hiring]].";
+        assert_eq!(
+            rewrite_section_links(inbound, "big-note", &moved, None).unwrap(),
+            "See [[databases#Indexes|indexes]], [[Notes/databases#^pg]], [[big-note]] and [[Other#Hiring]]."
+        );
+        // Inside a part, same-note links stay local and links to other parts gain the note name
+        assert_eq!(
+            rewrite_section_links("[[#Indexes]] and [[#Hiring]]", "big-note", &moved, Some("databases")).unwrap(),
+            "[[#Indexes]] and [[hiring#Hiring]]"
+        );
+        assert!(rewrite_section_links("[[big-note]]", "big-note", &moved, None).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_edited_plan_writes_parts_and_index() {
+        let mut plan = plan(None);
+        plan.exclude(2).unwrap();
+        plan.rename(1, "Redis caching").unwrap();
+        assert!(plan.merge(0, 5).is_err());
+        let taken: HashSet<String> = ["Notes/databases.md".to_string()].into_iter().collect();
+        let paths = plan.part_paths(&taken);
+        assert_eq!(paths, vec!["Notes/databases-2.md", "Notes/redis-caching.md"]);
+
+        let vault = FakeVault::new(&[("Notes/big-note.md", NOTE)]);
+        let mut transaction = VaultTransaction::new("split");
+        for (path, content) in plan.render(&paths).unwrap() {
+            transaction = transaction.write(&path, &content);
+        }
+        assert!(transaction.execute(&vault).await.unwrap().is_committed());
+
+        let part = vault.get("Notes/databases-2.md").unwrap();
+        assert!(part.starts_with("---\ntags:\n- research\nsplit_from: '[[big-note]]'\n---\n\n## Databases\n"));
+        assert!(!part.contains("aliases") && !part.contains("ai_analysis"));
+        let index = vault.get("Notes/big-note.md").unwrap();
+        assert!(index.starts_with("---\ntags: [research]\naliases: [Big]"));
+        assert!(index.contains("Intro text.\n\n## Hiring\n\nInterview loop.\n\n## Parts\n<!-- arrowhead:begin owner=split id=parts hash="));
+        assert!(index.contains(" -->\n- [[databases-2|Databases]]\n- [[redis-caching|Redis caching]]\n<!-- arrowhead:end id=parts -->\n"));
+        assert!(!index.contains("## Caching"));
+    }
+}
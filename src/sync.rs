@@ -0,0 +1,470 @@
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::calendar::create_calendar_adapter;
+use crate::calendar_adapter::CalendarEvent;
+use crate::cli::{SyncAction, SyncArgs};
+use crate::config::{CalendarSettings, Config};
+use crate::note_skeleton::stable_hash;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::timezone::{self, TimeZone};
+use crate::todo_model::{TodoItem, TodoStore};
+
+/// Property tagging a calendar event with the vault path of the todo it was made for
+pub const TODO_PROPERTY: &str = "X-ARROWHEAD-TODO";
+
+const STATE_VERSION: u32 = 1;
+
+/// Length of the event for a todo due at a time of day without an estimate
+const DEFAULT_EVENT_MINUTES: i64 = 30;
+
+pub fn default_todo_sync_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".config")
+        .join("arrowhead")
+        .join("todo-sync.json")
+}
+
+/// When a todo is due: a day, or a time on that day in the calendar's time zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DueAt {
+    pub date: NaiveDate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<NaiveTime>,
+}
+
+impl fmt::Display for DueAt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.time {
+            Some(time) => write!(f, "{} {}", self.date, time.format("%H:%M")),
+            None => write!(f, "{}", self.date),
+        }
+    }
+}
+
+impl DueAt {
+    /// When an open todo is due; None for finished todos and those without a due date
+    pub fn of_todo(todo: &TodoItem) -> Option<Self> {
+        let date = todo.due.filter(|_| todo.status.is_open())?;
+        Some(Self { date, time: todo.due_time() })
+    }
+
+    /// When an event is, read the way `event_for` writes it
+    pub fn of_event(event: &CalendarEvent, zone: &TimeZone) -> Self {
+        if event.all_day {
+            return Self { date: event.start_time.date_naive(), time: None };
+        }
+        let local = zone.to_local(event.start_time);
+        Self { date: local.date(), time: Some(local.time()) }
+    }
+}
+
+/// What the last sync left a todo and its event at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncedTodo {
+    pub event_id: String,
+    pub due: DueAt,
+}
+
+/// The todos and events as the last sync left them, so the next one can tell which side changed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    #[serde(default)]
+    pub version: u32,
+    /// Id of the tasks calendar the events are in
+    #[serde(default)]
+    pub calendar_id: Option<String>,
+    /// Keyed by todo path
+    #[serde(default)]
+    pub todos: BTreeMap<String, SyncedTodo>,
+}
+
+impl SyncState {
+    pub fn path(settings: &CalendarSettings) -> PathBuf {
+        settings.todo_sync_path.as_ref().map(PathBuf::from).unwrap_or_else(default_todo_sync_path)
+    }
+
+    /// The saved state, or an empty one before the first sync
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let state: Self = serde_json::from_str(&data).with_context(|| format!("Failed to parse {}", path.display()))?;
+        if state.version > STATE_VERSION {
+            bail!("{} was written by a newer arrowhead (version {})", path.display(), state.version);
+        }
+        Ok(state)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let state = Self { version: STATE_VERSION, ..self.clone() };
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&state)?)?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Record the outcome of a step that was carried out
+    pub fn apply(&mut self, step: &SyncStep) {
+        match step {
+            SyncStep::CreateEvent { path, event_id, due, .. }
+            | SyncStep::UpdateEvent { path, event_id, due, .. }
+            | SyncStep::UpdateTodo { path, event_id, due }
+            | SyncStep::InSync { path, event_id, due } => {
+                self.todos.insert(path.clone(), SyncedTodo { event_id: event_id.clone(), due: *due });
+            }
+            SyncStep::RemoveEvent { path, .. } | SyncStep::Forget { path } => {
+                self.todos.remove(path);
+            }
+            SyncStep::Conflict { .. } => {}
+        }
+    }
+}
+
+/// One change a sync makes, or reports, for a todo
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum SyncStep {
+    CreateEvent { path: String, event_id: String, title: String, due: DueAt },
+    UpdateEvent { path: String, event_id: String, title: String, due: DueAt },
+    /// The todo was finished, deleted or lost its due date
+    RemoveEvent { path: String, event_id: String, reason: String },
+    /// The event was moved in a calendar app
+    UpdateTodo { path: String, event_id: String, due: DueAt },
+    /// Both sides changed since the last sync; neither is touched
+    Conflict { path: String, todo: Option<DueAt>, event: Option<DueAt>, synced: DueAt },
+    /// Todo and event both gone
+    Forget { path: String },
+    InSync { path: String, event_id: String, due: DueAt },
+}
+
+impl SyncStep {
+    /// One line for the sync summary; None for steps with nothing to say
+    pub fn describe(&self) -> Option<String> {
+        Some(match self {
+            Self::CreateEvent { path, due, .. } => format!("＋ {}: added to the calendar on {}", path, due),
+            Self::UpdateEvent { path, due, .. } => format!("↻ {}: event moved to {}", path, due),
+            Self::RemoveEvent { path, reason, .. } => format!("✗ {}: event removed ({})", path, reason),
+            Self::UpdateTodo { path, due, .. } => format!("↪ {}: due date set to {} from the calendar", path, due),
+            Self::Conflict { path, todo, event, synced } => format!(
+                "⚠️  {}: changed on both sides since the last sync (was {}; todo {}, calendar {}). Change one to match the other and sync again.",
+                path,
+                synced,
+                todo.map_or("finished or undated".to_string(), |due| due.to_string()),
+                event.map_or("deleted".to_string(), |due| due.to_string()),
+            ),
+            Self::Forget { .. } | Self::InSync { .. } => return None,
+        })
+    }
+}
+
+/// The id of the event kept for the todo at `path`, the same on every machine
+pub fn event_id_for(path: &str) -> String {
+    format!("arrowhead-todo-{:016x}", stable_hash(path))
+}
+
+/// The event showing `todo` on the calendar: all day, or starting at its due time and lasting
+/// its estimate
+pub fn event_for(todo: &TodoItem, due: DueAt, calendar_id: &str, zone: &TimeZone) -> CalendarEvent {
+    let (start_time, end_time) = match due.time {
+        Some(time) => {
+            let start = zone.from_local(due.date.and_time(time));
+            (start, start + Duration::minutes(todo.estimate_minutes.map_or(DEFAULT_EVENT_MINUTES, i64::from)))
+        }
+        None => {
+            let start = due.date.and_time(NaiveTime::MIN).and_utc();
+            (start, start + Duration::days(1))
+        }
+    };
+    CalendarEvent {
+        id: String::new(),
+        title: todo.title.clone(),
+        description: Some(format!("Todo: {}", todo.path)),
+        start_time,
+        end_time,
+        location: None,
+        attendees: Vec::new(),
+        all_day: due.time.is_none(),
+        recurring: false,
+        calendar_id: calendar_id.to_string(),
+    }
+}
+
+/// Compare each todo, the event tagged with its path and what the last sync recorded, and say
+/// what to change. A side that changed since the last sync wins; when both did, the pair is
+/// reported as a conflict instead of either being overwritten. Without a record (first sync,
+/// or lost state), the todo wins.
+pub fn plan_sync(todos: &[TodoItem], events: &[(CalendarEvent, String)], state: &SyncState, zone: &TimeZone) -> Vec<SyncStep> {
+    let todos: HashMap<&str, &TodoItem> = todos.iter().map(|todo| (todo.path.as_str(), todo)).collect();
+    let mut by_path: HashMap<&str, &CalendarEvent> = HashMap::new();
+    for (event, path) in events {
+        by_path.entry(path.as_str()).or_insert(event);
+    }
+    let paths: BTreeSet<&str> = todos.iter()
+        .filter(|(_, todo)| DueAt::of_todo(todo).is_some())
+        .map(|(path, _)| *path)
+        .chain(by_path.keys().copied())
+        .chain(state.todos.keys().map(String::as_str))
+        .collect();
+
+    let mut steps = Vec::new();
+    for path in paths {
+        let todo = todos.get(path).copied();
+        let todo_due = todo.and_then(DueAt::of_todo);
+        let event = by_path.get(path).copied();
+        let event_due = event.map(|event| DueAt::of_event(event, zone));
+        let synced = state.todos.get(path);
+        let event_id = event.map(|event| event.id.clone())
+            .or_else(|| synced.map(|s| s.event_id.clone()))
+            .unwrap_or_else(|| event_id_for(path));
+        let path = path.to_string();
+        let reason = || match todo {
+            Some(todo) if !todo.status.is_open() => format!("todo {}", todo.status),
+            Some(_) => "no due date".to_string(),
+            None => "todo deleted".to_string(),
+        };
+        let write_event = |due: DueAt| {
+            let (todo, event) = (todo.expect("todo with a due date"), event);
+            match event {
+                Some(event) if event_due == Some(due) && event.title == todo.title => SyncStep::InSync { path: path.clone(), event_id: event_id.clone(), due },
+                Some(_) => SyncStep::UpdateEvent { path: path.clone(), event_id: event_id.clone(), title: todo.title.clone(), due },
+                None => SyncStep::CreateEvent { path: path.clone(), event_id: event_id.clone(), title: todo.title.clone(), due },
+            }
+        };
+
+        let step = match synced {
+            None => match (todo_due, event) {
+                (Some(due), _) => write_event(due),
+                (None, Some(_)) => SyncStep::RemoveEvent { path: path.clone(), event_id: event_id.clone(), reason: reason() },
+                (None, None) => continue,
+            },
+            Some(synced) => {
+                let todo_changed = todo_due != Some(synced.due);
+                let event_changed = event.is_some() && event_due != Some(synced.due);
+                match (todo_changed, event_changed) {
+                    (true, true) if todo_due.is_some() && todo_due == event_due => write_event(todo_due.unwrap()),
+                    (true, true) => SyncStep::Conflict { path: path.clone(), todo: todo_due, event: event_due, synced: synced.due },
+                    (true, false) => match (todo_due, event) {
+                        (Some(due), _) => write_event(due),
+                        (None, Some(_)) => SyncStep::RemoveEvent { path: path.clone(), event_id: event_id.clone(), reason: reason() },
+                        (None, None) => SyncStep::Forget { path: path.clone() },
+                    },
+                    (false, true) => SyncStep::UpdateTodo { path: path.clone(), event_id: event_id.clone(), due: event_due.expect("event") },
+                    // Unchanged on both sides: recreate an event deleted in the calendar app and
+                    // pick up a retitled todo
+                    (false, false) => write_event(synced.due),
+                }
+            }
+        };
+        steps.push(step);
+    }
+    steps
+}
+
+/// The tasks calendar's id, creating the calendar unless this is a dry run
+async fn tasks_calendar(calendar: &crate::calendar_adapter::CalendarAdapter, name: &str, dry_run: bool) -> Result<Option<String>> {
+    let calendars = calendar.get_calendar_list().await?;
+    if let Some(found) = calendars.iter().find(|c| c.name.eq_ignore_ascii_case(name)) {
+        return Ok(Some(found.id.clone()));
+    }
+    if dry_run {
+        println!("ℹ️  Would create the calendar '{}'", name);
+        return Ok(None);
+    }
+    let created = calendar.create_calendar(name).await?;
+    println!("📅 Created the calendar '{}'", name);
+    Ok(Some(created.id))
+}
+
+pub async fn handle_sync_command(args: SyncArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    match args.action {
+        SyncAction::TodosToCalendar { dry_run } => {
+            let config = Config::load()?;
+            let zone = timezone::resolve(&config.calendar.time_zone)?;
+            let calendar = create_calendar_adapter(&config.calendar, &config.network)?;
+            let state_path = SyncState::path(&config.calendar);
+            let mut state = SyncState::load(&state_path)?;
+
+            let calendar_id = tasks_calendar(&calendar, &config.calendar.tasks_calendar, dry_run).await?;
+            if state.calendar_id.is_some() && state.calendar_id != calendar_id {
+                // A different calendar: what was recorded about the old one's events no longer applies
+                state.todos.clear();
+            }
+            let events: Vec<(CalendarEvent, String)> = match &calendar_id {
+                Some(id) => calendar.list_events_with_property(id, TODO_PROPERTY).await?
+                    .into_iter()
+                    .filter_map(|(event, path)| path.map(|path| (event, path)))
+                    .collect(),
+                None => Vec::new(),
+            };
+            let store = TodoStore::new(adapter);
+            let todos: Vec<TodoItem> = store.list().await?.into_iter().filter(|todo| todo.source.is_none()).collect();
+
+            let steps = plan_sync(&todos, &events, &state, &zone);
+            let mut failed = 0;
+            for step in &steps {
+                if let Some(line) = step.describe() {
+                    println!("{}{}", if dry_run { "(dry run) " } else { "" }, line);
+                }
+                if dry_run {
+                    continue;
+                }
+                let id = calendar_id.as_deref().unwrap_or_default();
+                let done = match step {
+                    SyncStep::CreateEvent { path, event_id, due, .. } | SyncStep::UpdateEvent { path, event_id, due, .. } => {
+                        let todo = todos.iter().find(|todo| &todo.path == path).expect("planned from a todo");
+                        calendar.put_event(id, event_id, &event_for(todo, *due, id, &zone), &[(TODO_PROPERTY, path)]).await.map(|_| ())
+                    }
+                    SyncStep::RemoveEvent { event_id, .. } => calendar.delete_event(id, event_id).await,
+                    SyncStep::UpdateTodo { path, due, .. } => match todos.iter().find(|todo| &todo.path == path) {
+                        Some(todo) => {
+                            let mut todo = todo.clone();
+                            todo.due = Some(due.date);
+                            todo.due_raw = Some(due.to_string());
+                            store.save(&todo).await
+                        }
+                        None => Ok(()),
+                    },
+                    SyncStep::Conflict { .. } | SyncStep::Forget { .. } | SyncStep::InSync { .. } => Ok(()),
+                };
+                match done {
+                    Ok(()) => state.apply(step),
+                    Err(e) => {
+                        failed += 1;
+                        eprintln!("⚠️  {}: {:#}", step_path(step), e);
+                    }
+                }
+            }
+
+            let changed = steps.iter().filter(|step| step.describe().is_some()).count();
+            if changed == 0 {
+                println!("✅ Todos and the '{}' calendar already match", config.calendar.tasks_calendar);
+            }
+            if !dry_run {
+                state.calendar_id = calendar_id;
+                state.save(&state_path)?;
+            }
+            crate::script::publish_output(serde_json::json!({
+                "dry_run": dry_run,
+                "steps": steps.iter().filter(|step| !matches!(step, SyncStep::InSync { .. })).collect::<Vec<_>>(),
+                "failed": failed,
+            }));
+        }
+    }
+    Ok(())
+}
+
+fn step_path(step: &SyncStep) -> &str {
+    match step {
+        SyncStep::CreateEvent { path, .. }
+        | SyncStep::UpdateEvent { path, .. }
+        | SyncStep::RemoveEvent { path, .. }
+        | SyncStep::UpdateTodo { path, .. }
+        | SyncStep::Conflict { path, .. }
+        | SyncStep::Forget { path }
+        | SyncStep::InSync { path, .. } => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo_model::TodoStatus;
+
+    fn todo(path: &str, due: &str, status: &str) -> TodoItem {
+        TodoItem::parse(path, &format!("---\ndue_date: {}\nstatus: {}\n---\n\n- [ ] {}", due, status, path))
+    }
+
+    fn due(text: &str) -> DueAt {
+        DueAt { date: text[..10].parse().unwrap(), time: text.get(11..).map(|t| NaiveTime::parse_from_str(t, "%H:%M").unwrap()) }
+    }
+
+    fn event(todo: &TodoItem, at: &str) -> (CalendarEvent, String) {
+        let mut event = event_for(todo, due(at), "/cal/tasks/", &TimeZone::utc());
+        event.id = event_id_for(&todo.path);
+        (event, todo.path.clone())
+    }
+
+    fn synced(entries: &[(&str, &str)]) -> SyncState {
+        SyncState {
+            todos: entries.iter().map(|(path, at)| (path.to_string(), SyncedTodo { event_id: event_id_for(path), due: due(at) })).collect(),
+            ..SyncState::default()
+        }
+    }
+
+    #[test]
+    fn test_sync_plans_each_side_and_reports_conflicts() {
+        let zone = TimeZone::utc();
+        let new = todo("Todos/new.md", "2024-06-03 14:00", "open");
+        let moved_here = todo("Todos/moved-here.md", "2024-06-05", "open");
+        let moved_there = todo("Todos/moved-there.md", "2024-06-04", "open");
+        let done = todo("Todos/done.md", "2024-06-04", "done");
+        let both = todo("Todos/both.md", "2024-06-07", "open");
+        let steady = todo("Todos/steady.md", "2024-06-04", "open");
+        let todos = vec![new.clone(), moved_here.clone(), moved_there.clone(), done.clone(), both.clone(), steady.clone()];
+        let events = vec![
+            event(&moved_here, "2024-06-04"),
+            event(&moved_there, "2024-06-06 09:30"),
+            event(&done, "2024-06-04"),
+            event(&both, "2024-06-08"),
+            event(&steady, "2024-06-04"),
+        ];
+        let state = synced(&[
+            ("Todos/moved-here.md", "2024-06-04"),
+            ("Todos/moved-there.md", "2024-06-04"),
+            ("Todos/done.md", "2024-06-04"),
+            ("Todos/both.md", "2024-06-04"),
+            ("Todos/steady.md", "2024-06-04"),
+            ("Todos/gone.md", "2024-06-01"),
+        ]);
+
+        let steps = plan_sync(&todos, &events, &state, &zone);
+        let step = |path: &str| steps.iter().find(|step| step_path(step) == path).cloned();
+        assert!(matches!(step("Todos/new.md"), Some(SyncStep::CreateEvent { due, .. }) if due == self::due("2024-06-03 14:00")));
+        assert!(matches!(step("Todos/moved-here.md"), Some(SyncStep::UpdateEvent { due, .. }) if due == self::due("2024-06-05")));
+        assert!(matches!(step("Todos/moved-there.md"), Some(SyncStep::UpdateTodo { due, .. }) if due == self::due("2024-06-06 09:30")));
+        assert!(matches!(step("Todos/done.md"), Some(SyncStep::RemoveEvent { ref reason, .. }) if reason == "todo done"));
+        assert!(matches!(step("Todos/both.md"), Some(SyncStep::Conflict { .. })));
+        assert!(matches!(step("Todos/steady.md"), Some(SyncStep::InSync { .. })));
+        assert_eq!(step("Todos/gone.md"), Some(SyncStep::Forget { path: "Todos/gone.md".to_string() }));
+
+        // Applying the steps leaves a state the next sync has nothing to do for, except the conflict
+        let mut state = state;
+        steps.iter().for_each(|step| state.apply(step));
+        assert!(!state.todos.contains_key("Todos/done.md") && !state.todos.contains_key("Todos/gone.md"));
+        assert_eq!(state.todos["Todos/both.md"].due, due("2024-06-04"));
+        assert_eq!(state.todos["Todos/moved-there.md"].due, due("2024-06-06 09:30"));
+
+        // Without a record the todo wins, and an event for a finished todo is removed
+        let steps = plan_sync(&todos, &events, &SyncState::default(), &zone);
+        assert_eq!(step_path(&steps[0]), "Todos/both.md");
+        assert!(matches!(&steps[0], SyncStep::UpdateEvent { due, .. } if *due == self::due("2024-06-07")));
+        assert!(steps.iter().all(|step| !matches!(step, SyncStep::Conflict { .. })));
+    }
+
+    #[test]
+    fn test_due_times_round_trip_through_events_and_todos() {
+        let zone = TimeZone::utc();
+        let mut item = todo("Todos/call.md", "2024-06-03T14:00", "open");
+        assert_eq!(DueAt::of_todo(&item), Some(due("2024-06-03 14:00")));
+        let event = event_for(&item, due("2024-06-03 14:00"), "/cal/tasks/", &zone);
+        assert!(!event.all_day);
+        assert_eq!(event.end_time - event.start_time, Duration::minutes(DEFAULT_EVENT_MINUTES));
+        assert_eq!(DueAt::of_event(&event, &zone), due("2024-06-03 14:00"));
+
+        item.due = Some(due("2024-06-04").date);
+        item.due_raw = Some("2024-06-04 09:15".to_string());
+        assert!(item.to_markdown().contains("due_date: 2024-06-04 09:15\n"));
+        item.status = TodoStatus::Done;
+        assert_eq!(DueAt::of_todo(&item), None);
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::Result;
+use std::fmt::Write as _;
+
+use crate::cli::{TagsAction, TagsArgs};
+use crate::config::Config;
+use crate::conflicts::split_frontmatter;
+use crate::confirm::{confirm, Operation, OperationClass};
+use crate::obsidian_adapter::{ObsidianAdapter, TagEdit, TagIndex, TagRename};
+use crate::report::{DetailLevel, Report, ReportView};
+
+/// The tags by usage (or by name), one per line
+pub fn tag_list_report(index: &TagIndex, by_name: bool) -> Report {
+    let mut tags: Vec<(&String, &usize)> = index.tags.iter().collect();
+    if !by_name {
+        tags.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    }
+    let width = tags.iter().map(|(tag, _)| tag.chars().count()).max().unwrap_or(0);
+    let mut lines = String::new();
+    for (tag, notes) in &tags {
+        let _ = writeln!(lines, "  #{:<width$}  {}", tag, notes, width = width);
+    }
+    Report::new()
+        .text(DetailLevel::Summary, format!("{} tag(s) across {} note(s)\n", index.tags.len(), index.notes_scanned))
+        .text(DetailLevel::Normal, lines)
+        .field(DetailLevel::Summary, "notes_scanned", index.notes_scanned)
+        .field(DetailLevel::Normal, "tags", &index.tags)
+}
+
+/// The lines a tag rename changes in one note, as `-`/`+` pairs numbered by line.
+/// Frontmatter and body are compared separately, so a reformatted `tags` list doesn't
+/// drag unchanged body lines into the diff.
+pub fn edit_diff(edit: &TagEdit) -> String {
+    let (before_yaml, before_body) = split_frontmatter(&edit.before);
+    let (after_yaml, after_body) = split_frontmatter(&edit.after);
+    let mut out = format!("{}\n", edit.path);
+    // Body line numbers count from the top of the file, past the `---` fences
+    let body_start = |content: &str, body: &str| content[..content.len() - body.len()].lines().count();
+    diff_lines(&mut out, before_yaml.unwrap_or(""), after_yaml.unwrap_or(""), 2);
+    diff_lines(&mut out, before_body, after_body, body_start(&edit.before, before_body) + 1);
+    out
+}
+
+fn diff_lines(out: &mut String, before: &str, after: &str, first_line: usize) {
+    let before: Vec<&str> = before.lines().collect();
+    let after: Vec<&str> = after.lines().collect();
+    let prefix = before.iter().zip(&after).take_while(|(a, b)| a == b).count();
+    let suffix = before[prefix..].iter().rev().zip(after[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (removed, added) = (&before[prefix..before.len() - suffix], &after[prefix..after.len() - suffix]);
+    if removed.len() == added.len() {
+        for (i, (old, new)) in removed.iter().zip(added).enumerate() {
+            if old != new {
+                let _ = writeln!(out, "  {:>4} - {}", first_line + prefix + i, old);
+                let _ = writeln!(out, "       + {}", new);
+            }
+        }
+    } else if !removed.is_empty() || !added.is_empty() {
+        for (i, old) in removed.iter().enumerate() {
+            let _ = writeln!(out, "  {:>4} - {}", first_line + prefix + i, old);
+        }
+        for new in added {
+            let _ = writeln!(out, "       + {}", new);
+        }
+    }
+}
+
+async fn rename_tags(from: Vec<String>, to: String, dry_run: bool, yes: bool, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let mut rename = adapter.rename_tag(&from, &to, true).await?;
+    let tags = rename.from.iter().map(|tag| format!("#{}", tag)).collect::<Vec<_>>().join(", ");
+    let output = |rename: &TagRename, written: bool| serde_json::json!({
+        "from": rename.from,
+        "to": rename.to,
+        "dry_run": dry_run,
+        "notes_scanned": rename.notes_scanned,
+        "changed": if written || dry_run { rename.edits.iter().map(|edit| edit.path.as_str()).collect() } else { Vec::new() },
+        "skipped": rename.skipped,
+    });
+    for (path, reason) in &rename.skipped {
+        println!("Skipping {}: {}", path, reason);
+    }
+    if rename.edits.is_empty() {
+        println!("No notes use {} ({} note(s) checked).", tags, rename.notes_scanned);
+        crate::script::publish_output(output(&rename, false));
+        return Ok(());
+    }
+    if dry_run {
+        for edit in &rename.edits {
+            println!("{}", edit_diff(edit));
+        }
+        println!("Dry run: {} of {} note(s) would change ({} → #{})", rename.edits.len(), rename.notes_scanned, tags, rename.to);
+        crate::script::publish_output(output(&rename, false));
+        return Ok(());
+    }
+
+    for edit in &rename.edits {
+        println!("  {}", edit.path);
+    }
+    let operation = Operation::new(
+        OperationClass::BulkWrite,
+        format!("Rename {} to #{} in {} note(s)", tags, rename.to, rename.edits.len()),
+        rename.edits.len(),
+    );
+    if !confirm(&config.confirmations, &operation, yes)? {
+        println!("Nothing changed.");
+        return Ok(());
+    }
+    adapter.write_tag_rename(&mut rename).await?;
+    let committed = match &rename.report {
+        Some(report) => {
+            println!("{}", report.summary());
+            report.is_committed()
+        }
+        None => false,
+    };
+    crate::script::publish_output(output(&rename, committed));
+    Ok(())
+}
+
+pub async fn handle_tags_command(args: TagsArgs, view: ReportView, adapter: &ObsidianAdapter) -> Result<()> {
+    match args.action {
+        TagsAction::List { folder, by_name } => {
+            let index = adapter.collect_tag_index(folder.as_deref().unwrap_or("")).await?;
+            let report = tag_list_report(&index, by_name);
+            crate::script::publish_output(report.to_json(view.level));
+            report.print(view)
+        }
+        TagsAction::Rename { old, new, dry_run, yes } => rename_tags(vec![old], new, dry_run, yes, adapter).await,
+        TagsAction::Merge { tags, into, dry_run, yes } => rename_tags(tags, into, dry_run, yes, adapter).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_edit_diff_numbers_frontmatter_and_body_lines() {
+        let edit = TagEdit {
+            path: "Notes/a.md".to_string(),
+            before: "---\ntitle: A\ntags: [idea, draft]\n---\nFirst\nSee #idea\nLast\n".to_string(),
+            after: "---\ntitle: A\ntags:\n- seed\n- draft\n---\nFirst\nSee #seed\nLast\n".to_string(),
+        };
+        assert_eq!(edit_diff(&edit), "Notes/a.md\n\
+            \x20    3 - tags: [idea, draft]\n\
+            \x20      + tags:\n\
+            \x20      + - seed\n\
+            \x20      + - draft\n\
+            \x20    6 - See #idea\n\
+            \x20      + See #seed\n");
+    }
+
+    #[test]
+    fn test_tag_list_sorts_by_usage_then_name() {
+        let index = TagIndex {
+            notes_scanned: 4,
+            tags: BTreeMap::from([("alpha".to_string(), 1), ("beta".to_string(), 3), ("gamma".to_string(), 3)]),
+        };
+        assert_eq!(tag_list_report(&index, false).render(DetailLevel::Normal), "3 tag(s) across 4 note(s)\n  #beta   3\n  #gamma  3\n  #alpha  1\n");
+        assert_eq!(tag_list_report(&index, true).render(DetailLevel::Summary), "3 tag(s) across 4 note(s)\n");
+        assert!(tag_list_report(&index, true).render(DetailLevel::Normal).contains("#alpha  1\n  #beta"));
+    }
+}
@@ -0,0 +1,414 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::markdown::Markdown;
+use crate::note_skeleton::stable_hash;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::reindex::walk;
+use crate::todo_bulk::parse_estimate;
+use crate::todo_model::{TaskSource, TodoItem, TodoStatus, TODOS_DIR};
+use crate::vault_transaction::{VaultStore, DEFAULT_READ_CONCURRENCY};
+
+/// Checkbox tasks imported from other notes, with where each one lives
+pub const TASK_INDEX_PATH: &str = "Todos/task-index.json";
+
+const INDEX_VERSION: u32 = 1;
+
+/// A `- [ ]` or `- [x]` line in a note
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckboxTask {
+    /// Line number in the note, counting from 1 at the top of the file
+    pub line: usize,
+    /// Everything after the checkbox, metadata included; used to find the task again
+    pub text: String,
+    /// The text without its due date and tags
+    pub title: String,
+    pub done: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// Where the `[ ]` of a checkbox line starts, whether it is ticked, and the text after it
+fn checkbox(line: &str) -> Option<(usize, bool, &str)> {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    let rest = ["- ", "* ", "+ "].iter().find_map(|bullet| trimmed.strip_prefix(bullet))?;
+    let done = if rest.starts_with("[ ]") {
+        false
+    } else if rest.starts_with("[x]") || rest.starts_with("[X]") {
+        true
+    } else {
+        return None;
+    };
+    let text = &rest[3..];
+    if !text.is_empty() && !text.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some((indent + 2, done, text.trim()))
+}
+
+/// Checkbox lines as (1-based line number, line), outside frontmatter and code
+fn checkbox_lines(content: &str) -> Vec<(usize, &str)> {
+    let doc = Markdown::parse(content);
+    let mut offset = 0;
+    let mut lines = Vec::new();
+    for (i, line) in content.split_inclusive('\n').enumerate() {
+        let text_start = offset + line.len() - line.trim_start().len();
+        offset += line.len();
+        let line = line.trim_end_matches(['\n', '\r']);
+        if !doc.in_code(text_start) && checkbox(line).is_some_and(|(_, _, text)| !text.is_empty()) {
+            lines.push((i + 1, line));
+        }
+    }
+    lines
+}
+
+fn parse_date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
+}
+
+/// Split a task's text into its title, due date (`📅 2024-06-01` or `due:2024-06-01`) and `#tags`
+fn parse_metadata(text: &str) -> (String, Option<NaiveDate>, Vec<String>) {
+    let tag_ranges = Markdown::parse(text).tags();
+    let tags = tag_ranges.iter().map(|range| text[range.clone()].to_string()).collect();
+    // The text with each `#tag` taken out
+    let mut untagged = String::with_capacity(text.len());
+    let mut at = 0;
+    for range in &tag_ranges {
+        untagged.push_str(&text[at..range.start - 1]);
+        at = range.end;
+    }
+    untagged.push_str(&text[at..]);
+
+    let mut title = Vec::new();
+    let mut due = None;
+    let mut words = untagged.split_whitespace().peekable();
+    while let Some(word) = words.next() {
+        if let Some(rest) = word.strip_prefix('📅') {
+            let date = if rest.is_empty() { words.peek().and_then(|next| parse_date(next)) } else { parse_date(rest) };
+            if let Some(date) = date {
+                if rest.is_empty() {
+                    words.next();
+                }
+                due = Some(date);
+                continue;
+            }
+        }
+        if let Some(date) = word.strip_prefix("due::").or_else(|| word.strip_prefix("due:")).and_then(parse_date) {
+            due = Some(date);
+            continue;
+        }
+        title.push(word);
+    }
+    let title = if title.is_empty() { text.to_string() } else { title.join(" ") };
+    (title, due, tags)
+}
+
+/// Every checkbox task in a note. Lines inside fenced code blocks and frontmatter are skipped.
+pub fn extract_tasks(content: &str) -> Vec<CheckboxTask> {
+    checkbox_lines(content)
+        .into_iter()
+        .filter_map(|(line, text)| {
+            let (_, done, text) = checkbox(text)?;
+            let (title, due, tags) = parse_metadata(text);
+            Some(CheckboxTask { line, text: text.to_string(), title, done, due, tags })
+        })
+        .collect()
+}
+
+/// Tick or untick `task` in `content`. The task is looked for at its recorded line first and
+/// then, in case lines were added above it, at the nearest line with the same text.
+pub fn set_checkbox(content: &str, task: &CheckboxTask, done: bool) -> Option<(String, usize)> {
+    let line = checkbox_lines(content)
+        .into_iter()
+        .filter(|(_, line)| checkbox(line).is_some_and(|(_, _, text)| text == task.text))
+        .map(|(number, _)| number)
+        .min_by_key(|number| number.abs_diff(task.line))?;
+
+    let mark = if done { "[x]" } else { "[ ]" };
+    let updated = content
+        .split_inclusive('\n')
+        .enumerate()
+        .map(|(i, text)| match checkbox(text) {
+            Some((at, _, _)) if i + 1 == line => format!("{}{}{}", &text[..at], mark, &text[at + 3..]),
+            _ => text.to_string(),
+        })
+        .collect();
+    Some((updated, line))
+}
+
+/// A checkbox task registered in the task index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedTask {
+    pub id: String,
+    /// The note the checkbox is in
+    pub note: String,
+    #[serde(flatten)]
+    pub task: CheckboxTask,
+    pub imported: DateTime<Utc>,
+}
+
+impl IndexedTask {
+    /// The task as a todo, so `todo list` and the planners treat it like any other
+    pub fn to_todo(&self) -> TodoItem {
+        let task = &self.task;
+        TodoItem {
+            path: self.note.clone(),
+            title: task.title.clone(),
+            status: if task.done { TodoStatus::Done } else { TodoStatus::Open },
+            due_raw: task.due.map(|due| due.to_string()),
+            due: task.due,
+            priority: None,
+            tags: task.tags.clone(),
+            created: Some(self.imported),
+            completed: None,
+            estimate_minutes: task.title.split_whitespace().find_map(|w| w.strip_prefix('~').and_then(parse_estimate)),
+            content: format!("- [{}] {}", if task.done { 'x' } else { ' ' }, task.text),
+            warnings: Vec::new(),
+            source: Some(TaskSource { id: self.id.clone(), line: task.line }),
+        }
+    }
+}
+
+/// The same text twice in one note gets two ids, told apart by `occurrence`
+fn task_id(note: &str, text: &str, occurrence: usize) -> String {
+    format!("{:016x}", stable_hash(&format!("{}\n{}\n{}", note, text, occurrence)))[..10].to_string()
+}
+
+/// What an import run found
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportSummary {
+    pub notes_scanned: usize,
+    pub imported: Vec<IndexedTask>,
+    pub already_indexed: usize,
+    /// Indexed tasks whose checkbox is gone from the note
+    pub removed: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskIndex {
+    pub version: u32,
+    pub tasks: Vec<IndexedTask>,
+}
+
+impl TaskIndex {
+    pub async fn load<S: VaultStore + ?Sized>(store: &S) -> Result<Self> {
+        match store.read_file(TASK_INDEX_PATH).await? {
+            Some(raw) => serde_json::from_str(&raw).with_context(|| format!("{} is not a valid task index", TASK_INDEX_PATH)),
+            None => Ok(Self { version: INDEX_VERSION, tasks: Vec::new() }),
+        }
+    }
+
+    pub async fn save<S: VaultStore + ?Sized>(&self, store: &S) -> Result<()> {
+        store.write_file(TASK_INDEX_PATH, &serde_json::to_string_pretty(self)?).await
+            .context("Failed to write the task index")
+    }
+
+    pub fn get(&self, id: &str) -> Option<&IndexedTask> {
+        self.tasks.iter().find(|task| task.id == id)
+    }
+
+    /// Fold a scan of `notes` into the index. Tasks already indexed keep their id and get
+    /// their current line, state and metadata; new ones are added (ticked ones only with
+    /// `include_done`); indexed tasks of scanned notes that are no longer there are dropped.
+    pub fn merge_scan(&mut self, notes: &[(String, Vec<CheckboxTask>)], include_done: bool, now: DateTime<Utc>) -> ImportSummary {
+        let mut summary = ImportSummary { notes_scanned: notes.len(), ..Default::default() };
+        let mut seen = HashSet::new();
+        for (note, tasks) in notes {
+            let mut occurrences = std::collections::HashMap::new();
+            for task in tasks {
+                let occurrence = occurrences.entry(task.text.as_str()).or_insert(0);
+                let id = task_id(note, &task.text, *occurrence);
+                *occurrence += 1;
+                seen.insert(id.clone());
+                match self.tasks.iter_mut().find(|indexed| indexed.id == id) {
+                    Some(indexed) => {
+                        indexed.task = task.clone();
+                        summary.already_indexed += 1;
+                    }
+                    None if !task.done || include_done => {
+                        let indexed = IndexedTask { id, note: note.clone(), task: task.clone(), imported: now };
+                        summary.imported.push(indexed.clone());
+                        self.tasks.push(indexed);
+                    }
+                    None => {}
+                }
+            }
+        }
+        let scanned: HashSet<&str> = notes.iter().map(|(note, _)| note.as_str()).collect();
+        let before = self.tasks.len();
+        self.tasks.retain(|task| !scanned.contains(task.note.as_str()) || seen.contains(&task.id));
+        summary.removed = before - self.tasks.len();
+        summary
+    }
+}
+
+/// The checkbox tasks of every note under `folder` (the whole vault when `None`), leaving out
+/// the todo notes themselves
+pub async fn scan_tasks<S: VaultStore + ?Sized>(store: &S, folder: Option<&str>) -> Vec<(String, Vec<CheckboxTask>)> {
+    let folder = folder.unwrap_or("").trim_matches('/').to_string();
+    let todos_prefix = format!("{}/", TODOS_DIR);
+    let paths: Vec<String> = walk(store, &[folder]).await
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| !path.starts_with(&todos_prefix))
+        .collect();
+
+    let mut notes = Vec::new();
+    for (path, content) in store.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await {
+        match content {
+            Ok(content) => notes.push((path, extract_tasks(&content))),
+            Err(e) => eprintln!("Warning: could not read {}: {}", path, e),
+        }
+    }
+    notes
+}
+
+/// Scan for checkbox tasks and register the new ones. A second run only refreshes what is
+/// already indexed. With `dry_run` the index is not written.
+pub async fn import_tasks<S: VaultStore + ?Sized>(store: &S, folder: Option<&str>, include_done: bool, dry_run: bool) -> Result<ImportSummary> {
+    let mut index = TaskIndex::load(store).await?;
+    let notes = scan_tasks(store, folder).await;
+    let summary = index.merge_scan(&notes, include_done, Utc::now());
+    if !dry_run {
+        index.save(store).await?;
+    }
+    Ok(summary)
+}
+
+/// Tick (or untick) an imported task's checkbox in its note and record the change in the index
+pub async fn set_task_done<S: VaultStore + ?Sized>(store: &S, id: &str, done: bool) -> Result<IndexedTask> {
+    let mut index = TaskIndex::load(store).await?;
+    let Some(position) = index.tasks.iter().position(|task| task.id == id) else {
+        bail!("No imported task with id '{}'", id);
+    };
+    let indexed = &mut index.tasks[position];
+    let content = store.read_file(&indexed.note).await?
+        .ok_or_else(|| anyhow::anyhow!("{} no longer exists; run `arrowhead todo import --scan` to refresh the task index", indexed.note))?;
+    let Some((updated, line)) = set_checkbox(&content, &indexed.task, done) else {
+        bail!("\"{}\" is no longer in {}; run `arrowhead todo import --scan` to refresh the task index", indexed.task.text, indexed.note);
+    };
+    if updated != content {
+        store.write_file(&indexed.note, &updated).await
+            .with_context(|| format!("Failed to update the checkbox in {}", indexed.note))?;
+    }
+    indexed.task.line = line;
+    indexed.task.done = done;
+    let indexed = indexed.clone();
+    index.save(store).await?;
+    Ok(indexed)
+}
+
+pub async fn handle_import_command(scan: bool, folder: Option<String>, include_done: bool, dry_run: bool, adapter: &ObsidianAdapter) -> Result<()> {
+    if !scan {
+        bail!("Nothing to import from; pass --scan to look for `- [ ]` tasks in the vault");
+    }
+    let summary = import_tasks(adapter, folder.as_deref(), include_done, dry_run).await?;
+    let verb = if dry_run { "Would import" } else { "Imported" };
+    println!("Scanned {} note(s). {} {} new task(s); {} already indexed.", summary.notes_scanned, verb, summary.imported.len(), summary.already_indexed);
+    for indexed in &summary.imported {
+        println!("  + {} ({}:{})", indexed.task.title, indexed.note, indexed.task.line);
+    }
+    if summary.removed > 0 {
+        println!("Dropped {} task(s) whose checkbox is gone.", summary.removed);
+    }
+    if dry_run {
+        println!("Dry run: the task index was not changed.");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_transaction::testing::FakeVault;
+
+    const PLAN: &str = "---\ntags: [project]\n---\n# Plan\n\n- [ ] Draft the brief 📅 2024-06-01 #work\n* [x] Book the room\n- [ ] call Sam due:2024-05-20 #calls/weekly ~15m\n```\n- [ ] not a task, just an example\n```\n- [link](https://example.com)\n  - [ ] nested step\n- [ ]\n";
+
+    #[test]
+    fn test_extract_tasks_reads_metadata_and_skips_code() {
+        let tasks = extract_tasks(PLAN);
+        let summary: Vec<(usize, &str, bool)> = tasks.iter().map(|t| (t.line, t.title.as_str(), t.done)).collect();
+        assert_eq!(summary, vec![
+            (6, "Draft the brief", false),
+            (7, "Book the room", true),
+            (8, "call Sam ~15m", false),
+            (13, "nested step", false),
+        ]);
+        assert_eq!(tasks[0].due, Some(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap()));
+        assert_eq!(tasks[0].tags, vec!["work"]);
+        assert_eq!(tasks[2].due, Some(NaiveDate::from_ymd_opt(2024, 5, 20).unwrap()));
+        assert_eq!(tasks[2].tags, vec!["calls/weekly"]);
+        assert_eq!(tasks[2].text, "call Sam due:2024-05-20 #calls/weekly ~15m");
+
+        // Not dates or tags: left in the title
+        let (title, due, tags) = parse_metadata("ship 📅 soon #42 due:later");
+        assert_eq!((title.as_str(), due, tags.len()), ("ship 📅 soon #42 due:later", None, 0));
+        let (title, _, tags) = parse_metadata("email #ops/oncall about `#not-a-tag` #urgent");
+        assert_eq!((title.as_str(), tags), ("email about `#not-a-tag`", vec!["ops/oncall".to_string(), "urgent".to_string()]));
+    }
+
+    #[test]
+    fn test_tasks_in_the_shared_fixture_skip_code() {
+        let lines: Vec<&str> = checkbox_lines(crate::markdown::FIXTURE).into_iter().map(|(_, line)| line).collect();
+        assert_eq!(lines, vec!["- [ ] Review the Roadmap #q3", "- [x] Ship the Roadmap"]);
+    }
+
+    #[test]
+    fn test_set_checkbox_finds_moved_tasks() {
+        let task = extract_tasks(PLAN).remove(0);
+        let moved = PLAN.replace("# Plan\n", "# Plan\nIntro line\n");
+        let (updated, line) = set_checkbox(&moved, &task, true).unwrap();
+        assert_eq!(line, 7);
+        assert!(updated.contains("\n- [x] Draft the brief 📅 2024-06-01 #work\n"));
+        assert_eq!(updated.lines().count(), moved.lines().count());
+        // The example in the code block is never touched
+        assert!(updated.contains("- [ ] not a task"));
+
+        assert!(set_checkbox("- [ ] something else\n", &task, true).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_import_is_idempotent_and_done_flips_the_original_checkbox() {
+        let vault = FakeVault::new(&[
+            ("Projects/plan.md", PLAN),
+            ("Daily/2024-05-01.md", "- [ ] water plants\n- [ ] water plants\n"),
+            ("Todos/buy-milk.md", "---\nstatus: open\n---\n\n- [ ] buy milk\n"),
+        ]);
+
+        let first = import_tasks(&vault, None, false, false).await.unwrap();
+        assert_eq!(first.notes_scanned, 2, "todo notes are not scanned");
+        assert_eq!(first.imported.len(), 5, "{:?}", first.imported);
+        assert!(first.imported.iter().all(|t| !t.task.done));
+        let ids: HashSet<&str> = first.imported.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids.len(), 5, "repeated text gets its own id");
+
+        let dry = import_tasks(&vault, None, true, true).await.unwrap();
+        assert_eq!(dry.imported.len(), 1, "only the ticked task is new");
+        let second = import_tasks(&vault, None, false, false).await.unwrap();
+        assert!(second.imported.is_empty());
+        assert_eq!(second.already_indexed, 5);
+
+        let brief = first.imported.iter().find(|t| t.task.title == "Draft the brief").unwrap();
+        let todo = brief.to_todo();
+        assert_eq!(todo.location(), "Projects/plan.md:6");
+        assert_eq!(todo.due, brief.task.due);
+
+        vault.write_file("Projects/plan.md", &format!("Added on top\n{}", PLAN)).await.unwrap();
+        let done = set_task_done(&vault, &brief.id, true).await.unwrap();
+        assert_eq!(done.task.line, 7);
+        assert!(vault.get("Projects/plan.md").unwrap().contains("- [x] Draft the brief"));
+        let index = TaskIndex::load(&vault).await.unwrap();
+        assert!(index.get(&brief.id).unwrap().task.done);
+
+        // A checkbox that's gone is dropped on the next scan
+        vault.write_file("Daily/2024-05-01.md", "- [ ] water plants\n").await.unwrap();
+        let third = import_tasks(&vault, None, false, false).await.unwrap();
+        assert_eq!(third.removed, 1);
+        assert!(set_task_done(&vault, "missing", true).await.is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Values a note template is rendered with. A value may be a list, which a repeating section
+/// of the same name renders once per item; dotted names (`topic.owner`) reach into objects
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TemplateValues {
+    values: HashMap<String, Value>,
+}
+
+impl TemplateValues {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        self.values.insert(name.into(), value.into());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        let mut parts = name.split('.');
+        let mut value = self.values.get(parts.next()?)?;
+        for part in parts {
+            value = value.get(part)?;
+        }
+        Some(value)
+    }
+
+    /// The value as note text; lists are joined with commas
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.get(name).map(value_text)
+    }
+
+    /// Whether the value is there and not null, empty or false
+    pub fn has(&self, name: &str) -> bool {
+        match self.get(name) {
+            None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+            Some(Value::String(s)) => !s.is_empty(),
+            Some(Value::Array(items)) => !items.is_empty(),
+            Some(Value::Object(fields)) => !fields.is_empty(),
+            Some(_) => true,
+        }
+    }
+
+    /// Items a repeating section over `name` renders: each item of a list, a single other
+    /// value once, and nothing when the value is missing
+    pub fn items(&self, name: &str) -> Vec<Value> {
+        match self.get(name) {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(items)) => items.clone(),
+            Some(value) => vec![value.clone()],
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.values.iter()
+    }
+
+    /// These values with `name` bound to `item`
+    pub fn with_item(&self, name: &str, item: Value) -> Self {
+        let mut scoped = self.clone();
+        scoped.set(name, item);
+        scoped
+    }
+}
+
+impl From<HashMap<String, String>> for TemplateValues {
+    fn from(values: HashMap<String, String>) -> Self {
+        Self { values: values.into_iter().map(|(k, v)| (k, Value::String(v))).collect() }
+    }
+}
+
+impl From<HashMap<String, Value>> for TemplateValues {
+    fn from(values: HashMap<String, Value>) -> Self {
+        Self { values }
+    }
+}
+
+pub fn value_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(value_text).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+/// Condition of a conditional template section: `has(field)`, `field == "x"`, `field != "x"`,
+/// a bare `field` (same as `has`), `!` or `not`, `and`/`&&`, `or`/`||` and parentheses
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Has(String),
+    Equals(String, String),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Self> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens: &tokens, position: 0 };
+        let condition = parser.or()?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected {:?} in condition '{}'", token, text);
+        }
+        Ok(condition)
+    }
+
+    pub fn evaluate(&self, values: &TemplateValues) -> bool {
+        match self {
+            Condition::Has(name) => values.has(name),
+            Condition::Equals(name, expected) => values.text(name).unwrap_or_default() == *expected,
+            Condition::Not(inner) => !inner.evaluate(values),
+            Condition::And(left, right) => left.evaluate(values) && right.evaluate(values),
+            Condition::Or(left, right) => left.evaluate(values) || right.evaluate(values),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Open,
+    Close,
+    Not,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '!' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::NotEqual),
+            '!' => tokens.push(Token::Not),
+            '=' if chars.next_if_eq(&'=').is_some() => tokens.push(Token::Equal),
+            '&' if chars.next_if_eq(&'&').is_some() => tokens.push(Token::And),
+            '|' if chars.next_if_eq(&'|').is_some() => tokens.push(Token::Or),
+            '"' | '\'' => {
+                let mut literal = String::new();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some(other) => literal.push(other),
+                        None => bail!("Unterminated string in condition '{}'", text),
+                    }
+                }
+                tokens.push(Token::Text(literal));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.next_if(|n| n.is_alphanumeric() || matches!(n, '_' | '.' | '-')) {
+                    word.push(next);
+                }
+                tokens.push(match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+            other => bail!("Unexpected '{}' in condition '{}'", other, text),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if *token == expected => Ok(()),
+            other => bail!("Expected {:?}, found {:?}", expected, other),
+        }
+    }
+
+    fn or(&mut self) -> Result<Condition> {
+        let mut condition = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition> {
+        let mut condition = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.position += 1;
+            condition = Condition::And(Box::new(condition), Box::new(self.unary()?));
+        }
+        Ok(condition)
+    }
+
+    fn unary(&mut self) -> Result<Condition> {
+        if self.peek() == Some(&Token::Not) {
+            self.position += 1;
+            return Ok(Condition::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Condition> {
+        match self.next().cloned() {
+            Some(Token::Open) => {
+                let condition = self.or()?;
+                self.expect(Token::Close)?;
+                Ok(condition)
+            }
+            Some(Token::Word(word)) if word == "has" && self.peek() == Some(&Token::Open) => {
+                self.position += 1;
+                let field = match self.next() {
+                    Some(Token::Word(field)) => field.clone(),
+                    other => bail!("Expected a field name in has(), found {:?}", other),
+                };
+                self.expect(Token::Close)?;
+                Ok(Condition::Has(field))
+            }
+            Some(Token::Word(field)) => {
+                let negated = match self.peek() {
+                    Some(Token::Equal) => false,
+                    Some(Token::NotEqual) => true,
+                    _ => return Ok(Condition::Has(field)),
+                };
+                self.position += 1;
+                let expected = match self.next() {
+                    Some(Token::Text(text)) | Some(Token::Word(text)) => text.clone(),
+                    other => bail!("Expected a value to compare {} with, found {:?}", field, other),
+                };
+                let equals = Condition::Equals(field, expected);
+                Ok(if negated { Condition::Not(Box::new(equals)) } else { equals })
+            }
+            other => bail!("Expected a condition, found {:?}", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn values() -> TemplateValues {
+        TemplateValues::from(HashMap::from([
+            ("status".to_string(), json!("blocked")),
+            ("owner".to_string(), json!({ "name": "Ana" })),
+            ("notes".to_string(), json!("")),
+            ("topics".to_string(), json!(["launch", "hiring"])),
+        ]))
+    }
+
+    #[test]
+    fn test_conditions_evaluate_over_values() {
+        let values = values();
+        let check = |text: &str| Condition::parse(text).unwrap().evaluate(&values);
+        assert!(check("has(status)"));
+        assert!(!check("has(notes)") && !check("has(missing)"));
+        assert!(check("status == \"blocked\"") && check("status != 'done'"));
+        assert!(check("owner.name == Ana"));
+        assert!(check("!has(notes) && (topics or missing)"));
+        assert!(!check("not status == blocked || has(missing) and has(status)"));
+    }
+
+    #[test]
+    fn test_malformed_conditions_are_errors() {
+        for text in ["", "has(", "status ==", "status = 'x'", "(has(status)", "status == 'x", "if the project exists?"] {
+            assert!(Condition::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_items_and_text() {
+        let values = values();
+        assert_eq!(values.items("topics").len(), 2);
+        assert_eq!(values.items("status"), vec![json!("blocked")]);
+        assert!(values.items("missing").is_empty());
+        assert_eq!(values.text("topics").unwrap(), "launch, hiring");
+        assert_eq!(values.with_item("topic", json!("launch")).text("topic").unwrap(), "launch");
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::cli::{TemplateAction, TemplateArgs};
+use crate::ai_conversation::create_llm_client;
+use crate::config::Config;
+use crate::obsidian_adapter::{ObsidianAdapter, TemplateFormat};
+use crate::reindex::{embedding_indexer, walk};
+use crate::usage::UsagePurpose;
+
+pub async fn handle_template_command(args: TemplateArgs) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let mut templates = ObsidianAdapter::new(Some(config.obsidian.base_url.clone()), config.obsidian.api_key.clone());
+    templates.set_cache_settings(&config.cache);
+    templates.load_template_database()?;
+
+    match args.action {
+        TemplateAction::Discover { folder, min_cluster_size } => {
+            let mut discoverer = embedding_indexer(&config)?;
+            match create_llm_client(&config, UsagePurpose::Analysis) {
+                Ok(llm_client) => discoverer.set_llm_client(llm_client),
+                Err(e) => log::warn!("naming patterns without AI analysis: {}", e),
+            }
+            discoverer.load_vector_database()?;
+            discoverer.load_template_database()?;
+            let paths: Vec<String> = walk(&discoverer, &[folder.unwrap_or_default()]).await.into_iter().map(|(path, _)| path).collect();
+            let patterns = discoverer.discover_template_patterns(paths.iter().map(String::as_str).collect(), min_cluster_size).await?;
+            if patterns.is_empty() {
+                println!("No group of {} or more similar notes among {} note(s).", min_cluster_size, paths.len());
+            }
+            for pattern in &patterns {
+                println!("{} — {} notes, cohesion {:.2}", pattern.name, pattern.match_count, pattern.confidence);
+                for example in &pattern.examples {
+                    println!("    {}", example);
+                }
+            }
+            crate::script::publish_output(serde_json::json!({ "notes": paths.len(), "patterns": patterns }));
+        }
+        TemplateAction::Export { path } => {
+            let path = Path::new(&path);
+            let count = templates.export_templates(path, TemplateFormat::from_path(path))?;
+            println!("✅ Exported {} template(s) to {}", count, path.display());
+            crate::script::publish_output(serde_json::json!({ "path": path, "exported": count }));
+        }
+        TemplateAction::Import { path, on_conflict } => {
+            let path = Path::new(&path);
+            let summary = templates.import_templates(path, TemplateFormat::from_path(path), on_conflict)?;
+            println!(
+                "✅ Imported templates from {}: {} added, {} overwritten, {} duplicated, {} skipped",
+                path.display(), summary.added, summary.overwritten, summary.duplicated, summary.skipped
+            );
+            crate::script::publish_output(serde_json::to_value(&summary)?);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::managed_sections::without_managed_sections;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::snippets::stem;
+
+/// Words too common in notes to say what one is about
+const STOPWORDS: &[&str] = &[
+    "about", "after", "again", "all", "also", "and", "any", "are", "because", "been", "before",
+    "being", "but", "can", "could", "did", "does", "doing", "done", "each", "few", "for", "from",
+    "get", "got", "had", "has", "have", "her", "here", "him", "his", "how", "into", "its", "just",
+    "like", "made", "make", "many", "may", "more", "most", "much", "must", "need", "not", "now",
+    "off", "once", "one", "only", "other", "our", "out", "over", "own", "same", "see", "she",
+    "should", "some", "such", "than", "that", "the", "their", "them", "then", "there", "these",
+    "they", "thing", "things", "this", "those", "through", "too", "under", "until", "use", "used",
+    "using", "very", "was", "way", "well", "were", "what", "when", "where", "which", "while",
+    "who", "why", "will", "with", "would", "yet", "you", "your",
+];
+
+/// The words of a note's body worth weighing, lowercased, in order, with repeats. Code
+/// blocks, managed sections, stopwords, numbers and words shorter than three letters are
+/// left out.
+pub fn note_words(body: &str) -> Vec<String> {
+    let body = without_managed_sections(body, &[]);
+    let mut words = Vec::new();
+    let mut in_code = false;
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        for word in line.split(|c: char| !c.is_alphanumeric()) {
+            let word = word.to_lowercase();
+            if word.chars().count() >= 3 && !word.chars().all(|c| c.is_ascii_digit()) && !STOPWORDS.contains(&word.as_str()) {
+                words.push(word);
+            }
+        }
+    }
+    words
+}
+
+/// The distinct terms (stemmed words) of a note
+fn note_terms(content: &str) -> BTreeSet<String> {
+    let body = ObsidianAdapter::parse_markdown_file(content).map_or(content.to_string(), |file| file.content);
+    note_words(&body).iter().map(|word| stem(word)).collect()
+}
+
+/// Which indexed notes use each term, kept next to the vector database so keywords can be
+/// weighed by TF-IDF without an LLM. Only each note's terms are stored; the document
+/// frequencies are rebuilt when the index is loaded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TermIndex {
+    /// Distinct terms of each indexed note
+    notes: BTreeMap<String, BTreeSet<String>>,
+    /// Indexed notes using each term
+    #[serde(skip)]
+    frequency: HashMap<String, usize>,
+}
+
+impl TermIndex {
+    /// The index saved at `path`; empty when there is none yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path).context("Failed to read the term index")?;
+        let mut index: Self = serde_json::from_str(&data).context("Failed to parse the term index")?;
+        for terms in index.notes.values() {
+            for term in terms {
+                *index.frequency.entry(term.clone()).or_default() += 1;
+            }
+        }
+        Ok(index)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        crate::cache::write_atomic(path, serde_json::to_string(self)?.as_bytes()).context("Failed to write the term index")
+    }
+
+    /// Replace the terms of `path` with those in `content`; false when they are unchanged
+    pub fn update(&mut self, path: &str, content: &str) -> bool {
+        let terms = note_terms(content);
+        if self.notes.get(path) == Some(&terms) {
+            return false;
+        }
+        self.remove(path);
+        for term in &terms {
+            *self.frequency.entry(term.clone()).or_default() += 1;
+        }
+        self.notes.insert(path.to_string(), terms);
+        true
+    }
+
+    /// Forget `path` and its terms; false when it wasn't indexed
+    pub fn remove(&mut self, path: &str) -> bool {
+        let Some(terms) = self.notes.remove(path) else { return false };
+        for term in &terms {
+            if let Some(count) = self.frequency.get_mut(term) {
+                *count -= 1;
+                if *count == 0 {
+                    self.frequency.remove(term);
+                }
+            }
+        }
+        true
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        let Some(terms) = self.notes.remove(from) else { return false };
+        self.remove(to);
+        self.notes.insert(to.to_string(), terms);
+        true
+    }
+
+    /// Keep only the notes `keep` returns true for; returns how many were dropped
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) -> usize {
+        let dropped: Vec<String> = self.notes.keys().filter(|path| !keep(path)).cloned().collect();
+        for path in &dropped {
+            self.remove(path);
+        }
+        dropped.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.notes.clear();
+        self.frequency.clear();
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.notes.contains_key(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// Smoothed inverse document frequency of a stemmed term: 1.0 for a term in every
+    /// indexed note, higher the rarer it is. A note that isn't indexed yet counts as one more.
+    pub fn idf(&self, term: &str) -> f32 {
+        let notes = self.notes.len() as f32 + 1.0;
+        let with_term = self.frequency.get(term).copied().unwrap_or(0) as f32 + 1.0;
+        (notes / with_term).ln() + 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terms_skip_code_stopwords_and_numbers() {
+        let words = note_words("The budget for 2024 is planned.\n```\nlet hidden = 1;\n```\nBudgets are Planning work");
+        assert_eq!(words, vec!["budget", "planned", "budgets", "planning", "work"]);
+        let terms = note_terms("---\ntags: [finance]\n---\nThe budget for 2024 is planned. Budgets!");
+        assert_eq!(terms.into_iter().collect::<Vec<_>>(), vec!["budget", "plan"]);
+    }
+
+    #[test]
+    fn test_document_frequencies_follow_updates() {
+        let mut index = TermIndex::default();
+        assert!(index.update("a.md", "budget review"));
+        assert!(index.update("b.md", "budget garden"));
+        assert!(!index.update("b.md", "garden budget"));
+        assert!(index.idf("review") > index.idf("budget"));
+        assert_eq!(index.idf("budget"), 1.0);
+
+        assert!(index.rename("a.md", "c.md"));
+        index.retain(|path| path != "b.md");
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.idf("garden"), 2f32.ln() + 1.0);
+
+        let path = std::env::temp_dir().join(format!("arrowhead-terms-{}.json", uuid::Uuid::new_v4()));
+        index.save(&path).unwrap();
+        assert_eq!(TermIndex::load(&path).unwrap(), index);
+        fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,507 @@
+//! Test doubles for code that talks to an LLM or to the Obsidian Local REST API, behind the
+//! `testing` feature so integration tests here and in crates embedding arrowhead can run the
+//! AI paths without API keys or a running Obsidian. Also built for this crate's unit tests,
+//! which share its deterministic `TestRng`.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::ai_conversation::{FunctionSchema, LLMClient, Message, MessageRole};
+use crate::calendar_adapter::{Deadline, DeadlinePriority, DeadlineStatus, ReminderSettings};
+use crate::confirm::PromptReader;
+use crate::embedding_provider::EmbeddingProvider;
+use crate::note_skeleton::stable_hash;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The text of the last user message, which scripted responses are matched against
+pub fn last_user_message(messages: &[Message]) -> &str {
+    messages.iter().rev()
+        .find(|m| matches!(m.role, MessageRole::User))
+        .map_or("", |m| m.content.as_str())
+}
+
+struct Rule {
+    /// Matched case-insensitively against the last user message
+    pattern: String,
+    /// The reply, or the error to fail with
+    outcome: std::result::Result<String, String>,
+}
+
+#[derive(Default)]
+struct Script {
+    rules: Vec<Rule>,
+    otherwise: Option<String>,
+    latency: Option<Duration>,
+    /// Calls still to fail before the rules apply, and the error they fail with
+    failures: Option<(usize, String)>,
+    calls: Vec<Vec<Message>>,
+}
+
+/// An `LLMClient` that answers from a script. Rules are tried in the order they were added
+/// and the first whose pattern appears in the last user message wins; a request no rule
+/// matches fails unless `otherwise` gave a default. Every request is recorded. Clones share
+/// the script and the recording, so keep a clone to inspect calls after boxing one.
+#[derive(Clone, Default)]
+pub struct MockLLMClient {
+    script: Arc<Mutex<Script>>,
+}
+
+impl MockLLMClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reply with `reply` when the last user message contains `pattern`
+    pub fn respond_when(self, pattern: &str, reply: impl Into<String>) -> Self {
+        lock(&self.script).rules.push(Rule { pattern: pattern.to_lowercase(), outcome: Ok(reply.into()) });
+        self
+    }
+
+    /// Fail with `error` when the last user message contains `pattern`
+    pub fn fail_when(self, pattern: &str, error: impl Into<String>) -> Self {
+        lock(&self.script).rules.push(Rule { pattern: pattern.to_lowercase(), outcome: Err(error.into()) });
+        self
+    }
+
+    /// Reply with `reply` when no rule matches
+    pub fn otherwise(self, reply: impl Into<String>) -> Self {
+        lock(&self.script).otherwise = Some(reply.into());
+        self
+    }
+
+    /// Fail the first `count` requests with `error`, as a flaky provider would
+    pub fn fail_first(self, count: usize, error: impl Into<String>) -> Self {
+        lock(&self.script).failures = Some((count, error.into()));
+        self
+    }
+
+    /// Wait this long before answering each request
+    pub fn with_latency(self, latency: Duration) -> Self {
+        lock(&self.script).latency = Some(latency);
+        self
+    }
+
+    /// The messages of every request so far, oldest first
+    pub fn calls(&self) -> Vec<Vec<Message>> {
+        lock(&self.script).calls.clone()
+    }
+
+    pub fn call_count(&self) -> usize {
+        lock(&self.script).calls.len()
+    }
+
+    /// The last user message of every request so far, oldest first
+    pub fn prompts(&self) -> Vec<String> {
+        lock(&self.script).calls.iter().map(|call| last_user_message(call).to_string()).collect()
+    }
+
+    async fn answer(&self, messages: Vec<Message>) -> Result<String> {
+        let (latency, outcome) = {
+            let mut script = lock(&self.script);
+            let prompt = last_user_message(&messages).to_lowercase();
+            script.calls.push(messages);
+            let injected = match &mut script.failures {
+                Some((left, error)) if *left > 0 => {
+                    *left -= 1;
+                    Some(Err(error.clone()))
+                }
+                _ => None,
+            };
+            let outcome = injected
+                .or_else(|| script.rules.iter().find(|rule| prompt.contains(&rule.pattern)).map(|rule| rule.outcome.clone()))
+                .or_else(|| script.otherwise.clone().map(Ok))
+                .unwrap_or_else(|| Err(format!("MockLLMClient has no scripted response for: {}", prompt)));
+            (script.latency, outcome)
+        };
+        if let Some(latency) = latency {
+            tokio::time::sleep(latency).await;
+        }
+        outcome.map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+#[async_trait]
+impl LLMClient for MockLLMClient {
+    async fn send_message(&self, messages: Vec<Message>) -> Result<Message> {
+        let content = self.answer(messages).await?;
+        Ok(Message {
+            id: uuid::Uuid::new_v4().to_string(),
+            role: MessageRole::Assistant,
+            content,
+            timestamp: Utc::now(),
+            function_call: None,
+        })
+    }
+
+    async fn stream_response(&self, messages: Vec<Message>) -> Result<mpsc::Receiver<String>> {
+        let content = self.answer(messages).await?;
+        let (tx, rx) = mpsc::channel(1);
+        tx.send(content).await.ok();
+        Ok(rx)
+    }
+
+    async fn function_calling(&self, messages: Vec<Message>, _functions: Vec<FunctionSchema>) -> Result<Message> {
+        self.send_message(messages).await
+    }
+
+    fn get_model_name(&self) -> String {
+        "mock".to_string()
+    }
+}
+
+/// An `EmbeddingProvider` whose vectors are normalized word counts hashed into `dimension`
+/// buckets, so notes sharing words come out similar. Records every text it embeds.
+#[derive(Clone)]
+pub struct MockEmbeddingProvider {
+    dimension: usize,
+    embedded: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockEmbeddingProvider {
+    pub fn new(dimension: usize) -> Self {
+        Self { dimension: dimension.max(1), embedded: Arc::default() }
+    }
+
+    pub fn vector(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimension];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            vector[(stable_hash(&word.to_lowercase()) % self.dimension as u64) as usize] += 1.0;
+        }
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+
+    /// The texts embedded so far, oldest first
+    pub fn embedded(&self) -> Vec<String> {
+        lock(&self.embedded).clone()
+    }
+}
+
+impl Default for MockEmbeddingProvider {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for MockEmbeddingProvider {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        lock(&self.embedded).push(text.to_string());
+        Ok(self.vector(text))
+    }
+
+    fn model_id(&self) -> String {
+        format!("mock/bag-of-words-{}", self.dimension)
+    }
+
+    fn dimension(&self) -> Option<usize> {
+        Some(self.dimension)
+    }
+}
+
+/// A deterministic xorshift generator for synthetic embeddings, so tests measuring recall
+/// or ranking see the same vectors on every run
+pub struct TestRng(u64);
+
+impl TestRng {
+    /// `seed` must not be zero
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Uniform in [-1, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0
+    }
+
+    pub fn vector(&mut self, dimension: usize) -> Vec<f32> {
+        (0..dimension).map(|_| self.next_f32()).collect()
+    }
+
+    /// `center` with each component moved by up to `spread`
+    pub fn near(&mut self, center: &[f32], spread: f32) -> Vec<f32> {
+        center.iter().map(|c| c + self.next_f32() * spread).collect()
+    }
+}
+
+impl Default for TestRng {
+    fn default() -> Self {
+        Self::new(0x9e3779b97f4a7c15)
+    }
+}
+
+/// A `PromptReader` that answers from a script, in order, and then with Enter. Records
+/// every prompt it shows. A piped one is not a terminal, and asking it fails the test.
+pub struct ScriptedPrompt {
+    interactive: bool,
+    answers: VecDeque<String>,
+    pub prompts: Vec<String>,
+}
+
+impl ScriptedPrompt {
+    pub fn tty(answers: &[&str]) -> Self {
+        Self { interactive: true, answers: answers.iter().map(|a| a.to_string()).collect(), prompts: Vec::new() }
+    }
+
+    pub fn piped() -> Self {
+        Self { interactive: false, answers: VecDeque::new(), prompts: Vec::new() }
+    }
+}
+
+impl PromptReader for ScriptedPrompt {
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    fn read_answer(&mut self, prompt: &str) -> Result<String> {
+        assert!(self.interactive, "must never prompt without a terminal");
+        self.prompts.push(prompt.to_string());
+        Ok(self.answers.pop_front().map_or_else(|| "\n".to_string(), |answer| format!("{}\n", answer)))
+    }
+}
+
+/// A medium-priority, not yet started deadline with no estimate, reminders, blocks or
+/// milestones, created a week before it is due. Its id is its title; tests set the fields
+/// they care about with struct update syntax.
+pub fn deadline(title: &str, due: DateTime<Utc>) -> Deadline {
+    Deadline {
+        id: title.to_string(),
+        title: title.to_string(),
+        description: None,
+        due_date: due,
+        created_date: due - chrono::Duration::days(7),
+        priority: DeadlinePriority::Medium,
+        status: DeadlineStatus::NotStarted,
+        estimated_hours: 0.0,
+        completed_hours: 0.0,
+        category: "work".to_string(),
+        tags: Vec::new(),
+        dependencies: Vec::new(),
+        assignee: None,
+        project_id: None,
+        reminder_settings: ReminderSettings {
+            enabled: false,
+            advance_notifications: Vec::new(),
+            notification_channels: Vec::new(),
+            escalation_enabled: false,
+            escalation_delay_hours: 0,
+        },
+        time_blocks: Vec::new(),
+        progress_milestones: Vec::new(),
+    }
+}
+
+/// A request the mock vault server received
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Vault path, decoded; folders end in `/` and the vault root is empty
+    pub path: String,
+}
+
+#[derive(Default)]
+struct Vault {
+    files: BTreeMap<String, String>,
+    api_key: Option<String>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// The part of the Obsidian Local REST API the adapter uses, served from memory on a local
+/// port: GET, PUT, POST and DELETE of `/vault/<path>`, folder listings, and PATCH of a
+/// frontmatter field. Point an `ObsidianAdapter` at `base_url()`. Stops when dropped.
+pub struct MockVaultServer {
+    addr: SocketAddr,
+    vault: Arc<Mutex<Vault>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockVaultServer {
+    /// Start an empty vault; must be called within a Tokio runtime
+    pub async fn start() -> Result<Self> {
+        Self::with_files(&[]).await
+    }
+
+    /// Start a vault holding `files`, as (path, content) pairs
+    pub async fn with_files(files: &[(&str, &str)]) -> Result<Self> {
+        let vault = Arc::new(Mutex::new(Vault {
+            files: files.iter().map(|(path, content)| (path.to_string(), content.to_string())).collect(),
+            ..Vault::default()
+        }));
+        let shared = vault.clone();
+        let make_service = make_service_fn(move |_| {
+            let vault = shared.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |request| handle(vault.clone(), request))) }
+        });
+        let server = Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .context("Failed to bind the mock vault server")?
+            .serve(make_service);
+        let addr = server.local_addr();
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            stopped.await.ok();
+        }));
+        Ok(Self { addr, vault, shutdown: Some(shutdown) })
+    }
+
+    /// Answer 401 to requests without `Authorization: Bearer <api_key>`
+    pub fn require_api_key(&self, api_key: &str) {
+        lock(&self.vault).api_key = Some(api_key.to_string());
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn file(&self, path: &str) -> Option<String> {
+        lock(&self.vault).files.get(path).cloned()
+    }
+
+    /// Create or replace a file, as an edit in Obsidian would
+    pub fn insert(&self, path: &str, content: &str) {
+        lock(&self.vault).files.insert(path.to_string(), content.to_string());
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        lock(&self.vault).files.keys().cloned().collect()
+    }
+
+    /// Every request so far, oldest first
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        lock(&self.vault).requests.clone()
+    }
+}
+
+impl Drop for MockVaultServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+fn respond(status: StatusCode, content_type: &str, body: impl Into<Body>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", content_type)
+        .body(body.into())
+        .expect("static response parts are valid")
+}
+
+/// Errors in the Local REST API's shape
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    let body = serde_json::json!({ "errorCode": status.as_u16() as u32 * 100, "message": message });
+    respond(status, "application/json", body.to_string())
+}
+
+fn empty(status: StatusCode) -> Response<Body> {
+    Response::builder().status(status).body(Body::empty()).expect("static response parts are valid")
+}
+
+async fn handle(vault: Arc<Mutex<Vault>>, request: Request<Body>) -> std::result::Result<Response<Body>, Infallible> {
+    let Some(path) = request.uri().path().strip_prefix("/vault/") else {
+        return Ok(error(StatusCode::NOT_FOUND, "Not found"));
+    };
+    let path = urlencoding::decode(path).map(|p| p.into_owned()).unwrap_or_else(|_| path.to_string());
+    let method = request.method().clone();
+    let header = |name: &str| request.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let (authorization, operation, target_type, target) = (header("Authorization"), header("Operation"), header("Target-Type"), header("Target"));
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(_) => return Ok(error(StatusCode::BAD_REQUEST, "Unreadable request body")),
+    };
+
+    let mut vault = lock(&vault);
+    vault.requests.push(RecordedRequest { method: method.to_string(), path: path.clone() });
+    if let Some(key) = &vault.api_key {
+        if authorization.as_deref() != Some(format!("Bearer {}", key).as_str()) {
+            return Ok(error(StatusCode::UNAUTHORIZED, "Authorization required"));
+        }
+    }
+    let files = &mut vault.files;
+    let response = match method {
+        Method::GET if path.is_empty() || path.ends_with('/') => match list_folder(files, &path) {
+            Some(names) => respond(StatusCode::OK, "application/json", serde_json::json!({ "files": names }).to_string()),
+            None => error(StatusCode::NOT_FOUND, "Folder does not exist"),
+        },
+        Method::GET => match files.get(&path) {
+            Some(content) => respond(StatusCode::OK, "text/markdown", content.clone()),
+            None => error(StatusCode::NOT_FOUND, "File does not exist"),
+        },
+        Method::PUT => {
+            files.insert(path, body);
+            empty(StatusCode::NO_CONTENT)
+        }
+        // POST appends to an existing file and creates a missing one
+        Method::POST => {
+            files.entry(path).or_default().push_str(&body);
+            empty(StatusCode::NO_CONTENT)
+        }
+        Method::DELETE => match files.remove(&path) {
+            Some(_) => empty(StatusCode::NO_CONTENT),
+            None => error(StatusCode::NOT_FOUND, "File does not exist"),
+        },
+        Method::PATCH => match (target_type.as_deref(), operation.as_deref(), files.get_mut(&path)) {
+            (_, _, None) => error(StatusCode::NOT_FOUND, "File does not exist"),
+            (Some("frontmatter"), Some("replace"), Some(content)) => {
+                let field = urlencoding::decode(target.as_deref().unwrap_or_default()).map(|f| f.into_owned()).unwrap_or_default();
+                match replace_frontmatter_field(content, &field, &body) {
+                    Ok(updated) => {
+                        *content = updated;
+                        empty(StatusCode::OK)
+                    }
+                    Err(e) => error(StatusCode::BAD_REQUEST, &e.to_string()),
+                }
+            }
+            _ => error(StatusCode::BAD_REQUEST, "The mock vault only patches frontmatter fields with Operation: replace"),
+        },
+        _ => error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+    };
+    Ok(response)
+}
+
+/// Names in `folder` (empty for the vault root), subfolders ending in `/`; `None` when there
+/// is no such folder
+fn list_folder(files: &BTreeMap<String, String>, folder: &str) -> Option<Vec<String>> {
+    let names: BTreeSet<String> = files.keys()
+        .filter_map(|path| path.strip_prefix(folder))
+        .map(|rest| match rest.split_once('/') {
+            Some((subfolder, _)) => format!("{}/", subfolder),
+            None => rest.to_string(),
+        })
+        .collect();
+    (folder.is_empty() || !names.is_empty()).then(|| names.into_iter().collect())
+}
+
+/// Set one frontmatter field to a JSON value, adding the frontmatter block if there is none
+fn replace_frontmatter_field(content: &str, field: &str, value: &str) -> Result<String> {
+    let value: serde_json::Value = serde_json::from_str(value).context("Frontmatter value is not JSON")?;
+    let (yaml, body) = match content.strip_prefix("---\n").and_then(|rest| rest.split_once("\n---")) {
+        Some((yaml, body)) => (yaml, body.strip_prefix('\n').unwrap_or(body)),
+        None => ("", content),
+    };
+    let mut fields: serde_yaml::Mapping = if yaml.trim().is_empty() {
+        serde_yaml::Mapping::new()
+    } else {
+        serde_yaml::from_str(yaml).context("Frontmatter is not valid YAML")?
+    };
+    fields.insert(serde_yaml::Value::String(field.to_string()), serde_yaml::to_value(value)?);
+    Ok(format!("---\n{}---\n{}", serde_yaml::to_string(&fields)?, body))
+}
@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, LocalResult, NaiveDate, NaiveDateTime, Offset, TimeZone as _, Utc};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// A zone from the IANA tz database compiled into arrowhead, so named zones work the same
+/// on every platform
+#[derive(Debug, Clone)]
+pub struct TimeZone {
+    name: String,
+    tz: Tz,
+}
+
+/// The zone named in a setting: an IANA name such as `America/Los_Angeles`, or "local" (or
+/// empty) for the system's zone. Zones are looked up once per process. An unknown name is an
+/// error rather than UTC, which would put working hours at the wrong time of day.
+pub fn resolve(name: &str) -> Result<Arc<TimeZone>> {
+    static ZONES: OnceLock<Mutex<HashMap<String, Arc<TimeZone>>>> = OnceLock::new();
+    let mut zones = ZONES.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(zone) = zones.get(name) {
+        return Ok(zone.clone());
+    }
+    let zone = match name.trim() {
+        "" | "local" => TimeZone::local(),
+        name => TimeZone::named(name),
+    };
+    let zone = Arc::new(zone.context("Check [calendar] time_zone")?);
+    zones.insert(name.to_string(), zone.clone());
+    Ok(zone)
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        Self { name: "UTC".to_string(), tz: Tz::UTC }
+    }
+
+    /// A zone from the tz database, e.g. `Europe/Berlin`
+    pub fn named(name: &str) -> Result<Self> {
+        let tz: Tz = name.parse()
+            .map_err(|_| anyhow!("Unknown time zone '{}'; use an IANA name such as America/Los_Angeles", name))?;
+        Ok(Self { name: tz.name().to_string(), tz })
+    }
+
+    /// The system's zone: `TZ` if set, otherwise the zone the operating system is set to
+    pub fn local() -> Result<Self> {
+        if let Ok(tz) = std::env::var("TZ") {
+            let tz = tz.trim_start_matches(':');
+            if !tz.is_empty() {
+                return Self::named(tz).context("TZ is not an IANA zone name");
+            }
+        }
+        let name = iana_time_zone::get_timezone().context("Could not find the system time zone")?;
+        Self::named(&name)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Seconds east of UTC in effect at `at`
+    pub fn offset_seconds(&self, at: DateTime<Utc>) -> i32 {
+        self.tz.offset_from_utc_datetime(&at.naive_utc()).fix().local_minus_utc()
+    }
+
+    /// Wall-clock time in this zone
+    pub fn to_local(&self, at: DateTime<Utc>) -> NaiveDateTime {
+        at.with_timezone(&self.tz).naive_local()
+    }
+
+    /// The instant a wall-clock time happens. A time repeated when clocks go back is its
+    /// first occurrence; one skipped when they go forward is moved forward by the gap, so
+    /// 2:30 on a spring-forward night is 3:30.
+    pub fn from_local(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        match self.tz.from_local_datetime(&local) {
+            LocalResult::Single(at) | LocalResult::Ambiguous(at, _) => at.with_timezone(&Utc),
+            LocalResult::None => {
+                // Read on the offset from before the gap, which lands as far past it
+                let as_utc = local.and_utc();
+                as_utc - Duration::seconds(self.offset_seconds(as_utc - Duration::days(1)) as i64)
+            }
+        }
+    }
+
+    /// The instant a day's wall clock shows `hour:minute`; hour 24 is the next midnight
+    pub fn at(&self, day: NaiveDate, hour: u32, minute: u32) -> Option<DateTime<Utc>> {
+        match hour {
+            24 => day.succ_opt().and_then(|next| next.and_hms_opt(0, minute, 0)).map(|t| self.from_local(t)),
+            hour => day.and_hms_opt(hour, minute, 0).map(|t| self.from_local(t)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn los_angeles() -> TimeZone {
+        TimeZone::named("America/Los_Angeles").unwrap()
+    }
+
+    fn local(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_named_zones_follow_daylight_time() {
+        let zone = los_angeles();
+        let at = |s: &str| local(s).and_utc();
+        assert_eq!(zone.offset_seconds(at("2024-01-15 12:00")), -8 * 3600);
+        assert_eq!(zone.offset_seconds(at("2024-07-15 12:00")), -7 * 3600);
+        // 2024-03-10 02:00 PST and 2024-11-03 02:00 PDT
+        assert_eq!(zone.offset_seconds(at("2024-03-10 09:59")), -8 * 3600);
+        assert_eq!(zone.offset_seconds(at("2024-03-10 10:00")), -7 * 3600);
+        assert_eq!(zone.offset_seconds(at("2024-11-03 08:59")), -7 * 3600);
+        assert_eq!(zone.offset_seconds(at("2024-11-03 09:00")), -8 * 3600);
+
+        let sydney = TimeZone::named("Australia/Sydney").unwrap();
+        assert_eq!(sydney.offset_seconds(at("2024-01-15 00:00")), 11 * 3600);
+        assert_eq!(sydney.offset_seconds(at("2024-06-15 00:00")), 10 * 3600);
+        let kolkata = TimeZone::named("Asia/Kolkata").unwrap();
+        assert_eq!(kolkata.to_local(at("2024-06-15 00:00")), local("2024-06-15 05:30"));
+    }
+
+    #[test]
+    fn test_local_times_around_transitions() {
+        let zone = los_angeles();
+        assert_eq!(zone.from_local(local("2024-03-11 09:00")).to_rfc3339(), "2024-03-11T16:00:00+00:00");
+        assert_eq!(zone.from_local(local("2024-03-08 09:00")).to_rfc3339(), "2024-03-08T17:00:00+00:00");
+        // Skipped: 2:30 doesn't exist on the spring-forward night
+        assert_eq!(zone.to_local(zone.from_local(local("2024-03-10 02:30"))), local("2024-03-10 03:30"));
+        // Repeated: the first 1:30 on the fall-back night, still daylight time
+        assert_eq!(zone.from_local(local("2024-11-03 01:30")).to_rfc3339(), "2024-11-03T08:30:00+00:00");
+        assert_eq!(zone.at(NaiveDate::from_ymd_opt(2024, 11, 4).unwrap(), 24, 0).unwrap().to_rfc3339(), "2024-11-05T08:00:00+00:00");
+    }
+
+    #[test]
+    fn test_unknown_zones_are_errors() {
+        assert!(TimeZone::named("Not/AZone").is_err());
+        assert!(TimeZone::named("../etc/passwd").is_err());
+        let err = resolve("America/Los_Angles").unwrap_err();
+        assert!(format!("{:#}", err).contains("Unknown time zone 'America/Los_Angles'"), "{:#}", err);
+        assert_eq!(resolve("UTC").unwrap().name(), "UTC");
+        assert_eq!(resolve("Europe/Berlin").unwrap().name(), "Europe/Berlin");
+    }
+}
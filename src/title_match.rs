@@ -0,0 +1,344 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::fmt::Write as _;
+
+use crate::command_suggest::edit_distance;
+use crate::confirm::PromptReader;
+
+/// Most candidates shown when a title is ambiguous or not found
+const MAX_LISTED: usize = 9;
+
+/// A note that a free-text title might refer to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TitleCandidate {
+    /// Vault path, e.g. `Todos/prepare-project-review.md`
+    pub path: String,
+    pub title: String,
+    pub tags: Vec<String>,
+}
+
+/// A candidate and how well it matched, from 0.0 to 1.0
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoredMatch {
+    #[serde(flatten)]
+    pub candidate: TitleCandidate,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// A single clear winner
+    Found(ScoredMatch),
+    /// Several candidates scored too closely to pick one, best first
+    Ambiguous(Vec<ScoredMatch>),
+    /// Nothing scored above the threshold; the nearest misses, best first
+    NotFound(Vec<ScoredMatch>),
+}
+
+/// A title that couldn't be narrowed to one note. Carried inside `anyhow::Error` so callers
+/// such as the chat bridge can downcast it and offer the candidates.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum TitleMatchError {
+    #[error("\"{query}\" matches more than one {kind}:{}", numbered(candidates))]
+    Ambiguous { kind: String, query: String, candidates: Vec<ScoredMatch> },
+    #[error("No {kind} matches \"{query}\"{}", suggestions_hint(suggestions))]
+    NoMatch { kind: String, query: String, suggestions: Vec<ScoredMatch> },
+}
+
+fn numbered(candidates: &[ScoredMatch]) -> String {
+    let mut list = String::new();
+    for (i, found) in candidates.iter().enumerate() {
+        let _ = write!(list, "\n  {}. {} ({})", i + 1, found.candidate.title, found.candidate.path);
+    }
+    list
+}
+
+fn suggestions_hint(suggestions: &[ScoredMatch]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!("; did you mean:{}", numbered(suggestions))
+    }
+}
+
+/// Lowercased words, split on anything that isn't a letter or digit in any script
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Jaro-Winkler similarity over chars, so accented and non-Latin titles compare letter by letter
+pub fn jaro_winkler(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut b_matched = vec![false; b.len()];
+    let mut a_matches = Vec::new();
+    for (i, ca) in a.iter().enumerate() {
+        let from = i.saturating_sub(window);
+        let to = (i + window + 1).min(b.len());
+        if let Some(j) = (from..to).find(|&j| !b_matched[j] && b[j] == *ca) {
+            b_matched[j] = true;
+            a_matches.push(*ca);
+        }
+    }
+    if a_matches.is_empty() {
+        return 0.0;
+    }
+    let b_matches = b.iter().zip(&b_matched).filter(|(_, matched)| **matched).map(|(c, _)| *c);
+    let transpositions = a_matches.iter().zip(b_matches).filter(|(x, y)| **x != *y).count() / 2;
+
+    let m = a_matches.len() as f64;
+    let jaro = (m / a.len() as f64 + m / b.len() as f64 + (m - transpositions as f64) / m) / 3.0;
+    let prefix = a.iter().zip(&b).take(4).take_while(|(x, y)| x == y).count() as f64;
+    jaro + prefix * 0.1 * (1.0 - jaro)
+}
+
+/// Edit distance scaled to 0.0..=1.0 by the longer string's length
+fn levenshtein_similarity(a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(a, b) as f64 / longest as f64
+}
+
+/// Scores free-text titles against candidates and decides whether one clearly wins
+#[derive(Debug, Clone)]
+pub struct TitleResolver {
+    /// Lowest score a candidate may be auto-selected with
+    pub threshold: f64,
+    /// How far ahead of the runner-up the best candidate has to be
+    pub margin: f64,
+}
+
+impl Default for TitleResolver {
+    fn default() -> Self {
+        Self { threshold: 0.75, margin: 0.1 }
+    }
+}
+
+/// A query word counts towards a title word when they're at least this similar
+const WORD_MATCH: f64 = 0.85;
+/// Weight of a query word that names one of the candidate's tags rather than a title word
+const TAG_MATCH: f64 = 0.9;
+/// Words a request wraps around a title ("the project review task"), ignored unless the title uses them
+const FILLER_WORDS: &[&str] = &["the", "a", "an", "my", "task", "todo", "goal", "item"];
+
+impl TitleResolver {
+    /// How well `query` describes `candidate`, from 0.0 to 1.0. The title (or the file name,
+    /// whichever is closer) is compared as a whole and word by word; query words that match
+    /// one of the candidate's tags count too, so "work report" finds a `#work` "write report".
+    pub fn score(&self, query: &str, candidate: &TitleCandidate) -> f64 {
+        let query_words = words(query);
+        if query_words.is_empty() {
+            return 0.0;
+        }
+        let stem = candidate.path.rsplit('/').next().unwrap_or(&candidate.path).trim_end_matches(".md");
+        let tags: Vec<String> = candidate.tags.iter().map(|tag| tag.trim_start_matches('#').to_lowercase()).collect();
+        [candidate.title.as_str(), stem]
+            .iter()
+            .map(|name| Self::name_score(&query_words, &words(name), &tags))
+            .fold(0.0, f64::max)
+    }
+
+    fn name_score(query: &[String], name: &[String], tags: &[String]) -> f64 {
+        if name.is_empty() {
+            return 0.0;
+        }
+        let whole = levenshtein_similarity(&query.join(" "), &name.join(" "));
+
+        let mut name_used = vec![false; name.len()];
+        let mut covered = 0.0;
+        let mut counted = 0;
+        for word in query {
+            let best = name.iter().enumerate()
+                .map(|(i, candidate)| (i, jaro_winkler(word, candidate)))
+                .filter(|(_, similarity)| *similarity >= WORD_MATCH)
+                .max_by(|x, y| x.1.total_cmp(&y.1));
+            let tagged = if tags.contains(word) { TAG_MATCH } else { 0.0 };
+            match best {
+                Some((i, similarity)) if similarity >= tagged => {
+                    name_used[i] = true;
+                    covered += similarity;
+                }
+                None if tagged == 0.0 && FILLER_WORDS.contains(&word.as_str()) => continue,
+                _ => covered += tagged,
+            }
+            counted += 1;
+        }
+        if counted == 0 {
+            return whole;
+        }
+        let coverage = covered / counted as f64;
+        // Titles with words the query didn't mention score a little lower, so "project review"
+        // ranks "Project review" above "Project review slides"
+        let name_coverage = name_used.iter().filter(|used| **used).count() as f64 / name.len() as f64;
+        whole.max(coverage * (0.8 + 0.2 * name_coverage))
+    }
+
+    /// Pick the candidate `query` refers to, if one clearly does
+    pub fn resolve(&self, query: &str, candidates: &[TitleCandidate]) -> Resolution {
+        let mut scored: Vec<ScoredMatch> = candidates.iter()
+            .map(|candidate| ScoredMatch { candidate: candidate.clone(), score: self.score(query, candidate) })
+            .filter(|found| found.score > 0.0)
+            .collect();
+        scored.sort_by(|x, y| y.score.total_cmp(&x.score).then_with(|| x.candidate.path.cmp(&y.candidate.path)));
+
+        let Some(best) = scored.first().map(|found| found.score) else {
+            return Resolution::NotFound(Vec::new());
+        };
+        if best < self.threshold {
+            scored.retain(|found| found.score >= self.threshold - 0.25);
+            scored.truncate(3);
+            return Resolution::NotFound(scored);
+        }
+        // A title typed exactly wins outright, however close the longer titles containing it come
+        let margin = if best >= 1.0 { f64::EPSILON } else { self.margin };
+        let mut close: Vec<ScoredMatch> = scored.into_iter().filter(|found| best - found.score < margin).collect();
+        if close.len() == 1 {
+            return Resolution::Found(close.remove(0));
+        }
+        close.truncate(MAX_LISTED);
+        Resolution::Ambiguous(close)
+    }
+
+    /// Resolve `query` to one candidate, asking which one is meant when several fit and
+    /// there's a terminal to ask on. `kind` names the candidates in messages, e.g. "todo".
+    pub fn pick(
+        &self,
+        kind: &str,
+        query: &str,
+        candidates: &[TitleCandidate],
+        reader: &mut dyn PromptReader,
+    ) -> Result<TitleCandidate> {
+        let candidates = match self.resolve(query, candidates) {
+            Resolution::Found(found) => return Ok(found.candidate),
+            Resolution::NotFound(suggestions) => {
+                return Err(TitleMatchError::NoMatch { kind: kind.to_string(), query: query.to_string(), suggestions }.into());
+            }
+            Resolution::Ambiguous(candidates) => candidates,
+        };
+        if !reader.is_interactive() {
+            return Err(TitleMatchError::Ambiguous { kind: kind.to_string(), query: query.to_string(), candidates }.into());
+        }
+
+        println!("\"{}\" matches more than one {}:{}", query, kind, numbered(&candidates));
+        let answer = reader.read_answer(&format!("Which one? [1-{}, Enter to cancel]", candidates.len()))?;
+        match answer.trim().parse::<usize>() {
+            Ok(choice) if (1..=candidates.len()).contains(&choice) => Ok(candidates[choice - 1].candidate.clone()),
+            _ => bail!("No {} selected", kind),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ScriptedPrompt;
+
+    fn candidate(path: &str, title: &str, tags: &[&str]) -> TitleCandidate {
+        TitleCandidate { path: path.to_string(), title: title.to_string(), tags: tags.iter().map(|t| t.to_string()).collect() }
+    }
+
+    fn todos() -> Vec<TitleCandidate> {
+        vec![
+            candidate("Todos/prepare-project-review.md", "Prepare project review", &["work"]),
+            candidate("Todos/project-kickoff.md", "Project kickoff", &["work"]),
+            candidate("Todos/buy-milk.md", "Buy milk", &["errand"]),
+            candidate("Todos/write-report.md", "Write report", &["work"]),
+            candidate("Todos/write-report-draft.md", "Write report", &["personal"]),
+        ]
+    }
+
+    #[test]
+    fn test_clear_winner_is_selected_despite_typos() {
+        let resolver = TitleResolver::default();
+        for query in ["project review", "the projcet reveiw", "Prepare Project Review", "prepare-project-review"] {
+            match resolver.resolve(query, &todos()) {
+                Resolution::Found(found) => assert_eq!(found.candidate.path, "Todos/prepare-project-review.md", "{}", query),
+                other => panic!("{}: {:?}", query, other),
+            }
+        }
+        let mut with_slides = todos();
+        with_slides.push(candidate("Todos/project-review-slides.md", "Project review slides", &[]));
+        match resolver.resolve("prepare project review", &with_slides) {
+            Resolution::Found(found) => assert_eq!(found.candidate.path, "Todos/prepare-project-review.md"),
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(resolver.resolve("project review", &with_slides), Resolution::Ambiguous(_)));
+        assert!(jaro_winkler("martha", "marhta") > 0.95);
+        assert_eq!(jaro_winkler("milk", "milk"), 1.0);
+    }
+
+    #[test]
+    fn test_ties_are_ambiguous_unless_tags_break_them() {
+        let resolver = TitleResolver::default();
+        let Resolution::Ambiguous(tied) = resolver.resolve("write report", &todos()) else {
+            panic!("identical titles should tie");
+        };
+        assert_eq!(tied.len(), 2);
+        assert_eq!(tied[0].score, tied[1].score);
+
+        // A query word naming a tag picks the tagged one
+        match resolver.resolve("work write report", &todos()) {
+            Resolution::Found(found) => assert_eq!(found.candidate.path, "Todos/write-report.md"),
+            other => panic!("{:?}", other),
+        }
+
+        // Non-interactive callers get the candidates back in a structured error
+        let err = resolver.pick("todo", "write report", &todos(), &mut ScriptedPrompt::piped()).unwrap_err();
+        match err.downcast_ref::<TitleMatchError>() {
+            Some(TitleMatchError::Ambiguous { candidates, .. }) => assert_eq!(candidates.len(), 2),
+            other => panic!("{:?}", other),
+        }
+        assert!(err.to_string().contains("1. Write report (Todos/write-report-draft.md)"), "{}", err);
+
+        // On a terminal the user picks by number, and anything else cancels
+        let picked = resolver.pick("todo", "write report", &todos(), &mut ScriptedPrompt::tty(&["2"])).unwrap();
+        assert_eq!(picked.path, "Todos/write-report.md");
+        assert!(resolver.pick("todo", "write report", &todos(), &mut ScriptedPrompt::tty(&[""])).is_err());
+    }
+
+    #[test]
+    fn test_unicode_titles() {
+        let resolver = TitleResolver::default();
+        let candidates = vec![
+            candidate("Todos/cafe-besuch.md", "Café-Besuch mit Jürgen", &[]),
+            candidate("Todos/nihongo.md", "日本語の勉強", &["学習"]),
+            candidate("Todos/resume.md", "Update résumé", &[]),
+        ];
+        for (query, path) in [
+            ("cafe besuch mit jurgen", "Todos/cafe-besuch.md"),
+            ("CAFÉ-BESUCH", "Todos/cafe-besuch.md"),
+            ("日本語の勉強", "Todos/nihongo.md"),
+            ("update resume", "Todos/resume.md"),
+        ] {
+            match resolver.resolve(query, &candidates) {
+                Resolution::Found(found) => assert_eq!(found.candidate.path, path, "{}", query),
+                other => panic!("{}: {:?}", query, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_match_reports_near_misses() {
+        let resolver = TitleResolver::default();
+        assert_eq!(resolver.resolve("renew passport", &todos()), Resolution::NotFound(Vec::new()));
+        assert_eq!(resolver.resolve("   ", &todos()), Resolution::NotFound(Vec::new()));
+        assert_eq!(resolver.resolve("buy milk", &[]), Resolution::NotFound(Vec::new()));
+
+        let err = resolver.pick("todo", "renew passport", &todos(), &mut ScriptedPrompt::tty(&["1"])).unwrap_err();
+        assert_eq!(err.to_string(), "No todo matches \"renew passport\"");
+        assert!(matches!(err.downcast_ref::<TitleMatchError>(), Some(TitleMatchError::NoMatch { .. })));
+    }
+}
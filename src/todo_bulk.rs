@@ -0,0 +1,815 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, Local, NaiveDate, Utc};
+use std::fmt;
+use std::ops::Range;
+use uuid::Uuid;
+
+use crate::ai_conversation::{create_llm_client, LLMClient, Message, MessageRole};
+use crate::config::Config;
+use crate::dates::{self, first_of_month, month_end, week_start, DATE_FORMATS};
+use crate::confirm::{confirm, Operation, OperationClass};
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::todo_model::{set_frontmatter_entry, tags_entry, Priority, TodoItem, TodoStore, TODOS_DIR};
+use crate::usage::UsagePurpose;
+use crate::vault_transaction::{VaultTransaction};
+
+/// Longest date phrase a selector may use ("end of next month")
+const MAX_DATE_WORDS: usize = 4;
+
+/// Words that carry no meaning in a selector ("everything", "all open todos that are ...")
+const NOISE_WORDS: &[&str] = &[
+    "everything", "all", "every", "todos", "todo", "tasks", "task", "items", "item", "that", "which",
+    "are", "is", "with", "and", "the", "open", "of", "my", "reschedule", "move", "push", "shift", "bump", "set",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DueFilter {
+    On(NaiveDate),
+    /// Inclusive on both ends
+    Between(NaiveDate, NaiveDate),
+    Before(NaiveDate),
+    After(NaiveDate),
+    Overdue,
+    Undated,
+}
+
+/// One filter of a selector; a todo must satisfy every clause
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clause {
+    Due(DueFilter),
+    Tag(String),
+    Priority(Priority),
+    Folder(String),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BulkAction {
+    SetDue(NaiveDate),
+    /// Move the due date by this many days (negative is earlier)
+    ShiftDue(i64),
+    SetPriority(Priority),
+    AddTag(String),
+    RemoveTag(String),
+}
+
+/// A parsed `todo reschedule` command. Relative dates are resolved at parse time, so the
+/// `Display` form is the exact structured selector shown for confirmation and parses back
+/// to the same value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkEdit {
+    pub clauses: Vec<Clause>,
+    pub action: BulkAction,
+}
+
+fn quote_if_needed(text: &str) -> String {
+    if text.contains(char::is_whitespace) || text.is_empty() {
+        format!("\"{}\"", text)
+    } else {
+        text.to_string()
+    }
+}
+
+impl fmt::Display for Clause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Clause::Due(DueFilter::On(date)) => write!(f, "due on {}", date),
+            Clause::Due(DueFilter::Between(from, to)) => write!(f, "due between {} and {}", from, to),
+            Clause::Due(DueFilter::Before(date)) => write!(f, "due before {}", date),
+            Clause::Due(DueFilter::After(date)) => write!(f, "due after {}", date),
+            Clause::Due(DueFilter::Overdue) => f.write_str("overdue"),
+            Clause::Due(DueFilter::Undated) => f.write_str("undated"),
+            Clause::Tag(tag) => write!(f, "tagged #{}", tag),
+            Clause::Priority(priority) => write!(f, "priority {}", priority),
+            Clause::Folder(folder) => write!(f, "in {}", quote_if_needed(folder)),
+            Clause::Text(text) => write!(f, "matching {}", quote_if_needed(text)),
+        }
+    }
+}
+
+impl fmt::Display for BulkAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BulkAction::SetDue(date) => write!(f, "to {}", date),
+            BulkAction::ShiftDue(days) => write!(f, "by {:+} days", days),
+            BulkAction::SetPriority(priority) => write!(f, "to priority {}", priority),
+            BulkAction::AddTag(tag) => write!(f, "add #{}", tag),
+            BulkAction::RemoveTag(tag) => write!(f, "remove #{}", tag),
+        }
+    }
+}
+
+impl fmt::Display for BulkEdit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.clauses.is_empty() {
+            f.write_str("everything")?;
+        } else {
+            let clauses: Vec<String> = self.clauses.iter().map(Clause::to_string).collect();
+            f.write_str(&clauses.join(" "))?;
+        }
+        write!(f, " {}", self.action)
+    }
+}
+
+/// A selector or action the parser could not understand, with the byte span of the
+/// offending fragment so it can be pointed at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorError {
+    pub input: String,
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pad = self.input[..self.span.start].chars().count();
+        let width = self.input[self.span.clone()].chars().count().max(1);
+        writeln!(f, "{}", self.message)?;
+        writeln!(f, "  {}", self.input)?;
+        write!(f, "  {}{}", " ".repeat(pad), "^".repeat(width))
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+#[derive(Debug, Clone)]
+struct Token {
+    /// Lowercased word, or the verbatim contents of a quoted phrase
+    text: String,
+    quoted: bool,
+    span: Range<usize>,
+}
+
+fn tokenize(input: &str) -> std::result::Result<Vec<Token>, SelectorError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() || c == ',' {
+            chars.next();
+            continue;
+        }
+        if c == '"' {
+            chars.next();
+            let mut end = None;
+            for (i, c) in chars.by_ref() {
+                if c == '"' {
+                    end = Some(i);
+                    break;
+                }
+            }
+            let Some(end) = end else {
+                return Err(SelectorError {
+                    input: input.to_string(),
+                    span: start..input.len(),
+                    message: "unclosed quote".to_string(),
+                });
+            };
+            tokens.push(Token { text: input[start + 1..end].to_string(), quoted: true, span: start..end + 1 });
+            continue;
+        }
+        let mut end = input.len();
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_whitespace() || c == ',' || c == '"' {
+                end = i;
+                break;
+            }
+            chars.next();
+        }
+        tokens.push(Token { text: input[start..end].to_lowercase(), quoted: false, span: start..end });
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    today: NaiveDate,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_word(&self, offset: usize) -> Option<&str> {
+        self.tokens.get(self.pos + offset).filter(|t| !t.quoted).map(|t| t.text.as_str())
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error_at(&self, span: Range<usize>, message: impl Into<String>) -> SelectorError {
+        SelectorError { input: self.input.to_string(), span, message: message.into() }
+    }
+
+    /// Error at the current token, or just past the end of the input when it ran out
+    fn error_here(&self, message: impl Into<String>) -> SelectorError {
+        let span = match self.peek() {
+            Some(token) => token.span.clone(),
+            None => self.input.len()..self.input.len(),
+        };
+        self.error_at(span, message)
+    }
+
+    /// A date as `dates::parse_date` reads it, over as many of the next words as it takes
+    /// ("friday", "end of next month"), or `in` and a duration such as `in 3d`
+    fn date(&mut self) -> std::result::Result<NaiveDate, SelectorError> {
+        let words: Vec<&str> = self.tokens[self.pos.min(self.tokens.len())..].iter()
+            .take_while(|t| !t.quoted)
+            .take(MAX_DATE_WORDS)
+            .map(|t| t.text.as_str())
+            .collect();
+        let found = (1..=words.len()).rev()
+            .find_map(|count| dates::parse_date(&words[..count].join(" "), self.today).ok().map(|date| (count, date)));
+        if let Some((count, date)) = found {
+            self.pos += count;
+            return Ok(date);
+        }
+        if self.peek_word(0) == Some("in") {
+            self.pos += 1;
+            return Ok(self.today + Duration::days(self.duration_days()?));
+        }
+        Err(self.error_here(format!("expected a date: {}", DATE_FORMATS)))
+    }
+
+    /// A signed number of days: `3d`, `+1w`, `-2 days`, `1 week`
+    fn duration_days(&mut self) -> std::result::Result<i64, SelectorError> {
+        let expected = "expected a duration such as 3 days, +1w or -2d";
+        let Some(token) = self.peek().filter(|t| !t.quoted).cloned() else {
+            return Err(self.error_here(expected));
+        };
+        let split = token.text.find(|c: char| c.is_alphabetic()).unwrap_or(token.text.len());
+        let (number, unit) = token.text.split_at(split);
+        let Ok(count) = number.parse::<i64>() else {
+            return Err(self.error_here(expected));
+        };
+        // The unit is either glued on (`3d`) or the next word (`3 days`)
+        let unit = if unit.is_empty() {
+            self.pos += 1;
+            self.peek_word(0).unwrap_or_default().to_string()
+        } else {
+            unit.to_string()
+        };
+        let days = match unit.as_str() {
+            "d" | "day" | "days" => count,
+            "w" | "week" | "weeks" => count * 7,
+            _ if split < token.text.len() => return Err(self.error_at(token.span, expected)),
+            _ => return Err(self.error_here(expected)),
+        };
+        self.pos += 1;
+        Ok(days)
+    }
+
+    /// What follows `due`
+    fn due_filter(&mut self) -> std::result::Result<DueFilter, SelectorError> {
+        let filter = match self.peek_word(0) {
+            Some("before") => {
+                self.pos += 1;
+                DueFilter::Before(self.date()?)
+            }
+            Some("after") => {
+                self.pos += 1;
+                DueFilter::After(self.date()?)
+            }
+            Some("on") => {
+                self.pos += 1;
+                DueFilter::On(self.date()?)
+            }
+            Some("between") => {
+                self.pos += 1;
+                let from = self.date()?;
+                if self.peek_word(0) != Some("and") {
+                    return Err(self.error_here("expected `and` in `due between <date> and <date>`"));
+                }
+                self.pos += 1;
+                DueFilter::Between(from, self.date()?)
+            }
+            Some("this") if self.peek_word(1) == Some("week") => {
+                self.pos += 2;
+                let start = week_start(self.today);
+                DueFilter::Between(start, start + Duration::days(6))
+            }
+            Some("next") if self.peek_word(1) == Some("week") => {
+                self.pos += 2;
+                let start = week_start(self.today) + Duration::days(7);
+                DueFilter::Between(start, start + Duration::days(6))
+            }
+            Some("this") if self.peek_word(1) == Some("month") => {
+                self.pos += 2;
+                let start = first_of_month(self.today);
+                DueFilter::Between(start, month_end(self.today))
+            }
+            _ => DueFilter::On(self.date()?),
+        };
+        Ok(filter)
+    }
+
+    fn tag(&mut self) -> std::result::Result<String, SelectorError> {
+        match self.next() {
+            Some(token) if !token.quoted && !token.text.trim_start_matches('#').is_empty() => {
+                Ok(token.text.trim_start_matches('#').to_string())
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.error_here("expected a tag"))
+            }
+        }
+    }
+
+    fn clause(&mut self) -> std::result::Result<Option<Clause>, SelectorError> {
+        let token = self.peek().cloned().expect("called with tokens left");
+        if token.quoted {
+            self.pos += 1;
+            return Ok(Some(Clause::Text(token.text)));
+        }
+        let word = token.text.as_str();
+        if NOISE_WORDS.contains(&word) {
+            self.pos += 1;
+            return Ok(None);
+        }
+        if let Some(tag) = word.strip_prefix('#') {
+            self.pos += 1;
+            return Ok(Some(Clause::Tag(tag.to_string())));
+        }
+        if let Some(priority) = Priority::parse(word) {
+            if self.peek_word(1) == Some("priority") {
+                self.pos += 2;
+                return Ok(Some(Clause::Priority(priority)));
+            }
+        }
+
+        self.pos += 1;
+        let clause = match word {
+            "due" => Clause::Due(self.due_filter()?),
+            "overdue" => Clause::Due(DueFilter::Overdue),
+            "undated" => Clause::Due(DueFilter::Undated),
+            "without" | "no" if self.peek_word(0) == Some("due") => {
+                self.pos += 1;
+                if self.peek_word(0) == Some("date") {
+                    self.pos += 1;
+                }
+                Clause::Due(DueFilter::Undated)
+            }
+            "tagged" => Clause::Tag(self.tag()?),
+            "priority" => match self.peek_word(0).and_then(Priority::parse) {
+                Some(priority) => {
+                    self.pos += 1;
+                    Clause::Priority(priority)
+                }
+                None => return Err(self.error_here("expected high, medium or low")),
+            },
+            "in" => {
+                if self.peek_word(0) == Some("folder") {
+                    self.pos += 1;
+                }
+                match self.next() {
+                    Some(folder) => {
+                        let raw = if folder.quoted { folder.text } else { self.input[folder.span].to_string() };
+                        Clause::Folder(raw.trim_matches('/').to_string())
+                    }
+                    None => return Err(self.error_here("expected a folder")),
+                }
+            }
+            "matching" | "containing" | "mentioning" => match self.next() {
+                Some(text) => Clause::Text(text.text),
+                None => return Err(self.error_here("expected text to match")),
+            },
+            _ => {
+                return Err(self.error_at(
+                    token.span,
+                    "unknown filter; expected due ..., overdue, undated, tagged #tag, priority <level>, in <folder> or matching \"text\"",
+                ))
+            }
+        };
+        Ok(Some(clause))
+    }
+
+    fn action(&mut self) -> std::result::Result<BulkAction, SelectorError> {
+        let word = self.peek_word(0).map(str::to_string);
+        let action = match word.as_deref() {
+            Some("to") => {
+                self.pos += 1;
+                match (self.peek_word(0), self.peek_word(1)) {
+                    (Some("priority"), _) => match self.peek_word(1).and_then(Priority::parse) {
+                        Some(priority) => {
+                            self.pos += 2;
+                            BulkAction::SetPriority(priority)
+                        }
+                        None => {
+                            self.pos += 1;
+                            return Err(self.error_here("expected high, medium or low"));
+                        }
+                    },
+                    (Some(level), Some("priority")) if Priority::parse(level).is_some() => {
+                        let priority = Priority::parse(level).expect("checked above");
+                        self.pos += 2;
+                        BulkAction::SetPriority(priority)
+                    }
+                    _ => BulkAction::SetDue(self.date()?),
+                }
+            }
+            Some("by") => {
+                self.pos += 1;
+                BulkAction::ShiftDue(self.duration_days()?)
+            }
+            Some("add") | Some("remove") => {
+                self.pos += 1;
+                if self.peek_word(0) == Some("tag") {
+                    self.pos += 1;
+                }
+                let tag = self.tag()?;
+                if word.as_deref() == Some("add") { BulkAction::AddTag(tag) } else { BulkAction::RemoveTag(tag) }
+            }
+            _ => return Err(self.error_here("expected an action: to <date>, by <duration>, to priority <level>, add #tag or remove #tag")),
+        };
+        Ok(action)
+    }
+}
+
+fn is_action_start(word: &str) -> bool {
+    matches!(word, "to" | "by" | "add" | "remove")
+}
+
+/// Parse `<selector> <action>`, e.g. `everything due this week tagged #errand to saturday`
+pub fn parse_bulk_edit(input: &str, today: NaiveDate) -> std::result::Result<BulkEdit, SelectorError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { input, tokens, pos: 0, today };
+
+    let mut clauses = Vec::new();
+    while let Some(token) = parser.peek() {
+        if !token.quoted && is_action_start(&token.text) {
+            break;
+        }
+        if let Some(clause) = parser.clause()? {
+            clauses.push(clause);
+        }
+    }
+    let action = parser.action()?;
+    if let Some(token) = parser.peek() {
+        let span = token.span.start..input.len();
+        return Err(parser.error_at(span, "unexpected text after the action"));
+    }
+    Ok(BulkEdit { clauses, action })
+}
+
+/// Minutes in an estimate such as `45m`, `2h`, `1h30m`, `1.5h` or a bare number of minutes
+pub fn parse_estimate(text: &str) -> Option<u32> {
+    let text = text.trim().to_lowercase();
+    if let Ok(minutes) = text.parse::<u32>() {
+        return Some(minutes).filter(|m| *m > 0);
+    }
+    let (hours, rest) = match text.split_once('h') {
+        Some((hours, rest)) => (hours.parse::<f32>().ok()?, rest),
+        None => (0.0, text.as_str()),
+    };
+    let minutes = match rest.trim_end_matches("min").trim_end_matches('m') {
+        "" => 0,
+        minutes => minutes.parse::<u32>().ok()?,
+    };
+    let total = (hours * 60.0).round() as u32 + minutes;
+    Some(total).filter(|m| *m > 0)
+}
+
+/// `1h 30m`, `45m`, `2h`
+pub fn format_minutes(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h {}m", h, m),
+    }
+}
+
+impl Clause {
+    fn matches(&self, todo: &TodoItem, today: NaiveDate) -> bool {
+        match self {
+            Clause::Due(DueFilter::Undated) => todo.due_raw.is_none(),
+            Clause::Due(filter) => todo.due.is_some_and(|due| match filter {
+                DueFilter::On(date) => due == *date,
+                DueFilter::Between(from, to) => due >= *from && due <= *to,
+                DueFilter::Before(date) => due < *date,
+                DueFilter::After(date) => due > *date,
+                DueFilter::Overdue => due < today,
+                DueFilter::Undated => unreachable!("handled above"),
+            }),
+            Clause::Tag(tag) => todo.has_tag(tag),
+            Clause::Priority(priority) => todo.priority == Some(*priority),
+            Clause::Folder(folder) => {
+                let folder = folder.to_lowercase();
+                let path = todo.path.to_lowercase();
+                path.starts_with(&format!("{}/", folder)) || path.starts_with(&format!("{}/{}/", TODOS_DIR.to_lowercase(), folder))
+            }
+            Clause::Text(text) => {
+                let text = text.to_lowercase();
+                todo.title.to_lowercase().contains(&text) || todo.content.to_lowercase().contains(&text)
+            }
+        }
+    }
+}
+
+/// The exact edit planned for one todo
+#[derive(Debug, Clone)]
+pub struct PlannedChange {
+    pub path: String,
+    pub description: String,
+    pub before: String,
+    pub after: String,
+    pub new_content: String,
+}
+
+/// Matched todos that can't take the action, e.g. shifting a todo without a due date
+#[derive(Debug, Clone)]
+pub struct SkippedTodo {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BulkPlan {
+    pub changes: Vec<PlannedChange>,
+    pub skipped: Vec<SkippedTodo>,
+}
+
+impl BulkPlan {
+    pub fn preview(&self) -> String {
+        let mut out = String::new();
+        for change in &self.changes {
+            out.push_str(&format!("• {} ({})\n    {} → {}\n", change.path, change.description, change.before, change.after));
+        }
+        for skipped in &self.skipped {
+            out.push_str(&format!("– {} skipped: {}\n", skipped.path, skipped.reason));
+        }
+        out
+    }
+}
+
+fn plan_change(todo: &TodoItem, action: &BulkAction) -> std::result::Result<Option<PlannedChange>, String> {
+    let due_label = |due: Option<&str>| format!("due {}", due.unwrap_or("(none)"));
+    let tags_label = |tags: &[String]| {
+        if tags.is_empty() { "tags (none)".to_string() } else { format!("tags {}", tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ")) }
+    };
+
+    let new_due = match action {
+        BulkAction::SetDue(date) => Some(*date),
+        BulkAction::ShiftDue(days) => match (todo.due, todo.due_raw.as_deref()) {
+            (Some(due), _) => Some(due + Duration::days(*days)),
+            (None, None) => return Err("no due date to shift".to_string()),
+            (None, Some(raw)) => return Err(format!("due date \"{}\" is not YYYY-MM-DD", raw)),
+        },
+        _ => None,
+    };
+
+    let (before, after, new_content) = match action {
+        BulkAction::SetDue(_) | BulkAction::ShiftDue(_) => {
+            let date = new_due.expect("set for due actions");
+            if todo.due == Some(date) {
+                return Ok(None);
+            }
+            let entry = format!("due_date: {}", date);
+            (due_label(todo.due_raw.as_deref()), due_label(Some(&date.to_string())), set_frontmatter_entry(&todo.content, "due_date", Some(&entry)))
+        }
+        BulkAction::SetPriority(priority) => {
+            if todo.priority == Some(*priority) {
+                return Ok(None);
+            }
+            let entry = format!("priority: {}", priority);
+            (
+                format!("priority {}", todo.priority.map_or("(none)".to_string(), |p| p.to_string())),
+                format!("priority {}", priority),
+                set_frontmatter_entry(&todo.content, "priority", Some(&entry)),
+            )
+        }
+        BulkAction::AddTag(tag) | BulkAction::RemoveTag(tag) => {
+            let mut tags = todo.tags.clone();
+            if matches!(action, BulkAction::AddTag(_)) {
+                if todo.has_tag(tag) {
+                    return Ok(None);
+                }
+                tags.push(tag.clone());
+            } else {
+                if !todo.has_tag(tag) {
+                    return Ok(None);
+                }
+                tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+            }
+            let entry = tags_entry(&todo.content, &tags);
+            (tags_label(&todo.tags), tags_label(&tags), set_frontmatter_entry(&todo.content, "tags", entry.as_deref()))
+        }
+    };
+    Ok(Some(PlannedChange { path: todo.path.clone(), description: todo.title.clone(), before, after, new_content }))
+}
+
+/// Select the open todos matching every clause and work out each one's edit
+pub fn plan_bulk_edit(edit: &BulkEdit, todos: &[TodoItem], today: NaiveDate) -> BulkPlan {
+    let mut plan = BulkPlan::default();
+    for todo in todos.iter().filter(|t| t.status.is_open() && edit.clauses.iter().all(|c| c.matches(t, today))) {
+        match plan_change(todo, &edit.action) {
+            Ok(Some(change)) => plan.changes.push(change),
+            Ok(None) => {}
+            Err(reason) => plan.skipped.push(SkippedTodo { path: todo.path.clone(), reason }),
+        }
+    }
+    plan
+}
+
+/// Every todo note under `Todos/`, including subfolders
+/// Ask the LLM to restate free-form phrasing in the selector grammar
+async fn translate_with_llm(llm: &dyn LLMClient, request: &str, today: NaiveDate) -> Result<String> {
+    let prompt = format!(
+        "Rewrite this todo bulk-edit request in the constrained grammar below. Reply with the rewritten command only, on one line.\n\
+         Today is {} ({}).\n\n\
+         Grammar: <filters> <action>\n\
+         Filters (any number, all must hold): due on <date> | due before <date> | due after <date> | \
+         due between <date> and <date> | due this week | due next week | due this month | overdue | undated | \
+         tagged #<tag> | priority high|medium|low | in <folder> | matching \"<text>\" | everything\n\
+         Action (exactly one): to <date> | by <+N|-N> days | to priority high|medium|low | add #<tag> | remove #<tag>\n\
+         Dates: {}.\n\n\
+         Request: {}",
+        today, today.format("%A"), DATE_FORMATS, request
+    );
+    let response = llm.send_message(vec![Message {
+        id: Uuid::new_v4().to_string(),
+        role: MessageRole::User,
+        content: prompt,
+        timestamp: Utc::now(),
+        function_call: None,
+    }]).await?;
+    let line = response.content.lines().map(str::trim).find(|l| !l.is_empty() && !l.starts_with("```"))
+        .context("Empty response from the LLM")?;
+    Ok(line.trim_matches('`').to_string())
+}
+
+pub async fn handle_reschedule_command(
+    request: &str,
+    dry_run: bool,
+    yes: bool,
+    llm_assist: bool,
+    adapter: &ObsidianAdapter,
+) -> Result<()> {
+    let today = Local::now().date_naive();
+    let config = Config::load().unwrap_or_default();
+    let (edit, from_llm) = match parse_bulk_edit(request, today) {
+        Ok(edit) => (edit, false),
+        Err(e) if llm_assist => {
+            let llm = create_llm_client(&config, UsagePurpose::Analysis)?;
+            let translated = translate_with_llm(llm.as_ref(), request, today).await?;
+            let edit = parse_bulk_edit(&translated, today)
+                .map_err(|llm_error| anyhow::anyhow!("{}\n\nThe LLM suggested \"{}\", which did not parse either:\n{}", e, translated, llm_error))?;
+            (edit, true)
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    println!("Interpreted as: {}", edit);
+    // Imported checkbox tasks live inside other notes, which a bulk edit must not rewrite
+    let todos: Vec<TodoItem> = TodoStore::new(adapter).list().await?.into_iter().filter(|todo| todo.source.is_none()).collect();
+    let plan = plan_bulk_edit(&edit, &todos, today);
+    print!("{}", plan.preview());
+    let changed: Vec<&str> = plan.changes.iter().map(|c| c.path.as_str()).collect();
+    let output = |changed: &[&str]| serde_json::json!({ "selector": edit.to_string(), "dry_run": dry_run, "changed": changed });
+    if plan.changes.is_empty() {
+        println!("No todos need changing.");
+        return Ok(());
+    }
+    if dry_run {
+        println!("Dry run: {} todo(s) would change", plan.changes.len());
+        crate::script::publish_output(output(&changed));
+        return Ok(());
+    }
+    // A translated selector is always confirmed, even with --yes
+    let operation = Operation::new(OperationClass::BulkWrite, format!("Apply {} change(s)", plan.changes.len()), plan.changes.len());
+    if !confirm(&config.confirmations, &operation, yes && !from_llm)? {
+        println!("Nothing changed.");
+        return Ok(());
+    }
+
+    let mut transaction = VaultTransaction::new(&format!("todo reschedule: {}", edit))
+        .with_context(serde_json::json!({ "reschedule": edit.to_string() }));
+    for change in &plan.changes {
+        transaction = transaction.write(&change.path, &change.new_content);
+    }
+    let report = transaction.execute(adapter).await?;
+    if let Err(e) = report.append_to_log(adapter.transaction_log()) {
+        log::warn!("could not write transaction log: {}", e);
+    }
+    println!("{}", report.summary());
+    crate::script::publish_output(output(if report.is_committed() { &changed } else { &[] }));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        // A Wednesday
+        NaiveDate::from_ymd_opt(2024, 5, 15).unwrap()
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_parse_selector_and_round_trip() {
+        let edit = parse_bulk_edit("everything due this week tagged #errand to saturday", today()).unwrap();
+        assert_eq!(edit.clauses, vec![
+            Clause::Due(DueFilter::Between(date("2024-05-13"), date("2024-05-19"))),
+            Clause::Tag("errand".to_string()),
+        ]);
+        assert_eq!(edit.action, BulkAction::SetDue(date("2024-05-18")));
+        assert_eq!(parse_bulk_edit(&edit.to_string(), today()).unwrap(), edit);
+
+        let edit = parse_bulk_edit("overdue high priority todos in \"Work Stuff\" by 2 days", today()).unwrap();
+        assert_eq!(edit.clauses, vec![
+            Clause::Due(DueFilter::Overdue),
+            Clause::Priority(Priority::High),
+            Clause::Folder("Work Stuff".to_string()),
+        ]);
+        assert_eq!(edit.action, BulkAction::ShiftDue(2));
+        assert_eq!(parse_bulk_edit(&edit.to_string(), today()).unwrap(), edit);
+
+        let edit = parse_bulk_edit("matching \"dentist\" to priority low", today()).unwrap();
+        assert_eq!(edit.action, BulkAction::SetPriority(Priority::Low));
+        assert_eq!(parse_bulk_edit("undated remove tag errand", today()).unwrap().action, BulkAction::RemoveTag("errand".to_string()));
+    }
+
+    #[test]
+    fn test_selector_dates_read_like_other_dates() {
+        let edit = parse_bulk_edit("due before end of next month tagged #errand to june 7", today()).unwrap();
+        assert_eq!(edit.clauses, vec![Clause::Due(DueFilter::Before(date("2024-06-30"))), Clause::Tag("errand".to_string())]);
+        assert_eq!(edit.action, BulkAction::SetDue(date("2024-06-07")));
+        // A weekday is the coming one, never today
+        assert_eq!(parse_bulk_edit("overdue to wednesday", today()).unwrap().action, BulkAction::SetDue(date("2024-05-22")));
+        assert_eq!(parse_bulk_edit("due in 3d to tomorrow", today()).unwrap().clauses, vec![Clause::Due(DueFilter::On(date("2024-05-18")))]);
+    }
+
+    #[test]
+    fn test_errors_point_at_fragment() {
+        let input = "everything due soonish to saturday";
+        let error = parse_bulk_edit(input, today()).unwrap_err();
+        assert_eq!(&input[error.span.clone()], "soonish");
+        assert!(error.to_string().contains("expected a date"));
+        assert!(error.to_string().ends_with(&format!("  {}^^^^^^^", " ".repeat(15))));
+
+        let error = parse_bulk_edit("tagged #errand whenever to friday", today()).unwrap_err();
+        assert_eq!(error.span, 15..23);
+
+        let error = parse_bulk_edit("tagged #errand", today()).unwrap_err();
+        assert_eq!(error.span, 14..14);
+        assert!(error.message.starts_with("expected an action"));
+
+        let error = parse_bulk_edit("overdue to friday please", today()).unwrap_err();
+        assert_eq!(error.span, 18..24);
+    }
+
+    #[test]
+    fn test_estimates_from_frontmatter_or_inline() {
+        assert_eq!(parse_estimate("45m"), Some(45));
+        assert_eq!(parse_estimate("1h30m"), Some(90));
+        assert_eq!(parse_estimate("1.5h"), Some(90));
+        assert_eq!(parse_estimate("20"), Some(20));
+        assert_eq!(parse_estimate("soon"), None);
+        assert_eq!(format_minutes(135), "2h 15m");
+
+        let todo = TodoItem::parse("Todos/a.md", "---\nest: 2h\nstatus: open\n---\n\n- [ ] write report ~30m");
+        assert_eq!(todo.estimate_minutes, Some(120));
+        let todo = TodoItem::parse("Todos/b.md", "---\nstatus: open\n---\n\n- [ ] call bank ~15m");
+        assert_eq!(todo.estimate_minutes, Some(15));
+        assert_eq!(TodoItem::parse("Todos/c.md", "- [ ] no estimate").estimate_minutes, None);
+    }
+
+    #[test]
+    fn test_plan_edits_frontmatter_surgically() {
+        let todos = vec![
+            TodoItem::parse("Todos/buy-stamps.md", "---\ndue_date: 2024-05-16\ntags:\n- errand\nstatus: open\n---\n\n- [ ] buy stamps"),
+            TodoItem::parse("Todos/call-bank.md", "---\ndue_date: 2024-05-14\ntags: [errand, phone]\nstatus: open\n---\n\n- [ ] call bank"),
+            TodoItem::parse("Todos/done.md", "---\ndue_date: 2024-05-16\ntags:\n- errand\nstatus: done\n---\n\n- [x] done already"),
+            TodoItem::parse("Todos/later.md", "---\ndue_date: 2024-06-01\ntags:\n- errand\nstatus: open\n---\n\n- [ ] later"),
+            TodoItem::parse("Todos/undated.md", "---\ndue_date: null\ntags:\n- errand\nstatus: open\n---\n\n- [ ] someday"),
+        ];
+
+        let edit = parse_bulk_edit("everything due this week tagged #errand to saturday", today()).unwrap();
+        let plan = plan_bulk_edit(&edit, &todos, today());
+        let paths: Vec<&str> = plan.changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["Todos/buy-stamps.md", "Todos/call-bank.md"]);
+        assert_eq!(plan.changes[0].new_content, "---\ndue_date: 2024-05-18\ntags:\n- errand\nstatus: open\n---\n\n- [ ] buy stamps");
+        assert_eq!(plan.changes[0].before, "due 2024-05-16");
+        assert_eq!(plan.changes[0].after, "due 2024-05-18");
+
+        let plan = plan_bulk_edit(&parse_bulk_edit("tagged errand by +1w", today()).unwrap(), &todos, today());
+        assert_eq!(plan.changes.len(), 3);
+        assert_eq!(plan.skipped.len(), 1);
+        assert_eq!(plan.skipped[0].path, "Todos/undated.md");
+
+        let plan = plan_bulk_edit(&parse_bulk_edit("tagged #phone remove #errand", today()).unwrap(), &todos, today());
+        assert_eq!(plan.changes[0].new_content, "---\ndue_date: 2024-05-14\ntags: [phone]\nstatus: open\n---\n\n- [ ] call bank");
+
+        let plan = plan_bulk_edit(&parse_bulk_edit("undated to priority high", today()).unwrap(), &todos, today());
+        assert_eq!(plan.changes.len(), 1);
+        assert!(plan.changes[0].new_content.starts_with("---\ndue_date: null\ntags:\n- errand\nstatus: open\npriority: high\n---"));
+    }
+}
@@ -0,0 +1,583 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::obsidian_adapter::{MarkdownFile, ObsidianAdapter};
+use crate::task_import::{set_task_done, IndexedTask, TaskIndex};
+use crate::todo_bulk::parse_estimate;
+use crate::vault_transaction::{VaultStore, DEFAULT_READ_CONCURRENCY};
+
+pub const TODOS_DIR: &str = "Todos";
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TodoStatus {
+    #[default]
+    Open,
+    InProgress,
+    Done,
+    Cancelled,
+}
+
+impl TodoStatus {
+    /// Read a frontmatter `status`, accepting the spellings people actually use
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().replace(['_', ' '], "-").as_str() {
+            "open" | "todo" | "pending" => Some(Self::Open),
+            "in-progress" | "doing" | "active" | "started" => Some(Self::InProgress),
+            "done" | "complete" | "completed" => Some(Self::Done),
+            "cancelled" | "canceled" | "dropped" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// Still waiting to be worked on or finished
+    pub fn is_open(self) -> bool {
+        matches!(self, Self::Open | Self::InProgress)
+    }
+}
+
+impl fmt::Display for TodoStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Open => "open",
+            Self::InProgress => "in-progress",
+            Self::Done => "done",
+            Self::Cancelled => "cancelled",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+impl Priority {
+    pub fn parse(word: &str) -> Option<Self> {
+        match word {
+            "high" => Some(Self::High),
+            "medium" => Some(Self::Medium),
+            "low" => Some(Self::Low),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::High => "high",
+            Self::Medium => "medium",
+            Self::Low => "low",
+        })
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TodoSort {
+    /// Soonest due first, undated last
+    #[default]
+    Due,
+    /// High priority first, then by due date
+    Priority,
+    /// Oldest first
+    Created,
+    /// Alphabetical
+    Title,
+}
+
+impl fmt::Display for TodoSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Due => "due",
+            Self::Priority => "priority",
+            Self::Created => "created",
+            Self::Title => "title",
+        })
+    }
+}
+
+/// A todo note, read from its frontmatter and checkbox line
+#[derive(Debug, Clone)]
+pub struct TodoItem {
+    /// Vault path, e.g. `Todos/call-bank.md`
+    pub path: String,
+    pub title: String,
+    pub status: TodoStatus,
+    /// `due_date` exactly as written, which may not be a date
+    pub due_raw: Option<String>,
+    pub due: Option<NaiveDate>,
+    pub priority: Option<Priority>,
+    pub tags: Vec<String>,
+    pub created: Option<DateTime<Utc>>,
+    pub completed: Option<DateTime<Utc>>,
+    /// From `est:` in frontmatter or a `~45m` annotation in the title
+    pub estimate_minutes: Option<u32>,
+    /// The note as stored; `to_markdown` edits it in place so untouched lines keep their formatting
+    pub content: String,
+    /// Frontmatter values that could not be read, e.g. a `due_date` that isn't a date
+    pub warnings: Vec<String>,
+    /// Set for a checkbox task imported from another note; `path` is then that note
+    pub source: Option<TaskSource>,
+}
+
+/// Where an imported checkbox task lives in its note
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskSource {
+    /// Id in the task index
+    pub id: String,
+    pub line: usize,
+}
+
+pub(crate) fn frontmatter_block(content: &str) -> Option<&str> {
+    let rest = content.strip_prefix("---\n")?;
+    let end = rest.find("\n---")?;
+    Some(&rest[..end])
+}
+
+fn yaml_scalar(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// `2024-06-01T09:30:00Z`, `2024-06-01 09:30` or a bare date (midnight UTC)
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Some(at.with_timezone(&Utc));
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(|d| d.and_hms_opt(0, 0, 0)))
+        .map(|at| at.and_utc())
+}
+
+fn format_timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+impl TodoItem {
+    /// Read a todo from the raw note. Never fails: unreadable values are left unset and noted in `warnings`.
+    pub fn parse(path: &str, content: &str) -> Self {
+        let mut warnings = Vec::new();
+        let meta = match frontmatter_block(content).map(serde_yaml::from_str::<serde_yaml::Value>) {
+            Some(Ok(meta)) => meta,
+            Some(Err(e)) => {
+                warnings.push(format!("frontmatter is not valid YAML ({})", e));
+                serde_yaml::Value::Null
+            }
+            None => serde_yaml::Value::Null,
+        };
+        let field = |key: &str| meta.get(key).and_then(yaml_scalar).filter(|v| !v.is_empty());
+
+        let tags: Vec<String> = match meta.get("tags") {
+            Some(serde_yaml::Value::Sequence(items)) => items.iter().filter_map(yaml_scalar).collect(),
+            Some(serde_yaml::Value::String(list)) => list.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect(),
+            _ => Vec::new(),
+        };
+        let checkbox = content.lines().map(str::trim_start).find(|l| l.starts_with("- ["));
+        let title = checkbox
+            .and_then(|line| line.split_once(']').map(|(_, rest)| rest.trim().to_string()))
+            .unwrap_or_else(|| path.rsplit('/').next().unwrap_or(path).trim_end_matches(".md").to_string());
+
+        let due_raw = field("due_date");
+        let due = due_raw.as_deref().and_then(|d| NaiveDate::parse_from_str(d.get(..10).unwrap_or(d), "%Y-%m-%d").ok());
+        if let (Some(raw), None) = (&due_raw, due) {
+            warnings.push(format!("due_date '{}' is not a date (expected YYYY-MM-DD)", raw));
+        }
+
+        let mut status = match field("status") {
+            Some(value) => TodoStatus::parse(&value).unwrap_or_else(|| {
+                warnings.push(format!("unknown status '{}', treating it as open", value));
+                TodoStatus::Open
+            }),
+            None => TodoStatus::Open,
+        };
+        if status.is_open() && checkbox.is_some_and(|l| l.starts_with("- [x]")) {
+            status = TodoStatus::Done;
+        }
+
+        let priority = field("priority").map(|p| p.to_lowercase()).and_then(|p| {
+            let parsed = Priority::parse(&p);
+            if parsed.is_none() {
+                warnings.push(format!("unknown priority '{}' (expected high, medium or low)", p));
+            }
+            parsed
+        });
+        let mut timestamp = |key: &str| {
+            let raw = field(key)?;
+            let parsed = parse_timestamp(&raw);
+            if parsed.is_none() {
+                warnings.push(format!("{} '{}' is not a date or timestamp", key, raw));
+            }
+            parsed
+        };
+        let created = timestamp("created");
+        let completed = timestamp("completed");
+
+        let estimate_minutes = field("est").or_else(|| field("estimate")).as_deref().and_then(parse_estimate)
+            .or_else(|| title.split_whitespace().find_map(|w| w.strip_prefix('~').and_then(parse_estimate)));
+
+        Self {
+            path: path.to_string(),
+            title,
+            status,
+            due_raw,
+            due,
+            priority,
+            tags: tags.into_iter().map(|t| t.trim_start_matches('#').to_string()).collect(),
+            created,
+            completed,
+            estimate_minutes,
+            content: content.to_string(),
+            warnings,
+            source: None,
+        }
+    }
+
+    /// Read a todo from an already parsed note
+    pub fn from_markdown(path: &str, file: &MarkdownFile) -> Result<Self> {
+        let raw = ObsidianAdapter::serialize_markdown_file(file)
+            .with_context(|| format!("Failed to read todo {}", path))?;
+        Ok(Self::parse(path, &raw))
+    }
+
+    /// The note with this todo's current values written back. Only values that changed are
+    /// rewritten; the rest of the note is left exactly as it was.
+    pub fn to_markdown(&self) -> String {
+        let stored = Self::parse(&self.path, &self.content);
+        let mut content = self.content.clone();
+
+        if self.due_raw != stored.due_raw || self.due != stored.due {
+            let entry = match (&self.due_raw, self.due) {
+                // Keep a time written after the date, e.g. `2024-05-16 14:00`
+                (Some(raw), Some(due)) if raw.starts_with(&due.to_string()) => Some(raw.clone()),
+                (raw, due) => due.map(|d| d.to_string()).or_else(|| raw.clone()),
+            }
+            .map(|d| format!("due_date: {}", d));
+            content = set_frontmatter_entry(&content, "due_date", entry.as_deref());
+        }
+        if self.priority != stored.priority {
+            content = set_frontmatter_entry(&content, "priority", self.priority.map(|p| format!("priority: {}", p)).as_deref());
+        }
+        if self.tags != stored.tags {
+            let entry = tags_entry(&content, &self.tags);
+            content = set_frontmatter_entry(&content, "tags", entry.as_deref());
+        }
+        for (key, value, before) in [("created", self.created, stored.created), ("completed", self.completed, stored.completed)] {
+            if value != before {
+                let entry = value.map(|at| format!("{}: {}", key, format_timestamp(at)));
+                content = set_frontmatter_entry(&content, key, entry.as_deref());
+            }
+        }
+        if self.status != stored.status {
+            content = set_frontmatter_entry(&content, "status", Some(&format!("status: {}", self.status)));
+        }
+
+        let checked = self.status == TodoStatus::Done;
+        let stored_checked = content.lines().map(str::trim_start).find(|l| l.starts_with("- [")).map(|l| l.starts_with("- [x]"));
+        if stored_checked.is_some_and(|was| was != checked) || (stored_checked.is_some() && self.title != stored.title) {
+            content = content
+                .split_inclusive('\n')
+                .scan(false, |done, line| {
+                    let trimmed = line.trim_start();
+                    if *done || !trimmed.starts_with("- [") {
+                        return Some(line.to_string());
+                    }
+                    *done = true;
+                    let indent = &line[..line.len() - trimmed.len()];
+                    let newline = if line.ends_with('\n') { "\n" } else { "" };
+                    Some(format!("{}- [{}] {}{}", indent, if checked { 'x' } else { ' ' }, self.title, newline))
+                })
+                .collect();
+        }
+        content
+    }
+
+    /// The todo's note, with the line number for an imported checkbox task
+    pub fn location(&self) -> String {
+        match &self.source {
+            Some(source) => format!("{}:{}", self.path, source.line),
+            None => self.path.clone(),
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// Planning order for priorities: high, medium, unset, low
+    pub fn priority_rank(&self) -> u8 {
+        match self.priority {
+            Some(Priority::High) => 0,
+            Some(Priority::Medium) => 1,
+            None => 2,
+            Some(Priority::Low) => 3,
+        }
+    }
+
+    /// The time of day written after the due date, as in `2024-05-16 14:00` or `2024-05-16T14:00`
+    pub fn due_time(&self) -> Option<NaiveTime> {
+        let rest = self.due_raw.as_deref()?.get(10..)?.trim_start_matches(['T', ' ']).trim();
+        NaiveTime::parse_from_str(rest, "%H:%M").or_else(|_| NaiveTime::parse_from_str(rest, "%H:%M:%S")).ok()
+    }
+
+    /// Open and due before `today`
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        self.status.is_open() && self.due.is_some_and(|due| due < today)
+    }
+}
+
+/// Replace (or add) one top-level frontmatter key, leaving every other line untouched.
+/// `entry` is the full YAML for the key, e.g. `due_date: 2024-06-01`; `None` removes it.
+pub(crate) fn set_frontmatter_entry(content: &str, key: &str, entry: Option<&str>) -> String {
+    let Some(frontmatter) = frontmatter_block(content) else {
+        return match entry {
+            Some(entry) => format!("---\n{}\n---\n{}", entry, content),
+            None => content.to_string(),
+        };
+    };
+    let body = &content[4 + frontmatter.len()..];
+
+    let prefix = format!("{}:", key);
+    let mut lines: Vec<&str> = Vec::new();
+    let mut replaced = false;
+    let mut skipping = false;
+    for line in frontmatter.lines() {
+        if skipping {
+            if line.starts_with(' ') || line.starts_with('\t') || line.starts_with("- ") {
+                continue;
+            }
+            skipping = false;
+        }
+        if line.starts_with(&prefix) {
+            skipping = true;
+            if !replaced {
+                lines.extend(entry);
+                replaced = true;
+            }
+            continue;
+        }
+        lines.push(line);
+    }
+    if !replaced {
+        lines.extend(entry);
+    }
+    format!("---\n{}{}", lines.join("\n"), body)
+}
+
+/// Tags rendered in the style the note already uses (inline `[a, b]` or a block list)
+pub(crate) fn tags_entry(content: &str, tags: &[String]) -> Option<String> {
+    if tags.is_empty() {
+        return None;
+    }
+    let inline = frontmatter_block(content)
+        .and_then(|fm| fm.lines().find(|l| l.starts_with("tags:")))
+        .is_some_and(|l| l["tags:".len()..].trim_start().starts_with('['));
+    Some(if inline {
+        format!("tags: [{}]", tags.join(", "))
+    } else {
+        format!("tags:\n{}", tags.iter().map(|t| format!("- {}", t)).collect::<Vec<_>>().join("\n"))
+    })
+}
+
+/// Which todos `todo list` shows; every set condition must hold
+#[derive(Debug, Clone, Default)]
+pub struct TodoFilter {
+    pub status: Option<TodoStatus>,
+    /// Due strictly before this day; undated todos never match
+    pub due_before: Option<NaiveDate>,
+    pub overdue: bool,
+}
+
+impl TodoFilter {
+    pub fn matches(&self, todo: &TodoItem, today: NaiveDate) -> bool {
+        self.status.is_none_or(|status| todo.status == status)
+            && self.due_before.is_none_or(|before| todo.due.is_some_and(|due| due < before))
+            && (!self.overdue || todo.is_overdue(today))
+    }
+}
+
+pub fn sort_todos(todos: &mut [TodoItem], sort: TodoSort) {
+    match sort {
+        TodoSort::Due => todos.sort_by_key(|t| (t.due.is_none(), t.due, t.priority_rank(), t.path.clone())),
+        TodoSort::Priority => todos.sort_by_key(|t| (t.priority_rank(), t.due.is_none(), t.due, t.path.clone())),
+        TodoSort::Created => todos.sort_by_key(|t| (t.created.is_none(), t.created, t.path.clone())),
+        TodoSort::Title => todos.sort_by_key(|t| (t.title.to_lowercase(), t.path.clone())),
+    }
+}
+
+/// Todo notes in the vault, read and written through the adapter
+pub struct TodoStore<'a> {
+    adapter: &'a ObsidianAdapter,
+}
+
+impl<'a> TodoStore<'a> {
+    pub fn new(adapter: &'a ObsidianAdapter) -> Self {
+        Self { adapter }
+    }
+
+    /// Every todo under `Todos/`, subfolders included, followed by the checkbox tasks imported
+    /// with `todo import --scan`. Notes that can't be read, and values that can't be
+    /// understood, are reported on stderr rather than failing the listing.
+    pub async fn list(&self) -> Result<Vec<TodoItem>> {
+        let mut folders = vec![TODOS_DIR.to_string()];
+        let mut paths = Vec::new();
+        while let Some(folder) = folders.pop() {
+            for entry in self.adapter.list_files_in_folder(&folder).await? {
+                let path = format!("{}/{}", folder, entry.trim_end_matches('/'));
+                if entry.ends_with('/') {
+                    folders.push(path);
+                } else if entry.ends_with(".md") {
+                    paths.push(path);
+                }
+            }
+        }
+
+        let mut todos = Vec::new();
+        for (path, content) in self.adapter.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await {
+            match content {
+                Ok(content) => {
+                    let todo = TodoItem::parse(&path, &content);
+                    for warning in &todo.warnings {
+                        eprintln!("Warning: {}: {}", path, warning);
+                    }
+                    todos.push(todo);
+                }
+                Err(e) => eprintln!("Warning: could not read {}: {}", path, e),
+            }
+        }
+        match TaskIndex::load(self.adapter).await {
+            Ok(index) => todos.extend(index.tasks.iter().map(IndexedTask::to_todo)),
+            Err(e) => eprintln!("Warning: could not read the task index: {}", e),
+        }
+        Ok(todos)
+    }
+
+    /// The todos passing `filter`, in `sort` order
+    pub async fn query(&self, filter: &TodoFilter, sort: TodoSort, today: NaiveDate) -> Result<Vec<TodoItem>> {
+        let mut todos: Vec<TodoItem> = self.list().await?.into_iter().filter(|t| filter.matches(t, today)).collect();
+        sort_todos(&mut todos, sort);
+        Ok(todos)
+    }
+
+    pub async fn get(&self, path: &str) -> Result<TodoItem> {
+        let content = self.adapter.get_file(path).await
+            .with_context(|| format!("Failed to retrieve todo '{}'", path))?;
+        Ok(TodoItem::parse(path, &content))
+    }
+
+    /// Write the todo back. For an imported task only its completion is written, by ticking or
+    /// unticking the checkbox in its note.
+    pub async fn save(&self, todo: &TodoItem) -> Result<()> {
+        if let Some(source) = &todo.source {
+            set_task_done(self.adapter, &source.id, todo.status == TodoStatus::Done).await?;
+            return Ok(());
+        }
+        self.adapter.update_file(&todo.path, &todo.to_markdown()).await
+            .with_context(|| format!("Failed to update todo '{}'", todo.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(path: &str, frontmatter: &str, line: &str) -> TodoItem {
+        TodoItem::parse(path, &format!("---\n{}---\n\n{}", frontmatter, line))
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_reads_typed_fields_and_warns_on_bad_values() {
+        let item = todo(
+            "Todos/report.md",
+            "due_date: next tuesday\npriority: High\nstatus: in progress\ncreated: 2024-05-01T09:30:00Z\ncompleted: whenever\ntags: [work]\n",
+            "- [ ] write report ~30m",
+        );
+        assert_eq!(item.title, "write report ~30m");
+        assert_eq!(item.status, TodoStatus::InProgress);
+        assert_eq!(item.priority, Some(Priority::High));
+        assert_eq!(item.due_raw.as_deref(), Some("next tuesday"));
+        assert_eq!(item.due, None);
+        assert_eq!(item.created, Some(DateTime::parse_from_rfc3339("2024-05-01T09:30:00Z").unwrap().with_timezone(&Utc)));
+        assert_eq!(item.completed, None);
+        assert_eq!(item.estimate_minutes, Some(30));
+        assert_eq!(item.warnings.len(), 2, "{:?}", item.warnings);
+        assert!(item.warnings[0].contains("next tuesday"));
+
+        let checked = todo("Todos/a.md", "status: open\n", "- [x] already done");
+        assert_eq!(checked.status, TodoStatus::Done);
+        assert!(checked.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_to_markdown_rewrites_only_what_changed() {
+        let raw = "---\ndue_date: 2024-05-16\ntags: [errand]\nstatus: open\n---\n\n- [ ] buy stamps\nat the post office\n";
+        let mut item = TodoItem::parse("Todos/stamps.md", raw);
+        assert_eq!(item.to_markdown(), raw);
+
+        item.status = TodoStatus::Done;
+        item.completed = Some(DateTime::parse_from_rfc3339("2024-05-16T18:00:00Z").unwrap().with_timezone(&Utc));
+        item.priority = Some(Priority::Low);
+        let written = item.to_markdown();
+        assert_eq!(
+            written,
+            "---\ndue_date: 2024-05-16\ntags: [errand]\nstatus: done\npriority: low\ncompleted: 2024-05-16T18:00:00Z\n---\n\n- [x] buy stamps\nat the post office\n"
+        );
+
+        let reread = TodoItem::parse("Todos/stamps.md", &written);
+        assert_eq!(reread.status, TodoStatus::Done);
+        assert_eq!(reread.completed, item.completed);
+        assert_eq!(reread.priority, Some(Priority::Low));
+    }
+
+    #[test]
+    fn test_from_markdown_uses_the_parsed_frontmatter() {
+        let file = ObsidianAdapter::parse_markdown_file("---\ndue_date: 2024-06-01\nstatus: done\ntags:\n- home\n---\n\n- [x] fix the tap").unwrap();
+        let item = TodoItem::from_markdown("Todos/tap.md", &file).unwrap();
+        assert_eq!(item.due, Some(date("2024-06-01")));
+        assert_eq!(item.status, TodoStatus::Done);
+        assert_eq!(item.tags, vec!["home"]);
+        assert_eq!(item.title, "fix the tap");
+    }
+
+    #[test]
+    fn test_filter_and_sort() {
+        let today = date("2024-05-15");
+        let mut todos = vec![
+            todo("Todos/a.md", "due_date: 2024-05-20\npriority: low\nstatus: open\n", "- [ ] a"),
+            todo("Todos/b.md", "due_date: 2024-05-10\nstatus: open\n", "- [ ] b"),
+            todo("Todos/c.md", "due_date: 2024-05-01\nstatus: done\n", "- [x] c"),
+            todo("Todos/d.md", "priority: high\nstatus: open\n", "- [ ] d"),
+        ];
+
+        let overdue = TodoFilter { overdue: true, ..Default::default() };
+        let paths = |filter: &TodoFilter, todos: &[TodoItem]| -> Vec<String> {
+            todos.iter().filter(|t| filter.matches(t, today)).map(|t| t.path.clone()).collect()
+        };
+        assert_eq!(paths(&overdue, &todos), ["Todos/b.md"]);
+        let before = TodoFilter { due_before: Some(date("2024-05-12")), ..Default::default() };
+        assert_eq!(paths(&before, &todos), ["Todos/b.md", "Todos/c.md"]);
+        let done = TodoFilter { status: Some(TodoStatus::Done), ..Default::default() };
+        assert_eq!(paths(&done, &todos), ["Todos/c.md"]);
+
+        sort_todos(&mut todos, TodoSort::Due);
+        assert_eq!(todos.iter().map(|t| t.title.as_str()).collect::<Vec<_>>(), ["c", "b", "a", "d"]);
+        sort_todos(&mut todos, TodoSort::Priority);
+        assert_eq!(todos.iter().map(|t| t.title.as_str()).collect::<Vec<_>>(), ["d", "c", "b", "a"]);
+    }
+}
@@ -1,28 +1,50 @@
 use anyhow::{Result, Context};
-use crate::cli::{TodoAction, TodoArgs};
+use crate::cli::{OutputFormat, TodoAction, TodoArgs};
 use crate::obsidian_adapter::ObsidianAdapter;
+use crate::output::{print_json, NoteRecord};
+use crate::dates;
+use crate::task_import::handle_import_command;
+use crate::todo_bulk::{format_minutes, handle_reschedule_command, parse_estimate};
+use crate::todo_model::{TodoFilter, TodoItem, TodoStatus, TodoStore, TODOS_DIR};
+use crate::title_match::{TitleCandidate, TitleResolver};
+use crate::confirm::StdinPrompt;
 use crate::utils::slugify; // Import slugify from utils
 use serde::Serialize; // For serializing frontmatter
+use std::collections::BTreeMap;
 
 #[derive(Serialize)]
 struct TodoFrontmatter {
     due_date: Option<String>,
     tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    est: Option<String>,
     status: String,
+    created: String,
 }
 
-pub async fn handle_todo_command(args: TodoArgs, adapter: &ObsidianAdapter) -> Result<()> {
-    let todos_dir = "Todos"; // Define a base directory for todos
+pub async fn handle_todo_command(args: TodoArgs, format: OutputFormat, adapter: &ObsidianAdapter) -> Result<()> {
+    let todos_dir = TODOS_DIR;
 
     match args.action {
-        TodoAction::Add { description, due_date, tags } => {
+        TodoAction::Add { description, due_date, tags, estimate } => {
             println!("Attempting to add todo: '{}'", description);
+            if let Some(estimate) = &estimate {
+                if parse_estimate(estimate).is_none() {
+                    anyhow::bail!("Could not read estimate '{}'; use e.g. 45m, 2h or 1h30m", estimate);
+                }
+            }
+
+            let due_date = due_date.as_deref()
+                .map(|input| dates::frontmatter_date(input, "due", dates::configured_today()?))
+                .transpose()?;
 
             let fm_tags = if tags.is_empty() { None } else { Some(tags.clone()) };
             let frontmatter = TodoFrontmatter {
-                due_date: due_date.clone(),
+                due_date,
                 tags: fm_tags,
-                status: "open".to_string(),
+                est: estimate,
+                status: TodoStatus::Open.to_string(),
+                created: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
             };
 
             let fm_yaml = serde_yaml::to_string(&frontmatter)
@@ -44,254 +66,150 @@ pub async fn handle_todo_command(args: TodoArgs, adapter: &ObsidianAdapter) -> R
             adapter.create_file(&file_name, &content).await
                 .context(format!("Failed to create todo file '{}'", file_name))?;
 
+            crate::script::publish_output(serde_json::json!({ "path": file_name, "description": description }));
             println!("Todo '{}' created as '{}'.", description, file_name);
         }
-        TodoAction::List { status } => {
+        TodoAction::List { status, due_before, overdue, sort } => {
+            let today = dates::configured_today()?;
+            let due_before = due_before
+                .map(|word| dates::parse_date(&word, today).context("Could not read --due-before"))
+                .transpose()?;
+            let filter = TodoFilter { status, due_before, overdue };
+            let store = TodoStore::new(adapter);
+            if format == OutputFormat::Json {
+                let todos: Vec<NoteRecord> = store.query(&filter, sort, today).await?.iter().map(todo_record).collect();
+                crate::script::publish_output(serde_json::json!({ "todos": todos }));
+                return print_json(&todos);
+            }
             println!("Listing todos...");
-            if let Some(ref s) = status {
+            if let Some(s) = status {
                 println!("  Status filter: {}", s);
             }
-            
-            // Get all files in the Todos directory
-            match adapter.list_files_in_folder(todos_dir).await {
-                Ok(files) => {
-                    if files.is_empty() {
-                        println!("No todos found in {}/", todos_dir);
-                        return Ok(());
-                    }
-                    
-                    println!("Found {} todo files:", files.len());
-                    
-                    // Filter and display todos
-                    let mut found_todos = 0;
-                    
-                    for file in files {
-                        if !file.ends_with(".md") {
-                            continue;
-                        }
-                        
-                        let file_path = format!("{}/{}", todos_dir, file);
-                        
-                        // Try to get the file content
-                        match adapter.get_file(&file_path).await {
-                            Ok(content) => {
-                                // Parse the todo content
-                                let todo_status = extract_todo_status(&content);
-                                let todo_checkbox = extract_checkbox_status(&content);
-                                let todo_description = extract_todo_description(&content);
-                                let todo_due_date = extract_due_date(&content);
-                                let todo_tags = extract_tags(&content);
-                                
-                                // Apply status filter if provided
-                                let should_show = if let Some(filter_status) = &status {
-                                    match filter_status.as_str() {
-                                        "open" => todo_status == "open" || todo_checkbox == "[ ]",
-                                        "done" => todo_status == "done" || todo_checkbox == "[x]",
-                                        _ => true,
-                                    }
-                                } else {
-                                    true
-                                };
-                                
-                                if should_show {
-                                    found_todos += 1;
-                                    
-                                    // Extract the file name without extension for display
-                                    let display_name = file.strip_suffix(".md").unwrap_or(&file);
-                                    
-                                    // Format the output
-                                    let status_indicator = if todo_checkbox == "[x]" || todo_status == "done" {
-                                        "✓"
-                                    } else {
-                                        "•"
-                                    };
-                                    
-                                    println!("{} {} ({})", status_indicator, display_name, todo_description);
-                                    
-                                    if let Some(due_date) = todo_due_date {
-                                        println!("    Due: {}", due_date);
-                                    }
-                                    
-                                    if !todo_tags.is_empty() {
-                                        println!("    Tags: {}", todo_tags.join(", "));
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                println!("Warning: Could not read file {}: {}", file_path, e);
-                            }
-                        }
-                    }
-                    
-                    if found_todos == 0 {
-                        if let Some(filter_status) = &status {
-                            println!("No todos found with status: {}", filter_status);
-                        } else {
-                            println!("No todos found.");
-                        }
-                    } else {
-                        println!("\nTotal todos shown: {}", found_todos);
-                    }
-                }
+
+            let todos = match store.query(&filter, sort, today).await {
+                Ok(todos) => todos,
                 Err(e) => {
                     println!("Error listing todos: {}", e);
                     println!("Make sure the Todos directory exists and the MCP server is running.");
+                    return Ok(());
+                }
+            };
+            crate::script::publish_output(serde_json::json!({ "todos": todos.iter().map(todo_record).collect::<Vec<_>>() }));
+            if todos.is_empty() {
+                println!("No todos found.");
+                return Ok(());
+            }
+
+            // Estimated minutes per due date: (todos, minutes, todos without an estimate)
+            let mut estimate_totals: BTreeMap<String, (usize, u32, usize)> = BTreeMap::new();
+            for todo in &todos {
+                let location = todo.location();
+                let display_name = location.strip_prefix(&format!("{}/", todos_dir)).unwrap_or(&location);
+                let display_name = display_name.strip_suffix(".md").unwrap_or(display_name);
+                let status_indicator = match todo.status {
+                    TodoStatus::Done => "✓",
+                    TodoStatus::Cancelled => "✗",
+                    _ if todo.is_overdue(today) => "!",
+                    _ => "•",
+                };
+                println!("{} {} ({})", status_indicator, display_name, todo.title);
+                if let Some(due_date) = &todo.due_raw {
+                    println!("    Due: {}", due_date);
+                }
+                if let Some(priority) = todo.priority {
+                    println!("    Priority: {}", priority);
+                }
+                if !todo.tags.is_empty() {
+                    println!("    Tags: {}", todo.tags.join(", "));
+                }
+                if let Some(minutes) = todo.estimate_minutes {
+                    println!("    Estimate: {}", format_minutes(minutes));
+                }
+                let group = todo.due_raw.clone().unwrap_or_else(|| "No due date".to_string());
+                let total = estimate_totals.entry(group).or_default();
+                total.0 += 1;
+                total.1 += todo.estimate_minutes.unwrap_or(0);
+                total.2 += usize::from(todo.estimate_minutes.is_none());
+            }
+
+            println!("\nTotal todos shown: {}", todos.len());
+            if estimate_totals.values().any(|(_, minutes, _)| *minutes > 0) {
+                println!("Estimated time by due date:");
+                for (group, (count, minutes, unestimated)) in &estimate_totals {
+                    let missing = if *unestimated > 0 { format!(", {} without estimate", unestimated) } else { String::new() };
+                    println!("  {}: {} ({} todo(s){})", group, format_minutes(*minutes), count, missing);
                 }
             }
         }
         TodoAction::Done { id } => {
-            // 'id' here would likely be the filename (e.g., "my-important-task") or a unique ID from frontmatter
-            // For now, let's assume 'id' is the slugified filename part.
-            let file_name = format!("{}/{}.md", todos_dir, id);
+            let store = TodoStore::new(adapter);
+            let mut todo = find_todo(&store, &id).await?;
+            let file_name = todo.path.clone();
             println!("Attempting to mark todo '{}' as done.", file_name);
 
-            let current_content = adapter.get_file(&file_name).await
-                .context(format!("Failed to retrieve todo '{}' for marking as done.", file_name))?;
-
-            // This is a naive replacement. A more robust solution would parse the Markdown.
-            let updated_content = current_content.replacen("- [ ]", "- [x]", 1);
-
-            if current_content == updated_content {
-                 println!("Todo '{}' might already be marked as done or checkbox not found.", file_name);
+            if todo.status == TodoStatus::Done {
+                println!("Todo '{}' is already done.", file_name);
             } else {
-                // Ideally, also update status in frontmatter
-                // For simplicity now, just updating the checkbox
-                adapter.update_file(&file_name, &updated_content).await
-                    .context(format!("Failed to update todo '{}' to done.", file_name))?;
+                todo.status = TodoStatus::Done;
+                todo.completed = Some(chrono::Utc::now());
+                store.save(&todo).await?;
                 println!("Todo '{}' marked as done.", file_name);
             }
         }
         TodoAction::View { id } => {
-            // Assume 'id' is the slugified filename part.
-            let file_name = format!("{}/{}.md", todos_dir, id);
-            println!("Viewing todo '{}'.", file_name);
+            let todo = find_todo(&TodoStore::new(adapter), &id).await?;
+            let (file_name, content) = (&todo.path, &todo.content);
+            if format == OutputFormat::Json {
+                return print_json(&todo_record(&todo).with_content(content));
+            }
 
-            let content = adapter.get_file(&file_name).await
-                .context(format!("Failed to retrieve todo '{}' for viewing.", file_name))?;
+            println!("Viewing todo '{}'.", file_name);
 
             println!("--- Content of {} ---", file_name);
             println!("{}", content);
             println!("--- End of Content ---");
         }
-    }
-    Ok(())
-}
-
-// Helper functions for parsing todo content
-
-fn extract_todo_status(content: &str) -> String {
-    // Look for status in frontmatter
-    if let Some(frontmatter_end) = content.find("---\n") {
-        if let Some(second_frontmatter_end) = content[frontmatter_end + 4..].find("---\n") {
-            let frontmatter = &content[frontmatter_end + 4..frontmatter_end + 4 + second_frontmatter_end];
-            
-            // Simple parsing - look for "status: " line
-            for line in frontmatter.lines() {
-                if line.trim().starts_with("status:") {
-                    return line.split(':').nth(1).unwrap_or("unknown").trim().to_string();
-                }
-            }
-        }
-    }
-    "unknown".to_string()
-}
-
-fn extract_checkbox_status(content: &str) -> String {
-    // Look for checkbox patterns in the content
-    if content.contains("- [x]") {
-        "[x]".to_string()
-    } else if content.contains("- [ ]") {
-        "[ ]".to_string()
-    } else {
-        "none".to_string()
-    }
-}
-
-fn extract_todo_description(content: &str) -> String {
-    // Look for the todo description after the checkbox
-    for line in content.lines() {
-        if line.contains("- [") {
-            // Extract text after the checkbox
-            if let Some(pos) = line.find(']') {
-                return line[pos + 1..].trim().to_string();
-            }
-        }
-    }
-    
-    // Fallback: look for the first non-frontmatter line
-    let mut in_frontmatter = false;
-    let mut frontmatter_ended = false;
-    
-    for line in content.lines() {
-        if line.trim() == "---" {
-            if !in_frontmatter {
-                in_frontmatter = true;
-            } else {
-                frontmatter_ended = true;
-            }
-            continue;
+        TodoAction::Import { scan, folder, include_done, dry_run } => {
+            handle_import_command(scan, folder, include_done, dry_run, adapter).await?;
         }
-        
-        if frontmatter_ended && !line.trim().is_empty() {
-            return line.trim().to_string();
+        TodoAction::Reschedule { request, dry_run, yes, llm_assist } => {
+            handle_reschedule_command(&request, dry_run, yes, llm_assist, adapter).await?;
         }
     }
-    
-    "No description found".to_string()
+    Ok(())
 }
 
-fn extract_due_date(content: &str) -> Option<String> {
-    // Look for due_date in frontmatter
-    if let Some(frontmatter_end) = content.find("---\n") {
-        if let Some(second_frontmatter_end) = content[frontmatter_end + 4..].find("---\n") {
-            let frontmatter = &content[frontmatter_end + 4..frontmatter_end + 4 + second_frontmatter_end];
-            
-            // Simple parsing - look for "due_date: " line
-            for line in frontmatter.lines() {
-                if line.trim().starts_with("due_date:") {
-                    let due_date = line.split(':').nth(1).unwrap_or("").trim();
-                    if !due_date.is_empty() && due_date != "null" {
-                        return Some(due_date.to_string());
-                    }
-                }
-            }
-        }
+/// The todo `id` names: its file name (`my-important-task`), or failing that the todo whose
+/// title is closest, e.g. "project review". Several close titles are offered to choose from.
+async fn find_todo(store: &TodoStore<'_>, id: &str) -> Result<TodoItem> {
+    let mut todos = store.list().await?;
+    let file_name = format!("{}/{}.md", TODOS_DIR, id);
+    let exact = |todo: &TodoItem| match &todo.source {
+        Some(source) => source.id == id,
+        None => todo.path == file_name,
+    };
+    if let Some(i) = todos.iter().position(exact) {
+        return Ok(todos.swap_remove(i));
     }
-    None
+
+    // Imported tasks share their note's path, so candidates are told apart by location
+    let candidates: Vec<TitleCandidate> = todos.iter()
+        .map(|todo| TitleCandidate { path: todo.location(), title: todo.title.clone(), tags: todo.tags.clone() })
+        .collect();
+    let chosen = TitleResolver::default().pick("todo", id, &candidates, &mut StdinPrompt)?;
+    eprintln!("Matched \"{}\" to '{}'.", id, chosen.path);
+    let i = todos.iter().position(|todo| todo.location() == chosen.path).expect("chosen from these todos");
+    Ok(todos.swap_remove(i))
 }
 
-fn extract_tags(content: &str) -> Vec<String> {
-    // Look for tags in frontmatter
-    if let Some(frontmatter_end) = content.find("---\n") {
-        if let Some(second_frontmatter_end) = content[frontmatter_end + 4..].find("---\n") {
-            let frontmatter = &content[frontmatter_end + 4..frontmatter_end + 4 + second_frontmatter_end];
-            
-            // Simple parsing - look for "tags: " line
-            for line in frontmatter.lines() {
-                if line.trim().starts_with("tags:") {
-                    let tags_str = line.split(':').nth(1).unwrap_or("").trim();
-                    if !tags_str.is_empty() && tags_str != "null" {
-                        // Handle both array format and simple format
-                        if tags_str.starts_with('[') && tags_str.ends_with(']') {
-                            // Array format: [tag1, tag2, tag3]
-                            let tags_content = &tags_str[1..tags_str.len() - 1];
-                            return tags_content
-                                .split(',')
-                                .map(|s| s.trim().trim_matches('"').to_string())
-                                .filter(|s| !s.is_empty())
-                                .collect();
-                        } else {
-                            // Simple format: tag1, tag2, tag3
-                            return tags_str
-                                .split(',')
-                                .map(|s| s.trim().to_string())
-                                .filter(|s| !s.is_empty())
-                                .collect();
-                        }
-                    }
-                }
-            }
-        }
+fn todo_record(todo: &TodoItem) -> NoteRecord {
+    let record = NoteRecord::parse(&todo.path, &todo.content)
+        .with("description", &todo.title)
+        .with("status", todo.status)
+        .with("done", todo.status == TodoStatus::Done)
+        .with("estimate_minutes", todo.estimate_minutes);
+    match &todo.source {
+        Some(source) => record.with("task_id", &source.id).with("line", source.line),
+        None => record,
     }
-    Vec::new()
 }
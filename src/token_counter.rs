@@ -0,0 +1,165 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Estimates how many tokens a model will see for a piece of text
+pub trait TokenCounter: Debug + Send + Sync {
+    fn count(&self, text: &str) -> usize;
+
+    /// Tokens of a whole chat message, including any per-message framing
+    fn count_message(&self, content: &str) -> usize {
+        self.count(content)
+    }
+}
+
+/// The counter for `model`: BPE-style estimation for OpenAI models, a character heuristic otherwise
+pub fn for_model(model: &str) -> Arc<dyn TokenCounter> {
+    let model = model.to_ascii_lowercase();
+    if model.starts_with("gpt") || model.starts_with("o1") || model.starts_with("o3") || model.starts_with("o4") || model.starts_with("openai") {
+        Arc::new(BpeTokenCounter)
+    } else {
+        Arc::new(CharTokenCounter::default())
+    }
+}
+
+/// Roughly one token per `chars_per_token` characters, which is how Gemini documents its tokenizer
+#[derive(Debug, Clone)]
+pub struct CharTokenCounter {
+    pub chars_per_token: usize,
+}
+
+impl Default for CharTokenCounter {
+    fn default() -> Self {
+        Self { chars_per_token: 4 }
+    }
+}
+
+impl TokenCounter for CharTokenCounter {
+    fn count(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(self.chars_per_token.max(1))
+    }
+}
+
+/// Tokens OpenAI adds around each chat message
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Estimates cl100k-style BPE tokens without the merge table: text is split the way
+/// tiktoken's pre-tokenizer splits it (contractions, words with their leading space,
+/// digit groups of up to three, punctuation runs, whitespace), and each piece is costed
+/// by its length. Common words are single tokens; long or non-Latin words split further.
+#[derive(Debug, Clone, Default)]
+pub struct BpeTokenCounter;
+
+impl TokenCounter for BpeTokenCounter {
+    fn count(&self, text: &str) -> usize {
+        pre_tokenize(text).into_iter().map(piece_tokens).sum()
+    }
+
+    fn count_message(&self, content: &str) -> usize {
+        self.count(content) + MESSAGE_OVERHEAD
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Piece<'a> {
+    Word(&'a str),
+    Digits(&'a str),
+    Punctuation(&'a str),
+    Space(&'a str),
+}
+
+fn piece_tokens(piece: Piece) -> usize {
+    match piece {
+        Piece::Word(word) => {
+            let word = word.strip_prefix(' ').unwrap_or(word);
+            if word.is_ascii() {
+                word.len().div_ceil(6).max(1)
+            } else {
+                // Scripts outside ASCII merge far less: about a token per character
+                word.chars().count().max(1)
+            }
+        }
+        Piece::Digits(_) | Piece::Space(_) => 1,
+        Piece::Punctuation(run) => run.trim_start_matches(' ').chars().count().div_ceil(2).max(1),
+    }
+}
+
+/// Split `text` like the cl100k pre-tokenizer
+fn pre_tokenize<'t>(text: &'t str) -> Vec<Piece<'t>> {
+    const CONTRACTIONS: [&str; 7] = ["'s", "'t", "'re", "'ve", "'m", "'ll", "'d"];
+    let is_punctuation = |c: char| !c.is_whitespace() && !c.is_alphanumeric();
+    let mut pieces = Vec::new();
+    let mut at = 0;
+    while at < text.len() {
+        let rest = &text[at..];
+        let first = rest.chars().next().unwrap_or(' ');
+        let run = |start: usize, keep: &dyn Fn(char) -> bool| {
+            start + rest[start..].find(|c: char| !keep(c)).unwrap_or(rest.len() - start)
+        };
+        let contraction = CONTRACTIONS.iter().find(|c| first == '\'' && rest.get(..c.len()).is_some_and(|r| r.eq_ignore_ascii_case(c)));
+        let (len, piece): (usize, fn(&'t str) -> Piece<'t>) = if let Some(c) = contraction {
+            (c.len(), Piece::Word)
+        } else if first.is_alphabetic() {
+            (run(0, &|c| c.is_alphabetic()), Piece::Word)
+        } else if !first.is_alphanumeric() && first != '\n' && first != '\r'
+            && rest[first.len_utf8()..].starts_with(char::is_alphabetic)
+        {
+            // A word takes one leading space or symbol with it
+            (run(first.len_utf8(), &|c| c.is_alphabetic()), Piece::Word)
+        } else if first.is_numeric() {
+            let end = rest.char_indices().take_while(|(_, c)| c.is_numeric()).take(3).last().map_or(1, |(i, c)| i + c.len_utf8());
+            (end, Piece::Digits)
+        } else if is_punctuation(first) || (first == ' ' && rest[1..].starts_with(is_punctuation)) {
+            let start = usize::from(first == ' ');
+            let end = run(start, &is_punctuation);
+            (run(end, &|c| c == '\n' || c == '\r'), Piece::Punctuation)
+        } else {
+            // Whitespace: a run ending before a word leaves its last space for the word
+            let end = run(0, &char::is_whitespace);
+            let end = if end < rest.len() && end > 1 && rest[..end].ends_with(' ') { end - 1 } else { end };
+            (end, Piece::Space)
+        };
+        let len = len.max(first.len_utf8());
+        pieces.push(piece(&rest[..len]));
+        at += len;
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pre_tokenizer_splits_like_cl100k() {
+        let pieces = pre_tokenize("I'll pay 12345 now!!\n\n  Done");
+        assert_eq!(pieces, vec![
+            Piece::Word("I"),
+            Piece::Word("'ll"),
+            Piece::Word(" pay"),
+            Piece::Space(" "),
+            Piece::Digits("123"),
+            Piece::Digits("45"),
+            Piece::Word(" now"),
+            Piece::Punctuation("!!\n\n"),
+            Piece::Space(" "),
+            Piece::Word(" Done"),
+        ]);
+    }
+
+    #[test]
+    fn test_bpe_estimate_is_close_for_english() {
+        // tiktoken's cl100k_base gives 10 tokens for this sentence
+        let tokens = BpeTokenCounter.count("The quick brown fox jumps over the lazy dog.");
+        assert_eq!(tokens, 10);
+        assert_eq!(BpeTokenCounter.count_message(""), MESSAGE_OVERHEAD);
+        // Non-Latin text costs more per character than English
+        assert!(BpeTokenCounter.count("会议改到周五下午") >= 8);
+    }
+
+    #[test]
+    fn test_models_get_their_provider_counter() {
+        assert_eq!(for_model("gpt-4o-mini").count_message("hello"), 1 + MESSAGE_OVERHEAD);
+        assert_eq!(for_model("gemini-1.5-flash").count_message("hello"), 2);
+        assert_eq!(CharTokenCounter::default().count("abcdefghi"), 3);
+    }
+}
@@ -0,0 +1,290 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+/// Name of the function the model calls to page through a reduced result
+pub const PAGE_TOOL_NAME: &str = "page_tool_result";
+/// Page size when a page request doesn't give a limit
+const DEFAULT_PAGE_LIMIT: usize = 20;
+/// Excerpts are never trimmed below this many characters
+const MIN_EXCERPT_CHARS: usize = 80;
+
+/// `[tool_results]`: how much of a tool result goes into the conversation context
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolResultSettings {
+    /// Results estimated above this many tokens are reduced
+    pub max_tokens: usize,
+    /// Items kept from each list of a reduced result
+    pub max_items: usize,
+    /// Characters kept from each long text of a reduced result
+    pub max_excerpt_chars: usize,
+}
+
+impl Default for ToolResultSettings {
+    fn default() -> Self {
+        Self {
+            max_tokens: 1000,
+            max_items: 10,
+            max_excerpt_chars: 400,
+        }
+    }
+}
+
+/// Rough estimation used for the context window: 1 token ≈ 4 characters
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len() / 4
+}
+
+fn value_tokens(value: &Value) -> usize {
+    estimate_tokens(&value.to_string())
+}
+
+/// `[[links]]` and `^block-ids` in `text`, which must stay citable after trimming
+fn citation_anchors(text: &str) -> Vec<String> {
+    let mut anchors = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let Some(end) = rest[start..].find("]]") else { break };
+        anchors.push(rest[start..start + end + 2].to_string());
+        rest = &rest[start + end + 2..];
+    }
+    anchors.extend(text.split_whitespace()
+        .filter_map(|word| word.strip_prefix('^'))
+        .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_alphanumeric() || c == '-'))
+        .map(|id| format!("^{}", id)));
+    anchors
+}
+
+/// Cut `text` at a word boundary, listing the anchors that fell in the removed part
+pub fn trim_excerpt(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut = text.char_indices().nth(max_chars).map_or(text.len(), |(i, _)| i);
+    let cut = text[..cut].rfind(char::is_whitespace).filter(|&i| i > 0).unwrap_or(cut);
+    let (kept, removed) = text.split_at(cut);
+    let kept_anchors = citation_anchors(kept);
+    let mut lost: Vec<String> = Vec::new();
+    for anchor in citation_anchors(removed) {
+        if !kept_anchors.contains(&anchor) && !lost.contains(&anchor) {
+            lost.push(anchor);
+        }
+    }
+    let omitted = text.chars().count() - kept.chars().count();
+    if lost.is_empty() {
+        format!("{} … [{} more chars]", kept.trim_end(), omitted)
+    } else {
+        format!("{} … [{} more chars; cites {}]", kept.trim_end(), omitted, lost.join(" "))
+    }
+}
+
+fn reduce_value(value: &Value, items: usize, chars: usize, result_id: &str) -> Value {
+    match value {
+        Value::Array(list) => {
+            let mut kept: Vec<Value> = list.iter().take(items).map(|v| reduce_value(v, items, chars, result_id)).collect();
+            if list.len() > items {
+                kept.push(Value::String(format!(
+                    "… and {} more ({} with result_id \"{}\", offset {})",
+                    list.len() - items, PAGE_TOOL_NAME, result_id, items
+                )));
+            }
+            Value::Array(kept)
+        }
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), reduce_value(v, items, chars, result_id))).collect()),
+        Value::String(text) => Value::String(trim_excerpt(text, chars)),
+        other => other.clone(),
+    }
+}
+
+/// The result as it goes into the context: unchanged when within `max_tokens`, otherwise
+/// lists cut to their first items and long texts trimmed, tightening until it fits
+pub fn reduce_tool_result(value: &Value, settings: &ToolResultSettings, result_id: &str) -> Value {
+    if value_tokens(value) <= settings.max_tokens {
+        return value.clone();
+    }
+    let (mut items, mut chars) = (settings.max_items.max(1), settings.max_excerpt_chars.max(MIN_EXCERPT_CHARS));
+    loop {
+        let reduced = reduce_value(value, items, chars, result_id);
+        if value_tokens(&reduced) <= settings.max_tokens || (items == 1 && chars == MIN_EXCERPT_CHARS) {
+            return reduced;
+        }
+        items = (items / 2).max(1);
+        chars = (chars / 2).max(MIN_EXCERPT_CHARS);
+    }
+}
+
+/// The longest list in a result, which is the one worth paging through
+fn longest_list(value: &Value) -> Option<&Vec<Value>> {
+    match value {
+        Value::Array(list) => Some(list),
+        Value::Object(map) => map.values().filter_map(longest_list).max_by_key(|list| list.len()),
+        _ => None,
+    }
+}
+
+/// `offset`/`limit` parameters of a list-returning tool
+pub fn page_params(parameters: &HashMap<String, Value>) -> (usize, Option<usize>) {
+    let get = |key: &str| parameters.get(key).and_then(Value::as_u64).map(|n| n as usize);
+    (get("offset").unwrap_or(0), get("limit"))
+}
+
+/// JSON schema properties for `offset`/`limit`, merged into list-returning tools' schemas
+pub fn page_schema_properties() -> Value {
+    serde_json::json!({
+        "offset": { "type": "integer", "description": "Index of the first item to return" },
+        "limit": { "type": "integer", "description": "Maximum number of items to return" }
+    })
+}
+
+/// A tool result kept in full outside the context
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredToolResult {
+    pub id: String,
+    pub tool: String,
+    /// Number of the user turn the result belongs to
+    pub turn: usize,
+    pub full: Value,
+    pub full_tokens: usize,
+    pub context_tokens: usize,
+    pub created_at: DateTime<Utc>,
+}
+
+impl StoredToolResult {
+    pub fn saved_tokens(&self) -> usize {
+        self.full_tokens.saturating_sub(self.context_tokens)
+    }
+}
+
+/// Full tool results of a session, for `/expand` and paging
+#[derive(Debug, Default)]
+pub struct ToolResultStore {
+    results: Vec<StoredToolResult>,
+}
+
+impl ToolResultStore {
+    /// Keep `full` and return its id and the reduced value for the context
+    pub fn add(&mut self, tool: &str, turn: usize, full: Value, settings: &ToolResultSettings) -> (String, Value) {
+        let id = format!("r{}", self.results.len() + 1);
+        let reduced = reduce_tool_result(&full, settings, &id);
+        self.results.push(StoredToolResult {
+            id: id.clone(),
+            tool: tool.to_string(),
+            turn,
+            full_tokens: value_tokens(&full),
+            context_tokens: value_tokens(&reduced),
+            full,
+            created_at: Utc::now(),
+        });
+        (id, reduced)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&StoredToolResult> {
+        self.results.iter().find(|r| r.id == id)
+    }
+
+    pub fn last(&self) -> Option<&StoredToolResult> {
+        self.results.last()
+    }
+
+    pub fn results(&self) -> &[StoredToolResult] {
+        &self.results
+    }
+
+    pub fn total_saved(&self) -> usize {
+        self.results.iter().map(StoredToolResult::saved_tokens).sum()
+    }
+
+    /// Items `offset..offset + limit` of the result's longest list
+    pub fn page(&self, id: &str, offset: usize, limit: Option<usize>) -> Option<Value> {
+        let stored = self.get(id)?;
+        let list = longest_list(&stored.full)?;
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let items: Vec<Value> = list.iter().skip(offset).take(limit).cloned().collect();
+        Some(serde_json::json!({
+            "result_id": id,
+            "offset": offset,
+            "returned": items.len(),
+            "total": list.len(),
+            "items": items,
+        }))
+    }
+
+    /// Token savings per tool result, for `/context`
+    pub fn render(&self) -> String {
+        if self.results.is_empty() {
+            return "No tool results in this session yet.\n".to_string();
+        }
+        let mut out = String::from("Tool results (full versions kept out of context; /expand <id> shows one):\n");
+        for result in &self.results {
+            let _ = writeln!(
+                out,
+                "  turn {:<3} {:<4} {:<18} {:>6} → {:>5} tokens (saved {})",
+                result.turn, result.id, result.tool, result.full_tokens, result.context_tokens, result.saved_tokens()
+            );
+        }
+        let _ = writeln!(out, "Saved so far: {} tokens", self.total_saved());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todos(count: usize) -> Value {
+        let items: Vec<Value> = (0..count)
+            .map(|i| serde_json::json!({ "id": i, "title": format!("Follow up on item {} from the planning review", i), "due": "2024-06-07", "done": false }))
+            .collect();
+        serde_json::json!({ "success": true, "todos": items, "total": count })
+    }
+
+    #[test]
+    fn test_large_list_is_bounded_and_fully_paginatable() {
+        let settings = ToolResultSettings::default();
+        let mut store = ToolResultStore::default();
+        let full = todos(500);
+        assert!(value_tokens(&full) > 10 * settings.max_tokens);
+
+        let (id, reduced) = store.add("list_todos", 1, full, &settings);
+        assert!(value_tokens(&reduced) <= settings.max_tokens);
+        let kept = reduced["todos"].as_array().unwrap();
+        assert_eq!(kept.len(), settings.max_items + 1);
+        assert_eq!(kept.last().unwrap().as_str().unwrap(), "… and 490 more (page_tool_result with result_id \"r1\", offset 10)");
+        assert_eq!(reduced["total"], 500);
+        assert!(store.last().unwrap().saved_tokens() > 0);
+
+        let mut seen = Vec::new();
+        let mut offset = 0;
+        while let Some(page) = store.page(&id, offset, Some(64)) {
+            let items = page["items"].as_array().unwrap();
+            if items.is_empty() {
+                break;
+            }
+            assert_eq!(page["total"], 500);
+            seen.extend(items.iter().map(|item| item["id"].as_u64().unwrap()));
+            offset += items.len();
+        }
+        assert_eq!(seen, (0..500).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_small_results_pass_through() {
+        let mut store = ToolResultStore::default();
+        let (_, reduced) = store.add("list_todos", 1, todos(3), &ToolResultSettings::default());
+        assert_eq!(reduced, todos(3));
+        assert_eq!(store.total_saved(), 0);
+    }
+
+    #[test]
+    fn test_trimmed_excerpts_keep_citation_anchors() {
+        let text = format!("Start of the meeting note with [[Roadmap]]. {} The decision is in [[Decisions#Q3]] ^d42 and nowhere else.", "filler ".repeat(60));
+        let trimmed = trim_excerpt(&text, 100);
+        assert!(trimmed.starts_with("Start of the meeting note with [[Roadmap]]."));
+        assert!(trimmed.ends_with("cites [[Decisions#Q3]] ^d42]"));
+        assert!(trimmed.len() < 200);
+        assert_eq!(trim_excerpt("short", 100), "short");
+    }
+}
@@ -0,0 +1,500 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::ai_conversation::{FunctionSchema, LLMClient, Message};
+use crate::config::{Config, UsageSettings};
+use crate::dates;
+use crate::embedding_provider::EmbeddingProvider;
+use crate::report::{DetailLevel, Report, ReportView};
+use crate::token_counter::{self, TokenCounter};
+
+/// Serializes updates to the ledger file between the clients of one process
+static LEDGER_LOCK: Mutex<()> = Mutex::new(());
+
+/// What a request was made for; `arrowhead usage` breaks totals down by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsagePurpose {
+    /// Interactive conversation
+    Chat,
+    /// Note analysis, organization, link classification and other vault processing
+    Analysis,
+    /// Embeddings for semantic search
+    Embeddings,
+    /// Generated text offered to the user: focus summaries, agendas, template suggestions
+    Suggestions,
+}
+
+impl fmt::Display for UsagePurpose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UsagePurpose::Chat => "chat",
+            UsagePurpose::Analysis => "analysis",
+            UsagePurpose::Embeddings => "embeddings",
+            UsagePurpose::Suggestions => "suggestions",
+        })
+    }
+}
+
+/// Requests and tokens, as estimated by the model's `TokenCounter`; providers' own counts
+/// aren't available through every client
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl UsageTotals {
+    pub fn tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    fn add(&mut self, other: &UsageTotals) {
+        self.requests += other.requests;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
+}
+
+/// Usage of one provider for one purpose on one (local) day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageEntry {
+    pub date: NaiveDate,
+    pub provider: String,
+    pub purpose: UsagePurpose,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+/// Totals over a range of days, overall and broken down
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageBreakdown {
+    pub total: UsageTotals,
+    pub by_purpose: BTreeMap<UsagePurpose, UsageTotals>,
+    pub by_provider: BTreeMap<String, UsageTotals>,
+}
+
+/// The usage file: daily counters, and when the requests of the last minute started
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageLedger {
+    pub entries: Vec<UsageEntry>,
+    pub recent_requests: Vec<DateTime<Utc>>,
+}
+
+impl UsageLedger {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path).context("Failed to read usage ledger")?;
+        serde_json::from_str(&data).context("Failed to parse usage ledger")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?).context("Failed to write usage ledger")
+    }
+
+    pub fn add(&mut self, date: NaiveDate, provider: &str, purpose: UsagePurpose, totals: UsageTotals) {
+        match self.entries.iter_mut().find(|e| e.date == date && e.provider == provider && e.purpose == purpose) {
+            Some(entry) => entry.totals.add(&totals),
+            None => self.entries.push(UsageEntry { date, provider: provider.to_string(), purpose, totals }),
+        }
+    }
+
+    /// Totals of the days from `from` to `to`, inclusive
+    pub fn breakdown(&self, from: NaiveDate, to: NaiveDate) -> UsageBreakdown {
+        let mut breakdown = UsageBreakdown::default();
+        for entry in self.entries.iter().filter(|e| e.date >= from && e.date <= to) {
+            breakdown.total.add(&entry.totals);
+            breakdown.by_purpose.entry(entry.purpose).or_default().add(&entry.totals);
+            breakdown.by_provider.entry(entry.provider.clone()).or_default().add(&entry.totals);
+        }
+        breakdown
+    }
+
+    fn tokens_on(&self, date: NaiveDate) -> u64 {
+        self.entries.iter().filter(|e| e.date == date).map(|e| e.totals.tokens()).sum()
+    }
+
+    fn forget_before(&mut self, cutoff: DateTime<Utc>) {
+        self.recent_requests.retain(|started| *started > cutoff);
+    }
+}
+
+/// A request refused by a `[usage]` limit
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum UsageLimitExceeded {
+    #[error("LLM request limit reached: {limit} request(s) per minute ([usage] max_requests_per_minute); try again in {retry_in_secs}s")]
+    RequestsPerMinute { limit: u32, retry_in_secs: i64 },
+    #[error("Daily LLM token limit reached: {used} of {limit} token(s) used today and this needs about {needed} more ([usage] max_daily_tokens)")]
+    DailyTokens { limit: u64, used: u64, needed: u64 },
+}
+
+pub fn default_usage_path() -> PathBuf {
+    let mut path = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push(".config");
+    path.push("arrowhead");
+    path.push("usage.json");
+    path
+}
+
+pub fn usage_path(settings: &UsageSettings) -> PathBuf {
+    settings.path.as_ref().map(PathBuf::from).unwrap_or_else(default_usage_path)
+}
+
+/// Counts requests in the usage ledger and enforces the `[usage]` limits
+#[derive(Debug, Clone)]
+pub struct UsageTracker {
+    path: PathBuf,
+    settings: UsageSettings,
+}
+
+impl UsageTracker {
+    pub fn from_settings(settings: &UsageSettings) -> Self {
+        Self { path: usage_path(settings), settings: settings.clone() }
+    }
+
+    /// Refuse a batch expected to take `estimated_tokens` that would pass today's token limit,
+    /// before any of it is sent
+    pub fn check_batch(&self, estimated_tokens: u64) -> Result<()> {
+        let Some(limit) = self.settings.max_daily_tokens.filter(|_| self.settings.enabled) else { return Ok(()) };
+        let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let used = UsageLedger::load(&self.path)?.tokens_on(Local::now().date_naive());
+        if used + estimated_tokens > limit {
+            return Err(UsageLimitExceeded::DailyTokens { limit, used, needed: estimated_tokens }.into());
+        }
+        Ok(())
+    }
+
+    /// Check the limits for a request of about `input_tokens` starting `now`, and count it
+    /// towards the per-minute limit
+    fn begin(&self, input_tokens: u64, now: DateTime<Local>) -> Result<()> {
+        if !self.settings.enabled {
+            return Ok(());
+        }
+        let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut ledger = UsageLedger::load(&self.path)?;
+        if let Some(limit) = self.settings.max_daily_tokens {
+            let used = ledger.tokens_on(now.date_naive());
+            if used + input_tokens > limit {
+                return Err(UsageLimitExceeded::DailyTokens { limit, used, needed: input_tokens }.into());
+            }
+        }
+        let now = now.with_timezone(&Utc);
+        ledger.forget_before(now - Duration::minutes(1));
+        if let Some(limit) = self.settings.max_requests_per_minute {
+            if ledger.recent_requests.len() >= limit as usize {
+                let oldest = ledger.recent_requests.iter().min().copied().unwrap_or(now);
+                let retry_in_secs = (oldest + Duration::minutes(1) - now).num_seconds().max(1);
+                return Err(UsageLimitExceeded::RequestsPerMinute { limit, retry_in_secs }.into());
+            }
+        }
+        ledger.recent_requests.push(now);
+        ledger.save(&self.path)
+    }
+
+    /// Add a finished request to today's totals. A ledger that can't be written doesn't fail
+    /// the request it counts.
+    fn finish(&self, provider: &str, purpose: UsagePurpose, input_tokens: u64, output_tokens: u64, now: DateTime<Local>) {
+        if !self.settings.enabled {
+            return;
+        }
+        let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let totals = UsageTotals { requests: 1, input_tokens, output_tokens };
+        let result = UsageLedger::load(&self.path).and_then(|mut ledger| {
+            ledger.add(now.date_naive(), provider, purpose, totals);
+            ledger.save(&self.path)
+        });
+        if let Err(e) = result {
+            log::warn!("Could not record LLM usage: {:#}", e);
+        }
+    }
+}
+
+fn message_tokens(counter: &dyn TokenCounter, messages: &[Message]) -> u64 {
+    messages.iter().map(|m| counter.count_message(&m.content) as u64).sum()
+}
+
+fn reply_tokens(counter: &dyn TokenCounter, reply: &Message) -> u64 {
+    let call = reply.function_call.as_ref().map_or(0, |call| counter.count(&serde_json::to_string(&call.arguments).unwrap_or_default()));
+    (counter.count(&reply.content) + call) as u64
+}
+
+/// An `LLMClient` counting each request of one provider in the usage ledger, refusing
+/// requests over the `[usage]` limits
+pub struct MeteredLLMClient {
+    inner: Box<dyn LLMClient>,
+    provider: String,
+    purpose: UsagePurpose,
+    tracker: UsageTracker,
+    counter: Arc<dyn TokenCounter>,
+}
+
+impl MeteredLLMClient {
+    pub fn new(inner: Box<dyn LLMClient>, provider: &str, purpose: UsagePurpose, tracker: UsageTracker) -> Self {
+        let counter = token_counter::for_model(&inner.get_model_name());
+        Self { inner, provider: provider.to_string(), purpose, tracker, counter }
+    }
+}
+
+#[async_trait]
+impl LLMClient for MeteredLLMClient {
+    async fn send_message(&self, messages: Vec<Message>) -> Result<Message> {
+        let input = message_tokens(self.counter.as_ref(), &messages);
+        self.tracker.begin(input, Local::now())?;
+        let reply = self.inner.send_message(messages).await?;
+        self.tracker.finish(&self.provider, self.purpose, input, reply_tokens(self.counter.as_ref(), &reply), Local::now());
+        Ok(reply)
+    }
+
+    /// The stream is counted once it ends
+    async fn stream_response(&self, messages: Vec<Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+        let input = message_tokens(self.counter.as_ref(), &messages);
+        self.tracker.begin(input, Local::now())?;
+        let mut inner = self.inner.stream_response(messages).await?;
+        let (tx, rx) = tokio::sync::mpsc::channel(100);
+        let (tracker, provider, purpose, counter) = (self.tracker.clone(), self.provider.clone(), self.purpose, self.counter.clone());
+        tokio::spawn(async move {
+            let mut output = 0;
+            while let Some(part) = inner.recv().await {
+                output += counter.count(&part) as u64;
+                if tx.send(part).await.is_err() {
+                    break;
+                }
+            }
+            tracker.finish(&provider, purpose, input, output, Local::now());
+        });
+        Ok(rx)
+    }
+
+    async fn function_calling(&self, messages: Vec<Message>, functions: Vec<FunctionSchema>) -> Result<Message> {
+        let input = message_tokens(self.counter.as_ref(), &messages) + self.counter.count(&serde_json::to_string(&functions)?) as u64;
+        self.tracker.begin(input, Local::now())?;
+        let reply = self.inner.function_calling(messages, functions).await?;
+        self.tracker.finish(&self.provider, self.purpose, input, reply_tokens(self.counter.as_ref(), &reply), Local::now());
+        Ok(reply)
+    }
+
+    fn get_model_name(&self) -> String {
+        self.inner.get_model_name()
+    }
+}
+
+/// An `EmbeddingProvider` counting each embedded text in the usage ledger
+pub struct MeteredEmbeddings {
+    inner: Box<dyn EmbeddingProvider>,
+    provider: String,
+    tracker: UsageTracker,
+    counter: Arc<dyn TokenCounter>,
+}
+
+impl MeteredEmbeddings {
+    pub fn new(inner: Box<dyn EmbeddingProvider>, provider: &str, tracker: UsageTracker) -> Self {
+        let counter = token_counter::for_model(&inner.model_id());
+        Self { inner, provider: provider.to_string(), tracker, counter }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for MeteredEmbeddings {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let input = self.counter.count(text) as u64;
+        self.tracker.begin(input, Local::now())?;
+        let vector = self.inner.embed(text).await?;
+        self.tracker.finish(&self.provider, UsagePurpose::Embeddings, input, 0, Local::now());
+        Ok(vector)
+    }
+
+    fn model_id(&self) -> String {
+        self.inner.model_id()
+    }
+
+    fn dimension(&self) -> Option<usize> {
+        self.inner.dimension()
+    }
+}
+
+fn breakdown_text(title: &str, breakdown: &UsageBreakdown, by_provider: bool) -> String {
+    let line = |totals: &UsageTotals| format!(
+        "{} request(s), {} token(s) ({} in, {} out)",
+        totals.requests, totals.tokens(), totals.input_tokens, totals.output_tokens,
+    );
+    let mut out = format!("{}: {}\n", title, line(&breakdown.total));
+    for (purpose, totals) in &breakdown.by_purpose {
+        out.push_str(&format!("  {:<12} {}\n", purpose.to_string(), line(totals)));
+    }
+    if by_provider && breakdown.by_provider.len() > 1 {
+        for (provider, totals) in &breakdown.by_provider {
+            out.push_str(&format!("  {:<12} {}\n", format!("[{}]", provider), line(totals)));
+        }
+    }
+    out
+}
+
+/// Today's and this month's usage, by feature, and the limits in force
+pub fn usage_report(ledger: &UsageLedger, settings: &UsageSettings, today: NaiveDate) -> Report {
+    let month_start = dates::first_of_month(today);
+    let day = ledger.breakdown(today, today);
+    let month = ledger.breakdown(month_start, today);
+
+    let mut limits = Vec::new();
+    if let Some(limit) = settings.max_requests_per_minute {
+        limits.push(format!("{} request(s) per minute", limit));
+    }
+    if let Some(limit) = settings.max_daily_tokens {
+        limits.push(format!("{} token(s) per day, {} left today", limit, limit.saturating_sub(day.total.tokens())));
+    }
+    let mut footer = format!("Limits: {}\n", if limits.is_empty() { "none ([usage] max_requests_per_minute, max_daily_tokens)".to_string() } else { limits.join("; ") });
+    if !settings.enabled {
+        footer.push_str("Usage tracking is off ([usage] enabled = false)\n");
+    }
+    footer.push_str("Token counts are estimates; the provider's bill may differ.\n");
+
+    Report::new()
+        .text_only(DetailLevel::Summary, format!(
+            "Today: {} request(s), {} token(s); this month: {} request(s), {} token(s)\n",
+            day.total.requests, day.total.tokens(), month.total.requests, month.total.tokens(),
+        ))
+        .text(DetailLevel::Normal, breakdown_text(&format!("Today ({})", today), &day, true))
+        .text(DetailLevel::Normal, format!("\n{}", breakdown_text(&format!("This month ({})", today.format("%B %Y")), &month, true)))
+        .text(DetailLevel::Normal, format!("\n{}", footer))
+        .field(DetailLevel::Summary, "date", today)
+        .field(DetailLevel::Summary, "today", day)
+        .field(DetailLevel::Summary, "month", month)
+        .field(DetailLevel::Normal, "max_requests_per_minute", settings.max_requests_per_minute)
+        .field(DetailLevel::Normal, "max_daily_tokens", settings.max_daily_tokens)
+}
+
+pub async fn handle_usage_command(view: ReportView) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let ledger = UsageLedger::load(&usage_path(&config.usage))?;
+    let report = usage_report(&ledger, &config.usage, Local::now().date_naive());
+    crate::script::publish_output(report.to_json(view.level));
+    report.print(view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_conversation::MessageRole;
+    use chrono::TimeZone;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use uuid::Uuid;
+
+    fn temp_ledger() -> PathBuf {
+        std::env::temp_dir().join(format!("arrowhead-usage-{}.json", Uuid::new_v4()))
+    }
+
+    fn tracker(path: &Path, max_requests_per_minute: Option<u32>, max_daily_tokens: Option<u64>) -> UsageTracker {
+        UsageTracker::from_settings(&UsageSettings {
+            enabled: true,
+            max_requests_per_minute,
+            max_daily_tokens,
+            path: Some(path.to_string_lossy().to_string()),
+        })
+    }
+
+    fn at(day: u32, hour: u32, minute: u32, second: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, day, hour, minute, second).unwrap()
+    }
+
+    /// Replies with 40 characters and counts its calls
+    struct FixedClient(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl LLMClient for FixedClient {
+        async fn send_message(&self, _messages: Vec<Message>) -> Result<Message> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(Message { id: String::new(), role: MessageRole::Assistant, content: "x".repeat(40), timestamp: Utc::now(), function_call: None })
+        }
+
+        async fn stream_response(&self, _messages: Vec<Message>) -> Result<tokio::sync::mpsc::Receiver<String>> {
+            unimplemented!()
+        }
+
+        async fn function_calling(&self, messages: Vec<Message>, _functions: Vec<FunctionSchema>) -> Result<Message> {
+            self.send_message(messages).await
+        }
+
+        fn get_model_name(&self) -> String {
+            "fixed".to_string()
+        }
+    }
+
+    #[test]
+    fn test_ledger_breaks_usage_down_by_day_purpose_and_provider() {
+        let path = temp_ledger();
+        let tracker = tracker(&path, None, None);
+        tracker.finish("gemini", UsagePurpose::Chat, 100, 20, at(1, 9, 0, 0));
+        tracker.finish("gemini", UsagePurpose::Chat, 50, 10, at(15, 9, 0, 0));
+        tracker.finish("openai", UsagePurpose::Embeddings, 300, 0, at(15, 10, 0, 0));
+        tracker.finish("gemini", UsagePurpose::Analysis, 5, 5, at(15, 11, 0, 0));
+
+        let ledger = UsageLedger::load(&tracker.path).unwrap();
+        assert_eq!(ledger.entries.len(), 4);
+        let today = at(15, 12, 0, 0).date_naive();
+        let day = ledger.breakdown(today, today);
+        assert_eq!(day.total, UsageTotals { requests: 3, input_tokens: 355, output_tokens: 15 });
+        assert_eq!(day.by_purpose[&UsagePurpose::Embeddings].tokens(), 300);
+        assert_eq!(day.by_provider["gemini"].requests, 2);
+
+        let report = usage_report(&ledger, &UsageSettings { max_daily_tokens: Some(1000), ..Default::default() }, today);
+        let json = report.to_json(DetailLevel::Normal);
+        assert_eq!(json["month"]["total"]["requests"], 4);
+        assert_eq!(json["today"]["by_purpose"]["chat"]["input_tokens"], 50);
+        let text = report.render(DetailLevel::Normal);
+        assert!(text.contains("This month (October 2026): 4 request(s), 490 token(s)"));
+        assert!(text.contains("1000 token(s) per day, 630 left today"));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_limits_refuse_requests_with_a_clear_error() {
+        let path = temp_ledger();
+        let tracker = tracker(&path, Some(2), Some(500));
+        tracker.begin(10, at(15, 9, 0, 0)).unwrap();
+        tracker.begin(10, at(15, 9, 0, 20)).unwrap();
+        let error = tracker.begin(10, at(15, 9, 0, 30)).unwrap_err();
+        assert_eq!(error.downcast_ref::<UsageLimitExceeded>(), Some(&UsageLimitExceeded::RequestsPerMinute { limit: 2, retry_in_secs: 30 }));
+        // The first request has left the window
+        tracker.begin(10, at(15, 9, 1, 1)).unwrap();
+
+        tracker.finish("openai", UsagePurpose::Analysis, 400, 50, at(15, 9, 2, 0));
+        let error = tracker.begin(100, at(15, 9, 5, 0)).unwrap_err();
+        assert!(error.to_string().contains("450 of 500 token(s) used today"));
+        // Tomorrow starts over
+        tracker.begin(100, at(16, 9, 5, 0)).unwrap();
+        let _ = fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_metered_client_counts_requests_and_stops_at_the_limit() {
+        let path = temp_ledger();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let client = MeteredLLMClient::new(Box::new(FixedClient(calls.clone())), "gemini", UsagePurpose::Suggestions, tracker(&path, Some(1), None));
+        let message = Message { id: String::new(), role: MessageRole::User, content: "y".repeat(80), timestamp: Utc::now(), function_call: None };
+
+        client.send_message(vec![message.clone()]).await.unwrap();
+        let ledger = UsageLedger::load(&path).unwrap();
+        assert_eq!(ledger.entries[0].purpose, UsagePurpose::Suggestions);
+        assert_eq!(ledger.entries[0].totals, UsageTotals { requests: 1, input_tokens: 20, output_tokens: 10 });
+
+        let error = client.send_message(vec![message]).await.unwrap_err();
+        assert!(error.downcast_ref::<UsageLimitExceeded>().is_some());
+        // Refused before reaching the provider
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let _ = fs::remove_file(path);
+    }
+}
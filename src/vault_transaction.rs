@@ -0,0 +1,737 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::note_skeleton::stable_hash;
+use crate::obsidian_adapter::ObsidianAdapter;
+
+/// Size past which the transaction log is moved to `<log>.1`, replacing the previous one
+pub const MAX_LOG_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Parallel reads used by commands that load many notes at once
+pub const DEFAULT_READ_CONCURRENCY: usize = 8;
+
+/// Size and modification time of a file, as reported by a folder listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMeta {
+    pub size: u64,
+    /// Milliseconds since the Unix epoch
+    pub modified: i64,
+}
+
+/// One entry of a folder listing; subfolders end in `/`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListedFile {
+    pub name: String,
+    /// `None` when the backend lists names only
+    pub meta: Option<FileMeta>,
+}
+
+/// Minimal file operations a transaction needs from a vault backend
+#[async_trait]
+pub trait VaultStore: Send + Sync {
+    /// Read a file, returning `None` if it does not exist
+    async fn read_file(&self, path: &str) -> Result<Option<String>>;
+    /// Create or overwrite a file
+    async fn write_file(&self, path: &str, content: &str) -> Result<()>;
+    /// Delete a file
+    async fn delete_file(&self, path: &str) -> Result<()>;
+    /// List the entries of a folder, with their metadata when the backend provides it
+    async fn list_folder(&self, folder: &str) -> Result<Vec<ListedFile>>;
+
+    /// Read many files with at most `concurrency` requests in flight. Results come back
+    /// in input order; a missing or unreadable file only fails its own entry.
+    async fn fetch_many(&self, paths: &[String], concurrency: usize) -> Vec<(String, Result<String>)> {
+        let reads: Vec<_> = paths.iter().map(|path| read_existing(self, path)).collect();
+        futures::stream::iter(reads)
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+}
+
+async fn read_existing<S: VaultStore + ?Sized>(store: &S, path: &str) -> (String, Result<String>) {
+    let result = match store.read_file(path).await {
+        Ok(Some(content)) => Ok(content),
+        Ok(None) => Err(anyhow::anyhow!("File not found: {}", path)),
+        Err(e) => Err(e),
+    };
+    (path.to_string(), result)
+}
+
+#[async_trait]
+impl VaultStore for ObsidianAdapter {
+    async fn read_file(&self, path: &str) -> Result<Option<String>> {
+        self.get_file_if_exists(path).await
+    }
+
+    async fn write_file(&self, path: &str, content: &str) -> Result<()> {
+        self.update_file(path, content).await
+    }
+
+    async fn delete_file(&self, path: &str) -> Result<()> {
+        ObsidianAdapter::delete_file(self, path).await
+    }
+
+    async fn list_folder(&self, folder: &str) -> Result<Vec<ListedFile>> {
+        self.list_folder_entries(folder).await
+    }
+}
+
+/// A single planned change to the vault
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum PlannedWrite {
+    Write {
+        path: String,
+        content: String,
+        /// Hash of the content the write was computed from; the transaction fails with a
+        /// [`WriteConflict`] if the file no longer matches it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected_hash: Option<u64>,
+    },
+    Delete { path: String },
+}
+
+impl PlannedWrite {
+    pub fn path(&self) -> &str {
+        match self {
+            PlannedWrite::Write { path, .. } | PlannedWrite::Delete { path } => path,
+        }
+    }
+}
+
+/// A write planned with [`VaultTransaction::write_if_unchanged`] found the file changed
+/// since it was read. Nothing was written.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{path} changed since it was read; nothing was written")]
+pub struct WriteConflict {
+    pub path: String,
+}
+
+/// Content of a file before the transaction touched it (`None` if it did not exist)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSnapshot {
+    pub path: String,
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum TransactionOutcome {
+    /// Every write succeeded
+    Committed,
+    /// A write failed and all applied writes were restored from snapshots
+    RolledBack,
+    /// A write failed and some applied writes could not be restored
+    RollbackIncomplete,
+    /// `continue_on_error` was set and at least one write failed
+    PartiallyApplied,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Result of executing a transaction, also the record appended to the transaction log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionReport {
+    pub id: String,
+    pub description: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub outcome: TransactionOutcome,
+    /// Paths written successfully, in order
+    pub applied: Vec<String>,
+    /// Writes that failed
+    pub failed: Vec<FileFailure>,
+    /// Paths restored from snapshots during rollback
+    pub restored: Vec<String>,
+    /// Paths that could not be restored during rollback
+    pub rollback_failures: Vec<FileFailure>,
+    /// Pre-transaction content of every affected file
+    pub snapshots: Vec<FileSnapshot>,
+    /// Why the change was made (e.g. the filing decision trace), recorded in the log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<serde_json::Value>,
+}
+
+impl TransactionReport {
+    pub fn is_committed(&self) -> bool {
+        self.outcome == TransactionOutcome::Committed
+    }
+
+    /// Human-readable summary of what happened to each file
+    pub fn summary(&self) -> String {
+        let mut lines = Vec::new();
+        match self.outcome {
+            TransactionOutcome::Committed => {
+                lines.push(format!("✅ {}: {} file(s) updated", self.description, self.applied.len()));
+            }
+            TransactionOutcome::RolledBack => {
+                lines.push(format!("↩️  {}: failed, vault restored to its previous state", self.description));
+            }
+            TransactionOutcome::RollbackIncomplete => {
+                lines.push(format!("❌ {}: failed and rollback was incomplete", self.description));
+            }
+            TransactionOutcome::PartiallyApplied => {
+                lines.push(format!("⚠️  {}: {} file(s) updated, {} failed", self.description, self.applied.len(), self.failed.len()));
+            }
+        }
+
+        for failure in &self.failed {
+            lines.push(format!("    ✗ {}: {}", failure.path, failure.error));
+        }
+        for path in &self.restored {
+            lines.push(format!("    ↩ restored {}", path));
+        }
+        for failure in &self.rollback_failures {
+            lines.push(format!("    ‼ could not restore {}: {}", failure.path, failure.error));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Append this report as one JSON line to the transaction log, rotating it first if
+    /// the line would take it past `MAX_LOG_BYTES`
+    pub fn append_to_log(&self, log_path: &Path) -> Result<()> {
+        self.append_to_capped_log(log_path, MAX_LOG_BYTES)
+    }
+
+    fn append_to_capped_log(&self, log_path: &Path, max_bytes: u64) -> Result<()> {
+        let line = serde_json::to_string(self).context("Failed to serialize transaction report")?;
+        if let Some(parent) = log_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .context(format!("Failed to create {}", parent.display()))?;
+        }
+        let size = std::fs::metadata(log_path).map(|meta| meta.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 + 1 > max_bytes {
+            std::fs::rename(log_path, rotated_log_path(log_path))
+                .context(format!("Failed to rotate transaction log {}", log_path.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)
+            .context(format!("Failed to open transaction log {}", log_path.display()))?;
+        writeln!(file, "{}", line).context("Failed to write transaction log")?;
+        Ok(())
+    }
+}
+
+/// Where the transaction log goes when it is rotated
+fn rotated_log_path(log_path: &Path) -> PathBuf {
+    let mut name = log_path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// Read all reports from a transaction log and its rotated predecessor, most recent last
+pub fn read_transaction_log(log_path: &Path) -> Result<Vec<TransactionReport>> {
+    let mut reports = Vec::new();
+    for path in [rotated_log_path(log_path), log_path.to_path_buf()] {
+        if !path.exists() {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&path)
+            .context(format!("Failed to read transaction log {}", path.display()))?;
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            reports.push(serde_json::from_str(line).context("Failed to parse transaction log entry")?);
+        }
+    }
+    Ok(reports)
+}
+
+pub fn default_transaction_log_path() -> PathBuf {
+    let mut path = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push(".config");
+    path.push("arrowhead");
+    path.push("transactions.jsonl");
+    path
+}
+
+/// A planned set of writes that is applied all-or-nothing
+#[derive(Debug, Clone)]
+pub struct VaultTransaction {
+    description: String,
+    writes: Vec<PlannedWrite>,
+    continue_on_error: bool,
+    context: Option<serde_json::Value>,
+}
+
+impl VaultTransaction {
+    pub fn new(description: &str) -> Self {
+        Self {
+            description: description.to_string(),
+            writes: Vec::new(),
+            continue_on_error: false,
+            context: None,
+        }
+    }
+
+    pub fn write(mut self, path: &str, content: &str) -> Self {
+        self.writes.push(PlannedWrite::Write {
+            path: path.to_string(),
+            content: content.to_string(),
+            expected_hash: None,
+        });
+        self
+    }
+
+    /// Write `content`, computed from `read`, only if the file still holds `read` when the
+    /// transaction runs. The REST API has no conditional writes, so the check happens
+    /// while snapshotting, just before the first write.
+    pub fn write_if_unchanged(mut self, path: &str, content: &str, read: &str) -> Self {
+        self.writes.push(PlannedWrite::Write {
+            path: path.to_string(),
+            content: content.to_string(),
+            expected_hash: Some(stable_hash(read)),
+        });
+        self
+    }
+
+    pub fn delete(mut self, path: &str) -> Self {
+        self.writes.push(PlannedWrite::Delete { path: path.to_string() });
+        self
+    }
+
+    /// Keep applying remaining writes after a failure instead of rolling back
+    pub fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Attach structured context that is kept with the report in the transaction log
+    pub fn with_context(mut self, context: serde_json::Value) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    pub fn planned_writes(&self) -> &[PlannedWrite] {
+        &self.writes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Snapshot every affected file, apply writes in order and roll back on failure.
+    ///
+    /// Returns an error only if snapshotting fails or a file changed since it was read
+    /// ([`WriteConflict`]), in which case nothing was written.
+    pub async fn execute<S: VaultStore + ?Sized>(&self, store: &S) -> Result<TransactionReport> {
+        let started_at = Utc::now();
+
+        let mut snapshots: Vec<FileSnapshot> = Vec::new();
+        let mut snapshot_index: HashMap<String, usize> = HashMap::new();
+        for write in &self.writes {
+            let path = write.path();
+            if snapshot_index.contains_key(path) {
+                continue;
+            }
+            let content = store.read_file(path).await
+                .context(format!("Failed to snapshot '{}' before {}; no files were changed", path, self.description))?;
+            snapshot_index.insert(path.to_string(), snapshots.len());
+            snapshots.push(FileSnapshot { path: path.to_string(), content });
+        }
+        for write in &self.writes {
+            if let PlannedWrite::Write { path, expected_hash: Some(expected), .. } = write {
+                let current = snapshots[snapshot_index[path.as_str()]].content.as_deref().unwrap_or_default();
+                if stable_hash(current) != *expected {
+                    return Err(WriteConflict { path: path.clone() }.into());
+                }
+            }
+        }
+
+        let mut applied: Vec<String> = Vec::new();
+        let mut failed = Vec::new();
+        for write in &self.writes {
+            let result = match write {
+                PlannedWrite::Write { path, content, .. } => store.write_file(path, content).await,
+                PlannedWrite::Delete { path } => store.delete_file(path).await,
+            };
+
+            match result {
+                Ok(()) => {
+                    if !applied.iter().any(|p| p == write.path()) {
+                        applied.push(write.path().to_string());
+                    }
+                }
+                Err(e) => {
+                    failed.push(FileFailure {
+                        path: write.path().to_string(),
+                        error: e.to_string(),
+                    });
+                    if !self.continue_on_error {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut restored = Vec::new();
+        let mut rollback_failures = Vec::new();
+        let outcome = if failed.is_empty() {
+            TransactionOutcome::Committed
+        } else if self.continue_on_error {
+            TransactionOutcome::PartiallyApplied
+        } else {
+            // The failed write may have partially landed, so restore it as well
+            let mut to_restore = applied.clone();
+            for failure in &failed {
+                if !to_restore.contains(&failure.path) {
+                    to_restore.push(failure.path.clone());
+                }
+            }
+
+            for path in to_restore.iter().rev() {
+                let snapshot = &snapshots[snapshot_index[path.as_str()]];
+                let result = match &snapshot.content {
+                    Some(content) => store.write_file(path, content).await,
+                    None => match store.read_file(path).await {
+                        Ok(None) => Ok(()),
+                        Ok(Some(_)) => store.delete_file(path).await,
+                        Err(e) => Err(e),
+                    },
+                };
+
+                match result {
+                    Ok(()) => restored.push(path.clone()),
+                    Err(e) => rollback_failures.push(FileFailure {
+                        path: path.clone(),
+                        error: e.to_string(),
+                    }),
+                }
+            }
+
+            if rollback_failures.is_empty() {
+                TransactionOutcome::RolledBack
+            } else {
+                TransactionOutcome::RollbackIncomplete
+            }
+        };
+
+        Ok(TransactionReport {
+            id: Uuid::new_v4().to_string(),
+            description: self.description.clone(),
+            started_at,
+            finished_at: Utc::now(),
+            outcome,
+            applied,
+            failed,
+            restored,
+            rollback_failures,
+            snapshots,
+            context: self.context.clone(),
+        })
+    }
+}
+
+/// Test doubles shared by modules that run against a `VaultStore`
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Modification time given to files that exist before the test starts
+    const INITIAL_MTIME: i64 = 1_700_000_000_000;
+
+    /// In-memory vault that can be programmed to fail on the Nth write
+    pub(crate) struct FakeVault {
+        files: Mutex<HashMap<String, String>>,
+        mtimes: Mutex<HashMap<String, i64>>,
+        writes: Mutex<usize>,
+        reads: Mutex<usize>,
+        fail_on_write: Option<usize>,
+        pub(crate) fail_paths_after_failure: Vec<String>,
+        read_latency: Duration,
+        lists_metadata: bool,
+    }
+
+    impl FakeVault {
+        pub(crate) fn new(files: &[(&str, &str)]) -> Self {
+            Self {
+                files: Mutex::new(files.iter().map(|(p, c)| (p.to_string(), c.to_string())).collect()),
+                mtimes: Mutex::new(files.iter().map(|(p, _)| (p.to_string(), INITIAL_MTIME)).collect()),
+                writes: Mutex::new(0),
+                reads: Mutex::new(0),
+                fail_on_write: None,
+                fail_paths_after_failure: Vec::new(),
+                read_latency: Duration::ZERO,
+                lists_metadata: false,
+            }
+        }
+
+        /// Report size and modification time in folder listings
+        pub(crate) fn with_listing_metadata(mut self) -> Self {
+            self.lists_metadata = true;
+            self
+        }
+
+        /// Number of file reads so far
+        pub(crate) fn reads(&self) -> usize {
+            *self.reads.lock().unwrap()
+        }
+
+        /// Simulate the round trip of a REST call on every read
+        pub(crate) fn with_read_latency(mut self, latency: Duration) -> Self {
+            self.read_latency = latency;
+            self
+        }
+
+        pub(crate) fn failing_on_write(mut self, n: usize) -> Self {
+            self.fail_on_write = Some(n);
+            self
+        }
+
+        pub(crate) fn get(&self, path: &str) -> Option<String> {
+            self.files.lock().unwrap().get(path).cloned()
+        }
+
+        fn next_write(&self, path: &str) -> Result<usize> {
+            let mut writes = self.writes.lock().unwrap();
+            *writes += 1;
+            let failed_already = self.fail_on_write.is_some_and(|n| *writes > n);
+            if Some(*writes) == self.fail_on_write {
+                anyhow::bail!("injected failure on write {}", writes);
+            }
+            if failed_already && self.fail_paths_after_failure.iter().any(|p| p == path) {
+                anyhow::bail!("injected rollback failure for {}", path);
+            }
+            Ok(*writes)
+        }
+    }
+
+    #[async_trait]
+    impl VaultStore for FakeVault {
+        async fn read_file(&self, path: &str) -> Result<Option<String>> {
+            if !self.read_latency.is_zero() {
+                tokio::time::sleep(self.read_latency).await;
+            }
+            *self.reads.lock().unwrap() += 1;
+            Ok(self.get(path))
+        }
+
+        async fn write_file(&self, path: &str, content: &str) -> Result<()> {
+            let write = self.next_write(path)?;
+            self.files.lock().unwrap().insert(path.to_string(), content.to_string());
+            // Every write moves the clock on by a second
+            self.mtimes.lock().unwrap().insert(path.to_string(), INITIAL_MTIME + 1000 * write as i64);
+            Ok(())
+        }
+
+        async fn delete_file(&self, path: &str) -> Result<()> {
+            self.next_write(path)?;
+            self.files.lock().unwrap().remove(path);
+            self.mtimes.lock().unwrap().remove(path);
+            Ok(())
+        }
+
+        async fn list_folder(&self, folder: &str) -> Result<Vec<ListedFile>> {
+            let folder = folder.trim_end_matches('/');
+            let prefix = if folder.is_empty() { String::new() } else { format!("{}/", folder) };
+            let files = self.files.lock().unwrap();
+            let mtimes = self.mtimes.lock().unwrap();
+            let mut entries: Vec<ListedFile> = Vec::new();
+            for (path, content) in files.iter() {
+                let Some(rest) = path.strip_prefix(&prefix) else { continue };
+                let entry = match rest.split_once('/') {
+                    Some((sub, _)) => ListedFile { name: format!("{}/", sub), meta: None },
+                    None => ListedFile {
+                        name: rest.to_string(),
+                        meta: self.lists_metadata.then(|| FileMeta { size: content.len() as u64, modified: mtimes[path] }),
+                    },
+                };
+                if !entries.iter().any(|e| e.name == entry.name) {
+                    entries.push(entry);
+                }
+            }
+            entries.sort_by(|a, b| a.name.cmp(&b.name));
+            Ok(entries)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::FakeVault;
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn rename_plan() -> VaultTransaction {
+        VaultTransaction::new("rename old to new")
+            .write("Notes/new.md", "body")
+            .write("Notes/a.md", "see [[new]]")
+            .write("Notes/b.md", "also [[new]]")
+            .delete("Notes/old.md")
+    }
+
+    fn original_vault() -> FakeVault {
+        FakeVault::new(&[
+            ("Notes/old.md", "body"),
+            ("Notes/a.md", "see [[old]]"),
+            ("Notes/b.md", "also [[old]]"),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_commit_applies_all_writes() {
+        let vault = original_vault();
+        let report = rename_plan().execute(&vault).await.unwrap();
+
+        assert_eq!(report.outcome, TransactionOutcome::Committed);
+        assert_eq!(report.applied.len(), 4);
+        assert_eq!(vault.get("Notes/new.md").as_deref(), Some("body"));
+        assert_eq!(vault.get("Notes/a.md").as_deref(), Some("see [[new]]"));
+        assert!(vault.get("Notes/old.md").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_if_unchanged_detects_concurrent_edits() {
+        let vault = original_vault();
+        let plan = VaultTransaction::new("relink a")
+            .write("Notes/b.md", "also [[new]]")
+            .write_if_unchanged("Notes/a.md", "see [[new]]", "see [[old]]");
+        vault.write_file("Notes/a.md", "see [[old]] and more").await.unwrap();
+
+        let error = plan.execute(&vault).await.unwrap_err();
+        assert_eq!(error.downcast_ref::<WriteConflict>(), Some(&WriteConflict { path: "Notes/a.md".to_string() }));
+        assert_eq!(vault.get("Notes/b.md").unwrap(), "also [[old]]");
+
+        let plan = VaultTransaction::new("relink a").write_if_unchanged("Notes/a.md", "see [[new]] and more", "see [[old]] and more");
+        assert!(plan.execute(&vault).await.unwrap().is_committed());
+        assert_eq!(vault.get("Notes/a.md").unwrap(), "see [[new]] and more");
+    }
+
+    #[tokio::test]
+    async fn test_rollback_on_every_write_position() {
+        for n in 1..=4 {
+            let vault = original_vault().failing_on_write(n);
+            let report = rename_plan().execute(&vault).await.unwrap();
+
+            assert_eq!(report.outcome, TransactionOutcome::RolledBack, "failure on write {}", n);
+            assert_eq!(report.applied.len(), n - 1);
+            assert_eq!(report.failed.len(), 1);
+            assert!(report.rollback_failures.is_empty());
+
+            // The vault is exactly as it was before the transaction
+            assert_eq!(vault.get("Notes/old.md").as_deref(), Some("body"));
+            assert_eq!(vault.get("Notes/a.md").as_deref(), Some("see [[old]]"));
+            assert_eq!(vault.get("Notes/b.md").as_deref(), Some("also [[old]]"));
+            assert!(vault.get("Notes/new.md").is_none(), "created file left behind after failure on write {}", n);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rollback_reports_unrestorable_files() {
+        let mut vault = original_vault().failing_on_write(3);
+        vault.fail_paths_after_failure = vec!["Notes/a.md".to_string()];
+
+        let report = rename_plan().execute(&vault).await.unwrap();
+
+        assert_eq!(report.outcome, TransactionOutcome::RollbackIncomplete);
+        assert_eq!(report.rollback_failures.len(), 1);
+        assert_eq!(report.rollback_failures[0].path, "Notes/a.md");
+        assert!(report.restored.contains(&"Notes/new.md".to_string()));
+        assert!(vault.get("Notes/new.md").is_none());
+        assert!(report.summary().contains("could not restore Notes/a.md"));
+    }
+
+    #[tokio::test]
+    async fn test_continue_on_error_keeps_going() {
+        let vault = original_vault().failing_on_write(2);
+        let report = rename_plan().continue_on_error(true).execute(&vault).await.unwrap();
+
+        assert_eq!(report.outcome, TransactionOutcome::PartiallyApplied);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].path, "Notes/a.md");
+        assert_eq!(report.applied.len(), 3);
+        assert!(report.restored.is_empty());
+        assert_eq!(vault.get("Notes/a.md").as_deref(), Some("see [[old]]"));
+        assert!(vault.get("Notes/old.md").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_report_log_round_trip() {
+        let vault = original_vault();
+        let report = rename_plan().execute(&vault).await.unwrap();
+
+        let log_path = std::env::temp_dir().join(format!("arrowhead-tx-{}.jsonl", Uuid::new_v4()));
+        report.append_to_log(&log_path).unwrap();
+        report.append_to_log(&log_path).unwrap();
+
+        let entries = read_transaction_log(&log_path).unwrap();
+        std::fs::remove_file(&log_path).ok();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, report.id);
+        assert_eq!(entries[0].snapshots.len(), 4);
+        assert!(entries[0].snapshots.iter().any(|s| s.path == "Notes/new.md" && s.content.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_log_rotates_past_its_cap() {
+        let vault = original_vault();
+        let report = rename_plan().execute(&vault).await.unwrap();
+        let line_bytes = serde_json::to_string(&report).unwrap().len() as u64 + 1;
+
+        let dir = std::env::temp_dir().join(format!("arrowhead-tx-{}", Uuid::new_v4()));
+        let log_path = dir.join("transactions.jsonl");
+        for _ in 0..5 {
+            report.append_to_capped_log(&log_path, line_bytes * 2).unwrap();
+        }
+
+        // Two lines fit before each rotation, and only the last rotation is kept
+        let current = std::fs::read_to_string(&log_path).unwrap();
+        let rotated = std::fs::read_to_string(rotated_log_path(&log_path)).unwrap();
+        assert_eq!((rotated.lines().count(), current.lines().count()), (2, 1));
+        assert_eq!(read_transaction_log(&log_path).unwrap().len(), 3);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_many_keeps_order_and_per_file_errors() {
+        let vault = FakeVault::new(&[("Notes/a.md", "a"), ("Notes/c.md", "c")]);
+        let paths: Vec<String> = ["Notes/a.md", "Notes/b.md", "Notes/c.md"].iter().map(|p| p.to_string()).collect();
+
+        let results = vault.fetch_many(&paths, 2).await;
+
+        let order: Vec<&str> = results.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(order, ["Notes/a.md", "Notes/b.md", "Notes/c.md"]);
+        assert_eq!(results[0].1.as_deref().unwrap(), "a");
+        assert!(results[1].1.as_ref().unwrap_err().to_string().contains("not found"));
+        assert_eq!(results[2].1.as_deref().unwrap(), "c");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_many_parallel_reads_are_faster() {
+        let files: Vec<(String, String)> = (0..200).map(|i| (format!("Notes/{}.md", i), format!("note {}", i))).collect();
+        let refs: Vec<(&str, &str)> = files.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect();
+        let vault = FakeVault::new(&refs).with_read_latency(Duration::from_millis(2));
+        let paths: Vec<String> = files.iter().map(|(p, _)| p.clone()).collect();
+
+        let start = Instant::now();
+        let serial = vault.fetch_many(&paths, 1).await;
+        let serial_time = start.elapsed();
+
+        let start = Instant::now();
+        let parallel = vault.fetch_many(&paths, DEFAULT_READ_CONCURRENCY).await;
+        let parallel_time = start.elapsed();
+
+        assert!(serial.iter().chain(&parallel).all(|(_, r)| r.is_ok()));
+        assert_eq!(parallel[199].1.as_deref().unwrap(), "note 199");
+        // Ideal is 1/8; allow generous slack for scheduler noise
+        assert!(
+            parallel_time * 4 < serial_time,
+            "parallel {:?} vs serial {:?}", parallel_time, serial_time
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use std::fmt::Write as _;
+
+use crate::cache::CachePaths;
+use crate::cli::{VaultAction, VaultArgs};
+use crate::config::Config;
+
+/// One line per configured vault, the one in use starred, with where its caches live
+pub fn render_vaults(config: &Config) -> String {
+    if config.vaults.is_empty() {
+        return format!("No [[vaults]] configured; using [obsidian] at {}\n", config.obsidian.base_url);
+    }
+    let active = config.general.vault.as_deref();
+    let mut out = String::new();
+    for vault in &config.vaults {
+        let in_use = active.is_some_and(|name| name.eq_ignore_ascii_case(&vault.name));
+        let cache = CachePaths::new(&config.cache.for_vault(vault), &vault.base_url);
+        let _ = writeln!(out, "{} {}  {}", if in_use { "★" } else { "•" }, vault.name, vault.base_url);
+        let _ = writeln!(out, "    cache: {}", cache.root().display());
+    }
+    match active {
+        None => {
+            let _ = writeln!(out, "No vault chosen; using [obsidian] at {}. Pick one with `arrowhead vault use <name>`.", config.obsidian.base_url);
+        }
+        Some(name) if config.vault(name).is_err() => {
+            let _ = writeln!(out, "⚠️  There is no vault named '{}'; using [obsidian] at {}", name, config.obsidian.base_url);
+        }
+        Some(_) => {}
+    }
+    out
+}
+
+pub async fn handle_vault_command(args: VaultArgs) -> Result<()> {
+    match args.action {
+        VaultAction::List => {
+            let config = Config::load()?;
+            let vaults: Vec<serde_json::Value> = config.vaults.iter()
+                .map(|vault| serde_json::json!({
+                    "name": vault.name,
+                    "base_url": vault.base_url,
+                    "cache_dir": CachePaths::new(&config.cache.for_vault(vault), &vault.base_url).root(),
+                    "active": config.general.vault.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(&vault.name)),
+                }))
+                .collect();
+            crate::script::publish_output(serde_json::Value::Array(vaults));
+            print!("{}", render_vaults(&config));
+        }
+        VaultAction::Use { name } => {
+            let vault = Config::set_default_vault(&name)?;
+            println!("✅ Using vault '{}' at {}", vault.name, vault.base_url);
+            crate::script::publish_output(serde_json::json!({ "name": vault.name, "base_url": vault.base_url }));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,384 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use crate::ai_conversation::create_llm_client;
+use crate::cli::WatchArgs;
+use crate::config::{Config, WatchSettings};
+use crate::obsidian_adapter::{content_cache_key, ObsidianAdapter, OrganizationAction, OrganizationConfig};
+use crate::history::ChangeJournal;
+use crate::opt_out::is_opted_out;
+use crate::progress::{self, BatchProgress, NoProgress};
+use crate::reindex::{embedding_indexer, walk};
+use crate::usage::UsagePurpose;
+use crate::vault_transaction::{FileMeta, VaultStore, DEFAULT_READ_CONCURRENCY};
+
+/// Where changes are noticed: the vault's REST API, or the vault folder on this machine
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchSource {
+    Rest { folders: Vec<String> },
+    Local { root: PathBuf, folders: Vec<String> },
+}
+
+impl WatchSource {
+    pub fn from_settings(settings: &WatchSettings) -> Self {
+        match settings.vault_path.as_deref().filter(|p| !p.is_empty()) {
+            Some(root) => Self::Local { root: PathBuf::from(root), folders: settings.folders.clone() },
+            None => Self::Rest { folders: settings.folders.clone() },
+        }
+    }
+
+    /// A fingerprint per markdown note: size and modification time, or a content hash when
+    /// the REST listing has no metadata
+    pub async fn snapshot<S: VaultStore + ?Sized>(&self, store: &S) -> HashMap<String, String> {
+        match self {
+            Self::Rest { folders } => {
+                let listed = walk(store, folders).await;
+                let mut fingerprints = HashMap::new();
+                let mut unlisted = Vec::new();
+                for (path, meta) in listed {
+                    match meta {
+                        Some(meta) => { fingerprints.insert(path, fingerprint(&meta)); }
+                        None => unlisted.push(path),
+                    }
+                }
+                for (path, content) in store.fetch_many(&unlisted, DEFAULT_READ_CONCURRENCY).await {
+                    if let Ok(content) = content {
+                        fingerprints.insert(path, content_cache_key(&content));
+                    }
+                }
+                fingerprints
+            }
+            Self::Local { root, folders } => {
+                let mut fingerprints = HashMap::new();
+                for folder in folders {
+                    let folder = folder.trim_matches('/');
+                    scan_local(root, &root.join(folder), &mut fingerprints);
+                }
+                fingerprints
+            }
+        }
+    }
+}
+
+fn fingerprint(meta: &FileMeta) -> String {
+    format!("{}:{}", meta.size, meta.modified)
+}
+
+/// Fingerprint every markdown file under `dir`, keyed by its vault path. Hidden folders
+/// such as `.obsidian` and `.trash` are skipped.
+fn scan_local(root: &Path, dir: &Path, fingerprints: &mut HashMap<String, String>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(meta) = entry.metadata() else { continue };
+        if meta.is_dir() {
+            if !name.starts_with('.') {
+                scan_local(root, &path, fingerprints);
+            }
+        } else if name.ends_with(".md") {
+            let Ok(relative) = path.strip_prefix(root) else { continue };
+            let vault_path = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+            let modified = meta.modified().ok()
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis() as i64);
+            fingerprints.insert(vault_path, fingerprint(&FileMeta { size: meta.len(), modified }));
+        }
+    }
+}
+
+/// Paths that are new in `current` or whose fingerprint changed since `previous`
+pub fn changed_paths(previous: &HashMap<String, String>, current: &HashMap<String, String>) -> Vec<String> {
+    let mut changed: Vec<String> = current.iter()
+        .filter(|(path, fp)| previous.get(*path) != Some(*fp))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// Holds changed paths until they have been quiet for a while, so a burst of saves of one
+/// note triggers a single run
+#[derive(Debug)]
+pub struct Debouncer {
+    quiet: Duration,
+    pending: HashMap<String, Instant>,
+}
+
+impl Debouncer {
+    pub fn new(quiet: Duration) -> Self {
+        Self { quiet, pending: HashMap::new() }
+    }
+
+    /// Record a change, restarting the path's quiet period
+    pub fn touch(&mut self, path: &str, now: Instant) {
+        self.pending.insert(path.to_string(), now);
+    }
+
+    /// Paths quiet for the whole period, at most `limit` of them, oldest change first
+    pub fn ready(&mut self, now: Instant, limit: usize) -> Vec<String> {
+        let mut ready: Vec<(Instant, String)> = self.pending.iter()
+            .filter(|(_, at)| now.duration_since(**at) >= self.quiet)
+            .map(|(path, at)| (*at, path.clone()))
+            .collect();
+        ready.sort();
+        ready.truncate(limit);
+        for (_, path) in &ready {
+            self.pending.remove(path);
+        }
+        ready.into_iter().map(|(_, path)| path).collect()
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Counts shown when watch stops
+#[derive(Debug, Default, Clone, Copy)]
+struct WatchTotals {
+    analyzed: usize,
+    embedded: usize,
+    organized: usize,
+    failed: usize,
+}
+
+/// Analyzers for up to `concurrency` notes at once, and one adapter holding the vector
+/// database that embeds and organizes
+struct Workers {
+    analyzers: Vec<ObsidianAdapter>,
+    indexer: ObsidianAdapter,
+    organize: bool,
+}
+
+impl Workers {
+    fn new(config: &Config) -> Result<Self> {
+        let settings = &config.watch;
+        let journal = ChangeJournal::from_settings(&config.history);
+        let mut analyzers = Vec::new();
+        for _ in 0..settings.concurrency.max(1) {
+            let mut analyzer = ObsidianAdapter::with_ai_client(
+                Some(config.obsidian.base_url.clone()),
+                config.obsidian.api_key.clone(),
+                create_llm_client(config, UsagePurpose::Analysis)?,
+                None,
+            );
+            analyzer.set_cache_settings(&config.cache);
+            analyzer.set_opt_out_policy(config.opt_outs.clone());
+            if let Some(journal) = &journal {
+                analyzer.set_change_journal(journal.clone());
+            }
+            analyzers.push(analyzer);
+        }
+        let mut indexer = embedding_indexer(config)?;
+        indexer.load_vector_database()?;
+        if let Some(journal) = journal {
+            indexer.set_change_journal(journal);
+        }
+        if settings.organize {
+            indexer.set_llm_client(create_llm_client(config, UsagePurpose::Analysis)?);
+            indexer.set_organization_config(OrganizationConfig {
+                auto_apply_tags: settings.auto_apply_tags,
+                auto_move_notes: settings.auto_move_notes,
+                auto_insert_links: settings.auto_insert_links,
+                analysis_backend: config.organize.analysis_backend,
+                ..OrganizationConfig::default()
+            });
+        }
+        Ok(Self { analyzers, indexer, organize: settings.organize })
+    }
+
+    /// Analyze `paths` concurrently, then embed and organize them one by one, logging through
+    /// `progress`. Returns the paths the notes have afterwards (a note may have been moved).
+    async fn process(&mut self, paths: &[String], totals: &mut WatchTotals, progress: &dyn BatchProgress) -> Vec<String> {
+        let analyses = futures::future::join_all(self.analyzers.iter_mut().zip(paths).map(|(analyzer, path)| async move {
+            (path, analyzer.analyze_and_update_file(path).await)
+        })).await;
+
+        let mut touched = Vec::new();
+        for (path, analysis) in analyses {
+            match analysis {
+                Ok(_) => {
+                    totals.analyzed += 1;
+                    progress.println(&format!("🔍 {}: analyzed", path));
+                }
+                Err(e) if is_opted_out(&e) => {}
+                Err(e) => {
+                    totals.failed += 1;
+                    progress.println(&format!("⚠️  {}: analysis failed: {}", path, e));
+                }
+            }
+            match self.indexer.embed_document(path).await {
+                Ok(()) => totals.embedded += 1,
+                Err(e) if is_opted_out(&e) => {}
+                Err(e) => {
+                    totals.failed += 1;
+                    progress.println(&format!("⚠️  {}: embedding failed: {}", path, e));
+                }
+            }
+            touched.push(self.organize_note(path, totals, progress).await.unwrap_or_else(|| path.clone()));
+            progress.advance(path);
+        }
+        touched
+    }
+
+    /// Apply organization recommendations to the note; returns its new path if it moved
+    async fn organize_note(&mut self, path: &str, totals: &mut WatchTotals, progress: &dyn BatchProgress) -> Option<String> {
+        if !self.organize {
+            return None;
+        }
+        let result = match self.indexer.generate_organization_recommendations(path).await {
+            Ok(recommendations) => self.indexer.apply_organization_recommendations(path, &recommendations, false).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(summary) => {
+                if !summary.applied.is_empty() {
+                    totals.organized += 1;
+                    let applied: Vec<String> = summary.applied.iter().map(OrganizationAction::to_string).collect();
+                    progress.println(&format!("🗂  {}: {}", path, applied.join(", ")));
+                }
+                (summary.path != path).then_some(summary.path)
+            }
+            Err(e) if is_opted_out(&e) => None,
+            Err(e) => {
+                totals.failed += 1;
+                progress.println(&format!("⚠️  {}: organizing failed: {}", path, e));
+                None
+            }
+        }
+    }
+}
+
+pub async fn handle_watch_command(args: WatchArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let settings = &config.watch;
+    let source = WatchSource::from_settings(settings);
+    let mut workers = if args.dry_run { None } else { Some(Workers::new(&config)?) };
+    let limit = settings.concurrency.max(1);
+
+    let mut known = source.snapshot(adapter).await;
+    match &source {
+        WatchSource::Local { root, .. } => println!("👀 Watching {} note(s) in {} (Ctrl-C to stop)", known.len(), root.display()),
+        WatchSource::Rest { folders } => println!("👀 Watching {} note(s) in {} through the vault API (Ctrl-C to stop)", known.len(), folders.join(", ")),
+    }
+    if args.dry_run {
+        println!("Dry run: changes are logged, nothing is analyzed or written");
+    }
+
+    let mut debouncer = Debouncer::new(Duration::from_millis(settings.debounce_ms));
+    let mut totals = WatchTotals::default();
+    let interval = Duration::from_millis(settings.poll_interval_ms.max(100));
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+        let current = source.snapshot(adapter).await;
+        let now = Instant::now();
+        for path in changed_paths(&known, &current) {
+            debouncer.touch(&path, now);
+        }
+        known = current;
+
+        // More settled changes than one round handles (e.g. after a sync): show a bar
+        // while catching up
+        let catch_up: Box<dyn BatchProgress> = match debouncer.pending() {
+            pending if pending > limit && workers.is_some() => {
+                let bar = progress::bar("Catching up");
+                bar.start(pending);
+                Box::new(bar)
+            }
+            _ => Box::new(NoProgress),
+        };
+        loop {
+            let ready = debouncer.ready(Instant::now(), limit);
+            if ready.is_empty() {
+                break;
+            }
+            let Some(workers) = workers.as_mut() else {
+                for path in &ready {
+                    println!("Would analyze and embed {}{}", path, if settings.organize { ", then organize it" } else { "" });
+                }
+                continue;
+            };
+            let touched = workers.process(&ready, &mut totals, catch_up.as_ref()).await;
+            // Our own writes are not changes to react to
+            let after = source.snapshot(adapter).await;
+            for path in ready.iter().chain(&touched) {
+                match after.get(path) {
+                    Some(fp) => { known.insert(path.clone(), fp.clone()); }
+                    None => { known.remove(path); }
+                }
+            }
+        }
+        catch_up.finish();
+    }
+
+    println!("\nStopping…");
+    if let Some(workers) = &workers {
+        workers.indexer.save_vector_database().context("Failed to flush the vector database")?;
+    }
+    if debouncer.pending() > 0 {
+        println!("{} change(s) were still settling and were not processed", debouncer.pending());
+    }
+    println!(
+        "Analyzed {}, embedded {}, organized {} note(s){}",
+        totals.analyzed,
+        totals.embedded,
+        totals.organized,
+        if totals.failed > 0 { format!(", {} failure(s)", totals.failed) } else { String::new() },
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debouncer_waits_for_quiet_and_caps_batches() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(Duration::from_secs(2));
+        debouncer.touch("Notes/a.md", start);
+        debouncer.touch("Notes/b.md", start + Duration::from_millis(500));
+        // A second save of a.md restarts its quiet period
+        debouncer.touch("Notes/a.md", start + Duration::from_secs(1));
+        assert!(debouncer.ready(start + Duration::from_millis(2_400), 4).is_empty());
+        assert_eq!(debouncer.ready(start + Duration::from_millis(2_600), 4), vec!["Notes/b.md"]);
+
+        debouncer.touch("Notes/c.md", start + Duration::from_secs(1));
+        assert_eq!(debouncer.ready(start + Duration::from_secs(5), 1), vec!["Notes/a.md"]);
+        assert_eq!(debouncer.ready(start + Duration::from_secs(5), 1), vec!["Notes/c.md"]);
+        assert_eq!(debouncer.pending(), 0);
+    }
+
+    #[test]
+    fn test_local_scan_sees_edits_and_skips_hidden_folders() {
+        let root = std::env::temp_dir().join(format!("arrowhead-watch-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("Notes/deep")).unwrap();
+        fs::create_dir_all(root.join("Notes/.trash")).unwrap();
+        fs::write(root.join("Notes/a.md"), "a").unwrap();
+        fs::write(root.join("Notes/deep/b.md"), "b").unwrap();
+        fs::write(root.join("Notes/.trash/old.md"), "old").unwrap();
+        fs::write(root.join("Notes/image.png"), "png").unwrap();
+
+        let scan = || {
+            let mut fingerprints = HashMap::new();
+            scan_local(&root, &root.join("Notes"), &mut fingerprints);
+            fingerprints
+        };
+        let before = scan();
+        let mut paths: Vec<&String> = before.keys().collect();
+        paths.sort();
+        assert_eq!(paths, vec!["Notes/a.md", "Notes/deep/b.md"]);
+
+        fs::write(root.join("Notes/deep/b.md"), "b, edited").unwrap();
+        fs::write(root.join("Notes/c.md"), "c").unwrap();
+        assert_eq!(changed_paths(&before, &scan()), vec!["Notes/c.md", "Notes/deep/b.md"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
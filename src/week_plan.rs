@@ -0,0 +1,532 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fmt::Write as _;
+
+use crate::calendar::{available_working_hours, create_calendar_adapter, scheduling_constraints};
+use crate::calendar_adapter::{CalendarEvent, Deadline, DeadlinePriority, SchedulingConstraints};
+use crate::cli::{PlanAction, PlanArgs};
+use crate::config::{Config, PlannerSettings};
+use crate::dates;
+use crate::obsidian_adapter::ObsidianAdapter;
+use crate::risks::is_active;
+use crate::timezone;
+use crate::todo_bulk::format_minutes;
+use crate::todo_model::{TodoItem, TodoStore};
+
+/// A todo as placed by the planner
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedTask {
+    pub path: String,
+    pub description: String,
+    pub minutes: u32,
+    /// False when the configured default estimate was used
+    pub estimated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockKind {
+    Deadline { id: String, title: String },
+    Task { task: PlannedTask },
+    /// Several small todos done back to back
+    Admin { tasks: Vec<PlannedTask> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedBlock {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: BlockKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeekPlan {
+    pub week_start: NaiveDate,
+    pub blocks: Vec<PlannedBlock>,
+    /// Todos that fit nowhere this week
+    pub unscheduled: Vec<PlannedTask>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlanOptions {
+    /// Pack estimated todos into the time left after deadlines
+    pub tasks: bool,
+    /// Plan todos only, ignoring deadlines
+    pub only_tasks: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+/// Working time between `from` and `to` that no (timed) calendar event overlaps
+fn free_slots(constraints: &SchedulingConstraints, busy: &[CalendarEvent], from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<Slot>> {
+    let mut slots = Vec::new();
+    let zone = timezone::resolve(&constraints.time_zone)?;
+    let last = zone.to_local(to);
+    let mut day = zone.to_local(from).date();
+    while day < last.date() || (day == last.date() && last.time() > chrono::NaiveTime::MIN) {
+        for window in constraints.working_hours.iter().filter(|w| w.day_of_week == day.weekday().num_days_from_sunday()) {
+            let start = zone.at(day, window.start_hour, window.start_minute);
+            let end = zone.at(day, window.end_hour, window.end_minute);
+            let (Some(start), Some(end)) = (start, end) else { continue };
+            let start = start.max(from);
+            let end = end.min(to);
+            if end > start {
+                slots.push(Slot { start, end });
+            }
+        }
+        day = match day.succ_opt() {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    let mut events: Vec<&CalendarEvent> = busy.iter().filter(|e| !e.all_day).collect();
+    events.sort_by_key(|e| e.start_time);
+    for event in events {
+        slots = slots.into_iter().flat_map(|slot| {
+            if event.end_time <= slot.start || event.start_time >= slot.end {
+                return vec![slot];
+            }
+            let mut parts = Vec::new();
+            if event.start_time > slot.start {
+                parts.push(Slot { start: slot.start, end: event.start_time });
+            }
+            if event.end_time < slot.end {
+                parts.push(Slot { start: event.end_time, end: slot.end });
+            }
+            parts
+        }).collect();
+    }
+    Ok(slots)
+}
+
+/// Take the earliest free stretch of at least `min` and at most `max` minutes that starts
+/// no earlier than `not_before` and ends by `not_after`
+fn take(
+    free: &mut Vec<Slot>,
+    min: u32,
+    max: u32,
+    not_before: Option<DateTime<Utc>>,
+    not_after: Option<DateTime<Utc>>,
+) -> Option<Slot> {
+    for i in 0..free.len() {
+        let slot = free[i];
+        let start = not_before.map_or(slot.start, |nb| slot.start.max(nb));
+        let end = not_after.map_or(slot.end, |na| slot.end.min(na));
+        if end <= start {
+            continue;
+        }
+        let available = (end - start).num_minutes() as u32;
+        if available < min {
+            continue;
+        }
+        let taken = Slot { start, end: start + Duration::minutes(available.min(max) as i64) };
+        free.remove(i);
+        if taken.end < slot.end {
+            free.insert(i, Slot { start: taken.end, end: slot.end });
+        }
+        if slot.start < taken.start {
+            free.insert(i, Slot { start: slot.start, end: taken.start });
+        }
+        return Some(taken);
+    }
+    None
+}
+
+fn deadline_rank(priority: &DeadlinePriority) -> u8 {
+    match priority {
+        DeadlinePriority::Critical => 3,
+        DeadlinePriority::High => 2,
+        DeadlinePriority::Medium => 1,
+        DeadlinePriority::Low => 0,
+    }
+}
+
+fn end_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(23, 59, 59).expect("valid time").and_utc()
+}
+
+fn allocate_deadlines(
+    plan: &mut WeekPlan,
+    free: &mut Vec<Slot>,
+    deadlines: &[Deadline],
+    constraints: &SchedulingConstraints,
+    from: DateTime<Utc>,
+    week_end: DateTime<Utc>,
+    settings: &PlannerSettings,
+) -> Result<()> {
+    let mut active: Vec<&Deadline> = deadlines.iter().filter(|d| is_active(d)).collect();
+    active.sort_by(|a, b| {
+        (a.due_date, Reverse(deadline_rank(&a.priority)), &a.id).cmp(&(b.due_date, Reverse(deadline_rank(&b.priority)), &b.id))
+    });
+
+    for deadline in active {
+        let remaining = ((deadline.estimated_hours - deadline.completed_hours) * 60.0).round() as u32;
+        // Deadlines past this week get this week's share of the work left
+        let needed = if deadline.due_date <= week_end {
+            remaining
+        } else {
+            let days = (deadline.due_date - from).num_days().max(1);
+            let total = available_working_hours(constraints, from, days)?;
+            let this_week = available_working_hours(constraints, from, (week_end - from).num_days())?;
+            if total <= 0.0 { remaining } else { (remaining as f32 * (this_week / total).min(1.0)).round() as u32 }
+        };
+
+        // One block per day at most, so a deadline doesn't swallow a whole day
+        let mut left = needed;
+        let mut not_before = None;
+        while left > 0 {
+            let min = left.min(settings.min_deadline_block_minutes);
+            let Some(slot) = take(free, min, left.min(settings.max_deadline_block_minutes), not_before, Some(deadline.due_date)) else {
+                break;
+            };
+            left -= (slot.end - slot.start).num_minutes() as u32;
+            not_before = slot.start.date_naive().succ_opt().and_then(|d| d.and_hms_opt(0, 0, 0)).map(|d| d.and_utc());
+            plan.blocks.push(PlannedBlock {
+                start: slot.start,
+                end: slot.end,
+                kind: BlockKind::Deadline { id: deadline.id.clone(), title: deadline.title.clone() },
+            });
+        }
+        if left > 0 {
+            plan.warnings.push(format!("\"{}\" is short {} of the {} it needs this week", deadline.title, format_minutes(left), format_minutes(needed)));
+        }
+    }
+    Ok(())
+}
+
+/// Place a block before `due` if possible, otherwise anywhere with a warning
+fn place(plan: &mut WeekPlan, free: &mut Vec<Slot>, minutes: u32, due: Option<DateTime<Utc>>, label: &str) -> Option<Slot> {
+    if let Some(slot) = take(free, minutes, minutes, None, due) {
+        return Some(slot);
+    }
+    let slot = take(free, minutes, minutes, None, None)?;
+    plan.warnings.push(format!("{} is scheduled after its due date", label));
+    Some(slot)
+}
+
+fn pack_tasks(plan: &mut WeekPlan, free: &mut Vec<Slot>, todos: &[TodoItem], settings: &PlannerSettings) {
+    let mut open: Vec<&TodoItem> = todos.iter().filter(|t| t.status.is_open()).collect();
+    open.sort_by(|a, b| {
+        let key = |t: &TodoItem| (t.due.is_none(), t.due, t.priority_rank(), t.path.clone());
+        key(a).cmp(&key(b))
+    });
+
+    let mut admin: Vec<(PlannedTask, Option<DateTime<Utc>>)> = Vec::new();
+    let flush = |plan: &mut WeekPlan, free: &mut Vec<Slot>, admin: &mut Vec<(PlannedTask, Option<DateTime<Utc>>)>| {
+        if admin.is_empty() {
+            return;
+        }
+        let minutes = admin.iter().map(|(t, _)| t.minutes).sum();
+        let due = admin.iter().filter_map(|(_, due)| *due).min();
+        let tasks: Vec<PlannedTask> = admin.drain(..).map(|(t, _)| t).collect();
+        match place(plan, free, minutes, due, "An admin block") {
+            Some(slot) => {
+                let kind = match tasks.len() {
+                    1 => BlockKind::Task { task: tasks.into_iter().next().expect("one task") },
+                    _ => BlockKind::Admin { tasks },
+                };
+                plan.blocks.push(PlannedBlock { start: slot.start, end: slot.end, kind });
+            }
+            None => plan.unscheduled.extend(tasks),
+        }
+    };
+
+    for todo in open {
+        let task = PlannedTask {
+            path: todo.path.clone(),
+            description: todo.title.clone(),
+            minutes: todo.estimate_minutes.unwrap_or(settings.default_estimate_minutes),
+            estimated: todo.estimate_minutes.is_some(),
+        };
+        let due = todo.due.map(end_of_day);
+
+        if task.minutes <= settings.admin_task_max_minutes {
+            let pending: u32 = admin.iter().map(|(t, _)| t.minutes).sum();
+            if pending + task.minutes > settings.admin_block_minutes {
+                flush(plan, free, &mut admin);
+            }
+            admin.push((task, due));
+            continue;
+        }
+        match place(plan, free, task.minutes, due, &format!("\"{}\"", task.description)) {
+            Some(slot) => plan.blocks.push(PlannedBlock { start: slot.start, end: slot.end, kind: BlockKind::Task { task } }),
+            None => plan.unscheduled.push(task),
+        }
+    }
+    flush(plan, free, &mut admin);
+}
+
+/// Plan the week from `from` to the end of its Sunday: deadline work first, then
+/// (optionally) todos in due-date and priority order. Identical inputs give identical plans.
+pub fn plan_week(
+    from: DateTime<Utc>,
+    deadlines: &[Deadline],
+    todos: &[TodoItem],
+    busy: &[CalendarEvent],
+    constraints: &SchedulingConstraints,
+    settings: &PlannerSettings,
+    options: PlanOptions,
+) -> Result<WeekPlan> {
+    let today = from.date_naive();
+    let week_start = dates::week_start(today);
+    let week_end = (week_start + Duration::days(7)).and_hms_opt(0, 0, 0).expect("midnight").and_utc();
+    let mut free = free_slots(constraints, busy, from, week_end)?;
+    let mut plan = WeekPlan { week_start, blocks: Vec::new(), unscheduled: Vec::new(), warnings: Vec::new() };
+
+    if !options.only_tasks {
+        allocate_deadlines(&mut plan, &mut free, deadlines, constraints, from, week_end, settings)?;
+    }
+    if options.tasks || options.only_tasks {
+        pack_tasks(&mut plan, &mut free, todos, settings);
+    }
+    plan.blocks.sort_by_key(|b| b.start);
+    Ok(plan)
+}
+
+impl WeekPlan {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "📅 Week of {}", self.week_start.format("%b %-d, %Y"));
+        let mut current_day = None;
+        for block in &self.blocks {
+            let day = block.start.date_naive();
+            if current_day != Some(day) {
+                let _ = writeln!(out, "\n{}", day.format("%A %b %-d"));
+                current_day = Some(day);
+            }
+            let time = format!("{}–{}", block.start.format("%H:%M"), block.end.format("%H:%M"));
+            let task_label = |t: &PlannedTask| {
+                let estimate = if t.estimated { format_minutes(t.minutes) } else { format!("~{} default", format_minutes(t.minutes)) };
+                format!("{} ({})", t.description, estimate)
+            };
+            match &block.kind {
+                BlockKind::Deadline { title, .. } => { let _ = writeln!(out, "  {}  🎯 {}", time, title); }
+                BlockKind::Task { task } => { let _ = writeln!(out, "  {}  ☑️  {}", time, task_label(task)); }
+                BlockKind::Admin { tasks } => {
+                    let _ = writeln!(out, "  {}  🗂  Admin block ({} tasks)", time, tasks.len());
+                    for task in tasks {
+                        let _ = writeln!(out, "               • {}", task_label(task));
+                    }
+                }
+            }
+        }
+        if self.blocks.is_empty() {
+            let _ = writeln!(out, "\nNothing to plan.");
+        }
+
+        let task_minutes: u32 = self.blocks.iter().map(|b| match &b.kind {
+            BlockKind::Task { task } => task.minutes,
+            BlockKind::Admin { tasks } => tasks.iter().map(|t| t.minutes).sum(),
+            BlockKind::Deadline { .. } => 0,
+        }).sum();
+        if task_minutes > 0 {
+            let _ = writeln!(out, "\nTodos planned: {}", format_minutes(task_minutes));
+        }
+        if !self.unscheduled.is_empty() {
+            let _ = writeln!(out, "\nDidn't fit this week:");
+            for task in &self.unscheduled {
+                let _ = writeln!(out, "  • {} ({})", task.description, format_minutes(task.minutes));
+            }
+        }
+        for warning in &self.warnings {
+            let _ = writeln!(out, "⚠️  {}", warning);
+        }
+        out
+    }
+}
+
+pub async fn handle_plan_command(args: PlanArgs, adapter: &ObsidianAdapter) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let PlanAction::Week { tasks, only_tasks, next, json } = args.action;
+
+    let now = Utc::now();
+    let from = if next {
+        let today = now.date_naive();
+        let monday = dates::week_start(today) + Duration::days(7);
+        monday.and_hms_opt(0, 0, 0).expect("midnight").and_utc()
+    } else {
+        now
+    };
+
+    let mut busy = Vec::new();
+    let mut deadlines = Vec::new();
+    match create_calendar_adapter(&config.calendar, &config.network) {
+        Ok(calendar) => {
+            let week_end = from + Duration::days(8);
+            match calendar.list_events(&config.calendar.calendar_id, Some(from), Some(week_end)).await {
+                Ok(events) => busy = events,
+                Err(e) => println!("⚠️  Could not read calendar events, planning as if the week were free: {}", e),
+            }
+            if !only_tasks {
+                deadlines = calendar.get_deadlines(None).await?;
+            }
+        }
+        Err(e) if only_tasks => println!("⚠️  {}; planning as if the week were free", e),
+        Err(e) => return Err(e),
+    }
+    let todos = if tasks || only_tasks { TodoStore::new(adapter).list().await? } else { Vec::new() };
+
+    let plan = plan_week(
+        from,
+        &deadlines,
+        &todos,
+        &busy,
+        &scheduling_constraints(&config.calendar),
+        &config.planner,
+        PlanOptions { tasks, only_tasks },
+    )?;
+    crate::script::publish_output(serde_json::to_value(&plan)?);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+    } else {
+        print!("{}", plan.render());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::test_constraints;
+    use crate::calendar_adapter::DeadlineStatus;
+    use crate::testing;
+    use crate::todo_model::TodoStatus;
+    use chrono::TimeZone;
+
+    fn monday() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap()
+    }
+
+    fn todo(name: &str, frontmatter: &str, line: &str) -> TodoItem {
+        TodoItem::parse(&format!("Todos/{}.md", name), &format!("---\n{}status: open\n---\n\n- [ ] {}", frontmatter, line))
+    }
+
+    fn deadline(id: &str, due_days: i64, hours: f32) -> Deadline {
+        Deadline {
+            id: id.to_string(),
+            priority: DeadlinePriority::High,
+            status: DeadlineStatus::InProgress,
+            estimated_hours: hours,
+            ..testing::deadline(&format!("Deadline {}", id), monday() + Duration::days(due_days))
+        }
+    }
+
+    fn event(day: i64, start_hour: i64, hours: i64) -> CalendarEvent {
+        let start = monday().date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc() + Duration::days(day) + Duration::hours(start_hour);
+        CalendarEvent {
+            id: format!("e{}{}", day, start_hour),
+            title: "Busy".to_string(),
+            description: None,
+            start_time: start,
+            end_time: start + Duration::hours(hours),
+            location: None,
+            attendees: vec![],
+            all_day: false,
+            recurring: false,
+            calendar_id: "default".to_string(),
+        }
+    }
+
+    fn todos() -> Vec<TodoItem> {
+        vec![
+            todo("report", "est: 2h\ndue_date: 2024-06-04\n", "write report"),
+            todo("stamps", "", "buy stamps ~10m"),
+            todo("bank", "priority: high\n", "call bank ~15m"),
+            todo("email", "", "answer email ~5m"),
+            todo("taxes", "due_date: 2024-06-05\n", "sort receipts"),
+            todo("done", "est: 4h\n", "already done"),
+        ]
+        .into_iter()
+        .map(|mut t| {
+            if t.path == "Todos/done.md" {
+                t.status = TodoStatus::Done;
+            }
+            t
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_tasks_pack_after_deadlines_and_group_small_ones() {
+        let plan = plan_week(
+            monday(),
+            &[deadline("d1", 1, 6.0)],
+            &todos(),
+            &[event(0, 9, 2)],
+            &test_constraints(),
+            &PlannerSettings::default(),
+            PlanOptions { tasks: true, only_tasks: false },
+        ).unwrap();
+
+        // The deadline gets the first free time (after the 9–11 meeting), capped at one block a day
+        let first = &plan.blocks[0];
+        assert_eq!(first.kind, BlockKind::Deadline { id: "d1".to_string(), title: "Deadline d1".to_string() });
+        assert_eq!((first.start.format("%a %H:%M").to_string(), first.end.format("%H:%M").to_string()), ("Mon 11:00".to_string(), "13:00".to_string()));
+        assert!(plan.warnings.iter().any(|w| w.contains("Deadline d1") && w.contains("short")));
+
+        let labels: Vec<String> = plan.blocks.iter().map(|b| match &b.kind {
+            BlockKind::Deadline { id, .. } => id.clone(),
+            BlockKind::Task { task } => task.path.clone(),
+            BlockKind::Admin { tasks } => tasks.iter().map(|t| t.path.as_str()).collect::<Vec<_>>().join("+"),
+        }).collect();
+        // Due-dated todos first, then the small undated ones in one admin block, bank (high priority) leading
+        assert_eq!(labels, vec!["d1", "Todos/report.md", "Todos/taxes.md", "Todos/bank.md+Todos/email.md+Todos/stamps.md"]);
+        let admin = plan.blocks.iter().find(|b| matches!(b.kind, BlockKind::Admin { .. })).unwrap();
+        assert_eq!((admin.end - admin.start).num_minutes(), 30);
+        // "sort receipts" has no estimate, so it takes the default
+        let taxes = plan.blocks.iter().find_map(|b| match &b.kind {
+            BlockKind::Task { task } if task.path == "Todos/taxes.md" => Some(task.clone()),
+            _ => None,
+        }).unwrap();
+        assert_eq!((taxes.minutes, taxes.estimated), (PlannerSettings::default().default_estimate_minutes, false));
+        assert!(plan.unscheduled.is_empty());
+    }
+
+    #[test]
+    fn test_only_tasks_is_deterministic_and_respects_busy_time() {
+        let run = || plan_week(
+            monday(),
+            &[deadline("d1", 1, 6.0)],
+            &todos(),
+            &[event(0, 9, 8)],
+            &test_constraints(),
+            &PlannerSettings::default(),
+            PlanOptions { tasks: false, only_tasks: true },
+        ).unwrap();
+        let plan = run();
+        assert_eq!(plan, run());
+        assert!(plan.blocks.iter().all(|b| !matches!(b.kind, BlockKind::Deadline { .. })));
+        // Monday is fully booked, so everything lands on Tuesday or later
+        assert!(plan.blocks.iter().all(|b| b.start.weekday() != chrono::Weekday::Mon));
+        assert_eq!(plan.blocks[0].start, Utc.with_ymd_and_hms(2024, 6, 4, 9, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_overflow_is_unscheduled_and_late_tasks_warn() {
+        let mut settings = PlannerSettings::default();
+        settings.default_estimate_minutes = 60;
+        let busy: Vec<CalendarEvent> = (0..5).map(|day| event(day, 9, 7)).collect();
+        let todos = vec![
+            todo("a", "est: 1h\ndue_date: 2024-06-03\n", "due today"),
+            todo("b", "est: 3h\n", "too long"),
+        ];
+        let plan = plan_week(monday(), &[], &todos, &busy, &test_constraints(), &settings, PlanOptions { tasks: true, only_tasks: false }).unwrap();
+
+        // Only 16:00–17:00 is free each day: "due today" fits Monday, "too long" fits nowhere
+        assert_eq!(plan.blocks.len(), 1);
+        assert_eq!(plan.unscheduled.iter().map(|t| t.path.as_str()).collect::<Vec<_>>(), vec!["Todos/b.md"]);
+
+        let todos = vec![todo("late", "est: 1h\ndue_date: 2024-06-02\n", "overdue")];
+        let plan = plan_week(monday(), &[], &todos, &busy, &test_constraints(), &settings, PlanOptions { tasks: true, only_tasks: false }).unwrap();
+        assert!(plan.warnings[0].contains("after its due date"));
+    }
+}
@@ -0,0 +1,163 @@
+//! The analyze, embed and organize flows run against the mock LLM and the mock vault
+//! server, so changes to prompt construction or response parsing show up without API keys.
+//! Run with `cargo test --features testing`.
+
+use std::collections::HashMap;
+
+use arrowhead::error::ArrowheadError;
+use arrowhead::obsidian_adapter::{ObsidianAdapter, OrganizationAction, OrganizationConfig, SemanticSearchConfig};
+use arrowhead::opt_out::OptOutPolicy;
+use arrowhead::progress::NoProgress;
+use arrowhead::reindex::{scan_changes, MetadataSnapshot};
+use arrowhead::testing::{MockEmbeddingProvider, MockLLMClient, MockVaultServer, RecordedRequest};
+
+const RUST_NOTE: &str = "---\ntitle: Ownership\n---\n\nRust ownership rules: every value has one owner, and the borrow checker enforces them.\n";
+const GARDEN_NOTE: &str = "# Tomatoes\n\nWater the tomatoes every morning and stake them before they fall over.\n";
+
+const RUST_ANALYSIS: &str = r#"Here is the analysis:
+```json
+{
+  "themes": ["rust", "memory safety"],
+  "sentiment": { "overall": "positive", "confidence": 0.8, "emotions": ["curiosity"] },
+  "entities": [{ "text": "Rust", "entity_type": "MISC", "confidence": 0.9 }],
+  "concepts": [],
+  "summary": "Rust's ownership rules.",
+  "category": "Programming"
+}
+```"#;
+
+fn analyzer(server: &MockVaultServer, llm: &MockLLMClient) -> ObsidianAdapter {
+    ObsidianAdapter::with_ai_client(Some(server.base_url()), None, Box::new(llm.clone()), None)
+}
+
+#[tokio::test]
+async fn analyze_writes_the_parsed_reply_into_frontmatter() {
+    let server = MockVaultServer::with_files(&[("Notes/rust.md", RUST_NOTE)]).await.unwrap();
+    let llm = MockLLMClient::new().respond_when("borrow checker", RUST_ANALYSIS);
+    let mut adapter = analyzer(&server, &llm);
+
+    let file = adapter.analyze_and_update_file("Notes/rust.md").await.unwrap();
+    let analysis = file.frontmatter.ai_analysis.unwrap();
+    assert_eq!(analysis.themes, ["rust", "memory safety"]);
+    assert_eq!(analysis.sentiment.overall, "positive");
+    assert_eq!(analysis.category.as_deref(), Some("Programming"));
+
+    // The prompt carries the note body, not its frontmatter, and asks for JSON
+    let prompt = &llm.prompts()[0];
+    assert!(prompt.contains("every value has one owner"), "{}", prompt);
+    assert!(!prompt.contains("title: Ownership"), "{}", prompt);
+    assert!(prompt.contains("\"themes\""), "{}", prompt);
+
+    let saved = server.file("Notes/rust.md").unwrap();
+    assert!(saved.contains("memory safety"), "{}", saved);
+    assert!(saved.contains("title: Ownership"), "{}", saved);
+    assert!(saved.contains("every value has one owner"), "{}", saved);
+    assert!(server.requests().contains(&RecordedRequest { method: "PUT".to_string(), path: "Notes/rust.md".to_string() }));
+
+    // The same body is answered from the cache
+    adapter.analyze_content(&ObsidianAdapter::parse_markdown_file(RUST_NOTE).unwrap().content).await.unwrap();
+    assert_eq!(llm.call_count(), 1);
+}
+
+#[tokio::test]
+async fn analyze_reports_replies_that_are_not_json() {
+    let server = MockVaultServer::with_files(&[("Notes/rust.md", RUST_NOTE)]).await.unwrap();
+    let llm = MockLLMClient::new().otherwise("Sorry, I can't analyze that.");
+    let mut adapter = analyzer(&server, &llm);
+
+    let error = adapter.analyze_and_update_file("Notes/rust.md").await.unwrap_err();
+    assert!(format!("{:#}", error).contains("Failed to parse AI response as JSON"), "{:#}", error);
+    assert_eq!(server.file("Notes/rust.md").unwrap(), RUST_NOTE);
+}
+
+#[tokio::test]
+async fn batch_analysis_keeps_going_past_a_failed_request() {
+    let server = MockVaultServer::start().await.unwrap();
+    let llm = MockLLMClient::new()
+        .fail_first(1, "model overloaded")
+        .respond_when("borrow checker", RUST_ANALYSIS)
+        .respond_when("tomatoes", r#"{"themes": ["gardening"]}"#)
+        .with_latency(std::time::Duration::from_millis(5));
+    let mut adapter = analyzer(&server, &llm);
+    adapter.set_batch_concurrency(1);
+
+    let fetched = vec![
+        ("Notes/flaky.md".to_string(), Ok("Anything at all".to_string())),
+        ("Notes/rust.md".to_string(), Ok(RUST_NOTE.to_string())),
+        ("Garden/tomatoes.md".to_string(), Ok(GARDEN_NOTE.to_string())),
+    ];
+    let results = adapter.batch_analyze_contents(fetched, &NoProgress).await;
+    let outcomes: Vec<String> = results.iter()
+        .map(|(path, result)| match result {
+            Ok(analysis) => format!("{}: {}", path, analysis.themes.join(",")),
+            Err(e) => format!("{}: {:#}", path, e),
+        })
+        .collect();
+    assert_eq!(outcomes, [
+        "Notes/flaky.md: Failed to get AI analysis response: model overloaded",
+        "Notes/rust.md: rust,memory safety",
+        "Garden/tomatoes.md: gardening",
+    ]);
+}
+
+#[tokio::test]
+async fn index_scan_and_embedding_make_notes_searchable() {
+    let server = MockVaultServer::with_files(&[
+        ("Notes/rust.md", RUST_NOTE),
+        ("Notes/Garden/tomatoes.md", GARDEN_NOTE),
+        ("Notes/image.png", "not a note"),
+        ("Elsewhere/skip.md", "Outside the indexed folder"),
+    ]).await.unwrap();
+    let embedder = MockEmbeddingProvider::default();
+    let mut adapter = ObsidianAdapter::new(Some(server.base_url()), None);
+    adapter.set_embedding_providers(Box::new(embedder.clone()), None);
+    adapter.set_search_config(SemanticSearchConfig { min_similarity: 0.1, ..SemanticSearchConfig::default() });
+
+    let scan = scan_changes(&adapter, &["Notes".to_string()], &MetadataSnapshot::default(), &HashMap::new(), false, &OptOutPolicy::default()).await;
+    let mut changed: Vec<&str> = scan.changed.iter().map(|(path, _)| path.as_str()).collect();
+    changed.sort();
+    assert_eq!(changed, ["Notes/Garden/tomatoes.md", "Notes/rust.md"]);
+
+    let fetched = scan.changed.into_iter().map(|(path, content)| (path, Ok(content))).collect();
+    let embedded = adapter.batch_embed_contents(fetched, &NoProgress).await;
+    assert!(embedded.iter().all(|(_, result)| matches!(result, Ok(true))), "{:?}", embedded);
+    // Frontmatter isn't embedded
+    assert!(embedder.embedded().iter().all(|text| !text.contains("title: Ownership")));
+
+    let results = adapter.semantic_search("borrow checker ownership").await.unwrap();
+    assert_eq!(results[0].path, "Notes/rust.md");
+    assert!(results.iter().all(|result| result.path != "Notes/Garden/tomatoes.md" || result.similarity < results[0].similarity));
+}
+
+#[tokio::test]
+async fn organize_adds_suggested_tags_with_a_frontmatter_patch() {
+    let server = MockVaultServer::with_files(&[("Notes/rust.md", RUST_NOTE)]).await.unwrap();
+    let llm = MockLLMClient::new().respond_when("borrow checker", RUST_ANALYSIS);
+    let mut adapter = analyzer(&server, &llm);
+    adapter.set_organization_config(OrganizationConfig { auto_apply_tags: true, auto_tag_confidence_threshold: 0.5, ..OrganizationConfig::default() });
+
+    let recommendations = adapter.generate_organization_recommendations("Notes/rust.md").await.unwrap();
+    let tags: Vec<&str> = recommendations.suggested_tags.iter().map(|t| t.tag.as_str()).collect();
+    assert!(tags.contains(&"rust") && tags.contains(&"memory_safety"), "{:?}", tags);
+    assert!(recommendations.folder_suggestions.iter().any(|f| f.folder_path == "programming"));
+
+    let summary = adapter.apply_organization_recommendations("Notes/rust.md", &recommendations, false).await.unwrap();
+    assert!(summary.applied.contains(&OrganizationAction::AddTag("rust".to_string())), "{:?}", summary.applied);
+    // Moving is off by default, so the note stays put and only its tags change
+    assert!(server.requests().iter().any(|r| r.method == "PATCH" && r.path == "Notes/rust.md"));
+    assert!(!server.requests().iter().any(|r| r.method == "PUT"));
+    let saved = server.file("Notes/rust.md").unwrap();
+    assert!(saved.contains("title: Ownership") && saved.contains("- rust"), "{}", saved);
+    assert!(saved.ends_with(RUST_NOTE.split_once("---\n\n").unwrap().1), "{}", saved);
+}
+
+#[tokio::test]
+async fn vault_requests_without_the_api_key_are_refused() {
+    let server = MockVaultServer::with_files(&[("Notes/rust.md", RUST_NOTE)]).await.unwrap();
+    server.require_api_key("secret");
+
+    let error = ObsidianAdapter::new(Some(server.base_url()), None).get_file("Notes/rust.md").await.unwrap_err();
+    assert!(matches!(error.downcast_ref::<ArrowheadError>(), Some(ArrowheadError::AuthFailed { status: 401, .. })), "{:#}", error);
+    let content = ObsidianAdapter::new(Some(server.base_url()), Some("secret".to_string())).get_file("Notes/rust.md").await.unwrap();
+    assert_eq!(content, RUST_NOTE);
+}
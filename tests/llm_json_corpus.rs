@@ -0,0 +1,112 @@
+//! Replies collected from real models that the old first-`{`-to-last-`}` slicing got wrong.
+//! Every one must parse into the shape the prompt asked for, or fail with an error that
+//! quotes the reply.
+
+use arrowhead::llm_json;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Intent {
+    intent: String,
+    #[serde(default)]
+    confidence: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+struct Analysis {
+    #[serde(deserialize_with = "llm_json::strings")]
+    themes: Vec<String>,
+    #[serde(deserialize_with = "llm_json::null_as_default")]
+    summary: String,
+}
+
+fn intent(reply: &str) -> Intent {
+    llm_json::parse(reply).unwrap_or_else(|e| panic!("{:#}\nreply: {}", e, reply))
+}
+
+fn todo_add(confidence: f32) -> Intent {
+    Intent { intent: "todo_add".to_string(), confidence: Some(confidence) }
+}
+
+#[test]
+fn prose_around_the_json() {
+    assert_eq!(intent("Sure! Here's the parsed command: {\"intent\": \"todo_add\", \"confidence\": 0.9} Hope that helps."), todo_add(0.9));
+}
+
+#[test]
+fn fenced_json_with_commentary_after() {
+    let reply = "```json\n{\"intent\": \"todo_add\", \"confidence\": 0.8}\n```\n\nNote: I assumed {due} means tomorrow.";
+    assert_eq!(intent(reply), todo_add(0.8));
+}
+
+#[test]
+fn fence_without_language_tag() {
+    assert_eq!(intent("```\n{\"intent\": \"todo_add\", \"confidence\": 0.7}\n```"), todo_add(0.7));
+}
+
+#[test]
+fn example_object_before_the_answer() {
+    let reply = "The format is {\"example\": true}. The answer:\n{\"intent\": \"todo_add\", \"confidence\": 0.6}";
+    assert_eq!(intent(reply), todo_add(0.6));
+}
+
+#[test]
+fn braces_inside_strings() {
+    let reply = "{\"intent\": \"todo_add\", \"note\": \"use {braces} and } freely\", \"confidence\": 0.5}";
+    assert_eq!(intent(reply), todo_add(0.5));
+}
+
+#[test]
+fn trailing_commas() {
+    let reply = "{\n  \"intent\": \"todo_add\",\n  \"confidence\": 0.9,\n}";
+    assert_eq!(intent(reply), todo_add(0.9));
+    let analysis: Analysis = llm_json::parse("{\"themes\": [\"a\", \"b\",], \"summary\": \"s\",}").unwrap();
+    assert_eq!(analysis.themes, vec!["a", "b"]);
+}
+
+#[test]
+fn single_quotes() {
+    assert_eq!(intent("{'intent': 'todo_add', 'confidence': 0.9}"), todo_add(0.9));
+    let analysis: Analysis = llm_json::parse("{'themes': ['it\\'s fine'], 'summary': 'say \"hi\"'}").unwrap();
+    assert_eq!(analysis.themes, vec!["it's fine"]);
+    assert_eq!(analysis.summary, "say \"hi\"");
+}
+
+#[test]
+fn top_level_array() {
+    let intents: Vec<Intent> = llm_json::parse("Two readings:\n[{\"intent\": \"todo_add\", \"confidence\": 0.5}, {\"intent\": \"note_create\"}]").unwrap();
+    assert_eq!(intents.len(), 2);
+    assert_eq!(intents[1], Intent { intent: "note_create".to_string(), confidence: None });
+}
+
+#[test]
+fn nulls_and_loose_lists() {
+    let analysis: Analysis = llm_json::parse("{\"themes\": null, \"summary\": null}").unwrap();
+    assert_eq!(analysis, Analysis::default());
+    let analysis: Analysis = llm_json::parse("{\"themes\": \"productivity\"}").unwrap();
+    assert_eq!(analysis.themes, vec!["productivity"]);
+}
+
+#[test]
+fn truncated_reply_fails_with_a_snippet() {
+    let err = llm_json::parse::<Intent>("```json\n{\"intent\": \"todo_add\", \"confid").unwrap_err();
+    let message = err.to_string();
+    assert!(message.starts_with("no JSON found in: ```json"), "{}", message);
+}
+
+#[test]
+fn missing_required_field_names_it() {
+    let err = llm_json::parse::<Intent>("Here: {\"confidence\": 0.9}").unwrap_err();
+    let error = err.downcast_ref::<llm_json::LlmJsonError>().expect("an LlmJsonError");
+    assert!(error.reason.contains("missing field `intent`"), "{}", error.reason);
+    assert_eq!(error.snippet, "{\"confidence\": 0.9}");
+}
+
+#[test]
+fn long_replies_are_cut_in_errors() {
+    let reply = format!("{{\"note\": \"{}\"}}", "x".repeat(500));
+    let message = llm_json::parse::<Intent>(&reply).unwrap_err().to_string();
+    assert!(message.len() < 300, "{}", message);
+    assert!(message.ends_with('…'));
+}